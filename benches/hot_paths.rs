@@ -0,0 +1,359 @@
+//! Benchmarks of the hot paths of the renderer: building and
+//! traversing a BVH, evaluating a Disney BSDF, generating samples, and
+//! rendering a small image from start to finish.
+//!
+//! ```shell
+//! > cargo bench
+//! > cargo bench -- bvh
+//! ```
+
+// std
+use std::collections::HashMap;
+use std::sync::Arc;
+// others
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+// pbrt
+use pbrt::accelerators::bvh::{BVHAccel, SplitMethod};
+use pbrt::core::api::{pbrt_init, pbrt_render_to_memory};
+use pbrt::core::builder::{
+    CameraDesc, FilmDesc, IntegratorDesc, LightDesc, MaterialDesc, SamplerDesc, SceneBuilder,
+    ShapeDesc,
+};
+use pbrt::core::geometry::{Bounds2i, Point2f, Point2i, Point3f, Ray, Vector3f};
+use pbrt::core::interaction::SurfaceInteraction;
+use pbrt::core::material::{Material, TransportMode};
+use pbrt::core::paramset::{ParamSet, TextureParams};
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::reflection::BxdfType;
+use pbrt::core::rng::Rng;
+use pbrt::core::sampler::Sampler;
+use pbrt::core::scenecache::ApiCall;
+use pbrt::core::shape::Shape;
+use pbrt::core::transform::Transform;
+use pbrt::materials::disney::DisneyMaterial;
+use pbrt::samplers::halton::HaltonSampler;
+use pbrt::samplers::maxmin::MaxMinDistSampler;
+use pbrt::samplers::pmj02bn::PMJ02BNSampler;
+use pbrt::samplers::sobol::SobolSampler;
+use pbrt::samplers::zerotwosequence::ZeroTwoSequenceSampler;
+use pbrt::shapes::triangle::{Triangle, TriangleMesh};
+
+/// A wavy height field over [-1, 1] x [-1, 1] with 2 * *n* * *n*
+/// triangles, one primitive per triangle.
+fn height_field(n: usize) -> Vec<Arc<Primitive>> {
+    let mut p: Vec<Point3f> = Vec::with_capacity((n + 1) * (n + 1));
+    for j in 0..=n {
+        for i in 0..=n {
+            let x: Float = 2.0 * i as Float / n as Float - 1.0;
+            let y: Float = 2.0 * j as Float / n as Float - 1.0;
+            p.push(Point3f {
+                x,
+                y,
+                z: 0.1 * (8.0 * x).sin() * (8.0 * y).cos(),
+            });
+        }
+    }
+    let mut vertex_indices: Vec<u32> = Vec::with_capacity(6 * n * n);
+    for j in 0..n {
+        for i in 0..n {
+            let v: u32 = (j * (n + 1) + i) as u32;
+            let above: u32 = v + (n + 1) as u32;
+            vertex_indices.extend_from_slice(&[v, v + 1, above + 1, v, above + 1, above]);
+        }
+    }
+    let n_vertices: u32 = p.len() as u32;
+    let mesh: Arc<TriangleMesh> = Arc::new(TriangleMesh::new(
+        Transform::default(),
+        Transform::default(),
+        false,
+        (2 * n * n) as u32,
+        vertex_indices,
+        n_vertices,
+        p,
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        None,
+        None,
+    ));
+    (0..mesh.n_triangles)
+        .map(|id| {
+            let triangle: Arc<Shape> = Arc::new(Shape::Trngl(Triangle::new(
+                mesh.object_to_world,
+                mesh.world_to_object,
+                mesh.reverse_orientation,
+                mesh.clone(),
+                id,
+            )));
+            Arc::new(Primitive::Geometric(Box::new(GeometricPrimitive::new(
+                triangle, None, None, None,
+            ))))
+        })
+        .collect()
+}
+
+/// Rays from above the height field towards random points on it.
+fn random_rays(n: usize) -> Vec<Ray> {
+    let mut rng: Rng = Rng::new();
+    (0..n)
+        .map(|_| {
+            let o: Point3f = Point3f {
+                x: 2.0 * rng.uniform_float() - 1.0,
+                y: 2.0 * rng.uniform_float() - 1.0,
+                z: 2.0,
+            };
+            let target: Point3f = Point3f {
+                x: 2.0 * rng.uniform_float() - 1.0,
+                y: 2.0 * rng.uniform_float() - 1.0,
+                z: 0.0,
+            };
+            Ray {
+                o,
+                d: (target - o).normalize(),
+                t_max: Float::INFINITY,
+                time: 0.0,
+                medium: None,
+                differential: None,
+            }
+        })
+        .collect()
+}
+
+fn bvh(c: &mut Criterion) {
+    let prims: Vec<Arc<Primitive>> = height_field(128);
+    let mut group = c.benchmark_group("bvh");
+    group.sample_size(20);
+    for (name, split_method) in &[
+        ("build_sah", SplitMethod::SAH),
+        ("build_middle", SplitMethod::Middle),
+    ] {
+        group.bench_function(*name, |b| {
+            b.iter(|| BVHAccel::new(black_box(prims.clone()), 4, split_method.clone()))
+        });
+    }
+    let accel: Primitive = Primitive::BVH(Box::new(BVHAccel::new(prims, 4, SplitMethod::SAH)));
+    let rays: Vec<Ray> = random_rays(1024);
+    group.bench_function("intersect", |b| {
+        b.iter(|| {
+            let mut hits: usize = 0;
+            for ray in &rays {
+                let mut ray: Ray = ray.clone();
+                let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+                if accel.intersect(&mut ray, &mut isect) {
+                    hits += 1;
+                }
+            }
+            hits
+        })
+    });
+    group.bench_function("intersect_p", |b| {
+        b.iter(|| rays.iter().filter(|ray| accel.intersect_p(ray)).count())
+    });
+    group.finish();
+}
+
+fn disney_bsdf(c: &mut Criterion) {
+    let mut params: ParamSet = ParamSet::default();
+    params.add_float(String::from("metallic"), 0.3);
+    params.add_float(String::from("roughness"), 0.4);
+    params.add_float(String::from("sheen"), 0.5);
+    params.add_float(String::from("clearcoat"), 0.5);
+    params.add_float(String::from("spectrans"), 0.2);
+    let mut mp: TextureParams = TextureParams::new(
+        ParamSet::default(),
+        params,
+        Arc::new(HashMap::new()),
+        Arc::new(HashMap::new()),
+    );
+    let material: Arc<Material> = DisneyMaterial::create(&mut mp);
+    // a surface interaction to shade
+    let accel: Primitive = Primitive::BVH(Box::new(BVHAccel::new(
+        height_field(8),
+        4,
+        SplitMethod::SAH,
+    )));
+    let ray: Ray = random_rays(1)[0].clone();
+    let wo: Vector3f = -ray.d;
+    let mut group = c.benchmark_group("disney");
+    // (the surface interaction can't be cloned, intersect again)
+    group.bench_function("intersect_and_shade", |b| {
+        b.iter(|| {
+            let mut ray: Ray = ray.clone();
+            let mut si: SurfaceInteraction = SurfaceInteraction::default();
+            accel.intersect(&mut ray, &mut si);
+            material.compute_scattering_functions(
+                &mut si,
+                TransportMode::Radiance,
+                true,
+                None,
+                None,
+            );
+            si.bsdf.is_some()
+        })
+    });
+    let mut si: SurfaceInteraction = SurfaceInteraction::default();
+    assert!(accel.intersect(&mut ray.clone(), &mut si));
+    material.compute_scattering_functions(&mut si, TransportMode::Radiance, true, None, None);
+    let bsdf = si.bsdf.as_ref().unwrap();
+    let mut rng: Rng = Rng::new();
+    let directions: Vec<Vector3f> = (0..256)
+        .map(|_| {
+            Vector3f {
+                x: 2.0 * rng.uniform_float() - 1.0,
+                y: 2.0 * rng.uniform_float() - 1.0,
+                z: rng.uniform_float(),
+            }
+            .normalize()
+        })
+        .collect();
+    group.bench_function("f", |b| {
+        b.iter(|| {
+            let mut sum: Spectrum = Spectrum::default();
+            for wi in &directions {
+                sum += bsdf.f(&wo, wi, BxdfType::BsdfAll as u8);
+            }
+            sum
+        })
+    });
+    group.bench_function("sample_f", |b| {
+        b.iter(|| {
+            let mut sum: Spectrum = Spectrum::default();
+            for (i, _) in directions.iter().enumerate() {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5) / directions.len() as Float,
+                    y: rng.uniform_float(),
+                };
+                let mut wi: Vector3f = Vector3f::default();
+                let mut pdf: Float = 0.0;
+                let mut sampled_type: u8 = 0;
+                sum += bsdf.sample_f(
+                    &wo,
+                    &mut wi,
+                    u,
+                    &mut pdf,
+                    BxdfType::BsdfAll as u8,
+                    &mut sampled_type,
+                );
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+fn samplers(c: &mut Criterion) {
+    let sample_bounds: Bounds2i = Bounds2i {
+        p_min: Point2i { x: 0, y: 0 },
+        p_max: Point2i { x: 16, y: 16 },
+    };
+    let mut params: ParamSet = ParamSet::default();
+    params.add_int(String::from("pixelsamples"), 16);
+    let samplers: Vec<(&str, Box<Sampler>)> = vec![
+        ("halton", HaltonSampler::create(&params, &sample_bounds)),
+        ("maxmindist", MaxMinDistSampler::create(&params).unwrap()),
+        ("pmj02bn", PMJ02BNSampler::create(&params).unwrap()),
+        ("sobol", SobolSampler::create(&params, &sample_bounds)),
+        ("02sequence", ZeroTwoSequenceSampler::create(&params)),
+    ];
+    let mut group = c.benchmark_group("sampler");
+    for (name, sampler) in samplers {
+        // seeded like the render threads do (see SamplerIntegrator::render())
+        let mut sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+        // all samples (with 8 dimensions each) of a 16x16 tile
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut sum: Float = 0.0;
+                for y in 0..16 {
+                    for x in 0..16 {
+                        sampler.start_pixel(Point2i { x, y });
+                        loop {
+                            for _ in 0..4 {
+                                let u: Point2f = sampler.get_2d();
+                                sum += u.x + u.y;
+                            }
+                            if !sampler.start_next_sample() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                sum
+            })
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    let mut builder: SceneBuilder = SceneBuilder::new();
+    builder
+        .set_camera(
+            CameraDesc::Perspective {
+                fov: 45.0,
+                lens_radius: 0.0,
+                focal_distance: 1e6,
+            },
+            Transform::look_at(
+                &Point3f {
+                    x: 0.0,
+                    y: -5.0,
+                    z: 1.0,
+                },
+                &Point3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.5,
+                },
+                &Vector3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+            ),
+        )
+        .set_film(FilmDesc {
+            x_resolution: 32,
+            y_resolution: 32,
+            ..FilmDesc::default()
+        })
+        .set_sampler(SamplerDesc::Halton(4))
+        .set_integrator(IntegratorDesc::Path { max_depth: 5 })
+        .add_light(
+            LightDesc::Infinite {
+                l: Spectrum::new(1.0),
+                map_name: None,
+                n_samples: 1,
+            },
+            &Transform::default(),
+        )
+        .add_shape(
+            ShapeDesc::Sphere { radius: 0.5 },
+            &MaterialDesc::Matte {
+                kd: Spectrum::rgb(0.8, 0.2, 0.2),
+                sigma: 0.0,
+            },
+            &Transform::translate(&Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 0.5,
+            }),
+        );
+    let calls: Vec<ApiCall> = builder.calls();
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    // a single 32x32 tile (one thread)
+    group.bench_function("path_tile", |b| {
+        b.iter(|| {
+            let (mut api_state, mut bsdf_state) = pbrt_init(1);
+            for call in calls.iter().cloned() {
+                call.execute(&mut api_state, &mut bsdf_state);
+            }
+            pbrt_render_to_memory(&mut api_state)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bvh, disney_bsdf, samplers, render);
+criterion_main!(benches);
@@ -0,0 +1,458 @@
+//! C interface to embed the renderer into C/C++ applications. It is
+//! a crate of its own, so only C users build the shared (and static)
+//! library, *libpbrt_capi*:
+//!
+//! ```shell
+//! cargo build --release -p pbrt-capi
+//! ```
+//!
+//! The header **include/pbrt.h** is generated from this file with
+//! [cbindgen](https://github.com/eqrion/cbindgen):
+//!
+//! ```shell
+//! cbindgen --config capi/cbindgen.toml --output include/pbrt.h capi/src/lib.rs
+//! ```
+//!
+//! A scene is parsed once (from a file or from memory) into a
+//! **PbrtScene**, which can be rendered several times (e.g. with
+//! different settings) into a buffer provided by the caller. No Rust
+//! panic crosses the interface, errors are reported as return values.
+//! The functions taking pointers are unsafe: NULL pointers are
+//! rejected, but any other pointer has to be valid (see the *Safety*
+//! sections).
+
+// std
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_double, c_float, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+// pbrt
+use pbrt::core::api::{
+    make_film, make_filter, pbrt_init, pbrt_render_to_memory, pbrt_set_overrides,
+};
+use pbrt::core::api::{RenderOptions, RenderOverrides};
+use pbrt::core::integrator::{cancel_render, reset_render_cancelled};
+use pbrt::core::paramset::ParamSet;
+use pbrt::core::parseerror::ParseError;
+use pbrt::core::parser::{parse_file, parse_str, RecordMode, Recorder, Variables};
+use pbrt::core::progress::{set_progress_callback, ProgressReport};
+use pbrt::core::scenecache::ApiCall;
+
+/// Success.
+pub const PBRT_OK: c_int = 0;
+/// A pointer argument was NULL or a string was not valid UTF-8.
+pub const PBRT_INVALID_ARGUMENT: c_int = -1;
+/// The buffer does not match the size of the image.
+pub const PBRT_WRONG_SIZE: c_int = -2;
+/// Rendering failed (see the messages on stdout).
+pub const PBRT_RENDER_FAILED: c_int = -3;
+
+/// Progress of a render, see **pbrt_set_progress_callback()**.
+#[repr(C)]
+pub struct PbrtProgress {
+    /// 0 to 100
+    pub percent: c_double,
+    /// units of work (e.g. tiles) done so far
+    pub done: u64,
+    pub total: u64,
+    pub rays_per_second: c_double,
+    pub elapsed_seconds: c_double,
+    /// negative if not known yet
+    pub eta_seconds: c_double,
+    /// resident memory of the process (0 if not known)
+    pub memory_bytes: u64,
+}
+
+/// Gets the progress and the *user_data* given to
+/// **pbrt_set_progress_callback()**.
+pub type PbrtProgressCallback =
+    Option<extern "C" fn(progress: *const PbrtProgress, user_data: *mut c_void)>;
+
+/// A parsed scene (opaque for C).
+pub struct PbrtScene {
+    calls: Vec<ApiCall>,
+    overrides: RenderOverrides,
+    number_of_threads: u8,
+}
+
+/// # Safety
+///
+/// *s* has to be NULL or point to a NUL terminated string.
+unsafe fn to_string(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let c_str: &CStr = CStr::from_ptr(s);
+    c_str.to_str().ok().map(String::from)
+}
+
+/// Prints all errors on stdout, followed by the number of errors.
+fn print_errors(errors: &[ParseError]) {
+    for error in errors {
+        println!("{}", error);
+    }
+    println!("{} error(s) found.", errors.len());
+}
+
+fn new_scene(recorder: Option<Recorder>) -> *mut PbrtScene {
+    match recorder {
+        Some(recording) => Box::into_raw(Box::new(PbrtScene {
+            calls: recording.cache.calls,
+            overrides: RenderOverrides::default(),
+            number_of_threads: 0_u8,
+        })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Parses a scene file (and the files it includes). Returns NULL if
+/// the file can't be parsed (the errors get printed). The scene has to be
+/// released with **pbrt_scene_free()**.
+///
+/// # Safety
+///
+/// *filename* has to be NULL or point to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_from_file(filename: *const c_char) -> *mut PbrtScene {
+    let filename: String = match to_string(filename) {
+        Some(filename) => filename,
+        None => return ptr::null_mut(),
+    };
+    let result = panic::catch_unwind(|| {
+        let (mut api_state, mut bsdf_state) = pbrt_init(0_u8);
+        let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+        let mut variables: Variables = Variables::default();
+        parse_file(
+            filename,
+            &mut api_state,
+            &mut bsdf_state,
+            &mut recorder,
+            &mut variables,
+            "",
+        );
+        if api_state.errors.is_empty() {
+            recorder
+        } else {
+            print_errors(&api_state.errors);
+            None
+        }
+    });
+    match result {
+        Ok(recorder) => new_scene(recorder),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Parses a scene description from memory (a NUL terminated string).
+/// Relative filenames (includes, meshes, textures) are resolved
+/// against *search_directory*, which can be NULL. Returns NULL if the
+/// scene can't be parsed.
+///
+/// # Safety
+///
+/// *text* and *search_directory* have to be NULL or point to NUL
+/// terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_from_memory(
+    text: *const c_char,
+    search_directory: *const c_char,
+) -> *mut PbrtScene {
+    let text: String = match to_string(text) {
+        Some(text) => text,
+        None => return ptr::null_mut(),
+    };
+    let search_directory: Option<String> = to_string(search_directory);
+    let result = panic::catch_unwind(|| {
+        let (mut api_state, mut bsdf_state) = pbrt_init(0_u8);
+        let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+        if let Some(search_directory) = search_directory {
+            api_state.search_directory = Some(Box::new(PathBuf::from(&search_directory)));
+            if let Some(ref mut recording) = recorder {
+                recording
+                    .cache
+                    .calls
+                    .push(ApiCall::SearchDirectory(search_directory));
+            }
+        }
+        let mut variables: Variables = Variables::default();
+        parse_str(
+            &text,
+            &mut api_state,
+            &mut bsdf_state,
+            &mut recorder,
+            &mut variables,
+            "",
+        );
+        if api_state.errors.is_empty() {
+            recorder
+        } else {
+            print_errors(&api_state.errors);
+            None
+        }
+    });
+    match result {
+        Ok(recorder) => new_scene(recorder),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a scene (NULL is ignored).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a scene returned by
+/// **pbrt_scene_from_file()** or **pbrt_scene_from_memory()**, which
+/// wasn't released before, and isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_free(scene: *mut PbrtScene) {
+    if !scene.is_null() {
+        drop(Box::from_raw(scene));
+    }
+}
+
+/// Number of threads used for rendering (0 means all cores).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// which no other thread uses at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_set_threads(
+    scene: *mut PbrtScene,
+    number_of_threads: c_int,
+) -> c_int {
+    match scene.as_mut() {
+        Some(scene) if (0..=255).contains(&number_of_threads) => {
+            scene.number_of_threads = number_of_threads as u8;
+            PBRT_OK
+        }
+        _ => PBRT_INVALID_ARGUMENT,
+    }
+}
+
+/// Overrides the samples per pixel of the scene (0 restores them).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// which no other thread uses at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_set_pixel_samples(scene: *mut PbrtScene, spp: c_int) -> c_int {
+    match scene.as_mut() {
+        Some(scene) if spp >= 0 => {
+            scene.overrides.pixel_samples = if spp > 0 { Some(spp) } else { None };
+            PBRT_OK
+        }
+        _ => PBRT_INVALID_ARGUMENT,
+    }
+}
+
+/// Overrides the resolution of the scene (0 x 0 restores it).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// which no other thread uses at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_set_resolution(
+    scene: *mut PbrtScene,
+    x_resolution: c_int,
+    y_resolution: c_int,
+) -> c_int {
+    match scene.as_mut() {
+        Some(scene) if x_resolution > 0 && y_resolution > 0 => {
+            scene.overrides.resolution = Some((x_resolution, y_resolution));
+            PBRT_OK
+        }
+        Some(scene) if x_resolution == 0 && y_resolution == 0 => {
+            scene.overrides.resolution = None;
+            PBRT_OK
+        }
+        _ => PBRT_INVALID_ARGUMENT,
+    }
+}
+
+/// Overrides the integrator of the scene (e.g. "path", NULL restores
+/// it).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// which no other thread uses at the same time, *name* NULL or a NUL
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_set_integrator(
+    scene: *mut PbrtScene,
+    name: *const c_char,
+) -> c_int {
+    match scene.as_mut() {
+        Some(scene) => {
+            if name.is_null() {
+                scene.overrides.integrator = None;
+                return PBRT_OK;
+            }
+            match to_string(name) {
+                Some(name) => {
+                    scene.overrides.integrator = Some(name);
+                    PBRT_OK
+                }
+                None => PBRT_INVALID_ARGUMENT,
+            }
+        }
+        None => PBRT_INVALID_ARGUMENT,
+    }
+}
+
+/// Width and height of the image of a scene (taking the crop window
+/// into account), None if the film can't be created.
+fn image_size(scene: &PbrtScene) -> Option<(c_int, c_int)> {
+    panic::catch_unwind(|| {
+        // the last Film statement wins
+        let mut render_options: RenderOptions = RenderOptions::default();
+        for call in &scene.calls {
+            if let ApiCall::Params(params) = call {
+                if params.key_word == "Film" {
+                    render_options.film_params = (**params).clone();
+                }
+            }
+        }
+        render_options.apply_overrides(&scene.overrides);
+        let filter = make_filter("box", &ParamSet::default()).ok()?;
+        let film = make_film("image", &render_options.film_params, filter).ok()?;
+        let bounds = film.cropped_pixel_bounds;
+        Some((
+            (bounds.p_max.x - bounds.p_min.x) as c_int,
+            (bounds.p_max.y - bounds.p_min.y) as c_int,
+        ))
+    })
+    .unwrap_or(None)
+}
+
+/// Size of the image **pbrt_scene_render()** will produce (taking
+/// the crop window into account).
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// *width* and *height* NULL or valid pointers to write an int to.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_image_size(
+    scene: *const PbrtScene,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> c_int {
+    let scene: &PbrtScene = match scene.as_ref() {
+        Some(scene) => scene,
+        None => return PBRT_INVALID_ARGUMENT,
+    };
+    if width.is_null() || height.is_null() {
+        return PBRT_INVALID_ARGUMENT;
+    }
+    match image_size(scene) {
+        Some((x, y)) => {
+            *width = x;
+            *height = y;
+            PBRT_OK
+        }
+        None => PBRT_RENDER_FAILED,
+    }
+}
+
+/// Renders the scene into *buffer*, which has to hold *width* x
+/// *height* RGB triples (linear, not gamma corrected, row by row, top
+/// row first), see **pbrt_scene_image_size()**. Blocks until the
+/// image is done or **pbrt_cancel()** gets called. Returns
+/// PBRT_WRONG_SIZE (before rendering) if *width* and *height* aren't
+/// the size of the image.
+///
+/// # Safety
+///
+/// *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+/// *buffer* NULL or valid for writing *width* x *height* x 3 floats.
+#[no_mangle]
+pub unsafe extern "C" fn pbrt_scene_render(
+    scene: *const PbrtScene,
+    buffer: *mut c_float,
+    width: c_int,
+    height: c_int,
+) -> c_int {
+    let scene: &PbrtScene = match scene.as_ref() {
+        Some(scene) => scene,
+        None => return PBRT_INVALID_ARGUMENT,
+    };
+    if buffer.is_null() || width <= 0 || height <= 0 {
+        return PBRT_INVALID_ARGUMENT;
+    }
+    match image_size(scene) {
+        Some(size) if size == (width, height) => {}
+        Some(_) => return PBRT_WRONG_SIZE,
+        None => return PBRT_RENDER_FAILED,
+    }
+    reset_render_cancelled();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (mut api_state, mut bsdf_state) = pbrt_init(scene.number_of_threads);
+        pbrt_set_overrides(&mut api_state, scene.overrides.clone());
+        for call in &scene.calls {
+            if let ApiCall::WorldEnd = call {
+                break;
+            }
+            call.clone().execute(&mut api_state, &mut bsdf_state);
+        }
+        let image = pbrt_render_to_memory(&mut api_state);
+        if image.is_none() {
+            print_errors(&api_state.errors);
+        }
+        image
+    }));
+    match result {
+        Ok(Some((x, y, rgb))) => {
+            // the buffer is never written beyond the checked size
+            if x != width || y != height || rgb.len() != 3 * x as usize * y as usize {
+                return PBRT_WRONG_SIZE;
+            }
+            let pixels: &mut [c_float] = std::slice::from_raw_parts_mut(buffer, rgb.len());
+            for (pixel, value) in pixels.iter_mut().zip(rgb.iter()) {
+                *pixel = *value as c_float;
+            }
+            PBRT_OK
+        }
+        Ok(None) | Err(_) => PBRT_RENDER_FAILED,
+    }
+}
+
+/// Stops the render which is currently running (can be called from
+/// any thread). The buffer contains whatever was rendered so far.
+#[no_mangle]
+pub extern "C" fn pbrt_cancel() {
+    cancel_render();
+}
+
+/// Calls *callback* (from a render thread) with the progress of all
+/// following renders, about twice a second and once more when the
+/// render is done. NULL removes the callback.
+#[no_mangle]
+pub extern "C" fn pbrt_set_progress_callback(
+    callback: PbrtProgressCallback,
+    user_data: *mut c_void,
+) {
+    match callback {
+        Some(callback) => {
+            // the pointer is only handed back to C
+            let user_data: usize = user_data as usize;
+            set_progress_callback(Some(Box::new(move |report: &ProgressReport| {
+                let progress: PbrtProgress = PbrtProgress {
+                    percent: report.percent,
+                    done: report.done,
+                    total: report.total,
+                    rays_per_second: report.rays_per_second,
+                    elapsed_seconds: report.elapsed.as_secs_f64(),
+                    eta_seconds: report.eta.map_or(-1.0, |eta| eta.as_secs_f64()),
+                    memory_bytes: report.memory.unwrap_or(0),
+                };
+                callback(&progress, user_data as *mut c_void);
+            })));
+        }
+        None => set_progress_callback(None),
+    }
+}
@@ -0,0 +1,114 @@
+use pbrt::core::builder::{
+    CameraDesc, FilmDesc, IntegratorDesc, LightDesc, MaterialDesc, SamplerDesc, SceneBuilder,
+    ShapeDesc,
+};
+use pbrt::core::geometry::{Point3f, Vector3f};
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::transform::Transform;
+// std
+use std::env;
+
+fn main() {
+    let mut builder = SceneBuilder::new();
+    builder
+        .set_camera(
+            CameraDesc::Perspective {
+                fov: 40.0,
+                lens_radius: 0.0,
+                focal_distance: 1e6,
+            },
+            Transform::look_at(
+                &Point3f {
+                    x: 0.0,
+                    y: -6.0,
+                    z: 2.0,
+                },
+                &Point3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.5,
+                },
+                &Vector3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+            ),
+        )
+        .set_film(FilmDesc {
+            x_resolution: 400,
+            y_resolution: 300,
+            ..FilmDesc::default()
+        })
+        .set_sampler(SamplerDesc::Sobol(16))
+        .set_integrator(IntegratorDesc::Path { max_depth: 5 })
+        .add_named_material(
+            "gold",
+            &MaterialDesc::Metal {
+                eta: Spectrum::rgb(0.143, 0.374, 1.442),
+                k: Spectrum::rgb(3.983, 2.385, 1.603),
+                roughness: 0.05,
+            },
+        )
+        // ground
+        .add_shape(
+            ShapeDesc::Disk {
+                height: 0.0,
+                radius: 10.0,
+                inner_radius: 0.0,
+            },
+            &MaterialDesc::Matte {
+                kd: Spectrum::new(0.5 as Float),
+                sigma: 0.0,
+            },
+            &Transform::default(),
+        )
+        .add_shape(
+            ShapeDesc::Sphere { radius: 0.5 },
+            &MaterialDesc::Named(String::from("gold")),
+            &Transform::translate(&Vector3f {
+                x: -0.6,
+                y: 0.0,
+                z: 0.5,
+            }),
+        )
+        .add_shape(
+            ShapeDesc::Sphere { radius: 0.5 },
+            &MaterialDesc::Plastic {
+                kd: Spectrum::rgb(0.1, 0.2, 0.6),
+                ks: Spectrum::new(0.25 as Float),
+                roughness: 0.1,
+            },
+            &Transform::translate(&Vector3f {
+                x: 0.6,
+                y: 0.0,
+                z: 0.5,
+            }),
+        )
+        // small emitting sphere above the scene
+        .add_area_light(
+            ShapeDesc::Sphere { radius: 0.25 },
+            &MaterialDesc::None,
+            Spectrum::new(20.0 as Float),
+            false,
+            &Transform::translate(&Vector3f {
+                x: 0.0,
+                y: -1.0,
+                z: 3.0,
+            }),
+        )
+        .add_light(
+            LightDesc::Infinite {
+                l: Spectrum::new(0.2 as Float),
+                map_name: None,
+                n_samples: 1,
+            },
+            &Transform::default(),
+        );
+    // render the scene, or write it to the .pbrt file given as argument
+    if let Some(filename) = env::args().nth(1) {
+        builder.export(&filename).unwrap();
+    } else {
+        builder.render(0);
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Renders a .pbrt scene through the C interface and writes the
+ * (linear) image as binary PPM. Build the library first, then:
+ *
+ *   cargo build --release -p pbrt-capi
+ *   cc -Iinclude examples/c/render_scene.c -Ltarget/release -lpbrt_capi -o render_scene
+ *   LD_LIBRARY_PATH=target/release ./render_scene scene.pbrt image.ppm
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "pbrt.h"
+
+static unsigned char to_byte(float v)
+{
+    /* no gamma correction, just clamp */
+    if (v <= 0.0f)
+        return 0;
+    if (v >= 1.0f)
+        return 255;
+    return (unsigned char)(v * 255.0f + 0.5f);
+}
+
+int main(int argc, char **argv)
+{
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s scene.pbrt image.ppm\n", argv[0]);
+        return 1;
+    }
+    PbrtScene *scene = pbrt_scene_from_file(argv[1]);
+    if (scene == NULL) {
+        fprintf(stderr, "unable to parse %s\n", argv[1]);
+        return 1;
+    }
+    int width = 0, height = 0;
+    if (pbrt_scene_image_size(scene, &width, &height) != PBRT_OK) {
+        pbrt_scene_free(scene);
+        return 1;
+    }
+    float *buffer = malloc(sizeof(float) * 3 * width * height);
+    int result = pbrt_scene_render(scene, buffer, width, height);
+    pbrt_scene_free(scene);
+    if (result != PBRT_OK) {
+        fprintf(stderr, "rendering failed (%d)\n", result);
+        free(buffer);
+        return 1;
+    }
+    FILE *f = fopen(argv[2], "wb");
+    if (f == NULL) {
+        free(buffer);
+        return 1;
+    }
+    fprintf(f, "P6\n%d %d\n255\n", width, height);
+    for (int i = 0; i < 3 * width * height; i++)
+        fputc(to_byte(buffer[i]), f);
+    fclose(f);
+    free(buffer);
+    return 0;
+}
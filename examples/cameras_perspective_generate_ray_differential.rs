@@ -49,7 +49,7 @@ fn main() {
         35.0,
         filename,
         1.0,
-        std::f32::INFINITY,
+        Float::INFINITY,
     ));
     let pos = Point3f {
         x: 2.0,
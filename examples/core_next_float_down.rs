@@ -1,7 +1,7 @@
-use pbrt::core::pbrt::next_float_down;
+use pbrt::core::pbrt::{next_float_down, Float};
 
 fn main() {
-    let v: f32 = 0.699999392;
-    let vu: f32 = next_float_down(v);
+    let v: Float = 0.699999392;
+    let vu: Float = next_float_down(v);
     println!("next_float_down({:?}) = {:?})", v, vu);
 }
@@ -1,7 +1,7 @@
-use pbrt::core::pbrt::next_float_up;
+use pbrt::core::pbrt::{next_float_up, Float};
 
 fn main() {
-    let v: f32 = -0.999999583;
-    let vu: f32 = next_float_up(v);
+    let v: Float = -0.999999583;
+    let vu: Float = next_float_up(v);
     println!("next_float_up({:?}) = {:?})", v, vu);
 }
@@ -16,7 +16,7 @@ fn main() {
             y: -0.164096087,
             z: -0.984571517,
         },
-        t_max: std::f32::INFINITY,
+        t_max: Float::INFINITY,
         time: 0.0,
         medium: None,
         differential: None,
@@ -59,7 +59,7 @@ fn main() {
             y: -0.168564394,
             z: -0.84591651,
         },
-        t_max: std::f32::INFINITY,
+        t_max: Float::INFINITY,
         time: 0.0,
         medium: None,
         differential: None,
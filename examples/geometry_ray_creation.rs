@@ -1,4 +1,5 @@
 use pbrt::core::geometry::{Point3f, Ray, Vector3f};
+use pbrt::core::pbrt::Float;
 
 fn main() {
     let origin = Point3f {
@@ -14,7 +15,7 @@ fn main() {
     let _ray = Ray {
         o: origin,
         d: direction,
-        t_max: std::f32::INFINITY,
+        t_max: Float::INFINITY,
         time: 0.0,
         medium: None,
         differential: None,
@@ -0,0 +1,1283 @@
+// command line options
+use structopt::StructOpt;
+// others
+use byteorder::{ByteOrder, LittleEndian};
+// pbrt
+use pbrt::core::api::{make_accelerator, make_camera, make_film, make_filter, make_sampler};
+use pbrt::core::camera::Camera;
+use pbrt::core::film::Film;
+use pbrt::core::geometry::{bnd3_union_bnd3, pnt3_lerp};
+use pbrt::core::geometry::{Bounds2i, Bounds3f, Normal3f, Point2f, Point3f, Vector3f};
+use pbrt::core::integrator::{Integrator, SamplerIntegrator};
+use pbrt::core::light::Light;
+use pbrt::core::material::Material;
+use pbrt::core::medium::MediumInterface;
+use pbrt::core::paramset::{ParamSet, TextureParams};
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::sampler::Sampler;
+use pbrt::core::scene::Scene;
+use pbrt::core::shape::Shape;
+use pbrt::core::transform::{AnimatedTransform, Transform};
+use pbrt::integrators::path::PathIntegrator;
+use pbrt::lights::infinite::InfiniteAreaLight;
+use pbrt::materials::disney::DisneyMaterial;
+use pbrt::materials::hair::HairMaterial;
+use pbrt::shapes::curve::{Curve, CurveType};
+use pbrt::shapes::triangle::{Triangle, TriangleMesh};
+// std
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+/// Parse an Alembic archive (extension .abc, Ogawa container) and
+/// render it frame by frame.
+///
+/// Polygon meshes (and subdivision surfaces as their control cage),
+/// curves, transforms and cameras are read at the time of each frame
+/// (the closest sample at or before it). Alembic has no lights or
+/// materials, so the scene is lit by a dome light and meshes are
+/// grey, curves use the hair material.
+#[derive(StructOpt)]
+struct Cli {
+    /// samples per pixel
+    #[structopt(short = "s", long = "samples", default_value = "16")]
+    samples: u16,
+    /// horizontal resolution (the vertical one follows from the camera's aperture)
+    #[structopt(short = "x", long = "xres", default_value = "640")]
+    xres: i32,
+    /// maximum path length
+    #[structopt(short = "d", long = "maxdepth", default_value = "5")]
+    max_depth: i32,
+    /// first frame to render
+    #[structopt(short = "f", long = "frame", default_value = "1")]
+    frame: i32,
+    /// last frame to render (defaults to the first one)
+    #[structopt(short = "e", long = "end-frame")]
+    end_frame: Option<i32>,
+    /// frames per second (to convert frames into Alembic's time in seconds)
+    #[structopt(long = "fps", default_value = "24")]
+    fps: f64,
+    /// environment map (.hdr) for the dome light
+    #[structopt(long = "envmap", default_value = "")]
+    envmap: String,
+    /// The path to the file to read
+    #[structopt(parse(from_os_str))]
+    path: std::path::PathBuf,
+}
+
+// Ogawa
+
+/// Set for children which are data blocks (and not groups).
+const OGAWA_DATA_BIT: u64 = 0x8000_0000_0000_0000;
+
+/// An Ogawa container: a tree of groups, whose leaves are blocks of
+/// data. Children are referred to by their position in the file (with
+/// **OGAWA_DATA_BIT** set for data), position zero is an empty group
+/// or data block.
+struct Ogawa {
+    bytes: Vec<u8>,
+}
+
+impl Ogawa {
+    fn new(bytes: Vec<u8>) -> Self {
+        // magic, frozen flag, version and the position of the root group
+        if bytes.len() < 16 || &bytes[0..5] != b"Ogawa" {
+            panic!("Not an Alembic archive (Ogawa), HDF5 based ones are not supported.");
+        }
+        Ogawa { bytes }
+    }
+    fn read_u64(&self, pos: usize) -> u64 {
+        if pos + 8 > self.bytes.len() {
+            panic!("Alembic archive is truncated (reading at {}).", pos);
+        }
+        LittleEndian::read_u64(&self.bytes[pos..pos + 8])
+    }
+    fn root(&self) -> u64 {
+        self.read_u64(8)
+    }
+    fn children(&self, group: u64) -> Vec<u64> {
+        if group == 0 || group & OGAWA_DATA_BIT != 0 {
+            return Vec::new();
+        }
+        let pos: usize = group as usize;
+        let n_children: usize = self.read_u64(pos) as usize;
+        (0..n_children)
+            .map(|i| self.read_u64(pos + 8 * (i + 1)))
+            .collect()
+    }
+    fn data(&self, child: u64) -> &[u8] {
+        let pos: usize = (child & !OGAWA_DATA_BIT) as usize;
+        if child & OGAWA_DATA_BIT == 0 || pos == 0 {
+            return &[];
+        }
+        let size: usize = self.read_u64(pos) as usize;
+        if pos + 8 + size > self.bytes.len() {
+            panic!("Alembic archive is truncated (data at {}).", pos);
+        }
+        &self.bytes[pos + 8..pos + 8 + size]
+    }
+}
+
+// Alembic
+
+/// Sizes of the plain old data types (bool, uint8, int8, uint16,
+/// int16, uint32, int32, uint64, int64, float16, float32, float64).
+const POD_SIZES: [usize; 12] = [1, 1, 1, 2, 2, 4, 4, 8, 8, 2, 4, 8];
+
+/// Parses Alembic's "key=value;key=value" metadata.
+fn parse_metadata(metadata: &str) -> HashMap<String, String> {
+    metadata
+        .split(';')
+        .filter_map(|entry| {
+            let mut key_value = entry.splitn(2, '=');
+            match (key_value.next(), key_value.next()) {
+                (Some(key), Some(value)) => Some((key.to_string(), value.to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads an unsigned integer stored with 1, 2 or 4 bytes.
+fn read_with_hint(buf: &[u8], size_hint: u32, pos: &mut usize) -> usize {
+    let value: usize = match size_hint {
+        0 => buf[*pos] as usize,
+        1 => LittleEndian::read_u16(&buf[*pos..]) as usize,
+        _ => LittleEndian::read_u32(&buf[*pos..]) as usize,
+    };
+    *pos += 1 << size_hint;
+    value
+}
+
+/// Uniform, cyclic or acyclic sample times (in seconds).
+struct TimeSampling {
+    time_per_cycle: f64,
+    times: Vec<f64>,
+}
+
+impl TimeSampling {
+    fn is_acyclic(&self) -> bool {
+        // Alembic's marker for acyclic sampling
+        self.time_per_cycle >= std::f64::MAX / 64.0
+    }
+    fn sample_time(&self, index: usize) -> f64 {
+        let n: usize = self.times.len().max(1);
+        if self.times.is_empty() {
+            index as f64
+        } else if self.is_acyclic() {
+            self.times[index.min(n - 1)]
+        } else {
+            self.times[index % n] + (index / n) as f64 * self.time_per_cycle
+        }
+    }
+    /// Index of the last sample at or before *time*.
+    fn floor_index(&self, time: f64, n_samples: usize) -> usize {
+        let mut index: usize = 0;
+        for i in 1..n_samples {
+            // tolerate rounding of frame times
+            if self.sample_time(i) <= time + 1e-6 {
+                index = i;
+            } else {
+                break;
+            }
+        }
+        index
+    }
+}
+
+/// The decoded header of a property (see Alembic's
+/// AbcCoreOgawa/ReadUtil.cpp for the bit layout of the first word).
+struct AbcProperty {
+    name: String,
+    /// 0 compound, 1 scalar, 2 array
+    property_type: u32,
+    pod: usize,
+    n_samples: usize,
+    first_changed: usize,
+    last_changed: usize,
+    time_sampling: usize,
+    metadata: HashMap<String, String>,
+    group: u64,
+}
+
+impl AbcProperty {
+    fn is_compound(&self) -> bool {
+        self.property_type == 0
+    }
+    /// Only changed samples are stored.
+    fn stored_index(&self, index: usize) -> usize {
+        if self.first_changed == 0 && self.last_changed == 0 {
+            0
+        } else if index >= self.last_changed {
+            self.last_changed - self.first_changed + 1
+        } else if index < self.first_changed {
+            0
+        } else {
+            index - self.first_changed + 1
+        }
+    }
+}
+
+/// An object of the archive and the group holding its properties
+/// and children.
+struct AbcObject {
+    full_name: String,
+    metadata: HashMap<String, String>,
+    group: u64,
+}
+
+impl AbcObject {
+    fn schema(&self) -> &str {
+        self.metadata
+            .get("schema")
+            .map(|schema| schema.as_str())
+            .unwrap_or("")
+    }
+}
+
+struct AbcArchive {
+    ogawa: Ogawa,
+    time_samplings: Vec<TimeSampling>,
+    indexed_metadata: Vec<String>,
+}
+
+impl AbcArchive {
+    fn new(bytes: Vec<u8>) -> Self {
+        let ogawa: Ogawa = Ogawa::new(bytes);
+        let root: Vec<u64> = ogawa.children(ogawa.root());
+        if root.len() < 6 {
+            panic!(
+                "Alembic archive has {} root entries (6 expected).",
+                root.len()
+            );
+        }
+        // time samplings
+        let mut time_samplings: Vec<TimeSampling> = Vec::new();
+        let buf: &[u8] = ogawa.data(root[4]);
+        let mut pos: usize = 0;
+        while pos + 16 <= buf.len() {
+            // skip the maximum number of samples
+            let time_per_cycle: f64 = LittleEndian::read_f64(&buf[pos + 4..]);
+            let n_times: usize = LittleEndian::read_u32(&buf[pos + 12..]) as usize;
+            pos += 16;
+            let times: Vec<f64> = (0..n_times)
+                .map(|i| LittleEndian::read_f64(&buf[pos + 8 * i..]))
+                .collect();
+            pos += 8 * n_times;
+            time_samplings.push(TimeSampling {
+                time_per_cycle,
+                times,
+            });
+        }
+        // metadata shared by objects and properties (index 0 is empty)
+        let mut indexed_metadata: Vec<String> = vec![String::new()];
+        let buf: &[u8] = ogawa.data(root[5]);
+        let mut pos: usize = 0;
+        while pos < buf.len() {
+            let size: usize = buf[pos] as usize;
+            indexed_metadata
+                .push(String::from_utf8_lossy(&buf[pos + 1..pos + 1 + size]).to_string());
+            pos += 1 + size;
+        }
+        AbcArchive {
+            ogawa,
+            time_samplings,
+            indexed_metadata,
+        }
+    }
+    fn top_object(&self) -> AbcObject {
+        let root: Vec<u64> = self.ogawa.children(self.ogawa.root());
+        AbcObject {
+            full_name: String::from(""),
+            metadata: HashMap::new(),
+            group: root[2],
+        }
+    }
+    fn object_children(&self, object: &AbcObject) -> Vec<AbcObject> {
+        let mut children: Vec<AbcObject> = Vec::new();
+        let entries: Vec<u64> = self.ogawa.children(object.group);
+        if entries.len() < 2 {
+            return children;
+        }
+        // the headers of the children come last (followed by two hashes)
+        let buf: &[u8] = self.ogawa.data(entries[entries.len() - 1]);
+        let mut pos: usize = 0;
+        let mut index: usize = 1;
+        while pos + 32 < buf.len() && index < entries.len() - 1 {
+            let name_size: usize = LittleEndian::read_u32(&buf[pos..]) as usize;
+            pos += 4;
+            let name: String = String::from_utf8_lossy(&buf[pos..pos + name_size]).to_string();
+            pos += name_size;
+            let metadata_index: usize = buf[pos] as usize;
+            pos += 1;
+            let metadata: String = if metadata_index == 0xff {
+                let size: usize = LittleEndian::read_u32(&buf[pos..]) as usize;
+                pos += 4;
+                pos += size;
+                String::from_utf8_lossy(&buf[pos - size..pos]).to_string()
+            } else {
+                self.indexed_metadata
+                    .get(metadata_index)
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            children.push(AbcObject {
+                full_name: format!("{}/{}", object.full_name, name),
+                metadata: parse_metadata(&metadata),
+                group: entries[index],
+            });
+            index += 1;
+        }
+        children
+    }
+    /// The (top level) properties of an object.
+    fn object_properties(&self, object: &AbcObject) -> Vec<AbcProperty> {
+        match self.ogawa.children(object.group).first() {
+            Some(compound) => self.compound_properties(*compound),
+            None => Vec::new(),
+        }
+    }
+    fn compound_properties(&self, group: u64) -> Vec<AbcProperty> {
+        let mut properties: Vec<AbcProperty> = Vec::new();
+        let entries: Vec<u64> = self.ogawa.children(group);
+        if entries.is_empty() {
+            return properties;
+        }
+        // the property headers come last
+        let buf: &[u8] = self.ogawa.data(entries[entries.len() - 1]);
+        let mut pos: usize = 0;
+        let mut index: usize = 0;
+        while pos + 4 <= buf.len() && index < entries.len() - 1 {
+            let info: u32 = LittleEndian::read_u32(&buf[pos..]);
+            pos += 4;
+            let property_type: u32 = info & 0x3;
+            let size_hint: u32 = (info & 0xc) >> 2;
+            let mut property: AbcProperty = AbcProperty {
+                name: String::new(),
+                property_type,
+                pod: 0,
+                n_samples: 0,
+                first_changed: 0,
+                last_changed: 0,
+                time_sampling: 0,
+                metadata: HashMap::new(),
+                group: entries[index],
+            };
+            if property_type != 0 {
+                property.pod = ((info & 0xf0) >> 4) as usize;
+                property.n_samples = read_with_hint(buf, size_hint, &mut pos);
+                if info & 0x200 != 0 {
+                    // first and last changed index
+                    property.first_changed = read_with_hint(buf, size_hint, &mut pos);
+                    property.last_changed = read_with_hint(buf, size_hint, &mut pos);
+                } else if info & 0x800 != 0 {
+                    // constant
+                } else {
+                    property.first_changed = 1;
+                    property.last_changed = property.n_samples.saturating_sub(1);
+                }
+                if info & 0x100 != 0 {
+                    property.time_sampling = read_with_hint(buf, size_hint, &mut pos);
+                }
+            }
+            let name_size: usize = read_with_hint(buf, size_hint, &mut pos);
+            property.name = String::from_utf8_lossy(&buf[pos..pos + name_size]).to_string();
+            pos += name_size;
+            let metadata_index: usize = ((info & 0xff0_0000) >> 20) as usize;
+            let metadata: String = if metadata_index == 0xff {
+                let size: usize = read_with_hint(buf, size_hint, &mut pos);
+                pos += size;
+                String::from_utf8_lossy(&buf[pos - size..pos]).to_string()
+            } else {
+                self.indexed_metadata
+                    .get(metadata_index)
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            property.metadata = parse_metadata(&metadata);
+            properties.push(property);
+            index += 1;
+        }
+        properties
+    }
+    fn find_property(&self, properties: Vec<AbcProperty>, name: &str) -> Option<AbcProperty> {
+        properties
+            .into_iter()
+            .find(|property| property.name == name)
+    }
+    /// The properties of the compound holding the geometry of a shape
+    /// (**.geom**) or a transform (**.xform**).
+    fn schema_properties(&self, object: &AbcObject, compound: &str) -> Vec<AbcProperty> {
+        match self.find_property(self.object_properties(object), compound) {
+            Some(property) if property.is_compound() => self.compound_properties(property.group),
+            _ => Vec::new(),
+        }
+    }
+    /// Index of the sample (of a scalar or array property) which is
+    /// used at *time*.
+    fn sample_index(&self, property: &AbcProperty, time: f64) -> usize {
+        if property.n_samples <= 1 {
+            return 0;
+        }
+        match self.time_samplings.get(property.time_sampling) {
+            Some(time_sampling) => time_sampling.floor_index(time, property.n_samples),
+            None => 0,
+        }
+    }
+    /// Raw bytes of the sample at *time* (without the leading 16
+    /// byte key).
+    fn sample_bytes(&self, property: &AbcProperty, time: f64) -> &[u8] {
+        let stored: usize = property.stored_index(self.sample_index(property, time));
+        let entries: Vec<u64> = self.ogawa.children(property.group);
+        // array samples are followed by their dimensions
+        let entry: usize = if property.property_type == 2 {
+            2 * stored
+        } else {
+            stored
+        };
+        match entries.get(entry) {
+            Some(child) => {
+                let buf: &[u8] = self.ogawa.data(*child);
+                if buf.len() > 16 {
+                    &buf[16..]
+                } else {
+                    &[]
+                }
+            }
+            None => &[],
+        }
+    }
+    /// All numbers of the sample at *time* converted to **Float**.
+    fn read_floats(&self, property: &AbcProperty, time: f64) -> Vec<Float> {
+        let buf: &[u8] = self.sample_bytes(property, time);
+        if property.pod >= POD_SIZES.len() {
+            println!(
+                "WARNING: property {:?} does not hold numbers",
+                property.name
+            );
+            return Vec::new();
+        }
+        let size: usize = POD_SIZES[property.pod];
+        buf.chunks_exact(size)
+            .map(|value| match property.pod {
+                0 | 1 => value[0] as Float,
+                2 => value[0] as i8 as Float,
+                3 => LittleEndian::read_u16(value) as Float,
+                4 => LittleEndian::read_i16(value) as Float,
+                5 => LittleEndian::read_u32(value) as Float,
+                6 => LittleEndian::read_i32(value) as Float,
+                7 => LittleEndian::read_u64(value) as Float,
+                8 => LittleEndian::read_i64(value) as Float,
+                9 => half_to_float(LittleEndian::read_u16(value)),
+                10 => LittleEndian::read_f32(value) as Float,
+                _ => LittleEndian::read_f64(value) as Float,
+            })
+            .collect()
+    }
+    fn read_f64s(&self, property: &AbcProperty, time: f64) -> Vec<f64> {
+        let buf: &[u8] = self.sample_bytes(property, time);
+        if property.pod == 11 {
+            buf.chunks_exact(8).map(LittleEndian::read_f64).collect()
+        } else {
+            self.read_floats(property, time)
+                .iter()
+                .map(|value| *value as f64)
+                .collect()
+        }
+    }
+    fn read_indices(&self, property: &AbcProperty, time: f64) -> Vec<usize> {
+        self.read_floats(property, time)
+            .iter()
+            .map(|value| *value as usize)
+            .collect()
+    }
+}
+
+fn half_to_float(half: u16) -> Float {
+    let sign: Float = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent: i32 = ((half >> 10) & 0x1f) as i32;
+    let mantissa: Float = (half & 0x3ff) as Float;
+    match exponent {
+        0 => sign * mantissa * (2.0 as Float).powi(-24),
+        31 => {
+            if mantissa == 0.0 {
+                sign * Float::INFINITY
+            } else {
+                Float::NAN
+            }
+        }
+        _ => {
+            sign * (1.0 as Float + mantissa / 1024.0 as Float) * (2.0 as Float).powi(exponent - 15)
+        }
+    }
+}
+
+/// A geometry parameter (like **N** or **uv**), either a plain array
+/// or a compound of values (**.vals**) and indices (**.indices**).
+struct GeomParam {
+    values: Vec<Float>,
+    indices: Vec<usize>,
+    /// **vtx**, **var**, **fvr**, **uni** or **con**
+    scope: String,
+}
+
+impl GeomParam {
+    fn read(
+        archive: &AbcArchive,
+        properties: &[AbcProperty],
+        name: &str,
+        time: f64,
+    ) -> Option<GeomParam> {
+        let property: &AbcProperty = properties.iter().find(|p| p.name == name)?;
+        let scope: String = property
+            .metadata
+            .get("geoScope")
+            .cloned()
+            .unwrap_or_else(|| String::from("con"));
+        if property.is_compound() {
+            let inner: Vec<AbcProperty> = archive.compound_properties(property.group);
+            let values: Vec<Float> = inner
+                .iter()
+                .find(|p| p.name == ".vals")
+                .map(|p| archive.read_floats(p, time))
+                .unwrap_or_default();
+            let indices: Vec<usize> = inner
+                .iter()
+                .find(|p| p.name == ".indices")
+                .map(|p| archive.read_indices(p, time))
+                .unwrap_or_default();
+            Some(GeomParam {
+                values,
+                indices,
+                scope,
+            })
+        } else {
+            Some(GeomParam {
+                values: archive.read_floats(property, time),
+                indices: Vec::new(),
+                scope,
+            })
+        }
+    }
+    fn is_per_point(&self) -> bool {
+        self.indices.is_empty() && (self.scope == "vtx" || self.scope == "var")
+    }
+    /// Returns the index of the value used for polygon corner
+    /// *corner* of face *face*, which refers to point *point*.
+    fn lookup(&self, corner: usize, point: usize, face: usize) -> usize {
+        let element: usize = match self.scope.as_str() {
+            "vtx" | "var" => point,
+            "fvr" => corner,
+            "uni" => face,
+            _ => 0, // constant
+        };
+        if self.indices.is_empty() {
+            element
+        } else {
+            self.indices[element]
+        }
+    }
+    /// Checks that all lookups stay within bounds.
+    fn is_valid(
+        &self,
+        n_components: usize,
+        n_points: usize,
+        n_faces: usize,
+        n_corners: usize,
+    ) -> bool {
+        let n_elements: usize = match self.scope.as_str() {
+            "vtx" | "var" => n_points,
+            "fvr" => n_corners,
+            "uni" => n_faces,
+            _ => 1, // constant
+        };
+        let n_values: usize = self.values.len() / n_components;
+        if self.indices.is_empty() {
+            n_values >= n_elements
+        } else {
+            self.indices.len() >= n_elements && self.indices.iter().all(|i| *i < n_values)
+        }
+    }
+}
+
+/// Composes the operations of an **.xform** compound (Alembic stores
+/// matrices with the translation in the last row). Also returns if
+/// the parent's transform is inherited.
+fn read_xform(archive: &AbcArchive, object: &AbcObject, time: f64) -> (Transform, bool) {
+    let properties: Vec<AbcProperty> = archive.schema_properties(object, ".xform");
+    let mut transform: Transform = Transform::default();
+    let inherits: bool = match properties.iter().find(|p| p.name == ".inherits") {
+        Some(property) => archive
+            .read_floats(property, time)
+            .first()
+            .map(|value| *value != 0.0 as Float)
+            .unwrap_or(true),
+        None => true,
+    };
+    let ops: Vec<usize> = properties
+        .iter()
+        .find(|p| p.name == ".ops")
+        .map(|p| archive.read_indices(p, 0.0))
+        .unwrap_or_default();
+    let vals: Vec<f64> = properties
+        .iter()
+        .find(|p| p.name == ".vals")
+        .map(|p| archive.read_f64s(p, time))
+        .unwrap_or_default();
+    let mut pos: usize = 0;
+    for op in ops.iter() {
+        // the operation type is stored in the upper four bits
+        let op_type: usize = op >> 4;
+        let n_values: usize = match op_type {
+            0 | 1 => 3, // scale, translate
+            2 => 4,     // rotate (axis and angle)
+            3 => 16,    // matrix
+            _ => 1,     // rotate around x, y or z
+        };
+        if pos + n_values > vals.len() {
+            println!(
+                "WARNING: {}: transform has not enough values",
+                object.full_name
+            );
+            break;
+        }
+        let v: Vec<Float> = vals[pos..pos + n_values]
+            .iter()
+            .map(|value| *value as Float)
+            .collect();
+        pos += n_values;
+        let op_transform: Transform = match op_type {
+            0 => Transform::scale(v[0], v[1], v[2]),
+            1 => Transform::translate(&Vector3f {
+                x: v[0],
+                y: v[1],
+                z: v[2],
+            }),
+            2 => Transform::rotate(
+                v[3],
+                &Vector3f {
+                    x: v[0],
+                    y: v[1],
+                    z: v[2],
+                },
+            ),
+            3 => Transform::new(
+                v[0], v[4], v[8], v[12], v[1], v[5], v[9], v[13], v[2], v[6], v[10], v[14], v[3],
+                v[7], v[11], v[15],
+            ),
+            4 => Transform::rotate_x(v[0]),
+            5 => Transform::rotate_y(v[0]),
+            _ => Transform::rotate_z(v[0]),
+        };
+        transform = transform * op_transform;
+    }
+    (transform, inherits)
+}
+
+/// Triangulates a **PolyMesh** (or **SubD**) object in world space.
+/// Alembic stores polygons in clockwise order, so the winding is
+/// reversed.
+fn make_mesh_shapes(
+    archive: &AbcArchive,
+    object: &AbcObject,
+    obj_to_world: &Transform,
+    time: f64,
+) -> Vec<Arc<Shape>> {
+    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+    let properties: Vec<AbcProperty> = archive.schema_properties(object, ".geom");
+    let read = |name: &str| -> Vec<Float> {
+        properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| archive.read_floats(p, time))
+            .unwrap_or_default()
+    };
+    let points: Vec<Float> = read("P");
+    let counts: Vec<usize> = read(".faceCounts").iter().map(|c| *c as usize).collect();
+    let indices: Vec<usize> = read(".faceIndices").iter().map(|i| *i as usize).collect();
+    let p_ws: Vec<Point3f> = points
+        .chunks_exact(3)
+        .map(|p| {
+            obj_to_world.transform_point(&Point3f {
+                x: p[0],
+                y: p[1],
+                z: p[2],
+            })
+        })
+        .collect();
+    let n_points: usize = p_ws.len();
+    let n_faces: usize = counts.len();
+    let n_corners: usize = indices.len();
+    if n_points == 0 || n_corners == 0 {
+        // e.g. an empty frame of a fluid simulation
+        return shapes;
+    }
+    if counts.iter().sum::<usize>() != n_corners || indices.iter().any(|i| *i >= n_points) {
+        println!(
+            "WARNING: {}: face counts and indices do not match, mesh ignored",
+            object.full_name
+        );
+        return shapes;
+    }
+    // normals (if any) and texture coordinates
+    let mut normals: Option<GeomParam> = GeomParam::read(archive, &properties, "N", time);
+    let mut uv: Option<GeomParam> = GeomParam::read(archive, &properties, "uv", time);
+    if let Some(ref param) = normals {
+        if !param.is_valid(3, n_points, n_faces, n_corners) {
+            println!("WARNING: {}: invalid normals ignored", object.full_name);
+            normals = None;
+        }
+    }
+    if let Some(ref param) = uv {
+        if !param.is_valid(2, n_points, n_faces, n_corners) {
+            println!("WARNING: {}: invalid uvs ignored", object.full_name);
+            uv = None;
+        }
+    }
+    let unweld: bool = normals
+        .iter()
+        .chain(uv.iter())
+        .any(|param| !param.is_per_point());
+    let normal = |param: &GeomParam, corner: usize, point: usize, face: usize| -> Normal3f {
+        let i: usize = 3 * param.lookup(corner, point, face);
+        obj_to_world.transform_normal(&Normal3f {
+            x: param.values[i],
+            y: param.values[i + 1],
+            z: param.values[i + 2],
+        })
+    };
+    let texture_coordinates = |param: &GeomParam, corner: usize, point: usize, face: usize| {
+        let i: usize = 2 * param.lookup(corner, point, face);
+        Point2f {
+            x: param.values[i],
+            y: param.values[i + 1],
+        }
+    };
+    let mut p_mesh: Vec<Point3f> = Vec::new();
+    let mut n_ws: Vec<Normal3f> = Vec::new();
+    let mut uvs: Vec<Point2f> = Vec::new();
+    let mut corners: Vec<u32> = Vec::new();
+    if unweld {
+        // one mesh vertex per polygon corner
+        let mut corner: usize = 0;
+        for (face, count) in counts.iter().enumerate() {
+            for _k in 0..*count {
+                let point: usize = indices[corner];
+                p_mesh.push(p_ws[point]);
+                if let Some(ref param) = normals {
+                    n_ws.push(normal(param, corner, point, face));
+                }
+                if let Some(ref param) = uv {
+                    uvs.push(texture_coordinates(param, corner, point, face));
+                }
+                corners.push(corner as u32);
+                corner += 1;
+            }
+        }
+    } else {
+        if let Some(ref param) = normals {
+            for point in 0..n_points {
+                n_ws.push(normal(param, 0, point, 0));
+            }
+        }
+        if let Some(ref param) = uv {
+            for point in 0..n_points {
+                uvs.push(texture_coordinates(param, 0, point, 0));
+            }
+        }
+        p_mesh = p_ws;
+        corners = indices.iter().map(|i| *i as u32).collect();
+    }
+    // convert polygons to triangles (fan triangulation)
+    let mut vi_tri: Vec<u32> = Vec::new();
+    let mut first: usize = 0;
+    for count in counts.iter() {
+        for k in 1..count.saturating_sub(1) {
+            vi_tri.push(corners[first]);
+            vi_tri.push(corners[first + k + 1]);
+            vi_tri.push(corners[first + k]);
+        }
+        first += count;
+    }
+    let n_triangles: usize = vi_tri.len() / 3;
+    let s_ws: Vec<Vector3f> = Vec::new();
+    let n_vertices: usize = p_mesh.len();
+    let world_to_obj: Transform = Transform::inverse(obj_to_world);
+    let mesh = Arc::new(TriangleMesh::new(
+        *obj_to_world,
+        world_to_obj,
+        false, // reverse_orientation,
+        n_triangles.try_into().unwrap(),
+        vi_tri,
+        n_vertices as u32,
+        p_mesh, // in world space
+        s_ws,   // in world space
+        n_ws,   // in world space
+        uvs,
+        None,
+        None,
+    ));
+    for id in 0..mesh.n_triangles {
+        let triangle = Arc::new(Shape::Trngl(Triangle::new(
+            mesh.object_to_world,
+            mesh.world_to_object,
+            mesh.reverse_orientation,
+            mesh.clone(),
+            id,
+        )));
+        shapes.push(triangle);
+    }
+    shapes
+}
+
+/// Converts the control points of one curve segment (four for cubic
+/// bases, two for linear curves) to Bézier control points.
+fn to_bezier(p: &[Point3f], basis: u8) -> [Point3f; 4] {
+    if p.len() == 2 {
+        return [
+            p[0],
+            pnt3_lerp(1.0 / 3.0, &p[0], &p[1]),
+            pnt3_lerp(2.0 / 3.0, &p[0], &p[1]),
+            p[1],
+        ];
+    }
+    match basis {
+        // B-spline
+        2 => [
+            (p[0] + p[1] * 4.0 as Float + p[2]) / 6.0 as Float,
+            (p[1] * 2.0 as Float + p[2]) / 3.0 as Float,
+            (p[1] + p[2] * 2.0 as Float) / 3.0 as Float,
+            (p[1] + p[2] * 4.0 as Float + p[3]) / 6.0 as Float,
+        ],
+        // Catmull-Rom
+        3 => [
+            p[1],
+            p[1] + (p[2] - p[0]) / 6.0 as Float,
+            p[2] - (p[3] - p[1]) / 6.0 as Float,
+            p[2],
+        ],
+        // Bézier
+        _ => [p[0], p[1], p[2], p[3]],
+    }
+}
+
+/// Creates cylindrical curve segments for a **Curves** object. Cubic
+/// curves with a B-spline or Catmull-Rom basis are converted to
+/// Bézier segments, other bases are drawn as linear curves.
+fn make_curve_shapes(
+    archive: &AbcArchive,
+    object: &AbcObject,
+    obj_to_world: &Transform,
+    time: f64,
+) -> Vec<Arc<Shape>> {
+    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+    let properties: Vec<AbcProperty> = archive.schema_properties(object, ".geom");
+    let read = |name: &str| -> Vec<Float> {
+        properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| archive.read_floats(p, time))
+            .unwrap_or_default()
+    };
+    let points: Vec<Point3f> = read("P")
+        .chunks_exact(3)
+        .map(|p| Point3f {
+            x: p[0],
+            y: p[1],
+            z: p[2],
+        })
+        .collect();
+    let n_vertices: Vec<usize> = read("nVertices").iter().map(|n| *n as usize).collect();
+    // type (cubic, linear or variable order), wrap and basis
+    let basis_and_type: Vec<Float> = read("curveBasisAndType");
+    let mut cubic: bool = basis_and_type.first() == Some(&0.0);
+    let basis: u8 = basis_and_type.get(2).cloned().unwrap_or(0.0) as u8;
+    if basis_and_type.get(1) == Some(&1.0) {
+        println!(
+            "WARNING: {}: periodic curves are drawn open",
+            object.full_name
+        );
+    }
+    if cubic && !(basis == 1 || basis == 2 || basis == 3) {
+        println!(
+            "WARNING: {}: curve basis {} not supported, drawing linear curves",
+            object.full_name, basis
+        );
+        cubic = false;
+    }
+    let widths: Vec<Float> = match GeomParam::read(archive, &properties, "width", time) {
+        Some(param) if param.indices.is_empty() => param.values,
+        Some(param) => param
+            .indices
+            .iter()
+            .map(|i| param.values.get(*i).cloned().unwrap_or(0.0))
+            .collect(),
+        None => vec![0.1 as Float],
+    };
+    let width = |i: usize| -> Float {
+        if widths.len() == points.len() {
+            widths[i]
+        } else {
+            widths.first().cloned().unwrap_or(0.1 as Float)
+        }
+    };
+    if n_vertices.iter().sum::<usize>() != points.len() {
+        println!(
+            "WARNING: {}: nVertices and P do not match, curves ignored",
+            object.full_name
+        );
+        return shapes;
+    }
+    let world_to_obj: Transform = Transform::inverse(obj_to_world);
+    let mut first: usize = 0;
+    for n in n_vertices.iter() {
+        let (segment_size, step): (usize, usize) = if !cubic {
+            (2, 1)
+        } else if basis == 1 {
+            (4, 3)
+        } else {
+            (4, 1)
+        };
+        let mut start: usize = first;
+        while start + segment_size <= first + n {
+            let cp: [Point3f; 4] = to_bezier(&points[start..start + segment_size], basis);
+            // the widths at the ends of the (Bézier) segment
+            let (w0, w1): (Float, Float) = if segment_size == 2 || basis == 1 {
+                (width(start), width(start + segment_size - 1))
+            } else {
+                (width(start + 1), width(start + 2))
+            };
+            shapes.extend(Curve::create(
+                *obj_to_world,
+                world_to_obj,
+                false, // reverse_orientation
+                &cp,
+                w0,
+                w1,
+                CurveType::Cylinder,
+                None,
+                3, // split_depth
+            ));
+            start += step;
+        }
+        first += n;
+    }
+    shapes
+}
+
+/// The parameters of an Alembic camera (the **.core** property,
+/// apertures in cm and focal length in mm).
+struct AbcCamera {
+    cam_to_world: Transform,
+    focal_length: Float,
+    horizontal_aperture: Float,
+    vertical_aperture: Float,
+}
+
+/// Everything needed to render one frame.
+#[derive(Default)]
+struct Frame {
+    primitives: Vec<Arc<Primitive>>,
+    cameras: Vec<AbcCamera>,
+    bounds: Option<Bounds3f>,
+}
+
+fn add_shapes(frame: &mut Frame, shapes: Vec<Arc<Shape>>, material: &Arc<Material>) {
+    let mi: MediumInterface = MediumInterface::default();
+    for shape in shapes {
+        let bound: Bounds3f = shape.world_bound();
+        frame.bounds = Some(match frame.bounds {
+            Some(bounds) => bnd3_union_bnd3(&bounds, &bound),
+            None => bound,
+        });
+        frame
+            .primitives
+            .push(Arc::new(Primitive::Geometric(Box::new(
+                GeometricPrimitive::new(
+                    shape,
+                    Some(material.clone()),
+                    None,
+                    Some(Arc::new(mi.clone())),
+                ),
+            ))));
+    }
+}
+
+/// Walks the object hierarchy, accumulating transforms.
+fn gather(
+    archive: &AbcArchive,
+    object: &AbcObject,
+    parent_to_world: &Transform,
+    time: f64,
+    materials: &(Arc<Material>, Arc<Material>),
+    frame: &mut Frame,
+) {
+    // hidden objects (and their children)
+    let properties: Vec<AbcProperty> = archive.object_properties(object);
+    if let Some(visible) = properties.iter().find(|p| p.name == "visible") {
+        if archive.read_floats(visible, time).first() == Some(&0.0) {
+            return;
+        }
+    }
+    let mut obj_to_world: Transform = *parent_to_world;
+    let schema: String = object.schema().to_string();
+    if schema.starts_with("AbcGeom_Xform") {
+        let (local, inherits): (Transform, bool) = read_xform(archive, object, time);
+        obj_to_world = if inherits {
+            *parent_to_world * local
+        } else {
+            local
+        };
+    } else if schema.starts_with("AbcGeom_PolyMesh") || schema.starts_with("AbcGeom_SubD") {
+        let shapes: Vec<Arc<Shape>> = make_mesh_shapes(archive, object, &obj_to_world, time);
+        add_shapes(frame, shapes, &materials.0);
+    } else if schema.starts_with("AbcGeom_Curve") {
+        let shapes: Vec<Arc<Shape>> = make_curve_shapes(archive, object, &obj_to_world, time);
+        add_shapes(frame, shapes, &materials.1);
+    } else if schema.starts_with("AbcGeom_Camera") {
+        let core: Vec<f64> = archive
+            .schema_properties(object, ".geom")
+            .iter()
+            .find(|p| p.name == ".core")
+            .map(|p| archive.read_f64s(p, time))
+            .unwrap_or_default();
+        if core.len() >= 4 {
+            frame.cameras.push(AbcCamera {
+                cam_to_world: obj_to_world,
+                focal_length: core[0] as Float,
+                horizontal_aperture: core[1] as Float,
+                vertical_aperture: core[3] as Float,
+            });
+        }
+    } else if schema != "" && !schema.starts_with("AbcGeom_FaceSet") {
+        println!(
+            "WARNING: {}: schema {:?} not supported",
+            object.full_name, schema
+        );
+    }
+    for child in archive.object_children(object) {
+        gather(archive, &child, &obj_to_world, time, materials, frame);
+    }
+}
+
+pub fn make_perspective_camera(
+    filter_width: Float,
+    xres: i32,
+    yres: i32,
+    fov: Float,
+    animated_cam_to_world: AnimatedTransform,
+) -> Option<Arc<Camera>> {
+    let mut some_camera: Option<Arc<Camera>> = None;
+    let mut filter_params: ParamSet = ParamSet::default();
+    filter_params.add_float(String::from("xwidth"), filter_width);
+    filter_params.add_float(String::from("ywidth"), filter_width);
+    let some_filter = make_filter(&String::from("gaussian"), &filter_params).ok();
+    if let Some(filter) = some_filter {
+        let film_name: String = String::from("image");
+        let mut film_params: ParamSet = ParamSet::default();
+        film_params.add_int(String::from("xresolution"), xres);
+        film_params.add_int(String::from("yresolution"), yres);
+        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter).ok();
+        if let Some(film) = some_film {
+            let camera_name: String = String::from("perspective");
+            let mut camera_params: ParamSet = ParamSet::default();
+            camera_params.add_float(String::from("fov"), fov);
+            some_camera = make_camera(
+                &camera_name,
+                &camera_params,
+                animated_cam_to_world,
+                film,
+                &MediumInterface::default(),
+            )
+            .ok();
+        }
+    }
+    some_camera
+}
+
+fn make_path_integrator(
+    filter_width: Float,
+    xres: i32,
+    yres: i32,
+    fov: Float,
+    animated_cam_to_world: AnimatedTransform,
+    maxdepth: i32,
+    pixelsamples: i32,
+) -> Option<Box<Integrator>> {
+    let some_integrator: Option<Box<Integrator>>;
+    let some_camera: Option<Arc<Camera>> =
+        make_perspective_camera(filter_width, xres, yres, fov, animated_cam_to_world);
+    if let Some(camera) = some_camera {
+        let sampler_name: String = String::from("sobol");
+        let mut sampler_params: ParamSet = ParamSet::default();
+        sampler_params.add_int(String::from("pixelsamples"), pixelsamples);
+        let some_sampler: Option<Box<Sampler>> =
+            make_sampler(&sampler_name, &sampler_params, camera.get_film()).ok();
+        if let Some(sampler) = some_sampler {
+            // CreatePathIntegrator
+            let integrator_params: ParamSet = ParamSet::default();
+            let max_depth: i32 = integrator_params.find_one_int("maxdepth", maxdepth);
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            let rr_threshold: Float = integrator_params.find_one_float("rrthreshold", 1.0 as Float);
+            let light_strategy: String =
+                integrator_params.find_one_string("lightsamplestrategy", String::from("spatial"));
+            let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::Path(
+                PathIntegrator::new(
+                    max_depth as u32,
+                    camera,
+                    sampler,
+                    pixel_bounds,
+                    rr_threshold,
+                    light_strategy,
+                ),
+            )));
+            some_integrator = Some(integrator);
+        } else {
+            panic!("Unable to create sampler.");
+        }
+    } else {
+        panic!("Unable to create camera.");
+    }
+    some_integrator
+}
+
+fn make_scene(primitives: &Vec<Arc<Primitive>>, lights: Vec<Arc<Light>>) -> Scene {
+    let accelerator_name: String = String::from("bvh");
+    let some_accelerator =
+        make_accelerator(&accelerator_name, &primitives, &ParamSet::default()).ok();
+    if let Some(accelerator) = some_accelerator {
+        return Scene::new(accelerator, lights);
+    } else {
+        panic!("Unable to create accelerator.");
+    }
+}
+
+fn make_default_materials() -> (Arc<Material>, Arc<Material>) {
+    let mut mp: ParamSet = ParamSet::default();
+    mp.add_rgb_spectrum(String::from("color"), Spectrum::new(0.5 as Float));
+    let mut tp: TextureParams = TextureParams::new(
+        ParamSet::default(),
+        mp,
+        Arc::new(HashMap::new()),
+        Arc::new(HashMap::new()),
+    );
+    let mesh_material: Arc<Material> = DisneyMaterial::create(&mut tp);
+    let mut tp: TextureParams = TextureParams::new(
+        ParamSet::default(),
+        ParamSet::default(),
+        Arc::new(HashMap::new()),
+        Arc::new(HashMap::new()),
+    );
+    let curve_material: Arc<Material> = HairMaterial::create(&mut tp);
+    (mesh_material, curve_material)
+}
+
+fn main() -> std::io::Result<()> {
+    // handle command line options
+    let args = Cli::from_args();
+    let samples_per_pixel: u16 = args.samples;
+    let filter_width: Float = 2.0;
+    let xres: i32 = args.xres;
+    // input (.abc) file
+    println!("FILE = {:?}", args.path);
+    let f = File::open(&args.path)?;
+    let mut reader = BufReader::new(f);
+    let mut bytes: Vec<u8> = Vec::new();
+    let num_bytes = reader.read_to_end(&mut bytes);
+    if num_bytes.is_ok() {
+        let n_bytes = num_bytes.unwrap();
+        println!("{} bytes read", n_bytes);
+    }
+    let archive: AbcArchive = AbcArchive::new(bytes);
+    let materials: (Arc<Material>, Arc<Material>) = make_default_materials();
+    let stem: String = args
+        .path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("frame")
+        .to_string();
+    let end_frame: i32 = args.end_frame.unwrap_or(args.frame);
+    for frame_number in args.frame..=end_frame {
+        let time: f64 = frame_number as f64 / args.fps;
+        println!("frame {} (time {:.4}s)", frame_number, time);
+        let mut frame: Frame = Frame::default();
+        gather(
+            &archive,
+            &archive.top_object(),
+            &Transform::default(),
+            time,
+            &materials,
+            &mut frame,
+        );
+        // camera (or one looking at the whole scene along -z)
+        let (cam_to_world, fov, yres): (Transform, Float, i32) = if let Some(camera) =
+            frame.cameras.first()
+        {
+            let yres: i32 = (xres as Float * camera.vertical_aperture / camera.horizontal_aperture)
+                .round() as i32;
+            // pbrt's field of view is the one of the shorter image axis
+            let aperture: Float =
+                10.0 as Float * camera.horizontal_aperture.min(camera.vertical_aperture);
+            let fov: Float = (2.0 as Float
+                * (aperture / (2.0 as Float * camera.focal_length)).atan())
+            .to_degrees();
+            (camera.cam_to_world, fov, yres)
+        } else {
+            println!("WARNING: no camera found, framing the scene");
+            let bounds: Bounds3f = frame.bounds.unwrap_or_default();
+            let center: Point3f = (bounds.p_min + bounds.p_max) * 0.5 as Float;
+            let radius: Float = (bounds.p_max - bounds.p_min).length() * 0.5 as Float;
+            let distance: Float = radius / (20.0 as Float).to_radians().sin();
+            let eye: Vector3f = Vector3f {
+                x: center.x,
+                y: center.y,
+                z: center.z + distance.max(1.0 as Float),
+            };
+            (Transform::translate(&eye), 40.0 as Float, xres * 3 / 4)
+        };
+        // Alembic cameras look along the negative z-axis
+        let cam_to_world: Transform =
+            cam_to_world * Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+        let animated_cam_to_world: AnimatedTransform =
+            AnimatedTransform::new(&cam_to_world, 0.0, &cam_to_world, 1.0);
+        // Alembic archives have no lights, use a dome (y-axis as pole)
+        let dome: Arc<Light> = Arc::new(Light::InfiniteArea(Box::new(InfiniteAreaLight::new(
+            &Transform::rotate_x(-90.0 as Float),
+            &Spectrum::new(1.0 as Float),
+            1,
+            args.envmap.clone(),
+        ))));
+        println!("samples_per_pixel = {:?}", samples_per_pixel);
+        println!("number of primitives = {:?}", frame.primitives.len());
+        let some_integrator: Option<Box<Integrator>> = make_path_integrator(
+            filter_width,
+            xres,
+            yres,
+            fov,
+            animated_cam_to_world,
+            args.max_depth,
+            samples_per_pixel as i32,
+        );
+        if let Some(mut integrator) = some_integrator {
+            let scene = make_scene(&frame.primitives, vec![dome]);
+            let num_threads: u8 = num_cpus::get() as u8;
+            integrator.render(&scene, num_threads);
+        } else {
+            panic!("Unable to create integrator.");
+        }
+        // the film always writes pbrt.png
+        if end_frame > args.frame {
+            let filename: PathBuf = PathBuf::from(format!("{}_{:04}.png", stem, frame_number));
+            std::fs::rename("pbrt.png", &filename)?;
+            println!("Renamed image to {:?}", filename);
+        }
+    }
+    Ok(())
+}
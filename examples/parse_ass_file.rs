@@ -6,6 +6,7 @@ use pest::Parser;
 // command line options
 use structopt::StructOpt;
 // pbrt
+use pbrt::accelerators::bvh::{BVHAccel, SplitMethod};
 use pbrt::core::api::{make_accelerator, make_camera, make_film, make_filter, make_sampler};
 use pbrt::core::camera::Camera;
 use pbrt::core::film::Film;
@@ -13,35 +14,38 @@ use pbrt::core::geometry::{Bounds2i, Normal3f, Point2f, Point3f, Vector3f};
 use pbrt::core::integrator::{Integrator, SamplerIntegrator};
 use pbrt::core::light::Light;
 use pbrt::core::material::Material;
-use pbrt::core::medium::MediumInterface;
-use pbrt::core::paramset::ParamSet;
+use pbrt::core::medium::{Medium, MediumInterface};
+use pbrt::core::mipmap::ImageWrap;
+use pbrt::core::paramset::{ParamSet, TextureParams};
+use pbrt::core::pbrt::consts::PI;
 use pbrt::core::pbrt::{Float, Spectrum};
-use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::primitive::{GeometricPrimitive, Primitive, TransformedPrimitive};
 use pbrt::core::sampler::Sampler;
 use pbrt::core::scene::Scene;
 use pbrt::core::shape::Shape;
-use pbrt::core::texture::Texture;
+use pbrt::core::texture::{Texture, TextureMapping2D, UVMapping2D};
 use pbrt::core::transform::{AnimatedTransform, Transform};
-use pbrt::integrators::path::PathIntegrator;
+use pbrt::integrators::path::{DepthLimits, PathIntegrator};
+use pbrt::integrators::volpath::VolPathIntegrator;
 use pbrt::lights::diffuse::DiffuseAreaLight;
+use pbrt::lights::distant::DistantLight;
+use pbrt::lights::infinite::InfiniteAreaLight;
 use pbrt::lights::point::PointLight;
 use pbrt::lights::spot::SpotLight;
-use pbrt::materials::matte::MatteMaterial;
-use pbrt::materials::metal::MetalMaterial;
-use pbrt::materials::metal::{COPPER_K, COPPER_N, COPPER_SAMPLES, COPPER_WAVELENGTHS};
-use pbrt::materials::mirror::MirrorMaterial;
+use pbrt::materials::disney::DisneyMaterial;
+use pbrt::media::homogeneous::HomogeneousMedium;
 use pbrt::shapes::cylinder::Cylinder;
 use pbrt::shapes::disk::Disk;
 use pbrt::shapes::sphere::Sphere;
 use pbrt::shapes::triangle::{Triangle, TriangleMesh};
-use pbrt::textures::constant::ConstantTexture;
+use pbrt::textures::imagemap::ImageTexture;
+use pbrt::textures::imagemap::{convert_to_float, convert_to_spectrum};
 // std
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
+use std::io::{BufRead, BufReader};
 use std::iter::Peekable;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -103,6 +107,60 @@ fn strip_comments(input: &str) -> String {
     output
 }
 
+/// Reads an Arnold scene file node by node (without comments), so the
+/// whole file never has to be in memory.
+struct AssNodes<R: BufRead> {
+    reader: R,
+    line: String,
+    /// text of the node which is currently read
+    node: String,
+    bytes_read: usize,
+}
+
+impl<R: BufRead> AssNodes<R> {
+    fn new(reader: R) -> Self {
+        AssNodes {
+            reader,
+            line: String::new(),
+            node: String::new(),
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for AssNodes<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        // a node ends with the first closing brace
+        let mut end: Option<usize> = self.node.find('}');
+        while end.is_none() {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => {
+                    if !self.node.trim().is_empty() {
+                        panic!("missing '}}' after {:?}", self.node.trim());
+                    }
+                    return None;
+                }
+                Ok(n_bytes) => self.bytes_read += n_bytes,
+                Err(err) => panic!("{}", err),
+            }
+            let stripped: &str = match self.line.find('#') {
+                Some(comment) => &self.line[..comment],
+                None => &self.line,
+            };
+            if let Some(pos) = stripped.find('}') {
+                end = Some(self.node.len() + pos);
+            }
+            self.node.push_str(stripped);
+            self.node.push('\n');
+        }
+        let rest: String = self.node.split_off(end.unwrap() + 1);
+        Some(std::mem::replace(&mut self.node, rest))
+    }
+}
+
 fn get_shader_names(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<String> {
     let mut shader_names: Vec<String> = Vec::new();
     let mut is_int: bool = false;
@@ -150,29 +208,517 @@ fn get_shader_names(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<S
     shader_names
 }
 
+fn get_color(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Spectrum {
+    let mut color_r: Float = 0.0;
+    let mut color_g: Float = 0.0;
+    let mut color_b: Float = 0.0;
+    if let Some(color_str) = iter.next() {
+        color_r = Float::from_str(color_str).unwrap();
+    }
+    if let Some(color_str) = iter.next() {
+        color_g = Float::from_str(color_str).unwrap();
+    }
+    if let Some(color_str) = iter.next() {
+        color_b = Float::from_str(color_str).unwrap();
+    }
+    Spectrum::rgb(color_r, color_g, color_b)
+}
+
+fn get_vectors(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<Point3f> {
+    // <num_elements> <num_motionblur_keys> VECTOR <x1> <y1> <z1> ...
+    let mut points: Vec<Point3f> = Vec::new();
+    if let Some(num_elements_str) = iter.next() {
+        let num_elements: u32 = u32::from_str(num_elements_str).unwrap();
+        if let Some(num_motionblur_keys_str) = iter.next() {
+            let num_motionblur_keys: u32 = u32::from_str(num_motionblur_keys_str).unwrap();
+            if let Some(data_type_str) = iter.next() {
+                if data_type_str != "VECTOR" {
+                    panic!("ERROR: VECTOR expected ...");
+                }
+            }
+            let expected: u32 = num_elements * num_motionblur_keys;
+            for _i in 0..expected {
+                let mut xyz: [Float; 3] = [0.0 as Float; 3];
+                for coord in xyz.iter_mut() {
+                    if let Some(elem_str) = iter.next() {
+                        *coord = Float::from_str(elem_str).unwrap();
+                    }
+                }
+                points.push(Point3f {
+                    x: xyz[0],
+                    y: xyz[1],
+                    z: xyz[2],
+                });
+            }
+            // ignore motion blur keys (for now)
+            points.truncate(num_elements as usize);
+        }
+    }
+    points
+}
+
+fn get_matrices(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<Transform> {
+    // either 16 elements or
+    // <num_elements> <num_motionblur_keys> MATRIX <16 elements per key> ...
+    let mut num_keys: u32 = 1;
+    let mut look_ahead = iter.clone();
+    look_ahead.next();
+    look_ahead.next();
+    if look_ahead.next() == Some("MATRIX") {
+        let num_elements: u32 = u32::from_str(iter.next().unwrap()).unwrap();
+        let num_motionblur_keys: u32 = u32::from_str(iter.next().unwrap()).unwrap();
+        // skip MATRIX (we checked already)
+        iter.next();
+        num_keys = num_elements * num_motionblur_keys;
+    }
+    let mut matrices: Vec<Transform> = Vec::new();
+    for _key in 0..num_keys {
+        let mut m: [Float; 16] = [0.0 as Float; 16];
+        for elem in m.iter_mut() {
+            if let Some(elem_str) = iter.next() {
+                *elem = Float::from_str(elem_str).unwrap();
+            }
+        }
+        // Arnold stores the translation in the last row
+        matrices.push(Transform::new(
+            m[0], m[4], m[8], m[12], m[1], m[5], m[9], m[13], m[2], m[6], m[10], m[14], m[3], m[7],
+            m[11], m[15],
+        ));
+    }
+    matrices
+}
+
+/// Creates an animated transform from the first and last of (one or
+/// more) motion keys, spread over the time interval *start* to *end*.
+fn make_animated_transform(keys: &[Transform], start: Float, end: Float) -> AnimatedTransform {
+    let first: Transform = keys.first().cloned().unwrap_or_default();
+    let last: Transform = keys.last().cloned().unwrap_or_default();
+    AnimatedTransform::new(&first, start, &last, end)
+}
+
+/// Returns a single primitive (a BVH for more than one) which can be
+/// transformed as a whole.
+fn make_aggregate(prims: &[Arc<Primitive>]) -> Arc<Primitive> {
+    if prims.len() == 1 {
+        prims[0].clone()
+    } else {
+        Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
+            prims.to_vec(),
+            4,
+            SplitMethod::SAH,
+        ))))
+    }
+}
+
+/// An Arnold **ginstance** node: another instance of a shape node
+/// with its own (possibly animated) transform.
+struct GInstance {
+    node: String,
+    matrices: Vec<Transform>,
+    motion_start: Float,
+    motion_end: Float,
+    inherit_xform: bool,
+}
+
+fn get_vector2s(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<Point2f> {
+    // <num_elements> <num_motionblur_keys> VECTOR2 <x1> <y1> ...
+    let mut points: Vec<Point2f> = Vec::new();
+    if let Some(num_elements_str) = iter.next() {
+        let num_elements: u32 = u32::from_str(num_elements_str).unwrap();
+        if let Some(num_motionblur_keys_str) = iter.next() {
+            let num_motionblur_keys: u32 = u32::from_str(num_motionblur_keys_str).unwrap();
+            if let Some(data_type_str) = iter.next() {
+                if data_type_str != "VECTOR2" {
+                    panic!("ERROR: VECTOR2 expected ...");
+                }
+            }
+            let expected: u32 = num_elements * num_motionblur_keys;
+            for _i in 0..expected {
+                let mut xy: [Float; 2] = [0.0 as Float; 2];
+                for coord in xy.iter_mut() {
+                    if let Some(elem_str) = iter.next() {
+                        *coord = Float::from_str(elem_str).unwrap();
+                    }
+                }
+                points.push(Point2f { x: xy[0], y: xy[1] });
+            }
+            // ignore motion blur keys (for now)
+            points.truncate(num_elements as usize);
+        }
+    }
+    points
+}
+
+fn get_uints(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Vec<u32> {
+    // either <elem1> <elem2> ... or
+    // <num_elements> <num_motionblur_keys> UINT <elem1> <elem2> ...
+    let mut elems: Vec<u32> = Vec::new();
+    while let Some(check_for_int_str) = iter.peek() {
+        if let Ok(elem) = u32::from_str(check_for_int_str) {
+            elems.push(elem);
+            iter.next();
+        } else {
+            break;
+        }
+    }
+    let mut followed_by_type: bool = false;
+    if let Some(check_for_type_str) = iter.peek() {
+        if *check_for_type_str == "UINT" || *check_for_type_str == "BYTE" {
+            followed_by_type = true;
+        }
+    }
+    if followed_by_type && elems.len() == 2 {
+        // skip next (we checked already)
+        iter.next();
+        let expected: u32 = elems[0] * elems[1];
+        elems = Vec::new();
+        for _i in 0..expected {
+            if let Some(elem_str) = iter.next() {
+                elems.push(u32::from_str(elem_str).unwrap());
+            }
+        }
+    }
+    elems
+}
+
+fn default_quad_vertices() -> Vec<Point3f> {
+    vec![
+        Point3f {
+            x: -1.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: -1.0,
+            y: -1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 1.0,
+            y: -1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        },
+    ]
+}
+
+/// Creates one (one-sided) diffuse area light per shape and the
+/// primitives which make them visible to rays. Arnold lights have no
+/// surface shader, so the primitives have no material.
+fn make_area_lights(
+    shapes: &[Arc<Shape>],
+    light_to_world: &Transform,
+    l_emit: &Spectrum,
+    samples: i32,
+    lights: &mut Vec<Arc<Light>>,
+    light_primitives: &mut Vec<Arc<Primitive>>,
+) {
+    let mi: MediumInterface = MediumInterface::default();
+    for shape in shapes {
+        let area_light: Arc<Light> = Arc::new(Light::DiffuseArea(Box::new(DiffuseAreaLight::new(
+            light_to_world,
+            &mi,
+            l_emit,
+            samples,
+            shape.clone(),
+            false, // two_sided
+        ))));
+        lights.push(area_light.clone());
+        light_primitives.push(Arc::new(Primitive::Geometric(Box::new(
+            GeometricPrimitive::new(
+                shape.clone(),
+                None,
+                Some(area_light),
+                Some(Arc::new(mi.clone())),
+            ),
+        ))));
+    }
+}
+
+fn get_float(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Float {
+    let mut value: Float = 0.0;
+    if let Some(value_str) = iter.next() {
+        value = Float::from_str(value_str).unwrap();
+    }
+    value
+}
+
+fn get_bool(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> bool {
+    if let Some(value_str) = iter.next() {
+        value_str == "on" || value_str == "true" || value_str == "1"
+    } else {
+        false
+    }
+}
+
+/// Returns the name of the node a parameter is linked to (if the
+/// value is not a number).
+fn get_link(iter: &mut Peekable<std::str::SplitWhitespace<'_>>) -> Option<String> {
+    let mut is_linked: bool = false;
+    if let Some(check_for_float_str) = iter.peek() {
+        is_linked = Float::from_str(check_for_float_str).is_err();
+    }
+    if is_linked {
+        if let Some(link_str) = iter.next() {
+            // strip surrounding double quotes (if any)
+            return Some(link_str.trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+/// The parameters of an Arnold **standard_surface** shader (with
+/// Arnold's defaults) which can be mapped onto the Disney material,
+/// and the names of the nodes some of them are linked to.
+struct StandardSurface {
+    base: Float,
+    base_color: Spectrum,
+    metalness: Float,
+    specular_roughness: Float,
+    specular_ior: Float,
+    specular_anisotropy: Float,
+    transmission: Float,
+    coat: Float,
+    coat_roughness: Float,
+    sheen: Float,
+    thin_walled: bool,
+    links: HashMap<String, String>,
+}
+
+impl Default for StandardSurface {
+    fn default() -> Self {
+        StandardSurface {
+            base: 0.8,
+            base_color: Spectrum::new(1.0 as Float),
+            metalness: 0.0,
+            specular_roughness: 0.2,
+            specular_ior: 1.5,
+            specular_anisotropy: 0.0,
+            transmission: 0.0,
+            coat: 0.0,
+            coat_roughness: 0.1,
+            sheen: 0.0,
+            thin_walled: false,
+            links: HashMap::new(),
+        }
+    }
+}
+
+fn make_image_mapping() -> Box<TextureMapping2D> {
+    Box::new(TextureMapping2D::UV(UVMapping2D {
+        su: 1.0,
+        sv: 1.0,
+        du: 0.0,
+        dv: 0.0,
+    }))
+}
+
+/// An Arnold **image** node (with Arnold's defaults).
+struct ImageNode {
+    filename: String,
+    color_space: String,
+    swrap: String,
+    twrap: String,
+}
+
+impl Default for ImageNode {
+    fn default() -> Self {
+        ImageNode {
+            filename: String::from(""),
+            color_space: String::from("auto"),
+            swrap: String::from("periodic"),
+            twrap: String::from("periodic"),
+        }
+    }
+}
+
+impl ImageNode {
+    /// Do the texels need to be converted from sRGB to linear?
+    /// **auto** assumes that high dynamic range files are linear and
+    /// all others sRGB encoded.
+    fn gamma(&self) -> bool {
+        let color_space: String = self.color_space.to_lowercase();
+        if color_space == "auto" || color_space == "" {
+            let extension: String = Path::new(&self.filename)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("")
+                .to_lowercase();
+            !(extension == "hdr" || extension == "exr" || extension == "tx")
+        } else {
+            !(color_space.contains("linear") || color_space == "raw")
+        }
+    }
+    /// pbrt has only one wrap mode for both texture directions.
+    fn wrap_mode(&self) -> ImageWrap {
+        if self.twrap != self.swrap {
+            println!(
+                "WARNING: image {:?} uses swrap {:?} in both directions (twrap {:?})",
+                self.filename, self.swrap, self.twrap
+            );
+        }
+        match self.swrap.as_str() {
+            "black" => ImageWrap::Black,
+            "clamp" => ImageWrap::Clamp,
+            "periodic" => ImageWrap::Repeat,
+            _ => {
+                println!(
+                    "WARNING: swrap {:?} not supported, using \"periodic\"",
+                    self.swrap
+                );
+                ImageWrap::Repeat
+            }
+        }
+    }
+    fn make_spectrum_texture(&self, scale: Float) -> Arc<Texture<Spectrum>> {
+        Arc::new(Texture::Image(ImageTexture::new(
+            make_image_mapping(),
+            self.filename.clone(),
+            false, // do_trilinear
+            8.0,   // max_aniso
+            self.wrap_mode(),
+            scale,
+            self.gamma(),
+            convert_to_spectrum,
+        )))
+    }
+    fn make_float_texture(&self) -> Arc<Texture<Float>> {
+        Arc::new(Texture::Image(ImageTexture::new(
+            make_image_mapping(),
+            self.filename.clone(),
+            false, // do_trilinear
+            8.0,   // max_aniso
+            self.wrap_mode(),
+            1.0, // scale
+            self.gamma(),
+            convert_to_float,
+        )))
+    }
+}
+
+/// Maps a **standard_surface** onto the Disney material. Parameters
+/// linked to **image** nodes become image textures. The specular
+/// weight and color, and all other color tints, have no counterpart
+/// and are ignored.
+fn make_disney_material(
+    name: &str,
+    surface: &StandardSurface,
+    named_images: &HashMap<String, ImageNode>,
+) -> Arc<Material> {
+    let mut float_textures: HashMap<String, Arc<Texture<Float>>> = HashMap::new();
+    let mut spectrum_textures: HashMap<String, Arc<Texture<Spectrum>>> = HashMap::new();
+    let mut mp: ParamSet = ParamSet::default();
+    let linked_image = |parameter: &str| -> Option<&ImageNode> {
+        if let Some(image) = surface.links.get(parameter) {
+            if let Some(image_node) = named_images.get(image) {
+                if Path::new(&image_node.filename).exists() {
+                    return Some(image_node);
+                }
+                println!(
+                    "WARNING: {}.{}: image file {:?} not found",
+                    name, parameter, image_node.filename
+                );
+            } else {
+                println!(
+                    "WARNING: {}.{} linked to {:?}, which is not an image node",
+                    name, parameter, image
+                );
+            }
+        }
+        None
+    };
+    // base color (scaled by base weight)
+    if let Some(image_node) = linked_image("base_color") {
+        let texture_name: String = format!("{}.base_color", name);
+        spectrum_textures.insert(
+            texture_name.clone(),
+            image_node.make_spectrum_texture(surface.base),
+        );
+        mp.add_texture(String::from("color"), texture_name);
+    } else {
+        mp.add_rgb_spectrum(String::from("color"), surface.base_color * surface.base);
+    }
+    // scalar parameters
+    let float_params: [(&str, &str, Float); 7] = [
+        ("metalness", "metallic", surface.metalness),
+        (
+            "specular_roughness",
+            "roughness",
+            surface.specular_roughness,
+        ),
+        ("specular_IOR", "eta", surface.specular_ior),
+        (
+            "specular_anisotropy",
+            "anisotropic",
+            surface.specular_anisotropy,
+        ),
+        ("transmission", "spectrans", surface.transmission),
+        ("coat", "clearcoat", surface.coat),
+        ("sheen", "sheen", surface.sheen),
+    ];
+    for (arnold_name, pbrt_name, value) in float_params.iter() {
+        if let Some(image_node) = linked_image(arnold_name) {
+            let texture_name: String = format!("{}.{}", name, arnold_name);
+            float_textures.insert(texture_name.clone(), image_node.make_float_texture());
+            mp.add_texture(pbrt_name.to_string(), texture_name);
+        } else {
+            mp.add_float(pbrt_name.to_string(), *value);
+        }
+    }
+    mp.add_float(
+        String::from("clearcoatgloss"),
+        1.0 as Float - surface.coat_roughness,
+    );
+    mp.add_float(String::from("sheentint"), 0.0 as Float);
+    mp.add_bool(String::from("thin"), surface.thin_walled);
+    let mut tp: TextureParams = TextureParams::new(
+        ParamSet::default(),
+        mp,
+        Arc::new(float_textures),
+        Arc::new(spectrum_textures),
+    );
+    DisneyMaterial::create(&mut tp)
+}
+
 pub fn make_perspective_camera(
     filter_width: Float,
     xres: i32,
     yres: i32,
     fov: Float,
     animated_cam_to_world: AnimatedTransform,
+    shutter_open: Float,
+    shutter_close: Float,
+    camera_medium: Option<Arc<Medium>>,
 ) -> Option<Arc<Camera>> {
     let mut some_camera: Option<Arc<Camera>> = None;
     let mut filter_params: ParamSet = ParamSet::default();
     filter_params.add_float(String::from("xwidth"), filter_width);
     filter_params.add_float(String::from("ywidth"), filter_width);
-    let some_filter = make_filter(&String::from("gaussian"), &filter_params);
+    let some_filter = make_filter(&String::from("gaussian"), &filter_params).ok();
     if let Some(filter) = some_filter {
         let film_name: String = String::from("image");
         let mut film_params: ParamSet = ParamSet::default();
         film_params.add_int(String::from("xresolution"), xres);
         film_params.add_int(String::from("yresolution"), yres);
-        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter);
+        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter).ok();
         if let Some(film) = some_film {
             let camera_name: String = String::from("perspective");
             let mut camera_params: ParamSet = ParamSet::default();
             camera_params.add_float(String::from("fov"), fov);
-            some_camera = make_camera(&camera_name, &camera_params, animated_cam_to_world, film);
+            camera_params.add_float(String::from("shutteropen"), shutter_open);
+            camera_params.add_float(String::from("shutterclose"), shutter_close);
+            some_camera = make_camera(
+                &camera_name,
+                &camera_params,
+                animated_cam_to_world,
+                film,
+                &MediumInterface::new(None, camera_medium),
+            )
+            .ok();
         }
     }
     some_camera
@@ -184,18 +730,31 @@ fn make_path_integrator(
     yres: i32,
     fov: Float,
     animated_cam_to_world: AnimatedTransform,
+    shutter_open: Float,
+    shutter_close: Float,
     maxdepth: i32,
+    depth_limits: DepthLimits,
     pixelsamples: i32,
+    camera_medium: Option<Arc<Medium>>,
+    have_scattering_media: bool,
 ) -> Option<Box<Integrator>> {
     let some_integrator: Option<Box<Integrator>>;
-    let some_camera: Option<Arc<Camera>> =
-        make_perspective_camera(filter_width, xres, yres, fov, animated_cam_to_world);
+    let some_camera: Option<Arc<Camera>> = make_perspective_camera(
+        filter_width,
+        xres,
+        yres,
+        fov,
+        animated_cam_to_world,
+        shutter_open,
+        shutter_close,
+        camera_medium,
+    );
     if let Some(camera) = some_camera {
         let sampler_name: String = String::from("sobol");
         let mut sampler_params: ParamSet = ParamSet::default();
         sampler_params.add_int(String::from("pixelsamples"), pixelsamples);
         let some_sampler: Option<Box<Sampler>> =
-            make_sampler(&sampler_name, &sampler_params, camera.get_film());
+            make_sampler(&sampler_name, &sampler_params, camera.get_film()).ok();
         if let Some(sampler) = some_sampler {
             // CreatePathIntegrator
             let integrator_params: ParamSet = ParamSet::default();
@@ -204,16 +763,32 @@ fn make_path_integrator(
             let rr_threshold: Float = integrator_params.find_one_float("rrthreshold", 1.0 as Float);
             let light_strategy: String =
                 integrator_params.find_one_string("lightsamplestrategy", String::from("spatial"));
-            let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::Path(
-                PathIntegrator::new(
+            let integrator = if have_scattering_media {
+                // the path tracer ignores participating media
+                Box::new(Integrator::Sampler(SamplerIntegrator::VolPath(
+                    VolPathIntegrator::new(
+                        max_depth as u32,
+                        camera,
+                        sampler,
+                        pixel_bounds,
+                        rr_threshold,
+                        light_strategy,
+                    ),
+                )))
+            } else {
+                let mut path_integrator: PathIntegrator = PathIntegrator::new(
                     max_depth as u32,
                     camera,
                     sampler,
                     pixel_bounds,
                     rr_threshold,
                     light_strategy,
-                ),
-            )));
+                );
+                path_integrator.depth_limits = depth_limits;
+                Box::new(Integrator::Sampler(SamplerIntegrator::Path(
+                    path_integrator,
+                )))
+            };
             some_integrator = Some(integrator);
         } else {
             panic!("Unable to create sampler.");
@@ -226,7 +801,8 @@ fn make_path_integrator(
 
 fn make_scene(primitives: &Vec<Arc<Primitive>>, lights: Vec<Arc<Light>>) -> Scene {
     let accelerator_name: String = String::from("bvh");
-    let some_accelerator = make_accelerator(&accelerator_name, &primitives, &ParamSet::default());
+    let some_accelerator =
+        make_accelerator(&accelerator_name, &primitives, &ParamSet::default()).ok();
     if let Some(accelerator) = some_accelerator {
         return Scene::new(accelerator, lights);
     } else {
@@ -245,58 +821,89 @@ fn main() -> std::io::Result<()> {
     let mut render_camera: String = String::from(""); // no default name
     let mut mesh: String = String::from(""); // no default name
     let mut fov: Float = 90.0; // read persp_camera.fov
-    let mut intensity: Float = 1.0; // read mesh_light.intensity
-    let mut cone_angle: Float = 30.0; // read spot_light.cone_angle
-    let cone_delta_angle: Float = 5.0; // TODO: read from .ass file?
+    let mut intensity: Float = 1.0; // read *_light.intensity
+    let mut exposure: Float = 0.0; // read *_light.exposure
+    let mut normalize: bool = true; // read *_light.normalize
+    let mut light_radius: Float = 0.0; // read [point_light, spot_light].radius
+    let mut cone_angle: Float = 65.0; // read spot_light.cone_angle
+    let mut penumbra_angle: Float = 0.0; // read spot_light.penumbra_angle
+    let mut quad_vertices: Vec<Point3f> = default_quad_vertices(); // read quad_light.vertices
     let mut radius: Float = 0.5; // read [cylinder, disk, sphere].radius
     let mut hole: Float = 0.0; // read disk.hole
     let mut color: Spectrum = Spectrum::new(1.0 as Float);
-    // read standard_surface.base_color
-    let mut base_color: Spectrum = Spectrum::new(0.5 as Float);
-    // read standard_surface.specular_color
-    let mut specular_color: Spectrum = Spectrum::new(1.0 as Float);
-    let mut specular_roughness: Float = 0.01; // read standard_surface.specular_roughness
-    let mut metalness: Float = 0.0; // read standard_surface.metalness
+    let mut surface: StandardSurface = StandardSurface::default(); // read standard_surface
+    let mut image_node: ImageNode = ImageNode::default(); // read image
+    let mut light_link: Option<String> = None; // read skydome_light.color
+    let mut linked_skydomes: Vec<(Transform, Float, i32, String)> = Vec::new();
+    let mut atmosphere: String = String::from(""); // read options.atmosphere
+    let mut density: Float = 1.0; // read [atmosphere_volume, standard_volume].density
     let mut animated_cam_to_world: AnimatedTransform = AnimatedTransform::default();
+    // read atmosphere_volume.rgb_density or standard_volume.scatter_color
+    let mut scatter_color: Spectrum = Spectrum::new(1.0 as Float);
+    // read atmosphere_volume.attenuation or standard_volume.absorption
+    let mut absorption: Float = 0.0;
+    // read atmosphere_volume.rgb_attenuation or standard_volume.absorption_color
+    let mut absorption_color: Spectrum = Spectrum::new(1.0 as Float);
+    // read atmosphere_volume.eccentricity or standard_volume.scatter_anisotropy
+    let mut anisotropy: Float = 0.0;
     let mut xres: i32 = 1280; // read options.xres
     let mut yres: i32 = 720; // read options.yres
     let mut max_depth: i32 = 5; // read options.GI_total_depth
+    let mut diffuse_depth: Option<i32> = None; // read options.GI_diffuse_depth
+    let mut glossy_depth: Option<i32> = None; // read options.GI_glossy_depth (or GI_specular_depth)
+    let mut specular_depth: Option<i32> = None; // read options.GI_specular_depth
+    let mut transmission_depth: Option<i32> = None; // read options.GI_transmission_depth
     let mut samples: i32 = 1; // read mesh_light.samples
-    let mut cur_transform: Transform = Transform::default();
-    let mut obj_to_world: Transform = Transform::default();
-    let mut world_to_obj: Transform = Transform::default();
+    let mut cur_transform: Transform;
+    let mut matrices: Vec<Transform>; // read matrix (one per motion key)
+    let mut motion_start: Float; // read motion_start
+    let mut motion_end: Float; // read motion_end
+    let mut shutter_start: Float = 0.0; // read persp_camera.shutter_start
+    let mut shutter_end: Float = 0.0; // read persp_camera.shutter_end
+    let mut ginstance_node: String = String::from(""); // read ginstance.node
+    let mut inherit_xform: bool = true; // read ginstance.inherit_xform
+    let mut ginstances: Vec<GInstance> = Vec::new();
+    let mut named_transforms: HashMap<String, Vec<Transform>> = HashMap::new();
+    let mut named_motions: HashMap<String, (Float, Float)> = HashMap::new();
+    let mut obj_to_world: Transform;
+    let mut world_to_obj: Transform;
     let mut nsides: Vec<u32> = Vec::new();
     let mut shidxs: Vec<u32> = Vec::new();
     let mut shader_names: Vec<String> = Vec::new();
     let mut p_ws: Vec<Point3f> = Vec::new();
     let mut p_ws_len: usize = 0;
     let mut vi: Vec<u32> = Vec::new();
+    let mut nlist: Vec<Normal3f> = Vec::new(); // read polymesh.nlist
+    let mut nidxs: Vec<u32> = Vec::new(); // read polymesh.nidxs
+    let mut uvlist: Vec<Point2f> = Vec::new(); // read polymesh.uvlist
+    let mut uvidxs: Vec<u32> = Vec::new(); // read polymesh.uvidxs
+    let mut smoothing: bool = false; // read polymesh.smoothing
     let mut primitives: Vec<Arc<Primitive>> = Vec::new();
     let mut lights: Vec<Arc<Light>> = Vec::new();
+    let mut light_primitives: Vec<Arc<Primitive>> = Vec::new();
+    let mut named_surfaces: HashMap<String, StandardSurface> = HashMap::new();
+    let mut named_images: HashMap<String, ImageNode> = HashMap::new();
     let mut named_materials: HashMap<String, Arc<Material>> = HashMap::new();
+    let mut named_media: HashMap<String, Arc<Medium>> = HashMap::new();
     let mut named_primitives: HashMap<String, (Vec<String>, Vec<(u32, Arc<Primitive>)>)> =
         HashMap::new();
     // input (.ass) file
     println!("FILE = {:?}", args.path);
     let f = File::open(&args.path)?;
+    let mut search_directory: PathBuf = args.path.parent().unwrap().to_path_buf();
     if args.path.is_relative() {
         let cp: PathBuf = env::current_dir().unwrap();
-        let pb: PathBuf = cp.join(args.path);
-        let search_directory: &Path = pb.as_path().parent().unwrap();
+        let pb: PathBuf = cp.join(&args.path);
+        search_directory = pb.as_path().parent().unwrap().to_path_buf();
         println!("search_directory is {}", search_directory.display());
     }
-    let mut reader = BufReader::new(f);
-    let mut str_buf: String = String::default();
-    let num_bytes = reader.read_to_string(&mut str_buf);
-    if num_bytes.is_ok() {
-        let n_bytes = num_bytes.unwrap();
-        println!("{} bytes read", n_bytes);
-    }
-    // parser
-    let pairs = AssParser::parse(Rule::ass, &str_buf).unwrap_or_else(|e| panic!("{}", e));
-    // let tokens: Vec<_> = pairs.flatten().tokens().collect();
-    // println!("{} pairs", tokens.len());
-    for pair in pairs {
+    let mut nodes: AssNodes<BufReader<File>> = AssNodes::new(BufReader::new(f));
+    // parser (one node at a time)
+    for node in &mut nodes {
+        let pair = AssParser::parse(Rule::node, node.trim_start())
+            .unwrap_or_else(|e| panic!("{}", e))
+            .next()
+            .unwrap();
         let span = pair.clone().as_span();
         // println!("Rule:    {:?}", pair.as_rule());
         // println!("Span:    {:?}", span);
@@ -309,9 +916,19 @@ fn main() -> std::io::Result<()> {
                         || node_type == "standard_surface"
                         || node_type == "spot_light"
                         || node_type == "point_light"
+                        || node_type == "distant_light"
+                        || node_type == "quad_light"
+                        || node_type == "skydome_light"
                     {
                         print!("{} {{", node_type);
                     }
+                    // nodes without a matrix use the identity
+                    cur_transform = Transform::default();
+                    obj_to_world = Transform::default();
+                    world_to_obj = Transform::default();
+                    matrices = Vec::new();
+                    motion_start = 0.0 as Float;
+                    motion_end = 1.0 as Float;
                     let stripped = strip_comments(span.as_str());
                     let mut iter = stripped.split_whitespace().peekable();
                     loop {
@@ -326,61 +943,28 @@ fn main() -> std::io::Result<()> {
                                         print!(" {} {} ", next, node_name);
                                     }
                                 } else if next == "matrix" {
-                                    let mut elems: Vec<Float> = Vec::new();
-                                    let expected: u32 = 16;
-                                    for _i in 0..expected {
-                                        if let Some(elem_str) = iter.next() {
-                                            let elem: f32 = f32::from_str(elem_str).unwrap();
-                                            elems.push(elem as Float);
-                                        }
-                                    }
-                                    // print!("\n matrix ... ");
-                                    // print!("\n {:?}", elems);
-                                    let m00: Float = elems[0];
-                                    let m01: Float = elems[1];
-                                    let m02: Float = elems[2];
-                                    let m03: Float = elems[3];
-                                    let m10: Float = elems[4];
-                                    let m11: Float = elems[5];
-                                    let m12: Float = elems[6];
-                                    let m13: Float = elems[7];
-                                    let m20: Float = elems[8];
-                                    let m21: Float = elems[9];
-                                    let m22: Float = elems[10];
-                                    let m23: Float = elems[11];
-                                    let m30: Float = elems[12];
-                                    let m31: Float = elems[13];
-                                    let m32: Float = elems[14];
-                                    let m33: Float = elems[15];
-                                    cur_transform = Transform::new(
-                                        m00, m10, m20, m30, m01, m11, m21, m31, m02, m12, m22, m32,
-                                        m03, m13, m23, m33,
-                                    );
-                                    // print!("\n {:?}", cur_transform);
-                                    obj_to_world = Transform {
-                                        m: cur_transform.m,
-                                        m_inv: cur_transform.m_inv,
-                                    };
-                                    world_to_obj = Transform {
-                                        m: cur_transform.m_inv,
-                                        m_inv: cur_transform.m,
-                                    };
-                                    if node_type == "persp_camera" && node_name == render_camera {
-                                        let transform_start_time: Float = 0.0;
-                                        let transform_end_time: Float = 1.0;
-                                        let scale: Transform = Transform::scale(
-                                            1.0 as Float,
-                                            1.0 as Float,
-                                            -1.0 as Float,
-                                        );
-                                        cur_transform = cur_transform * scale;
-                                        animated_cam_to_world = AnimatedTransform::new(
-                                            &cur_transform,
-                                            transform_start_time,
-                                            &cur_transform,
-                                            transform_end_time,
-                                        );
+                                    matrices = get_matrices(&mut iter);
+                                    cur_transform = matrices[0];
+                                    // animated shapes stay in object space (see below)
+                                    let is_animated_shape: bool = matrices.len() > 1
+                                        && (node_type == "polymesh"
+                                            || node_type == "disk"
+                                            || node_type == "sphere"
+                                            || node_type == "cylinder");
+                                    if !is_animated_shape {
+                                        obj_to_world = Transform {
+                                            m: cur_transform.m,
+                                            m_inv: cur_transform.m_inv,
+                                        };
+                                        world_to_obj = Transform {
+                                            m: cur_transform.m_inv,
+                                            m_inv: cur_transform.m,
+                                        };
                                     }
+                                } else if next == "motion_start" {
+                                    motion_start = get_float(&mut iter);
+                                } else if next == "motion_end" {
+                                    motion_end = get_float(&mut iter);
                                 }
                                 // by node type
                                 if node_type == "options" {
@@ -406,28 +990,60 @@ fn main() -> std::io::Result<()> {
                                             max_depth = i32::from_str(max_depth_str).unwrap();
                                             print!("\n GI_total_depth {} ", max_depth);
                                         }
+                                    } else if next.starts_with("GI_") && next.ends_with("_depth") {
+                                        if let Some(depth_str) = iter.next() {
+                                            let depth: i32 = i32::from_str(depth_str).unwrap();
+                                            print!("\n {} {} ", next, depth);
+                                            // Arnold 5 names, and those of Arnold 4
+                                            match next {
+                                                "GI_diffuse_depth" => diffuse_depth = Some(depth),
+                                                "GI_specular_depth" => {
+                                                    glossy_depth = Some(depth);
+                                                    specular_depth = Some(depth);
+                                                }
+                                                "GI_glossy_depth" => glossy_depth = Some(depth),
+                                                "GI_reflection_depth" => {
+                                                    specular_depth = Some(depth)
+                                                }
+                                                "GI_transmission_depth" | "GI_refraction_depth" => {
+                                                    transmission_depth = Some(depth)
+                                                }
+                                                _ => {}
+                                            }
+                                        }
+                                    } else if next == "atmosphere" {
+                                        if let Some(atmosphere_str) = iter.next() {
+                                            // strip surrounding double quotes
+                                            let v: Vec<&str> = atmosphere_str.split('"').collect();
+                                            atmosphere = v[1].to_string();
+                                            print!("\n atmosphere {:?} ", atmosphere);
+                                        }
                                     }
                                 } else if node_type == "persp_camera" && node_name == render_camera
                                 {
                                     // camera_name = String::from("perspective");
                                     if next == "fov" {
                                         if let Some(fov_str) = iter.next() {
-                                            fov = f32::from_str(fov_str).unwrap();
+                                            fov = Float::from_str(fov_str).unwrap();
                                             // print!("\n fov {} ", fov);
                                         }
+                                    } else if next == "shutter_start" {
+                                        shutter_start = get_float(&mut iter);
+                                    } else if next == "shutter_end" {
+                                        shutter_end = get_float(&mut iter);
                                     }
                                 } else if node_type == "gaussian_filter" {
                                     filter_name = String::from("gaussian");
                                     if next == "width" {
                                         if let Some(filter_width_str) = iter.next() {
-                                            filter_width = f32::from_str(filter_width_str).unwrap();
+                                            filter_width = Float::from_str(filter_width_str).unwrap();
                                             // print!("\n filter_width {} ", filter_width);
                                         }
                                     }
                                 } else if node_type == "mesh_light" {
                                     if next == "intensity" {
                                         if let Some(intensity_str) = iter.next() {
-                                            intensity = f32::from_str(intensity_str).unwrap();
+                                            intensity = Float::from_str(intensity_str).unwrap();
                                             // print!("\n intensity {} ", intensity);
                                         }
                                     } else if next == "color" {
@@ -435,19 +1051,23 @@ fn main() -> std::io::Result<()> {
                                         let mut color_g: Float = 0.0;
                                         let mut color_b: Float = 0.0;
                                         if let Some(color_str) = iter.next() {
-                                            color_r = f32::from_str(color_str).unwrap();
+                                            color_r = Float::from_str(color_str).unwrap();
                                         }
                                         if let Some(color_str) = iter.next() {
-                                            color_g = f32::from_str(color_str).unwrap();
+                                            color_g = Float::from_str(color_str).unwrap();
                                         }
                                         if let Some(color_str) = iter.next() {
-                                            color_b = f32::from_str(color_str).unwrap();
+                                            color_b = Float::from_str(color_str).unwrap();
                                         }
                                         color = Spectrum::rgb(color_r, color_g, color_b);
                                     // print!(
                                     //     "\n color {} {} {} ",
                                     //     color_r, color_g, color_b
                                     // );
+                                    } else if next == "exposure" {
+                                        if let Some(exposure_str) = iter.next() {
+                                            exposure = Float::from_str(exposure_str).unwrap();
+                                        }
                                     } else if next == "samples" {
                                         if let Some(samples_str) = iter.next() {
                                             samples = i32::from_str(samples_str).unwrap();
@@ -461,54 +1081,76 @@ fn main() -> std::io::Result<()> {
                                             // print!("\n mesh {:?} ", mesh);
                                         }
                                     }
-                                } else if node_type == "point_light" {
+                                } else if node_type == "point_light"
+                                    || node_type == "spot_light"
+                                    || node_type == "distant_light"
+                                    || node_type == "quad_light"
+                                    || node_type == "skydome_light"
+                                {
                                     if next == "intensity" {
                                         if let Some(intensity_str) = iter.next() {
-                                            intensity = f32::from_str(intensity_str).unwrap();
+                                            intensity = Float::from_str(intensity_str).unwrap();
                                             print!("\n intensity {} ", intensity);
                                         }
                                     } else if next == "color" {
-                                        let mut color_r: Float = 0.0;
-                                        let mut color_g: Float = 0.0;
-                                        let mut color_b: Float = 0.0;
-                                        if let Some(color_str) = iter.next() {
-                                            color_r = f32::from_str(color_str).unwrap();
-                                        }
-                                        if let Some(color_str) = iter.next() {
-                                            color_g = f32::from_str(color_str).unwrap();
+                                        let mut is_linked: bool = false;
+                                        if let Some(check_for_float_str) = iter.peek() {
+                                            is_linked = Float::from_str(check_for_float_str).is_err();
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_b = f32::from_str(color_str).unwrap();
+                                        if is_linked {
+                                            // color is connected to a shader node
+                                            if let Some(link_str) = iter.next() {
+                                                if node_type == "skydome_light" {
+                                                    light_link = Some(
+                                                        link_str.trim_matches('"').to_string(),
+                                                    );
+                                                } else {
+                                                    println!(
+                                                        "\nWARNING: {}.color linked to {:?} not supported",
+                                                        node_type, link_str
+                                                    );
+                                                }
+                                            }
+                                        } else {
+                                            color = get_color(&mut iter);
+                                            print!("\n color {:?} ", color);
                                         }
-                                        color = Spectrum::rgb(color_r, color_g, color_b);
-                                        print!("\n color {} {} {} ", color_r, color_g, color_b);
-                                    }
-                                } else if node_type == "spot_light" {
-                                    if next == "intensity" {
-                                        if let Some(intensity_str) = iter.next() {
-                                            intensity = f32::from_str(intensity_str).unwrap();
-                                            print!("\n intensity {} ", intensity);
+                                    } else if next == "exposure" {
+                                        if let Some(exposure_str) = iter.next() {
+                                            exposure = Float::from_str(exposure_str).unwrap();
+                                            print!("\n exposure {} ", exposure);
                                         }
-                                    } else if next == "color" {
-                                        let mut color_r: Float = 0.0;
-                                        let mut color_g: Float = 0.0;
-                                        let mut color_b: Float = 0.0;
-                                        if let Some(color_str) = iter.next() {
-                                            color_r = f32::from_str(color_str).unwrap();
+                                    } else if next == "normalize" {
+                                        if let Some(normalize_str) = iter.next() {
+                                            normalize = normalize_str == "on"
+                                                || normalize_str == "true"
+                                                || normalize_str == "1";
+                                            print!("\n normalize {} ", normalize);
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_g = f32::from_str(color_str).unwrap();
+                                    } else if next == "samples" {
+                                        if let Some(samples_str) = iter.next() {
+                                            samples = i32::from_str(samples_str).unwrap();
+                                            print!("\n samples {} ", samples);
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_b = f32::from_str(color_str).unwrap();
+                                    } else if next == "radius" {
+                                        if let Some(radius_str) = iter.next() {
+                                            light_radius = Float::from_str(radius_str).unwrap();
+                                            print!("\n radius {} ", light_radius);
                                         }
-                                        color = Spectrum::rgb(color_r, color_g, color_b);
-                                        print!("\n color {} {} {} ", color_r, color_g, color_b);
                                     } else if next == "cone_angle" {
                                         if let Some(cone_angle_str) = iter.next() {
-                                            cone_angle = f32::from_str(cone_angle_str).unwrap();
+                                            cone_angle = Float::from_str(cone_angle_str).unwrap();
                                             print!("\n cone_angle {} ", cone_angle);
                                         }
+                                    } else if next == "penumbra_angle" {
+                                        if let Some(penumbra_angle_str) = iter.next() {
+                                            penumbra_angle =
+                                                Float::from_str(penumbra_angle_str).unwrap();
+                                            print!("\n penumbra_angle {} ", penumbra_angle);
+                                        }
+                                    } else if next == "vertices" {
+                                        quad_vertices = get_vectors(&mut iter);
+                                        print!("\n vertices {:?} ", quad_vertices);
                                     }
                                 } else if node_type == "polymesh" {
                                     if next == "vlist" {
@@ -536,8 +1178,8 @@ fn main() -> std::io::Result<()> {
                                                             num_elements * num_motionblur_keys * 3;
                                                         for _i in 0..expected {
                                                             if let Some(elem_str) = iter.next() {
-                                                                let elem: f32 =
-                                                                    f32::from_str(elem_str)
+                                                                let elem: Float =
+                                                                    Float::from_str(elem_str)
                                                                         .unwrap();
                                                                 elems.push(elem as Float);
                                                             }
@@ -671,6 +1313,23 @@ fn main() -> std::io::Result<()> {
                                     //     num_elements, num_motionblur_keys
                                     // );
                                     // print!("\n {:?} ", vi);
+                                    } else if next == "nlist" {
+                                        nlist = get_vectors(&mut iter)
+                                            .iter()
+                                            .map(|p| Normal3f {
+                                                x: p.x,
+                                                y: p.y,
+                                                z: p.z,
+                                            })
+                                            .collect();
+                                    } else if next == "nidxs" {
+                                        nidxs = get_uints(&mut iter);
+                                    } else if next == "uvlist" {
+                                        uvlist = get_vector2s(&mut iter);
+                                    } else if next == "uvidxs" {
+                                        uvidxs = get_uints(&mut iter);
+                                    } else if next == "smoothing" {
+                                        smoothing = get_bool(&mut iter);
                                     } else if next == "shidxs" {
                                         shidxs = Vec::new();
                                         loop {
@@ -729,12 +1388,12 @@ fn main() -> std::io::Result<()> {
                                 } else if node_type == "disk" {
                                     if next == "radius" {
                                         if let Some(radius_str) = iter.next() {
-                                            radius = f32::from_str(radius_str).unwrap();
+                                            radius = Float::from_str(radius_str).unwrap();
                                             // print!("\n radius {} ", radius);
                                         }
                                     } else if next == "hole" {
                                         if let Some(hole_str) = iter.next() {
-                                            hole = f32::from_str(hole_str).unwrap();
+                                            hole = Float::from_str(hole_str).unwrap();
                                             // print!("\n hole {} ", hole);
                                         }
                                     } else if next == "shader" {
@@ -744,7 +1403,7 @@ fn main() -> std::io::Result<()> {
                                 } else if node_type == "sphere" {
                                     if next == "radius" {
                                         if let Some(radius_str) = iter.next() {
-                                            radius = f32::from_str(radius_str).unwrap();
+                                            radius = Float::from_str(radius_str).unwrap();
                                             // print!("\n radius {} ", radius);
                                         }
                                     } else if next == "shader" {
@@ -754,60 +1413,105 @@ fn main() -> std::io::Result<()> {
                                 } else if node_type == "cylinder" {
                                     if next == "radius" {
                                         if let Some(radius_str) = iter.next() {
-                                            radius = f32::from_str(radius_str).unwrap();
+                                            radius = Float::from_str(radius_str).unwrap();
                                             // print!("\n radius {} ", radius);
                                         }
                                     } else if next == "shader" {
                                         shader_names = get_shader_names(&mut iter);
                                         // print!("\n {:?} ", shader_names);
                                     }
-                                } else if node_type == "standard_surface" {
-                                    if next == "base_color" {
-                                        let mut color_r: Float = 0.0;
-                                        let mut color_g: Float = 0.0;
-                                        let mut color_b: Float = 0.0;
-                                        if let Some(color_str) = iter.next() {
-                                            color_r = f32::from_str(color_str).unwrap();
+                                } else if node_type == "atmosphere_volume"
+                                    || node_type == "standard_volume"
+                                {
+                                    if next == "density" {
+                                        if let Some(density_str) = iter.next() {
+                                            density = Float::from_str(density_str).unwrap();
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_g = f32::from_str(color_str).unwrap();
+                                    } else if next == "rgb_density" || next == "scatter_color" {
+                                        scatter_color = get_color(&mut iter);
+                                    } else if next == "attenuation" || next == "absorption" {
+                                        if let Some(absorption_str) = iter.next() {
+                                            absorption = Float::from_str(absorption_str).unwrap();
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_b = f32::from_str(color_str).unwrap();
+                                    } else if next == "rgb_attenuation"
+                                        || next == "absorption_color"
+                                    {
+                                        absorption_color = get_color(&mut iter);
+                                    } else if next == "eccentricity" || next == "scatter_anisotropy"
+                                    {
+                                        if let Some(anisotropy_str) = iter.next() {
+                                            anisotropy = Float::from_str(anisotropy_str).unwrap();
                                         }
-                                        base_color = Spectrum::rgb(color_r, color_g, color_b);
-                                        print!(
-                                            "\n base_color {} {} {} ",
-                                            color_r, color_g, color_b
-                                        );
-                                    } else if next == "specular_color" {
-                                        let mut color_r: Float = 0.0;
-                                        let mut color_g: Float = 0.0;
-                                        let mut color_b: Float = 0.0;
-                                        if let Some(color_str) = iter.next() {
-                                            color_r = f32::from_str(color_str).unwrap();
+                                    }
+                                } else if node_type == "standard_surface" {
+                                    if next == "base"
+                                        || next == "base_color"
+                                        || next == "metalness"
+                                        || next == "specular_roughness"
+                                        || next == "specular_IOR"
+                                        || next == "specular_anisotropy"
+                                        || next == "transmission"
+                                        || next == "coat"
+                                        || next == "coat_roughness"
+                                        || next == "sheen"
+                                    {
+                                        if let Some(link) = get_link(&mut iter) {
+                                            print!("\n {} {} ", next, link);
+                                            surface.links.insert(next.to_string(), link);
+                                        } else if next == "base_color" {
+                                            surface.base_color = get_color(&mut iter);
+                                            print!("\n {} {:?} ", next, surface.base_color);
+                                        } else {
+                                            let value: Float = get_float(&mut iter);
+                                            print!("\n {} {} ", next, value);
+                                            match next {
+                                                "base" => surface.base = value,
+                                                "metalness" => surface.metalness = value,
+                                                "specular_roughness" => {
+                                                    surface.specular_roughness = value
+                                                }
+                                                "specular_IOR" => surface.specular_ior = value,
+                                                "specular_anisotropy" => {
+                                                    surface.specular_anisotropy = value
+                                                }
+                                                "transmission" => surface.transmission = value,
+                                                "coat" => surface.coat = value,
+                                                "coat_roughness" => surface.coat_roughness = value,
+                                                _ => surface.sheen = value,
+                                            }
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_g = f32::from_str(color_str).unwrap();
+                                    } else if next == "thin_walled" {
+                                        surface.thin_walled = get_bool(&mut iter);
+                                        print!("\n thin_walled {} ", surface.thin_walled);
+                                    }
+                                } else if node_type == "ginstance" {
+                                    if next == "node" {
+                                        if let Some(node_str) = iter.next() {
+                                            // strip surrounding double quotes
+                                            ginstance_node = node_str.trim_matches('"').to_string();
                                         }
-                                        if let Some(color_str) = iter.next() {
-                                            color_b = f32::from_str(color_str).unwrap();
+                                    } else if next == "inherit_xform" {
+                                        inherit_xform = get_bool(&mut iter);
+                                    }
+                                } else if node_type == "image" {
+                                    if next == "filename" {
+                                        if let Some(filename_str) = iter.next() {
+                                            // strip surrounding double quotes
+                                            image_node.filename =
+                                                filename_str.trim_matches('"').to_string();
                                         }
-                                        specular_color = Spectrum::rgb(color_r, color_g, color_b);
-                                        print!(
-                                            "\n specular_color {} {} {} ",
-                                            color_r, color_g, color_b
-                                        );
-                                    } else if next == "specular_roughness" {
-                                        if let Some(specular_roughness_str) = iter.next() {
-                                            specular_roughness =
-                                                f32::from_str(specular_roughness_str).unwrap();
-                                            print!("\n specular_roughness {} ", specular_roughness);
+                                    } else if next == "color_space" {
+                                        if let Some(color_space_str) = iter.next() {
+                                            image_node.color_space =
+                                                color_space_str.trim_matches('"').to_string();
+                                        }
+                                    } else if next == "swrap" {
+                                        if let Some(swrap_str) = iter.next() {
+                                            image_node.swrap = swrap_str.to_string();
                                         }
-                                    } else if next == "metalness" {
-                                        if let Some(metalness_str) = iter.next() {
-                                            metalness = f32::from_str(metalness_str).unwrap();
-                                            print!("\n metalness {} ", metalness);
+                                    } else if next == "twrap" {
+                                        if let Some(twrap_str) = iter.next() {
+                                            image_node.twrap = twrap_str.to_string();
                                         }
                                     }
                                 }
@@ -817,7 +1521,27 @@ fn main() -> std::io::Result<()> {
                                     println!("}}");
                                 } else if node_type == "persp_camera" && node_name == render_camera
                                 {
+                                    let scale: Transform =
+                                        Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+                                    let cam_keys: Vec<Transform> =
+                                        matrices.iter().map(|m| *m * scale).collect();
+                                    animated_cam_to_world = make_animated_transform(
+                                        &cam_keys,
+                                        motion_start,
+                                        motion_end,
+                                    );
                                     // println!("}}");
+                                } else if node_type == "ginstance" {
+                                    ginstances.push(GInstance {
+                                        node: ginstance_node.clone(),
+                                        matrices: matrices.clone(),
+                                        motion_start,
+                                        motion_end,
+                                        inherit_xform,
+                                    });
+                                    // reset
+                                    ginstance_node = String::from("");
+                                    inherit_xform = true;
                                 } else if node_type == "gaussian_filter" {
                                     // println!("}}");
                                 } else if node_type == "mesh_light" {
@@ -834,8 +1558,8 @@ fn main() -> std::io::Result<()> {
                                                             let shape = primitive.shape.clone();
                                                             let mi: MediumInterface =
                                                                 MediumInterface::default();
-                                                            let l_emit: Spectrum =
-                                                                color * intensity;
+                                                            let l_emit: Spectrum = color
+                                                                * (intensity * exposure.exp2());
                                                             let two_sided: bool = false;
                                                             let area_light: Arc<Light> = Arc::new(
                                                                 Light::DiffuseArea(Box::new(
@@ -867,23 +1591,152 @@ fn main() -> std::io::Result<()> {
                                 // println!("}}");
                                 } else if node_type == "point_light" {
                                     let mi: MediumInterface = MediumInterface::default();
-                                    let point_light = Arc::new(Light::Point(Box::new(
-                                        PointLight::new(&cur_transform, &mi, &(color * intensity)),
-                                    )));
-                                    lights.push(point_light);
+                                    let i: Spectrum = color * (intensity * exposure.exp2());
+                                    if light_radius > 0.0 as Float {
+                                        // spherical light, emitting the same power as
+                                        // the point light (if normalized)
+                                        let sphere = Arc::new(Shape::Sphr(Sphere::new(
+                                            obj_to_world,
+                                            world_to_obj,
+                                            false,
+                                            light_radius,
+                                            -light_radius,  // z_min
+                                            light_radius,   // z_max
+                                            360.0 as Float, // phi_max
+                                        )));
+                                        let mut l_emit: Spectrum = i;
+                                        if normalize {
+                                            l_emit = i / (PI * light_radius * light_radius);
+                                        }
+                                        make_area_lights(
+                                            &[sphere],
+                                            &cur_transform,
+                                            &l_emit,
+                                            samples,
+                                            &mut lights,
+                                            &mut light_primitives,
+                                        );
+                                    } else {
+                                        let point_light = Arc::new(Light::Point(Box::new(
+                                            PointLight::new(&cur_transform, &mi, &i),
+                                        )));
+                                        lights.push(point_light);
+                                    }
                                     println!("}}");
                                 } else if node_type == "spot_light" {
                                     let mi: MediumInterface = MediumInterface::default();
+                                    // Arnold's spot lights point down the negative z-axis
+                                    let flip_z: Transform =
+                                        Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+                                    // cone_angle is the full opening angle
+                                    let total_width: Float = 0.5 as Float * cone_angle;
+                                    let falloff_start: Float =
+                                        (total_width - penumbra_angle).max(0.0 as Float);
                                     let spot_light =
                                         Arc::new(Light::Spot(Box::new(SpotLight::new(
-                                            &cur_transform,
+                                            &(cur_transform * flip_z),
                                             &mi,
-                                            &(color * intensity),
-                                            cone_angle,
-                                            cone_angle - cone_delta_angle,
+                                            &(color * (intensity * exposure.exp2())),
+                                            total_width,
+                                            falloff_start,
                                         ))));
                                     lights.push(spot_light);
                                     println!("}}");
+                                } else if node_type == "distant_light" {
+                                    // light travels down the negative z-axis
+                                    let w_light: Vector3f = Vector3f {
+                                        x: 0.0 as Float,
+                                        y: 0.0 as Float,
+                                        z: 1.0 as Float,
+                                    };
+                                    let distant_light =
+                                        Arc::new(Light::Distant(Box::new(DistantLight::new(
+                                            &cur_transform,
+                                            &(color * (intensity * exposure.exp2())),
+                                            &w_light,
+                                        ))));
+                                    lights.push(distant_light);
+                                    println!("}}");
+                                } else if node_type == "quad_light" {
+                                    if quad_vertices.len() != 4 {
+                                        panic!(
+                                            "ERROR: quad_light({:?}) needs 4 vertices, got {}",
+                                            node_name,
+                                            quad_vertices.len()
+                                        );
+                                    }
+                                    let p_ws: Vec<Point3f> = quad_vertices
+                                        .iter()
+                                        .map(|p| obj_to_world.transform_point(p))
+                                        .collect();
+                                    // reversed winding: the light emits down the
+                                    // negative z-axis for the default vertices
+                                    let vertex_indices: Vec<u32> = vec![0, 2, 1, 0, 3, 2];
+                                    let mesh = Arc::new(TriangleMesh::new(
+                                        obj_to_world,
+                                        world_to_obj,
+                                        false, // reverse_orientation,
+                                        2,
+                                        vertex_indices,
+                                        4,
+                                        p_ws,
+                                        Vec::new(),
+                                        Vec::new(),
+                                        Vec::new(),
+                                        None,
+                                        None,
+                                    ));
+                                    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+                                    let mut area: Float = 0.0 as Float;
+                                    for id in 0..mesh.n_triangles {
+                                        let triangle = Arc::new(Shape::Trngl(Triangle::new(
+                                            mesh.object_to_world,
+                                            mesh.world_to_object,
+                                            mesh.reverse_orientation,
+                                            mesh.clone(),
+                                            id,
+                                        )));
+                                        area += triangle.area();
+                                        shapes.push(triangle);
+                                    }
+                                    let mut l_emit: Spectrum =
+                                        color * (intensity * exposure.exp2());
+                                    if normalize && area > 0.0 as Float {
+                                        l_emit = l_emit / area;
+                                    }
+                                    make_area_lights(
+                                        &shapes,
+                                        &cur_transform,
+                                        &l_emit,
+                                        samples,
+                                        &mut lights,
+                                        &mut light_primitives,
+                                    );
+                                    println!("}}");
+                                } else if node_type == "skydome_light" {
+                                    // Arnold's skydome has the y-axis as pole, pbrt
+                                    // the z-axis
+                                    let y_up: Transform = Transform::rotate_x(-90.0 as Float);
+                                    if let Some(link) = light_link.take() {
+                                        // created once all image nodes are known
+                                        linked_skydomes.push((
+                                            cur_transform * y_up,
+                                            intensity * exposure.exp2(),
+                                            samples,
+                                            link,
+                                        ));
+                                    } else {
+                                        let skydome_light = Arc::new(Light::InfiniteArea(
+                                            Box::new(InfiniteAreaLight::new(
+                                                &(cur_transform * y_up),
+                                                &(color * (intensity * exposure.exp2())),
+                                                samples,
+                                                String::from(""),
+                                            )),
+                                        ));
+                                        lights.push(skydome_light);
+                                    }
+                                    println!("}}");
                                 } else if node_type == "polymesh" {
                                     // make sure there are no out of-bounds vertex indices
                                     for i in 0..vi.len() {
@@ -895,61 +1748,112 @@ fn main() -> std::io::Result<()> {
                                                         );
                                         }
                                     }
-                                    // convert quads to triangles
+                                    let n_corners: usize = vi.len();
+                                    // normals and uvs are indexed per polygon corner
+                                    // (nidxs, uvidxs) or per vertex
+                                    if !smoothing {
+                                        nlist = Vec::new();
+                                    }
+                                    if !nidxs.is_empty() && nidxs.len() != n_corners {
+                                        println!(
+                                            "WARNING: polymesh({:?}) has {} \"nidxs\" for {} \"vidxs\", ignoring normals",
+                                            node_name, nidxs.len(), n_corners
+                                        );
+                                        nlist = Vec::new();
+                                    }
+                                    if !uvidxs.is_empty() && uvidxs.len() != n_corners {
+                                        println!(
+                                            "WARNING: polymesh({:?}) has {} \"uvidxs\" for {} \"vidxs\", ignoring uvs",
+                                            node_name, uvidxs.len(), n_corners
+                                        );
+                                        uvlist = Vec::new();
+                                    }
+                                    let unweld: bool = (!nlist.is_empty() && !nidxs.is_empty())
+                                        || (!uvlist.is_empty() && !uvidxs.is_empty());
+                                    let mut p_mesh: Vec<Point3f> = Vec::new();
+                                    let mut n_ws: Vec<Normal3f> = Vec::new();
+                                    let mut uvs: Vec<Point2f> = Vec::new();
+                                    let mut corners: Vec<u32> = Vec::new();
+                                    if unweld {
+                                        // one mesh vertex per polygon corner
+                                        for c in 0..n_corners {
+                                            let v: usize = vi[c] as usize;
+                                            p_mesh.push(p_ws[v]);
+                                            if !nlist.is_empty() {
+                                                let ni: usize = if nidxs.is_empty() {
+                                                    v
+                                                } else {
+                                                    nidxs[c] as usize
+                                                };
+                                                n_ws.push(
+                                                    obj_to_world.transform_normal(&nlist[ni]),
+                                                );
+                                            }
+                                            if !uvlist.is_empty() {
+                                                let uvi: usize = if uvidxs.is_empty() {
+                                                    v
+                                                } else {
+                                                    uvidxs[c] as usize
+                                                };
+                                                uvs.push(uvlist[uvi]);
+                                            }
+                                            corners.push(c as u32);
+                                        }
+                                    } else {
+                                        p_mesh = p_ws.clone();
+                                        if nlist.len() == p_ws_len {
+                                            for n in nlist.iter() {
+                                                n_ws.push(obj_to_world.transform_normal(n));
+                                            }
+                                        }
+                                        if uvlist.len() == p_ws_len {
+                                            uvs = uvlist.clone();
+                                        }
+                                        corners = vi.clone();
+                                    }
+                                    // without nsides all polygons are triangles
+                                    if nsides.is_empty() {
+                                        nsides = vec![3_u32; n_corners / 3];
+                                    }
+                                    // convert polygons to triangles (fan triangulation)
                                     let mut vi_tri: Vec<u32> = Vec::new();
                                     let mut shidxs_tri: Vec<u32> = Vec::new();
                                     let mut count_vi: usize = 0;
-                                    let mut count_shidxs: usize = 0;
-                                    for i in 0..nsides.len() {
-                                        let nside = nsides[i];
-                                        if nside == 3 {
-                                            // triangle
-                                            vi_tri.push(vi[count_vi]);
-                                            count_vi += 1;
-                                            vi_tri.push(vi[count_vi]);
-                                            count_vi += 1;
-                                            vi_tri.push(vi[count_vi]);
-                                            count_vi += 1;
-                                            shidxs_tri.push(shidxs[count_shidxs]);
-                                            count_shidxs += 1;
-                                        } else if nside == 4 {
-                                            // quad gets split into 2 triangles
-                                            vi_tri.push(vi[count_vi]);
-                                            vi_tri.push(vi[count_vi + 1]);
-                                            vi_tri.push(vi[count_vi + 2]);
-                                            vi_tri.push(vi[count_vi]);
-                                            vi_tri.push(vi[count_vi + 2]);
-                                            vi_tri.push(vi[count_vi + 3]);
-                                            count_vi += 4;
-                                            shidxs_tri.push(shidxs[count_shidxs]);
-                                            shidxs_tri.push(shidxs[count_shidxs]);
-                                            count_shidxs += 1;
-                                        } else {
+                                    for (face, nside) in nsides.iter().enumerate() {
+                                        let nside: usize = *nside as usize;
+                                        if nside < 3 {
                                             panic!("{}-sided poygons are not supported", nside);
                                         }
+                                        // a single shader does not need shidxs
+                                        let shidx: u32 = if shidxs.is_empty() {
+                                            0_u32
+                                        } else {
+                                            shidxs[face]
+                                        };
+                                        for k in 1..(nside - 1) {
+                                            vi_tri.push(corners[count_vi]);
+                                            vi_tri.push(corners[count_vi + k]);
+                                            vi_tri.push(corners[count_vi + k + 1]);
+                                            shidxs_tri.push(shidx);
+                                        }
+                                        count_vi += nside;
                                     }
                                     let n_triangles: usize = vi_tri.len() / 3;
                                     assert!(shidxs_tri.len() == n_triangles);
                                     // TriangleMesh
                                     let mut shapes: Vec<Arc<Shape>> = Vec::new();
                                     let s_ws: Vec<Vector3f> = Vec::new();
-                                    let n_ws: Vec<Normal3f> = Vec::new();
-                                    let uvs: Vec<Point2f> = Vec::new();
-                                    // vertex indices are expected as usize, not u32
-                                    let mut vertex_indices: Vec<u32> = Vec::new();
-                                    for i in 0..vi_tri.len() {
-                                        vertex_indices.push(vi_tri[i] as u32);
-                                    }
+                                    let n_vertices: usize = p_mesh.len();
                                     let mesh = Arc::new(TriangleMesh::new(
                                         obj_to_world,
                                         world_to_obj,
                                         false, // reverse_orientation,
                                         n_triangles.try_into().unwrap(),
-                                        vertex_indices,
-                                        p_ws_len as u32,
-                                        p_ws.clone(), // in world space
-                                        s_ws,         // in world space
-                                        n_ws,         // in world space
+                                        vi_tri,
+                                        n_vertices as u32,
+                                        p_mesh, // in world space
+                                        s_ws,   // in world space
+                                        n_ws,   // in world space
                                         uvs,
                                         None,
                                         None,
@@ -982,6 +1886,16 @@ fn main() -> std::io::Result<()> {
                                     }
                                     named_primitives
                                         .insert(node_name.clone(), (shader_names.clone(), prims));
+                                    // reset
+                                    nsides = Vec::new();
+                                    vi = Vec::new();
+                                    shidxs = Vec::new();
+                                    shader_names = Vec::new();
+                                    nlist = Vec::new();
+                                    nidxs = Vec::new();
+                                    uvlist = Vec::new();
+                                    uvidxs = Vec::new();
+                                    smoothing = false;
                                 // println!("}}");
                                 } else if node_type == "disk" {
                                     let mut shapes: Vec<Arc<Shape>> = Vec::new();
@@ -1075,60 +1989,67 @@ fn main() -> std::io::Result<()> {
                                         .insert(node_name.clone(), (shader_names.clone(), prims));
                                 // println!("}}");
                                 } else if node_type == "standard_surface" {
-                                    if metalness > 0.0 as Float {
-                                        if metalness == 1.0 as Float {
-                                            let kr = Arc::new(ConstantTexture::new(specular_color));
-                                            let mirror = Arc::new(Material::Mirror(Box::new(
-                                                MirrorMaterial::new(kr, None),
-                                            )));
-                                            named_materials.insert(node_name.clone(), mirror);
-                                        } else {
-                                            let copper_n: Spectrum = Spectrum::from_sampled(
-                                                &COPPER_WAVELENGTHS,
-                                                &COPPER_N,
-                                                COPPER_SAMPLES as i32,
-                                            );
-                                            let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                                Arc::new(ConstantTexture::new(copper_n));
-                                            let copper_k: Spectrum = Spectrum::from_sampled(
-                                                &COPPER_WAVELENGTHS,
-                                                &COPPER_K,
-                                                COPPER_SAMPLES as i32,
-                                            );
-                                            let k: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                                Arc::new(ConstantTexture::new(copper_k));
-                                            let roughness = Arc::new(ConstantTexture::new(
-                                                specular_roughness as Float,
-                                            ));
-                                            let remap_roughness: bool = true;
-                                            let metal = Arc::new(Material::Metal(Box::new(
-                                                MetalMaterial::new(
-                                                    eta,
-                                                    k,
-                                                    roughness,
-                                                    None,
-                                                    None,
-                                                    None,
-                                                    remap_roughness,
-                                                ),
-                                            )));
-                                            named_materials.insert(node_name.clone(), metal);
-                                        }
-                                    } else {
-                                        // TODO: create a matte material for now
-                                        let kd = Arc::new(ConstantTexture::new(base_color));
-                                        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
-                                        let matte = Arc::new(Material::Matte(Box::new(
-                                            MatteMaterial::new(kd, sigma, None),
-                                        )));
-                                        named_materials.insert(node_name.clone(), matte);
+                                    // materials are made once all image nodes are known
+                                    named_surfaces.insert(
+                                        node_name.clone(),
+                                        std::mem::replace(&mut surface, StandardSurface::default()),
+                                    );
+                                    println!("}}");
+                                } else if node_type == "image" {
+                                    // relative to the directory of the .ass file
+                                    if Path::new(&image_node.filename).is_relative() {
+                                        image_node.filename = search_directory
+                                            .join(&image_node.filename)
+                                            .to_string_lossy()
+                                            .to_string();
                                     }
+                                    named_images.insert(
+                                        node_name.clone(),
+                                        std::mem::replace(&mut image_node, ImageNode::default()),
+                                    );
+                                } else if node_type == "atmosphere_volume"
+                                    || node_type == "standard_volume"
+                                {
+                                    // homogeneous approximation of the volume shader
+                                    let sig_s: Spectrum = scatter_color * density;
+                                    let sig_a: Spectrum = absorption_color * (absorption * density);
+                                    let medium = Arc::new(Medium::Homogeneous(
+                                        HomogeneousMedium::new(&sig_a, &sig_s, anisotropy),
+                                    ));
+                                    named_media.insert(node_name.clone(), medium);
                                     // reset
-                                    base_color = Spectrum::new(0.5 as Float);
-                                    specular_color = Spectrum::new(1.0 as Float);
-                                    specular_roughness = 0.01 as Float;
-                                    metalness = 0.0 as Float;
-                                    println!("}}");
+                                    density = 1.0 as Float;
+                                    scatter_color = Spectrum::new(1.0 as Float);
+                                    absorption = 0.0 as Float;
+                                    absorption_color = Spectrum::new(1.0 as Float);
+                                    anisotropy = 0.0 as Float;
+                                }
+                                if node_type == "polymesh"
+                                    || node_type == "disk"
+                                    || node_type == "sphere"
+                                    || node_type == "cylinder"
+                                {
+                                    if matrices.is_empty() {
+                                        matrices.push(Transform::default());
+                                    }
+                                    named_transforms.insert(node_name.clone(), matrices.clone());
+                                    if matrices.len() > 1 {
+                                        named_motions
+                                            .insert(node_name.clone(), (motion_start, motion_end));
+                                    }
+                                }
+                                if node_type.ends_with("_light") {
+                                    // reset
+                                    light_link = None;
+                                    color = Spectrum::new(1.0 as Float);
+                                    intensity = 1.0 as Float;
+                                    exposure = 0.0 as Float;
+                                    normalize = true;
+                                    samples = 1;
+                                    light_radius = 0.0 as Float;
+                                    cone_angle = 65.0 as Float;
+                                    penumbra_angle = 0.0 as Float;
+                                    quad_vertices = default_quad_vertices();
                                 }
                             }
                         } else {
@@ -1140,18 +2061,79 @@ fn main() -> std::io::Result<()> {
             }
         }
     }
+    println!("{} bytes read", nodes.bytes_read);
     println!("render_camera = {:?} ", render_camera);
     println!("fov = {:?} ", fov);
     println!("filter_name = {:?}", filter_name);
     println!("filter_width = {:?}", filter_width);
     println!("max_depth = {:?}", max_depth);
-    for value in named_primitives.values_mut() {
+    let kind_depth = |depth: Option<i32>| -> u32 { depth.unwrap_or(max_depth).max(0) as u32 };
+    let depth_limits: DepthLimits = DepthLimits {
+        diffuse: kind_depth(diffuse_depth),
+        glossy: kind_depth(glossy_depth),
+        specular: kind_depth(specular_depth),
+        transmission: kind_depth(transmission_depth),
+    };
+    println!("depth_limits = {:?}", depth_limits);
+    // the atmosphere surrounds all shapes and the camera
+    let mut outside: Option<Arc<Medium>> = None;
+    if atmosphere != "" {
+        if let Some(named_medium) = named_media.get(&atmosphere) {
+            outside = Some(named_medium.clone());
+        } else {
+            println!("WARNING: atmosphere {:?} not defined", atmosphere);
+        }
+    }
+    for (light_to_world, scale, samples, link) in linked_skydomes.iter() {
+        let mut texmap: String = String::from("");
+        if let Some(image_node) = named_images.get(link) {
+            texmap = image_node.filename.clone();
+        } else {
+            println!(
+                "WARNING: skydome_light.color linked to {:?}, which is not an image node",
+                link
+            );
+        }
+        let skydome_light = Arc::new(Light::InfiniteArea(Box::new(InfiniteAreaLight::new(
+            light_to_world,
+            &Spectrum::new(*scale),
+            *samples,
+            texmap,
+        ))));
+        lights.push(skydome_light);
+    }
+    for (name, surface) in named_surfaces.iter() {
+        named_materials.insert(
+            name.clone(),
+            make_disney_material(name, surface, &named_images),
+        );
+    }
+    // the atmosphere surrounds the lights as well
+    for prim in light_primitives.iter_mut() {
+        if outside.is_some() {
+            let prim_opt = Arc::get_mut(prim);
+            if let Some(Primitive::Geometric(primitive)) = prim_opt {
+                primitive.medium_interface = Some(Arc::new(MediumInterface::new(
+                    outside.clone(),
+                    outside.clone(),
+                )));
+            }
+        }
+        primitives.push(prim.clone());
+    }
+    let mut node_primitives: HashMap<String, Vec<Arc<Primitive>>> = HashMap::new();
+    for (name, value) in named_primitives.iter_mut() {
         let (shader_names, tuple_vec) = value;
+        let mut prims: Vec<Arc<Primitive>> = Vec::new();
         // let mut count: usize = 0;
         for (shader_idx, prim) in tuple_vec.iter_mut() {
+            let mut inside: Option<Arc<Medium>> = outside.clone();
             if shader_names.len() > 0 as usize {
                 let shader_name: String = shader_names[*shader_idx as usize].clone();
-                if let Some(named_material) = named_materials.get(&shader_name) {
+                if let Some(named_medium) = named_media.get(&shader_name) {
+                    // volume shader: no surface, but a medium inside
+                    inside = Some(named_medium.clone());
+                } else if let Some(named_material) = named_materials.get(&shader_name) {
                     // println!("#{}: {} -> {:?}", count, shader_idx, shader_name);
                     let prim_opt = Arc::get_mut(prim);
                     if prim_opt.is_some() {
@@ -1169,9 +2151,71 @@ fn main() -> std::io::Result<()> {
             } else {
                 println!("WARNING: No shader names");
             }
-            primitives.push(prim.clone());
+            if inside.is_some() || outside.is_some() {
+                let prim_opt = Arc::get_mut(prim);
+                if let Some(Primitive::Geometric(primitive)) = prim_opt {
+                    primitive.medium_interface = Some(Arc::new(MediumInterface::new(
+                        inside.clone(),
+                        outside.clone(),
+                    )));
+                } else {
+                    println!("WARNING: Can't replace GeometricPrimitive.medium_interface");
+                }
+            }
+            prims.push(prim.clone());
             // count += 1;
         }
+        node_primitives.insert(name.clone(), prims);
+    }
+    // animated shapes were created in object space
+    let mut aggregates: HashMap<String, Arc<Primitive>> = HashMap::new();
+    for (name, prims) in node_primitives.iter() {
+        if let Some((start, end)) = named_motions.get(name) {
+            let aggregate: Arc<Primitive> = make_aggregate(prims);
+            aggregates.insert(name.clone(), aggregate.clone());
+            primitives.push(Arc::new(Primitive::Transformed(Box::new(
+                TransformedPrimitive::new(
+                    aggregate,
+                    make_animated_transform(&named_transforms[name], *start, *end),
+                ),
+            ))));
+        } else {
+            primitives.extend(prims.iter().cloned());
+        }
+    }
+    for ginstance in ginstances.iter() {
+        if let Some(prims) = node_primitives.get(&ginstance.node) {
+            let aggregate: Arc<Primitive> = aggregates
+                .entry(ginstance.node.clone())
+                .or_insert_with(|| make_aggregate(prims))
+                .clone();
+            // transform from the space the shapes were created in
+            let node_keys: &Vec<Transform> = &named_transforms[&ginstance.node];
+            let node_is_animated: bool = named_motions.contains_key(&ginstance.node);
+            let (prim_first, prim_last): (Transform, Transform) =
+                match (node_is_animated, ginstance.inherit_xform) {
+                    (true, true) => (node_keys[0], node_keys[node_keys.len() - 1]),
+                    (false, false) => (
+                        Transform::inverse(&node_keys[0]),
+                        Transform::inverse(&node_keys[0]),
+                    ),
+                    _ => (Transform::default(), Transform::default()),
+                };
+            let instance_first: Transform = ginstance.matrices.first().cloned().unwrap_or_default();
+            let instance_last: Transform = ginstance.matrices.last().cloned().unwrap_or_default();
+            let keys: [Transform; 2] = [instance_first * prim_first, instance_last * prim_last];
+            primitives.push(Arc::new(Primitive::Transformed(Box::new(
+                TransformedPrimitive::new(
+                    aggregate,
+                    make_animated_transform(&keys, ginstance.motion_start, ginstance.motion_end),
+                ),
+            ))));
+        } else {
+            println!(
+                "WARNING: ginstance of unknown shape node {:?} ignored",
+                ginstance.node
+            );
+        }
     }
     println!("samples_per_pixel = {:?}", samples_per_pixel);
     println!("number of lights = {:?}", lights.len());
@@ -1182,8 +2226,13 @@ fn main() -> std::io::Result<()> {
         yres,
         fov,
         animated_cam_to_world,
+        shutter_start,
+        shutter_end,
         max_depth,
+        depth_limits,
         samples_per_pixel as i32,
+        outside.clone(),
+        !named_media.is_empty(),
     );
     if let Some(mut integrator) = some_integrator {
         let scene = make_scene(&primitives, lights);
@@ -31,6 +31,7 @@ use pbrt::core::integrator::{Integrator, SamplerIntegrator};
 use pbrt::core::light::Light;
 use pbrt::core::material::Material;
 use pbrt::core::medium::MediumInterface;
+use pbrt::core::microfacet::RoughnessMapping;
 use pbrt::core::mipmap::ImageWrap;
 use pbrt::core::paramset::ParamSet;
 use pbrt::core::pbrt::degrees;
@@ -76,7 +77,7 @@ struct Cli {
     camera_name: Option<String>,
     /// global light scaling
     #[structopt(short = "l", long = "light_scale", default_value = "1.0")]
-    light_scale: f32,
+    light_scale: Float,
     /// pixel samples
     #[structopt(short = "s", long = "samples", default_value = "1")]
     samples: u32,
@@ -97,10 +98,10 @@ struct Cli {
     mutations_per_pixel: u32,
     /// prob of discarding path [MLT]
     #[structopt(long = "step_probability", default_value = "0.3")]
-    step_probability: f32,
+    step_probability: Float,
     /// perturbation deviation [MLT]
     #[structopt(long = "sigma", default_value = "0.01")]
-    sigma: f32,
+    sigma: Float,
     /// frequency to write image [SPPM]
     #[structopt(long = "write_frequency", default_value = "1")]
     write_frequency: i32,
@@ -113,14 +114,14 @@ struct Cli {
 
 #[derive(Debug, Default, Copy, Clone)]
 struct PbrtSphere {
-    pub radius: f32,
-    pub zmin: f32,
-    pub zmax: f32,
-    pub phimax: f32,
+    pub radius: Float,
+    pub zmin: Float,
+    pub zmax: Float,
+    pub phimax: Float,
 }
 
 impl PbrtSphere {
-    fn new(radius: f32, zmin: f32, zmax: f32, phimax: f32) -> Self {
+    fn new(radius: Float, zmin: Float, zmax: Float, phimax: Float) -> Self {
         PbrtSphere {
             radius,
             zmin,
@@ -132,14 +133,14 @@ impl PbrtSphere {
 
 #[derive(Debug, Default, Copy, Clone)]
 struct PbrtCylinder {
-    pub radius: f32,
-    pub zmin: f32,
-    pub zmax: f32,
-    pub phimax: f32,
+    pub radius: Float,
+    pub zmin: Float,
+    pub zmax: Float,
+    pub phimax: Float,
 }
 
 impl PbrtCylinder {
-    fn new(radius: f32, zmin: f32, zmax: f32, phimax: f32) -> Self {
+    fn new(radius: Float, zmin: Float, zmax: Float, phimax: Float) -> Self {
         PbrtCylinder {
             radius,
             zmin,
@@ -151,14 +152,14 @@ impl PbrtCylinder {
 
 #[derive(Debug, Default, Copy, Clone)]
 struct PbrtDisk {
-    pub height: f32,
-    pub radius: f32,
-    pub innerradius: f32,
-    pub phimax: f32,
+    pub height: Float,
+    pub radius: Float,
+    pub innerradius: Float,
+    pub phimax: Float,
 }
 
 impl PbrtDisk {
-    fn new(height: f32, radius: f32, innerradius: f32, phimax: f32) -> Self {
+    fn new(height: Float, radius: Float, innerradius: Float, phimax: Float) -> Self {
         PbrtDisk {
             height,
             radius,
@@ -172,31 +173,31 @@ impl PbrtDisk {
 
 #[derive(Debug, Default, Copy, Clone)]
 struct BlendCamera {
-    pub lens: f32,
-    pub angle_x: f32,
-    pub angle_y: f32,
+    pub lens: Float,
+    pub angle_x: Float,
+    pub angle_y: Float,
 }
 
 #[derive(Debug, Default, Copy, Clone)]
 struct Blend279Material {
-    pub r: f32,
-    pub g: f32,
-    pub b: f32,
-    pub a: f32,
-    pub specr: f32,
-    pub specg: f32,
-    pub specb: f32,
-    pub mirr: f32,
-    pub mirg: f32,
-    pub mirb: f32,
-    pub emit: f32,
-    pub ang: f32, // IOR
-    pub ray_mirror: f32,
-    pub roughness: f32,
+    pub r: Float,
+    pub g: Float,
+    pub b: Float,
+    pub a: Float,
+    pub specr: Float,
+    pub specg: Float,
+    pub specb: Float,
+    pub mirr: Float,
+    pub mirg: Float,
+    pub mirb: Float,
+    pub emit: Float,
+    pub ang: Float, // IOR
+    pub ray_mirror: Float,
+    pub roughness: Float,
 }
 
-fn focallength_to_fov(focal_length: f32, sensor: f32) -> f32 {
-    2.0_f32 * ((sensor / 2.0_f32) / focal_length).atan()
+fn focallength_to_fov(focal_length: Float, sensor: Float) -> Float {
+    2.0 * ((sensor / 2.0) / focal_length).atan()
 }
 
 // TMP (see pbrt_spheres_differentials_texfilt.rs)
@@ -358,7 +359,7 @@ impl SceneDescriptionBuilder {
         &mut self,
         light_to_world: Transform,
         texmap: String,
-        light_scale: f32,
+        light_scale: Float,
     ) -> &mut SceneDescriptionBuilder {
         let l: Spectrum = Spectrum::new(1.0 as Float);
         let sc: Spectrum = Spectrum::new(light_scale as Float);
@@ -376,7 +377,7 @@ impl SceneDescriptionBuilder {
         &mut self,
         light_to_world: Transform,
         l: Spectrum,
-        light_scale: f32,
+        light_scale: Float,
     ) -> &mut SceneDescriptionBuilder {
         let sc: Spectrum = Spectrum::new(light_scale as Float);
         let mut from: Point3f = Point3f {
@@ -406,7 +407,7 @@ impl SceneDescriptionBuilder {
         &mut self,
         light_to_world: Transform,
         l: Spectrum,
-        light_scale: f32,
+        light_scale: Float,
     ) -> &mut SceneDescriptionBuilder {
         let sc: Spectrum = Spectrum::new(light_scale as Float);
         let medium_interface: MediumInterface = MediumInterface::default();
@@ -457,8 +458,8 @@ impl RenderOptions {
         let mut shape_lights: Vec<Option<Arc<Light>>> = Vec::new();
         let mut lights: Vec<Arc<Light>> = Vec::new();
         // default material
-        let kd = Arc::new(ConstantTexture::new(Spectrum::new(1.0)));
-        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
+        let kd = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(1.0))));
+        let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
         let default_material = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
             kd, sigma, None,
         ))));
@@ -499,13 +500,17 @@ impl RenderOptions {
                 } else {
                     if mat.ang != 1.0 {
                         // GlassMaterial
-                        let kr = Arc::new(ConstantTexture::new(Spectrum::new(1.0)));
-                        let kt = Arc::new(ConstantTexture::new(Spectrum::rgb(
+                        let kr =
+                            Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(1.0))));
+                        let kt = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
                             mat.specr, mat.specg, mat.specb,
-                        )));
-                        let u_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let v_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let index = Arc::new(ConstantTexture::new(mat.ang as Float));
+                        ))));
+                        let u_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let v_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let index =
+                            Arc::new(Texture::Constant(ConstantTexture::new(mat.ang as Float)));
                         let glass = Arc::new(Material::Glass(Box::new(GlassMaterial {
                             kr: kr,
                             kt: kt,
@@ -513,7 +518,8 @@ impl RenderOptions {
                             v_roughness: v_roughness,
                             index: index,
                             bump_map: None,
-                            remap_roughness: true,
+                            roughness_mapping: RoughnessMapping::Pbrt,
+                            cauchy_b: 0.0 as Float,
                         })));
                         shapes.push(cylinder.clone());
                         shape_materials.push(glass.clone());
@@ -526,35 +532,38 @@ impl RenderOptions {
                                 &COPPER_N,
                                 COPPER_SAMPLES as i32,
                             );
-                            let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_n));
+                            let eta: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_n)));
                             let copper_k: Spectrum = Spectrum::from_sampled(
                                 &COPPER_WAVELENGTHS,
                                 &COPPER_K,
                                 COPPER_SAMPLES as i32,
                             );
-                            let k: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_k));
-                            let remap_roughness: bool = true;
+                            let k: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_k)));
+                            let roughness_mapping: RoughnessMapping = RoughnessMapping::Pbrt;
                             let metal = Arc::new(Material::Metal(Box::new(MetalMaterial::new(
                                 eta,
                                 k,
-                                Arc::new(ConstantTexture::new(mat.roughness as Float)),
+                                Arc::new(Texture::Constant(ConstantTexture::new(
+                                    mat.roughness as Float,
+                                ))),
                                 None,
                                 None,
                                 None,
-                                remap_roughness,
+                                roughness_mapping,
                             ))));
                             shapes.push(cylinder.clone());
                             shape_materials.push(metal.clone());
                             shape_lights.push(None);
                         } else {
                             // MirrorMaterial
-                            let kr = Arc::new(ConstantTexture::new(Spectrum::rgb(
-                                mat.mirr * mat.ray_mirror,
-                                mat.mirg * mat.ray_mirror,
-                                mat.mirb * mat.ray_mirror,
-                            )));
+                            let kr =
+                                Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
+                                    mat.mirr * mat.ray_mirror,
+                                    mat.mirg * mat.ray_mirror,
+                                    mat.mirb * mat.ray_mirror,
+                                ))));
                             let mirror =
                                 Arc::new(Material::Mirror(Box::new(MirrorMaterial::new(kr, None))));
                             shapes.push(cylinder.clone());
@@ -563,8 +572,9 @@ impl RenderOptions {
                         }
                     } else {
                         // MatteMaterial
-                        let mut kd: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                            Arc::new(ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)));
+                        let mut kd: Arc<Texture<Spectrum>> = Arc::new(Texture::Constant(
+                            ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)),
+                        ));
                         if let Some(tex) = texture_hm.get(cylinder_name) {
                             // first try texture with exactly the same name as the mesh
                             let su: Float = 1.0;
@@ -584,7 +594,7 @@ impl RenderOptions {
                             let wrap_mode: ImageWrap = ImageWrap::Repeat;
                             let scale: Float = 1.0;
                             let gamma: bool = true;
-                            kd = Arc::new(ImageTexture::new(
+                            kd = Arc::new(Texture::Image(ImageTexture::new(
                                 mapping,
                                 filename,
                                 do_trilinear,
@@ -593,7 +603,7 @@ impl RenderOptions {
                                 scale,
                                 gamma,
                                 convert_to_spectrum,
-                            ));
+                            )));
                         } else {
                             // then remove trailing digits from mesh name
                             let mut ntd: String = String::new();
@@ -631,7 +641,7 @@ impl RenderOptions {
                                 let wrap_mode: ImageWrap = ImageWrap::Repeat;
                                 let scale: Float = 1.0;
                                 let gamma: bool = true;
-                                kd = Arc::new(ImageTexture::new(
+                                kd = Arc::new(Texture::Image(ImageTexture::new(
                                     mapping,
                                     filename,
                                     do_trilinear,
@@ -640,10 +650,10 @@ impl RenderOptions {
                                     scale,
                                     gamma,
                                     convert_to_spectrum,
-                                ));
+                                )));
                             }
                         }
-                        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
+                        let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
                         let matte = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
                             kd,
                             sigma.clone(),
@@ -689,13 +699,17 @@ impl RenderOptions {
                 } else {
                     if mat.ang != 1.0 {
                         // GlassMaterial
-                        let kr = Arc::new(ConstantTexture::new(Spectrum::new(1.0)));
-                        let kt = Arc::new(ConstantTexture::new(Spectrum::rgb(
+                        let kr =
+                            Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(1.0))));
+                        let kt = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
                             mat.specr, mat.specg, mat.specb,
-                        )));
-                        let u_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let v_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let index = Arc::new(ConstantTexture::new(mat.ang as Float));
+                        ))));
+                        let u_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let v_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let index =
+                            Arc::new(Texture::Constant(ConstantTexture::new(mat.ang as Float)));
                         let glass = Arc::new(Material::Glass(Box::new(GlassMaterial {
                             kr: kr,
                             kt: kt,
@@ -703,7 +717,8 @@ impl RenderOptions {
                             v_roughness: v_roughness,
                             index: index,
                             bump_map: None,
-                            remap_roughness: true,
+                            roughness_mapping: RoughnessMapping::Pbrt,
+                            cauchy_b: 0.0 as Float,
                         })));
                         shapes.push(disk.clone());
                         shape_materials.push(glass.clone());
@@ -716,35 +731,38 @@ impl RenderOptions {
                                 &COPPER_N,
                                 COPPER_SAMPLES as i32,
                             );
-                            let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_n));
+                            let eta: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_n)));
                             let copper_k: Spectrum = Spectrum::from_sampled(
                                 &COPPER_WAVELENGTHS,
                                 &COPPER_K,
                                 COPPER_SAMPLES as i32,
                             );
-                            let k: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_k));
-                            let remap_roughness: bool = true;
+                            let k: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_k)));
+                            let roughness_mapping: RoughnessMapping = RoughnessMapping::Pbrt;
                             let metal = Arc::new(Material::Metal(Box::new(MetalMaterial::new(
                                 eta,
                                 k,
-                                Arc::new(ConstantTexture::new(mat.roughness as Float)),
+                                Arc::new(Texture::Constant(ConstantTexture::new(
+                                    mat.roughness as Float,
+                                ))),
                                 None,
                                 None,
                                 None,
-                                remap_roughness,
+                                roughness_mapping,
                             ))));
                             shapes.push(disk.clone());
                             shape_materials.push(metal.clone());
                             shape_lights.push(None);
                         } else {
                             // MirrorMaterial
-                            let kr = Arc::new(ConstantTexture::new(Spectrum::rgb(
-                                mat.mirr * mat.ray_mirror,
-                                mat.mirg * mat.ray_mirror,
-                                mat.mirb * mat.ray_mirror,
-                            )));
+                            let kr =
+                                Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
+                                    mat.mirr * mat.ray_mirror,
+                                    mat.mirg * mat.ray_mirror,
+                                    mat.mirb * mat.ray_mirror,
+                                ))));
                             let mirror =
                                 Arc::new(Material::Mirror(Box::new(MirrorMaterial::new(kr, None))));
                             shapes.push(disk.clone());
@@ -753,8 +771,9 @@ impl RenderOptions {
                         }
                     } else {
                         // MatteMaterial
-                        let mut kd: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                            Arc::new(ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)));
+                        let mut kd: Arc<Texture<Spectrum>> = Arc::new(Texture::Constant(
+                            ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)),
+                        ));
                         if let Some(tex) = texture_hm.get(disk_name) {
                             // first try texture with exactly the same name as the mesh
                             let su: Float = 1.0;
@@ -774,7 +793,7 @@ impl RenderOptions {
                             let wrap_mode: ImageWrap = ImageWrap::Repeat;
                             let scale: Float = 1.0;
                             let gamma: bool = true;
-                            kd = Arc::new(ImageTexture::new(
+                            kd = Arc::new(Texture::Image(ImageTexture::new(
                                 mapping,
                                 filename,
                                 do_trilinear,
@@ -783,7 +802,7 @@ impl RenderOptions {
                                 scale,
                                 gamma,
                                 convert_to_spectrum,
-                            ));
+                            )));
                         } else {
                             // then remove trailing digits from mesh name
                             let mut ntd: String = String::new();
@@ -821,7 +840,7 @@ impl RenderOptions {
                                 let wrap_mode: ImageWrap = ImageWrap::Repeat;
                                 let scale: Float = 1.0;
                                 let gamma: bool = true;
-                                kd = Arc::new(ImageTexture::new(
+                                kd = Arc::new(Texture::Image(ImageTexture::new(
                                     mapping,
                                     filename,
                                     do_trilinear,
@@ -830,10 +849,10 @@ impl RenderOptions {
                                     scale,
                                     gamma,
                                     convert_to_spectrum,
-                                ));
+                                )));
                             }
                         }
-                        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
+                        let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
                         let matte = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
                             kd,
                             sigma.clone(),
@@ -879,13 +898,17 @@ impl RenderOptions {
                 } else {
                     if mat.ang != 1.0 {
                         // GlassMaterial
-                        let kr = Arc::new(ConstantTexture::new(Spectrum::new(1.0)));
-                        let kt = Arc::new(ConstantTexture::new(Spectrum::rgb(
+                        let kr =
+                            Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(1.0))));
+                        let kt = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
                             mat.specr, mat.specg, mat.specb,
-                        )));
-                        let u_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let v_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let index = Arc::new(ConstantTexture::new(mat.ang as Float));
+                        ))));
+                        let u_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let v_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let index =
+                            Arc::new(Texture::Constant(ConstantTexture::new(mat.ang as Float)));
                         let glass = Arc::new(Material::Glass(Box::new(GlassMaterial {
                             kr: kr,
                             kt: kt,
@@ -893,7 +916,8 @@ impl RenderOptions {
                             v_roughness: v_roughness,
                             index: index,
                             bump_map: None,
-                            remap_roughness: true,
+                            roughness_mapping: RoughnessMapping::Pbrt,
+                            cauchy_b: 0.0 as Float,
                         })));
                         shapes.push(sphere.clone());
                         shape_materials.push(glass.clone());
@@ -906,35 +930,38 @@ impl RenderOptions {
                                 &COPPER_N,
                                 COPPER_SAMPLES as i32,
                             );
-                            let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_n));
+                            let eta: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_n)));
                             let copper_k: Spectrum = Spectrum::from_sampled(
                                 &COPPER_WAVELENGTHS,
                                 &COPPER_K,
                                 COPPER_SAMPLES as i32,
                             );
-                            let k: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_k));
-                            let remap_roughness: bool = true;
+                            let k: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_k)));
+                            let roughness_mapping: RoughnessMapping = RoughnessMapping::Pbrt;
                             let metal = Arc::new(Material::Metal(Box::new(MetalMaterial::new(
                                 eta,
                                 k,
-                                Arc::new(ConstantTexture::new(mat.roughness as Float)),
+                                Arc::new(Texture::Constant(ConstantTexture::new(
+                                    mat.roughness as Float,
+                                ))),
                                 None,
                                 None,
                                 None,
-                                remap_roughness,
+                                roughness_mapping,
                             ))));
                             shapes.push(sphere.clone());
                             shape_materials.push(metal.clone());
                             shape_lights.push(None);
                         } else {
                             // MirrorMaterial
-                            let kr = Arc::new(ConstantTexture::new(Spectrum::rgb(
-                                mat.mirr * mat.ray_mirror,
-                                mat.mirg * mat.ray_mirror,
-                                mat.mirb * mat.ray_mirror,
-                            )));
+                            let kr =
+                                Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
+                                    mat.mirr * mat.ray_mirror,
+                                    mat.mirg * mat.ray_mirror,
+                                    mat.mirb * mat.ray_mirror,
+                                ))));
                             let mirror =
                                 Arc::new(Material::Mirror(Box::new(MirrorMaterial::new(kr, None))));
                             shapes.push(sphere.clone());
@@ -943,8 +970,9 @@ impl RenderOptions {
                         }
                     } else {
                         // MatteMaterial
-                        let mut kd: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                            Arc::new(ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)));
+                        let mut kd: Arc<Texture<Spectrum>> = Arc::new(Texture::Constant(
+                            ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)),
+                        ));
                         if let Some(tex) = texture_hm.get(sphere_name) {
                             // first try texture with exactly the same name as the mesh
                             let su: Float = 1.0;
@@ -964,7 +992,7 @@ impl RenderOptions {
                             let wrap_mode: ImageWrap = ImageWrap::Repeat;
                             let scale: Float = 1.0;
                             let gamma: bool = true;
-                            kd = Arc::new(ImageTexture::new(
+                            kd = Arc::new(Texture::Image(ImageTexture::new(
                                 mapping,
                                 filename,
                                 do_trilinear,
@@ -973,7 +1001,7 @@ impl RenderOptions {
                                 scale,
                                 gamma,
                                 convert_to_spectrum,
-                            ));
+                            )));
                         } else {
                             // then remove trailing digits from mesh name
                             let mut ntd: String = String::new();
@@ -1011,7 +1039,7 @@ impl RenderOptions {
                                 let wrap_mode: ImageWrap = ImageWrap::Repeat;
                                 let scale: Float = 1.0;
                                 let gamma: bool = true;
-                                kd = Arc::new(ImageTexture::new(
+                                kd = Arc::new(Texture::Image(ImageTexture::new(
                                     mapping,
                                     filename,
                                     do_trilinear,
@@ -1020,10 +1048,10 @@ impl RenderOptions {
                                     scale,
                                     gamma,
                                     convert_to_spectrum,
-                                ));
+                                )));
                             }
                         }
-                        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
+                        let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
                         let matte = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
                             kd,
                             sigma.clone(),
@@ -1085,13 +1113,17 @@ impl RenderOptions {
                 } else {
                     if mat.ang != 1.0 {
                         // GlassMaterial
-                        let kr = Arc::new(ConstantTexture::new(Spectrum::new(1.0)));
-                        let kt = Arc::new(ConstantTexture::new(Spectrum::rgb(
+                        let kr =
+                            Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(1.0))));
+                        let kt = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
                             mat.specr, mat.specg, mat.specb,
-                        )));
-                        let u_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let v_roughness = Arc::new(ConstantTexture::new(0.0 as Float));
-                        let index = Arc::new(ConstantTexture::new(mat.ang as Float));
+                        ))));
+                        let u_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let v_roughness =
+                            Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+                        let index =
+                            Arc::new(Texture::Constant(ConstantTexture::new(mat.ang as Float)));
                         let glass = Arc::new(Material::Glass(Box::new(GlassMaterial {
                             kr: kr,
                             kt: kt,
@@ -1099,7 +1131,8 @@ impl RenderOptions {
                             v_roughness: v_roughness,
                             index: index,
                             bump_map: None,
-                            remap_roughness: true,
+                            roughness_mapping: RoughnessMapping::Pbrt,
+                            cauchy_b: 0.0 as Float,
                         })));
                         for _i in 0..triangles.len() {
                             shape_materials.push(glass.clone());
@@ -1113,24 +1146,26 @@ impl RenderOptions {
                                 &COPPER_N,
                                 COPPER_SAMPLES as i32,
                             );
-                            let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_n));
+                            let eta: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_n)));
                             let copper_k: Spectrum = Spectrum::from_sampled(
                                 &COPPER_WAVELENGTHS,
                                 &COPPER_K,
                                 COPPER_SAMPLES as i32,
                             );
-                            let k: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                                Arc::new(ConstantTexture::new(copper_k));
-                            let remap_roughness: bool = true;
+                            let k: Arc<Texture<Spectrum>> =
+                                Arc::new(Texture::Constant(ConstantTexture::new(copper_k)));
+                            let roughness_mapping: RoughnessMapping = RoughnessMapping::Pbrt;
                             let metal = Arc::new(Material::Metal(Box::new(MetalMaterial::new(
                                 eta,
                                 k,
-                                Arc::new(ConstantTexture::new(mat.roughness as Float)),
+                                Arc::new(Texture::Constant(ConstantTexture::new(
+                                    mat.roughness as Float,
+                                ))),
                                 None,
                                 None,
                                 None,
-                                remap_roughness,
+                                roughness_mapping,
                             ))));
                             for _i in 0..triangles.len() {
                                 shape_materials.push(metal.clone());
@@ -1138,11 +1173,12 @@ impl RenderOptions {
                             }
                         } else {
                             // MirrorMaterial
-                            let kr = Arc::new(ConstantTexture::new(Spectrum::rgb(
-                                mat.mirr * mat.ray_mirror,
-                                mat.mirg * mat.ray_mirror,
-                                mat.mirb * mat.ray_mirror,
-                            )));
+                            let kr =
+                                Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::rgb(
+                                    mat.mirr * mat.ray_mirror,
+                                    mat.mirg * mat.ray_mirror,
+                                    mat.mirb * mat.ray_mirror,
+                                ))));
                             let mirror =
                                 Arc::new(Material::Mirror(Box::new(MirrorMaterial::new(kr, None))));
                             for _i in 0..triangles.len() {
@@ -1152,8 +1188,9 @@ impl RenderOptions {
                         }
                     } else {
                         // MatteMaterial
-                        let mut kd: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                            Arc::new(ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)));
+                        let mut kd: Arc<Texture<Spectrum>> = Arc::new(Texture::Constant(
+                            ConstantTexture::new(Spectrum::rgb(mat.r, mat.g, mat.b)),
+                        ));
                         if let Some(tex) = texture_hm.get(mesh_name) {
                             // first try texture with exactly the same name as the mesh
                             let su: Float = 1.0;
@@ -1173,7 +1210,7 @@ impl RenderOptions {
                             let wrap_mode: ImageWrap = ImageWrap::Repeat;
                             let scale: Float = 1.0;
                             let gamma: bool = true;
-                            kd = Arc::new(ImageTexture::new(
+                            kd = Arc::new(Texture::Image(ImageTexture::new(
                                 mapping,
                                 filename,
                                 do_trilinear,
@@ -1182,7 +1219,7 @@ impl RenderOptions {
                                 scale,
                                 gamma,
                                 convert_to_spectrum,
-                            ));
+                            )));
                         } else {
                             // then remove trailing digits from mesh name
                             let mut ntd: String = String::new();
@@ -1220,7 +1257,7 @@ impl RenderOptions {
                                 let wrap_mode: ImageWrap = ImageWrap::Repeat;
                                 let scale: Float = 1.0;
                                 let gamma: bool = true;
-                                kd = Arc::new(ImageTexture::new(
+                                kd = Arc::new(Texture::Image(ImageTexture::new(
                                     mapping,
                                     filename,
                                     do_trilinear,
@@ -1229,10 +1266,10 @@ impl RenderOptions {
                                     scale,
                                     gamma,
                                     convert_to_spectrum,
-                                ));
+                                )));
                             }
                         }
-                        let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
+                        let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
                         let mut matte = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
                             kd,
                             sigma.clone(),
@@ -1243,7 +1280,9 @@ impl RenderOptions {
                             // ignore textures, use triangle colors
                             for i in 0..triangles.len() {
                                 // overwrite kd
-                                kd = Arc::new(ConstantTexture::new(triangle_colors[i]));
+                                kd = Arc::new(Texture::Constant(ConstantTexture::new(
+                                    triangle_colors[i],
+                                )));
                                 matte = Arc::new(Material::Matte(Box::new(MatteMaterial::new(
                                     kd,
                                     sigma.clone(),
@@ -1668,18 +1707,25 @@ pub fn make_perspective_camera(
     let mut filter_params: ParamSet = ParamSet::default();
     filter_params.add_float(String::from("xwidth"), filter_width);
     filter_params.add_float(String::from("ywidth"), filter_width);
-    let some_filter = make_filter(&String::from("gaussian"), &filter_params);
+    let some_filter = make_filter(&String::from("gaussian"), &filter_params).ok();
     if let Some(filter) = some_filter {
         let film_name: String = String::from("image");
         let mut film_params: ParamSet = ParamSet::default();
         film_params.add_int(String::from("xresolution"), xres);
         film_params.add_int(String::from("yresolution"), yres);
-        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter);
+        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter).ok();
         if let Some(film) = some_film {
             let camera_name: String = String::from("perspective");
             let mut camera_params: ParamSet = ParamSet::default();
             camera_params.add_float(String::from("fov"), fov);
-            some_camera = make_camera(&camera_name, &camera_params, animated_cam_to_world, film);
+            some_camera = make_camera(
+                &camera_name,
+                &camera_params,
+                animated_cam_to_world,
+                film,
+                &MediumInterface::default(),
+            )
+            .ok();
         }
     }
     some_camera
@@ -1703,7 +1749,7 @@ fn make_integrator(
         let mut sampler_params: ParamSet = ParamSet::default();
         sampler_params.add_int(String::from("pixelsamples"), pixelsamples);
         let some_sampler: Option<Box<Sampler>> =
-            make_sampler(&sampler_name, &sampler_params, camera.get_film());
+            make_sampler(&sampler_name, &sampler_params, camera.get_film()).ok();
         if let Some(sampler) = some_sampler {
             print!("integrator = {:?} [", integrator_name);
             if integrator_name == "whitted" {
@@ -1835,8 +1881,19 @@ fn make_integrator(
                 let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
                 let cos_sample: bool = integrator_params.find_one_bool("cossample", true);
                 let n_samples: i32 = integrator_params.find_one_int("nsamples", 64 as i32);
+                let max_distance: Float =
+                    integrator_params.find_one_float("maxdistance", Float::INFINITY);
+                let gpu: bool = integrator_params.find_one_bool("gpu", false);
                 let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::AO(
-                    AOIntegrator::new(cos_sample, n_samples, camera, sampler, pixel_bounds),
+                    AOIntegrator::new(
+                        cos_sample,
+                        n_samples,
+                        max_distance,
+                        gpu,
+                        camera,
+                        sampler,
+                        pixel_bounds,
+                    ),
                 )));
                 some_integrator = Some(integrator);
             } else if integrator_name == "sppm" {
@@ -1876,7 +1933,8 @@ fn make_integrator(
 
 fn make_scene(primitives: &Vec<Arc<Primitive>>, lights: Vec<Arc<Light>>) -> Scene {
     let accelerator_name: String = String::from("bvh");
-    let some_accelerator = make_accelerator(&accelerator_name, &primitives, &ParamSet::default());
+    let some_accelerator =
+        make_accelerator(&accelerator_name, &primitives, &ParamSet::default()).ok();
     if let Some(accelerator) = some_accelerator {
         return Scene::new(accelerator, lights);
     } else {
@@ -1892,12 +1950,12 @@ fn main() -> std::io::Result<()> {
         VERSION, num_threads
     );
     // PBRT
-    let mut scale_length: f32 = 1.0;
+    let mut scale_length: Float = 1.0;
     let mut resolution_x: u32 = 640;
     let mut resolution_y: u32 = 480;
     let mut resolution_percentage: u16 = 100;
-    let mut angle_x: f32 = 45.0;
-    let mut angle_y: f32 = 45.0;
+    let mut angle_x: Float = 45.0;
+    let mut angle_y: Float = 45.0;
     let mut base_name = String::new();
     let mut camera_hm: HashMap<String, BlendCamera> = HashMap::new();
     let mut texture_hm: HashMap<String, OsString> = HashMap::new();
@@ -2229,28 +2287,28 @@ fn main() -> std::io::Result<()> {
                         if base_name.starts_with("PbrtSphere") {
                             // store sphere values for later
                             let pbrt_sphere: PbrtSphere = PbrtSphere::new(
-                                prop_radius as f32,
-                                prop_zmin as f32,
-                                prop_zmax as f32,
-                                prop_phimax as f32,
+                                prop_radius as Float,
+                                prop_zmin as Float,
+                                prop_zmax as Float,
+                                prop_phimax as Float,
                             );
                             spheres_hm.insert(base_name.clone(), pbrt_sphere);
                         } else if base_name.starts_with("PbrtCylinder") {
                             // store cylinder values for later
                             let pbrt_cylinder: PbrtCylinder = PbrtCylinder::new(
-                                prop_radius as f32,
-                                prop_zmin as f32,
-                                prop_zmax as f32,
-                                prop_phimax as f32,
+                                prop_radius as Float,
+                                prop_zmin as Float,
+                                prop_zmax as Float,
+                                prop_phimax as Float,
                             );
                             cylinders_hm.insert(base_name.clone(), pbrt_cylinder);
                         } else if base_name.starts_with("PbrtDisk") {
                             // store disk values for later
                             let pbrt_disk: PbrtDisk = PbrtDisk::new(
-                                prop_height as f32,
-                                prop_radius as f32,
-                                prop_innerradius as f32,
-                                prop_phimax as f32,
+                                prop_height as Float,
+                                prop_radius as Float,
+                                prop_innerradius as Float,
+                                prop_phimax as Float,
                             );
                             disks_hm.insert(base_name.clone(), pbrt_disk);
                         }
@@ -2423,28 +2481,28 @@ fn main() -> std::io::Result<()> {
                             if base_name.starts_with("PbrtSphere") {
                                 // store sphere values for later
                                 let pbrt_sphere: PbrtSphere = PbrtSphere::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 spheres_hm.insert(base_name.clone(), pbrt_sphere);
                             } else if base_name.starts_with("PbrtCylinder") {
                                 // store cylinder values for later
                                 let pbrt_cylinder: PbrtCylinder = PbrtCylinder::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 cylinders_hm.insert(base_name.clone(), pbrt_cylinder);
                             } else if base_name.starts_with("PbrtDisk") {
                                 // store disk values for later
                                 let pbrt_disk: PbrtDisk = PbrtDisk::new(
-                                    prop_height as f32,
-                                    prop_radius as f32,
-                                    prop_innerradius as f32,
-                                    prop_phimax as f32,
+                                    prop_height as Float,
+                                    prop_radius as Float,
+                                    prop_innerradius as Float,
+                                    prop_phimax as Float,
                                 );
                                 disks_hm.insert(base_name.clone(), pbrt_disk);
                             }
@@ -2559,7 +2617,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 rot_buf[i] = buffer[skip_bytes + i];
                             }
-                            let _rot: f32 = unsafe { mem::transmute(rot_buf) };
+                            let _rot: Float = unsafe { mem::transmute::<[u8; 4], f32>(rot_buf) } as Float;
                             // println!("  rot[{}] = {}", i, rot);
                             skip_bytes += 4;
                         }
@@ -2579,20 +2637,20 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             rot_angle_buf[i] = buffer[skip_bytes + i];
                         }
-                        let _rot_angle: f32 = unsafe { mem::transmute(rot_angle_buf) };
+                        let _rot_angle: Float = unsafe { mem::transmute::<[u8; 4], f32>(rot_angle_buf) } as Float;
                         // println!("  rot_angle = {}", rot_angle);
                         skip_bytes += 4;
                         // drotAngle
                         skip_bytes += 4;
                         // obmat
-                        let mut mat_values: [f32; 16] = [0.0_f32; 16];
+                        let mut mat_values: [Float; 16] = [0.0; 16];
                         for i in 0..4 {
                             for j in 0..4 {
                                 let mut obmat_buf: [u8; 4] = [0_u8; 4];
                                 for i in 0..4 as usize {
                                     obmat_buf[i] = buffer[skip_bytes + i];
                                 }
-                                let obmat: f32 = unsafe { mem::transmute(obmat_buf) };
+                                let obmat: Float = unsafe { mem::transmute::<[u8; 4], f32>(obmat_buf) } as Float;
                                 // println!("  obmat[{}][{}] = {}", i, j, obmat);
                                 mat_values[i * 4 + j] = obmat;
                                 skip_bytes += 4;
@@ -2624,7 +2682,7 @@ fn main() -> std::io::Result<()> {
                                 for i in 0..4 as usize {
                                     parentinv_buf[i] = buffer[skip_bytes + i];
                                 }
-                                let _parentinv: f32 = unsafe { mem::transmute(parentinv_buf) };
+                                let _parentinv: Float = unsafe { mem::transmute::<[u8; 4], f32>(parentinv_buf) } as Float;
                                 // println!("  parentinv[{}][{}] = {}", i, j, parentinv);
                                 skip_bytes += 4;
                             }
@@ -2636,7 +2694,7 @@ fn main() -> std::io::Result<()> {
                                 for i in 0..4 as usize {
                                     constinv_buf[i] = buffer[skip_bytes + i];
                                 }
-                                let _constinv: f32 = unsafe { mem::transmute(constinv_buf) };
+                                let _constinv: Float = unsafe { mem::transmute::<[u8; 4], f32>(constinv_buf) } as Float;
                                 // println!("  constinv[{}][{}] = {}", i, j, constinv);
                                 skip_bytes += 4;
                             }
@@ -2648,7 +2706,7 @@ fn main() -> std::io::Result<()> {
                                 for i in 0..4 as usize {
                                     imat_buf[i] = buffer[skip_bytes + i];
                                 }
-                                let _imat: f32 = unsafe { mem::transmute(imat_buf) };
+                                let _imat: Float = unsafe { mem::transmute::<[u8; 4], f32>(imat_buf) } as Float;
                                 // println!("  imat[{}][{}] = {}", i, j, imat);
                                 skip_bytes += 4;
                             }
@@ -2660,7 +2718,7 @@ fn main() -> std::io::Result<()> {
                                 for i in 0..4 as usize {
                                     imat_ren_buf[i] = buffer[skip_bytes + i];
                                 }
-                                let _imat_ren: f32 = unsafe { mem::transmute(imat_ren_buf) };
+                                let _imat_ren: Float = unsafe { mem::transmute::<[u8; 4], f32>(imat_ren_buf) } as Float;
                                 // println!("  imat_ren[{}][{}] = {}", i, j, imat_ren);
                                 skip_bytes += 4;
                             }
@@ -2674,28 +2732,28 @@ fn main() -> std::io::Result<()> {
                             if base_name.starts_with("PbrtSphere") {
                                 // store sphere values for later
                                 let pbrt_sphere: PbrtSphere = PbrtSphere::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 spheres_hm.insert(base_name.clone(), pbrt_sphere);
                             } else if base_name.starts_with("PbrtCylinder") {
                                 // store cylinder values for later
                                 let pbrt_cylinder: PbrtCylinder = PbrtCylinder::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 cylinders_hm.insert(base_name.clone(), pbrt_cylinder);
                             } else if base_name.starts_with("PbrtDisk") {
                                 // store disk values for later
                                 let pbrt_disk: PbrtDisk = PbrtDisk::new(
-                                    prop_height as f32,
-                                    prop_radius as f32,
-                                    prop_innerradius as f32,
-                                    prop_phimax as f32,
+                                    prop_height as Float,
+                                    prop_radius as Float,
+                                    prop_innerradius as Float,
+                                    prop_phimax as Float,
                                 );
                                 disks_hm.insert(base_name.clone(), pbrt_disk);
                             }
@@ -3117,7 +3175,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             scale_length_buf[i] = buffer[skip_bytes + i];
                         }
-                        scale_length = unsafe { mem::transmute(scale_length_buf) };
+                        scale_length = unsafe { mem::transmute::<[u8; 4], f32>(scale_length_buf) } as Float;
                         // println!("    scale_length = {}", scale_length);
                         // skip_bytes += 4;
                         // reset booleans
@@ -3180,7 +3238,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             lens_buf[i] = buffer[skip_bytes + i];
                         }
-                        let lens: f32 = unsafe { mem::transmute(lens_buf) };
+                        let lens: Float = unsafe { mem::transmute::<[u8; 4], f32>(lens_buf) } as Float;
                         // println!("  lens = {}", lens);
                         skip_bytes += 4;
                         // ortho_scale
@@ -3204,7 +3262,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             sensor_x_buf[i] = buffer[skip_bytes + i];
                         }
-                        let sensor_x: f32 = unsafe { mem::transmute(sensor_x_buf) };
+                        let sensor_x: Float = unsafe { mem::transmute::<[u8; 4], f32>(sensor_x_buf) } as Float;
                         // println!("  sensor_x = {}", sensor_x);
                         skip_bytes += 4;
                         // sensor_y
@@ -3212,7 +3270,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             sensor_y_buf[i] = buffer[skip_bytes + i];
                         }
-                        let sensor_y: f32 = unsafe { mem::transmute(sensor_y_buf) };
+                        let sensor_y: Float = unsafe { mem::transmute::<[u8; 4], f32>(sensor_y_buf) } as Float;
                         // println!("  sensor_y = {}", sensor_y);
                         // skip_bytes += 4;
                         // calculate angle_x and angle_y
@@ -3251,28 +3309,28 @@ fn main() -> std::io::Result<()> {
                             if base_name.starts_with("PbrtSphere") {
                                 // store sphere values for later
                                 let pbrt_sphere: PbrtSphere = PbrtSphere::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 spheres_hm.insert(base_name.clone(), pbrt_sphere);
                             } else if base_name.starts_with("PbrtCylinder") {
                                 // store cylinder values for later
                                 let pbrt_cylinder: PbrtCylinder = PbrtCylinder::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 cylinders_hm.insert(base_name.clone(), pbrt_cylinder);
                             } else if base_name.starts_with("PbrtDisk") {
                                 // store disk values for later
                                 let pbrt_disk: PbrtDisk = PbrtDisk::new(
-                                    prop_height as f32,
-                                    prop_radius as f32,
-                                    prop_innerradius as f32,
-                                    prop_phimax as f32,
+                                    prop_height as Float,
+                                    prop_radius as Float,
+                                    prop_innerradius as Float,
+                                    prop_phimax as Float,
                                 );
                                 disks_hm.insert(base_name.clone(), pbrt_disk);
                             }
@@ -3401,7 +3459,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 r_buf[i] = buffer[skip_bytes + i];
                             }
-                            let r: f32 = unsafe { mem::transmute(r_buf) };
+                            let r: Float = unsafe { mem::transmute::<[u8; 4], f32>(r_buf) } as Float;
                             // println!("  r = {}", r);
                             skip_bytes += 4;
                             // g
@@ -3409,7 +3467,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 g_buf[i] = buffer[skip_bytes + i];
                             }
-                            let g: f32 = unsafe { mem::transmute(g_buf) };
+                            let g: Float = unsafe { mem::transmute::<[u8; 4], f32>(g_buf) } as Float;
                             // println!("  g = {}", g);
                             skip_bytes += 4;
                             // b
@@ -3417,7 +3475,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 b_buf[i] = buffer[skip_bytes + i];
                             }
-                            let b: f32 = unsafe { mem::transmute(b_buf) };
+                            let b: Float = unsafe { mem::transmute::<[u8; 4], f32>(b_buf) } as Float;
                             // println!("  b = {}", b);
                             skip_bytes += 4;
                             // specr
@@ -3425,7 +3483,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specr_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specr: f32 = unsafe { mem::transmute(specr_buf) };
+                            let specr: Float = unsafe { mem::transmute::<[u8; 4], f32>(specr_buf) } as Float;
                             // println!("  specr = {}", specr);
                             skip_bytes += 4;
                             // specg
@@ -3433,7 +3491,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specg_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specg: f32 = unsafe { mem::transmute(specg_buf) };
+                            let specg: Float = unsafe { mem::transmute::<[u8; 4], f32>(specg_buf) } as Float;
                             // println!("  specg = {}", specg);
                             skip_bytes += 4;
                             // specb
@@ -3441,7 +3499,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specb_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specb: f32 = unsafe { mem::transmute(specb_buf) };
+                            let specb: Float = unsafe { mem::transmute::<[u8; 4], f32>(specb_buf) } as Float;
                             // println!("  specb = {}", specb);
                             skip_bytes += 4;
                             // mirr
@@ -3449,7 +3507,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 mirr_buf[i] = buffer[skip_bytes + i];
                             }
-                            let mirr: f32 = unsafe { mem::transmute(mirr_buf) };
+                            let mirr: Float = unsafe { mem::transmute::<[u8; 4], f32>(mirr_buf) } as Float;
                             // println!("  mirr = {}", mirr);
                             skip_bytes += 4;
                             // mirg
@@ -3457,7 +3515,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 mirg_buf[i] = buffer[skip_bytes + i];
                             }
-                            let mirg: f32 = unsafe { mem::transmute(mirg_buf) };
+                            let mirg: Float = unsafe { mem::transmute::<[u8; 4], f32>(mirg_buf) } as Float;
                             // println!("  mirg = {}", mirg);
                             skip_bytes += 4;
                             // mirb
@@ -3465,7 +3523,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 mirb_buf[i] = buffer[skip_bytes + i];
                             }
-                            let mirb: f32 = unsafe { mem::transmute(mirb_buf) };
+                            let mirb: Float = unsafe { mem::transmute::<[u8; 4], f32>(mirb_buf) } as Float;
                             // println!("  mirb = {}", mirb);
                             skip_bytes += 4;
                             // ambr, ambg, ambb
@@ -3477,7 +3535,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 emit_buf[i] = buffer[skip_bytes + i];
                             }
-                            let emit: f32 = unsafe { mem::transmute(emit_buf) };
+                            let emit: Float = unsafe { mem::transmute::<[u8; 4], f32>(emit_buf) } as Float;
                             // println!("  emit = {}", emit);
                             skip_bytes += 4;
                             // ang (called "IOR" in Blender's UI)
@@ -3485,7 +3543,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 ang_buf[i] = buffer[skip_bytes + i];
                             }
-                            let ang: f32 = unsafe { mem::transmute(ang_buf) };
+                            let ang: Float = unsafe { mem::transmute::<[u8; 4], f32>(ang_buf) } as Float;
                             // println!("  ang = {}", ang);
                             skip_bytes += 4;
                             // spectra
@@ -3495,7 +3553,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 ray_mirror_buf[i] = buffer[skip_bytes + i];
                             }
-                            let ray_mirror: f32 = unsafe { mem::transmute(ray_mirror_buf) };
+                            let ray_mirror: Float = unsafe { mem::transmute::<[u8; 4], f32>(ray_mirror_buf) } as Float;
                             // println!("  ray_mirror = {}", ray_mirror);
                             skip_bytes += 4;
                             // alpha, ref, spec, zoffs, add, translucency
@@ -3543,7 +3601,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 roughness_buf[i] = buffer[skip_bytes + i];
                             }
-                            let roughness: f32 = unsafe { mem::transmute(roughness_buf) };
+                            let roughness: Float = unsafe { mem::transmute::<[u8; 4], f32>(roughness_buf) } as Float;
                             // println!("  roughness = {}", roughness);
                             // skip_bytes += 4;
                             // Blend279Material
@@ -3575,7 +3633,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 r_buf[i] = buffer[skip_bytes + i];
                             }
-                            let r: f32 = unsafe { mem::transmute(r_buf) };
+                            let r: Float = unsafe { mem::transmute::<[u8; 4], f32>(r_buf) } as Float;
                             // println!("  r = {}", r);
                             skip_bytes += 4;
                             // g
@@ -3583,7 +3641,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 g_buf[i] = buffer[skip_bytes + i];
                             }
-                            let g: f32 = unsafe { mem::transmute(g_buf) };
+                            let g: Float = unsafe { mem::transmute::<[u8; 4], f32>(g_buf) } as Float;
                             // println!("  g = {}", g);
                             skip_bytes += 4;
                             // b
@@ -3591,7 +3649,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 b_buf[i] = buffer[skip_bytes + i];
                             }
-                            let b: f32 = unsafe { mem::transmute(b_buf) };
+                            let b: Float = unsafe { mem::transmute::<[u8; 4], f32>(b_buf) } as Float;
                             // println!("  b = {}", b);
                             skip_bytes += 4;
                             // a
@@ -3599,7 +3657,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 a_buf[i] = buffer[skip_bytes + i];
                             }
-                            let a: f32 = unsafe { mem::transmute(a_buf) };
+                            let a: Float = unsafe { mem::transmute::<[u8; 4], f32>(a_buf) } as Float;
                             // println!("  a = {}", a);
                             skip_bytes += 4;
                             // specr
@@ -3607,7 +3665,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specr_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specr: f32 = unsafe { mem::transmute(specr_buf) };
+                            let specr: Float = unsafe { mem::transmute::<[u8; 4], f32>(specr_buf) } as Float;
                             // println!("  specr = {}", specr);
                             skip_bytes += 4;
                             // specg
@@ -3615,7 +3673,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specg_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specg: f32 = unsafe { mem::transmute(specg_buf) };
+                            let specg: Float = unsafe { mem::transmute::<[u8; 4], f32>(specg_buf) } as Float;
                             // println!("  specg = {}", specg);
                             skip_bytes += 4;
                             // specb
@@ -3623,7 +3681,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 specb_buf[i] = buffer[skip_bytes + i];
                             }
-                            let specb: f32 = unsafe { mem::transmute(specb_buf) };
+                            let specb: Float = unsafe { mem::transmute::<[u8; 4], f32>(specb_buf) } as Float;
                             // println!("  specb = {}", specb);
                             skip_bytes += 4;
                             // alpha
@@ -3633,7 +3691,7 @@ fn main() -> std::io::Result<()> {
                             for i in 0..4 as usize {
                                 ray_mirror_buf[i] = buffer[skip_bytes + i];
                             }
-                            let ray_mirror: f32 = unsafe { mem::transmute(ray_mirror_buf) };
+                            let ray_mirror: Float = unsafe { mem::transmute::<[u8; 4], f32>(ray_mirror_buf) } as Float;
                             // println!("  ray_mirror = {}", ray_mirror);
                             skip_bytes += 4;
                             // spec, gloss_mir, roughness, metallic
@@ -3710,7 +3768,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             r_buf[i] = buffer[skip_bytes + i];
                         }
-                        let r: f32 = unsafe { mem::transmute(r_buf) };
+                        let r: Float = unsafe { mem::transmute::<[u8; 4], f32>(r_buf) } as Float;
                         // println!("  r = {}", r);
                         skip_bytes += 4;
                         // g
@@ -3718,7 +3776,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             g_buf[i] = buffer[skip_bytes + i];
                         }
-                        let g: f32 = unsafe { mem::transmute(g_buf) };
+                        let g: Float = unsafe { mem::transmute::<[u8; 4], f32>(g_buf) } as Float;
                         // println!("  g = {}", g);
                         skip_bytes += 4;
                         // b
@@ -3726,7 +3784,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             b_buf[i] = buffer[skip_bytes + i];
                         }
-                        let b: f32 = unsafe { mem::transmute(b_buf) };
+                        let b: Float = unsafe { mem::transmute::<[u8; 4], f32>(b_buf) } as Float;
                         // println!("  b = {}", b);
                         skip_bytes += 4;
                         // k, shdwr, shdwg, shdwb, shdwpad
@@ -3736,7 +3794,7 @@ fn main() -> std::io::Result<()> {
                         for i in 0..4 as usize {
                             energy_buf[i] = buffer[skip_bytes + i];
                         }
-                        let energy: f32 = unsafe { mem::transmute(energy_buf) };
+                        let energy: Float = unsafe { mem::transmute::<[u8; 4], f32>(energy_buf) } as Float;
                         // println!("  energy = {}", energy);
                         // skip_bytes += 4;
                         // check light type
@@ -3853,8 +3911,8 @@ fn main() -> std::io::Result<()> {
                                 // println!("  SDNAnr = {}", sdna_nr);
                                 // println!("  {} ({})", types[type_id], tlen[type_id]);
                                 let mut skip_bytes: usize = 0;
-                                let factor: f32 = 1.0 / 32767.0;
-                                let mut coords: [f32; 3] = [0.0_f32; 3];
+                                let factor: Float = 1.0 / 32767.0;
+                                let mut coords: [Float; 3] = [0.0; 3];
                                 for _v in 0..data_len {
                                     // println!("  {}:", v + 1);
                                     // co
@@ -3863,7 +3921,7 @@ fn main() -> std::io::Result<()> {
                                         for b in 0..4 as usize {
                                             co_buf[b] = buffer[skip_bytes + b];
                                         }
-                                        let co: f32 = unsafe { mem::transmute(co_buf) };
+                                        let co: Float = unsafe { mem::transmute::<[u8; 4], f32>(co_buf) } as Float;
                                         // println!("    co[{}] = {}", i, co);
                                         coords[i] = co;
                                         skip_bytes += 4;
@@ -3878,7 +3936,7 @@ fn main() -> std::io::Result<()> {
                                         let mut no: i16 = 0;
                                         no += (buffer[skip_bytes] as i16) << 0;
                                         no += (buffer[skip_bytes + 1] as i16) << 8;
-                                        let nof: f32 = no as f32 * factor;
+                                        let nof: Float = no as Float * factor;
                                         // println!("    no[{}] = {}", i, nof);
                                         coords[i] = nof;
                                         skip_bytes += 2;
@@ -3931,7 +3989,7 @@ fn main() -> std::io::Result<()> {
                                 // println!("  SDNAnr = {}", sdna_nr);
                                 // println!("  {} ({})", types[type_id], tlen[type_id]);
                                 let mut skip_bytes: usize = 0;
-                                let mut coords: [f32; 2] = [0.0_f32; 2];
+                                let mut coords: [Float; 2] = [0.0; 2];
                                 for _l in 0..data_len {
                                     // println!("  {}:", l + 1);
                                     // float uv[2]
@@ -3940,7 +3998,7 @@ fn main() -> std::io::Result<()> {
                                         for b in 0..4 as usize {
                                             uv_buf[b] = buffer[skip_bytes + b];
                                         }
-                                        let uv: f32 = unsafe { mem::transmute(uv_buf) };
+                                        let uv: Float = unsafe { mem::transmute::<[u8; 4], f32>(uv_buf) } as Float;
                                         // println!("    uv[{}] = {}", i, uv);
                                         coords[i] = uv;
                                         skip_bytes += 4;
@@ -4079,28 +4137,28 @@ fn main() -> std::io::Result<()> {
                             if base_name.starts_with("PbrtSphere") {
                                 // store sphere values for later
                                 let pbrt_sphere: PbrtSphere = PbrtSphere::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 spheres_hm.insert(base_name.clone(), pbrt_sphere);
                             } else if base_name.starts_with("PbrtCylinder") {
                                 // store cylinder values for later
                                 let pbrt_cylinder: PbrtCylinder = PbrtCylinder::new(
-                                    prop_radius as f32,
-                                    prop_zmin as f32,
-                                    prop_zmax as f32,
-                                    prop_phimax as f32,
+                                    prop_radius as Float,
+                                    prop_zmin as Float,
+                                    prop_zmax as Float,
+                                    prop_phimax as Float,
                                 );
                                 cylinders_hm.insert(base_name.clone(), pbrt_cylinder);
                             } else if base_name.starts_with("PbrtDisk") {
                                 // store disk values for later
                                 let pbrt_disk: PbrtDisk = PbrtDisk::new(
-                                    prop_height as f32,
-                                    prop_radius as f32,
-                                    prop_innerradius as f32,
-                                    prop_phimax as f32,
+                                    prop_height as Float,
+                                    prop_radius as Float,
+                                    prop_innerradius as Float,
+                                    prop_phimax as Float,
                                 );
                                 disks_hm.insert(base_name.clone(), pbrt_disk);
                             }
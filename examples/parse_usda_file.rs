@@ -0,0 +1,1307 @@
+use pest_derive::*;
+
+// parser
+use pest::iterators::Pair;
+use pest::Parser;
+
+// command line options
+use structopt::StructOpt;
+// pbrt
+use pbrt::core::api::{make_accelerator, make_camera, make_film, make_filter, make_sampler};
+use pbrt::core::camera::Camera;
+use pbrt::core::film::Film;
+use pbrt::core::geometry::{Bounds2i, Normal3f, Point2f, Point3f, Vector3f};
+use pbrt::core::integrator::{Integrator, SamplerIntegrator};
+use pbrt::core::light::Light;
+use pbrt::core::material::Material;
+use pbrt::core::medium::MediumInterface;
+use pbrt::core::mipmap::ImageWrap;
+use pbrt::core::paramset::{ParamSet, TextureParams};
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::quaternion::{quat_normalize, Quaternion};
+use pbrt::core::sampler::Sampler;
+use pbrt::core::scene::Scene;
+use pbrt::core::shape::Shape;
+use pbrt::core::texture::{Texture, TextureMapping2D, UVMapping2D};
+use pbrt::core::transform::{AnimatedTransform, Transform};
+use pbrt::integrators::path::PathIntegrator;
+use pbrt::lights::diffuse::DiffuseAreaLight;
+use pbrt::lights::distant::DistantLight;
+use pbrt::lights::infinite::InfiniteAreaLight;
+use pbrt::materials::disney::DisneyMaterial;
+use pbrt::shapes::triangle::{Triangle, TriangleMesh};
+use pbrt::textures::imagemap::ImageTexture;
+use pbrt::textures::imagemap::{convert_to_float, convert_to_spectrum};
+// std
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+/// Parse a USD ASCII file (extension .usda) and render it.
+///
+/// Only a subset of USD is understood: **Xform**, **Mesh**,
+/// **Material** with **UsdPreviewSurface** and **UsdUVTexture**
+/// shaders, **DistantLight**, **DomeLight** and **Camera** prims.
+/// Composition arcs (references, payloads, variants, ...) are not
+/// resolved and animated attributes use their first time sample.
+#[derive(StructOpt)]
+struct Cli {
+    /// samples per pixel
+    #[structopt(short = "s", long = "samples", default_value = "16")]
+    samples: u16,
+    /// horizontal resolution (the vertical one follows from the camera's aperture)
+    #[structopt(short = "x", long = "xres", default_value = "640")]
+    xres: i32,
+    /// maximum path length
+    #[structopt(short = "d", long = "maxdepth", default_value = "5")]
+    max_depth: i32,
+    /// The path to the file to read
+    #[structopt(parse(from_os_str))]
+    path: std::path::PathBuf,
+}
+
+#[derive(Parser)]
+#[grammar = "../examples/usda.pest"]
+struct UsdaParser;
+
+/// The value of an attribute, a relationship or a metadata entry.
+#[derive(Debug, Clone)]
+enum UsdValue {
+    None,
+    Bool(bool),
+    Number(Float),
+    String(String),
+    Token(String),
+    Asset(String),
+    Path(String),
+    Tuple(Vec<UsdValue>),
+    Array(Vec<UsdValue>),
+    TimeSamples(Vec<(Float, UsdValue)>),
+}
+
+impl UsdValue {
+    /// Animated values are evaluated at their first time sample.
+    fn at_first_sample(&self) -> &UsdValue {
+        match self {
+            UsdValue::TimeSamples(samples) => {
+                if let Some((_time, value)) = samples.first() {
+                    value
+                } else {
+                    &UsdValue::None
+                }
+            }
+            _ => self,
+        }
+    }
+    fn as_float(&self) -> Option<Float> {
+        match self.at_first_sample() {
+            UsdValue::Number(number) => Some(*number),
+            UsdValue::Bool(boolean) => Some(if *boolean { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+    /// All numbers of (nested) tuples and arrays in one flat vector.
+    fn as_floats(&self) -> Vec<Float> {
+        let mut floats: Vec<Float> = Vec::new();
+        match self.at_first_sample() {
+            UsdValue::Number(number) => floats.push(*number),
+            UsdValue::Tuple(values) | UsdValue::Array(values) => {
+                for value in values {
+                    floats.extend(value.as_floats());
+                }
+            }
+            _ => {}
+        }
+        floats
+    }
+    fn as_str(&self) -> Option<&str> {
+        match self.at_first_sample() {
+            UsdValue::String(s) | UsdValue::Token(s) | UsdValue::Asset(s) | UsdValue::Path(s) => {
+                Some(s)
+            }
+            // e.g. a relationship targeting a single prim
+            UsdValue::Array(values) if values.len() == 1 => values[0].as_str(),
+            _ => None,
+        }
+    }
+    fn as_strs(&self) -> Vec<String> {
+        match self.at_first_sample() {
+            UsdValue::Array(values) | UsdValue::Tuple(values) => values
+                .iter()
+                .filter_map(|value| value.as_str())
+                .map(|s| s.to_string())
+                .collect(),
+            value => value.as_str().map(|s| s.to_string()).into_iter().collect(),
+        }
+    }
+}
+
+/// A primvar (or the **normals** attribute) with its (optional)
+/// indices and its interpolation.
+struct Primvar {
+    values: Vec<Float>,
+    indices: Vec<usize>,
+    interpolation: String,
+}
+
+impl Primvar {
+    /// Interpolated per point, i.e. usable without unwelding the
+    /// mesh.
+    fn is_per_point(&self) -> bool {
+        self.indices.is_empty()
+            && (self.interpolation == "vertex" || self.interpolation == "varying")
+    }
+    /// Returns the index of the value used for polygon corner
+    /// *corner* of face *face*, which refers to point *point*.
+    fn lookup(&self, corner: usize, point: usize, face: usize) -> usize {
+        let element: usize = match self.interpolation.as_str() {
+            "vertex" | "varying" => point,
+            "faceVarying" => corner,
+            "uniform" => face,
+            _ => 0, // constant
+        };
+        if self.indices.is_empty() {
+            element
+        } else {
+            self.indices[element]
+        }
+    }
+    /// Checks that all lookups stay within bounds.
+    fn is_valid(
+        &self,
+        n_components: usize,
+        n_points: usize,
+        n_faces: usize,
+        n_corners: usize,
+    ) -> bool {
+        let n_elements: usize = match self.interpolation.as_str() {
+            "vertex" | "varying" => n_points,
+            "faceVarying" => n_corners,
+            "uniform" => n_faces,
+            _ => 1, // constant
+        };
+        let n_values: usize = self.values.len() / n_components;
+        if self.indices.is_empty() {
+            n_values >= n_elements
+        } else {
+            self.indices.len() >= n_elements && self.indices.iter().all(|i| *i < n_values)
+        }
+    }
+}
+
+/// A prim (with its properties and children) as it appears in the
+/// file. Properties are stored by name, connections and time samples
+/// keep their suffix (e.g. **inputs:diffuseColor.connect**).
+#[derive(Default)]
+struct UsdPrim {
+    specifier: String,
+    type_name: String,
+    path: String,
+    metadata: HashMap<String, UsdValue>,
+    attributes: HashMap<String, UsdValue>,
+    types: HashMap<String, String>,
+    interpolations: HashMap<String, String>,
+    children: Vec<UsdPrim>,
+}
+
+impl UsdPrim {
+    fn get(&self, name: &str) -> Option<&UsdValue> {
+        // time samples override the default value
+        self.attributes
+            .get(&format!("{}.timeSamples", name))
+            .or_else(|| self.attributes.get(name))
+            .map(|value| value.at_first_sample())
+    }
+    fn get_float(&self, name: &str, default: Float) -> Float {
+        self.get(name)
+            .and_then(|value| value.as_float())
+            .unwrap_or(default)
+    }
+    fn get_floats(&self, name: &str) -> Vec<Float> {
+        self.get(name)
+            .map(|value| value.as_floats())
+            .unwrap_or_default()
+    }
+    fn get_string(&self, name: &str) -> Option<String> {
+        self.get(name)
+            .and_then(|value| value.as_str())
+            .map(|s| s.to_string())
+    }
+    /// Light parameters are called **inputs:intensity** etc. since
+    /// USD 21.02, older files use **intensity**.
+    fn get_input(&self, name: &str) -> Option<&UsdValue> {
+        self.get(&format!("inputs:{}", name))
+            .or_else(|| self.get(name))
+    }
+    fn get_input_float(&self, name: &str, default: Float) -> Float {
+        self.get_input(name)
+            .and_then(|value| value.as_float())
+            .unwrap_or(default)
+    }
+    fn get_primvar(&self, name: &str, default_interpolation: &str) -> Option<Primvar> {
+        let values: Vec<Float> = self.get_floats(name);
+        if values.is_empty() {
+            return None;
+        }
+        let indices: Vec<usize> = self
+            .get_floats(&format!("{}:indices", name))
+            .iter()
+            .map(|i| *i as usize)
+            .collect();
+        let interpolation: String = self
+            .interpolations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| String::from(default_interpolation));
+        Some(Primvar {
+            values,
+            indices,
+            interpolation,
+        })
+    }
+    /// Composes the ops listed in **xformOpOrder**. Also returns if
+    /// the transforms of the ancestors are ignored
+    /// (**!resetXformStack!**).
+    fn local_transform(&self) -> (Transform, bool) {
+        let mut transform: Transform = Transform::default();
+        let mut resets_xform_stack: bool = false;
+        let op_order: Vec<String> = self
+            .get("xformOpOrder")
+            .map(|value| value.as_strs())
+            .unwrap_or_default();
+        for op in op_order.iter() {
+            if op == "!resetXformStack!" {
+                resets_xform_stack = true;
+                transform = Transform::default();
+                continue;
+            }
+            let (invert, op_name): (bool, &str) = if op.starts_with("!invert!") {
+                (true, &op["!invert!".len()..])
+            } else {
+                (false, op.as_str())
+            };
+            let v: Vec<Float> = self.get_floats(op_name);
+            let op_type: &str = op_name.split(':').nth(1).unwrap_or("");
+            let expected: usize = match op_type {
+                "rotateX" | "rotateY" | "rotateZ" => 1,
+                "orient" => 4,
+                "transform" => 16,
+                _ => 3,
+            };
+            if v.len() < expected {
+                println!(
+                    "WARNING: {}: xformOp {:?} has no (valid) value",
+                    self.path, op_name
+                );
+                continue;
+            }
+            let mut op_transform: Transform = match op_type {
+                "translate" => Transform::translate(&Vector3f {
+                    x: v[0],
+                    y: v[1],
+                    z: v[2],
+                }),
+                "scale" => Transform::scale(v[0], v[1], v[2]),
+                "rotateX" => Transform::rotate_x(v[0]),
+                "rotateY" => Transform::rotate_y(v[0]),
+                "rotateZ" => Transform::rotate_z(v[0]),
+                "rotateXYZ" | "rotateXZY" | "rotateYXZ" | "rotateYZX" | "rotateZXY"
+                | "rotateZYX" => {
+                    // the angles are always stored as (x, y, z), the
+                    // first axis of the name is rotated about first
+                    let mut rotation: Transform = Transform::default();
+                    for axis in op_type["rotate".len()..].chars() {
+                        let axis_rotation: Transform = match axis {
+                            'X' => Transform::rotate_x(v[0]),
+                            'Y' => Transform::rotate_y(v[1]),
+                            _ => Transform::rotate_z(v[2]),
+                        };
+                        rotation = axis_rotation * rotation;
+                    }
+                    rotation
+                }
+                "orient" => {
+                    // stored as (real, i, j, k)
+                    let q: Quaternion = Quaternion {
+                        v: Vector3f {
+                            x: v[1],
+                            y: v[2],
+                            z: v[3],
+                        },
+                        w: v[0],
+                    };
+                    quat_normalize(&q).to_transform()
+                }
+                "transform" => {
+                    // USD stores the translation in the last row
+                    Transform::new(
+                        v[0], v[4], v[8], v[12], v[1], v[5], v[9], v[13], v[2], v[6], v[10], v[14],
+                        v[3], v[7], v[11], v[15],
+                    )
+                }
+                _ => {
+                    println!(
+                        "WARNING: {}: xformOp {:?} not supported",
+                        self.path, op_name
+                    );
+                    Transform::default()
+                }
+            };
+            if invert {
+                op_transform = Transform::inverse(&op_transform);
+            }
+            transform = transform * op_transform;
+        }
+        (transform, resets_xform_stack)
+    }
+}
+
+fn unquote(s: &str) -> String {
+    if s.starts_with("\"\"\"") && s.len() >= 6 {
+        s[3..s.len() - 3].to_string()
+    } else if s.len() >= 2 {
+        s[1..s.len() - 1].replace("\\\"", "\"")
+    } else {
+        s.to_string()
+    }
+}
+
+fn parse_value(pair: Pair<Rule>) -> UsdValue {
+    match pair.as_rule() {
+        Rule::boolean => UsdValue::Bool(pair.as_str() == "true"),
+        Rule::number => UsdValue::Number(Float::from_str(pair.as_str()).unwrap_or(0.0 as Float)),
+        Rule::string => UsdValue::String(unquote(pair.as_str())),
+        Rule::token => UsdValue::Token(pair.as_str().to_string()),
+        Rule::asset => {
+            // @path@ (followed by an optional prim path)
+            let asset: &str = pair.as_str();
+            UsdValue::Asset(asset[1..].split('@').next().unwrap_or("").to_string())
+        }
+        Rule::path => UsdValue::Path(
+            pair.as_str()
+                .trim_matches(|c| c == '<' || c == '>')
+                .to_string(),
+        ),
+        Rule::tuple => UsdValue::Tuple(pair.into_inner().map(parse_value).collect()),
+        Rule::array => UsdValue::Array(pair.into_inner().map(parse_value).collect()),
+        Rule::samples => UsdValue::TimeSamples(
+            pair.into_inner()
+                .map(|sample| {
+                    let mut inner = sample.into_inner();
+                    let time: Float = inner
+                        .next()
+                        .and_then(|time| Float::from_str(time.as_str()).ok())
+                        .unwrap_or(0.0 as Float);
+                    let value: UsdValue = inner.next().map(parse_value).unwrap_or(UsdValue::None);
+                    (time, value)
+                })
+                .collect(),
+        ),
+        _ => UsdValue::None,
+    }
+}
+
+/// Metadata entries by name (list edits like **prepend** are
+/// treated as plain assignments).
+fn parse_metadata(pair: Pair<Rule>) -> HashMap<String, UsdValue> {
+    let mut metadata: HashMap<String, UsdValue> = HashMap::new();
+    for item in pair.into_inner() {
+        if item.as_rule() == Rule::metadata_item {
+            let name: String = item
+                .as_str()
+                .split('=')
+                .next()
+                .and_then(|key| key.split_whitespace().last())
+                .unwrap_or("")
+                .to_string();
+            let value: UsdValue = item
+                .into_inner()
+                .next()
+                .map(parse_value)
+                .unwrap_or(UsdValue::None);
+            metadata.insert(name, value);
+        }
+    }
+    metadata
+}
+
+fn parse_property(pair: Pair<Rule>, prim: &mut UsdPrim) {
+    let mut type_name: String = String::from("rel");
+    let mut name: String = String::from("");
+    let mut value: Option<UsdValue> = None;
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::type_name => type_name = inner_pair.as_str().to_string(),
+            Rule::property_name => name = inner_pair.as_str().to_string(),
+            Rule::metadata => {
+                let metadata: HashMap<String, UsdValue> = parse_metadata(inner_pair);
+                if let Some(interpolation) = metadata.get("interpolation").and_then(|v| v.as_str())
+                {
+                    prim.interpolations
+                        .insert(name.clone(), interpolation.to_string());
+                }
+            }
+            _ => value = Some(parse_value(inner_pair)),
+        }
+    }
+    prim.types.insert(name.clone(), type_name);
+    if let Some(value) = value {
+        prim.attributes.insert(name, value);
+    }
+}
+
+fn parse_prim(pair: Pair<Rule>, parent_path: &str) -> UsdPrim {
+    let mut prim: UsdPrim = UsdPrim::default();
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::specifier => prim.specifier = inner_pair.as_str().to_string(),
+            Rule::prim_type => prim.type_name = inner_pair.as_str().to_string(),
+            Rule::string => prim.path = format!("{}/{}", parent_path, unquote(inner_pair.as_str())),
+            Rule::metadata => prim.metadata = parse_metadata(inner_pair),
+            Rule::prim => {
+                let child: UsdPrim = parse_prim(inner_pair, &prim.path);
+                prim.children.push(child);
+            }
+            Rule::property => {
+                for property in inner_pair.into_inner() {
+                    parse_property(property, &mut prim);
+                }
+            }
+            _ => {}
+        }
+    }
+    prim
+}
+
+fn index_prims<'a>(prim: &'a UsdPrim, prims: &mut HashMap<String, &'a UsdPrim>) {
+    prims.insert(prim.path.clone(), prim);
+    for child in prim.children.iter() {
+        index_prims(child, prims);
+    }
+}
+
+/// The prims which get rendered, with their object-to-world
+/// transforms (and the path of the bound material for meshes).
+#[derive(Default)]
+struct Gathered<'a> {
+    meshes: Vec<(&'a UsdPrim, Transform, Option<String>)>,
+    lights: Vec<(&'a UsdPrim, Transform)>,
+    cameras: Vec<(&'a UsdPrim, Transform)>,
+}
+
+/// Walks the prim tree, accumulating transforms and inheriting
+/// material bindings.
+fn gather<'a>(
+    prim: &'a UsdPrim,
+    parent_to_world: &Transform,
+    parent_binding: Option<String>,
+    gathered: &mut Gathered<'a>,
+) {
+    if prim.specifier != "def" {
+        if prim.specifier == "over" {
+            println!(
+                "WARNING: {}: \"over\" can not be composed, ignored",
+                prim.path
+            );
+        }
+        return;
+    }
+    if let Some(UsdValue::Bool(false)) = prim.metadata.get("active") {
+        return;
+    }
+    if prim.get_string("visibility").as_deref() == Some("invisible") {
+        return;
+    }
+    for arc in ["references", "payload", "inherits", "specializes"].iter() {
+        if prim.metadata.contains_key(*arc) {
+            println!("WARNING: {}: {} are not resolved", prim.path, arc);
+        }
+    }
+    let (local, resets_xform_stack): (Transform, bool) = prim.local_transform();
+    let obj_to_world: Transform = if resets_xform_stack {
+        local
+    } else {
+        *parent_to_world * local
+    };
+    let binding: Option<String> = prim.get_string("material:binding").or(parent_binding);
+    match prim.type_name.as_str() {
+        "Mesh" => gathered.meshes.push((prim, obj_to_world, binding.clone())),
+        "DistantLight" | "DomeLight" => gathered.lights.push((prim, obj_to_world)),
+        "Camera" => gathered.cameras.push((prim, obj_to_world)),
+        "" | "Xform" | "Scope" | "Material" | "Shader" | "NodeGraph" | "GeomSubset" => {}
+        type_name => println!(
+            "WARNING: {}: prim type {:?} not supported",
+            prim.path, type_name
+        ),
+    }
+    for child in prim.children.iter() {
+        gather(child, &obj_to_world, binding.clone(), gathered);
+    }
+}
+
+/// An input of a shader: either a value or the output of another
+/// shader it is connected to.
+enum ShaderInput<'a> {
+    Value(UsdValue),
+    Output(&'a UsdPrim, String),
+}
+
+/// Follows connections (through node graphs and material interface
+/// inputs) until a value or a shader output is found.
+fn resolve_input<'a>(
+    prims: &HashMap<String, &'a UsdPrim>,
+    prim: &'a UsdPrim,
+    name: &str,
+    depth: u32,
+) -> Option<ShaderInput<'a>> {
+    let connection: Option<String> = prim
+        .get(&format!("{}.connect", name))
+        .and_then(|value| value.as_str())
+        .map(|s| s.to_string());
+    if let Some(target) = connection {
+        if let Some((prim_path, property)) = target.rsplit_once('.') {
+            if let Some(source) = prims.get(prim_path) {
+                if source.type_name == "Shader" && property.starts_with("outputs:") {
+                    return Some(ShaderInput::Output(source, property.to_string()));
+                } else if depth < 16 {
+                    return resolve_input(prims, source, property, depth + 1);
+                }
+            }
+        }
+        println!(
+            "WARNING: {}: can not resolve connection of {:?} to {:?}",
+            prim.path, name, target
+        );
+        None
+    } else {
+        prim.get(name).cloned().map(ShaderInput::Value)
+    }
+}
+
+fn make_image_mapping() -> Box<TextureMapping2D> {
+    Box::new(TextureMapping2D::UV(UVMapping2D {
+        su: 1.0,
+        sv: 1.0,
+        du: 0.0,
+        dv: 0.0,
+    }))
+}
+
+/// A **UsdUVTexture** shader (with the defaults of the
+/// UsdPreviewSurface specification). The texture coordinates always
+/// come from the mesh's **st** primvar.
+struct UvTexture {
+    filename: String,
+    source_color_space: String,
+    wrap_s: String,
+    wrap_t: String,
+    scale: Float,
+}
+
+impl UvTexture {
+    /// Returns **None** (with a warning) if the shader is not a
+    /// **UsdUVTexture** or the image file does not exist.
+    fn from_shader(shader: &UsdPrim, search_directory: &Path) -> Option<UvTexture> {
+        let id: String = shader.get_string("info:id").unwrap_or_default();
+        if id != "UsdUVTexture" {
+            println!("WARNING: {}: shader {:?} not supported", shader.path, id);
+            return None;
+        }
+        let file: String = shader.get_string("inputs:file").unwrap_or_default();
+        let mut path: PathBuf = PathBuf::from(&file);
+        if path.is_relative() {
+            path = search_directory.join(path);
+        }
+        if !path.exists() {
+            println!("WARNING: {}: image file {:?} not found", shader.path, file);
+            return None;
+        }
+        // use the average of the RGB scale (alpha is ignored)
+        let scale: Vec<Float> = shader.get_floats("inputs:scale");
+        Some(UvTexture {
+            filename: path.to_str().unwrap_or("").to_string(),
+            source_color_space: shader
+                .get_string("inputs:sourceColorSpace")
+                .unwrap_or_else(|| String::from("auto")),
+            wrap_s: shader
+                .get_string("inputs:wrapS")
+                .unwrap_or_else(|| String::from("useMetadata")),
+            wrap_t: shader
+                .get_string("inputs:wrapT")
+                .unwrap_or_else(|| String::from("useMetadata")),
+            scale: if scale.len() >= 3 {
+                (scale[0] + scale[1] + scale[2]) / 3.0 as Float
+            } else {
+                1.0 as Float
+            },
+        })
+    }
+    /// Do the texels need to be converted from sRGB to linear?
+    /// **auto** assumes that high dynamic range files are linear and
+    /// all others sRGB encoded.
+    fn gamma(&self) -> bool {
+        match self.source_color_space.as_str() {
+            "raw" => false,
+            "sRGB" => true,
+            _ => {
+                let extension: String = Path::new(&self.filename)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or("")
+                    .to_lowercase();
+                !(extension == "hdr" || extension == "exr")
+            }
+        }
+    }
+    /// pbrt has only one wrap mode for both texture directions (and
+    /// images carry no metadata, so **useMetadata** repeats).
+    fn wrap_mode(&self) -> ImageWrap {
+        if self.wrap_t != self.wrap_s {
+            println!(
+                "WARNING: image {:?} uses wrapS {:?} in both directions (wrapT {:?})",
+                self.filename, self.wrap_s, self.wrap_t
+            );
+        }
+        match self.wrap_s.as_str() {
+            "black" => ImageWrap::Black,
+            "clamp" => ImageWrap::Clamp,
+            "repeat" | "useMetadata" => ImageWrap::Repeat,
+            _ => {
+                println!(
+                    "WARNING: wrapS {:?} not supported, using \"repeat\"",
+                    self.wrap_s
+                );
+                ImageWrap::Repeat
+            }
+        }
+    }
+    fn make_spectrum_texture(&self) -> Arc<Texture<Spectrum>> {
+        Arc::new(Texture::Image(ImageTexture::new(
+            make_image_mapping(),
+            self.filename.clone(),
+            false, // do_trilinear
+            8.0,   // max_aniso
+            self.wrap_mode(),
+            self.scale,
+            self.gamma(),
+            convert_to_spectrum,
+        )))
+    }
+    /// Single channel outputs (**outputs:r** etc.) use the luminance
+    /// of the texels.
+    fn make_float_texture(&self) -> Arc<Texture<Float>> {
+        Arc::new(Texture::Image(ImageTexture::new(
+            make_image_mapping(),
+            self.filename.clone(),
+            false, // do_trilinear
+            8.0,   // max_aniso
+            self.wrap_mode(),
+            self.scale,
+            self.gamma(),
+            convert_to_float,
+        )))
+    }
+}
+
+fn value_to_spectrum(value: &UsdValue) -> Option<Spectrum> {
+    let rgb: Vec<Float> = value.as_floats();
+    if rgb.len() >= 3 {
+        Some(Spectrum::rgb(rgb[0], rgb[1], rgb[2]))
+    } else {
+        None
+    }
+}
+
+/// Maps a **UsdPreviewSurface** shader (or its defaults, if there is
+/// none) onto the Disney material. Inputs connected to
+/// **UsdUVTexture** shaders become image textures. Also returns the
+/// (constant) emissive color. The specular workflow, opacity
+/// thresholds, normal maps and displacement are ignored.
+fn make_preview_surface(
+    shader: Option<&UsdPrim>,
+    default_color: Spectrum,
+    prims: &HashMap<String, &UsdPrim>,
+    search_directory: &Path,
+) -> (Arc<Material>, Spectrum) {
+    let mut float_textures: HashMap<String, Arc<Texture<Float>>> = HashMap::new();
+    let mut spectrum_textures: HashMap<String, Arc<Texture<Spectrum>>> = HashMap::new();
+    let mut mp: ParamSet = ParamSet::default();
+    let input = |name: &str| -> Option<ShaderInput> {
+        shader.and_then(|shader| resolve_input(prims, shader, &format!("inputs:{}", name), 0))
+    };
+    let texture = |name: &str| -> Option<UvTexture> {
+        if let Some(ShaderInput::Output(texture_shader, _output)) = input(name) {
+            UvTexture::from_shader(texture_shader, search_directory)
+        } else {
+            None
+        }
+    };
+    let texture_name = |name: &str| -> String {
+        format!("{}.{}", shader.map(|s| s.path.as_str()).unwrap_or(""), name)
+    };
+    if let Some(shader) = shader {
+        if shader.get_float("inputs:useSpecularWorkflow", 0.0) != 0.0 as Float {
+            println!(
+                "WARNING: {}: specular workflow not supported, using metallic",
+                shader.path
+            );
+        }
+    }
+    // diffuse color
+    if let Some(uv_texture) = texture("diffuseColor") {
+        spectrum_textures.insert(
+            texture_name("diffuseColor"),
+            uv_texture.make_spectrum_texture(),
+        );
+        mp.add_texture(String::from("color"), texture_name("diffuseColor"));
+    } else {
+        let color: Spectrum = match input("diffuseColor") {
+            Some(ShaderInput::Value(value)) => value_to_spectrum(&value).unwrap_or(default_color),
+            _ => default_color,
+        };
+        mp.add_rgb_spectrum(String::from("color"), color);
+    }
+    // scalar parameters
+    let float_params: [(&str, &str, Float); 4] = [
+        ("metallic", "metallic", 0.0),
+        ("roughness", "roughness", 0.5),
+        ("ior", "eta", 1.5),
+        ("clearcoat", "clearcoat", 0.0),
+    ];
+    for (usd_name, pbrt_name, default) in float_params.iter() {
+        if let Some(uv_texture) = texture(usd_name) {
+            float_textures.insert(texture_name(usd_name), uv_texture.make_float_texture());
+            mp.add_texture(pbrt_name.to_string(), texture_name(usd_name));
+        } else {
+            let value: Float = match input(usd_name) {
+                Some(ShaderInput::Value(value)) => value.as_float().unwrap_or(*default),
+                _ => *default,
+            };
+            mp.add_float(pbrt_name.to_string(), value);
+        }
+    }
+    let constant = |name: &str, default: Float| -> Float {
+        match input(name) {
+            Some(ShaderInput::Value(value)) => value.as_float().unwrap_or(default),
+            _ => default,
+        }
+    };
+    mp.add_float(
+        String::from("clearcoatgloss"),
+        1.0 as Float - constant("clearcoatRoughness", 0.01),
+    );
+    mp.add_float(
+        String::from("spectrans"),
+        1.0 as Float - constant("opacity", 1.0),
+    );
+    mp.add_float(String::from("sheentint"), 0.0 as Float);
+    let emission: Spectrum = match input("emissiveColor") {
+        Some(ShaderInput::Value(value)) => value_to_spectrum(&value).unwrap_or_default(),
+        _ => Spectrum::default(),
+    };
+    let mut tp: TextureParams = TextureParams::new(
+        ParamSet::default(),
+        mp,
+        Arc::new(float_textures),
+        Arc::new(spectrum_textures),
+    );
+    (DisneyMaterial::create(&mut tp), emission)
+}
+
+/// Finds the **UsdPreviewSurface** shader connected to the surface
+/// output of a material.
+fn find_surface_shader<'a>(
+    prims: &HashMap<String, &'a UsdPrim>,
+    material_path: &str,
+) -> Option<&'a UsdPrim> {
+    if let Some(material) = prims.get(material_path) {
+        match resolve_input(prims, material, "outputs:surface", 0) {
+            Some(ShaderInput::Output(shader, _output)) => {
+                let id: String = shader.get_string("info:id").unwrap_or_default();
+                if id == "UsdPreviewSurface" {
+                    return Some(shader);
+                }
+                println!(
+                    "WARNING: {}: surface shader {:?} not supported",
+                    material_path, id
+                );
+            }
+            _ => println!("WARNING: {}: no surface shader", material_path),
+        }
+    } else {
+        println!("WARNING: material {:?} not found", material_path);
+    }
+    None
+}
+
+/// Triangulates a **Mesh** prim (fan triangulation of its polygons)
+/// in world space. Normals and **st** coordinates with a
+/// per-corner interpolation (or indices) require one vertex per
+/// polygon corner.
+fn make_mesh_shapes(prim: &UsdPrim, obj_to_world: &Transform) -> Vec<Arc<Shape>> {
+    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+    let points: Vec<Float> = prim.get_floats("points");
+    let counts: Vec<usize> = prim
+        .get_floats("faceVertexCounts")
+        .iter()
+        .map(|count| *count as usize)
+        .collect();
+    let indices: Vec<usize> = prim
+        .get_floats("faceVertexIndices")
+        .iter()
+        .map(|index| *index as usize)
+        .collect();
+    let p_ws: Vec<Point3f> = points
+        .chunks_exact(3)
+        .map(|p| {
+            obj_to_world.transform_point(&Point3f {
+                x: p[0],
+                y: p[1],
+                z: p[2],
+            })
+        })
+        .collect();
+    let n_points: usize = p_ws.len();
+    let n_faces: usize = counts.len();
+    let n_corners: usize = indices.len();
+    if n_points == 0 || n_corners == 0 {
+        println!("WARNING: {}: empty mesh ignored", prim.path);
+        return shapes;
+    }
+    if counts.iter().sum::<usize>() != n_corners || indices.iter().any(|i| *i >= n_points) {
+        println!(
+            "WARNING: {}: faceVertexCounts and faceVertexIndices do not match, mesh ignored",
+            prim.path
+        );
+        return shapes;
+    }
+    // normals (if any) and texture coordinates
+    let mut normals: Option<Primvar> = prim
+        .get_primvar("primvars:normals", "constant")
+        .or_else(|| prim.get_primvar("normals", "vertex"));
+    let st_name: Option<String> = if prim.attributes.contains_key("primvars:st") {
+        Some(String::from("primvars:st"))
+    } else {
+        // e.g. primvars:UVMap, the first set of texture coordinates
+        let mut names: Vec<&String> = prim
+            .types
+            .iter()
+            .filter(|(name, type_name)| {
+                name.starts_with("primvars:") && type_name.starts_with("texCoord2")
+            })
+            .map(|(name, _type_name)| name)
+            .collect();
+        names.sort();
+        names.first().map(|name| name.to_string())
+    };
+    let mut st: Option<Primvar> = st_name.and_then(|name| prim.get_primvar(&name, "constant"));
+    if let Some(ref primvar) = normals {
+        if !primvar.is_valid(3, n_points, n_faces, n_corners) {
+            println!("WARNING: {}: invalid normals ignored", prim.path);
+            normals = None;
+        }
+    }
+    if let Some(ref primvar) = st {
+        if !primvar.is_valid(2, n_points, n_faces, n_corners) {
+            println!(
+                "WARNING: {}: invalid texture coordinates ignored",
+                prim.path
+            );
+            st = None;
+        }
+    }
+    let unweld: bool = normals.iter().chain(st.iter()).any(|pv| !pv.is_per_point());
+    let normal = |primvar: &Primvar, corner: usize, point: usize, face: usize| -> Normal3f {
+        let i: usize = 3 * primvar.lookup(corner, point, face);
+        obj_to_world.transform_normal(&Normal3f {
+            x: primvar.values[i],
+            y: primvar.values[i + 1],
+            z: primvar.values[i + 2],
+        })
+    };
+    let uv = |primvar: &Primvar, corner: usize, point: usize, face: usize| -> Point2f {
+        let i: usize = 2 * primvar.lookup(corner, point, face);
+        Point2f {
+            x: primvar.values[i],
+            y: primvar.values[i + 1],
+        }
+    };
+    let mut p_mesh: Vec<Point3f> = Vec::new();
+    let mut n_ws: Vec<Normal3f> = Vec::new();
+    let mut uvs: Vec<Point2f> = Vec::new();
+    let mut corners: Vec<u32> = Vec::new();
+    if unweld {
+        // one mesh vertex per polygon corner
+        let mut corner: usize = 0;
+        for (face, count) in counts.iter().enumerate() {
+            for _k in 0..*count {
+                let point: usize = indices[corner];
+                p_mesh.push(p_ws[point]);
+                if let Some(ref primvar) = normals {
+                    n_ws.push(normal(primvar, corner, point, face));
+                }
+                if let Some(ref primvar) = st {
+                    uvs.push(uv(primvar, corner, point, face));
+                }
+                corners.push(corner as u32);
+                corner += 1;
+            }
+        }
+    } else {
+        if let Some(ref primvar) = normals {
+            for point in 0..n_points {
+                n_ws.push(normal(primvar, 0, point, 0));
+            }
+        }
+        if let Some(ref primvar) = st {
+            for point in 0..n_points {
+                uvs.push(uv(primvar, 0, point, 0));
+            }
+        }
+        p_mesh = p_ws;
+        corners = indices.iter().map(|i| *i as u32).collect();
+    }
+    // convert polygons to triangles (fan triangulation)
+    let left_handed: bool = prim.get_string("orientation").as_deref() == Some("leftHanded");
+    let mut vi_tri: Vec<u32> = Vec::new();
+    let mut first: usize = 0;
+    for count in counts.iter() {
+        for k in 1..count.saturating_sub(1) {
+            vi_tri.push(corners[first]);
+            if left_handed {
+                vi_tri.push(corners[first + k + 1]);
+                vi_tri.push(corners[first + k]);
+            } else {
+                vi_tri.push(corners[first + k]);
+                vi_tri.push(corners[first + k + 1]);
+            }
+        }
+        first += count;
+    }
+    let n_triangles: usize = vi_tri.len() / 3;
+    let s_ws: Vec<Vector3f> = Vec::new();
+    let n_vertices: usize = p_mesh.len();
+    let world_to_obj: Transform = Transform::inverse(obj_to_world);
+    let mesh = Arc::new(TriangleMesh::new(
+        *obj_to_world,
+        world_to_obj,
+        false, // reverse_orientation,
+        n_triangles.try_into().unwrap(),
+        vi_tri,
+        n_vertices as u32,
+        p_mesh, // in world space
+        s_ws,   // in world space
+        n_ws,   // in world space
+        uvs,
+        None,
+        None,
+    ));
+    for id in 0..mesh.n_triangles {
+        let triangle = Arc::new(Shape::Trngl(Triangle::new(
+            mesh.object_to_world,
+            mesh.world_to_object,
+            mesh.reverse_orientation,
+            mesh.clone(),
+            id,
+        )));
+        shapes.push(triangle);
+    }
+    shapes
+}
+
+pub fn make_perspective_camera(
+    filter_width: Float,
+    xres: i32,
+    yres: i32,
+    fov: Float,
+    animated_cam_to_world: AnimatedTransform,
+) -> Option<Arc<Camera>> {
+    let mut some_camera: Option<Arc<Camera>> = None;
+    let mut filter_params: ParamSet = ParamSet::default();
+    filter_params.add_float(String::from("xwidth"), filter_width);
+    filter_params.add_float(String::from("ywidth"), filter_width);
+    let some_filter = make_filter(&String::from("gaussian"), &filter_params).ok();
+    if let Some(filter) = some_filter {
+        let film_name: String = String::from("image");
+        let mut film_params: ParamSet = ParamSet::default();
+        film_params.add_int(String::from("xresolution"), xres);
+        film_params.add_int(String::from("yresolution"), yres);
+        let some_film: Option<Arc<Film>> = make_film(&film_name, &film_params, filter).ok();
+        if let Some(film) = some_film {
+            let camera_name: String = String::from("perspective");
+            let mut camera_params: ParamSet = ParamSet::default();
+            camera_params.add_float(String::from("fov"), fov);
+            some_camera = make_camera(
+                &camera_name,
+                &camera_params,
+                animated_cam_to_world,
+                film,
+                &MediumInterface::default(),
+            )
+            .ok();
+        }
+    }
+    some_camera
+}
+
+fn make_path_integrator(
+    filter_width: Float,
+    xres: i32,
+    yres: i32,
+    fov: Float,
+    animated_cam_to_world: AnimatedTransform,
+    maxdepth: i32,
+    pixelsamples: i32,
+) -> Option<Box<Integrator>> {
+    let some_integrator: Option<Box<Integrator>>;
+    let some_camera: Option<Arc<Camera>> =
+        make_perspective_camera(filter_width, xres, yres, fov, animated_cam_to_world);
+    if let Some(camera) = some_camera {
+        let sampler_name: String = String::from("sobol");
+        let mut sampler_params: ParamSet = ParamSet::default();
+        sampler_params.add_int(String::from("pixelsamples"), pixelsamples);
+        let some_sampler: Option<Box<Sampler>> =
+            make_sampler(&sampler_name, &sampler_params, camera.get_film()).ok();
+        if let Some(sampler) = some_sampler {
+            // CreatePathIntegrator
+            let integrator_params: ParamSet = ParamSet::default();
+            let max_depth: i32 = integrator_params.find_one_int("maxdepth", maxdepth);
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            let rr_threshold: Float = integrator_params.find_one_float("rrthreshold", 1.0 as Float);
+            let light_strategy: String =
+                integrator_params.find_one_string("lightsamplestrategy", String::from("spatial"));
+            let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::Path(
+                PathIntegrator::new(
+                    max_depth as u32,
+                    camera,
+                    sampler,
+                    pixel_bounds,
+                    rr_threshold,
+                    light_strategy,
+                ),
+            )));
+            some_integrator = Some(integrator);
+        } else {
+            panic!("Unable to create sampler.");
+        }
+    } else {
+        panic!("Unable to create camera.");
+    }
+    some_integrator
+}
+
+fn make_scene(primitives: &Vec<Arc<Primitive>>, lights: Vec<Arc<Light>>) -> Scene {
+    let accelerator_name: String = String::from("bvh");
+    let some_accelerator =
+        make_accelerator(&accelerator_name, &primitives, &ParamSet::default()).ok();
+    if let Some(accelerator) = some_accelerator {
+        return Scene::new(accelerator, lights);
+    } else {
+        panic!("Unable to create accelerator.");
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    // handle command line options
+    let args = Cli::from_args();
+    let samples_per_pixel: u16 = args.samples;
+    let filter_width: Float = 2.0;
+    let xres: i32 = args.xres;
+    let mut primitives: Vec<Arc<Primitive>> = Vec::new();
+    let mut lights: Vec<Arc<Light>> = Vec::new();
+    let mut named_materials: HashMap<String, (Arc<Material>, Spectrum)> = HashMap::new();
+    // input (.usda) file
+    println!("FILE = {:?}", args.path);
+    let f = File::open(&args.path)?;
+    let mut search_directory: PathBuf = args.path.parent().unwrap().to_path_buf();
+    if args.path.is_relative() {
+        let cp: PathBuf = env::current_dir().unwrap();
+        let pb: PathBuf = cp.join(&args.path);
+        search_directory = pb.as_path().parent().unwrap().to_path_buf();
+        println!("search_directory is {}", search_directory.display());
+    }
+    let mut reader = BufReader::new(f);
+    let mut str_buf: String = String::default();
+    let num_bytes = reader.read_to_string(&mut str_buf);
+    if num_bytes.is_ok() {
+        let n_bytes = num_bytes.unwrap();
+        println!("{} bytes read", n_bytes);
+    }
+    // parser
+    let pairs = UsdaParser::parse(Rule::usda, &str_buf).unwrap_or_else(|e| panic!("{}", e));
+    let mut root_prims: Vec<UsdPrim> = Vec::new();
+    for pair in pairs {
+        if pair.as_rule() == Rule::prim {
+            root_prims.push(parse_prim(pair, ""));
+        }
+    }
+    let mut prims: HashMap<String, &UsdPrim> = HashMap::new();
+    for prim in root_prims.iter() {
+        index_prims(prim, &mut prims);
+    }
+    println!("number of prims = {:?}", prims.len());
+    let mut gathered: Gathered = Gathered::default();
+    for prim in root_prims.iter() {
+        gather(prim, &Transform::default(), None, &mut gathered);
+    }
+    // meshes
+    let mi: MediumInterface = MediumInterface::default();
+    for (prim, obj_to_world, binding) in gathered.meshes.iter() {
+        let (material, emission): (Arc<Material>, Spectrum) = if let Some(binding) = binding {
+            named_materials
+                .entry(binding.clone())
+                .or_insert_with(|| {
+                    make_preview_surface(
+                        find_surface_shader(&prims, binding),
+                        Spectrum::new(0.18 as Float),
+                        &prims,
+                        &search_directory,
+                    )
+                })
+                .clone()
+        } else {
+            // unbound meshes use their display color
+            let display_color: Vec<Float> = prim.get_floats("primvars:displayColor");
+            let color: Spectrum = if display_color.len() >= 3 {
+                Spectrum::rgb(display_color[0], display_color[1], display_color[2])
+            } else {
+                Spectrum::new(0.18 as Float)
+            };
+            make_preview_surface(None, color, &prims, &search_directory)
+        };
+        let two_sided: bool = prim.get_float("doubleSided", 0.0) != 0.0 as Float;
+        for shape in make_mesh_shapes(prim, obj_to_world) {
+            let mut area_light: Option<Arc<Light>> = None;
+            if !emission.is_black() {
+                let light: Arc<Light> =
+                    Arc::new(Light::DiffuseArea(Box::new(DiffuseAreaLight::new(
+                        obj_to_world,
+                        &mi,
+                        &emission,
+                        1,
+                        shape.clone(),
+                        two_sided,
+                    ))));
+                lights.push(light.clone());
+                area_light = Some(light);
+            }
+            primitives.push(Arc::new(Primitive::Geometric(Box::new(
+                GeometricPrimitive::new(
+                    shape,
+                    Some(material.clone()),
+                    area_light,
+                    Some(Arc::new(mi.clone())),
+                ),
+            ))));
+        }
+    }
+    // lights
+    for (prim, light_to_world) in gathered.lights.iter() {
+        let color: Spectrum = prim
+            .get_input("color")
+            .and_then(value_to_spectrum)
+            .unwrap_or_else(|| Spectrum::new(1.0 as Float));
+        let exposure: Float = prim.get_input_float("exposure", 0.0);
+        if prim.type_name == "DistantLight" {
+            // lights shine along the negative z-axis
+            let w_light: Vector3f = Vector3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: 1.0 as Float,
+            };
+            let intensity: Float = prim.get_input_float("intensity", 50000.0);
+            lights.push(Arc::new(Light::Distant(Box::new(DistantLight::new(
+                light_to_world,
+                &(color * (intensity * exposure.exp2())),
+                &w_light,
+            )))));
+        } else {
+            // USD's dome has the y-axis as pole, pbrt the z-axis
+            let y_up: Transform = Transform::rotate_x(-90.0 as Float);
+            let intensity: Float = prim.get_input_float("intensity", 1.0);
+            let mut texmap: String = String::from("");
+            if let Some(file) = prim.get_input("texture:file").and_then(|v| v.as_str()) {
+                let mut path: PathBuf = PathBuf::from(file);
+                if path.is_relative() {
+                    path = search_directory.join(path);
+                }
+                if path.exists() {
+                    texmap = path.to_str().unwrap_or("").to_string();
+                } else {
+                    println!("WARNING: {}: texture file {:?} not found", prim.path, file);
+                }
+            }
+            lights.push(Arc::new(Light::InfiniteArea(Box::new(
+                InfiniteAreaLight::new(
+                    &(*light_to_world * y_up),
+                    &(color * (intensity * exposure.exp2())),
+                    1,
+                    texmap,
+                ),
+            ))));
+        }
+    }
+    if lights.is_empty() {
+        println!("WARNING: no lights found, the image will be black");
+    }
+    // camera
+    if gathered.cameras.len() > 1 {
+        println!(
+            "WARNING: {} cameras found, using {}",
+            gathered.cameras.len(),
+            gathered.cameras[0].0.path
+        );
+    }
+    let (camera, cam_to_world): (&UsdPrim, Transform) =
+        if let Some(camera) = gathered.cameras.first() {
+            *camera
+        } else {
+            panic!("No Camera prim found.");
+        };
+    if camera.get_string("projection").as_deref() == Some("orthographic") {
+        println!("WARNING: orthographic projection not supported, using perspective");
+    }
+    // apertures and focal length are in the same units
+    let focal_length: Float = camera.get_float("focalLength", 50.0);
+    let horizontal_aperture: Float = camera.get_float("horizontalAperture", 20.955);
+    let vertical_aperture: Float = camera.get_float("verticalAperture", 15.2908);
+    let yres: i32 = (xres as Float * vertical_aperture / horizontal_aperture).round() as i32;
+    // pbrt's field of view is the one of the shorter image axis
+    let aperture: Float = horizontal_aperture.min(vertical_aperture);
+    let fov: Float =
+        (2.0 as Float * (aperture / (2.0 as Float * focal_length)).atan()).to_degrees();
+    // USD cameras look along the negative z-axis
+    let cam_to_world: Transform =
+        cam_to_world * Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+    let animated_cam_to_world: AnimatedTransform =
+        AnimatedTransform::new(&cam_to_world, 0.0, &cam_to_world, 1.0);
+    println!("samples_per_pixel = {:?}", samples_per_pixel);
+    println!("number of lights = {:?}", lights.len());
+    println!("number of primitives = {:?}", primitives.len());
+    let some_integrator: Option<Box<Integrator>> = make_path_integrator(
+        filter_width,
+        xres,
+        yres,
+        fov,
+        animated_cam_to_world,
+        args.max_depth,
+        samples_per_pixel as i32,
+    );
+    if let Some(mut integrator) = some_integrator {
+        let scene = make_scene(&primitives, lights);
+        let num_threads: u8 = num_cpus::get() as u8;
+        integrator.render(&scene, num_threads);
+    } else {
+        panic!("Unable to create integrator.");
+    }
+    Ok(())
+}
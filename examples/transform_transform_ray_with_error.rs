@@ -1,4 +1,5 @@
 use pbrt::core::geometry::{Point3f, Ray, Vector3f};
+use pbrt::core::pbrt::Float;
 use pbrt::core::transform::Transform;
 
 fn main() {
@@ -20,7 +21,7 @@ fn main() {
     let r: Ray = Ray {
         o: o,
         d: d,
-        t_max: std::f32::INFINITY,
+        t_max: Float::INFINITY,
         time: 0.0,
         medium: None,
         differential: None,
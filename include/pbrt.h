@@ -0,0 +1,191 @@
+#ifndef PBRT_H
+#define PBRT_H
+
+/* Generated with cbindgen from capi/src/lib.rs, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success.
+ */
+#define PBRT_OK 0
+
+/**
+ * A pointer argument was NULL or a string was not valid UTF-8.
+ */
+#define PBRT_INVALID_ARGUMENT -1
+
+/**
+ * The buffer does not match the size of the image.
+ */
+#define PBRT_WRONG_SIZE -2
+
+/**
+ * Rendering failed (see the messages on stdout).
+ */
+#define PBRT_RENDER_FAILED -3
+
+/**
+ * A parsed scene (opaque for C).
+ */
+typedef struct PbrtScene PbrtScene;
+
+/**
+ * Progress of a render, see **pbrt_set_progress_callback()**.
+ */
+typedef struct PbrtProgress {
+  /**
+   * 0 to 100
+   */
+  double percent;
+  /**
+   * units of work (e.g. tiles) done so far
+   */
+  uint64_t done;
+  uint64_t total;
+  double rays_per_second;
+  double elapsed_seconds;
+  /**
+   * negative if not known yet
+   */
+  double eta_seconds;
+  /**
+   * resident memory of the process (0 if not known)
+   */
+  uint64_t memory_bytes;
+} PbrtProgress;
+
+/**
+ * Gets the progress and the *user_data* given to
+ * **pbrt_set_progress_callback()**.
+ */
+typedef void (*PbrtProgressCallback)(const struct PbrtProgress *progress, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses a scene file (and the files it includes). Returns NULL if
+ * the file can't be parsed (the errors get printed). The scene has to be
+ * released with **pbrt_scene_free()**.
+ *
+ * # Safety
+ *
+ * *filename* has to be NULL or point to a NUL terminated string.
+ */
+struct PbrtScene *pbrt_scene_from_file(const char *filename);
+
+/**
+ * Parses a scene description from memory (a NUL terminated string).
+ * Relative filenames (includes, meshes, textures) are resolved
+ * against *search_directory*, which can be NULL. Returns NULL if the
+ * scene can't be parsed.
+ *
+ * # Safety
+ *
+ * *text* and *search_directory* have to be NULL or point to NUL
+ * terminated strings.
+ */
+struct PbrtScene *pbrt_scene_from_memory(const char *text, const char *search_directory);
+
+/**
+ * Releases a scene (NULL is ignored).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a scene returned by
+ * **pbrt_scene_from_file()** or **pbrt_scene_from_memory()**, which
+ * wasn't released before, and isn't used afterwards.
+ */
+void pbrt_scene_free(struct PbrtScene *scene);
+
+/**
+ * Number of threads used for rendering (0 means all cores).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * which no other thread uses at the same time.
+ */
+int pbrt_scene_set_threads(struct PbrtScene *scene, int number_of_threads);
+
+/**
+ * Overrides the samples per pixel of the scene (0 restores them).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * which no other thread uses at the same time.
+ */
+int pbrt_scene_set_pixel_samples(struct PbrtScene *scene, int spp);
+
+/**
+ * Overrides the resolution of the scene (0 x 0 restores it).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * which no other thread uses at the same time.
+ */
+int pbrt_scene_set_resolution(struct PbrtScene *scene, int x_resolution, int y_resolution);
+
+/**
+ * Overrides the integrator of the scene (e.g. "path", NULL restores
+ * it).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * which no other thread uses at the same time, *name* NULL or a NUL
+ * terminated string.
+ */
+int pbrt_scene_set_integrator(struct PbrtScene *scene, const char *name);
+
+/**
+ * Size of the image **pbrt_scene_render()** will produce (taking
+ * the crop window into account).
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * *width* and *height* NULL or valid pointers to write an int to.
+ */
+int pbrt_scene_image_size(const struct PbrtScene *scene, int *width, int *height);
+
+/**
+ * Renders the scene into *buffer*, which has to hold *width* x
+ * *height* RGB triples (linear, not gamma corrected, row by row, top
+ * row first), see **pbrt_scene_image_size()**. Blocks until the
+ * image is done or **pbrt_cancel()** gets called. Returns
+ * PBRT_WRONG_SIZE (before rendering) if *width* and *height* aren't
+ * the size of the image.
+ *
+ * # Safety
+ *
+ * *scene* has to be NULL or a valid scene (see **pbrt_scene_free()**),
+ * *buffer* NULL or valid for writing *width* x *height* x 3 floats.
+ */
+int pbrt_scene_render(const struct PbrtScene *scene, float *buffer, int width, int height);
+
+/**
+ * Stops the render which is currently running (can be called from
+ * any thread). The buffer contains whatever was rendered so far.
+ */
+void pbrt_cancel(void);
+
+/**
+ * Calls *callback* (from a render thread) with the progress of all
+ * following renders, about twice a second and once more when the
+ * render is done. NULL removes the callback.
+ */
+void pbrt_set_progress_callback(PbrtProgressCallback callback, void *user_data);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* PBRT_H */
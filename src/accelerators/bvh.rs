@@ -2,6 +2,7 @@
 use std;
 use std::sync::Arc;
 // others
+use log::warn;
 // use time::PreciseTime;
 use typed_arena::Arena;
 // pbrt
@@ -9,13 +10,20 @@ use crate::core::geometry::{bnd3_union_bnd3, bnd3_union_pnt3};
 use crate::core::geometry::{Bounds3f, Point3f, Ray, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::light::Light;
+use crate::core::lowmemory::low_memory;
 use crate::core::material::Material;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::primitive::Primitive;
+use crate::core::stats::{StatCounter, StatMemoryCounter, StatRatio};
 
 // see bvh.h
 
+static TREE_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/BVH tree");
+static PRIMS_PER_LEAF: StatRatio = StatRatio::new("BVH/Primitives per leaf node");
+static INTERIOR_NODES: StatCounter = StatCounter::new("BVH/Interior nodes");
+static LEAF_NODES: StatCounter = StatCounter::new("BVH/Leaf nodes");
+
 #[derive(Debug, Clone)]
 pub enum SplitMethod {
     SAH,
@@ -66,6 +74,9 @@ impl<'a> Default for BVHBuildNode<'a> {
 
 impl<'a> BVHBuildNode<'a> {
     pub fn init_leaf(&mut self, first: usize, n: usize, b: &Bounds3f) {
+        LEAF_NODES.inc();
+        PRIMS_PER_LEAF.add_num(n as u64);
+        PRIMS_PER_LEAF.add_denom(1);
         self.first_prim_offset = first;
         self.n_primitives = n;
         self.bounds = *b;
@@ -78,6 +89,7 @@ impl<'a> BVHBuildNode<'a> {
         c0: &'a mut BVHBuildNode<'a>,
         c1: &'a mut BVHBuildNode<'a>,
     ) {
+        INTERIOR_NODES.inc();
         self.n_primitives = 0;
         self.bounds = bnd3_union_bnd3(&c0.bounds, &c1.bounds);
         self.child1 = Some(c0);
@@ -101,23 +113,284 @@ impl Default for BucketInfo {
     }
 }
 
+/// Nodes are 32 bytes and aligned to 32 bytes, the two children of
+/// an interior node are neighbors, so both of them share a 64 byte
+/// cache line.
 #[derive(Debug, Default, Copy, Clone)]
+#[repr(C, align(32))]
 pub struct LinearBVHNode {
-    bounds: Bounds3f,
+    pub bounds: Bounds3f,
     // in C++ a union { int primitivesOffset;     // leaf
     //                  int secondChildOffset; }; // interior
-    offset: i32,
-    n_primitives: u16,
-    axis: u8,
+    // here the interior node stores the offset of its first child,
+    // the second child follows right after it
+    pub offset: i32,
+    pub n_primitives: u16,
+    pub axis: u8,
     pad: u8,
 }
 
+/// A **LinearBVHNode** with its bounds quantized to 16 bits per
+/// coordinate, relative to the (dequantized) bounds of its parent.
+/// The quantized bounds are rounded outwards, so they contain the
+/// original ones. Nodes are 20 instead of 32 bytes.
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct QuantizedBVHNode {
+    pub q_min: [u16; 3],
+    pub q_max: [u16; 3],
+    pub offset: u32,
+    pub n_primitives: u8,
+    pub axis: u8,
+}
+
+impl QuantizedBVHNode {
+    fn new(node: &LinearBVHNode, parent: &Bounds3f) -> Self {
+        let mut q_min: [u16; 3] = [0_u16; 3];
+        let mut q_max: [u16; 3] = [u16::MAX; 3];
+        for axis in 0..3_u8 {
+            let (lo, hi): (Float, Float) = (parent.p_min[axis], parent.p_max[axis]);
+            let extent: Float = hi - lo;
+            if extent <= 0.0 as Float || !extent.is_finite() {
+                continue;
+            }
+            let scale: Float = u16::MAX as Float / extent;
+            let (b_min, b_max): (Float, Float) = (node.bounds.p_min[axis], node.bounds.p_max[axis]);
+            let i: usize = axis as usize;
+            q_min[i] = ((b_min - lo) * scale)
+                .floor()
+                .max(0.0 as Float)
+                .min(u16::MAX as Float) as u16;
+            while q_min[i] > 0 && dequantize(lo, hi, q_min[i]) > b_min {
+                q_min[i] -= 1;
+            }
+            q_max[i] = ((b_max - lo) * scale)
+                .ceil()
+                .min(u16::MAX as Float)
+                .max(0.0 as Float) as u16;
+            while q_max[i] < u16::MAX && dequantize(lo, hi, q_max[i]) < b_max {
+                q_max[i] += 1;
+            }
+        }
+        QuantizedBVHNode {
+            q_min,
+            q_max,
+            offset: node.offset as u32,
+            n_primitives: node.n_primitives as u8,
+            axis: node.axis,
+        }
+    }
+    fn bounds(&self, parent: &Bounds3f) -> Bounds3f {
+        let corner = |q: &[u16; 3]| Point3f {
+            x: dequantize(parent.p_min.x, parent.p_max.x, q[0]),
+            y: dequantize(parent.p_min.y, parent.p_max.y, q[1]),
+            z: dequantize(parent.p_min.z, parent.p_max.z, q[2]),
+        };
+        Bounds3f {
+            p_min: corner(&self.q_min),
+            p_max: corner(&self.q_max),
+        }
+    }
+}
+
+fn dequantize(lo: Float, hi: Float, q: u16) -> Float {
+    match q {
+        0 => lo,
+        u16::MAX => hi,
+        _ => lo + (hi - lo) * (q as Float / u16::MAX as Float),
+    }
+}
+
+/// The nodes of a BVH for the low-memory mode (see **lowmemory**), in
+/// the same layout as the **LinearBVHNode**s they were made of. The
+/// traversal keeps the dequantized bounds of the nodes on its stack.
+#[derive(Debug, Default, Clone)]
+pub struct QuantizedBVH {
+    /// the bounds of the root node
+    pub bounds: Bounds3f,
+    pub nodes: Vec<QuantizedBVHNode>,
+}
+
+impl QuantizedBVH {
+    pub fn new(nodes: &[LinearBVHNode]) -> Self {
+        if nodes.is_empty() {
+            return QuantizedBVH::default();
+        }
+        let bounds: Bounds3f = nodes[0].bounds;
+        let mut quantized: Vec<QuantizedBVHNode> = vec![QuantizedBVHNode::default(); nodes.len()];
+        // nodes with the dequantized bounds of their parent
+        let mut to_visit: Vec<(usize, Bounds3f)> = vec![(0, bounds)];
+        while let Some((index, parent)) = to_visit.pop() {
+            let node: &LinearBVHNode = &nodes[index];
+            quantized[index] = QuantizedBVHNode::new(node, &parent);
+            if node.n_primitives == 0 {
+                let node_bounds: Bounds3f = quantized[index].bounds(&parent);
+                let offset: usize = node.offset as usize;
+                to_visit.push((offset, node_bounds));
+                to_visit.push((offset + 1, node_bounds));
+            }
+        }
+        QuantizedBVH {
+            bounds,
+            nodes: quantized,
+        }
+    }
+    /// The nodes with dequantized bounds (e.g. to refit them).
+    pub fn to_linear(&self) -> Vec<LinearBVHNode> {
+        let mut nodes: Vec<LinearBVHNode> = vec![LinearBVHNode::default(); self.nodes.len()];
+        let mut to_visit: Vec<(usize, Bounds3f)> = Vec::new();
+        if !self.nodes.is_empty() {
+            to_visit.push((0, self.bounds));
+        }
+        while let Some((index, parent)) = to_visit.pop() {
+            let node: &QuantizedBVHNode = &self.nodes[index];
+            let bounds: Bounds3f = node.bounds(&parent);
+            nodes[index] = LinearBVHNode {
+                bounds,
+                offset: node.offset as i32,
+                n_primitives: u16::from(node.n_primitives),
+                axis: node.axis,
+                pad: 0_u8,
+            };
+            if node.n_primitives == 0 {
+                let offset: usize = node.offset as usize;
+                to_visit.push((offset, bounds));
+                to_visit.push((offset + 1, bounds));
+            }
+        }
+        nodes
+    }
+    /// Calls *hit_primitive* (with the index of the primitive) for the
+    /// primitives in the leaves along the ray, it returns whether the
+    /// primitive was hit (and shortened the ray).
+    pub fn intersect<F>(&self, ray: &mut Ray, mut hit_primitive: F) -> bool
+    where
+        F: FnMut(&mut Ray, usize) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut hit: bool = false;
+        let inv_dir: Vector3f = Vector3f {
+            x: 1.0 / ray.d.x,
+            y: 1.0 / ray.d.y,
+            z: 1.0 / ray.d.z,
+        };
+        let dir_is_neg: [u8; 3] = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+        let mut to_visit_offset: usize = 0;
+        let mut nodes_to_visit: [(u32, Bounds3f); 64] = [(0_u32, Bounds3f::default()); 64];
+        let mut current: (u32, Bounds3f) = (0_u32, self.bounds);
+        loop {
+            let node: &QuantizedBVHNode = &self.nodes[current.0 as usize];
+            if current.1.intersect_p(ray, &inv_dir, &dir_is_neg) {
+                let offset: usize = node.offset as usize;
+                if node.n_primitives > 0 {
+                    for i in offset..offset + node.n_primitives as usize {
+                        if hit_primitive(ray, i) {
+                            hit = true;
+                        }
+                    }
+                } else {
+                    // put far BVH node on the stack, advance to near node
+                    let (near, far): (usize, usize) = if dir_is_neg[node.axis as usize] == 1_u8 {
+                        (offset + 1, offset)
+                    } else {
+                        (offset, offset + 1)
+                    };
+                    nodes_to_visit[to_visit_offset] =
+                        (far as u32, self.nodes[far].bounds(&current.1));
+                    to_visit_offset += 1;
+                    current = (near as u32, self.nodes[near].bounds(&current.1));
+                    continue;
+                }
+            }
+            if to_visit_offset == 0 {
+                break;
+            }
+            to_visit_offset -= 1;
+            current = nodes_to_visit[to_visit_offset];
+        }
+        hit
+    }
+    /// Like **intersect()**, but stops at the first primitive which
+    /// *hit_primitive* reports as hit.
+    pub fn intersect_p<F>(&self, ray: &Ray, mut hit_primitive: F) -> bool
+    where
+        F: FnMut(usize) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv_dir: Vector3f = Vector3f {
+            x: 1.0 / ray.d.x,
+            y: 1.0 / ray.d.y,
+            z: 1.0 / ray.d.z,
+        };
+        let dir_is_neg: [u8; 3] = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+        let mut to_visit_offset: usize = 0;
+        let mut nodes_to_visit: [(u32, Bounds3f); 64] = [(0_u32, Bounds3f::default()); 64];
+        let mut current: (u32, Bounds3f) = (0_u32, self.bounds);
+        loop {
+            let node: &QuantizedBVHNode = &self.nodes[current.0 as usize];
+            if current.1.intersect_p(ray, &inv_dir, &dir_is_neg) {
+                let offset: usize = node.offset as usize;
+                if node.n_primitives > 0 {
+                    for i in offset..offset + node.n_primitives as usize {
+                        if hit_primitive(i) {
+                            return true;
+                        }
+                    }
+                } else {
+                    let (near, far): (usize, usize) = if dir_is_neg[node.axis as usize] == 1_u8 {
+                        (offset + 1, offset)
+                    } else {
+                        (offset, offset + 1)
+                    };
+                    nodes_to_visit[to_visit_offset] =
+                        (far as u32, self.nodes[far].bounds(&current.1));
+                    to_visit_offset += 1;
+                    current = (near as u32, self.nodes[near].bounds(&current.1));
+                    continue;
+                }
+            }
+            if to_visit_offset == 0 {
+                break;
+            }
+            to_visit_offset -= 1;
+            current = nodes_to_visit[to_visit_offset];
+        }
+        false
+    }
+}
+
+/// Depth (in sibling pairs) of the treelets **flatten_bvh_tree()**
+/// lays out next to each other. A treelet of depth 3 (up to 14
+/// nodes) fits into 7 cache lines.
+const TREELET_DEPTH: usize = 3;
+
+/// The parent of each node and the leaf of each primitive, built on
+/// demand by **set_primitive()**.
+#[derive(Debug, Default, Clone)]
+struct RefitIndex {
+    parents: Vec<u32>,
+    leaves: Vec<u32>,
+}
+
 // BVHAccel -> Aggregate -> Primitive
 pub struct BVHAccel {
-    max_prims_in_node: usize,
-    split_method: SplitMethod,
     pub primitives: Vec<Arc<Primitive>>,
+    /// empty if the nodes were quantized (see **quantize()**)
     pub nodes: Vec<LinearBVHNode>,
+    pub quantized: Option<QuantizedBVH>,
+    refit_index: Option<RefitIndex>,
 }
 
 impl BVHAccel {
@@ -126,57 +399,71 @@ impl BVHAccel {
         max_prims_in_node: usize,
         split_method: SplitMethod,
     ) -> Self {
-        let bvh = Arc::new(BVHAccel {
-            max_prims_in_node: std::cmp::min(max_prims_in_node, 255),
-            split_method: split_method.clone(),
-            primitives: p,
-            nodes: Vec::new(),
-        });
-        let num_prims = bvh.primitives.len();
-        if num_prims == 0_usize {
-            let unwrapped = Arc::try_unwrap(bvh);
-            return unwrapped.ok().unwrap();
+        let bounds: Vec<Bounds3f> = p.iter().map(|primitive| primitive.world_bound()).collect();
+        let (nodes, order) = BVHAccel::build_nodes(
+            &bounds,
+            std::cmp::min(max_prims_in_node, 255),
+            &split_method,
+        );
+        // primitives.swap(orderedPrims);
+        let primitives: Vec<Arc<Primitive>> = order.into_iter().map(|i| p[i].clone()).collect();
+        if !nodes.is_empty() {
+            TREE_BYTES.add(
+                nodes.len() * std::mem::size_of::<LinearBVHNode>()
+                    + std::mem::size_of::<BVHAccel>()
+                    + primitives.len() * std::mem::size_of::<Arc<Primitive>>(),
+            );
         }
-        let mut primitive_info = vec![BVHPrimitiveInfo::default(); num_prims];
-        for (i, item) in primitive_info.iter_mut().enumerate().take(num_prims) {
-            let world_bound = bvh.primitives[i].world_bound();
-            *item = BVHPrimitiveInfo::new(i, world_bound);
+        BVHAccel {
+            primitives,
+            nodes,
+            quantized: None,
+            refit_index: None,
         }
+    }
+    /// Builds the (flattened) nodes of a BVH over primitives with the
+    /// given bounds, returns them together with the order of the
+    /// primitives in the leaves (as indices into *bounds*).
+    pub fn build_nodes(
+        bounds: &[Bounds3f],
+        max_prims_in_node: usize,
+        split_method: &SplitMethod,
+    ) -> (Vec<LinearBVHNode>, Vec<usize>) {
+        let num_prims = bounds.len();
+        if num_prims == 0_usize {
+            return (Vec::new(), Vec::new());
+        }
+        let mut primitive_info: Vec<BVHPrimitiveInfo> = bounds
+            .iter()
+            .enumerate()
+            .map(|(i, world_bound)| BVHPrimitiveInfo::new(i, *world_bound))
+            .collect();
         // TODO: if (splitMethod == SplitMethod::HLBVH)
         let arena: Arena<BVHBuildNode> = Arena::with_capacity(1024 * 1024);
-        let mut total_nodes: usize = 0;
-        let mut ordered_prims: Vec<Arc<Primitive>> = Vec::with_capacity(num_prims);
+        let mut ordered_prims: Vec<usize> = Vec::with_capacity(num_prims);
         // println!("BVHAccel::recursive_build(..., {}, ...)", num_prims);
         // let start = PreciseTime::now();
         let root = BVHAccel::recursive_build(
-            bvh, // instead of self
             &arena,
             &mut primitive_info,
             0,
             num_prims,
-            &mut total_nodes,
+            max_prims_in_node,
+            split_method,
             &mut ordered_prims,
         );
+        let total_nodes: usize = arena.len();
         // let end = PreciseTime::now();
         // println!("{} seconds for building BVH ...", start.to(end));
         // flatten first
-        let mut nodes = vec![LinearBVHNode::default(); total_nodes];
-        let mut offset: usize = 0;
         // println!("BVHAccel::flatten_bvh_tree(...)");
         // let start = PreciseTime::now();
-        BVHAccel::flatten_bvh_tree(root, &mut nodes, &mut offset);
+        let nodes: Vec<LinearBVHNode> = BVHAccel::flatten_bvh_tree(root, total_nodes);
         // let end = PreciseTime::now();
         // println!("{} seconds for flattening BVH ...", start.to(end));
-        assert!(nodes.len() == total_nodes);
-        // primitives.swap(orderedPrims);
-        let bvh_ordered_prims = Arc::new(BVHAccel {
-            max_prims_in_node: std::cmp::min(max_prims_in_node, 255),
-            split_method,
-            primitives: ordered_prims,
-            nodes,
-        });
-        let unwrapped = Arc::try_unwrap(bvh_ordered_prims);
-        unwrapped.ok().unwrap()
+        // an interior root is followed by an unused node (to align the pairs)
+        assert!(nodes.len() == total_nodes || nodes.len() == total_nodes + 1);
+        (nodes, ordered_prims)
     }
     pub fn create(prims: Vec<Arc<Primitive>>, ps: &ParamSet) -> Primitive {
         let split_method_name: String = ps.find_one_string("splitmethod", String::from("sah"));
@@ -190,31 +477,30 @@ impl BVHAccel {
         } else if split_method_name == "equal" {
             split_method = SplitMethod::EqualCounts;
         } else {
-            println!(
-                "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
+            warn!(
+                "BVH split method \"{}\" unknown.  Using \"sah\".",
                 split_method_name
             );
             split_method = SplitMethod::SAH;
         }
         let max_prims_in_node: i32 = ps.find_one_int("maxnodeprims", 4);
-        Primitive::BVH(Box::new(BVHAccel::new(
-            prims,
-            max_prims_in_node as usize,
-            split_method,
-        )))
+        let mut bvh: BVHAccel = BVHAccel::new(prims, max_prims_in_node as usize, split_method);
+        if low_memory().enabled {
+            bvh.quantize();
+        }
+        Primitive::BVH(Box::new(bvh))
     }
     pub fn recursive_build<'a>(
-        bvh: Arc<BVHAccel>,
         arena: &'a Arena<BVHBuildNode<'a>>,
         primitive_info: &mut Vec<BVHPrimitiveInfo>,
         start: usize,
         end: usize,
-        total_nodes: &mut usize,
-        ordered_prims: &mut Vec<Arc<Primitive>>,
+        max_prims_in_node: usize,
+        split_method: &SplitMethod,
+        ordered_prims: &mut Vec<usize>,
     ) -> &'a mut BVHBuildNode<'a> {
         assert_ne!(start, end);
         let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
-        *total_nodes += 1_usize;
         // compute bounds of all primitives in BVH node
         let mut bounds: Bounds3f = Bounds3f::default();
         for item in primitive_info.iter().take(end).skip(start) {
@@ -226,7 +512,7 @@ impl BVHAccel {
             let first_prim_offset: usize = ordered_prims.len();
             for item in primitive_info.iter().take(end).skip(start) {
                 let prim_num: usize = item.primitive_number;
-                ordered_prims.push(bvh.primitives[prim_num].clone());
+                ordered_prims.push(prim_num);
             }
             node.init_leaf(first_prim_offset, n_primitives, &bounds);
             return node;
@@ -244,13 +530,13 @@ impl BVHAccel {
                 let first_prim_offset: usize = ordered_prims.len();
                 for item in primitive_info.iter().take(end).skip(start) {
                     let prim_num: usize = item.primitive_number;
-                    ordered_prims.push(bvh.primitives[prim_num].clone());
+                    ordered_prims.push(prim_num);
                 }
                 node.init_leaf(first_prim_offset, n_primitives, &bounds);
                 return node;
             } else {
                 // partition primitives based on _splitMethod_
-                match bvh.split_method {
+                match split_method {
                     SplitMethod::Middle => {
                         // TODO
                     }
@@ -316,7 +602,7 @@ impl BVHAccel {
                             // either create leaf or split primitives
                             // at selected SAH bucket
                             let leaf_cost: Float = n_primitives as Float;
-                            if n_primitives > bvh.max_prims_in_node || min_cost < leaf_cost {
+                            if n_primitives > max_prims_in_node || min_cost < leaf_cost {
                                 let (mut left, mut right): (
                                     Vec<BVHPrimitiveInfo>,
                                     Vec<BVHPrimitiveInfo>,
@@ -346,7 +632,7 @@ impl BVHAccel {
                                 let first_prim_offset: usize = ordered_prims.len();
                                 for item in primitive_info.iter().take(end).skip(start) {
                                     let prim_num: usize = item.primitive_number;
-                                    ordered_prims.push(bvh.primitives[prim_num].clone());
+                                    ordered_prims.push(prim_num);
                                 }
                                 node.init_leaf(first_prim_offset, n_primitives, &bounds);
                                 return node;
@@ -356,21 +642,21 @@ impl BVHAccel {
                 }
                 // make sure we get result for c1 before c0
                 let c1 = BVHAccel::recursive_build(
-                    bvh.clone(),
                     arena,
                     primitive_info,
                     mid,
                     end,
-                    total_nodes,
+                    max_prims_in_node,
+                    split_method,
                     ordered_prims,
                 );
                 let c0 = BVHAccel::recursive_build(
-                    bvh,
                     arena,
                     primitive_info,
                     start,
                     mid,
-                    total_nodes,
+                    max_prims_in_node,
+                    split_method,
                     ordered_prims,
                 );
                 node.init_interior(dim, c0, c1);
@@ -378,50 +664,152 @@ impl BVHAccel {
         }
         node
     }
-    pub fn flatten_bvh_tree<'a>(
-        node: &mut BVHBuildNode<'a>,
-        nodes: &mut Vec<LinearBVHNode>,
-        offset: &mut usize,
-    ) -> usize {
-        let my_offset: usize = *offset;
-        *offset += 1;
+    /// Lays out the nodes in a cache friendly order: the root comes
+    /// first, the children of each interior node are stored as a pair
+    /// (starting at an even index), and the pairs of a treelet (of
+    /// depth **TREELET_DEPTH**) are stored next to each other, breadth
+    /// first. Treelets are stored depth first, similar to a van Emde
+    /// Boas layout, so a ray going down the tree touches few cache
+    /// lines (and pages).
+    pub fn flatten_bvh_tree(root: &BVHBuildNode, total_nodes: usize) -> Vec<LinearBVHNode> {
+        let mut nodes: Vec<LinearBVHNode> = Vec::with_capacity(total_nodes + 1);
+        nodes.push(BVHAccel::linear_node(root));
+        if root.n_primitives > 0 {
+            return nodes;
+        }
+        // unused, to align the pairs
+        nodes.push(LinearBVHNode::default());
+        let mut treelets: Vec<(&BVHBuildNode, usize)> = vec![(root, 0)];
+        while let Some(treelet) = treelets.pop() {
+            let mut level: Vec<(&BVHBuildNode, usize)> = vec![treelet];
+            let mut next_treelets: Vec<(&BVHBuildNode, usize)> = Vec::new();
+            for depth in 0..TREELET_DEPTH {
+                let mut next_level: Vec<(&BVHBuildNode, usize)> = Vec::new();
+                for (node, index) in level {
+                    if let (Some(child1), Some(child2)) = (&node.child1, &node.child2) {
+                        let first_child: usize = nodes.len();
+                        nodes[index].offset = first_child as i32;
+                        for (i, child) in [&**child1, &**child2].iter().enumerate() {
+                            let child: &BVHBuildNode = child;
+                            nodes.push(BVHAccel::linear_node(child));
+                            if child.n_primitives == 0 {
+                                if depth + 1 < TREELET_DEPTH {
+                                    next_level.push((child, first_child + i));
+                                } else {
+                                    next_treelets.push((child, first_child + i));
+                                }
+                            }
+                        }
+                    }
+                }
+                level = next_level;
+            }
+            // the first child's treelet gets processed (and stored) first
+            treelets.extend(next_treelets.into_iter().rev());
+        }
+        nodes
+    }
+    /// A leaf, or an interior node without children (yet).
+    fn linear_node(node: &BVHBuildNode) -> LinearBVHNode {
         if node.n_primitives > 0 {
-            // leaf
-            let linear_node = LinearBVHNode {
+            LinearBVHNode {
                 bounds: node.bounds,
                 offset: node.first_prim_offset as i32,
                 n_primitives: node.n_primitives as u16,
                 axis: 0_u8,
                 pad: 0_u8,
-            };
-            nodes[my_offset] = linear_node;
+            }
         } else {
-            // interior
-            if let Some(ref mut child1) = node.child1 {
-                BVHAccel::flatten_bvh_tree(child1, nodes, offset);
+            LinearBVHNode {
+                bounds: node.bounds,
+                offset: 0_i32,
+                n_primitives: 0_u16,
+                axis: node.split_axis,
+                pad: 0_u8,
             }
-            if let Some(ref mut child2) = node.child2 {
-                let linear_node = LinearBVHNode {
-                    bounds: node.bounds,
-                    offset: BVHAccel::flatten_bvh_tree(child2, nodes, offset) as i32,
-                    n_primitives: 0_u16,
-                    axis: node.split_axis,
-                    pad: 0_u8,
+        }
+    }
+    /// Replaces a primitive (e.g. after moving it) and refits the
+    /// bounds of its leaf and of all nodes above it, instead of
+    /// building the BVH again. The structure of the tree doesn't
+    /// change, so primitives moved far away make traversal slower.
+    pub fn set_primitive(&mut self, index: usize, primitive: Arc<Primitive>) {
+        if let Some(quantized) = self.quantized.take() {
+            // refit the dequantized nodes, then quantize all of them again
+            self.nodes = quantized.to_linear();
+            self.set_primitive(index, primitive);
+            self.quantize();
+            return;
+        }
+        self.primitives[index] = primitive;
+        if self.refit_index.is_none() {
+            self.refit_index = Some(self.build_refit_index());
+        }
+        if let Some(ref refit_index) = self.refit_index {
+            let mut node_index: usize = refit_index.leaves[index] as usize;
+            loop {
+                let node: LinearBVHNode = self.nodes[node_index];
+                let offset: usize = node.offset as usize;
+                let bounds: Bounds3f = if node.n_primitives > 0 {
+                    self.primitives[offset + 1..offset + node.n_primitives as usize]
+                        .iter()
+                        .fold(self.primitives[offset].world_bound(), |b, p| {
+                            bnd3_union_bnd3(&b, &p.world_bound())
+                        })
+                } else {
+                    bnd3_union_bnd3(&self.nodes[offset].bounds, &self.nodes[offset + 1].bounds)
                 };
-                nodes[my_offset] = linear_node;
+                self.nodes[node_index].bounds = bounds;
+                if node_index == 0 {
+                    break;
+                }
+                node_index = refit_index.parents[node_index] as usize;
             }
         }
-        my_offset
+    }
+    fn build_refit_index(&self) -> RefitIndex {
+        let mut refit_index: RefitIndex = RefitIndex {
+            parents: vec![0_u32; self.nodes.len()],
+            leaves: vec![0_u32; self.primitives.len()],
+        };
+        let mut to_visit: Vec<usize> = vec![0];
+        while let Some(node_index) = to_visit.pop() {
+            let node: &LinearBVHNode = &self.nodes[node_index];
+            let offset: usize = node.offset as usize;
+            if node.n_primitives > 0 {
+                for leaf in &mut refit_index.leaves[offset..offset + node.n_primitives as usize] {
+                    *leaf = node_index as u32;
+                }
+            } else {
+                for child in offset..offset + 2 {
+                    refit_index.parents[child] = node_index as u32;
+                    to_visit.push(child);
+                }
+            }
+        }
+        refit_index
+    }
+    /// Replaces the nodes by quantized ones (see **QuantizedBVH**),
+    /// which need less memory but make traversal a bit slower.
+    pub fn quantize(&mut self) {
+        self.quantized = Some(QuantizedBVH::new(&self.nodes));
+        self.nodes = Vec::new();
+        self.refit_index = None;
     }
     // Primitive
     pub fn world_bound(&self) -> Bounds3f {
-        if !self.nodes.is_empty() {
+        if let Some(ref quantized) = self.quantized {
+            quantized.bounds
+        } else if !self.nodes.is_empty() {
             self.nodes[0].bounds
         } else {
             Bounds3f::default()
         }
     }
     pub fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
+        if let Some(ref quantized) = self.quantized {
+            return quantized.intersect(ray, |ray, i| self.primitives[i].intersect(ray, isect));
+        }
         if self.nodes.is_empty() {
             return false;
         }
@@ -465,13 +853,13 @@ impl BVHAccel {
                     // put far BVH node on _nodesToVisit_ stack,
                     // advance to near node
                     if dir_is_neg[node.axis as usize] == 1_u8 {
-                        nodes_to_visit[to_visit_offset as usize] = current_node_index + 1_u32;
+                        nodes_to_visit[to_visit_offset as usize] = node.offset as u32;
                         to_visit_offset += 1_u32;
-                        current_node_index = node.offset as u32;
+                        current_node_index = node.offset as u32 + 1_u32;
                     } else {
-                        nodes_to_visit[to_visit_offset as usize] = node.offset as u32;
+                        nodes_to_visit[to_visit_offset as usize] = node.offset as u32 + 1_u32;
                         to_visit_offset += 1_u32;
-                        current_node_index += 1_u32;
+                        current_node_index = node.offset as u32;
                     }
                 }
             } else {
@@ -489,6 +877,9 @@ impl BVHAccel {
         }
     }
     pub fn intersect_p(&self, ray: &Ray) -> bool {
+        if let Some(ref quantized) = self.quantized {
+            return quantized.intersect_p(ray, |i| self.primitives[i].intersect_p(ray));
+        }
         if self.nodes.is_empty() {
             return false;
         }
@@ -523,13 +914,13 @@ impl BVHAccel {
                     to_visit_offset -= 1_u32;
                     current_node_index = nodes_to_visit[to_visit_offset as usize];
                 } else if dir_is_neg[node.axis as usize] == 1_u8 {
-                    nodes_to_visit[to_visit_offset as usize] = current_node_index + 1_u32;
+                    nodes_to_visit[to_visit_offset as usize] = node.offset as u32;
                     to_visit_offset += 1_u32;
-                    current_node_index = node.offset as u32;
+                    current_node_index = node.offset as u32 + 1_u32;
                 } else {
-                    nodes_to_visit[to_visit_offset as usize] = node.offset as u32;
+                    nodes_to_visit[to_visit_offset as usize] = node.offset as u32 + 1_u32;
                     to_visit_offset += 1_u32;
-                    current_node_index += 1_u32;
+                    current_node_index = node.offset as u32;
                 }
             } else {
                 if to_visit_offset == 0_u32 {
@@ -0,0 +1,483 @@
+//! Experimental intersection of ray batches on the GPU (feature
+//! "gpu"). The triangles of the scene and a BVH over them are copied
+//! to the GPU once, and batches of rays (e.g. all camera rays of a
+//! tile, or all shadow rays spawned by them) are intersected by wgpu
+//! compute shaders. Shading stays on the CPU: the closest hit only
+//! returns the primitive and distance, and the **SurfaceInteraction**
+//! is computed by intersecting that single primitive again.
+//!
+//! Only scenes consisting of (world space) triangles without alpha
+//! masks are supported, otherwise **GpuIntersector::new()** returns
+//! None and rendering falls back to the CPU.
+
+// std
+use std::borrow::Cow;
+use std::sync::Arc;
+// others
+use log::{info, warn};
+use wgpu::util::DeviceExt;
+// pbrt
+use crate::accelerators::bvh::{BVHAccel, LinearBVHNode, SplitMethod};
+use crate::core::geometry::Ray;
+use crate::core::pbrt::Float;
+use crate::core::primitive::Primitive;
+use crate::core::scene::Scene;
+use crate::core::shape::Shape;
+
+/// number of threads per workgroup (see *@workgroup_size* in the shader)
+const WORKGROUP_SIZE: u32 = 64;
+/// maximum number of workgroups per dispatch dimension
+const MAX_WORKGROUPS: u32 = 65_535;
+/// marks a ray which didn't hit anything
+const NO_HIT: u32 = 0xffff_ffff;
+
+const SHADER: &str = r#"
+struct Node {
+    bounds_min: vec3<f32>,
+    // first primitive (leaf) or second child (interior)
+    offset: u32,
+    bounds_max: vec3<f32>,
+    // number of primitives | split axis << 16
+    counts: u32,
+};
+
+struct Triangle {
+    p0: vec4<f32>,
+    p1: vec4<f32>,
+    p2: vec4<f32>,
+};
+
+struct GpuRay {
+    o: vec3<f32>,
+    t_max: f32,
+    d: vec3<f32>,
+    pad: f32,
+};
+
+@group(0) @binding(0) var<storage, read> nodes: array<Node>;
+@group(0) @binding(1) var<storage, read> triangles: array<Triangle>;
+@group(0) @binding(2) var<storage, read> rays: array<GpuRay>;
+@group(0) @binding(3) var<storage, read_write> results: array<u32>;
+
+const NO_HIT: u32 = 0xffffffffu;
+
+fn hit_bounds(node: Node, o: vec3<f32>, inv_dir: vec3<f32>, t_max: f32) -> bool {
+    let t0 = (node.bounds_min - o) * inv_dir;
+    let t1 = (node.bounds_max - o) * inv_dir;
+    let t_near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), max(min(t0.z, t1.z), 0.0));
+    let t_far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), min(max(t0.z, t1.z), t_max));
+    // be conservative (like 1 + 2 * gamma(3) in pbrt)
+    return t_near <= t_far * 1.0000004;
+}
+
+fn hit_triangle(tri: Triangle, o: vec3<f32>, d: vec3<f32>, t_max: f32) -> f32 {
+    let e1 = tri.p1.xyz - tri.p0.xyz;
+    let e2 = tri.p2.xyz - tri.p0.xyz;
+    let pv = cross(d, e2);
+    let det = dot(e1, pv);
+    if (det == 0.0) {
+        return -1.0;
+    }
+    let inv_det = 1.0 / det;
+    let tv = o - tri.p0.xyz;
+    let u = dot(tv, pv) * inv_det;
+    if (u < 0.0 || u > 1.0) {
+        return -1.0;
+    }
+    let qv = cross(tv, e1);
+    let v = dot(d, qv) * inv_det;
+    if (v < 0.0 || u + v > 1.0) {
+        return -1.0;
+    }
+    let t = dot(e2, qv) * inv_det;
+    if (t <= 0.0 || t >= t_max) {
+        return -1.0;
+    }
+    return t;
+}
+
+// returns the distance (as bits) and index of the closest (or any) hit
+fn trace(ray: GpuRay, any_hit: bool) -> vec2<u32> {
+    var t_max = ray.t_max;
+    var hit = NO_HIT;
+    let inv_dir = 1.0 / ray.d;
+    var dir_is_neg = array<bool, 3>(inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0);
+    var nodes_to_visit: array<u32, 64>;
+    var to_visit_offset = 0u;
+    var current_node_index = 0u;
+    loop {
+        let node = nodes[current_node_index];
+        if (hit_bounds(node, ray.o, inv_dir, t_max)) {
+            let n_primitives = node.counts & 0xffffu;
+            if (n_primitives > 0u) {
+                for (var i = 0u; i < n_primitives; i = i + 1u) {
+                    let t = hit_triangle(triangles[node.offset + i], ray.o, ray.d, t_max);
+                    if (t > 0.0) {
+                        t_max = t;
+                        hit = node.offset + i;
+                        if (any_hit) {
+                            return vec2<u32>(bitcast<u32>(t_max), hit);
+                        }
+                    }
+                }
+                if (to_visit_offset == 0u) {
+                    break;
+                }
+                to_visit_offset = to_visit_offset - 1u;
+                current_node_index = nodes_to_visit[to_visit_offset];
+            } else {
+                let axis = node.counts >> 16u;
+                // the children are neighbors
+                if (dir_is_neg[axis]) {
+                    nodes_to_visit[to_visit_offset] = node.offset;
+                    current_node_index = node.offset + 1u;
+                } else {
+                    nodes_to_visit[to_visit_offset] = node.offset + 1u;
+                    current_node_index = node.offset;
+                }
+                to_visit_offset = to_visit_offset + 1u;
+            }
+        } else {
+            if (to_visit_offset == 0u) {
+                break;
+            }
+            to_visit_offset = to_visit_offset - 1u;
+            current_node_index = nodes_to_visit[to_visit_offset];
+        }
+    }
+    return vec2<u32>(bitcast<u32>(t_max), hit);
+}
+
+fn ray_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * 64u;
+}
+
+@compute @workgroup_size(64)
+fn closest_hit(@builtin(global_invocation_id) id: vec3<u32>,
+               @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = ray_index(id, groups);
+    if (i >= arrayLength(&rays)) {
+        return;
+    }
+    let hit = trace(rays[i], false);
+    results[2u * i] = hit.x;
+    results[2u * i + 1u] = hit.y;
+}
+
+@compute @workgroup_size(64)
+fn any_hit(@builtin(global_invocation_id) id: vec3<u32>,
+           @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = ray_index(id, groups);
+    if (i >= arrayLength(&rays)) {
+        return;
+    }
+    let hit = trace(rays[i], true);
+    results[i] = select(0u, 1u, hit.y != NO_HIT);
+}
+"#;
+
+/// The closest intersection found on the GPU.
+#[derive(Debug, Copy, Clone)]
+pub struct GpuHit {
+    /// parametric distance along the ray
+    pub t_hit: Float,
+    /// index of the hit triangle (see **GpuIntersector::primitive()**)
+    pub primitive: usize,
+}
+
+pub struct GpuIntersector {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    bind_group_layout: wgpu::BindGroupLayout,
+    closest_pipeline: wgpu::ComputePipeline,
+    any_pipeline: wgpu::ComputePipeline,
+    node_buffer: wgpu::Buffer,
+    triangle_buffer: wgpu::Buffer,
+    /// the triangles in the order of the GPU BVH
+    primitives: Vec<Arc<Primitive>>,
+}
+
+impl GpuIntersector {
+    /// Copies the triangles of the scene to the GPU. Returns None if
+    /// no GPU is available or the scene contains anything else.
+    pub fn new(scene: &Scene) -> Option<GpuIntersector> {
+        let mut triangles: Vec<Arc<Primitive>> = Vec::new();
+        if !collect_triangles(&scene.aggregate, &mut triangles) {
+            warn!("Only triangles (without alpha masks) can be intersected on the GPU.");
+            return None;
+        }
+        if triangles.is_empty() {
+            return None;
+        }
+        let instance: wgpu::Instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter: wgpu::Adapter = match pollster::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        ) {
+            Some(adapter) => adapter,
+            None => {
+                warn!("No GPU adapter found.");
+                return None;
+            }
+        };
+        let (device, queue) = match pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("pbrt"),
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::downlevel_defaults(),
+            },
+            None,
+        )) {
+            Ok(device_and_queue) => device_and_queue,
+            Err(err) => {
+                warn!("Can't use GPU: {}", err);
+                return None;
+            }
+        };
+        info!(
+            "Intersecting rays on the GPU ({}) ...",
+            adapter.get_info().name
+        );
+        // build a BVH over the triangles and flatten it for the GPU
+        let bvh: BVHAccel = BVHAccel::new(triangles, 4, SplitMethod::SAH);
+        let nodes: Vec<u32> = bvh.nodes.iter().flat_map(gpu_node).collect();
+        let mut vertices: Vec<f32> = Vec::with_capacity(bvh.primitives.len() * 12);
+        for primitive in &bvh.primitives {
+            if let Primitive::Geometric(ref geometric) = **primitive {
+                if let Shape::Trngl(ref triangle) = *geometric.shape {
+                    let mesh = triangle.get_mesh();
+                    for v in 0..3 {
+                        let index: usize = (triangle.id * 3) as usize + v;
+                        let p = mesh.p[mesh.vertex_indices[index] as usize];
+                        vertices.extend_from_slice(&[p.x as f32, p.y as f32, p.z as f32, 0.0]);
+                    }
+                }
+            }
+        }
+        let node_buffer: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("bvh nodes"),
+                contents: bytemuck::cast_slice(&nodes),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let triangle_buffer: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("triangles"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("intersect"),
+                entries: &[
+                    storage(0, true),
+                    storage(1, true),
+                    storage(2, true),
+                    storage(3, false),
+                ],
+            });
+        let pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("intersect"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("intersect"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+            });
+        let pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+        let closest_pipeline: wgpu::ComputePipeline = pipeline("closest_hit");
+        let any_pipeline: wgpu::ComputePipeline = pipeline("any_hit");
+        Some(GpuIntersector {
+            device,
+            queue,
+            bind_group_layout,
+            closest_pipeline,
+            any_pipeline,
+            node_buffer,
+            triangle_buffer,
+            primitives: bvh.primitives,
+        })
+    }
+    /// The primitive of a **GpuHit**.
+    pub fn primitive(&self, index: usize) -> &Arc<Primitive> {
+        &self.primitives[index]
+    }
+    /// Finds the closest intersection (if any) of each ray.
+    pub fn intersect(&self, rays: &[Ray]) -> Vec<Option<GpuHit>> {
+        let results: Vec<u32> = self.dispatch(&self.closest_pipeline, rays, 2);
+        results
+            .chunks(2)
+            .map(|hit| {
+                if hit[1] == NO_HIT {
+                    None
+                } else {
+                    Some(GpuHit {
+                        t_hit: f32::from_bits(hit[0]) as Float,
+                        primitive: hit[1] as usize,
+                    })
+                }
+            })
+            .collect()
+    }
+    /// Tests each ray for any intersection (e.g. for shadow rays).
+    pub fn intersect_p(&self, rays: &[Ray]) -> Vec<bool> {
+        self.dispatch(&self.any_pipeline, rays, 1)
+            .iter()
+            .map(|hit| *hit != 0)
+            .collect()
+    }
+    fn dispatch(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        rays: &[Ray],
+        results_per_ray: usize,
+    ) -> Vec<u32> {
+        if rays.is_empty() {
+            return Vec::new();
+        }
+        let mut gpu_rays: Vec<f32> = Vec::with_capacity(rays.len() * 8);
+        for ray in rays {
+            gpu_rays.extend_from_slice(&[
+                ray.o.x as f32,
+                ray.o.y as f32,
+                ray.o.z as f32,
+                ray.t_max as f32,
+                ray.d.x as f32,
+                ray.d.y as f32,
+                ray.d.z as f32,
+                0.0,
+            ]);
+        }
+        let ray_buffer: wgpu::Buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("rays"),
+                    contents: bytemuck::cast_slice(&gpu_rays),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+        let size: u64 = (rays.len() * results_per_ray * std::mem::size_of::<u32>()) as u64;
+        let result_buffer: wgpu::Buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("results"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer: wgpu::Buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read results"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group: wgpu::BindGroup =
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("intersect"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.node_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.triangle_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: ray_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: result_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+        let mut encoder: wgpu::CommandEncoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass: wgpu::ComputePass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: None,
+                    timestamp_writes: None,
+                });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            // spread large batches over two dimensions
+            let groups: u32 = (rays.len() as u32 + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+            let groups_x: u32 = groups.min(MAX_WORKGROUPS);
+            let groups_y: u32 = (groups + groups_x - 1) / groups_x;
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&result_buffer, 0, &read_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+        let slice: wgpu::BufferSlice = read_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("mapping GPU results failed")
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        let results: Vec<u32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        read_buffer.unmap();
+        results
+    }
+}
+
+/// Collects the triangles of an aggregate, returns false for anything
+/// the GPU can't intersect (other shapes, instances, whole meshes of
+/// the low-memory mode, alpha masks).
+fn collect_triangles(primitive: &Arc<Primitive>, triangles: &mut Vec<Arc<Primitive>>) -> bool {
+    match **primitive {
+        Primitive::Geometric(ref geometric) => match *geometric.shape {
+            Shape::Trngl(ref triangle) => {
+                let mesh = triangle.get_mesh();
+                if mesh.alpha_mask.is_some() || mesh.shadow_alpha_mask.is_some() {
+                    return false;
+                }
+                triangles.push(primitive.clone());
+                true
+            }
+            _ => false,
+        },
+        Primitive::Mesh(_) | Primitive::Transformed(_) => false,
+        Primitive::BVH(ref bvh) => bvh
+            .primitives
+            .iter()
+            .all(|p| collect_triangles(p, triangles)),
+        Primitive::KdTree(ref kdtree) => kdtree
+            .primitives
+            .iter()
+            .all(|p| collect_triangles(p, triangles)),
+    }
+}
+
+/// The GPU layout of a BVH node (see *struct Node* in the shader).
+fn gpu_node(node: &LinearBVHNode) -> Vec<u32> {
+    vec![
+        (node.bounds.p_min.x as f32).to_bits(),
+        (node.bounds.p_min.y as f32).to_bits(),
+        (node.bounds.p_min.z as f32).to_bits(),
+        node.offset as u32,
+        (node.bounds.p_max.x as f32).to_bits(),
+        (node.bounds.p_max.y as f32).to_bits(),
+        (node.bounds.p_max.z as f32).to_bits(),
+        u32::from(node.n_primitives) | u32::from(node.axis) << 16,
+    ]
+}
@@ -303,7 +303,7 @@ impl KdTreeAccel {
         // choose split axis position for interior node
         let mut best_axis: i32 = -1;
         let mut best_offset: i32 = -1;
-        let mut best_cost: Float = std::f32::INFINITY;
+        let mut best_cost: Float = Float::INFINITY;
         let old_cost: Float = self.isect_cost as Float * n_primitives as Float;
         let total_sa: Float = node_bounds.surface_area();
         let inv_total_sa: Float = 1.0 as Float / total_sa;
@@ -0,0 +1,65 @@
+// std
+use std::sync::Arc;
+// pbrt
+use crate::accelerators::bvh::{BVHAccel, QuantizedBVH, QuantizedBVHNode, SplitMethod};
+use crate::core::geometry::{Bounds3f, Ray};
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::pbrt::Float;
+use crate::core::stats::StatMemoryCounter;
+use crate::shapes::triangle::TriangleMesh;
+
+static MESH_BVH_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Mesh BVHs");
+
+/// A BVH over the triangles of one mesh for the low-memory mode (see
+/// **lowmemory**). The leaves reference the triangles by their index
+/// into the mesh (4 bytes each) instead of by a **Primitive** with a
+/// **Triangle** shape of their own, and the nodes are quantized.
+pub struct MeshBVH {
+    pub mesh: Arc<TriangleMesh>,
+    /// the triangles in the order of the leaves
+    pub triangles: Vec<u32>,
+    pub bvh: QuantizedBVH,
+}
+
+impl MeshBVH {
+    pub fn new(mesh: Arc<TriangleMesh>) -> Self {
+        let bounds: Vec<Bounds3f> = (0..mesh.n_triangles)
+            .map(|id| mesh.triangle_bound(id))
+            .collect();
+        let (nodes, order) = BVHAccel::build_nodes(&bounds, 4, &SplitMethod::SAH);
+        let mesh_bvh: MeshBVH = MeshBVH {
+            triangles: order.into_iter().map(|id| id as u32).collect(),
+            bvh: QuantizedBVH::new(&nodes),
+            mesh,
+        };
+        MESH_BVH_BYTES.add(mesh_bvh.memory());
+        mesh_bvh
+    }
+    /// Bytes used by the triangle indices and the nodes (not the mesh).
+    pub fn memory(&self) -> usize {
+        self.triangles.len() * std::mem::size_of::<u32>()
+            + self.bvh.nodes.len() * std::mem::size_of::<QuantizedBVHNode>()
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        self.bvh.bounds
+    }
+    pub fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
+        self.bvh.intersect(ray, |ray, i| {
+            let mut t_hit: Float = 0.0;
+            if self
+                .mesh
+                .intersect_triangle(self.triangles[i], ray, &mut t_hit, isect)
+            {
+                ray.t_max = t_hit;
+                true
+            } else {
+                false
+            }
+        })
+    }
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        self.bvh.intersect_p(ray, |i| {
+            self.mesh.intersect_p_triangle(self.triangles[i], ray)
+        })
+    }
+}
@@ -7,6 +7,13 @@
 //!
 //! - BVHAccel
 //! - KdTreeAccel
+//! - MeshBVH (the triangles of one mesh, for the low-memory mode)
+//!
+//! With the feature "gpu" batches of rays can be intersected on the
+//! GPU (see **GpuIntersector**).
 
 pub mod bvh;
+#[cfg(feature = "gpu")]
+pub mod gpu;
 pub mod kdtreeaccel;
+pub mod meshbvh;
@@ -1,866 +1,626 @@
-use pest_derive::*;
-
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Parser)]
-#[grammar = "../examples/pbrt.pest"]
-struct PbrtParser;
-
-// parser
-use pest::Parser;
-
 // command line options
 use structopt::StructOpt;
 // pbrt
+use pbrt::core::api::RenderOverrides;
 use pbrt::core::api::{
-    pbrt_accelerator, pbrt_active_transform_all, pbrt_active_transform_end_time,
-    pbrt_active_transform_start_time, pbrt_area_light_source, pbrt_attribute_begin,
-    pbrt_attribute_end, pbrt_camera, pbrt_cleanup, pbrt_concat_transform, pbrt_coord_sys_transform,
-    pbrt_film, pbrt_init, pbrt_integrator, pbrt_light_source, pbrt_look_at,
-    pbrt_make_named_material, pbrt_make_named_medium, pbrt_material, pbrt_medium_interface,
-    pbrt_named_material, pbrt_object_begin, pbrt_object_end, pbrt_object_instance,
-    pbrt_pixel_filter, pbrt_reverse_orientation, pbrt_rotate, pbrt_sampler, pbrt_scale, pbrt_shape,
-    pbrt_texture, pbrt_transform, pbrt_transform_begin, pbrt_transform_end, pbrt_translate,
-    pbrt_world_begin,
+    pbrt_end_of_input, pbrt_init, pbrt_set_distributed, pbrt_set_light_distributions,
+    pbrt_set_overrides, pbrt_set_stats_only,
 };
-use pbrt::core::api::{ApiState, BsdfState};
-use pbrt::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
-use pbrt::core::paramset::ParamSet;
-use pbrt::core::pbrt::{Float, Spectrum};
-use pbrt::core::transform::Transform;
+use pbrt::core::checkpoint::{set_checkpoint, CheckpointSettings};
+use pbrt::core::distributed::DistributedRole;
+use pbrt::core::export::export_pbrt;
+use pbrt::core::integrator::{
+    cancel_render_on_interrupt, is_render_interrupted, set_progressive, set_render_budget,
+    set_tile_shape, ImageFlush, Progressive, RenderBudget, TileShape,
+};
+use pbrt::core::jsonscene::export_json;
+use pbrt::core::geometry::Point2i;
+use pbrt::core::lightdistrib::LightDistributionCache;
+use pbrt::core::lint::{lint_scene, LintWarning};
+use pbrt::core::lowmemory::{parse_memory_size, set_low_memory, LowMemory};
+use pbrt::core::memory::set_memory_mapping;
+use pbrt::core::microfacet::{set_roughness_mapping, RoughnessMapping};
+use pbrt::core::parseerror::ParseError;
+use pbrt::core::parser::{parse_file, RecordMode, Recorder, Variables};
+use pbrt::core::pathdebug::set_debug_pixel;
+use pbrt::core::scenecache::SceneCache;
 // std
-use std::env;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-/// Parse a PBRT scene file (extension .pbrt) and render it.
+/// Parse a PBRT scene file (extension .pbrt or .json) and render it.
 #[derive(StructOpt)]
 struct Cli {
     /// use specified number of threads for rendering
     #[structopt(short = "t", long = "nthreads", default_value = "0")]
     nthreads: u8,
+    /// read the parsed scene from (or write it to) a binary cache file
+    #[structopt(short = "c", long = "cache", parse(from_os_str))]
+    cache: Option<PathBuf>,
+    /// write the parsed scene to a .pbrt (or .json) file (instead of rendering it)
+    #[structopt(short = "e", long = "export", parse(from_os_str))]
+    export: Option<PathBuf>,
+    /// define a variable (-Dname=value) for ${name} in the scene file
+    #[structopt(short = "D", number_of_values = 1)]
+    defines: Vec<String>,
+    /// override the samples per pixel of the scene file
+    #[structopt(long = "spp")]
+    spp: Option<i32>,
+    /// override the seed of the sampler (renders with the same seed are identical)
+    #[structopt(long = "seed")]
+    seed: Option<i32>,
+    /// override the resolution of the scene file (e.g. 640x480)
+    #[structopt(long = "resolution", parse(try_from_str = parse_resolution))]
+    resolution: Option<(i32, i32)>,
+    /// override the integrator of the scene file (e.g. path)
+    #[structopt(long = "integrator")]
+    integrator: Option<String>,
+    /// write the (PNG) image to this file
+    #[structopt(long = "outfile")]
+    outfile: Option<String>,
+    /// denoise the image before writing it (needs rs_pbrt built with the feature "denoise")
+    #[structopt(long = "denoise")]
+    denoise: bool,
+    /// report statistics and potential problems of the scene (instead of rendering it)
+    #[structopt(long = "stats-only")]
+    stats_only: bool,
+    /// check the scene for problems (undefined names, missing files, ...) without rendering it
+    #[structopt(long = "lint")]
+    lint: bool,
+    /// render the frames of an animation (e.g. 1-24), ${frame} is the frame number
+    #[structopt(long = "frames", parse(try_from_str = parse_frames))]
+    frames: Option<(i32, i32)>,
+    /// paint pixels with NaN (magenta) or infinite (cyan) radiance and report where they came from
+    #[structopt(long = "debug-nans")]
+    debug_nans: bool,
+    /// render only this pixel and dump the paths of its samples to debug_pixel_X_Y.json
+    #[structopt(long = "debug-pixel", number_of_values = 2, value_names = &["X", "Y"])]
+    debug_pixel: Vec<i32>,
+    /// write the paths of --debug-pixel as polylines to debug_pixel_X_Y.obj as well
+    #[structopt(long = "debug-pixel-obj", requires = "debug-pixel")]
+    debug_pixel_obj: bool,
+    /// render tiles of this size (in pixels), or strips this many scanlines high [default: 16, or 1 with --strips]
+    #[structopt(long = "tile-size")]
+    tile_size: Option<i32>,
+    /// render strips of whole scanlines instead of square tiles
+    #[structopt(long = "strips")]
+    strips: bool,
+    /// render the whole image in passes of this many samples per pixel (instead of tile by tile)
+    #[structopt(long = "progressive")]
+    progressive: Option<i64>,
+    /// write the image of --progressive after every N passes, or every N seconds (e.g. 30s) [default: 1]
+    #[structopt(
+        long = "write-every",
+        requires = "progressive",
+        parse(try_from_str = parse_image_flush)
+    )]
+    write_every: Option<ImageFlush>,
+    /// write the state of the render to this file now and then and on Ctrl+C, to continue it with --resume
+    #[structopt(long = "checkpoint")]
+    checkpoint: Option<String>,
+    /// write a checkpoint every N seconds [default: 60]
+    #[structopt(long = "checkpoint-every")]
+    checkpoint_every: Option<u64>,
+    /// continue the render of a checkpoint (and keep writing checkpoints to it, unless --checkpoint says otherwise)
+    #[structopt(long = "resume")]
+    resume: Option<String>,
+    /// stop rendering after this much time (e.g. 90s, 30m, or 2h) and write the image rendered so far
+    #[structopt(long = "max-time", parse(try_from_str = parse_max_time))]
+    max_time: Option<Duration>,
+    /// take at most this many samples per pixel (the first ones of the scene's sampler)
+    #[structopt(long = "max-spp")]
+    max_spp: Option<i64>,
+    /// keep triangle meshes whole and quantize BVH nodes, for scenes which don't fit into memory otherwise
+    #[structopt(long = "low-memory")]
+    low_memory: bool,
+    /// store normals and (u, v) coordinates of meshes as 16-bit floats (implies --low-memory)
+    #[structopt(long = "half-attributes")]
+    half_attributes: bool,
+    /// report the memory used by geometry and accelerators against this budget (e.g. 16G)
+    #[structopt(long = "memory-budget", parse(try_from_str = parse_memory_budget))]
+    memory_budget: Option<usize>,
+    /// map the roughness of all materials to microfacet alpha the same way (none, pbrt, sqrt, or square)
+    #[structopt(long = "roughness-mapping", parse(try_from_str = parse_roughness_mapping))]
+    roughness_mapping: Option<RoughnessMapping>,
+    /// render again whenever the scene (or a file it uses) changes
+    #[structopt(short = "w", long = "watch")]
+    watch: bool,
+    /// render together with workers, which connect to this address (e.g. 0.0.0.0:7878)
+    #[structopt(long = "coordinator", conflicts_with = "worker")]
+    coordinator: Option<String>,
+    /// render tiles for the coordinator at this address (e.g. host:7878), writes no image
+    #[structopt(long = "worker")]
+    worker: Option<String>,
     /// The path to the file to read
     #[structopt(parse(from_os_str))]
     path: std::path::PathBuf,
 }
 
-// Accelerator
-// CoordinateSystem
-// Identity
-// TransformTimes
+fn parse_resolution(s: &str) -> Result<(i32, i32), String> {
+    let xy: Vec<&str> = s.split('x').collect();
+    if xy.len() == 2 {
+        if let (Ok(x), Ok(y)) = (i32::from_str(xy[0]), i32::from_str(xy[1])) {
+            if x > 0 && y > 0 {
+                return Ok((x, y));
+            }
+        }
+    }
+    Err(format!("expected WIDTHxHEIGHT, got {:?}", s))
+}
 
-fn pbrt_bool_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, bool) {
-    // single string with or without brackets
-    let ident = pairs.next();
-    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-    let option = pairs.next();
-    let lbrack = option.clone().unwrap();
-    let string2 = if lbrack.as_str() == "[" {
-        // check for brackets
-        let string = pairs.next();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
-    } else {
-        // no brackets
-        let string = option.clone();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
+fn parse_memory_budget(s: &str) -> Result<usize, String> {
+    parse_memory_size(s).ok_or_else(|| format!("expected a size like 512M or 16G, got {:?}", s))
+}
+
+fn parse_roughness_mapping(s: &str) -> Result<RoughnessMapping, String> {
+    RoughnessMapping::from_name(s)
+        .ok_or_else(|| format!("expected none, pbrt, sqrt, or square, got {:?}", s))
+}
+
+fn parse_image_flush(s: &str) -> Result<ImageFlush, String> {
+    let flush: Option<ImageFlush> = match s.strip_suffix('s') {
+        Some(seconds) => f64::from_str(seconds)
+            .ok()
+            .filter(|seconds| *seconds >= 0.0)
+            .map(|seconds| ImageFlush::Interval(Duration::from_secs_f64(seconds))),
+        None => u32::from_str(s)
+            .ok()
+            .filter(|passes| *passes > 0)
+            .map(ImageFlush::Passes),
     };
-    // return boolean (instead of string)
-    let b: bool;
-    if string2 == "true" {
-        b = true;
-    } else if string2 == "false" {
-        b = false
-    } else {
-        println!(
-            "WARNING: parameter {:?} not well defined, defaulting to false",
-            string
-        );
-        b = false
-    }
-    (string, b)
+    flush.ok_or_else(|| {
+        format!(
+            "expected a number of passes or seconds (e.g. 30s), got {:?}",
+            s
+        )
+    })
 }
 
-fn pbrt_float_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<Float>) {
-    let mut floats: Vec<Float> = Vec::new();
-    // single float or several floats using brackets
-    let ident = pairs.next();
-    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-    let option = pairs.next();
-    let lbrack = option.clone().unwrap();
-    if lbrack.as_str() == "[" {
-        // check for brackets
-        let mut number = pairs.next();
-        while number.is_some() {
-            let pair = number.unwrap().clone();
-            if pair.as_str() == "]" {
-                // closing bracket found
-                break;
-            } else {
-                let float: Float = f32::from_str(pair.as_span().as_str()).unwrap();
-                floats.push(float);
-            }
-            number = pairs.next();
-        }
-    } else {
-        // no brackets
-        let mut number = option.clone();
-        while number.is_some() {
-            let pair = number.unwrap().clone();
-            let float: Float = f32::from_str(pair.as_span().as_str()).unwrap();
-            floats.push(float);
-            number = pairs.next();
+fn parse_max_time(s: &str) -> Result<Duration, String> {
+    let (number, unit): (&str, f64) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1.0),
+        Some((i, 'm')) => (&s[..i], 60.0),
+        Some((i, 'h')) => (&s[..i], 3600.0),
+        _ => (s, 1.0),
+    };
+    f64::from_str(number)
+        .ok()
+        .filter(|time| *time > 0.0)
+        .map(|time| Duration::from_secs_f64(time * unit))
+        .ok_or_else(|| format!("expected a time like 90s, 30m, or 2h, got {:?}", s))
+}
+
+fn parse_frames(s: &str) -> Result<(i32, i32), String> {
+    let (first, last) = match s.find('-') {
+        // allow negative frame numbers
+        Some(pos) if pos > 0 => (i32::from_str(&s[..pos]), i32::from_str(&s[pos + 1..])),
+        _ => (i32::from_str(s), i32::from_str(s)),
+    };
+    if let (Ok(first), Ok(last)) = (first, last) {
+        if first <= last {
+            return Ok((first, last));
         }
     }
-    (string, floats)
+    Err(format!(
+        "expected FIRST-LAST (or a single frame), got {:?}",
+        s
+    ))
 }
 
-fn pbrt_integer_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<i32>) {
-    let mut integers: Vec<i32> = Vec::new();
-    // single integer or several integers using brackets
-    let ident = pairs.next();
-    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-    let option = pairs.next();
-    let lbrack = option.clone().unwrap();
-    if lbrack.as_str() == "[" {
-        // check for brackets
-        let mut number = pairs.next();
-        while number.is_some() {
-            let pair = number.unwrap().clone();
-            if pair.as_str() == "]" {
-                // closing bracket found
-                break;
-            } else {
-                let integer: i32 = i32::from_str(pair.as_span().as_str()).unwrap();
-                integers.push(integer);
+/// The -D variables plus **${frame}** (if a frame gets rendered).
+fn frame_defines(defines: &[String], frame: Option<i32>) -> Vec<String> {
+    let mut defines: Vec<String> = defines.to_vec();
+    if let Some(frame) = frame {
+        defines.push(format!("frame={}", frame));
+    }
+    defines
+}
+/// The files which were modified after *since* (missing files are
+/// ignored).
+fn changed_files(files: &[String], since: SystemTime) -> Vec<String> {
+    files
+        .iter()
+        .filter(|filename| {
+            match std::fs::metadata(filename).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified > since,
+                Err(_) => false,
             }
-            number = pairs.next();
-        }
-    } else {
-        // no brackets
-        let mut number = option.clone();
-        while number.is_some() {
-            let pair = number.unwrap().clone();
-            let integer: i32 = i32::from_str(pair.as_span().as_str()).unwrap();
-            integers.push(integer);
-            number = pairs.next();
-        }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Prints all errors, followed by the number of errors.
+fn print_errors(errors: &[ParseError]) {
+    for error in errors {
+        println!("{}", error);
     }
-    (string, integers)
+    println!("{} error(s) found.", errors.len());
 }
 
-fn pbrt_string_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, String) {
-    // single string with or without brackets
-    let ident = pairs.next();
-    let string1: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-    let option = pairs.next();
-    let lbrack = option.clone().unwrap();
-    let string2 = if lbrack.as_str() == "[" {
-        // check for brackets
-        let string = pairs.next();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
-    } else {
-        // no brackets
-        let string = option.clone();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
-    };
-    (string1, string2)
+/// Prints all errors found in the scene (if any) and exits.
+fn exit_on_errors(errors: &[ParseError]) {
+    if !errors.is_empty() {
+        print_errors(errors);
+        std::process::exit(1);
+    }
 }
 
-fn pbrt_texture_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, String) {
-    // single string with or without brackets
-    let ident = pairs.next();
-    let string1: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-    let option = pairs.next();
-    let lbrack = option.clone().unwrap();
-    let string2 = if lbrack.as_str() == "[" {
-        // check for brackets
-        let string = pairs.next();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
-    } else {
-        // no brackets
-        let string = option.clone();
-        let pair = string.unwrap().clone();
-        let ident = pair.into_inner().next();
-        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
-    };
-    (string1, string2)
+/// Exits (like the shell does for Ctrl+C) if the render was cancelled
+/// by Ctrl+C, after the partially rendered image was written.
+fn exit_if_interrupted() {
+    if is_render_interrupted() {
+        std::process::exit(130);
+    }
 }
 
-fn extract_params(key_word: String, pairs: pest::iterators::Pair<Rule>) -> ParamSet {
-    let mut params: ParamSet = ParamSet::default();
-    params.key_word = key_word;
-    let mut counter: u8 = 0_u8;
-    for pair in pairs.into_inner() {
-        // let span = pair.clone().as_span();
-        // println!("Rule:    {:?}", pair.as_rule());
-        // println!("Span:    {:?}", span);
-        // println!("Text:    {}", span.as_str());
-        match pair.as_rule() {
-            Rule::identifier => {
-                // ignore (was added above)
-            }
-            Rule::empty_string => {}
-            Rule::string => {
-                match counter {
-                    0 => {
-                        // name
-                        let mut string_pairs = pair.into_inner();
-                        let ident = string_pairs.next();
-                        params.name =
-                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-                    }
-                    1 => {
-                        // tex_type
-                        let mut string_pairs = pair.into_inner();
-                        let ident = string_pairs.next();
-                        params.tex_type =
-                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-                    }
-                    2 => {
-                        // tex_name
-                        let mut string_pairs = pair.into_inner();
-                        let ident = string_pairs.next();
-                        params.tex_name =
-                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-                    }
-                    _ => unreachable!(),
-                };
-                counter += 1_u8;
-            }
-            Rule::type_name => {
-                // name
-                let mut string_pairs = pair.into_inner();
-                let ident = string_pairs.next();
-                params.name = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-            }
-            Rule::file_name => {
-                // name
-                let mut string_pairs = pair.into_inner();
-                let ident = string_pairs.next();
-                params.name = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
-            }
-            Rule::parameter => {
-                for parameter_pair in pair.into_inner() {
-                    // println!("DEBUG: {:?}", parameter_pair.as_rule());
-                    match parameter_pair.as_rule() {
-                        Rule::bool_param => {
-                            let tuple: (String, bool) =
-                                pbrt_bool_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let b: bool = tuple.1;
-                            params.add_bool(string, b);
-                        }
-                        Rule::blackbody_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            params.add_blackbody_spectrum(string, floats);
-                        }
-                        Rule::float_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            if floats.len() == 1 {
-                                params.add_float(string, floats[0]);
-                            } else {
-                                params.add_floats(string, floats);
-                            }
-                        }
-                        Rule::integer_param => {
-                            let tuple: (String, Vec<i32>) =
-                                pbrt_integer_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let integers: Vec<i32> = tuple.1;
-                            if integers.len() == 1 {
-                                params.add_int(string, integers[0]);
-                            } else {
-                                params.add_ints(string, integers);
-                            }
-                        }
-                        Rule::point_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            if floats.len() == 3 {
-                                params.add_point3f(
-                                    string,
-                                    Point3f {
-                                        x: floats[0],
-                                        y: floats[1],
-                                        z: floats[2],
-                                    },
-                                );
-                            } else {
-                                params.add_point3fs(string, floats);
-                            }
-                        }
-                        Rule::point2_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            if floats.len() == 2 {
-                                params.add_point2f(
-                                    string,
-                                    Point2f {
-                                        x: floats[0],
-                                        y: floats[1],
-                                    },
-                                );
-                            } else {
-                                params.add_point2fs(string, floats);
-                            }
-                        }
-                        Rule::normal_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            if floats.len() == 3 {
-                                params.add_normal3f(
-                                    string,
-                                    Normal3f {
-                                        x: floats[0],
-                                        y: floats[1],
-                                        z: floats[2],
-                                    },
-                                );
-                            } else {
-                                params.add_normal3fs(string, floats);
-                            }
-                        }
-                        Rule::rgb_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            params.add_rgb_spectrum(
-                                string,
-                                Spectrum {
-                                    c: [floats[0], floats[1], floats[2]],
-                                },
-                            );
-                        }
-                        Rule::spectrum_param => {
-                            // TODO: "spectrum Kd" [ 300 .3  400 .6   410 .65  415 .8  500 .2  600 .1 ]
-                            // let tuple: (String, Vec<Float>) =
-                            //     pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            // let string: String = tuple.0;
-                            // let floats: Vec<Float> = tuple.1;
-                            // params.add_rgb_spectrum(
-                            //     string,
-                            //     Spectrum {
-                            //         c: [floats[0], floats[1], floats[2]],
-                            //     },
-                            // );
-                            // or
-                            // "spectrum Kd" "filename"
-                            let tuple: (String, String) =
-                                pbrt_string_parameter(&mut parameter_pair.into_inner());
-                            let string1: String = tuple.0;
-                            let string2: String = tuple.1;
-                            let mut strings: Vec<String> = Vec::with_capacity(1_usize);
-                            strings.push(string2);
-                            params.add_sampled_spectrum_files(string1, strings);
-                        }
-                        Rule::string_param => {
-                            let tuple: (String, String) =
-                                pbrt_string_parameter(&mut parameter_pair.into_inner());
-                            let string1: String = tuple.0;
-                            let string2: String = tuple.1;
-                            params.add_string(string1, string2);
-                        }
-                        Rule::texture_param => {
-                            let tuple: (String, String) =
-                                pbrt_texture_parameter(&mut parameter_pair.into_inner());
-                            let string1: String = tuple.0;
-                            let string2: String = tuple.1;
-                            params.add_texture(string1, string2);
-                        }
-                        Rule::vector_param => {
-                            let tuple: (String, Vec<Float>) =
-                                pbrt_float_parameter(&mut parameter_pair.into_inner());
-                            let string: String = tuple.0;
-                            let floats: Vec<Float> = tuple.1;
-                            if floats.len() == 3 {
-                                params.add_vector3f(
-                                    string,
-                                    Vector3f {
-                                        x: floats[0],
-                                        y: floats[1],
-                                        z: floats[2],
-                                    },
-                                );
-                            } else {
-                                params.add_vector3fs(string, floats);
-                            }
-                        }
-                        // TODO: more rules
-                        _ => println!("TODO: {:?}", parameter_pair.as_rule()),
-                    }
-                }
-            }
-            _ => println!("TODO: {:?}", pair.as_rule()),
-        }
+/// Parses the scene without executing it, reports the errors and all
+/// problems found by **lint_scene()**, and exits with 1 if there are
+/// any.
+fn lint(filename: String, defines: &[String]) {
+    let (mut api_state, mut bsdf_state) = pbrt_init(1);
+    let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+    let mut variables: Variables = Variables::from_defines(defines, &mut api_state);
+    parse_file(
+        filename,
+        &mut api_state,
+        &mut bsdf_state,
+        &mut recorder,
+        &mut variables,
+        "",
+    );
+    let warnings: Vec<LintWarning> = match recorder {
+        Some(recording) => lint_scene(&recording.cache.calls, &recording.locations),
+        None => Vec::new(),
+    };
+    for error in &api_state.errors {
+        println!("{}", error);
+    }
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+    if api_state.errors.is_empty() && warnings.is_empty() {
+        println!("No problems found.");
+    } else {
+        println!(
+            "{} error(s) and {} warning(s) found.",
+            api_state.errors.len(),
+            warnings.len()
+        );
+        std::process::exit(1);
     }
-    params
 }
 
-fn parse_line(
-    api_state: &mut ApiState,
-    bsdf_state: &mut BsdfState,
-    identifier: &str,
-    str_buf: String,
-) {
-    if str_buf == "" {
-        // no additional arguments
-        match identifier {
-            "AttributeBegin" => {
-                // AttributeBegin
-                // println!("{} {}", identifier, str_buf);
-                pbrt_attribute_begin(api_state);
-            }
-            "AttributeEnd" => {
-                // AttributeEnd
-                // println!("{} {}", identifier, str_buf);
-                pbrt_attribute_end(api_state);
-            }
-            "ObjectEnd" => {
-                // ObjectEnd
-                // println!("{} {}", identifier, str_buf);
-                pbrt_object_end(api_state);
-            }
-            "ReverseOrientation" => {
-                // ReverseOrientation
-                // println!("{} {}", identifier, str_buf);
-                pbrt_reverse_orientation(api_state);
-            }
-            "TransformBegin" => {
-                // TransformBegin
-                pbrt_transform_begin(api_state);
-            }
-            "TransformEnd" => {
-                // TransformEnd
-                pbrt_transform_end(api_state);
-            }
-            "WorldBegin" => {
-                // WorldBegin
-                // println!("{} {}", identifier, str_buf);
-                pbrt_world_begin(api_state);
+/// Renders the scene, waits until the scene file, one of its includes,
+/// or one of the files it uses (meshes, textures, ...) is modified, and
+/// renders it again. Failures (e.g. a typo in the scene file) are
+/// reported and the files are watched anyway.
+fn watch(filename: String, number_of_threads: u8, overrides: &RenderOverrides, defines: &[String]) {
+    // the watched files can get rewritten while they are used
+    set_memory_mapping(false);
+    // renders after edits which didn't touch the lights reuse them
+    let light_distributions: LightDistributionCache = LightDistributionCache::default();
+    loop {
+        // Ctrl+C cancels the render, a second one stops watching
+        cancel_render_on_interrupt();
+        let since: SystemTime = SystemTime::now();
+        let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Execute));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+            pbrt_set_overrides(&mut api_state, overrides.clone());
+            pbrt_set_light_distributions(&mut api_state, light_distributions.clone());
+            let mut variables: Variables = Variables::from_defines(defines, &mut api_state);
+            parse_file(
+                filename.clone(),
+                &mut api_state,
+                &mut bsdf_state,
+                &mut recorder,
+                &mut variables,
+                "",
+            );
+            pbrt_end_of_input(&mut api_state);
+            if !api_state.errors.is_empty() {
+                print_errors(&api_state.errors);
             }
-            "WorldEnd" => {
-                // WorldEnd
-                // println!("{} {}", identifier, str_buf);
-                pbrt_cleanup(api_state);
-            }
-            _ => println!("{} {:?}", identifier, str_buf),
+        }));
+        if result.is_err() {
+            println!("WARNING: Rendering {:?} failed.", filename);
         }
-    } else {
-        let statement = String::from(identifier) + " " + &str_buf;
-        // println!("DEBUG: {:?}", &statement);
-        let pairs = PbrtParser::parse(Rule::name_and_or_params, &statement)
-            .expect("unsuccessful parse")
-            .next()
-            .unwrap();
-        for inner_pair in pairs.into_inner() {
-            // println!("DEBUG: {:?}", inner_pair.as_rule());
-            match inner_pair.as_rule() {
-                Rule::type_params => {
-                    // identifier "type" parameter-list
-                    let for_printing = inner_pair.as_str();
-                    // println!("DEBUG: {}", for_printing);
-                    let params = extract_params(String::from(identifier), inner_pair);
-                    match identifier {
-                        "Accelerator" => {
-                            // Accelerator
-                            pbrt_accelerator(api_state, params);
-                        }
-                        "AreaLightSource" => {
-                            // AreaLightSource
-                            pbrt_area_light_source(api_state, params);
-                        }
-                        "Camera" => {
-                            // Camera
-                            pbrt_camera(api_state, params);
-                        }
-                        "CoordSysTransform" => {
-                            // CoordSysTransform
-                            pbrt_coord_sys_transform(api_state, params);
-                        }
-                        "Film" => {
-                            // Film
-                            pbrt_film(api_state, params);
-                        }
-                        "Include" => {
-                            // Include
-                            let mut include_file: String = params.name.clone();
-                            if let Some(ref search_directory) = api_state.search_directory {
-                                let mut path_buf: PathBuf = PathBuf::from("/");
-                                path_buf.push(search_directory.as_ref());
-                                path_buf.push(params.name);
-                                include_file = String::from(path_buf.to_str().unwrap());
-                                // println!("DEBUG: {:?}", include_file);
-                            }
-                            let todo: Vec<&str> = for_printing.splitn(3, '"').collect();
-                            println!("Include {:?}", include_file);
-                            parse_file(include_file, api_state, bsdf_state, todo[2]);
-                        }
-                        "Integrator" => {
-                            // Integrator
-                            pbrt_integrator(api_state, params);
-                        }
-                        "LightSource" => {
-                            // LightSource
-                            pbrt_light_source(api_state, params);
-                        }
-                        "MakeNamedMaterial" => {
-                            // MakeNamedMaterial
-                            pbrt_make_named_material(api_state, bsdf_state, params);
-                        }
-                        "MakeNamedMedium" => {
-                            // MakeNamedMedium
-                            pbrt_make_named_medium(api_state, params);
-                        }
-                        "Material" => {
-                            // Material
-                            pbrt_material(api_state, params);
-                        }
-                        "NamedMaterial" => {
-                            // NamedMaterial
-                            pbrt_named_material(api_state, params);
-                        }
-                        "ObjectBegin" => {
-                            // ObjectBegin
-                            pbrt_object_begin(api_state, params);
-                        }
-                        "ObjectInstance" => {
-                            // ObjectInstance
-                            pbrt_object_instance(api_state, params);
-                        }
-                        "PixelFilter" => {
-                            // PixelFilter
-                            pbrt_pixel_filter(api_state, params);
-                        }
-                        "Sampler" => {
-                            // Sampler
-                            pbrt_sampler(api_state, params);
-                        }
-                        "Shape" => {
-                            // Shape
-                            pbrt_shape(api_state, bsdf_state, params);
-                        }
-                        "Texture" => {
-                            // Texture
-                            pbrt_texture(api_state, params);
-                        }
-                        _ => println!("> {}", for_printing),
-                    }
-                }
-                Rule::active_transform => {
-                    // ActiveTransform
-                    for rule_pair in inner_pair.into_inner() {
-                        match rule_pair.as_rule() {
-                            Rule::all => {
-                                pbrt_active_transform_all(api_state);
-                            }
-                            Rule::start_time => {
-                                pbrt_active_transform_start_time(api_state);
-                            }
-                            Rule::end_time => {
-                                pbrt_active_transform_end_time(api_state);
-                            }
-                            _ => unreachable!(),
-                        }
-                    }
-                }
-                Rule::concat_transform => {
-                    // ConcatTransform m00 .. m33
-                    let mut m: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        // ignore brackets
-                        let not_opening: bool = rule_pair.as_str() != "[";
-                        let not_closing: bool = rule_pair.as_str() != "]";
-                        if not_opening && not_closing {
-                            let number: Float =
-                                f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                            m.push(number);
-                        }
-                    }
-                    let m00: Float = m[0];
-                    let m01: Float = m[1];
-                    let m02: Float = m[2];
-                    let m03: Float = m[3];
-                    let m10: Float = m[4];
-                    let m11: Float = m[5];
-                    let m12: Float = m[6];
-                    let m13: Float = m[7];
-                    let m20: Float = m[8];
-                    let m21: Float = m[9];
-                    let m22: Float = m[10];
-                    let m23: Float = m[11];
-                    let m30: Float = m[12];
-                    let m31: Float = m[13];
-                    let m32: Float = m[14];
-                    let m33: Float = m[15];
-                    let tr: Transform = Transform::new(
-                        m00, m10, m20, m30, m01, m11, m21, m31, m02, m12, m22, m32, m03, m13, m23,
-                        m33,
-                    );
-                    pbrt_concat_transform(api_state, &tr);
-                }
-                Rule::look_at => {
-                    // LookAt eye_x eye_y eye_z look_x look_y look_z up_x up_y up_z
-                    let mut v: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        let number: Float =
-                            f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                        v.push(number);
-                    }
-                    // println!(
-                    //     "LookAt {} {} {} {} {} {} {} {} {}",
-                    //     v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8],
-                    // );
-                    pbrt_look_at(
-                        api_state, v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8],
-                    );
-                }
-                Rule::medium_interface => {
-                    // MediumInterface
-                    let mut strings: Vec<String> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        match rule_pair.as_rule() {
-                            Rule::empty_string => {
-                                strings.push(String::from(""));
-                            }
-                            Rule::string => {
-                                let ident = rule_pair.into_inner().next();
-                                let string: String =
-                                    String::from_str(ident.unwrap().clone().as_span().as_str())
-                                        .unwrap();
-                                strings.push(string);
-                            }
-                            _ => unreachable!(),
-                        }
-                    }
-                    assert!(
-                        strings.len() == 2_usize,
-                        "ERROR: expected two strings, found {:?}",
-                        strings.len()
-                    );
-                    pbrt_medium_interface(api_state, &strings[0], &strings[1]);
-                }
-                Rule::rotate => {
-                    // Rotate angle x y z
-                    let mut v: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        let number: Float =
-                            f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                        v.push(number);
-                    }
-                    // println!("Rotate {} {} {} {}", v[0], v[1], v[2], v[3]);
-                    pbrt_rotate(api_state, v[0], v[1], v[2], v[3]);
-                }
-                Rule::scale => {
-                    // Scale x y z
-                    let mut v: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        let number: Float =
-                            f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                        v.push(number);
-                    }
-                    // println!("Scale {} {} {}", v[0], v[1], v[2]);
-                    pbrt_scale(api_state, v[0], v[1], v[2]);
-                }
-                Rule::transform => {
-                    // Transform m00 .. m33
-                    let mut m: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        // ignore brackets
-                        let not_opening: bool = rule_pair.as_str() != "[";
-                        let not_closing: bool = rule_pair.as_str() != "]";
-                        if not_opening && not_closing {
-                            let number: Float =
-                                f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                            m.push(number);
-                        }
-                    }
-                    let m00: Float = m[0];
-                    let m01: Float = m[1];
-                    let m02: Float = m[2];
-                    let m03: Float = m[3];
-                    let m10: Float = m[4];
-                    let m11: Float = m[5];
-                    let m12: Float = m[6];
-                    let m13: Float = m[7];
-                    let m20: Float = m[8];
-                    let m21: Float = m[9];
-                    let m22: Float = m[10];
-                    let m23: Float = m[11];
-                    let m30: Float = m[12];
-                    let m31: Float = m[13];
-                    let m32: Float = m[14];
-                    let m33: Float = m[15];
-                    let tr: Transform = Transform::new(
-                        m00, m10, m20, m30, m01, m11, m21, m31, m02, m12, m22, m32, m03, m13, m23,
-                        m33,
-                    );
-                    pbrt_transform(api_state, &tr);
-                }
-                Rule::translate => {
-                    // Translate x y z
-                    let mut v: Vec<Float> = Vec::new();
-                    for rule_pair in inner_pair.into_inner() {
-                        let number: Float =
-                            f32::from_str(rule_pair.clone().as_span().as_str()).unwrap();
-                        v.push(number);
-                    }
-                    // println!("Translate {} {} {}", v[0], v[1], v[2]);
-                    pbrt_translate(api_state, v[0], v[1], v[2]);
-                }
-                Rule::remaining_line => {
-                    // predetermined number of arguments of predetermined type
-                    println!("< {}", inner_pair.as_str());
-                }
-                // _ => unreachable!(),
-                _ => println!("TODO: {:?}", inner_pair.as_rule()),
+        // the scene cache uses canonical paths
+        let mut files: Vec<String> = match std::fs::canonicalize(&filename) {
+            Ok(path) => vec![path.to_string_lossy().into_owned()],
+            Err(_) => vec![filename.clone()],
+        };
+        if let Some(recording) = recorder {
+            files.extend(
+                recording
+                    .cache
+                    .files
+                    .iter()
+                    .map(|(name, _hash)| name.clone()),
+            );
+            files.extend(recording.cache.referenced_files());
+        }
+        files.sort();
+        files.dedup();
+        println!(
+            "Watching {} file(s) for changes (press Ctrl-C to stop) ...",
+            files.len()
+        );
+        loop {
+            let changed: Vec<String> = changed_files(&files, since);
+            if !changed.is_empty() {
+                println!("Changed: {:?}", changed);
+                // give editors time to finish writing
+                thread::sleep(Duration::from_millis(100));
+                break;
             }
+            thread::sleep(Duration::from_millis(500));
         }
     }
 }
 
-fn parse_file(
-    filename: String,
-    api_state: &mut ApiState,
-    bsdf_state: &mut BsdfState,
-    append: &str,
+/// Renders the frames *first* to *last* (inclusive) of an animation,
+/// each one with a fresh scene (and **${frame}** set to the frame
+/// number) to its own image file (see **api::frame_filename()**).
+fn render_frames(
+    filename: &str,
+    number_of_threads: u8,
+    overrides: &RenderOverrides,
+    defines: &[String],
+    stats_only: bool,
+    (first, last): (i32, i32),
 ) {
-    // println!("FILE = {}", x);
-    let f = File::open(filename.clone()).unwrap();
-    let ip: &Path = Path::new(filename.as_str());
-    if ip.is_relative() {
-        let cp: PathBuf = env::current_dir().unwrap();
-        let pb: PathBuf = cp.join(ip);
-        let search_directory: &Path = pb.as_path().parent().unwrap();
-        // println!("search_directory is {}", search_directory.display());
-        api_state.search_directory = Some(Box::new(PathBuf::from(search_directory)));
-    }
-    let mut reader = BufReader::new(f);
-    let mut str_buf: String = String::default();
-    let _num_bytes = reader.read_to_string(&mut str_buf);
-    // if num_bytes.is_ok() {
-    //     let n_bytes = num_bytes.unwrap();
-    //     println!("{} bytes read", n_bytes);
-    // }
-    if append != "" {
-        str_buf += append;
-        str_buf += "\n";
-    }
-    let pairs = PbrtParser::parse(Rule::pbrt, &str_buf)
-        .expect("unsuccessful parse")
-        .next()
-        .unwrap();
-    let mut identifier: &str = "";
-    // let mut comment_count: u64 = 0;
-    // let mut empty_count: u64 = 0;
-    // let mut todo_count: u64 = 0;
-    let mut parse_again: String = String::default();
-    // first parse file line by line
-    for inner_pair in pairs.into_inner() {
-        match inner_pair.as_rule() {
-            // comment lines (starting with '#')
-            Rule::comment_line => {
-                // comment_count += 1;
-            }
-            Rule::statement_line => {
-                for statement_pair in inner_pair.into_inner() {
-                    match statement_pair.as_rule() {
-                        Rule::identifier => {
-                            if identifier != "" {
-                                parse_line(api_state, bsdf_state, identifier, parse_again.clone());
-                            }
-                            identifier = statement_pair.as_str();
-                            parse_again = String::default();
-                        }
-                        Rule::remaining_line => {
-                            if parse_again != "" {
-                                parse_again = parse_again + " " + statement_pair.as_str();
-                            } else {
-                                parse_again += statement_pair.as_str();
-                            }
-                        }
-                        Rule::trailing_comment => {
-                            // ignore (only if there are no '"' chars)
-                            if statement_pair.as_str().contains('\"') {
-                                if parse_again != "" {
-                                    parse_again = parse_again + " " + statement_pair.as_str();
-                                } else {
-                                    parse_again += statement_pair.as_str();
-                                }
-                            }
-                        }
-                        _ => println!("TODO: {:?}", statement_pair.as_rule()),
-                    }
-                }
-            }
-            Rule::empty_line => {
-                // empty_count += 1;
-            }
-            Rule::todo_line => {
-                // todo_count += 1;
-                for params_pair in inner_pair.into_inner() {
-                    match params_pair.as_rule() {
-                        Rule::remaining_params => {
-                            if parse_again != "" {
-                                parse_again = parse_again + " " + params_pair.as_str();
-                            } else {
-                                parse_again += params_pair.as_str();
-                            }
-                        }
-                        Rule::trailing_comment => {
-                            // ignore
-                        }
-                        _ => println!("TODO: {:?}", params_pair.as_rule()),
-                    }
-                }
-            }
-            Rule::EOI => parse_line(api_state, bsdf_state, identifier, parse_again.clone()),
-            _ => unreachable!(),
-        }
+    // frames with the same lights reuse their light distributions
+    let light_distributions: LightDistributionCache = LightDistributionCache::default();
+    for frame in first..=last {
+        println!("Frame {} [{}-{}]", frame, first, last);
+        let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+        pbrt_set_overrides(
+            &mut api_state,
+            RenderOverrides {
+                frame: Some(frame),
+                ..overrides.clone()
+            },
+        );
+        pbrt_set_stats_only(&mut api_state, stats_only);
+        pbrt_set_light_distributions(&mut api_state, light_distributions.clone());
+        let mut recorder: Option<Recorder> = None;
+        let mut variables: Variables =
+            Variables::from_defines(&frame_defines(defines, Some(frame)), &mut api_state);
+        parse_file(
+            String::from(filename),
+            &mut api_state,
+            &mut bsdf_state,
+            &mut recorder,
+            &mut variables,
+            "",
+        );
+        pbrt_end_of_input(&mut api_state);
+        exit_on_errors(&api_state.errors);
+        exit_if_interrupted();
     }
-    // println!("Number of comment line(s):   {}", comment_count);
-    // println!("Number of parameter line(s): {}", todo_count);
-    // println!("Number of empty line(s):     {}", empty_count);
+}
+
+/// Prints the log events of the renderer like the messages it used
+/// to print, e.g. *WARNING: ...*. The environment variable
+/// *RUST_LOG* (e.g. *RUST_LOG=warn*) filters them, the default is
+/// *info*.
+fn init_logging() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .target(env_logger::Target::Stdout)
+        .format(|buf, record| match record.level() {
+            log::Level::Error => writeln!(buf, "ERROR: {}", record.args()),
+            log::Level::Warn => writeln!(buf, "WARNING: {}", record.args()),
+            _ => writeln!(buf, "{}", record.args()),
+        })
+        .init();
 }
 
 fn main() {
     // handle command line options
     let args = Cli::from_args();
+    init_logging();
     let number_of_threads: u8 = args.nthreads;
     let num_cores = num_cpus::get();
     println!("pbrt version {} [Detected {} cores]", VERSION, num_cores);
     println!("Copyright (c) 2016-2020 Jan Douglas Bert Walter.");
     println!("Rust code based on C++ code by Matt Pharr, Greg Humphreys, and Wenzel Jakob.");
+    if let Some(ref outfile) = args.outfile {
+        if !outfile.to_lowercase().ends_with(".png") {
+            println!(
+                "ERROR: Only PNG images can be written (--outfile {:?}).",
+                outfile
+            );
+            std::process::exit(1);
+        }
+    }
+    let overrides: RenderOverrides = RenderOverrides {
+        pixel_samples: args.spp,
+        seed: args.seed,
+        resolution: args.resolution,
+        integrator: args.integrator,
+        outfile: args.outfile,
+        frame: None,
+        denoise: args.denoise,
+        debug_nans: args.debug_nans,
+    };
+    let distributed: Option<DistributedRole> = match (args.coordinator, args.worker) {
+        (Some(address), _) => Some(DistributedRole::Coordinator(address)),
+        (None, Some(address)) => Some(DistributedRole::Worker(address)),
+        (None, None) => None,
+    };
+    let filename: String = args.path.into_os_string().into_string().unwrap();
+    if args.lint {
+        let first_frame: Option<i32> = args.frames.map(|(first, _last)| first);
+        lint(filename, &frame_defines(&args.defines, first_frame));
+        return;
+    }
+    // Ctrl+C writes the partially rendered image
+    cancel_render_on_interrupt();
+    let debug_pixel: Option<Point2i> = match args.debug_pixel[..] {
+        [x, y] => Some(Point2i { x, y }),
+        _ => None,
+    };
+    set_debug_pixel(debug_pixel, args.debug_pixel_obj);
+    set_tile_shape(match (args.strips, args.tile_size) {
+        (true, rows) => TileShape::Strips(rows.unwrap_or(1)),
+        (false, Some(size)) => TileShape::Square(size),
+        (false, None) => TileShape::default(),
+    });
+    let flush: ImageFlush = args.write_every.unwrap_or(ImageFlush::Passes(1));
+    set_progressive(args.progressive.map(|pass_samples| Progressive {
+        pass_samples,
+        flush,
+    }));
+    set_render_budget(RenderBudget {
+        max_time: args.max_time,
+        max_spp: args.max_spp,
+    });
+    let resume: Option<String> = args.resume;
+    let checkpoint: Option<String> = args.checkpoint.or_else(|| resume.clone());
+    if checkpoint.is_some() && (distributed.is_some() || args.watch || args.frames.is_some()) {
+        println!(
+            "WARNING: Checkpoints are not used for distributed rendering, --frames, or --watch."
+        );
+    } else {
+        let interval: Duration = Duration::from_secs(args.checkpoint_every.unwrap_or(60));
+        set_checkpoint(checkpoint.map(|filename| CheckpointSettings {
+            filename,
+            interval,
+            resume,
+        }));
+    }
+    set_low_memory(LowMemory {
+        enabled: args.low_memory || args.half_attributes,
+        half_attributes: args.half_attributes,
+        budget: args.memory_budget,
+    });
+    set_roughness_mapping(args.roughness_mapping);
+    if distributed.is_some() && (args.watch || args.frames.is_some()) {
+        println!("WARNING: Distributed rendering is not used for --frames or --watch.");
+    }
+    if let Some((first, last)) = args.frames {
+        if args.watch || args.export.is_some() {
+            if first != last {
+                println!("WARNING: Only frame {} is used (--frames).", first);
+            }
+        } else {
+            if args.cache.is_some() {
+                // the cache only knows about the scene files
+                println!("WARNING: Scene cache is not used for frame sequences.");
+            }
+            render_frames(
+                &filename,
+                number_of_threads,
+                &overrides,
+                &args.defines,
+                args.stats_only,
+                (first, last),
+            );
+            return;
+        }
+    }
+    let first_frame: Option<i32> = args.frames.map(|(first, _last)| first);
+    let defines: Vec<String> = frame_defines(&args.defines, first_frame);
+    if args.watch && args.export.is_none() && !args.stats_only {
+        if args.cache.is_some() {
+            println!("WARNING: Scene cache is not used in watch mode.");
+        }
+        let overrides: RenderOverrides = RenderOverrides {
+            frame: first_frame,
+            ..overrides
+        };
+        watch(filename, number_of_threads, &overrides, &defines);
+        return;
+    }
     let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+    pbrt_set_overrides(&mut api_state, overrides);
+    pbrt_set_stats_only(&mut api_state, args.stats_only);
+    pbrt_set_distributed(&mut api_state, distributed);
+    let mut recorder: Option<Recorder> = None;
+    let mut variables: Variables = Variables::from_defines(&defines, &mut api_state);
+    if let Some(export_path) = args.export {
+        let export_file: String = export_path.into_os_string().into_string().unwrap();
+        recorder = Some(Recorder::new(RecordMode::Record));
+        parse_file(
+            filename,
+            &mut api_state,
+            &mut bsdf_state,
+            &mut recorder,
+            &mut variables,
+            "",
+        );
+        exit_on_errors(&api_state.errors);
+        if let Some(recording) = recorder.take() {
+            let result = if export_file.ends_with(".json") {
+                export_json(&export_file, &recording.cache.calls)
+            } else {
+                export_pbrt(&export_file, &recording.cache.calls)
+            };
+            match result {
+                Ok(()) => println!("Scene exported to {:?}", export_file),
+                Err(err) => println!(
+                    "WARNING: Unable to export scene to {:?}: {}",
+                    export_file, err
+                ),
+            }
+        }
+        return;
+    }
+    let mut cache: Option<PathBuf> = args.cache;
+    if cache.is_some() && !defines.is_empty() {
+        // the cache only knows about the scene files
+        println!("WARNING: Scene cache is not used for scenes with -D variables.");
+        cache = None;
+    }
+    if let Some(cache_path) = cache {
+        let cache_file: String = cache_path.into_os_string().into_string().unwrap();
+        match SceneCache::read(&cache_file) {
+            Ok(cache) => {
+                if cache.is_up_to_date() {
+                    println!("Reading scene from cache {:?}", cache_file);
+                    api_state.scene_filename = Some(filename);
+                    cache.replay(&mut api_state, &mut bsdf_state);
+                    pbrt_end_of_input(&mut api_state);
+                    exit_on_errors(&api_state.errors);
+                    exit_if_interrupted();
+                    return;
+                }
+                println!("Scene cache {:?} is out of date", cache_file);
+            }
+            Err(err) => {
+                if Path::new(&cache_file).exists() {
+                    println!(
+                        "WARNING: Unable to read scene cache {:?}: {}",
+                        cache_file, err
+                    );
+                }
+            }
+        }
+        recorder = Some(Recorder::new(RecordMode::Cache(cache_file)));
+    }
     parse_file(
-        args.path.into_os_string().into_string().unwrap(),
+        filename,
         &mut api_state,
         &mut bsdf_state,
+        &mut recorder,
+        &mut variables,
         "",
     );
+    // pbrt-v4 scenes end without WorldEnd
+    if let Some(recording) = recorder.take() {
+        if api_state.errors.is_empty() {
+            recording.write();
+        }
+    }
+    pbrt_end_of_input(&mut api_state);
+    exit_on_errors(&api_state.errors);
+    exit_if_interrupted();
 }
@@ -0,0 +1,839 @@
+//! A headless render server with a small HTTP/REST API, e.g. to
+//! render scenes for web dashboards or pipelines:
+//!
+//! - **POST /renders** queues a render of the scene in the request
+//!   body (.pbrt or .json format), or of the scene file given by
+//!   *?path=...*, which gets read by the server. Relative filenames
+//!   of uploaded scenes are resolved against *?dir=...* (or the scene
+//!   root). Returns the id of the render.
+//! - **GET /renders** lists all renders, **GET /renders/{id}** returns
+//!   the state and progress of one of them (as JSON).
+//! - **GET /renders/{id}/events** streams the state and progress as
+//!   server-sent events until the render is finished.
+//! - **GET /renders/{id}/preview.jpg** returns what was rendered so
+//!   far (or the final image).
+//! - **GET /renders/{id}/image.exr** returns the final image, the
+//!   render is forgotten afterwards (the image file stays in the
+//!   output directory).
+//! - **DELETE /renders/{id}** cancels a queued or running render.
+//!
+//! Scenes can only read files below the directory given by
+//! *--sceneroot*: *?path=*, *?dir=*, included and imported files,
+//! meshes, textures, spectra, etc. (see **set_file_access()**).
+//! Without it *?path=* and *?dir=* are refused, and uploaded scenes
+//! can't read any files.
+//!
+//! Clients have **HEADER_TIMEOUT** to send the request line and the
+//! headers, and the body has to arrive at **MIN_UPLOAD_RATE** at
+//! least. At most **MAX_CONNECTIONS** connections are handled at the
+//! same time, further ones wait until one of them is closed.
+//!
+//! The server keeps at most **MAX_JOBS** renders, the oldest finished
+//! ones are forgotten first. Renders are done one after the other,
+//! each one with all threads.
+//! Ctrl+C cancels the running render, writes its image, and stops
+//! the server.
+//!
+//! ```shell
+//! > rs_pbrt_server --address 127.0.0.1:8080 --sceneroot /data/scenes
+//! > curl --data-binary @scene.pbrt http://127.0.0.1:8080/renders
+//! {"id":1}
+//! > curl http://127.0.0.1:8080/renders/1/events
+//! > curl -o scene.exr http://127.0.0.1:8080/renders/1/image.exr
+//! ```
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// command line options
+use structopt::StructOpt;
+// others
+use serde_json::{json, Value};
+// pbrt
+use pbrt::core::api::{pbrt_init, pbrt_make_render, pbrt_set_overrides, RenderOverrides};
+use pbrt::core::film::Film;
+use pbrt::core::geometry::{Bounds2i, Point2i};
+use pbrt::core::integrator::{
+    cancel_render, cancel_render_on_interrupt, is_render_cancelled, is_render_interrupted,
+    render_preview, render_progress, reset_render_cancelled,
+};
+use pbrt::core::memory::{set_file_access, FileAccess};
+use pbrt::core::parseerror::ParseError;
+use pbrt::core::parser::{parse_file, parse_json_str, parse_str, RecordMode, Recorder, Variables};
+use pbrt::core::pbrt::{clamp_t, gamma_correct, Float};
+use pbrt::core::scenecache::ApiCall;
+use pbrt::core::tiledexr::write_exr;
+// std
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how often **/renders/{id}/events** reports the progress
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+/// uploaded scenes can't be larger than this
+const MAX_BODY_SIZE: usize = 256 * 1024 * 1024;
+/// the request line and the headers can't be longer than this
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+/// time to send the request line and the headers
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// bytes per second, slower uploads time out
+const MIN_UPLOAD_RATE: u64 = 64 * 1024;
+/// time to take a response (e.g. the next event)
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+/// connections handled at the same time (each by a thread of its own)
+const MAX_CONNECTIONS: usize = 64;
+/// queued, running, and finished renders the server keeps track of
+const MAX_JOBS: usize = 100;
+const JPEG_QUALITY: u8 = 85;
+
+/// Render scenes sent over HTTP (see the documentation of this binary).
+#[derive(StructOpt)]
+struct Cli {
+    /// listen on this address
+    #[structopt(short = "a", long = "address", default_value = "127.0.0.1:8080")]
+    address: String,
+    /// use specified number of threads for rendering
+    #[structopt(short = "t", long = "nthreads", default_value = "0")]
+    nthreads: u8,
+    /// write the final images (EXR) to this directory
+    #[structopt(short = "o", long = "outdir", parse(from_os_str))]
+    outdir: Option<PathBuf>,
+    /// denoise the final images (needs rs_pbrt built with the feature "denoise")
+    #[structopt(long = "denoise")]
+    denoise: bool,
+    /// allow ?path=, ?dir=, and all files scenes read below this directory (and nowhere else)
+    #[structopt(long = "sceneroot", parse(from_os_str))]
+    sceneroot: Option<PathBuf>,
+}
+
+enum SceneSource {
+    /// a scene file (and the files it includes) on the server
+    Path(String),
+    /// an uploaded scene and the directory for relative filenames
+    Text(String, Option<String>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum State {
+    Queued,
+    Rendering,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl State {
+    fn name(self) -> &'static str {
+        match self {
+            State::Queued => "queued",
+            State::Rendering => "rendering",
+            State::Done => "done",
+            State::Failed => "failed",
+            State::Cancelled => "cancelled",
+        }
+    }
+    fn is_finished(self) -> bool {
+        !matches!(self, State::Queued | State::Rendering)
+    }
+}
+
+struct Job {
+    id: usize,
+    /// taken by the render thread
+    source: Option<SceneSource>,
+    state: State,
+    errors: Vec<String>,
+    /// the final image
+    exr: Option<PathBuf>,
+    preview: Option<Vec<u8>>,
+}
+
+impl Job {
+    fn to_json(&self) -> Value {
+        let progress: Option<Float> = match self.state {
+            State::Queued => Some(0.0 as Float),
+            State::Rendering => match render_progress() {
+                (_done, 0) => None,
+                (done, total) => Some(done as Float / total as Float),
+            },
+            State::Done => Some(1.0 as Float),
+            State::Failed | State::Cancelled => None,
+        };
+        json!({
+            "id": self.id,
+            "state": self.state.name(),
+            "progress": progress,
+            "errors": self.errors,
+        })
+    }
+}
+
+struct Server {
+    /// oldest first
+    jobs: Mutex<Vec<Job>>,
+    /// ids aren't reused, even if a render is forgotten
+    next_id: AtomicUsize,
+    /// signaled whenever a render gets queued
+    queued: Condvar,
+    /// the number of connections being handled
+    connections: Mutex<usize>,
+    /// signaled whenever a connection is closed
+    connection_closed: Condvar,
+    outdir: PathBuf,
+    /// canonicalized, None if scenes can't be read from the server
+    scene_root: Option<PathBuf>,
+    number_of_threads: u8,
+    denoise: bool,
+}
+
+impl Server {
+    fn with_job<T>(&self, id: usize, f: impl FnOnce(&mut Job) -> T) -> Option<T> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.iter_mut().find(|job| job.id == id).map(f)
+    }
+    /// Queues a render, unless there are already **MAX_JOBS** renders
+    /// which aren't finished. Forgets the oldest finished renders to
+    /// make room. Returns the id of the render.
+    fn queue(&self, source: SceneSource) -> Option<usize> {
+        let mut jobs = self.jobs.lock().unwrap();
+        while jobs.len() >= MAX_JOBS {
+            let finished = jobs.iter().position(|job| job.state.is_finished())?;
+            jobs.remove(finished);
+        }
+        let id: usize = self.next_id.fetch_add(1, Ordering::SeqCst);
+        jobs.push(Job {
+            id,
+            source: Some(source),
+            state: State::Queued,
+            errors: Vec::new(),
+            exr: None,
+            preview: None,
+        });
+        Some(id)
+    }
+    /// Forgets a finished render (its image file stays).
+    fn forget(&self, id: usize) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| job.id != id || !job.state.is_finished());
+    }
+    /// The canonical path of *path* (relative to the scene root), if
+    /// it exists below the scene root.
+    fn scene_path(&self, path: &str) -> Option<PathBuf> {
+        let root: &Path = self.scene_root.as_ref()?;
+        let path: PathBuf = root.join(path).canonicalize().ok()?;
+        if path.starts_with(root) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+    /// Waits until fewer than **MAX_CONNECTIONS** connections are
+    /// handled, the returned slot counts one more until it's dropped.
+    fn open_connection(self: &Arc<Self>) -> ConnectionSlot {
+        let mut connections = self.connections.lock().unwrap();
+        while *connections >= MAX_CONNECTIONS {
+            connections = self.connection_closed.wait(connections).unwrap();
+        }
+        *connections += 1;
+        ConnectionSlot {
+            server: self.clone(),
+        }
+    }
+    fn set_state(&self, id: usize, state: State, errors: Vec<String>) {
+        self.with_job(id, |job| {
+            job.state = state;
+            job.errors = errors;
+        });
+    }
+    /// Renders the queued scenes one after the other (never returns).
+    fn render_loop(&self) {
+        loop {
+            let (id, source) = {
+                let mut jobs = self.jobs.lock().unwrap();
+                loop {
+                    if let Some(job) = jobs.iter_mut().find(|job| job.state == State::Queued) {
+                        job.state = State::Rendering;
+                        break (job.id, job.source.take().unwrap());
+                    }
+                    jobs = self.queued.wait(jobs).unwrap();
+                }
+            };
+            println!("Render {}: started", id);
+            reset_render_cancelled();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                render_to_memory(&source, self.number_of_threads, self.denoise)
+            }));
+            let (state, errors) = match result {
+                Ok(Ok((width, height, rgb, metadata))) => {
+                    // even a cancelled render has an image
+                    self.finish(id, width, height, &rgb, &metadata);
+                    if is_render_cancelled() {
+                        (State::Cancelled, Vec::new())
+                    } else {
+                        (State::Done, Vec::new())
+                    }
+                }
+                Ok(Err(errors)) => (State::Failed, errors),
+                Err(_) => (State::Failed, vec![String::from("rendering panicked")]),
+            };
+            println!("Render {}: {}", id, state.name());
+            self.set_state(id, state, errors);
+            if is_render_interrupted() {
+                println!("Stopping the server (Ctrl+C)");
+                std::process::exit(130);
+            }
+        }
+    }
+    /// Writes the final image (with the render settings as metadata)
+    /// and keeps its preview.
+    fn finish(
+        &self,
+        id: usize,
+        width: i32,
+        height: i32,
+        rgb: &[Float],
+        metadata: &[(String, String)],
+    ) {
+        let exr: PathBuf = self.outdir.join(format!("render_{}.exr", id));
+        let data_window: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i {
+                x: width,
+                y: height,
+            },
+        };
+        let exr: Option<PathBuf> = match write_exr(
+            &exr.to_string_lossy(),
+            data_window.p_max,
+            data_window,
+            rgb,
+            metadata,
+        ) {
+            Ok(()) => Some(exr),
+            Err(err) => {
+                println!("WARNING: Unable to write {:?}: {}", exr, err);
+                None
+            }
+        };
+        let preview: Option<Vec<u8>> = encode_jpeg(width, height, rgb);
+        self.with_job(id, |job| {
+            job.exr = exr;
+            job.preview = preview;
+        });
+    }
+    fn handle(&self, stream: &mut TcpStream) -> Result<()> {
+        let request: Request = match Request::read(stream)? {
+            Some(request) => request,
+            None => return respond_json(stream, 400, &json!({"error": "bad request"})),
+        };
+        let segments: Vec<&str> = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", []) | ("GET", ["renders"]) => {
+                let jobs: Vec<Value> = self.jobs.lock().unwrap().iter().map(Job::to_json).collect();
+                respond_json(stream, 200, &Value::from(jobs))
+            }
+            ("POST", ["renders"]) => {
+                let forbidden = |stream: &mut TcpStream, name: &str| {
+                    let error: String = format!("?{}= has to be below the scene root", name);
+                    respond_json(stream, 403, &json!({ "error": error }))
+                };
+                let search_directory: Option<String> = match request.query_value("dir") {
+                    Some(dir) => match self.scene_path(&dir) {
+                        Some(dir) => Some(dir.to_string_lossy().into_owned()),
+                        None => return forbidden(stream, "dir"),
+                    },
+                    None => None,
+                };
+                let source: SceneSource = match request.query_value("path") {
+                    Some(path) => match self.scene_path(&path) {
+                        Some(path) => SceneSource::Path(path.to_string_lossy().into_owned()),
+                        None => return forbidden(stream, "path"),
+                    },
+                    None => match String::from_utf8(request.body) {
+                        Ok(text) if !text.trim().is_empty() => SceneSource::Text(
+                            text,
+                            search_directory.or_else(|| {
+                                // never the working directory of the server
+                                self.scene_root
+                                    .as_ref()
+                                    .map(|root| root.to_string_lossy().into_owned())
+                            }),
+                        ),
+                        _ => {
+                            return respond_json(
+                                stream,
+                                400,
+                                &json!({"error": "expected a scene (UTF-8) or ?path="}),
+                            )
+                        }
+                    },
+                };
+                let id: usize = match self.queue(source) {
+                    Some(id) => id,
+                    None => {
+                        return respond_json(
+                            stream,
+                            503,
+                            &json!({"error": "too many renders queued"}),
+                        )
+                    }
+                };
+                self.queued.notify_one();
+                println!("Render {}: queued", id);
+                respond_json(stream, 201, &json!({ "id": id }))
+            }
+            (method, ["renders", id, rest @ ..]) => {
+                let id: usize = match id.parse() {
+                    Ok(id) => id,
+                    Err(_) => return respond_json(stream, 404, &json!({"error": "not found"})),
+                };
+                match (method, rest) {
+                    ("GET", []) => match self.with_job(id, |job| job.to_json()) {
+                        Some(status) => respond_json(stream, 200, &status),
+                        None => respond_json(stream, 404, &json!({"error": "not found"})),
+                    },
+                    ("GET", ["events"]) => self.stream_events(stream, id),
+                    ("GET", ["preview.jpg"]) => self.send_preview(stream, id),
+                    ("GET", ["image.exr"]) => match self.with_job(id, |job| job.exr.clone()) {
+                        Some(Some(exr)) => {
+                            let bytes: Vec<u8> = std::fs::read(exr)?;
+                            respond(stream, 200, "image/x-exr", &bytes)?;
+                            self.forget(id);
+                            Ok(())
+                        }
+                        Some(None) => {
+                            respond_json(stream, 409, &json!({"error": "not rendered yet"}))
+                        }
+                        None => respond_json(stream, 404, &json!({"error": "not found"})),
+                    },
+                    ("DELETE", []) => {
+                        let state: Option<State> = self.with_job(id, |job| {
+                            if job.state == State::Queued {
+                                job.state = State::Cancelled;
+                                job.source = None;
+                            }
+                            job.state
+                        });
+                        match state {
+                            Some(State::Rendering) => {
+                                cancel_render();
+                                respond_json(stream, 202, &json!({"state": "cancelling"}))
+                            }
+                            Some(state) => {
+                                respond_json(stream, 200, &json!({"state": state.name()}))
+                            }
+                            None => respond_json(stream, 404, &json!({"error": "not found"})),
+                        }
+                    }
+                    _ => respond_json(stream, 404, &json!({"error": "not found"})),
+                }
+            }
+            _ => respond_json(stream, 404, &json!({"error": "not found"})),
+        }
+    }
+    /// Sends the status of a render as server-sent events until it is
+    /// finished (or the client disconnects).
+    fn stream_events(&self, stream: &mut TcpStream, id: usize) -> Result<()> {
+        if self.with_job(id, |_job| ()).is_none() {
+            return respond_json(stream, 404, &json!({"error": "not found"}));
+        }
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )?;
+        loop {
+            // the render might have been forgotten meanwhile
+            let (status, finished) =
+                match self.with_job(id, |job| (job.to_json(), job.state.is_finished())) {
+                    Some(status) => status,
+                    None => return Ok(()),
+                };
+            stream.write_all(format!("data: {}\n\n", status).as_bytes())?;
+            stream.flush()?;
+            if finished {
+                return Ok(());
+            }
+            thread::sleep(EVENT_INTERVAL);
+        }
+    }
+    fn send_preview(&self, stream: &mut TcpStream, id: usize) -> Result<()> {
+        let (state, preview) = match self.with_job(id, |job| (job.state, job.preview.clone())) {
+            Some(job) => job,
+            None => return respond_json(stream, 404, &json!({"error": "not found"})),
+        };
+        let jpeg: Option<Vec<u8>> = match (state, preview) {
+            (_, Some(jpeg)) => Some(jpeg),
+            (State::Rendering, None) => {
+                render_preview().and_then(|(width, height, rgb)| encode_jpeg(width, height, &rgb))
+            }
+            _ => None,
+        };
+        match jpeg {
+            Some(jpeg) => respond(stream, 200, "image/jpeg", &jpeg),
+            None => respond_json(stream, 409, &json!({"error": "no preview available"})),
+        }
+    }
+}
+
+/// A connection counted by **Server::open_connection()**.
+struct ConnectionSlot {
+    server: Arc<Server>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        *self.server.connections.lock().unwrap() -= 1;
+        self.server.connection_closed.notify_one();
+    }
+}
+
+/// width, height, (linear) RGB values, and metadata of an image
+type RenderedImage = (i32, i32, Vec<Float>, Vec<(String, String)>);
+
+/// Parses the scene and renders it into memory. Returns the image
+/// (see **RenderedImage**), or the errors.
+fn render_to_memory(
+    source: &SceneSource,
+    number_of_threads: u8,
+    denoise: bool,
+) -> std::result::Result<RenderedImage, Vec<String>> {
+    let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+    // record the calls, so the scene gets rendered into memory (not by WorldEnd)
+    let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+    let mut variables: Variables = Variables::default();
+    match source {
+        SceneSource::Path(path) => parse_file(
+            path.clone(),
+            &mut api_state,
+            &mut bsdf_state,
+            &mut recorder,
+            &mut variables,
+            "",
+        ),
+        SceneSource::Text(text, search_directory) => {
+            if let Some(search_directory) = search_directory {
+                api_state.search_directory = Some(Box::new(PathBuf::from(search_directory)));
+            }
+            if text.trim_start().starts_with('{') {
+                parse_json_str(text, &mut api_state, &mut bsdf_state, &mut recorder);
+            } else {
+                parse_str(
+                    text,
+                    &mut api_state,
+                    &mut bsdf_state,
+                    &mut recorder,
+                    &mut variables,
+                    "",
+                );
+            }
+        }
+    }
+    let errors =
+        |errors: &[ParseError]| -> Vec<String> { errors.iter().map(ToString::to_string).collect() };
+    if !api_state.errors.is_empty() {
+        return Err(errors(&api_state.errors));
+    }
+    let calls: Vec<ApiCall> = recorder.map_or_else(Vec::new, |recording| recording.cache.calls);
+    let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+    match source {
+        SceneSource::Path(path) => api_state.scene_filename = Some(path.clone()),
+        SceneSource::Text(_text, Some(search_directory)) => {
+            api_state.search_directory = Some(Box::new(PathBuf::from(search_directory)));
+        }
+        SceneSource::Text(_text, None) => {}
+    }
+    for call in calls {
+        if let ApiCall::WorldEnd = call {
+            break;
+        }
+        call.execute(&mut api_state, &mut bsdf_state);
+    }
+    pbrt_set_overrides(
+        &mut api_state,
+        RenderOverrides {
+            denoise,
+            ..Default::default()
+        },
+    );
+    let (mut integrator, scene) =
+        pbrt_make_render(&mut api_state).ok_or_else(|| errors(&api_state.errors))?;
+    integrator.render(&scene, number_of_threads);
+    let film: Arc<Film> = integrator.get_camera().get_film();
+    let bounds: Bounds2i = film.cropped_pixel_bounds;
+    let rgb: Vec<Float> = film.image.read().unwrap().clone();
+    Ok((
+        bounds.p_max.x - bounds.p_min.x,
+        bounds.p_max.y - bounds.p_min.y,
+        rgb,
+        film.metadata(),
+    ))
+}
+
+/// Gamma corrected 8-bit JPEG of a (linear) RGB image.
+fn encode_jpeg(width: i32, height: i32, rgb: &[Float]) -> Option<Vec<u8>> {
+    if width <= 0 || height <= 0 || rgb.len() != (3 * width * height) as usize {
+        return None;
+    }
+    let buffer: Vec<u8> = rgb
+        .iter()
+        .map(|v| clamp_t(255.0 as Float * gamma_correct(*v) + 0.5, 0.0, 255.0) as u8)
+        .collect();
+    let mut jpeg: Vec<u8> = Vec::new();
+    image::jpeg::JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode(&buffer, width as u32, height as u32, image::ColorType::Rgb8)
+        .ok()?;
+    Some(jpeg)
+}
+
+struct Request {
+    method: String,
+    /// without the query
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Reads from a stream until a deadline, after which reading fails
+/// (with **ErrorKind::TimedOut**), no matter how slowly the data
+/// trickles in.
+struct DeadlineReader<'s> {
+    stream: &'s TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining: Duration = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::new(ErrorKind::TimedOut, "request timed out"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Request {
+    /// Reads the request line, the headers, and the body (if there is
+    /// a *Content-Length*), within **HEADER_TIMEOUT** and at
+    /// **MIN_UPLOAD_RATE**. Returns **None** for malformed requests.
+    fn read(stream: &TcpStream) -> Result<Option<Request>> {
+        let mut reader: BufReader<DeadlineReader> = BufReader::new(DeadlineReader {
+            stream,
+            deadline: Instant::now() + HEADER_TIMEOUT,
+        });
+        let mut header_size: u64 = 0;
+        // a line without newline (too long, or the connection closed) is malformed
+        let mut read_line = |reader: &mut BufReader<DeadlineReader>, line: &mut String| {
+            let n: usize = reader
+                .by_ref()
+                .take(MAX_HEADER_SIZE - header_size)
+                .read_line(line)?;
+            header_size += n as u64;
+            Ok::<bool, Error>(line.ends_with('\n'))
+        };
+        let mut line: String = String::new();
+        if !read_line(&mut reader, &mut line)? {
+            return Ok(None);
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 3 {
+            return Ok(None);
+        }
+        let method: String = String::from(words[0]);
+        let (path, query) = match words[1].find('?') {
+            Some(pos) => (&words[1][..pos], &words[1][pos + 1..]),
+            None => (words[1], ""),
+        };
+        let path: String = percent_decode(path);
+        let query: Vec<(String, String)> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.find('=') {
+                Some(pos) => (
+                    percent_decode(&pair[..pos]),
+                    percent_decode(&pair[pos + 1..]),
+                ),
+                None => (percent_decode(pair), String::new()),
+            })
+            .collect();
+        let mut content_length: usize = 0;
+        loop {
+            let mut header: String = String::new();
+            if !read_line(&mut reader, &mut header)? {
+                return Ok(None);
+            }
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some(pos) = header.find(':') {
+                if header[..pos].trim().eq_ignore_ascii_case("content-length") {
+                    match header[pos + 1..].trim().parse() {
+                        Ok(length) => content_length = length,
+                        Err(_) => return Ok(None),
+                    }
+                }
+            }
+        }
+        if content_length > MAX_BODY_SIZE {
+            return Ok(None);
+        }
+        reader.get_mut().deadline =
+            Instant::now() + Duration::from_secs(1 + content_length as u64 / MIN_UPLOAD_RATE);
+        let mut body: Vec<u8> = vec![0_u8; content_length];
+        reader.read_exact(&mut body)?;
+        Ok(Some(Request {
+            method,
+            path,
+            query,
+            body,
+        }))
+    }
+    fn query_value(&self, name: &str) -> Option<String> {
+        self.query
+            .iter()
+            .find(|(key, _value)| key == name)
+            .map(|(_key, value)| value.clone())
+    }
+}
+
+/// Decodes %XX escapes (and '+' as space) of URLs.
+fn percent_decode(s: &str) -> String {
+    let bytes: &[u8] = s.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i: usize = 0;
+    while i < bytes.len() {
+        let escaped: Option<u8> = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> Result<()> {
+    let reason: &str = match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn respond_json(stream: &mut TcpStream, status: u16, value: &Value) -> Result<()> {
+    respond(
+        stream,
+        status,
+        "application/json",
+        value.to_string().as_bytes(),
+    )
+}
+
+/// Prints the log events of the renderer like the messages it used
+/// to print, e.g. *WARNING: ...*. The environment variable
+/// *RUST_LOG* (e.g. *RUST_LOG=warn*) filters them, the default is
+/// *info*.
+fn init_logging() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .target(env_logger::Target::Stdout)
+        .format(|buf, record| match record.level() {
+            log::Level::Error => writeln!(buf, "ERROR: {}", record.args()),
+            log::Level::Warn => writeln!(buf, "WARNING: {}", record.args()),
+            _ => writeln!(buf, "{}", record.args()),
+        })
+        .init();
+}
+
+fn main() {
+    let args = Cli::from_args();
+    init_logging();
+    println!("pbrt render server version {}", VERSION);
+    let outdir: PathBuf = args
+        .outdir
+        .unwrap_or_else(|| std::env::temp_dir().join("rs_pbrt_server"));
+    if let Err(err) = std::fs::create_dir_all(&outdir) {
+        panic!("Unable to create output directory {:?}: {}", outdir, err);
+    }
+    let listener: TcpListener = match TcpListener::bind(&args.address) {
+        Ok(listener) => listener,
+        Err(err) => panic!("Unable to listen on {:?}: {}", args.address, err),
+    };
+    println!(
+        "Listening on http://{} (images are written to {:?}) ...",
+        args.address, outdir
+    );
+    let scene_root: Option<PathBuf> = args.sceneroot.map(|root| match root.canonicalize() {
+        Ok(root) => root,
+        Err(err) => panic!("Unable to use scene root {:?}: {}", root, err),
+    });
+    set_file_access(match scene_root {
+        Some(ref root) => FileAccess::Below(root.clone()),
+        None => FileAccess::Nothing,
+    });
+    cancel_render_on_interrupt();
+    let server: Arc<Server> = Arc::new(Server {
+        jobs: Mutex::new(Vec::new()),
+        next_id: AtomicUsize::new(1),
+        queued: Condvar::new(),
+        connections: Mutex::new(0),
+        connection_closed: Condvar::new(),
+        outdir,
+        scene_root,
+        number_of_threads: args.nthreads,
+        denoise: args.denoise,
+    });
+    {
+        let server: Arc<Server> = server.clone();
+        thread::spawn(move || server.render_loop());
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                // wait for a free slot before starting another thread
+                let slot: ConnectionSlot = server.open_connection();
+                thread::spawn(move || {
+                    let server: &Server = &slot.server;
+                    if let Err(err) = stream
+                        .set_write_timeout(Some(WRITE_TIMEOUT))
+                        .and_then(|()| server.handle(&mut stream))
+                    {
+                        println!("WARNING: HTTP request failed: {}", err);
+                    }
+                });
+            }
+            Err(err) => println!("WARNING: Connection failed: {}", err),
+        }
+    }
+}
@@ -3,7 +3,10 @@
 //! not changed after creation we simply work through it with an
 //! atomic counter to track the index of the next block to work on.
 
+// std
 use std::sync::atomic::{AtomicUsize, Ordering};
+// others
+use log::warn;
 
 // see github/tray_rust/src/sampler/block_queue.rs
 
@@ -43,7 +46,7 @@ impl BlockQueue {
                 .collect();
         }
         if blocks.is_empty() {
-            println!("Warning: This block queue is empty!");
+            warn!("This block queue is empty!");
         }
         BlockQueue {
             blocks,
@@ -68,13 +71,24 @@ impl BlockQueue {
             Some(self.blocks[i])
         }
     }
+    /// Get the next block in the queue together with its position in
+    /// the queue (to process the results in queue order), or None if
+    /// the queue is finished
+    pub fn next_with_position(&self) -> Option<(usize, (u32, u32))> {
+        let i = self.next.fetch_add(1, Ordering::AcqRel);
+        if i >= self.blocks.len() {
+            None
+        } else {
+            Some((i, self.blocks[i]))
+        }
+    }
     /// Get the length of the queue
     pub fn len(&self) -> usize {
         self.blocks.len()
     }
     /// Check if the queue is empty
     pub fn is_empty(&self) -> bool {
-        self.next.load(Ordering::AcqRel) >= self.blocks.len()
+        self.next.load(Ordering::Acquire) >= self.blocks.len()
     }
 }
 
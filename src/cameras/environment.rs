@@ -1,15 +1,16 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::error::{Error, Result};
 use crate::core::film::Film;
-use crate::core::geometry::{Bounds2f, Point2f, Point3f, Ray, Vector3f};
+use crate::core::geometry::{Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::InteractionCommon;
 use crate::core::light::VisibilityTester;
 use crate::core::medium::Medium;
 use crate::core::paramset::ParamSet;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::transform::AnimatedTransform;
@@ -46,46 +47,25 @@ impl EnvironmentCamera {
         cam2world: AnimatedTransform,
         film: Arc<Film>,
         medium: Option<Arc<Medium>>,
-    ) -> Arc<Camera> {
+    ) -> Result<Arc<Camera>> {
         let shutteropen: Float = params.find_one_float("shutteropen", 0.0);
         let shutterclose: Float = params.find_one_float("shutterclose", 1.0);
         // TODO: std::swap(shutterclose, shutteropen);
         assert!(shutterclose >= shutteropen);
         // let lensradius: Float = params.find_one_float(String::from("lensradius"), 0.0);
         // let focaldistance: Float = params.find_one_float(String::from("focaldistance"), 1e30);
-        let frame: Float = params.find_one_float(
-            "frameaspectratio",
-            (film.full_resolution.x as Float) / (film.full_resolution.y as Float),
-        );
-        let mut screen: Bounds2f = Bounds2f::default();
-        if frame > 1.0 {
-            screen.p_min.x = -frame;
-            screen.p_max.x = frame;
-            screen.p_min.y = -1.0;
-            screen.p_max.y = 1.0;
-        } else {
-            screen.p_min.x = -1.0;
-            screen.p_max.x = 1.0;
-            screen.p_min.y = -1.0 / frame;
-            screen.p_max.y = 1.0 / frame;
-        }
+        // the screen window doesn't affect the environment camera,
+        // but is validated like for the other cameras
         let sw: Vec<Float> = params.find_float("screenwindow");
-        if !sw.is_empty() {
-            if sw.len() == 4 {
-                screen.p_min.x = sw[0];
-                screen.p_max.x = sw[1];
-                screen.p_min.y = sw[2];
-                screen.p_max.y = sw[3];
-            } else {
-                panic!("\"screenwindow\" should have four values");
-            }
+        if !sw.is_empty() && sw.len() != 4 {
+            return Err(Error::create(
+                "Camera",
+                "environment",
+                String::from("\"screenwindow\" should have four values"),
+            ));
         }
-        Arc::new(Camera::Environment(Box::new(EnvironmentCamera::new(
-            cam2world,
-            shutteropen,
-            shutterclose,
-            film,
-            medium,
+        Ok(Arc::new(Camera::Environment(Box::new(
+            EnvironmentCamera::new(cam2world, shutteropen, shutterclose, film, medium),
         ))))
     }
     // Camera
@@ -100,7 +80,7 @@ impl EnvironmentCamera {
         let mut in_ray: Ray = Ray {
             o: Point3f::default(),
             d: dir,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: lerp(sample.time, self.shutter_open, self.shutter_close),
             medium: None,
             differential: None,
@@ -3,6 +3,7 @@ use std;
 use std::sync::Arc;
 // pbrt
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::error::{Error, Result};
 use crate::core::film::Film;
 use crate::core::geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential, Vector3f};
 use crate::core::interaction::InteractionCommon;
@@ -101,7 +102,7 @@ impl OrthographicCamera {
         cam2world: AnimatedTransform,
         film: Arc<Film>,
         medium: Option<Arc<Medium>>,
-    ) -> Arc<Camera> {
+    ) -> Result<Arc<Camera>> {
         let shutteropen: Float = params.find_one_float("shutteropen", 0.0);
         let shutterclose: Float = params.find_one_float("shutterclose", 1.0);
         // TODO: std::swap(shutterclose, shutteropen);
@@ -132,18 +133,24 @@ impl OrthographicCamera {
                 screen.p_min.y = sw[2];
                 screen.p_max.y = sw[3];
             } else {
-                panic!("\"screenwindow\" should have four values");
+                return Err(Error::create(
+                    "Camera",
+                    "orthographic",
+                    String::from("\"screenwindow\" should have four values"),
+                ));
             }
         }
-        Arc::new(Camera::Orthographic(Box::new(OrthographicCamera::new(
-            cam2world,
-            screen,
-            shutteropen,
-            shutterclose,
-            lensradius,
-            focaldistance,
-            film,
-            medium,
+        Ok(Arc::new(Camera::Orthographic(Box::new(
+            OrthographicCamera::new(
+                cam2world,
+                screen,
+                shutteropen,
+                shutterclose,
+                lensradius,
+                focaldistance,
+                film,
+                medium,
+            ),
         ))))
     }
     // Camera
@@ -163,7 +170,7 @@ impl OrthographicCamera {
                 y: 0.0,
                 z: 1.0,
             },
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: lerp(sample.time, self.shutter_open, self.shutter_close),
             medium: None,
             differential: None,
@@ -1,9 +1,9 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::error::Result;
 use crate::core::film::Film;
 use crate::core::geometry::{nrm_abs_dot_vec3, vec3_dot_vec3};
 use crate::core::geometry::{
@@ -13,6 +13,7 @@ use crate::core::interaction::InteractionCommon;
 use crate::core::light::VisibilityTester;
 use crate::core::medium::{Medium, MediumInterface};
 use crate::core::paramset::ParamSet;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::sampling::concentric_sample_disk;
@@ -133,7 +134,7 @@ impl PerspectiveCamera {
         cam2world: AnimatedTransform,
         film: Arc<Film>,
         medium: Option<Arc<Medium>>,
-    ) -> Arc<Camera> {
+    ) -> Result<Arc<Camera>> {
         let shutteropen: Float = params.find_one_float("shutteropen", 0.0);
         let shutterclose: Float = params.find_one_float("shutterclose", 1.0);
         // TODO: std::swap(shutterclose, shutteropen);
@@ -170,16 +171,18 @@ impl PerspectiveCamera {
         //     params.find_one_float(String::from("halffov"), -1.0);
         // TODO: if (halffov > 0.f)
         // TODO: let perspective_camera: Arc<Camera + Sync + Send> =
-        Arc::new(Camera::Perspective(Box::new(PerspectiveCamera::new(
-            cam2world,
-            screen,
-            shutteropen,
-            shutterclose,
-            lensradius,
-            focaldistance,
-            fov,
-            film,
-            medium,
+        Ok(Arc::new(Camera::Perspective(Box::new(
+            PerspectiveCamera::new(
+                cam2world,
+                screen,
+                shutteropen,
+                shutterclose,
+                lensradius,
+                focaldistance,
+                fov,
+                film,
+                medium,
+            ),
         ))))
     }
     // Camera
@@ -218,7 +221,7 @@ impl PerspectiveCamera {
         let mut in_ray: Ray = Ray {
             o: Point3f::default(),
             d: dir,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: lerp(sample.time, self.shutter_open, self.shutter_close),
             medium: None,
             differential: Some(diff),
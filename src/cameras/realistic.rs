@@ -2,8 +2,11 @@
 use std;
 use std::path::PathBuf;
 use std::sync::Arc;
+// others
+use log::{debug, error, warn};
 // pbrt
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::error::{Error, Result};
 use crate::core::film::Film;
 use crate::core::floatfile::read_float_file;
 use crate::core::geometry::{bnd2_expand, bnd2_union_pnt2, nrm_faceforward_vec3, pnt2_inside_bnd2};
@@ -15,6 +18,7 @@ use crate::core::medium::Medium;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::{lerp, quadratic};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
 use crate::core::reflection::refract;
 use crate::core::transform::{AnimatedTransform, Transform};
 
@@ -59,7 +63,7 @@ impl RealisticCamera {
             let mut diameter: Float = lens_data[i + 3];
             if lens_data[i] == 0.0 as Float {
                 if aperture_diameter > lens_data[i + 3] {
-                    println!("Specified aperture diameter {} is greater than maximum possible {}.  Clamping it.",
+                    warn!("Specified aperture diameter {} is greater than maximum possible {}.  Clamping it.",
                              aperture_diameter,
                              lens_data[i + 3]);
                 } else {
@@ -125,16 +129,23 @@ impl RealisticCamera {
                 }
                 // spawn thread to report progress
                 scope.spawn(move |_| {
-                    for _ in pbr::PbIter::new(0..num_cores) {
+                    let mut progress: ProgressReporter = ProgressReporter::new(
+                        num_cores as u64,
+                        "Computing exit pupil bounds",
+                        "bands",
+                    );
+                    for _ in 0..num_cores {
                         band_rx.recv().unwrap();
+                        progress.update(1);
                     }
+                    progress.done();
                 });
             })
             .unwrap();
         }
         camera.exit_pupil_bounds = exit_pupil_bounds;
         if camera.simple_weighting {
-            println!("WARNING: \"simpleweighting\" option with RealisticCamera no longer necessarily matches regular camera images. Further, pixel values will vary a bit depending on the aperture size. See this discussion for details: https://github.com/mmp/pbrt-v3/issues/162#issuecomment-348625837");
+            warn!("\"simpleweighting\" option with RealisticCamera no longer necessarily matches regular camera images. Further, pixel values will vary a bit depending on the aperture size. See this discussion for details: https://github.com/mmp/pbrt-v3/issues/162#issuecomment-348625837");
         }
         camera
     }
@@ -144,7 +155,7 @@ impl RealisticCamera {
         film: Arc<Film>,
         medium: Option<Arc<Medium>>,
         search_directory: Option<&PathBuf>,
-    ) -> Arc<Camera> {
+    ) -> Result<Arc<Camera>> {
         let shutteropen: Float = params.find_one_float("shutteropen", 0.0);
         let shutterclose: Float = params.find_one_float("shutterclose", 1.0);
         // TODO: std::swap(shutterclose, shutteropen);
@@ -160,26 +171,30 @@ impl RealisticCamera {
             }
         }
         if lens_file == "" {
-            println!("ERROR: No lens description file supplied!");
-        } else {
-            println!("lens_file = {:?}", lens_file);
+            return Err(Error::create(
+                "Camera",
+                "realistic",
+                String::from("No lens description file supplied"),
+            ));
         }
+        debug!("lens_file = {:?}", lens_file);
         let aperture_diameter: Float = params.find_one_float("aperturediameter", 1.0);
         let focus_distance: Float = params.find_one_float("focusdistance", 10.0);
         let simple_weighting: bool = params.find_one_bool("simpleweighting", true);
-        let mut lens_data: Vec<Float> = Vec::new();
-        if !read_float_file(&lens_file, &mut lens_data) {
-            println!(
-                "ERROR: Error reading lens specification file {:?}.",
-                lens_file
-            );
-        }
+        let lens_data: Vec<Float> = read_float_file(&lens_file)?;
         if lens_data.len() % 4_usize != 0_usize {
-            println!("ERROR: Excess values in lens specification file {:?}; must be multiple-of-four values, read {}.",
-                     lens_file, lens_data.len());
+            return Err(Error::create(
+                "Camera",
+                "realistic",
+                format!(
+                    "Excess values in lens specification file {:?}; must be multiple-of-four values, read {}.",
+                    lens_file,
+                    lens_data.len()
+                ),
+            ));
         }
         // println!("lens_data = {:?}", lens_data);
-        Arc::new(Camera::Realistic(Box::new(RealisticCamera::new(
+        Ok(Arc::new(Camera::Realistic(Box::new(RealisticCamera::new(
             cam2world,
             shutteropen,
             shutterclose,
@@ -189,7 +204,7 @@ impl RealisticCamera {
             &lens_data,
             film,
             medium,
-        ))))
+        )))))
     }
     pub fn generate_ray(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
         // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
@@ -218,7 +233,7 @@ impl RealisticCamera {
         let mut r_film: Ray = Ray::default();
         r_film.o = p_film;
         r_film.d = p_rear - p_film;
-        r_film.t_max = std::f32::INFINITY;
+        r_film.t_max = Float::INFINITY;
         r_film.time = lerp(sample.time, self.shutter_open, self.shutter_close);
         if !self.trace_lenses_from_film(&r_film, Some(ray)) {
             // ++vignettedRays;
@@ -414,13 +429,13 @@ impl RealisticCamera {
         true
     }
     pub fn draw_lens_system(&self) {
-        println!("TODO: RealisticCamera::draw_lens_system()");
+        warn!("TODO: RealisticCamera::draw_lens_system()");
     }
     pub fn draw_ray_path_from_film(&self, _r: &Ray, _arrow: bool, _to_optical_intercept: bool) {
-        println!("TODO: RealisticCamera::draw_ray_path_from_film()");
+        warn!("TODO: RealisticCamera::draw_ray_path_from_film()");
     }
     pub fn draw_ray_path_from_scene(&self, _r: &Ray, _arrow: bool, _to_optical_intercept: bool) {
-        println!("TODO: RealisticCamera::draw_ray_path_from_scene()");
+        warn!("TODO: RealisticCamera::draw_ray_path_from_scene()");
     }
     pub fn compute_cardinal_points(
         &self,
@@ -450,7 +465,7 @@ impl RealisticCamera {
                 y: 0.0 as Float,
                 z: -1.0 as Float,
             },
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: 0.0 as Float,
             medium: None,
             differential: None,
@@ -539,7 +554,7 @@ impl RealisticCamera {
                         y: 0.0 as Float,
                         z: film_distance,
                     },
-                    t_max: std::f32::INFINITY,
+                    t_max: Float::INFINITY,
                     time: 0.0 as Float,
                     medium: None,
                     differential: None,
@@ -551,16 +566,16 @@ impl RealisticCamera {
             }
         }
         if !found_focus_ray {
-            println!(
-                "ERROR: Focus ray at lens pos({},0) didn't make it through the lenses with film distance {}?!??",
+            error!(
+                "Focus ray at lens pos({},0) didn't make it through the lenses with film distance {}?!??",
                 lu, film_distance);
-            return std::f32::INFINITY;
+            return Float::INFINITY;
         }
         // compute distance _zFocus_ where ray intersects the principal axis
         let t_focus: Float = -ray.o.x / ray.d.x;
         let mut z_focus: Float = ray.position(t_focus).z;
         if z_focus < 0.0 as Float {
-            z_focus = std::f32::INFINITY;
+            z_focus = Float::INFINITY;
         }
         z_focus
     }
@@ -612,7 +627,7 @@ impl RealisticCamera {
                 &Ray {
                     o: p_film,
                     d: p_rear - p_film,
-                    t_max: std::f32::INFINITY,
+                    t_max: Float::INFINITY,
                     time: 0.0 as Float,
                     medium: None,
                     differential: None,
@@ -645,7 +660,7 @@ impl RealisticCamera {
         pupil_bounds
     }
     pub fn render_exit_pupil(&self, _sx: Float, _sy: Float, _filename: String) {
-        println!("TODO: RealisticCamera::render_exit_pupil()");
+        warn!("TODO: RealisticCamera::render_exit_pupil()");
     }
     pub fn sample_exit_pupil(
         &self,
@@ -681,7 +696,7 @@ impl RealisticCamera {
         }
     }
     pub fn test_exit_pupil_bounds(&self) {
-        println!("TODO: RealisticCamera::test_exit_pupil_bounds()");
+        warn!("TODO: RealisticCamera::test_exit_pupil_bounds()");
     }
     // Camera
     pub fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
@@ -4,36 +4,59 @@
 
 // std
 use std;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+// others
+use log::{error, info, warn};
 // pbrt
 use crate::accelerators::bvh::{BVHAccel, SplitMethod};
 use crate::accelerators::kdtreeaccel::KdTreeAccel;
+use crate::accelerators::meshbvh::MeshBVH;
 use crate::cameras::environment::EnvironmentCamera;
 use crate::cameras::orthographic::OrthographicCamera;
 use crate::cameras::perspective::PerspectiveCamera;
 use crate::cameras::realistic::RealisticCamera;
 use crate::core::camera::Camera;
+use crate::core::distributed::{render_distributed, DistributedRole};
+use crate::core::error::Error;
+use crate::core::export::{matrix_to_string, statement_to_string};
 use crate::core::film::Film;
 use crate::core::filter::Filter;
 use crate::core::geometry::{vec3_coordinate_system, vec3_cross_vec3};
-use crate::core::geometry::{Bounds2i, Normal3f, Point2f, Point2i, Point3f, Vector3f};
+use crate::core::geometry::{Bounds2i, Bounds3f, Normal3f, Point2f, Point2i, Point3f, Vector3f};
 use crate::core::integrator::{Integrator, SamplerIntegrator};
+use crate::core::irradiancecache::IrradianceCache;
+use crate::core::library::Namespace;
 use crate::core::light::Light;
+use crate::core::lightdistrib::LightDistributionCache;
+use crate::core::lowmemory::{low_memory, report_memory_budget};
 use crate::core::material::Material;
 use crate::core::medium::get_medium_scattering_properties;
-use crate::core::medium::{Medium, MediumInterface};
+use crate::core::medium::mie_phase_function;
+use crate::core::medium::{Medium, MediumInterface, PhaseFunction, TabulatedPhaseFunction};
+use crate::core::memory::readable_path;
 use crate::core::mipmap::ImageWrap;
 use crate::core::paramset::{ParamSet, TextureParams};
+use crate::core::parseerror::{Location, ParseError};
 use crate::core::pbrt::lerp;
+use crate::core::pbrt::radians;
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::primitive::{GeometricPrimitive, Primitive, TransformedPrimitive};
+use crate::core::primitive::{GeometricPrimitive, MeshPrimitive, Primitive, TransformedPrimitive};
 use crate::core::reflection::FourierBSDFTable;
 use crate::core::sampler::Sampler;
 use crate::core::scene::Scene;
+use crate::core::scenecache::{hash_params, resolve_filename, FILENAME_PARAMS};
+use crate::core::scenestats::SceneStats;
 use crate::core::shape::Shape;
+use crate::core::stats::{clear_stats, stats_report, StatCounter};
 use crate::core::texture::{
     CylindricalMapping2D, IdentityMapping3D, PlanarMapping2D, SphericalMapping2D, Texture,
     TextureMapping2D, TextureMapping3D, UVMapping2D,
@@ -47,8 +70,11 @@ use crate::filters::triangle::TriangleFilter;
 use crate::integrators::ao::AOIntegrator;
 use crate::integrators::bdpt::BDPTIntegrator;
 use crate::integrators::directlighting::{DirectLightingIntegrator, LightStrategy};
+use crate::integrators::gradientpath::{GradientPathIntegrator, Reconstruction};
+use crate::integrators::lighttracing::LightTracingIntegrator;
 use crate::integrators::mlt::MLTIntegrator;
-use crate::integrators::path::PathIntegrator;
+use crate::integrators::path::{DepthLimits, PathIntegrator};
+use crate::integrators::photonmap::PhotonMapIntegrator;
 use crate::integrators::sppm::SPPMIntegrator;
 use crate::integrators::volpath::VolPathIntegrator;
 use crate::integrators::whitted::WhittedIntegrator;
@@ -60,6 +86,7 @@ use crate::lights::point::PointLight;
 use crate::lights::projection::ProjectionLight;
 use crate::lights::spot::SpotLight;
 use crate::materials::disney::DisneyMaterial;
+use crate::materials::fluorescent::FluorescentMaterial;
 use crate::materials::fourier::FourierMaterial;
 use crate::materials::glass::GlassMaterial;
 use crate::materials::hair::HairMaterial;
@@ -72,10 +99,12 @@ use crate::materials::substrate::SubstrateMaterial;
 use crate::materials::subsurface::SubsurfaceMaterial;
 use crate::materials::translucent::TranslucentMaterial;
 use crate::materials::uber::UberMaterial;
+use crate::media::aggregate::VolumeAggregate;
 use crate::media::grid::GridDensityMedium;
 use crate::media::homogeneous::HomogeneousMedium;
 use crate::samplers::halton::HaltonSampler;
 use crate::samplers::maxmin::MaxMinDistSampler;
+use crate::samplers::pmj02bn::PMJ02BNSampler;
 use crate::samplers::random::RandomSampler;
 use crate::samplers::sobol::SobolSampler;
 use crate::samplers::stratified::StratifiedSampler;
@@ -86,7 +115,7 @@ use crate::shapes::disk::Disk;
 use crate::shapes::loopsubdiv::loop_subdivide;
 use crate::shapes::nurbs::nurbs_evaluate_surface;
 use crate::shapes::nurbs::Homogeneous3;
-use crate::shapes::plymesh::create_ply_mesh;
+use crate::shapes::plymesh::{ply_mesh_shapes, read_ply_mesh};
 use crate::shapes::sphere::Sphere;
 use crate::shapes::triangle::{Triangle, TriangleMesh};
 use crate::textures::checkerboard::Checkerboard2DTexture;
@@ -103,6 +132,9 @@ use crate::textures::wrinkled::WrinkledTexture;
 
 // see api.cpp
 
+static N_OBJECT_INSTANCES_CREATED: StatCounter = StatCounter::new("Scene/Object instances created");
+static N_OBJECT_INSTANCES_USED: StatCounter = StatCounter::new("Scene/Object instances used");
+
 pub struct BsdfState {
     pub loaded_bsdfs: HashMap<String, Arc<FourierBSDFTable>>,
 }
@@ -127,6 +159,33 @@ pub struct ApiState {
     pushed_transforms: Vec<TransformSet>,
     pushed_active_transform_bits: Vec<u8>,
     param_set: ParamSet,
+    inside_world_block: bool,
+    overrides: RenderOverrides,
+    stats_only: bool,
+    distributed: Option<DistributedRole>,
+    scene_stats: SceneStats,
+    /// the statement which is currently executed
+    pub location: Location,
+    /// errors found so far (scenes with errors don't get rendered)
+    pub errors: Vec<ParseError>,
+    /// libraries which are currently imported, innermost last (see
+    /// **library::Namespace**)
+    pub imports: Vec<Namespace>,
+    /// the scene file which was parsed first (for the image metadata)
+    pub scene_filename: Option<String>,
+    /// light distributions to reuse (see **pbrt_set_light_distributions()**)
+    light_distributions: LightDistributionCache,
+}
+
+impl ApiState {
+    /// Records an error, errors without a location get the one of the
+    /// statement which is currently executed.
+    pub fn add_error(&mut self, mut error: ParseError) {
+        if error.location == Location::default() {
+            error.location = self.location.clone();
+        }
+        self.errors.push(error);
+    }
 }
 
 impl Default for ApiState {
@@ -162,6 +221,16 @@ impl Default for ApiState {
             pushed_transforms: Vec::new(),
             pushed_active_transform_bits: Vec::new(),
             param_set: ParamSet::default(),
+            inside_world_block: false,
+            overrides: RenderOverrides::default(),
+            stats_only: false,
+            distributed: None,
+            scene_stats: SceneStats::new(),
+            location: Location::default(),
+            errors: Vec::new(),
+            imports: Vec::new(),
+            scene_filename: None,
+            light_distributions: LightDistributionCache::default(),
         }
     }
 }
@@ -182,6 +251,62 @@ impl TransformSet {
     }
 }
 
+/// Settings (e.g. from the command line) which replace the ones of
+/// the scene file (see **pbrt_set_overrides()**).
+#[derive(Debug, Default, Clone)]
+pub struct RenderOverrides {
+    /// samples per pixel
+    pub pixel_samples: Option<i32>,
+    /// the *seed* of the sampler (renders with the same seed are identical)
+    pub seed: Option<i32>,
+    /// x and y resolution of the film
+    pub resolution: Option<(i32, i32)>,
+    /// name of the integrator (its parameters are kept)
+    pub integrator: Option<String>,
+    /// filename of the (PNG) image
+    pub outfile: Option<String>,
+    /// frame of an animation (see **frame_filename()**)
+    pub frame: Option<i32>,
+    /// denoise the image (see *denoise* of **Film**)
+    pub denoise: bool,
+    /// flag NaN and infinite samples (see
+    /// **SamplerIntegrator::set_debug_non_finite()**)
+    pub debug_nans: bool,
+}
+
+/// The image filename of a *frame* of an animation: the (last) run of
+/// '#' characters in *filename* gets replaced by the zero padded frame
+/// number (e.g. "frame####.png" -> "frame0012.png"), filenames without
+/// '#' get the frame number appended (e.g. "pbrt.png" ->
+/// "pbrt_0012.png").
+pub fn frame_filename(filename: &str, frame: i32) -> String {
+    if let Some(end) = filename.rfind('#') {
+        let start: usize = filename[..end].trim_end_matches('#').len();
+        let width: usize = end + 1 - start;
+        format!(
+            "{}{:0width$}{}",
+            &filename[..start],
+            frame,
+            &filename[end + 1..],
+            width = width
+        )
+    } else {
+        let path: &Path = Path::new(filename);
+        match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(extension)) => path
+                .with_file_name(format!(
+                    "{}_{:04}.{}",
+                    stem.to_string_lossy(),
+                    frame,
+                    extension.to_string_lossy()
+                ))
+                .to_string_lossy()
+                .into_owned(),
+            _ => format!("{}_{:04}", filename, frame),
+        }
+    }
+}
+
 pub struct RenderOptions {
     pub transform_start_time: Float,
     pub transform_end_time: Float,
@@ -198,284 +323,681 @@ pub struct RenderOptions {
     pub camera_name: String, // "perspective";
     pub camera_params: ParamSet,
     pub camera_to_world: TransformSet,
+    pub camera_medium: String, // ""
     pub named_media: HashMap<String, Arc<Medium>>,
     pub lights: Vec<Arc<Light>>,
     pub primitives: Vec<Arc<Primitive>>,
     pub instances: HashMap<String, Vec<Arc<Primitive>>>,
     pub current_instance: String,
     pub have_scattering_media: bool, // false
+    pub have_shadow_catchers: bool,  // false
+    pub have_holdouts: bool,         // false
+    pub light_fingerprint: u64,      // 0
+    pub debug_non_finite: bool,      // false
+    /// BVHs of large meshes which get built while parsing continues
+    pub pending_builds: Vec<BuildHandle>,
+    /// BVHs of object instances which get built after **ObjectEnd**
+    /// (oldest first)
+    pub pending_instances: Vec<(String, BuildHandle)>,
 }
 
 impl RenderOptions {
-    pub fn make_integrator(&self) -> Option<Box<Integrator>> {
-        let mut some_integrator: Option<Box<Integrator>> = None;
-        let some_camera: Option<Arc<Camera>> = self.make_camera();
-        if let Some(camera) = some_camera {
-            let some_sampler: Option<Box<Sampler>> =
-                make_sampler(&self.sampler_name, &self.sampler_params, camera.get_film());
-            if let Some(sampler) = some_sampler {
-                if self.integrator_name == "whitted" {
-                    // CreateWhittedIntegrator
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::Whitted(
-                        WhittedIntegrator::new(max_depth as u32, camera, sampler, pixel_bounds),
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "directlighting" {
-                    // CreateDirectLightingIntegrator
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let st: String = self
-                        .integrator_params
-                        .find_one_string("strategy", String::from("all"));
-                    let strategy: LightStrategy;
-                    if st == "one" {
-                        strategy = LightStrategy::UniformSampleOne;
-                    } else if st == "all" {
-                        strategy = LightStrategy::UniformSampleAll;
-                    } else {
-                        panic!("Strategy \"{}\" for direct lighting unknown.", st);
-                    }
-                    // TODO: const int *pb = params.FindInt("pixelbounds", &np);
-                    let xres: i32 = self.film_params.find_one_int("xresolution", 1280);
-                    let yres: i32 = self.film_params.find_one_int("yresolution", 720);
-                    let pixel_bounds: Bounds2i = Bounds2i {
-                        p_min: Point2i { x: 0, y: 0 },
-                        p_max: Point2i { x: xres, y: yres },
-                    };
-                    let integrator = Box::new(Integrator::Sampler(
-                        SamplerIntegrator::DirectLighting(DirectLightingIntegrator::new(
-                            strategy,
-                            max_depth as u32,
-                            camera,
-                            sampler,
-                            pixel_bounds,
-                        )),
+    pub fn make_integrator(&self) -> Result<Box<Integrator>, Error> {
+        let camera: Arc<Camera> = self.make_camera()?;
+        // the density of moving grid media is exact in the middle of
+        // the shutter interval (aggregates share the named media)
+        let shutter_time: Float =
+            0.5 as Float * (camera.get_shutter_open() + camera.get_shutter_close());
+        for medium in self.named_media.values() {
+            if let Medium::GridDensity(ref grid) = **medium {
+                grid.set_reference_time(shutter_time);
+            }
+        }
+        let sampler: Box<Sampler> =
+            make_sampler(&self.sampler_name, &self.sampler_params, camera.get_film())?;
+        let mut integrator: Box<Integrator> = if self.integrator_name == "whitted" {
+            // CreateWhittedIntegrator
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            Box::new(Integrator::Sampler(SamplerIntegrator::Whitted(
+                WhittedIntegrator::new(max_depth as u32, camera, sampler, pixel_bounds),
+            )))
+        } else if self.integrator_name == "directlighting" {
+            // CreateDirectLightingIntegrator
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let st: String = self
+                .integrator_params
+                .find_one_string("strategy", String::from("all"));
+            let strategy: LightStrategy;
+            if st == "one" {
+                strategy = LightStrategy::UniformSampleOne;
+            } else if st == "all" {
+                strategy = LightStrategy::UniformSampleAll;
+            } else {
+                return Err(Error::create(
+                    "Integrator",
+                    &self.integrator_name,
+                    format!("strategy {:?} for direct lighting unknown", st),
+                ));
+            }
+            // TODO: const int *pb = params.FindInt("pixelbounds", &np);
+            let xres: i32 = self.film_params.find_one_int("xresolution", 1280);
+            let yres: i32 = self.film_params.find_one_int("yresolution", 720);
+            let pixel_bounds: Bounds2i = Bounds2i {
+                p_min: Point2i { x: 0, y: 0 },
+                p_max: Point2i { x: xres, y: yres },
+            };
+            Box::new(Integrator::Sampler(SamplerIntegrator::DirectLighting(
+                DirectLightingIntegrator::new(
+                    strategy,
+                    max_depth as u32,
+                    camera,
+                    sampler,
+                    pixel_bounds,
+                ),
+            )))
+        } else if self.integrator_name == "path" {
+            // CreatePathIntegrator
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
+            let np: usize = pb.len();
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            if np > 0 {
+                if np != 4 {
+                    return Err(Error::create(
+                        "Integrator",
+                        &self.integrator_name,
+                        format!("expected four values for \"pixelbounds\", got {}", np),
+                    ));
+                } else {
+                    warn!("TODO: pixelBounds = Intersect(...)");
+                    // pixelBounds = Intersect(pixelBounds,
+                    //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
+                    // if (pixelBounds.Area() == 0)
+                    //     Error("Degenerate \"pixelbounds\" specified.");
+                }
+            }
+            let rr_threshold: Float = self
+                .integrator_params
+                .find_one_float("rrthreshold", 1.0 as Float);
+            let light_strategy: String = self
+                .integrator_params
+                .find_one_string("lightsamplestrategy", String::from("spatial"));
+            let mut path_integrator: PathIntegrator = PathIntegrator::new(
+                max_depth as u32,
+                camera,
+                sampler,
+                pixel_bounds,
+                rr_threshold,
+                light_strategy,
+            );
+            // bounces of each kind, by default limited by "maxdepth" only
+            let kind_depth = |name: &str| -> u32 {
+                self.integrator_params.find_one_int(name, max_depth).max(0) as u32
+            };
+            path_integrator.depth_limits = DepthLimits {
+                diffuse: kind_depth("maxdiffusedepth"),
+                glossy: kind_depth("maxglossydepth"),
+                specular: kind_depth("maxspeculardepth"),
+                transmission: kind_depth("maxtransmissiondepth"),
+            };
+            path_integrator.clamp_indirect = self
+                .integrator_params
+                .find_one_float("clampindirect", 0.0 as Float);
+            path_integrator.roughening = self
+                .integrator_params
+                .find_one_float("roughening", 0.0 as Float);
+            if self
+                .integrator_params
+                .find_one_bool("irradiancecache", false)
+            {
+                let max_error: Float = self
+                    .integrator_params
+                    .find_one_float("irradiancecacheerror", 0.4 as Float);
+                let samples: i32 = self
+                    .integrator_params
+                    .find_one_int("irradiancecachesamples", 256);
+                path_integrator.irradiance_cache = Some(IrradianceCache::new(max_error, samples));
+            }
+            if self.integrator_params.find_one_bool("polarizer", false) {
+                let angle: Float = self
+                    .integrator_params
+                    .find_one_float("polarizerangle", 0.0 as Float);
+                #[cfg(feature = "polarization")]
+                {
+                    path_integrator.polarizer = Some(radians(angle));
+                }
+                #[cfg(not(feature = "polarization"))]
+                warn!(
+                    "Ignoring polarizer (angle {}), build with the \"polarization\" feature.",
+                    angle
+                );
+            }
+            Box::new(Integrator::Sampler(SamplerIntegrator::Path(
+                path_integrator,
+            )))
+        } else if self.integrator_name == "gradientpath" {
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            let rr_threshold: Float = self
+                .integrator_params
+                .find_one_float("rrthreshold", 1.0 as Float);
+            let light_strategy: String = self
+                .integrator_params
+                .find_one_string("lightsamplestrategy", String::from("spatial"));
+            let reconstruction: Reconstruction = match self
+                .integrator_params
+                .find_one_string("reconstruction", String::from("L1"))
+                .to_uppercase()
+                .as_str()
+            {
+                "L1" => Reconstruction::L1,
+                "L2" => Reconstruction::L2,
+                name => {
+                    return Err(Error::create(
+                        "Integrator",
+                        &self.integrator_name,
+                        format!("reconstruction {:?} unknown (expected L1 or L2)", name),
+                    ));
+                }
+            };
+            let alpha: Float = self
+                .integrator_params
+                .find_one_float("reconstructionalpha", 0.2 as Float);
+            let write_gradients: bool = self
+                .integrator_params
+                .find_one_bool("writegradients", false);
+            Box::new(Integrator::GradientPath(GradientPathIntegrator::new(
+                PathIntegrator::new(
+                    max_depth as u32,
+                    camera,
+                    sampler,
+                    pixel_bounds,
+                    rr_threshold,
+                    light_strategy,
+                ),
+                reconstruction,
+                alpha,
+                write_gradients,
+            )))
+        } else if self.integrator_name == "volpath" {
+            // CreateVolPathIntegrator
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
+            let np: usize = pb.len();
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            if np > 0 {
+                if np != 4 {
+                    return Err(Error::create(
+                        "Integrator",
+                        &self.integrator_name,
+                        format!("expected four values for \"pixelbounds\", got {}", np),
                     ));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "path" {
-                    // CreatePathIntegrator
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
-                    let np: usize = pb.len();
-                    let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    if np > 0 as usize {
-                        if np != 4 as usize {
-                            panic!(
-                                "Expected four values for \"pixelbounds\" parameter. Got {}.",
-                                np
-                            );
-                        } else {
-                            println!("TODO: pixelBounds = Intersect(...)");
-                            // pixelBounds = Intersect(pixelBounds,
-                            //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
-                            // if (pixelBounds.Area() == 0)
-                            //     Error("Degenerate \"pixelbounds\" specified.");
-                        }
-                    }
-                    let rr_threshold: Float = self
-                        .integrator_params
-                        .find_one_float("rrthreshold", 1.0 as Float);
-                    let light_strategy: String = self
-                        .integrator_params
-                        .find_one_string("lightsamplestrategy", String::from("spatial"));
-                    let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::Path(
-                        PathIntegrator::new(
-                            max_depth as u32,
-                            camera,
-                            sampler,
-                            pixel_bounds,
-                            rr_threshold,
-                            light_strategy,
-                        ),
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "volpath" {
-                    // CreateVolPathIntegrator
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
-                    let np: usize = pb.len();
-                    let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    if np > 0 as usize {
-                        if np != 4 as usize {
-                            panic!(
-                                "Expected four values for \"pixelbounds\" parameter. Got {}.",
-                                np
-                            );
-                        } else {
-                            println!("TODO: pixelBounds = Intersect(...)");
-                            // pixelBounds = Intersect(pixelBounds,
-                            //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
-                            // if (pixelBounds.Area() == 0)
-                            //     Error("Degenerate \"pixelbounds\" specified.");
-                        }
-                    }
-                    let rr_threshold: Float = self
-                        .integrator_params
-                        .find_one_float("rrthreshold", 1.0 as Float);
-                    let light_strategy: String = self
-                        .integrator_params
-                        .find_one_string("lightsamplestrategy", String::from("spatial"));
-                    let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::VolPath(
-                        VolPathIntegrator::new(
-                            max_depth as u32,
-                            camera,
-                            sampler,
-                            pixel_bounds,
-                            rr_threshold,
-                            light_strategy,
-                        ),
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "bdpt" {
-                    // CreateBDPTIntegrator
-                    let mut max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let visualize_strategies: bool = self
-                        .integrator_params
-                        .find_one_bool("visualizestrategies", false);
-                    let visualize_weights: bool = self
-                        .integrator_params
-                        .find_one_bool("visualizeweights", false);
-                    if (visualize_strategies || visualize_weights) && max_depth > 5_i32 {
-                        print!("WARNING: visualizestrategies/visualizeweights was enabled,");
-                        println!(" limiting maxdepth to 5");
-                        max_depth = 5;
-                    }
-                    let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    let light_strategy: String = self
-                        .integrator_params
-                        .find_one_string("lightsamplestrategy", String::from("power"));
-                    let integrator = Box::new(Integrator::BDPT(BDPTIntegrator::new(
-                        camera,
-                        sampler,
-                        pixel_bounds,
-                        max_depth as u32,
-                        light_strategy,
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "mlt" {
-                    // CreateMLTIntegrator
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let n_bootstrap: i32 = self
-                        .integrator_params
-                        .find_one_int("bootstrapsamples", 100_000);
-                    let n_chains: i32 = self.integrator_params.find_one_int("chains", 1000);
-                    let mutations_per_pixel: i32 = self
-                        .integrator_params
-                        .find_one_int("mutationsperpixel", 100);
-                    let large_step_probability: Float = self
-                        .integrator_params
-                        .find_one_float("largestepprobability", 0.3 as Float);
-                    let sigma: Float = self
-                        .integrator_params
-                        .find_one_float("sigma", 0.01 as Float);
-                    let integrator = Box::new(Integrator::MLT(MLTIntegrator::new(
-                        camera,
-                        max_depth as u32,
-                        n_bootstrap as u32,
-                        n_chains as u32,
-                        mutations_per_pixel as u32,
-                        sigma,
-                        large_step_probability,
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "ambientocclusion" {
-                    // CreateAOIntegrator
-                    let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
-                    let np: usize = pb.len();
-                    let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    if np > 0 as usize {
-                        if np != 4 as usize {
-                            panic!(
-                                "Expected four values for \"pixelbounds\" parameter. Got {}.",
-                                np
-                            );
-                        } else {
-                            println!("TODO: pixelBounds = Intersect(...)");
-                            // pixelBounds = Intersect(pixelBounds,
-                            //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
-                            // if (pixelBounds.Area() == 0)
-                            //     Error("Degenerate \"pixelbounds\" specified.");
-                        }
-                    }
-                    let cos_sample: bool = self.integrator_params.find_one_bool("cossample", true);
-                    let n_samples: i32 = self.integrator_params.find_one_int("nsamples", 64 as i32);
-                    let integrator = Box::new(Integrator::Sampler(SamplerIntegrator::AO(
-                        AOIntegrator::new(cos_sample, n_samples, camera, sampler, pixel_bounds),
-                    )));
-                    some_integrator = Some(integrator);
-                } else if self.integrator_name == "sppm" {
-                    // CreateSPPMIntegrator
-                    let mut n_iterations: i32 =
-                        self.integrator_params.find_one_int("numiterations", 64);
-                    n_iterations = self
-                        .integrator_params
-                        .find_one_int("iterations", n_iterations);
-                    let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
-                    let photons_per_iter: i32 = self
-                        .integrator_params
-                        .find_one_int("photonsperiteration", -1);
-                    let write_freq: i32 = self
-                        .integrator_params
-                        .find_one_int("imagewritefrequency", 1 << 31);
-                    let radius: Float = self
-                        .integrator_params
-                        .find_one_float("radius", 1.0 as Float);
-                    // TODO: if (PbrtOptions.quickRender) nIterations = std::max(1, nIterations / 16);
-                    let integrator = Box::new(Integrator::SPPM(SPPMIntegrator::new(
-                        camera,
-                        n_iterations,
-                        photons_per_iter,
-                        max_depth as u32,
-                        radius,
-                        write_freq,
-                    )));
-                    some_integrator = Some(integrator);
                 } else {
-                    println!("Integrator \"{}\" unknown.", self.integrator_name);
+                    warn!("TODO: pixelBounds = Intersect(...)");
+                    // pixelBounds = Intersect(pixelBounds,
+                    //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
+                    // if (pixelBounds.Area() == 0)
+                    //     Error("Degenerate \"pixelbounds\" specified.");
                 }
+            }
+            let rr_threshold: Float = self
+                .integrator_params
+                .find_one_float("rrthreshold", 1.0 as Float);
+            let light_strategy: String = self
+                .integrator_params
+                .find_one_string("lightsamplestrategy", String::from("spatial"));
+            let mut volpath_integrator: VolPathIntegrator = VolPathIntegrator::new(
+                max_depth as u32,
+                camera,
+                sampler,
+                pixel_bounds,
+                rr_threshold,
+                light_strategy,
+            );
+            volpath_integrator.clamp_indirect = self
+                .integrator_params
+                .find_one_float("clampindirect", 0.0 as Float);
+            volpath_integrator.roughening = self
+                .integrator_params
+                .find_one_float("roughening", 0.0 as Float);
+            Box::new(Integrator::Sampler(SamplerIntegrator::VolPath(
+                volpath_integrator,
+            )))
+        } else if self.integrator_name == "bdpt" {
+            // CreateBDPTIntegrator
+            let mut max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let visualize_strategies: bool = self
+                .integrator_params
+                .find_one_bool("visualizestrategies", false);
+            let visualize_weights: bool = self
+                .integrator_params
+                .find_one_bool("visualizeweights", false);
+            if (visualize_strategies || visualize_weights) && max_depth > 5_i32 {
+                warn!("visualizestrategies/visualizeweights was enabled, limiting maxdepth to 5");
+                max_depth = 5;
+            }
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            let light_strategy: String = self
+                .integrator_params
+                .find_one_string("lightsamplestrategy", String::from("power"));
+            Box::new(Integrator::BDPT(BDPTIntegrator::new(
+                camera,
+                sampler,
+                pixel_bounds,
+                max_depth as u32,
+                light_strategy,
+            )))
+        } else if self.integrator_name == "lighttracing" {
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            if !matches!(*camera, Camera::Perspective(_)) {
+                return Err(Error::create(
+                    "Integrator",
+                    &self.integrator_name,
+                    String::from("only supported with a \"perspective\" camera"),
+                ));
+            }
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            Box::new(Integrator::LightTracing(LightTracingIntegrator::new(
+                camera,
+                sampler,
+                pixel_bounds,
+                max_depth as u32,
+            )))
+        } else if self.integrator_name == "mlt" {
+            // CreateMLTIntegrator
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let n_bootstrap: i32 = self
+                .integrator_params
+                .find_one_int("bootstrapsamples", 100_000);
+            let n_chains: i32 = self.integrator_params.find_one_int("chains", 1000);
+            let mutations_per_pixel: i32 = self
+                .integrator_params
+                .find_one_int("mutationsperpixel", 100);
+            let large_step_probability: Float = self
+                .integrator_params
+                .find_one_float("largestepprobability", 0.3 as Float);
+            let sigma: Float = self
+                .integrator_params
+                .find_one_float("sigma", 0.01 as Float);
+            Box::new(Integrator::MLT(MLTIntegrator::new(
+                camera,
+                max_depth as u32,
+                n_bootstrap as u32,
+                n_chains as u32,
+                mutations_per_pixel as u32,
+                sigma,
+                large_step_probability,
+            )))
+        } else if self.integrator_name == "ambientocclusion" {
+            // CreateAOIntegrator
+            let pb: Vec<i32> = self.integrator_params.find_int("pixelbounds");
+            let np: usize = pb.len();
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            if np > 0 {
+                if np != 4 {
+                    return Err(Error::create(
+                        "Integrator",
+                        &self.integrator_name,
+                        format!("expected four values for \"pixelbounds\", got {}", np),
+                    ));
+                } else {
+                    warn!("TODO: pixelBounds = Intersect(...)");
+                    // pixelBounds = Intersect(pixelBounds,
+                    //                         Bounds2i{{pb[0], pb[2]}, {pb[1], pb[3]}});
+                    // if (pixelBounds.Area() == 0)
+                    //     Error("Degenerate \"pixelbounds\" specified.");
+                }
+            }
+            let cos_sample: bool = self.integrator_params.find_one_bool("cossample", true);
+            let n_samples: i32 = self.integrator_params.find_one_int("nsamples", 64);
+            let max_distance: Float = self
+                .integrator_params
+                .find_one_float("maxdistance", Float::INFINITY);
+            let gpu: bool = self.integrator_params.find_one_bool("gpu", false);
+            if gpu && !cfg!(feature = "gpu") {
+                warn!(
+                    "\"gpu\" needs rs_pbrt built with the feature \"gpu\", rendering on the CPU."
+                );
+            }
+            Box::new(Integrator::Sampler(SamplerIntegrator::AO(
+                AOIntegrator::new(
+                    cos_sample,
+                    n_samples,
+                    max_distance,
+                    gpu,
+                    camera,
+                    sampler,
+                    pixel_bounds,
+                ),
+            )))
+        } else if self.integrator_name == "photonmap" {
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let pixel_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+            let mut integrator: PhotonMapIntegrator =
+                PhotonMapIntegrator::new(max_depth as u32, camera, sampler, pixel_bounds);
+            integrator.photons = self
+                .integrator_params
+                .find_one_int("photons", integrator.photons);
+            integrator.caustic_photons = self
+                .integrator_params
+                .find_one_int("causticphotons", integrator.caustic_photons);
+            integrator.lookup_photons = self
+                .integrator_params
+                .find_one_int("lookupphotons", integrator.lookup_photons);
+            integrator.radius = self
+                .integrator_params
+                .find_one_float("radius", integrator.radius);
+            integrator.final_gather = self
+                .integrator_params
+                .find_one_bool("finalgather", integrator.final_gather);
+            integrator.final_gather_samples = self
+                .integrator_params
+                .find_one_int("finalgathersamples", integrator.final_gather_samples);
+            Box::new(Integrator::Sampler(SamplerIntegrator::PhotonMap(
+                integrator,
+            )))
+        } else if self.integrator_name == "sppm" {
+            // CreateSPPMIntegrator
+            let mut n_iterations: i32 = self.integrator_params.find_one_int("numiterations", 64);
+            n_iterations = self
+                .integrator_params
+                .find_one_int("iterations", n_iterations);
+            let max_depth: i32 = self.integrator_params.find_one_int("maxdepth", 5);
+            let photons_per_iter: i32 = self
+                .integrator_params
+                .find_one_int("photonsperiteration", -1);
+            let write_freq: i32 = self
+                .integrator_params
+                .find_one_int("imagewritefrequency", 1 << 31);
+            let radius: Float = self
+                .integrator_params
+                .find_one_float("radius", 1.0 as Float);
+            // TODO: if (PbrtOptions.quickRender) nIterations = std::max(1, nIterations / 16);
+            Box::new(Integrator::SPPM(SPPMIntegrator::new(
+                camera,
+                n_iterations,
+                photons_per_iter,
+                max_depth as u32,
+                radius,
+                write_freq,
+            )))
+        } else {
+            return Err(Error::create(
+                "Integrator",
+                &self.integrator_name,
+                String::from("unknown"),
+            ));
+        };
+        integrator.set_terminator_shadowing(
+            self.integrator_params
+                .find_one_bool("terminatorshadowing", false),
+        );
+        if let Integrator::Sampler(ref mut integrator) = *integrator {
+            integrator.set_debug_non_finite(self.debug_non_finite);
+        }
+        if self.have_scattering_media
+            && !["volpath", "bdpt", "lighttracing", "mlt"].contains(&self.integrator_name.as_str())
+        {
+            warn!(
+                "Scene has scattering media but {:?} integrator doesn't support volume scattering. Consider using \"volpath\", \"bdpt\", or \"mlt\".",
+                self.integrator_name
+            );
+        }
+        Ok(integrator)
+    }
+    /// The number of samples per pixel (or the equivalent for
+    /// **sppm** and **mlt**), see **apply_overrides()**.
+    pub fn samples_per_pixel(&self) -> i32 {
+        if self.integrator_name == "sppm" {
+            let n_iterations: i32 = self.integrator_params.find_one_int("numiterations", 64);
+            self.integrator_params
+                .find_one_int("iterations", n_iterations)
+        } else if self.integrator_name == "mlt" {
+            self.integrator_params
+                .find_one_int("mutationsperpixel", 100)
+        } else if self.sampler_name == "stratified" {
+            self.sampler_params.find_one_int("xsamples", 4)
+                * self.sampler_params.find_one_int("ysamples", 4)
+        } else if self.sampler_name == "random" {
+            self.sampler_params.find_one_int("pixelsamples", 4)
+        } else {
+            self.sampler_params.find_one_int("pixelsamples", 16)
+        }
+    }
+    /// The render settings which get embedded into the written images
+    /// (see **Film::set_metadata()**).
+    pub fn image_metadata(&self, scene_filename: Option<&str>) -> Vec<(String, String)> {
+        let mut metadata: Vec<(String, String)> = vec![(
+            String::from("software"),
+            format!("rs_pbrt {}", env!("CARGO_PKG_VERSION")),
+        )];
+        if let Some(scene_filename) = scene_filename {
+            metadata.push((String::from("scene"), String::from(scene_filename)));
+        }
+        metadata.push((
+            String::from("samplesPerPixel"),
+            self.samples_per_pixel().to_string(),
+        ));
+        metadata.push((
+            String::from("cameraToWorld"),
+            matrix_to_string(&self.camera_to_world.t[0]),
+        ));
+        metadata.push((
+            String::from("camera"),
+            statement_to_string("Camera", &self.camera_name, &self.camera_params),
+        ));
+        metadata.push((
+            String::from("sampler"),
+            statement_to_string("Sampler", &self.sampler_name, &self.sampler_params),
+        ));
+        metadata.push((
+            String::from("integrator"),
+            statement_to_string("Integrator", &self.integrator_name, &self.integrator_params),
+        ));
+        metadata
+    }
+    pub fn apply_overrides(&mut self, overrides: &RenderOverrides) {
+        if let Some(ref integrator) = overrides.integrator {
+            self.integrator_name = integrator.clone();
+        }
+        if let Some(spp) = overrides.pixel_samples {
+            if self.integrator_name == "sppm" {
+                // one photon pass per iteration
+                self.integrator_params
+                    .erase_int(String::from("numiterations"));
+                self.integrator_params
+                    .add_int(String::from("numiterations"), spp);
+            } else if self.integrator_name == "mlt" {
+                self.integrator_params
+                    .erase_int(String::from("mutationsperpixel"));
+                self.integrator_params
+                    .add_int(String::from("mutationsperpixel"), spp);
+            } else if self.sampler_name == "stratified" {
+                let n: i32 = ((spp as Float).sqrt().round() as i32).max(1);
+                if n * n != spp {
+                    warn!(
+                        "Stratified sampler uses {}x{} instead of {} samples per pixel.",
+                        n, n, spp
+                    );
+                }
+                self.sampler_params.erase_int(String::from("xsamples"));
+                self.sampler_params.erase_int(String::from("ysamples"));
+                self.sampler_params.add_int(String::from("xsamples"), n);
+                self.sampler_params.add_int(String::from("ysamples"), n);
             } else {
-                panic!("Unable to create sampler.");
+                self.sampler_params.erase_int(String::from("pixelsamples"));
+                self.sampler_params
+                    .add_int(String::from("pixelsamples"), spp);
+            }
+        }
+        if let Some(seed) = overrides.seed {
+            self.sampler_params.erase_int(String::from("seed"));
+            self.sampler_params.add_int(String::from("seed"), seed);
+        }
+        if let Some((xres, yres)) = overrides.resolution {
+            self.film_params.erase_int(String::from("xresolution"));
+            self.film_params.erase_int(String::from("yresolution"));
+            self.film_params.add_int(String::from("xresolution"), xres);
+            self.film_params.add_int(String::from("yresolution"), yres);
+        }
+        if let Some(ref outfile) = overrides.outfile {
+            self.film_params.erase_string(String::from("filename"));
+            self.film_params
+                .add_string(String::from("filename"), outfile.clone());
+        }
+        if let Some(frame) = overrides.frame {
+            let mut filename: String = self.film_params.find_one_string("filename", String::new());
+            if !filename.to_lowercase().ends_with(".png") {
+                // see Film::png_filename()
+                filename = String::from("pbrt.png");
             }
+            self.film_params.erase_string(String::from("filename"));
+            self.film_params
+                .add_string(String::from("filename"), frame_filename(&filename, frame));
+        }
+        if overrides.denoise {
+            self.film_params.erase_bool(String::from("denoise"));
+            self.film_params.add_bool(String::from("denoise"), true);
+        }
+        self.debug_non_finite = overrides.debug_nans;
+    }
+    /// A film with *diskbacked* only works for integrators which render
+    /// the image tile by tile, and only if image files get written.
+    pub fn check_disk_backed_film(&mut self, in_memory: bool) {
+        if !self.film_params.find_one_bool("diskbacked", false) {
+            return;
+        }
+        let reason: String = if in_memory {
+            String::from("rendering into memory")
+        } else if ["bdpt", "gradientpath", "lighttracing", "mlt", "sppm"]
+            .contains(&self.integrator_name.as_str())
+        {
+            format!("integrator {:?}", self.integrator_name)
         } else {
-            panic!("Unable to create camera.");
+            return;
+        };
+        warn!(
+            "Film \"diskbacked\" is not supported for {}, keeping the whole image in memory.",
+            reason
+        );
+        self.film_params.erase_bool(String::from("diskbacked"));
+    }
+    /// The AOVs (see **AovType**), deep images, and denoiser features
+    /// of a film are only written by the integrators which render the
+    /// image tile by tile, sample by sample (see **SamplerIntegrator**).
+    pub fn check_film_outputs(&mut self) {
+        if !["bdpt", "gradientpath", "lighttracing", "mlt", "sppm"]
+            .contains(&self.integrator_name.as_str())
+        {
+            return;
+        }
+        if !self.film_params.find_string("aovs").is_empty() {
+            warn!(
+                "Film \"aovs\" are not supported for integrator {:?}, ignoring them.",
+                self.integrator_name
+            );
+            self.film_params.erase_string(String::from("aovs"));
+        }
+        if self.film_params.find_one_bool("deep", false) {
+            warn!(
+                "Film \"deep\" is not supported for integrator {:?}, ignoring it.",
+                self.integrator_name
+            );
+            self.film_params.erase_bool(String::from("deep"));
+        }
+        if self.film_params.find_one_bool("denoiserfeatures", false) {
+            warn!(
+                "Film \"denoiserfeatures\" is not supported for integrator {:?}, ignoring it.",
+                self.integrator_name
+            );
+            self.film_params
+                .erase_bool(String::from("denoiserfeatures"));
+        }
+    }
+    /// Waits for the oldest background BVH builds (see
+    /// **pending_builds** and **pending_instances**) until at most
+    /// *max_pending* are left. Meshes get added to the primitives, the
+    /// BVH of an instance replaces the primitives of the instance.
+    pub fn wait_for_builds(&mut self, max_pending: usize) {
+        while self.pending_builds.len() + self.pending_instances.len() > max_pending {
+            if self.pending_builds.is_empty() {
+                let (name, handle) = self.pending_instances.remove(0);
+                self.finish_instance(&name, handle);
+            } else {
+                let handle: BuildHandle = self.pending_builds.remove(0);
+                self.primitives.push(join_build(handle));
+            }
+        }
+    }
+    /// Waits for the BVH of the instance *name* (if it's still among
+    /// **pending_instances**).
+    pub fn wait_for_instance(&mut self, name: &str) {
+        if let Some(index) = self
+            .pending_instances
+            .iter()
+            .position(|(pending, _handle)| pending == name)
+        {
+            let (name, handle) = self.pending_instances.remove(index);
+            self.finish_instance(&name, handle);
+        }
+    }
+    fn finish_instance(&mut self, name: &str, handle: BuildHandle) {
+        let accelerator: Arc<Primitive> = join_build(handle);
+        if let Some(instance_vec) = self.instances.get_mut(name) {
+            instance_vec.clear();
+            instance_vec.push(accelerator);
         }
-        some_integrator
     }
-    pub fn make_scene(&self) -> Scene {
-        let some_accelerator = make_accelerator(
+    pub fn make_scene(&mut self) -> Result<Scene, Error> {
+        // this joins the builds of instances which never got instanced, too
+        self.wait_for_builds(0);
+        let accelerator: Arc<Primitive> = make_accelerator(
             &self.accelerator_name,
             &self.primitives,
             &self.accelerator_params,
+        )?;
+        let mut scene: Scene = Scene::new(accelerator, self.lights.clone());
+        scene.has_shadow_catchers = self.have_shadow_catchers;
+        scene.has_holdouts = self.have_holdouts;
+        scene.light_fingerprint = self.light_fingerprint;
+        Ok(scene)
+    }
+    pub fn make_camera(&self) -> Result<Arc<Camera>, Error> {
+        let filter: Box<Filter> = make_filter(&self.filter_name, &self.filter_params)?;
+        let film: Arc<Film> = make_film(&self.film_name, &self.film_params, filter)?;
+        let animated_cam_to_world: AnimatedTransform = AnimatedTransform::new(
+            &self.camera_to_world.t[0],
+            self.transform_start_time,
+            &self.camera_to_world.t[1],
+            self.transform_end_time,
         );
-        if let Some(accelerator) = some_accelerator {
-            Scene::new(accelerator, self.lights.clone())
-        } else {
-            panic!("Unable to create accelerator.");
-        }
-    }
-    pub fn make_camera(&self) -> Option<Arc<Camera>> {
-        let mut some_camera: Option<Arc<Camera>> = None;
-        let some_filter = make_filter(&self.filter_name, &self.filter_params);
-        if let Some(filter) = some_filter {
-            let some_film: Option<Arc<Film>> =
-                make_film(&self.film_name, &self.film_params, filter);
-            if let Some(film) = some_film {
-                let animated_cam_to_world: AnimatedTransform = AnimatedTransform::new(
-                    &self.camera_to_world.t[0],
-                    self.transform_start_time,
-                    &self.camera_to_world.t[1],
-                    self.transform_end_time,
-                );
-                some_camera = make_camera(
-                    &self.camera_name,
-                    &self.camera_params,
-                    animated_cam_to_world,
-                    film,
-                );
+        make_camera(
+            &self.camera_name,
+            &self.camera_params,
+            animated_cam_to_world,
+            film,
+            &self.make_camera_medium_interface()?,
+        )
+    }
+    /// The camera sits in the outside medium which was current when
+    /// the camera was declared. This gives a scene-wide atmosphere
+    /// (e.g. fog) without enclosing the scene in geometry.
+    fn make_camera_medium_interface(&self) -> Result<MediumInterface, Error> {
+        let mut m: MediumInterface = MediumInterface::default();
+        if !self.camera_medium.is_empty() {
+            match self.named_media.get(&self.camera_medium) {
+                Some(camera_medium_arc) => m.outside = Some(camera_medium_arc.clone()),
+                None => {
+                    return Err(Error::create(
+                        "Camera",
+                        &self.camera_name,
+                        format!("named medium {:?} undefined", self.camera_medium),
+                    ));
+                }
             }
         }
-        some_camera
+        Ok(m)
     }
 }
 
@@ -516,12 +1038,19 @@ impl Default for RenderOptions {
                     },
                 }; 2],
             },
+            camera_medium: String::new(),
             named_media: HashMap::new(),
             lights: Vec::new(),
             primitives: Vec::new(),
             instances: HashMap::new(),
             current_instance: String::from(""),
             have_scattering_media: false,
+            have_shadow_catchers: false,
+            have_holdouts: false,
+            light_fingerprint: 0,
+            debug_non_finite: false,
+            pending_builds: Vec::new(),
+            pending_instances: Vec::new(),
         }
     }
 }
@@ -530,8 +1059,8 @@ impl Default for RenderOptions {
 pub struct GraphicsState {
     pub current_inside_medium: String,
     pub current_outside_medium: String,
-    pub float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>>>,
-    pub spectrum_textures: Arc<HashMap<String, Arc<dyn Texture<Spectrum> + Send + Sync>>>,
+    pub float_textures: Arc<HashMap<String, Arc<Texture<Float>>>>,
+    pub spectrum_textures: Arc<HashMap<String, Arc<Texture<Spectrum>>>>,
     pub material_params: ParamSet,
     pub material: String,
     pub named_materials: Arc<HashMap<String, Option<Arc<Material>>>>,
@@ -543,9 +1072,8 @@ pub struct GraphicsState {
 
 impl GraphicsState {
     pub fn new() -> Self {
-        let float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>>> =
-            Arc::new(HashMap::new());
-        let spectrum_textures: Arc<HashMap<String, Arc<dyn Texture<Spectrum> + Send + Sync>>> =
+        let float_textures: Arc<HashMap<String, Arc<Texture<Float>>>> = Arc::new(HashMap::new());
+        let spectrum_textures: Arc<HashMap<String, Arc<Texture<Spectrum>>>> =
             Arc::new(HashMap::new());
         let mut tp: TextureParams = TextureParams::new(
             ParamSet::default(),
@@ -592,7 +1120,10 @@ impl GraphicsState {
     // }
 }
 
-fn create_material(api_state: &ApiState, bsdf_state: &mut BsdfState) -> Option<Arc<Material>> {
+fn create_material(
+    api_state: &ApiState,
+    bsdf_state: &mut BsdfState,
+) -> Result<Option<Arc<Material>>, ParseError> {
     // CreateMaterial
     let mut material_params = ParamSet::default();
     material_params.copy_from(&api_state.graphics_state.material_params);
@@ -609,11 +1140,11 @@ fn create_material(api_state: &ApiState, bsdf_state: &mut BsdfState) -> Option<A
             .get(api_state.graphics_state.current_material.as_str())
         {
             Some(named_material) => {
-                return named_material.clone();
+                return Ok(named_material.clone());
             }
             None => {
-                println!(
-                    "WARNING: Named material \"{}\" not defined. Using \"matte\".",
+                warn!(
+                    "Named material \"{}\" not defined. Using \"matte\".",
                     api_state.graphics_state.current_material
                 );
             }
@@ -621,35 +1152,43 @@ fn create_material(api_state: &ApiState, bsdf_state: &mut BsdfState) -> Option<A
     } else {
         // MakeMaterial
         if api_state.graphics_state.material == "" || api_state.graphics_state.material == "none" {
-            return None;
+            return Ok(None);
         } else if api_state.graphics_state.material == "matte" {
-            return Some(MatteMaterial::create(&mut mp));
+            return Ok(Some(MatteMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "plastic" {
-            return Some(PlasticMaterial::create(&mut mp));
+            return Ok(Some(PlasticMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "translucent" {
-            return Some(TranslucentMaterial::create(&mut mp));
+            return Ok(Some(TranslucentMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "glass" {
-            return Some(GlassMaterial::create(&mut mp));
+            return Ok(Some(GlassMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "mirror" {
-            return Some(MirrorMaterial::create(&mut mp));
+            return Ok(Some(MirrorMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "hair" {
-            return Some(HairMaterial::create(&mut mp));
+            return Ok(Some(HairMaterial::create(&mut mp)));
+        } else if api_state.graphics_state.material == "fluorescent" {
+            return Ok(Some(FluorescentMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "mix" {
             let m1: String = mp.find_string("namedmaterial1", String::from(""));
             let m2: String = mp.find_string("namedmaterial2", String::from(""));
             let mat1 = match api_state.graphics_state.named_materials.get(&m1) {
                 Some(named_material) => named_material,
                 None => {
-                    panic!("Material \"{}\" unknown.", m1);
+                    return Err(ParseError::new(
+                        &m1,
+                        format!("Named material \"{}\" unknown", m1),
+                    ));
                 }
             };
             let mat2 = match api_state.graphics_state.named_materials.get(&m2) {
                 Some(named_material) => named_material,
                 None => {
-                    panic!("Material \"{}\" unknown.", m2);
+                    return Err(ParseError::new(
+                        &m2,
+                        format!("Named material \"{}\" unknown", m2),
+                    ));
                 }
             };
-            let scale: Arc<dyn Texture<Spectrum> + Send + Sync> =
+            let scale: Arc<Texture<Spectrum>> =
                 mp.get_spectrum_texture("amount", Spectrum::new(0.5));
             if let Some(m1) = mat1 {
                 if let Some(m2) = mat2 {
@@ -658,39 +1197,39 @@ fn create_material(api_state: &ApiState, bsdf_state: &mut BsdfState) -> Option<A
                         m2.clone(),
                         scale,
                     ))));
-                    return Some(mix);
+                    return Ok(Some(mix));
                 }
             }
-            return None;
+            return Ok(None);
         } else if api_state.graphics_state.material == "metal" {
-            return Some(MetalMaterial::create(&mut mp));
+            return Ok(Some(MetalMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "substrate" {
-            return Some(SubstrateMaterial::create(&mut mp));
+            return Ok(Some(SubstrateMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "uber" {
-            return Some(UberMaterial::create(&mut mp));
+            return Ok(Some(UberMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "subsurface" {
-            return Some(SubsurfaceMaterial::create(&mut mp));
+            return Ok(Some(SubsurfaceMaterial::create(&mut mp)));
         } else if api_state.graphics_state.material == "kdsubsurface" {
-            println!("TODO: CreateKdsubsurfaceMaterial");
+            warn!("TODO: CreateKdsubsurfaceMaterial");
         } else if api_state.graphics_state.material == "fourier" {
-            return Some(FourierMaterial::create(&mut mp, bsdf_state));
+            return Ok(Some(FourierMaterial::create(&mut mp, bsdf_state)));
         } else if api_state.graphics_state.material == "disney" {
-            return Some(DisneyMaterial::create(&mut mp));
+            return Ok(Some(DisneyMaterial::create(&mut mp)));
         } else {
-            panic!(
-                "Material \"{}\" unknown.",
-                api_state.graphics_state.material
-            );
+            return Err(ParseError::new(
+                &api_state.graphics_state.material,
+                format!("Material \"{}\" unknown", api_state.graphics_state.material),
+            ));
         }
     }
-    let kd = Arc::new(ConstantTexture::new(Spectrum::new(0.5)));
-    let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
-    Some(Arc::new(Material::Matte(Box::new(MatteMaterial::new(
-        kd, sigma, None,
+    let kd = Arc::new(Texture::Constant(ConstantTexture::new(Spectrum::new(0.5))));
+    let sigma = Arc::new(Texture::Constant(ConstantTexture::new(0.0 as Float)));
+    Ok(Some(Arc::new(Material::Matte(Box::new(
+        MatteMaterial::new(kd, sigma, None),
     )))))
 }
 
-fn create_medium_interface(api_state: &ApiState) -> MediumInterface {
+fn create_medium_interface(api_state: &ApiState) -> Result<MediumInterface, ParseError> {
     let mut m: MediumInterface = MediumInterface::default();
     if api_state.graphics_state.current_inside_medium != "" {
         match api_state
@@ -700,10 +1239,13 @@ fn create_medium_interface(api_state: &ApiState) -> MediumInterface {
         {
             Some(inside_medium_arc) => m.inside = Some(inside_medium_arc.clone()),
             None => {
-                panic!(
-                    "ERROR: Named medium \"{}\" undefined.",
-                    api_state.graphics_state.current_inside_medium
-                );
+                return Err(ParseError::new(
+                    &api_state.graphics_state.current_inside_medium,
+                    format!(
+                        "Named medium \"{}\" undefined",
+                        api_state.graphics_state.current_inside_medium
+                    ),
+                ));
             }
         }
     }
@@ -715,14 +1257,17 @@ fn create_medium_interface(api_state: &ApiState) -> MediumInterface {
         {
             Some(outside_medium_arc) => m.outside = Some(outside_medium_arc.clone()),
             None => {
-                panic!(
-                    "ERROR: Named medium \"{}\" undefined.",
-                    api_state.graphics_state.current_outside_medium
-                );
+                return Err(ParseError::new(
+                    &api_state.graphics_state.current_outside_medium,
+                    format!(
+                        "Named medium \"{}\" undefined",
+                        api_state.graphics_state.current_outside_medium
+                    ),
+                ));
             }
         }
     }
-    m
+    Ok(m)
 }
 
 fn make_light(api_state: &mut ApiState, medium_interface: &MediumInterface) {
@@ -890,7 +1435,20 @@ fn make_light(api_state: &mut ApiState, medium_interface: &MediumInterface) {
                 let mut path_buf: PathBuf = PathBuf::from("/");
                 path_buf.push(search_directory.as_ref());
                 path_buf.push(texmap);
-                texmap = String::from(path_buf.to_str().unwrap());
+                match path_buf.to_str() {
+                    Some(path) => texmap = String::from(path),
+                    None => {
+                        api_state.add_error(
+                            Error::create(
+                                "LightSource",
+                                "infinite",
+                                format!("\"mapname\" {:?} is not valid UTF-8", path_buf),
+                            )
+                            .into(),
+                        );
+                        return;
+                    }
+                }
             }
         }
         let n_samples: i32 = api_state.param_set.find_one_int("nsamples", 1 as i32);
@@ -905,14 +1463,57 @@ fn make_light(api_state: &mut ApiState, medium_interface: &MediumInterface) {
         ))));
         api_state.render_options.lights.push(infinte_light);
     } else {
-        panic!("MakeLight: unknown name {}", api_state.param_set.name);
+        let name: String = api_state.param_set.name.clone();
+        api_state.add_error(ParseError::new(
+            &name,
+            format!("LightSource \"{}\" unknown", name),
+        ));
+    }
+}
+
+/// Returns a phase function other than Henyey-Greenstein, if the
+/// medium asks for one via _"string phase"_. Invalid tables (or Mie
+/// parameters) are reported as errors.
+fn make_phase_function(api_state: &mut ApiState) -> Option<Arc<PhaseFunction>> {
+    let params: &ParamSet = &api_state.param_set;
+    let phase_type: String = params.find_one_string("phase", String::from("hg"));
+    let phase: Result<TabulatedPhaseFunction, String> = if phase_type == "hg" {
+        return None;
+    } else if phase_type == "tabulated" {
+        let angles: Vec<Float> = params.find_float("phaseangles");
+        let values: Vec<Float> = params.find_float("phasevalues");
+        let theta: Vec<Float> = angles.iter().map(|a| radians(*a)).collect();
+        TabulatedPhaseFunction::new(&theta, &values)
+    } else if phase_type == "mie" {
+        // default: typical cloud/fog water droplet
+        let radius: Float = params.find_one_float("radius", 10.0 as Float);
+        let eta: Float = params.find_one_float("eta", 1.333 as Float);
+        mie_phase_function(radius, eta, 1024)
+    } else {
+        warn!(
+            "Phase function \"{}\" unknown. Using Henyey-Greenstein.",
+            phase_type
+        );
+        return None;
+    };
+    match phase {
+        Ok(phase) => Some(Arc::new(PhaseFunction::Tabulated(Box::new(phase)))),
+        Err(message) => {
+            api_state.add_error(ParseError::new(&phase_type, message));
+            None
+        }
     }
 }
 
 fn make_medium(api_state: &mut ApiState) {
     let medium_type: String = api_state.param_set.find_one_string("type", String::new());
     if medium_type == "" {
-        panic!("ERROR: No parameter string \"type\" found in MakeNamedMedium");
+        let name: String = api_state.param_set.name.clone();
+        api_state.add_error(ParseError::new(
+            &name,
+            String::from("No parameter string \"type\" found in MakeNamedMedium"),
+        ));
+        return;
     }
     // MakeMedium (api.cpp:685)
     let sig_a_rgb: [Float; 3] = [0.0011, 0.0024, 0.014];
@@ -922,8 +1523,8 @@ fn make_medium(api_state: &mut ApiState) {
     let preset: String = api_state.param_set.find_one_string("preset", String::new());
     let found: bool = get_medium_scattering_properties(&preset, &mut sig_a, &mut sig_s);
     if preset != "" && !found {
-        println!(
-            "WARNING: Material preset \"{:?}\" not found.  Using defaults.",
+        warn!(
+            "Material preset \"{:?}\" not found.  Using defaults.",
             preset
         );
     }
@@ -931,15 +1532,33 @@ fn make_medium(api_state: &mut ApiState) {
     let g: Float = api_state.param_set.find_one_float("g", 0.0 as Float);
     sig_a = api_state.param_set.find_one_spectrum("sigma_a", sig_a) * scale;
     sig_s = api_state.param_set.find_one_spectrum("sigma_s", sig_s) * scale;
+    // alternatively specify absorption by the (spectral) transmittance
+    // after a given distance, e.g. for colored glass or liquids
+    let transmittance: Vec<Spectrum> = api_state.param_set.find_spectrum("transmittance");
+    if !transmittance.is_empty() {
+        let distance: Float = api_state
+            .param_set
+            .find_one_float("transmittancedistance", 1.0 as Float);
+        if distance > 0.0 as Float {
+            for i in 0..Spectrum::N_SAMPLES {
+                let t: Float = transmittance[0][i].max(1e-6 as Float).min(1.0 as Float);
+                sig_a[i] = -t.ln() / distance;
+            }
+        } else {
+            warn!("\"transmittancedistance\" has to be positive. Ignoring \"transmittance\".");
+        }
+    }
     let some_medium: Option<Arc<Medium>>;
     if medium_type == "homogeneous" {
-        some_medium = Some(Arc::new(Medium::Homogeneous(HomogeneousMedium::new(
-            &sig_a, &sig_s, g,
-        ))));
+        let mut medium: HomogeneousMedium = HomogeneousMedium::new(&sig_a, &sig_s, g);
+        if let Some(phase) = make_phase_function(api_state) {
+            medium.phase = phase;
+        }
+        some_medium = Some(Arc::new(Medium::Homogeneous(medium)));
     } else if medium_type == "heterogeneous" {
         let data: Arc<Vec<Float>> = Arc::new(api_state.param_set.find_float("density"));
         if data.is_empty() {
-            println!("ERROR: No \"density\" values provided for heterogeneous medium?");
+            error!("No \"density\" values provided for heterogeneous medium?");
             some_medium = None;
         } else {
             let nx: i32 = api_state.param_set.find_one_int("nx", 1_i32);
@@ -962,8 +1581,8 @@ fn make_medium(api_state: &mut ApiState) {
                 },
             );
             if data.len() != (nx * ny * nz) as usize {
-                println!(
-                    "ERROR: GridDensityMedium has {} density values; expected nx*ny*nz = {}",
+                error!(
+                    "GridDensityMedium has {} density values; expected nx*ny*nz = {}",
                     data.len(),
                     nx * ny * nz
                 );
@@ -972,7 +1591,28 @@ fn make_medium(api_state: &mut ApiState) {
                 let data_2_medium: Transform = Transform::translate(&Vector3f::from(p0))
                     * Transform::scale(p1.x - p0.x, p1.y - p0.y, p1.z - p0.z);
                 let medium_2_world = api_state.cur_transform.t[0];
-                some_medium = Some(Arc::new(Medium::GridDensity(GridDensityMedium::new(
+                // optional temperature grid for emissive media (fire)
+                let temperature: Vec<Float> = api_state.param_set.find_float("temperature");
+                let some_temperature: Option<Arc<Vec<Float>>> = if temperature.is_empty() {
+                    None
+                } else if temperature.len() != (nx * ny * nz) as usize {
+                    error!(
+                        "GridDensityMedium has {} temperature values; expected nx*ny*nz = {}",
+                        temperature.len(),
+                        nx * ny * nz
+                    );
+                    None
+                } else {
+                    Some(Arc::new(temperature))
+                };
+                let le_scale: Float = api_state.param_set.find_one_float("Lescale", 1.0 as Float);
+                let temperature_cutoff: Float = api_state
+                    .param_set
+                    .find_one_float("temperaturecutoff", 0.0 as Float);
+                let temperature_scale: Float = api_state
+                    .param_set
+                    .find_one_float("temperaturescale", 1.0 as Float);
+                let mut medium: GridDensityMedium = GridDensityMedium::new(
                     &sig_a,
                     &sig_s,
                     g,
@@ -981,11 +1621,85 @@ fn make_medium(api_state: &mut ApiState) {
                     nz,
                     &(medium_2_world * data_2_medium),
                     data,
-                ))));
+                    some_temperature,
+                    le_scale,
+                    temperature_cutoff,
+                    temperature_scale,
+                );
+                if let Some(phase) = make_phase_function(api_state) {
+                    medium.phase = phase;
+                }
+                // optional velocity grids for motion blur
+                let velocity: Vec<Float> = api_state.param_set.find_float("velocity");
+                if !velocity.is_empty() {
+                    if velocity.len() != 3 * (nx * ny * nz) as usize {
+                        error!(
+                            "GridDensityMedium has {} velocity values; expected 3*nx*ny*nz = {}",
+                            velocity.len(),
+                            3 * nx * ny * nz
+                        );
+                    } else {
+                        let velocity_scale: Float = api_state
+                            .param_set
+                            .find_one_float("velocityscale", 1.0 as Float);
+                        // from (p0, p1) space into the unit cube
+                        let extent: [Float; 3] = [p1.x - p0.x, p1.y - p0.y, p1.z - p0.z];
+                        let mut components: Vec<Arc<Vec<Float>>> = Vec::with_capacity(3);
+                        for (axis, item) in extent.iter().enumerate() {
+                            components.push(Arc::new(
+                                velocity
+                                    .iter()
+                                    .skip(axis)
+                                    .step_by(3)
+                                    .map(|v| v * velocity_scale / item)
+                                    .collect(),
+                            ));
+                        }
+                        // the reference time is set by make_integrator()
+                        medium.velocity = Some([
+                            components[0].clone(),
+                            components[1].clone(),
+                            components[2].clone(),
+                        ]);
+                    }
+                }
+                some_medium = Some(Arc::new(Medium::GridDensity(Box::new(medium))));
+            }
+        }
+    } else if medium_type == "aggregate" {
+        // combine previously defined (bounded) media
+        let names: Vec<String> = api_state.param_set.find_string("media");
+        let mut media: Vec<Arc<Medium>> = Vec::with_capacity(names.len());
+        let mut bounds: Vec<Bounds3f> = Vec::with_capacity(names.len());
+        for name in names {
+            match api_state.render_options.named_media.get(name.as_str()) {
+                Some(medium) => {
+                    if let Medium::GridDensity(ref grid) = **medium {
+                        bounds.push(grid.world_bound());
+                        media.push(medium.clone());
+                    } else {
+                        warn!("Medium \"{}\" is unbounded and can't be aggregated.", name);
+                    }
+                }
+                None => {
+                    error!("Named medium \"{}\" undefined.", name);
+                }
             }
         }
+        if media.is_empty() {
+            error!("No \"media\" provided for aggregate medium?");
+            some_medium = None;
+        } else {
+            some_medium = Some(Arc::new(Medium::Aggregate(VolumeAggregate::new(
+                media, bounds,
+            ))));
+        }
     } else {
-        panic!("MakeMedium: unknown name {}", medium_type);
+        api_state.add_error(ParseError::new(
+            &medium_type,
+            format!("Medium \"{}\" unknown", medium_type),
+        ));
+        return;
     }
     if let Some(medium) = some_medium {
         api_state
@@ -1013,33 +1727,33 @@ fn make_texture(api_state: &mut ApiState) {
             .float_textures
             .get(api_state.param_set.name.as_str())
         {
-            println!("Texture \"{}\" being redefined", api_state.param_set.name);
+            warn!("Texture \"{}\" being redefined", api_state.param_set.name);
         }
         // TODO: WARN_IF_ANIMATED_TRANSFORM("Texture");
         // MakeFloatTexture(texname, curTransform[0], tp);
         if api_state.param_set.tex_name == "constant" {
-            let ct = Arc::new(ConstantTexture::<Float>::new(
+            let ct = Arc::new(Texture::Constant(ConstantTexture::<Float>::new(
                 tp.find_float("value", 1.0 as Float),
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ct);
         } else if api_state.param_set.tex_name == "scale" {
-            let ft = Arc::new(ScaleTexture::<Float>::new(
+            let ft = Arc::new(Texture::Scale(ScaleTexture::<Float>::new(
                 tp.get_float_texture("tex1", 1.0 as Float),
                 tp.get_float_texture("tex2", 1.0 as Float),
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "mix" {
-            let mt = Arc::new(MixTexture::<Float>::new(
+            let mt = Arc::new(Texture::Mix(MixTexture::<Float>::new(
                 tp.get_float_texture("tex1", 0.0 as Float),
                 tp.get_float_texture("tex2", 1.0 as Float),
                 tp.get_float_texture("amount", 0.5 as Float),
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), mt);
         } else if api_state.param_set.tex_name == "bilerp" {
-            println!("TODO: CreateBilerpFloatTexture");
+            warn!("TODO: CreateBilerpFloatTexture");
         } else if api_state.param_set.tex_name == "imagemap" {
             // CreateImageFloatTexture
             let map: Option<Box<TextureMapping2D>>;
@@ -1087,7 +1801,11 @@ fn make_texture(api_state: &mut ApiState) {
                     dt: tp.find_float("vdelta", 0.0),
                 })));
             } else {
-                panic!("2D texture mapping \"{}\" unknown", mapping);
+                api_state.add_error(ParseError::new(
+                    &mapping,
+                    format!("2D texture mapping \"{}\" unknown", mapping),
+                ));
+                return;
             }
             // initialize _ImageTexture_ parameters
             let max_aniso: Float = tp.find_float("maxanisotropy", 8.0);
@@ -1106,7 +1824,20 @@ fn make_texture(api_state: &mut ApiState) {
                 let mut path_buf: PathBuf = PathBuf::from("/");
                 path_buf.push(search_directory.as_ref());
                 path_buf.push(filename);
-                filename = String::from(path_buf.to_str().unwrap());
+                match path_buf.to_str() {
+                    Some(path) => filename = String::from(path),
+                    None => {
+                        api_state.add_error(
+                            Error::create(
+                                "Texture",
+                                "imagemap",
+                                format!("\"filename\" {:?} is not valid UTF-8", path_buf),
+                            )
+                            .into(),
+                        );
+                        return;
+                    }
+                }
             }
             // TODO: default depends on:
             // HasExtension(filename,
@@ -1116,7 +1847,7 @@ fn make_texture(api_state: &mut ApiState) {
             let gamma: bool = tp.find_bool("gamma", true);
 
             if let Some(mapping) = map {
-                let ft = Arc::new(ImageTexture::new(
+                let ft = Arc::new(Texture::Image(ImageTexture::new(
                     mapping,
                     filename,
                     do_trilinear,
@@ -1125,14 +1856,14 @@ fn make_texture(api_state: &mut ApiState) {
                     scale,
                     gamma,
                     convert_to_float,
-                ));
+                )));
                 Arc::make_mut(&mut api_state.graphics_state.float_textures)
                     .insert(api_state.param_set.name.clone(), ft);
             }
         } else if api_state.param_set.tex_name == "uv" {
-            println!("TODO: CreateUVFloatTexture");
+            warn!("TODO: CreateUVFloatTexture");
         } else if api_state.param_set.tex_name == "checkerboard" {
-            println!("TODO: CreateCheckerboardFloatTexture");
+            warn!("TODO: CreateCheckerboardFloatTexture");
         } else if api_state.param_set.tex_name == "dots" {
             // CreateDotsFloatTexture
             let map: Option<Box<TextureMapping2D>>;
@@ -1180,14 +1911,18 @@ fn make_texture(api_state: &mut ApiState) {
                     dt: tp.find_float("vdelta", 0.0),
                 })));
             } else {
-                panic!("2D texture mapping \"{}\" unknown", mapping);
+                api_state.add_error(ParseError::new(
+                    &mapping,
+                    format!("2D texture mapping \"{}\" unknown", mapping),
+                ));
+                return;
             }
             if let Some(mapping) = map {
-                let dt = Arc::new(DotsTexture::new(
+                let dt = Arc::new(Texture::Dots(DotsTexture::new(
                     mapping,
                     tp.get_float_texture("inside", 1.0 as Float),
                     tp.get_float_texture("outside", 0.0 as Float),
-                ));
+                )));
                 Arc::make_mut(&mut api_state.graphics_state.float_textures)
                     .insert(api_state.param_set.name.clone(), dt);
             }
@@ -1202,7 +1937,7 @@ fn make_texture(api_state: &mut ApiState) {
             ));
             let octaves: i32 = tp.find_int("octaves", 8_i32);
             let roughness: Float = tp.find_float("roughness", 0.5 as Float);
-            let ft = Arc::new(FBmTexture::new(map, octaves, roughness));
+            let ft = Arc::new(Texture::FBm(FBmTexture::new(map, octaves, roughness)));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "wrinkled" {
@@ -1216,11 +1951,13 @@ fn make_texture(api_state: &mut ApiState) {
             ));
             let octaves: i32 = tp.find_int("octaves", 8_i32);
             let roughness: Float = tp.find_float("roughness", 0.5 as Float);
-            let ft = Arc::new(WrinkledTexture::new(map, octaves, roughness));
+            let ft = Arc::new(Texture::Wrinkled(WrinkledTexture::new(
+                map, octaves, roughness,
+            )));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "marble" {
-            println!("TODO: CreateMarbleFloatTexture");
+            warn!("TODO: CreateMarbleFloatTexture");
         } else if api_state.param_set.tex_name == "windy" {
             // CreateWindyFloatTexture
             let tex_2_world: Transform = Transform {
@@ -1230,13 +1967,13 @@ fn make_texture(api_state: &mut ApiState) {
             let map: Box<TextureMapping3D> = Box::new(TextureMapping3D::Identity(
                 IdentityMapping3D::new(tex_2_world),
             ));
-            let ft = Arc::new(WindyTexture::new(map));
+            let ft = Arc::new(Texture::Windy(WindyTexture::new(map)));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "ptex" {
-            println!("TODO: CreatePtexFloatTexture");
+            warn!("TODO: CreatePtexFloatTexture");
         } else {
-            println!(
+            error!(
                 "Float texture \"{}\" unknown.",
                 api_state.param_set.tex_name
             );
@@ -1248,34 +1985,32 @@ fn make_texture(api_state: &mut ApiState) {
             .spectrum_textures
             .get(api_state.param_set.name.as_str())
         {
-            println!("Texture \"{}\" being redefined", api_state.param_set.name);
+            warn!("Texture \"{}\" being redefined", api_state.param_set.name);
         }
         // TODO: WARN_IF_ANIMATED_TRANSFORM("Texture");
         // MakeSpectrumTexture(texname, curTransform[0], tp);
         if api_state.param_set.tex_name == "constant" {
-            let ct = Arc::new(ConstantTexture::new(
+            let ct = Arc::new(Texture::Constant(ConstantTexture::new(
                 tp.find_spectrum("value", Spectrum::new(1.0)),
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), ct);
         } else if api_state.param_set.tex_name == "scale" {
-            let tex1: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                tp.get_spectrum_texture("tex1", Spectrum::new(1.0));
-            let tex2: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                tp.get_spectrum_texture("tex2", Spectrum::new(0.0));
-            let st = Arc::new(ScaleTexture::<Spectrum>::new(tex1, tex2));
+            let tex1: Arc<Texture<Spectrum>> = tp.get_spectrum_texture("tex1", Spectrum::new(1.0));
+            let tex2: Arc<Texture<Spectrum>> = tp.get_spectrum_texture("tex2", Spectrum::new(0.0));
+            let st = Arc::new(Texture::Scale(ScaleTexture::<Spectrum>::new(tex1, tex2)));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), st);
         } else if api_state.param_set.tex_name == "mix" {
-            let mt = Arc::new(MixTexture::<Spectrum>::new(
+            let mt = Arc::new(Texture::Mix(MixTexture::<Spectrum>::new(
                 tp.get_spectrum_texture("tex1", Spectrum::new(0.0)),
                 tp.get_spectrum_texture("tex2", Spectrum::new(1.0)),
                 tp.get_float_texture("amount", 0.5 as Float),
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), mt);
         } else if api_state.param_set.tex_name == "bilerp" {
-            println!("TODO: CreateBilerpSpectrumTexture");
+            warn!("TODO: CreateBilerpSpectrumTexture");
         } else if api_state.param_set.tex_name == "imagemap" {
             // CreateImageSpectrumTexture
             let map: Option<Box<TextureMapping2D>>;
@@ -1323,7 +2058,11 @@ fn make_texture(api_state: &mut ApiState) {
                     dt: tp.find_float("vdelta", 0.0),
                 })));
             } else {
-                panic!("2D texture mapping \"{}\" unknown", mapping);
+                api_state.add_error(ParseError::new(
+                    &mapping,
+                    format!("2D texture mapping \"{}\" unknown", mapping),
+                ));
+                return;
             }
             // initialize _ImageTexture_ parameters
             let max_aniso: Float = tp.find_float("maxanisotropy", 8.0);
@@ -1342,7 +2081,20 @@ fn make_texture(api_state: &mut ApiState) {
                 let mut path_buf: PathBuf = PathBuf::from("/");
                 path_buf.push(search_directory.as_ref());
                 path_buf.push(filename);
-                filename = String::from(path_buf.to_str().unwrap());
+                match path_buf.to_str() {
+                    Some(path) => filename = String::from(path),
+                    None => {
+                        api_state.add_error(
+                            Error::create(
+                                "Texture",
+                                "imagemap",
+                                format!("\"filename\" {:?} is not valid UTF-8", path_buf),
+                            )
+                            .into(),
+                        );
+                        return;
+                    }
+                }
             }
             // TODO: default depends on:
             // HasExtension(filename,
@@ -1352,7 +2104,7 @@ fn make_texture(api_state: &mut ApiState) {
             let gamma: bool = tp.find_bool("gamma", true);
 
             if let Some(mapping) = map {
-                let st = Arc::new(ImageTexture::new(
+                let st = Arc::new(Texture::Image(ImageTexture::new(
                     mapping,
                     filename,
                     do_trilinear,
@@ -1361,22 +2113,24 @@ fn make_texture(api_state: &mut ApiState) {
                     scale,
                     gamma,
                     convert_to_spectrum,
-                ));
+                )));
                 Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                     .insert(api_state.param_set.name.clone(), st);
             }
         } else if api_state.param_set.tex_name == "uv" {
-            println!("TODO: CreateUVSpectrumTexture");
+            warn!("TODO: CreateUVSpectrumTexture");
         } else if api_state.param_set.tex_name == "checkerboard" {
             // CreateCheckerboardSpectrumTexture
             let dim: i32 = tp.find_int("dimension", 2);
             if dim != 2 && dim != 3 {
-                panic!("{} dimensional checkerboard texture not supported", dim);
+                api_state.add_error(ParseError::new(
+                    "dimension",
+                    format!("{} dimensional checkerboard texture not supported", dim),
+                ));
+                return;
             }
-            let tex1: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                tp.get_spectrum_texture("tex1", Spectrum::new(1.0));
-            let tex2: Arc<dyn Texture<Spectrum> + Send + Sync> =
-                tp.get_spectrum_texture("tex2", Spectrum::new(0.0));
+            let tex1: Arc<Texture<Spectrum>> = tp.get_spectrum_texture("tex1", Spectrum::new(1.0));
+            let tex2: Arc<Texture<Spectrum>> = tp.get_spectrum_texture("tex2", Spectrum::new(0.0));
             if dim == 2 {
                 let map: Option<Box<TextureMapping2D>>;
                 let mapping: String = tp.find_string("mapping", String::from("uv"));
@@ -1423,17 +2177,23 @@ fn make_texture(api_state: &mut ApiState) {
                         dt: tp.find_float("vdelta", 0.0),
                     })));
                 } else {
-                    panic!("2D texture mapping \"{}\" unknown", mapping);
+                    api_state.add_error(ParseError::new(
+                        &mapping,
+                        format!("2D texture mapping \"{}\" unknown", mapping),
+                    ));
+                    return;
                 }
                 // TODO: aamode
                 if let Some(mapping) = map {
-                    let st = Arc::new(Checkerboard2DTexture::new(mapping, tex1, tex2));
+                    let st = Arc::new(Texture::Checkerboard(Checkerboard2DTexture::new(
+                        mapping, tex1, tex2,
+                    )));
                     Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                         .insert(api_state.param_set.name.clone(), st);
                 }
             } else {
                 // dim == 3
-                println!("TODO: TextureMapping3D");
+                warn!("TODO: TextureMapping3D");
             }
         } else if api_state.param_set.tex_name == "dots" {
             // CreateDotsSpectrumTexture
@@ -1482,14 +2242,18 @@ fn make_texture(api_state: &mut ApiState) {
                     dt: tp.find_float("vdelta", 0.0),
                 })));
             } else {
-                panic!("2D texture mapping \"{}\" unknown", mapping);
+                api_state.add_error(ParseError::new(
+                    &mapping,
+                    format!("2D texture mapping \"{}\" unknown", mapping),
+                ));
+                return;
             }
-            let inside: Arc<dyn Texture<Spectrum> + Send + Sync> =
+            let inside: Arc<Texture<Spectrum>> =
                 tp.get_spectrum_texture("inside", Spectrum::new(1.0));
-            let outside: Arc<dyn Texture<Spectrum> + Send + Sync> =
+            let outside: Arc<Texture<Spectrum>> =
                 tp.get_spectrum_texture("outside", Spectrum::new(0.0));
             if let Some(mapping) = map {
-                let dt = Arc::new(DotsTexture::new(mapping, inside, outside));
+                let dt = Arc::new(Texture::Dots(DotsTexture::new(mapping, inside, outside)));
                 Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                     .insert(api_state.param_set.name.clone(), dt);
             }
@@ -1504,7 +2268,7 @@ fn make_texture(api_state: &mut ApiState) {
             ));
             let octaves: i32 = tp.find_int("octaves", 8_i32);
             let roughness: Float = tp.find_float("roughness", 0.5 as Float);
-            let ft = Arc::new(FBmTexture::new(map, octaves, roughness));
+            let ft = Arc::new(Texture::FBm(FBmTexture::new(map, octaves, roughness)));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "wrinkled" {
@@ -1518,7 +2282,9 @@ fn make_texture(api_state: &mut ApiState) {
             ));
             let octaves: i32 = tp.find_int("octaves", 8_i32);
             let roughness: Float = tp.find_float("roughness", 0.5 as Float);
-            let ft = Arc::new(WrinkledTexture::new(map, octaves, roughness));
+            let ft = Arc::new(Texture::Wrinkled(WrinkledTexture::new(
+                map, octaves, roughness,
+            )));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "marble" {
@@ -1533,9 +2299,9 @@ fn make_texture(api_state: &mut ApiState) {
             let roughness: Float = tp.find_float("roughness", 0.5 as Float);
             let scale: Float = tp.find_float("scale", 1.0 as Float);
             let variation: Float = tp.find_float("variation", 0.2 as Float);
-            let mt = Arc::new(MarbleTexture::new(
+            let mt = Arc::new(Texture::Marble(MarbleTexture::new(
                 map, octaves, roughness, scale, variation,
-            ));
+            )));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), mt);
         } else if api_state.param_set.tex_name == "windy" {
@@ -1547,17 +2313,21 @@ fn make_texture(api_state: &mut ApiState) {
             let map: Box<TextureMapping3D> = Box::new(TextureMapping3D::Identity(
                 IdentityMapping3D::new(tex_2_world),
             ));
-            let ft = Arc::new(WindyTexture::new(map));
+            let ft = Arc::new(Texture::Windy(WindyTexture::new(map)));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else {
-            println!(
+            error!(
                 "Spectrum texture \"{}\" unknown.",
                 api_state.param_set.tex_name
             );
         }
     } else {
-        panic!("Texture type \"{}\" unknown.", api_state.param_set.tex_type);
+        let tex_type: String = api_state.param_set.tex_type.clone();
+        api_state.add_error(ParseError::new(
+            &tex_type,
+            format!("Texture type \"{}\" unknown", tex_type),
+        ));
     }
     // MakeFloatTexture(texname, curTransform[0], tp);
     // or
@@ -1568,22 +2338,60 @@ pub fn make_accelerator(
     accelerator_name: &str,
     primitives: &[Arc<Primitive>],
     accelerator_params: &ParamSet,
-) -> Option<Arc<Primitive>> {
-    let mut some_accelerator: Option<Arc<Primitive>> = None;
+) -> Result<Arc<Primitive>, Error> {
     if accelerator_name == "bvh" {
         // CreateBVHAccelerator
-        some_accelerator = Some(Arc::new(BVHAccel::create(
+        Ok(Arc::new(BVHAccel::create(
             primitives.to_owned(),
             accelerator_params,
-        )));
+        )))
     } else if accelerator_name == "kdtree" {
         // CreateKdTreeAccelerator
-        some_accelerator = Some(Arc::new(KdTreeAccel::create(
+        Ok(Arc::new(KdTreeAccel::create(
             primitives.to_owned(),
             accelerator_params,
-        )));
+        )))
+    } else {
+        Err(Error::create(
+            "Accelerator",
+            accelerator_name,
+            String::from("unknown"),
+        ))
+    }
+}
+
+/// A BVH which gets built on a worker thread (see **spawn_bvh_build()**).
+#[cfg(not(target_arch = "wasm32"))]
+pub type BuildHandle = JoinHandle<Arc<Primitive>>;
+/// There are no threads in WebAssembly, the BVH is built right away.
+#[cfg(target_arch = "wasm32")]
+pub type BuildHandle = Arc<Primitive>;
+
+/// Builds a BVH over *primitives* on a worker thread, so parsing can
+/// continue meanwhile.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_bvh_build(primitives: Vec<Arc<Primitive>>, accelerator_params: &ParamSet) -> BuildHandle {
+    let accelerator_params: ParamSet = accelerator_params.clone();
+    thread::spawn(move || Arc::new(BVHAccel::create(primitives, &accelerator_params)))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_bvh_build(primitives: Vec<Arc<Primitive>>, accelerator_params: &ParamSet) -> BuildHandle {
+    Arc::new(BVHAccel::create(primitives, accelerator_params))
+}
+
+/// Waits for a BVH from **spawn_bvh_build()**.
+#[cfg(not(target_arch = "wasm32"))]
+fn join_build(handle: BuildHandle) -> Arc<Primitive> {
+    match handle.join() {
+        Ok(primitive) => primitive,
+        Err(payload) => std::panic::resume_unwind(payload),
     }
-    some_accelerator
+}
+
+#[cfg(target_arch = "wasm32")]
+fn join_build(handle: BuildHandle) -> Arc<Primitive> {
+    handle
 }
 
 pub fn make_camera(
@@ -1591,132 +2399,157 @@ pub fn make_camera(
     camera_params: &ParamSet,
     animated_cam_to_world: AnimatedTransform,
     film: Arc<Film>,
-) -> Option<Arc<Camera>> {
-    let mut some_camera: Option<Arc<Camera>> = None;
-    let medium_interface: MediumInterface = MediumInterface::default();
+    medium_interface: &MediumInterface,
+) -> Result<Arc<Camera>, Error> {
     if camera_name == "perspective" {
-        let camera: Arc<Camera> = PerspectiveCamera::create(
+        PerspectiveCamera::create(
             &camera_params,
             animated_cam_to_world,
             film,
-            medium_interface.outside,
-        );
-        some_camera = Some(camera);
+            medium_interface.outside.clone(),
+        )
     } else if camera_name == "orthographic" {
-        let camera: Arc<Camera> = OrthographicCamera::create(
+        OrthographicCamera::create(
             &camera_params,
             animated_cam_to_world,
             film,
-            medium_interface.outside,
-        );
-        some_camera = Some(camera);
+            medium_interface.outside.clone(),
+        )
     } else if camera_name == "realistic" {
         // if let Some(ref search_directory) = api_state.search_directory {
-        //     let camera: Arc<Camera> = RealisticCamera::create(
+        //     RealisticCamera::create(
         //         &camera_params,
         //         animated_cam_to_world,
         //         film,
         //         medium_interface.outside,
         //         // additional parameters:
         //         Some(search_directory),
-        //     );
-        //     some_camera = Some(camera);
+        //     )
         // } else {
-        let camera: Arc<Camera> = RealisticCamera::create(
+        RealisticCamera::create(
             &camera_params,
             animated_cam_to_world,
             film,
-            medium_interface.outside,
+            medium_interface.outside.clone(),
             // additional parameters:
             None,
-        );
-        some_camera = Some(camera);
+        )
     // }
     } else if camera_name == "environment" {
-        let camera: Arc<Camera> = EnvironmentCamera::create(
+        EnvironmentCamera::create(
             &camera_params,
             animated_cam_to_world,
             film,
-            medium_interface.outside,
-        );
-        some_camera = Some(camera);
+            medium_interface.outside.clone(),
+        )
     } else {
-        println!("Camera \"{}\" unknown.", camera_name);
+        Err(Error::create(
+            "Camera",
+            camera_name,
+            String::from("unknown"),
+        ))
     }
-    some_camera
 }
 
-pub fn make_sampler(name: &str, param_set: &ParamSet, film: Arc<Film>) -> Option<Box<Sampler>> {
-    let mut some_sampler: Option<Box<Sampler>> = None;
+pub fn make_sampler(
+    name: &str,
+    param_set: &ParamSet,
+    film: Arc<Film>,
+) -> Result<Box<Sampler>, Error> {
+    // the global samplers spread their samples over the full frame,
+    // not just the crop window, so a cropped re-render of a region
+    // gets the same samples as the full frame there
+    let frame: Bounds2i = Bounds2i::new(Point2i::default(), film.full_resolution);
     if name == "lowdiscrepancy" || name == "02sequence" {
         // CreateZeroTwoSequenceSampler
-        let sampler = ZeroTwoSequenceSampler::create(param_set);
-        some_sampler = Some(sampler);
+        Ok(ZeroTwoSequenceSampler::create(param_set))
     } else if name == "maxmindist" {
         // CreateMaxMinDistSampler
-        let sampler = MaxMinDistSampler::create(param_set);
-        some_sampler = Some(sampler);
+        MaxMinDistSampler::create(param_set)
+    } else if name == "pmj02bn" {
+        // CreatePMJ02BNSampler
+        PMJ02BNSampler::create(param_set)
     } else if name == "halton" {
         // CreateHaltonSampler
-        let sampler = HaltonSampler::create(param_set, &film.get_sample_bounds());
-        some_sampler = Some(sampler);
+        Ok(HaltonSampler::create(param_set, &frame))
     } else if name == "sobol" {
         // CreateSobolSampler
-        let sampler = SobolSampler::create(param_set, &film.get_sample_bounds());
-        some_sampler = Some(sampler);
+        Ok(SobolSampler::create(param_set, &frame))
     } else if name == "random" {
         // CreateRandomSampler
-        let sampler = RandomSampler::create(param_set);
-        some_sampler = Some(sampler);
+        Ok(RandomSampler::create(param_set))
     } else if name == "stratified" {
         // CreateStratifiedSampler
-        let sampler = StratifiedSampler::create(param_set);
-        some_sampler = Some(sampler);
+        Ok(StratifiedSampler::create(param_set))
     } else {
-        println!("Sampler \"{}\" unknown.", name);
+        Err(Error::create("Sampler", name, String::from("unknown")))
     }
-    some_sampler
 }
 
-pub fn make_filter(name: &str, param_set: &ParamSet) -> Option<Box<Filter>> {
-    let mut some_filter: Option<Box<Filter>> = None;
+pub fn make_filter(name: &str, param_set: &ParamSet) -> Result<Box<Filter>, Error> {
     if name == "box" {
-        some_filter = Some(BoxFilter::create(param_set));
+        Ok(BoxFilter::create(param_set))
     } else if name == "gaussian" {
-        some_filter = Some(GaussianFilter::create(param_set));
+        Ok(GaussianFilter::create(param_set))
     } else if name == "mitchell" {
-        some_filter = Some(MitchellNetravali::create(param_set));
+        Ok(MitchellNetravali::create(param_set))
     } else if name == "sinc" {
-        some_filter = Some(LanczosSincFilter::create(param_set));
+        Ok(LanczosSincFilter::create(param_set))
     } else if name == "triangle" {
-        some_filter = Some(TriangleFilter::create(param_set));
+        Ok(TriangleFilter::create(param_set))
     } else {
-        println!("Filter \"{}\" unknown.", name);
+        Err(Error::create("Filter", name, String::from("unknown")))
     }
-    some_filter
 }
 
-pub fn make_film(name: &str, param_set: &ParamSet, filter: Box<Filter>) -> Option<Arc<Film>> {
+pub fn make_film(
+    name: &str,
+    param_set: &ParamSet,
+    filter: Box<Filter>,
+) -> Result<Arc<Film>, Error> {
     if name == "image" {
-        Some(Film::create(param_set, filter))
+        Ok(Film::create(param_set, filter))
     } else {
-        println!("Film \"{}\" unknown.", name);
-        None
+        Err(Error::create("Film", name, String::from("unknown")))
     }
 }
 
+/// A triangle mesh which isn't split into triangle shapes (see
+/// **lowmemory**), with its material.
+type MeshAndMaterial = (Arc<TriangleMesh>, Option<Arc<Material>>);
+/// The shapes of a **Shape** statement with their materials, and the
+/// whole meshes (see **MeshAndMaterial**).
+type ShapesAndMaterials = (
+    Vec<Arc<Shape>>,
+    Vec<Option<Arc<Material>>>,
+    Vec<MeshAndMaterial>,
+);
+
+/// Triangle meshes count their triangles and vertices with **u32**.
+fn mesh_size(shape: &str, param: &str, n: usize) -> Result<u32, ParseError> {
+    n.try_into().map_err(|_| {
+        ParseError::from(Error::create(
+            "Shape",
+            shape,
+            format!("\"{}\" gives too many elements ({})", param, n),
+        ))
+    })
+}
+
 fn get_shapes_and_materials(
     api_state: &ApiState,
     bsdf_state: &mut BsdfState,
-) -> (Vec<Arc<Shape>>, Vec<Option<Arc<Material>>>) {
+) -> Result<ShapesAndMaterials, ParseError> {
     if shape_may_set_material_parameters(&api_state.param_set) {
         // TODO: see C++ code and shape_may_set_material_parameters() call
 
-        // println!("Shape \"{}\"", api_state.param_set.name);
-        // print_params(&api_state.param_set);
+        // log_statement("Shape", &api_state.param_set);
     }
     let mut shapes: Vec<Arc<Shape>> = Vec::new();
     let mut materials: Vec<Option<Arc<Material>>> = Vec::new();
+    // in low-memory mode meshes stay whole (unless they emit light)
+    let mut meshes: Vec<MeshAndMaterial> = Vec::new();
+    let keep_meshes: bool = low_memory().enabled && api_state.graphics_state.area_light.is_empty();
     // pbrtShape (api.cpp:1153)
     // TODO: if (!curTransform.IsAnimated()) { ... }
     // TODO: transformCache.Lookup(curTransform[0], &ObjToWorld, &WorldToObj);
@@ -1726,7 +2559,7 @@ fn get_shapes_and_materials(
     };
     let world_to_obj = if api_state.cur_transform.is_animated() {
         if api_state.graphics_state.area_light != "" {
-            println!("WARNING: Ignoring currently set area light when creating animated shape",);
+            warn!("Ignoring currently set area light when creating animated shape",);
         }
         // set both transforms to identity
         obj_to_world = Transform::default();
@@ -1753,7 +2586,7 @@ fn get_shapes_and_materials(
             z_max,
             phi_max,
         )));
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
         shapes.push(sphere);
         materials.push(mtl);
     } else if api_state.param_set.name == "cylinder" {
@@ -1770,7 +2603,7 @@ fn get_shapes_and_materials(
             z_max,
             phi_max,
         )));
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
         shapes.push(cylinder);
         materials.push(mtl);
     } else if api_state.param_set.name == "disk" {
@@ -1787,23 +2620,23 @@ fn get_shapes_and_materials(
             inner_radius,
             phi_max,
         )));
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
         shapes.push(disk);
         materials.push(mtl);
     } else if api_state.param_set.name == "cone" {
-        println!("TODO: CreateConeShape");
+        warn!("TODO: CreateConeShape");
     } else if api_state.param_set.name == "paraboloid" {
-        println!("TODO: CreateParaboloidShape");
+        warn!("TODO: CreateParaboloidShape");
     } else if api_state.param_set.name == "hyperboloid" {
-        println!("TODO: CreateHyperboloidShape");
+        warn!("TODO: CreateHyperboloidShape");
     } else if api_state.param_set.name == "curve" {
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
         let curve_shapes: Vec<Arc<Shape>> = create_curve_shape(
             &obj_to_world,
             &world_to_obj,
             false, // reverse_orientation
             &api_state.param_set,
-        );
+        )?;
         for shape in curve_shapes {
             shapes.push(shape.clone());
             materials.push(mtl.clone());
@@ -1836,14 +2669,42 @@ fn get_shapes_and_materials(
         }
         if !uvs.is_empty() {
             // TODO: if (nuvi < npi) {...} else if (nuvi > npi) ...
-            assert!(uvs.len() == p.len());
+            if uvs.len() != p.len() {
+                return Err(ParseError::new(
+                    "uv",
+                    format!(
+                        "{} \"uv\" values given for {} vertices of triangle mesh",
+                        uvs.len(),
+                        p.len()
+                    ),
+                ));
+            }
+        }
+        if vi.is_empty() {
+            return Err(ParseError::new(
+                "indices",
+                String::from("Vertex indices \"indices\" not provided for triangle mesh shape"),
+            ));
+        }
+        if p.is_empty() {
+            return Err(ParseError::new(
+                "P",
+                String::from("Vertex positions \"P\" not provided for triangle mesh shape"),
+            ));
         }
-        assert!(!vi.is_empty());
-        assert!(!p.is_empty());
         let s = api_state.param_set.find_vector3f("S");
         let mut s_ws: Vec<Vector3f> = Vec::new();
         if !s.is_empty() {
-            assert!(s.len() == p.len());
+            if s.len() != p.len() {
+                return Err(ParseError::new(
+                    "S",
+                    format!(
+                        "{} \"S\" values given for {} vertices of triangle mesh",
+                        s.len(),
+                        p.len()
+                    ),
+                ));
+            }
             // transform tangents to world space
             let n_tangents: usize = s.len();
             for item in s.iter().take(n_tangents) {
@@ -1853,7 +2714,16 @@ fn get_shapes_and_materials(
         let n = api_state.param_set.find_normal3f("N");
         let mut n_ws: Vec<Normal3f> = Vec::new();
         if !n.is_empty() {
-            assert!(n.len() == p.len());
+            if n.len() != p.len() {
+                return Err(ParseError::new(
+                    "N",
+                    format!(
+                        "{} \"N\" values given for {} vertices of triangle mesh",
+                        n.len(),
+                        p.len()
+                    ),
+                ));
+            }
             // transform normals to world space
             let n_normals: usize = n.len();
             for item in n.iter().take(n_normals) {
@@ -1862,11 +2732,14 @@ fn get_shapes_and_materials(
         }
         for item in &vi {
             if *item as usize >= p.len() {
-                panic!(
-                    "trianglemesh has out of-bounds vertex index {} ({} \"P\" values were given)",
-                    item,
-                    p.len()
-                );
+                return Err(ParseError::new(
+                    "indices",
+                    format!(
+                        "trianglemesh has out of-bounds vertex index {} ({} \"P\" values were given)",
+                        item,
+                        p.len()
+                    ),
+                ));
             }
         }
         // TODO: alpha
@@ -1886,9 +2759,9 @@ fn get_shapes_and_materials(
             obj_to_world,
             world_to_obj,
             api_state.graphics_state.reverse_orientation,
-            (vi.len() / 3).try_into().unwrap(), // n_triangles
+            mesh_size("trianglemesh", "indices", vi.len() / 3)?, // n_triangles
             vertex_indices,
-            n_vertices.try_into().unwrap(),
+            mesh_size("trianglemesh", "P", n_vertices)?,
             p_ws, // in world space
             s_ws, // in world space
             n_ws, // in world space
@@ -1896,22 +2769,26 @@ fn get_shapes_and_materials(
             None,
             None,
         ));
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
-        for id in 0..mesh.n_triangles {
-            let triangle = Arc::new(Shape::Trngl(Triangle::new(
-                mesh.object_to_world,
-                mesh.world_to_object,
-                mesh.reverse_orientation,
-                mesh.clone(),
-                id.try_into().unwrap(),
-            )));
-            shapes.push(triangle.clone());
-            materials.push(mtl.clone());
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
+        if keep_meshes {
+            meshes.push((mesh, mtl));
+        } else {
+            for id in 0..mesh.n_triangles {
+                let triangle = Arc::new(Shape::Trngl(Triangle::new(
+                    mesh.object_to_world,
+                    mesh.world_to_object,
+                    mesh.reverse_orientation,
+                    mesh.clone(),
+                    id,
+                )));
+                shapes.push(triangle.clone());
+                materials.push(mtl.clone());
+            }
         }
     } else if api_state.param_set.name == "plymesh" {
         if let Some(ref search_directory) = api_state.search_directory {
-            let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
-            let ply_shapes: Vec<Arc<Shape>> = create_ply_mesh(
+            let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
+            let mesh: Arc<TriangleMesh> = read_ply_mesh(
                 &obj_to_world,
                 &world_to_obj,
                 false, // reverse_orientation
@@ -1919,16 +2796,23 @@ fn get_shapes_and_materials(
                 api_state.graphics_state.float_textures.clone(),
                 // additional parameters:
                 Some(search_directory),
-            );
-            for shape in ply_shapes {
-                shapes.push(shape.clone());
-                materials.push(mtl.clone());
+            )?;
+            if keep_meshes {
+                meshes.push((mesh, mtl));
+            } else {
+                for shape in ply_mesh_shapes(&mesh) {
+                    shapes.push(shape.clone());
+                    materials.push(mtl.clone());
+                }
             }
         } else {
-            panic!("No search directory for plymesh.");
+            return Err(ParseError::new(
+                "plymesh",
+                String::from("No search directory for plymesh"),
+            ));
         }
     } else if api_state.param_set.name == "heightfield" {
-        println!("TODO: CreateHeightfield");
+        warn!("TODO: CreateHeightfield");
     } else if api_state.param_set.name == "loopsubdiv" {
         // CreateLoopSubdiv
         let n_levels: i32 = api_state
@@ -1938,10 +2822,16 @@ fn get_shapes_and_materials(
         let vertex_indices: Vec<i32> = api_state.param_set.find_int("indices");
         let p = api_state.param_set.find_point3f("P");
         if vertex_indices.is_empty() {
-            panic!("Vertex indices \"indices\" not provided for LoopSubdiv shape.");
+            return Err(ParseError::new(
+                "indices",
+                String::from("Vertex indices \"indices\" not provided for LoopSubdiv shape"),
+            ));
         }
         if p.is_empty() {
-            panic!("Vertex positions \"P\" not provided for LoopSubdiv shape.");
+            return Err(ParseError::new(
+                "P",
+                String::from("Vertex positions \"P\" not provided for LoopSubdiv shape"),
+            ));
         }
         // don't actually use this for now...
         let _scheme: String = api_state
@@ -1955,35 +2845,51 @@ fn get_shapes_and_materials(
             &vertex_indices,
             &p,
         );
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
-        for id in 0..mesh.n_triangles {
-            let triangle = Arc::new(Shape::Trngl(Triangle::new(
-                mesh.object_to_world,
-                mesh.world_to_object,
-                mesh.reverse_orientation,
-                mesh.clone(),
-                id.try_into().unwrap(),
-            )));
-            shapes.push(triangle.clone());
-            materials.push(mtl.clone());
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
+        if keep_meshes {
+            meshes.push((mesh, mtl));
+        } else {
+            for id in 0..mesh.n_triangles {
+                let triangle = Arc::new(Shape::Trngl(Triangle::new(
+                    mesh.object_to_world,
+                    mesh.world_to_object,
+                    mesh.reverse_orientation,
+                    mesh.clone(),
+                    id,
+                )));
+                shapes.push(triangle.clone());
+                materials.push(mtl.clone());
+            }
         }
     } else if api_state.param_set.name == "nurbs" {
         // CreateNURBS
         let nu: i32 = api_state.param_set.find_one_int("nu", -1);
         if nu == -1_i32 {
-            panic!("Must provide number of control points \"nu\" with NURBS shape.");
+            return Err(ParseError::new(
+                "nu",
+                String::from("Must provide number of control points \"nu\" with NURBS shape"),
+            ));
         }
         let uorder: i32 = api_state.param_set.find_one_int("uorder", -1);
         if uorder == -1_i32 {
-            panic!("Must provide u order \"uorder\" with NURBS shape.");
+            return Err(ParseError::new(
+                "uorder",
+                String::from("Must provide u order \"uorder\" with NURBS shape"),
+            ));
         }
         let uknots: Vec<Float> = api_state.param_set.find_float("uknots");
         if uknots.is_empty() {
-            panic!("Must provide u knot vector \"uknots\" with NURBS shape.");
+            return Err(ParseError::new(
+                "uknots",
+                String::from("Must provide u knot vector \"uknots\" with NURBS shape"),
+            ));
         }
         if uknots.len() != (nu + uorder) as usize {
-            panic!("Number of knots in u knot vector {} doesn't match sum of number of u control points {} and u order {}.",
-                   uknots.len(), nu, uorder);
+            return Err(ParseError::new(
+                "uknots",
+                format!("Number of knots in u knot vector {} doesn't match sum of number of u control points {} and u order {}",
+                        uknots.len(), nu, uorder),
+            ));
         }
         let u0: Float = api_state
             .param_set
@@ -1993,19 +2899,31 @@ fn get_shapes_and_materials(
             .find_one_float("u1", uknots[nu as usize]);
         let nv: i32 = api_state.param_set.find_one_int("nv", -1);
         if nv == -1_i32 {
-            panic!("Must provide number of control points \"nv\" with NURBS shape.");
+            return Err(ParseError::new(
+                "nv",
+                String::from("Must provide number of control points \"nv\" with NURBS shape"),
+            ));
         }
         let vorder: i32 = api_state.param_set.find_one_int("vorder", -1);
         if vorder == -1_i32 {
-            panic!("Must provide u order \"vorder\" with NURBS shape.");
+            return Err(ParseError::new(
+                "vorder",
+                String::from("Must provide u order \"vorder\" with NURBS shape"),
+            ));
         }
         let vknots: Vec<Float> = api_state.param_set.find_float("vknots");
         if vknots.is_empty() {
-            panic!("Must provide u knot vector \"vknots\" with NURBS shape.");
+            return Err(ParseError::new(
+                "vknots",
+                String::from("Must provide u knot vector \"vknots\" with NURBS shape"),
+            ));
         }
         if vknots.len() != (nv + vorder) as usize {
-            panic!("Number of knots in v knot vector {} doesn't match sum of number of v control points {} and v order {}.",
-                   vknots.len(), nv, vorder);
+            return Err(ParseError::new(
+                "vknots",
+                format!("Number of knots in v knot vector {} doesn't match sum of number of v control points {} and v order {}",
+                        vknots.len(), nv, vorder),
+            ));
         }
         let v0: Float = api_state
             .param_set
@@ -2020,22 +2938,33 @@ fn get_shapes_and_materials(
         if p.is_empty() {
             pw = api_state.param_set.find_float("Pw");
             if pw.is_empty() {
-                panic!("Must provide control points via \"P\" or \"Pw\" parameter to NURBS shape.");
+                return Err(ParseError::new(
+                    "P",
+                    String::from(
+                        "Must provide control points via \"P\" or \"Pw\" parameter to NURBS shape",
+                    ),
+                ));
             }
             if pw.len() % 4 != 0 {
-                panic!("Number of \"Pw\" control points provided to NURBS shape must be multiple of four");
+                return Err(ParseError::new(
+                    "Pw",
+                    String::from("Number of \"Pw\" control points provided to NURBS shape must be multiple of four"),
+                ));
             }
             npts = pw.len() / 4_usize;
             is_homogeneous = true;
         }
         if npts != (nu * nv) as usize {
-            panic!(
-                "NURBS shape was expecting {}x{}={} control points, was given {}",
-                nu,
-                nv,
-                nu * nv,
-                npts
-            );
+            return Err(ParseError::new(
+                "P",
+                format!(
+                    "NURBS shape was expecting {}x{}={} control points, was given {}",
+                    nu,
+                    nv,
+                    nu * nv,
+                    npts
+                ),
+            ));
         }
         // compute NURBS dicing rates
         let diceu: usize = 30;
@@ -2106,14 +3035,15 @@ fn get_shapes_and_materials(
         let n_tris: usize = 2 * (diceu - 1) * (dicev - 1);
         let mut vertices: Vec<u32> = Vec::with_capacity(3 * n_tris);
         // compute the vertex offset numbers for the triangles
-        for v in 0_usize..(dicev - 1) as usize {
-            for u in 0_usize..(diceu - 1) as usize {
-                vertices.push((v * diceu + u).try_into().unwrap());
-                vertices.push((v * diceu + u + 1).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
-                vertices.push((v * diceu + u).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u).try_into().unwrap());
+        let row: u32 = mesh_size("nurbs", "P", diceu)?;
+        for v in 0_u32..mesh_size("nurbs", "P", dicev - 1)? {
+            for u in 0_u32..(row - 1) {
+                vertices.push(v * row + u);
+                vertices.push(v * row + u + 1);
+                vertices.push((v + 1) * row + u + 1);
+                vertices.push(v * row + u);
+                vertices.push((v + 1) * row + u + 1);
+                vertices.push((v + 1) * row + u);
             }
         }
         // transform mesh vertices to world space
@@ -2132,9 +3062,9 @@ fn get_shapes_and_materials(
             obj_to_world,
             world_to_obj,
             api_state.graphics_state.reverse_orientation,
-            n_tris.try_into().unwrap(), // n_triangles
+            mesh_size("nurbs", "P", n_tris)?, // n_triangles
             vertices,
-            n_vertices.try_into().unwrap(),
+            mesh_size("nurbs", "P", n_vertices)?,
             p_ws,       // in world space
             Vec::new(), // in world space
             n_ws,       // in world space
@@ -2142,139 +3072,237 @@ fn get_shapes_and_materials(
             None,
             None,
         ));
-        let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
-        for id in 0..mesh.n_triangles {
-            let triangle = Arc::new(Shape::Trngl(Triangle::new(
-                mesh.object_to_world,
-                mesh.world_to_object,
-                mesh.reverse_orientation,
-                mesh.clone(),
-                id.try_into().unwrap(),
-            )));
-            shapes.push(triangle.clone());
-            materials.push(mtl.clone());
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state)?;
+        if keep_meshes {
+            meshes.push((mesh, mtl));
+        } else {
+            for id in 0..mesh.n_triangles {
+                let triangle = Arc::new(Shape::Trngl(Triangle::new(
+                    mesh.object_to_world,
+                    mesh.world_to_object,
+                    mesh.reverse_orientation,
+                    mesh.clone(),
+                    id,
+                )));
+                shapes.push(triangle.clone());
+                materials.push(mtl.clone());
+            }
         }
     } else {
-        panic!("Shape \"{}\" unknown.", api_state.param_set.name);
+        return Err(ParseError::new(
+            &api_state.param_set.name,
+            format!("Shape \"{}\" unknown", api_state.param_set.name),
+        ));
     }
-    (shapes, materials)
+    Ok((shapes, materials, meshes))
 }
 
-fn print_params(params: &ParamSet) {
-    for p in &params.strings {
-        if p.n_values == 1_usize {
-            println!("  \"string {}\" [\"{}\"]", p.name, p.values[0]);
-        }
+/// Logs a statement of the scene file (with its parameters) on one
+/// line, e.g. *Film "image" "integer xresolution" [ 32 ] ...*.
+fn log_statement(key_word: &str, params: &ParamSet) {
+    info!("{}", statement_to_string(key_word, &params.name, params));
+}
+
+pub fn pbrt_init(number_of_threads: u8) -> (ApiState, BsdfState) {
+    let mut api_state: ApiState = ApiState::default();
+    let bsdf_state: BsdfState = BsdfState::default();
+    api_state.number_of_threads = number_of_threads;
+    (api_state, bsdf_state)
+}
+
+/// The overrides are applied right before rendering starts, i.e. they
+/// win over the settings of the scene file.
+pub fn pbrt_set_overrides(api_state: &mut ApiState, overrides: RenderOverrides) {
+    api_state.overrides = overrides;
+}
+
+/// Renders with the same *light_distributions* (e.g. the frames of an
+/// animation) reuse the light distributions of previous renders with
+/// the same lights (see **create_light_sample_distribution()**).
+/// Without it, only the scenes of this **ApiState** share them.
+pub fn pbrt_set_light_distributions(
+    api_state: &mut ApiState,
+    light_distributions: LightDistributionCache,
+) {
+    api_state.light_distributions = light_distributions;
+}
+
+/// Instead of rendering the scene **WorldEnd** prints statistics
+/// about it (see **scenestats::SceneStats**).
+pub fn pbrt_set_stats_only(api_state: &mut ApiState, stats_only: bool) {
+    api_state.stats_only = stats_only;
+}
+
+/// **WorldEnd** renders the scene together with other machines (see
+/// **distributed**) instead of on its own.
+pub fn pbrt_set_distributed(api_state: &mut ApiState, distributed: Option<DistributedRole>) {
+    api_state.distributed = distributed;
+}
+
+/// Records an error if the scene can't be rendered because of
+/// unmatched **AttributeBegin** or **TransformBegin** statements.
+fn check_world_end(api_state: &mut ApiState) {
+    if !api_state.pushed_graphics_states.is_empty() {
+        api_state.add_error(ParseError::new(
+            "AttributeBegin",
+            format!(
+                "Missing end to {} AttributeBegin(s)",
+                api_state.pushed_graphics_states.len()
+            ),
+        ));
+    } else if !api_state.pushed_transforms.is_empty() {
+        api_state.add_error(ParseError::new(
+            "TransformBegin",
+            format!(
+                "Missing end to {} TransformBegin(s)",
+                api_state.pushed_transforms.len()
+            ),
+        ));
     }
-    for p in &params.bools {
-        if p.n_values == 1_usize {
-            println!("  \"bool {}\" [{}]", p.name, p.values[0]);
-        } else {
-            print!("  \"bool {}\" [ ", p.name);
-            for i in 0..p.n_values {
-                print!("{} ", p.values[i]);
+}
+
+/// Creates the integrator (with its camera and sampler) and the
+/// scene, the errors get recorded in **ApiState::errors**.
+fn make_integrator_and_scene(api_state: &mut ApiState) -> Option<(Box<Integrator>, Scene)> {
+    let render_options: &mut RenderOptions = &mut api_state.render_options;
+    let result: Result<(Box<Integrator>, Scene), Error> = render_options
+        .make_integrator()
+        .and_then(|integrator| Ok((integrator, render_options.make_scene()?)));
+    match result {
+        Ok((integrator, mut scene)) => {
+            scene.light_distributions = api_state.light_distributions.clone();
+            if scene.has_holdouts || scene.has_shadow_catchers {
+                if !matches!(*integrator, Integrator::Sampler(_)) {
+                    warn!(
+                        "Holdouts and shadow catchers are rendered like other objects by {:?}.",
+                        api_state.render_options.integrator_name
+                    );
+                }
+                // keep the alpha channel, even if nothing is transparent
+                integrator
+                    .get_camera()
+                    .get_film()
+                    .write_alpha
+                    .store(true, Ordering::Relaxed);
             }
-            println!("]");
+            Some((integrator, scene))
         }
-    }
-    for p in &params.ints {
-        if p.n_values == 1_usize {
-            println!("  \"integer {}\" [{}]", p.name, p.values[0]);
-        } else {
-            print!("  \"integer {}\" [ ", p.name);
-            for i in 0..p.n_values {
-                print!("{} ", p.values[i]);
-            }
-            println!("]");
+        Err(err) => {
+            api_state.add_error(err.into());
+            None
         }
     }
-    for p in &params.floats {
-        if p.n_values == 1_usize {
-            println!("  \"float {}\" [{}]", p.name, p.values[0]);
-        } else {
-            print!("  \"float {}\" [ ", p.name);
-            for i in 0..p.n_values {
-                print!("{} ", p.values[i]);
-            }
-            println!("]");
-        }
+}
+
+/// Reports the memory used by the geometry of the scene against the
+/// budget, if there is one (see **lowmemory**).
+fn check_memory_budget(scene: &Scene) {
+    if low_memory().budget.is_some() {
+        let mut stats: SceneStats = SceneStats::new();
+        stats.collect(scene);
+        report_memory_budget(&stats);
     }
-    for p in &params.point3fs {
-        if p.n_values == 1_usize {
-            println!(
-                "  \"point {}\" [{} {} {}]",
-                p.name, p.values[0].x, p.values[0].y, p.values[0].z
-            );
-        } else {
-            println!("  \"point {}\" [", p.name);
-            for i in 0..p.n_values {
-                println!("    {} {} {} ", p.values[i].x, p.values[i].y, p.values[i].z);
-            }
-            println!("  ]");
-        }
+}
+
+/// Hands the render settings to the film (right before rendering
+/// starts, so the render time can be measured).
+fn set_image_metadata(api_state: &ApiState, integrator: &Integrator) {
+    let metadata: Vec<(String, String)> = api_state
+        .render_options
+        .image_metadata(api_state.scene_filename.as_deref());
+    integrator.get_camera().get_film().set_metadata(metadata);
+}
+
+pub fn pbrt_cleanup(api_state: &mut ApiState) {
+    // println!("WorldEnd");
+    api_state.inside_world_block = false;
+    check_world_end(api_state);
+    if !api_state.errors.is_empty() {
+        error!(
+            "Not rendering the scene because of {} error(s).",
+            api_state.errors.len()
+        );
+        return;
     }
-    for p in &params.vector3fs {
-        if p.n_values == 1_usize {
-            println!(
-                "  \"vector {}\" [{} {} {}]",
-                p.name, p.values[0].x, p.values[0].y, p.values[0].z
-            );
-        }
+    api_state
+        .render_options
+        .apply_overrides(&api_state.overrides);
+    api_state.render_options.check_disk_backed_film(false);
+    api_state.render_options.check_film_outputs();
+    if api_state.stats_only {
+        // same setup as for rendering, but report instead
+        let start: Instant = Instant::now();
+        let (_integrator, scene) = match make_integrator_and_scene(api_state) {
+            Some(integrator_and_scene) => integrator_and_scene,
+            None => return,
+        };
+        api_state.scene_stats.build_time = start.elapsed();
+        api_state.scene_stats.collect(&scene);
+        api_state.scene_stats.print();
+        report_memory_budget(&api_state.scene_stats);
+        return;
     }
-    for p in &params.normals {
-        if p.n_values == 1_usize {
-            println!(
-                "  \"normal {}\" [{} {} {}]",
-                p.name, p.values[0].x, p.values[0].y, p.values[0].z
-            );
+    // MakeIntegrator
+    if let Some((mut integrator, scene)) = make_integrator_and_scene(api_state) {
+        check_memory_budget(&scene);
+        set_image_metadata(api_state, &integrator);
+        let num_threads: u8 = api_state.number_of_threads;
+        if let Some(ref role) = api_state.distributed {
+            render_distributed(&mut integrator, &scene, num_threads, role);
         } else {
-            println!("  \"normal {}\" [", p.name);
-            for i in 0..p.n_values {
-                println!("    {} {} {} ", p.values[i].x, p.values[i].y, p.values[i].z);
-            }
-            println!("  ]");
+            integrator.render(&scene, num_threads);
         }
-    }
-    for p in &params.spectra {
-        if p.n_values == 1_usize {
-            println!(
-                "  \"rgb {}\" [{} {} {}]",
-                p.name, p.values[0].c[0], p.values[0].c[1], p.values[0].c[2]
-            );
+        let report: String = stats_report();
+        if !report.is_empty() {
+            info!("{}", report.trim_end());
         }
+        clear_stats();
     }
-    for p in &params.textures {
-        if p.n_values == 1_usize {
-            println!("  \"texture {}\" \"{}\"", p.name, p.values[0]);
-        }
+}
+
+/// Creates the integrator and the scene (instead of **WorldEnd**) to
+/// render into memory, no image files get written. Returns **None**
+/// if there were errors (see **ApiState::errors**).
+pub fn pbrt_make_render(api_state: &mut ApiState) -> Option<(Box<Integrator>, Scene)> {
+    api_state.inside_world_block = false;
+    check_world_end(api_state);
+    if !api_state.errors.is_empty() {
+        return None;
     }
+    api_state
+        .render_options
+        .apply_overrides(&api_state.overrides);
+    api_state.render_options.check_disk_backed_film(true);
+    let (integrator, scene) = make_integrator_and_scene(api_state)?;
+    check_memory_budget(&scene);
+    let film: Arc<Film> = integrator.get_camera().get_film();
+    film.write_files.store(false, Ordering::Relaxed);
+    set_image_metadata(api_state, &integrator);
+    Some((integrator, scene))
 }
 
-pub fn pbrt_init(number_of_threads: u8) -> (ApiState, BsdfState) {
-    let mut api_state: ApiState = ApiState::default();
-    let bsdf_state: BsdfState = BsdfState::default();
-    api_state.number_of_threads = number_of_threads;
-    (api_state, bsdf_state)
+/// Renders the scene (instead of **WorldEnd**) into memory, no image
+/// files get written. Returns the width and height of the (cropped)
+/// image and its linear RGB values, or **None** if there were errors
+/// (see **ApiState::errors**).
+pub fn pbrt_render_to_memory(api_state: &mut ApiState) -> Option<(i32, i32, Vec<Float>)> {
+    let (mut integrator, scene) = pbrt_make_render(api_state)?;
+    integrator.render(&scene, api_state.number_of_threads);
+    let film: Arc<Film> = integrator.get_camera().get_film();
+    let bounds: Bounds2i = film.cropped_pixel_bounds;
+    let rgb: Vec<Float> = film.image.read().unwrap().clone();
+    Some((
+        bounds.p_max.x - bounds.p_min.x,
+        bounds.p_max.y - bounds.p_min.y,
+        rgb,
+    ))
 }
 
-pub fn pbrt_cleanup(api_state: &ApiState) {
-    // println!("WorldEnd");
-    assert!(
-        api_state.pushed_graphics_states.is_empty(),
-        "Missing end to pbrtAttributeBegin()"
-    );
-    assert!(
-        api_state.pushed_transforms.is_empty(),
-        "Missing end to pbrtTransformBegin()"
-    );
-    // MakeIntegrator
-    let some_integrator: Option<Box<Integrator>> = api_state.render_options.make_integrator();
-    if let Some(mut integrator) = some_integrator {
-        let scene = api_state.render_options.make_scene();
-        let num_threads: u8 = api_state.number_of_threads;
-        integrator.render(&scene, num_threads);
-    } else {
-        panic!("Unable to create integrator.");
+/// Scenes for pbrt-v4 have no **WorldEnd**, rendering starts once the
+/// whole input was parsed.
+pub fn pbrt_end_of_input(api_state: &mut ApiState) {
+    if api_state.inside_world_block {
+        pbrt_cleanup(api_state);
     }
 }
 
@@ -2406,7 +3434,7 @@ pub fn pbrt_coord_sys_transform(api_state: &mut ApiState, params: ParamSet) {
             api_state.cur_transform.t[1] = transform_set.t[1];
         }
         None => {
-            println!(
+            warn!(
                 "Couldn't find named coordinate system \"{}\"",
                 api_state.param_set.name
             );
@@ -2430,14 +3458,13 @@ pub fn pbrt_active_transform_start_time(api_state: &mut ApiState) {
 }
 
 pub fn pbrt_transform_times(api_state: &mut ApiState, start: Float, end: Float) {
-    println!("TransformTimes {} {}", start, end);
+    info!("TransformTimes {} {}", start, end);
     api_state.render_options.transform_start_time = start;
     api_state.render_options.transform_end_time = end;
 }
 
 pub fn pbrt_pixel_filter(api_state: &mut ApiState, params: ParamSet) {
-    // println!("PixelFilter \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("PixelFilter", &params);
     api_state.render_options.filter_name = params.name.clone();
     api_state.param_set = params;
     api_state
@@ -2447,8 +3474,7 @@ pub fn pbrt_pixel_filter(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_film(api_state: &mut ApiState, params: ParamSet) {
-    println!("Film \"{}\"", params.name);
-    print_params(&params);
+    log_statement("Film", &params);
     api_state.render_options.film_name = params.name.clone();
     api_state.param_set = params;
     api_state
@@ -2458,8 +3484,7 @@ pub fn pbrt_film(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_sampler(api_state: &mut ApiState, params: ParamSet) {
-    println!("Sampler \"{}\"", params.name);
-    print_params(&params);
+    log_statement("Sampler", &params);
     api_state.render_options.sampler_name = params.name.clone();
     api_state.param_set = params;
     api_state
@@ -2469,8 +3494,7 @@ pub fn pbrt_sampler(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_accelerator(api_state: &mut ApiState, params: ParamSet) {
-    println!("Accelerator \"{}\"", params.name);
-    print_params(&params);
+    log_statement("Accelerator", &params);
     api_state.render_options.accelerator_name = params.name.clone();
     api_state.param_set = params;
     api_state
@@ -2480,8 +3504,7 @@ pub fn pbrt_accelerator(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_integrator(api_state: &mut ApiState, params: ParamSet) {
-    println!("Integrator \"{}\"", params.name);
-    print_params(&params);
+    log_statement("Integrator", &params);
     api_state.render_options.integrator_name = params.name.clone();
     api_state.param_set = params;
     api_state
@@ -2491,9 +3514,10 @@ pub fn pbrt_integrator(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_camera(api_state: &mut ApiState, params: ParamSet) {
-    // println!("Camera \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("Camera", &params);
     api_state.render_options.camera_name = params.name.clone();
+    api_state.render_options.camera_medium =
+        api_state.graphics_state.current_outside_medium.clone();
     api_state.param_set = params;
     api_state.render_options.camera_to_world.t[0] =
         Transform::inverse(&api_state.cur_transform.t[0]);
@@ -2515,8 +3539,7 @@ pub fn pbrt_camera(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_make_named_medium(api_state: &mut ApiState, params: ParamSet) {
-    // println!("MakeNamedMedium \"{}\"", params.name);
-    // print_params(&api_state.param_set);
+    // log_statement("MakeNamedMedium", &api_state.param_set);
     api_state.param_set = params;
     make_medium(api_state);
 }
@@ -2530,6 +3553,7 @@ pub fn pbrt_medium_interface(api_state: &mut ApiState, inside_name: &str, outsid
 
 pub fn pbrt_world_begin(api_state: &mut ApiState) {
     // println!("WorldBegin");
+    api_state.inside_world_block = true;
     api_state.cur_transform.t[0] = Transform::default();
     api_state.cur_transform.t[1] = Transform::default();
     api_state.active_transform_bits = 3_u8; // 0x11
@@ -2580,14 +3604,30 @@ pub fn pbrt_attribute_begin(api_state: &mut ApiState) {
 pub fn pbrt_attribute_end(api_state: &mut ApiState) {
     // println!("AttributeEnd");
     if api_state.pushed_graphics_states.is_empty() {
-        panic!("Unmatched pbrtAttributeEnd() encountered.")
+        api_state.add_error(ParseError::new(
+            "AttributeEnd",
+            String::from("Unmatched AttributeEnd encountered"),
+        ));
+        return;
+    }
+    if let Some(graphics_state) = api_state.pushed_graphics_states.pop() {
+        api_state.graphics_state = graphics_state;
+    }
+    // a TransformEnd within the block took the transform already
+    match (
+        api_state.pushed_transforms.pop(),
+        api_state.pushed_active_transform_bits.pop(),
+    ) {
+        (Some(popped_transform_set), Some(active_transform_bits)) => {
+            api_state.cur_transform.t[0] = popped_transform_set.t[0];
+            api_state.cur_transform.t[1] = popped_transform_set.t[1];
+            api_state.active_transform_bits = active_transform_bits;
+        }
+        _ => api_state.add_error(ParseError::new(
+            "AttributeEnd",
+            String::from("Unmatched AttributeEnd/TransformEnd nesting"),
+        )),
     }
-    api_state.graphics_state = api_state.pushed_graphics_states.pop().unwrap();
-    let popped_transform_set: TransformSet = api_state.pushed_transforms.pop().unwrap();
-    api_state.cur_transform.t[0] = popped_transform_set.t[0];
-    api_state.cur_transform.t[1] = popped_transform_set.t[1];
-    let active_transform_bits: u8 = api_state.pushed_active_transform_bits.pop().unwrap();
-    api_state.active_transform_bits = active_transform_bits;
 }
 
 pub fn pbrt_transform_begin(api_state: &mut ApiState) {
@@ -2611,6 +3651,13 @@ pub fn pbrt_transform_begin(api_state: &mut ApiState) {
 
 pub fn pbrt_transform_end(api_state: &mut ApiState) {
     // println!("TransformEnd");
+    if api_state.pushed_transforms.is_empty() {
+        api_state.add_error(ParseError::new(
+            "TransformEnd",
+            String::from("Unmatched TransformEnd encountered"),
+        ));
+        return;
+    }
     let popped_transform_set: TransformSet = api_state.pushed_transforms.pop().unwrap();
     api_state.cur_transform.t[0] = popped_transform_set.t[0];
     api_state.cur_transform.t[1] = popped_transform_set.t[1];
@@ -2623,14 +3670,20 @@ pub fn pbrt_texture(api_state: &mut ApiState, params: ParamSet) {
     //     "Texture \"{}\" \"{}\" \"{}\"",
     //     params.name, params.tex_type, params.tex_name
     // );
-    // print_params(&params);
+    // log_statement("Texture", &params);
+    match params.tex_type.as_str() {
+        "float" => api_state.scene_stats.float_textures += 1,
+        _ => api_state.scene_stats.spectrum_textures += 1,
+    }
+    if params.tex_name == "imagemap" {
+        api_state.scene_stats.image_textures += 1;
+    }
     api_state.param_set = params;
     make_texture(api_state);
 }
 
 pub fn pbrt_material(api_state: &mut ApiState, params: ParamSet) {
-    // println!("MakeMaterial \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("MakeMaterial", &params);
     api_state.param_set = params;
     api_state.graphics_state.material = api_state.param_set.name.clone();
     api_state
@@ -2645,12 +3698,16 @@ pub fn pbrt_make_named_material(
     bsdf_state: &mut BsdfState,
     params: ParamSet,
 ) {
-    // println!("MakeNamedMaterial \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("MakeNamedMaterial", &params);
     api_state.param_set = params;
     let mat_type: String = api_state.param_set.find_one_string("type", String::new());
     if mat_type == "" {
-        panic!("No parameter string \"type\" found in MakeNamedMaterial");
+        let name: String = api_state.param_set.name.clone();
+        api_state.add_error(ParseError::new(
+            &name,
+            String::from("No parameter string \"type\" found in MakeNamedMaterial"),
+        ));
+        return;
     }
     api_state.graphics_state.material = mat_type.clone();
     api_state
@@ -2658,13 +3715,19 @@ pub fn pbrt_make_named_material(
         .material_params
         .copy_from(&api_state.param_set);
     api_state.graphics_state.current_material = String::new();
-    let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
+    let mtl: Option<Arc<Material>> = match create_material(api_state, bsdf_state) {
+        Ok(mtl) => mtl,
+        Err(error) => {
+            api_state.add_error(error);
+            return;
+        }
+    };
     if let Some(_named_material) = api_state
         .graphics_state
         .named_materials
         .get(api_state.param_set.name.as_str())
     {
-        println!("Named material \"{}\" redefined", mat_type);
+        warn!("Named material \"{}\" redefined", mat_type);
     }
     Arc::make_mut(&mut api_state.graphics_state.named_materials)
         .insert(api_state.param_set.name.clone(), mtl);
@@ -2677,16 +3740,17 @@ pub fn pbrt_named_material(api_state: &mut ApiState, params: ParamSet) {
 }
 
 pub fn pbrt_light_source(api_state: &mut ApiState, params: ParamSet) {
-    // println!("LightSource \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("LightSource", &params);
     api_state.param_set = params;
-    let mi: MediumInterface = create_medium_interface(&api_state);
-    make_light(api_state, &mi);
+    match create_medium_interface(api_state) {
+        Ok(mi) => make_light(api_state, &mi),
+        Err(error) => api_state.add_error(error),
+    }
+    api_state.render_options.light_fingerprint = light_fingerprint(api_state, false);
 }
 
 pub fn pbrt_area_light_source(api_state: &mut ApiState, params: ParamSet) {
-    // println!("AreaLightSource \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("AreaLightSource", &params);
     api_state.param_set = params;
     api_state.graphics_state.area_light = api_state.param_set.name.clone();
     api_state
@@ -2695,9 +3759,81 @@ pub fn pbrt_area_light_source(api_state: &mut ApiState, params: ParamSet) {
         .copy_from(&api_state.param_set);
 }
 
+/// Folds the light source (or the shape of an area light) which was
+/// just described by the current parameters and transformation into
+/// the light fingerprint of the scene (see
+/// **Scene::light_fingerprint**).
+fn light_fingerprint(api_state: &ApiState, area_light: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    api_state.render_options.light_fingerprint.hash(&mut hasher);
+    if area_light {
+        api_state.graphics_state.area_light.hash(&mut hasher);
+        hash_params(&api_state.graphics_state.area_light_params, &mut hasher);
+        let area_light_params: &ParamSet = &api_state.graphics_state.area_light_params;
+        hash_file_stamps(api_state, area_light_params, &mut hasher);
+        api_state.graphics_state.reverse_orientation.hash(&mut hasher);
+    }
+    hash_params(&api_state.param_set, &mut hasher);
+    hash_file_stamps(api_state, &api_state.param_set, &mut hasher);
+    for transform in &api_state.cur_transform.t {
+        for row in &transform.m.m {
+            for value in row {
+                value.to_bits().hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+/// Feeds the modification times and sizes of the files *params* refer
+/// to (e.g. the image of an environment map) to *hasher*, so editing
+/// them changes the light fingerprint.
+fn hash_file_stamps<H: Hasher>(api_state: &ApiState, params: &ParamSet, hasher: &mut H) {
+    for item in &params.strings {
+        if !FILENAME_PARAMS.contains(&item.name.as_str()) {
+            continue;
+        }
+        for value in &item.values {
+            let filename: String = resolve_filename(api_state.search_directory.as_deref(), value);
+            if let Ok(metadata) = readable_path(&filename).and_then(std::fs::metadata) {
+                metadata.len().hash(hasher);
+                if let Ok(modified) = metadata.modified() {
+                    modified.hash(hasher);
+                }
+            }
+        }
+    }
+}
+
+/// The shapes (with their materials) and the medium interface for
+/// **pbrt_shape()**, errors get recorded in *api_state*.
+#[allow(clippy::type_complexity)]
+fn shapes_materials_and_media(
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+) -> Option<(
+    Vec<Arc<Shape>>,
+    Vec<Option<Arc<Material>>>,
+    Vec<MeshAndMaterial>,
+    MediumInterface,
+)> {
+    let result = get_shapes_and_materials(api_state, bsdf_state).and_then(
+        |(shapes, materials, meshes)| {
+            assert_eq!(shapes.len(), materials.len());
+            create_medium_interface(api_state).map(|mi| (shapes, materials, meshes, mi))
+        },
+    );
+    match result {
+        Ok(result) => Some(result),
+        Err(error) => {
+            api_state.add_error(error);
+            None
+        }
+    }
+}
+
 pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params: ParamSet) {
-    // println!("Shape \"{}\"", params.name);
-    // print_params(&params);
+    // log_statement("Shape", &params);
     api_state.param_set = params;
     // collect area lights
     let mut prims: Vec<Arc<Primitive>> = Vec::new();
@@ -2708,11 +3844,11 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
         if api_state.graphics_state.area_light == "area"
             || api_state.graphics_state.area_light == "diffuse"
         {
-            // first create the shape
-            let (shapes, materials) = get_shapes_and_materials(&api_state, bsdf_state);
-            assert_eq!(shapes.len(), materials.len());
-            // MediumInterface
-            let mi: MediumInterface = create_medium_interface(&api_state);
+            // first create the shape (meshes get split, see get_shapes_and_materials())
+            let (shapes, materials, _meshes, mi) = match shapes_materials_and_media(api_state, bsdf_state) {
+                Some(result) => result,
+                None => return,
+            };
             for i in 0..shapes.len() {
                 let shape = &shapes[i];
                 let material = &materials[i];
@@ -2759,21 +3895,48 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
         }
     } else {
         // continue with shape itself
-        let (shapes, materials) = get_shapes_and_materials(&api_state, bsdf_state);
-        assert_eq!(shapes.len(), materials.len());
-        // MediumInterface
-        let mi: MediumInterface = create_medium_interface(&api_state);
+        let (shapes, materials, meshes, mi) = match shapes_materials_and_media(api_state, bsdf_state) {
+            Some(result) => result,
+            None => return,
+        };
+        // shadow catchers only record shadows and reflections
+        let shadow_catcher: bool = api_state.param_set.find_one_bool("shadowcatcher", false);
+        // holdouts cut holes into the alpha channel
+        let holdout: bool = api_state.param_set.find_one_bool("holdout", false);
+        // nested dielectrics and overlapping media
+        let priority: i32 = api_state.param_set.find_one_int("priority", 0_i32);
+        if shadow_catcher {
+            api_state.render_options.have_shadow_catchers = true;
+        }
+        if holdout {
+            api_state.render_options.have_holdouts = true;
+        }
         for i in 0..shapes.len() {
             let shape = &shapes[i];
             let material = &materials[i];
-            let geo_prim = Arc::new(Primitive::Geometric(Box::new(GeometricPrimitive::new(
+            let mut geometric_primitive: GeometricPrimitive = GeometricPrimitive::new(
                 shape.clone(),
                 material.clone(),
                 None,
                 Some(Arc::new(mi.clone())),
-            ))));
+            );
+            geometric_primitive.shadow_catcher = shadow_catcher;
+            geometric_primitive.holdout = holdout;
+            geometric_primitive.priority = priority;
+            let geo_prim = Arc::new(Primitive::Geometric(Box::new(geometric_primitive)));
             prims.push(geo_prim.clone());
         }
+        for (mesh, material) in meshes {
+            let mut mesh_primitive: MeshPrimitive = MeshPrimitive::new(
+                Arc::new(MeshBVH::new(mesh)),
+                material,
+                Some(Arc::new(mi.clone())),
+            );
+            mesh_primitive.shadow_catcher = shadow_catcher;
+            mesh_primitive.holdout = holdout;
+            mesh_primitive.priority = priority;
+            prims.push(Arc::new(Primitive::Mesh(Box::new(mesh_primitive))));
+        }
         // animated?
         if api_state.cur_transform.is_animated() {
             let animated_object_to_world: AnimatedTransform = AnimatedTransform::new(
@@ -2802,7 +3965,7 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
     // add _prims_ and _areaLights_ to scene or current instance
     if api_state.render_options.current_instance != "" {
         if !area_lights.is_empty() {
-            println!("WARNING: Area lights not supported with object instancing");
+            warn!("Area lights not supported with object instancing");
         }
         if let Some(instance_vec) = api_state
             .render_options
@@ -2814,13 +3977,31 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
             }
         }
     } else {
-        for prim in prims {
-            api_state.render_options.primitives.push(prim.clone());
+        // large meshes get their own BVH, built while parsing continues
+        let min_prims: i32 = api_state
+            .render_options
+            .accelerator_params
+            .find_one_int("meshbuildprims", 65536);
+        if api_state.render_options.accelerator_name == "bvh"
+            && area_lights.is_empty()
+            && min_prims > 0
+            && prims.len() >= min_prims as usize
+        {
+            let handle: BuildHandle =
+                spawn_bvh_build(prims, &api_state.render_options.accelerator_params);
+            api_state.render_options.pending_builds.push(handle);
+            // don't build more BVHs at once than there are cores
+            api_state.render_options.wait_for_builds(num_cpus::get());
+        } else {
+            for prim in prims {
+                api_state.render_options.primitives.push(prim.clone());
+            }
         }
         if !area_lights.is_empty() {
             for area_light in area_lights {
                 api_state.render_options.lights.push(area_light.clone());
             }
+            api_state.render_options.light_fingerprint = light_fingerprint(api_state, true);
         }
     }
 }
@@ -2909,19 +4090,52 @@ pub fn pbrt_object_begin(api_state: &mut ApiState, params: ParamSet) {
     api_state.param_set = params;
     pbrt_attribute_begin(api_state);
     if api_state.render_options.current_instance != "" {
-        println!("ERROR: ObjectBegin called inside of instance definition");
+        error!("ObjectBegin called inside of instance definition");
     }
+    // a redefined instance doesn't need the previous aggregate, but
+    // its build counts until it's done
+    api_state
+        .render_options
+        .wait_for_instance(&api_state.param_set.name);
     api_state
         .render_options
         .instances
         .insert(api_state.param_set.name.clone(), Vec::new());
     api_state.render_options.current_instance = api_state.param_set.name.clone();
+    N_OBJECT_INSTANCES_CREATED.inc();
 }
 
 pub fn pbrt_object_end(api_state: &mut ApiState) {
     // println!("ObjectEnd");
     if api_state.render_options.current_instance == "" {
-        println!("ERROR: ObjectEnd called outside of instance definition");
+        error!("ObjectEnd called outside of instance definition");
+    }
+    // start building the aggregate for the instance _Primitive_s, the
+    // first **ObjectInstance** waits for it (and reports unknown
+    // accelerators)
+    let render_options: &mut RenderOptions = &mut api_state.render_options;
+    let accelerator_params: Option<ParamSet> = if render_options.accelerator_name == "bvh" {
+        Some(render_options.accelerator_params.clone())
+    } else if render_options.accelerator_name == "kdtree" {
+        // WARNING: Use BVHAccel for now !!!
+        Some(ParamSet::default())
+    } else {
+        None
+    };
+    if let (Some(accelerator_params), Some(instance_vec)) = (
+        accelerator_params,
+        render_options
+            .instances
+            .get(&render_options.current_instance),
+    ) {
+        if instance_vec.len() > 1_usize {
+            let handle: BuildHandle = spawn_bvh_build(instance_vec.clone(), &accelerator_params);
+            render_options
+                .pending_instances
+                .push((render_options.current_instance.clone(), handle));
+            // don't build more BVHs at once than there are cores
+            render_options.wait_for_builds(num_cpus::get());
+        }
     }
     api_state.render_options.current_instance = String::from("");
     pbrt_attribute_end(api_state);
@@ -2932,9 +4146,27 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
     api_state.param_set = params;
     // perform object instance error checking
     if api_state.render_options.current_instance != "" {
-        println!("ERROR: ObjectInstance can't be called inside instance definition");
+        error!("ObjectInstance can't be called inside instance definition");
+        return;
+    }
+    let accelerator_name: String = api_state.render_options.accelerator_name.clone();
+    let num_prims: usize = api_state
+        .render_options
+        .instances
+        .get(&api_state.param_set.name)
+        .map_or(0, |instance_vec| instance_vec.len());
+    if num_prims > 1 && accelerator_name != "bvh" && accelerator_name != "kdtree" {
+        api_state.add_error(ParseError::new(
+            &accelerator_name,
+            format!("Accelerator \"{}\" unknown", accelerator_name),
+        ));
         return;
     }
+    // the aggregate for the instance _Primitive_s was built since
+    // **ObjectEnd**
+    api_state
+        .render_options
+        .wait_for_instance(&api_state.param_set.name);
     if let Some(instance_vec) = api_state
         .render_options
         .instances
@@ -2943,58 +4175,7 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
         if instance_vec.is_empty() {
             return;
         }
-        // TODO: ++nObjectInstancesUsed;
-        if instance_vec.len() > 1_usize {
-            // create aggregate for instance _Primitive_s
-            if api_state.render_options.accelerator_name == "bvh" {
-                //  CreateBVHAccelerator
-                let split_method_name: String = api_state
-                    .render_options
-                    .accelerator_params
-                    .find_one_string("splitmethod", String::from("sah"));
-                let split_method;
-                if split_method_name == "sah" {
-                    split_method = SplitMethod::SAH;
-                } else if split_method_name == "hlbvh" {
-                    split_method = SplitMethod::HLBVH;
-                } else if split_method_name == "middle" {
-                    split_method = SplitMethod::Middle;
-                } else if split_method_name == "equal" {
-                    split_method = SplitMethod::EqualCounts;
-                } else {
-                    println!(
-                        "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                        split_method_name
-                    );
-                    split_method = SplitMethod::SAH;
-                }
-                let max_prims_in_node: i32 = api_state
-                    .render_options
-                    .accelerator_params
-                    .find_one_int("maxnodeprims", 4);
-                let accelerator: Arc<Primitive> =
-                    Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
-                        instance_vec.clone(),
-                        max_prims_in_node as usize,
-                        split_method,
-                    ))));
-                instance_vec.clear();
-                instance_vec.push(accelerator);
-            } else if api_state.render_options.accelerator_name == "kdtree" {
-                // println!("TODO: CreateKdTreeAccelerator");
-                // WARNING: Use BVHAccel for now !!!
-                let accelerator: Arc<Primitive> = Arc::new(Primitive::BVH(Box::new(
-                    BVHAccel::new(instance_vec.clone(), 4, SplitMethod::SAH),
-                )));
-                instance_vec.clear();
-                instance_vec.push(accelerator);
-            } else {
-                panic!(
-                    "Accelerator \"{}\" unknown.",
-                    api_state.render_options.accelerator_name
-                );
-            }
-        }
+        N_OBJECT_INSTANCES_USED.inc();
         // create _animatedInstanceToWorld_ transform for instance
         let animated_instance_to_world: AnimatedTransform = AnimatedTransform::new(
             &api_state.cur_transform.t[0],
@@ -3007,8 +4188,8 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
         )));
         api_state.render_options.primitives.push(prim);
     } else {
-        println!(
-            "ERROR: Unable to find instance named {:?}",
+        error!(
+            "Unable to find instance named {:?}",
             api_state.param_set.name.clone()
         );
     }
@@ -6,26 +6,32 @@
 use std;
 use std::borrow::Borrow;
 use std::cell::Cell;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{
-    nrm_cross_vec3, nrm_dot_nrm, nrm_dot_vec3, pnt3_distance, vec3_dot_nrm, vec3_dot_vec3,
+    nrm_cross_vec3, nrm_dot_nrm, nrm_dot_vec3, nrm_faceforward_vec3, pnt3_distance, vec3_dot_nrm,
+    vec3_dot_vec3,
 };
 use crate::core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
-use crate::core::interaction::{InteractionCommon, SurfaceInteraction};
+use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::interpolation::{
     catmull_rom_weights, integrate_catmull_rom, sample_catmull_rom_2d,
 };
 use crate::core::material::{Material, TransportMode};
 use crate::core::medium::phase_hg;
+use crate::core::medium::HenyeyGreenstein;
 use crate::core::pbrt::clamp_t;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::INV_4_PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{cos_theta, fr_dielectric};
 use crate::core::reflection::{Bsdf, Bxdf, BxdfType};
+use crate::core::sampler::Sampler;
 use crate::core::scene::Scene;
 
+/// Upper bound for the number of scattering events of a random walk.
+const RANDOM_WALK_MAX_STEPS: u32 = 256;
+
 pub struct TabulatedBssrdf {
     // BSSRDF Protected Data
     pub po_p: Point3f,   // pub po: &SurfaceInteraction,
@@ -42,6 +48,12 @@ pub struct TabulatedBssrdf {
     pub table: Arc<BssrdfTable>,
     pub sigma_t: Spectrum,
     pub rho: Spectrum,
+    // random walk
+    /// trace a volumetric random walk inside the surface instead of
+    /// using the tabulated profile
+    pub random_walk: bool,
+    /// asymmetry parameter of the phase function used by the walk
+    pub g: Float,
 }
 
 impl TabulatedBssrdf {
@@ -56,7 +68,7 @@ impl TabulatedBssrdf {
     ) -> Self {
         let sigma_t: Spectrum = *sigma_a + *sigma_s;
         let mut rho: Spectrum = Spectrum::new(0.0 as Float);
-        for c in 0..3 {
+        for c in 0..Spectrum::N_SAMPLES {
             if sigma_t[c] != 0.0 as Float {
                 rho.c[c] = sigma_s[c] / sigma_t[c];
             } else {
@@ -79,6 +91,8 @@ impl TabulatedBssrdf {
                 table,
                 sigma_t,
                 rho,
+                random_walk: false,
+                g: 0.0 as Float,
             }
         } else {
             panic!("TabulatedBssrdf needs Material pointer")
@@ -115,9 +129,9 @@ impl TabulatedBssrdf {
         // return combined probability from all BSSRDF sampling strategies
         let mut pdf: Float = 0.0;
         let axis_prob: [Float; 3] = [0.25 as Float, 0.25 as Float, 0.5 as Float];
-        let ch_prob: Float = 1.0 as Float / 3.0 as Float;
+        let ch_prob: Float = 1.0 as Float / Spectrum::N_SAMPLES as Float;
         for axis in 0..3_usize {
-            for ch in 0..3_usize {
+            for ch in 0..Spectrum::N_SAMPLES {
                 pdf += self.pdf_sr(ch, r_proj[axis])
                     * n_local[axis as u8].abs()
                     * ch_prob
@@ -160,8 +174,9 @@ impl TabulatedBssrdf {
             u1 = (u1 - 0.75 as Float) * 4.0 as Float;
         }
         // choose spectral channel for BSSRDF sampling
-        let ch: usize = clamp_t((u1 * 3.0 as Float) as usize, 0_usize, 2_usize);
-        u1 = u1 * 3.0 as Float - ch as Float;
+        let n_channels: Float = Spectrum::N_SAMPLES as Float;
+        let ch: usize = clamp_t((u1 * n_channels) as usize, 0_usize, Spectrum::N_SAMPLES - 1);
+        u1 = u1 * n_channels - ch as Float;
         // sample BSSRDF profile in polar coordinates
         let r: Float = self.sample_sr(ch, u2.x);
         if r < 0.0 as Float {
@@ -210,7 +225,7 @@ impl TabulatedBssrdf {
                 base.medium_interface = None;
                 // append admissible intersection to _IntersectionChain_
                 if let Some(geo_prim_raw) = si.primitive {
-		    let geo_prim = unsafe { &*geo_prim_raw };
+                    let geo_prim = unsafe { &*geo_prim_raw };
                     if let Some(material) = geo_prim.get_material() {
                         //     if (ptr->si.primitive->GetMaterial() == this->material) {
                         if Arc::ptr_eq(&material, &self.material) {
@@ -281,7 +296,7 @@ impl TabulatedBssrdf {
     }
     pub fn sr(&self, r: Float) -> Spectrum {
         let mut sr: Spectrum = Spectrum::default();
-        for ch in 0..3_usize {
+        for ch in 0..Spectrum::N_SAMPLES {
             // convert $r$ into unitless optical radius $r_{\roman{optical}}$
             let r_optical: Float = r * self.sigma_t.c[ch];
             // compute spline weights to interpolate BSSRDF on channel _ch_
@@ -323,7 +338,7 @@ impl TabulatedBssrdf {
         }
         // transform BSSRDF value into world space units
         sr *= self.sigma_t * self.sigma_t;
-        sr.clamp(0.0 as Float, std::f32::INFINITY as Float)
+        sr.clamp(0.0 as Float, Float::INFINITY)
     }
     pub fn pdf_sr(&self, ch: usize, r: Float) -> Float {
         // convert $r$ into unitless optical radius $r_{\roman{optical}}$
@@ -419,6 +434,117 @@ impl TabulatedBssrdf {
             (sp, None)
         }
     }
+    /// Brute force alternative to _sample_s()_: follows the refracted
+    /// _ray_ through the (homogeneous) interior until it leaves the
+    /// surface again. No diffusion assumption is made, which handles
+    /// thin geometry and strongly forward scattering materials. The
+    /// returned weight already includes the sampling PDF. Only surfaces
+    /// with this material end the walk, others (e.g. objects inside,
+    /// or medium interfaces) are passed through.
+    pub fn sample_random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        sampler: &mut Sampler,
+    ) -> (Spectrum, Option<SurfaceInteraction<'a>>) {
+        let sigma_s: Spectrum = self.rho * self.sigma_t;
+        let phase: HenyeyGreenstein = HenyeyGreenstein { g: self.g };
+        let mut beta: Spectrum = Spectrum::new(1.0 as Float);
+        let mut r: Ray = ray.clone();
+        r.medium = None;
+        for _step in 0..RANDOM_WALK_MAX_STEPS {
+            // sample a distance using the extinction of one channel
+            let ch: usize = clamp_t(
+                (sampler.get_1d() * Spectrum::N_SAMPLES as Float) as usize,
+                0_usize,
+                Spectrum::N_SAMPLES - 1,
+            );
+            let t: Float = -(1.0 as Float - sampler.get_1d()).ln() / self.sigma_t[ch];
+            let origin: Point3f = r.o;
+            let d: Vector3f = r.d.normalize();
+            // distance to the boundary (if it's closer than _t_)
+            let mut t_hit: Float = 0.0 as Float;
+            let mut exit: Option<SurfaceInteraction> = None;
+            loop {
+                r.t_max = Float::INFINITY;
+                let mut si: SurfaceInteraction = SurfaceInteraction::default();
+                if !scene.intersect(&mut r, &mut si) {
+                    // open geometry, the walk escaped
+                    return (Spectrum::default(), None);
+                }
+                t_hit += r.t_max * r.d.length();
+                if t_hit > t {
+                    break;
+                }
+                let mut is_boundary: bool = false;
+                if let Some(prim_raw) = si.primitive {
+                    let prim = unsafe { &*prim_raw };
+                    if let Some(material) = prim.get_material() {
+                        is_boundary = Arc::ptr_eq(&material, &self.material);
+                    }
+                }
+                if is_boundary {
+                    exit = Some(si);
+                    break;
+                }
+                // another surface, continue behind it
+                r = si.spawn_ray(&d);
+            }
+            if let Some(mut si) = exit {
+                // the walk leaves the surface, weight by the
+                // transmittance divided by the PDF averaged over channels
+                let tr: Spectrum = (-self.sigma_t * t_hit).exp();
+                let pdf: Float = tr.average();
+                if pdf == 0.0 as Float {
+                    return (Spectrum::default(), None);
+                }
+                beta *= tr / pdf;
+                // exit through the dielectric boundary
+                si.bsdf = Some(Bsdf::new(&si, 1.0));
+                if let Some(bsdf) = &mut si.bsdf {
+                    bsdf.bxdfs[0] = Bxdf::Bssrdf(SeparableBssrdfAdapter::new(
+                        self.clone(),
+                        self.mode,
+                        self.eta,
+                    ));
+                }
+                si.wo = Vector3f::from(nrm_faceforward_vec3(&si.shading.n, &d));
+                return (beta, Some(si));
+            }
+            // scatter inside the surface
+            let tr: Spectrum = (-self.sigma_t * t).exp();
+            let density: Spectrum = self.sigma_t * tr;
+            let pdf: Float = density.average();
+            if pdf == 0.0 as Float {
+                return (Spectrum::default(), None);
+            }
+            beta *= sigma_s * tr / pdf;
+            if beta.is_black() {
+                return (Spectrum::default(), None);
+            }
+            // possibly terminate the walk with Russian roulette
+            let max_beta: Float = beta.max_component_value();
+            if max_beta < 0.25 as Float {
+                let q: Float = (0.05 as Float).max(1.0 as Float - max_beta);
+                if sampler.get_1d() < q {
+                    return (Spectrum::default(), None);
+                }
+                beta /= 1.0 as Float - q;
+            }
+            let p: Point3f = origin + d * t;
+            let mut wi: Vector3f = Vector3f::default();
+            phase.sample_p(&(-d), &mut wi, sampler.get_2d());
+            r = Ray {
+                o: p,
+                d: wi,
+                t_max: Float::INFINITY,
+                time: r.time,
+                differential: None,
+                medium: None,
+            };
+        }
+        (Spectrum::default(), None)
+    }
 }
 
 impl Clone for TabulatedBssrdf {
@@ -436,6 +562,8 @@ impl Clone for TabulatedBssrdf {
             table: self.table.clone(),
             sigma_t: self.sigma_t,
             rho: self.rho,
+            random_walk: self.random_walk,
+            g: self.g,
         }
     }
 }
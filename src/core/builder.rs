@@ -0,0 +1,673 @@
+//! Build scenes programmatically instead of writing (and parsing)
+//! scene files.
+//!
+//! A **SceneBuilder** collects typed descriptions of the camera, film,
+//! sampler, integrator, shapes, materials, and lights and turns them
+//! into the same API calls the scene file parser would make (see
+//! **scenecache::ApiCall**). Because the calls are only executed by
+//! **SceneBuilder::render()**, the rendering options and the scene
+//! content can be added in any order.
+//!
+//! ```no_run
+//! use pbrt::core::builder::{
+//!     CameraDesc, IntegratorDesc, LightDesc, MaterialDesc, SceneBuilder, ShapeDesc,
+//! };
+//! use pbrt::core::geometry::{Point3f, Vector3f};
+//! use pbrt::core::pbrt::Spectrum;
+//! use pbrt::core::transform::Transform;
+//!
+//! let mut builder = SceneBuilder::new();
+//! builder
+//!     .set_camera(
+//!         CameraDesc::Perspective {
+//!             fov: 45.0,
+//!             lens_radius: 0.0,
+//!             focal_distance: 1e6,
+//!         },
+//!         Transform::look_at(
+//!             &Point3f { x: 0.0, y: -5.0, z: 1.0 },
+//!             &Point3f { x: 0.0, y: 0.0, z: 0.5 },
+//!             &Vector3f { x: 0.0, y: 0.0, z: 1.0 },
+//!         ),
+//!     )
+//!     .set_integrator(IntegratorDesc::Path { max_depth: 5 })
+//!     .add_light(
+//!         LightDesc::Infinite {
+//!             l: Spectrum::new(1.0),
+//!             map_name: None,
+//!             n_samples: 1,
+//!         },
+//!         &Transform::default(),
+//!     )
+//!     .add_shape(
+//!         ShapeDesc::Sphere { radius: 0.5 },
+//!         &MaterialDesc::Matte {
+//!             kd: Spectrum::rgb(0.8, 0.2, 0.2),
+//!             sigma: 0.0,
+//!         },
+//!         &Transform::translate(&Vector3f { x: 0.0, y: 0.0, z: 0.5 }),
+//!     );
+//! builder.render(0);
+//! ```
+
+// pbrt
+use crate::core::api::pbrt_init;
+use crate::core::export::export_pbrt;
+use crate::core::geometry::{Normal3f, Point2f, Point3f};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::scenecache::ApiCall;
+use crate::core::transform::Transform;
+
+/// Cameras (the transform is passed separately).
+pub enum CameraDesc {
+    Perspective {
+        /// field of view (in degrees) of the shorter image axis
+        fov: Float,
+        lens_radius: Float,
+        focal_distance: Float,
+    },
+    Orthographic {
+        lens_radius: Float,
+        focal_distance: Float,
+    },
+    /// renders the full sphere of directions (lat-long)
+    Environment,
+    /// any other camera, *name* of the **ParamSet** is the type
+    Custom(Box<ParamSet>),
+}
+
+/// The (image) film.
+pub struct FilmDesc {
+    pub x_resolution: i32,
+    pub y_resolution: i32,
+    /// *[x_min, x_max, y_min, y_max]* in NDC space
+    pub crop_window: Option<[Float; 4]>,
+    /// extra pixels on every side of the frame (in percent of the
+    /// resolution)
+    pub overscan: Float,
+    pub filename: String,
+}
+
+impl Default for FilmDesc {
+    fn default() -> Self {
+        FilmDesc {
+            x_resolution: 1280,
+            y_resolution: 720,
+            crop_window: None,
+            overscan: 0.0 as Float,
+            filename: String::from("pbrt.png"),
+        }
+    }
+}
+
+/// Pixel reconstruction filters (with their default parameters).
+pub enum FilterDesc {
+    Box,
+    Gaussian,
+    Mitchell,
+    Sinc,
+    Triangle,
+}
+
+/// Samplers with the number of samples per pixel.
+pub enum SamplerDesc {
+    Halton(i32),
+    MaxMinDist(i32),
+    PMJ02BN(i32),
+    Random(i32),
+    Sobol(i32),
+    Stratified {
+        x_samples: i32,
+        y_samples: i32,
+        jitter: bool,
+    },
+    ZeroTwoSequence(i32),
+}
+
+/// Integrators and their most important settings.
+pub enum IntegratorDesc {
+    AmbientOcclusion {
+        n_samples: i32,
+        cos_sample: bool,
+    },
+    Bdpt {
+        max_depth: i32,
+    },
+    DirectLighting {
+        max_depth: i32,
+    },
+    Path {
+        max_depth: i32,
+    },
+    VolPath {
+        max_depth: i32,
+    },
+    Whitted {
+        max_depth: i32,
+    },
+    /// any other integrator, *name* of the **ParamSet** is the type
+    Custom(Box<ParamSet>),
+}
+
+/// Shapes (in object space).
+pub enum ShapeDesc {
+    Cylinder {
+        radius: Float,
+        z_min: Float,
+        z_max: Float,
+    },
+    Disk {
+        height: Float,
+        radius: Float,
+        inner_radius: Float,
+    },
+    Sphere {
+        radius: Float,
+    },
+    /// three vertex indices per triangle, normals and uvs are optional
+    /// (leave them empty)
+    TriangleMesh {
+        indices: Vec<i32>,
+        p: Vec<Point3f>,
+        n: Vec<Normal3f>,
+        uv: Vec<Point2f>,
+    },
+    PlyMesh {
+        filename: String,
+    },
+    /// any other shape, *name* of the **ParamSet** is the type
+    Custom(Box<ParamSet>),
+}
+
+/// Materials with constant (not textured) parameters.
+pub enum MaterialDesc {
+    Glass {
+        kr: Spectrum,
+        kt: Spectrum,
+        eta: Float,
+        roughness: Float,
+    },
+    Matte {
+        kd: Spectrum,
+        sigma: Float,
+    },
+    Metal {
+        eta: Spectrum,
+        k: Spectrum,
+        roughness: Float,
+    },
+    Mirror {
+        kr: Spectrum,
+    },
+    Plastic {
+        kd: Spectrum,
+        ks: Spectrum,
+        roughness: Float,
+    },
+    /// a material defined before by **SceneBuilder::add_named_material()**
+    Named(String),
+    /// no material at all (e.g. for boundaries of participating media)
+    None,
+    /// any other material, *name* of the **ParamSet** is the type
+    Custom(Box<ParamSet>),
+}
+
+/// Light sources (area lights are added with their shape, see
+/// **SceneBuilder::add_area_light()**).
+pub enum LightDesc {
+    Distant {
+        l: Spectrum,
+        from: Point3f,
+        to: Point3f,
+    },
+    Infinite {
+        l: Spectrum,
+        /// optional lat-long environment map
+        map_name: Option<String>,
+        n_samples: i32,
+    },
+    Point {
+        i: Spectrum,
+        from: Point3f,
+    },
+    Spot {
+        i: Spectrum,
+        from: Point3f,
+        to: Point3f,
+        cone_angle: Float,
+        cone_delta: Float,
+    },
+    /// any other light, *name* of the **ParamSet** is the type
+    Custom(Box<ParamSet>),
+}
+
+fn new_params(key_word: &str, name: &str) -> ParamSet {
+    ParamSet {
+        key_word: String::from(key_word),
+        name: String::from(name),
+        ..Default::default()
+    }
+}
+
+fn custom_params(key_word: &str, mut params: ParamSet) -> ParamSet {
+    params.key_word = String::from(key_word);
+    params
+}
+
+fn add_point3fs(params: &mut ParamSet, name: &str, points: &[Point3f]) {
+    let values: Vec<Float> = points.iter().flat_map(|p| vec![p.x, p.y, p.z]).collect();
+    params.add_point3fs(String::from(name), values);
+}
+
+impl CameraDesc {
+    fn to_params(&self) -> ParamSet {
+        match self {
+            CameraDesc::Perspective {
+                fov,
+                lens_radius,
+                focal_distance,
+            } => {
+                let mut params: ParamSet = new_params("Camera", "perspective");
+                params.add_float(String::from("fov"), *fov);
+                params.add_float(String::from("lensradius"), *lens_radius);
+                params.add_float(String::from("focaldistance"), *focal_distance);
+                params
+            }
+            CameraDesc::Orthographic {
+                lens_radius,
+                focal_distance,
+            } => {
+                let mut params: ParamSet = new_params("Camera", "orthographic");
+                params.add_float(String::from("lensradius"), *lens_radius);
+                params.add_float(String::from("focaldistance"), *focal_distance);
+                params
+            }
+            CameraDesc::Environment => new_params("Camera", "environment"),
+            CameraDesc::Custom(params) => custom_params("Camera", (**params).clone()),
+        }
+    }
+}
+
+impl FilmDesc {
+    fn to_params(&self) -> ParamSet {
+        let mut params: ParamSet = new_params("Film", "image");
+        params.add_int(String::from("xresolution"), self.x_resolution);
+        params.add_int(String::from("yresolution"), self.y_resolution);
+        if let Some(crop_window) = self.crop_window {
+            params.add_floats(String::from("cropwindow"), crop_window.to_vec());
+        }
+        if self.overscan > 0.0 as Float {
+            params.add_float(String::from("overscan"), self.overscan);
+        }
+        params.add_string(String::from("filename"), self.filename.clone());
+        params
+    }
+}
+
+impl FilterDesc {
+    fn to_params(&self) -> ParamSet {
+        let name: &str = match self {
+            FilterDesc::Box => "box",
+            FilterDesc::Gaussian => "gaussian",
+            FilterDesc::Mitchell => "mitchell",
+            FilterDesc::Sinc => "sinc",
+            FilterDesc::Triangle => "triangle",
+        };
+        new_params("PixelFilter", name)
+    }
+}
+
+impl SamplerDesc {
+    fn to_params(&self) -> ParamSet {
+        let (name, pixel_samples): (&str, i32) = match self {
+            SamplerDesc::Halton(spp) => ("halton", *spp),
+            SamplerDesc::MaxMinDist(spp) => ("maxmindist", *spp),
+            SamplerDesc::PMJ02BN(spp) => ("pmj02bn", *spp),
+            SamplerDesc::Random(spp) => ("random", *spp),
+            SamplerDesc::Sobol(spp) => ("sobol", *spp),
+            SamplerDesc::ZeroTwoSequence(spp) => ("02sequence", *spp),
+            SamplerDesc::Stratified {
+                x_samples,
+                y_samples,
+                jitter,
+            } => {
+                let mut params: ParamSet = new_params("Sampler", "stratified");
+                params.add_int(String::from("xsamples"), *x_samples);
+                params.add_int(String::from("ysamples"), *y_samples);
+                params.add_bool(String::from("jitter"), *jitter);
+                return params;
+            }
+        };
+        let mut params: ParamSet = new_params("Sampler", name);
+        params.add_int(String::from("pixelsamples"), pixel_samples);
+        params
+    }
+}
+
+impl IntegratorDesc {
+    fn to_params(&self) -> ParamSet {
+        let (name, max_depth): (&str, i32) = match self {
+            IntegratorDesc::AmbientOcclusion {
+                n_samples,
+                cos_sample,
+            } => {
+                let mut params: ParamSet = new_params("Integrator", "ambientocclusion");
+                params.add_int(String::from("nsamples"), *n_samples);
+                params.add_bool(String::from("cossample"), *cos_sample);
+                return params;
+            }
+            IntegratorDesc::Bdpt { max_depth } => ("bdpt", *max_depth),
+            IntegratorDesc::DirectLighting { max_depth } => ("directlighting", *max_depth),
+            IntegratorDesc::Path { max_depth } => ("path", *max_depth),
+            IntegratorDesc::VolPath { max_depth } => ("volpath", *max_depth),
+            IntegratorDesc::Whitted { max_depth } => ("whitted", *max_depth),
+            IntegratorDesc::Custom(params) => {
+                return custom_params("Integrator", (**params).clone())
+            }
+        };
+        let mut params: ParamSet = new_params("Integrator", name);
+        params.add_int(String::from("maxdepth"), max_depth);
+        params
+    }
+}
+
+impl ShapeDesc {
+    fn to_params(&self) -> ParamSet {
+        match self {
+            ShapeDesc::Cylinder {
+                radius,
+                z_min,
+                z_max,
+            } => {
+                let mut params: ParamSet = new_params("Shape", "cylinder");
+                params.add_float(String::from("radius"), *radius);
+                params.add_float(String::from("zmin"), *z_min);
+                params.add_float(String::from("zmax"), *z_max);
+                params
+            }
+            ShapeDesc::Disk {
+                height,
+                radius,
+                inner_radius,
+            } => {
+                let mut params: ParamSet = new_params("Shape", "disk");
+                params.add_float(String::from("height"), *height);
+                params.add_float(String::from("radius"), *radius);
+                params.add_float(String::from("innerradius"), *inner_radius);
+                params
+            }
+            ShapeDesc::Sphere { radius } => {
+                let mut params: ParamSet = new_params("Shape", "sphere");
+                params.add_float(String::from("radius"), *radius);
+                params
+            }
+            ShapeDesc::TriangleMesh { indices, p, n, uv } => {
+                let mut params: ParamSet = new_params("Shape", "trianglemesh");
+                params.add_ints(String::from("indices"), indices.clone());
+                add_point3fs(&mut params, "P", p);
+                if !n.is_empty() {
+                    let values: Vec<Float> = n.iter().flat_map(|n| vec![n.x, n.y, n.z]).collect();
+                    params.add_normal3fs(String::from("N"), values);
+                }
+                if !uv.is_empty() {
+                    let values: Vec<Float> = uv.iter().flat_map(|uv| vec![uv.x, uv.y]).collect();
+                    params.add_point2fs(String::from("uv"), values);
+                }
+                params
+            }
+            ShapeDesc::PlyMesh { filename } => {
+                let mut params: ParamSet = new_params("Shape", "plymesh");
+                params.add_string(String::from("filename"), filename.clone());
+                params
+            }
+            ShapeDesc::Custom(params) => custom_params("Shape", (**params).clone()),
+        }
+    }
+}
+
+impl MaterialDesc {
+    /// The material type and its parameters.
+    fn to_params(&self, key_word: &str) -> ParamSet {
+        match self {
+            MaterialDesc::Glass {
+                kr,
+                kt,
+                eta,
+                roughness,
+            } => {
+                let mut params: ParamSet = new_params(key_word, "glass");
+                params.add_rgb_spectrum(String::from("Kr"), *kr);
+                params.add_rgb_spectrum(String::from("Kt"), *kt);
+                params.add_float(String::from("eta"), *eta);
+                params.add_float(String::from("uroughness"), *roughness);
+                params.add_float(String::from("vroughness"), *roughness);
+                params
+            }
+            MaterialDesc::Matte { kd, sigma } => {
+                let mut params: ParamSet = new_params(key_word, "matte");
+                params.add_rgb_spectrum(String::from("Kd"), *kd);
+                params.add_float(String::from("sigma"), *sigma);
+                params
+            }
+            MaterialDesc::Metal { eta, k, roughness } => {
+                let mut params: ParamSet = new_params(key_word, "metal");
+                params.add_rgb_spectrum(String::from("eta"), *eta);
+                params.add_rgb_spectrum(String::from("k"), *k);
+                params.add_float(String::from("roughness"), *roughness);
+                params
+            }
+            MaterialDesc::Mirror { kr } => {
+                let mut params: ParamSet = new_params(key_word, "mirror");
+                params.add_rgb_spectrum(String::from("Kr"), *kr);
+                params
+            }
+            MaterialDesc::Plastic { kd, ks, roughness } => {
+                let mut params: ParamSet = new_params(key_word, "plastic");
+                params.add_rgb_spectrum(String::from("Kd"), *kd);
+                params.add_rgb_spectrum(String::from("Ks"), *ks);
+                params.add_float(String::from("roughness"), *roughness);
+                params
+            }
+            MaterialDesc::Named(name) => new_params("NamedMaterial", name),
+            MaterialDesc::None => new_params(key_word, "none"),
+            MaterialDesc::Custom(params) => custom_params(key_word, (**params).clone()),
+        }
+    }
+}
+
+impl LightDesc {
+    fn to_params(&self) -> ParamSet {
+        match self {
+            LightDesc::Distant { l, from, to } => {
+                let mut params: ParamSet = new_params("LightSource", "distant");
+                params.add_rgb_spectrum(String::from("L"), *l);
+                params.add_point3f(String::from("from"), *from);
+                params.add_point3f(String::from("to"), *to);
+                params
+            }
+            LightDesc::Infinite {
+                l,
+                map_name,
+                n_samples,
+            } => {
+                let mut params: ParamSet = new_params("LightSource", "infinite");
+                params.add_rgb_spectrum(String::from("L"), *l);
+                if let Some(map_name) = map_name {
+                    params.add_string(String::from("mapname"), map_name.clone());
+                }
+                params.add_int(String::from("nsamples"), *n_samples);
+                params
+            }
+            LightDesc::Point { i, from } => {
+                let mut params: ParamSet = new_params("LightSource", "point");
+                params.add_rgb_spectrum(String::from("I"), *i);
+                params.add_point3f(String::from("from"), *from);
+                params
+            }
+            LightDesc::Spot {
+                i,
+                from,
+                to,
+                cone_angle,
+                cone_delta,
+            } => {
+                let mut params: ParamSet = new_params("LightSource", "spot");
+                params.add_rgb_spectrum(String::from("I"), *i);
+                params.add_point3f(String::from("from"), *from);
+                params.add_point3f(String::from("to"), *to);
+                params.add_float(String::from("coneangle"), *cone_angle);
+                params.add_float(String::from("conedeltaangle"), *cone_delta);
+                params
+            }
+            LightDesc::Custom(params) => custom_params("LightSource", (**params).clone()),
+        }
+    }
+}
+
+/// Collects the description of a scene, see the module documentation.
+pub struct SceneBuilder {
+    pub camera: CameraDesc,
+    pub world_to_camera: Transform,
+    pub film: FilmDesc,
+    pub filter: FilterDesc,
+    pub sampler: SamplerDesc,
+    pub integrator: IntegratorDesc,
+    /// everything between **WorldBegin** and **WorldEnd**
+    pub world: Vec<ApiCall>,
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        SceneBuilder {
+            camera: CameraDesc::Perspective {
+                fov: 90.0 as Float,
+                lens_radius: 0.0 as Float,
+                focal_distance: 1e6 as Float,
+            },
+            world_to_camera: Transform::default(),
+            film: FilmDesc::default(),
+            filter: FilterDesc::Box,
+            sampler: SamplerDesc::Halton(16),
+            integrator: IntegratorDesc::Path { max_depth: 5 },
+            world: Vec::new(),
+        }
+    }
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        SceneBuilder::default()
+    }
+    /// Sets the camera and where it is (see **Transform::look_at()**).
+    pub fn set_camera(&mut self, camera: CameraDesc, world_to_camera: Transform) -> &mut Self {
+        self.camera = camera;
+        self.world_to_camera = world_to_camera;
+        self
+    }
+    pub fn set_film(&mut self, film: FilmDesc) -> &mut Self {
+        self.film = film;
+        self
+    }
+    pub fn set_filter(&mut self, filter: FilterDesc) -> &mut Self {
+        self.filter = filter;
+        self
+    }
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) -> &mut Self {
+        self.sampler = sampler;
+        self
+    }
+    pub fn set_integrator(&mut self, integrator: IntegratorDesc) -> &mut Self {
+        self.integrator = integrator;
+        self
+    }
+    /// Relative filenames (e.g. of PLY meshes or textures) are
+    /// resolved against this directory.
+    pub fn set_search_directory(&mut self, directory: &str) -> &mut Self {
+        self.world
+            .push(ApiCall::SearchDirectory(String::from(directory)));
+        self
+    }
+    /// Defines a material which can be used later via
+    /// **MaterialDesc::Named**.
+    pub fn add_named_material(&mut self, name: &str, material: &MaterialDesc) -> &mut Self {
+        let mut params: ParamSet = material.to_params("MakeNamedMaterial");
+        let mat_type: String = params.name.clone();
+        params.name = String::from(name);
+        params.add_string(String::from("type"), mat_type);
+        self.world.push(ApiCall::Params(Box::new(params)));
+        self
+    }
+    fn begin_object(&mut self, material: &MaterialDesc, object_to_world: &Transform) {
+        self.world.push(ApiCall::AttributeBegin);
+        self.world.push(ApiCall::ConcatTransform(*object_to_world));
+        self.world
+            .push(ApiCall::Params(Box::new(material.to_params("Material"))));
+    }
+    pub fn add_shape(
+        &mut self,
+        shape: ShapeDesc,
+        material: &MaterialDesc,
+        object_to_world: &Transform,
+    ) -> &mut Self {
+        self.begin_object(material, object_to_world);
+        self.world
+            .push(ApiCall::Params(Box::new(shape.to_params())));
+        self.world.push(ApiCall::AttributeEnd);
+        self
+    }
+    /// Adds a shape which emits the (constant) radiance *l*.
+    pub fn add_area_light(
+        &mut self,
+        shape: ShapeDesc,
+        material: &MaterialDesc,
+        l: Spectrum,
+        two_sided: bool,
+        object_to_world: &Transform,
+    ) -> &mut Self {
+        self.begin_object(material, object_to_world);
+        let mut params: ParamSet = new_params("AreaLightSource", "diffuse");
+        params.add_rgb_spectrum(String::from("L"), l);
+        params.add_bool(String::from("twosided"), two_sided);
+        self.world.push(ApiCall::Params(Box::new(params)));
+        self.world
+            .push(ApiCall::Params(Box::new(shape.to_params())));
+        self.world.push(ApiCall::AttributeEnd);
+        self
+    }
+    pub fn add_light(&mut self, light: LightDesc, light_to_world: &Transform) -> &mut Self {
+        self.world.push(ApiCall::AttributeBegin);
+        self.world.push(ApiCall::ConcatTransform(*light_to_world));
+        self.world
+            .push(ApiCall::Params(Box::new(light.to_params())));
+        self.world.push(ApiCall::AttributeEnd);
+        self
+    }
+    /// All API calls describing the scene, in the order a scene file
+    /// would contain them.
+    pub fn calls(&self) -> Vec<ApiCall> {
+        let mut calls: Vec<ApiCall> = Vec::with_capacity(self.world.len() + 8);
+        calls.push(ApiCall::Transform(self.world_to_camera));
+        calls.push(ApiCall::Params(Box::new(self.camera.to_params())));
+        calls.push(ApiCall::Params(Box::new(self.film.to_params())));
+        calls.push(ApiCall::Params(Box::new(self.filter.to_params())));
+        calls.push(ApiCall::Params(Box::new(self.sampler.to_params())));
+        calls.push(ApiCall::Params(Box::new(self.integrator.to_params())));
+        calls.push(ApiCall::WorldBegin);
+        calls.extend(self.world.iter().cloned());
+        calls
+    }
+    /// Writes the scene as .pbrt file instead of rendering it.
+    pub fn export(&self, filename: &str) -> std::io::Result<()> {
+        export_pbrt(filename, &self.calls())
+    }
+    /// Renders the scene using *number_of_threads* threads (0 means
+    /// as many as there are cores).
+    pub fn render(&self, number_of_threads: u8) {
+        let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+        for call in self.calls() {
+            call.execute(&mut api_state, &mut bsdf_state);
+        }
+        ApiCall::WorldEnd.execute(&mut api_state, &mut bsdf_state);
+    }
+}
@@ -14,6 +14,7 @@ use crate::core::geometry::{Point2f, Ray, Vector3f};
 use crate::core::interaction::InteractionCommon;
 use crate::core::light::VisibilityTester;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::transform::AnimatedTransform;
 
 // see camera.h
 
@@ -81,6 +82,14 @@ impl Camera {
             Camera::Realistic(camera) => camera.get_shutter_close(),
         }
     }
+    pub fn get_camera_to_world(&self) -> &AnimatedTransform {
+        match self {
+            Camera::Environment(camera) => &camera.camera_to_world,
+            Camera::Orthographic(camera) => &camera.camera_to_world,
+            Camera::Perspective(camera) => &camera.camera_to_world,
+            Camera::Realistic(camera) => &camera.camera_to_world,
+        }
+    }
     pub fn get_film(&self) -> Arc<Film> {
         match self {
             Camera::Environment(camera) => camera.get_film(),
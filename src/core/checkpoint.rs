@@ -0,0 +1,399 @@
+//! Render checkpoints, to resume an interrupted render.
+//!
+//! With **set_checkpoint()** the integrators rendering tile by tile
+//! (**SamplerIntegrator**) write the state of a render to a file every
+//! now and then, and when the render gets cancelled (e.g. by Ctrl+C,
+//! see **cancel_render_on_interrupt()**): the values the film
+//! accumulated so far (see **Film::write_sums()**), the pass of a
+//! progressive render (see **set_progressive()**), and which tiles of
+//! that pass were merged into the film. A later run of the same scene
+//! can resume from the file, it only renders the missing tiles (and
+//! passes). Because the sampler gets reseeded for every pixel and
+//! pass (see **pixel_seed()**), the resumed render ends up with the
+//! same samples as one which was never interrupted.
+//!
+//! A checkpoint only belongs to a render with the same sample bounds,
+//! tiles, samples per pixel (and per pass), and film outputs, others
+//! get rejected with a warning and the render starts from scratch.
+//! So do truncated or corrupted files: the whole file gets read and
+//! checked (its size and a CRC-32 of its content) before anything is
+//! restored. Films with *diskbacked* or *deep* can't be checkpointed.
+
+// std
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+// others
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crc32fast::Hasher;
+use log::{error, info, warn};
+// pbrt
+use crate::core::film::Film;
+use crate::core::geometry::Bounds2i;
+use crate::core::integrator::TileGrid;
+use crate::core::memory::readable_path;
+
+const MAGIC: &[u8; 8] = b"RSPBRTCP";
+const VERSION: u32 = 2;
+
+/// Where (and how often) checkpoints get written, see
+/// **set_checkpoint()**.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointSettings {
+    /// the checkpoint file, rewritten each time
+    pub filename: String,
+    /// time between two checkpoints while rendering
+    pub interval: Duration,
+    /// a checkpoint file to continue rendering from
+    pub resume: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref CHECKPOINT: Mutex<Option<CheckpointSettings>> = Mutex::new(None);
+}
+
+/// Lets all following renders write checkpoints (and resume from
+/// one), see **checkpoint**. **None** turns it off.
+pub fn set_checkpoint(settings: Option<CheckpointSettings>) {
+    *CHECKPOINT.lock().unwrap() = settings;
+}
+
+pub fn checkpoint_settings() -> Option<CheckpointSettings> {
+    CHECKPOINT.lock().unwrap().clone()
+}
+
+/// What a checkpoint has to agree on with the render resuming it.
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+    sample_bounds: [i32; 4],
+    pixel_bounds: [i32; 4],
+    tile_size: [i32; 2],
+    samples_per_pixel: i64,
+    pass_samples: i64,
+    n_aovs: u32,
+    features: bool,
+}
+
+impl Layout {
+    fn write<W: Write>(&self, w: &mut W) -> Result<()> {
+        for v in self.sample_bounds.iter().chain(self.pixel_bounds.iter()) {
+            w.write_i32::<LittleEndian>(*v)?;
+        }
+        for v in &self.tile_size {
+            w.write_i32::<LittleEndian>(*v)?;
+        }
+        w.write_i64::<LittleEndian>(self.samples_per_pixel)?;
+        w.write_i64::<LittleEndian>(self.pass_samples)?;
+        w.write_u32::<LittleEndian>(self.n_aovs)?;
+        w.write_u8(self.features as u8)
+    }
+    fn read<R: Read>(r: &mut R) -> Result<Layout> {
+        let mut sample_bounds: [i32; 4] = [0; 4];
+        r.read_i32_into::<LittleEndian>(&mut sample_bounds)?;
+        let mut pixel_bounds: [i32; 4] = [0; 4];
+        r.read_i32_into::<LittleEndian>(&mut pixel_bounds)?;
+        let mut tile_size: [i32; 2] = [0; 2];
+        r.read_i32_into::<LittleEndian>(&mut tile_size)?;
+        Ok(Layout {
+            sample_bounds,
+            pixel_bounds,
+            tile_size,
+            samples_per_pixel: r.read_i64::<LittleEndian>()?,
+            pass_samples: r.read_i64::<LittleEndian>()?,
+            n_aovs: r.read_u32::<LittleEndian>()?,
+            features: r.read_u8()? != 0,
+        })
+    }
+}
+
+/// Passes everything written on to *inner*, and hashes it (see
+/// **Checkpoint::write_file()**).
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n: usize = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn bounds_array(b: &Bounds2i) -> [i32; 4] {
+    [b.p_min.x, b.p_min.y, b.p_max.x, b.p_max.y]
+}
+
+/// The state of a running render, see **checkpoint**. Only the thread
+/// merging tiles into the film updates it, so the film and the tiles
+/// marked as done always agree.
+pub struct Checkpoint {
+    filename: String,
+    interval: Duration,
+    layout: Layout,
+    /// the pass being rendered
+    pass: i64,
+    /// the tiles of that pass which are merged into the film
+    done: Vec<bool>,
+    last_write: Instant,
+}
+
+impl Checkpoint {
+    /// Starts checkpointing a render (if **set_checkpoint()** asked
+    /// for it), the film gets restored from the checkpoint to resume.
+    /// Renders in passes of *pass_samples* samples per pixel (all
+    /// *samples_per_pixel* for one pass).
+    pub fn start(
+        film: &Film,
+        grid: &TileGrid,
+        samples_per_pixel: i64,
+        pass_samples: i64,
+    ) -> Option<Checkpoint> {
+        let settings: CheckpointSettings = checkpoint_settings()?;
+        if film.is_disk_backed() || film.is_deep() {
+            warn!("Checkpoints are ignored with Film \"diskbacked\" or \"deep\".");
+            return None;
+        }
+        let mut checkpoint: Checkpoint = Checkpoint {
+            filename: settings.filename,
+            interval: settings.interval,
+            layout: Layout {
+                sample_bounds: bounds_array(&grid.sample_bounds),
+                pixel_bounds: bounds_array(&film.cropped_pixel_bounds),
+                tile_size: [grid.tile_size.x, grid.tile_size.y],
+                samples_per_pixel,
+                pass_samples,
+                n_aovs: film.aovs.len() as u32,
+                features: film.wants_denoiser_features(),
+            },
+            pass: 0,
+            done: vec![false; grid.len()],
+            last_write: Instant::now(),
+        };
+        if let Some(resume) = settings.resume {
+            match checkpoint.read_file(&resume, film) {
+                Ok(()) => info!(
+                    "Resuming from {:?} at pass {} with {} of {} tiles done ...",
+                    resume,
+                    checkpoint.pass,
+                    checkpoint.done.iter().filter(|done| **done).count(),
+                    checkpoint.done.len()
+                ),
+                Err(err) => {
+                    warn!("Can't resume from {:?}: {}", resume, err);
+                    warn!("Rendering from scratch.");
+                    checkpoint.pass = 0;
+                    checkpoint.done = vec![false; grid.len()];
+                }
+            }
+        }
+        Some(checkpoint)
+    }
+    /// The pass to render (the number of passes once the render is
+    /// done).
+    pub fn pass(&self) -> i64 {
+        self.pass
+    }
+    /// Which tiles of **pass()** are merged into the film already.
+    pub fn done_tiles(&self) -> &[bool] {
+        &self.done
+    }
+    /// Records that the tile *index* of the current pass got merged
+    /// into the film, and writes a checkpoint if it's time to.
+    pub fn tile_done(&mut self, film: &Film, index: usize) {
+        self.done[index] = true;
+        if self.last_write.elapsed() >= self.interval {
+            self.write(film);
+        }
+    }
+    /// Continues with the next pass.
+    pub fn pass_done(&mut self) {
+        self.pass += 1;
+        self.done.iter_mut().for_each(|done| *done = false);
+    }
+    /// Writes a checkpoint now, errors get logged.
+    pub fn write(&mut self, film: &Film) {
+        info!("Writing checkpoint {:?} ...", self.filename);
+        if let Err(err) = self.write_file(film) {
+            error!("Can't write checkpoint {:?}: {}", self.filename, err);
+        }
+        self.last_write = Instant::now();
+    }
+    /// Writes to a temporary file first, which replaces the previous
+    /// checkpoint when it's complete. The file ends with a CRC-32 of
+    /// everything before it.
+    fn write_file(&self, film: &Film) -> Result<()> {
+        let tmp: String = format!("{}.tmp", self.filename);
+        {
+            let mut w = HashingWriter {
+                inner: BufWriter::new(File::create(&tmp)?),
+                hasher: Hasher::new(),
+            };
+            w.write_all(MAGIC)?;
+            w.write_u32::<LittleEndian>(VERSION)?;
+            self.layout.write(&mut w)?;
+            w.write_i64::<LittleEndian>(self.pass)?;
+            w.write_u32::<LittleEndian>(self.done.len() as u32)?;
+            for done in &self.done {
+                w.write_u8(*done as u8)?;
+            }
+            film.write_sums(&mut w)?;
+            let crc: u32 = w.hasher.clone().finalize();
+            w.inner.write_u32::<LittleEndian>(crc)?;
+            w.flush()?;
+        }
+        std::fs::rename(&tmp, &self.filename)
+    }
+    /// Restores the film (and the pass and its tiles) from a
+    /// checkpoint file. Neither gets touched unless the whole file is
+    /// valid.
+    fn read_file(&mut self, filename: &str, film: &Film) -> Result<()> {
+        let mut data: Vec<u8> = Vec::new();
+        BufReader::new(File::open(readable_path(filename)?)?).read_to_end(&mut data)?;
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+        if data.len() < 4 {
+            return Err(invalid("truncated checkpoint"));
+        }
+        let (content, mut crc) = data.split_at(data.len() - 4);
+        let mut r: &[u8] = content;
+        let mut magic: [u8; 8] = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || r.read_u32::<LittleEndian>()? != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not an rs_pbrt checkpoint (or another version)",
+            ));
+        }
+        if Layout::read(&mut r)? != self.layout {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the checkpoint belongs to another render (scene, sampler, or film settings)",
+            ));
+        }
+        let pass: i64 = r.read_i64::<LittleEndian>()?;
+        let n_tiles: usize = r.read_u32::<LittleEndian>()? as usize;
+        if n_tiles != self.done.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong number of tiles"));
+        }
+        let mut done: Vec<bool> = vec![false; n_tiles];
+        for done in done.iter_mut() {
+            *done = r.read_u8()? != 0;
+        }
+        if r.len() != film.sums_size() {
+            return Err(invalid("truncated checkpoint (or with trailing data)"));
+        }
+        let mut hasher: Hasher = Hasher::new();
+        hasher.update(content);
+        if hasher.finalize() != crc.read_u32::<LittleEndian>()? {
+            return Err(invalid("corrupted checkpoint (CRC mismatch)"));
+        }
+        film.read_sums(&mut r)?;
+        self.pass = pass;
+        self.done = done;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::film::FilmTile;
+    use crate::core::geometry::Point2f;
+    use crate::core::integrator::TileShape;
+    use crate::core::paramset::ParamSet;
+    use crate::core::pbrt::{Float, Spectrum};
+    use crate::filters::boxfilter::BoxFilter;
+    use std::sync::Arc;
+
+    fn film() -> Arc<Film> {
+        let mut params: ParamSet = ParamSet::default();
+        params.add_int(String::from("xresolution"), 8);
+        params.add_int(String::from("yresolution"), 8);
+        Film::create(&params, BoxFilter::create(&ParamSet::default()))
+    }
+
+    fn checkpoint(film: &Film, filename: &str) -> Checkpoint {
+        let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), TileShape::Square(4));
+        Checkpoint {
+            filename: String::from(filename),
+            interval: Duration::from_secs(60),
+            layout: Layout {
+                sample_bounds: bounds_array(&grid.sample_bounds),
+                pixel_bounds: bounds_array(&film.cropped_pixel_bounds),
+                tile_size: [grid.tile_size.x, grid.tile_size.y],
+                samples_per_pixel: 4,
+                pass_samples: 4,
+                n_aovs: 0,
+                features: false,
+            },
+            pass: 0,
+            done: vec![false; grid.len()],
+            last_write: Instant::now(),
+        }
+    }
+
+    fn sums(film: &Film) -> Vec<u8> {
+        let mut sums: Vec<u8> = Vec::new();
+        film.write_sums(&mut sums).unwrap();
+        sums
+    }
+
+    #[test]
+    fn resume_from_truncated_checkpoint() {
+        let filename: String = std::env::temp_dir()
+            .join(format!("rs_pbrt_checkpoint_{}.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        // a film with some samples, and half of its tiles done
+        let rendered: Arc<Film> = film();
+        let mut tile: FilmTile = rendered.get_film_tile(&rendered.get_sample_bounds());
+        for i in 0..8 {
+            let p_film: Point2f = Point2f {
+                x: i as Float + 0.5 as Float,
+                y: i as Float + 0.5 as Float,
+            };
+            tile.add_sample(p_film, &mut Spectrum::new(i as Float), 1.0 as Float);
+        }
+        rendered.merge_film_tile(&tile);
+        let mut written: Checkpoint = checkpoint(&rendered, &filename);
+        written.pass = 1;
+        written.done[0] = true;
+        written.done[1] = true;
+        written.write_file(&rendered).unwrap();
+        let data: Vec<u8> = std::fs::read(&filename).unwrap();
+        // the complete file restores everything
+        let resumed: Arc<Film> = film();
+        let mut restored: Checkpoint = checkpoint(&resumed, &filename);
+        restored.read_file(&filename, &resumed).unwrap();
+        assert_eq!(sums(&resumed), sums(&rendered));
+        assert_eq!(
+            (restored.pass, restored.done.clone()),
+            (1, written.done.clone())
+        );
+        // a truncated one (even just its last pixels) leaves the film untouched
+        let fresh: Vec<u8> = sums(&film());
+        for length in [data.len() - 1, data.len() - 4, data.len() / 2, 20] {
+            std::fs::write(&filename, &data[..length]).unwrap();
+            let resumed: Arc<Film> = film();
+            let mut restored: Checkpoint = checkpoint(&resumed, &filename);
+            assert!(restored.read_file(&filename, &resumed).is_err());
+            assert_eq!(sums(&resumed), fresh);
+            assert_eq!(restored.pass, 0);
+            assert!(restored.done.iter().all(|done| !done));
+        }
+        // so does a corrupted one
+        let mut corrupted: Vec<u8> = data.clone();
+        corrupted[data.len() - 12] ^= 1;
+        std::fs::write(&filename, &corrupted).unwrap();
+        let resumed: Arc<Film> = film();
+        let mut restored: Checkpoint = checkpoint(&resumed, &filename);
+        assert!(restored.read_file(&filename, &resumed).is_err());
+        assert_eq!(sums(&resumed), fresh);
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -0,0 +1,465 @@
+//! Compatibility layer for scenes written for pbrt-v4.
+//!
+//! pbrt-v4 renamed many plugins (e.g. *"diffuse"* instead of
+//! *"matte"*, *"conductor"* instead of *"metal"*) and some of their
+//! parameters. **convert_v4_params()** rewrites a parsed **ParamSet**
+//! in place so that it can be handed to the existing API functions.
+//! Names and parameters which pbrt-v3 already understands are left
+//! alone, so the conversion can be applied to every directive.
+//! Features without an equivalent are approximated (or ignored) with
+//! a warning.
+
+// others
+use log::warn;
+// pbrt
+use crate::core::paramset::{ParamSet, ParamSetItem};
+use crate::core::pbrt::{consts, Float, Spectrum};
+
+/// Named spectra of pbrt-v4 (e.g. *"metal-Au-eta"* or
+/// *"glass-BK7"*), approximated by their values at the red, green and
+/// blue wavelengths.
+pub fn named_spectrum(name: &str) -> Option<Spectrum> {
+    let rgb: [Float; 3] = match name {
+        "metal-Ag-eta" => [0.155, 0.117, 0.138],
+        "metal-Ag-k" => [4.828, 3.122, 2.147],
+        "metal-Al-eta" => [1.657, 0.880, 0.521],
+        "metal-Al-k" => [9.224, 6.270, 4.837],
+        "metal-Au-eta" => [0.143, 0.374, 1.442],
+        "metal-Au-k" => [3.983, 2.385, 1.603],
+        "metal-Cu-eta" => [0.200, 0.924, 1.102],
+        "metal-Cu-k" => [3.912, 2.452, 2.142],
+        "metal-CuZn-eta" => [0.444, 0.527, 1.094],
+        "metal-CuZn-k" => [3.695, 2.765, 1.829],
+        "metal-MgO-eta" => [1.732, 1.738, 1.750],
+        "metal-MgO-k" => [0.0, 0.0, 0.0],
+        "metal-TiO2-eta" => [2.554, 2.651, 2.869],
+        "metal-TiO2-k" => [0.0, 0.0, 0.0],
+        "glass-BK7" => [1.5145, 1.5185, 1.5253],
+        "glass-BAF10" => [1.6657, 1.6718, 1.6815],
+        "glass-FK51A" => [1.4849, 1.4871, 1.4907],
+        "glass-LASF9" => [1.8419, 1.8521, 1.8704],
+        "glass-F5" => [1.6680, 1.6762, 1.6903],
+        "glass-F10" => [1.7222, 1.7337, 1.7546],
+        "glass-F11" => [1.7768, 1.7904, 1.8155],
+        _ => return None,
+    };
+    Some(Spectrum::rgb(rgb[0], rgb[1], rgb[2]))
+}
+
+fn rename_items<T>(items: &mut [ParamSetItem<T>], from: &str, to: &str) -> bool {
+    let mut found: bool = false;
+    for item in items.iter_mut() {
+        if item.name == from {
+            item.name = String::from(to);
+            found = true;
+        }
+    }
+    found
+}
+
+fn has_items<T>(items: &[ParamSetItem<T>], name: &str) -> bool {
+    items.iter().any(|item| item.name == name)
+}
+
+/// Renames a parameter (of any type), returns **false** if there is
+/// no parameter called *from*.
+pub fn rename_param(params: &mut ParamSet, from: &str, to: &str) -> bool {
+    let mut found: bool = false;
+    found |= rename_items(&mut params.bools, from, to);
+    found |= rename_items(&mut params.ints, from, to);
+    found |= rename_items(&mut params.floats, from, to);
+    found |= rename_items(&mut params.point2fs, from, to);
+    found |= rename_items(&mut params.vector2fs, from, to);
+    found |= rename_items(&mut params.point3fs, from, to);
+    found |= rename_items(&mut params.vector3fs, from, to);
+    found |= rename_items(&mut params.normals, from, to);
+    found |= rename_items(&mut params.spectra, from, to);
+    found |= rename_items(&mut params.strings, from, to);
+    found |= rename_items(&mut params.textures, from, to);
+    found
+}
+
+/// Is there a parameter (of any type) called *name*?
+pub fn has_param(params: &ParamSet, name: &str) -> bool {
+    has_items(&params.bools, name)
+        || has_items(&params.ints, name)
+        || has_items(&params.floats, name)
+        || has_items(&params.point2fs, name)
+        || has_items(&params.vector2fs, name)
+        || has_items(&params.point3fs, name)
+        || has_items(&params.vector3fs, name)
+        || has_items(&params.normals, name)
+        || has_items(&params.spectra, name)
+        || has_items(&params.strings, name)
+        || has_items(&params.textures, name)
+}
+
+fn remove_float(params: &mut ParamSet, name: &str) -> Option<Float> {
+    let value: Option<Float> = params
+        .floats
+        .iter()
+        .find(|item| item.name == name && item.n_values == 1)
+        .map(|item| item.values[0]);
+    params.floats.retain(|item| item.name != name);
+    value
+}
+
+fn replace_string(params: &mut ParamSet, name: &str, value: &str) {
+    params.strings.retain(|item| item.name != name);
+    params.add_string(String::from(name), String::from(value));
+}
+
+fn replace_spectrum(params: &mut ParamSet, name: &str, value: Spectrum) {
+    params.erase_spectrum(String::from(name));
+    params.add_rgb_spectrum(String::from(name), value);
+}
+
+/// pbrt-v4 uses a single *"roughness"* (float or texture) where
+/// pbrt-v3 often expects *"uroughness"* and *"vroughness"*.
+fn split_roughness(params: &mut ParamSet, default: Float) {
+    if has_param(params, "uroughness") || has_param(params, "vroughness") {
+        return;
+    }
+    if let Some(tex) = params
+        .textures
+        .iter()
+        .find(|item| item.name == "roughness")
+        .map(|item| item.values[0].clone())
+    {
+        params.add_texture(String::from("uroughness"), tex.clone());
+        params.add_texture(String::from("vroughness"), tex);
+    } else {
+        let roughness: Float = params.find_one_float("roughness", default);
+        params.add_float(String::from("uroughness"), roughness);
+        params.add_float(String::from("vroughness"), roughness);
+    }
+}
+
+/// Converts a (constant) pbrt-v4 *"reflectance"* of a conductor
+/// into an equivalent complex index of refraction (with *eta* = 1).
+fn reflectance_to_eta_k(params: &mut ParamSet) {
+    if params
+        .textures
+        .iter()
+        .any(|item| item.name == "reflectance")
+    {
+        warn!("Textured \"reflectance\" of conductors is not supported. Ignoring it.");
+        return;
+    }
+    let r: Vec<Spectrum> = params.find_spectrum("reflectance");
+    if r.is_empty() {
+        return;
+    }
+    let r: Spectrum = r[0].clamp(0.0 as Float, 0.9999 as Float);
+    let mut k: Spectrum = Spectrum::new(0.0 as Float);
+    for i in 0..Spectrum::N_SAMPLES {
+        k[i] = 2.0 as Float * r[i].sqrt() / (1.0 as Float - r[i]).sqrt();
+    }
+    replace_spectrum(params, "eta", Spectrum::new(1.0 as Float));
+    replace_spectrum(params, "k", k);
+}
+
+fn warn_ignored(params: &ParamSet, names: &[&str]) {
+    for name in names {
+        if has_param(params, name) {
+            warn!(
+                "{} \"{}\": parameter \"{}\" is not supported. Ignoring it.",
+                params.key_word, params.name, name
+            );
+        }
+    }
+}
+
+/// Maps a pbrt-v4 material type (and its parameters) onto one of the
+/// existing materials.
+fn convert_material(mat_type: &str, params: &mut ParamSet) -> String {
+    // bump mapping is called displacement in pbrt-v4
+    rename_param(params, "displacement", "bumpmap");
+    warn_ignored(params, &["normalmap"]);
+    match mat_type {
+        "diffuse" => {
+            rename_param(params, "reflectance", "Kd");
+            String::from("matte")
+        }
+        "coateddiffuse" => {
+            // a dielectric coating on top of a diffuse base
+            rename_param(params, "reflectance", "Kd");
+            let eta: Float = params.find_one_float("eta", 1.5 as Float);
+            let f0: Float = ((eta - 1.0 as Float) / (eta + 1.0 as Float)).powi(2);
+            replace_spectrum(params, "Ks", Spectrum::new(f0));
+            split_roughness(params, 0.0 as Float);
+            warn_ignored(params, &["albedo", "g", "thickness"]);
+            String::from("substrate")
+        }
+        "coatedconductor" => {
+            warn!("Material \"coatedconductor\": ignoring the dielectric coating.");
+            for name in &["eta", "k", "roughness", "uroughness", "vroughness"] {
+                let from: String = String::from("conductor.") + name;
+                rename_param(params, &from, name);
+            }
+            reflectance_to_eta_k(params);
+            if !has_param(params, "roughness") {
+                params.add_float(String::from("roughness"), 0.0 as Float);
+            }
+            String::from("metal")
+        }
+        "conductor" => {
+            reflectance_to_eta_k(params);
+            if !has_param(params, "roughness") {
+                params.add_float(String::from("roughness"), 0.0 as Float);
+            }
+            String::from("metal")
+        }
+        "dielectric" | "thindielectric" => {
+            if mat_type == "thindielectric" {
+                warn!("Material \"thindielectric\": approximated by \"glass\".");
+            }
+            // glass only knows a single index of refraction
+            let eta: Vec<Spectrum> = params.find_spectrum("eta");
+            if !eta.is_empty() {
+                let eta: Float = eta[0].average();
+                params.erase_spectrum(String::from("eta"));
+                params.add_float(String::from("eta"), eta);
+            }
+            split_roughness(params, 0.0 as Float);
+            String::from("glass")
+        }
+        "diffusetransmission" => {
+            rename_param(params, "reflectance", "reflect");
+            rename_param(params, "transmittance", "transmit");
+            for name in &["reflect", "transmit"] {
+                if !has_param(params, name) {
+                    params.add_rgb_spectrum(String::from(*name), Spectrum::new(0.25 as Float));
+                }
+            }
+            replace_spectrum(params, "Kd", Spectrum::new(1.0 as Float));
+            replace_spectrum(params, "Ks", Spectrum::new(0.0 as Float));
+            String::from("translucent")
+        }
+        "mix" => {
+            let materials: Vec<String> = params.find_string("materials");
+            if materials.len() == 2 {
+                // pbrt-v4 weights the second material with "amount",
+                // pbrt-v3 the first one
+                params.add_string(String::from("namedmaterial1"), materials[1].clone());
+                params.add_string(String::from("namedmaterial2"), materials[0].clone());
+                if let Some(amount) = remove_float(params, "amount") {
+                    replace_spectrum(params, "amount", Spectrum::new(amount));
+                } else if params.textures.iter().any(|item| item.name == "amount") {
+                    warn!("Material \"mix\": float texture \"amount\" not supported. Using 0.5.");
+                    params.textures.retain(|item| item.name != "amount");
+                }
+            }
+            String::from("mix")
+        }
+        "interface" => String::from("none"),
+        "measured" => {
+            warn!("Material \"measured\" not supported. Using \"matte\".");
+            String::from("matte")
+        }
+        "subsurface" => {
+            warn_ignored(params, &["reflectance"]);
+            split_roughness(params, 0.0 as Float);
+            String::from("subsurface")
+        }
+        _ => String::from(mat_type),
+    }
+}
+
+fn convert_light(params: &mut ParamSet) {
+    let name: String = params.name.clone();
+    // "scale" is a float in pbrt-v4
+    if let Some(scale) = remove_float(params, "scale") {
+        replace_spectrum(params, "scale", Spectrum::new(scale));
+    }
+    if name == "goniometric" || name == "projection" || name == "infinite" {
+        rename_param(params, "filename", "mapname");
+    }
+    let scale: Spectrum = params.find_one_spectrum("scale", Spectrum::new(1.0 as Float));
+    let power: Option<Float> = remove_float(params, "power");
+    let illuminance: Option<Float> = remove_float(params, "illuminance");
+    if name == "point" || name == "spot" {
+        if let Some(power) = power {
+            let i: Float = params
+                .find_one_spectrum("I", Spectrum::new(1.0 as Float))
+                .y();
+            // solid angle the light emits into
+            let solid_angle: Float = if name == "spot" {
+                let cone_angle: Float = params.find_one_float("coneangle", 30.0 as Float);
+                let cone_delta: Float = params.find_one_float("conedeltaangle", 5.0 as Float);
+                let cos_end: Float = cone_angle.to_radians().cos();
+                let cos_start: Float = (cone_angle - cone_delta).to_radians().cos();
+                2.0 as Float
+                    * consts::PI
+                    * (1.0 as Float - 0.5 as Float * (cos_start + cos_end))
+            } else {
+                4.0 as Float * consts::PI
+            };
+            if i > 0.0 as Float && solid_angle > 0.0 as Float {
+                replace_spectrum(params, "scale", scale * (power / (i * solid_angle)));
+            }
+        }
+    } else if name == "distant" {
+        if let Some(illuminance) = illuminance {
+            let l: Float = params
+                .find_one_spectrum("L", Spectrum::new(1.0 as Float))
+                .y();
+            if l > 0.0 as Float {
+                replace_spectrum(params, "scale", scale * (illuminance / l));
+            }
+        }
+    } else if name == "infinite" {
+        if has_param(params, "mapname") {
+            warn!(
+                "LightSource \"infinite\": pbrt-v4 environment maps use an equal-area mapping, pbrt-v3 expects a lat-long map."
+            );
+            if illuminance.is_some() {
+                warn!("LightSource \"infinite\": ignoring \"illuminance\" of an environment map.");
+            }
+        } else if let Some(illuminance) = illuminance {
+            // a constant environment illuminates an upward facing
+            // surface with pi * L
+            let l: Float = params
+                .find_one_spectrum("L", Spectrum::new(1.0 as Float))
+                .y();
+            if l > 0.0 as Float {
+                replace_spectrum(
+                    params,
+                    "scale",
+                    scale * (illuminance / (consts::PI * l)),
+                );
+            }
+        }
+        warn_ignored(params, &["portal"]);
+    } else if power.is_some() {
+        warn!("{} \"{}\": ignoring \"power\".", params.key_word, name);
+    }
+    if params.key_word == "AreaLightSource" {
+        warn_ignored(params, &["filename"]);
+    }
+}
+
+fn convert_shape(params: &mut ParamSet) {
+    if params.name == "bilinearmesh" {
+        // two triangles per bilinear patch
+        let n_points: usize = params.find_point3f("P").len();
+        let mut indices: Vec<i32> = params.find_int("indices");
+        if indices.is_empty() && n_points == 4 {
+            indices = vec![0, 1, 2, 3];
+        }
+        let mut triangles: Vec<i32> = Vec::with_capacity(indices.len() / 4 * 6);
+        for quad in indices.chunks_exact(4) {
+            triangles.extend_from_slice(&[quad[0], quad[1], quad[3], quad[0], quad[3], quad[2]]);
+        }
+        params.ints.retain(|item| item.name != "indices");
+        params.add_ints(String::from("indices"), triangles);
+        warn!("Shape \"bilinearmesh\": patches are split into triangles.");
+        params.name = String::from("trianglemesh");
+    } else if params.name == "trianglemesh"
+        && !has_param(params, "indices")
+        && params.find_point3f("P").len() == 3
+    {
+        params.add_ints(String::from("indices"), vec![0, 1, 2]);
+    }
+}
+
+fn convert_texture(params: &mut ParamSet) {
+    if params.tex_name == "scale" && !has_param(params, "tex1") && !has_param(params, "tex2") {
+        rename_param(params, "tex", "tex1");
+        rename_param(params, "scale", "tex2");
+    } else if params.tex_name == "directionmix" {
+        warn!("Texture \"directionmix\" not supported. Using \"mix\".");
+        params.tex_name = String::from("mix");
+    } else if params.tex_name == "imagemap" {
+        let encoding: String = params.find_one_string("encoding", String::new());
+        if encoding == "linear" {
+            params.add_bool(String::from("gamma"), false);
+        } else if encoding.starts_with("gamma") {
+            warn!(
+                "Texture \"imagemap\": encoding {:?} approximated by sRGB.",
+                encoding
+            );
+        }
+        let filter: String = params.find_one_string("filter", String::new());
+        if filter == "bilinear" || filter == "trilinear" {
+            params.add_bool(String::from("trilinear"), true);
+        }
+    }
+}
+
+/// Rewrites the parameters of a pbrt-v4 directive (in
+/// *params.key_word*) so they can be passed to the pbrt-v3 API
+/// functions. Parameters of pbrt-v3 scenes don't get changed.
+pub fn convert_v4_params(params: &mut ParamSet) {
+    match params.key_word.as_str() {
+        "Camera" if params.name == "spherical" => {
+            let mapping: String = params.find_one_string("mapping", String::from("equalarea"));
+            if mapping != "equirectangular" {
+                warn!(
+                    "Camera \"spherical\": {:?} mapping not supported. Using \"equirectangular\".",
+                    mapping
+                );
+            }
+            params.name = String::from("environment");
+        }
+        "Film" if params.name == "rgb" || params.name == "gbuffer" || params.name == "spectral" => {
+            if params.name != "rgb" {
+                warn!("Film \"{}\": writing an RGB image only.", params.name);
+            }
+            params.name = String::from("image");
+            rename_param(params, "maxcomponentvalue", "maxsampleluminance");
+            if let Some(iso) = remove_float(params, "iso") {
+                let scale: Float = params.find_one_float("scale", 1.0 as Float);
+                remove_float(params, "scale");
+                params.add_float(String::from("scale"), scale * iso / 100.0 as Float);
+            }
+            warn_ignored(params, &["sensor", "whitebalance"]);
+        }
+        "Sampler" => {
+            let name: &str = match params.name.as_str() {
+                "independent" => "random",
+                "zsobol" | "paddedsobol" => "sobol",
+                _ => return,
+            };
+            warn!("Sampler \"{}\": using \"{}\".", params.name, name);
+            params.name = String::from(name);
+        }
+        "Integrator" => {
+            let name: &str = match params.name.as_str() {
+                "simplepath" | "randomwalk" | "lightpath" | "aov" => "path",
+                "simplevolpath" => "volpath",
+                _ => return,
+            };
+            warn!("Integrator \"{}\": using \"{}\".", params.name, name);
+            params.name = String::from(name);
+        }
+        "LightSource" | "AreaLightSource" => convert_light(params),
+        "Material" => {
+            let mat_type: String = params.name.clone();
+            params.name = convert_material(&mat_type, params);
+        }
+        "MakeNamedMaterial" => {
+            let mat_type: String = params.find_one_string("type", String::new());
+            let converted: String = convert_material(&mat_type, params);
+            if converted != mat_type {
+                replace_string(params, "type", &converted);
+            }
+        }
+        "MakeNamedMedium" => {
+            let medium_type: String = params.find_one_string("type", String::new());
+            if medium_type == "uniformgrid" {
+                replace_string(params, "type", "heterogeneous");
+            } else if medium_type == "rgbgrid" || medium_type == "cloud" || medium_type == "nanovdb"
+            {
+                warn!(
+                    "Medium {:?} not supported. Using \"homogeneous\".",
+                    medium_type
+                );
+                replace_string(params, "type", "homogeneous");
+            }
+        }
+        "Shape" => convert_shape(params),
+        "Texture" => convert_texture(params),
+        _ => {}
+    }
+}
@@ -0,0 +1,166 @@
+//! Writes deep scanline (uncompressed, 32-bit float) OpenEXR files.
+//!
+//! Each pixel of a deep image holds a list of samples, sorted front to
+//! back, each with a depth (*Z*), a coverage (*A*), and a color
+//! premultiplied by it (*R*, *G*, *B*). Compositors merge deep images
+//! of several renders sample by sample (e.g. for holdouts or volumes),
+//! and composite them front to back (*over*) to get a flat image.
+//!
+//! Unlike tiled files (see **TiledExrWriter**), each scan line is a
+//! chunk of its own, starting with the (cumulative) number of samples
+//! of its pixels, followed by the samples of all pixels, one channel
+//! after the other.
+
+// std
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+// others
+use byteorder::{LittleEndian, WriteBytesExt};
+// pbrt
+use crate::core::geometry::{Bounds2i, Point2i};
+use crate::core::pbrt::Float;
+use crate::core::tiledexr::{box2i, write_attribute};
+
+const MAGIC: u32 = 20_000_630;
+/// file format version 2, single part, deep ("non-image") data
+const VERSION: u32 = 2 | 0x800;
+/// pixel type FLOAT (see ImfPixelType.h)
+const PIXEL_TYPE_FLOAT: i32 = 2;
+/// line order INCREASING_Y
+const LINE_ORDER_INCREASING_Y: u8 = 0;
+
+/// One sample of a deep pixel.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DeepSample {
+    /// distance from the camera
+    pub z: Float,
+    /// linear RGB, premultiplied by *alpha*
+    pub rgb: [Float; 3],
+    pub alpha: Float,
+}
+
+/// Writes a deep image, *pixels* holds the samples (front to back) of
+/// the *data_window* pixels row by row. The *metadata* becomes string
+/// attributes of the header.
+pub fn write_deep_exr(
+    filename: &str,
+    resolution: Point2i,
+    data_window: Bounds2i,
+    pixels: &[Vec<DeepSample>],
+    metadata: &[(String, String)],
+) -> Result<()> {
+    let width: usize = (data_window.p_max.x - data_window.p_min.x).max(0) as usize;
+    let height: usize = (data_window.p_max.y - data_window.p_min.y).max(0) as usize;
+    let mut header: Vec<u8> = Vec::new();
+    header.write_u32::<LittleEndian>(MAGIC)?;
+    header.write_u32::<LittleEndian>(VERSION)?;
+    // channels (sorted by name)
+    let mut channels: Vec<u8> = Vec::new();
+    for name in &["A", "B", "G", "R", "Z"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.write_i32::<LittleEndian>(PIXEL_TYPE_FLOAT)?;
+        // pLinear and reserved
+        channels.extend_from_slice(&[0_u8; 4]);
+        // x and y sampling
+        channels.write_i32::<LittleEndian>(1)?;
+        channels.write_i32::<LittleEndian>(1)?;
+    }
+    channels.push(0);
+    write_attribute(&mut header, "channels", "chlist", &channels)?;
+    write_attribute(
+        &mut header,
+        "chunkCount",
+        "int",
+        &(height as i32).to_le_bytes(),
+    )?;
+    // no compression (one scan line per chunk)
+    write_attribute(&mut header, "compression", "compression", &[0_u8])?;
+    let data: Bounds2i = Bounds2i {
+        p_min: data_window.p_min,
+        p_max: Point2i {
+            x: data_window.p_max.x - 1,
+            y: data_window.p_max.y - 1,
+        },
+    };
+    write_attribute(&mut header, "dataWindow", "box2i", &box2i(&data)?)?;
+    let display: Bounds2i = Bounds2i {
+        p_min: Point2i { x: 0, y: 0 },
+        p_max: Point2i {
+            x: resolution.x - 1,
+            y: resolution.y - 1,
+        },
+    };
+    write_attribute(&mut header, "displayWindow", "box2i", &box2i(&display)?)?;
+    write_attribute(
+        &mut header,
+        "lineOrder",
+        "lineOrder",
+        &[LINE_ORDER_INCREASING_Y],
+    )?;
+    write_attribute(&mut header, "name", "string", b"deep")?;
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    )?;
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0_u8; 8])?;
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    )?;
+    write_attribute(&mut header, "type", "string", b"deepscanline")?;
+    write_attribute(&mut header, "version", "int", &1_i32.to_le_bytes())?;
+    for (name, value) in metadata {
+        write_attribute(&mut header, name, "string", value.as_bytes())?;
+    }
+    header.push(0);
+    // chunks follow the offset table
+    let mut chunks: Vec<Vec<u8>> = Vec::with_capacity(height);
+    for y in 0..height {
+        let row: &[Vec<DeepSample>] = &pixels[y * width..(y + 1) * width];
+        let n_samples: usize = row.iter().map(|samples| samples.len()).sum();
+        let mut counts: Vec<u8> = Vec::with_capacity(4 * width);
+        let mut total: i32 = 0;
+        for samples in row {
+            total += samples.len() as i32;
+            counts.write_i32::<LittleEndian>(total)?;
+        }
+        let mut data: Vec<u8> = Vec::with_capacity(4 * 5 * n_samples);
+        for channel in 0..5 {
+            for sample in row.iter().flatten() {
+                let value: Float = match channel {
+                    0 => sample.alpha,
+                    1 => sample.rgb[2],
+                    2 => sample.rgb[1],
+                    3 => sample.rgb[0],
+                    _ => sample.z,
+                };
+                data.write_f32::<LittleEndian>(value as f32)?;
+            }
+        }
+        let mut chunk: Vec<u8> = Vec::with_capacity(28 + counts.len() + data.len());
+        chunk.write_i32::<LittleEndian>(data_window.p_min.y + y as i32)?;
+        chunk.write_u64::<LittleEndian>(counts.len() as u64)?;
+        // packed and unpacked size of the samples
+        chunk.write_u64::<LittleEndian>(data.len() as u64)?;
+        chunk.write_u64::<LittleEndian>(data.len() as u64)?;
+        chunk.extend_from_slice(&counts);
+        chunk.extend_from_slice(&data);
+        chunks.push(chunk);
+    }
+    let mut file: BufWriter<File> = BufWriter::new(File::create(filename)?);
+    file.write_all(&header)?;
+    let mut offset: u64 = (header.len() + 8 * height) as u64;
+    for chunk in &chunks {
+        file.write_u64::<LittleEndian>(offset)?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in &chunks {
+        file.write_all(chunk)?;
+    }
+    file.flush()
+}
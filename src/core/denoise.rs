@@ -0,0 +1,92 @@
+//! Denoising of the final image with Intel Open Image Denoise.
+//!
+//! A film with *denoise* (or **rs_pbrt --denoise**) replaces the
+//! image by the output of the *RT* filter of Open Image Denoise
+//! before writing it. The filter works much better with the albedo
+//! and normal of each pixel (see *denoiserfeatures* of **Film**),
+//! which the sampler integrators provide, for the others (e.g.
+//! **bdpt**) it only gets the image.
+//!
+//! It needs the feature *denoise* and the Open Image Denoise library
+//! (version 2), which is found by *pkg-config*, or the environment
+//! variable *OIDN_DIR* points to the directory it was installed to
+//! (with the library in its *lib* subdirectory) when building:
+//!
+//! ```shell
+//! > OIDN_DIR=/opt/oidn cargo build --release --features denoise
+//! ```
+//!
+//! Without the feature the image is written as rendered.
+
+// others
+#[cfg(not(feature = "denoise"))]
+use log::warn;
+#[cfg(feature = "denoise")]
+use log::{error, info};
+// pbrt
+use crate::core::pbrt::Float;
+
+/// Denoises a (linear, HDR) RGB image of *width* x *height* pixels,
+/// optionally guided by the albedo and (shading) normal of each pixel
+/// (same layout). Returns **None** if denoising failed.
+#[cfg(feature = "denoise")]
+pub fn denoise_image(
+    width: usize,
+    height: usize,
+    color: &[Float],
+    features: Option<(&[Float], &[Float])>,
+) -> Option<Vec<Float>> {
+    info!(
+        "Denoising image ({}x{}, {}) ...",
+        width,
+        height,
+        if features.is_some() {
+            "with albedo and normal"
+        } else {
+            "color only"
+        }
+    );
+    match run_filter(width, height, color, features) {
+        Ok(output) => Some(output.iter().map(|v| *v as Float).collect()),
+        Err(err) => {
+            error!("Open Image Denoise failed: {}", err);
+            None
+        }
+    }
+}
+
+/// Runs the *RT* filter of Open Image Denoise (on the default device).
+#[cfg(feature = "denoise")]
+fn run_filter(
+    width: usize,
+    height: usize,
+    color: &[Float],
+    features: Option<(&[Float], &[Float])>,
+) -> Result<Vec<f32>, oidn::Error> {
+    let to_f32 = |values: &[Float]| -> Vec<f32> { values.iter().map(|v| *v as f32).collect() };
+    let device: oidn::Device = oidn::Device::new()?;
+    let mut filter: oidn::RayTracing = oidn::RayTracing::try_new(&device)?;
+    filter.hdr(true).image_dimensions(width, height);
+    if let Some((albedo, normal)) = features {
+        // the filter expects albedos in [0, 1], one sample estimates
+        // can be a bit off
+        let albedo: Vec<f32> = albedo.iter().map(|a| (*a as f32).clamp(0.0, 1.0)).collect();
+        filter.albedo_normal(&albedo, &to_f32(normal));
+    }
+    let mut output: Vec<f32> = vec![0.0_f32; color.len()];
+    filter.filter(&to_f32(color), &mut output)?;
+    device.get_error()?;
+    Ok(output)
+}
+
+/// Without the feature *denoise* the image can't be denoised.
+#[cfg(not(feature = "denoise"))]
+pub fn denoise_image(
+    _width: usize,
+    _height: usize,
+    _color: &[Float],
+    _features: Option<(&[Float], &[Float])>,
+) -> Option<Vec<Float>> {
+    warn!("Denoising needs rs_pbrt built with the feature \"denoise\", writing the noisy image.");
+    None
+}
@@ -0,0 +1,614 @@
+//! Distributed rendering over TCP.
+//!
+//! A *coordinator* parses the scene like any other render, listens
+//! for *workers*, and renders tiles itself while it waits for
+//! them. Each worker parses the same scene (all machines need the
+//! scene file and the files it uses), connects to the coordinator,
+//! and asks for work. The coordinator hands out batches of tile
+//! indices (the samples are seeded per pixel, see
+//! **SamplerIntegrator::render_tile()**), the worker renders them and
+//! sends the accumulated pixels back, where they get merged into the
+//! film. Tiles of a worker which disconnects are rendered by someone
+//! else, so the image is the same as the one of a local render.
+//!
+//! Every message is framed by its length (a little-endian **u32**, at
+//! most **MAX_FRAME_SIZE**) and starts with a one byte tag:
+//!
+//! - **HELLO** (worker): magic, protocol version, sample bounds, tile
+//!   width and height, samples per pixel, spectrum size, film buffers
+//!   (number of AOVs, *deep*, denoiser features), and number of
+//!   threads
+//! - **TILES** (coordinator): the indices of the tiles to render
+//! - **TILE** (worker): the index, pixel bounds, pixels, and the AOV,
+//!   deep, and denoiser feature sums of a tile
+//! - **DONE** (coordinator): there is nothing left to render
+//!
+//! Only integrators which render tile by tile (**SamplerIntegrator**)
+//! can be distributed.
+
+// std
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+// others
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::{info, warn};
+// pbrt
+use crate::core::film::{Film, FilmTile};
+use crate::core::geometry::{Bounds2i, Point2i};
+use crate::core::integrator::{
+    is_render_cancelled, tile_shape, Integrator, SamplerIntegrator, TileGrid,
+};
+use crate::core::memory::MemoryArena;
+use crate::core::numa;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
+use crate::core::sampler::Sampler;
+use crate::core::scene::Scene;
+
+const MAGIC: &[u8; 8] = b"RSPBRTDR";
+const VERSION: u32 = 3;
+/// Larger frames are refused (a tile of a *deep* film with many
+/// samples per pixel is the largest message).
+pub const MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+/// a worker gets this many tiles per thread at once
+const TILES_PER_THREAD: usize = 2;
+/// how often idle threads check for new work (or workers)
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const HELLO: u8 = 1;
+const TILES: u8 = 2;
+const TILE: u8 = 3;
+const DONE: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DistributedRole {
+    /// listen for workers on this address (e.g. 0.0.0.0:7878)
+    Coordinator(String),
+    /// render tiles for the coordinator at this address
+    Worker(String),
+}
+
+/// Renders the scene as coordinator or worker. Integrators which
+/// don't render tile by tile are rendered locally by a coordinator
+/// and not at all by a worker.
+pub fn render_distributed(
+    integrator: &mut Integrator,
+    scene: &Scene,
+    num_threads: u8,
+    role: &DistributedRole,
+) {
+    match (integrator, role) {
+        (Integrator::Sampler(integrator), DistributedRole::Coordinator(address)) => {
+            render_coordinator(integrator, scene, num_threads, address)
+        }
+        (Integrator::Sampler(integrator), DistributedRole::Worker(address)) => {
+            render_worker(integrator, scene, num_threads, address)
+        }
+        (integrator, DistributedRole::Coordinator(_)) => {
+            warn!("The integrator can't be distributed, rendering locally.");
+            integrator.render(scene, num_threads);
+        }
+        (_, DistributedRole::Worker(_)) => {
+            warn!("The integrator can't be distributed, nothing to do.");
+        }
+    }
+}
+
+/// What a worker renders, it has to match the coordinator's scene.
+#[derive(Debug, Clone)]
+struct Hello {
+    sample_bounds: [i32; 4],
+    tile_size: [i32; 2],
+    samples_per_pixel: i64,
+    spectrum_size: u32,
+    /// number of AOVs, *deep* (0 or 1), and denoiser features (0 or 1)
+    film_buffers: [u32; 3],
+    threads: u32,
+}
+
+impl Hello {
+    fn new(grid: &TileGrid, sampler: &Sampler, film: &Film, threads: usize) -> Self {
+        let b: Bounds2i = grid.sample_bounds;
+        Hello {
+            sample_bounds: [b.p_min.x, b.p_min.y, b.p_max.x, b.p_max.y],
+            tile_size: [grid.tile_size.x, grid.tile_size.y],
+            samples_per_pixel: sampler.get_samples_per_pixel(),
+            spectrum_size: Spectrum::default().c.len() as u32,
+            film_buffers: [
+                film.aovs.len() as u32,
+                film.is_deep() as u32,
+                film.wants_denoiser_features() as u32,
+            ],
+            threads: threads as u32,
+        }
+    }
+    fn matches(&self, other: &Hello) -> bool {
+        self.sample_bounds == other.sample_bounds
+            && self.tile_size == other.tile_size
+            && self.samples_per_pixel == other.samples_per_pixel
+            && self.spectrum_size == other.spectrum_size
+            && self.film_buffers == other.film_buffers
+    }
+    fn to_payload(&self) -> Result<Vec<u8>> {
+        let mut w: Vec<u8> = vec![HELLO];
+        w.write_all(MAGIC)?;
+        w.write_u32::<LittleEndian>(VERSION)?;
+        for v in &self.sample_bounds {
+            w.write_i32::<LittleEndian>(*v)?;
+        }
+        for v in &self.tile_size {
+            w.write_i32::<LittleEndian>(*v)?;
+        }
+        w.write_i64::<LittleEndian>(self.samples_per_pixel)?;
+        w.write_u32::<LittleEndian>(self.spectrum_size)?;
+        for v in &self.film_buffers {
+            w.write_u32::<LittleEndian>(*v)?;
+        }
+        w.write_u32::<LittleEndian>(self.threads)?;
+        Ok(w)
+    }
+    fn from_payload(payload: &[u8]) -> Result<Hello> {
+        let mut r: &[u8] = expect_tag(payload, HELLO)?;
+        let mut magic: [u8; 8] = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || r.read_u32::<LittleEndian>()? != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not an rs_pbrt worker (or another version)",
+            ));
+        }
+        let mut sample_bounds: [i32; 4] = [0; 4];
+        r.read_i32_into::<LittleEndian>(&mut sample_bounds)?;
+        let mut tile_size: [i32; 2] = [0; 2];
+        r.read_i32_into::<LittleEndian>(&mut tile_size)?;
+        let samples_per_pixel: i64 = r.read_i64::<LittleEndian>()?;
+        let spectrum_size: u32 = r.read_u32::<LittleEndian>()?;
+        let mut film_buffers: [u32; 3] = [0; 3];
+        r.read_u32_into::<LittleEndian>(&mut film_buffers)?;
+        Ok(Hello {
+            sample_bounds,
+            tile_size,
+            samples_per_pixel,
+            spectrum_size,
+            film_buffers,
+            threads: r.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+fn frame_too_large() -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("frame larger than {} bytes", MAX_FRAME_SIZE),
+    )
+}
+
+pub(crate) fn write_frame<W: Write>(w: &mut W, payload: &[u8]) -> Result<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(frame_too_large());
+    }
+    w.write_u32::<LittleEndian>(payload.len() as u32)?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+pub(crate) fn read_frame<R: Read>(r: &mut R) -> Result<Vec<u8>> {
+    let len: usize = r.read_u32::<LittleEndian>()? as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(frame_too_large());
+    }
+    let mut payload: Vec<u8> = vec![0_u8; len];
+    r.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// Returns the payload after the tag.
+fn expect_tag(payload: &[u8], tag: u8) -> Result<&[u8]> {
+    match payload.split_first() {
+        Some((first, rest)) if *first == tag => Ok(rest),
+        _ => Err(Error::new(ErrorKind::InvalidData, "unexpected message")),
+    }
+}
+
+fn tiles_payload(tiles: &[usize]) -> Result<Vec<u8>> {
+    let mut w: Vec<u8> = vec![TILES];
+    w.write_u32::<LittleEndian>(tiles.len() as u32)?;
+    for index in tiles {
+        w.write_u32::<LittleEndian>(*index as u32)?;
+    }
+    Ok(w)
+}
+
+fn read_tiles(payload: &[u8], grid: &TileGrid) -> Result<Vec<usize>> {
+    let mut r: &[u8] = expect_tag(payload, TILES)?;
+    let n: usize = r.read_u32::<LittleEndian>()? as usize;
+    let mut tiles: Vec<usize> = Vec::with_capacity(n);
+    for _ in 0..n {
+        let index: usize = r.read_u32::<LittleEndian>()? as usize;
+        if index >= grid.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "tile index out of range",
+            ));
+        }
+        tiles.push(index);
+    }
+    Ok(tiles)
+}
+
+// the casts are needed if Float is f64
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn tile_payload(index: usize, tile: &FilmTile) -> Result<Vec<u8>> {
+    let mut w: Vec<u8> = vec![TILE];
+    w.write_u32::<LittleEndian>(index as u32)?;
+    let b: Bounds2i = tile.pixel_bounds;
+    for v in &[b.p_min.x, b.p_min.y, b.p_max.x, b.p_max.y] {
+        w.write_i32::<LittleEndian>(*v)?;
+    }
+    // single precision is enough for the pixel sums
+    for pixel in tile.get_pixels() {
+        for c in pixel.contrib_sum.c.iter() {
+            w.write_f32::<LittleEndian>(*c as f32)?;
+        }
+        w.write_f32::<LittleEndian>(pixel.filter_weight_sum as f32)?;
+        w.write_f32::<LittleEndian>(pixel.alpha_sum as f32)?;
+    }
+    tile.write_buffers(&mut w)?;
+    Ok(w)
+}
+
+/// Returns the tile index and the tile (ready to be merged into the
+/// film).
+pub(crate) fn read_tile<'a>(
+    payload: &[u8],
+    grid: &TileGrid,
+    film: &'a Film,
+) -> Result<(usize, FilmTile<'a>)> {
+    let mut r: &[u8] = expect_tag(payload, TILE)?;
+    let index: usize = r.read_u32::<LittleEndian>()? as usize;
+    if index >= grid.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "tile index out of range",
+        ));
+    }
+    let mut tile: FilmTile = film.get_film_tile(&grid.tile_bounds(grid.tile(index)));
+    let mut bounds: [i32; 4] = [0; 4];
+    r.read_i32_into::<LittleEndian>(&mut bounds)?;
+    let b: Bounds2i = tile.pixel_bounds;
+    if bounds != [b.p_min.x, b.p_min.y, b.p_max.x, b.p_max.y] {
+        return Err(Error::new(ErrorKind::InvalidData, "tile bounds differ"));
+    }
+    for pixel in tile.get_pixels_mut() {
+        for c in pixel.contrib_sum.c.iter_mut() {
+            *c = r.read_f32::<LittleEndian>()? as Float;
+        }
+        pixel.filter_weight_sum = r.read_f32::<LittleEndian>()? as Float;
+        pixel.alpha_sum = r.read_f32::<LittleEndian>()? as Float;
+    }
+    tile.read_buffers(&mut r)?;
+    if !r.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "tile buffers differ"));
+    }
+    Ok((index, tile))
+}
+
+/// The tiles which weren't rendered (or handed out) yet.
+struct TileQueue {
+    pending: Mutex<VecDeque<usize>>,
+    remaining: AtomicUsize,
+}
+
+impl TileQueue {
+    fn new(n_tiles: usize) -> Self {
+        TileQueue {
+            pending: Mutex::new((0..n_tiles).collect()),
+            remaining: AtomicUsize::new(n_tiles),
+        }
+    }
+    fn take(&self, n: usize) -> Vec<usize> {
+        let mut pending = self.pending.lock().unwrap();
+        let n: usize = std::cmp::min(n, pending.len());
+        pending.drain(..n).collect()
+    }
+    /// Tiles of a worker which disconnected get rendered by someone
+    /// else.
+    fn give_back(&self, tiles: &[usize]) {
+        self.pending.lock().unwrap().extend(tiles);
+    }
+    /// A tile was merged into the film.
+    fn finish(&self) {
+        self.remaining.fetch_sub(1, Ordering::SeqCst);
+    }
+    fn is_finished(&self) -> bool {
+        self.remaining.load(Ordering::SeqCst) == 0 || is_render_cancelled()
+    }
+}
+
+fn number_of_cores(num_threads: u8) -> usize {
+    if num_threads == 0_u8 {
+        num_cpus::get()
+    } else {
+        num_threads as usize
+    }
+}
+
+/// Renders tiles locally and accepts workers (on *address*) until
+/// all tiles are merged into the film, then writes the image.
+pub fn render_coordinator(
+    integrator: &mut SamplerIntegrator,
+    scene: &Scene,
+    num_threads: u8,
+    address: &str,
+) {
+    let listener: TcpListener = match TcpListener::bind(address)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!(
+                "Unable to listen on {:?} ({}), rendering locally.",
+                address, err
+            );
+            integrator.render(scene, num_threads);
+            return;
+        }
+    };
+    integrator.preprocess(scene);
+    let film = integrator.get_camera().get_film();
+    let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), tile_shape());
+    let hello: Hello = Hello::new(&grid, integrator.get_sampler(), &film, 0);
+    let queue: TileQueue = TileQueue::new(grid.len());
+    let num_cores: usize = number_of_cores(num_threads);
+    info!(
+        "Rendering with {:?} thread(s), waiting for workers on {} ...",
+        num_cores, address
+    );
+    let progress: Mutex<ProgressReporter> = Mutex::new(ProgressReporter::new(
+        grid.len() as u64,
+        "Rendering",
+        "tiles",
+    ));
+    {
+        let integrator: &SamplerIntegrator = integrator;
+        let sampler: &Sampler = integrator.get_sampler();
+        let film: &Film = &film;
+        let grid: &TileGrid = &grid;
+        let hello: &Hello = &hello;
+        let queue: &TileQueue = &queue;
+        let progress = &progress;
+        let listener: &TcpListener = &listener;
+        crossbeam::scope(|scope| {
+            // spawn local render threads
+            for thread_index in 0..num_cores {
+                scope.spawn(move |_| {
+                    numa::pin_render_thread(thread_index);
+                    let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+                    let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+                    while !queue.is_finished() {
+                        if let Some(index) = queue.take(1).pop() {
+                            let film_tile = integrator.render_tile(
+                                scene,
+                                grid,
+                                grid.tile(index),
+                                film,
+                                &mut tile_sampler,
+                                &mut arena,
+                            );
+                            film.merge_film_tile(&film_tile);
+                            queue.finish();
+                            progress.lock().unwrap().update(1);
+                        } else {
+                            // the remaining tiles are rendered by workers
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                    }
+                });
+            }
+            // accept workers until all tiles are rendered
+            scope.spawn(move |scope| {
+                while !queue.is_finished() {
+                    match listener.accept() {
+                        Ok((stream, peer)) => {
+                            scope.spawn(move |_| {
+                                match serve_worker(stream, hello, grid, film, queue, progress) {
+                                    Ok(n) => info!("Worker {} rendered {} tile(s)", peer, n),
+                                    Err(err) => {
+                                        warn!("Worker {} failed: {}", peer, err)
+                                    }
+                                }
+                            });
+                        }
+                        Err(ref err) if err.kind() == ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        Err(err) => {
+                            warn!("Unable to accept worker: {}", err);
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                    }
+                }
+            });
+        })
+        .unwrap();
+    }
+    progress.lock().unwrap().done();
+    integrator.write_film(&film);
+}
+
+/// Hands out tiles to one worker until all tiles are rendered.
+/// Returns the number of tiles the worker rendered.
+fn serve_worker(
+    mut stream: TcpStream,
+    coordinator: &Hello,
+    grid: &TileGrid,
+    film: &Film,
+    queue: &TileQueue,
+    progress: &Mutex<ProgressReporter>,
+) -> Result<usize> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    let worker: Hello = Hello::from_payload(&read_frame(&mut stream)?)?;
+    if !worker.matches(coordinator) {
+        write_frame(&mut stream, &[DONE])?;
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "the worker's scene differs (resolution, crop window, tile shape, samples per pixel, spectrum, AOVs, deep, or denoiser features)",
+        ));
+    }
+    let batch_size: usize = std::cmp::max(1, worker.threads as usize) * TILES_PER_THREAD;
+    let mut rendered: usize = 0;
+    loop {
+        let mut tiles: Vec<usize> = queue.take(batch_size);
+        if tiles.is_empty() {
+            if queue.is_finished() {
+                write_frame(&mut stream, &[DONE])?;
+                return Ok(rendered);
+            }
+            // wait for tiles given back by other workers (if any)
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        let n: usize = tiles.len();
+        if let Err(err) = render_remotely(&mut stream, &mut tiles, grid, film, queue, progress) {
+            queue.give_back(&tiles);
+            return Err(err);
+        }
+        rendered += n;
+    }
+}
+
+/// Sends a batch of tiles to a worker and merges the tiles it sends
+/// back. Tiles are removed from *tiles* once they are merged.
+fn render_remotely(
+    stream: &mut TcpStream,
+    tiles: &mut Vec<usize>,
+    grid: &TileGrid,
+    film: &Film,
+    queue: &TileQueue,
+    progress: &Mutex<ProgressReporter>,
+) -> Result<()> {
+    write_frame(stream, &tiles_payload(tiles)?)?;
+    while !tiles.is_empty() {
+        let (index, film_tile) = read_tile(&read_frame(stream)?, grid, film)?;
+        if let Some(pos) = tiles.iter().position(|i| *i == index) {
+            tiles.swap_remove(pos);
+        } else {
+            return Err(Error::new(ErrorKind::InvalidData, "tile wasn't requested"));
+        }
+        film.merge_film_tile(&film_tile);
+        queue.finish();
+        progress.lock().unwrap().update(1);
+    }
+    Ok(())
+}
+
+/// Renders the tiles the coordinator at *address* asks for, until it
+/// has no more work. No image gets written.
+pub fn render_worker(
+    integrator: &mut SamplerIntegrator,
+    scene: &Scene,
+    num_threads: u8,
+    address: &str,
+) {
+    integrator.preprocess(scene);
+    let num_cores: usize = number_of_cores(num_threads);
+    info!(
+        "Rendering with {:?} thread(s) for coordinator {} ...",
+        num_cores, address
+    );
+    match work_for(integrator, scene, num_cores, address) {
+        Ok(n) => info!("Rendered {} tile(s) for coordinator {}", n, address),
+        Err(err) => warn!("Lost coordinator {}: {}", address, err),
+    }
+}
+
+fn work_for(
+    integrator: &SamplerIntegrator,
+    scene: &Scene,
+    num_cores: usize,
+    address: &str,
+) -> Result<usize> {
+    let mut stream: TcpStream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    let film = integrator.get_camera().get_film();
+    let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), tile_shape());
+    let hello: Hello = Hello::new(&grid, integrator.get_sampler(), &film, num_cores);
+    write_frame(&mut stream, &hello.to_payload()?)?;
+    let mut rendered: usize = 0;
+    loop {
+        let payload: Vec<u8> = read_frame(&mut stream)?;
+        if payload.first() == Some(&DONE) {
+            return Ok(rendered);
+        }
+        let tiles: Vec<usize> = read_tiles(&payload, &grid)?;
+        render_batch(
+            integrator,
+            scene,
+            num_cores,
+            &grid,
+            &film,
+            &tiles,
+            &mut stream,
+        )?;
+        rendered += tiles.len();
+    }
+}
+
+/// Renders a batch of tiles in parallel and sends each one to the
+/// coordinator as soon as it's done.
+fn render_batch(
+    integrator: &SamplerIntegrator,
+    scene: &Scene,
+    num_cores: usize,
+    grid: &TileGrid,
+    film: &Film,
+    tiles: &[usize],
+    stream: &mut TcpStream,
+) -> Result<()> {
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let sampler: &Sampler = integrator.get_sampler();
+    let next = &next;
+    crossbeam::scope(|scope| {
+        let (tile_tx, tile_rx) = crossbeam_channel::bounded(num_cores);
+        for thread_index in 0..std::cmp::min(num_cores, tiles.len()) {
+            let tile_tx = tile_tx.clone();
+            scope.spawn(move |_| {
+                numa::pin_render_thread(thread_index);
+                let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+                let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+                loop {
+                    let i: usize = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= tiles.len() {
+                        break;
+                    }
+                    let tile: Point2i = grid.tile(tiles[i]);
+                    let film_tile = integrator.render_tile(
+                        scene,
+                        grid,
+                        tile,
+                        film,
+                        &mut tile_sampler,
+                        &mut arena,
+                    );
+                    let payload: Result<Vec<u8>> = tile_payload(tiles[i], &film_tile);
+                    if tile_tx.send(payload).is_err() {
+                        // the coordinator is gone
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tile_tx);
+        for payload in tile_rx.iter() {
+            write_frame(stream, &payload?)?;
+        }
+        Ok(())
+    })
+    .unwrap()
+}
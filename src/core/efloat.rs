@@ -7,7 +7,7 @@
 use std;
 use std::ops::{Add, Div, Mul, Sub};
 // pbrt
-use crate::core::pbrt::MACHINE_EPSILON;
+use crate::core::pbrt::{Float, MACHINE_EPSILON};
 use crate::core::pbrt::{next_float_down, next_float_up};
 
 // see efloat.h
@@ -21,14 +21,14 @@ pub fn quadratic_efloat(a: EFloat, b: EFloat, c: EFloat, t0: &mut EFloat, t1: &m
     } else {
         let root_discrim: f64 = discrim.sqrt();
         let float_root_discrim: EFloat = EFloat::new(
-            root_discrim as f32,
-            MACHINE_EPSILON as f32 * root_discrim as f32,
+            root_discrim as Float,
+            MACHINE_EPSILON * root_discrim as Float,
         );
         // compute quadratic _t_ values
-        let q = if b.v < 0.0f32 {
-            (b - float_root_discrim) * -0.5f32
+        let q = if b.v < 0.0 {
+            (b - float_root_discrim) * -0.5
         } else {
-            (b + float_root_discrim) * -0.5f32
+            (b + float_root_discrim) * -0.5
         };
         *t0 = q / a;
         *t1 = c / q;
@@ -43,13 +43,13 @@ pub fn quadratic_efloat(a: EFloat, b: EFloat, c: EFloat, t0: &mut EFloat, t1: &m
 /// uncertainty of a value of interest.
 #[derive(Debug, Default, Copy, Clone)]
 pub struct EFloat {
-    pub v: f32,
-    pub low: f32,
-    pub high: f32,
+    pub v: Float,
+    pub low: Float,
+    pub high: Float,
 }
 
 impl EFloat {
-    pub fn new(v: f32, err: f32) -> Self {
+    pub fn new(v: Float, err: Float) -> Self {
         if err == 0.0 {
             EFloat { v, low: v, high: v }
         } else {
@@ -60,10 +60,10 @@ impl EFloat {
             }
         }
     }
-    pub fn lower_bound(&self) -> f32 {
+    pub fn lower_bound(&self) -> Float {
         self.low
     }
-    pub fn upper_bound(&self) -> f32 {
+    pub fn upper_bound(&self) -> Float {
         self.high
     }
 }
@@ -101,7 +101,7 @@ impl Sub for EFloat {
 impl Mul for EFloat {
     type Output = EFloat;
     fn mul(self, rhs: EFloat) -> EFloat {
-        let prod: [f32; 4] = [
+        let prod: [Float; 4] = [
             self.lower_bound() * rhs.lower_bound(),
             self.upper_bound() * rhs.lower_bound(),
             self.lower_bound() * rhs.upper_bound(),
@@ -116,9 +116,9 @@ impl Mul for EFloat {
     }
 }
 
-impl Mul<f32> for EFloat {
+impl Mul<Float> for EFloat {
     type Output = EFloat;
-    fn mul(self, rhs: f32) -> EFloat {
+    fn mul(self, rhs: Float) -> EFloat {
         EFloat::new(rhs, 0.0) * self
     }
 }
@@ -126,7 +126,7 @@ impl Mul<f32> for EFloat {
 impl Div for EFloat {
     type Output = EFloat;
     fn div(self, rhs: EFloat) -> EFloat {
-        let div: [f32; 4] = [
+        let div: [Float; 4] = [
             self.lower_bound() / rhs.lower_bound(),
             self.upper_bound() / rhs.lower_bound(),
             self.lower_bound() / rhs.upper_bound(),
@@ -138,8 +138,8 @@ impl Div for EFloat {
             // return an interval of everything
             EFloat {
                 v: self.v / rhs.v,
-                low: -std::f32::INFINITY,
-                high: std::f32::INFINITY,
+                low: -Float::INFINITY,
+                high: Float::INFINITY,
             }
         } else {
             EFloat {
@@ -0,0 +1,91 @@
+//! The error type of the renderer.
+//!
+//! Functions which create objects from a scene description (e.g.
+//! **api::make_camera()**, **RenderOptions::make_integrator()**, or
+//! **floatfile::read_float_file()**) return an **Error** instead of
+//! stopping the process, so applications which embed the renderer can
+//! report bad input and carry on. The API functions called by the
+//! parser turn them into **ParseError**s (see **ApiState::errors**).
+
+// std
+use std::fmt;
+use std::io;
+// pbrt
+use crate::core::parseerror::ParseError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// a statement of a scene description
+    Parse(ParseError),
+    /// an object which can't be created from its type and parameters,
+    /// e.g. an unknown sampler or a parameter with too few values
+    Create {
+        /// the directive, e.g. "Sampler"
+        key_word: String,
+        /// the type, e.g. "halton"
+        name: String,
+        message: String,
+    },
+    /// a file which can't be read
+    Io { filename: String, source: io::Error },
+}
+
+impl Error {
+    pub fn create(key_word: &str, name: &str, message: String) -> Self {
+        Error::Create {
+            key_word: String::from(key_word),
+            name: String::from(name),
+            message,
+        }
+    }
+    pub fn io(filename: &str, source: io::Error) -> Self {
+        Error::Io {
+            filename: String::from(filename),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(error) => write!(f, "{}", error),
+            Error::Create {
+                key_word,
+                name,
+                message,
+            } => write!(f, "{} \"{}\": {}", key_word, name, message),
+            Error::Io { filename, source } => write!(f, "{:?}: {}", filename, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(error) => Some(error),
+            Error::Create { .. } => None,
+            Error::Io { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
+
+/// Errors without a location get the one of the statement which is
+/// currently executed (see **ApiState::add_error()**).
+impl From<Error> for ParseError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Parse(error) => error,
+            Error::Create { ref name, .. } => ParseError::new(name, error.to_string()),
+            Error::Io { ref filename, .. } => ParseError::new(filename, error.to_string()),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
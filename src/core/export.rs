@@ -0,0 +1,228 @@
+//! Write scenes back out as .pbrt text files.
+//!
+//! The exporter works on the same sequence of API calls which is
+//! recorded for the binary scene cache (see **scenecache::ApiCall**)
+//! and collected by the **builder::SceneBuilder**. Everything which
+//! can be expressed as API calls can therefore be converted to a
+//! .pbrt file (e.g. pbrt-v4 scenes after their conversion, or scenes
+//! built programmatically). Relative filenames (meshes, textures,
+//! environment maps) are resolved against the search directory of
+//! the original scene, so the exported file can be placed anywhere.
+
+// std
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::PathBuf;
+// pbrt
+use crate::core::paramset::{ParamSet, ParamSetItem};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::scenecache::{resolve_filename, ApiCall, FILENAME_PARAMS};
+use crate::core::transform::Transform;
+
+/// Writes the API calls as .pbrt file. A **WorldEnd** is added if the
+/// calls don't contain one (e.g. for pbrt-v4 scenes).
+pub fn export_pbrt(filename: &str, calls: &[ApiCall]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(filename)?);
+    write_pbrt(&mut w, calls)?;
+    w.flush()
+}
+
+/// Writes the API calls in the .pbrt text format.
+pub fn write_pbrt<W: Write>(w: &mut W, calls: &[ApiCall]) -> Result<()> {
+    let mut indent: usize = 0;
+    let mut search_directory: Option<PathBuf> = None;
+    let mut world_end: bool = false;
+    for call in calls {
+        // un-indent before writing the end of a block
+        match call {
+            ApiCall::AttributeEnd | ApiCall::ObjectEnd | ApiCall::TransformEnd => {
+                indent = indent.saturating_sub(1);
+            }
+            _ => {}
+        }
+        let prefix: String = "  ".repeat(indent);
+        match call {
+            ApiCall::ActiveTransformAll => writeln!(w, "{}ActiveTransform All", prefix)?,
+            ApiCall::ActiveTransformEndTime => writeln!(w, "{}ActiveTransform EndTime", prefix)?,
+            ApiCall::ActiveTransformStartTime => {
+                writeln!(w, "{}ActiveTransform StartTime", prefix)?
+            }
+            ApiCall::AttributeBegin => writeln!(w, "{}AttributeBegin", prefix)?,
+            ApiCall::AttributeEnd => writeln!(w, "{}AttributeEnd", prefix)?,
+            ApiCall::ConcatTransform(tr) => {
+                writeln!(w, "{}ConcatTransform {}", prefix, matrix_to_string(tr))?
+            }
+            ApiCall::LookAt(v) => writeln!(
+                w,
+                "{}LookAt {} {} {}  {} {} {}  {} {} {}",
+                prefix, v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8]
+            )?,
+            ApiCall::MediumInterface(inside, outside) => writeln!(
+                w,
+                "{}MediumInterface \"{}\" \"{}\"",
+                prefix, inside, outside
+            )?,
+            ApiCall::ObjectEnd => writeln!(w, "{}ObjectEnd", prefix)?,
+            ApiCall::Params(params) => {
+                write_params(w, &prefix, params, search_directory.as_ref())?;
+            }
+            ApiCall::ReverseOrientation => writeln!(w, "{}ReverseOrientation", prefix)?,
+            ApiCall::Rotate(v) => {
+                writeln!(w, "{}Rotate {} {} {} {}", prefix, v[0], v[1], v[2], v[3])?
+            }
+            ApiCall::Scale(v) => writeln!(w, "{}Scale {} {} {}", prefix, v[0], v[1], v[2])?,
+            ApiCall::SearchDirectory(dir) => {
+                // only used to resolve relative filenames (see above)
+                search_directory = Some(PathBuf::from(dir));
+            }
+            ApiCall::Transform(tr) => writeln!(w, "{}Transform {}", prefix, matrix_to_string(tr))?,
+            ApiCall::TransformBegin => writeln!(w, "{}TransformBegin", prefix)?,
+            ApiCall::TransformEnd => writeln!(w, "{}TransformEnd", prefix)?,
+            ApiCall::Translate(v) => writeln!(w, "{}Translate {} {} {}", prefix, v[0], v[1], v[2])?,
+            ApiCall::WorldBegin => {
+                writeln!(w)?;
+                writeln!(w, "WorldBegin")?;
+                writeln!(w)?;
+            }
+            ApiCall::WorldEnd => {
+                writeln!(w, "WorldEnd")?;
+                world_end = true;
+            }
+        }
+        // indent after writing the begin of a block
+        match call {
+            ApiCall::AttributeBegin | ApiCall::TransformBegin => indent += 1,
+            ApiCall::Params(params) if params.key_word == "ObjectBegin" => indent += 1,
+            _ => {}
+        }
+    }
+    if !world_end {
+        writeln!(w, "WorldEnd")?;
+    }
+    Ok(())
+}
+
+/// A render setting (e.g. the integrator and its parameters) as .pbrt
+/// statement on a single line, e.g. for the metadata of images.
+pub fn statement_to_string(key_word: &str, name: &str, params: &ParamSet) -> String {
+    let mut params: ParamSet = params.clone();
+    params.key_word = String::from(key_word);
+    params.name = String::from(name);
+    let mut w: Vec<u8> = Vec::new();
+    // writing into memory can't fail
+    write_params(&mut w, "", &params, None).unwrap();
+    let text: String = String::from_utf8_lossy(&w).into_owned();
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    lines.join(" ")
+}
+
+/// The 16 matrix entries in the (column major) order of the scene
+/// file, see **ConcatTransform** in the parser.
+pub fn matrix_to_string(tr: &Transform) -> String {
+    let mut values: Vec<String> = Vec::with_capacity(16);
+    for j in 0..4 {
+        for i in 0..4 {
+            values.push(format!("{}", tr.m.m[i][j]));
+        }
+    }
+    format!("[ {} ]", values.join(" "))
+}
+
+fn write_item<W: Write, T>(
+    w: &mut W,
+    prefix: &str,
+    type_name: &str,
+    item: &ParamSetItem<T>,
+    to_string: impl Fn(&T) -> String,
+) -> Result<()> {
+    let values: Vec<String> = item.values.iter().map(to_string).collect();
+    if values.len() > 1 && values[0].contains(' ') {
+        // one tuple (e.g. point or normal) per line
+        writeln!(w, "{}  \"{} {}\" [", prefix, type_name, item.name)?;
+        for value in values {
+            writeln!(w, "{}    {}", prefix, value)?;
+        }
+        writeln!(w, "{}  ]", prefix)
+    } else {
+        writeln!(
+            w,
+            "{}  \"{} {}\" [ {} ]",
+            prefix,
+            type_name,
+            item.name,
+            values.join(" ")
+        )
+    }
+}
+
+fn spectrum_to_string(s: &Spectrum) -> String {
+    let mut rgb: [Float; 3] = [0.0 as Float; 3];
+    s.to_rgb(&mut rgb);
+    format!("{} {} {}", rgb[0], rgb[1], rgb[2])
+}
+
+fn write_params<W: Write>(
+    w: &mut W,
+    prefix: &str,
+    params: &ParamSet,
+    search_directory: Option<&PathBuf>,
+) -> Result<()> {
+    match params.key_word.as_str() {
+        "Texture" => writeln!(
+            w,
+            "{}Texture \"{}\" \"{}\" \"{}\"",
+            prefix, params.name, params.tex_type, params.tex_name
+        )?,
+        _ => writeln!(w, "{}{} \"{}\"", prefix, params.key_word, params.name)?,
+    }
+    for item in &params.strings {
+        // the film's filename is an output
+        let resolve: bool =
+            params.key_word != "Film" && FILENAME_PARAMS.contains(&item.name.as_str());
+        write_item(w, prefix, "string", item, |s| {
+            if resolve {
+                format!("\"{}\"", resolve_filename(search_directory, s))
+            } else {
+                format!("\"{}\"", s)
+            }
+        })?;
+    }
+    for item in &params.bools {
+        write_item(w, prefix, "bool", item, |b| format!("\"{}\"", b))?;
+    }
+    for item in &params.ints {
+        write_item(w, prefix, "integer", item, |i| format!("{}", i))?;
+    }
+    for item in &params.floats {
+        write_item(w, prefix, "float", item, |f| format!("{}", f))?;
+    }
+    for item in &params.point2fs {
+        write_item(w, prefix, "point2", item, |p| format!("{} {}", p.x, p.y))?;
+    }
+    for item in &params.vector2fs {
+        write_item(w, prefix, "vector2", item, |v| format!("{} {}", v.x, v.y))?;
+    }
+    for item in &params.point3fs {
+        write_item(w, prefix, "point", item, |p| {
+            format!("{} {} {}", p.x, p.y, p.z)
+        })?;
+    }
+    for item in &params.vector3fs {
+        write_item(w, prefix, "vector", item, |v| {
+            format!("{} {} {}", v.x, v.y, v.z)
+        })?;
+    }
+    for item in &params.normals {
+        write_item(w, prefix, "normal", item, |n| {
+            format!("{} {} {}", n.x, n.y, n.z)
+        })?;
+    }
+    for item in &params.spectra {
+        // spectra from files, blackbodies etc. are stored as RGB
+        write_item(w, prefix, "rgb", item, spectrum_to_string)?;
+    }
+    for item in &params.textures {
+        write_item(w, prefix, "texture", item, |t| format!("\"{}\"", t))?;
+    }
+    Ok(())
+}
@@ -8,66 +8,429 @@
 //! image. When the main rendering loop exits, the **Film** writes the
 //! final image to file.
 //!
+//! With *diskbacked* the **Film** doesn't keep the whole image in
+//! memory: pixels are stored in tiles, and each tile gets written to a
+//! tiled OpenEXR file as soon as no more samples can contribute to it
+//! (see **DiskTiles**). This allows very high resolutions (e.g. for
+//! prints) on machines with modest amounts of memory, but only for
+//! integrators which render the image tile by tile (not for **bdpt**,
+//! **mlt**, **sppm**, or **gradientpath**, which splat samples
+//! anywhere or set the whole image at the end).
+//!
+//! With *overscan* (in percent of the resolution) the **Film** renders
+//! beyond the nominal frame on every side, so compositors have pixels
+//! to spare for camera shake or lens distortion in post. The crop
+//! window may then extend beyond [0, 1] by the same amount (a negative
+//! crop), and the pixel bounds start at negative coordinates. Tiled
+//! OpenEXR files (*diskbacked*) store them as their data window, with
+//! the nominal frame as display window, other images get the pixel
+//! bounds as metadata (*pixelBounds*).
+//!
+//! With *halfprecision* the **Film** stores the pixels in 16 bytes
+//! instead of 32 (64 with *float_as_double*), for very large images:
+//! the XYZ value and alpha of each pixel are 16-bit floats, normalized
+//! by the filter weight sum, which stays in full precision (see
+//! **HalfPixels**). That costs some precision:
+//!
+//! - 16-bit floats have 11 significant bits, each merged tile rounds
+//!   a pixel to about 0.05%, which is well below what 8-bit images or
+//!   the noise of a few hundred samples show, but can add up for
+//!   progressive renders with many passes,
+//! - values beyond 65504 (before *scale*) are clamped to it, so very
+//!   bright highlights in OpenEXR output lose their intensity,
+//! - splats (**bdpt**, **mlt**) are sums of many tiny contributions,
+//!   which would vanish in 16 bits, so they stay in full precision
+//!   (allocated by the first splat).
+//!
+//! It's ignored with *diskbacked*, which needs even less memory.
+//!
+//! Integrators can keep extra images (AOVs, see **FilmAov**) with the
+//! pixel bounds of the **Film**, which get written as OpenEXR files
+//! next to the image (see **Film::write_aov()**).
+//!
+//! With *aovs* (a list of names, see **AovType**) the **Film** keeps
+//! such images for compositing, which the sampler integrators (e.g.
+//! **path**, **whitted**) fill while they render the image:
+//!
+//! - *albedo*: the reflectance of the first surface hit (estimated
+//!   with one BSDF sample per camera ray),
+//! - *normal*: its shading normal in world space,
+//! - *depth*: its distance from the camera (0 where all samples miss),
+//! - *emission*, *direct*, *indirect*: the image split into light
+//!   seen directly, light after one bounce, and the rest, which add
+//!   up to the image (only **path** and **volpath** split the light).
+//!
+//! Each **FilmTile** accumulates the samples of its pixels (without
+//! filtering) and **Film::merge_film_tile()** merges them, the images
+//! get written as "pbrt_albedo.exr" etc. (see **Film::write_aov()**).
+//! Distributed rendering (see **distributed**) doesn't send them.
+//!
+//! With *deep* the **Film** also keeps every sample of a pixel with
+//! the distance of the first surface hit by its camera ray, and the
+//! sampler integrators write a deep OpenEXR file ("pbrt_deep.exr",
+//! see **write_deep_exr()**) next to the image. Samples of (nearly)
+//! the same depth are merged, and their coverage gets converted, so
+//! that compositing them front to back (*over*) gives the image
+//! again (without filtering). Camera rays which miss everything and
+//! see no light leave the pixel transparent, so other renders can
+//! show through.
+//!
+//! With *denoiserfeatures* the **Film** keeps two more images,
+//! filtered like the image itself, which help denoisers (like Intel
+//! Open Image Denoise or OptiX) to tell noise from detail: the albedo
+//! and the shading normal (in camera space) of what each pixel shows.
+//! **path** takes them from the first vertex which isn't perfectly
+//! specular (seen through mirrors and glass), other sampler
+//! integrators from the first surface hit. They get written together
+//! with the (linear) image as PFM files (see
+//! **Film::write_denoiser_features()**).
+//!
+//! With *denoise* the image gets denoised (guided by the same albedo
+//! and normal, if the integrator provides them) before it's written,
+//! see **denoise_image()**. The PFM files of *denoiserfeatures* keep
+//! the noisy image. It's ignored with *diskbacked*.
+//!
 
 // std
 #[cfg(feature = "openexr")]
 use std;
-use std::ops::{DerefMut, Index};
-use std::path::Path;
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::collections::HashMap;
+use std::io::{Read, Result, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Instant;
 
 // others
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crc32fast::Hasher;
 use image;
+use image::png::PngEncoder;
+use log::{error, info, warn};
 #[cfg(feature = "openexr")]
 use openexr::{FrameBuffer, Header, PixelType, ScanlineOutputFile};
 use smallvec::SmallVec;
 // pbrt
+use crate::core::deepexr::{write_deep_exr, DeepSample};
+use crate::core::denoise::denoise_image;
 use crate::core::filter::Filter;
 use crate::core::geometry::{
     bnd2_intersect_bnd2, pnt2_ceil, pnt2_floor, pnt2_inside_exclusive, pnt2_max_pnt2, pnt2_min_pnt2,
 };
-use crate::core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i, Vector2f};
+use crate::core::geometry::{Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Vector2f};
+use crate::core::lowmemory::{f16_to_f32, f32_to_f16};
+use crate::core::parallel::AtomicFloat;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::{clamp_t, gamma_correct};
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::spectrum::xyz_to_rgb;
+use crate::core::spectrum::{
+    color_matrix_mul, color_temperature_spectrum, std_illuminant, white_balance, RGBColorSpace,
+};
+use crate::core::stats::StatMemoryCounter;
+use crate::core::tiledexr::{tile_bounds, tile_count, write_exr, TiledExrWriter};
 
 // see film.h
 
 const FILTER_TABLE_WIDTH: usize = 16;
+/// width and height (in pixels) of the tiles of a film with *diskbacked*
+const DISK_TILE_SIZE: i32 = 64;
+/// the largest finite 16-bit float
+const HALF_MAX: Float = 65504.0;
+/// rows of pixels of a film with *halfprecision* sharing a lock
+const HALF_ROW_LOCKS: usize = 64;
 
-#[derive(Debug, Clone)]
+/// relative depth difference of samples merged by a film with *deep*
+const DEEP_MERGE_TOLERANCE: Float = 0.001;
+
+static FILM_PIXEL_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Film pixels");
+static DEEP_SAMPLE_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Deep samples");
+
+/// All values are accumulated atomically, so render threads can
+/// merge tiles and splat samples without locking the whole film.
+/// Floats added by several threads at once get summed up in whatever
+/// order the threads come, which rounds differently from run to run.
+/// Renders are only reproducible because the integrators merge their
+/// tiles (and splats) in a fixed order (see **OrderedMerge**).
+#[derive(Debug, Default, Clone)]
 pub struct Pixel {
+    xyz: [AtomicFloat; 3],
+    filter_weight_sum: AtomicFloat,
+    splat_xyz: [AtomicFloat; 3],
+    alpha_sum: AtomicFloat,
+}
+
+impl Pixel {
+    /// Adds the (filtered) samples of a tile pixel.
+    fn merge(&self, tile_pixel: &FilmTilePixel) {
+        let mut xyz: [Float; 3] = [0.0; 3];
+        tile_pixel.contrib_sum.to_xyz(&mut xyz);
+        for (i, item) in xyz.iter().enumerate() {
+            self.xyz[i].add(*item);
+        }
+        self.filter_weight_sum.add(tile_pixel.filter_weight_sum);
+        self.alpha_sum.add(tile_pixel.alpha_sum);
+    }
+    fn sums(&self) -> PixelSums {
+        PixelSums {
+            xyz: [
+                Float::from(&self.xyz[0]),
+                Float::from(&self.xyz[1]),
+                Float::from(&self.xyz[2]),
+            ],
+            filter_weight_sum: Float::from(&self.filter_weight_sum),
+            splat_xyz: [
+                Float::from(&self.splat_xyz[0]),
+                Float::from(&self.splat_xyz[1]),
+                Float::from(&self.splat_xyz[2]),
+            ],
+            alpha_sum: Float::from(&self.alpha_sum),
+        }
+    }
+    fn restore(&self, sums: &PixelSums) {
+        for i in 0..3 {
+            self.xyz[i].store(sums.xyz[i]);
+            self.splat_xyz[i].store(sums.splat_xyz[i]);
+        }
+        self.filter_weight_sum.store(sums.filter_weight_sum);
+        self.alpha_sum.store(sums.alpha_sum);
+    }
+}
+
+/// The values of a **Pixel** (or a **HalfPixel**) at one point in
+/// time.
+#[derive(Debug, Default, Copy, Clone)]
+struct PixelSums {
     xyz: [Float; 3],
     filter_weight_sum: Float,
     splat_xyz: [Float; 3],
-    pad: Float,
+    alpha_sum: Float,
+}
+
+/// A pixel of a film with *halfprecision*.
+#[derive(Debug, Default)]
+struct HalfPixel {
+    /// X, Y, Z, and alpha (divided by the filter weight sum) as
+    /// 16-bit floats
+    xyza: AtomicU64,
+    filter_weight_sum: AtomicFloat,
+}
+
+/// The pixels of a film with *halfprecision*. Storing normalized
+/// values keeps them within the range of 16-bit floats no matter how
+/// many samples were accumulated, but merging a tile pixel has to
+/// update the value and the weight of a pixel together, so rows of
+/// pixels share a lock (tiles of different threads only overlap at
+/// their borders).
+struct HalfPixels {
+    pixels: Vec<HalfPixel>,
+    /// one per pixel, allocated by the first splat
+    splats: OnceLock<Vec<[AtomicFloat; 3]>>,
+    row_locks: Vec<Mutex<()>>,
+}
+
+impl HalfPixels {
+    fn new(n_pixels: usize) -> Self {
+        FILM_PIXEL_BYTES.add(n_pixels * std::mem::size_of::<HalfPixel>());
+        HalfPixels {
+            pixels: (0..n_pixels).map(|_| HalfPixel::default()).collect(),
+            splats: OnceLock::new(),
+            row_locks: (0..HALF_ROW_LOCKS).map(|_| Mutex::new(())).collect(),
+        }
+    }
+    fn row_lock(&self, row: i32) -> &Mutex<()> {
+        &self.row_locks[row.rem_euclid(HALF_ROW_LOCKS as i32) as usize]
+    }
+    /// Adds the (filtered) samples of a tile pixel, the caller holds
+    /// the lock of the row (see **row_lock()**).
+    fn merge(&self, offset: usize, tile_pixel: &FilmTilePixel) {
+        let pixel: &HalfPixel = &self.pixels[offset];
+        let weight: Float = Float::from(&pixel.filter_weight_sum);
+        let new_weight: Float = weight + tile_pixel.filter_weight_sum;
+        pixel.filter_weight_sum.store(new_weight);
+        if new_weight == 0.0 as Float {
+            return;
+        }
+        let mut xyz: [Float; 3] = [0.0; 3];
+        tile_pixel.contrib_sum.to_xyz(&mut xyz);
+        let old: [Float; 4] = unpack_half(pixel.xyza.load(Ordering::Relaxed));
+        let sums: [Float; 4] = [xyz[0], xyz[1], xyz[2], tile_pixel.alpha_sum];
+        let mut new: [Float; 4] = [0.0 as Float; 4];
+        for i in 0..4 {
+            new[i] = (old[i] * weight + sums[i]) / new_weight;
+        }
+        pixel.xyza.store(pack_half(new), Ordering::Relaxed);
+    }
+    fn set(&self, offset: usize, xyz: [Float; 3]) {
+        let pixel: &HalfPixel = &self.pixels[offset];
+        pixel.xyza.store(
+            pack_half([xyz[0], xyz[1], xyz[2], 1.0 as Float]),
+            Ordering::Relaxed,
+        );
+        pixel.filter_weight_sum.store(1.0 as Float);
+        if let Some(splats) = self.splats.get() {
+            for splat in splats[offset].iter() {
+                splat.store(0.0 as Float);
+            }
+        }
+    }
+    fn add_splat(&self, offset: usize, xyz: [Float; 3]) {
+        let splats: &Vec<[AtomicFloat; 3]> = self.splats.get_or_init(|| {
+            FILM_PIXEL_BYTES.add(self.pixels.len() * std::mem::size_of::<[AtomicFloat; 3]>());
+            (0..self.pixels.len()).map(|_| Default::default()).collect()
+        });
+        for (splat, value) in splats[offset].iter().zip(xyz.iter()) {
+            splat.add(*value);
+        }
+    }
+    fn sums(&self, offset: usize) -> PixelSums {
+        let pixel: &HalfPixel = &self.pixels[offset];
+        let filter_weight_sum: Float = Float::from(&pixel.filter_weight_sum);
+        let xyza: [Float; 4] = unpack_half(pixel.xyza.load(Ordering::Relaxed));
+        let mut splat_xyz: [Float; 3] = [0.0 as Float; 3];
+        if let Some(splats) = self.splats.get() {
+            for (value, splat) in splat_xyz.iter_mut().zip(splats[offset].iter()) {
+                *value = Float::from(splat);
+            }
+        }
+        PixelSums {
+            xyz: [
+                xyza[0] * filter_weight_sum,
+                xyza[1] * filter_weight_sum,
+                xyza[2] * filter_weight_sum,
+            ],
+            filter_weight_sum,
+            splat_xyz,
+            alpha_sum: xyza[3] * filter_weight_sum,
+        }
+    }
+    /// Replaces the values of a pixel by *sums* (see **sums()**).
+    fn restore(&self, offset: usize, sums: &PixelSums) {
+        let pixel: &HalfPixel = &self.pixels[offset];
+        let weight: Float = sums.filter_weight_sum;
+        pixel.filter_weight_sum.store(weight);
+        let mut xyza: [Float; 4] = [0.0 as Float; 4];
+        if weight != 0.0 as Float {
+            xyza = [
+                sums.xyz[0] / weight,
+                sums.xyz[1] / weight,
+                sums.xyz[2] / weight,
+                sums.alpha_sum / weight,
+            ];
+        }
+        pixel.xyza.store(pack_half(xyza), Ordering::Relaxed);
+        if sums.splat_xyz.iter().any(|value| *value != 0.0 as Float) {
+            self.add_splat(offset, sums.splat_xyz);
+        }
+    }
 }
 
-impl Default for Pixel {
-    fn default() -> Self {
-        Pixel {
-            xyz: [0.0 as Float; 3],
-            filter_weight_sum: 0.0 as Float,
-            splat_xyz: [Float::default(), Float::default(), Float::default()],
-            pad: 0.0 as Float,
+/// Four values as 16-bit floats (clamped to their finite range).
+#[allow(clippy::unnecessary_cast)]
+fn pack_half(values: [Float; 4]) -> u64 {
+    values.iter().enumerate().fold(0_u64, |bits, (i, value)| {
+        let half: u16 = f32_to_f16(clamp_t(*value, -HALF_MAX, HALF_MAX) as f32);
+        bits | (half as u64) << (16 * i)
+    })
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn unpack_half(bits: u64) -> [Float; 4] {
+    let mut values: [Float; 4] = [0.0 as Float; 4];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = f16_to_f32((bits >> (16 * i)) as u16) as Float;
+    }
+    values
+}
+
+/// The AOVs a film can keep for compositing (see *aovs*).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AovType {
+    Albedo,
+    Normal,
+    Depth,
+    Emission,
+    Direct,
+    Indirect,
+}
+
+impl AovType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "albedo" => Some(AovType::Albedo),
+            "normal" => Some(AovType::Normal),
+            "depth" => Some(AovType::Depth),
+            "emission" => Some(AovType::Emission),
+            "direct" => Some(AovType::Direct),
+            "indirect" => Some(AovType::Indirect),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            AovType::Albedo => "albedo",
+            AovType::Normal => "normal",
+            AovType::Depth => "depth",
+            AovType::Emission => "emission",
+            AovType::Direct => "direct",
+            AovType::Indirect => "indirect",
         }
     }
+    /// Is it a part of the light of the image (instead of a property
+    /// of the first surface hit)?
+    pub fn is_light(&self) -> bool {
+        matches!(
+            self,
+            AovType::Emission | AovType::Direct | AovType::Indirect
+        )
+    }
+}
+
+/// A camera sample kept by a film with *deep*.
+#[derive(Debug, Copy, Clone)]
+struct DeepFilmSample {
+    /// distance of the first surface hit (infinite for none)
+    z: Float,
+    /// (premultiplied) radiance
+    xyz: [Float; 3],
+    alpha: Float,
+    weight: Float,
+}
+
+/// The samples of a pixel of a film with *deep*, only those which
+/// cover something (see **FilmTile::add_deep_sample()**) are kept.
+#[derive(Debug, Default, Clone)]
+struct DeepPixel {
+    samples: Vec<DeepFilmSample>,
+    /// of all samples
+    weight_sum: Float,
 }
 
 #[derive(Debug, Default, Copy, Clone)]
 pub struct FilmTilePixel {
-    contrib_sum: Spectrum,
-    filter_weight_sum: Float,
+    pub contrib_sum: Spectrum,
+    pub filter_weight_sum: Float,
+    pub alpha_sum: Float,
 }
 
 pub struct FilmTile<'a> {
     pub pixel_bounds: Bounds2i,
+    /// the pixels whose samples end up in this tile
+    pub sample_bounds: Bounds2i,
     filter_radius: Vector2f,
     inv_filter_radius: Vector2f,
     filter_table: &'a [Float; FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH],
     filter_table_size: usize,
     pixels: Vec<FilmTilePixel>,
+    /// RGB and weight sums of each AOV of the film (see
+    /// **add_aov_sample()**)
+    aov_pixels: Vec<Vec<[Float; 4]>>,
+    /// empty unless the film is *deep*
+    deep_pixels: Vec<DeepPixel>,
+    /// albedo, normal, and weight sums, empty unless the film wants
+    /// *denoiserfeatures*
+    feature_pixels: Vec<[Float; 7]>,
+    /// splats onto the whole film (see **add_splat()**), in the order
+    /// they were made
+    splats: Vec<(Point2f, Spectrum)>,
     max_sample_luminance: Float,
 }
 
@@ -81,6 +444,7 @@ impl<'a> FilmTile<'a> {
     ) -> Self {
         FilmTile {
             pixel_bounds,
+            sample_bounds: pixel_bounds,
             filter_radius,
             inv_filter_radius: Vector2f {
                 x: 1.0 / filter_radius.x,
@@ -90,20 +454,98 @@ impl<'a> FilmTile<'a> {
             filter_table_size,
             // TODO: pixels = std::vector<FilmTilePixel>(std::max(0, pixelBounds.Area()));
             pixels: vec![FilmTilePixel::default(); pixel_bounds.area() as usize],
+            aov_pixels: Vec::new(),
+            deep_pixels: Vec::new(),
+            feature_pixels: Vec::new(),
+            splats: Vec::new(),
             max_sample_luminance,
         }
     }
     pub fn add_sample(&mut self, p_film: Point2f, l: &mut Spectrum, sample_weight: Float) {
+        self.add_sample_with_alpha(p_film, l, 1.0 as Float, sample_weight);
+    }
+    /// Same as **add_sample()**, but with an explicit coverage value
+    /// (e.g. for shadow catchers), which ends up in the alpha channel.
+    pub fn add_sample_with_alpha(
+        &mut self,
+        p_film: Point2f,
+        l: &mut Spectrum,
+        alpha: Float,
+        sample_weight: Float,
+    ) {
         // TODO: ProfilePhase _(Prof::AddFilmSample);
         if l.y() > self.max_sample_luminance {
             *l *= Spectrum::new(self.max_sample_luminance / l.y());
         }
-        // compute sample's raster bounds
+        // loop over filter support and add sample to pixel arrays
+        let (p0, p1, ifx, ify) = self.filter_footprint(p_film);
+        for y in p0.y..p1.y {
+            for x in p0.x..p1.x {
+                // evaluate filter value at $(x,y)$ pixel
+                let offset: usize =
+                    ify[(y - p0.y) as usize] * self.filter_table_size + ifx[(x - p0.x) as usize];
+                let filter_weight: Float = self.filter_table[offset];
+                // update pixel values with filtered sample contribution
+                let idx = self.get_pixel_index(x, y);
+                let pixel = &mut self.pixels[idx];
+                pixel.contrib_sum +=
+                    *l * Spectrum::new(sample_weight) * Spectrum::new(filter_weight);
+                pixel.filter_weight_sum += filter_weight;
+                pixel.alpha_sum += alpha * sample_weight * filter_weight;
+            }
+        }
+    }
+    /// Adds the denoiser features of a sample (if the film wants them,
+    /// see *denoiserfeatures*), filtered like its radiance.
+    pub fn add_features(
+        &mut self,
+        p_film: Point2f,
+        albedo: &Spectrum,
+        n: &Normal3f,
+        sample_weight: Float,
+    ) {
+        if self.feature_pixels.is_empty() {
+            return;
+        }
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        albedo.to_rgb(&mut rgb);
+        let features: [Float; 6] = [rgb[0], rgb[1], rgb[2], n.x, n.y, n.z];
+        let (p0, p1, ifx, ify) = self.filter_footprint(p_film);
+        for y in p0.y..p1.y {
+            for x in p0.x..p1.x {
+                let offset: usize =
+                    ify[(y - p0.y) as usize] * self.filter_table_size + ifx[(x - p0.x) as usize];
+                let filter_weight: Float = self.filter_table[offset];
+                let idx = self.get_pixel_index(x, y);
+                let sums: &mut [Float; 7] = &mut self.feature_pixels[idx];
+                for (sum, feature) in sums.iter_mut().zip(features.iter()) {
+                    *sum += *feature * sample_weight * filter_weight;
+                }
+                sums[6] += filter_weight;
+            }
+        }
+    }
+    /// The pixels *p0* (inclusive) to *p1* (exclusive) a sample at
+    /// *p_film* contributes to, and the offsets of their filter
+    /// weights in x and y direction.
+    fn filter_footprint(
+        &self,
+        p_film: Point2f,
+    ) -> (
+        Point2i,
+        Point2i,
+        SmallVec<[usize; 16]>,
+        SmallVec<[usize; 16]>,
+    ) {
+        // compute sample's raster bounds (half-open, so a sample right
+        // on the edge of the filter only counts for one of the two
+        // pixels there, and only pixels within **get_sample_bounds()**
+        // contribute to a crop)
         let p_film_discrete: Point2f = p_film - Vector2f { x: 0.5, y: 0.5 };
-        let p0f: Point2f = pnt2_ceil(p_film_discrete - self.filter_radius);
+        let p0f: Point2f = pnt2_floor(p_film_discrete - self.filter_radius);
         let mut p0: Point2i = Point2i {
-            x: p0f.x as i32,
-            y: p0f.y as i32,
+            x: p0f.x as i32 + 1,
+            y: p0f.y as i32 + 1,
         };
         let p1f: Point2f = pnt2_floor(p_film_discrete + self.filter_radius);
         let mut p1: Point2i = Point2i {
@@ -113,8 +555,6 @@ impl<'a> FilmTile<'a> {
         p0 = pnt2_max_pnt2(p0, self.pixel_bounds.p_min);
         p1 = pnt2_min_pnt2(p1, self.pixel_bounds.p_max);
 
-        // loop over filter support and add sample to pixel arrays
-
         // precompute $x$ and $y$ filter table offsets
         let mut ifx: SmallVec<[usize; 16]> =
             SmallVec::with_capacity(p1.x as usize - p0.x as usize);
@@ -134,20 +574,132 @@ impl<'a> FilmTile<'a> {
                 .abs();
             ify.push(fy.floor().min(self.filter_table_size as Float - 1.0) as usize);
         }
-        for y in p0.y..p1.y {
-            for x in p0.x..p1.x {
-                // evaluate filter value at $(x,y)$ pixel
-                let offset: usize =
-                    ify[(y - p0.y) as usize] * self.filter_table_size + ifx[(x - p0.x) as usize];
-                let filter_weight: Float = self.filter_table[offset];
-                // update pixel values with filtered sample contribution
-                let idx = self.get_pixel_index(x, y);
-                let pixel = &mut self.pixels[idx];
-                pixel.contrib_sum +=
-                    *l * Spectrum::new(sample_weight) * Spectrum::new(filter_weight);
-                pixel.filter_weight_sum += filter_weight;
+        (p0, p1, ifx, ify)
+    }
+    /// Adds a sample of the AOV *index* of the film (see
+    /// **Film::aovs**) to the pixel containing *p_film*, without
+    /// filtering.
+    pub fn add_aov_sample(
+        &mut self,
+        index: usize,
+        p_film: Point2f,
+        rgb: &[Float; 3],
+        weight: Float,
+    ) {
+        let pixel: Point2i = Point2i {
+            x: p_film.x.floor() as i32,
+            y: p_film.y.floor() as i32,
+        };
+        if index >= self.aov_pixels.len() || !pnt2_inside_exclusive(pixel, &self.pixel_bounds) {
+            return;
+        }
+        let idx = self.get_pixel_index(pixel.x, pixel.y);
+        let sums: &mut [Float; 4] = &mut self.aov_pixels[index][idx];
+        for c in 0..3 {
+            sums[c] += rgb[c] * weight;
+        }
+        sums[3] += weight;
+    }
+    /// Keeps a sample for the deep image (if the film is *deep*) in the
+    /// pixel containing *p_film*, with the distance *z* of the first
+    /// surface its camera ray hit.
+    pub fn add_deep_sample(
+        &mut self,
+        p_film: Point2f,
+        z: Float,
+        l: &Spectrum,
+        alpha: Float,
+        sample_weight: Float,
+    ) {
+        let pixel: Point2i = Point2i {
+            x: p_film.x.floor() as i32,
+            y: p_film.y.floor() as i32,
+        };
+        if self.deep_pixels.is_empty() || !pnt2_inside_exclusive(pixel, &self.pixel_bounds) {
+            return;
+        }
+        let idx = self.get_pixel_index(pixel.x, pixel.y);
+        let deep_pixel: &mut DeepPixel = &mut self.deep_pixels[idx];
+        deep_pixel.weight_sum += sample_weight;
+        // transparent, e.g. rays leaving the scene in the dark
+        let empty: bool = l.is_black() && (alpha == 0.0 as Float || z.is_infinite());
+        if !empty && sample_weight > 0.0 as Float {
+            let mut xyz: [Float; 3] = [0.0 as Float; 3];
+            l.to_xyz(&mut xyz);
+            deep_pixel.samples.push(DeepFilmSample {
+                z,
+                xyz,
+                alpha,
+                weight: sample_weight,
+            });
+        }
+    }
+    /// Keeps a splat (see **Film::add_splat()**), which can land
+    /// anywhere on the film, until the tile gets merged. The tiles are
+    /// merged in a fixed order (see **OrderedMerge**), so the splats
+    /// get summed up in the same order for any number of threads.
+    pub fn add_splat(&mut self, p: Point2f, v: &Spectrum) {
+        self.splats.push((p, *v));
+    }
+    /// The pixels (row by row within *pixel_bounds*), e.g. to send
+    /// the tile to another machine (see **distributed**).
+    pub fn get_pixels(&self) -> &[FilmTilePixel] {
+        &self.pixels
+    }
+    pub fn get_pixels_mut(&mut self) -> &mut [FilmTilePixel] {
+        &mut self.pixels
+    }
+    /// Writes the AOV, deep, and denoiser feature sums of the tile
+    /// (whatever the film asked for) as 32-bit floats, e.g. to send
+    /// them along with the pixels (see **distributed**).
+    #[allow(clippy::unnecessary_cast)]
+    pub fn write_buffers<W: Write>(&self, w: &mut W) -> Result<()> {
+        for value in self.aov_pixels.iter().flatten().flatten() {
+            w.write_f32::<LittleEndian>(*value as f32)?;
+        }
+        for deep_pixel in &self.deep_pixels {
+            w.write_f32::<LittleEndian>(deep_pixel.weight_sum as f32)?;
+            w.write_u32::<LittleEndian>(deep_pixel.samples.len() as u32)?;
+            for sample in &deep_pixel.samples {
+                w.write_f32::<LittleEndian>(sample.z as f32)?;
+                for value in &sample.xyz {
+                    w.write_f32::<LittleEndian>(*value as f32)?;
+                }
+                w.write_f32::<LittleEndian>(sample.alpha as f32)?;
+                w.write_f32::<LittleEndian>(sample.weight as f32)?;
+            }
+        }
+        for value in self.feature_pixels.iter().flatten() {
+            w.write_f32::<LittleEndian>(*value as f32)?;
+        }
+        Ok(())
+    }
+    /// Replaces the AOV, deep, and denoiser feature sums by the ones
+    /// **write_buffers()** wrote for a tile of a film with the same
+    /// AOVs, *deep*, and denoiser features.
+    pub fn read_buffers<R: Read>(&mut self, r: &mut R) -> Result<()> {
+        for value in self.aov_pixels.iter_mut().flatten().flatten() {
+            *value = r.read_f32::<LittleEndian>()? as Float;
+        }
+        for deep_pixel in self.deep_pixels.iter_mut() {
+            deep_pixel.weight_sum = r.read_f32::<LittleEndian>()? as Float;
+            let n_samples: u32 = r.read_u32::<LittleEndian>()?;
+            deep_pixel.samples.clear();
+            for _ in 0..n_samples {
+                let mut values: [f32; 6] = [0.0; 6];
+                r.read_f32_into::<LittleEndian>(&mut values)?;
+                deep_pixel.samples.push(DeepFilmSample {
+                    z: values[0] as Float,
+                    xyz: [values[1] as Float, values[2] as Float, values[3] as Float],
+                    alpha: values[4] as Float,
+                    weight: values[5] as Float,
+                });
             }
         }
+        for value in self.feature_pixels.iter_mut().flatten() {
+            *value = r.read_f32::<LittleEndian>()? as Float;
+        }
+        Ok(())
     }
     fn get_pixel_index(&self, x: i32, y: i32) -> usize {
         let width: i32 = self.pixel_bounds.p_max.x - self.pixel_bounds.p_min.x;
@@ -168,9 +720,49 @@ pub struct Film {
     pub filename: String,
     /// A crop window that may specify a subset of the image to render
     pub cropped_pixel_bounds: Bounds2i,
+    /// Color space (primaries and white point) of the written image
+    pub color_space: RGBColorSpace,
+    /// Illuminant which gets neutralized (white balance), if any
+    pub white_balance: Option<Spectrum>,
+    /// The (linear) RGB values of the last image written
+    pub image: RwLock<Vec<Float>>,
+    /// Write image files (false when rendering into memory)
+    pub write_files: AtomicBool,
+    /// Write an alpha channel even if all pixels are opaque (e.g. for
+    /// scenes with holdouts), so compositing doesn't depend on what
+    /// ended up in the frame
+    pub write_alpha: AtomicBool,
+    /// Render settings embedded into the image files (see
+    /// **set_metadata()**)
+    metadata: RwLock<Vec<(String, String)>>,
+    /// When rendering started (for the render time in the metadata)
+    render_start: Mutex<Option<Instant>>,
+    /// Pixels painted in a flag color (see **flag_pixel()**)
+    flagged_pixels: Mutex<Vec<(Point2i, [Float; 3])>>,
+    /// Extra images for compositing (see *aovs*), filled by
+    /// **FilmTile::add_aov_sample()**
+    pub aovs: Vec<(AovType, FilmAov)>,
+    /// The samples of each pixel with *deep* (see
+    /// **FilmTile::add_deep_sample()**)
+    deep: Option<Mutex<Vec<DeepPixel>>>,
+    /// Albedo, normal, and weight sums of each pixel with
+    /// *denoiserfeatures* or *denoise* (see
+    /// **FilmTile::add_features()**)
+    features: Option<Vec<[AtomicFloat; 7]>>,
+    /// Write the denoiser features (see **write_denoiser_features()**)
+    write_features: bool,
+    /// Denoise the image before writing it (see **denoise_image()**)
+    denoise: bool,
+    /// The (linear) RGB values of the last image written before they
+    /// were denoised (empty unless *denoise*)
+    noisy_image: RwLock<Vec<Float>>,
 
     // Film Private Data
-    pub pixels: RwLock<Vec<Pixel>>,
+    /// empty with *diskbacked* (see **disk**) or *halfprecision* (see
+    /// **half**)
+    pub pixels: Vec<Pixel>,
+    disk: Option<Mutex<DiskTiles>>,
+    half: Option<HalfPixels>,
     filter_table: [Float; FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH],
     scale: Float,
     max_sample_luminance: Float,
@@ -185,6 +777,31 @@ impl Film {
         filename: String,
         scale: Float,
         max_sample_luminance: Float,
+    ) -> Self {
+        let mut film: Film = Film::without_pixels(
+            resolution,
+            crop_window,
+            filter,
+            diagonal,
+            filename,
+            scale,
+            max_sample_luminance,
+        );
+        // allocate film image storage
+        film.pixels = vec![Pixel::default(); film.cropped_pixel_bounds.area() as usize];
+        FILM_PIXEL_BYTES.add(film.pixels.len() * std::mem::size_of::<Pixel>());
+        film
+    }
+    /// Same as **new()**, but the pixels are not allocated (see
+    /// *diskbacked* and *halfprecision*).
+    fn without_pixels(
+        resolution: Point2i,
+        crop_window: Bounds2f,
+        filter: Box<Filter>,
+        diagonal: Float,
+        filename: String,
+        scale: Float,
+        max_sample_luminance: Float,
     ) -> Self {
         let cropped_pixel_bounds: Bounds2i = Bounds2i {
             p_min: Point2i {
@@ -196,8 +813,6 @@ impl Film {
                 y: (resolution.y as Float * crop_window.p_max.y).ceil() as i32,
             },
         };
-        // allocate film image storage
-        // let pixels: Vec<Pixel> = vec![Pixel::default(); cropped_pixel_bounds.area() as usize];
         // precompute filter weight table
         let mut filter_table: [Float; FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH] =
             [0.0; FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH];
@@ -219,7 +834,23 @@ impl Film {
             filter,
             filename,
             cropped_pixel_bounds,
-            pixels: RwLock::new(vec![Pixel::default(); cropped_pixel_bounds.area() as usize]),
+            color_space: RGBColorSpace::default(),
+            white_balance: None,
+            image: RwLock::new(Vec::new()),
+            write_files: AtomicBool::new(true),
+            write_alpha: AtomicBool::new(false),
+            metadata: RwLock::new(Vec::new()),
+            render_start: Mutex::new(None),
+            flagged_pixels: Mutex::new(Vec::new()),
+            aovs: Vec::new(),
+            deep: None,
+            features: None,
+            write_features: false,
+            denoise: false,
+            noisy_image: RwLock::new(Vec::new()),
+            pixels: Vec::new(),
+            disk: None,
+            half: None,
             filter_table,
             scale,
             max_sample_luminance,
@@ -232,16 +863,19 @@ impl Film {
         let resolution: Point2i = Point2i { x: xres, y: yres };
         // TODO: if (PbrtOptions.quickRender) xres = std::max(1, xres / 4);
         // TODO: if (PbrtOptions.quickRender) yres = std::max(1, yres / 4);
+        // extra (NDC) space on every side of the nominal frame
+        let overscan: Float = params.find_one_float("overscan", 0.0).max(0.0) / 100.0 as Float;
+        let (low, high): (Float, Float) = (-overscan, 1.0 as Float + overscan);
         let mut crop: Bounds2f = Bounds2f {
-            p_min: Point2f { x: 0.0, y: 0.0 },
-            p_max: Point2f { x: 1.0, y: 1.0 },
+            p_min: Point2f { x: low, y: low },
+            p_max: Point2f { x: high, y: high },
         };
         let cr: Vec<Float> = params.find_float("cropwindow");
         if cr.len() == 4 {
-            crop.p_min.x = clamp_t(cr[0].min(cr[1]), 0.0, 1.0);
-            crop.p_max.x = clamp_t(cr[0].max(cr[1]), 0.0, 1.0);
-            crop.p_min.y = clamp_t(cr[2].min(cr[3]), 0.0, 1.0);
-            crop.p_max.y = clamp_t(cr[2].max(cr[3]), 0.0, 1.0);
+            crop.p_min.x = clamp_t(cr[0].min(cr[1]), low, high);
+            crop.p_max.x = clamp_t(cr[0].max(cr[1]), low, high);
+            crop.p_min.y = clamp_t(cr[2].min(cr[3]), low, high);
+            crop.p_max.y = clamp_t(cr[2].max(cr[3]), low, high);
         } else if !cr.is_empty() {
             panic!(
                 "{:?} values supplied for \"cropwindow\". Expected 4.",
@@ -251,16 +885,100 @@ impl Film {
         let scale: Float = params.find_one_float("scale", 1.0);
         let diagonal: Float = params.find_one_float("diagonal", 35.0);
         let max_sample_luminance: Float =
-            params.find_one_float("maxsampleluminance", std::f32::INFINITY);
-        Arc::new(Film::new(
-            resolution,
-            crop,
-            filter,
-            diagonal,
-            filename,
-            scale,
-            max_sample_luminance,
-        ))
+            params.find_one_float("maxsampleluminance", Float::INFINITY);
+        let color_space_name: String = params.find_one_string("colorspace", String::from("srgb"));
+        let color_space: RGBColorSpace = match RGBColorSpace::from_name(&color_space_name) {
+            Some(color_space) => color_space,
+            None => {
+                warn!(
+                    "Color space {:?} unknown. Using \"srgb\".",
+                    color_space_name
+                );
+                RGBColorSpace::SRGB
+            }
+        };
+        // white balance for a color temperature (in Kelvin) or a
+        // named standard illuminant (e.g. "stdillum-A")
+        let mut balance: Option<Spectrum> = None;
+        let temperature: Float = params.find_one_float("whitebalance", 0.0 as Float);
+        let illuminant_name: String =
+            params.find_one_string("whitebalanceilluminant", String::new());
+        if !illuminant_name.is_empty() {
+            balance = std_illuminant(&illuminant_name);
+            if balance.is_none() {
+                warn!(
+                    "Illuminant {:?} unknown. No white balance.",
+                    illuminant_name
+                );
+            }
+        } else if temperature > 0.0 as Float {
+            balance = Some(color_temperature_spectrum(temperature));
+        }
+        let disk_backed: bool = params.find_one_bool("diskbacked", false);
+        let mut half_precision: bool = params.find_one_bool("halfprecision", false);
+        if disk_backed && half_precision {
+            warn!("Film \"halfprecision\" is ignored with \"diskbacked\".");
+            half_precision = false;
+        }
+        let mut film: Film = if disk_backed || half_precision {
+            Film::without_pixels(
+                resolution,
+                crop,
+                filter,
+                diagonal,
+                filename,
+                scale,
+                max_sample_luminance,
+            )
+        } else {
+            Film::new(
+                resolution,
+                crop,
+                filter,
+                diagonal,
+                filename,
+                scale,
+                max_sample_luminance,
+            )
+        };
+        film.color_space = color_space;
+        film.white_balance = balance;
+        for name in params.find_string("aovs") {
+            match AovType::from_name(&name) {
+                Some(aov_type) => {
+                    if film.aovs.iter().all(|(t, _aov)| *t != aov_type) {
+                        let aov: FilmAov = film.make_aov(aov_type.name());
+                        film.aovs.push((aov_type, aov));
+                    }
+                }
+                None => warn!("AOV {:?} unknown. Ignoring it.", name),
+            }
+        }
+        film.write_features = params.find_one_bool("denoiserfeatures", false);
+        film.denoise = params.find_one_bool("denoise", false);
+        if disk_backed && film.denoise {
+            warn!("Film \"denoise\" is ignored with \"diskbacked\".");
+            film.denoise = false;
+        }
+        if film.write_features || film.denoise {
+            film.features = Some(
+                (0..film.cropped_pixel_bounds.area())
+                    .map(|_| Default::default())
+                    .collect(),
+            );
+        }
+        if params.find_one_bool("deep", false) {
+            film.deep = Some(Mutex::new(vec![
+                DeepPixel::default();
+                film.cropped_pixel_bounds.area() as usize
+            ]));
+        }
+        if disk_backed {
+            film.disk = Some(Mutex::new(DiskTiles::new(&film)));
+        } else if half_precision {
+            film.half = Some(HalfPixels::new(film.cropped_pixel_bounds.area() as usize));
+        }
+        Arc::new(film)
     }
     pub fn get_cropped_pixel_bounds(&self) -> Bounds2i {
         self.cropped_pixel_bounds
@@ -337,18 +1055,79 @@ impl Film {
             },
             &self.cropped_pixel_bounds,
         );
-        FilmTile::new(
+        let mut tile: FilmTile = FilmTile::new(
             tile_pixel_bounds,
             self.filter.get_radius(),
             &self.filter_table,
             FILTER_TABLE_WIDTH,
             self.max_sample_luminance,
-        )
+        );
+        tile.sample_bounds = *sample_bounds;
+        tile.aov_pixels =
+            vec![vec![[0.0 as Float; 4]; tile_pixel_bounds.area() as usize]; self.aovs.len()];
+        if self.deep.is_some() {
+            tile.deep_pixels = vec![DeepPixel::default(); tile_pixel_bounds.area() as usize];
+        }
+        if self.features.is_some() {
+            tile.feature_pixels = vec![[0.0 as Float; 7]; tile_pixel_bounds.area() as usize];
+        }
+        tile
     }
     pub fn merge_film_tile(&self, tile: &FilmTile) {
         // TODO: ProfilePhase p(Prof::MergeFilmTile);
         // println!("Merging film tile {:?}", tile.pixel_bounds);
-        // TODO: std::lock_guard<std::mutex> lock(mutex);
+        self.add_splats(&tile.splats);
+        for ((_aov_type, aov), sums) in self.aovs.iter().zip(tile.aov_pixels.iter()) {
+            for pixel in &tile.pixel_bounds {
+                let sum: &[Float; 4] = &sums[tile.get_pixel_index(pixel.x, pixel.y)];
+                if sum[3] != 0.0 as Float {
+                    aov.merge(pixel, sum);
+                }
+            }
+        }
+        if let Some(ref features) = self.features {
+            let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+            for (pixel, sums) in (&tile.pixel_bounds).into_iter().zip(&tile.feature_pixels) {
+                let offset: i32 = (pixel.x - self.cropped_pixel_bounds.p_min.x)
+                    + (pixel.y - self.cropped_pixel_bounds.p_min.y) * width;
+                for (value, sum) in features[offset as usize].iter().zip(sums.iter()) {
+                    value.add(*sum);
+                }
+            }
+        }
+        if let Some(ref deep) = self.deep {
+            let mut deep_pixels = deep.lock().unwrap();
+            let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+            for (pixel, tile_pixel) in (&tile.pixel_bounds).into_iter().zip(&tile.deep_pixels) {
+                let offset: i32 = (pixel.x - self.cropped_pixel_bounds.p_min.x)
+                    + (pixel.y - self.cropped_pixel_bounds.p_min.y) * width;
+                let deep_pixel: &mut DeepPixel = &mut deep_pixels[offset as usize];
+                deep_pixel.samples.extend_from_slice(&tile_pixel.samples);
+                deep_pixel.weight_sum += tile_pixel.weight_sum;
+                DEEP_SAMPLE_BYTES
+                    .add(tile_pixel.samples.len() * std::mem::size_of::<DeepFilmSample>());
+            }
+        }
+        if let Some(ref disk) = self.disk {
+            disk.lock().unwrap().merge_film_tile(self, tile);
+            return;
+        }
+        if let Some(ref half) = self.half {
+            let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+            for y in tile.pixel_bounds.p_min.y..tile.pixel_bounds.p_max.y {
+                let _row = half.row_lock(y).lock().unwrap();
+                for x in tile.pixel_bounds.p_min.x..tile.pixel_bounds.p_max.x {
+                    assert!(pnt2_inside_exclusive(
+                        Point2i { x, y },
+                        &self.cropped_pixel_bounds
+                    ));
+                    let offset: i32 = (x - self.cropped_pixel_bounds.p_min.x)
+                        + (y - self.cropped_pixel_bounds.p_min.y) * width;
+                    half.merge(offset as usize, &tile.pixels[tile.get_pixel_index(x, y)]);
+                }
+            }
+            return;
+        }
         for pixel in &tile.pixel_bounds {
             // merge _pixel_ into _Film::pixels_
             let idx = tile.get_pixel_index(pixel.x, pixel.y);
@@ -358,63 +1137,59 @@ impl Film {
             let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
             let offset: i32 = (pixel.x - self.cropped_pixel_bounds.p_min.x)
                 + (pixel.y - self.cropped_pixel_bounds.p_min.y) * width;
-            let mut pixels_write = self.pixels.write().unwrap();
-            let mut merge_pixel = &mut pixels_write[offset as usize];
+            let merge_pixel: &Pixel = &self.pixels[offset as usize];
             // END let mut merge_pixel: &mut Pixel = self.get_pixel_mut(pixel);
-            let mut xyz: [Float; 3] = [0.0; 3];
-            tile_pixel.contrib_sum.to_xyz(&mut xyz);
-            for (i, item) in xyz.iter().enumerate() {
-                merge_pixel.xyz[i] += item;
-            }
-            merge_pixel.filter_weight_sum += tile_pixel.filter_weight_sum;
-            // write pixel back
-            // pixels_write[offset as usize] = *merge_pixel;
+            merge_pixel.merge(tile_pixel);
         }
     }
     pub fn set_image(&self, img: &[Spectrum]) {
         let n_pixels: i32 = self.cropped_pixel_bounds.area();
-        let mut pixels_write = self.pixels.write().unwrap();
         for i in 0..n_pixels as usize {
-            let mut merge_pixel = &mut pixels_write[i];
             let mut xyz: [Float; 3] = [0.0; 3];
             img[i].to_xyz(&mut xyz);
+            if let Some(ref half) = self.half {
+                half.set(i, xyz);
+                continue;
+            }
+            let merge_pixel: &Pixel = &self.pixels[i];
             for (i, item) in xyz.iter().enumerate() {
-                merge_pixel.xyz[i] = *item;
+                merge_pixel.xyz[i].store(*item);
             }
-            merge_pixel.filter_weight_sum = 1.0 as Float;
-            merge_pixel.splat_xyz[0] = 0.0;
-            merge_pixel.splat_xyz[1] = 0.0;
-            merge_pixel.splat_xyz[2] = 0.0;
+            merge_pixel.filter_weight_sum.store(1.0 as Float);
+            merge_pixel.alpha_sum.store(1.0 as Float);
+            merge_pixel.splat_xyz[0].store(0.0);
+            merge_pixel.splat_xyz[1].store(0.0);
+            merge_pixel.splat_xyz[2].store(0.0);
         }
     }
     pub fn add_splat(&self, p: Point2f, v: &Spectrum) {
         let mut v: Spectrum = *v;
         // TODO: ProfilePhase pp(Prof::SplatFilm);
         if v.has_nans() {
-            println!(
-                "ERROR: Ignoring splatted spectrum with NaN values at ({:?}, {:?})",
+            error!(
+                "Ignoring splatted spectrum with NaN values at ({:?}, {:?})",
                 p.x, p.y
             );
             return;
         } else if v.y() < 0.0 as Float {
-            println!(
-                "ERROR: Ignoring splatted spectrum with negative luminance {:?} at ({:?}, {:?})",
+            error!(
+                "Ignoring splatted spectrum with negative luminance {:?} at ({:?}, {:?})",
                 v.y(),
                 p.x,
                 p.y
             );
             return;
         } else if v.y().is_infinite() {
-            println!(
-                "ERROR: Ignoring splatted spectrum with infinite luminance at ({:?}, {:?})",
+            error!(
+                "Ignoring splatted spectrum with infinite luminance at ({:?}, {:?})",
                 p.x, p.y
             );
             return;
         }
 
         let pi: Point2i = Point2i {
-            x: p.x as i32,
-            y: p.y as i32,
+            x: p.x.floor() as i32,
+            y: p.y.floor() as i32,
         };
         if !pnt2_inside_exclusive(pi, &self.cropped_pixel_bounds) {
             return;
@@ -427,66 +1202,239 @@ impl Film {
         let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
         let offset: i32 = (pi.x - self.cropped_pixel_bounds.p_min.x)
             + (pi.y - self.cropped_pixel_bounds.p_min.y) * width;
-        let mut pixels_write: RwLockWriteGuard<Vec<Pixel>> = self.pixels.write().unwrap();
-        let pixel_vec: &mut Vec<Pixel> = pixels_write.deref_mut();
-        let pixel: &mut Pixel = &mut pixel_vec[offset as usize];
-
-        let splat_xyz: &mut [Float; 3] = &mut pixel.splat_xyz;
-        splat_xyz[0] += xyz[0];
-        splat_xyz[1] += xyz[1];
-        splat_xyz[2] += xyz[2];
+        if let Some(ref half) = self.half {
+            half.add_splat(offset as usize, xyz);
+            return;
+        }
+        let pixel: &Pixel = &self.pixels[offset as usize];
+        pixel.splat_xyz[0].add(xyz[0]);
+        pixel.splat_xyz[1].add(xyz[1]);
+        pixel.splat_xyz[2].add(xyz[2]);
+    }
+    /// Adds *splats* one after another (see **add_splat()**), e.g. the
+    /// ones a Markov chain of **mlt** made.
+    pub fn add_splats(&self, splats: &[(Point2f, Spectrum)]) {
+        for (p, v) in splats {
+            self.add_splat(*p, v);
+        }
+    }
+    /// Paints *pixel* in the (linear RGB) color *rgb* when the image
+    /// gets written, no matter what was rendered there, e.g. to mark
+    /// pixels with NaN radiance. Not supported with *diskbacked*.
+    pub fn flag_pixel(&self, pixel: Point2i, rgb: [Float; 3]) {
+        self.flagged_pixels.lock().unwrap().push((pixel, rgb));
+    }
+    fn paint_flagged_pixels(&self, rgb: &mut [Float]) {
+        let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+        for (pixel, color) in self.flagged_pixels.lock().unwrap().iter() {
+            if pnt2_inside_exclusive(*pixel, &self.cropped_pixel_bounds) {
+                let offset: usize = ((pixel.x - self.cropped_pixel_bounds.p_min.x)
+                    + (pixel.y - self.cropped_pixel_bounds.p_min.y) * width)
+                    as usize;
+                rgb[3 * offset..3 * offset + 3].copy_from_slice(color);
+            }
+        }
+    }
+    /// The (linear) RGB values of the samples merged so far, e.g. to
+    /// show a preview while rendering. **None** with *diskbacked*.
+    pub fn preview_rgb(&self) -> Option<Vec<Float>> {
+        if self.disk.is_some() {
+            return None;
+        }
+        let xyz_to_rgb: [[Float; 3]; 3] = self.xyz_to_rgb_matrix();
+        let n_pixels: usize = self.cropped_pixel_bounds.area() as usize;
+        let mut rgb: Vec<Float> = Vec::with_capacity(3 * n_pixels);
+        for offset in 0..n_pixels {
+            rgb.extend_from_slice(&self.pixel_rgb(
+                &self.pixel_sums(offset),
+                &xyz_to_rgb,
+                1.0 as Float,
+            ));
+        }
+        Some(rgb)
+    }
+    /// The values of the pixel at *offset* (within the cropped pixel
+    /// bounds, row by row).
+    fn pixel_sums(&self, offset: usize) -> PixelSums {
+        match self.half {
+            Some(ref half) => half.sums(offset),
+            None => self.pixels[offset].sums(),
+        }
+    }
+    /// Writes the values accumulated so far (pixels, AOVs, and
+    /// denoiser features) as 64-bit floats, so a render can continue
+    /// where it stopped (see **checkpoint**). Films with *diskbacked*
+    /// or *deep* can't be written this way.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn write_sums<W: Write>(&self, w: &mut W) -> Result<()> {
+        let n_pixels: usize = self.cropped_pixel_bounds.area() as usize;
+        for offset in 0..n_pixels {
+            let sums: PixelSums = self.pixel_sums(offset);
+            for value in sums
+                .xyz
+                .iter()
+                .chain(std::iter::once(&sums.filter_weight_sum))
+                .chain(sums.splat_xyz.iter())
+                .chain(std::iter::once(&sums.alpha_sum))
+            {
+                w.write_f64::<LittleEndian>(*value as f64)?;
+            }
+        }
+        for (_aov_type, aov) in &self.aovs {
+            for value in aov.pixels.iter().flatten() {
+                w.write_f64::<LittleEndian>(Float::from(value) as f64)?;
+            }
+        }
+        if let Some(ref features) = self.features {
+            for value in features.iter().flatten() {
+                w.write_f64::<LittleEndian>(Float::from(value) as f64)?;
+            }
+        }
+        Ok(())
+    }
+    /// The number of bytes **write_sums()** writes.
+    pub fn sums_size(&self) -> usize {
+        let n_pixels: usize = self.cropped_pixel_bounds.area() as usize;
+        let n_aov_values: usize = self.aovs.iter().map(|(_, aov)| 4 * aov.pixels.len()).sum();
+        let n_feature_values: usize = self.features.as_ref().map_or(0, |f| 7 * f.len());
+        8 * (8 * n_pixels + n_aov_values + n_feature_values)
+    }
+    /// Replaces the accumulated values by the ones **write_sums()**
+    /// wrote for a film with the same pixel bounds, AOVs, and denoiser
+    /// features.
+    pub fn read_sums<R: Read>(&self, r: &mut R) -> Result<()> {
+        let n_pixels: usize = self.cropped_pixel_bounds.area() as usize;
+        let mut values: [f64; 8] = [0.0; 8];
+        for offset in 0..n_pixels {
+            r.read_f64_into::<LittleEndian>(&mut values)?;
+            let sums: PixelSums = PixelSums {
+                xyz: [values[0] as Float, values[1] as Float, values[2] as Float],
+                filter_weight_sum: values[3] as Float,
+                splat_xyz: [values[4] as Float, values[5] as Float, values[6] as Float],
+                alpha_sum: values[7] as Float,
+            };
+            match self.half {
+                Some(ref half) => half.restore(offset, &sums),
+                None => self.pixels[offset].restore(&sums),
+            }
+        }
+        for (_aov_type, aov) in &self.aovs {
+            for value in aov.pixels.iter().flatten() {
+                value.store(r.read_f64::<LittleEndian>()? as Float);
+            }
+        }
+        if let Some(ref features) = self.features {
+            for value in features.iter().flatten() {
+                value.store(r.read_f64::<LittleEndian>()? as Float);
+            }
+        }
+        Ok(())
+    }
+    /// Sets the key/value pairs (e.g. the renderer version, or the
+    /// integrator settings) which get embedded into the written images
+    /// (EXR header attributes, PNG text chunks), so they describe
+    /// themselves in review tools. The render time gets measured from
+    /// now on.
+    pub fn set_metadata(&self, metadata: Vec<(String, String)>) {
+        *self.metadata.write().unwrap() = metadata;
+        // there is no clock in WebAssembly
+        if cfg!(not(target_arch = "wasm32")) {
+            *self.render_start.lock().unwrap() = Some(Instant::now());
+        }
+    }
+    /// The metadata (see **set_metadata()**) plus the time rendering
+    /// took so far (*renderTime*, in seconds), and the pixel bounds
+    /// (*pixelBounds*, "x_min y_min x_max y_max") if they differ from
+    /// the nominal frame (e.g. with *overscan*).
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        let mut metadata: Vec<(String, String)> = self.metadata.read().unwrap().clone();
+        let bounds: Bounds2i = self.cropped_pixel_bounds;
+        if bounds.p_min != (Point2i { x: 0, y: 0 }) || bounds.p_max != self.full_resolution {
+            metadata.push((
+                String::from("pixelBounds"),
+                format!(
+                    "{} {} {} {}",
+                    bounds.p_min.x, bounds.p_min.y, bounds.p_max.x, bounds.p_max.y
+                ),
+            ));
+        }
+        if let Some(start) = *self.render_start.lock().unwrap() {
+            metadata.push((
+                String::from("renderTime"),
+                format!("{:.3}", start.elapsed().as_secs_f64()),
+            ));
+        }
+        metadata
+    }
+    /// The matrix which converts XYZ to RGB values of the color space
+    /// (white balance included).
+    fn xyz_to_rgb_matrix(&self) -> [[Float; 3]; 3] {
+        let xyz_to_rgb: [[Float; 3]; 3] = self.color_space.xyz_to_rgb_matrix();
+        if let Some(ref illuminant) = self.white_balance {
+            white_balance(&xyz_to_rgb, illuminant)
+        } else {
+            xyz_to_rgb
+        }
+    }
+    /// The final (linear) RGB value of a pixel.
+    fn pixel_rgb(
+        &self,
+        pixel: &PixelSums,
+        xyz_to_rgb: &[[Float; 3]; 3],
+        splat_scale: Float,
+    ) -> [Float; 3] {
+        // convert pixel XYZ color to RGB
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        color_matrix_mul(xyz_to_rgb, &pixel.xyz, &mut rgb);
+        // normalize pixel with weight sum
+        let filter_weight_sum: Float = pixel.filter_weight_sum;
+        if filter_weight_sum != 0.0 as Float {
+            let inv_wt: Float = 1.0 as Float / filter_weight_sum;
+            for c in &mut rgb {
+                *c = (*c * inv_wt).max(0.0 as Float);
+            }
+        }
+        // add splat value at pixel
+        let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
+        color_matrix_mul(xyz_to_rgb, &pixel.splat_xyz, &mut splat_rgb);
+        for c in 0..3 {
+            rgb[c] += splat_scale * splat_rgb[c];
+            // scale pixel value by _scale_
+            rgb[c] *= self.scale;
+        }
+        rgb
     }
     #[cfg(not(feature = "openexr"))]
     pub fn write_image(&self, splat_scale: Float) {
-        let mut rgb: Vec<Float> =
-            vec![0.0 as Float; (3 * self.cropped_pixel_bounds.area()) as usize];
-        let mut offset;
-        for p in &self.cropped_pixel_bounds {
-            // convert pixel XYZ color to RGB
-            assert!(pnt2_inside_exclusive(p, &self.cropped_pixel_bounds));
-            let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
-            offset = ((p.x - self.cropped_pixel_bounds.p_min.x)
-                + (p.y - self.cropped_pixel_bounds.p_min.y) * width) as usize;
-            let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
-
-            let start: usize = 3 * offset;
-            let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
-            xyz_to_rgb(&pixel.xyz, &mut rgb_array); // TODO: Use 'rgb' directly.
-            rgb[start] = rgb_array[0];
-            rgb[start + 1] = rgb_array[1];
-            rgb[start + 2] = rgb_array[2];
-            // normalize pixel with weight sum
-            let filter_weight_sum: Float = pixel.filter_weight_sum;
-            if filter_weight_sum != 0.0 as Float {
-                let inv_wt: Float = 1.0 as Float / filter_weight_sum;
-                rgb[start] = (rgb[start] * inv_wt).max(0.0 as Float);
-                rgb[start + 1] = (rgb[start + 1] * inv_wt).max(0.0 as Float);
-                rgb[start + 2] = (rgb[start + 2] * inv_wt).max(0.0 as Float);
-            }
-            // add splat value at pixel
-            let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
-            let pixel_splat_xyz: &[Float; 3] = &pixel.splat_xyz;
-            let splat_xyz: [Float; 3] = [
-                *pixel_splat_xyz.index(0),
-                *pixel_splat_xyz.index(1),
-                *pixel_splat_xyz.index(2),
-            ];
-            xyz_to_rgb(&splat_xyz, &mut splat_rgb);
-            rgb[start] += splat_scale * splat_rgb[0];
-            rgb[start + 1] += splat_scale * splat_rgb[1];
-            rgb[start + 2] += splat_scale * splat_rgb[2];
-            // scale pixel value by _scale_
-            rgb[start] *= self.scale;
-            rgb[start + 1] *= self.scale;
-            rgb[start + 2] *= self.scale;
+        if let Some(ref disk) = self.disk {
+            disk.lock().unwrap().finish(self);
+            return;
         }
-        let filename = "pbrt.png";
-        println!(
+        let xyz_to_rgb: [[Float; 3]; 3] = self.xyz_to_rgb_matrix();
+        let n_pixels: usize = self.cropped_pixel_bounds.area() as usize;
+        let mut rgb: Vec<Float> = Vec::with_capacity(3 * n_pixels);
+        for offset in 0..n_pixels {
+            rgb.extend_from_slice(&self.pixel_rgb(
+                &self.pixel_sums(offset),
+                &xyz_to_rgb,
+                splat_scale,
+            ));
+        }
+        self.denoise_rgb(&mut rgb);
+        self.paint_flagged_pixels(&mut rgb);
+        *self.image.write().unwrap() = rgb.clone();
+        if !self.write_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let filename: &str = self.png_filename();
+        info!(
             "Writing image {:?} with bounds {:?}",
-            filename, // TODO: self.filename,
+            filename,
             self.cropped_pixel_bounds
         );
         // TODO: pbrt::WriteImage(filename, &rgb[0], croppedPixelBounds, fullResolution);
+        // PNG files expect straight (not premultiplied) alpha
+        let alpha: Option<Vec<Float>> = self.unpremultiply(&mut rgb);
         let mut buffer: Vec<u8> = vec![0.0 as u8; (3 * self.cropped_pixel_bounds.area()) as usize];
         // 8-bit format; apply gamma (see WriteImage(...) in imageio.cpp)
         let width: u32 =
@@ -518,15 +1466,8 @@ impl Film {
                 ) as u8;
             }
         }
-        // write "pbrt.png" to disk
-        image::save_buffer(
-            &Path::new("pbrt.png"),
-            &buffer,
-            width,
-            height,
-            image::ColorType::Rgb8,
-        )
-        .unwrap();
+        // write "pbrt.png" (or the PNG filename) to disk
+        self.save_png(&buffer, alpha, width, height);
     }
     #[cfg(feature = "openexr")]
     pub fn write_image(&self, splat_scale: Float) {
@@ -534,6 +1475,7 @@ impl Film {
             vec![0.0 as Float; (3 * self.cropped_pixel_bounds.area()) as usize];
         let mut exr: Vec<(Float, Float, Float)> = // copy data for OpenEXR image
             vec![(0.0_f32, 0.0_f32, 0.0_f32); self.cropped_pixel_bounds.area() as usize];
+        let xyz_to_rgb: [[Float; 3]; 3] = self.xyz_to_rgb_matrix();
         let mut offset;
         for p in &self.cropped_pixel_bounds {
             // convert pixel XYZ color to RGB
@@ -541,49 +1483,35 @@ impl Film {
             let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
             offset = ((p.x - self.cropped_pixel_bounds.p_min.x)
                 + (p.y - self.cropped_pixel_bounds.p_min.y) * width) as usize;
-            let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
             let start = 3 * offset;
-            let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
-            xyz_to_rgb(&pixel.xyz, &mut rgb_array); // TODO: Use 'rgb' directly.
+            let rgb_array: [Float; 3] =
+                self.pixel_rgb(&self.pixel_sums(offset), &xyz_to_rgb, splat_scale);
             rgb[start] = rgb_array[0];
             rgb[start + 1] = rgb_array[1];
             rgb[start + 2] = rgb_array[2];
-            // normalize pixel with weight sum
-            let filter_weight_sum: Float = pixel.filter_weight_sum;
-            if filter_weight_sum != 0.0 as Float {
-                let inv_wt: Float = 1.0 as Float / filter_weight_sum;
-                rgb[start] = (rgb[start] * inv_wt).max(0.0 as Float);
-                rgb[start + 1] = (rgb[start + 1] * inv_wt).max(0.0 as Float);
-                rgb[start + 2] = (rgb[start + 2] * inv_wt).max(0.0 as Float);
-            }
-            // add splat value at pixel
-            let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
-            let pixel_splat_xyz: &[Float; 3] = &pixel.splat_xyz;
-            let splat_xyz: [Float; 3] = [
-                *pixel_splat_xyz.index(0),
-                *pixel_splat_xyz.index(1),
-                *pixel_splat_xyz.index(2),
-            ];
-            xyz_to_rgb(&splat_xyz, &mut splat_rgb);
-            rgb[start] += splat_scale * splat_rgb[0];
-            rgb[start + 1] += splat_scale * splat_rgb[1];
-            rgb[start + 2] += splat_scale * splat_rgb[2];
-            // scale pixel value by _scale_
-            rgb[start] *= self.scale;
-            rgb[start + 1] *= self.scale;
-            rgb[start + 2] *= self.scale;
             // copy data for OpenEXR image
             exr[offset].0 = rgb[start];
             exr[offset].1 = rgb[start + 1];
             exr[offset].2 = rgb[start + 2];
         }
-        let filename = "pbrt.png";
-        println!(
+        self.denoise_rgb(&mut rgb);
+        self.paint_flagged_pixels(&mut rgb);
+        for (offset, pixel) in exr.iter_mut().enumerate() {
+            *pixel = (rgb[3 * offset], rgb[3 * offset + 1], rgb[3 * offset + 2]);
+        }
+        *self.image.write().unwrap() = rgb.clone();
+        if !self.write_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let filename: &str = self.png_filename();
+        info!(
             "Writing image {:?} with bounds {:?}",
-            filename, // TODO: self.filename,
+            filename,
             self.cropped_pixel_bounds
         );
         // TODO: pbrt::WriteImage(filename, &rgb[0], croppedPixelBounds, fullResolution);
+        // PNG files expect straight (not premultiplied) alpha
+        let alpha: Option<Vec<Float>> = self.unpremultiply(&mut rgb);
         let mut buffer: Vec<u8> = vec![0.0 as u8; (3 * self.cropped_pixel_bounds.area()) as usize];
         // 8-bit format; apply gamma (see WriteImage(...) in imageio.cpp)
         let width: u32 =
@@ -592,7 +1520,7 @@ impl Film {
             (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as u32;
         // OpenEXR
         let filename = "pbrt_rust.exr";
-        println!(
+        info!(
             "Writing image {:?} with bounds {:?}",
             filename, // TODO: self.filename,
             self.cropped_pixel_bounds
@@ -637,15 +1565,278 @@ impl Film {
                 ) as u8;
             }
         }
-        // write "pbrt.png" to disk
-        image::save_buffer(
-            &Path::new("pbrt.png"),
-            &buffer,
-            width,
-            height,
-            image::ColorType::Rgb8,
-        )
-        .unwrap();
+        // write "pbrt.png" (or the PNG filename) to disk
+        self.save_png(&buffer, alpha, width, height);
+    }
+    /// Returns the (normalized) alpha value of each pixel, but only if
+    /// at least one sample had a coverage below one (see
+    /// **FilmTile::add_sample_with_alpha()**) or *write_alpha* is set.
+    fn get_alpha(&self) -> Option<Vec<Float>> {
+        let alpha: Vec<Float> = (0..self.cropped_pixel_bounds.area() as usize)
+            .map(|offset| pixel_alpha(&self.pixel_sums(offset)))
+            .collect();
+        if self.write_alpha.load(Ordering::Relaxed)
+            || alpha.iter().any(|a| *a < 1.0 as Float - 1.0e-4 as Float)
+        {
+            Some(alpha)
+        } else {
+            None
+        }
+    }
+    /// PNG files are written to the *filename* of the film, other
+    /// formats (like the default "pbrt.exr") are written as "pbrt.png".
+    fn png_filename(&self) -> &str {
+        if self.filename.to_lowercase().ends_with(".png") {
+            &self.filename
+        } else {
+            "pbrt.png"
+        }
+    }
+    /// Divides the (premultiplied) colors in *rgb* by the alpha
+    /// values of **get_alpha()** and returns those.
+    fn unpremultiply(&self, rgb: &mut [Float]) -> Option<Vec<Float>> {
+        let alpha: Option<Vec<Float>> = self.get_alpha();
+        if let Some(ref alpha) = alpha {
+            for (pixel, a) in rgb.chunks_mut(3).zip(alpha) {
+                for c in pixel {
+                    *c = if *a > 0.0 as Float {
+                        *c / *a
+                    } else {
+                        0.0 as Float
+                    };
+                }
+            }
+        }
+        alpha
+    }
+    fn save_png(&self, buffer: &[u8], alpha: Option<Vec<Float>>, width: u32, height: u32) {
+        let mut png: Vec<u8> = Vec::new();
+        let result = if let Some(alpha) = alpha {
+            // add alpha channel
+            let mut rgba: Vec<u8> = vec![0_u8; (4 * width * height) as usize];
+            for (i, a) in alpha.iter().enumerate() {
+                rgba[4 * i] = buffer[3 * i];
+                rgba[4 * i + 1] = buffer[3 * i + 1];
+                rgba[4 * i + 2] = buffer[3 * i + 2];
+                rgba[4 * i + 3] =
+                    clamp_t(255.0 as Float * *a + 0.5, 0.0 as Float, 255.0 as Float) as u8;
+            }
+            PngEncoder::new(&mut png).encode(&rgba, width, height, image::ColorType::Rgba8)
+        } else {
+            PngEncoder::new(&mut png).encode(buffer, width, height, image::ColorType::Rgb8)
+        };
+        result.unwrap();
+        add_png_text(&mut png, &self.metadata());
+        std::fs::write(self.png_filename(), &png).unwrap();
+    }
+    /// An extra image (see **FilmAov**) with the (cropped) pixel
+    /// bounds of the film.
+    pub fn make_aov(&self, name: &str) -> FilmAov {
+        FilmAov::new(name, self.cropped_pixel_bounds)
+    }
+    /// Writes an extra image to an OpenEXR file next to the image,
+    /// e.g. "pbrt_dx.exr" for the AOV "dx" of "pbrt.png".
+    pub fn write_aov(&self, aov: &FilmAov) {
+        if !self.write_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let png_filename: &str = self.png_filename();
+        let stem: &str = &png_filename[..png_filename.len() - ".png".len()];
+        let filename: String = format!("{}_{}.exr", stem, aov.name);
+        info!("Writing AOV {:?} to {:?}", aov.name, filename);
+        if let Err(err) = write_exr(
+            &filename,
+            self.full_resolution,
+            aov.pixel_bounds,
+            &aov.rgb(),
+            &self.metadata(),
+        ) {
+            error!("Can't write {:?}: {}", filename, err);
+        }
+    }
+    /// Does the film want denoiser features (see *denoiserfeatures*
+    /// and *denoise*)?
+    pub fn wants_denoiser_features(&self) -> bool {
+        self.features.is_some()
+    }
+    /// The albedo and normal (RGB and XYZ) of each pixel, but only if
+    /// the integrator provided them (see **FilmTile::add_features()**).
+    fn feature_images(&self) -> Option<(Vec<Float>, Vec<Float>)> {
+        let features = self.features.as_ref()?;
+        let mut filled: bool = false;
+        let mut albedo: Vec<Float> = Vec::with_capacity(3 * features.len());
+        let mut normal: Vec<Float> = Vec::with_capacity(3 * features.len());
+        for sums in features {
+            let weight: Float = Float::from(&sums[6]);
+            let inv_weight: Float = if weight != 0.0 as Float {
+                filled = true;
+                1.0 as Float / weight
+            } else {
+                0.0 as Float
+            };
+            for c in 0..3 {
+                albedo.push(Float::from(&sums[c]) * inv_weight);
+                normal.push(Float::from(&sums[3 + c]) * inv_weight);
+            }
+        }
+        if filled {
+            Some((albedo, normal))
+        } else {
+            None
+        }
+    }
+    /// Replaces the (linear) RGB values of the image by denoised ones
+    /// with *denoise*, the noisy ones are kept (see **noisy_image**).
+    fn denoise_rgb(&self, rgb: &mut Vec<Float>) {
+        if !self.denoise {
+            return;
+        }
+        let width: usize =
+            (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as usize;
+        let height: usize =
+            (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as usize;
+        let features: Option<(Vec<Float>, Vec<Float>)> = self.feature_images();
+        let guide: Option<(&[Float], &[Float])> = features
+            .as_ref()
+            .map(|(albedo, normal)| (&albedo[..], &normal[..]));
+        if let Some(denoised) = denoise_image(width, height, rgb, guide) {
+            *self.noisy_image.write().unwrap() = std::mem::replace(rgb, denoised);
+        }
+    }
+    /// Writes the image (as written last, see **image**, but before
+    /// it was denoised), and the albedo and normal of each pixel to
+    /// PFM files next to it, e.g. "pbrt_color.pfm", "pbrt_albedo.pfm",
+    /// and "pbrt_normal.pfm" for "pbrt.png", as expected by denoisers
+    /// (like Intel Open Image Denoise or OptiX).
+    pub fn write_denoiser_features(&self) {
+        if !self.write_features || !self.write_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let (albedo, normal) = match self.feature_images() {
+            Some(images) => images,
+            None => return,
+        };
+        let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+        let height: i32 = self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y;
+        let png_filename: &str = self.png_filename();
+        let stem: &str = &png_filename[..png_filename.len() - ".png".len()];
+        let noisy: Vec<Float> = self.noisy_image.read().unwrap().clone();
+        let color: Vec<Float> = if noisy.is_empty() {
+            self.image.read().unwrap().clone()
+        } else {
+            noisy
+        };
+        let mut images: Vec<(&str, Vec<Float>)> = vec![("albedo", albedo), ("normal", normal)];
+        if color.len() == 3 * (width * height) as usize {
+            images.insert(0, ("color", color));
+        } else {
+            // e.g. with *diskbacked*
+            warn!("No image to write as \"color\" for the denoiser.");
+        }
+        for (name, rgb) in &images {
+            let filename: String = format!("{}_{}.pfm", stem, name);
+            info!("Writing denoiser {} to {:?}", name, filename);
+            if let Err(err) = write_pfm(&filename, width, height, rgb) {
+                error!("Can't write {:?}: {}", filename, err);
+            }
+        }
+    }
+    /// Does it write the image tile by tile (*diskbacked*, see
+    /// **DiskTiles**)?
+    pub fn is_disk_backed(&self) -> bool {
+        self.disk.is_some()
+    }
+    /// Is it a *deep* film (see **write_deep()**)?
+    pub fn is_deep(&self) -> bool {
+        self.deep.is_some()
+    }
+    /// Writes the samples of a *deep* film to a deep OpenEXR file next
+    /// to the image, e.g. "pbrt_deep.exr" for "pbrt.png".
+    pub fn write_deep(&self) {
+        let deep = match self.deep {
+            Some(ref deep) => deep,
+            None => return,
+        };
+        if !self.write_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let xyz_to_rgb: [[Float; 3]; 3] = self.xyz_to_rgb_matrix();
+        let pixels: Vec<Vec<DeepSample>> = deep
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|deep_pixel| self.deep_samples(deep_pixel, &xyz_to_rgb))
+            .collect();
+        let png_filename: &str = self.png_filename();
+        let stem: &str = &png_filename[..png_filename.len() - ".png".len()];
+        let filename: String = format!("{}_deep.exr", stem);
+        info!(
+            "Writing {} deep sample(s) to {:?}",
+            pixels.iter().map(|samples| samples.len()).sum::<usize>(),
+            filename
+        );
+        if let Err(err) = write_deep_exr(
+            &filename,
+            self.full_resolution,
+            self.cropped_pixel_bounds,
+            &pixels,
+            &self.metadata(),
+        ) {
+            error!("Can't write {:?}: {}", filename, err);
+        }
+    }
+    /// The samples of a deep pixel, front to back. Each camera sample
+    /// covers its (weighted) share of the pixel, the coverage of a
+    /// deep sample is relative to what the samples in front of it
+    /// leave uncovered.
+    fn deep_samples(
+        &self,
+        deep_pixel: &DeepPixel,
+        xyz_to_rgb: &[[Float; 3]; 3],
+    ) -> Vec<DeepSample> {
+        if deep_pixel.weight_sum <= 0.0 as Float {
+            return Vec::new();
+        }
+        let mut sorted: Vec<DeepFilmSample> = deep_pixel.samples.clone();
+        sorted.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap());
+        // merge samples of (nearly) the same depth, with the
+        // coverage and (premultiplied) color they add to the pixel
+        let mut merged: Vec<DeepSample> = Vec::new();
+        for sample in &sorted {
+            let share: Float = sample.weight / deep_pixel.weight_sum;
+            let mut rgb: [Float; 3] = [0.0 as Float; 3];
+            color_matrix_mul(xyz_to_rgb, &sample.xyz, &mut rgb);
+            for c in &mut rgb {
+                *c *= share * self.scale;
+            }
+            if let Some(last) = merged.last_mut() {
+                if sample.z == last.z || sample.z - last.z <= DEEP_MERGE_TOLERANCE * last.z {
+                    for (sum, c) in last.rgb.iter_mut().zip(rgb.iter()) {
+                        *sum += *c;
+                    }
+                    last.alpha += sample.alpha * share;
+                    continue;
+                }
+            }
+            merged.push(DeepSample {
+                z: sample.z,
+                rgb,
+                alpha: sample.alpha * share,
+            });
+        }
+        // compositing them front to back gives the sums again
+        let mut uncovered: Float = 1.0 as Float;
+        for sample in &mut merged {
+            let coverage: Float = sample.alpha;
+            if uncovered > 0.0 as Float {
+                for c in &mut sample.rgb {
+                    *c /= uncovered;
+                }
+                sample.alpha = (sample.alpha / uncovered).min(1.0 as Float);
+            }
+            uncovered = (uncovered - coverage).max(0.0 as Float);
+        }
+        merged
     }
     // pub fn get_pixel<'a>(&self, p: &Point2i) -> &'a Pixel {
     //     assert!(pnt2_inside_exclusive(p, &self.cropped_pixel_bounds));
@@ -655,3 +1846,352 @@ impl Film {
     //     &self.pixels.read().unwrap()[offset as usize]
     // }
 }
+
+/// An extra image (arbitrary output variable) of a film, e.g. the
+/// image gradients of **GradientPathIntegrator**. Each pixel averages
+/// the (possibly negative) RGB values added to it, without filtering.
+pub struct FilmAov {
+    pub name: String,
+    pub pixel_bounds: Bounds2i,
+    /// RGB and weight sums
+    pixels: Vec<[AtomicFloat; 4]>,
+}
+
+impl FilmAov {
+    pub fn new(name: &str, pixel_bounds: Bounds2i) -> Self {
+        FilmAov {
+            name: String::from(name),
+            pixel_bounds,
+            pixels: (0..pixel_bounds.area())
+                .map(|_| Default::default())
+                .collect(),
+        }
+    }
+    pub fn add(&self, p: Point2i, l: &Spectrum, weight: Float) {
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        l.to_rgb(&mut rgb);
+        self.merge(
+            p,
+            &[rgb[0] * weight, rgb[1] * weight, rgb[2] * weight, weight],
+        );
+    }
+    /// Adds RGB and weight sums (e.g. of a **FilmTile**) to pixel *p*.
+    pub fn merge(&self, p: Point2i, sums: &[Float; 4]) {
+        debug_assert!(pnt2_inside_exclusive(p, &self.pixel_bounds));
+        let width: i32 = self.pixel_bounds.p_max.x - self.pixel_bounds.p_min.x;
+        let offset: i32 =
+            (p.y - self.pixel_bounds.p_min.y) * width + (p.x - self.pixel_bounds.p_min.x);
+        let pixel: &[AtomicFloat; 4] = &self.pixels[offset as usize];
+        for (value, sum) in pixel.iter().zip(sums.iter()) {
+            value.add(*sum);
+        }
+    }
+    /// The average RGB value of each pixel (black without any), row by
+    /// row.
+    pub fn rgb(&self) -> Vec<Float> {
+        let mut rgb: Vec<Float> = Vec::with_capacity(3 * self.pixels.len());
+        for pixel in &self.pixels {
+            let weight: Float = Float::from(&pixel[3]);
+            for value in pixel.iter().take(3) {
+                rgb.push(if weight > 0.0 as Float {
+                    Float::from(value) / weight
+                } else {
+                    0.0 as Float
+                });
+            }
+        }
+        rgb
+    }
+}
+
+/// The pixels of a film with *diskbacked*. Each tile of the image is
+/// kept in memory until all samples which can contribute to its
+/// pixels were merged (see **Film::merge_film_tile()**), then it gets
+/// written to a tiled OpenEXR file (see **TiledExrWriter**).
+struct DiskTiles {
+    /// "pbrt.exr" or the EXR filename of the film
+    filename: String,
+    /// the pixels of tiles which are not complete yet
+    pixels: HashMap<usize, Vec<Pixel>>,
+    /// the sample positions (pixels of the sample bounds) which
+    /// contribute to each tile
+    needed: Vec<Bounds2i>,
+    /// the number of those sample positions which were not merged yet
+    missing: Vec<i64>,
+    written: Vec<bool>,
+    writer: Option<TiledExrWriter>,
+    /// write errors are reported only once
+    failed: bool,
+}
+
+impl DiskTiles {
+    fn new(film: &Film) -> Self {
+        let filename: String = if film.filename.to_lowercase().ends_with(".exr") {
+            film.filename.clone()
+        } else {
+            if !film.filename.is_empty() {
+                warn!(
+                    "Film \"diskbacked\" writes OpenEXR files, writing \"pbrt.exr\" instead of {:?}.",
+                    film.filename
+                );
+            }
+            String::from("pbrt.exr")
+        };
+        let (tiles_x, tiles_y) = tile_count(&film.cropped_pixel_bounds, DISK_TILE_SIZE);
+        let n_tiles: usize = (tiles_x * tiles_y).max(0) as usize;
+        let sample_bounds: Bounds2i = film.get_sample_bounds();
+        let radius: Vector2f = film.filter.get_radius();
+        let mut needed: Vec<Bounds2i> = Vec::with_capacity(n_tiles);
+        let mut missing: Vec<i64> = Vec::with_capacity(n_tiles);
+        for index in 0..n_tiles {
+            // samples contribute to pixels within the filter radius
+            let bounds: Bounds2i = tile_bounds(&film.cropped_pixel_bounds, DISK_TILE_SIZE, index);
+            let samples: Bounds2i = bnd2_intersect_bnd2(
+                &Bounds2i {
+                    p_min: Point2i {
+                        x: (bounds.p_min.x as Float - 0.5 as Float - radius.x).floor() as i32,
+                        y: (bounds.p_min.y as Float - 0.5 as Float - radius.y).floor() as i32,
+                    },
+                    p_max: Point2i {
+                        x: (bounds.p_max.x as Float - 0.5 as Float + radius.x).ceil() as i32 + 1,
+                        y: (bounds.p_max.y as Float - 0.5 as Float + radius.y).ceil() as i32 + 1,
+                    },
+                },
+                &sample_bounds,
+            );
+            needed.push(samples);
+            missing.push(area(&samples));
+        }
+        DiskTiles {
+            filename,
+            pixels: HashMap::new(),
+            needed,
+            missing,
+            written: vec![false; n_tiles],
+            writer: None,
+            failed: false,
+        }
+    }
+    /// The tiles which overlap *bounds*.
+    fn tiles_overlapping(&self, film: &Film, bounds: &Bounds2i) -> Vec<usize> {
+        let data_window: &Bounds2i = &film.cropped_pixel_bounds;
+        let (tiles_x, tiles_y) = tile_count(data_window, DISK_TILE_SIZE);
+        let tile = |v: i32, origin: i32, n: i32| -> i32 {
+            ((v - origin).max(0) / DISK_TILE_SIZE).min(n - 1)
+        };
+        let x0: i32 = tile(bounds.p_min.x, data_window.p_min.x, tiles_x);
+        let x1: i32 = tile(bounds.p_max.x - 1, data_window.p_min.x, tiles_x);
+        let y0: i32 = tile(bounds.p_min.y, data_window.p_min.y, tiles_y);
+        let y1: i32 = tile(bounds.p_max.y - 1, data_window.p_min.y, tiles_y);
+        let mut tiles: Vec<usize> = Vec::new();
+        for ty in y0.max(0)..=y1 {
+            for tx in x0.max(0)..=x1 {
+                tiles.push((ty * tiles_x + tx) as usize);
+            }
+        }
+        tiles
+    }
+    fn merge_film_tile(&mut self, film: &Film, tile: &FilmTile) {
+        for index in self.tiles_overlapping(film, &tile.pixel_bounds) {
+            let bounds: Bounds2i = tile_bounds(&film.cropped_pixel_bounds, DISK_TILE_SIZE, index);
+            let overlap: Bounds2i = bnd2_intersect_bnd2(&bounds, &tile.pixel_bounds);
+            if area(&overlap) == 0 {
+                continue;
+            }
+            if self.written[index] {
+                warn!(
+                    "Samples for pixels {:?}, which were already written to disk, are ignored.",
+                    overlap
+                );
+                continue;
+            }
+            let pixels: &mut Vec<Pixel> = self
+                .pixels
+                .entry(index)
+                .or_insert_with(|| vec![Pixel::default(); bounds.area() as usize]);
+            let width: i32 = bounds.p_max.x - bounds.p_min.x;
+            for pixel in &overlap {
+                let tile_pixel: &FilmTilePixel =
+                    &tile.pixels[tile.get_pixel_index(pixel.x, pixel.y)];
+                pixels[((pixel.x - bounds.p_min.x) + (pixel.y - bounds.p_min.y) * width) as usize]
+                    .merge(tile_pixel);
+            }
+        }
+        // samples of the tile can contribute to tiles beyond its pixel bounds
+        let radius: Vector2f = film.filter.get_radius();
+        let reach: Bounds2i = Bounds2i {
+            p_min: Point2i {
+                x: tile.sample_bounds.p_min.x - radius.x.ceil() as i32 - 2,
+                y: tile.sample_bounds.p_min.y - radius.y.ceil() as i32 - 2,
+            },
+            p_max: Point2i {
+                x: tile.sample_bounds.p_max.x + radius.x.ceil() as i32 + 2,
+                y: tile.sample_bounds.p_max.y + radius.y.ceil() as i32 + 2,
+            },
+        };
+        for index in self.tiles_overlapping(film, &reach) {
+            self.missing[index] -= area(&bnd2_intersect_bnd2(
+                &self.needed[index],
+                &tile.sample_bounds,
+            ));
+            if self.missing[index] <= 0 && !self.written[index] {
+                self.write_tile(film, index);
+            }
+        }
+    }
+    /// Writes the pixels of a tile to disk (and frees them).
+    fn write_tile(&mut self, film: &Film, index: usize) {
+        self.written[index] = true;
+        let pixels: Option<Vec<Pixel>> = self.pixels.remove(&index);
+        if self.writer.is_none() && !self.failed {
+            info!(
+                "Writing image {:?} (tile by tile) with bounds {:?}",
+                self.filename, film.cropped_pixel_bounds
+            );
+            // the header is written first, i.e. without the render time
+            let metadata: Vec<(String, String)> = film.metadata.read().unwrap().clone();
+            match TiledExrWriter::create(
+                &self.filename,
+                film.full_resolution,
+                film.cropped_pixel_bounds,
+                DISK_TILE_SIZE,
+                film.write_alpha.load(Ordering::Relaxed),
+                &metadata,
+            ) {
+                Ok(writer) => self.writer = Some(writer),
+                Err(err) => self.write_error(err),
+            }
+        }
+        if let Some(ref mut writer) = self.writer {
+            let xyz_to_rgb: [[Float; 3]; 3] = film.xyz_to_rgb_matrix();
+            let n_pixels: usize = writer.tile_bounds(index).area() as usize;
+            let n_channels: usize = writer.n_channels();
+            let mut values: Vec<Float> = Vec::with_capacity(n_channels * n_pixels);
+            match pixels {
+                Some(pixels) => {
+                    for pixel in &pixels {
+                        let sums: PixelSums = pixel.sums();
+                        values.extend_from_slice(&film.pixel_rgb(&sums, &xyz_to_rgb, 1.0 as Float));
+                        if writer.alpha {
+                            values.push(pixel_alpha(&sums));
+                        }
+                    }
+                }
+                None => values.resize(n_channels * n_pixels, 0.0 as Float),
+            }
+            if let Err(err) = writer.write_tile(index, &values) {
+                self.writer = None;
+                self.write_error(err);
+            }
+        }
+    }
+    /// Writes all remaining tiles (e.g. if rendering was cancelled)
+    /// and finishes the file.
+    fn finish(&mut self, film: &Film) {
+        for index in 0..self.written.len() {
+            if !self.written[index] {
+                self.write_tile(film, index);
+            }
+        }
+        if let Some(writer) = self.writer.take() {
+            if let Err(err) = writer.finish() {
+                self.write_error(err);
+            }
+        }
+    }
+    fn write_error(&mut self, err: std::io::Error) {
+        if !self.failed {
+            error!("Unable to write {:?}: {}", self.filename, err);
+            self.failed = true;
+        }
+    }
+}
+
+/// The (normalized) alpha value of a pixel.
+fn pixel_alpha(pixel: &PixelSums) -> Float {
+    if pixel.filter_weight_sum != 0.0 as Float {
+        clamp_t(
+            pixel.alpha_sum / pixel.filter_weight_sum,
+            0.0 as Float,
+            1.0 as Float,
+        )
+    } else {
+        1.0 as Float
+    }
+}
+
+/// Inserts an (uncompressed, UTF-8) iTXt chunk for each key/value
+/// pair right before the IEND chunk, which ends the PNG file. Pairs
+/// with a key which isn't a valid PNG keyword get dropped.
+fn add_png_text(png: &mut Vec<u8>, text: &[(String, String)]) {
+    let iend: Vec<u8> = png.split_off(png.len() - 12);
+    for (key, value) in text {
+        let keyword: Vec<u8> = match png_keyword(key) {
+            Some(keyword) => keyword,
+            None => {
+                warn!(
+                    "{:?} is not a valid PNG keyword, not written to the image.",
+                    key
+                );
+                continue;
+            }
+        };
+        let mut chunk: Vec<u8> = Vec::new();
+        chunk.extend_from_slice(b"iTXt");
+        // keyword, not compressed, no language or translated keyword
+        chunk.extend_from_slice(&keyword);
+        chunk.extend_from_slice(&[0_u8, 0_u8, 0_u8, 0_u8, 0_u8]);
+        chunk.extend_from_slice(value.as_bytes());
+        let mut hasher: Hasher = Hasher::new();
+        hasher.update(&chunk);
+        png.extend_from_slice(&((chunk.len() - 4) as u32).to_be_bytes());
+        png.extend_from_slice(&chunk);
+        png.extend_from_slice(&hasher.finalize().to_be_bytes());
+    }
+    png.extend_from_slice(&iend);
+}
+
+/// The Latin-1 bytes of a PNG keyword: 1 to 79 printable characters
+/// (no NUL), without leading, trailing, or consecutive spaces, or None
+/// if *key* can't be one.
+fn png_keyword(key: &str) -> Option<Vec<u8>> {
+    let keyword: Vec<u8> = key
+        .chars()
+        .map(|c| match c as u32 {
+            32..=126 | 161..=255 => Some(c as u32 as u8),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    if keyword.is_empty()
+        || keyword.len() > 79
+        || keyword.starts_with(b" ")
+        || keyword.ends_with(b" ")
+        || keyword.windows(2).any(|pair| pair == b"  ")
+    {
+        None
+    } else {
+        Some(keyword)
+    }
+}
+
+/// Writes a (linear) RGB image in the Portable Float Map format, *rgb*
+/// holds the pixels row by row from the top (PFM files store them
+/// from the bottom).
+fn write_pfm(filename: &str, width: i32, height: i32, rgb: &[Float]) -> std::io::Result<()> {
+    let mut pfm: Vec<u8> = format!("PF\n{} {}\n-1\n", width, height).into_bytes();
+    pfm.reserve(12 * (width * height) as usize);
+    for y in (0..height as usize).rev() {
+        let row: &[Float] = &rgb[3 * y * width as usize..3 * (y + 1) * width as usize];
+        for &value in row {
+            let value: Float = value;
+            // negative scale: little endian
+            pfm.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+    }
+    std::fs::write(filename, &pfm)
+}
+
+/// Like **Bounds2i::area()**, but without overflows for huge images.
+fn area(b: &Bounds2i) -> i64 {
+    (b.p_max.x - b.p_min.x).max(0) as i64 * (b.p_max.y - b.p_min.y).max(0) as i64
+}
@@ -3,43 +3,62 @@
 // std
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::result::Result;
+// others
+use log::warn;
 // pbrt
+use crate::core::error::{Error, Result};
+use crate::core::memory::readable_path;
 use crate::core::pbrt::Float;
+use crate::core::spectrum::{sort_spectrum_samples, spectrum_samples_sorted};
 
-pub fn read_float_file(filename: &str, values: &mut Vec<Float>) -> bool {
-    let path = Path::new(&filename);
-    let result = File::open(path);
-    if let Result::Ok(f) = result {
-        let reader = BufReader::new(f);
-        for (line_number, line_result) in reader.lines().enumerate() {
-            if let Result::Ok(line) = line_result {
-                if !line.is_empty() {
-                    if line.starts_with('#') {
-                        // ignore comments
-                    } else {
-                        for token in line.split_whitespace() {
-                            match token.parse::<f32>() {
-                                Ok(float) => values.push(float),
-                                Err(_) => {
-                                    println!(
-                                    "WARNING: Unexpected text found at line {} of float file {:?}",
-                                    line_number, filename
-                                );
-                                    continue;
-                                }
-                            }
-                        }
-                    }
+/// Reads all values of the file, lines starting with **#** are
+/// comments, other text is skipped (with a warning).
+pub fn read_float_file(filename: &str) -> Result<Vec<Float>> {
+    let f: File = readable_path(filename)
+        .and_then(File::open)
+        .map_err(|err| Error::io(filename, err))?;
+    let mut values: Vec<Float> = Vec::new();
+    let reader = BufReader::new(f);
+    for (line_number, line_result) in reader.lines().enumerate() {
+        let line: String = line_result.map_err(|err| Error::io(filename, err))?;
+        if line.starts_with('#') {
+            // ignore comments
+            continue;
+        }
+        for token in line.split_whitespace() {
+            match token.parse::<Float>() {
+                Ok(float) => values.push(float),
+                Err(_) => {
+                    warn!(
+                        "Unexpected text found at line {} of float file {:?}",
+                        line_number, filename
+                    );
                 }
-            } else {
-                return false;
             }
         }
-        true
-    } else {
-        println!("ERROR: Unable to open file {:?}", filename);
-        false
     }
+    Ok(values)
+}
+
+/// Reads a spectral power distribution (SPD) file: pairs of
+/// wavelength (in nm) and value, sorted by wavelength. Returns the
+/// wavelengths and the values.
+pub fn read_spectrum_file(filename: &str) -> Result<(Vec<Float>, Vec<Float>)> {
+    let vals: Vec<Float> = read_float_file(filename)?;
+    if vals.len() % 2 == 1_usize {
+        warn!(
+            "Extra value found in spectrum file {:?}. Ignoring it.",
+            filename
+        );
+    }
+    let mut lambda: Vec<Float> = Vec::with_capacity(vals.len() / 2);
+    let mut values: Vec<Float> = Vec::with_capacity(vals.len() / 2);
+    for pair in vals.chunks_exact(2) {
+        lambda.push(pair[0]);
+        values.push(pair[1]);
+    }
+    if !spectrum_samples_sorted(&lambda, &values, lambda.len() as i32) {
+        sort_spectrum_samples(&mut lambda, &mut values);
+    }
+    Ok((lambda, values))
 }
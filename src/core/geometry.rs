@@ -90,6 +90,7 @@
 //!
 //! ```rust
 //! use pbrt::core::geometry::{Ray, Point3f, Vector3f};
+//! use pbrt::core::pbrt::Float;
 //!
 //! fn main() {
 //!     let origin = Point3f {
@@ -105,7 +106,7 @@
 //!     let ray = Ray {
 //!         o: origin,
 //!         d: direction,
-//!         t_max: std::f32::INFINITY,
+//!         t_max: Float::INFINITY,
 //!         time: 0.0,
 //!         medium: None,
 //!         differential: None,
@@ -171,7 +172,6 @@
 
 // std
 use std;
-use std::f32::consts::PI;
 use std::ops::{
     Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
 };
@@ -180,8 +180,13 @@ use std::sync::Arc;
 use num;
 // pbrt
 use crate::core::medium::Medium;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
-use crate::core::pbrt::{clamp_t, gamma, lerp, next_float_down, next_float_up};
+use crate::core::pbrt::{
+    bits_to_float, clamp_t, float_to_bits, gamma, lerp, next_float_down, next_float_up, FloatBits,
+};
+#[cfg(feature = "simd")]
+use crate::core::simd;
 
 // see geometry.h
 
@@ -301,12 +306,12 @@ where
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl Div<Float> for Vector2<f32> {
-    type Output = Vector2<f32>;
-    fn div(self, rhs: Float) -> Vector2<f32> {
+impl Div<Float> for Vector2<Float> {
+    type Output = Vector2<Float>;
+    fn div(self, rhs: Float) -> Vector2<Float> {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
-        Vector2::<f32> {
+        Vector2::<Float> {
             x: self.x * inv,
             y: self.y * inv,
         }
@@ -315,7 +320,7 @@ impl Div<Float> for Vector2<f32> {
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl DivAssign<Float> for Vector2<f32> {
+impl DivAssign<Float> for Vector2<Float> {
     fn div_assign(&mut self, rhs: Float) {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
@@ -469,12 +474,12 @@ where
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl Div<Float> for Vector3<f32> {
-    type Output = Vector3<f32>;
-    fn div(self, rhs: Float) -> Vector3<f32> {
+impl Div<Float> for Vector3<Float> {
+    type Output = Vector3<Float>;
+    fn div(self, rhs: Float) -> Vector3<Float> {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
-        Vector3::<f32> {
+        Vector3::<Float> {
             x: self.x * inv,
             y: self.y * inv,
             z: self.z * inv,
@@ -484,7 +489,7 @@ impl Div<Float> for Vector3<f32> {
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl DivAssign<Float> for Vector3<f32> {
+impl DivAssign<Float> for Vector3<Float> {
     fn div_assign(&mut self, rhs: Float) {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
@@ -596,16 +601,23 @@ where
 /// perpendicular to both of them.
 #[inline]
 pub fn vec3_cross_vec3(v1: &Vector3f, v2: &Vector3f) -> Vector3f {
-    let v1x: f64 = v1.x as f64;
-    let v1y: f64 = v1.y as f64;
-    let v1z: f64 = v1.z as f64;
-    let v2x: f64 = v2.x as f64;
-    let v2y: f64 = v2.y as f64;
-    let v2z: f64 = v2.z as f64;
-    Vector3f {
-        x: ((v1y * v2z) - (v1z * v2y)) as Float,
-        y: ((v1z * v2x) - (v1x * v2z)) as Float,
-        z: ((v1x * v2y) - (v1y * v2x)) as Float,
+    #[cfg(feature = "simd")]
+    {
+        simd::cross([v1.x, v1.y, v1.z], [v2.x, v2.y, v2.z])
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        let v1x: f64 = v1.x as f64;
+        let v1y: f64 = v1.y as f64;
+        let v1z: f64 = v1.z as f64;
+        let v2x: f64 = v2.x as f64;
+        let v2y: f64 = v2.y as f64;
+        let v2z: f64 = v2.z as f64;
+        Vector3f {
+            x: ((v1y * v2z) - (v1z * v2y)) as Float,
+            y: ((v1z * v2x) - (v1x * v2z)) as Float,
+            z: ((v1x * v2y) - (v1y * v2x)) as Float,
+        }
     }
 }
 
@@ -613,16 +625,23 @@ pub fn vec3_cross_vec3(v1: &Vector3f, v2: &Vector3f) -> Vector3f {
 /// that is perpendicular to both of them.
 #[inline]
 pub fn vec3_cross_nrm(v1: &Vector3f, v2: &Normal3f) -> Vector3f {
-    let v1x: f64 = v1.x as f64;
-    let v1y: f64 = v1.y as f64;
-    let v1z: f64 = v1.z as f64;
-    let v2x: f64 = v2.x as f64;
-    let v2y: f64 = v2.y as f64;
-    let v2z: f64 = v2.z as f64;
-    Vector3f {
-        x: ((v1y * v2z) - (v1z * v2y)) as Float,
-        y: ((v1z * v2x) - (v1x * v2z)) as Float,
-        z: ((v1x * v2y) - (v1y * v2x)) as Float,
+    #[cfg(feature = "simd")]
+    {
+        simd::cross([v1.x, v1.y, v1.z], [v2.x, v2.y, v2.z])
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        let v1x: f64 = v1.x as f64;
+        let v1y: f64 = v1.y as f64;
+        let v1z: f64 = v1.z as f64;
+        let v2x: f64 = v2.x as f64;
+        let v2y: f64 = v2.y as f64;
+        let v2z: f64 = v2.z as f64;
+        Vector3f {
+            x: ((v1y * v2z) - (v1z * v2y)) as Float,
+            y: ((v1z * v2x) - (v1x * v2z)) as Float,
+            z: ((v1x * v2y) - (v1y * v2x)) as Float,
+        }
     }
 }
 
@@ -1026,12 +1045,12 @@ where
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl Div<Float> for Point3<f32> {
-    type Output = Point3<f32>;
-    fn div(self, rhs: Float) -> Point3<f32> {
+impl Div<Float> for Point3<Float> {
+    type Output = Point3<Float>;
+    fn div(self, rhs: Float) -> Point3<Float> {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
-        Point3::<f32> {
+        Point3::<Float> {
             x: self.x * inv,
             y: self.y * inv,
             z: self.z * inv,
@@ -1041,7 +1060,7 @@ impl Div<Float> for Point3<f32> {
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl DivAssign<Float> for Point3<f32> {
+impl DivAssign<Float> for Point3<Float> {
     fn div_assign(&mut self, rhs: Float) {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
@@ -1150,10 +1169,36 @@ where
     (*p1 - *p2).length_squared()
 }
 
+/// Coordinates closer to zero than this get offset by a fixed amount
+/// (see **offset_along_normal()**), their ULPs are too small.
+const OFFSET_ORIGIN: Float = 1.0 / 32.0;
+/// The fixed offset (scaled by the normal) close to zero.
+const OFFSET_FLOAT_SCALE: Float = 1.0 / 65536.0;
+/// The number of ULPs (scaled by the normal) further away from zero.
+const OFFSET_INT_SCALE: Float = 256.0;
+
+/// Moves the coordinate *v* by a number of ULPs proportional to
+/// *n* (the same coordinate of a unit normal), so the offset grows
+/// with the magnitude of *v* (Wächter and Binder, "A Fast and Robust
+/// Method for Avoiding Self-Intersection", Ray Tracing Gems, 2019).
+fn offset_along_normal(v: Float, n: Float) -> Float {
+    if v.abs() < OFFSET_ORIGIN {
+        v + OFFSET_FLOAT_SCALE * n
+    } else {
+        let ulps: i64 = (OFFSET_INT_SCALE * n) as i64;
+        // the bits of negative values grow away from zero
+        let ulps: i64 = if v < 0.0 as Float { -ulps } else { ulps };
+        bits_to_float((float_to_bits(v) as i64).wrapping_add(ulps) as FloatBits)
+    }
+}
+
 /// When tracing spawned rays leaving the intersection point p, we
 /// offset their origins enough to ensure that they are past the
 /// boundary of the error box and thus won't incorrectly re-intersect
-/// the surface.
+/// the surface. Points also get moved at least a number of ULPs
+/// along the (geometric) normal, so they leave the surface no matter
+/// how far away from the origin of the world they are (or if they
+/// have no error bounds).
 pub fn pnt3_offset_ray_origin(
     p: &Point3f,
     p_error: &Vector3f,
@@ -1168,17 +1213,22 @@ pub fn pnt3_offset_ray_origin(
     //     // (In case of any bugs in the epsilons code...)
     //     d *= 1024.;
     // #endif
-    let mut offset: Vector3f = Vector3f::from(*n) * d;
+    let mut n_w: Vector3f = Vector3f::from(*n);
     if vec3_dot_nrm(w, n) < 0.0 as Float {
-        offset = -offset;
+        n_w = -n_w;
     }
-    let mut po: Point3f = *p + offset;
-    // round offset point _po_ away from _p_
+    let mut po: Point3f = *p;
     for i in 0..3 {
-        if offset[i] > 0.0 as Float {
-            po[i] = next_float_up(po[i]);
-        } else if offset[i] < 0.0 as Float {
-            po[i] = next_float_down(po[i]);
+        // whichever offset moves further: past the error box or
+        // by ULPs (error bounds can be too small far away from the
+        // origin)
+        let past_error: Float = p[i] + n_w[i] * d;
+        let by_ulps: Float = offset_along_normal(p[i], n_w[i]);
+        // round offset point _po_ away from _p_
+        if n_w[i] > 0.0 as Float {
+            po[i] = next_float_up(past_error.max(by_ulps));
+        } else if n_w[i] < 0.0 as Float {
+            po[i] = next_float_down(past_error.min(by_ulps));
         }
     }
     po
@@ -1359,12 +1409,12 @@ where
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl Div<Float> for Normal3<f32> {
-    type Output = Normal3<f32>;
-    fn div(self, rhs: Float) -> Normal3<f32> {
+impl Div<Float> for Normal3<Float> {
+    type Output = Normal3<Float>;
+    fn div(self, rhs: Float) -> Normal3<Float> {
         assert_ne!(rhs, 0.0 as Float);
         let inv: Float = 1.0 as Float / rhs;
-        Normal3::<f32> {
+        Normal3::<Float> {
             x: self.x * inv,
             y: self.y * inv,
             z: self.z * inv,
@@ -1586,12 +1636,12 @@ pub struct Bounds3<T> {
 
 // work around bug
 // https://github.com/rust-lang/rust/issues/40395
-impl Default for Bounds3<f32> {
-    fn default() -> Bounds3<f32> {
-        let min_num: Float = std::f32::MIN;
-        let max_num: Float = std::f32::MAX;
+impl Default for Bounds3<Float> {
+    fn default() -> Bounds3<Float> {
+        let min_num: Float = Float::MIN;
+        let max_num: Float = Float::MAX;
         // Bounds3f
-        Bounds3::<f32> {
+        Bounds3::<Float> {
             p_min: Point3f {
                 x: max_num,
                 y: max_num,
@@ -1746,10 +1796,18 @@ impl Bounds3<Float> {
     }
     #[inline]
     pub fn intersect_p(&self, ray: &Ray, inv_dir: &Vector3f, dir_is_neg: &[u8; 3]) -> bool {
+        // all slabs at once
+        #[cfg(feature = "simd")]
+        let ([mut t_min, ty_min, tz_min, _], [mut t_max, mut ty_max, mut tz_max, _]) =
+            simd::slab_distances(self, ray, inv_dir, dir_is_neg);
         // check for ray intersection against $x$ and $y$ slabs
+        #[cfg(not(feature = "simd"))]
         let mut t_min: Float = (self[dir_is_neg[0]].x - ray.o.x) * inv_dir.x;
+        #[cfg(not(feature = "simd"))]
         let mut t_max: Float = (self[1_u8 - dir_is_neg[0]].x - ray.o.x) * inv_dir.x;
+        #[cfg(not(feature = "simd"))]
         let ty_min: Float = (self[dir_is_neg[1]].y - ray.o.y) * inv_dir.y;
+        #[cfg(not(feature = "simd"))]
         let mut ty_max: Float = (self[1_u8 - dir_is_neg[1]].y - ray.o.y) * inv_dir.y;
         // update _t_max_ and _ty_max_ to ensure robust bounds intersection
         t_max *= 1.0 + 2.0 * gamma(3_i32);
@@ -1764,7 +1822,9 @@ impl Bounds3<Float> {
             t_max = ty_max;
         }
         // check for ray intersection against $z$ slab
+        #[cfg(not(feature = "simd"))]
         let tz_min: Float = (self[dir_is_neg[2]].z - ray.o.z) * inv_dir.z;
+        #[cfg(not(feature = "simd"))]
         let mut tz_max: Float = (self[1_u8 - dir_is_neg[2]].z - ray.o.z) * inv_dir.z;
         // update _tz_max_ to ensure robust bounds intersection
         tz_max *= 1.0 + 2.0 * gamma(3_i32);
@@ -1933,3 +1993,69 @@ pub struct RayDifferential {
     pub rx_direction: Vector3f,
     pub ry_direction: Vector3f,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The number of ULPs between two values of the same sign.
+    fn ulps_between(a: Float, b: Float) -> i64 {
+        (float_to_bits(a) as i64 - float_to_bits(b) as i64).abs()
+    }
+
+    #[test]
+    fn offset_ray_origin_far_from_the_origin() {
+        let n: Normal3f = Normal3f {
+            x: 0.48 as Float,
+            y: -0.6 as Float,
+            z: 0.64 as Float,
+        };
+        let points: [Point3f; 3] = [
+            Point3f {
+                x: 1.0e6 as Float,
+                y: -3.0e5 as Float,
+                z: 2.0e7 as Float,
+            },
+            Point3f {
+                x: -4.0e7 as Float,
+                y: 5.0e6 as Float,
+                z: -1.0e6 as Float,
+            },
+            Point3f {
+                x: 0.01 as Float,
+                y: -8.0e6 as Float,
+                z: 0.0 as Float,
+            },
+        ];
+        // no error bounds and ones too small to leave the surface
+        let errors: [Vector3f; 2] = [
+            Vector3f::default(),
+            Vector3f {
+                x: 1.0e-4 as Float,
+                y: 1.0e-4 as Float,
+                z: 1.0e-4 as Float,
+            },
+        ];
+        for p in &points {
+            for p_error in &errors {
+                for side in &[1.0 as Float, -1.0 as Float] {
+                    let w: Vector3f = Vector3f::from(n) * *side;
+                    let po: Point3f = pnt3_offset_ray_origin(p, p_error, &n, &w);
+                    for i in 0..3 {
+                        let moved: Float = (po[i] - p[i]) * n[i] * *side;
+                        assert!(moved > 0.0 as Float, "{:?} offset to {:?}", p, po);
+                        if p[i].abs() >= OFFSET_ORIGIN {
+                            let min_ulps: i64 = (OFFSET_INT_SCALE * n[i].abs()) as i64;
+                            assert!(
+                                ulps_between(po[i], p[i]) >= min_ulps,
+                                "{:?} offset to {:?}",
+                                p,
+                                po
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -3,34 +3,524 @@
 
 // std
 use std;
-use std::sync::Arc;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+// others
+use log::{debug, error, info, warn};
 // pbrt
 use crate::blockqueue::BlockQueue;
 use crate::core::camera::{Camera, CameraSample};
-use crate::core::geometry::{pnt2_inside_exclusive, vec3_abs_dot_nrm};
-use crate::core::geometry::{Bounds2i, Point2f, Point2i, Ray, Vector2i, Vector3f};
+use crate::core::checkpoint::Checkpoint;
+use crate::core::film::{AovType, Film, FilmTile};
+use crate::core::geometry::{nrm_faceforward_vec3, vec3_dot_nrm};
+use crate::core::geometry::{pnt2_inside_exclusive, pnt3_distance, vec3_abs_dot_nrm};
+use crate::core::geometry::{Bounds2i, Normal3f, Point2f, Point2i, Ray, Vector2i, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::light::is_delta_light;
 use crate::core::light::{Light, VisibilityTester};
+use crate::core::lightdistrib::LightDistribution;
+use crate::core::material::TransportMode;
+use crate::core::memory::MemoryArena;
+use crate::core::numa;
+use crate::core::pathdebug::{begin_sample, debug_pixel, end_sample, write_debug_pixel_files};
+use crate::core::pbrt::clamp_t;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
 use crate::core::reflection::BxdfType;
-use crate::core::sampler::Sampler;
+use crate::core::rng::Rng;
+use crate::core::sampler::{pixel_seed, Sampler};
 use crate::core::sampling::power_heuristic;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::StatCounter;
 use crate::integrators::ao::AOIntegrator;
 use crate::integrators::bdpt::BDPTIntegrator;
 use crate::integrators::directlighting::DirectLightingIntegrator;
+use crate::integrators::gradientpath::GradientPathIntegrator;
+use crate::integrators::lighttracing::LightTracingIntegrator;
 use crate::integrators::mlt::MLTIntegrator;
 use crate::integrators::path::PathIntegrator;
+use crate::integrators::photonmap::PhotonMapIntegrator;
 use crate::integrators::sppm::SPPMIntegrator;
 use crate::integrators::volpath::VolPathIntegrator;
 use crate::integrators::whitted::WhittedIntegrator;
 
 // see integrator.h
 
+/// Checked by the render loops (between pixels, mutations, or
+/// iterations) to stop rendering early.
+static RENDER_CANCELLED: AtomicBool = AtomicBool::new(false);
+/// Set while **Integrator::render()** runs (see
+/// **cancel_render_on_interrupt()**).
+static RENDER_RUNNING: AtomicBool = AtomicBool::new(false);
+/// Set if the render was cancelled by Ctrl+C.
+static RENDER_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Pixels with NaN radiance get painted magenta ...
+const NAN_FLAG_COLOR: [Float; 3] = [1.0, 0.0, 1.0];
+/// ... pixels with infinite radiance cyan (see
+/// **SamplerIntegrator::set_debug_non_finite()**).
+const INF_FLAG_COLOR: [Float; 3] = [0.0, 1.0, 1.0];
+
+thread_local! {
+    /// Set while this thread renders the samples of an integrator in
+    /// the debug mode for non-finite values (see
+    /// **SamplerIntegrator::set_debug_non_finite()**) ...
+    static DEBUG_NON_FINITE: Cell<bool> = const { Cell::new(false) };
+    /// ... and the path depth where the sample this thread renders became NaN
+    /// (true) or infinite (false), see **report_non_finite()**.
+    static NON_FINITE_DEPTH: Cell<Option<(u32, bool)>> = const { Cell::new(None) };
+    /// The light of the sample this thread renders which reached the
+    /// camera directly and after one bounce, while the film keeps
+    /// those AOVs (see **record_light()**).
+    static LIGHT_SPLIT: RefCell<Option<[Spectrum; 2]>> = const { RefCell::new(None) };
+    /// Set while this thread renders a sample and the film wants
+    /// denoiser features (see **record_features()**) ...
+    static FEATURES_WANTED: Cell<bool> = const { Cell::new(false) };
+    /// ... which are reported here.
+    static FEATURES: RefCell<Option<(Spectrum, Normal3f)>> = const { RefCell::new(None) };
+}
+
+static N_CAMERA_RAYS: StatCounter = StatCounter::new("Integrator/Camera rays traced");
+
+/// Stops the running render (e.g. from another thread) as soon as
+/// possible. The image contains whatever was rendered so far.
+pub fn cancel_render() {
+    RENDER_CANCELLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_render_cancelled() -> bool {
+    RENDER_CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Has to be called before a render starts, which should not be
+/// cancelled by an earlier **cancel_render()**.
+pub fn reset_render_cancelled() {
+    RENDER_CANCELLED.store(false, Ordering::Relaxed);
+}
+
+/// Lets Ctrl+C (SIGINT) cancel the running render (see
+/// **cancel_render()**), so the partially rendered image still gets
+/// written. A second Ctrl+C, or one while nothing gets rendered (e.g.
+/// while the scene is parsed), terminates the process right away.
+/// Resets an earlier cancellation.
+#[cfg(unix)]
+pub fn cancel_render_on_interrupt() {
+    extern "C" fn on_interrupt(_signal: libc::c_int) {
+        // only atomics and _exit() are safe in a signal handler
+        if RENDER_RUNNING.load(Ordering::Relaxed)
+            && !RENDER_INTERRUPTED.swap(true, Ordering::Relaxed)
+        {
+            cancel_render();
+        } else {
+            unsafe { libc::_exit(130) };
+        }
+    }
+    reset_render_cancelled();
+    RENDER_INTERRUPTED.store(false, Ordering::Relaxed);
+    let handler: extern "C" fn(libc::c_int) = on_interrupt;
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+/// Ctrl+C keeps terminating the process on other platforms.
+#[cfg(not(unix))]
+pub fn cancel_render_on_interrupt() {
+    reset_render_cancelled();
+    RENDER_INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+/// Was the last render cancelled by Ctrl+C (see
+/// **cancel_render_on_interrupt()**)?
+pub fn is_render_interrupted() -> bool {
+    RENDER_INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Called by integrators with the radiance (or the throughput) *s* of
+/// a path at *depth*. In the debug mode of
+/// **SamplerIntegrator::set_debug_non_finite()**
+/// the first depth where the sample turns NaN or infinite gets
+/// remembered for the report, and true is returned (the path should
+/// be terminated). Always false otherwise.
+pub fn report_non_finite(s: &Spectrum, depth: u32) -> bool {
+    if !DEBUG_NON_FINITE.with(|on| on.get()) {
+        return false;
+    }
+    let is_nan: bool = s.has_nans();
+    if !is_nan && !s.has_infs() {
+        return false;
+    }
+    NON_FINITE_DEPTH.with(|non_finite| {
+        if non_finite.get().is_none() {
+            non_finite.set(Some((depth, is_nan)));
+        }
+    });
+    true
+}
+
+/// Called by integrators (**path** and **volpath**) with the light *l*
+/// they add to a sample after *bounces* scattering events, for the
+/// *emission* and *direct* AOVs of the film (see **AovType**), the
+/// rest of the sample is indirect light. Does nothing unless the film
+/// keeps those AOVs.
+pub fn record_light(bounces: u32, l: &Spectrum) {
+    if bounces > 1 {
+        return;
+    }
+    LIGHT_SPLIT.with(|split| {
+        if let Some(ref mut split) = *split.borrow_mut() {
+            split[bounces as usize] += *l;
+        }
+    });
+}
+
+/// Called by integrators (**path**) with the albedo (weighted by the
+/// throughput of the path so far) and the shading normal (in world
+/// space) of the vertex which should represent a sample for
+/// denoisers (see *denoiserfeatures* of **Film**). Only the first
+/// call of a sample counts, and only if the film wants them.
+pub fn record_features(albedo: &Spectrum, n: &Normal3f) {
+    if !FEATURES_WANTED.with(|wanted| wanted.get()) {
+        return;
+    }
+    FEATURES.with(|features| {
+        let mut features = features.borrow_mut();
+        if features.is_none() {
+            *features = Some((*albedo, *n));
+        }
+    });
+}
+
+/// Firefly suppression of **path** and **volpath**: the light *l* a
+/// path picks up after its first bounce gets scaled down (keeping its
+/// hue) so that no component exceeds *max_value* (**clampindirect**,
+/// 0 disables clamping). Darkens bright indirect light, but removes
+/// the rare, very bright samples of e.g. caustics.
+pub fn clamp_indirect(l: Spectrum, bounces: u32, max_value: Float) -> Spectrum {
+    let max_component: Float = l.max_component_value();
+    if bounces > 0 && max_value > 0.0 as Float && max_component > max_value {
+        l * (max_value / max_component)
+    } else {
+        l
+    }
+}
+
+/// The result of **Scene::intersect()** for the first ray of a path
+/// (if it was found and the **SurfaceInteraction**), in case the
+/// caller traced that ray already (see
+/// **SamplerIntegrator::li_with_alpha()**).
+pub type FirstHit<'a> = Option<(bool, SurfaceInteraction<'a>)>;
+
+/// Intersects *ray* with the scene, unless *first_hit* holds the
+/// result already.
+pub fn first_intersection<'a>(
+    scene: &Scene,
+    ray: &mut Ray,
+    first_hit: FirstHit<'a>,
+) -> (bool, SurfaceInteraction<'a>) {
+    match first_hit {
+        Some(hit) => hit,
+        None => {
+            let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+            let found: bool = scene.intersect(ray, &mut isect);
+            (found, isect)
+        }
+    }
+}
+
+/// What the running render is doing, for other threads (e.g. to
+/// report its progress or to show a preview).
+#[derive(Default)]
+struct RenderProgress {
+    film: Option<Arc<Film>>,
+    tiles_done: usize,
+    tiles_total: usize,
+}
+
+lazy_static::lazy_static! {
+    /// See **set_tile_shape()**.
+    static ref TILE_SHAPE: Mutex<TileShape> = Mutex::new(TileShape::default());
+    /// See **set_progressive()**.
+    static ref PROGRESSIVE: Mutex<Option<Progressive>> = Mutex::new(None);
+    /// See **set_render_budget()**.
+    static ref RENDER_BUDGET: Mutex<RenderBudget> = Mutex::new(RenderBudget::default());
+    static ref RENDER_PROGRESS: Mutex<RenderProgress> = Mutex::new(RenderProgress::default());
+}
+
+/// The number of tiles rendered so far and the number of all tiles of
+/// the running render. Both are zero if nothing gets rendered or if
+/// the integrator doesn't render tile by tile.
+pub fn render_progress() -> (usize, usize) {
+    let progress = RENDER_PROGRESS.lock().unwrap();
+    (progress.tiles_done, progress.tiles_total)
+}
+
+/// The width, height, and (linear) RGB values of what the running
+/// render merged into its film so far (see **Film::preview_rgb()**).
+pub fn render_preview() -> Option<(i32, i32, Vec<Float>)> {
+    let film: Arc<Film> = RENDER_PROGRESS.lock().unwrap().film.clone()?;
+    let bounds: Bounds2i = film.cropped_pixel_bounds;
+    film.preview_rgb().map(|rgb| {
+        (
+            bounds.p_max.x - bounds.p_min.x,
+            bounds.p_max.y - bounds.p_min.y,
+            rgb,
+        )
+    })
+}
+
+fn start_render_progress(film: Option<Arc<Film>>, tiles_total: usize) {
+    let mut progress = RENDER_PROGRESS.lock().unwrap();
+    progress.film = film;
+    progress.tiles_done = 0;
+    progress.tiles_total = tiles_total;
+}
+
+fn advance_render_progress(tiles: usize) {
+    RENDER_PROGRESS.lock().unwrap().tiles_done += tiles;
+}
+
+/// How the image gets split into the tiles which are rendered in
+/// parallel (see **set_tile_shape()**).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileShape {
+    /// square tiles, this many pixels wide and high
+    Square(i32),
+    /// strips of whole scanlines, this many rows high
+    Strips(i32),
+}
+
+impl Default for TileShape {
+    fn default() -> Self {
+        TileShape::Square(16)
+    }
+}
+
+/// Sets the shape of the tiles for all following renders. Small tiles
+/// balance the load better when some parts of the image take much
+/// longer than others (e.g. volumes), large tiles or strips have less
+/// scheduling overhead for very cheap scenes. Used by the integrators
+/// rendering tile by tile and **bdpt**, for distributed rendering the
+/// coordinator and the workers have to use the same shape.
+pub fn set_tile_shape(shape: TileShape) {
+    *TILE_SHAPE.lock().unwrap() = match shape {
+        TileShape::Square(size) => TileShape::Square(size.max(1)),
+        TileShape::Strips(rows) => TileShape::Strips(rows.max(1)),
+    };
+}
+
+pub fn tile_shape() -> TileShape {
+    *TILE_SHAPE.lock().unwrap()
+}
+
+/// When a progressive render writes the image rendered so far (see
+/// **set_progressive()**).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFlush {
+    /// after every this many passes
+    Passes(u32),
+    /// after the first pass which ends this long after the last write
+    Interval(Duration),
+}
+
+/// Progressive rendering (see **set_progressive()**).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Progressive {
+    /// samples per pixel of each pass over the whole image
+    pub pass_samples: i64,
+    pub flush: ImageFlush,
+}
+
+/// Lets the integrators rendering tile by tile (**SamplerIntegrator**)
+/// render all following images progressively: instead of rendering
+/// all samples of a tile before the next one, they render the whole
+/// image in passes of *pass_samples* samples per pixel, and write the
+/// image rendered so far (e.g. "pbrt.png", not the AOVs) as often as
+/// *flush* says. A long render which gets aborted (or cancelled, see
+/// **cancel_render_on_interrupt()**) still leaves an image with all
+/// pixels rendered, only noisier. Halton and Sobol samplers render
+/// exactly the samples of a render without passes, the other samplers
+/// get reseeded for each pass (the same number of samples, but
+/// different ones). Ignored with *diskbacked* films, which write each
+/// tile only once. **None** turns it off.
+pub fn set_progressive(progressive: Option<Progressive>) {
+    *PROGRESSIVE.lock().unwrap() = progressive.map(|progressive| Progressive {
+        pass_samples: progressive.pass_samples.max(1),
+        flush: match progressive.flush {
+            ImageFlush::Passes(passes) => ImageFlush::Passes(passes.max(1)),
+            interval => interval,
+        },
+    });
+}
+
+pub fn progressive() -> Option<Progressive> {
+    *PROGRESSIVE.lock().unwrap()
+}
+
+/// Limits of all following renders (see **set_render_budget()**).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RenderBudget {
+    /// wall-clock time of a render
+    pub max_time: Option<Duration>,
+    /// samples per pixel (mutations per pixel for **mlt**, iterations
+    /// for **sppm**)
+    pub max_spp: Option<i64>,
+}
+
+/// Lets all following renders stop early: after *max_time* the
+/// render gets cancelled (see **cancel_render()**), and no pixel gets
+/// more than *max_spp* samples, even if the sampler of the scene asks
+/// for more (see **budget_samples()**). Either way the image gets
+/// written with what was rendered, pixels without any samples stay
+/// black (a progressive render, see **set_progressive()**, has all
+/// pixels rendered after the first pass). Unlike overriding the
+/// sampler's samples per pixel, the samples taken are the first ones
+/// of the sampler of the scene.
+pub fn set_render_budget(budget: RenderBudget) {
+    *RENDER_BUDGET.lock().unwrap() = RenderBudget {
+        max_time: budget.max_time,
+        max_spp: budget.max_spp.map(|max_spp| max_spp.max(1)),
+    };
+}
+
+pub fn render_budget() -> RenderBudget {
+    *RENDER_BUDGET.lock().unwrap()
+}
+
+/// The samples per pixel to take with a sampler for *spp* samples per
+/// pixel, at most *max_spp* of **set_render_budget()**.
+pub fn budget_samples(spp: i64) -> i64 {
+    match render_budget().max_spp {
+        Some(max_spp) => spp.min(max_spp),
+        None => spp,
+    }
+}
+
+/// The scale of the splats of a render which took *samples* samples
+/// (e.g. light paths, one for each camera sample) for an image of
+/// *n_pixels* pixels, i.e. one over the samples per pixel. A
+/// cancelled render took fewer samples than the sampler asked for.
+pub fn splat_scale(n_pixels: i64, samples: u64) -> Float {
+    if samples == 0 {
+        return 0.0 as Float;
+    }
+    n_pixels as Float / samples as Float
+}
+
+/// Cancels the running render after *max_time* (see **RenderBudget**),
+/// unless the returned sender gets dropped before.
+#[cfg(not(target_arch = "wasm32"))]
+fn start_watchdog(
+    max_time: Duration,
+) -> (crossbeam_channel::Sender<()>, std::thread::JoinHandle<()>) {
+    let (tx, rx) = crossbeam_channel::bounded::<()>(0);
+    let handle = std::thread::spawn(move || {
+        if let Err(crossbeam_channel::RecvTimeoutError::Timeout) = rx.recv_timeout(max_time) {
+            info!("The time budget of {:?} is used up, stopping ...", max_time);
+            cancel_render();
+        }
+    });
+    (tx, handle)
+}
+
+/// The image tiles a **SamplerIntegrator** renders independently of
+/// each other.
+#[derive(Debug, Copy, Clone)]
+pub struct TileGrid {
+    pub sample_bounds: Bounds2i,
+    /// width and height of a tile
+    pub tile_size: Vector2i,
+    pub n_tiles: Point2i,
+}
+
+impl TileGrid {
+    pub fn new(sample_bounds: Bounds2i, shape: TileShape) -> Self {
+        let sample_extent: Vector2i = sample_bounds.diagonal();
+        let tile_size: Vector2i = match shape {
+            TileShape::Square(size) => Vector2i { x: size, y: size },
+            TileShape::Strips(rows) => Vector2i {
+                x: sample_extent.x.max(1),
+                y: rows,
+            },
+        };
+        let x: i32 = (sample_extent.x + tile_size.x - 1) / tile_size.x;
+        let y: i32 = (sample_extent.y + tile_size.y - 1) / tile_size.y;
+        TileGrid {
+            sample_bounds,
+            tile_size,
+            n_tiles: Point2i { x, y },
+        }
+    }
+    pub fn len(&self) -> usize {
+        (self.n_tiles.x * self.n_tiles.y) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn tile(&self, index: usize) -> Point2i {
+        Point2i {
+            x: index as i32 % self.n_tiles.x,
+            y: index as i32 / self.n_tiles.x,
+        }
+    }
+    /// The pixels of a tile, clipped to the sample bounds.
+    pub fn tile_bounds(&self, tile: Point2i) -> Bounds2i {
+        let x0: i32 = self.sample_bounds.p_min.x + tile.x * self.tile_size.x;
+        let x1: i32 = std::cmp::min(x0 + self.tile_size.x, self.sample_bounds.p_max.x);
+        let y0: i32 = self.sample_bounds.p_min.y + tile.y * self.tile_size.y;
+        let y1: i32 = std::cmp::min(y0 + self.tile_size.y, self.sample_bounds.p_max.y);
+        Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 })
+    }
+}
+
+/// Merges the rendered tiles in the order of their positions in a
+/// **BlockQueue** (see **BlockQueue::next_with_position()**), no
+/// matter in which order the threads finish them. The pixels along
+/// tile borders get samples of neighbouring tiles, and the splats of
+/// a tile can land anywhere (see **FilmTile::add_splat()**), so this
+/// sums them up in the same order for any number of threads.
+pub struct OrderedMerge<T> {
+    /// tiles which arrived before an earlier one in the queue
+    pending: BTreeMap<usize, T>,
+    next_position: usize,
+}
+
+// derived, it would need T: Default
+impl<T> Default for OrderedMerge<T> {
+    fn default() -> Self {
+        OrderedMerge {
+            pending: BTreeMap::new(),
+            next_position: 0_usize,
+        }
+    }
+}
+
+impl<T> OrderedMerge<T> {
+    /// Passes the tile at *position* to *merge*, as soon as all tiles
+    /// before it in the queue are merged.
+    pub fn add(&mut self, position: usize, tile: T, merge: &mut impl FnMut(T)) {
+        self.pending.insert(position, tile);
+        while let Some(tile) = self.pending.remove(&self.next_position) {
+            merge(tile);
+            self.next_position += 1;
+        }
+    }
+    /// Merges the tiles left behind the gaps a cancelled render
+    /// leaves in the queue.
+    pub fn finish(self, merge: &mut impl FnMut(T)) {
+        for tile in self.pending.into_values() {
+            merge(tile);
+        }
+    }
+}
+
 pub enum Integrator {
     BDPT(BDPTIntegrator),
+    GradientPath(GradientPathIntegrator),
+    LightTracing(LightTracingIntegrator),
     MLT(MLTIntegrator),
     SPPM(SPPMIntegrator),
     Sampler(SamplerIntegrator),
@@ -38,12 +528,51 @@ pub enum Integrator {
 
 impl Integrator {
     pub fn render(&mut self, scene: &Scene, num_threads: u8) {
+        start_render_progress(Some(self.get_camera().get_film()), 0);
+        RENDER_RUNNING.store(true, Ordering::Relaxed);
+        // no threads in WebAssembly
+        #[cfg(not(target_arch = "wasm32"))]
+        let watchdog = render_budget().max_time.map(start_watchdog);
         match self {
             Integrator::BDPT(integrator) => integrator.render(scene, num_threads),
+            Integrator::GradientPath(integrator) => integrator.render(scene, num_threads),
+            Integrator::LightTracing(integrator) => integrator.render(scene, num_threads),
             Integrator::MLT(integrator) => integrator.render(scene, num_threads),
             Integrator::SPPM(integrator) => integrator.render(scene, num_threads),
             Integrator::Sampler(integrator) => integrator.render(scene, num_threads),
         }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some((tx, handle)) = watchdog {
+            drop(tx);
+            handle.join().unwrap();
+        }
+        RENDER_RUNNING.store(false, Ordering::Relaxed);
+        start_render_progress(None, 0);
+        if is_render_cancelled() {
+            warn!("Rendering was cancelled, the image contains only the samples rendered so far.");
+        }
+    }
+    /// Softens the shadow terminators of shading normals in all BSDFs
+    /// (see **Bsdf::terminator_shadowing()**).
+    pub fn set_terminator_shadowing(&mut self, on: bool) {
+        match self {
+            Integrator::BDPT(integrator) => integrator.terminator_shadowing = on,
+            Integrator::GradientPath(integrator) => integrator.path.terminator_shadowing = on,
+            Integrator::LightTracing(integrator) => integrator.terminator_shadowing = on,
+            Integrator::MLT(integrator) => integrator.terminator_shadowing = on,
+            Integrator::SPPM(integrator) => integrator.terminator_shadowing = on,
+            Integrator::Sampler(integrator) => integrator.set_terminator_shadowing(on),
+        }
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        match self {
+            Integrator::BDPT(integrator) => integrator.get_camera(),
+            Integrator::GradientPath(integrator) => integrator.get_camera(),
+            Integrator::LightTracing(integrator) => integrator.get_camera(),
+            Integrator::MLT(integrator) => integrator.get_camera(),
+            Integrator::SPPM(integrator) => integrator.get_camera(),
+            Integrator::Sampler(integrator) => integrator.get_camera(),
+        }
     }
 }
 
@@ -51,6 +580,7 @@ pub enum SamplerIntegrator {
     AO(AOIntegrator),
     DirectLighting(DirectLightingIntegrator),
     Path(PathIntegrator),
+    PhotonMap(PhotonMapIntegrator),
     VolPath(VolPathIntegrator),
     Whitted(WhittedIntegrator),
 }
@@ -61,179 +591,745 @@ impl SamplerIntegrator {
             SamplerIntegrator::AO(integrator) => integrator.preprocess(scene),
             SamplerIntegrator::DirectLighting(integrator) => integrator.preprocess(scene),
             SamplerIntegrator::Path(integrator) => integrator.preprocess(scene),
+            SamplerIntegrator::PhotonMap(integrator) => integrator.preprocess(scene),
             SamplerIntegrator::VolPath(integrator) => integrator.preprocess(scene),
             SamplerIntegrator::Whitted(integrator) => integrator.preprocess(scene),
         }
     }
     pub fn render(&mut self, scene: &Scene, num_threads: u8) {
+        #[cfg(feature = "gpu")]
+        {
+            if let SamplerIntegrator::AO(integrator) = self {
+                if integrator.gpu && integrator.render_gpu(scene) {
+                    return;
+                }
+            }
+        }
         match self {
             _ => {
                 let film = self.get_camera().get_film();
                 let sample_bounds: Bounds2i = film.get_sample_bounds();
+                if let Some(pixel) = debug_pixel() {
+                    if !pnt2_inside_exclusive(pixel, &self.get_pixel_bounds()) {
+                        warn!(
+                            "The debug pixel ({}, {}) is outside of the image.",
+                            pixel.x, pixel.y
+                        );
+                    }
+                }
                 self.preprocess(scene);
-                let sample_extent: Vector2i = sample_bounds.diagonal();
-                let tile_size: i32 = 16;
-                let x: i32 = (sample_extent.x + tile_size - 1) / tile_size;
-                let y: i32 = (sample_extent.y + tile_size - 1) / tile_size;
-                let n_tiles: Point2i = Point2i { x, y };
-                // TODO: ProgressReporter reporter(nTiles.x * nTiles.y, "Rendering");
+                let grid: TileGrid = TileGrid::new(sample_bounds, tile_shape());
                 let num_cores = if num_threads == 0_u8 {
                     num_cpus::get()
                 } else {
                     num_threads as usize
                 };
-                println!("Rendering with {:?} thread(s) ...", num_cores);
+                info!("Rendering with {:?} thread(s) ...", num_cores);
+                // no threads (and no clock for the progress bar) in WebAssembly
+                #[cfg(target_arch = "wasm32")]
                 {
-                    let block_queue = BlockQueue::new(
-                        (
-                            (n_tiles.x * tile_size) as u32,
-                            (n_tiles.y * tile_size) as u32,
-                        ),
-                        (tile_size as u32, tile_size as u32),
-                        (0, 0),
-                    );
-                    let integrator = &self;
-                    let bq = &block_queue;
-                    let sampler = &self.get_sampler();
-                    let camera = &self.get_camera();
-                    let film = &film;
-                    let pixel_bounds = &self.get_pixel_bounds();
-                    crossbeam::scope(|scope| {
-                        let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
-                        // spawn worker threads
-                        for _ in 0..num_cores {
-                            let pixel_tx = pixel_tx.clone();
-                            let mut tile_sampler: Box<Sampler> =
-                                sampler.clone_with_seed(0_u64);
-                            scope.spawn(move |_| {
-                                while let Some((x, y)) = bq.next() {
-                                    let tile: Point2i = Point2i {
-                                        x: x as i32,
-                                        y: y as i32,
-                                    };
-                                    let seed: i32 = tile.y * n_tiles.x + tile.x;
-                                    tile_sampler.reseed(seed as u64);
-                                    let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
-                                    let x1: i32 =
-                                        std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
-                                    let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
-                                    let y1: i32 =
-                                        std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
-                                    let tile_bounds: Bounds2i = Bounds2i::new(
-                                        Point2i { x: x0, y: y0 },
-                                        Point2i { x: x1, y: y1 },
-                                    );
-                                    // println!("Starting image tile {:?}", tile_bounds);
-                                    let mut film_tile = film.get_film_tile(&tile_bounds);
-                                    for pixel in &tile_bounds {
-                                        tile_sampler.start_pixel(pixel);
-                                        if !pnt2_inside_exclusive(pixel, &pixel_bounds) {
-                                            continue;
-                                        }
-                                        let mut done: bool = false;
-                                        while !done {
-                                            // let's use the copy_arena crate instead of pbrt's MemoryArena
-                                            // let mut arena: Arena = Arena::with_capacity(262144); // 256kB
-
-                                            // initialize _CameraSample_ for current sample
-                                            let camera_sample: CameraSample =
-                                                tile_sampler.get_camera_sample(pixel);
-                                            // generate camera ray for current sample
-                                            let mut ray: Ray = Ray::default();
-                                            let ray_weight: Float = camera
-                                                .generate_ray_differential(
-                                                    &camera_sample,
-                                                    &mut ray,
-                                                );
-                                            ray.scale_differentials(
-                                                1.0 as Float
-                                                    / (tile_sampler.get_samples_per_pixel()
-                                                        as Float)
-                                                        .sqrt(),
-                                            );
-                                            // TODO: ++nCameraRays;
-                                            // evaluate radiance along camera ray
-                                            let mut l: Spectrum = Spectrum::new(0.0 as Float);
-                                            let y: Float = l.y();
-                                            if ray_weight > 0.0 {
-                                                l = integrator.li(
-                                                    &mut ray,
-                                                    scene,
-                                                    &mut tile_sampler, // &mut arena,
-                                                    0_i32,
-                                                );
-                                            }
-                                            if l.has_nans() {
-                                                println!(
-                                                    "Not-a-number radiance value returned for pixel \
-                                                     ({:?}, {:?}), sample {:?}. Setting to black.",
-                                                    pixel.x,
-                                                    pixel.y,
-                                                    tile_sampler.get_current_sample_number()
-                                                );
-                                                l = Spectrum::new(0.0);
-                                            } else if y < -10.0e-5 as Float {
-                                                println!(
-                                                "Negative luminance value, {:?}, returned for pixel \
-                                                 ({:?}, {:?}), sample {:?}. Setting to black.",
-                                                y,
-                                                pixel.x,
-                                                pixel.y,
-                                                tile_sampler.get_current_sample_number()
-                                                );
-                                                l = Spectrum::new(0.0);
-                                            } else if y.is_infinite() {
-                                                println!(
-                                                "Infinite luminance value returned for pixel ({:?}, \
-                                                 {:?}), sample {:?}. Setting to black.",
-                                                pixel.x,
-                                                pixel.y,
-                                                tile_sampler.get_current_sample_number()
-                                                );
-                                                l = Spectrum::new(0.0);
-                                            }
-                                            // println!("Camera sample: {:?} -> ray: {:?} -> L = {:?}",
-                                            //          camera_sample, ray, l);
-                                            // add camera ray's contribution to image
-                                            film_tile.add_sample(
-                                                camera_sample.p_film,
-                                                &mut l,
-                                                ray_weight,
-                                            );
-                                            done = !tile_sampler.start_next_sample();
-                                        } // arena is dropped here !
-                                    }
-                                    // send the tile through the channel to main thread
-                                    pixel_tx
-                                        .send(film_tile)
-                                        .unwrap_or_else(|_| panic!("Failed to send tile"));
-                                }
-                            });
+                    start_render_progress(Some(film.clone()), grid.len());
+                    let mut tile_sampler: Box<Sampler> = self.get_sampler().clone_with_seed(0_u64);
+                    let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+                    for index in 0..grid.len() {
+                        let film_tile = self.render_tile(
+                            scene,
+                            &grid,
+                            grid.tile(index),
+                            &film,
+                            &mut tile_sampler,
+                            &mut arena,
+                        );
+                        film.merge_film_tile(&film_tile);
+                        advance_render_progress(1);
+                    }
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    // all samples at once, or passes over the whole image
+                    let spp: i64 = budget_samples(self.get_sampler().get_samples_per_pixel());
+                    let progressive: Option<Progressive> = match progressive() {
+                        Some(_) if film.is_disk_backed() => {
+                            warn!("Progressive rendering is ignored with Film \"diskbacked\".");
+                            None
+                        }
+                        progressive => progressive,
+                    };
+                    let pass_samples: i64 = match progressive {
+                        Some(progressive) => progressive.pass_samples.min(spp),
+                        None => spp,
+                    };
+                    let n_passes: i64 = ((spp + pass_samples - 1) / pass_samples).max(1);
+                    // resume from a checkpoint (restores the film)
+                    let mut checkpoint: Option<Checkpoint> =
+                        Checkpoint::start(&film, &grid, spp, pass_samples);
+                    let first_pass: i64 = checkpoint
+                        .as_ref()
+                        .map_or(0, |checkpoint| checkpoint.pass().min(n_passes));
+                    start_render_progress(Some(film.clone()), grid.len() * n_passes as usize);
+                    advance_render_progress(grid.len() * first_pass as usize);
+                    let mut last_flush: std::time::Instant = std::time::Instant::now();
+                    for pass in first_pass..n_passes {
+                        let samples: (i64, i64) =
+                            (pass * pass_samples, ((pass + 1) * pass_samples).min(spp));
+                        self.render_pass(scene, &grid, &film, num_cores, samples, &mut checkpoint);
+                        if is_render_cancelled() {
+                            break;
                         }
-                        // spawn thread to collect pixels and render image to file
-                        scope.spawn(move |_| {
-                            for _ in pbr::PbIter::new(0..bq.len()) {
-                                let film_tile = pixel_rx.recv().unwrap();
-                                // merge image tile into _Film_
-                                film.merge_film_tile(&film_tile);
+                        if let Some(ref mut checkpoint) = checkpoint {
+                            checkpoint.pass_done();
+                        }
+                        if pass + 1 == n_passes {
+                            break;
+                        }
+                        // write the image rendered so far
+                        let flush: bool = match progressive.map(|progressive| progressive.flush) {
+                            Some(ImageFlush::Passes(passes)) => (pass + 1) % passes as i64 == 0,
+                            Some(ImageFlush::Interval(interval)) => {
+                                last_flush.elapsed() >= interval
                             }
-                        });
-                    })
-                    .unwrap();
+                            None => false,
+                        };
+                        if flush {
+                            info!(
+                                "Writing the image after pass {} of {} ({} of {} samples per pixel) ...",
+                                pass + 1,
+                                n_passes,
+                                samples.1,
+                                spp
+                            );
+                            film.write_image(1.0 as Float);
+                            last_flush = std::time::Instant::now();
+                        }
+                    }
+                    if let Some(ref mut checkpoint) = checkpoint {
+                        checkpoint.write(&film);
+                    }
                 }
-                film.write_image(1.0 as Float);
+                self.write_film(&film);
+            }
+        }
+    }
+    /// Writes the image of a finished render, and the AOVs, deep
+    /// samples, and denoiser features the film asked for.
+    pub fn write_film(&self, film: &Film) {
+        film.write_image(1.0 as Float);
+        for (aov_type, aov) in &film.aovs {
+            if aov_type.is_light() && !self.splits_light() {
+                warn!(
+                    "AOV {:?} is only written by \"path\" and \"volpath\".",
+                    aov_type.name()
+                );
+                continue;
             }
+            film.write_aov(aov);
         }
+        film.write_deep();
+        film.write_denoiser_features();
+        write_debug_pixel_files();
     }
-    pub fn li(&self, ray: &mut Ray, scene: &Scene, sampler: &mut Sampler, depth: i32) -> Spectrum {
+    /// Renders the samples *samples.0..samples.1* of each pixel, tile
+    /// by tile on *num_cores* threads, and merges them into the film.
+    /// The tiles get merged in the order of the block queue, not in
+    /// the order the threads finish them: the filters of neighbouring
+    /// tiles overlap, and adding floats in another order rounds
+    /// differently, so this (together with the per pixel seeds, see
+    /// **render_tile_samples()**) makes the image independent of the
+    /// number of threads. With a *checkpoint* the tiles it has done
+    /// already get skipped, and the merged ones recorded.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_pass(
+        &self,
+        scene: &Scene,
+        grid: &TileGrid,
+        film: &Arc<Film>,
+        num_cores: usize,
+        samples: (i64, i64),
+        checkpoint: &mut Option<Checkpoint>,
+    ) {
+        let skip: Vec<bool> = match checkpoint.as_ref() {
+            Some(checkpoint) => checkpoint.done_tiles().to_vec(),
+            None => vec![false; grid.len()],
+        };
+        let n_skipped: usize = skip.iter().filter(|skip| **skip).count();
+        advance_render_progress(n_skipped);
+        let checkpointing: bool = checkpoint.is_some();
+        let skip: &[bool] = &skip;
+        let tile_size: Vector2i = grid.tile_size;
+        let n_tiles: Point2i = grid.n_tiles;
+        let block_queue = BlockQueue::new(
+            (
+                (n_tiles.x * tile_size.x) as u32,
+                (n_tiles.y * tile_size.y) as u32,
+            ),
+            (tile_size.x as u32, tile_size.y as u32),
+            (0, 0),
+        );
+        let integrator = &self;
+        let bq = &block_queue;
+        let sampler = &self.get_sampler();
+        crossbeam::scope(|scope| {
+            let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
+            // spawn worker threads
+            for thread_index in 0..num_cores {
+                let pixel_tx = pixel_tx.clone();
+                scope.spawn(move |_| {
+                    // allocate after pinning (NUMA node-local memory)
+                    numa::pin_render_thread(thread_index);
+                    let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+                    // per-thread memory for shading, reused by all samples
+                    let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+                    while let Some((position, (x, y))) = bq.next_with_position() {
+                        // stop taking tiles, the rendered ones get merged
+                        if is_render_cancelled() {
+                            break;
+                        }
+                        let tile: Point2i = Point2i {
+                            x: x as i32,
+                            y: y as i32,
+                        };
+                        let index: usize = (tile.y * n_tiles.x + tile.x) as usize;
+                        if skip[index] {
+                            // the main thread must not wait for this one
+                            pixel_tx
+                                .send((position, index, None))
+                                .unwrap_or_else(|_| panic!("Failed to send tile"));
+                            continue;
+                        }
+                        let film_tile = integrator.render_tile_samples(
+                            scene,
+                            grid,
+                            tile,
+                            film,
+                            &mut tile_sampler,
+                            &mut arena,
+                            samples,
+                        );
+                        // a checkpoint can't record partly rendered tiles
+                        if checkpointing && is_render_cancelled() {
+                            break;
+                        }
+                        // send the tile through the channel to main thread
+                        pixel_tx
+                            .send((position, index, Some(film_tile)))
+                            .unwrap_or_else(|_| panic!("Failed to send tile"));
+                    }
+                });
+            }
+            // the channel closes when all workers are done
+            drop(pixel_tx);
+            // spawn thread to collect pixels and render image to file
+            scope.spawn(move |_| {
+                let mut reporter: ProgressReporter =
+                    ProgressReporter::new((bq.len() - n_skipped) as u64, "Rendering", "tiles");
+                let mut ordered: OrderedMerge<(usize, Option<FilmTile>)> = OrderedMerge::default();
+                let mut merge = |(index, film_tile): (usize, Option<FilmTile>)| {
+                    if let Some(film_tile) = film_tile {
+                        // merge image tile into _Film_
+                        film.merge_film_tile(&film_tile);
+                        if let Some(checkpoint) = checkpoint.as_mut() {
+                            checkpoint.tile_done(film, index);
+                        }
+                        advance_render_progress(1);
+                        reporter.update(1);
+                    }
+                };
+                for (position, index, film_tile) in pixel_rx.iter() {
+                    ordered.add(position, (index, film_tile), &mut merge);
+                }
+                ordered.finish(&mut merge);
+                reporter.done();
+            });
+        })
+        .unwrap();
+    }
+    /// Renders all samples (see **budget_samples()**) of one tile of
+    /// the image. The sampler gets reseeded for every pixel (see
+    /// **pixel_seed()**), so a pixel looks the same no matter which
+    /// tile, thread (or machine, see **distributed**), or crop window
+    /// renders it. Expects **preprocess()** to be called before.
+    pub fn render_tile<'a>(
+        &self,
+        scene: &Scene,
+        grid: &TileGrid,
+        tile: Point2i,
+        film: &'a Film,
+        tile_sampler: &mut Sampler,
+        arena: &mut MemoryArena,
+    ) -> FilmTile<'a> {
+        let spp: i64 = budget_samples(tile_sampler.get_samples_per_pixel());
+        self.render_tile_samples(scene, grid, tile, film, tile_sampler, arena, (0, spp))
+    }
+    /// Renders the samples *samples.0..samples.1* of each pixel of one
+    /// tile (a pass of a progressive render, see **set_progressive()**).
+    /// Passes after the first one reseed the sampler with the first
+    /// sample number mixed in, and skip the samples of earlier passes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_tile_samples<'a>(
+        &self,
+        scene: &Scene,
+        grid: &TileGrid,
+        tile: Point2i,
+        film: &'a Film,
+        tile_sampler: &mut Sampler,
+        arena: &mut MemoryArena,
+        samples: (i64, i64),
+    ) -> FilmTile<'a> {
+        let (pass_start, pass_end) = samples;
+        let camera = self.get_camera();
+        let pixel_bounds: Bounds2i = self.get_pixel_bounds();
+        let tile_bounds: Bounds2i = grid.tile_bounds(tile);
+        // println!("Starting image tile {:?}", tile_bounds);
+        let mut film_tile = film.get_film_tile(&tile_bounds);
+        let debug_pixel: Option<Point2i> = debug_pixel();
+        // the AOVs of the film (if any) and their own random numbers
+        let aov_types: Vec<AovType> = film.aovs.iter().map(|(aov_type, _aov)| *aov_type).collect();
+        let split_light: bool =
+            self.splits_light() && aov_types.iter().any(|aov_type| aov_type.is_light());
+        let with_albedo: bool = aov_types.contains(&AovType::Albedo);
+        let deep: bool = film.is_deep();
+        // denoiser features, reported by the integrator or taken from
+        // the first surface hit
+        let features: bool = film.wants_denoiser_features();
+        let records_features: bool = features && self.records_features();
+        let with_albedo: bool = with_albedo || (features && !records_features);
+        let mut aov_rng: Rng = Rng::new();
+        let debug_non_finite: bool = self.debug_non_finite();
+        DEBUG_NON_FINITE.with(|on| on.set(debug_non_finite));
+        for pixel in &tile_bounds {
+            if is_render_cancelled() {
+                break;
+            }
+            let seed: u64 = pixel_seed(pixel).wrapping_add(pass_start as u64);
+            tile_sampler.reseed(seed);
+            tile_sampler.start_pixel(pixel);
+            aov_rng.set_sequence(seed);
+            if !pnt2_inside_exclusive(pixel, &pixel_bounds) {
+                continue;
+            }
+            // only the debug pixel gets rendered (if any)
+            let is_debug_pixel: bool = debug_pixel == Some(pixel);
+            if debug_pixel.is_some() && !is_debug_pixel {
+                continue;
+            }
+            // skip the samples of earlier passes
+            if pass_start > 0 && !tile_sampler.set_sample_number(pass_start) {
+                for _sample in 0..pass_start {
+                    tile_sampler.start_next_sample();
+                }
+            }
+            let mut done: bool = pass_start >= pass_end;
+            while !done {
+                // initialize _CameraSample_ for current sample
+                let camera_sample: CameraSample = tile_sampler.get_camera_sample(pixel);
+                // generate camera ray for current sample
+                let mut ray: Ray = Ray::default();
+                let ray_weight: Float = camera.generate_ray_differential(&camera_sample, &mut ray);
+                ray.scale_differentials(
+                    1.0 as Float / (tile_sampler.get_samples_per_pixel() as Float).sqrt(),
+                );
+                N_CAMERA_RAYS.inc();
+                if is_debug_pixel {
+                    begin_sample(camera_sample.p_film, &ray, ray_weight);
+                }
+                let first_hit: Option<(Spectrum, Normal3f, Float)> =
+                    if (!aov_types.is_empty() || deep || features) && ray_weight > 0.0 {
+                        first_hit_aovs(scene, &ray, with_albedo, &mut aov_rng)
+                    } else {
+                        None
+                    };
+                if split_light {
+                    LIGHT_SPLIT.with(|split| *split.borrow_mut() = Some(Default::default()));
+                }
+                if records_features {
+                    FEATURES_WANTED.with(|wanted| wanted.set(true));
+                }
+                // evaluate radiance along camera ray
+                let mut l: Spectrum = Spectrum::new(0.0 as Float);
+                let mut alpha: Float = 1.0 as Float;
+                if ray_weight > 0.0 {
+                    let (li, a) = self.li_with_alpha(&mut ray, scene, tile_sampler, arena, 0_i32);
+                    l = li;
+                    alpha = a;
+                }
+                if is_debug_pixel {
+                    end_sample(pixel, tile_sampler.get_current_sample_number(), &l);
+                }
+                let y: Float = l.y();
+                // the depth where the path turned NaN or infinite (if reported)
+                let non_finite: Option<(u32, bool)> = if debug_non_finite {
+                    NON_FINITE_DEPTH.with(|non_finite| non_finite.take())
+                } else {
+                    None
+                };
+                if debug_non_finite && (non_finite.is_some() || l.has_nans() || l.has_infs()) {
+                    let is_nan: bool = match non_finite {
+                        Some((_depth, is_nan)) => is_nan,
+                        None => l.has_nans(),
+                    };
+                    let depth: String = match non_finite {
+                        Some((depth, _is_nan)) => depth.to_string(),
+                        None => String::from("unknown"),
+                    };
+                    error!(
+                        "{} radiance value for pixel ({}, {}), sample {}, path depth {}.",
+                        if is_nan { "Not-a-number" } else { "Infinite" },
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number(),
+                        depth
+                    );
+                    film.flag_pixel(
+                        pixel,
+                        if is_nan {
+                            NAN_FLAG_COLOR
+                        } else {
+                            INF_FLAG_COLOR
+                        },
+                    );
+                    l = Spectrum::new(0.0);
+                } else if l.has_nans() {
+                    warn!(
+                        "Not-a-number radiance value returned for pixel \
+                         ({:?}, {:?}), sample {:?}. Setting to black.",
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                } else if y < -10.0e-5 as Float {
+                    warn!(
+                        "Negative luminance value, {:?}, returned for pixel \
+                         ({:?}, {:?}), sample {:?}. Setting to black.",
+                        y,
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                } else if y.is_infinite() {
+                    warn!(
+                        "Infinite luminance value returned for pixel ({:?}, \
+                         {:?}), sample {:?}. Setting to black.",
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                }
+                // println!("Camera sample: {:?} -> ray: {:?} -> L = {:?}",
+                //          camera_sample, ray, l);
+                let light: [Spectrum; 2] = if split_light {
+                    LIGHT_SPLIT
+                        .with(|split| split.borrow_mut().take())
+                        .unwrap_or_default()
+                } else {
+                    Default::default()
+                };
+                for (index, aov_type) in aov_types.iter().enumerate() {
+                    let mut rgb: [Float; 3] = [0.0 as Float; 3];
+                    let mut weight: Float = 1.0 as Float;
+                    match (aov_type, first_hit) {
+                        (AovType::Albedo, Some((albedo, _n, _depth))) => albedo.to_rgb(&mut rgb),
+                        (AovType::Normal, Some((_albedo, n, _depth))) => rgb = [n.x, n.y, n.z],
+                        // misses don't count
+                        (AovType::Depth, None) => weight = 0.0 as Float,
+                        (AovType::Depth, Some((_albedo, _n, depth))) => rgb = [depth; 3],
+                        (AovType::Emission, _) | (AovType::Direct, _) | (AovType::Indirect, _) => {
+                            // l was set to black for invalid samples
+                            if !l.is_black() {
+                                let light: Spectrum = match aov_type {
+                                    AovType::Emission => light[0],
+                                    AovType::Direct => light[1],
+                                    _ => l - light[0] - light[1],
+                                };
+                                light.to_rgb(&mut rgb);
+                            }
+                            weight = ray_weight;
+                        }
+                        _ => {}
+                    }
+                    if weight > 0.0 as Float {
+                        film_tile.add_aov_sample(index, camera_sample.p_film, &rgb, weight);
+                    }
+                }
+                if features {
+                    let (albedo, n): (Spectrum, Normal3f) = if records_features {
+                        FEATURES_WANTED.with(|wanted| wanted.set(false));
+                        FEATURES
+                            .with(|features| features.borrow_mut().take())
+                            .unwrap_or_default()
+                    } else {
+                        match first_hit {
+                            Some((albedo, n, _depth)) => (albedo, n),
+                            None => (Spectrum::default(), Normal3f::default()),
+                        }
+                    };
+                    // normals in camera space
+                    let camera_to_world = camera.get_camera_to_world();
+                    let axis = |x: Float, y: Float, z: Float| -> Vector3f {
+                        camera_to_world.transform_vector(ray.time, &Vector3f { x, y, z })
+                    };
+                    let n_camera: Normal3f = Normal3f {
+                        x: vec3_dot_nrm(&axis(1.0, 0.0, 0.0), &n),
+                        y: vec3_dot_nrm(&axis(0.0, 1.0, 0.0), &n),
+                        z: vec3_dot_nrm(&axis(0.0, 0.0, 1.0), &n),
+                    };
+                    film_tile.add_features(camera_sample.p_film, &albedo, &n_camera, ray_weight);
+                }
+                if deep {
+                    let z: Float = match first_hit {
+                        Some((_albedo, _n, depth)) => depth,
+                        None => Float::INFINITY,
+                    };
+                    film_tile.add_deep_sample(camera_sample.p_film, z, &l, alpha, ray_weight);
+                }
+                // add camera ray's contribution to image
+                film_tile.add_sample_with_alpha(camera_sample.p_film, &mut l, alpha, ray_weight);
+                // free _MemoryArena_ memory from computing image sample value
+                arena.reset();
+                done = !tile_sampler.start_next_sample()
+                    || tile_sampler.get_current_sample_number() >= pass_end;
+            }
+        }
+        film_tile
+    }
+    /// Do the integrators report the light of their samples for the
+    /// *emission*, *direct*, and *indirect* AOVs (see
+    /// **record_light()**)?
+    pub fn splits_light(&self) -> bool {
+        matches!(
+            self,
+            SamplerIntegrator::Path(_) | SamplerIntegrator::VolPath(_)
+        )
+    }
+    /// Does the integrator report the denoiser features of its samples
+    /// (see **record_features()**)?
+    pub fn records_features(&self) -> bool {
+        matches!(self, SamplerIntegrator::Path(_))
+    }
+    pub fn li(
+        &self,
+        ray: &mut Ray,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
         match self {
-            SamplerIntegrator::AO(integrator) => integrator.li(ray, scene, sampler, depth),
+            SamplerIntegrator::AO(integrator) => integrator.li(ray, scene, sampler, arena, depth),
             SamplerIntegrator::DirectLighting(integrator) => {
-                integrator.li(ray, scene, sampler, depth)
+                integrator.li(ray, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::Path(integrator) => integrator.li(ray, scene, sampler, arena, depth),
+            SamplerIntegrator::PhotonMap(integrator) => {
+                integrator.li(ray, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::VolPath(integrator) => {
+                integrator.li(ray, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::Whitted(integrator) => {
+                integrator.li(ray, scene, sampler, arena, depth)
             }
-            SamplerIntegrator::Path(integrator) => integrator.li(ray, scene, sampler, depth),
-            SamplerIntegrator::VolPath(integrator) => integrator.li(ray, scene, sampler, depth),
-            SamplerIntegrator::Whitted(integrator) => integrator.li(ray, scene, sampler, depth),
+        }
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        ray: &mut Ray,
+        first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        match self {
+            SamplerIntegrator::AO(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::DirectLighting(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::Path(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::PhotonMap(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::VolPath(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+            SamplerIntegrator::Whitted(integrator) => {
+                integrator.li_first_hit(ray, first_hit, scene, sampler, arena, depth)
+            }
+        }
+    }
+    /// Like **li()**, but also returns the coverage (alpha) of the
+    /// camera ray. Camera rays hitting a shadow catcher return only
+    /// the reflections of other objects, and the alpha value tells how
+    /// much of the light reaching the catcher is blocked by them. If
+    /// the scene contains shadow catchers, rays escaping the scene are
+    /// fully transparent. Camera rays hitting a holdout are black and
+    /// fully transparent.
+    pub fn li_with_alpha(
+        &self,
+        ray: &mut Ray,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> (Spectrum, Float) {
+        if !scene.has_shadow_catchers && !scene.has_holdouts {
+            return (self.li(ray, scene, sampler, arena, depth), 1.0 as Float);
+        }
+        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+        let found: bool = scene.intersect(ray, &mut isect);
+        if let Some(primitive_raw) = isect.primitive {
+            let primitive = unsafe { &*primitive_raw };
+            if primitive.is_holdout() {
+                return (Spectrum::default(), 0.0 as Float);
+            }
+            if primitive.is_shadow_catcher() {
+                return self.shadow_catcher_li(ray, &mut isect, scene, sampler, arena, depth);
+            }
+        }
+        // the backplate shows through where nothing was hit
+        let alpha: Float = if !found && scene.has_shadow_catchers {
+            0.0 as Float
+        } else {
+            1.0 as Float
+        };
+        let first_hit: FirstHit = Some((found, isect));
+        (
+            self.li_first_hit(ray, first_hit, scene, sampler, arena, depth),
+            alpha,
+        )
+    }
+    fn shadow_catcher_li(
+        &self,
+        ray: &Ray,
+        isect: &mut SurfaceInteraction,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> (Spectrum, Float) {
+        // compare (diffuse) direct lighting with and without occlusion
+        let n: Normal3f = nrm_faceforward_vec3(&isect.n, &isect.wo);
+        let it_common: InteractionCommon = InteractionCommon {
+            p: isect.p,
+            time: isect.time,
+            p_error: isect.p_error,
+            wo: isect.wo,
+            n: isect.n,
+            medium_interface: isect.get_medium_interface(),
+        };
+        let mut unoccluded: Float = 0.0;
+        let mut occluded: Float = 0.0;
+        for light in &scene.lights {
+            let mut wi: Vector3f = Vector3f::default();
+            let mut light_pdf: Float = 0.0 as Float;
+            let mut visibility: VisibilityTester = VisibilityTester::default();
+            let li: Spectrum = light.sample_li(
+                &it_common,
+                sampler.get_2d(),
+                &mut wi,
+                &mut light_pdf,
+                &mut visibility,
+            );
+            let cos_theta: Float = vec3_dot_nrm(&wi, &n);
+            if light_pdf > 0.0 as Float && cos_theta > 0.0 as Float && !li.is_black() {
+                let contrib: Float = li.y() * cos_theta / light_pdf;
+                unoccluded += contrib;
+                if !visibility.unoccluded(scene) {
+                    occluded += contrib;
+                }
+            }
+        }
+        let alpha: Float = if unoccluded > 0.0 as Float {
+            clamp_t(occluded / unoccluded, 0.0 as Float, 1.0 as Float)
+        } else {
+            0.0 as Float
+        };
+        // reflections of other (non-catcher) objects
+        let mut l: Spectrum = Spectrum::default();
+        isect.compute_scattering_functions(ray, true, TransportMode::Radiance);
+        if let Some(ref bsdf) = isect.bsdf {
+            let mut wi: Vector3f = Vector3f::default();
+            let mut pdf: Float = 0.0 as Float;
+            let mut sampled_type: u8 = u8::MAX; // != 0
+            let f: Spectrum = bsdf.sample_f(
+                &isect.wo,
+                &mut wi,
+                sampler.get_2d(),
+                &mut pdf,
+                BxdfType::BsdfAll as u8,
+                &mut sampled_type,
+            );
+            if !f.is_black() && pdf > 0.0 as Float {
+                let mut reflected_ray: Ray = isect.spawn_ray(&wi);
+                let mut reflected_isect: SurfaceInteraction = SurfaceInteraction::default();
+                // escaping rays see the backplate, not the environment
+                if scene.intersect(&mut reflected_ray, &mut reflected_isect) {
+                    let mut hit_catcher: bool = false;
+                    if let Some(primitive_raw) = reflected_isect.primitive {
+                        let primitive = unsafe { &*primitive_raw };
+                        hit_catcher = primitive.is_shadow_catcher();
+                    }
+                    if !hit_catcher {
+                        let first_hit: FirstHit = Some((true, reflected_isect));
+                        let li: Spectrum = self.li_first_hit(
+                            &mut reflected_ray,
+                            first_hit,
+                            scene,
+                            sampler,
+                            arena,
+                            depth + 1,
+                        );
+                        l = f * li * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf;
+                    }
+                }
+            }
+        }
+        (l, alpha)
+    }
+    /// See **Integrator::set_terminator_shadowing()**.
+    pub fn set_terminator_shadowing(&mut self, on: bool) {
+        match self {
+            // ambient occlusion doesn't evaluate BSDFs
+            SamplerIntegrator::AO(_) => {}
+            SamplerIntegrator::DirectLighting(integrator) => integrator.terminator_shadowing = on,
+            SamplerIntegrator::Path(integrator) => integrator.terminator_shadowing = on,
+            SamplerIntegrator::PhotonMap(integrator) => integrator.terminator_shadowing = on,
+            SamplerIntegrator::VolPath(integrator) => integrator.terminator_shadowing = on,
+            SamplerIntegrator::Whitted(integrator) => integrator.terminator_shadowing = on,
+        }
+    }
+    /// Turns the debug mode for tracking down NaN and infinite radiance
+    /// values on or off. Instead of silently setting such samples to
+    /// black, the pixel gets painted (NaN magenta, infinite cyan) and
+    /// the pixel, the sample index, and the path depth where the value
+    /// turned non-finite get logged. The depth is known for **path**
+    /// and **volpath** (see **report_non_finite()**).
+    pub fn set_debug_non_finite(&mut self, on: bool) {
+        match self {
+            SamplerIntegrator::AO(integrator) => integrator.debug_non_finite = on,
+            SamplerIntegrator::DirectLighting(integrator) => integrator.debug_non_finite = on,
+            SamplerIntegrator::Path(integrator) => integrator.debug_non_finite = on,
+            SamplerIntegrator::PhotonMap(integrator) => integrator.debug_non_finite = on,
+            SamplerIntegrator::VolPath(integrator) => integrator.debug_non_finite = on,
+            SamplerIntegrator::Whitted(integrator) => integrator.debug_non_finite = on,
+        }
+    }
+    pub fn debug_non_finite(&self) -> bool {
+        match self {
+            SamplerIntegrator::AO(integrator) => integrator.debug_non_finite,
+            SamplerIntegrator::DirectLighting(integrator) => integrator.debug_non_finite,
+            SamplerIntegrator::Path(integrator) => integrator.debug_non_finite,
+            SamplerIntegrator::PhotonMap(integrator) => integrator.debug_non_finite,
+            SamplerIntegrator::VolPath(integrator) => integrator.debug_non_finite,
+            SamplerIntegrator::Whitted(integrator) => integrator.debug_non_finite,
         }
     }
     pub fn get_camera(&self) -> Arc<Camera> {
@@ -241,6 +1337,7 @@ impl SamplerIntegrator {
             SamplerIntegrator::AO(integrator) => integrator.get_camera(),
             SamplerIntegrator::DirectLighting(integrator) => integrator.get_camera(),
             SamplerIntegrator::Path(integrator) => integrator.get_camera(),
+            SamplerIntegrator::PhotonMap(integrator) => integrator.get_camera(),
             SamplerIntegrator::VolPath(integrator) => integrator.get_camera(),
             SamplerIntegrator::Whitted(integrator) => integrator.get_camera(),
         }
@@ -250,6 +1347,7 @@ impl SamplerIntegrator {
             SamplerIntegrator::AO(integrator) => integrator.get_sampler(),
             SamplerIntegrator::DirectLighting(integrator) => integrator.get_sampler(),
             SamplerIntegrator::Path(integrator) => integrator.get_sampler(),
+            SamplerIntegrator::PhotonMap(integrator) => integrator.get_sampler(),
             SamplerIntegrator::VolPath(integrator) => integrator.get_sampler(),
             SamplerIntegrator::Whitted(integrator) => integrator.get_sampler(),
         }
@@ -259,6 +1357,7 @@ impl SamplerIntegrator {
             SamplerIntegrator::AO(integrator) => integrator.get_pixel_bounds(),
             SamplerIntegrator::DirectLighting(integrator) => integrator.get_pixel_bounds(),
             SamplerIntegrator::Path(integrator) => integrator.get_pixel_bounds(),
+            SamplerIntegrator::PhotonMap(integrator) => integrator.get_pixel_bounds(),
             SamplerIntegrator::VolPath(integrator) => integrator.get_pixel_bounds(),
             SamplerIntegrator::Whitted(integrator) => integrator.get_pixel_bounds(),
         }
@@ -269,14 +1368,15 @@ impl SamplerIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         match self {
             SamplerIntegrator::DirectLighting(integrator) => {
-                integrator.specular_reflect(ray, isect, scene, sampler, depth)
+                integrator.specular_reflect(ray, isect, scene, sampler, arena, depth)
             }
             SamplerIntegrator::Whitted(integrator) => {
-                integrator.specular_reflect(ray, isect, scene, sampler, depth)
+                integrator.specular_reflect(ray, isect, scene, sampler, arena, depth)
             }
             _ => Spectrum::default(),
         }
@@ -287,14 +1387,15 @@ impl SamplerIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         match self {
             SamplerIntegrator::DirectLighting(integrator) => {
-                integrator.specular_transmit(ray, isect, scene, sampler, depth)
+                integrator.specular_transmit(ray, isect, scene, sampler, arena, depth)
             }
             SamplerIntegrator::Whitted(integrator) => {
-                integrator.specular_transmit(ray, isect, scene, sampler, depth)
+                integrator.specular_transmit(ray, isect, scene, sampler, arena, depth)
             }
             _ => Spectrum::default(),
         }
@@ -308,6 +1409,7 @@ pub fn uniform_sample_all_lights(
     it: &SurfaceInteraction,
     scene: &Scene,
     sampler: &mut Sampler,
+    arena: &MemoryArena,
     n_light_samples: &[i32],
     handle_media: bool,
 ) -> Spectrum {
@@ -316,8 +1418,15 @@ pub fn uniform_sample_all_lights(
     for (j, n_samples) in n_light_samples.iter().enumerate().take(scene.lights.len()) {
         // accumulate contribution of _j_th light to _L_
         let light = &scene.lights[j];
-        let u_light_array: Vec<Point2f> = sampler.get_2d_array_vec(*n_samples);
-        let u_scattering_array: Vec<Point2f> = sampler.get_2d_array_vec(*n_samples);
+        // copy the sample arrays, the sampler is needed for more samples
+        let u_light_array: &[Point2f] = match sampler.get_2d_array(*n_samples) {
+            Some(u) => arena.alloc_slice_copy(u),
+            None => &[],
+        };
+        let u_scattering_array: &[Point2f] = match sampler.get_2d_array(*n_samples) {
+            Some(u) => arena.alloc_slice_copy(u),
+            None => &[],
+        };
         if u_light_array.is_empty() || u_scattering_array.is_empty() {
             // use a single sample for illumination from _light_
             let u_light: Point2f = sampler.get_2d();
@@ -401,6 +1510,39 @@ pub fn uniform_sample_one_light(
     ) / pdf
 }
 
+/// Estimate direct lighting for only one light, picked by the light
+/// sampling distribution (e.g. a **LightBvh**) for the interaction's
+/// point and normal, and divide by the probability of picking it.
+pub fn sample_one_light(
+    it: &dyn Interaction,
+    scene: &Scene,
+    sampler: &mut Sampler,
+    handle_media: bool,
+    light_distribution: &LightDistribution,
+) -> Spectrum {
+    if scene.lights.is_empty() {
+        return Spectrum::default();
+    }
+    let n: Normal3f = it.get_shading_n().unwrap_or_else(|| it.get_n());
+    let (light_num, pdf) = match light_distribution.sample(&it.get_p(), &n, sampler.get_1d()) {
+        Some(sampled) => sampled,
+        None => return Spectrum::default(),
+    };
+    let light = &scene.lights[light_num];
+    let u_light: Point2f = sampler.get_2d();
+    let u_scattering: Point2f = sampler.get_2d();
+    estimate_direct(
+        it,
+        u_scattering,
+        light.clone(),
+        u_light,
+        scene,
+        sampler,
+        handle_media,
+        false,
+    ) / pdf
+}
+
 /// Computes a direct lighting estimate for a single light source sample.
 pub fn estimate_direct(
     it: &dyn Interaction,
@@ -502,7 +1644,7 @@ pub fn estimate_direct(
                     sampled_specular = (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8;
                 }
             } else {
-                println!("TODO: if let Some(ref bsdf) = it.get_bsdf() failed");
+                debug!("TODO: if let Some(ref bsdf) = it.get_bsdf() failed");
             }
         } else {
             // sample scattered direction for medium interactions
@@ -587,3 +1729,47 @@ pub fn compute_light_power_distribution(scene: &Scene) -> Option<Arc<Distributio
     }
     Some(Arc::new(Distribution1D::new(light_power)))
 }
+
+/// The albedo (with one BSDF sample if *with_albedo*), the shading
+/// normal, and the distance of the first surface the camera *ray*
+/// hits (skipping medium boundaries), for the AOVs of the film (see
+/// **AovType**).
+fn first_hit_aovs(
+    scene: &Scene,
+    ray: &Ray,
+    with_albedo: bool,
+    rng: &mut Rng,
+) -> Option<(Spectrum, Normal3f, Float)> {
+    let mut r: Ray = ray.clone();
+    let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+    while scene.intersect(&mut r, &mut isect) {
+        isect.compute_scattering_functions(&r, true, TransportMode::Radiance);
+        if let Some(ref bsdf) = isect.bsdf {
+            let mut albedo: Spectrum = Spectrum::default();
+            if with_albedo {
+                let mut wi: Vector3f = Vector3f::default();
+                let mut pdf: Float = 0.0 as Float;
+                let mut sampled_type: u8 = u8::MAX; // != 0
+                let u: Point2f = Point2f {
+                    x: rng.uniform_float(),
+                    y: rng.uniform_float(),
+                };
+                let f: Spectrum = bsdf.sample_f(
+                    &isect.wo,
+                    &mut wi,
+                    u,
+                    &mut pdf,
+                    BxdfType::BsdfAll as u8,
+                    &mut sampled_type,
+                );
+                if pdf > 0.0 as Float {
+                    albedo = f * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf;
+                }
+            }
+            return Some((albedo, isect.shading.n, pnt3_distance(&ray.o, &isect.p)));
+        }
+        r = isect.spawn_ray(&r.d);
+        isect = SurfaceInteraction::default();
+    }
+    None
+}
@@ -18,7 +18,7 @@ use crate::core::geometry::{
 };
 use crate::core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::material::TransportMode;
-use crate::core::medium::{HenyeyGreenstein, Medium, MediumInterface};
+use crate::core::medium::{Medium, MediumInterface, PhaseFunction};
 use crate::core::pbrt::SHADOW_EPSILON;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::primitive::Primitive;
@@ -40,7 +40,7 @@ pub trait Interaction {
     fn get_medium_interface(&self) -> Option<Arc<MediumInterface>>;
     fn get_bsdf(&self) -> Option<&Bsdf>;
     fn get_shading_n(&self) -> Option<Normal3f>;
-    fn get_phase(&self) -> Option<Arc<HenyeyGreenstein>>;
+    fn get_phase(&self) -> Option<Arc<PhaseFunction>>;
 }
 
 #[derive(Default, Clone)]
@@ -60,7 +60,7 @@ impl InteractionCommon {
         Ray {
             o,
             d: *d,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: self.time,
             differential: None,
             medium: self.get_medium(d),
@@ -126,7 +126,9 @@ pub struct MediumInteraction {
     pub n: Normal3f,
     pub medium_interface: Option<Arc<MediumInterface>>,
     // MediumInteraction Public Data
-    pub phase: Option<Arc<HenyeyGreenstein>>,
+    pub phase: Option<Arc<PhaseFunction>>,
+    /// radiance emitted at the interaction (e.g. by hot gas)
+    pub le: Spectrum,
 }
 
 impl MediumInteraction {
@@ -135,7 +137,7 @@ impl MediumInteraction {
         wo: &Vector3f,
         time: Float,
         medium: Option<Arc<Medium>>,
-        phase: Option<Arc<HenyeyGreenstein>>,
+        phase: Option<Arc<PhaseFunction>>,
     ) -> Self {
         if let Some(medium_arc) = medium {
             let inside: Option<Arc<Medium>> = Some(medium_arc.clone());
@@ -148,6 +150,7 @@ impl MediumInteraction {
                 n: Normal3f::default(),
                 medium_interface: Some(Arc::new(MediumInterface::new(inside, outside))),
                 phase,
+                le: Spectrum::default(),
             }
         } else {
             MediumInteraction {
@@ -158,6 +161,7 @@ impl MediumInteraction {
                 n: Normal3f::default(),
                 medium_interface: None,
                 phase,
+                le: Spectrum::default(),
             }
         }
     }
@@ -189,7 +193,7 @@ impl MediumInteraction {
             false
         }
     }
-    pub fn get_phase(&self) -> Option<Arc<HenyeyGreenstein>> {
+    pub fn get_phase(&self) -> Option<Arc<PhaseFunction>> {
         if let Some(ref phase) = self.phase {
             Some(phase.clone())
         } else {
@@ -210,7 +214,7 @@ impl Interaction for MediumInteraction {
         Ray {
             o,
             d: *d,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: self.time,
             differential: None,
             medium: self.get_medium(d),
@@ -244,7 +248,7 @@ impl Interaction for MediumInteraction {
     fn get_shading_n(&self) -> Option<Normal3f> {
         None
     }
-    fn get_phase(&self) -> Option<Arc<HenyeyGreenstein>> {
+    fn get_phase(&self) -> Option<Arc<PhaseFunction>> {
         if let Some(ref phase) = self.phase {
             Some(phase.clone())
         } else {
@@ -279,6 +283,9 @@ pub struct SurfaceInteraction<'a> {
     pub bsdf: Option<Bsdf>,
     pub bssrdf: Option<TabulatedBssrdf>,
     pub shape: Option<&'a Shape>,
+    /// index of refraction on the outside of a nested dielectric
+    /// (see **InteriorList**), _None_ for vacuum
+    pub eta_outside: Option<Float>,
 }
 
 impl<'a> SurfaceInteraction<'a> {
@@ -339,6 +346,7 @@ impl<'a> SurfaceInteraction<'a> {
                 bsdf: None,
                 bssrdf: None,
                 shape: Some(shape.clone()),
+                eta_outside: None,
             }
         } else {
             SurfaceInteraction {
@@ -364,6 +372,7 @@ impl<'a> SurfaceInteraction<'a> {
                 bsdf: None,
                 bssrdf: None,
                 shape: None,
+                eta_outside: None,
             }
         }
     }
@@ -431,6 +440,13 @@ impl<'a> SurfaceInteraction<'a> {
             );
         }
     }
+    /// Turns the terminator shadowing of the BSDF (if any) on or off,
+    /// see **Bsdf::terminator_shadowing()**.
+    pub fn set_terminator_shadowing(&mut self, on: bool) {
+        if let Some(ref mut bsdf) = self.bsdf {
+            bsdf.shadow_terminator = on;
+        }
+    }
     pub fn compute_differentials(&mut self, ray: &Ray) {
         if let Some(ref diff) = ray.differential {
             // estimate screen space change in $\pt{}$ and $(u,v)$
@@ -543,7 +559,7 @@ impl<'a> Interaction for SurfaceInteraction<'a> {
         Ray {
             o,
             d: *d,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: self.time,
             differential: None,
             medium: self.get_medium(d),
@@ -581,7 +597,7 @@ impl<'a> Interaction for SurfaceInteraction<'a> {
     fn get_shading_n(&self) -> Option<Normal3f> {
         Some(self.shading.n)
     }
-    fn get_phase(&self) -> Option<Arc<HenyeyGreenstein>> {
+    fn get_phase(&self) -> Option<Arc<PhaseFunction>> {
         None
     }
 }
@@ -1,11 +1,10 @@
 //! Spline-based interpolation to reconstruct BSDF values (instead of
 //! using large lookup tables).
 
-// std
-use std::f32::consts::PI;
 // others
 use smallvec::SmallVec;
 // pbrt
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::find_interval;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::INV_2_PI;
@@ -248,7 +247,7 @@ pub fn sample_fourier(
     }
     let mut a: f64 = 0.0;
     let mut b: f64 = PI as f64;
-    let mut phi: f64 = (0.5_f32 * PI) as f64;
+    let mut phi: f64 = (0.5 as Float * PI) as f64;
     let mut cf: f64;
     let mut f: f64;
     loop {
@@ -0,0 +1,406 @@
+//! An irradiance cache for diffuse interreflection.
+//!
+//! The indirect irradiance *E* arriving at a diffuse surface changes
+//! slowly over the surface, except close to other geometry. Instead
+//! of tracing many rays wherever it is needed, it gets computed at a
+//! few points (the records) by sampling the hemisphere around the
+//! normal with *M x N* stratified, cosine-weighted rays, and
+//! interpolated in between.
+//!
+//! Each record remembers the harmonic mean distance *R* of the
+//! surfaces seen by its rays and is used within *max_error * R* of
+//! its position, as long as the normals differ by less than
+//! **MAX_ANGLE_DIFFERENCE**. Besides *E* it stores the rotational and
+//! translational gradients of *E* (after Ward and Heckbert,
+//! "Irradiance Gradients"), which extrapolate *E* from the record to
+//! the point (and normal) being shaded. The translational gradient
+//! follows from how much light each boundary between neighboring
+//! strata lets through when it moves with the (nearer) surface seen
+//! there. Across the boundaries between azimuthal strata that is
+//! (sin(theta+) - sin(theta-)) / r per difference of radiance, not
+//! Ward and Heckbert's (cos(theta-) - cos(theta+)) / (sin(theta) r),
+//! which overestimates it. The records are stored in an octree, each in
+//! the nodes covering its area of influence, which are about as large
+//! as that area.
+
+// std
+use std::sync::RwLock;
+// pbrt
+use crate::core::geometry::{nrm_dot_nrm, vec3_coordinate_system, vec3_cross_vec3};
+use crate::core::geometry::{vec3_dot_vec3, Bounds3f, Normal3f, Point3f, Vector3f};
+use crate::core::pbrt::consts::PI;
+use crate::core::pbrt::{radians, Float, Spectrum};
+use crate::core::rng::Rng;
+use crate::core::stats::{StatCounter, StatMemoryCounter, StatPercent};
+
+static RECORDS_COMPUTED: StatCounter = StatCounter::new("Irradiance Cache/Records computed");
+static INTERPOLATED: StatPercent = StatPercent::new("Irradiance Cache/Interpolated lookups");
+static CACHE_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Irradiance cache");
+
+/// records aren't used for normals differing by more (degrees)
+pub const MAX_ANGLE_DIFFERENCE: Float = 10.0;
+/// limits of the harmonic mean distance *R* of a record relative to
+/// the diagonal of the scene bounds, so that there are neither too
+/// many records in corners nor too few in open spaces
+const MIN_RADIUS_FRACTION: Float = 0.01;
+const MAX_RADIUS_FRACTION: Float = 0.25;
+const MAX_OCTREE_DEPTH: u32 = 16;
+
+/// The (indirect) irradiance computed at one point.
+#[derive(Debug, Copy, Clone)]
+pub struct IrradianceRecord {
+    pub p: Point3f,
+    pub n: Normal3f,
+    pub e: Spectrum,
+    /// distance within which the record gets used
+    pub max_dist: Float,
+    /// change of *E* per rotation of the normal around the x, y, and
+    /// z axes
+    pub rot_grad: [Spectrum; 3],
+    /// change of *E* per distance along the x, y, and z axes
+    pub trans_grad: [Spectrum; 3],
+}
+
+impl IrradianceRecord {
+    /// The weight of the record at *p* with normal *n* (zero if it
+    /// can't be used) and the irradiance extrapolated to there.
+    fn extrapolate(&self, p: &Point3f, n: &Normal3f, cos_max_angle: Float) -> (Float, Spectrum) {
+        let d: Vector3f = *p - self.p;
+        let cos_angle: Float = nrm_dot_nrm(n, &self.n);
+        if cos_angle < cos_max_angle {
+            return (0.0 as Float, Spectrum::default());
+        }
+        // ignore records in front of the point
+        let n_avg: Vector3f = Vector3f::from(*n + self.n) * 0.5 as Float;
+        if vec3_dot_vec3(&d, &n_avg) < -0.1 as Float * self.max_dist {
+            return (0.0 as Float, Spectrum::default());
+        }
+        let p_err: Float = d.length() / self.max_dist;
+        let n_err: Float =
+            ((1.0 as Float - cos_angle).max(0.0 as Float) / (1.0 as Float - cos_max_angle)).sqrt();
+        let err: Float = p_err.max(n_err);
+        if err >= 1.0 as Float {
+            return (0.0 as Float, Spectrum::default());
+        }
+        let rot: Vector3f = vec3_cross_vec3(&Vector3f::from(self.n), &Vector3f::from(*n));
+        let mut e: Spectrum = self.e;
+        for axis in 0..3_u8 {
+            e +=
+                self.rot_grad[axis as usize] * rot[axis] + self.trans_grad[axis as usize] * d[axis];
+        }
+        (1.0 as Float - err, e.clamp(0.0 as Float, Float::INFINITY))
+    }
+}
+
+#[derive(Default)]
+struct OctNode {
+    children: [Option<Box<OctNode>>; 8],
+    records: Vec<IrradianceRecord>,
+}
+
+fn octant_bounds(bound: &Bounds3f, child: usize) -> Bounds3f {
+    let mid: Point3f = (bound.p_min + bound.p_max) * 0.5 as Float;
+    let mut b: Bounds3f = Bounds3f {
+        p_min: bound.p_min,
+        p_max: mid,
+    };
+    if child & 1 != 0 {
+        b.p_min.x = mid.x;
+        b.p_max.x = bound.p_max.x;
+    }
+    if child & 2 != 0 {
+        b.p_min.y = mid.y;
+        b.p_max.y = bound.p_max.y;
+    }
+    if child & 4 != 0 {
+        b.p_min.z = mid.z;
+        b.p_max.z = bound.p_max.z;
+    }
+    b
+}
+
+fn overlaps(b1: &Bounds3f, b2: &Bounds3f) -> bool {
+    b1.p_max.x >= b2.p_min.x
+        && b1.p_min.x <= b2.p_max.x
+        && b1.p_max.y >= b2.p_min.y
+        && b1.p_min.y <= b2.p_max.y
+        && b1.p_max.z >= b2.p_min.z
+        && b1.p_min.z <= b2.p_max.z
+}
+
+impl OctNode {
+    fn add(
+        &mut self,
+        bound: &Bounds3f,
+        record: &IrradianceRecord,
+        record_bound: &Bounds3f,
+        depth: u32,
+    ) {
+        // store the record in the smallest nodes at least as large as
+        // its area of influence (at most eight)
+        let diagonal2: Float = (record_bound.p_max - record_bound.p_min).length_squared();
+        if depth == MAX_OCTREE_DEPTH || bound.diagonal().length_squared() < 4.0 as Float * diagonal2
+        {
+            self.records.push(*record);
+            CACHE_BYTES.add(std::mem::size_of::<IrradianceRecord>());
+            return;
+        }
+        for child in 0..8 {
+            let child_bound: Bounds3f = octant_bounds(bound, child);
+            if !overlaps(&child_bound, record_bound) {
+                continue;
+            }
+            if self.children[child].is_none() {
+                self.children[child] = Some(Box::new(OctNode::default()));
+                CACHE_BYTES.add(std::mem::size_of::<OctNode>());
+            }
+            if let Some(ref mut node) = self.children[child] {
+                node.add(&child_bound, record, record_bound, depth + 1);
+            }
+        }
+    }
+    fn lookup<F>(&self, bound: &Bounds3f, p: &Point3f, f: &mut F)
+    where
+        F: FnMut(&IrradianceRecord),
+    {
+        for record in &self.records {
+            f(record);
+        }
+        let mid: Point3f = (bound.p_min + bound.p_max) * 0.5 as Float;
+        let child: usize =
+            (p.x > mid.x) as usize + 2 * ((p.y > mid.y) as usize) + 4 * ((p.z > mid.z) as usize);
+        if let Some(ref node) = self.children[child] {
+            node.lookup(&octant_bounds(bound, child), p, f);
+        }
+    }
+}
+
+/// Irradiance records in an octree (see the module documentation).
+pub struct IrradianceCache {
+    /// records are used within *max_error* times their harmonic mean
+    /// distance
+    pub max_error: Float,
+    /// rays traced per record
+    pub samples: i32,
+    bounds: Bounds3f,
+    min_radius: Float,
+    max_radius: Float,
+    root: RwLock<OctNode>,
+}
+
+impl IrradianceCache {
+    pub fn new(max_error: Float, samples: i32) -> Self {
+        IrradianceCache {
+            max_error,
+            samples,
+            bounds: Bounds3f::default(),
+            min_radius: 0.0 as Float,
+            max_radius: Float::INFINITY,
+            root: RwLock::new(OctNode::default()),
+        }
+    }
+    /// Removes all records and sizes the octree for a scene with
+    /// these bounds.
+    pub fn reset(&mut self, world_bound: &Bounds3f) {
+        let diagonal: Float = world_bound.diagonal().length();
+        // records slightly outside still get found
+        let margin: Vector3f = Vector3f {
+            x: 0.01 as Float * diagonal,
+            y: 0.01 as Float * diagonal,
+            z: 0.01 as Float * diagonal,
+        };
+        self.bounds = Bounds3f {
+            p_min: world_bound.p_min - margin,
+            p_max: world_bound.p_max + margin,
+        };
+        self.min_radius = MIN_RADIUS_FRACTION * diagonal;
+        self.max_radius = MAX_RADIUS_FRACTION * diagonal;
+        self.root = RwLock::new(OctNode::default());
+    }
+    /// Interpolates the irradiance at *p* with normal *n* from the
+    /// records nearby, if there are any.
+    pub fn lookup(&self, p: &Point3f, n: &Normal3f) -> Option<Spectrum> {
+        let cos_max_angle: Float = radians(MAX_ANGLE_DIFFERENCE).cos();
+        let mut sum_wt: Float = 0.0 as Float;
+        let mut e: Spectrum = Spectrum::default();
+        if p.x < self.bounds.p_min.x
+            || p.y < self.bounds.p_min.y
+            || p.z < self.bounds.p_min.z
+            || p.x > self.bounds.p_max.x
+            || p.y > self.bounds.p_max.y
+            || p.z > self.bounds.p_max.z
+        {
+            return None;
+        }
+        let root = self.root.read().unwrap();
+        root.lookup(&self.bounds, p, &mut |record: &IrradianceRecord| {
+            let (wt, e_record) = record.extrapolate(p, n, cos_max_angle);
+            if wt > 0.0 as Float {
+                e += e_record * wt;
+                sum_wt += wt;
+            }
+        });
+        if sum_wt > 0.0 as Float {
+            Some(e / sum_wt)
+        } else {
+            None
+        }
+    }
+    pub fn add(&self, record: &IrradianceRecord) {
+        let r: Vector3f = Vector3f {
+            x: record.max_dist,
+            y: record.max_dist,
+            z: record.max_dist,
+        };
+        let record_bound: Bounds3f = Bounds3f {
+            p_min: record.p - r,
+            p_max: record.p + r,
+        };
+        let mut root = self.root.write().unwrap();
+        root.add(&self.bounds, record, &record_bound, 0);
+    }
+    /// The irradiance at *p* with normal *n*, interpolated from the
+    /// records nearby, or from a new record if there are none. For a
+    /// new record *radiance* gets called for each direction sampled
+    /// and returns the incident radiance and the distance to the
+    /// surface it comes from (infinite if none), *rng* jitters the
+    /// directions.
+    pub fn irradiance<F>(&self, p: &Point3f, n: &Normal3f, rng: &mut Rng, radiance: F) -> Spectrum
+    where
+        F: FnMut(&Vector3f) -> (Spectrum, Float),
+    {
+        INTERPOLATED.inc_denom();
+        if let Some(e) = self.lookup(p, n) {
+            INTERPOLATED.inc_num();
+            return e;
+        }
+        let record: IrradianceRecord = self.compute_record(p, n, rng, radiance);
+        self.add(&record);
+        record.e
+    }
+    /// Samples the hemisphere around *n* to compute a new record (see
+    /// **irradiance()**).
+    pub fn compute_record<F>(
+        &self,
+        p: &Point3f,
+        n: &Normal3f,
+        rng: &mut Rng,
+        mut radiance: F,
+    ) -> IrradianceRecord
+    where
+        F: FnMut(&Vector3f) -> (Spectrum, Float),
+    {
+        RECORDS_COMPUTED.inc();
+        // M strata in theta, N ~ PI M in phi
+        let n_theta: usize = ((self.samples.max(1) as Float / PI).sqrt().round() as usize).max(1);
+        let n_phi: usize = ((self.samples.max(1) as usize + n_theta / 2) / n_theta).max(1);
+        let w: Vector3f = Vector3f::from(*n);
+        let mut u: Vector3f = Vector3f::default();
+        let mut v: Vector3f = Vector3f::default();
+        vec3_coordinate_system(&w, &mut u, &mut v);
+        let dir = |sin_theta: Float, cos_theta: Float, phi: Float| -> Vector3f {
+            u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + w * cos_theta
+        };
+        let perpendicular = |phi: Float| -> Vector3f { u * -phi.sin() + v * phi.cos() };
+        // incident radiance and distances per stratum
+        let mut l: Vec<Spectrum> = Vec::with_capacity(n_theta * n_phi);
+        let mut r: Vec<Float> = Vec::with_capacity(n_theta * n_phi);
+        for j in 0..n_theta {
+            for k in 0..n_phi {
+                let u1: Float = (j as Float + rng.uniform_float()) / n_theta as Float;
+                let u2: Float = (k as Float + rng.uniform_float()) / n_phi as Float;
+                let wi: Vector3f = dir(
+                    u1.sqrt(),
+                    (1.0 as Float - u1).max(0.0 as Float).sqrt(),
+                    2.0 as Float * PI * u2,
+                );
+                let (l_jk, r_jk) = radiance(&wi);
+                l.push(l_jk);
+                r.push(r_jk.max(Float::EPSILON));
+            }
+        }
+        let idx = |j: usize, k: usize| -> usize { j * n_phi + k };
+        let mut e: Spectrum = Spectrum::default();
+        let mut inv_r_sum: Float = 0.0 as Float;
+        for i in 0..l.len() {
+            e += l[i];
+            inv_r_sum += 1.0 as Float / r[i];
+        }
+        e = e * (PI / l.len() as Float);
+        let mut rot_grad: [Spectrum; 3] = [Spectrum::default(); 3];
+        let mut trans_grad: [Spectrum; 3] = [Spectrum::default(); 3];
+        let add_grad = |grad: &mut [Spectrum; 3], d: &Vector3f, s: Spectrum| {
+            for axis in 0..3_u8 {
+                grad[axis as usize] += s * d[axis];
+            }
+        };
+        for k in 0..n_phi {
+            let phi_mid: Float = 2.0 as Float * PI * (k as Float + 0.5 as Float) / n_phi as Float;
+            let phi_min: Float = 2.0 as Float * PI * k as Float / n_phi as Float;
+            // rotational gradient
+            let mut s: Spectrum = Spectrum::default();
+            for j in 0..n_theta {
+                let u_mid: Float = (j as Float + 0.5 as Float) / n_theta as Float;
+                let tan_theta: Float = (u_mid / (1.0 as Float - u_mid)).sqrt();
+                s += l[idx(j, k)] * tan_theta;
+            }
+            add_grad(
+                &mut rot_grad,
+                &perpendicular(phi_mid),
+                s * (PI / (n_theta * n_phi) as Float),
+            );
+            // translational gradient, across the boundaries in theta
+            let mut s: Spectrum = Spectrum::default();
+            for j in 1..n_theta {
+                let u_min: Float = j as Float / n_theta as Float;
+                let sin_theta: Float = u_min.sqrt();
+                let cos2_theta: Float = 1.0 as Float - u_min;
+                let dist: Float = r[idx(j, k)].min(r[idx(j - 1, k)]);
+                s += (l[idx(j, k)] - l[idx(j - 1, k)]) * (sin_theta * cos2_theta / dist);
+            }
+            add_grad(
+                &mut trans_grad,
+                &dir(1.0 as Float, 0.0 as Float, phi_mid),
+                s * (2.0 as Float * PI / n_phi as Float),
+            );
+            // and across the boundaries in phi
+            if n_phi > 1 {
+                let k_prev: usize = (k + n_phi - 1) % n_phi;
+                let mut s: Spectrum = Spectrum::default();
+                for j in 0..n_theta {
+                    let sin_theta_min: Float = (j as Float / n_theta as Float).sqrt();
+                    let sin_theta_max: Float = ((j + 1) as Float / n_theta as Float).sqrt();
+                    let dist: Float = r[idx(j, k)].min(r[idx(j, k_prev)]);
+                    s += (l[idx(j, k)] - l[idx(j, k_prev)])
+                        * ((sin_theta_max - sin_theta_min) / dist);
+                }
+                add_grad(&mut trans_grad, &perpendicular(phi_min), s);
+            }
+        }
+        // the harmonic mean distance, smaller where the irradiance
+        // changes quickly
+        let mut radius: Float = l.len() as Float / inv_r_sum;
+        let mut e_rgb: [Float; 3] = [0.0 as Float; 3];
+        e.to_rgb(&mut e_rgb);
+        for c in 0..3 {
+            let mut grad_rgb: [Float; 3] = [0.0 as Float; 3];
+            let mut grad2: Float = 0.0 as Float;
+            for grad in &trans_grad {
+                grad.to_rgb(&mut grad_rgb);
+                grad2 += grad_rgb[c] * grad_rgb[c];
+            }
+            if grad2 > 0.0 as Float {
+                radius = radius.min(e_rgb[c] / grad2.sqrt());
+            }
+        }
+        radius = radius.max(self.min_radius).min(self.max_radius);
+        IrradianceRecord {
+            p: *p,
+            n: *n,
+            e,
+            max_dist: self.max_error * radius,
+            rot_grad,
+            trans_grad,
+        }
+    }
+}
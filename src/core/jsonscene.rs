@@ -0,0 +1,659 @@
+//! JSON scene description format.
+//!
+//! A JSON scene contains the same statements as a .pbrt file, but
+//! it's much easier (and safer) to generate from other tools than the
+//! positional text syntax. The statements before **WorldBegin** go
+//! into *options*, the ones between **WorldBegin** and **WorldEnd**
+//! into *world*. Statements without arguments are plain strings, all
+//! others are objects with the statement as (only) key. Parameters
+//! use the same "type name" keys as the text format, single values
+//! don't need brackets:
+//!
+//! ```json
+//! {
+//!   "options": [
+//!     { "LookAt": [0, -5, 1,  0, 0, 0.5,  0, 0, 1] },
+//!     { "Camera": { "name": "perspective", "params": { "float fov": 40 } } },
+//!     { "Film": { "name": "image", "params": { "integer xresolution": 400,
+//!                                              "integer yresolution": 300 } } }
+//!   ],
+//!   "world": [
+//!     { "LightSource": { "name": "infinite", "params": { "rgb L": [1, 1, 1] } } },
+//!     "AttributeBegin",
+//!     { "Material": { "name": "matte", "params": { "rgb Kd": [0.8, 0.1, 0.1] } } },
+//!     { "Shape": { "name": "sphere", "params": { "float radius": 0.5 } } },
+//!     "AttributeEnd"
+//!   ]
+//! }
+//! ```
+//!
+//! Scene files with the extension .json are read by
+//! **parser::parse_file()**, and recorded scenes can be written back
+//! with **export_json()**.
+
+// std
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::PathBuf;
+// others
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+// pbrt
+use crate::core::compat::convert_v4_params;
+use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use crate::core::paramset::{ParamSet, ParamSetItem};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::scenecache::{resolve_filename, ApiCall, FILENAME_PARAMS};
+use crate::core::transform::Transform;
+
+/// A complete scene, the statements before and after **WorldBegin**.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct JsonScene {
+    #[serde(default)]
+    pub options: Vec<JsonStatement>,
+    #[serde(default)]
+    pub world: Vec<JsonStatement>,
+}
+
+/// A statement with a name (e.g. the shape type) and a parameter
+/// list, like **Shape "sphere" "float radius" [ 0.5 ]**.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonDirective {
+    #[serde(alias = "type")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+}
+
+/// **Texture "name" "type" "class"** with its parameter list.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonTexture {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tex_type: String,
+    pub class: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+}
+
+/// One statement of the scene file format.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum JsonStatement {
+    Accelerator(JsonDirective),
+    /// "All", "StartTime", or "EndTime"
+    ActiveTransform(String),
+    AreaLightSource(JsonDirective),
+    AttributeBegin,
+    AttributeEnd,
+    Camera(JsonDirective),
+    /// 16 values in the (column major) order of the text format
+    ConcatTransform([Float; 16]),
+    CoordSysTransform(String),
+    Film(JsonDirective),
+    Integrator(JsonDirective),
+    LightSource(JsonDirective),
+    LookAt([Float; 9]),
+    MakeNamedMaterial(JsonDirective),
+    MakeNamedMedium(JsonDirective),
+    Material(JsonDirective),
+    /// inside and outside medium
+    MediumInterface([String; 2]),
+    NamedMaterial(String),
+    ObjectBegin(String),
+    ObjectEnd,
+    ObjectInstance(String),
+    PixelFilter(JsonDirective),
+    ReverseOrientation,
+    Rotate([Float; 4]),
+    Sampler(JsonDirective),
+    Scale([Float; 3]),
+    Shape(JsonDirective),
+    Texture(JsonTexture),
+    /// 16 values in the (column major) order of the text format
+    Transform([Float; 16]),
+    TransformBegin,
+    TransformEnd,
+    Translate([Float; 3]),
+}
+
+impl JsonScene {
+    /// Converts the statements into API calls (including
+    /// **WorldBegin** and **WorldEnd**). Relative filenames of
+    /// spectra are resolved against *search_directory*. Returns the
+    /// errors of all statements which can't be converted (prefixed by
+    /// the position of the statement, e.g. "world[3]").
+    pub fn to_calls(
+        &self,
+        search_directory: Option<&PathBuf>,
+    ) -> std::result::Result<Vec<ApiCall>, Vec<String>> {
+        let mut calls: Vec<ApiCall> = Vec::with_capacity(self.options.len() + self.world.len() + 2);
+        let mut errors: Vec<String> = Vec::new();
+        for (block, statements) in &[("options", &self.options), ("world", &self.world)] {
+            if *block == "world" {
+                calls.push(ApiCall::WorldBegin);
+            }
+            for (i, statement) in statements.iter().enumerate() {
+                match statement.to_call(search_directory) {
+                    Ok(call) => calls.push(call),
+                    Err(err) => errors.push(format!("{}[{}]: {}", block, i, err)),
+                }
+            }
+        }
+        calls.push(ApiCall::WorldEnd);
+        if errors.is_empty() {
+            Ok(calls)
+        } else {
+            Err(errors)
+        }
+    }
+    /// The JSON equivalent of recorded API calls. Relative filenames
+    /// are resolved (see **export::write_pbrt()**).
+    pub fn from_calls(calls: &[ApiCall]) -> JsonScene {
+        let mut scene: JsonScene = JsonScene::default();
+        let mut search_directory: Option<PathBuf> = None;
+        let mut in_world: bool = false;
+        for call in calls {
+            let statement: JsonStatement = match call {
+                ApiCall::ActiveTransformAll => JsonStatement::ActiveTransform(String::from("All")),
+                ApiCall::ActiveTransformEndTime => {
+                    JsonStatement::ActiveTransform(String::from("EndTime"))
+                }
+                ApiCall::ActiveTransformStartTime => {
+                    JsonStatement::ActiveTransform(String::from("StartTime"))
+                }
+                ApiCall::AttributeBegin => JsonStatement::AttributeBegin,
+                ApiCall::AttributeEnd => JsonStatement::AttributeEnd,
+                ApiCall::ConcatTransform(tr) => JsonStatement::ConcatTransform(matrix_values(tr)),
+                ApiCall::LookAt(v) => JsonStatement::LookAt(*v),
+                ApiCall::MediumInterface(inside, outside) => {
+                    JsonStatement::MediumInterface([inside.clone(), outside.clone()])
+                }
+                ApiCall::ObjectEnd => JsonStatement::ObjectEnd,
+                ApiCall::Params(params) => {
+                    match params_to_statement(params, search_directory.as_ref()) {
+                        Some(statement) => statement,
+                        None => continue,
+                    }
+                }
+                ApiCall::ReverseOrientation => JsonStatement::ReverseOrientation,
+                ApiCall::Rotate(v) => JsonStatement::Rotate(*v),
+                ApiCall::Scale(v) => JsonStatement::Scale(*v),
+                ApiCall::SearchDirectory(dir) => {
+                    search_directory = Some(PathBuf::from(dir));
+                    continue;
+                }
+                ApiCall::Transform(tr) => JsonStatement::Transform(matrix_values(tr)),
+                ApiCall::TransformBegin => JsonStatement::TransformBegin,
+                ApiCall::TransformEnd => JsonStatement::TransformEnd,
+                ApiCall::Translate(v) => JsonStatement::Translate(*v),
+                ApiCall::WorldBegin => {
+                    in_world = true;
+                    continue;
+                }
+                ApiCall::WorldEnd => break,
+            };
+            if in_world {
+                scene.world.push(statement);
+            } else {
+                scene.options.push(statement);
+            }
+        }
+        scene
+    }
+}
+
+impl JsonStatement {
+    fn to_call(&self, search_directory: Option<&PathBuf>) -> std::result::Result<ApiCall, String> {
+        let call: ApiCall = match self {
+            JsonStatement::Accelerator(d) => directive("Accelerator", d, search_directory)?,
+            JsonStatement::ActiveTransform(which) => match which.as_str() {
+                "All" => ApiCall::ActiveTransformAll,
+                "EndTime" => ApiCall::ActiveTransformEndTime,
+                "StartTime" => ApiCall::ActiveTransformStartTime,
+                _ => return Err(format!("ActiveTransform {:?} unknown", which)),
+            },
+            JsonStatement::AreaLightSource(d) => directive("AreaLightSource", d, search_directory)?,
+            JsonStatement::AttributeBegin => ApiCall::AttributeBegin,
+            JsonStatement::AttributeEnd => ApiCall::AttributeEnd,
+            JsonStatement::Camera(d) => directive("Camera", d, search_directory)?,
+            JsonStatement::ConcatTransform(m) => ApiCall::ConcatTransform(matrix_transform(m)),
+            JsonStatement::CoordSysTransform(name) => named("CoordSysTransform", name),
+            JsonStatement::Film(d) => directive("Film", d, search_directory)?,
+            JsonStatement::Integrator(d) => directive("Integrator", d, search_directory)?,
+            JsonStatement::LightSource(d) => directive("LightSource", d, search_directory)?,
+            JsonStatement::LookAt(v) => ApiCall::LookAt(*v),
+            JsonStatement::MakeNamedMaterial(d) => {
+                directive("MakeNamedMaterial", d, search_directory)?
+            }
+            JsonStatement::MakeNamedMedium(d) => directive("MakeNamedMedium", d, search_directory)?,
+            JsonStatement::Material(d) => directive("Material", d, search_directory)?,
+            JsonStatement::MediumInterface(names) => {
+                ApiCall::MediumInterface(names[0].clone(), names[1].clone())
+            }
+            JsonStatement::NamedMaterial(name) => named("NamedMaterial", name),
+            JsonStatement::ObjectBegin(name) => named("ObjectBegin", name),
+            JsonStatement::ObjectEnd => ApiCall::ObjectEnd,
+            JsonStatement::ObjectInstance(name) => named("ObjectInstance", name),
+            JsonStatement::PixelFilter(d) => directive("PixelFilter", d, search_directory)?,
+            JsonStatement::ReverseOrientation => ApiCall::ReverseOrientation,
+            JsonStatement::Rotate(v) => ApiCall::Rotate(*v),
+            JsonStatement::Sampler(d) => directive("Sampler", d, search_directory)?,
+            JsonStatement::Scale(v) => ApiCall::Scale(*v),
+            JsonStatement::Shape(d) => directive("Shape", d, search_directory)?,
+            JsonStatement::Texture(t) => {
+                let mut params: ParamSet =
+                    to_param_set("Texture", &t.name, &t.params, search_directory)?;
+                params.tex_type = t.tex_type.clone();
+                params.tex_name = t.class.clone();
+                ApiCall::Params(Box::new(params))
+            }
+            JsonStatement::Transform(m) => ApiCall::Transform(matrix_transform(m)),
+            JsonStatement::TransformBegin => ApiCall::TransformBegin,
+            JsonStatement::TransformEnd => ApiCall::TransformEnd,
+            JsonStatement::Translate(v) => ApiCall::Translate(*v),
+        };
+        Ok(call)
+    }
+}
+
+/// Writes the API calls as JSON scene file.
+pub fn export_json(filename: &str, calls: &[ApiCall]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(filename)?);
+    serde_json::to_writer_pretty(&mut w, &JsonScene::from_calls(calls))?;
+    writeln!(w)?;
+    w.flush()
+}
+
+fn named(key_word: &str, name: &str) -> ApiCall {
+    ApiCall::Params(Box::new(ParamSet {
+        key_word: String::from(key_word),
+        name: String::from(name),
+        ..Default::default()
+    }))
+}
+
+fn directive(
+    key_word: &str,
+    d: &JsonDirective,
+    search_directory: Option<&PathBuf>,
+) -> std::result::Result<ApiCall, String> {
+    let params: ParamSet = to_param_set(key_word, &d.name, &d.params, search_directory)?;
+    Ok(ApiCall::Params(Box::new(params)))
+}
+
+/// See **ConcatTransform** in the parser.
+fn matrix_transform(m: &[Float; 16]) -> Transform {
+    Transform::new(
+        m[0], m[4], m[8], m[12], m[1], m[5], m[9], m[13], m[2], m[6], m[10], m[14], m[3], m[7],
+        m[11], m[15],
+    )
+}
+
+fn matrix_values(tr: &Transform) -> [Float; 16] {
+    let mut values: [Float; 16] = [0.0 as Float; 16];
+    for j in 0..4 {
+        for i in 0..4 {
+            values[j * 4 + i] = tr.m.m[i][j];
+        }
+    }
+    values
+}
+
+/// A single value or an array of values.
+fn as_array(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        _ => vec![value],
+    }
+}
+
+fn floats(key: &str, value: &Value) -> std::result::Result<Vec<Float>, String> {
+    as_array(value)
+        .iter()
+        .map(|v| {
+            v.as_f64()
+                .map(|f| f as Float)
+                .ok_or_else(|| format!("{:?}: number expected, got {}", key, v))
+        })
+        .collect()
+}
+
+fn strings(key: &str, value: &Value) -> std::result::Result<Vec<String>, String> {
+    as_array(value)
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(String::from)
+                .ok_or_else(|| format!("{:?}: string expected, got {}", key, v))
+        })
+        .collect()
+}
+
+fn tuples(key: &str, value: &Value, n: usize) -> std::result::Result<Vec<Float>, String> {
+    let values: Vec<Float> = floats(key, value)?;
+    if values.is_empty() || !values.chunks_exact(n).remainder().is_empty() {
+        return Err(format!("{:?}: multiple of {} numbers expected", key, n));
+    }
+    Ok(values)
+}
+
+fn to_param_set(
+    key_word: &str,
+    name: &str,
+    json_params: &BTreeMap<String, Value>,
+    search_directory: Option<&PathBuf>,
+) -> std::result::Result<ParamSet, String> {
+    let mut params: ParamSet = ParamSet {
+        key_word: String::from(key_word),
+        name: String::from(name),
+        ..Default::default()
+    };
+    for (key, value) in json_params {
+        let words: Vec<&str> = key.split_whitespace().collect();
+        if words.len() != 2 {
+            return Err(format!(
+                "{} {:?}: \"type name\" expected, got {:?}",
+                key_word, name, key
+            ));
+        }
+        let param_name: String = String::from(words[1]);
+        match words[0] {
+            "blackbody" => params.add_blackbody_spectrum(param_name, floats(key, value)?),
+            "bool" => {
+                let b: bool = match value {
+                    Value::Bool(b) => *b,
+                    Value::String(s) if s == "true" || s == "false" => s == "true",
+                    _ => return Err(format!("{:?}: true or false expected, got {}", key, value)),
+                };
+                params.add_bool(param_name, b);
+            }
+            "float" => {
+                let values: Vec<Float> = floats(key, value)?;
+                if values.len() == 1 {
+                    params.add_float(param_name, values[0]);
+                } else {
+                    params.add_floats(param_name, values);
+                }
+            }
+            "integer" => {
+                let values: Vec<i32> = as_array(value)
+                    .iter()
+                    .map(|v| {
+                        v.as_i64()
+                            .map(|i| i as i32)
+                            .ok_or_else(|| format!("{:?}: integer expected, got {}", key, v))
+                    })
+                    .collect::<std::result::Result<Vec<i32>, String>>()?;
+                if values.len() == 1 {
+                    params.add_int(param_name, values[0]);
+                } else {
+                    params.add_ints(param_name, values);
+                }
+            }
+            "normal" | "normal3" => {
+                let values: Vec<Float> = tuples(key, value, 3)?;
+                if values.len() == 3 {
+                    params.add_normal3f(
+                        param_name,
+                        Normal3f {
+                            x: values[0],
+                            y: values[1],
+                            z: values[2],
+                        },
+                    );
+                } else {
+                    params.add_normal3fs(param_name, values);
+                }
+            }
+            "point" | "point3" => {
+                let values: Vec<Float> = tuples(key, value, 3)?;
+                if values.len() == 3 {
+                    params.add_point3f(
+                        param_name,
+                        Point3f {
+                            x: values[0],
+                            y: values[1],
+                            z: values[2],
+                        },
+                    );
+                } else {
+                    params.add_point3fs(param_name, values);
+                }
+            }
+            "point2" => {
+                let values: Vec<Float> = tuples(key, value, 2)?;
+                if values.len() == 2 {
+                    params.add_point2f(
+                        param_name,
+                        Point2f {
+                            x: values[0],
+                            y: values[1],
+                        },
+                    );
+                } else {
+                    params.add_point2fs(param_name, values);
+                }
+            }
+            "rgb" | "color" => {
+                let values: Vec<Float> = floats(key, value)?;
+                if values.len() != 3 {
+                    return Err(format!("{:?}: 3 numbers expected", key));
+                }
+                params.add_rgb_spectrum(param_name, Spectrum::rgb(values[0], values[1], values[2]));
+            }
+            "spectrum" => {
+                // (wavelength, value) pairs or filenames
+                if as_array(value).iter().all(|v| v.is_number()) {
+                    params.add_sampled_spectrum(param_name, floats(key, value)?);
+                } else {
+                    params.add_sampled_spectrum_files(
+                        param_name,
+                        strings(key, value)?,
+                        search_directory,
+                    );
+                }
+            }
+            "string" => params.add_strings(param_name, strings(key, value)?),
+            "texture" => {
+                let values: Vec<String> = strings(key, value)?;
+                if values.len() != 1 {
+                    return Err(format!("{:?}: one texture name expected", key));
+                }
+                params.add_texture(param_name, values[0].clone());
+            }
+            "vector" | "vector3" => {
+                let values: Vec<Float> = tuples(key, value, 3)?;
+                if values.len() == 3 {
+                    params.add_vector3f(
+                        param_name,
+                        Vector3f {
+                            x: values[0],
+                            y: values[1],
+                            z: values[2],
+                        },
+                    );
+                } else {
+                    params.add_vector3fs(param_name, values);
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "{} {:?}: unknown parameter type in {:?}",
+                    key_word, name, key
+                ))
+            }
+        }
+    }
+    // map pbrt-v4 names and parameters (like the parser does)
+    convert_v4_params(&mut params);
+    Ok(params)
+}
+
+fn json_number(f: Float) -> Value {
+    // the shortest decimal representation (0.4 instead of 0.4000000059604645)
+    let shortest: f64 = format!("{}", f).parse().unwrap_or(f64::NAN);
+    match serde_json::Number::from_f64(shortest) {
+        Some(n) => Value::Number(n),
+        None => Value::Null,
+    }
+}
+
+/// Single values are written without brackets.
+fn item_to_value<T>(item: &ParamSetItem<T>, to_values: impl Fn(&T) -> Vec<Value>) -> Value {
+    let mut values: Vec<Value> = Vec::new();
+    for v in &item.values {
+        values.extend(to_values(v));
+    }
+    if values.len() == 1 {
+        values.pop().unwrap()
+    } else {
+        Value::Array(values)
+    }
+}
+
+fn params_to_map(params: &ParamSet, search_directory: Option<&PathBuf>) -> BTreeMap<String, Value> {
+    let mut map: BTreeMap<String, Value> = BTreeMap::new();
+    for item in &params.strings {
+        // the film's filename is an output
+        let resolve: bool =
+            params.key_word != "Film" && FILENAME_PARAMS.contains(&item.name.as_str());
+        let value: Value = item_to_value(item, |s| {
+            if resolve {
+                vec![Value::String(resolve_filename(search_directory, s))]
+            } else {
+                vec![Value::String(s.clone())]
+            }
+        });
+        map.insert(format!("string {}", item.name), value);
+    }
+    for item in &params.bools {
+        map.insert(
+            format!("bool {}", item.name),
+            item_to_value(item, |b| vec![Value::Bool(*b)]),
+        );
+    }
+    for item in &params.ints {
+        map.insert(
+            format!("integer {}", item.name),
+            item_to_value(item, |i| vec![Value::from(*i)]),
+        );
+    }
+    for item in &params.floats {
+        map.insert(
+            format!("float {}", item.name),
+            item_to_value(item, |f| vec![json_number(*f)]),
+        );
+    }
+    for item in &params.point2fs {
+        map.insert(
+            format!("point2 {}", item.name),
+            Value::Array(
+                item.values
+                    .iter()
+                    .flat_map(|p| vec![json_number(p.x), json_number(p.y)])
+                    .collect(),
+            ),
+        );
+    }
+    for item in &params.point3fs {
+        map.insert(
+            format!("point {}", item.name),
+            Value::Array(
+                item.values
+                    .iter()
+                    .flat_map(|p| vec![json_number(p.x), json_number(p.y), json_number(p.z)])
+                    .collect(),
+            ),
+        );
+    }
+    for item in &params.vector3fs {
+        map.insert(
+            format!("vector {}", item.name),
+            Value::Array(
+                item.values
+                    .iter()
+                    .flat_map(|v| vec![json_number(v.x), json_number(v.y), json_number(v.z)])
+                    .collect(),
+            ),
+        );
+    }
+    for item in &params.normals {
+        map.insert(
+            format!("normal {}", item.name),
+            Value::Array(
+                item.values
+                    .iter()
+                    .flat_map(|n| vec![json_number(n.x), json_number(n.y), json_number(n.z)])
+                    .collect(),
+            ),
+        );
+    }
+    for item in &params.spectra {
+        // spectra from files, blackbodies etc. are stored as RGB
+        map.insert(
+            format!("rgb {}", item.name),
+            Value::Array(
+                item.values
+                    .iter()
+                    .flat_map(|s| {
+                        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+                        s.to_rgb(&mut rgb);
+                        rgb.iter().map(|c| json_number(*c)).collect::<Vec<Value>>()
+                    })
+                    .collect(),
+            ),
+        );
+    }
+    for item in &params.textures {
+        map.insert(
+            format!("texture {}", item.name),
+            item_to_value(item, |t| vec![Value::String(t.clone())]),
+        );
+    }
+    map
+}
+
+fn params_to_statement(
+    params: &ParamSet,
+    search_directory: Option<&PathBuf>,
+) -> Option<JsonStatement> {
+    let d = || JsonDirective {
+        name: params.name.clone(),
+        params: params_to_map(params, search_directory),
+    };
+    let statement: JsonStatement = match params.key_word.as_str() {
+        "Accelerator" => JsonStatement::Accelerator(d()),
+        "AreaLightSource" => JsonStatement::AreaLightSource(d()),
+        "Camera" => JsonStatement::Camera(d()),
+        "CoordSysTransform" => JsonStatement::CoordSysTransform(params.name.clone()),
+        "Film" => JsonStatement::Film(d()),
+        "Integrator" => JsonStatement::Integrator(d()),
+        "LightSource" => JsonStatement::LightSource(d()),
+        "MakeNamedMaterial" => JsonStatement::MakeNamedMaterial(d()),
+        "MakeNamedMedium" => JsonStatement::MakeNamedMedium(d()),
+        "Material" => JsonStatement::Material(d()),
+        "NamedMaterial" => JsonStatement::NamedMaterial(params.name.clone()),
+        "ObjectBegin" => JsonStatement::ObjectBegin(params.name.clone()),
+        "ObjectInstance" => JsonStatement::ObjectInstance(params.name.clone()),
+        "PixelFilter" => JsonStatement::PixelFilter(d()),
+        "Sampler" => JsonStatement::Sampler(d()),
+        "Shape" => JsonStatement::Shape(d()),
+        "Texture" => JsonStatement::Texture(JsonTexture {
+            name: params.name.clone(),
+            tex_type: params.tex_type.clone(),
+            class: params.tex_name.clone(),
+            params: params_to_map(params, search_directory),
+        }),
+        _ => {
+            warn!("Unknown directive {:?}. Ignoring it.", params.key_word);
+            return None;
+        }
+    };
+    Some(statement)
+}
@@ -0,0 +1,192 @@
+//! Named asset libraries (the **Import** directive).
+//!
+//! A library is a scene file which only defines named things:
+//! materials (**MakeNamedMaterial**), textures (**Texture**), media
+//! (**MakeNamedMedium**), and objects (**ObjectBegin** ... **ObjectEnd**).
+//! Many scene files can share it, e.g. a studio material library:
+//!
+//! ```text
+//! WorldBegin
+//! Import "lib/studio.pbrt" "string namespace" "studio"
+//! NamedMaterial "studio:brushed_steel"
+//! Shape "sphere"
+//! ObjectInstance "studio:chair"
+//! ```
+//!
+//! With a *namespace* all names defined by the library get the prefix
+//! "namespace:", so libraries can't clash with each other or with the
+//! scene. Inside the library its own names are used without the
+//! prefix, names it doesn't define refer to the importing scene.
+//! Libraries can import other libraries (the prefixes are nested,
+//! e.g. "studio:metals:steel").
+//!
+//! The parser renames the API calls while the library gets read (see
+//! **Namespace::rename()**), so recorded scenes (scene cache, export)
+//! contain the prefixed names and no **Import** statements.
+
+// std
+use std::collections::HashSet;
+// pbrt
+use crate::core::paramset::ParamSet;
+use crate::core::parseerror::ParseError;
+use crate::core::scenecache::ApiCall;
+
+/// String parameters which are names of named materials.
+const MATERIAL_PARAMS: [&str; 2] = ["namedmaterial1", "namedmaterial2"];
+
+/// The names defined by a library which is currently imported.
+#[derive(Debug, Default, Clone)]
+pub struct Namespace {
+    /// "namespace:" (or empty)
+    pub prefix: String,
+    pub materials: HashSet<String>,
+    pub textures: HashSet<String>,
+    pub media: HashSet<String>,
+    pub objects: HashSet<String>,
+    /// between **ObjectBegin** and **ObjectEnd**
+    pub in_object: bool,
+}
+
+impl Namespace {
+    pub fn new(namespace: &str) -> Self {
+        let prefix: String = if namespace.is_empty() {
+            String::new()
+        } else {
+            format!("{}:", namespace)
+        };
+        Namespace {
+            prefix,
+            ..Default::default()
+        }
+    }
+    /// Checks if *call* is allowed in a library and prefixes the names
+    /// it defines or uses (if they are defined by the library).
+    pub fn rename(&mut self, call: ApiCall) -> Result<ApiCall, ParseError> {
+        match call {
+            ApiCall::Params(mut params) => {
+                let definition: bool = match params.key_word.as_str() {
+                    "MakeNamedMaterial" | "Texture" | "MakeNamedMedium" | "ObjectBegin" => {
+                        !self.in_object
+                    }
+                    _ => false,
+                };
+                if definition {
+                    // the textures of a material can come from the library
+                    self.rename_references(&mut params);
+                    let names: &mut HashSet<String> = match params.key_word.as_str() {
+                        "MakeNamedMaterial" => &mut self.materials,
+                        "Texture" => &mut self.textures,
+                        "MakeNamedMedium" => &mut self.media,
+                        _ => {
+                            self.in_object = true;
+                            &mut self.objects
+                        }
+                    };
+                    names.insert(params.name.clone());
+                    params.name = format!("{}{}", self.prefix, params.name);
+                    Ok(ApiCall::Params(params))
+                } else if self.in_object {
+                    match params.key_word.as_str() {
+                        "NamedMaterial" => {
+                            params.name = prefixed(&self.materials, &self.prefix, &params.name)
+                        }
+                        "ObjectInstance" => {
+                            params.name = prefixed(&self.objects, &self.prefix, &params.name)
+                        }
+                        _ => {}
+                    }
+                    self.rename_references(&mut params);
+                    Ok(ApiCall::Params(params))
+                } else {
+                    Err(not_allowed(&params.key_word))
+                }
+            }
+            ApiCall::ObjectEnd if self.in_object => {
+                self.in_object = false;
+                Ok(ApiCall::ObjectEnd)
+            }
+            ApiCall::MediumInterface(inside, outside) if self.in_object => {
+                Ok(ApiCall::MediumInterface(
+                    prefixed(&self.media, &self.prefix, &inside),
+                    prefixed(&self.media, &self.prefix, &outside),
+                ))
+            }
+            ApiCall::WorldBegin | ApiCall::WorldEnd | ApiCall::ObjectEnd => {
+                Err(not_allowed(directive(&call)))
+            }
+            // transforms and attributes of objects
+            call if self.in_object => Ok(call),
+            call => Err(not_allowed(directive(&call))),
+        }
+    }
+    /// Prefixes the textures and named materials a parameter list
+    /// refers to.
+    fn rename_references(&self, params: &mut ParamSet) {
+        for texture in &mut params.textures {
+            for name in &mut texture.values {
+                *name = prefixed(&self.textures, &self.prefix, name);
+            }
+        }
+        for string in &mut params.strings {
+            if MATERIAL_PARAMS.contains(&string.name.as_str()) {
+                for name in &mut string.values {
+                    *name = prefixed(&self.materials, &self.prefix, name);
+                }
+            }
+        }
+    }
+}
+
+/// Prefixes *name* if it is one of *names*.
+fn prefixed(names: &HashSet<String>, prefix: &str, name: &str) -> String {
+    if names.contains(name) {
+        format!("{}{}", prefix, name)
+    } else {
+        String::from(name)
+    }
+}
+
+/// Renames a call of an imported library for all libraries which are
+/// currently imported (innermost first).
+pub fn rename_imported(imports: &mut [Namespace], call: ApiCall) -> Result<ApiCall, ParseError> {
+    let mut call: ApiCall = call;
+    for namespace in imports.iter_mut().rev() {
+        call = namespace.rename(call)?;
+    }
+    Ok(call)
+}
+
+fn directive(call: &ApiCall) -> &str {
+    match call {
+        ApiCall::ActiveTransformAll
+        | ApiCall::ActiveTransformEndTime
+        | ApiCall::ActiveTransformStartTime => "ActiveTransform",
+        ApiCall::AttributeBegin => "AttributeBegin",
+        ApiCall::AttributeEnd => "AttributeEnd",
+        ApiCall::ConcatTransform(_) => "ConcatTransform",
+        ApiCall::LookAt(_) => "LookAt",
+        ApiCall::MediumInterface(_, _) => "MediumInterface",
+        ApiCall::ObjectEnd => "ObjectEnd",
+        ApiCall::Params(params) => &params.key_word,
+        ApiCall::ReverseOrientation => "ReverseOrientation",
+        ApiCall::Rotate(_) => "Rotate",
+        ApiCall::Scale(_) => "Scale",
+        ApiCall::SearchDirectory(_) => "SearchDirectory",
+        ApiCall::Transform(_) => "Transform",
+        ApiCall::TransformBegin => "TransformBegin",
+        ApiCall::TransformEnd => "TransformEnd",
+        ApiCall::Translate(_) => "Translate",
+        ApiCall::WorldBegin => "WorldBegin",
+        ApiCall::WorldEnd => "WorldEnd",
+    }
+}
+
+fn not_allowed(directive: &str) -> ParseError {
+    ParseError::new(
+        directive,
+        format!(
+            "{} is not allowed in an imported library (only named materials, textures, media, and objects)",
+            directive
+        ),
+    )
+}
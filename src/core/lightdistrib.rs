@@ -4,19 +4,36 @@
 use atomic::{Atomic, Ordering};
 use std;
 use std::sync::{Arc, RwLock};
+// others
+use log::warn;
 // pbrt
+use crate::core::geometry::{
+    bnd3_union_bnd3, bnd3_union_pnt3, nrm_abs_dot_vec3, pnt3_distance_squared, vec3_cross_vec3,
+    vec3_dot_vec3,
+};
 use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Point3i, Vector3f};
 use crate::core::integrator::compute_light_power_distribution;
 use crate::core::interaction::InteractionCommon;
-use crate::core::light::VisibilityTester;
+use crate::core::light::{Light, VisibilityTester};
 use crate::core::lowdiscrepancy::radical_inverse;
-use crate::core::pbrt::clamp_t;
+use crate::core::pbrt::consts::PI;
+use crate::core::pbrt::{clamp_t, degrees};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::rng::FLOAT_ONE_MINUS_EPSILON;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::shape::Shape;
+use crate::core::stats::{StatCounter, StatIntDistribution, StatRatio};
+use crate::core::transform::Transform;
 
 // see lightdistrib.h
 
+static LOOKUPS_PER_DISTRIBUTION: StatRatio =
+    StatRatio::new("SpatialLightDistribution/Lookups per distribution");
+static PROBES_PER_LOOKUP: StatIntDistribution =
+    StatIntDistribution::new("SpatialLightDistribution/Hash probes per lookup");
+static BVH_LIGHTS: StatCounter = StatCounter::new("LightBvh/Lights in the tree");
+
 /// LightDistribution defines a general interface for classes that
 /// provide probability distributions for sampling light sources at a
 /// given point in space.
@@ -24,6 +41,7 @@ pub enum LightDistribution {
     Uniform(UniformLightDistribution),
     Power(PowerLightDistribution),
     Spatial(SpatialLightDistribution),
+    Bvh(LightBvh),
 }
 
 impl LightDistribution {
@@ -32,6 +50,22 @@ impl LightDistribution {
             LightDistribution::Uniform(distribution) => distribution.lookup(p),
             LightDistribution::Power(distribution) => distribution.lookup(p),
             LightDistribution::Spatial(distribution) => distribution.lookup(p),
+            LightDistribution::Bvh(distribution) => distribution.lookup(p),
+        }
+    }
+    /// Picks a light for the point *p* with the surface normal *n*
+    /// (zero in media), returns its index and probability (or
+    /// **None** if no light gets sampled).
+    pub fn sample(&self, p: &Point3f, n: &Normal3f, u: Float) -> Option<(usize, Float)> {
+        if let LightDistribution::Bvh(distribution) = self {
+            return distribution.sample(p, n, u);
+        }
+        let mut pdf: Float = 0.0 as Float;
+        let light_num: usize = self.lookup(p).sample_discrete(u, Some(&mut pdf));
+        if pdf == 0.0 as Float {
+            None
+        } else {
+            Some((light_num, pdf))
         }
     }
 }
@@ -114,7 +148,8 @@ impl PowerLightDistribution {
 /// over the scene bounds and a sampling distribution is computed as
 /// needed for each voxel.
 pub struct SpatialLightDistribution {
-    pub scene: Scene,
+    pub lights: Vec<Arc<Light>>,
+    pub world_bound: Bounds3f,
     pub n_voxels: [i32; 3],
     hash_table: Arc<Vec<HashEntry>>,
     pub hash_table_size: usize,
@@ -152,7 +187,8 @@ impl SpatialLightDistribution {
             hash_table.push(hash_entry);
         }
         SpatialLightDistribution {
-            scene: scene.clone(),
+            lights: scene.lights.clone(),
+            world_bound: b,
             n_voxels,
             hash_table: Arc::new(hash_table),
             hash_table_size,
@@ -174,8 +210,8 @@ impl SpatialLightDistribution {
             z: (pi[2] + 1) as Float / self.n_voxels[2] as Float,
         };
         let voxel_bounds: Bounds3f = Bounds3f {
-            p_min: self.scene.world_bound().lerp(&p0),
-            p_max: self.scene.world_bound().lerp(&p1),
+            p_min: self.world_bound.lerp(&p0),
+            p_max: self.world_bound.lerp(&p1),
         };
         // Compute the sampling distribution. Sample a number of
         // points inside voxelBounds using a 3D Halton sequence; at
@@ -185,7 +221,7 @@ impl SpatialLightDistribution {
         // source) as an approximation to how much the light is likely
         // to contribute to illumination in the voxel.
         let n_samples: usize = 128;
-        let mut light_contrib: Vec<Float> = vec![0.0 as Float; self.scene.lights.len()];
+        let mut light_contrib: Vec<Float> = vec![0.0 as Float; self.lights.len()];
         for i in 0..n_samples {
             let po: Point3f = voxel_bounds.lerp(&Point3f {
                 x: radical_inverse(0, i as u64),
@@ -211,16 +247,11 @@ impl SpatialLightDistribution {
                 x: radical_inverse(3, i as u64),
                 y: radical_inverse(4, i as u64),
             };
-            for (j, item) in light_contrib
-                .iter_mut()
-                .enumerate()
-                .take(self.scene.lights.len())
-            {
+            for (j, item) in light_contrib.iter_mut().enumerate().take(self.lights.len()) {
                 let mut pdf: Float = 0.0 as Float;
                 let mut wi: Vector3f = Vector3f::default();
                 let mut vis: VisibilityTester = VisibilityTester::default();
-                let li: Spectrum =
-                    self.scene.lights[j].sample_li(&intr, u, &mut wi, &mut pdf, &mut vis);
+                let li: Spectrum = self.lights[j].sample_li(&intr, u, &mut wi, &mut pdf, &mut vis);
                 if pdf > 0.0 as Float {
                     // TODO: look at tracing shadow rays / computing
                     // beam transmittance. Probably shouldn't give
@@ -262,11 +293,11 @@ impl SpatialLightDistribution {
     /// point.
     pub fn lookup(&self, p: &Point3f) -> Arc<Distribution1D> {
         // TODO: ProfilePhase _(Prof::LightDistribLookup);
-        // TODO: ++nLookups;
+        LOOKUPS_PER_DISTRIBUTION.add_num(1);
 
         // first, compute integer voxel coordinates for the given
         // point |p| with respect to the overall voxel grid.
-        let offset: Vector3f = self.scene.world_bound().offset(&p); // offset in [0,1].
+        let offset: Vector3f = self.world_bound.offset(p); // offset in [0,1].
         let mut pi: Point3i = Point3i::default();
         for i in 0..3 {
             // the clamp should almost never be necessary, but is
@@ -303,9 +334,9 @@ impl SpatialLightDistribution {
         // entry is already used for another value; step stores the
         // square root of the probe step.
         let mut step: u64 = 1;
-        // TODO: int nProbes = 0;
+        let mut n_probes: u64 = 0;
         loop {
-            // TODO: ++nProbes;
+            n_probes += 1;
             let entry: &HashEntry = &self.hash_table[hash as usize];
             // does the hash table entry at offset |hash| match the current point?
             let entry_packed_pos: u64 = entry.packed_pos.load(Ordering::Acquire);
@@ -315,6 +346,7 @@ impl SpatialLightDistribution {
                 let option: &Option<Arc<Distribution1D>> = &*entry.distribution.read().unwrap();
                 if let Some(ref dist) = *option {
                     // We have a valid sampling distribution.
+                    PROBES_PER_LOOKUP.report_value(n_probes);
                     return dist.clone();
                 }
             } else if entry_packed_pos != INVALID_PACKED_POS {
@@ -344,6 +376,8 @@ impl SpatialLightDistribution {
                     let box_dist: Arc<Distribution1D> = Arc::new(dist);
                     let mut distribution = entry.distribution.write().unwrap();
                     *distribution = Some(box_dist.clone());
+                    LOOKUPS_PER_DISTRIBUTION.add_denom(1);
+                    PROBES_PER_LOOKUP.report_value(n_probes);
                     return box_dist;
                 }
             }
@@ -351,16 +385,513 @@ impl SpatialLightDistribution {
     }
 }
 
+/// A cone of directions around *w* (normalized) with the half angle
+/// *acos(cos_theta)*.
+#[derive(Debug, Copy, Clone)]
+struct DirectionCone {
+    w: Vector3f,
+    cos_theta: Float,
+}
+
+impl DirectionCone {
+    fn entire_sphere() -> Self {
+        DirectionCone {
+            w: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            cos_theta: -1.0 as Float,
+        }
+    }
+    /// The smallest cone containing both cones.
+    fn union(a: &DirectionCone, b: &DirectionCone) -> DirectionCone {
+        // handle the cases where one cone is inside the other
+        let theta_a: Float = clamp_t(a.cos_theta, -1.0, 1.0).acos();
+        let theta_b: Float = clamp_t(b.cos_theta, -1.0, 1.0).acos();
+        let theta_d: Float = clamp_t(vec3_dot_vec3(&a.w, &b.w), -1.0, 1.0).acos();
+        if (theta_d + theta_b).min(PI) <= theta_a {
+            return *a;
+        }
+        if (theta_d + theta_a).min(PI) <= theta_b {
+            return *b;
+        }
+        // compute the spread angle of the merged cone
+        let theta_o: Float = (theta_a + theta_d + theta_b) / 2.0 as Float;
+        if theta_o >= PI {
+            return DirectionCone::entire_sphere();
+        }
+        // rotate the axis of _a_ towards the one of _b_
+        let theta_r: Float = theta_o - theta_a;
+        let wr: Vector3f = vec3_cross_vec3(&a.w, &b.w);
+        if wr.length_squared() == 0.0 as Float {
+            return DirectionCone::entire_sphere();
+        }
+        DirectionCone {
+            w: Transform::rotate(degrees(theta_r), &wr).transform_vector(&a.w),
+            cos_theta: theta_o.cos(),
+        }
+    }
+}
+
+/// Bounds the positions and emission of one or more lights: the
+/// total power *phi* leaves the box *bounds* in directions within
+/// *acos(cos_theta_o)* of *w*, spreading at most *acos(cos_theta_e)*
+/// further.
+#[derive(Debug, Copy, Clone)]
+struct LightBounds {
+    bounds: Bounds3f,
+    w: Vector3f,
+    phi: Float,
+    cos_theta_o: Float,
+    cos_theta_e: Float,
+    two_sided: bool,
+}
+
+impl LightBounds {
+    /// Returns **None** for lights without a position (distant and
+    /// infinite area lights).
+    fn for_light(light: &Light) -> Option<LightBounds> {
+        let omni = |p: Point3f, phi: Float| LightBounds {
+            bounds: Bounds3f::new(p, p),
+            w: DirectionCone::entire_sphere().w,
+            phi,
+            cos_theta_o: -1.0 as Float,
+            cos_theta_e: 0.0 as Float,
+            two_sided: false,
+        };
+        match light {
+            Light::Distant(_) | Light::InfiniteArea(_) => None,
+            Light::Point(point) => Some(omni(point.p_light, light.power().y())),
+            Light::GonioPhotometric(gonio) => Some(omni(gonio.p_light, light.power().y())),
+            Light::Projection(projection) => Some(omni(projection.p_light, light.power().y())),
+            Light::Spot(spot) => {
+                let w: Vector3f = spot
+                    .light_to_world
+                    .transform_vector(&Vector3f {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    })
+                    .normalize();
+                let theta_o: Float = clamp_t(spot.cos_falloff_start, -1.0, 1.0).acos();
+                let theta_e: Float = clamp_t(spot.cos_total_width, -1.0, 1.0).acos() - theta_o;
+                Some(LightBounds {
+                    bounds: Bounds3f::new(spot.p_light, spot.p_light),
+                    w,
+                    phi: 4.0 as Float * PI * spot.i.y(),
+                    cos_theta_o: spot.cos_falloff_start,
+                    cos_theta_e: theta_e.max(0.0 as Float).cos(),
+                    two_sided: false,
+                })
+            }
+            Light::DiffuseArea(area) => {
+                // flat shapes emit around their normal
+                let (w, cos_theta_o) = match *area.shape {
+                    Shape::Trngl(_) | Shape::Dsk(_) => {
+                        let mut pdf: Float = 0.0;
+                        let it: InteractionCommon =
+                            area.shape.sample(Point2f { x: 0.5, y: 0.5 }, &mut pdf);
+                        (Vector3f::from(it.n).normalize(), 1.0 as Float)
+                    }
+                    _ => (DirectionCone::entire_sphere().w, -1.0 as Float),
+                };
+                Some(LightBounds {
+                    bounds: area.shape.world_bound(),
+                    w,
+                    phi: light.power().y(),
+                    cos_theta_o,
+                    cos_theta_e: 0.0 as Float,
+                    two_sided: area.two_sided,
+                })
+            }
+        }
+    }
+    fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.phi == 0.0 as Float {
+            return *b;
+        }
+        if b.phi == 0.0 as Float {
+            return *a;
+        }
+        let cone: DirectionCone = DirectionCone::union(
+            &DirectionCone {
+                w: a.w,
+                cos_theta: a.cos_theta_o,
+            },
+            &DirectionCone {
+                w: b.w,
+                cos_theta: b.cos_theta_o,
+            },
+        );
+        LightBounds {
+            bounds: bnd3_union_bnd3(&a.bounds, &b.bounds),
+            w: cone.w,
+            phi: a.phi + b.phi,
+            cos_theta_o: cone.cos_theta,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+    fn centroid(&self) -> Point3f {
+        (self.bounds.p_min + self.bounds.p_max) / 2.0 as Float
+    }
+    /// A conservative estimate of the light arriving at *p* (with
+    /// the surface normal *n*, or a zero normal in media).
+    fn importance(&self, p: &Point3f, n: &Normal3f) -> Float {
+        // clamp the squared distance to avoid huge values close by
+        let pc: Point3f = self.centroid();
+        let d2: Float = pnt3_distance_squared(p, &pc).max(self.bounds.diagonal().length() / 2.0);
+        // cone of directions from _p_ to the bounds
+        let mut center: Point3f = Point3f::default();
+        let mut radius: Float = 0.0;
+        Bounds3f::bounding_sphere(&self.bounds, &mut center, &mut radius);
+        let dc2: Float = pnt3_distance_squared(p, &center);
+        if dc2 < radius * radius {
+            // _p_ is inside, all angles below are clamped to zero
+            return self.phi / d2;
+        }
+        let sin2_theta_b: Float = radius * radius / dc2;
+        let cos_theta_b: Float = (1.0 as Float - sin2_theta_b).max(0.0).sqrt();
+        let sin_theta_b: Float = sin2_theta_b.max(0.0).sqrt();
+        // cosine and sine of the difference of two angles, clamped
+        // to zero (a > b)
+        let cos_sub_clamped = |sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float| {
+            if cos_a > cos_b {
+                1.0 as Float
+            } else {
+                cos_a * cos_b + sin_a * sin_b
+            }
+        };
+        let sin_sub_clamped = |sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float| {
+            if cos_a > cos_b {
+                0.0 as Float
+            } else {
+                sin_a * cos_b - cos_a * sin_b
+            }
+        };
+        // angle between the emission axis and the direction to _p_
+        let wi: Vector3f = (*p - pc).normalize();
+        let mut cos_theta_w: Float = vec3_dot_vec3(&self.w, &wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w: Float = (1.0 as Float - cos_theta_w * cos_theta_w).max(0.0).sqrt();
+        let sin_theta_o: Float = (1.0 as Float - self.cos_theta_o * self.cos_theta_o)
+            .max(0.0)
+            .sqrt();
+        let cos_theta_x: Float =
+            cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x: Float =
+            sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p: Float =
+            cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0 as Float;
+        }
+        let mut importance: Float = self.phi * cos_theta_p / d2;
+        // account for the incident angle at _p_
+        if n.x != 0.0 as Float || n.y != 0.0 as Float || n.z != 0.0 as Float {
+            let cos_theta_i: Float = nrm_abs_dot_vec3(n, &wi);
+            let sin_theta_i: Float = (1.0 as Float - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0 as Float)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct LightBvhNode {
+    light_bounds: LightBounds,
+    /// the second child of interior nodes (the first one follows the
+    /// node), the light index of leaves
+    child_or_light: usize,
+    is_leaf: bool,
+}
+
+/// Hierarchical light sampling: a bounding volume hierarchy over the
+/// lights with a position, built with the surface area orientation
+/// heuristic (SAOH), is traversed from the root by picking one of
+/// two children with a probability proportional to its estimated
+/// contribution to the shaded point. Scales to many thousands of
+/// lights and (unlike the *spatial* distribution) takes the surface
+/// normal into account. Lights without a position (distant and
+/// infinite area lights) are sampled uniformly instead of the tree.
+pub struct LightBvh {
+    infinite_lights: Vec<usize>,
+    nodes: Vec<LightBvhNode>,
+    /// for light sampling without a point (e.g. BDPT light paths)
+    power: Arc<Distribution1D>,
+}
+
+impl LightBvh {
+    pub fn new(scene: &Scene) -> Self {
+        let mut infinite_lights: Vec<usize> = Vec::new();
+        let mut bvh_lights: Vec<(usize, LightBounds)> = Vec::new();
+        for (index, light) in scene.lights.iter().enumerate() {
+            match LightBounds::for_light(light) {
+                None => infinite_lights.push(index),
+                Some(light_bounds) => {
+                    // lights without power never get sampled
+                    if light_bounds.phi > 0.0 as Float {
+                        bvh_lights.push((index, light_bounds));
+                    }
+                }
+            }
+        }
+        let mut nodes: Vec<LightBvhNode> = Vec::with_capacity(2 * bvh_lights.len());
+        if !bvh_lights.is_empty() {
+            let n_lights: usize = bvh_lights.len();
+            LightBvh::build(&mut bvh_lights, 0, n_lights, &mut nodes);
+        }
+        BVH_LIGHTS.add(bvh_lights.len() as u64);
+        let power: Arc<Distribution1D> =
+            PowerLightDistribution::new(scene).lookup(&Point3f::default());
+        LightBvh {
+            infinite_lights,
+            nodes,
+            power,
+        }
+    }
+    /// Appends the subtree of the lights in *start..end* to *nodes*,
+    /// returns the bounds of its root.
+    fn build(
+        bvh_lights: &mut [(usize, LightBounds)],
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<LightBvhNode>,
+    ) -> LightBounds {
+        if end - start == 1 {
+            let (index, light_bounds) = bvh_lights[start];
+            nodes.push(LightBvhNode {
+                light_bounds,
+                child_or_light: index,
+                is_leaf: true,
+            });
+            return light_bounds;
+        }
+        // bounds of the lights and of their centroids
+        let mut bounds: Bounds3f = Bounds3f::default();
+        let mut centroid_bounds: Bounds3f = Bounds3f::default();
+        for (_index, light_bounds) in bvh_lights[start..end].iter() {
+            bounds = bnd3_union_bnd3(&bounds, &light_bounds.bounds);
+            centroid_bounds = bnd3_union_pnt3(&centroid_bounds, &light_bounds.centroid());
+        }
+        // find the cheapest split between buckets along each axis
+        let mut min_cost: Float = Float::INFINITY;
+        let mut min_cost_split: Option<(u8, usize)> = None;
+        for dim in 0..3_u8 {
+            if centroid_bounds.p_max[dim] == centroid_bounds.p_min[dim] {
+                continue;
+            }
+            let mut buckets: Vec<Option<LightBounds>> = vec![None; N_BUCKETS];
+            for (_index, light_bounds) in bvh_lights[start..end].iter() {
+                let b: usize = bucket(&centroid_bounds, &light_bounds.centroid(), dim);
+                buckets[b] = Some(match buckets[b] {
+                    Some(ref bucket_bounds) => LightBounds::union(bucket_bounds, light_bounds),
+                    None => *light_bounds,
+                });
+            }
+            for split in 0..N_BUCKETS - 1 {
+                let cost: Float = split_cost(&buckets[..=split], &bounds, dim)
+                    + split_cost(&buckets[split + 1..], &bounds, dim);
+                if cost > 0.0 as Float && cost < min_cost {
+                    min_cost = cost;
+                    min_cost_split = Some((dim, split));
+                }
+            }
+        }
+        // partition the lights (or split them in half)
+        let mut mid: usize = (start + end) / 2;
+        if let Some((dim, split)) = min_cost_split {
+            let mut first: usize = start;
+            for i in start..end {
+                if bucket(&centroid_bounds, &bvh_lights[i].1.centroid(), dim) <= split {
+                    bvh_lights.swap(i, first);
+                    first += 1;
+                }
+            }
+            if first != start && first != end {
+                mid = first;
+            }
+        }
+        // the first child follows the node, the second one after it
+        let node_index: usize = nodes.len();
+        nodes.push(LightBvhNode {
+            light_bounds: bvh_lights[start].1,
+            child_or_light: 0,
+            is_leaf: false,
+        });
+        let first: LightBounds = LightBvh::build(bvh_lights, start, mid, nodes);
+        let second_index: usize = nodes.len();
+        let second: LightBounds = LightBvh::build(bvh_lights, mid, end, nodes);
+        let light_bounds: LightBounds = LightBounds::union(&first, &second);
+        nodes[node_index].light_bounds = light_bounds;
+        nodes[node_index].child_or_light = second_index;
+        light_bounds
+    }
+    /// Picks a light for the point *p* with the surface normal *n*
+    /// (zero in media), returns its index and probability.
+    pub fn sample(&self, p: &Point3f, n: &Normal3f, u: Float) -> Option<(usize, Float)> {
+        // infinite lights and the tree share a uniform probability
+        let n_infinite: usize = self.infinite_lights.len();
+        let p_infinite: Float =
+            n_infinite as Float / (n_infinite + if self.nodes.is_empty() { 0 } else { 1 }) as Float;
+        if u < p_infinite {
+            let index: usize =
+                ((u / p_infinite * n_infinite as Float) as usize).min(n_infinite - 1);
+            return Some((
+                self.infinite_lights[index],
+                p_infinite / n_infinite as Float,
+            ));
+        }
+        if self.nodes.is_empty() {
+            return None;
+        }
+        // traverse the tree, picking children by their importance
+        let mut u: Float =
+            ((u - p_infinite) / (1.0 as Float - p_infinite)).min(FLOAT_ONE_MINUS_EPSILON);
+        let mut node_index: usize = 0;
+        let mut pmf: Float = 1.0 as Float - p_infinite;
+        loop {
+            let node: &LightBvhNode = &self.nodes[node_index];
+            if node.is_leaf {
+                if node_index > 0 || node.light_bounds.importance(p, n) > 0.0 as Float {
+                    return Some((node.child_or_light, pmf));
+                }
+                return None;
+            }
+            let ci: [Float; 2] = [
+                self.nodes[node_index + 1].light_bounds.importance(p, n),
+                self.nodes[node.child_or_light]
+                    .light_bounds
+                    .importance(p, n),
+            ];
+            if ci[0] == 0.0 as Float && ci[1] == 0.0 as Float {
+                return None;
+            }
+            let p0: Float = ci[0] / (ci[0] + ci[1]);
+            if u < p0 {
+                u = (u / p0).min(FLOAT_ONE_MINUS_EPSILON);
+                pmf *= p0;
+                node_index += 1;
+            } else {
+                u = ((u - p0) / (1.0 as Float - p0)).min(FLOAT_ONE_MINUS_EPSILON);
+                pmf *= 1.0 as Float - p0;
+                node_index = node.child_or_light;
+            }
+        }
+    }
+
+    // LightDistribution
+
+    /// The tree needs a point and a normal, light paths (e.g. of
+    /// BDPT) sample the lights by their power instead.
+    pub fn lookup(&self, _p: &Point3f) -> Arc<Distribution1D> {
+        self.power.clone()
+    }
+}
+
+const N_BUCKETS: usize = 12;
+
+/// The bucket of *centroid* along *dim* within the *centroid_bounds*.
+fn bucket(centroid_bounds: &Bounds3f, centroid: &Point3f, dim: u8) -> usize {
+    let b: usize = (N_BUCKETS as Float * centroid_bounds.offset(centroid)[dim]) as usize;
+    b.min(N_BUCKETS - 1)
+}
+
+/// The SAOH cost of the lights in *buckets* (within a node with the
+/// given *bounds*, split along *dim*).
+fn split_cost(buckets: &[Option<LightBounds>], bounds: &Bounds3f, dim: u8) -> Float {
+    let mut merged: Option<LightBounds> = None;
+    for bucket_bounds in buckets.iter().flatten() {
+        merged = Some(match merged {
+            Some(ref light_bounds) => LightBounds::union(light_bounds, bucket_bounds),
+            None => *bucket_bounds,
+        });
+    }
+    let b: LightBounds = match merged {
+        Some(b) => b,
+        None => return 0.0 as Float,
+    };
+    // solid angle measure of the emission (plus spread)
+    let theta_o: Float = clamp_t(b.cos_theta_o, -1.0, 1.0).acos();
+    let theta_e: Float = clamp_t(b.cos_theta_e, -1.0, 1.0).acos();
+    let theta_w: Float = (theta_o + theta_e).min(PI);
+    let sin_theta_o: Float = (1.0 as Float - b.cos_theta_o * b.cos_theta_o)
+        .max(0.0)
+        .sqrt();
+    let m_omega: Float = 2.0 as Float * PI * (1.0 as Float - b.cos_theta_o)
+        + PI / 2.0 as Float
+            * (2.0 as Float * theta_w * sin_theta_o
+                - (theta_o - 2.0 as Float * theta_w).cos()
+                - 2.0 as Float * theta_o * sin_theta_o
+                + b.cos_theta_o);
+    // penalize thin boxes split along their short side
+    let diagonal: Vector3f = bounds.diagonal();
+    let kr: Float = diagonal.x.max(diagonal.y).max(diagonal.z) / diagonal[dim];
+    b.phi * m_omega * kr * b.bounds.surface_area()
+}
+
 // see lightdistrib.cpp
 
 const INVALID_PACKED_POS: u64 = 0xffff_ffff_ffff_ffff;
 
+/// Identifies the scene a cached light distribution was created for.
+#[derive(PartialEq)]
+struct CacheKey {
+    name: String,
+    light_fingerprint: u64,
+    n_lights: usize,
+    world_bound: [Float; 6],
+}
+
+/// The light distribution created last for a scene with a known light
+/// fingerprint. The scenes of one render session (e.g. the frames of
+/// an animation) share a cache, clones refer to the same one.
+#[derive(Clone, Default)]
+pub struct LightDistributionCache {
+    last: Arc<RwLock<Option<(CacheKey, Arc<LightDistribution>)>>>,
+}
+
 /// Decides based on the name and the number of scene lights which
-/// light distribution to return.
+/// light distribution to return. If the scene has the same lights
+/// (see **Scene::light_fingerprint**) and bounds as the scene of the
+/// previous call with the same cache (see
+/// **Scene::light_distributions**), e.g. for the next frame of an
+/// animation, the previous distribution gets reused (including all
+/// the voxels a *spatial* distribution has computed so far).
 pub fn create_light_sample_distribution(
     name: String,
     scene: &Scene,
 ) -> Option<Arc<LightDistribution>> {
+    if scene.light_fingerprint == 0 {
+        return new_light_sample_distribution(name, scene);
+    }
+    let cache: &RwLock<Option<(CacheKey, Arc<LightDistribution>)>> =
+        &scene.light_distributions.last;
+    let b: Bounds3f = scene.world_bound();
+    let key: CacheKey = CacheKey {
+        name,
+        light_fingerprint: scene.light_fingerprint,
+        n_lights: scene.lights.len(),
+        world_bound: [
+            b.p_min.x, b.p_min.y, b.p_min.z, b.p_max.x, b.p_max.y, b.p_max.z,
+        ],
+    };
+    if let Some((last_key, distribution)) = cache.read().unwrap().as_ref() {
+        if *last_key == key {
+            return Some(distribution.clone());
+        }
+    }
+    let some_distribution = new_light_sample_distribution(key.name.clone(), scene);
+    *cache.write().unwrap() = some_distribution
+        .as_ref()
+        .map(|distribution| (key, distribution.clone()));
+    some_distribution
+}
+
+fn new_light_sample_distribution(name: String, scene: &Scene) -> Option<Arc<LightDistribution>> {
     if name == "uniform" || scene.lights.len() == 1 {
         Some(Arc::new(LightDistribution::Uniform(
             UniformLightDistribution::new(scene),
@@ -369,12 +900,14 @@ pub fn create_light_sample_distribution(
         Some(Arc::new(LightDistribution::Power(
             PowerLightDistribution::new(scene),
         )))
+    } else if name == "bvh" {
+        Some(Arc::new(LightDistribution::Bvh(LightBvh::new(scene))))
     } else if name == "spatial" {
         Some(Arc::new(LightDistribution::Spatial(
             SpatialLightDistribution::new(scene, 64),
         )))
     } else {
-        println!(
+        warn!(
             "Light sample distribution type \"{:?}\" unknown. Using \"spatial\".",
             name
         );
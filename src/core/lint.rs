@@ -0,0 +1,698 @@
+//! Checks a scene description for problems without rendering it.
+//!
+//! **lint_scene()** walks through the recorded API calls of a scene
+//! (see **parser::Recorder**) and reports everything which is most
+//! likely a mistake, all at once and each with the location of the
+//! statement:
+//!
+//! * textures, named materials, media, object instances, or
+//!   coordinate systems which are used, but not defined (before)
+//! * missing files (e.g. PLY meshes, image maps, or lens files)
+//! * degenerate transformations (scaled by zero, or with NaN or
+//!   infinite values) and **LookAt** statements without a viewing
+//!   direction
+//! * area lights on shapes without any area
+//! * parameter names which no type of the directive knows (e.g. a
+//!   typo like *"radious"*)
+//!
+//! Nothing gets created, so missing files don't stop the checks.
+
+// std
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+// pbrt
+use crate::core::geometry::{vec3_cross_vec3, Point3f, Vector3f};
+use crate::core::paramset::ParamSet;
+use crate::core::parseerror::Location;
+use crate::core::pbrt::Float;
+use crate::core::scenecache::{resolve_filename, ApiCall, FILENAME_PARAMS};
+use crate::core::transform::Transform;
+
+/// parameters of all materials (shapes can override them, too)
+const MATERIAL_PARAMS: &[&str] = &[
+    "Kd",
+    "Kr",
+    "Ks",
+    "Kt",
+    "abbe",
+    "alpha",
+    "amount",
+    "anisotropic",
+    "bandwidth",
+    "beta_m",
+    "beta_n",
+    "bsdffile",
+    "bumpmap",
+    "cauchyb",
+    "clearcoat",
+    "clearcoatgloss",
+    "color",
+    "difftrans",
+    "efficiency",
+    "emission",
+    "eta",
+    "eumelanin",
+    "excitation",
+    "flatness",
+    "g",
+    "index",
+    "k",
+    "metallic",
+    "method",
+    "name",
+    "namedmaterial1",
+    "namedmaterial2",
+    "opacity",
+    "pheomelanin",
+    "reflect",
+    "remaproughness",
+    "reradiation",
+    "roughness",
+    "roughnessmapping",
+    "scale",
+    "scatterdistance",
+    "sheen",
+    "sheentint",
+    "sigma",
+    "sigma_a",
+    "sigma_s",
+    "spectrans",
+    "speculartint",
+    "thin",
+    "transmit",
+    "type",
+    "uroughness",
+    "vroughness",
+];
+const SHAPE_PARAMS: &[&str] = &[
+    "N",
+    "P",
+    "Pw",
+    "S",
+    "alpha",
+    "filename",
+    "height",
+    "holdout",
+    "indices",
+    "innerradius",
+    "levels",
+    "nlevels",
+    "nu",
+    "nv",
+    "phimax",
+    "priority",
+    "radius",
+    "scheme",
+    "shadowalpha",
+    "shadowcatcher",
+    "splitdepth",
+    "st",
+    "type",
+    "u0",
+    "u1",
+    "uknots",
+    "uorder",
+    "uv",
+    "v0",
+    "v1",
+    "vknots",
+    "vorder",
+    "width",
+    "width0",
+    "width1",
+    "zmax",
+    "zmin",
+];
+const LIGHT_PARAMS: &[&str] = &[
+    "I",
+    "L",
+    "coneangle",
+    "conedeltaangle",
+    "fov",
+    "from",
+    "mapname",
+    "nsamples",
+    "samples",
+    "scale",
+    "to",
+    "twosided",
+];
+const TEXTURE_PARAMS: &[&str] = &[
+    "amount",
+    "dimension",
+    "filename",
+    "gamma",
+    "inside",
+    "mapping",
+    "maxanisotropy",
+    "octaves",
+    "outside",
+    "roughness",
+    "scale",
+    "tex1",
+    "tex2",
+    "trilinear",
+    "udelta",
+    "uscale",
+    "v1",
+    "v2",
+    "value",
+    "variation",
+    "vdelta",
+    "vscale",
+    "wrap",
+];
+const MEDIUM_PARAMS: &[&str] = &[
+    "Lescale",
+    "density",
+    "eta",
+    "g",
+    "media",
+    "nx",
+    "ny",
+    "nz",
+    "p0",
+    "p1",
+    "phase",
+    "phaseangles",
+    "phasevalues",
+    "preset",
+    "radius",
+    "scale",
+    "sigma_a",
+    "sigma_s",
+    "temperature",
+    "temperaturecutoff",
+    "temperaturescale",
+    "transmittance",
+    "transmittancedistance",
+    "type",
+    "velocity",
+    "velocityscale",
+];
+const CAMERA_PARAMS: &[&str] = &[
+    "aperturediameter",
+    "focaldistance",
+    "focusdistance",
+    "fov",
+    "frameaspectratio",
+    "lensfile",
+    "lensradius",
+    "mapping",
+    "screenwindow",
+    "shutterclose",
+    "shutteropen",
+    "simpleweighting",
+];
+const FILM_PARAMS: &[&str] = &[
+    "aovs",
+    "colorspace",
+    "cropwindow",
+    "deep",
+    "denoise",
+    "denoiserfeatures",
+    "diagonal",
+    "diskbacked",
+    "filename",
+    "halfprecision",
+    "maxsampleluminance",
+    "overscan",
+    "scale",
+    "whitebalance",
+    "whitebalanceilluminant",
+    "xresolution",
+    "yresolution",
+];
+const FILTER_PARAMS: &[&str] = &["B", "C", "alpha", "tau", "xwidth", "ywidth"];
+const SAMPLER_PARAMS: &[&str] = &[
+    "dimensions",
+    "jitter",
+    "pixelsamples",
+    "randomization",
+    "samplepixelcenter",
+    "seed",
+    "xsamples",
+    "ysamples",
+];
+const INTEGRATOR_PARAMS: &[&str] = &[
+    "bootstrapsamples",
+    "causticphotons",
+    "chains",
+    "clampindirect",
+    "cossample",
+    "finalgather",
+    "finalgathersamples",
+    "gpu",
+    "imagewritefrequency",
+    "irradiancecache",
+    "irradiancecacheerror",
+    "irradiancecachesamples",
+    "iterations",
+    "largestepprobability",
+    "lightsamplestrategy",
+    "lookupphotons",
+    "maxdepth",
+    "maxdiffusedepth",
+    "maxdistance",
+    "maxglossydepth",
+    "maxspeculardepth",
+    "maxtransmissiondepth",
+    "mutationsperpixel",
+    "nsamples",
+    "numiterations",
+    "photons",
+    "photonsperiteration",
+    "pixelbounds",
+    "polarizer",
+    "polarizerangle",
+    "radius",
+    "reconstruction",
+    "reconstructionalpha",
+    "roughening",
+    "rrthreshold",
+    "sigma",
+    "strategy",
+    "terminatorshadowing",
+    "visualizestrategies",
+    "visualizeweights",
+    "writegradients",
+];
+const ACCELERATOR_PARAMS: &[&str] = &[
+    "emptybonus",
+    "intersectcost",
+    "maxdepth",
+    "maxnodeprims",
+    "maxprims",
+    "meshbuildprims",
+    "splitmethod",
+    "traversalcost",
+];
+
+/// A problem found by **lint_scene()**.
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: warning: {}", self.location, self.message)
+    }
+}
+
+/// Names which are scoped by **AttributeBegin** and **AttributeEnd**
+/// (like in **api::GraphicsState**).
+#[derive(Clone)]
+struct LintScope {
+    float_textures: HashSet<String>,
+    spectrum_textures: HashSet<String>,
+    named_materials: HashSet<String>,
+    area_light: bool,
+}
+
+struct Linter {
+    warnings: Vec<LintWarning>,
+    location: Location,
+    scope: LintScope,
+    pushed_scopes: Vec<LintScope>,
+    cur_transform: [Transform; 2],
+    pushed_transforms: Vec<[Transform; 2]>,
+    active_transform_bits: u8,
+    named_coordinate_systems: HashMap<String, [Transform; 2]>,
+    named_media: HashSet<String>,
+    instances: HashSet<String>,
+    search_directory: Option<PathBuf>,
+}
+
+/// Checks the recorded *calls* of a scene, *locations* are the ones of
+/// the calls (see **parser::Recorder::locations**).
+pub fn lint_scene(calls: &[ApiCall], locations: &[Location]) -> Vec<LintWarning> {
+    let mut named_materials: HashSet<String> = HashSet::new();
+    // see GraphicsState::new()
+    named_materials.insert(String::from("matte"));
+    let mut linter: Linter = Linter {
+        warnings: Vec::new(),
+        location: Location::default(),
+        scope: LintScope {
+            float_textures: HashSet::new(),
+            spectrum_textures: HashSet::new(),
+            named_materials,
+            area_light: false,
+        },
+        pushed_scopes: Vec::new(),
+        cur_transform: [Transform::default(); 2],
+        pushed_transforms: Vec::new(),
+        active_transform_bits: 3_u8,
+        named_coordinate_systems: HashMap::new(),
+        named_media: HashSet::new(),
+        instances: HashSet::new(),
+        search_directory: None,
+    };
+    for (i, call) in calls.iter().enumerate() {
+        linter.location = locations.get(i).cloned().unwrap_or_default();
+        linter.check_call(call);
+    }
+    linter.warnings
+}
+
+impl Linter {
+    fn warn(&mut self, message: String) {
+        self.warnings.push(LintWarning {
+            location: self.location.clone(),
+            message,
+        });
+    }
+    fn check_call(&mut self, call: &ApiCall) {
+        match call {
+            ApiCall::ActiveTransformAll => self.active_transform_bits = 3_u8,
+            ApiCall::ActiveTransformEndTime => self.active_transform_bits = 2_u8,
+            ApiCall::ActiveTransformStartTime => self.active_transform_bits = 1_u8,
+            ApiCall::AttributeBegin => self.attribute_begin(),
+            ApiCall::AttributeEnd => self.attribute_end("AttributeEnd"),
+            ApiCall::ConcatTransform(tr) => self.apply_transform(tr, false),
+            ApiCall::LookAt(v) => self.look_at(v),
+            ApiCall::MediumInterface(inside, outside) => {
+                for name in &[inside, outside] {
+                    if !name.is_empty() && !self.named_media.contains(name.as_str()) {
+                        self.warn(format!("undefined medium {:?}", name));
+                    }
+                }
+            }
+            ApiCall::ObjectEnd => self.attribute_end("ObjectEnd"),
+            ApiCall::Params(params) => self.check_params(params),
+            ApiCall::ReverseOrientation => {}
+            ApiCall::Rotate(v) => {
+                let axis: Vector3f = Vector3f {
+                    x: v[1],
+                    y: v[2],
+                    z: v[3],
+                };
+                if axis.length() == 0.0 as Float {
+                    self.warn(String::from("Rotate: the rotation axis is zero"));
+                } else {
+                    self.apply_transform(&Transform::rotate(v[0], &axis), false);
+                }
+            }
+            ApiCall::Scale(v) => self.apply_transform(&Transform::scale(v[0], v[1], v[2]), false),
+            ApiCall::SearchDirectory(dir) => self.search_directory = Some(PathBuf::from(dir)),
+            ApiCall::Transform(tr) => self.apply_transform(tr, true),
+            ApiCall::TransformBegin => self.pushed_transforms.push(self.cur_transform),
+            ApiCall::TransformEnd => match self.pushed_transforms.pop() {
+                Some(transforms) => self.cur_transform = transforms,
+                None => self.warn(String::from("unmatched TransformEnd")),
+            },
+            ApiCall::Translate(v) => self.apply_transform(
+                &Transform::translate(&Vector3f {
+                    x: v[0],
+                    y: v[1],
+                    z: v[2],
+                }),
+                false,
+            ),
+            ApiCall::WorldBegin => {
+                self.cur_transform = [Transform::default(); 2];
+                self.active_transform_bits = 3_u8;
+                self.named_coordinate_systems
+                    .insert(String::from("world"), [Transform::default(); 2]);
+            }
+            ApiCall::WorldEnd => {}
+        }
+    }
+    fn attribute_begin(&mut self) {
+        self.pushed_scopes.push(self.scope.clone());
+        self.pushed_transforms.push(self.cur_transform);
+    }
+    fn attribute_end(&mut self, key_word: &str) {
+        match (self.pushed_scopes.pop(), self.pushed_transforms.pop()) {
+            (Some(scope), Some(transforms)) => {
+                self.scope = scope;
+                self.cur_transform = transforms;
+            }
+            _ => self.warn(format!("unmatched {}", key_word)),
+        }
+    }
+    /// Replaces (or concatenates) the active transformations, a
+    /// degenerate result gets reported once (not for each following
+    /// statement).
+    fn apply_transform(&mut self, tr: &Transform, replace: bool) {
+        let was_degenerate: bool = self.cur_transform.iter().any(is_degenerate);
+        for i in 0..2 {
+            if self.active_transform_bits & (1_u8 << i) > 0_u8 {
+                self.cur_transform[i] = if replace {
+                    *tr
+                } else {
+                    self.cur_transform[i] * *tr
+                };
+            }
+        }
+        if is_degenerate(tr) || (!was_degenerate && self.cur_transform.iter().any(is_degenerate)) {
+            self.warn(String::from(
+                "degenerate transformation (scaled by zero, or with NaN or infinite values)",
+            ));
+        }
+    }
+    fn look_at(&mut self, v: &[Float; 9]) {
+        let pos: Point3f = Point3f {
+            x: v[0],
+            y: v[1],
+            z: v[2],
+        };
+        let look: Point3f = Point3f {
+            x: v[3],
+            y: v[4],
+            z: v[5],
+        };
+        let up: Vector3f = Vector3f {
+            x: v[6],
+            y: v[7],
+            z: v[8],
+        };
+        let dir: Vector3f = look - pos;
+        if dir.length() == 0.0 as Float {
+            self.warn(String::from(
+                "LookAt: the eye and the look-at point are the same",
+            ));
+        } else if vec3_cross_vec3(&up, &dir).length() == 0.0 as Float {
+            // Transform::look_at() uses the identity
+            self.warn(String::from(
+                "LookAt: the up vector is parallel to the viewing direction (or zero)",
+            ));
+        } else {
+            self.apply_transform(&Transform::look_at(&pos, &look, &up), false);
+        }
+    }
+    fn check_params(&mut self, params: &ParamSet) {
+        let key_word: &str = params.key_word.as_str();
+        self.check_parameter_names(params);
+        self.check_textures(params);
+        if key_word != "Film" {
+            self.check_files(params);
+        }
+        match key_word {
+            "AreaLightSource" => self.scope.area_light = !params.name.is_empty(),
+            "Camera" => {
+                let camera_to_world: [Transform; 2] = [
+                    Transform::inverse(&self.cur_transform[0]),
+                    Transform::inverse(&self.cur_transform[1]),
+                ];
+                self.named_coordinate_systems
+                    .insert(String::from("camera"), camera_to_world);
+            }
+            "CoordSysTransform" => match self.named_coordinate_systems.get(&params.name) {
+                Some(transforms) => self.cur_transform = *transforms,
+                None => self.warn(format!("undefined coordinate system {:?}", params.name)),
+            },
+            "MakeNamedMaterial" => {
+                let mat_type: String = params.find_one_string("type", String::new());
+                if mat_type.is_empty() {
+                    self.warn(format!(
+                        "named material {:?} has no \"type\" parameter",
+                        params.name
+                    ));
+                }
+                self.check_mix_material(&mat_type, params);
+                self.scope.named_materials.insert(params.name.clone());
+            }
+            "MakeNamedMedium" => {
+                self.named_media.insert(params.name.clone());
+            }
+            "Material" => self.check_mix_material(&params.name, params),
+            "NamedMaterial" => self.check_named_material(&params.name),
+            "ObjectBegin" => {
+                self.attribute_begin();
+                self.instances.insert(params.name.clone());
+            }
+            "ObjectInstance" if !self.instances.contains(&params.name) => {
+                self.warn(format!("undefined object {:?}", params.name));
+            }
+            "Shape" if self.scope.area_light && self.has_zero_area(params) => {
+                self.warn(format!(
+                    "area light on a shape {:?} without any area",
+                    params.name
+                ));
+            }
+            "Texture" => {
+                if params.tex_type == "float" {
+                    self.scope.float_textures.insert(params.name.clone());
+                } else {
+                    self.scope.spectrum_textures.insert(params.name.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    fn check_parameter_names(&mut self, params: &ParamSet) {
+        let known: Vec<&str> = match params.key_word.as_str() {
+            "Accelerator" => ACCELERATOR_PARAMS.to_vec(),
+            "AreaLightSource" | "LightSource" => LIGHT_PARAMS.to_vec(),
+            "Camera" => CAMERA_PARAMS.to_vec(),
+            "Film" => FILM_PARAMS.to_vec(),
+            "Integrator" => INTEGRATOR_PARAMS.to_vec(),
+            "MakeNamedMaterial" | "Material" => MATERIAL_PARAMS.to_vec(),
+            "MakeNamedMedium" => MEDIUM_PARAMS.to_vec(),
+            "PixelFilter" => FILTER_PARAMS.to_vec(),
+            "Sampler" => SAMPLER_PARAMS.to_vec(),
+            "Shape" => [SHAPE_PARAMS, MATERIAL_PARAMS].concat(),
+            "Texture" => TEXTURE_PARAMS.to_vec(),
+            _ => return,
+        };
+        for name in parameter_names(params) {
+            if !known.contains(&name.as_str()) {
+                self.warn(format!(
+                    "unsupported parameter {:?} for {} {:?}",
+                    name, params.key_word, params.name
+                ));
+            }
+        }
+    }
+    fn check_textures(&mut self, params: &ParamSet) {
+        for item in &params.textures {
+            for name in &item.values {
+                if !self.scope.float_textures.contains(name)
+                    && !self.scope.spectrum_textures.contains(name)
+                {
+                    self.warn(format!(
+                        "undefined texture {:?} (parameter {:?})",
+                        name, item.name
+                    ));
+                }
+            }
+        }
+    }
+    fn check_files(&mut self, params: &ParamSet) {
+        for item in &params.strings {
+            if FILENAME_PARAMS.contains(&item.name.as_str()) || item.name == "bsdffile" {
+                for value in &item.values {
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let filename: String = resolve_filename(self.search_directory.as_ref(), value);
+                    if !Path::new(&filename).exists() {
+                        self.warn(format!("file {:?} not found", filename));
+                    }
+                }
+            }
+        }
+    }
+    fn check_mix_material(&mut self, mat_type: &str, params: &ParamSet) {
+        if mat_type == "mix" {
+            for name in &["namedmaterial1", "namedmaterial2"] {
+                let material: String = params.find_one_string(name, String::new());
+                self.check_named_material(&material);
+            }
+        }
+    }
+    fn check_named_material(&mut self, name: &str) {
+        if !name.is_empty() && name != "none" && !self.scope.named_materials.contains(name) {
+            self.warn(format!("undefined named material {:?}", name));
+        }
+    }
+    /// Only checks shapes which can be checked without reading files
+    /// (e.g. PLY meshes) or refining them (e.g. curves).
+    fn has_zero_area(&self, params: &ParamSet) -> bool {
+        let transform: &Transform = &self.cur_transform[0];
+        if is_degenerate(transform) {
+            return true;
+        }
+        let radius: Float = params.find_one_float("radius", 1.0 as Float);
+        let phi_max: Float = params.find_one_float("phimax", 360.0 as Float);
+        match params.name.as_str() {
+            "sphere" => {
+                let z_min: Float = params.find_one_float("zmin", -radius);
+                let z_max: Float = params.find_one_float("zmax", radius);
+                radius <= 0.0 as Float
+                    || phi_max <= 0.0 as Float
+                    || z_min.max(-radius) >= z_max.min(radius)
+            }
+            "cylinder" => {
+                let z_min: Float = params.find_one_float("zmin", -1.0 as Float);
+                let z_max: Float = params.find_one_float("zmax", 1.0 as Float);
+                radius <= 0.0 as Float || phi_max <= 0.0 as Float || z_min == z_max
+            }
+            "disk" => {
+                let inner_radius: Float = params.find_one_float("innerradius", 0.0 as Float);
+                radius <= inner_radius || phi_max <= 0.0 as Float
+            }
+            "trianglemesh" | "loopsubdiv" => {
+                let p: Vec<Point3f> = params
+                    .find_point3f("P")
+                    .iter()
+                    .map(|p| transform.transform_point(p))
+                    .collect();
+                let mut indices: Vec<i32> = params.find_int("indices");
+                if indices.is_empty() && p.len() == 3 {
+                    indices = vec![0, 1, 2];
+                }
+                let mut area: Float = 0.0 as Float;
+                for triangle in indices.chunks_exact(3) {
+                    let vertex = |i: i32| p.get(i as usize).copied();
+                    if let (Some(p0), Some(p1), Some(p2)) = (
+                        vertex(triangle[0]),
+                        vertex(triangle[1]),
+                        vertex(triangle[2]),
+                    ) {
+                        area += 0.5 as Float * vec3_cross_vec3(&(p1 - p0), &(p2 - p0)).length();
+                    }
+                }
+                area.is_nan() || area <= 0.0 as Float
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Scaled by zero (in any direction), or with NaN or infinite values.
+fn is_degenerate(transform: &Transform) -> bool {
+    let m = &transform.m.m;
+    let det: Float = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    det == 0.0 as Float
+        || !det.is_finite()
+        || transform
+            .m
+            .m
+            .iter()
+            .chain(transform.m_inv.m.iter())
+            .flatten()
+            .any(|v| !v.is_finite())
+}
+
+/// The names of all parameters (of any type).
+fn parameter_names(params: &ParamSet) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    names.extend(params.bools.iter().map(|p| p.name.clone()));
+    names.extend(params.ints.iter().map(|p| p.name.clone()));
+    names.extend(params.floats.iter().map(|p| p.name.clone()));
+    names.extend(params.point2fs.iter().map(|p| p.name.clone()));
+    names.extend(params.vector2fs.iter().map(|p| p.name.clone()));
+    names.extend(params.point3fs.iter().map(|p| p.name.clone()));
+    names.extend(params.vector3fs.iter().map(|p| p.name.clone()));
+    names.extend(params.normals.iter().map(|p| p.name.clone()));
+    names.extend(params.spectra.iter().map(|p| p.name.clone()));
+    names.extend(params.strings.iter().map(|p| p.name.clone()));
+    names.extend(params.textures.iter().map(|p| p.name.clone()));
+    names
+}
@@ -4,7 +4,7 @@ use hexf::*;
 
 // pbrt
 use crate::core::geometry::{Point2f, Point2i};
-use crate::core::pbrt::Float;
+use crate::core::pbrt::{mix_bits, Float};
 use crate::core::rng::Rng;
 use crate::core::rng::FLOAT_ONE_MINUS_EPSILON;
 use crate::core::sampling::shuffle;
@@ -1051,13 +1051,25 @@ pub fn sobol_sample(index: i64, dimension: i32, scramble: u64) -> Float {
 
 /// Takes a 64 bit index and 32x52 matrices to calculate sample values.
 pub fn sobol_sample_float(a: i64, dimension: i32, scramble: u32) -> Float {
+    let v: u32 = scramble ^ sobol_sample_bits(a, dimension);
+    //#ifndef PBRT_HAVE_HEX_FP_CONSTANTS
+    // let x = (2.0 as f32).powi(-32 as i32); // 0x1p-32f: 1/2^32
+    // (v as Float * x).min(FLOAT_ONE_MINUS_EPSILON)
+    //#else
+    (v as Float * hexf32!("0x1.0p-32") as Float).min(FLOAT_ONE_MINUS_EPSILON)
+    //#endif
+}
+
+/// The (unscrambled) Sobol' sample *a* of *dimension* as a 32 bit
+/// fixed point number.
+pub fn sobol_sample_bits(a: i64, dimension: i32) -> u32 {
     assert!(
         dimension < NUM_SOBOL_DIMENSIONS as i32,
         "Integrator has consumed too many Sobol' dimensions; \
          you may want to use a Sampler without a dimension limit like \"02sequence.\""
     );
     let mut a: i64 = a;
-    let mut v: u32 = scramble;
+    let mut v: u32 = 0_u32;
     // for (int i = dimension * SobolMatrixSize; a != 0; a >>= 1, i++)
     let mut i: usize = dimension as usize * SOBOL_MATRIX_SIZE as usize;
     while a != 0 {
@@ -1067,12 +1079,71 @@ pub fn sobol_sample_float(a: i64, dimension: i32, scramble: u32) -> Float {
         a >>= 1;
         i += 1_usize;
     }
-    //#ifndef PBRT_HAVE_HEX_FP_CONSTANTS
-    // let x = (2.0 as f32).powi(-32 as i32); // 0x1p-32f: 1/2^32
-    // (v as Float * x).min(FLOAT_ONE_MINUS_EPSILON)
-    //#else
+    v
+}
+
+/// How the bits of Sobol' samples get randomized. All of them keep
+/// the stratification of the samples (in elementary intervals).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SobolRandomization {
+    /// the plain Sobol' points
+    None,
+    /// all bits XORed with the seed (random digit scrambling)
+    Xor,
+    /// Owen scrambling, each bit gets flipped depending on a hash of
+    /// the seed and the bits before it
+    Owen,
+    /// Owen scrambling approximated by a few multiplications (see
+    /// Burley: Practical Hash-based Owen Scrambling)
+    FastOwen,
+}
+
+/// Owen scrambling of the 32 bit fixed point number *v*, a random
+/// permutation of each half of an interval (for all the nested
+/// dyadic intervals), picked by *seed*.
+pub fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v: u32 = v;
+    if seed & 1 != 0 {
+        v ^= 1_u32 << 31;
+    }
+    for b in 1..32 {
+        // apply Owen scrambling to binary digit _b_ in _v_
+        let mask: u32 = (!0_u32) << (32 - b);
+        if (mix_bits(((v & mask) ^ seed) as u64) as u32) & (1_u32 << b) != 0 {
+            v ^= 1_u32 << (31 - b);
+        }
+    }
+    v
+}
+
+/// A fast approximation of **owen_scramble()**, the hash of
+/// Laine and Karras applied to the reversed bits of *v*.
+pub fn fast_owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v: u32 = reverse_bits_32(v);
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    reverse_bits_32(v)
+}
+
+/// The Sobol' sample *a* of *dimension* randomized with
+/// *randomization* (and *seed*).
+pub fn sobol_sample_randomized(
+    a: i64,
+    dimension: i32,
+    randomization: SobolRandomization,
+    seed: u32,
+) -> Float {
+    let v: u32 = sobol_sample_bits(a, dimension);
+    let v: u32 = match randomization {
+        SobolRandomization::None => v,
+        SobolRandomization::Xor => v ^ seed,
+        SobolRandomization::Owen => owen_scramble(v, seed),
+        SobolRandomization::FastOwen => fast_owen_scramble(v, seed),
+    };
     (v as Float * hexf32!("0x1.0p-32") as Float).min(FLOAT_ONE_MINUS_EPSILON)
-    //#endif
 }
 
 // see lowdiscrepancy.cpp
@@ -0,0 +1,156 @@
+//! A low-memory mode for huge scenes.
+//!
+//! Usually every triangle of a mesh becomes a **Shape** of its own
+//! (with a copy of the mesh transforms) wrapped in a
+//! **GeometricPrimitive**, and BVH nodes store their bounds as
+//! floats. That's a few hundred bytes per triangle, too much for
+//! scenes with 100M+ triangles. With **set_low_memory()**
+//!
+//! - triangle meshes (without area lights) stay whole: a
+//!   **MeshPrimitive** references the triangles by their 32-bit index
+//!   into the shared vertex pool of the mesh (see **MeshBVH**),
+//! - BVH nodes store their bounds quantized to 16 bits per coordinate,
+//!   relative to the bounds of their parent (see **QuantizedBVH**),
+//! - optionally the normals and (u, v) coordinates of meshes are
+//!   stored as 16-bit floats (see **TriangleMesh**).
+//!
+//! Independent of the mode, the memory used by the geometry and the
+//! acceleration structures can be reported against a budget once the
+//! scene was built (see **report_memory_budget()**).
+
+// std
+use std::sync::Mutex;
+// others
+use log::{info, warn};
+// pbrt
+use crate::core::scenestats::{format_bytes, SceneStats};
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LowMemory {
+    /// whole meshes and quantized BVH nodes
+    pub enabled: bool,
+    /// normals and (u, v) coordinates of meshes as 16-bit floats
+    pub half_attributes: bool,
+    /// bytes the geometry and acceleration structures should fit into
+    pub budget: Option<usize>,
+}
+
+lazy_static::lazy_static! {
+    static ref LOW_MEMORY: Mutex<LowMemory> = Mutex::new(LowMemory::default());
+}
+
+/// Applies to scenes parsed afterwards.
+pub fn set_low_memory(low_memory: LowMemory) {
+    *LOW_MEMORY.lock().unwrap() = low_memory;
+}
+
+pub fn low_memory() -> LowMemory {
+    *LOW_MEMORY.lock().unwrap()
+}
+
+/// Parses a number of bytes with an optional unit, e.g. "512M",
+/// "16G", or "16GiB" (units are powers of 1024).
+pub fn parse_memory_size(s: &str) -> Option<usize> {
+    let s: &str = s.trim();
+    let digits: usize = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let value: f64 = s[..digits].parse().ok()?;
+    let shift: u32 = match s[digits..].trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return None,
+    };
+    Some((value * (1_u64 << shift) as f64) as usize)
+}
+
+/// Logs how much of the budget the geometry and the acceleration
+/// structures of the (collected) scene use, and warns if they don't
+/// fit.
+pub fn report_memory_budget(stats: &SceneStats) {
+    let settings: LowMemory = low_memory();
+    let budget: usize = match settings.budget {
+        Some(budget) => budget,
+        None => return,
+    };
+    let used: usize = stats.geometry_memory + stats.accelerator_memory;
+    let message: String = format!(
+        "Geometry and accelerators use {} ({} + {}) of the memory budget of {} ({:.1}%)",
+        format_bytes(used),
+        format_bytes(stats.geometry_memory),
+        format_bytes(stats.accelerator_memory),
+        format_bytes(budget),
+        used as f64 * 100.0 / budget.max(1) as f64
+    );
+    if used <= budget {
+        info!("{}", message);
+    } else if !settings.enabled {
+        warn!("{}, try the low-memory mode", message);
+    } else if !settings.half_attributes {
+        warn!("{}, try half-precision normals and (u, v) coordinates", message);
+    } else {
+        warn!("{}", message);
+    }
+}
+
+/// The nearest 16-bit float (IEEE 754 binary16), too large values
+/// become infinite.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits: u32 = value.to_bits();
+    let sign: u16 = ((bits >> 16) & 0x8000) as u16;
+    let exponent: i32 = ((bits >> 23) & 0xff) as i32;
+    let mantissa: u32 = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        // infinity or NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+    let exponent: i32 = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // keep 10 bits of the mantissa (or less for subnormals), round to
+    // nearest even
+    let (half, shift): (u32, u32) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        (0, (14 - exponent) as u32)
+    } else {
+        ((exponent as u32) << 10, 13)
+    };
+    let mantissa: u32 = if exponent <= 0 {
+        mantissa | 0x0080_0000
+    } else {
+        mantissa
+    };
+    let mut half: u32 = half | (mantissa >> shift);
+    let rest: u32 = mantissa & ((1 << shift) - 1);
+    let halfway: u32 = 1 << (shift - 1);
+    if rest > halfway || (rest == halfway && half & 1 == 1) {
+        // a carry into the exponent is still correct (up to infinity)
+        half += 1;
+    }
+    sign | half as u16
+}
+
+pub fn f16_to_f32(half: u16) -> f32 {
+    let exponent: i32 = ((half >> 10) & 0x1f) as i32;
+    let mantissa: f32 = (half & 0x3ff) as f32;
+    let value: f32 = if exponent == 0 {
+        mantissa * (2.0_f32).powi(-24)
+    } else if exponent != 0x1f {
+        (mantissa + 1024.0) * (2.0_f32).powi(exponent - 25)
+    } else if mantissa == 0.0 {
+        f32::INFINITY
+    } else {
+        f32::NAN
+    };
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
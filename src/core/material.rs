@@ -11,6 +11,7 @@ use crate::core::interaction::SurfaceInteraction;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::texture::Texture;
 use crate::materials::disney::DisneyMaterial;
+use crate::materials::fluorescent::FluorescentMaterial;
 use crate::materials::fourier::FourierMaterial;
 use crate::materials::glass::GlassMaterial;
 use crate::materials::hair::HairMaterial;
@@ -37,6 +38,7 @@ pub enum TransportMode {
 
 pub enum Material {
     Disney(Box<DisneyMaterial>),
+    Fluorescent(Box<FluorescentMaterial>),
     Fourier(Box<FourierMaterial>),
     Glass(Box<GlassMaterial>),
     Hair(Box<HairMaterial>),
@@ -72,6 +74,9 @@ impl Material {
             Material::Disney(material) => {
                 material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
             }
+            Material::Fluorescent(material) => {
+                material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
+            }
             Material::Fourier(material) => {
                 material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
             }
@@ -112,7 +117,7 @@ impl Material {
     }
     /// Computing the effect of bump mapping at the point being shaded
     /// given a particular displacement texture.
-    pub fn bump(d: &Arc<dyn Texture<Float> + Send + Sync>, si: &mut SurfaceInteraction)
+    pub fn bump(d: &Arc<Texture<Float>>, si: &mut SurfaceInteraction)
     where
         Self: Sized,
     {
@@ -2,15 +2,23 @@
 //! compute the beam transmittance along a given ray.
 
 // std
-use std::f32::consts::PI;
 use std::sync::Arc;
+// others
+use num::complex::Complex;
 // pbrt
+use crate::core::geometry::vec3_dot_nrm;
 use crate::core::geometry::{spherical_direction_vec3, vec3_coordinate_system, vec3_dot_vec3};
 use crate::core::geometry::{Point2f, Ray, Vector3f};
-use crate::core::interaction::MediumInteraction;
+use crate::core::interaction::{MediumInteraction, SurfaceInteraction};
+use crate::core::pbrt::clamp_t;
+use crate::core::pbrt::consts::PI;
+use crate::core::pbrt::find_interval;
 use crate::core::pbrt::INV_4_PI;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::primitive::Primitive;
 use crate::core::sampler::Sampler;
+use crate::core::sampling::Distribution1D;
+use crate::media::aggregate::VolumeAggregate;
 use crate::media::grid::GridDensityMedium;
 use crate::media::homogeneous::HomogeneousMedium;
 
@@ -268,14 +276,16 @@ pub struct MeasuredSS {
 pub struct NoMedium {}
 
 pub enum Medium {
+    Aggregate(VolumeAggregate),
     Empty(NoMedium),
-    GridDensity(GridDensityMedium),
+    GridDensity(Box<GridDensityMedium>),
     Homogeneous(HomogeneousMedium),
 }
 
 impl Medium {
     pub fn tr(&self, r_world: &Ray, sampler: &mut Sampler) -> Spectrum {
         match self {
+            Medium::Aggregate(medium) => medium.tr(r_world, sampler),
             Medium::Empty(_medium) => Spectrum::default(),
             Medium::GridDensity(medium) => medium.tr(r_world, sampler),
             Medium::Homogeneous(medium) => medium.tr(r_world, sampler),
@@ -287,6 +297,7 @@ impl Medium {
         sampler: &mut Sampler,
     ) -> (Spectrum, Option<MediumInteraction>) {
         match self {
+            Medium::Aggregate(medium) => medium.sample(r_world, sampler),
             Medium::Empty(_medium) => (Spectrum::default(), None),
             Medium::GridDensity(medium) => medium.sample(r_world, sampler),
             Medium::Homogeneous(medium) => medium.sample(r_world, sampler),
@@ -294,6 +305,26 @@ impl Medium {
     }
 }
 
+pub enum PhaseFunction {
+    HenyeyGreenstein(HenyeyGreenstein),
+    Tabulated(Box<TabulatedPhaseFunction>),
+}
+
+impl PhaseFunction {
+    pub fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        match self {
+            PhaseFunction::HenyeyGreenstein(phase) => phase.p(wo, wi),
+            PhaseFunction::Tabulated(phase) => phase.p(wo, wi),
+        }
+    }
+    pub fn sample_p(&self, wo: &Vector3f, wi: &mut Vector3f, u: Point2f) -> Float {
+        match self {
+            PhaseFunction::HenyeyGreenstein(phase) => phase.sample_p(wo, wi, u),
+            PhaseFunction::Tabulated(phase) => phase.sample_p(wo, wi, u),
+        }
+    }
+}
+
 pub struct HenyeyGreenstein {
     pub g: Float,
 }
@@ -327,6 +358,203 @@ impl HenyeyGreenstein {
     }
 }
 
+/// A phase function given as a table of values over the scattering
+/// angle, e.g. measured data or the output of **mie_phase_function()**.
+/// The table is treated as piecewise constant in $\cos \theta$, which
+/// allows the phase function to be sampled exactly.
+pub struct TabulatedPhaseFunction {
+    /// $\cos \theta$ at the bin boundaries (decreasing)
+    pub cos_theta: Vec<Float>,
+    /// normalized phase function value of each bin
+    pub values: Vec<Float>,
+    pub distribution: Distribution1D,
+}
+
+impl TabulatedPhaseFunction {
+    /// Expects scattering angles _theta_ (in radians, increasing, from
+    /// forward to backward scattering) and the (unnormalized) phase
+    /// function values at those angles. Returns an error if there
+    /// aren't as many values as angles (at least two), or if the
+    /// values don't integrate to a positive number.
+    pub fn new(theta: &[Float], values: &[Float]) -> Result<Self, String> {
+        if theta.len() < 2 || theta.len() != values.len() {
+            return Err(format!(
+                "tabulated phase function needs matching angles and values, at least two ({} vs. {})",
+                theta.len(),
+                values.len()
+            ));
+        }
+        let cos_theta: Vec<Float> = theta
+            .iter()
+            .map(|t| clamp_t(t.cos(), -1.0 as Float, 1.0 as Float))
+            .collect();
+        let n_bins: usize = theta.len() - 1;
+        let mut bin_values: Vec<Float> = Vec::with_capacity(n_bins);
+        let mut weights: Vec<Float> = Vec::with_capacity(n_bins);
+        let mut integral: Float = 0.0 as Float;
+        for i in 0..n_bins {
+            let value: Float = 0.5 as Float * (values[i] + values[i + 1]).max(0.0 as Float);
+            let width: Float = (cos_theta[i] - cos_theta[i + 1]).max(0.0 as Float);
+            bin_values.push(value);
+            weights.push(value * width);
+            integral += 2.0 as Float * PI * value * width;
+        }
+        // also false for NaN
+        if !(integral > 0.0 as Float && integral.is_finite()) {
+            return Err(format!(
+                "tabulated phase function integrates to {} (has to be positive)",
+                integral
+            ));
+        }
+        // normalize phase function over the sphere of directions
+        for value in bin_values.iter_mut() {
+            *value /= integral;
+        }
+        Ok(TabulatedPhaseFunction {
+            cos_theta,
+            values: bin_values,
+            distribution: Distribution1D::new(weights),
+        })
+    }
+    fn lookup(&self, cos_theta: Float) -> Float {
+        let n: usize = self.cos_theta.len();
+        if cos_theta > self.cos_theta[0] || cos_theta < self.cos_theta[n - 1] {
+            return 0.0 as Float;
+        }
+        let i: i32 = find_interval(n as i32, |index| {
+            self.cos_theta[index as usize] >= cos_theta
+        });
+        self.values[i as usize]
+    }
+    pub fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
+        // same convention as **HenyeyGreenstein**: _wo_ points away
+        // from the scattering point
+        self.lookup(-vec3_dot_vec3(wo, wi))
+    }
+    pub fn sample_p(&self, wo: &Vector3f, wi: &mut Vector3f, u: Point2f) -> Float {
+        // pick a bin and sample $\cos \theta$ uniformly within it
+        let mut offset: usize = 0;
+        let x: Float = self
+            .distribution
+            .sample_continuous(u[0], None, Some(&mut offset));
+        let du: Float = clamp_t(
+            x * self.distribution.count() as Float - offset as Float,
+            0.0 as Float,
+            1.0 as Float,
+        );
+        let cos_theta: Float =
+            self.cos_theta[offset] - du * (self.cos_theta[offset] - self.cos_theta[offset + 1]);
+        // compute direction _wi_ for tabulated phase function sample
+        let sin_theta: Float = (0.0 as Float)
+            .max(1.0 as Float - cos_theta * cos_theta)
+            .sqrt();
+        let phi: Float = 2.0 as Float * PI * u[1];
+        let mut v1: Vector3f = Vector3f::default();
+        let mut v2: Vector3f = Vector3f::default();
+        vec3_coordinate_system(wo, &mut v1, &mut v2);
+        *wi = spherical_direction_vec3(sin_theta, cos_theta, phi, &v1, &v2, &(-*wo));
+        self.values[offset]
+    }
+}
+
+/// Computes the Mie phase function of spherical particles (e.g. water
+/// droplets in clouds or fog) with the given _radius_ (in micrometers)
+/// and (real) index of refraction _eta_ relative to the surrounding
+/// medium, averaged over red, green, and blue wavelengths. See Bohren
+/// and Huffman, "Absorption and Scattering of Light by Small
+/// Particles". Returns an error unless _radius_ and _eta_ are
+/// positive.
+pub fn mie_phase_function(
+    radius: Float,
+    eta: Float,
+    n_angles: usize,
+) -> Result<TabulatedPhaseFunction, String> {
+    assert!(n_angles >= 2);
+    // also false for NaN
+    if !(radius > 0.0 as Float && eta > 0.0 as Float) {
+        return Err(format!(
+            "Mie phase function needs a positive radius and eta ({} and {})",
+            radius, eta
+        ));
+    }
+    // sample scattering angles densely around the forward peak
+    let theta: Vec<Float> = (0..n_angles)
+        .map(|i| {
+            let t: Float = i as Float / (n_angles - 1) as Float;
+            PI * t * t
+        })
+        .collect();
+    let mut values: Vec<Float> = vec![0.0 as Float; n_angles];
+    let wavelengths: [f64; 3] = [0.65, 0.55, 0.45]; // micrometers
+    for lambda in wavelengths.iter() {
+        let x: f64 = 2.0 * std::f64::consts::PI * radius as f64 / lambda;
+        let intensities: Vec<f64> = mie_intensities(x, eta as f64, &theta);
+        for (value, intensity) in values.iter_mut().zip(intensities.iter()) {
+            *value += *intensity as Float;
+        }
+    }
+    TabulatedPhaseFunction::new(&theta, &values)
+}
+
+/// Returns the unpolarized scattered intensity $(|S_1|^2 + |S_2|^2) / 2$
+/// for size parameter _x_ and relative refractive index _m_.
+fn mie_intensities(x: f64, m: f64, theta: &[Float]) -> Vec<f64> {
+    let n_stop: usize = (x + 4.0 * x.powf(1.0 / 3.0) + 2.0) as usize;
+    let y: f64 = m * x;
+    let n_max: usize = n_stop.max(y.abs() as usize) + 15;
+    // logarithmic derivative $D_n(mx)$ by downward recurrence
+    let mut d: Vec<f64> = vec![0.0; n_max + 1];
+    for n in (1..=n_max).rev() {
+        let en: f64 = n as f64 / y;
+        d[n - 1] = en - 1.0 / (d[n] + en);
+    }
+    // Mie coefficients $a_n$ and $b_n$
+    let mut a: Vec<Complex<f64>> = Vec::with_capacity(n_stop);
+    let mut b: Vec<Complex<f64>> = Vec::with_capacity(n_stop);
+    let mut psi0: f64 = x.cos();
+    let mut psi1: f64 = x.sin();
+    let mut chi0: f64 = -x.sin();
+    let mut chi1: f64 = x.cos();
+    let mut xi1: Complex<f64> = Complex::new(psi1, -chi1);
+    for (n, d_n) in (1..=n_stop).zip(&d[1..]) {
+        let nf: f64 = n as f64;
+        let psi: f64 = (2.0 * nf - 1.0) * psi1 / x - psi0;
+        let chi: f64 = (2.0 * nf - 1.0) * chi1 / x - chi0;
+        let xi: Complex<f64> = Complex::new(psi, -chi);
+        let da: f64 = d_n / m + nf / x;
+        let db: f64 = d_n * m + nf / x;
+        a.push((da * psi - psi1) / (xi * da - xi1));
+        b.push((db * psi - psi1) / (xi * db - xi1));
+        psi0 = psi1;
+        psi1 = psi;
+        chi0 = chi1;
+        chi1 = chi;
+        xi1 = Complex::new(psi1, -chi1);
+    }
+    // sum up amplitude functions $S_1$ and $S_2$ for each angle
+    theta
+        .iter()
+        .map(|t| {
+            let mu: f64 = (*t as f64).cos();
+            let mut s1: Complex<f64> = Complex::new(0.0, 0.0);
+            let mut s2: Complex<f64> = Complex::new(0.0, 0.0);
+            let mut pi0: f64 = 0.0;
+            let mut pi1: f64 = 1.0;
+            for n in 1..=n_stop {
+                let nf: f64 = n as f64;
+                let f: f64 = (2.0 * nf + 1.0) / (nf * (nf + 1.0));
+                let tau: f64 = nf * mu * pi1 - (nf + 1.0) * pi0;
+                s1 += (a[n - 1] * pi1 + b[n - 1] * tau) * f;
+                s2 += (a[n - 1] * tau + b[n - 1] * pi1) * f;
+                let pi: f64 = ((2.0 * nf + 1.0) * mu * pi1 - (nf + 1.0) * pi0) / nf;
+                pi0 = pi1;
+                pi1 = pi;
+            }
+            0.5 * (s1.norm_sqr() + s2.norm_sqr())
+        })
+        .collect()
+}
+
 #[derive(Default, Clone)]
 pub struct MediumInterface {
     pub inside: Option<Arc<Medium>>,
@@ -376,6 +604,116 @@ impl MediumInterface {
     }
 }
 
+/// A prioritized primitive a path is currently inside of.
+pub struct InteriorEntry {
+    pub primitive: *const Primitive,
+    pub priority: i32,
+    /// absolute index of refraction
+    pub eta: Float,
+    pub medium: Option<Arc<Medium>>,
+}
+
+/// Keeps track of the prioritized primitives (see _"integer
+/// priority"_) a path is currently inside of. Surfaces of primitives
+/// with a lower priority than the enclosing one are ignored (false
+/// intersections), which resolves nested dielectrics (ice in water in
+/// glass) and overlapping participating media. See Schmidt and Budge,
+/// "Simple Nested Dielectrics in Ray Traced Images".
+#[derive(Default)]
+pub struct InteriorList {
+    pub entries: Vec<InteriorEntry>,
+}
+
+impl InteriorList {
+    /// Returns the entry with the highest priority, ignoring
+    /// _primitive_.
+    fn highest(&self, primitive: *const Primitive) -> Option<&InteriorEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.primitive != primitive)
+            .max_by_key(|entry| entry.priority)
+    }
+    fn get_priority(isect: &SurfaceInteraction) -> (*const Primitive, i32) {
+        if let Some(primitive) = isect.primitive {
+            let priority: i32 = unsafe { &*primitive }.get_priority();
+            (primitive, priority)
+        } else {
+            (std::ptr::null(), 0_i32)
+        }
+    }
+    /// Should the surface hit at _isect_ be skipped, because the path
+    /// is inside a primitive with a higher priority?
+    pub fn is_false_intersection(&self, isect: &SurfaceInteraction) -> bool {
+        let (primitive, priority) = InteriorList::get_priority(isect);
+        if priority > 0_i32 {
+            if let Some(entry) = self.highest(primitive) {
+                return priority < entry.priority;
+            }
+        }
+        false
+    }
+    /// Index of refraction on the other side of the surface at _isect_.
+    pub fn eta_outside(&self, isect: &SurfaceInteraction) -> Option<Float> {
+        let (primitive, priority) = InteriorList::get_priority(isect);
+        if priority > 0_i32 {
+            if let Some(entry) = self.highest(primitive) {
+                return Some(entry.eta);
+            }
+        }
+        None
+    }
+    /// Updates the list after the path crossed the surface at _isect_
+    /// with the newly spawned _ray_ and sets the medium _ray_ travels
+    /// through. The _medium_ is the one the path was in before.
+    pub fn cross(
+        &mut self,
+        isect: &SurfaceInteraction,
+        ray: &mut Ray,
+        medium: Option<Arc<Medium>>,
+    ) {
+        let (primitive, priority) = InteriorList::get_priority(isect);
+        if priority <= 0_i32 {
+            return;
+        }
+        let mut is_transition: bool = false;
+        if let Some(ref medium_interface) = isect.medium_interface {
+            is_transition = medium_interface.is_medium_transition();
+        }
+        let entering: bool = vec3_dot_nrm(&ray.d, &isect.n) < 0.0 as Float;
+        if entering {
+            if !self
+                .entries
+                .iter()
+                .any(|entry| entry.primitive == primitive)
+            {
+                let mut eta: Float = 1.0 as Float;
+                if let Some(ref bsdf) = isect.bsdf {
+                    eta = bsdf.eta * isect.eta_outside.unwrap_or(1.0 as Float);
+                }
+                let inside: Option<Arc<Medium>> = if is_transition {
+                    ray.medium.clone()
+                } else {
+                    medium.clone()
+                };
+                self.entries.push(InteriorEntry {
+                    primitive,
+                    priority,
+                    eta,
+                    medium: inside,
+                });
+            }
+        } else {
+            self.entries.retain(|entry| entry.primitive != primitive);
+        }
+        // the medium with the highest priority wins
+        if let Some(entry) = self.highest(std::ptr::null()) {
+            ray.medium = entry.medium.clone();
+        } else if !is_transition {
+            ray.medium = medium;
+        }
+    }
+}
+
 pub fn get_medium_scattering_properties(
     name: &str,
     sigma_a: &mut Spectrum,
@@ -2,6 +2,14 @@
 
 // std
 use std;
+use std::cell::UnsafeCell;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::mem::{align_of, size_of_val, MaybeUninit};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 // others
 use num;
 use std::ops::{Add, Index, IndexMut};
@@ -100,3 +108,279 @@ where
         &mut self.data[offset]
     }
 }
+
+/// The alignment of all allocations of a **MemoryArena** (in bytes).
+const ARENA_ALIGNMENT: usize = 16;
+
+/// One block of arena memory, 16 byte aligned.
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+struct ArenaChunk {
+    _bytes: MaybeUninit<[u8; ARENA_ALIGNMENT]>,
+}
+
+#[derive(Default)]
+struct ArenaBlocks {
+    current_block: Vec<ArenaChunk>,
+    current_block_pos: usize,
+    used_blocks: Vec<Vec<ArenaChunk>>,
+    available_blocks: Vec<Vec<ArenaChunk>>,
+}
+
+/// A bump allocator for short-lived values (see pbrt's MemoryArena).
+///
+/// Each render thread owns one arena. Allocating only moves a
+/// position inside the current block, and **reset()** frees all
+/// allocations at once, keeping the blocks for the next camera
+/// sample, so shading does not have to go through the global heap
+/// allocator (and its locks) for each ray.
+pub struct MemoryArena {
+    block_size: usize,
+    blocks: UnsafeCell<ArenaBlocks>,
+}
+
+impl MemoryArena {
+    /// Creates an arena which allocates its memory in blocks of (at
+    /// least) *block_size* bytes.
+    pub fn new(block_size: usize) -> Self {
+        MemoryArena {
+            block_size: block_size.max(ARENA_ALIGNMENT),
+            blocks: UnsafeCell::new(ArenaBlocks::default()),
+        }
+    }
+    /// Allocates a copy of *values*. The copy lives until the arena
+    /// gets reset.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> &mut [T] {
+        assert!(align_of::<T>() <= ARENA_ALIGNMENT);
+        let n_bytes: usize = size_of_val(values);
+        if n_bytes == 0 {
+            return &mut [];
+        }
+        let n_chunks: usize =
+            ((n_bytes + ARENA_ALIGNMENT - 1) & !(ARENA_ALIGNMENT - 1)) / ARENA_ALIGNMENT;
+        // the borrow of the blocks ends before this function returns,
+        // and blocks are never freed or moved while allocations exist
+        // (see reset()), so the returned slices don't overlap
+        let blocks: &mut ArenaBlocks = unsafe { &mut *self.blocks.get() };
+        if blocks.current_block_pos + n_chunks > blocks.current_block.len() {
+            // add the current block to the used ones
+            if !blocks.current_block.is_empty() {
+                let full: Vec<ArenaChunk> = std::mem::take(&mut blocks.current_block);
+                blocks.used_blocks.push(full);
+            }
+            // try to get memory block from the available ones
+            let available: Option<usize> = blocks
+                .available_blocks
+                .iter()
+                .position(|block| block.len() >= n_chunks);
+            blocks.current_block = match available {
+                Some(index) => blocks.available_blocks.swap_remove(index),
+                None => {
+                    let chunks: usize = n_chunks.max(self.block_size / ARENA_ALIGNMENT);
+                    let chunk: ArenaChunk = ArenaChunk {
+                        _bytes: MaybeUninit::uninit(),
+                    };
+                    vec![chunk; chunks]
+                }
+            };
+            blocks.current_block_pos = 0;
+        }
+        let start: *mut T = blocks.current_block[blocks.current_block_pos..].as_mut_ptr() as *mut T;
+        blocks.current_block_pos += n_chunks;
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), start, values.len());
+            std::slice::from_raw_parts_mut(start, values.len())
+        }
+    }
+    /// Frees all allocations. The memory blocks are kept for reuse.
+    pub fn reset(&mut self) {
+        let blocks: &mut ArenaBlocks = self.blocks.get_mut();
+        blocks.current_block_pos = 0;
+        let used: Vec<Vec<ArenaChunk>> = std::mem::take(&mut blocks.used_blocks);
+        blocks.available_blocks.extend(used);
+    }
+    /// The number of bytes reserved by the arena.
+    pub fn total_allocated(&self) -> usize {
+        let blocks: &ArenaBlocks = unsafe { &*self.blocks.get() };
+        let chunks: usize = blocks.current_block.len()
+            + blocks
+                .used_blocks
+                .iter()
+                .chain(blocks.available_blocks.iter())
+                .map(|block| block.len())
+                .sum::<usize>();
+        chunks * ARENA_ALIGNMENT
+    }
+}
+
+/// See **set_memory_mapping()**.
+static MEMORY_MAPPING: AtomicBool = AtomicBool::new(true);
+
+/// Turns memory mapping of files (see **MappedFile**) on (the
+/// default) or off. Reading a mapped file which gets truncated
+/// meanwhile (e.g. by an exporter writing it again) crashes with
+/// SIGBUS, so files which can change during a render (like in the
+/// watch mode) should be read into memory instead.
+pub fn set_memory_mapping(on: bool) {
+    MEMORY_MAPPING.store(on, Ordering::Relaxed);
+}
+
+pub fn is_memory_mapping() -> bool {
+    MEMORY_MAPPING.load(Ordering::Relaxed)
+}
+
+/// Which files scenes may read (see **set_file_access()**).
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileAccess {
+    /// any file (the default)
+    All,
+    /// only files below this (canonical) directory
+    Below(PathBuf),
+    /// no files at all
+    Nothing,
+}
+
+/// See **set_file_access()**.
+static FILE_ACCESS: RwLock<FileAccess> = RwLock::new(FileAccess::All);
+
+/// Restricts the files the parser and the loaders of meshes, images,
+/// spectra, etc. read (see **readable_path()**), e.g. for a server
+/// which renders scenes uploaded by its clients.
+pub fn set_file_access(access: FileAccess) {
+    *FILE_ACCESS.write().unwrap() = access;
+}
+
+/// The path to open a file of a scene with: the canonical path if the
+/// file access is restricted (see **set_file_access()**), so symbolic
+/// links and *..* can't lead outside of the allowed directory. Fails
+/// with **ErrorKind::PermissionDenied** for files which mustn't be
+/// read.
+pub fn readable_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path: &Path = path.as_ref();
+    let refused = || {
+        Error::new(
+            ErrorKind::PermissionDenied,
+            format!("reading {:?} is not allowed", path),
+        )
+    };
+    match *FILE_ACCESS.read().unwrap() {
+        FileAccess::All => Ok(path.to_path_buf()),
+        FileAccess::Below(ref root) => {
+            let canonical: PathBuf = path.canonicalize()?;
+            if canonical.starts_with(root) {
+                Ok(canonical)
+            } else {
+                Err(refused())
+            }
+        }
+        FileAccess::Nothing => Err(refused()),
+    }
+}
+
+/// The (read-only) content of a file, mapped into memory.
+///
+/// Big binary assets (PLY meshes, Fourier BSDF tables, scene caches)
+/// are parsed directly from the mapped pages, instead of copying them
+/// through read buffers first. **MappedFile** dereferences to the
+/// bytes of the file (and **&[u8]** implements **Read** and
+/// **BufRead**). On platforms without *mmap*, or if memory mapping is
+/// turned off (see **set_memory_mapping()**), the file gets read into
+/// memory.
+pub struct MappedFile {
+    #[cfg(unix)]
+    ptr: *mut libc::c_void,
+    #[cfg(unix)]
+    len: usize,
+    /// the content of a file which wasn't mapped
+    data: Vec<u8>,
+}
+
+// the mapping is read-only and private
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    #[cfg(unix)]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+        use std::os::unix::io::AsRawFd;
+
+        if !is_memory_mapping() {
+            return MappedFile::read(path);
+        }
+        let file: File = File::open(readable_path(path)?)?;
+        let len: usize = file.metadata()?.len() as usize;
+        if len == 0 {
+            // mmap() fails for zero bytes
+            return Ok(MappedFile {
+                ptr: std::ptr::null_mut(),
+                len,
+                data: Vec::new(),
+            });
+        }
+        let ptr: *mut libc::c_void = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        // the mapping stays valid after the file gets closed
+        Ok(MappedFile {
+            ptr,
+            len,
+            data: Vec::new(),
+        })
+    }
+    #[cfg(not(unix))]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+        MappedFile::read(path)
+    }
+    /// Reads the whole file into memory instead of mapping it.
+    fn read<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+        use std::io::Read;
+
+        let mut data: Vec<u8> = Vec::new();
+        File::open(readable_path(path)?)?.read_to_end(&mut data)?;
+        Ok(MappedFile {
+            #[cfg(unix)]
+            ptr: std::ptr::null_mut(),
+            #[cfg(unix)]
+            len: 0,
+            data,
+        })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            &self.data
+        } else {
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
@@ -2,12 +2,24 @@
 //! specular reflection and transmission have been effective at
 //! modeling light scattering from a variety of glossy materials,
 //! including metals, plastic, and frosted glass.
+//!
+//! Materials map their *roughness* parameters to the alpha of their
+//! microfacet distribution with a **RoughnessMapping**: the one of
+//! their *roughnessmapping* parameter, none for *remaproughness*
+//! false, or else the one of **set_roughness_mapping()**, which
+//! defaults to pbrt-v3's fit for all materials but **disney**, which
+//! squares the roughness. Using the same mapping everywhere makes
+//! looks match between materials (and other renderers).
 
 // std
-use std::f32::consts::PI;
+use std::sync::Mutex;
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{spherical_direction, vec3_abs_dot_vec3};
 use crate::core::geometry::{Point2f, Vector3f};
+use crate::core::paramset::TextureParams;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{erf, erf_inv};
 use crate::core::reflection::{
@@ -18,6 +30,75 @@ use crate::materials::disney::DisneyMicrofacetDistribution;
 
 // see microfacet.h
 
+lazy_static::lazy_static! {
+    static ref ROUGHNESS_MAPPING: Mutex<Option<RoughnessMapping>> = Mutex::new(None);
+}
+
+/// How *roughness* parameters map to the alpha of a microfacet
+/// distribution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoughnessMapping {
+    /// roughness is alpha
+    None,
+    /// pbrt-v3's fit (see **TrowbridgeReitzDistribution::roughness_to_alpha()**)
+    Pbrt,
+    /// alpha = sqrt(roughness), like pbrt-v4
+    Sqrt,
+    /// alpha = roughness², like Disney's principled BRDF (and most
+    /// renderers with a principled material)
+    Square,
+}
+
+impl RoughnessMapping {
+    pub fn from_name(name: &str) -> Option<RoughnessMapping> {
+        match name {
+            "none" => Some(RoughnessMapping::None),
+            "pbrt" | "pbrt-v3" => Some(RoughnessMapping::Pbrt),
+            "sqrt" | "pbrt-v4" => Some(RoughnessMapping::Sqrt),
+            "square" | "disney" => Some(RoughnessMapping::Square),
+            _ => None,
+        }
+    }
+    pub fn alpha(&self, roughness: Float) -> Float {
+        match self {
+            RoughnessMapping::None => roughness,
+            RoughnessMapping::Pbrt => TrowbridgeReitzDistribution::roughness_to_alpha(roughness),
+            RoughnessMapping::Sqrt => roughness.max(0.0 as Float).sqrt(),
+            RoughnessMapping::Square => roughness * roughness,
+        }
+    }
+}
+
+/// Overrides the default roughness mapping of all materials (parsed
+/// afterwards) which don't choose one themselves.
+pub fn set_roughness_mapping(mapping: Option<RoughnessMapping>) {
+    *ROUGHNESS_MAPPING.lock().unwrap() = mapping;
+}
+
+pub fn roughness_mapping() -> Option<RoughnessMapping> {
+    *ROUGHNESS_MAPPING.lock().unwrap()
+}
+
+/// The roughness mapping of a material (see module documentation),
+/// *default* is the one the material uses traditionally.
+pub fn find_roughness_mapping(
+    mp: &mut TextureParams,
+    default: RoughnessMapping,
+) -> RoughnessMapping {
+    let name: String = mp.find_string("roughnessmapping", String::new());
+    if !name.is_empty() {
+        match RoughnessMapping::from_name(&name) {
+            Some(mapping) => return mapping,
+            None => warn!("Roughness mapping {:?} unknown. Ignoring it.", name),
+        }
+    }
+    if !mp.find_bool("remaproughness", true) {
+        RoughnessMapping::None
+    } else {
+        roughness_mapping().unwrap_or(default)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum MicrofacetDistribution {
     Beckmann(BeckmannDistribution),
@@ -82,6 +163,38 @@ impl MicrofacetDistribution {
             }
         }
     }
+    /// The larger one of *alpha_x* and *alpha_y*.
+    pub fn alpha(&self) -> Float {
+        match self {
+            MicrofacetDistribution::Beckmann(distribution) => {
+                distribution.alpha_x.max(distribution.alpha_y)
+            }
+            MicrofacetDistribution::TrowbridgeReitz(distribution) => {
+                distribution.alpha_x.max(distribution.alpha_y)
+            }
+            MicrofacetDistribution::DisneyMicrofacet(distribution) => {
+                distribution.inner.alpha_x.max(distribution.inner.alpha_y)
+            }
+        }
+    }
+    /// Makes *alpha_x* and *alpha_y* at least *alpha* (see
+    /// **Bsdf::roughen()**).
+    pub fn roughen(&mut self, alpha: Float) {
+        let (alpha_x, alpha_y) = match self {
+            MicrofacetDistribution::Beckmann(distribution) => {
+                (&mut distribution.alpha_x, &mut distribution.alpha_y)
+            }
+            MicrofacetDistribution::TrowbridgeReitz(distribution) => {
+                (&mut distribution.alpha_x, &mut distribution.alpha_y)
+            }
+            MicrofacetDistribution::DisneyMicrofacet(distribution) => (
+                &mut distribution.inner.alpha_x,
+                &mut distribution.inner.alpha_y,
+            ),
+        };
+        *alpha_x = alpha_x.max(alpha);
+        *alpha_y = alpha_y.max(alpha);
+    }
 }
 
 #[derive(Default, Copy, Clone)]
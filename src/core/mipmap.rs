@@ -6,6 +6,7 @@
 // std
 use std;
 use std::ops::{Add, AddAssign, Div, Mul};
+use std::sync::atomic::{AtomicUsize, Ordering};
 // others
 use num;
 // pbrt
@@ -13,12 +14,26 @@ use crate::core::geometry::{Point2f, Point2i, Vector2f};
 use crate::core::memory::BlockedArray;
 use crate::core::pbrt::{clamp_t, is_power_of_2, lerp, mod_t, round_up_pow2_32};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::stats::{StatCounter, StatMemoryCounter};
 use crate::core::texture::lanczos;
 
 // see mipmap.h
 
 const WEIGHT_LUT_SIZE: usize = 128;
 
+/// Memory (in bytes) of all image pyramids created so far.
+static MIPMAP_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+static N_TRILERP_LOOKUPS: StatCounter = StatCounter::new("Texture/Trilinear MIPMap lookups");
+static N_EWA_LOOKUPS: StatCounter = StatCounter::new("Texture/EWA lookups");
+static MIPMAP_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Texture MIP maps");
+
+/// Memory (in bytes) of all image pyramids created so far (see
+/// **scenestats::SceneStats**).
+pub fn mipmap_memory() -> usize {
+    MIPMAP_MEMORY.load(Ordering::Relaxed)
+}
+
 #[derive(Debug, Clone)]
 pub enum ImageWrap {
     Repeat,
@@ -138,7 +153,7 @@ where
                     resampled_image[(t * res_pow_2.x + s) as usize] = Clampable::clamp(
                         work_data[t as usize],
                         0.0 as Float,
-                        std::f32::INFINITY as Float,
+                        Float::INFINITY,
                     );
                 }
             }
@@ -194,7 +209,9 @@ where
                 mipmap.weight_lut[i] = (-alpha * r2).exp() - (-alpha).exp();
             }
         }
-        // TODO: mipMapMemory += (4 * resolution[0] * resolution[1] * sizeof(T)) / 3;
+        let texels: usize = mipmap.pyramid.iter().map(|level| level.data.len()).sum();
+        MIPMAP_MEMORY.fetch_add(texels * std::mem::size_of::<T>(), Ordering::Relaxed);
+        MIPMAP_BYTES.add(texels * std::mem::size_of::<T>());
         mipmap
     }
     pub fn width(&self) -> i32 {
@@ -234,7 +251,7 @@ where
         &l[(ss, tt)]
     }
     pub fn lookup_pnt_flt(&self, st: Point2f, width: Float) -> T {
-        // TODO: ++nTrilerpLookups;
+        N_TRILERP_LOOKUPS.inc();
         // TODO: ProfilePhase p(Prof::TexFiltTrilerp);
         // compute MIPMap level for trilinear filtering
         let level: Float = self.levels() as Float - 1.0 as Float + width.max(1e-8 as Float).log2();
@@ -262,7 +279,7 @@ where
                 .max(dst1.x.abs().max(dst1.y.abs()));
             return self.lookup_pnt_flt(st, width);
         }
-        // TODO: ++nEWALookups;
+        N_EWA_LOOKUPS.inc();
         // TODO: ProfilePhase p(Prof::TexFiltEWA);
         // compute ellipse minor and major axes
         if dst0.length_squared() < dst1.length_squared() {
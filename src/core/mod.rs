@@ -2,8 +2,16 @@
 
 pub mod api;
 pub mod bssrdf;
+pub mod builder;
 pub mod camera;
+pub mod checkpoint;
+pub mod compat;
+pub mod deepexr;
+pub mod denoise;
+pub mod distributed;
 pub mod efloat;
+pub mod error;
+pub mod export;
 pub mod film;
 pub mod filter;
 pub mod floatfile;
@@ -11,26 +19,45 @@ pub mod geometry;
 pub mod integrator;
 pub mod interaction;
 pub mod interpolation;
+pub mod irradiancecache;
+pub mod jsonscene;
+pub mod library;
+pub mod lint;
 pub mod light;
 pub mod lightdistrib;
 pub mod lowdiscrepancy;
+pub mod lowmemory;
 pub mod material;
 pub mod medium;
 pub mod memory;
 pub mod microfacet;
 pub mod mipmap;
+pub mod numa;
 pub mod parallel;
 pub mod paramset;
+pub mod parseerror;
+pub mod parser;
+pub mod pathdebug;
 pub mod pbrt;
+pub mod poisson;
+#[cfg(feature = "polarization")]
+pub mod polarization;
 pub mod primitive;
+pub mod progress;
 pub mod quaternion;
 pub mod reflection;
 pub mod rng;
 pub mod sampler;
 pub mod sampling;
 pub mod scene;
+pub mod scenecache;
+pub mod scenestats;
 pub mod shape;
+#[cfg(feature = "simd")]
+pub mod simd;
 pub mod sobolmatrices;
 pub mod spectrum;
+pub mod stats;
 pub mod texture;
+pub mod tiledexr;
 pub mod transform;
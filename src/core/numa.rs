@@ -0,0 +1,94 @@
+//! NUMA-aware placement of render threads.
+//!
+//! On machines with several NUMA nodes (e.g. multi-socket servers)
+//! memory of another node is much slower to access than local
+//! memory. With the feature *numa* (Linux only) each tile rendering
+//! thread gets pinned to the CPUs of one node, the threads are spread
+//! round-robin over the nodes. Linux places memory on the node of the
+//! thread which touches it first, so everything a thread allocates
+//! after **pin_render_thread()** (its sampler, memory arena, and film
+//! tiles) is node-local. Without the feature (or with a single node)
+//! nothing happens.
+
+#[cfg(all(feature = "numa", target_os = "linux"))]
+lazy_static::lazy_static! {
+    /// the CPUs of each NUMA node (empty if the topology is unknown)
+    static ref NODES: Vec<Vec<usize>> = detect_nodes();
+}
+
+/// Reads the NUMA topology from sysfs. Node ids don't have to be
+/// contiguous (e.g. node0 and node2), the online ones are listed like
+/// CPUs.
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn detect_nodes() -> Vec<Vec<usize>> {
+    let mut nodes: Vec<Vec<usize>> = Vec::new();
+    let online: Vec<usize> = match std::fs::read_to_string("/sys/devices/system/node/online") {
+        Ok(online) => parse_cpu_list(&online),
+        Err(_) => return nodes,
+    };
+    for node in online {
+        let filename: String = format!("/sys/devices/system/node/node{}/cpulist", node);
+        if let Ok(cpulist) = std::fs::read_to_string(&filename) {
+            let cpus: Vec<usize> = parse_cpu_list(&cpulist);
+            // memory-only nodes have no CPUs
+            if !cpus.is_empty() {
+                nodes.push(cpus);
+            }
+        }
+    }
+    if nodes.len() > 1 {
+        log::info!("Pinning render threads to {} NUMA nodes ...", nodes.len());
+    }
+    nodes
+}
+
+/// Parses a list like "0-3,8-11" (of CPUs or nodes).
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn parse_cpu_list(cpulist: &str) -> Vec<usize> {
+    let mut cpus: Vec<usize> = Vec::new();
+    for range in cpulist.trim().split(',').filter(|range| !range.is_empty()) {
+        let mut bounds = range.splitn(2, '-').map(|cpu| cpu.parse::<usize>());
+        match (bounds.next(), bounds.next()) {
+            (Some(Ok(first)), Some(Ok(last))) => cpus.extend(first..=last),
+            (Some(Ok(cpu)), None) => cpus.push(cpu),
+            _ => return Vec::new(),
+        }
+    }
+    cpus
+}
+
+/// The number of NUMA nodes with CPUs (1 without the feature
+/// *numa*).
+#[cfg(all(feature = "numa", target_os = "linux"))]
+pub fn number_of_nodes() -> usize {
+    std::cmp::max(1, NODES.len())
+}
+
+#[cfg(not(all(feature = "numa", target_os = "linux")))]
+pub fn number_of_nodes() -> usize {
+    1
+}
+
+/// Restricts the calling thread to the CPUs of node *thread_index*
+/// modulo the number of nodes. Should be called before the thread
+/// allocates its memory. Returns **false** if the thread wasn't
+/// pinned (single node, unknown topology, or no permission).
+#[cfg(all(feature = "numa", target_os = "linux"))]
+pub fn pin_render_thread(thread_index: usize) -> bool {
+    if NODES.len() < 2 {
+        return false;
+    }
+    let cpus: &Vec<usize> = &NODES[thread_index % NODES.len()];
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in cpus {
+            libc::CPU_SET(*cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(not(all(feature = "numa", target_os = "linux")))]
+pub fn pin_render_thread(_thread_index: usize) -> bool {
+    false
+}
@@ -4,14 +4,14 @@
 // others
 use atomic::{Atomic, Ordering};
 // pbrt
-use crate::core::pbrt::Float;
 use crate::core::pbrt::{bits_to_float, float_to_bits};
+use crate::core::pbrt::{Float, FloatBits};
 
 // parallel.h
 
 #[derive(Debug)]
 pub struct AtomicFloat {
-    pub bits: Atomic<u32>,
+    pub bits: Atomic<FloatBits>,
 }
 
 impl AtomicFloat {
@@ -21,10 +21,10 @@ impl AtomicFloat {
         }
     }
     pub fn add(&self, v: Float) {
-        let mut old_bits: u32 = self.bits.load(Ordering::Relaxed);
+        let mut old_bits: FloatBits = self.bits.load(Ordering::Relaxed);
         loop {
             let f: Float = bits_to_float(old_bits);
-            let new_bits: u32 = float_to_bits(f + v);
+            let new_bits: FloatBits = float_to_bits(f + v);
             match self.bits.compare_exchange_weak(
                 old_bits,
                 new_bits,
@@ -40,11 +40,14 @@ impl AtomicFloat {
             }
         }
     }
+    pub fn store(&self, v: Float) {
+        self.bits.store(float_to_bits(v), Ordering::SeqCst);
+    }
 }
 
 impl Clone for AtomicFloat {
     fn clone(&self) -> Self {
-        let bits: u32 = self.bits.load(Ordering::SeqCst);
+        let bits: FloatBits = self.bits.load(Ordering::SeqCst);
         AtomicFloat {
             bits: Atomic::new(bits),
         }
@@ -59,7 +62,7 @@ impl Default for AtomicFloat {
 
 impl<'a> From<&'a AtomicFloat> for Float {
     fn from(a: &'a AtomicFloat) -> Float {
-        let bits: u32 = a.bits.load(Ordering::SeqCst);
+        let bits: FloatBits = a.bits.load(Ordering::SeqCst);
         bits_to_float(bits) as Float
     }
 }
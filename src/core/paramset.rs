@@ -2,21 +2,28 @@
 
 // std
 use std::collections::HashMap;
-use std::env;
-use std::fs::File;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+// others
+use log::warn;
 // pbrt
-use crate::core::floatfile::read_float_file;
+use crate::core::compat::named_spectrum;
+use crate::core::floatfile::read_spectrum_file;
 use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector2f, Vector3f};
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::spectrum::blackbody_normalized;
-use crate::core::spectrum::{CIE_LAMBDA, N_CIE_SAMPLES};
+use crate::core::spectrum::{blackbody_spectrum, std_illuminant};
 use crate::core::texture::Texture;
 use crate::textures::constant::ConstantTexture;
 
 // see paramset.h
 
+lazy_static::lazy_static! {
+    /// SPD files are often shared by many parameters (e.g. metal
+    /// materials), so read each of them only once.
+    static ref CACHED_SPECTRA: Mutex<HashMap<String, Spectrum>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone)]
 pub struct ParamSetItem<T> {
     pub name: String,
     pub values: Vec<T>,
@@ -24,7 +31,7 @@ pub struct ParamSetItem<T> {
     pub looked_up: bool, // false
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ParamSet {
     pub key_word: String,
     pub name: String,
@@ -164,44 +171,76 @@ impl ParamSet {
             looked_up: false,
         });
     }
-    pub fn add_sampled_spectrum_files(&mut self, name: String, names: Vec<String>) {
-        // TODO: cachedSpectra
+    /// Adds a spectrum given by (wavelength, value) pairs, e.g.
+    /// **"spectrum Kd" [ 300 .3  400 .6  410 .65  415 .8  500 .2  600 .1 ]**.
+    pub fn add_sampled_spectrum(&mut self, name: String, values: Vec<Float>) {
+        self.erase_spectrum(name.clone());
+        if values.len() % 2 == 1_usize {
+            warn!("Extra value found in spectrum {:?}. Ignoring it.", name);
+        }
+        let mut wls: Vec<Float> = Vec::with_capacity(values.len() / 2_usize);
+        let mut v: Vec<Float> = Vec::with_capacity(values.len() / 2_usize);
+        for pair in values.chunks_exact(2) {
+            wls.push(pair[0]);
+            v.push(pair[1]);
+        }
+        let s: Spectrum = Spectrum::from_sampled(&wls[..], &v[..], wls.len() as i32);
+        self.spectra.push(ParamSetItem::<Spectrum> {
+            name,
+            values: vec![s],
+            n_values: 1_usize,
+            looked_up: false,
+        });
+    }
+    /// Adds spectra read from SPD files (see
+    /// **floatfile::read_spectrum_file()**) or named standard
+    /// illuminants. Relative filenames are resolved against
+    /// *search_directory* (the directory of the scene file).
+    pub fn add_sampled_spectrum_files(
+        &mut self,
+        name: String,
+        names: Vec<String>,
+        search_directory: Option<&PathBuf>,
+    ) {
         self.erase_spectrum(name.clone());
         let mut s: Vec<Spectrum> = Vec::with_capacity(names.len());
         for name in &names {
-            // std::string filename = AbsolutePath(ResolveFilename(name));
-            let fn_str: &String = &name;
-            let _f = File::open(fn_str.clone()).unwrap();
-            let ip: &Path = Path::new(fn_str.as_str());
-            if ip.is_relative() {
-                let cp: PathBuf = env::current_dir().unwrap();
-                let pb: PathBuf = cp.join(ip);
-                let search_directory: &Path = pb.as_path().parent().unwrap();
+            // named spectra (e.g. "stdillum-D65") don't need a file
+            if let Some(illuminant) = std_illuminant(name) {
+                s.push(illuminant);
+                continue;
+            }
+            // as well as the named spectra of pbrt-v4 scenes
+            if let Some(spectrum) = named_spectrum(name) {
+                s.push(spectrum);
+                continue;
+            }
+            let mut filename: String = name.clone();
+            if let Some(ref search_directory) = search_directory {
+                // absolute filenames replace the search directory
                 let mut path_buf: PathBuf = PathBuf::from("/");
                 path_buf.push(search_directory);
-                path_buf.push(ip.file_name().unwrap());
-                let filename = String::from(path_buf.to_str().unwrap());
-                let mut vals: Vec<Float> = Vec::new();
-                if !read_float_file(&filename, &mut vals) {
-                    println!(
-                        "WARNING: Unable to read SPD file {:?}. Using black distribution.",
-                        filename
+                path_buf.push(name);
+                filename = String::from(path_buf.to_str().unwrap());
+            }
+            let mut cached_spectra = CACHED_SPECTRA.lock().unwrap();
+            if let Some(spectrum) = cached_spectra.get(&filename) {
+                s.push(*spectrum);
+                continue;
+            }
+            match read_spectrum_file(&filename) {
+                Ok((wls, v)) => {
+                    let spectrum: Spectrum =
+                        Spectrum::from_sampled(&wls[..], &v[..], wls.len() as i32);
+                    cached_spectra.insert(filename, spectrum);
+                    s.push(spectrum);
+                }
+                Err(err) => {
+                    warn!(
+                        "Unable to read SPD file ({}). Using black distribution.",
+                        err
                     );
                     s.push(Spectrum::default());
-                } else {
-                    if vals.len() % 2 == 1_usize {
-                        println!(
-                            "WARNING: Extra value found in spectrum file {:?}. Ignoring it.",
-                            filename
-                        );
-                    }
-                    let mut wls: Vec<Float> = Vec::new();
-                    let mut v: Vec<Float> = Vec::new();
-                    for j in 0..(vals.len() / 2_usize) {
-                        wls.push(vals[2 * j]);
-                        v.push(vals[2 * j + 1]);
-                    }
-                    s.push(Spectrum::from_sampled(&wls[..], &v[..], wls.len() as i32));
                 }
             }
         }
@@ -221,6 +260,15 @@ impl ParamSet {
             looked_up: false,
         });
     }
+    pub fn add_strings(&mut self, name: String, values: Vec<String>) {
+        let n_values: usize = values.len();
+        self.strings.push(ParamSetItem::<String> {
+            name,
+            values,
+            n_values,
+            looked_up: false,
+        });
+    }
     pub fn add_texture(&mut self, name: String, value: String) {
         self.textures.push(ParamSetItem::<String> {
             name,
@@ -290,16 +338,18 @@ impl ParamSet {
         });
     }
     pub fn add_blackbody_spectrum(&mut self, name: String, values: Vec<Float>) {
+        // a single temperature without scale
+        let values: Vec<Float> = if values.len() == 1 {
+            vec![values[0], 1.0 as Float]
+        } else {
+            values
+        };
         assert!(values.len() % 2 == 0);
         // temperature (K), scale, ...
         let n_values: usize = values.len() / 2_usize;
         let mut s: Vec<Spectrum> = Vec::with_capacity(n_values);
-        let mut v: Vec<Float> = Vec::with_capacity(N_CIE_SAMPLES as usize);
         for i in 0..n_values {
-            blackbody_normalized(&CIE_LAMBDA, N_CIE_SAMPLES as usize, values[2 * i], &mut v);
-            s.push(
-                Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32) * values[2 * i + 1],
-            );
+            s.push(blackbody_spectrum(values[2 * i], values[2 * i + 1]));
         }
         self.spectra.push(ParamSetItem::<Spectrum> {
             name,
@@ -407,6 +457,33 @@ impl ParamSet {
             });
         }
     }
+    pub fn erase_bool(&mut self, name: String) -> bool {
+        for i in 0..self.bools.len() {
+            if self.bools[i].name == name {
+                self.bools.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+    pub fn erase_int(&mut self, name: String) -> bool {
+        for i in 0..self.ints.len() {
+            if self.ints[i].name == name {
+                self.ints.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+    pub fn erase_string(&mut self, name: String) -> bool {
+        for i in 0..self.strings.len() {
+            if self.strings[i].name == name {
+                self.strings.remove(i);
+                return true;
+            }
+        }
+        false
+    }
     pub fn erase_spectrum(&mut self, name: String) -> bool {
         for i in 0..self.spectra.len() {
             if self.spectra[i].name == name {
@@ -517,6 +594,19 @@ impl ParamSet {
         }
         values
     }
+    pub fn find_string(&self, name: &str) -> Vec<String> {
+        let mut values: Vec<String> = Vec::new();
+        for v in &self.strings {
+            if v.name == name {
+                let n_values = v.n_values;
+                // v.looked_up = true;
+                for i in 0..n_values {
+                    values.push(v.values[i].clone());
+                }
+            }
+        }
+        values
+    }
     pub fn find_point2f(&self, name: &str) -> Vec<Point2f> {
         let mut values: Vec<Point2f> = Vec::new();
         for v in &self.point2fs {
@@ -599,8 +689,8 @@ impl ParamSet {
 
 #[derive(Default)]
 pub struct TextureParams {
-    pub float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>>>,
-    pub spectrum_textures: Arc<HashMap<String, Arc<dyn Texture<Spectrum> + Send + Sync>>>,
+    pub float_textures: Arc<HashMap<String, Arc<Texture<Float>>>>,
+    pub spectrum_textures: Arc<HashMap<String, Arc<Texture<Spectrum>>>>,
     pub geom_params: ParamSet,
     pub material_params: ParamSet,
 }
@@ -609,8 +699,8 @@ impl TextureParams {
     pub fn new(
         geom_params: ParamSet,
         material_params: ParamSet,
-        f_tex: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>>>,
-        s_tex: Arc<HashMap<String, Arc<dyn Texture<Spectrum> + Send + Sync>>>,
+        f_tex: Arc<HashMap<String, Arc<Texture<Float>>>>,
+        s_tex: Arc<HashMap<String, Arc<Texture<Spectrum>>>>,
     ) -> Self {
         TextureParams {
             float_textures: f_tex,
@@ -619,11 +709,7 @@ impl TextureParams {
             material_params,
         }
     }
-    pub fn get_spectrum_texture(
-        &mut self,
-        n: &str,
-        def: Spectrum,
-    ) -> Arc<dyn Texture<Spectrum> + Send + Sync> {
+    pub fn get_spectrum_texture(&mut self, n: &str, def: Spectrum) -> Arc<Texture<Spectrum>> {
         let mut name: String = self.geom_params.find_texture(n);
         if name == "" {
             name = self.material_params.find_texture(n);
@@ -641,14 +727,11 @@ impl TextureParams {
                 }
             }
         }
-        let mut val: Spectrum = self.material_params.find_one_spectrum(n.clone(), def);
-        val = self.geom_params.find_one_spectrum(n.clone(), val);
-        Arc::new(ConstantTexture { value: val })
+        let mut val: Spectrum = self.material_params.find_one_spectrum(n, def);
+        val = self.geom_params.find_one_spectrum(n, val);
+        Arc::new(Texture::Constant(ConstantTexture { value: val }))
     }
-    pub fn get_spectrum_texture_or_null(
-        &mut self,
-        n: &str,
-    ) -> Option<Arc<dyn Texture<Spectrum> + Send + Sync>> {
+    pub fn get_spectrum_texture_or_null(&mut self, n: &str) -> Option<Arc<Texture<Spectrum>>> {
         let mut name: String = self.geom_params.find_texture(n);
         if name == "" {
             name = self.material_params.find_texture(n);
@@ -657,7 +740,7 @@ impl TextureParams {
             match self.spectrum_textures.get(name.as_str()) {
                 Some(spectrum_texture) => return Some(spectrum_texture.clone()),
                 None => {
-                    println!(
+                    warn!(
                         "Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
                         name, n
                     );
@@ -672,37 +755,29 @@ impl TextureParams {
         if val.is_empty() {
             None
         } else {
-            Some(Arc::new(ConstantTexture { value: val[0] }))
+            Some(Arc::new(Texture::Constant(ConstantTexture {
+                value: val[0],
+            })))
         }
     }
-    pub fn get_float_texture(
-        &mut self,
-        n: &str,
-        def: Float,
-    ) -> Arc<dyn Texture<Float> + Send + Sync> {
+    pub fn get_float_texture(&mut self, n: &str, def: Float) -> Arc<Texture<Float>> {
         let tex_option = self.get_float_texture_or_null(n);
         if let Some(tex) = tex_option {
             tex
         } else {
             let mut val: Float = self.material_params.find_one_float(n, def);
             val = self.geom_params.find_one_float(n, val);
-            Arc::new(ConstantTexture { value: val })
+            Arc::new(Texture::Constant(ConstantTexture { value: val }))
         }
     }
-    pub fn get_float_texture_or_null(
-        &mut self,
-        n: &str,
-    ) -> Option<Arc<dyn Texture<Float> + Send + Sync>> {
+    pub fn get_float_texture_or_null(&mut self, n: &str) -> Option<Arc<Texture<Float>>> {
         let mut name: String = self.geom_params.find_texture(n);
         if name == "" {
             let s: Vec<Float> = self.geom_params.find_float(n);
             if s.len() > 1 {
-                println!(
-                    "Ignoring excess values provided with parameter \"{}\"",
-                    n.clone()
-                );
+                warn!("Ignoring excess values provided with parameter \"{}\"", n);
             } else if !s.is_empty() {
-                return Some(Arc::new(ConstantTexture { value: s[0] }));
+                return Some(Arc::new(Texture::Constant(ConstantTexture { value: s[0] })));
             }
             name = self.material_params.find_texture(n);
         }
@@ -712,7 +787,7 @@ impl TextureParams {
                     return Some(float_texture.clone());
                 }
                 None => {
-                    println!(
+                    warn!(
                         "Couldn't find float texture named \"{}\" for parameter \"{}\"",
                         name, n
                     );
@@ -727,7 +802,9 @@ impl TextureParams {
         if val.is_empty() {
             None
         } else {
-            Some(Arc::new(ConstantTexture { value: val[0] }))
+            Some(Arc::new(Texture::Constant(ConstantTexture {
+                value: val[0],
+            })))
         }
     }
     pub fn find_float(&mut self, name: &str, d: Float) -> Float {
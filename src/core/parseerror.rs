@@ -0,0 +1,70 @@
+//! Errors in scene descriptions.
+//!
+//! Instead of stopping at the first problem, the parser (and the API
+//! functions it calls) collect a **ParseError** for each statement
+//! which can't be handled in **ApiState::errors**, and keep going.
+//! Scenes with errors are not rendered, the application reports all
+//! errors at once, each with the file, line, and column of the
+//! offending token.
+
+// std
+use std::error::Error;
+use std::fmt;
+
+/// Position of a statement (or token) in a scene file. Scenes parsed
+/// from memory, or calls which didn't come from a scene file (e.g.
+/// replayed from a scene cache), have an empty filename.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Location {
+    pub filename: String,
+    /// starting at 1
+    pub line: usize,
+    /// starting at 1 (in bytes)
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let filename: &str = if self.filename.is_empty() {
+            "<input>"
+        } else {
+            &self.filename
+        };
+        if self.line == 0 {
+            write!(f, "{}", filename)
+        } else {
+            write!(f, "{}:{}:{}", filename, self.line, self.column)
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseError {
+    pub location: Location,
+    /// the offending token (can be empty)
+    pub token: String,
+    pub message: String,
+}
+
+impl ParseError {
+    /// An error without a location (see **ApiState::add_error()**).
+    pub fn new(token: &str, message: String) -> Self {
+        ParseError {
+            location: Location::default(),
+            token: String::from(token),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: error: {}", self.location, self.message)?;
+        if !self.token.is_empty() {
+            write!(f, " (at {:?})", self.token)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
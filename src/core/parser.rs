@@ -0,0 +1,1853 @@
+//! Parser for scene files (extension .pbrt, or .json for the JSON
+//! format of **jsonscene::JsonScene**).
+//!
+//! Scene files are read line by line and split into statements (see
+//! **StatementReader**), the grammar (see examples/pbrt.pest) collects
+//! the parameters of each statement in a **ParamSet**, which gets
+//! handed to the corresponding **pbrt_...()** API function (see
+//! **scenecache::ApiCall**). Large numeric arrays (e.g. of triangle
+//! meshes) are converted while reading, so the text of a scene file is
+//! never kept in memory as a whole.
+//!
+//! Errors don't stop the parser, statements with errors are skipped
+//! and the errors (with their location) get collected in
+//! **ApiState::errors** (see **parseerror**).
+
+use pest_derive::*;
+
+#[derive(Parser)]
+#[grammar = "../examples/pbrt.pest"]
+struct PbrtParser;
+
+// parser
+use pest::Parser;
+
+// std
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+// others
+use log::{debug, info, warn};
+// pbrt
+use crate::core::api::{ApiState, BsdfState};
+use crate::core::compat::convert_v4_params;
+use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use crate::core::jsonscene::JsonScene;
+use crate::core::library::{rename_imported, Namespace};
+use crate::core::memory::readable_path;
+use crate::core::paramset::ParamSet;
+use crate::core::parseerror::{Location, ParseError};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::scenecache::{ApiCall, SceneCache};
+use crate::core::transform::Transform;
+
+pub enum RecordMode {
+    /// write the binary scene cache to a file (before rendering starts)
+    Cache(String),
+    /// only record the calls, don't execute (and render) them
+    Record,
+    /// record and execute the calls (e.g. to know which files the
+    /// scene uses)
+    Execute,
+}
+
+/// Collects the API calls of the scene (for the binary scene cache,
+/// the exporter, or the watch mode) while it gets parsed.
+pub struct Recorder {
+    pub cache: SceneCache,
+    pub mode: RecordMode,
+    /// where each of the recorded calls came from (e.g. for
+    /// **lint::lint_scene()**)
+    pub locations: Vec<Location>,
+}
+
+impl Recorder {
+    pub fn new(mode: RecordMode) -> Self {
+        Recorder {
+            cache: SceneCache::new(),
+            mode,
+            locations: Vec::new(),
+        }
+    }
+    /// Writes the scene cache (for **RecordMode::Cache**).
+    pub fn write(&self) {
+        if let RecordMode::Cache(ref filename) = self.mode {
+            match self.cache.write(filename) {
+                Ok(()) => info!("Scene cache written to {:?}", filename),
+                Err(err) => warn!("Unable to write scene cache {:?}: {}", filename, err),
+            }
+        }
+    }
+}
+
+/// Variables for the **${name}** substitution in scene files.
+#[derive(Default)]
+pub struct Variables {
+    pub values: HashMap<String, String>,
+    /// defined on the command line, can't be redefined by scene files
+    pub overrides: HashSet<String>,
+}
+
+impl Variables {
+    /// The variables defined on the command line (-Dname=value),
+    /// invalid definitions are reported in **ApiState::errors**.
+    pub fn from_defines(defines: &[String], api_state: &mut ApiState) -> Self {
+        let mut variables: Variables = Variables::default();
+        for define in defines {
+            let name_value: Vec<&str> = define.splitn(2, '=').collect();
+            if name_value.len() != 2 || name_value[0].is_empty() {
+                api_state.errors.push(ParseError {
+                    location: Location {
+                        filename: String::from("<command line>"),
+                        line: 0,
+                        column: 0,
+                    },
+                    token: format!("-D{}", define),
+                    message: String::from("expected -Dname=value"),
+                });
+                continue;
+            }
+            variables
+                .values
+                .insert(String::from(name_value[0]), String::from(name_value[1]));
+            variables.overrides.insert(String::from(name_value[0]));
+        }
+        variables
+    }
+    /// Replaces each **${name}** in the text of *statement* by the
+    /// value the variable has when the statement gets executed (so a
+    /// **Define** in an included file is visible after the
+    /// **Include**).
+    fn expand(&self, statement: &mut Statement) -> Result<(), ParseError> {
+        if !statement.text.contains("${") {
+            return Ok(());
+        }
+        let source: &mut SourceMap = &mut statement.source;
+        let text: &str = &statement.text;
+        let mut result: String = String::with_capacity(text.len());
+        // (end of the variable in *text*, end of its value in *result*)
+        let mut substitutions: Vec<(usize, usize)> = Vec::new();
+        let mut rest: &str = text;
+        while let Some(start) = rest.find("${") {
+            let offset: usize = text.len() - rest.len() + start;
+            result += &rest[..start];
+            let after: &str = &rest[start + 2..];
+            let end: usize = match after.find('}') {
+                Some(end) => end,
+                None => {
+                    return Err(source.error(
+                        offset,
+                        "${",
+                        String::from("missing '}' after \"${\""),
+                    ))
+                }
+            };
+            let name: &str = &after[..end];
+            match self.values.get(name) {
+                Some(value) => result += value,
+                None => {
+                    return Err(source.error(
+                        offset,
+                        &rest[start..start + end + 3],
+                        format!("undefined variable {:?}", name),
+                    ))
+                }
+            }
+            rest = &after[end + 1..];
+            substitutions.push((text.len() - rest.len(), result.len()));
+        }
+        result += rest;
+        // the text after each value continues where the variable ended
+        let mut segments: Vec<(usize, usize, usize)> = Vec::new();
+        let mut shift: isize = 0;
+        let mut next: usize = 0;
+        for &(start, line, column) in &source.segments {
+            let text_start: usize = start.saturating_sub(source.trimmed);
+            while next < substitutions.len() && substitutions[next].0 <= text_start {
+                let (old_end, new_end) = substitutions[next];
+                let location: Location = source.location_at(old_end);
+                segments.push((new_end + source.trimmed, location.line, location.column));
+                shift = new_end as isize - old_end as isize;
+                next += 1;
+            }
+            segments.push(((start as isize + shift).max(0) as usize, line, column));
+        }
+        for &(old_end, new_end) in &substitutions[next..] {
+            let location: Location = source.location_at(old_end);
+            segments.push((new_end + source.trimmed, location.line, location.column));
+        }
+        segments.sort_by_key(|s| s.0);
+        source.segments = segments;
+        statement.text = result;
+        Ok(())
+    }
+    /// Handles a variable definition (**Define "name" "value"**), the
+    /// name and value can be with or without double quotes. Variables
+    /// defined on the command line keep their value.
+    fn define(&mut self, statement: &Statement) -> Result<(), ParseError> {
+        let rest: &str = statement.text.as_str();
+        let (name, value): (&str, &str) = if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => ("", ""),
+            }
+        } else {
+            match rest.find(char::is_whitespace) {
+                Some(end) => (&rest[..end], &rest[end..]),
+                None => (rest, ""),
+            }
+        };
+        let value: &str = value.trim();
+        let value: &str = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            &value[1..value.len() - 1]
+        } else {
+            value
+        };
+        if name.is_empty() || value.is_empty() {
+            return Err(ParseError {
+                location: statement.source.location.clone(),
+                token: String::from("Define"),
+                message: String::from("Define expects a name and a value"),
+            });
+        }
+        if !self.overrides.contains(name) {
+            self.values.insert(String::from(name), String::from(value));
+        }
+        Ok(())
+    }
+}
+
+/// The identifiers which start a new statement (see examples/pbrt.pest).
+const IDENTIFIERS: [&str; 42] = [
+    "AttributeBegin",
+    "AttributeEnd",
+    "Attribute",
+    "ActiveTransform",
+    "AreaLightSource",
+    "Accelerator",
+    "ConcatTransform",
+    "CoordinateSystem",
+    "CoordSysTransform",
+    "ColorSpace",
+    "Camera",
+    "Define",
+    "Film",
+    "Integrator",
+    "Include",
+    "Import",
+    "Identity",
+    "LightSource",
+    "LookAt",
+    "MakeNamedMaterial",
+    "MakeNamedMedium",
+    "Material",
+    "MediumInterface",
+    "NamedMaterial",
+    "ObjectBegin",
+    "ObjectEnd",
+    "ObjectInstance",
+    "Option",
+    "PixelFilter",
+    "ReverseOrientation",
+    "Rotate",
+    "Shape",
+    "Sampler",
+    "Scale",
+    "TransformBegin",
+    "TransformEnd",
+    "Transform",
+    "Translate",
+    "TransformTimes",
+    "Texture",
+    "WorldBegin",
+    "WorldEnd",
+];
+
+/// Arrays with more values are converted while reading (see
+/// **NumericArray**), smaller ones are left to the grammar.
+const MAX_PARSED_ARRAY_SIZE: usize = 256;
+
+/// A numeric parameter array (e.g. the vertices or indices of a
+/// triangle mesh). The numbers are converted while the scene file
+/// gets read, instead of being handed to the grammar as text.
+struct NumericArray {
+    param_type: String,
+    name: String,
+    floats: Vec<Float>,
+    integers: Vec<i32>,
+    /// where the parameter starts in **StatementReader::text** (as
+    /// long as the array is small enough to stay there)
+    text_start: Option<usize>,
+    /// contains variables (see **Variables::expand()**), the text
+    /// gets parsed after they were substituted
+    deferred: bool,
+    /// of the opening bracket
+    location: Location,
+}
+
+impl NumericArray {
+    /// Returns **None** if *type_name* (e.g. "point3 P") isn't of a
+    /// type with an arbitrary number of values.
+    fn new(type_name: &str, text_start: usize) -> Option<Self> {
+        let mut words = type_name.trim().splitn(2, char::is_whitespace);
+        let param_type: &str = words.next()?;
+        let name: &str = words.next()?.trim();
+        let param_type: &str = match param_type {
+            "float" | "integer" | "point2" => param_type,
+            "point" | "point3" => "point",
+            "normal" | "normal3" => "normal",
+            _ => return None,
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(NumericArray {
+            param_type: String::from(param_type),
+            name: String::from(name),
+            floats: Vec::new(),
+            integers: Vec::new(),
+            text_start: Some(text_start),
+            deferred: false,
+            location: Location::default(),
+        })
+    }
+    fn len(&self) -> usize {
+        self.floats.len() + self.integers.len()
+    }
+    fn push(&mut self, token: &str) -> Result<(), String> {
+        if self.param_type == "integer" {
+            match i32::from_str(token) {
+                Ok(integer) => self.integers.push(integer),
+                Err(_) => return Err(format!("expected an integer (parameter {:?})", self.name)),
+            }
+        } else {
+            match Float::from_str(token) {
+                Ok(float) => self.floats.push(float),
+                Err(_) => return Err(format!("expected a number (parameter {:?})", self.name)),
+            }
+        }
+        Ok(())
+    }
+    fn add_to(self, params: &mut ParamSet) {
+        if self.param_type == "integer" {
+            add_integer_param(params, self.name, self.integers);
+        } else {
+            add_float_param(params, &self.param_type, self.name, self.floats);
+        }
+    }
+}
+
+/// Maps the text of a statement back to lines and columns of the
+/// scene file (for error messages).
+#[derive(Default)]
+struct SourceMap {
+    /// of the identifier
+    location: Location,
+    /// bytes trimmed from the start of the text
+    trimmed: usize,
+    /// (offset in the text, line, column), the text is a copy of the
+    /// scene file from each offset until the next one
+    segments: Vec<(usize, usize, usize)>,
+}
+
+impl SourceMap {
+    /// Location of the byte at *offset* in the text of the statement.
+    fn location_at(&self, offset: usize) -> Location {
+        let offset: usize = offset + self.trimmed;
+        match self.segments.iter().rev().find(|s| s.0 <= offset) {
+            Some(&(start, line, column)) => Location {
+                filename: self.location.filename.clone(),
+                line,
+                column: column + offset - start,
+            },
+            None => self.location.clone(),
+        }
+    }
+    /// An error at *offset* in the text of the statement.
+    fn error(&self, offset: usize, token: &str, message: String) -> ParseError {
+        ParseError {
+            location: self.location_at(offset),
+            token: String::from(token),
+            message,
+        }
+    }
+}
+
+/// A complete statement: the identifier, the remaining text (without
+/// comments), and the numeric arrays which were taken out of the text.
+struct Statement {
+    identifier: String,
+    text: String,
+    arrays: Vec<NumericArray>,
+    source: SourceMap,
+}
+
+/// Splits a scene description into statements while it gets read
+/// line by line, so neither the scene file nor the text of large
+/// parameter arrays has to be kept in memory.
+#[derive(Default)]
+struct StatementReader {
+    identifier: String,
+    text: String,
+    arrays: Vec<NumericArray>,
+    /// the numeric array which is currently read (if any)
+    array: Option<NumericArray>,
+    /// start and end (in **text**) of the last string
+    last_string: Option<(usize, usize)>,
+    filename: String,
+    /// number of the current line (starting at 1)
+    line: usize,
+    source: SourceMap,
+    /// the current statement contains errors and gets skipped
+    failed: bool,
+    errors: Vec<ParseError>,
+}
+
+/// Tokens end at whitespace, strings, comments, and brackets.
+fn token_end(bytes: &[u8], start: usize) -> usize {
+    let mut end: usize = start;
+    while end < bytes.len() {
+        match bytes[end] {
+            b'"' | b'#' | b'[' | b']' => break,
+            c if c.is_ascii_whitespace() => break,
+            _ => end += 1,
+        }
+    }
+    end
+}
+
+/// The first token of *text* (a whole string, a bracket, or up to the
+/// next whitespace), for error messages.
+fn first_token(text: &str) -> &str {
+    if let Some(string) = text.strip_prefix('"') {
+        match string.find('"') {
+            Some(end) => &text[..end + 2],
+            None => text,
+        }
+    } else {
+        let end: usize = token_end(text.as_bytes(), 0);
+        if end == 0 {
+            &text[..text.len().min(1)]
+        } else {
+            &text[..end]
+        }
+    }
+}
+
+impl StatementReader {
+    fn new(filename: &str) -> Self {
+        let mut reader: StatementReader = StatementReader {
+            filename: String::from(filename),
+            ..Default::default()
+        };
+        reader.source.location.filename = String::from(filename);
+        reader
+    }
+    fn location(&self, column: usize) -> Location {
+        Location {
+            filename: self.filename.clone(),
+            line: self.line,
+            column,
+        }
+    }
+    /// Records an error in the current line, the statement gets
+    /// skipped.
+    fn add_error(&mut self, column: usize, token: &str, message: String) {
+        self.errors.push(ParseError {
+            location: self.location(column),
+            token: String::from(token),
+            message,
+        });
+        self.failed = true;
+    }
+    /// Reads the next line (variables get substituted later, see
+    /// **Variables::expand()**), the statements which are complete
+    /// get added to *statements*.
+    fn read_line(&mut self, line: &str, statements: &mut Vec<Statement>) {
+        let bytes: &[u8] = line.as_bytes();
+        self.source.segments.push((self.text.len(), self.line, 1));
+        let mut i: usize = 0;
+        while i < bytes.len() {
+            if self.array.is_some() {
+                match bytes[i] {
+                    b'#' => break,
+                    b']' => {
+                        let array: NumericArray = self.array.take().unwrap();
+                        i += 1;
+                        if array.text_start.is_some() {
+                            // small array, the text gets parsed later
+                            self.text.push(']');
+                        } else {
+                            self.arrays.push(array);
+                            self.source
+                                .segments
+                                .push((self.text.len(), self.line, i + 1));
+                        }
+                    }
+                    b'"' | b'[' => {
+                        self.add_error(
+                            i + 1,
+                            first_token(&line[i..]),
+                            String::from("expected a number or ']'"),
+                        );
+                        // read the rest as usual
+                        self.array = None;
+                    }
+                    c if c.is_ascii_whitespace() => {
+                        if let Some(NumericArray {
+                            text_start: Some(_),
+                            ..
+                        }) = self.array
+                        {
+                            self.text.push(' ');
+                        }
+                        i += 1;
+                    }
+                    _ => {
+                        let end: usize = token_end(bytes, i);
+                        let token: &str = &line[i..end];
+                        if IDENTIFIERS.contains(&token) {
+                            // the next statement starts here
+                            self.missing_bracket();
+                            continue;
+                        }
+                        let array: &mut NumericArray = self.array.as_mut().unwrap();
+                        if token.contains("${") {
+                            if array.text_start.is_some() {
+                                array.deferred = true;
+                                self.text += token;
+                            } else {
+                                self.add_error(
+                                    i + 1,
+                                    token,
+                                    format!(
+                                        "variables are only allowed in arrays of up to {} values",
+                                        MAX_PARSED_ARRAY_SIZE
+                                    ),
+                                );
+                                // read the rest as usual
+                                self.array = None;
+                            }
+                        } else if let Err(message) = array.push(token) {
+                            self.add_error(i + 1, token, message);
+                            // read the rest as usual
+                            self.array = None;
+                        } else if let Some(start) = array.text_start {
+                            if array.len() > MAX_PARSED_ARRAY_SIZE && !array.deferred {
+                                // from now on only keep the numbers
+                                self.text.truncate(start);
+                                self.source.segments.retain(|s| s.0 <= start);
+                                array.text_start = None;
+                            } else {
+                                self.text += token;
+                            }
+                        }
+                        i = end;
+                    }
+                }
+                continue;
+            }
+            match bytes[i] {
+                b'#' => break,
+                b'"' => {
+                    let end: usize = match line[i + 1..].find('"') {
+                        Some(pos) => i + pos + 2,
+                        None => {
+                            self.add_error(
+                                i + 1,
+                                line[i..].trim_end(),
+                                String::from("missing '\"' at the end of the string"),
+                            );
+                            bytes.len()
+                        }
+                    };
+                    let start: usize = self.text.len();
+                    self.text += &line[i..end];
+                    self.last_string = Some((start, self.text.len()));
+                    i = end;
+                }
+                b'[' => {
+                    self.array = self.numeric_array();
+                    let location: Location = self.location(i + 1);
+                    if let Some(ref mut array) = self.array {
+                        array.location = location;
+                    }
+                    self.text.push('[');
+                    i += 1;
+                }
+                b']' => {
+                    self.text.push(']');
+                    i += 1;
+                }
+                c if c.is_ascii_whitespace() => {
+                    self.text.push(' ');
+                    i += 1;
+                }
+                _ => {
+                    let end: usize = token_end(bytes, i);
+                    let token: &str = &line[i..end];
+                    if IDENTIFIERS.contains(&token) {
+                        if let Some(statement) = self.finish() {
+                            statements.push(statement);
+                        }
+                        self.identifier = String::from(token);
+                        self.source.location = self.location(i + 1);
+                        self.source.segments.push((0, self.line, end + 1));
+                    } else {
+                        self.text += token;
+                    }
+                    i = end;
+                }
+            }
+        }
+        // a line break separates tokens
+        self.text.push(' ');
+    }
+    /// Checks if the opening bracket (just read) starts an array of
+    /// numbers, the string in front of it is the type and name.
+    fn numeric_array(&mut self) -> Option<NumericArray> {
+        let (start, end) = self.last_string?;
+        if self.identifier == "Option" || self.text.trim_end().len() != end {
+            return None;
+        }
+        let string: &str = &self.text[start..end];
+        if string.len() < 2 || !string.ends_with('"') {
+            return None;
+        }
+        let array: NumericArray = NumericArray::new(&string[1..string.len() - 1], start)?;
+        self.last_string = None;
+        Some(array)
+    }
+    /// Records an error if a numeric array is still open.
+    fn missing_bracket(&mut self) {
+        if let Some(array) = self.array.take() {
+            self.errors.push(ParseError {
+                location: array.location,
+                token: String::from("["),
+                message: format!("missing ']' after the values of parameter {:?}", array.name),
+            });
+            self.failed = true;
+        }
+    }
+    /// Returns the statement read so far (if any). Statements with
+    /// errors are skipped.
+    fn finish(&mut self) -> Option<Statement> {
+        self.missing_bracket();
+        self.last_string = None;
+        let text: String = String::from(self.text.trim());
+        let trimmed: usize = self.text.len() - self.text.trim_start().len();
+        self.text.clear();
+        let arrays: Vec<NumericArray> = self.arrays.drain(..).collect();
+        let mut source: SourceMap = std::mem::take(&mut self.source);
+        self.source.location.filename = self.filename.clone();
+        source.trimmed = trimmed;
+        let identifier: String = std::mem::take(&mut self.identifier);
+        if self.failed {
+            self.failed = false;
+            return None;
+        }
+        if identifier.is_empty() {
+            if !text.is_empty() {
+                self.errors.push(source.error(
+                    0,
+                    first_token(&text),
+                    String::from("unknown identifier"),
+                ));
+            }
+            return None;
+        }
+        Some(Statement {
+            identifier,
+            text,
+            arrays,
+            source,
+        })
+    }
+}
+
+fn execute(
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+    call: ApiCall,
+) {
+    // calls of imported libraries use prefixed names
+    let call: ApiCall = match rename_imported(&mut api_state.imports, call) {
+        Ok(call) => call,
+        Err(error) => {
+            api_state.add_error(error);
+            return;
+        }
+    };
+    if let Some(ref mut recording) = recorder {
+        recording.locations.push(api_state.location.clone());
+        if let RecordMode::Record = recording.mode {
+            recording.cache.calls.push(call);
+            return;
+        }
+        recording.cache.calls.push(call.clone());
+    }
+    if let ApiCall::WorldEnd = call {
+        // write the cache before rendering starts
+        if let Some(Recorder {
+            mode: RecordMode::Cache(_),
+            ..
+        }) = recorder
+        {
+            if let Some(recording) = recorder.take() {
+                if api_state.errors.is_empty() {
+                    recording.write();
+                } else {
+                    warn!("Not writing scene cache because of errors.");
+                }
+            }
+        }
+    }
+    call.execute(api_state, bsdf_state);
+}
+
+// Accelerator
+// CoordinateSystem
+// Identity
+// TransformTimes
+
+fn pbrt_bool_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, bool) {
+    // single string with or without brackets
+    let ident = pairs.next();
+    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let option = pairs.next();
+    let lbrack = option.clone().unwrap();
+    let pair = if lbrack.as_str() == "[" {
+        // check for brackets
+        pairs.next().unwrap()
+    } else {
+        // no brackets
+        lbrack
+    };
+    let string2 = if pair.as_rule() == Rule::bool_literal {
+        // pbrt-v4 (without quotes)
+        String::from(pair.as_str())
+    } else {
+        let ident = pair.into_inner().next();
+        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
+    };
+    // return boolean (instead of string)
+    let b: bool;
+    if string2 == "true" {
+        b = true;
+    } else if string2 == "false" {
+        b = false
+    } else {
+        warn!(
+            "parameter {:?} not well defined, defaulting to false",
+            string
+        );
+        b = false
+    }
+    (string, b)
+}
+
+fn pbrt_float_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<Float>) {
+    let mut floats: Vec<Float> = Vec::new();
+    // single float or several floats using brackets
+    let ident = pairs.next();
+    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let option = pairs.next();
+    let lbrack = option.clone().unwrap();
+    if lbrack.as_str() == "[" {
+        // check for brackets
+        let mut number = pairs.next();
+        while number.is_some() {
+            let pair = number.unwrap().clone();
+            if pair.as_str() == "]" {
+                // closing bracket found
+                break;
+            } else {
+                let float: Float = Float::from_str(pair.as_span().as_str()).unwrap();
+                floats.push(float);
+            }
+            number = pairs.next();
+        }
+    } else {
+        // no brackets
+        let mut number = option.clone();
+        while number.is_some() {
+            let pair = number.unwrap().clone();
+            let float: Float = Float::from_str(pair.as_span().as_str()).unwrap();
+            floats.push(float);
+            number = pairs.next();
+        }
+    }
+    (string, floats)
+}
+
+fn pbrt_integer_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<i32>) {
+    let mut integers: Vec<i32> = Vec::new();
+    // single integer or several integers using brackets
+    let ident = pairs.next();
+    let string: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let option = pairs.next();
+    let lbrack = option.clone().unwrap();
+    if lbrack.as_str() == "[" {
+        // check for brackets
+        let mut number = pairs.next();
+        while number.is_some() {
+            let pair = number.unwrap().clone();
+            if pair.as_str() == "]" {
+                // closing bracket found
+                break;
+            } else {
+                let integer: i32 = i32::from_str(pair.as_span().as_str()).unwrap();
+                integers.push(integer);
+            }
+            number = pairs.next();
+        }
+    } else {
+        // no brackets
+        let mut number = option.clone();
+        while number.is_some() {
+            let pair = number.unwrap().clone();
+            let integer: i32 = i32::from_str(pair.as_span().as_str()).unwrap();
+            integers.push(integer);
+            number = pairs.next();
+        }
+    }
+    (string, integers)
+}
+
+fn pbrt_strings_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<String>) {
+    // one or more strings with or without brackets
+    let ident = pairs.next();
+    let string1: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let mut strings: Vec<String> = Vec::new();
+    for pair in pairs {
+        if pair.as_rule() == Rule::string {
+            let ident = pair.into_inner().next();
+            strings.push(String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap());
+        }
+    }
+    (string1, strings)
+}
+
+fn pbrt_texture_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, String) {
+    // single string with or without brackets
+    let ident = pairs.next();
+    let string1: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let option = pairs.next();
+    let lbrack = option.clone().unwrap();
+    let string2 = if lbrack.as_str() == "[" {
+        // check for brackets
+        let string = pairs.next();
+        let pair = string.unwrap().clone();
+        let ident = pair.into_inner().next();
+        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
+    } else {
+        // no brackets
+        let string = option.clone();
+        let pair = string.unwrap().clone();
+        let ident = pair.into_inner().next();
+        String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap()
+    };
+    (string1, string2)
+}
+
+/// Adds a **float**, **point**, **point2**, or **normal** parameter
+/// (a single value or an array).
+fn add_float_param(params: &mut ParamSet, param_type: &str, name: String, floats: Vec<Float>) {
+    match param_type {
+        "float" => {
+            if floats.len() == 1 {
+                params.add_float(name, floats[0]);
+            } else {
+                params.add_floats(name, floats);
+            }
+        }
+        "point" => {
+            if floats.len() == 3 {
+                params.add_point3f(
+                    name,
+                    Point3f {
+                        x: floats[0],
+                        y: floats[1],
+                        z: floats[2],
+                    },
+                );
+            } else {
+                params.add_point3fs(name, floats);
+            }
+        }
+        "point2" => {
+            if floats.len() == 2 {
+                params.add_point2f(
+                    name,
+                    Point2f {
+                        x: floats[0],
+                        y: floats[1],
+                    },
+                );
+            } else {
+                params.add_point2fs(name, floats);
+            }
+        }
+        "normal" => {
+            if floats.len() == 3 {
+                params.add_normal3f(
+                    name,
+                    Normal3f {
+                        x: floats[0],
+                        y: floats[1],
+                        z: floats[2],
+                    },
+                );
+            } else {
+                params.add_normal3fs(name, floats);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Adds an **integer** parameter (a single value or an array).
+fn add_integer_param(params: &mut ParamSet, name: String, integers: Vec<i32>) {
+    if integers.len() == 1 {
+        params.add_int(name, integers[0]);
+    } else {
+        params.add_ints(name, integers);
+    }
+}
+
+fn extract_params(
+    key_word: String,
+    pairs: pest::iterators::Pair<Rule>,
+    search_directory: Option<&PathBuf>,
+    arrays: Vec<NumericArray>,
+) -> ParamSet {
+    let mut params: ParamSet = ParamSet {
+        key_word,
+        ..Default::default()
+    };
+    let mut counter: u8 = 0_u8;
+    for pair in pairs.into_inner() {
+        // let span = pair.clone().as_span();
+        // println!("Rule:    {:?}", pair.as_rule());
+        // println!("Span:    {:?}", span);
+        // println!("Text:    {}", span.as_str());
+        match pair.as_rule() {
+            Rule::identifier => {
+                // ignore (was added above)
+            }
+            Rule::empty_string => {}
+            Rule::string => {
+                match counter {
+                    0 => {
+                        // name
+                        let mut string_pairs = pair.into_inner();
+                        let ident = string_pairs.next();
+                        params.name =
+                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+                    }
+                    1 => {
+                        // tex_type
+                        let mut string_pairs = pair.into_inner();
+                        let ident = string_pairs.next();
+                        params.tex_type =
+                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+                    }
+                    2 => {
+                        // tex_name
+                        let mut string_pairs = pair.into_inner();
+                        let ident = string_pairs.next();
+                        params.tex_name =
+                            String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+                    }
+                    _ => unreachable!(),
+                };
+                counter += 1_u8;
+            }
+            Rule::type_name => {
+                // name
+                let mut string_pairs = pair.into_inner();
+                let ident = string_pairs.next();
+                params.name = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+            }
+            Rule::file_name => {
+                // name
+                let mut string_pairs = pair.into_inner();
+                let ident = string_pairs.next();
+                params.name = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+            }
+            Rule::parameter => {
+                for parameter_pair in pair.into_inner() {
+                    // println!("DEBUG: {:?}", parameter_pair.as_rule());
+                    match parameter_pair.as_rule() {
+                        Rule::bool_param => {
+                            let tuple: (String, bool) =
+                                pbrt_bool_parameter(&mut parameter_pair.into_inner());
+                            let string: String = tuple.0;
+                            let b: bool = tuple.1;
+                            params.add_bool(string, b);
+                        }
+                        Rule::blackbody_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            let string: String = tuple.0;
+                            let floats: Vec<Float> = tuple.1;
+                            params.add_blackbody_spectrum(string, floats);
+                        }
+                        Rule::float_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            add_float_param(&mut params, "float", tuple.0, tuple.1);
+                        }
+                        Rule::integer_param => {
+                            let tuple: (String, Vec<i32>) =
+                                pbrt_integer_parameter(&mut parameter_pair.into_inner());
+                            add_integer_param(&mut params, tuple.0, tuple.1);
+                        }
+                        Rule::point_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            add_float_param(&mut params, "point", tuple.0, tuple.1);
+                        }
+                        Rule::point2_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            add_float_param(&mut params, "point2", tuple.0, tuple.1);
+                        }
+                        Rule::normal_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            add_float_param(&mut params, "normal", tuple.0, tuple.1);
+                        }
+                        Rule::rgb_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            let string: String = tuple.0;
+                            let floats: Vec<Float> = tuple.1;
+                            params.add_rgb_spectrum(
+                                string,
+                                Spectrum::rgb(floats[0], floats[1], floats[2]),
+                            );
+                        }
+                        Rule::spectrum_param => {
+                            // "spectrum Kd" [ 300 .3  400 .6   410 .65  415 .8  500 .2  600 .1 ]
+                            // or
+                            // "spectrum Kd" "filename"
+                            let is_sampled: bool = parameter_pair
+                                .clone()
+                                .into_inner()
+                                .any(|pair| pair.as_rule() == Rule::number);
+                            if is_sampled {
+                                let tuple: (String, Vec<Float>) =
+                                    pbrt_float_parameter(&mut parameter_pair.into_inner());
+                                params.add_sampled_spectrum(tuple.0, tuple.1);
+                            } else {
+                                let tuple: (String, Vec<String>) =
+                                    pbrt_strings_parameter(&mut parameter_pair.into_inner());
+                                params.add_sampled_spectrum_files(
+                                    tuple.0,
+                                    tuple.1,
+                                    search_directory,
+                                );
+                            }
+                        }
+                        Rule::string_param => {
+                            let tuple: (String, Vec<String>) =
+                                pbrt_strings_parameter(&mut parameter_pair.into_inner());
+                            let string: String = tuple.0;
+                            let strings: Vec<String> = tuple.1;
+                            params.add_strings(string, strings);
+                        }
+                        Rule::texture_param => {
+                            let tuple: (String, String) =
+                                pbrt_texture_parameter(&mut parameter_pair.into_inner());
+                            let string1: String = tuple.0;
+                            let string2: String = tuple.1;
+                            params.add_texture(string1, string2);
+                        }
+                        Rule::vector_param => {
+                            let tuple: (String, Vec<Float>) =
+                                pbrt_float_parameter(&mut parameter_pair.into_inner());
+                            let string: String = tuple.0;
+                            let floats: Vec<Float> = tuple.1;
+                            if floats.len() == 3 {
+                                params.add_vector3f(
+                                    string,
+                                    Vector3f {
+                                        x: floats[0],
+                                        y: floats[1],
+                                        z: floats[2],
+                                    },
+                                );
+                            } else {
+                                params.add_vector3fs(string, floats);
+                            }
+                        }
+                        // TODO: more rules
+                        _ => debug!("TODO: {:?}", parameter_pair.as_rule()),
+                    }
+                }
+            }
+            _ => debug!("TODO: {:?}", pair.as_rule()),
+        }
+    }
+    // numeric arrays which were converted while reading
+    for array in arrays {
+        array.add_to(&mut params);
+    }
+    params
+}
+
+fn parse_line(
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+    variables: &mut Variables,
+    mut statement: Statement,
+) {
+    api_state.location = statement.source.location.clone();
+    if let Err(error) = variables.expand(&mut statement) {
+        api_state.errors.push(error);
+        return;
+    }
+    if statement.identifier == "Define" {
+        if let Err(error) = variables.define(&statement) {
+            api_state.errors.push(error);
+        }
+        return;
+    }
+    let Statement {
+        identifier,
+        text: str_buf,
+        mut arrays,
+        source,
+    } = statement;
+    let identifier: &str = identifier.as_str();
+    api_state.location = source.location.clone();
+    if str_buf.is_empty() {
+        // no additional arguments
+        match identifier {
+            "AttributeBegin" => {
+                // AttributeBegin
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::AttributeBegin);
+            }
+            "AttributeEnd" => {
+                // AttributeEnd
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::AttributeEnd);
+            }
+            "ObjectEnd" => {
+                // ObjectEnd
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::ObjectEnd);
+            }
+            "ReverseOrientation" => {
+                // ReverseOrientation
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::ReverseOrientation);
+            }
+            "TransformBegin" => {
+                // TransformBegin
+                execute(api_state, bsdf_state, recorder, ApiCall::TransformBegin);
+            }
+            "TransformEnd" => {
+                // TransformEnd
+                execute(api_state, bsdf_state, recorder, ApiCall::TransformEnd);
+            }
+            "WorldBegin" => {
+                // WorldBegin
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::WorldBegin);
+            }
+            "WorldEnd" => {
+                // WorldEnd
+                // println!("{} {}", identifier, str_buf);
+                execute(api_state, bsdf_state, recorder, ApiCall::WorldEnd);
+            }
+            "Identity" => debug!("{} {:?}", identifier, str_buf),
+            _ => api_state.errors.push(ParseError {
+                location: source.location.clone(),
+                token: String::from(identifier),
+                message: format!("missing arguments for {}", identifier),
+            }),
+        }
+    } else {
+        let statement = String::from(identifier) + " " + &str_buf;
+        // println!("DEBUG: {:?}", &statement);
+        // errors at a position in the statement (after the identifier)
+        let error_at = |pos: usize, message: String| -> ParseError {
+            let offset: usize = pos.saturating_sub(identifier.len() + 1);
+            source.error(offset, first_token(&str_buf[offset..]), message)
+        };
+        let pairs = match PbrtParser::parse(Rule::name_and_or_params, &statement) {
+            Ok(mut pairs) => pairs.next().unwrap(),
+            Err(err) => {
+                let pos: usize = match err.location {
+                    pest::error::InputLocation::Pos(pos) => pos,
+                    pest::error::InputLocation::Span((start, _end)) => start,
+                };
+                api_state
+                    .errors
+                    .push(error_at(pos, format!("invalid {} statement", identifier)));
+                return;
+            }
+        };
+        // the grammar has to consume the whole statement
+        let rest: &str = statement[pairs.as_span().end()..].trim_start();
+        if !rest.is_empty() {
+            api_state.errors.push(error_at(
+                statement.len() - rest.len(),
+                format!("unexpected token in {} statement", identifier),
+            ));
+            return;
+        }
+        for inner_pair in pairs.into_inner() {
+            // println!("DEBUG: {:?}", inner_pair.as_rule());
+            match inner_pair.as_rule() {
+                Rule::type_params => {
+                    // identifier "type" parameter-list
+                    let for_printing = inner_pair.as_str();
+                    // println!("DEBUG: {}", for_printing);
+                    let search_directory: Option<&PathBuf> =
+                        api_state.search_directory.as_ref().map(|dir| dir.as_ref());
+                    let mut params = Box::new(extract_params(
+                        String::from(identifier),
+                        inner_pair,
+                        search_directory,
+                        std::mem::take(&mut arrays),
+                    ));
+                    // map pbrt-v4 names and parameters
+                    convert_v4_params(&mut params);
+                    match identifier {
+                        "Accelerator" => {
+                            // Accelerator
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "AreaLightSource" => {
+                            // AreaLightSource
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Attribute" => {
+                            // Attribute (pbrt-v4)
+                            warn!("Attribute \"{}\" not supported. Ignoring it.", params.name);
+                        }
+                        "Camera" => {
+                            // Camera
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "ColorSpace" => {
+                            // ColorSpace (pbrt-v4)
+                            if params.name != "srgb" {
+                                warn!(
+                                    "ColorSpace \"{}\" not supported. Using \"srgb\".",
+                                    params.name
+                                );
+                            }
+                        }
+                        "CoordSysTransform" => {
+                            // CoordSysTransform
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Film" => {
+                            // Film
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Import" => {
+                            // Import (see library)
+                            let import_file: String = included_file(api_state, &params.name);
+                            let namespace: String =
+                                params.find_one_string("namespace", String::new());
+                            info!("Import {:?}", import_file);
+                            // relative filenames of the library are relative to it
+                            let search_directory: PathBuf = match api_state.search_directory {
+                                Some(ref dir) => PathBuf::from(dir.as_ref()),
+                                None => env::current_dir().unwrap(),
+                            };
+                            if let Some(dir) = Path::new(&import_file).parent() {
+                                set_search_directory(api_state, recorder, dir);
+                            }
+                            let location: Location = api_state.location.clone();
+                            api_state.imports.push(Namespace::new(&namespace));
+                            parse_file(
+                                import_file.clone(),
+                                api_state,
+                                bsdf_state,
+                                recorder,
+                                variables,
+                                "",
+                            );
+                            if matches!(api_state.imports.last(), Some(ns) if ns.in_object) {
+                                api_state.errors.push(ParseError {
+                                    location,
+                                    token: import_file,
+                                    message: String::from("missing ObjectEnd in imported library"),
+                                });
+                                // avoid follow-up errors
+                                execute(api_state, bsdf_state, recorder, ApiCall::ObjectEnd);
+                            }
+                            api_state.imports.pop();
+                            set_search_directory(api_state, recorder, &search_directory);
+                        }
+                        "Include" => {
+                            // Include
+                            let include_file: String = included_file(api_state, &params.name);
+                            let todo: Vec<&str> = for_printing.splitn(3, '"').collect();
+                            info!("Include {:?}", include_file);
+                            parse_file(
+                                include_file,
+                                api_state,
+                                bsdf_state,
+                                recorder,
+                                variables,
+                                todo[2],
+                            );
+                        }
+                        "Integrator" => {
+                            // Integrator
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "LightSource" => {
+                            // LightSource
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "MakeNamedMaterial" => {
+                            // MakeNamedMaterial
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "MakeNamedMedium" => {
+                            // MakeNamedMedium
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Material" => {
+                            // Material
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "NamedMaterial" => {
+                            // NamedMaterial
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "ObjectBegin" => {
+                            // ObjectBegin
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "ObjectInstance" => {
+                            // ObjectInstance
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "PixelFilter" => {
+                            // PixelFilter
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Sampler" => {
+                            // Sampler
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Shape" => {
+                            // Shape
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        "Texture" => {
+                            // Texture
+                            execute(api_state, bsdf_state, recorder, ApiCall::Params(params));
+                        }
+                        _ => debug!("> {}", for_printing),
+                    }
+                }
+                Rule::option => {
+                    // Option (pbrt-v4)
+                    let params =
+                        extract_params(String::from(identifier), inner_pair, None, Vec::new());
+                    for name in params
+                        .bools
+                        .iter()
+                        .map(|p| &p.name)
+                        .chain(params.ints.iter().map(|p| &p.name))
+                        .chain(params.floats.iter().map(|p| &p.name))
+                        .chain(params.strings.iter().map(|p| &p.name))
+                    {
+                        warn!("Option \"{}\" not supported. Ignoring it.", name);
+                    }
+                }
+                Rule::active_transform => {
+                    // ActiveTransform
+                    for rule_pair in inner_pair.into_inner() {
+                        match rule_pair.as_rule() {
+                            Rule::all => {
+                                execute(
+                                    api_state,
+                                    bsdf_state,
+                                    recorder,
+                                    ApiCall::ActiveTransformAll,
+                                );
+                            }
+                            Rule::start_time => {
+                                execute(
+                                    api_state,
+                                    bsdf_state,
+                                    recorder,
+                                    ApiCall::ActiveTransformStartTime,
+                                );
+                            }
+                            Rule::end_time => {
+                                execute(
+                                    api_state,
+                                    bsdf_state,
+                                    recorder,
+                                    ApiCall::ActiveTransformEndTime,
+                                );
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+                Rule::concat_transform => {
+                    // ConcatTransform m00 .. m33
+                    let mut m: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        // ignore brackets
+                        let not_opening: bool = rule_pair.as_str() != "[";
+                        let not_closing: bool = rule_pair.as_str() != "]";
+                        if not_opening && not_closing {
+                            let number: Float =
+                                Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                            m.push(number);
+                        }
+                    }
+                    let m00: Float = m[0];
+                    let m01: Float = m[1];
+                    let m02: Float = m[2];
+                    let m03: Float = m[3];
+                    let m10: Float = m[4];
+                    let m11: Float = m[5];
+                    let m12: Float = m[6];
+                    let m13: Float = m[7];
+                    let m20: Float = m[8];
+                    let m21: Float = m[9];
+                    let m22: Float = m[10];
+                    let m23: Float = m[11];
+                    let m30: Float = m[12];
+                    let m31: Float = m[13];
+                    let m32: Float = m[14];
+                    let m33: Float = m[15];
+                    let tr: Transform = Transform::new(
+                        m00, m10, m20, m30, m01, m11, m21, m31, m02, m12, m22, m32, m03, m13, m23,
+                        m33,
+                    );
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::ConcatTransform(tr),
+                    );
+                }
+                Rule::look_at => {
+                    // LookAt eye_x eye_y eye_z look_x look_y look_z up_x up_y up_z
+                    let mut v: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        let number: Float =
+                            Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                        v.push(number);
+                    }
+                    // println!(
+                    //     "LookAt {} {} {} {} {} {} {} {} {}",
+                    //     v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8],
+                    // );
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::LookAt([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8]]),
+                    );
+                }
+                Rule::medium_interface => {
+                    // MediumInterface
+                    let mut strings: Vec<String> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        match rule_pair.as_rule() {
+                            Rule::empty_string => {
+                                strings.push(String::from(""));
+                            }
+                            Rule::string => {
+                                let ident = rule_pair.into_inner().next();
+                                let string: String =
+                                    String::from_str(ident.unwrap().clone().as_span().as_str())
+                                        .unwrap();
+                                strings.push(string);
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    assert!(
+                        strings.len() == 2_usize,
+                        "ERROR: expected two strings, found {:?}",
+                        strings.len()
+                    );
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::MediumInterface(strings[0].clone(), strings[1].clone()),
+                    );
+                }
+                Rule::rotate => {
+                    // Rotate angle x y z
+                    let mut v: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        let number: Float =
+                            Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                        v.push(number);
+                    }
+                    // println!("Rotate {} {} {} {}", v[0], v[1], v[2], v[3]);
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::Rotate([v[0], v[1], v[2], v[3]]),
+                    );
+                }
+                Rule::scale => {
+                    // Scale x y z
+                    let mut v: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        let number: Float =
+                            Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                        v.push(number);
+                    }
+                    // println!("Scale {} {} {}", v[0], v[1], v[2]);
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::Scale([v[0], v[1], v[2]]),
+                    );
+                }
+                Rule::transform => {
+                    // Transform m00 .. m33
+                    let mut m: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        // ignore brackets
+                        let not_opening: bool = rule_pair.as_str() != "[";
+                        let not_closing: bool = rule_pair.as_str() != "]";
+                        if not_opening && not_closing {
+                            let number: Float =
+                                Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                            m.push(number);
+                        }
+                    }
+                    let m00: Float = m[0];
+                    let m01: Float = m[1];
+                    let m02: Float = m[2];
+                    let m03: Float = m[3];
+                    let m10: Float = m[4];
+                    let m11: Float = m[5];
+                    let m12: Float = m[6];
+                    let m13: Float = m[7];
+                    let m20: Float = m[8];
+                    let m21: Float = m[9];
+                    let m22: Float = m[10];
+                    let m23: Float = m[11];
+                    let m30: Float = m[12];
+                    let m31: Float = m[13];
+                    let m32: Float = m[14];
+                    let m33: Float = m[15];
+                    let tr: Transform = Transform::new(
+                        m00, m10, m20, m30, m01, m11, m21, m31, m02, m12, m22, m32, m03, m13, m23,
+                        m33,
+                    );
+                    execute(api_state, bsdf_state, recorder, ApiCall::Transform(tr));
+                }
+                Rule::translate => {
+                    // Translate x y z
+                    let mut v: Vec<Float> = Vec::new();
+                    for rule_pair in inner_pair.into_inner() {
+                        let number: Float =
+                            Float::from_str(rule_pair.clone().as_span().as_str()).unwrap();
+                        v.push(number);
+                    }
+                    // println!("Translate {} {} {}", v[0], v[1], v[2]);
+                    execute(
+                        api_state,
+                        bsdf_state,
+                        recorder,
+                        ApiCall::Translate([v[0], v[1], v[2]]),
+                    );
+                }
+                Rule::remaining_line => {
+                    // predetermined number of arguments of predetermined type
+                    if identifier == "TransformTimes" {
+                        debug!("< {}", inner_pair.as_str());
+                    } else {
+                        api_state.errors.push(error_at(
+                            identifier.len() + 1,
+                            format!("invalid arguments for {}", identifier),
+                        ));
+                    }
+                }
+                // _ => unreachable!(),
+                _ => debug!("TODO: {:?}", inner_pair.as_rule()),
+            }
+        }
+    }
+}
+
+/// The full path of an included (or imported) file, relative
+/// filenames are relative to the search directory.
+fn included_file(api_state: &ApiState, name: &str) -> String {
+    if let Some(ref search_directory) = api_state.search_directory {
+        let mut path_buf: PathBuf = PathBuf::from("/");
+        path_buf.push(search_directory.as_ref());
+        path_buf.push(name);
+        String::from(path_buf.to_str().unwrap())
+    } else {
+        String::from(name)
+    }
+}
+
+/// Sets the directory relative filenames get resolved against (and
+/// records it).
+fn set_search_directory(
+    api_state: &mut ApiState,
+    recorder: &mut Option<Recorder>,
+    search_directory: &Path,
+) {
+    api_state.search_directory = Some(Box::new(PathBuf::from(search_directory)));
+    if let Some(ref mut recording) = recorder {
+        recording.locations.push(api_state.location.clone());
+        recording
+            .cache
+            .calls
+            .push(ApiCall::SearchDirectory(String::from(
+                search_directory.to_str().unwrap(),
+            )));
+    }
+}
+
+/// Parses a scene file (and the files it includes). Depending on the
+/// *recorder* the scene gets rendered once **WorldEnd** is reached.
+pub fn parse_file(
+    filename: String,
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+    variables: &mut Variables,
+    append: &str,
+) {
+    // println!("FILE = {}", x);
+    let f = match readable_path(&filename).and_then(File::open) {
+        Ok(f) => f,
+        Err(err) => {
+            let message: String = format!("unable to open scene file: {}", err);
+            if api_state.location == Location::default() {
+                // not included by another file
+                api_state.errors.push(ParseError {
+                    location: Location {
+                        filename,
+                        line: 0,
+                        column: 0,
+                    },
+                    token: String::new(),
+                    message,
+                });
+            } else {
+                api_state.add_error(ParseError::new(&filename, message));
+            }
+            return;
+        }
+    };
+    if api_state.location == Location::default() && api_state.scene_filename.is_none() {
+        // not included by another file
+        api_state.scene_filename = Some(filename.clone());
+    }
+    let ip: &Path = Path::new(filename.as_str());
+    if ip.is_relative() {
+        let cp: PathBuf = env::current_dir().unwrap();
+        let pb: PathBuf = cp.join(ip);
+        let search_directory: &Path = pb.as_path().parent().unwrap();
+        // println!("search_directory is {}", search_directory.display());
+        set_search_directory(api_state, recorder, search_directory);
+    }
+    if let Some(ref mut recording) = recorder {
+        recording.cache.add_file(&filename);
+    }
+    let mut reader = BufReader::new(f);
+    if filename.ends_with(".json") {
+        api_state.location = Location {
+            filename: filename.clone(),
+            line: 0,
+            column: 0,
+        };
+        let mut str_buf: String = String::default();
+        if let Err(err) = reader.read_to_string(&mut str_buf) {
+            api_state.add_error(ParseError::new(
+                "",
+                format!("unable to read scene file: {}", err),
+            ));
+            return;
+        }
+        parse_json_str(&str_buf, api_state, bsdf_state, recorder);
+    } else {
+        parse_reader(
+            reader, &filename, api_state, bsdf_state, recorder, variables, append,
+        );
+    }
+}
+
+/// Parses a scene in the JSON format (see **jsonscene::JsonScene**).
+/// Relative filenames are resolved against the search directory of
+/// *api_state* (if any). Errors refer to the file of
+/// **ApiState::location**.
+pub fn parse_json_str(
+    str_buf: &str,
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+) {
+    let scene: JsonScene = match serde_json::from_str(str_buf) {
+        Ok(scene) => scene,
+        Err(err) => {
+            // the location is part of the error
+            let message: String = err.to_string();
+            let message: &str = match message.rfind(" at line ") {
+                Some(pos) => &message[..pos],
+                None => &message,
+            };
+            api_state.errors.push(ParseError {
+                location: Location {
+                    filename: api_state.location.filename.clone(),
+                    line: err.line(),
+                    column: err.column(),
+                },
+                token: String::new(),
+                message: format!("invalid JSON scene: {}", message),
+            });
+            return;
+        }
+    };
+    let search_directory: Option<PathBuf> = api_state
+        .search_directory
+        .as_ref()
+        .map(|dir| PathBuf::from(dir.as_ref()));
+    match scene.to_calls(search_directory.as_ref()) {
+        Ok(calls) => {
+            for call in calls {
+                execute(api_state, bsdf_state, recorder, call);
+            }
+        }
+        Err(errors) => {
+            for error in errors {
+                api_state.add_error(ParseError::new(
+                    "",
+                    format!("invalid JSON scene: {}", error),
+                ));
+            }
+        }
+    }
+}
+
+/// Parses a scene description which is already in memory. Relative
+/// filenames are resolved against the search directory of
+/// *api_state* (if any).
+pub fn parse_str(
+    str_buf: &str,
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+    variables: &mut Variables,
+    append: &str,
+) {
+    parse_reader(
+        str_buf.as_bytes(),
+        "",
+        api_state,
+        bsdf_state,
+        recorder,
+        variables,
+        append,
+    );
+}
+
+/// Parses a scene description line by line (see **StatementReader**)
+/// and calls the API for each statement as soon as it is complete.
+/// Errors get collected in **ApiState::errors**, with *filename*
+/// (which can be empty) as part of their location.
+fn parse_reader<R: BufRead>(
+    mut reader: R,
+    filename: &str,
+    api_state: &mut ApiState,
+    bsdf_state: &mut BsdfState,
+    recorder: &mut Option<Recorder>,
+    variables: &mut Variables,
+    append: &str,
+) {
+    let mut statement_reader: StatementReader = StatementReader::new(filename);
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut line: String = String::default();
+    loop {
+        line.clear();
+        statement_reader.line += 1;
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                statement_reader.add_error(
+                    1,
+                    "",
+                    format!("unable to read scene description: {}", err),
+                );
+                break;
+            }
+        }
+        statement_reader.read_line(&line, &mut statements);
+        for statement in statements.drain(..) {
+            parse_line(api_state, bsdf_state, recorder, variables, statement);
+        }
+        api_state.errors.append(&mut statement_reader.errors);
+    }
+    if !append.is_empty() {
+        statement_reader.read_line(append, &mut statements);
+    }
+    statements.extend(statement_reader.finish());
+    for statement in statements {
+        parse_line(api_state, bsdf_state, recorder, variables, statement);
+    }
+    api_state.errors.append(&mut statement_reader.errors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::api::pbrt_init;
+
+    fn parse_errors(scene: &str) -> Vec<ParseError> {
+        let (mut api_state, mut bsdf_state) = pbrt_init(1);
+        parse_str(
+            scene,
+            &mut api_state,
+            &mut bsdf_state,
+            &mut None,
+            &mut Variables::default(),
+            "",
+        );
+        api_state.errors
+    }
+
+    #[test]
+    fn interleaved_attribute_and_transform_blocks() {
+        for scene in [
+            "WorldBegin\nAttributeBegin\nTransformEnd\nAttributeEnd\n",
+            "WorldBegin\nObjectBegin \"a\"\nTransformEnd\nObjectEnd\n",
+        ] {
+            let errors: Vec<ParseError> = parse_errors(scene);
+            assert_eq!(errors.len(), 1, "{:?}: {:?}", scene, errors);
+            assert_eq!(
+                errors[0].message, "Unmatched AttributeEnd/TransformEnd nesting",
+                "{:?}",
+                scene
+            );
+        }
+    }
+}
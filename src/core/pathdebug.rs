@@ -0,0 +1,285 @@
+//! Traces the samples of a single pixel and dumps their paths.
+//!
+//! With **set_debug_pixel()** only one pixel gets rendered, and every
+//! sample of it records what happened along its path: the camera ray,
+//! each surface hit (position and normals), emitted light, direct
+//! lighting, the sampled BSDF lobe with its direction, value, and PDF,
+//! the path throughput afterwards, Russian roulette, and the ray
+//! leaving the scene. After rendering, the recorded samples get
+//! written to a JSON file (see **PathRecord**), and optionally the
+//! paths as polylines to a Wavefront OBJ file, which can be loaded
+//! together with the scene geometry into any 3D viewer.
+//!
+//! The **path** integrator records its events, other integrators
+//! only record the camera ray and the radiance of each sample.
+
+// std
+use std::cell::{Cell, RefCell};
+use std::fmt::Write;
+use std::sync::Mutex;
+// others
+use log::{error, info};
+use serde::Serialize;
+// pbrt
+use crate::core::geometry::{Normal3f, Point2f, Point2i, Point3f, Ray, Vector3f};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::reflection::BxdfType;
+
+/// The pixel to trace, and whether an OBJ file gets written as well.
+struct DebugPixel {
+    pixel: Point2i,
+    write_obj: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref DEBUG_PIXEL: Mutex<Option<DebugPixel>> = Mutex::new(None);
+    static ref RECORDS: Mutex<Vec<PathRecord>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    /// Set while this thread renders a sample of the debug pixel.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+    static EVENTS: RefCell<Vec<PathEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Something which happened along the path of a sample, *depth* is
+/// the number of bounces so far.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PathEvent {
+    Camera {
+        p_film: [Float; 2],
+        origin: [Float; 3],
+        direction: [Float; 3],
+        weight: Float,
+    },
+    Hit {
+        depth: u32,
+        p: [Float; 3],
+        n: [Float; 3],
+        shading_n: [Float; 3],
+    },
+    /// light emitted by the surface towards the previous vertex
+    /// (weighted by the throughput)
+    Emission { depth: u32, l: [Float; 3] },
+    /// sampled light from one light source (weighted by the
+    /// throughput)
+    DirectLight { depth: u32, l: [Float; 3] },
+    BsdfSample {
+        depth: u32,
+        /// e.g. "diffuse reflection"
+        lobe: String,
+        wi: [Float; 3],
+        f: [Float; 3],
+        pdf: Float,
+        /// the path throughput afterwards
+        beta: [Float; 3],
+    },
+    RussianRoulette {
+        depth: u32,
+        q: Float,
+        survived: bool,
+    },
+    /// the ray left the scene, *l* is the light of infinite lights
+    /// (weighted by the throughput)
+    Escaped {
+        depth: u32,
+        origin: [Float; 3],
+        direction: [Float; 3],
+        l: [Float; 3],
+    },
+}
+
+/// A sample of the debug pixel, as written to the JSON file.
+#[derive(Debug, Clone, Serialize)]
+pub struct PathRecord {
+    pub pixel: [i32; 2],
+    pub sample: i64,
+    pub events: Vec<PathEvent>,
+    /// the radiance of the sample
+    pub l: [Float; 3],
+}
+
+/// Renders only *pixel* (if any) and records the paths of its
+/// samples, see **write_debug_pixel_files()**.
+pub fn set_debug_pixel(pixel: Option<Point2i>, write_obj: bool) {
+    *DEBUG_PIXEL.lock().unwrap() = pixel.map(|pixel| DebugPixel { pixel, write_obj });
+    RECORDS.lock().unwrap().clear();
+}
+
+pub fn debug_pixel() -> Option<Point2i> {
+    DEBUG_PIXEL
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|debug| debug.pixel)
+}
+
+/// Is a sample of the debug pixel rendered by this thread? Integrators
+/// use it to skip collecting data for **record_event()**.
+pub fn is_recording() -> bool {
+    RECORDING.with(|recording| recording.get())
+}
+
+pub fn record_event(event: PathEvent) {
+    if is_recording() {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+}
+
+/// Starts recording a sample of the debug pixel.
+pub fn begin_sample(p_film: Point2f, ray: &Ray, weight: Float) {
+    RECORDING.with(|recording| recording.set(true));
+    EVENTS.with(|events| events.borrow_mut().clear());
+    record_event(PathEvent::Camera {
+        p_film: [p_film.x, p_film.y],
+        origin: pnt3(&ray.o),
+        direction: vec3(&ray.d),
+        weight,
+    });
+}
+
+/// Stops recording and keeps the sample for the JSON file.
+pub fn end_sample(pixel: Point2i, sample: i64, l: &Spectrum) {
+    RECORDING.with(|recording| recording.set(false));
+    let events: Vec<PathEvent> = EVENTS.with(|events| events.replace(Vec::new()));
+    RECORDS.lock().unwrap().push(PathRecord {
+        pixel: [pixel.x, pixel.y],
+        sample,
+        events,
+        l: rgb(l),
+    });
+}
+
+/// Writes the recorded samples to "debug_pixel_X_Y.json" (and the
+/// paths to "debug_pixel_X_Y.obj"), if a debug pixel was set.
+pub fn write_debug_pixel_files() {
+    let (pixel, write_obj) = match *DEBUG_PIXEL.lock().unwrap() {
+        Some(ref debug) => (debug.pixel, debug.write_obj),
+        None => return,
+    };
+    let mut records: Vec<PathRecord> = RECORDS.lock().unwrap().clone();
+    records.sort_by_key(|record| record.sample);
+    let stem: String = format!("debug_pixel_{}_{}", pixel.x, pixel.y);
+    let filename: String = format!("{}.json", stem);
+    info!(
+        "Writing {} path(s) of pixel ({}, {}) to {:?}",
+        records.len(),
+        pixel.x,
+        pixel.y,
+        filename
+    );
+    let json: String = serde_json::to_string_pretty(&records).unwrap();
+    if let Err(err) = std::fs::write(&filename, json) {
+        error!("Can't write {:?}: {}", filename, err);
+    }
+    if write_obj {
+        let filename: String = format!("{}.obj", stem);
+        info!("Writing paths to {:?}", filename);
+        if let Err(err) = std::fs::write(&filename, paths_to_obj(&records)) {
+            error!("Can't write {:?}: {}", filename, err);
+        }
+    }
+}
+
+/// One polyline (OBJ line element) per sample, from the camera
+/// through all hits. Rays leaving the scene end after the length of
+/// the longest segment of all paths.
+fn paths_to_obj(records: &[PathRecord]) -> String {
+    let mut paths: Vec<Vec<[Float; 3]>> = Vec::new();
+    let mut escaped: Vec<Option<([Float; 3], [Float; 3])>> = Vec::new();
+    let mut max_length: Float = 0.0 as Float;
+    for record in records {
+        let mut points: Vec<[Float; 3]> = Vec::new();
+        let mut escape: Option<([Float; 3], [Float; 3])> = None;
+        for event in &record.events {
+            match event {
+                PathEvent::Camera { origin, .. } => points.push(*origin),
+                PathEvent::Hit { p, .. } => {
+                    if let Some(last) = points.last() {
+                        max_length = max_length.max(distance(last, p));
+                    }
+                    points.push(*p);
+                }
+                PathEvent::Escaped {
+                    origin, direction, ..
+                } => escape = Some((*origin, *direction)),
+                _ => {}
+            }
+        }
+        paths.push(points);
+        escaped.push(escape);
+    }
+    if max_length == 0.0 as Float {
+        max_length = 1.0 as Float;
+    }
+    let mut obj: String = String::from("# paths of the samples of one pixel (see --debug-pixel)\n");
+    let mut n_vertices: usize = 0;
+    for (points, escape) in paths.iter_mut().zip(escaped.iter()) {
+        if let Some((origin, direction)) = escape {
+            points.push([
+                origin[0] + direction[0] * max_length,
+                origin[1] + direction[1] * max_length,
+                origin[2] + direction[2] * max_length,
+            ]);
+        }
+        if points.len() < 2 {
+            continue;
+        }
+        for p in points.iter() {
+            writeln!(obj, "v {} {} {}", p[0], p[1], p[2]).unwrap();
+        }
+        obj.push('l');
+        for i in 0..points.len() {
+            write!(obj, " {}", n_vertices + i + 1).unwrap();
+        }
+        obj.push('\n');
+        n_vertices += points.len();
+    }
+    obj
+}
+
+fn distance(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    ((a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1]) + (a[2] - b[2]) * (a[2] - b[2]))
+        .sqrt()
+}
+
+/// The name of a sampled BSDF lobe (**BxdfType** flags), e.g.
+/// "specular transmission".
+pub fn lobe_name(sampled_type: u8) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    if sampled_type & BxdfType::BsdfDiffuse as u8 != 0 {
+        names.push("diffuse");
+    }
+    if sampled_type & BxdfType::BsdfGlossy as u8 != 0 {
+        names.push("glossy");
+    }
+    if sampled_type & BxdfType::BsdfSpecular as u8 != 0 {
+        names.push("specular");
+    }
+    if sampled_type & BxdfType::BsdfReflection as u8 != 0 {
+        names.push("reflection");
+    }
+    if sampled_type & BxdfType::BsdfTransmission as u8 != 0 {
+        names.push("transmission");
+    }
+    names.join(" ")
+}
+
+pub fn pnt3(p: &Point3f) -> [Float; 3] {
+    [p.x, p.y, p.z]
+}
+
+pub fn vec3(v: &Vector3f) -> [Float; 3] {
+    [v.x, v.y, v.z]
+}
+
+pub fn nrm(n: &Normal3f) -> [Float; 3] {
+    [n.x, n.y, n.z]
+}
+
+pub fn rgb(s: &Spectrum) -> [Float; 3] {
+    let mut rgb: [Float; 3] = [0.0 as Float; 3];
+    s.to_rgb(&mut rgb);
+    rgb
+}
@@ -3,20 +3,34 @@
 
 // std
 use std;
-use std::f32::consts::PI;
 use std::ops::{Add, BitAnd, Div, Mul, Sub};
 // others
 use num;
 // pbrt
+#[cfg(not(feature = "sampled_spectrum"))]
 use crate::core::spectrum::RGBSpectrum;
+#[cfg(feature = "sampled_spectrum")]
+use crate::core::spectrum::SampledSpectrum;
 
 // see pbrt.h
 
+#[cfg(not(feature = "sampled_spectrum"))]
 pub type Spectrum = RGBSpectrum;
+#[cfg(feature = "sampled_spectrum")]
+pub type Spectrum = SampledSpectrum;
 
+#[cfg(not(feature = "float_as_double"))]
 pub type Float = f32;
+#[cfg(feature = "float_as_double")]
+pub type Float = f64;
 
-pub const MACHINE_EPSILON: Float = std::f32::EPSILON * 0.5;
+/// Mathematical constants (like **PI**) with the precision of **Float**.
+#[cfg(not(feature = "float_as_double"))]
+pub use std::f32::consts;
+#[cfg(feature = "float_as_double")]
+pub use std::f64::consts;
+
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
 pub const SHADOW_EPSILON: Float = 0.0001;
 pub const INV_PI: Float = 0.318_309_886_183_790_671_54;
 pub const INV_2_PI: Float = 0.159_154_943_091_895_335_77;
@@ -25,18 +39,24 @@ pub const PI_OVER_2: Float = 1.570_796_326_794_896_619_23;
 pub const PI_OVER_4: Float = 0.785_398_163_397_448_309_61;
 pub const SQRT_2: Float = 1.414_213_562_373_095_048_80;
 
+/// The unsigned integer type with the same size as **Float**.
+#[cfg(not(feature = "float_as_double"))]
+pub type FloatBits = u32;
+#[cfg(feature = "float_as_double")]
+pub type FloatBits = u64;
+
 /// Use **unsafe**
 /// [std::mem::transmute_copy][transmute_copy]
-/// to convert *f32* to *u32*.
+/// to convert *Float* to *FloatBits*.
 ///
 /// [transmute_copy]: https://doc.rust-lang.org/std/mem/fn.transmute_copy.html
-pub fn float_to_bits(f: f32) -> u32 {
+pub fn float_to_bits(f: Float) -> FloatBits {
     // uint64_t ui;
     // memcpy(&ui, &f, sizeof(double));
     // return ui;
-    let rui: u32;
+    let rui: FloatBits;
     unsafe {
-        let ui: u32 = std::mem::transmute_copy(&f);
+        let ui: FloatBits = std::mem::transmute_copy(&f);
         rui = ui;
     }
     rui
@@ -44,16 +64,16 @@ pub fn float_to_bits(f: f32) -> u32 {
 
 /// Use **unsafe**
 /// [std::mem::transmute_copy][transmute_copy]
-/// to convert *u32* to *f32*.
+/// to convert *FloatBits* to *Float*.
 ///
 /// [transmute_copy]: https://doc.rust-lang.org/std/mem/fn.transmute_copy.html
-pub fn bits_to_float(ui: u32) -> f32 {
+pub fn bits_to_float(ui: FloatBits) -> Float {
     // float f;
     // memcpy(&f, &ui, sizeof(uint32_t));
     // return f;
-    let rf: f32;
+    let rf: Float;
     unsafe {
-        let f: f32 = std::mem::transmute_copy(&ui);
+        let f: Float = std::mem::transmute_copy(&ui);
         rf = f;
     }
     rf
@@ -61,12 +81,12 @@ pub fn bits_to_float(ui: u32) -> f32 {
 
 /// Bump a floating-point value up to the next greater representable
 /// floating-point value.
-pub fn next_float_up(v: f32) -> f32 {
+pub fn next_float_up(v: Float) -> Float {
     if v.is_infinite() && v > 0.0 {
         v
     } else {
         let new_v = if v == -0.0 { 0.0 } else { v };
-        let mut ui: u32 = float_to_bits(new_v);
+        let mut ui: FloatBits = float_to_bits(new_v);
         if new_v >= 0.0 {
             ui += 1;
         } else {
@@ -78,12 +98,12 @@ pub fn next_float_up(v: f32) -> f32 {
 
 /// Bump a floating-point value down to the next smaller representable
 /// floating-point value.
-pub fn next_float_down(v: f32) -> f32 {
+pub fn next_float_down(v: Float) -> Float {
     if v.is_infinite() && v < 0.0 {
         v
     } else {
         let new_v = if v == 0.0 { -0.0 } else { v };
-        let mut ui: u32 = float_to_bits(new_v);
+        let mut ui: FloatBits = float_to_bits(new_v);
         if new_v > 0.0 {
             ui -= 1;
         } else {
@@ -148,12 +168,12 @@ where
 
 /// Convert from angles expressed in degrees to radians.
 pub fn radians(deg: Float) -> Float {
-    (PI / 180.0) * deg
+    (consts::PI / 180.0) * deg
 }
 
 /// Convert from angles expressed in radians to degrees.
 pub fn degrees(rad: Float) -> Float {
-    (180.0 / PI) * rad
+    (180.0 / consts::PI) * rad
 }
 
 pub fn log_2(x: Float) -> Float {
@@ -216,6 +236,18 @@ pub fn round_up_pow2_64(v: i64) -> i64 {
     ret + 1
 }
 
+/// Scrambles the bits of a 64-bit value, a cheap hash function
+/// (MixBits() of pbrt-v4).
+pub fn mix_bits(v: u64) -> u64 {
+    let mut v: u64 = v;
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
 /// Helper function which emulates the behavior of std::upper_bound().
 pub fn find_interval<P>(size: i32, pred: P) -> i32
 where
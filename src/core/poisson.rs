@@ -0,0 +1,245 @@
+//! A screened Poisson solver for gradient-domain rendering.
+//!
+//! Given a noisy image (the *primal* image) and estimates of its
+//! finite differences, *dx* (towards the right neighbor of each pixel)
+//! and *dy* (towards the neighbor below), the reconstructed image *I*
+//! minimizes
+//!
+//! ```text
+//! alpha^2 |I - primal|^2 + |dx(I) - dx|^2 + |dy(I) - dy|^2
+//! ```
+//!
+//! which is the L2 reconstruction of gradient-domain path tracing
+//! (see **GradientPathIntegrator**). The smaller *alpha*, the more
+//! the result follows the gradients, which are usually much less
+//! noisy than the primal image, and the primal image only fixes the
+//! overall brightness. The minimum is found by solving the normal
+//! equations
+//!
+//! ```text
+//! (alpha^2 + L) I = alpha^2 primal + D^T g
+//! ```
+//!
+//! with conjugate gradients, where *D* computes the finite
+//! differences *g* of an image and *L* = *D^T D* is the Laplacian of
+//! the pixel grid (with no differences across the image borders).
+//!
+//! The L2 reconstruction is unbiased, but spreads the error of a few
+//! bad gradients (e.g. at the edges of bright light sources) over
+//! large parts of the image. The L1 reconstruction (the same, with
+//! absolute instead of squared differences) is slightly biased, but
+//! much more robust. It gets solved by iteratively reweighted least
+//! squares: each term of a weighted L2 problem is weighted by the
+//! inverse of its absolute difference in the previous solution.
+
+// pbrt
+use crate::core::pbrt::Float;
+
+/// Stop once the residual is that small relative to the right-hand
+/// side of the normal equations.
+const RELATIVE_TOLERANCE: f64 = 1.0e-6;
+/// weighted L2 problems solved for the L1 reconstruction
+const L1_ITERATIONS: usize = 8;
+
+/// Reconstructs one channel of a *width* x *height* image (row by
+/// row) from its *primal* values and its finite differences *dx* and
+/// *dy* (the values of the last column of *dx* and the last row of
+/// *dy* are ignored) in the L2 sense, with at most *max_iterations*
+/// steps of conjugate gradients.
+///
+/// ```rust
+/// use pbrt::core::pbrt::Float;
+/// use pbrt::core::poisson::solve_screened_poisson;
+///
+/// // a noisy constant image, but exact (zero) gradients
+/// let primal = [1.2, 0.8, 0.9, 1.1];
+/// let zero = [0.0; 4];
+/// let image = solve_screened_poisson(2, 2, &primal, &zero, &zero, 0.2, 100);
+/// // the noise is mostly gone, the mean stays
+/// for value in &image {
+///     assert!((value - 1.0).abs() < 0.01);
+/// }
+/// let sum: Float = image.iter().sum();
+/// assert!((sum - 4.0).abs() < 1.0e-4);
+/// ```
+pub fn solve_screened_poisson(
+    width: usize,
+    height: usize,
+    primal: &[Float],
+    dx: &[Float],
+    dy: &[Float],
+    alpha: Float,
+    max_iterations: usize,
+) -> Vec<Float> {
+    let problem: Problem = Problem::new(width, height, primal, dx, dy, alpha);
+    let ones: Weights = Weights::ones(width * height);
+    let start: Vec<f64> = problem.primal.clone();
+    problem
+        .solve(&ones, start, max_iterations)
+        .into_iter()
+        .map(|v| v as Float)
+        .collect()
+}
+
+/// Like **solve_screened_poisson()**, but in the L1 sense.
+pub fn solve_screened_poisson_l1(
+    width: usize,
+    height: usize,
+    primal: &[Float],
+    dx: &[Float],
+    dy: &[Float],
+    alpha: Float,
+    max_iterations: usize,
+) -> Vec<Float> {
+    let problem: Problem = Problem::new(width, height, primal, dx, dy, alpha);
+    let n: usize = width * height;
+    // differences below that count as exact (avoids infinite weights)
+    let mean: f64 = problem.primal.iter().map(|v| v.abs()).sum::<f64>() / n.max(1) as f64;
+    let epsilon: f64 = (1.0e-3 * mean).max(1.0e-8);
+    let mut weights: Weights = Weights::ones(n);
+    let mut image: Vec<f64> = problem.solve(&weights, problem.primal.clone(), max_iterations);
+    for _iteration in 1..L1_ITERATIONS {
+        for y in 0..height {
+            for x in 0..width {
+                let i: usize = y * width + x;
+                weights.primal[i] = 1.0 / (image[i] - problem.primal[i]).abs().max(epsilon);
+                if x + 1 < width {
+                    let r: f64 = image[i + 1] - image[i] - problem.dx[i];
+                    weights.dx[i] = 1.0 / r.abs().max(epsilon);
+                }
+                if y + 1 < height {
+                    let r: f64 = image[i + width] - image[i] - problem.dy[i];
+                    weights.dy[i] = 1.0 / r.abs().max(epsilon);
+                }
+            }
+        }
+        image = problem.solve(&weights, image, max_iterations);
+    }
+    image.into_iter().map(|v| v as Float).collect()
+}
+
+/// The primal image and the gradients of one channel.
+struct Problem {
+    width: usize,
+    height: usize,
+    alpha2: f64,
+    primal: Vec<f64>,
+    dx: Vec<f64>,
+    dy: Vec<f64>,
+}
+
+/// Per pixel and per finite difference weights of a weighted L2
+/// problem.
+struct Weights {
+    primal: Vec<f64>,
+    dx: Vec<f64>,
+    dy: Vec<f64>,
+}
+
+impl Weights {
+    fn ones(n: usize) -> Self {
+        Weights {
+            primal: vec![1.0; n],
+            dx: vec![1.0; n],
+            dy: vec![1.0; n],
+        }
+    }
+}
+
+impl Problem {
+    fn new(
+        width: usize,
+        height: usize,
+        primal: &[Float],
+        dx: &[Float],
+        dy: &[Float],
+        alpha: Float,
+    ) -> Self {
+        let n: usize = width * height;
+        assert!(primal.len() == n && dx.len() == n && dy.len() == n);
+        let to_f64 = |values: &[Float]| -> Vec<f64> { values.iter().map(|v| *v as f64).collect() };
+        Problem {
+            width,
+            height,
+            alpha2: alpha as f64 * alpha as f64,
+            primal: to_f64(primal),
+            dx: to_f64(dx),
+            dy: to_f64(dy),
+        }
+    }
+    /// (alpha^2 W_p + D^T W_g D) v
+    fn apply(&self, weights: &Weights, v: &[f64], out: &mut [f64]) {
+        let width: usize = self.width;
+        for y in 0..self.height {
+            for x in 0..width {
+                let i: usize = y * width + x;
+                let mut sum: f64 = self.alpha2 * weights.primal[i] * v[i];
+                if x > 0 {
+                    sum += weights.dx[i - 1] * (v[i] - v[i - 1]);
+                }
+                if x + 1 < width {
+                    sum += weights.dx[i] * (v[i] - v[i + 1]);
+                }
+                if y > 0 {
+                    sum += weights.dy[i - width] * (v[i] - v[i - width]);
+                }
+                if y + 1 < self.height {
+                    sum += weights.dy[i] * (v[i] - v[i + width]);
+                }
+                out[i] = sum;
+            }
+        }
+    }
+    /// Solves the weighted normal equations with conjugate gradients,
+    /// starting with the image *start*.
+    fn solve(&self, weights: &Weights, start: Vec<f64>, max_iterations: usize) -> Vec<f64> {
+        let width: usize = self.width;
+        let n: usize = width * self.height;
+        // right-hand side alpha^2 W_p primal + D^T W_g g
+        let mut b: Vec<f64> = (0..n)
+            .map(|i| self.alpha2 * weights.primal[i] * self.primal[i])
+            .collect();
+        for y in 0..self.height {
+            for x in 0..width {
+                let i: usize = y * width + x;
+                if x + 1 < width {
+                    let g: f64 = weights.dx[i] * self.dx[i];
+                    b[i] -= g;
+                    b[i + 1] += g;
+                }
+                if y + 1 < self.height {
+                    let g: f64 = weights.dy[i] * self.dy[i];
+                    b[i] -= g;
+                    b[i + width] += g;
+                }
+            }
+        }
+        let dot =
+            |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b.iter()).map(|(a, b)| a * b).sum() };
+        let mut image: Vec<f64> = start;
+        let mut a_p: Vec<f64> = vec![0.0; n];
+        self.apply(weights, &image, &mut a_p);
+        let mut r: Vec<f64> = b.iter().zip(a_p.iter()).map(|(b, a)| b - a).collect();
+        let mut p: Vec<f64> = r.clone();
+        let mut rr: f64 = dot(&r, &r);
+        let tolerance: f64 = RELATIVE_TOLERANCE * RELATIVE_TOLERANCE * dot(&b, &b);
+        for _iteration in 0..max_iterations {
+            if rr <= tolerance {
+                break;
+            }
+            self.apply(weights, &p, &mut a_p);
+            let step: f64 = rr / dot(&p, &a_p);
+            for i in 0..n {
+                image[i] += step * p[i];
+                r[i] -= step * a_p[i];
+            }
+            let rr_new: f64 = dot(&r, &r);
+            let beta: f64 = rr_new / rr;
+            for i in 0..n {
+                p[i] = r[i] + beta * p[i];
+            }
+            rr = rr_new;
+        }
+        image
+    }
+}
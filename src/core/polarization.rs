@@ -0,0 +1,291 @@
+//! Optional polarized light transport (enabled with the
+//! **polarization** feature).
+//!
+//! Light is described by a Stokes vector *[I, Q, U, V]* and
+//! (specular) Fresnel interactions by 4x4 Mueller matrices. Because
+//! light sources emit unpolarized light, it is enough to trace a
+//! single row vector from the camera: it starts as the first row of
+//! the camera's polarization filter and gets multiplied by the Mueller
+//! matrix of each specular bounce along the path. The first component
+//! of that row scales the (unpolarized) emitted radiance.
+//!
+//! Only perfectly specular Fresnel events (dielectrics, conductors and
+//! mirrors) polarize light, all other bounces depolarize it.
+
+// others
+use num::complex::Complex;
+// pbrt
+use crate::core::geometry::{
+    vec3_coordinate_system, vec3_cross_nrm, vec3_cross_vec3, vec3_dot_nrm, vec3_dot_vec3,
+};
+use crate::core::geometry::{Normal3f, Vector3f};
+use crate::core::pbrt::Float;
+use crate::core::reflection::{Bsdf, Bxdf, BxdfType, Fresnel};
+
+/// A 4x4 Mueller matrix acting on Stokes vectors.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MuellerMatrix {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Default for MuellerMatrix {
+    fn default() -> Self {
+        MuellerMatrix::identity()
+    }
+}
+
+impl MuellerMatrix {
+    pub fn identity() -> Self {
+        let mut m: [[Float; 4]; 4] = [[0.0 as Float; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0 as Float;
+        }
+        MuellerMatrix { m }
+    }
+    /// Reflection given the complex Fresnel amplitude coefficients
+    /// for s- and p-polarized light.
+    pub fn fresnel_reflection(rs: Complex<Float>, rp: Complex<Float>) -> Self {
+        let a: Float = rs.norm_sqr();
+        let b: Float = rp.norm_sqr();
+        let c: Complex<Float> = rs * rp.conj();
+        MuellerMatrix {
+            m: [
+                [0.5 * (a + b), 0.5 * (a - b), 0.0, 0.0],
+                [0.5 * (a - b), 0.5 * (a + b), 0.0, 0.0],
+                [0.0, 0.0, c.re, c.im],
+                [0.0, 0.0, -c.im, c.re],
+            ],
+        }
+    }
+    /// Transmission given the transmitted fractions of power for s-
+    /// and p-polarized light.
+    pub fn fresnel_transmission(ts: Float, tp: Float) -> Self {
+        let c: Float = (ts * tp).max(0.0 as Float).sqrt();
+        MuellerMatrix {
+            m: [
+                [0.5 * (ts + tp), 0.5 * (ts - tp), 0.0, 0.0],
+                [0.5 * (ts - tp), 0.5 * (ts + tp), 0.0, 0.0],
+                [0.0, 0.0, c, 0.0],
+                [0.0, 0.0, 0.0, c],
+            ],
+        }
+    }
+    /// Ideal linear polarizer with its transmission axis at *angle*
+    /// (radians) from the reference direction.
+    pub fn linear_polarizer(angle: Float) -> Self {
+        let c: Float = (2.0 as Float * angle).cos();
+        let s: Float = (2.0 as Float * angle).sin();
+        MuellerMatrix {
+            m: [
+                [0.5, 0.5 * c, 0.5 * s, 0.0],
+                [0.5 * c, 0.5 * c * c, 0.5 * c * s, 0.0],
+                [0.5 * s, 0.5 * c * s, 0.5 * s * s, 0.0],
+                [0.0, 0.0, 0.0, 0.0],
+            ],
+        }
+    }
+    /// Divides by the first element (the unpolarized throughput,
+    /// which is already part of the BSDF value).
+    pub fn normalized(&self) -> Self {
+        let m00: Float = self.m[0][0];
+        if m00 == 0.0 as Float {
+            return *self;
+        }
+        let mut m: [[Float; 4]; 4] = self.m;
+        for row in m.iter_mut() {
+            for v in row.iter_mut() {
+                *v /= m00;
+            }
+        }
+        MuellerMatrix { m }
+    }
+}
+
+/// Complex Fresnel amplitude coefficients *(rs, rp)* for light
+/// arriving at *cos_theta_i* on an interface with relative (possibly
+/// complex) index of refraction *eta*. Total internal reflection and
+/// conductors are handled by the complex square root.
+pub fn fresnel_amplitudes(
+    cos_theta_i: Float,
+    eta: Complex<Float>,
+) -> (Complex<Float>, Complex<Float>) {
+    let cos_i: Complex<Float> = Complex::new(cos_theta_i.min(1.0 as Float), 0.0 as Float);
+    let sin2_i: Complex<Float> = Complex::new(1.0 as Float, 0.0 as Float) - cos_i * cos_i;
+    let cos_t: Complex<Float> =
+        (Complex::new(1.0 as Float, 0.0 as Float) - sin2_i / (eta * eta)).sqrt();
+    let rs: Complex<Float> = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp: Complex<Float> = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs, rp)
+}
+
+fn dielectric_mueller(
+    cos_theta_i: Float,
+    eta_i: Float,
+    eta_t: Float,
+    reflect: bool,
+) -> MuellerMatrix {
+    // flip indices of refraction if the light arrives from inside
+    let (cos_i, eta) = if cos_theta_i > 0.0 as Float {
+        (cos_theta_i, eta_t / eta_i)
+    } else {
+        (-cos_theta_i, eta_i / eta_t)
+    };
+    let (rs, rp) = fresnel_amplitudes(cos_i, Complex::new(eta, 0.0 as Float));
+    if reflect {
+        MuellerMatrix::fresnel_reflection(rs, rp)
+    } else {
+        MuellerMatrix::fresnel_transmission(
+            1.0 as Float - rs.norm_sqr(),
+            1.0 as Float - rp.norm_sqr(),
+        )
+    }
+}
+
+/// Returns the (normalized) Mueller matrix of a specular event
+/// sampled by **Bsdf::sample_f()**, or **None** if the sampled
+/// component does not polarize light.
+pub fn specular_mueller(bsdf: &Bsdf, wi: &Vector3f, sampled_type: u8) -> Option<MuellerMatrix> {
+    if sampled_type & BxdfType::BsdfSpecular as u8 == 0_u8 {
+        return None;
+    }
+    let reflect: bool = sampled_type & BxdfType::BsdfReflection as u8 != 0_u8;
+    // the angle at which the light arrives (i.e. along -wi)
+    let cos_theta_i: Float = vec3_dot_nrm(wi, &bsdf.ns);
+    for bxdf in bsdf.bxdfs.iter() {
+        let mueller: Option<MuellerMatrix> = match bxdf {
+            Bxdf::SpecRefl(bxdf) if reflect => match bxdf.fresnel {
+                Fresnel::Dielectric(ref fresnel) => Some(dielectric_mueller(
+                    cos_theta_i,
+                    fresnel.eta_i,
+                    fresnel.eta_t,
+                    true,
+                )),
+                Fresnel::Conductor(ref fresnel) => {
+                    // average over the spectral channels
+                    let eta: Complex<Float> = Complex::new(
+                        fresnel.eta_t.average() / fresnel.eta_i.average(),
+                        fresnel.k.average() / fresnel.eta_i.average(),
+                    );
+                    let (rs, rp) = fresnel_amplitudes(cos_theta_i.abs(), eta);
+                    Some(MuellerMatrix::fresnel_reflection(rs, rp))
+                }
+                Fresnel::NoOp(_) => {
+                    // perfect conductor: handedness of circular
+                    // polarization flips
+                    Some(MuellerMatrix::fresnel_reflection(
+                        Complex::new(-1.0 as Float, 0.0 as Float),
+                        Complex::new(1.0 as Float, 0.0 as Float),
+                    ))
+                }
+                Fresnel::Disney(_) => None,
+            },
+            Bxdf::SpecTrans(bxdf) if !reflect => Some(dielectric_mueller(
+                cos_theta_i,
+                bxdf.eta_a,
+                bxdf.eta_b,
+                false,
+            )),
+            Bxdf::FresnelSpec(bxdf) => Some(dielectric_mueller(
+                cos_theta_i,
+                bxdf.eta_a,
+                bxdf.eta_b,
+                reflect,
+            )),
+            _ => None,
+        };
+        if let Some(mueller) = mueller {
+            return Some(mueller.normalized());
+        }
+    }
+    None
+}
+
+/// Polarization state of a camera path: the row vector the Stokes
+/// vector of light arriving along the path gets multiplied with
+/// (normalized to a first component of one, the rest of the
+/// throughput is part of the path's *beta*), and the reference
+/// direction (perpendicular to the ray) its components are expressed
+/// in.
+#[derive(Debug, Copy, Clone)]
+pub struct PolarizationState {
+    pub weight: [Float; 4],
+    pub reference: Vector3f,
+}
+
+impl PolarizationState {
+    /// Starts a camera path along *d*. With a linear polarizer its
+    /// transmission axis is rotated by *angle* (radians) away from
+    /// *x_axis* (usually the camera's horizontal axis in world space).
+    /// Also returns the throughput of the filter for unpolarized
+    /// light (one half for an ideal polarizer).
+    pub fn new(d: &Vector3f, x_axis: &Vector3f, angle: Option<Float>) -> (Self, Float) {
+        let dn: Vector3f = d.normalize();
+        let mut reference: Vector3f = *x_axis - dn * vec3_dot_vec3(x_axis, &dn);
+        if reference.length_squared() == 0.0 as Float {
+            let mut v: Vector3f = Vector3f::default();
+            vec3_coordinate_system(&dn, &mut reference, &mut v);
+        }
+        let mut state: PolarizationState = PolarizationState {
+            weight: [1.0 as Float, 0.0 as Float, 0.0 as Float, 0.0 as Float],
+            reference: reference.normalize(),
+        };
+        let mut throughput: Float = 1.0 as Float;
+        if let Some(angle) = angle {
+            state.weight = MuellerMatrix::linear_polarizer(angle).m[0];
+            throughput = state.normalize();
+        }
+        (state, throughput)
+    }
+    fn normalize(&mut self) -> Float {
+        let w0: Float = self.weight[0];
+        if w0 > 0.0 as Float {
+            for w in self.weight.iter_mut() {
+                *w /= w0;
+            }
+        }
+        w0
+    }
+    /// Updates the state for a bounce from direction *wo* (towards
+    /// the camera) to *wi* and returns the factor the path throughput
+    /// has to be scaled with. **None** for *mueller* depolarizes.
+    pub fn scatter(
+        &mut self,
+        n: &Normal3f,
+        wo: &Vector3f,
+        wi: &Vector3f,
+        mueller: Option<MuellerMatrix>,
+    ) -> Float {
+        if let Some(mueller) = mueller {
+            // rotate into the s/p basis of the plane of incidence
+            let k: Vector3f = wo.normalize();
+            let mut s: Vector3f = vec3_cross_nrm(&k, n);
+            if s.length_squared() < 1e-12 as Float {
+                // normal incidence: any basis will do
+                s = self.reference;
+            }
+            s = s.normalize();
+            let y: Vector3f = vec3_cross_vec3(&k, &self.reference);
+            let cos_phi: Float = vec3_dot_vec3(&s, &self.reference);
+            let sin_phi: Float = vec3_dot_vec3(&s, &y);
+            let c2: Float = cos_phi * cos_phi - sin_phi * sin_phi;
+            let s2: Float = 2.0 as Float * sin_phi * cos_phi;
+            let w: [Float; 4] = [
+                self.weight[0],
+                c2 * self.weight[1] + s2 * self.weight[2],
+                -s2 * self.weight[1] + c2 * self.weight[2],
+                self.weight[3],
+            ];
+            // apply the Mueller matrix
+            for j in 0..4 {
+                self.weight[j] = (0..4).map(|i| w[i] * mueller.m[i][j]).sum();
+            }
+            self.reference = s;
+            self.normalize().max(0.0 as Float)
+        } else {
+            self.weight = [1.0 as Float, 0.0 as Float, 0.0 as Float, 0.0 as Float];
+            let mut v: Vector3f = Vector3f::default();
+            vec3_coordinate_system(&wi.normalize(), &mut self.reference, &mut v);
+            1.0 as Float
+        }
+    }
+}
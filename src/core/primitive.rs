@@ -6,6 +6,7 @@ use std::sync::Arc;
 // pbrt
 use crate::accelerators::bvh::BVHAccel;
 use crate::accelerators::kdtreeaccel::KdTreeAccel;
+use crate::accelerators::meshbvh::MeshBVH;
 use crate::core::geometry::nrm_dot_nrm;
 use crate::core::geometry::{Bounds3f, Ray};
 use crate::core::interaction::SurfaceInteraction;
@@ -20,6 +21,7 @@ use crate::core::transform::{AnimatedTransform, Transform};
 
 pub enum Primitive {
     Geometric(Box<GeometricPrimitive>),
+    Mesh(Box<MeshPrimitive>),
     Transformed(Box<TransformedPrimitive>),
     BVH(Box<BVHAccel>),
     KdTree(Box<KdTreeAccel>),
@@ -29,6 +31,7 @@ impl Primitive {
     pub fn world_bound(&self) -> Bounds3f {
         match self {
             Primitive::Geometric(primitive) => primitive.world_bound(),
+            Primitive::Mesh(primitive) => primitive.bvh.world_bound(),
             Primitive::Transformed(primitive) => primitive.world_bound(),
             Primitive::BVH(primitive) => primitive.world_bound(),
             Primitive::KdTree(primitive) => primitive.world_bound(),
@@ -44,6 +47,13 @@ impl Primitive {
                 }
                 hit_surface
             }
+            Primitive::Mesh(primitive) => {
+                let hit_surface: bool = primitive.intersect(ray, isect);
+                if hit_surface {
+                    isect.primitive = Some(self);
+                }
+                hit_surface
+            }
             Primitive::Transformed(primitive) => primitive.intersect(ray, isect),
             Primitive::BVH(primitive) => primitive.intersect(ray, isect),
             Primitive::KdTree(primitive) => primitive.intersect(ray, isect),
@@ -52,6 +62,7 @@ impl Primitive {
     pub fn intersect_p(&self, ray: &Ray) -> bool {
         match self {
             Primitive::Geometric(primitive) => primitive.intersect_p(ray),
+            Primitive::Mesh(primitive) => primitive.bvh.intersect_p(ray),
             Primitive::Transformed(primitive) => primitive.intersect_p(ray),
             Primitive::BVH(primitive) => primitive.intersect_p(ray),
             Primitive::KdTree(primitive) => primitive.intersect_p(ray),
@@ -60,14 +71,45 @@ impl Primitive {
     pub fn get_area_light(&self) -> Option<Arc<Light>> {
         match self {
             Primitive::Geometric(primitive) => primitive.get_area_light(),
+            Primitive::Mesh(_) => None,
             Primitive::Transformed(primitive) => primitive.get_area_light(),
             Primitive::BVH(primitive) => primitive.get_area_light(),
             Primitive::KdTree(primitive) => primitive.get_area_light(),
         }
     }
+    /// Shadow-catcher primitives only record the shadows and
+    /// reflections they receive (see **SamplerIntegrator**).
+    pub fn is_shadow_catcher(&self) -> bool {
+        match self {
+            Primitive::Geometric(primitive) => primitive.shadow_catcher,
+            Primitive::Mesh(primitive) => primitive.shadow_catcher,
+            _ => false,
+        }
+    }
+    /// Holdout primitives cut holes into the alpha channel of the
+    /// image where the camera sees them, but otherwise (blocking
+    /// light, casting shadows, in reflections) they act like any other
+    /// primitive (see **SamplerIntegrator**).
+    pub fn is_holdout(&self) -> bool {
+        match self {
+            Primitive::Geometric(primitive) => primitive.holdout,
+            Primitive::Mesh(primitive) => primitive.holdout,
+            _ => false,
+        }
+    }
+    /// Priority for nested dielectrics and overlapping media (see
+    /// **InteriorList**), zero means no priority tracking.
+    pub fn get_priority(&self) -> i32 {
+        match self {
+            Primitive::Geometric(primitive) => primitive.priority,
+            Primitive::Mesh(primitive) => primitive.priority,
+            _ => 0_i32,
+        }
+    }
     pub fn get_material(&self) -> Option<Arc<Material>> {
         match self {
             Primitive::Geometric(primitive) => primitive.get_material(),
+            Primitive::Mesh(primitive) => primitive.material.clone(),
             Primitive::Transformed(primitive) => primitive.get_material(),
             Primitive::BVH(primitive) => primitive.get_material(),
             Primitive::KdTree(primitive) => primitive.get_material(),
@@ -107,6 +149,9 @@ pub struct GeometricPrimitive {
     pub material: Option<Arc<Material>>,
     pub area_light: Option<Arc<Light>>,
     pub medium_interface: Option<Arc<MediumInterface>>,
+    pub shadow_catcher: bool,
+    pub holdout: bool,
+    pub priority: i32,
 }
 
 impl GeometricPrimitive {
@@ -123,6 +168,9 @@ impl GeometricPrimitive {
                     material,
                     area_light: Some(area_light),
                     medium_interface: Some(medium_interface),
+                    shadow_catcher: false,
+                    holdout: false,
+                    priority: 0_i32,
                 }
             } else {
                 GeometricPrimitive {
@@ -130,6 +178,9 @@ impl GeometricPrimitive {
                     material,
                     area_light: Some(area_light),
                     medium_interface: None,
+                    shadow_catcher: false,
+                    holdout: false,
+                    priority: 0_i32,
                 }
             }
         } else if let Some(medium_interface) = medium_interface {
@@ -138,6 +189,9 @@ impl GeometricPrimitive {
                 material,
                 area_light: None,
                 medium_interface: Some(medium_interface),
+                shadow_catcher: false,
+                holdout: false,
+                priority: 0_i32,
             }
         } else {
             GeometricPrimitive {
@@ -145,6 +199,9 @@ impl GeometricPrimitive {
                 material,
                 area_light: None,
                 medium_interface: None,
+                shadow_catcher: false,
+                holdout: false,
+                priority: 0_i32,
             }
         }
     }
@@ -161,26 +218,7 @@ impl GeometricPrimitive {
             assert!(nrm_dot_nrm(&isect.n, &isect.shading.n) >= 0.0 as Float);
             // initialize _SurfaceInteraction::mediumInterface_ after
             // _Shape_ intersection
-            if let Some(ref medium_interface) = self.medium_interface {
-                if medium_interface.is_medium_transition() {
-                    isect.medium_interface = Some(medium_interface.clone());
-                } else if let Some(ref medium_arc) = ray.medium {
-                    let inside: Option<Arc<Medium>> = Some(medium_arc.clone());
-                    let outside: Option<Arc<Medium>> = Some(medium_arc.clone());
-                    isect.medium_interface = Some(Arc::new(MediumInterface::new(inside, outside)));
-                }
-                // print!("medium_interface = {{inside = ");
-                // if let Some(ref inside) = medium_interface.inside {
-                //     print!("{:p} , outside = ", inside);
-                // } else {
-                //     print!("0x0 , outside = ")
-                // }
-                // if let Some(ref outside) = medium_interface.outside {
-                //     println!("{:p}}}", outside);
-                // } else {
-                //     println!("0x0}}")
-                // }
-            }
+            set_medium_interface(&self.medium_interface, ray, isect);
             true
         } else {
             false
@@ -205,6 +243,73 @@ impl GeometricPrimitive {
     }
 }
 
+/// The medium interface of a surface hit, with the medium of the ray
+/// on both sides if the surface doesn't separate two media.
+fn set_medium_interface(
+    medium_interface: &Option<Arc<MediumInterface>>,
+    ray: &Ray,
+    isect: &mut SurfaceInteraction,
+) {
+    if let Some(medium_interface) = medium_interface {
+        if medium_interface.is_medium_transition() {
+            isect.medium_interface = Some(medium_interface.clone());
+        } else if let Some(ref medium_arc) = ray.medium {
+            let inside: Option<Arc<Medium>> = Some(medium_arc.clone());
+            let outside: Option<Arc<Medium>> = Some(medium_arc.clone());
+            isect.medium_interface = Some(Arc::new(MediumInterface::new(inside, outside)));
+        }
+        // print!("medium_interface = {{inside = ");
+        // if let Some(ref inside) = medium_interface.inside {
+        //     print!("{:p} , outside = ", inside);
+        // } else {
+        //     print!("0x0 , outside = ")
+        // }
+        // if let Some(ref outside) = medium_interface.outside {
+        //     println!("{:p}}}", outside);
+        // } else {
+        //     println!("0x0}}")
+        // }
+    }
+}
+
+/// All triangles of a mesh as one primitive, for the low-memory mode
+/// (see **lowmemory**). Meshes with area lights are never stored this
+/// way, every emitting triangle needs a shape of its own.
+#[derive(Clone)]
+pub struct MeshPrimitive {
+    pub bvh: Arc<MeshBVH>,
+    pub material: Option<Arc<Material>>,
+    pub medium_interface: Option<Arc<MediumInterface>>,
+    pub shadow_catcher: bool,
+    pub holdout: bool,
+    pub priority: i32,
+}
+
+impl MeshPrimitive {
+    pub fn new(
+        bvh: Arc<MeshBVH>,
+        material: Option<Arc<Material>>,
+        medium_interface: Option<Arc<MediumInterface>>,
+    ) -> Self {
+        MeshPrimitive {
+            bvh,
+            material,
+            medium_interface,
+            shadow_catcher: false,
+            holdout: false,
+            priority: 0_i32,
+        }
+    }
+    pub fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
+        if self.bvh.intersect(ray, isect) {
+            set_medium_interface(&self.medium_interface, ray, isect);
+            true
+        } else {
+            false
+        }
+    }
+}
+
 pub struct TransformedPrimitive {
     pub primitive: Arc<Primitive>,
     pub primitive_to_world: AnimatedTransform,
@@ -258,9 +363,8 @@ impl TransformedPrimitive {
                 // is.shading.dpdv = new_isect.shading.dpdv;
                 // is.shading.dndu = new_isect.shading.dndu;
                 // is.shading.dndv = new_isect.shading.dndv;
-                return true;
             }
-            false
+            true
         } else {
             false
         }
@@ -0,0 +1,278 @@
+//! Progress of the running render (see **ProgressReporter**), printed
+//! to the terminal and handed to a callback (see
+//! **set_progress_callback()**), e.g. for render farm wrappers.
+//!
+//! The printed lines have a fixed format, so they can be parsed:
+//!
+//! ```text
+//! Rendering: 45.3% | 116/256 tiles | 1.82M rays/s | elapsed 0:00:42 | ETA 0:00:51 | mem 512 MiB
+//! ```
+//!
+//! On a terminal the line gets redrawn, otherwise (e.g. when stdout is
+//! redirected into a log file) a new line is written every
+//! **LOG_INTERVAL**. Unknown values (the ETA before the first unit of
+//! work is done, the memory on other systems than Linux) are written
+//! as **-**.
+
+// std
+use std::cell::Cell;
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// how often the line gets redrawn on a terminal
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(250);
+/// how often a line gets written if stdout is not a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// how often the callback gets called (at most)
+const CALLBACK_INTERVAL: Duration = Duration::from_millis(500);
+/// rays are counted per thread and added to **RAYS_TRACED** in batches
+const RAY_BATCH: u64 = 1024;
+
+static RAYS_TRACED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static RAYS_NOT_ADDED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Counts a traced ray (see **Scene::intersect()**), for the rays per
+/// second of the progress reports.
+pub fn count_ray() {
+    RAYS_NOT_ADDED.with(|n| {
+        let count: u64 = n.get() + 1;
+        if count == RAY_BATCH {
+            RAYS_TRACED.fetch_add(RAY_BATCH, Ordering::Relaxed);
+            n.set(0);
+        } else {
+            n.set(count);
+        }
+    });
+}
+
+/// The number of rays traced so far (by all threads, in batches of
+/// **RAY_BATCH**).
+pub fn rays_traced() -> u64 {
+    RAYS_TRACED.load(Ordering::Relaxed)
+}
+
+/// Gets called with each progress report.
+pub type ProgressCallback = Box<dyn Fn(&ProgressReport) + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref PROGRESS_CALLBACK: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+}
+
+/// Sets the function which gets the progress of all following renders
+/// (**None** removes it). It gets called from a render thread, at
+/// most every **CALLBACK_INTERVAL**, and once more when the work is
+/// done.
+pub fn set_progress_callback(callback: Option<ProgressCallback>) {
+    *PROGRESS_CALLBACK.write().unwrap() = callback;
+}
+
+/// The state of the work reported by a **ProgressReporter**.
+#[derive(Debug, Clone)]
+pub struct ProgressReport {
+    /// what is done, e.g. "Rendering"
+    pub title: String,
+    /// what is counted, e.g. "tiles"
+    pub unit: String,
+    pub done: u64,
+    pub total: u64,
+    /// 0 to 100
+    pub percent: f64,
+    /// since the reporter was created
+    pub rays_per_second: f64,
+    pub elapsed: Duration,
+    /// **None** until the first unit of work is done
+    pub eta: Option<Duration>,
+    /// resident memory of the process in bytes (Linux only)
+    pub memory: Option<u64>,
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.1}% | {}/{} {} | {} rays/s | elapsed {} | ETA {} | mem ",
+            self.title,
+            self.percent,
+            self.done,
+            self.total,
+            self.unit,
+            format_count(self.rays_per_second),
+            format_duration(self.elapsed),
+            self.eta.map_or_else(|| String::from("-"), format_duration),
+        )?;
+        match self.memory {
+            Some(bytes) => write!(f, "{} MiB", bytes / (1024 * 1024)),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+/// Reports the progress of *total* units of work (e.g. tiles) on the
+/// terminal and to the callback (see **set_progress_callback()**),
+/// like pbrt's **ProgressReporter**.
+pub struct ProgressReporter {
+    title: String,
+    unit: String,
+    total: u64,
+    done: u64,
+    start: Instant,
+    rays_at_start: u64,
+    terminal: bool,
+    last_print: Option<Instant>,
+    last_callback: Option<Instant>,
+}
+
+impl ProgressReporter {
+    pub fn new(total: u64, title: &str, unit: &str) -> Self {
+        let mut reporter: ProgressReporter = ProgressReporter {
+            title: String::from(title),
+            unit: String::from(unit),
+            total,
+            done: 0,
+            start: Instant::now(),
+            rays_at_start: rays_traced(),
+            terminal: stdout_is_terminal(),
+            last_print: None,
+            last_callback: None,
+        };
+        reporter.report(false);
+        reporter
+    }
+    /// Another *n* units of work are done.
+    pub fn update(&mut self, n: u64) {
+        self.done = std::cmp::min(self.done + n, self.total);
+        self.report(false);
+    }
+    /// Reports one last time (e.g. after the render was cancelled, the
+    /// work doesn't have to be complete).
+    pub fn done(&mut self) {
+        self.report(true);
+        if self.terminal {
+            println!();
+        }
+    }
+    pub fn get_report(&self) -> ProgressReport {
+        let elapsed: Duration = self.start.elapsed();
+        let seconds: f64 = elapsed.as_secs_f64();
+        let rays: u64 = rays_traced().saturating_sub(self.rays_at_start);
+        ProgressReport {
+            title: self.title.clone(),
+            unit: self.unit.clone(),
+            done: self.done,
+            total: self.total,
+            percent: if self.total == 0 {
+                100.0
+            } else {
+                100.0 * self.done as f64 / self.total as f64
+            },
+            rays_per_second: if seconds > 0.0 {
+                rays as f64 / seconds
+            } else {
+                0.0
+            },
+            elapsed,
+            eta: if self.done == 0 {
+                None
+            } else {
+                let remaining: u64 = self.total - self.done;
+                Some(Duration::from_secs_f64(
+                    seconds * remaining as f64 / self.done as f64,
+                ))
+            },
+            memory: resident_memory(),
+        }
+    }
+    /// Prints the report and calls the callback (unless that was done
+    /// recently).
+    fn report(&mut self, last: bool) {
+        let now: Instant = Instant::now();
+        let due = |time: Option<Instant>, interval: Duration| -> bool {
+            last || time.is_none_or(|time| now.duration_since(time) >= interval)
+        };
+        let print_interval: Duration = if self.terminal {
+            TERMINAL_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        let print: bool = due(self.last_print, print_interval);
+        let callback: bool = due(self.last_callback, CALLBACK_INTERVAL);
+        if !print && !callback {
+            return;
+        }
+        let report: ProgressReport = self.get_report();
+        if print {
+            self.last_print = Some(now);
+            if self.terminal {
+                // clear the rest of the (longer) line printed before
+                print!("\r{}\x1b[K", report);
+                std::io::stdout().flush().ok();
+            } else {
+                println!("{}", report);
+            }
+        }
+        if callback {
+            self.last_callback = Some(now);
+            if let Some(ref callback) = *PROGRESS_CALLBACK.read().unwrap() {
+                callback(&report);
+            }
+        }
+    }
+}
+
+/// E.g. "1.82M".
+fn format_count(count: f64) -> String {
+    if count >= 1e9 {
+        format!("{:.2}G", count / 1e9)
+    } else if count >= 1e6 {
+        format!("{:.2}M", count / 1e6)
+    } else if count >= 1e3 {
+        format!("{:.2}K", count / 1e3)
+    } else {
+        format!("{:.0}", count)
+    }
+}
+
+/// E.g. "1:02:03" (hours, minutes, seconds).
+fn format_duration(duration: Duration) -> String {
+    let seconds: u64 = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+    false
+}
+
+/// The resident set size (see **/proc/self/statm**).
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let statm: String = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size: i64 = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if page_size > 0 {
+        Some(pages * page_size as u64)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory() -> Option<u64> {
+    None
+}
@@ -6,20 +6,19 @@
 
 // std
 use std;
-use std::f32::consts::PI;
-use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::sync::Arc;
 // others
 use byteorder::{LittleEndian, ReadBytesExt};
+use log::error;
 use num::Zero;
 use smallvec::SmallVec;
 // pbrt
 use crate::core::bssrdf::SeparableBssrdfAdapter;
 use crate::core::geometry::{
-    nrm_cross_vec3, nrm_dot_vec3, nrm_faceforward_vec3, vec3_abs_dot_vec3, vec3_dot_nrm,
-    vec3_dot_vec3,
+    nrm_cross_vec3, nrm_dot_nrm, nrm_dot_vec3, nrm_faceforward_vec3, vec3_abs_dot_vec3,
+    vec3_dot_nrm, vec3_dot_vec3,
 };
 use crate::core::geometry::{Normal3f, Point2f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
@@ -27,7 +26,9 @@ use crate::core::interpolation::{
     catmull_rom_weights, fourier, sample_catmull_rom_2d, sample_fourier,
 };
 use crate::core::material::TransportMode;
-use crate::core::microfacet::MicrofacetDistribution;
+use crate::core::memory::MappedFile;
+use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::INV_PI;
 use crate::core::pbrt::{clamp_t, lerp, radians};
 use crate::core::pbrt::{Float, Spectrum};
@@ -81,13 +82,14 @@ pub struct FourierBSDFTable {
 impl FourierBSDFTable {
     pub fn read(&mut self, filename: &str) -> bool {
         let path = Path::new(&filename);
-        let result = File::open(path);
+        let result = MappedFile::open(path);
         if result.is_err() {
-            println!("ERROR: Unable to open tabulated BSDF file {:?}", filename);
+            error!("Unable to open tabulated BSDF file {:?}", filename);
             return false;
         }
         // header
-        let mut file = result.unwrap();
+        let mapped_file: MappedFile = result.unwrap();
+        let mut file: &[u8] = &mapped_file;
         let mut buffer = [0; 8];
         let io_result = file.read_exact(&mut buffer);
         if io_result.is_ok() {
@@ -105,7 +107,7 @@ impl FourierBSDFTable {
                     let mut buffer: [f32; 1] = [0_f32; 1]; // 1 32-bit float
                     let io_result = file.read_f32_into::<LittleEndian>(&mut buffer);
                     if io_result.is_ok() {
-                        self.eta = buffer[0];
+                        self.eta = buffer[0] as Float;
                         let mut buffer: [i32; 4] = [0; 4]; // 4 32-bit (signed) integers are unused
                         let io_result = file.read_i32_into::<LittleEndian>(&mut buffer);
                         if io_result.is_ok() {
@@ -231,6 +233,12 @@ pub struct Bsdf {
     pub ss: Vector3f,
     pub ts: Vector3f,
     pub bxdfs: [Bxdf; 8],
+    /// fluorescence: row-major re-radiation matrix (incident spectral
+    /// channel in columns, re-emitted channel in rows)
+    pub reradiation: Option<Arc<Vec<Float>>>,
+    /// soften the shadow terminator (see **terminator_shadowing()**),
+    /// set by integrators with *terminatorshadowing*
+    pub shadow_terminator: bool,
 }
 
 impl Bsdf {
@@ -252,6 +260,193 @@ impl Bsdf {
                 Bxdf::Empty(NoBxdf::default()),
                 Bxdf::Empty(NoBxdf::default()),
             ],
+            reradiation: None,
+            shadow_terminator: false,
+        }
+    }
+    /// Once a path only carries a single spectral channel (e.g. after
+    /// a dispersive refraction) further refractions have to use the
+    /// index of refraction of that channel.
+    pub fn set_dispersion_channel(&mut self, beta: &Spectrum) {
+        let channel: Option<usize> = beta.single_channel();
+        for bxdf in self.bxdfs.iter_mut() {
+            match bxdf {
+                Bxdf::SpecTrans(bxdf) => bxdf.channel = channel,
+                Bxdf::FresnelSpec(bxdf) => bxdf.channel = channel,
+                _ => {}
+            }
+        }
+    }
+    /// Path roughening: makes the specular and glossy components at
+    /// least as rough as a Trowbridge-Reitz distribution with *alpha*.
+    /// Specular components become microfacet ones (which ignore
+    /// dispersion), specular glass gets split into reflection and
+    /// transmission if there is room for another component.
+    pub fn roughen(&mut self, alpha: Float) {
+        if alpha <= 0.0 as Float {
+            return;
+        }
+        let rough = MicrofacetDistribution::TrowbridgeReitz(TrowbridgeReitzDistribution::new(
+            alpha, alpha, true,
+        ));
+        let mut free: usize = self
+            .bxdfs
+            .iter()
+            .filter(|bxdf| matches!(bxdf, Bxdf::Empty(_)))
+            .count();
+        let mut transmissions: Vec<Bxdf> = Vec::new();
+        for bxdf in self.bxdfs.iter_mut() {
+            let roughened: Option<Bxdf> = match bxdf {
+                Bxdf::SpecRefl(specular) => Some(Bxdf::MicrofacetRefl(MicrofacetReflection::new(
+                    specular.r,
+                    rough,
+                    specular.fresnel,
+                    specular.sc_opt,
+                ))),
+                Bxdf::SpecTrans(specular) => {
+                    Some(Bxdf::MicrofacetTrans(MicrofacetTransmission::new(
+                        specular.t,
+                        rough,
+                        specular.eta_a,
+                        specular.eta_b,
+                        specular.mode,
+                        specular.sc_opt,
+                    )))
+                }
+                Bxdf::FresnelSpec(specular) if free > 0 => {
+                    free -= 1;
+                    transmissions.push(Bxdf::MicrofacetTrans(MicrofacetTransmission::new(
+                        specular.t,
+                        rough,
+                        specular.eta_a,
+                        specular.eta_b,
+                        specular.mode,
+                        specular.sc_opt,
+                    )));
+                    Some(Bxdf::MicrofacetRefl(MicrofacetReflection::new(
+                        specular.r,
+                        rough,
+                        Fresnel::Dielectric(FresnelDielectric {
+                            eta_i: specular.eta_a,
+                            eta_t: specular.eta_b,
+                        }),
+                        specular.sc_opt,
+                    )))
+                }
+                Bxdf::MicrofacetRefl(glossy) => {
+                    glossy.distribution.roughen(alpha);
+                    None
+                }
+                Bxdf::MicrofacetTrans(glossy) => {
+                    glossy.distribution.roughen(alpha);
+                    None
+                }
+                Bxdf::FresnelBlnd(glossy) => {
+                    if let Some(ref mut distribution) = glossy.distribution {
+                        distribution.roughen(alpha);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(roughened) = roughened {
+                *bxdf = roughened;
+            }
+        }
+        for transmission in transmissions {
+            if let Some(bxdf) = self
+                .bxdfs
+                .iter_mut()
+                .find(|bxdf| matches!(bxdf, Bxdf::Empty(_)))
+            {
+                *bxdf = transmission;
+            }
+        }
+    }
+    /// The roughness (as microfacet *alpha*, at most 1) of the
+    /// roughest component matching *flags*: 0 for specular and 1 for
+    /// diffuse components (and glossy ones without a microfacet
+    /// distribution).
+    pub fn roughness(&self, flags: u8) -> Float {
+        let mut roughness: Float = 0.0 as Float;
+        for bxdf in self.bxdfs.iter() {
+            if !bxdf.matches_flags(flags) {
+                continue;
+            }
+            let alpha: Float = match bxdf {
+                Bxdf::SpecRefl(_) | Bxdf::SpecTrans(_) | Bxdf::FresnelSpec(_) => 0.0 as Float,
+                Bxdf::MicrofacetRefl(glossy) => glossy.distribution.alpha(),
+                Bxdf::MicrofacetTrans(glossy) => glossy.distribution.alpha(),
+                _ => 1.0 as Float,
+            };
+            roughness = roughness.max(alpha.min(1.0 as Float));
+        }
+        roughness
+    }
+    /// Samples a wavelength shift for fluorescent surfaces (with a
+    /// re-radiation matrix). Afterwards the camera path only carries
+    /// the (incident) spectral channel light gets absorbed in, while
+    /// its radiance belongs to the channel the camera sees, see
+    /// **ChannelShift**.
+    pub fn reradiate(
+        &self,
+        beta: &Spectrum,
+        l: &mut Spectrum,
+        shift: &mut Option<ChannelShift>,
+        u: Float,
+    ) -> Spectrum {
+        if let Some(ref matrix) = self.reradiation {
+            let n: usize = Spectrum::N_SAMPLES;
+            let (camera, current, value, mut u) = if let Some(ref old) = shift {
+                // radiance gathered so far belongs to the camera channel
+                *l = old.apply(l);
+                (old.camera, old.current, beta[old.current], u)
+            } else {
+                // choose the channel the camera sees proportional to beta
+                let sum: Float = (0..n).map(|i| beta[i]).sum();
+                if sum <= 0.0 as Float {
+                    return Spectrum::default();
+                }
+                let mut camera: usize = n - 1;
+                let mut cdf: Float = 0.0 as Float;
+                let mut u_remapped: Float = u;
+                for i in 0..n {
+                    let p: Float = beta[i] / sum;
+                    if u < cdf + p || i == n - 1 {
+                        camera = i;
+                        u_remapped = ((u - cdf) / p).min(FLOAT_ONE_MINUS_EPSILON);
+                        break;
+                    }
+                    cdf += p;
+                }
+                (camera, camera, sum, u_remapped)
+            };
+            // choose the incident channel proportional to the row of
+            // the re-emitted channel
+            let row: &[Float] = &matrix[current * n..(current + 1) * n];
+            let row_sum: Float = row.iter().sum();
+            if row_sum <= 0.0 as Float {
+                return Spectrum::default();
+            }
+            u *= row_sum;
+            let mut incident: usize = n - 1;
+            for (k, m) in row.iter().enumerate() {
+                if u < *m {
+                    incident = k;
+                    break;
+                }
+                u -= *m;
+            }
+            *shift = Some(ChannelShift {
+                camera,
+                current: incident,
+                l_start: *l,
+            });
+            let mut result: Spectrum = Spectrum::default();
+            result[incident] = value * row_sum;
+            result
+        } else {
+            *beta
         }
     }
     pub fn num_components(&self, flags: u8) -> u8 {
@@ -278,6 +473,29 @@ impl Bsdf {
             z: self.ss.z * v.x + self.ts.z * v.y + self.ns.z * v.z,
         }
     }
+    /// Softens the hard shadow terminator of shading normals (smooth
+    /// normals of low-poly meshes or strong bump maps) which tilt the
+    /// surface towards light the geometry doesn't get (Chiang et al.,
+    /// "Taming the Shadow Terminator", 2019). Scales the reflected
+    /// light from *wi_w* down to zero where the geometric normal turns
+    /// away from it. Only applied if **shadow_terminator** is set and
+    /// the shading normal differs from the geometric one, otherwise
+    /// the factor is one.
+    pub fn terminator_shadowing(&self, wi_w: &Vector3f) -> Float {
+        if !self.shadow_terminator || self.ns == self.ng {
+            return 1.0 as Float;
+        }
+        let cos_ng: Float = vec3_dot_nrm(wi_w, &self.ng).abs();
+        let cos_ns: Float = vec3_dot_nrm(wi_w, &self.ns).abs();
+        let ng_dot_ns: Float = nrm_dot_nrm(&self.ng, &self.ns).abs();
+        if cos_ns * ng_dot_ns <= cos_ng {
+            return 1.0 as Float;
+        }
+        let g: Float = cos_ng / (cos_ns * ng_dot_ns);
+        // smooth falloff of G (the shadowing of a microfacet-like
+        // distribution of normals)
+        -g * g * g + g * g + g
+    }
     pub fn f(&self, wo_w: &Vector3f, wi_w: &Vector3f, flags: u8) -> Spectrum {
         // TODO: ProfilePhase pp(Prof::BSDFEvaluation);
         let wi: Vector3f = self.world_to_local(wi_w);
@@ -299,6 +517,9 @@ impl Bsdf {
                 f += self.bxdfs[i].f(&wo, &wi);
             }
         }
+        if reflect {
+            f = f * self.terminator_shadowing(wi_w);
+        }
         f
     }
     /// Calls the individual Bxdf::sample_f() methods to generate samples.
@@ -415,6 +636,10 @@ impl Bsdf {
                     }
                 }
             }
+            if vec3_dot_nrm(&*wi_world, &self.ng) * vec3_dot_nrm(wo_world, &self.ng) > 0.0 as Float
+            {
+                f = f * self.terminator_shadowing(wi_world);
+            }
             // let mut ratio: Spectrum = Spectrum::default();
             // if *pdf > 0.0 as Float {
             //     ratio = f / *pdf;
@@ -639,6 +864,29 @@ impl Bxdf {
     }
 }
 
+/// Tracks a camera path whose wavelength got shifted by a
+/// fluorescent surface: radiance the path gathers in the *current*
+/// spectral channel (since it reached *l_start*) is seen by the camera
+/// in another one.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ChannelShift {
+    pub camera: usize,
+    pub current: usize,
+    pub l_start: Spectrum,
+}
+
+impl ChannelShift {
+    /// Moves the radiance gathered since the shift into the camera's
+    /// channel.
+    pub fn apply(&self, l: &Spectrum) -> Spectrum {
+        let mut result: Spectrum = *l;
+        let gathered: Float = l[self.current] - self.l_start[self.current];
+        result[self.current] = self.l_start[self.current];
+        result[self.camera] += gathered;
+        result
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum Fresnel {
     NoOp(FresnelNoOp),
@@ -766,6 +1014,10 @@ pub struct SpecularTransmission {
     pub fresnel: FresnelDielectric,
     pub mode: TransportMode,
     pub sc_opt: Option<Spectrum>,
+    /// Cauchy coefficient B (zero means no dispersion)
+    pub cauchy_b: Float,
+    /// spectral channel already chosen for dispersion (if any)
+    pub channel: Option<usize>,
 }
 
 impl SpecularTransmission {
@@ -786,6 +1038,8 @@ impl SpecularTransmission {
             },
             mode,
             sc_opt,
+            cauchy_b: 0.0 as Float,
+            channel: None,
         }
     }
     pub fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
@@ -795,14 +1049,16 @@ impl SpecularTransmission {
         &self,
         wo: &Vector3f,
         wi: &mut Vector3f,
-        _sample: Point2f,
+        sample: Point2f,
         pdf: &mut Float,
         _sampled_type: &mut u8,
     ) -> Spectrum {
+        // for dispersion pick the wavelength to refract
+        let (eta_b, weight) = sample_dispersion(self.eta_b, self.cauchy_b, self.channel, sample[1]);
         // figure out which $\eta$ is incident and which is transmitted
         let entering: bool = cos_theta(wo) > 0.0;
-        let eta_i = if entering { self.eta_a } else { self.eta_b };
-        let eta_t = if entering { self.eta_b } else { self.eta_a };
+        let eta_i = if entering { self.eta_a } else { eta_b };
+        let eta_t = if entering { eta_b } else { self.eta_a };
         // compute ray direction for specular transmission
         if !refract(
             wo,
@@ -820,8 +1076,12 @@ impl SpecularTransmission {
             return Spectrum::default();
         }
         *pdf = 1.0;
+        let fresnel: FresnelDielectric = FresnelDielectric {
+            eta_i: self.eta_a,
+            eta_t: eta_b,
+        };
         let mut ft: Spectrum =
-            self.t * (Spectrum::new(1.0 as Float) - self.fresnel.evaluate(cos_theta(&*wi)));
+            weight * self.t * (Spectrum::new(1.0 as Float) - fresnel.evaluate(cos_theta(&*wi)));
         // account for non-symmetry with transmission to different medium
         if self.mode == TransportMode::Radiance {
             ft *= Spectrum::new((eta_i * eta_i) / (eta_t * eta_t));
@@ -852,6 +1112,10 @@ pub struct FresnelSpecular {
     pub eta_b: Float,
     pub mode: TransportMode,
     pub sc_opt: Option<Spectrum>,
+    /// Cauchy coefficient B (zero means no dispersion)
+    pub cauchy_b: Float,
+    /// spectral channel already chosen for dispersion (if any)
+    pub channel: Option<usize>,
 }
 
 impl FresnelSpecular {
@@ -870,6 +1134,8 @@ impl FresnelSpecular {
             eta_b,
             mode,
             sc_opt,
+            cauchy_b: 0.0 as Float,
+            channel: None,
         }
     }
     pub fn f(&self, _wo: &Vector3f, _wi: &Vector3f) -> Spectrum {
@@ -906,10 +1172,13 @@ impl FresnelSpecular {
         } else {
             // compute specular transmission for _FresnelSpecular_
 
+            // for dispersion pick the wavelength to refract
+            let (eta_b, weight) =
+                sample_dispersion(self.eta_b, self.cauchy_b, self.channel, sample[1]);
             // figure out which $\eta$ is incident and which is transmitted
             let entering: bool = cos_theta(wo) > 0.0 as Float;
-            let eta_i = if entering { self.eta_a } else { self.eta_b };
-            let eta_t = if entering { self.eta_b } else { self.eta_a };
+            let eta_i = if entering { self.eta_a } else { eta_b };
+            let eta_t = if entering { eta_b } else { self.eta_a };
             // compute ray direction for specular transmission
             if !refract(
                 wo,
@@ -926,7 +1195,9 @@ impl FresnelSpecular {
             ) {
                 return Spectrum::default();
             }
-            let mut ft: Spectrum = self.t * (1.0 as Float - f);
+            // the Fresnel term used for sampling ignores dispersion
+            let ft_lambda: Float = 1.0 as Float - fr_dielectric(ct, self.eta_a, eta_b);
+            let mut ft: Spectrum = weight * self.t * ft_lambda;
             // account for non-symmetry with transmission to different medium
             if self.mode == TransportMode::Radiance {
                 ft *= Spectrum::new((eta_i * eta_i) / (eta_t * eta_t));
@@ -1409,7 +1680,7 @@ impl FresnelBlend {
                 * (distribution.d(&wh)
                     / (4.0
                         * vec3_dot_vec3(wi, &wh).abs()
-                        * f32::max(abs_cos_theta(wi), abs_cos_theta(wo))));
+                        * Float::max(abs_cos_theta(wi), abs_cos_theta(wo))));
             if let Some(sc) = self.sc_opt {
                 sc * (diffuse + specular)
             } else {
@@ -1573,9 +1844,9 @@ impl FourierBSDF {
             let g: Float = 1.398_29 as Float * y - 0.100_913 as Float * b - 0.297_375 as Float * r;
             let rgb: [Float; 3] = [r * scale, g * scale, b * scale];
             if let Some(sc) = self.sc_opt {
-                sc * Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
+                sc * Spectrum::from_rgb(&rgb).clamp(0.0 as Float, Float::INFINITY)
             } else {
-                Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
+                Spectrum::from_rgb(&rgb).clamp(0.0 as Float, Float::INFINITY)
             }
         }
     }
@@ -1712,9 +1983,9 @@ impl FourierBSDF {
             let g: Float = 1.398_29 as Float * y - 0.100_913 as Float * b - 0.297_375 as Float * r;
             let rgb: [Float; 3] = [r * scale, g * scale, b * scale];
             if let Some(sc) = self.sc_opt {
-                sc * Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
+                sc * Spectrum::from_rgb(&rgb).clamp(0.0 as Float, Float::INFINITY)
             } else {
-                Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
+                Spectrum::from_rgb(&rgb).clamp(0.0 as Float, Float::INFINITY)
             }
         }
     }
@@ -1939,6 +2210,58 @@ pub fn fr_dielectric(cos_theta_i: Float, eta_i: Float, eta_t: Float) -> Float {
     (r_parl * r_parl + r_perp * r_perp) / 2.0
 }
 
+/// Wavelength (in nm) of the Fraunhofer d line, at which the index of
+/// refraction of dispersive dielectrics is specified.
+pub const FRAUNHOFER_D: Float = 587.56;
+
+/// Index of refraction at **lambda** (in nm) following Cauchy's
+/// equation, given the index **eta** at the Fraunhofer d line and the
+/// coefficient **cauchy_b** (in square micrometers).
+pub fn cauchy_eta(eta: Float, cauchy_b: Float, lambda: Float) -> Float {
+    let l: Float = lambda / 1000.0 as Float;
+    let l_d: Float = FRAUNHOFER_D / 1000.0 as Float;
+    eta + cauchy_b * (1.0 as Float / (l * l) - 1.0 as Float / (l_d * l_d))
+}
+
+/// Cauchy coefficient B for a glass with index **eta** (at the
+/// Fraunhofer d line) and the given Abbe number.
+pub fn cauchy_b_from_abbe(eta: Float, abbe: Float) -> Float {
+    // Fraunhofer F and C lines (in micrometers)
+    let l_f: Float = 0.486_13;
+    let l_c: Float = 0.656_27;
+    (eta - 1.0 as Float) / (abbe * (1.0 as Float / (l_f * l_f) - 1.0 as Float / (l_c * l_c)))
+}
+
+/// Without dispersion returns **eta** and a white weight. Otherwise a
+/// single spectral channel is chosen (uniformly, using **u**, unless
+/// **channel** was chosen before) to carry the refracted light, and
+/// its index of refraction is returned with a weight that is zero
+/// except for that channel.
+pub fn sample_dispersion(
+    eta: Float,
+    cauchy_b: Float,
+    channel: Option<usize>,
+    u: Float,
+) -> (Float, Spectrum) {
+    if cauchy_b == 0.0 as Float {
+        return (eta, Spectrum::new(1.0 as Float));
+    }
+    let n: usize = Spectrum::N_SAMPLES;
+    let (ch, pdf) = match channel {
+        Some(ch) => (ch, 1.0 as Float),
+        None => (
+            ((u * n as Float) as usize).min(n - 1),
+            1.0 as Float / n as Float,
+        ),
+    };
+    let mut weight: Spectrum = Spectrum::default();
+    weight[ch] = 1.0 as Float / pdf;
+    (
+        cauchy_eta(eta, cauchy_b, Spectrum::channel_wavelength(ch)),
+        weight,
+    )
+}
+
 /// Computes the Fresnel reflectance at the boundary between a
 /// conductor and a dielectric medium.
 pub fn fr_conductor(cos_theta_i: Float, eta_i: Spectrum, eta_t: Spectrum, k: Spectrum) -> Spectrum {
@@ -10,7 +10,10 @@ use crate::core::pbrt::Float;
 //#ifndef PBRT_HAVE_HEX_FP_CONSTANTS
 //pub const FLOAT_ONE_MINUS_EPSILON: Float = 0.99999994;
 //#else
+#[cfg(not(feature = "float_as_double"))]
 pub const FLOAT_ONE_MINUS_EPSILON: Float = hexf32!("0x1.fffffep-1");
+#[cfg(feature = "float_as_double")]
+pub const FLOAT_ONE_MINUS_EPSILON: Float = hexf64!("0x1.fffffffffffffp-1");
 //#endif
 pub const PCG32_DEFAULT_STATE: u64 = 0x853c_49e6_748f_ea9b;
 pub const PCG32_DEFAULT_STREAM: u64 = 0xda3e_39cb_94b9_5bdb;
@@ -5,10 +5,12 @@
 // pbrt
 use crate::core::camera::CameraSample;
 use crate::core::geometry::{Point2f, Point2i};
-use crate::core::pbrt::Float;
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::{mix_bits, Float};
 use crate::integrators::mlt::MLTSampler;
 use crate::samplers::halton::HaltonSampler;
 use crate::samplers::maxmin::MaxMinDistSampler;
+use crate::samplers::pmj02bn::PMJ02BNSampler;
 use crate::samplers::random::RandomSampler;
 use crate::samplers::sobol::SobolSampler;
 use crate::samplers::stratified::StratifiedSampler;
@@ -20,6 +22,7 @@ pub enum Sampler {
     Halton(HaltonSampler),
     MaxMinDist(MaxMinDistSampler),
     MLT(MLTSampler),
+    PMJ02BN(PMJ02BNSampler),
     Random(RandomSampler),
     Sobol(SobolSampler),
     Stratified(StratifiedSampler),
@@ -32,6 +35,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.clone_with_seed(seed),
             Sampler::MaxMinDist(sampler) => sampler.clone_with_seed(seed),
             Sampler::MLT(sampler) => sampler.clone_with_seed(seed),
+            Sampler::PMJ02BN(sampler) => sampler.clone_with_seed(seed),
             Sampler::Random(sampler) => sampler.clone_with_seed(seed),
             Sampler::Sobol(sampler) => sampler.clone_with_seed(seed),
             Sampler::Stratified(sampler) => sampler.clone_with_seed(seed),
@@ -43,6 +47,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.start_pixel(p),
             Sampler::MaxMinDist(sampler) => sampler.start_pixel(p),
             Sampler::MLT(sampler) => sampler.start_pixel(p),
+            Sampler::PMJ02BN(sampler) => sampler.start_pixel(p),
             Sampler::Random(sampler) => sampler.start_pixel(p),
             Sampler::Sobol(sampler) => sampler.start_pixel(p),
             Sampler::Stratified(sampler) => sampler.start_pixel(p),
@@ -54,6 +59,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_1d(),
             Sampler::MaxMinDist(sampler) => sampler.get_1d(),
             Sampler::MLT(sampler) => sampler.get_1d(),
+            Sampler::PMJ02BN(sampler) => sampler.get_1d(),
             Sampler::Random(sampler) => sampler.get_1d(),
             Sampler::Sobol(sampler) => sampler.get_1d(),
             Sampler::Stratified(sampler) => sampler.get_1d(),
@@ -65,6 +71,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_2d(),
             Sampler::MaxMinDist(sampler) => sampler.get_2d(),
             Sampler::MLT(sampler) => sampler.get_2d(),
+            Sampler::PMJ02BN(sampler) => sampler.get_2d(),
             Sampler::Random(sampler) => sampler.get_2d(),
             Sampler::Sobol(sampler) => sampler.get_2d(),
             Sampler::Stratified(sampler) => sampler.get_2d(),
@@ -86,6 +93,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.request_2d_array(n),
             Sampler::MaxMinDist(sampler) => sampler.request_2d_array(n),
             Sampler::MLT(sampler) => sampler.request_2d_array(n),
+            Sampler::PMJ02BN(sampler) => sampler.request_2d_array(n),
             Sampler::Random(sampler) => sampler.request_2d_array(n),
             Sampler::Sobol(sampler) => sampler.request_2d_array(n),
             Sampler::Stratified(sampler) => sampler.request_2d_array(n),
@@ -97,6 +105,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.round_count(count),
             Sampler::MaxMinDist(sampler) => sampler.round_count(count),
             Sampler::MLT(sampler) => sampler.round_count(count),
+            Sampler::PMJ02BN(sampler) => sampler.round_count(count),
             Sampler::Random(sampler) => sampler.round_count(count),
             Sampler::Sobol(sampler) => sampler.round_count(count),
             Sampler::Stratified(sampler) => sampler.round_count(count),
@@ -108,6 +117,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_2d_array(n),
             Sampler::MaxMinDist(sampler) => sampler.get_2d_array(n),
             Sampler::MLT(sampler) => sampler.get_2d_array(n),
+            Sampler::PMJ02BN(sampler) => sampler.get_2d_array(n),
             Sampler::Random(sampler) => sampler.get_2d_array(n),
             Sampler::Sobol(sampler) => sampler.get_2d_array(n),
             Sampler::Stratified(sampler) => sampler.get_2d_array(n),
@@ -119,6 +129,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_2d_arrays(n),
             Sampler::MaxMinDist(sampler) => sampler.get_2d_arrays(n),
             Sampler::MLT(sampler) => sampler.get_2d_arrays(n),
+            Sampler::PMJ02BN(sampler) => sampler.get_2d_arrays(n),
             Sampler::Random(sampler) => sampler.get_2d_arrays(n),
             Sampler::Sobol(sampler) => sampler.get_2d_arrays(n),
             Sampler::Stratified(sampler) => sampler.get_2d_arrays(n),
@@ -130,6 +141,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_2d_array_vec(n),
             Sampler::MaxMinDist(sampler) => sampler.get_2d_array_vec(n),
             Sampler::MLT(sampler) => sampler.get_2d_array_vec(n),
+            Sampler::PMJ02BN(sampler) => sampler.get_2d_array_vec(n),
             Sampler::Random(sampler) => sampler.get_2d_array_vec(n),
             Sampler::Sobol(sampler) => sampler.get_2d_array_vec(n),
             Sampler::Stratified(sampler) => sampler.get_2d_array_vec(n),
@@ -141,6 +153,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.start_next_sample(),
             Sampler::MaxMinDist(sampler) => sampler.start_next_sample(),
             Sampler::MLT(sampler) => sampler.start_next_sample(),
+            Sampler::PMJ02BN(sampler) => sampler.start_next_sample(),
             Sampler::Random(sampler) => sampler.start_next_sample(),
             Sampler::Sobol(sampler) => sampler.start_next_sample(),
             Sampler::Stratified(sampler) => sampler.start_next_sample(),
@@ -152,6 +165,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.reseed(seed),
             Sampler::MaxMinDist(sampler) => sampler.reseed(seed),
             Sampler::MLT(sampler) => sampler.reseed(seed),
+            Sampler::PMJ02BN(sampler) => sampler.reseed(seed),
             Sampler::Random(sampler) => sampler.reseed(seed),
             Sampler::Sobol(sampler) => sampler.reseed(seed),
             Sampler::Stratified(sampler) => sampler.reseed(seed),
@@ -163,6 +177,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_current_pixel(),
             Sampler::MaxMinDist(sampler) => sampler.get_current_pixel(),
             Sampler::MLT(sampler) => sampler.get_current_pixel(),
+            Sampler::PMJ02BN(sampler) => sampler.get_current_pixel(),
             Sampler::Random(sampler) => sampler.get_current_pixel(),
             Sampler::Sobol(sampler) => sampler.get_current_pixel(),
             Sampler::Stratified(sampler) => sampler.get_current_pixel(),
@@ -174,6 +189,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_current_sample_number(),
             Sampler::MaxMinDist(sampler) => sampler.get_current_sample_number(),
             Sampler::MLT(sampler) => sampler.get_current_sample_number(),
+            Sampler::PMJ02BN(sampler) => sampler.get_current_sample_number(),
             Sampler::Random(sampler) => sampler.get_current_sample_number(),
             Sampler::Sobol(sampler) => sampler.get_current_sample_number(),
             Sampler::Stratified(sampler) => sampler.get_current_sample_number(),
@@ -185,6 +201,7 @@ impl Sampler {
             Sampler::Halton(sampler) => sampler.get_samples_per_pixel(),
             Sampler::MaxMinDist(sampler) => sampler.get_samples_per_pixel(),
             Sampler::MLT(sampler) => sampler.get_samples_per_pixel(),
+            Sampler::PMJ02BN(sampler) => sampler.get_samples_per_pixel(),
             Sampler::Random(sampler) => sampler.get_samples_per_pixel(),
             Sampler::Sobol(sampler) => sampler.get_samples_per_pixel(),
             Sampler::Stratified(sampler) => sampler.get_samples_per_pixel(),
@@ -200,3 +217,25 @@ impl Sampler {
         }
     }
 }
+
+/// The seed for the samples of one pixel. It depends on nothing but
+/// the pixel's (absolute) coordinates, so a pixel gets the same
+/// samples no matter which tile, crop window, or thread renders it,
+/// and a cropped re-render of a region matches the full frame there.
+pub fn pixel_seed(pixel: Point2i) -> u64 {
+    mix_bits(((pixel.x as u32 as u64) << 32) | pixel.y as u32 as u64)
+}
+
+/// The *seed* parameter of a sampler (default: 0). Renders with the
+/// same seed are identical, another seed gives other samples (and
+/// noise) for every pixel.
+pub fn sampler_seed(params: &ParamSet) -> u64 {
+    params.find_one_int("seed", 0) as u32 as u64
+}
+
+/// Mixes the *seed* of a sampler (see **sampler_seed()**) into the
+/// seed of a pixel (see **pixel_seed()**). Seed 0 keeps the pixel
+/// seed, so the default renders don't change.
+pub fn seeded_pixel_seed(pixel_seed: u64, seed: u64) -> u64 {
+    pixel_seed ^ mix_bits(seed)
+}
@@ -1,11 +1,11 @@
 //! Draw random samples from a chosen probability distribution.
 
 // std
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{Point2f, Vector2f, Vector3f};
 use crate::core::pbrt::clamp_t;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{INV_2_PI, INV_4_PI, INV_PI, PI_OVER_2, PI_OVER_4};
 use crate::core::rng::Rng;
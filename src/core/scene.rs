@@ -2,25 +2,80 @@
 //! the lights and geometric primitives in the scene. These are all
 //! stored in the **Scene** object.
 //!
+//! An interactive front-end can edit a scene between renders instead
+//! of building it again: move a primitive or an instance
+//! (**set_primitive_to_world()**), swap a material
+//! (**replace_material()**), or change a light (**set_light()**). A
+//! moved primitive only refits the bounds of the BVH nodes above it,
+//! and integrators update their light distributions in
+//! **preprocess()**, i.e. with the next render.
+//!
 
 // std
+use std::fmt;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{Bounds3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
 use crate::core::light::{Light, LightFlags};
+use crate::core::lightdistrib::LightDistributionCache;
+use crate::core::material::Material;
 use crate::core::pbrt::Spectrum;
-use crate::core::primitive::Primitive;
+use crate::core::primitive::{GeometricPrimitive, MeshPrimitive, Primitive, TransformedPrimitive};
+use crate::core::progress::count_ray;
 use crate::core::sampler::Sampler;
+use crate::core::stats::StatCounter;
+use crate::core::transform::AnimatedTransform;
 
 // see scene.h
 
+static N_INTERSECTION_TESTS: StatCounter =
+    StatCounter::new("Intersections/Regular ray intersection tests");
+static N_SHADOW_TESTS: StatCounter =
+    StatCounter::new("Intersections/Shadow ray intersection tests");
+
+/// Why an edit of the scene was rejected.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SceneEditError {
+    /// the scene is shared (e.g. with a render which is still running)
+    Shared,
+    /// there is no primitive (or light) with this index
+    OutOfRange,
+    /// area lights don't move with their shapes, and shapes keep
+    /// their area light
+    AreaLight,
+    /// a kd-tree can't be refitted (only a BVH)
+    NotRefittable,
+}
+
+impl fmt::Display for SceneEditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message: &str = match self {
+            SceneEditError::Shared => "scene is in use",
+            SceneEditError::OutOfRange => "index out of range",
+            SceneEditError::AreaLight => "area lights can't be edited",
+            SceneEditError::NotRefittable => "accelerator can't be refitted",
+        };
+        write!(f, "{}", message)
+    }
+}
+
 #[derive(Clone)]
 pub struct Scene {
     pub lights: Vec<Arc<Light>>,
     pub infinite_lights: Vec<Arc<Light>>,
     pub aggregate: Arc<Primitive>,
     pub world_bound: Bounds3f,
+    /// true if any primitive was created as a shadow catcher
+    pub has_shadow_catchers: bool,
+    /// true if any primitive was created as a holdout
+    pub has_holdouts: bool,
+    /// a hash of the light sources as they were described (0 if
+    /// unknown), scenes with the same fingerprint have the same lights
+    pub light_fingerprint: u64,
+    /// where light distributions get reused (see
+    /// **create_light_sample_distribution()**)
+    pub light_distributions: LightDistributionCache,
 }
 
 impl Scene {
@@ -31,6 +86,10 @@ impl Scene {
             infinite_lights: Vec::new(),
             aggregate: aggregate.clone(),
             world_bound,
+            has_shadow_catchers: false,
+            has_holdouts: false,
+            light_fingerprint: 0,
+            light_distributions: LightDistributionCache::default(),
         };
         let mut changed_lights = Vec::new();
         let mut infinite_lights = Vec::new();
@@ -47,13 +106,141 @@ impl Scene {
             infinite_lights,
             aggregate,
             world_bound,
+            has_shadow_catchers: false,
+            has_holdouts: false,
+            light_fingerprint: 0,
+            light_distributions: LightDistributionCache::default(),
         }
     }
     pub fn world_bound(&self) -> Bounds3f {
         self.world_bound
     }
+    /// The primitives of the aggregate (or the aggregate itself, if
+    /// it's a single primitive), which can be edited by index.
+    pub fn primitives(&self) -> &[Arc<Primitive>] {
+        match &*self.aggregate {
+            Primitive::BVH(bvh) => &bvh.primitives,
+            Primitive::KdTree(kdtree) => &kdtree.primitives,
+            _ => std::slice::from_ref(&self.aggregate),
+        }
+    }
+    /// The index of the primitive a ray hits first (e.g. to select an
+    /// object with the mouse). Tests all primitives, so it's meant
+    /// for single rays, not for rendering.
+    pub fn pick(&self, ray: &Ray) -> Option<usize> {
+        let mut ray: Ray = ray.clone();
+        let mut picked: Option<usize> = None;
+        for (index, primitive) in self.primitives().iter().enumerate() {
+            let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+            // a hit shortens the ray
+            if primitive.intersect(&mut ray, &mut isect) {
+                picked = Some(index);
+            }
+        }
+        picked
+    }
+    /// Moves a primitive. An instance gets *primitive_to_world* as its
+    /// new transform, other primitives (which are in world space
+    /// already) get transformed relative to where the scene put them.
+    pub fn set_primitive_to_world(
+        &mut self,
+        index: usize,
+        primitive_to_world: AnimatedTransform,
+    ) -> Result<(), SceneEditError> {
+        let primitive: Arc<Primitive> = self
+            .primitives()
+            .get(index)
+            .ok_or(SceneEditError::OutOfRange)?
+            .clone();
+        let untransformed: Arc<Primitive> = match &*primitive {
+            Primitive::Transformed(transformed) => transformed.primitive.clone(),
+            _ => primitive.clone(),
+        };
+        if untransformed.get_area_light().is_some() {
+            return Err(SceneEditError::AreaLight);
+        }
+        let moved: Arc<Primitive> = Arc::new(Primitive::Transformed(Box::new(
+            TransformedPrimitive::new(untransformed, primitive_to_world),
+        )));
+        self.replace_primitive(index, moved, true)?;
+        // e.g. infinite lights depend on the bounds of the scene
+        self.world_bound = self.aggregate.world_bound();
+        for light in &self.lights {
+            light.preprocess(self);
+        }
+        Ok(())
+    }
+    /// Uses *material* instead of *old* for all primitives (but not
+    /// for the shapes of instances, which are shared). Returns the
+    /// number of primitives which changed.
+    pub fn replace_material(
+        &mut self,
+        old: &Arc<Material>,
+        material: Option<Arc<Material>>,
+    ) -> Result<usize, SceneEditError> {
+        let mut changed: Vec<(usize, Arc<Primitive>)> = Vec::new();
+        for (index, primitive) in self.primitives().iter().enumerate() {
+            if let Some(primitive) = with_material(primitive, old, &material) {
+                changed.push((index, primitive));
+            }
+        }
+        let n_changed: usize = changed.len();
+        for (index, primitive) in changed {
+            // the bounds stay the same
+            self.replace_primitive(index, primitive, false)?;
+        }
+        Ok(n_changed)
+    }
+    /// Replaces a light (except area lights, which belong to shapes).
+    pub fn set_light(&mut self, index: usize, light: Arc<Light>) -> Result<(), SceneEditError> {
+        let area: u8 = LightFlags::Area as u8;
+        match self.lights.get(index) {
+            None => return Err(SceneEditError::OutOfRange),
+            Some(old) if old.get_flags() & area != 0 || light.get_flags() & area != 0 => {
+                return Err(SceneEditError::AreaLight);
+            }
+            _ => {}
+        }
+        light.preprocess(self);
+        self.lights[index] = light;
+        self.light_fingerprint = 0;
+        let infinite: u8 = LightFlags::Infinite as u8;
+        self.infinite_lights = self
+            .lights
+            .iter()
+            .filter(|light| light.get_flags() & infinite == infinite)
+            .cloned()
+            .collect();
+        Ok(())
+    }
+    fn replace_primitive(
+        &mut self,
+        index: usize,
+        primitive: Arc<Primitive>,
+        refit: bool,
+    ) -> Result<(), SceneEditError> {
+        let aggregate: &mut Primitive =
+            Arc::get_mut(&mut self.aggregate).ok_or(SceneEditError::Shared)?;
+        match aggregate {
+            Primitive::BVH(bvh) => {
+                if refit {
+                    bvh.set_primitive(index, primitive);
+                } else {
+                    bvh.primitives[index] = primitive;
+                }
+            }
+            Primitive::KdTree(kdtree) => {
+                if refit {
+                    return Err(SceneEditError::NotRefittable);
+                }
+                kdtree.primitives[index] = primitive;
+            }
+            _ => self.aggregate = primitive,
+        }
+        Ok(())
+    }
     pub fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
-        // TODO: ++nIntersectionTests;
+        N_INTERSECTION_TESTS.inc();
         assert_ne!(
             ray.d,
             Vector3f {
@@ -62,10 +249,11 @@ impl Scene {
                 z: 0.0,
             }
         );
+        count_ray();
         self.aggregate.intersect(ray, isect)
     }
     pub fn intersect_p(&self, ray: &mut Ray) -> bool {
-        // TODO: ++nShadowTests;
+        N_SHADOW_TESTS.inc();
         assert_ne!(
             ray.d,
             Vector3f {
@@ -74,6 +262,7 @@ impl Scene {
                 z: 0.0,
             }
         );
+        count_ray();
         self.aggregate.intersect_p(ray)
     }
     pub fn intersect_tr(
@@ -105,3 +294,48 @@ impl Scene {
         }
     }
 }
+
+/// A copy of the primitive which uses *material* instead of *old*
+/// (**None** if it doesn't use *old*). Moved primitives (see
+/// **Scene::set_primitive_to_world()**) are copied with their
+/// transform.
+fn with_material(
+    primitive: &Primitive,
+    old: &Arc<Material>,
+    material: &Option<Arc<Material>>,
+) -> Option<Arc<Primitive>> {
+    match primitive {
+        Primitive::Geometric(geometric) => match geometric.material {
+            Some(ref current) if Arc::ptr_eq(current, old) => Some(Arc::new(Primitive::Geometric(
+                Box::new(GeometricPrimitive {
+                    shape: geometric.shape.clone(),
+                    material: material.clone(),
+                    area_light: geometric.area_light.clone(),
+                    medium_interface: geometric.medium_interface.clone(),
+                    shadow_catcher: geometric.shadow_catcher,
+                    holdout: geometric.holdout,
+                    priority: geometric.priority,
+                }),
+            ))),
+            _ => None,
+        },
+        Primitive::Mesh(mesh) => match mesh.material {
+            Some(ref current) if Arc::ptr_eq(current, old) => {
+                Some(Arc::new(Primitive::Mesh(Box::new(MeshPrimitive {
+                    material: material.clone(),
+                    ..(**mesh).clone()
+                }))))
+            }
+            _ => None,
+        },
+        Primitive::Transformed(transformed) => with_material(&transformed.primitive, old, material)
+            .map(|primitive| {
+                Arc::new(Primitive::Transformed(Box::new(TransformedPrimitive::new(
+                    primitive,
+                    transformed.primitive_to_world,
+                ))))
+            }),
+        // instances share their aggregate
+        _ => None,
+    }
+}
@@ -0,0 +1,569 @@
+//! Binary scene cache for fast reloads.
+//!
+//! Parsing large scene files can take much longer than loading the
+//! same data from a compact binary file. A **SceneCache** records the
+//! sequence of API calls (with their fully parsed parameter lists,
+//! i.e. meshes, materials, lights, and transforms) while a scene gets
+//! parsed and writes it to disk before rendering starts. Together
+//! with the calls it stores a hash for each of the scene files which
+//! were read, so a later run can replay the calls instead of parsing
+//! the scene again, as long as none of the files changed. External
+//! files referenced by the scene (e.g. PLY meshes or image textures)
+//! are still read while replaying.
+
+// std
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+// others
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+// pbrt
+use crate::core::api::{
+    pbrt_accelerator, pbrt_active_transform_all, pbrt_active_transform_end_time,
+    pbrt_active_transform_start_time, pbrt_area_light_source, pbrt_attribute_begin,
+    pbrt_attribute_end, pbrt_camera, pbrt_cleanup, pbrt_concat_transform, pbrt_coord_sys_transform,
+    pbrt_film, pbrt_integrator, pbrt_light_source, pbrt_look_at, pbrt_make_named_material,
+    pbrt_make_named_medium, pbrt_material, pbrt_medium_interface, pbrt_named_material,
+    pbrt_object_begin, pbrt_object_end, pbrt_object_instance, pbrt_pixel_filter,
+    pbrt_reverse_orientation, pbrt_rotate, pbrt_sampler, pbrt_scale, pbrt_shape, pbrt_texture,
+    pbrt_transform, pbrt_transform_begin, pbrt_transform_end, pbrt_translate, pbrt_world_begin,
+};
+use crate::core::api::{ApiState, BsdfState};
+use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector2f, Vector3f};
+use crate::core::memory::MappedFile;
+use crate::core::paramset::{ParamSet, ParamSetItem};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::transform::{Matrix4x4, Transform};
+
+const MAGIC: &[u8; 8] = b"RSPBRTSC";
+/// String parameters which are filenames relative to the scene file.
+pub const FILENAME_PARAMS: [&str; 3] = ["filename", "lensfile", "mapname"];
+/// the caches of f32 and f64 builds (see **Float**) differ
+#[cfg(not(feature = "float_as_double"))]
+const VERSION: u32 = 1;
+#[cfg(feature = "float_as_double")]
+const VERSION: u32 = 0x8000_0001;
+
+/// A single call of one of the **pbrt_...()** API functions.
+#[derive(Clone)]
+pub enum ApiCall {
+    ActiveTransformAll,
+    ActiveTransformEndTime,
+    ActiveTransformStartTime,
+    AttributeBegin,
+    AttributeEnd,
+    ConcatTransform(Transform),
+    LookAt([Float; 9]),
+    MediumInterface(String, String),
+    ObjectEnd,
+    /// all directives with a parameter list, like **Shape** or
+    /// **Material**, the directive is stored in *key_word*
+    Params(Box<ParamSet>),
+    ReverseOrientation,
+    Rotate([Float; 4]),
+    Scale([Float; 3]),
+    /// the directory relative filenames get resolved against
+    SearchDirectory(String),
+    Transform(Transform),
+    TransformBegin,
+    TransformEnd,
+    Translate([Float; 3]),
+    WorldBegin,
+    WorldEnd,
+}
+
+impl ApiCall {
+    pub fn execute(self, api_state: &mut ApiState, bsdf_state: &mut BsdfState) {
+        match self {
+            ApiCall::ActiveTransformAll => pbrt_active_transform_all(api_state),
+            ApiCall::ActiveTransformEndTime => pbrt_active_transform_end_time(api_state),
+            ApiCall::ActiveTransformStartTime => pbrt_active_transform_start_time(api_state),
+            ApiCall::AttributeBegin => pbrt_attribute_begin(api_state),
+            ApiCall::AttributeEnd => pbrt_attribute_end(api_state),
+            ApiCall::ConcatTransform(tr) => pbrt_concat_transform(api_state, &tr),
+            ApiCall::LookAt(v) => pbrt_look_at(
+                api_state, v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8],
+            ),
+            ApiCall::MediumInterface(inside, outside) => {
+                pbrt_medium_interface(api_state, &inside, &outside)
+            }
+            ApiCall::ObjectEnd => pbrt_object_end(api_state),
+            ApiCall::Params(params) => {
+                let params: ParamSet = *params;
+                match params.key_word.as_str() {
+                    "Accelerator" => pbrt_accelerator(api_state, params),
+                    "AreaLightSource" => pbrt_area_light_source(api_state, params),
+                    "Camera" => pbrt_camera(api_state, params),
+                    "CoordSysTransform" => pbrt_coord_sys_transform(api_state, params),
+                    "Film" => pbrt_film(api_state, params),
+                    "Integrator" => pbrt_integrator(api_state, params),
+                    "LightSource" => pbrt_light_source(api_state, params),
+                    "MakeNamedMaterial" => pbrt_make_named_material(api_state, bsdf_state, params),
+                    "MakeNamedMedium" => pbrt_make_named_medium(api_state, params),
+                    "Material" => pbrt_material(api_state, params),
+                    "NamedMaterial" => pbrt_named_material(api_state, params),
+                    "ObjectBegin" => pbrt_object_begin(api_state, params),
+                    "ObjectInstance" => pbrt_object_instance(api_state, params),
+                    "PixelFilter" => pbrt_pixel_filter(api_state, params),
+                    "Sampler" => pbrt_sampler(api_state, params),
+                    "Shape" => pbrt_shape(api_state, bsdf_state, params),
+                    "Texture" => pbrt_texture(api_state, params),
+                    _ => warn!("Unknown directive {:?}. Ignoring it.", params.key_word),
+                }
+            }
+            ApiCall::ReverseOrientation => pbrt_reverse_orientation(api_state),
+            ApiCall::Rotate(v) => pbrt_rotate(api_state, v[0], v[1], v[2], v[3]),
+            ApiCall::Scale(v) => pbrt_scale(api_state, v[0], v[1], v[2]),
+            ApiCall::SearchDirectory(dir) => {
+                api_state.search_directory = Some(Box::new(PathBuf::from(dir)));
+            }
+            ApiCall::Transform(tr) => pbrt_transform(api_state, &tr),
+            ApiCall::TransformBegin => pbrt_transform_begin(api_state),
+            ApiCall::TransformEnd => pbrt_transform_end(api_state),
+            ApiCall::Translate(v) => pbrt_translate(api_state, v[0], v[1], v[2]),
+            ApiCall::WorldBegin => pbrt_world_begin(api_state),
+            ApiCall::WorldEnd => pbrt_cleanup(api_state),
+        }
+    }
+}
+
+/// Relative filenames are relative to the *search_directory* (the
+/// directory of the scene file).
+pub fn resolve_filename(search_directory: Option<&PathBuf>, filename: &str) -> String {
+    if let Some(search_directory) = search_directory {
+        if PathBuf::from(filename).is_relative() {
+            let mut path_buf: PathBuf = PathBuf::from("/");
+            path_buf.push(search_directory);
+            path_buf.push(filename);
+            return String::from(path_buf.to_str().unwrap());
+        }
+    }
+    String::from(filename)
+}
+
+/// Hashes the content of a file (64-bit FNV-1a), **None** if the file
+/// can't be read.
+pub fn hash_file(filename: &str) -> Option<u64> {
+    let mapped_file: MappedFile = MappedFile::open(filename).ok()?;
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in mapped_file.iter() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Some(hash)
+}
+
+/// The recorded API calls of a scene and the hashes of the scene
+/// files they were parsed from.
+#[derive(Default)]
+pub struct SceneCache {
+    pub files: Vec<(String, u64)>,
+    pub calls: Vec<ApiCall>,
+}
+
+impl SceneCache {
+    pub fn new() -> Self {
+        SceneCache::default()
+    }
+    /// Remembers the hash of a scene file (the main one or one read
+    /// via **Include**).
+    pub fn add_file(&mut self, filename: &str) {
+        // independent of the current working directory
+        let path: PathBuf = match std::fs::canonicalize(filename) {
+            Ok(path) => path,
+            Err(_) => PathBuf::from(filename),
+        };
+        let filename: String = path.to_string_lossy().into_owned();
+        if let Some(hash) = hash_file(&filename) {
+            self.files.push((filename, hash));
+        }
+    }
+    /// The external files (e.g. PLY meshes or image textures) used by
+    /// the recorded API calls.
+    pub fn referenced_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        let mut search_directory: Option<PathBuf> = None;
+        for call in &self.calls {
+            match call {
+                ApiCall::SearchDirectory(dir) => search_directory = Some(PathBuf::from(dir)),
+                // the film's filename is an output
+                ApiCall::Params(params) if params.key_word != "Film" => {
+                    for item in &params.strings {
+                        if FILENAME_PARAMS.contains(&item.name.as_str()) {
+                            for value in &item.values {
+                                files.push(resolve_filename(search_directory.as_ref(), value));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        files
+    }
+    /// Did none of the scene files change since the cache was written?
+    pub fn is_up_to_date(&self) -> bool {
+        !self.files.is_empty()
+            && self
+                .files
+                .iter()
+                .all(|(filename, hash)| hash_file(filename) == Some(*hash))
+    }
+    /// Executes all recorded API calls (in order).
+    pub fn replay(self, api_state: &mut ApiState, bsdf_state: &mut BsdfState) {
+        for call in self.calls {
+            call.execute(api_state, bsdf_state);
+        }
+    }
+    pub fn write(&self, filename: &str) -> Result<()> {
+        let mut w = BufWriter::new(File::create(filename)?);
+        w.write_all(MAGIC)?;
+        w.write_u32::<LittleEndian>(VERSION)?;
+        // caches are only valid for the same spectral representation
+        w.write_u32::<LittleEndian>(Spectrum::N_SAMPLES as u32)?;
+        w.write_u32::<LittleEndian>(self.files.len() as u32)?;
+        for (name, hash) in &self.files {
+            write_string(&mut w, name)?;
+            w.write_u64::<LittleEndian>(*hash)?;
+        }
+        w.write_u64::<LittleEndian>(self.calls.len() as u64)?;
+        for call in &self.calls {
+            write_call(&mut w, call)?;
+        }
+        w.flush()
+    }
+    pub fn read(filename: &str) -> Result<SceneCache> {
+        let mapped_file: MappedFile = MappedFile::open(filename)?;
+        let mut r: &[u8] = &mapped_file;
+        let mut magic: [u8; 8] = [0_u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a scene cache"));
+        }
+        if r.read_u32::<LittleEndian>()? != VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+        if r.read_u32::<LittleEndian>()? != Spectrum::N_SAMPLES as u32 {
+            return Err(invalid_data("different number of spectral samples"));
+        }
+        let mut cache: SceneCache = SceneCache::new();
+        let n_files: u32 = r.read_u32::<LittleEndian>()?;
+        for _ in 0..n_files {
+            let name: String = read_string(&mut r)?;
+            let hash: u64 = r.read_u64::<LittleEndian>()?;
+            cache.files.push((name, hash));
+        }
+        let n_calls: u64 = r.read_u64::<LittleEndian>()?;
+        for _ in 0..n_calls {
+            cache.calls.push(read_call(&mut r)?);
+        }
+        Ok(cache)
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// writing
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> Result<()> {
+    w.write_u32::<LittleEndian>(s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+#[cfg(not(feature = "float_as_double"))]
+fn write_float<W: Write>(w: &mut W, f: Float) -> Result<()> {
+    w.write_f32::<LittleEndian>(f)
+}
+
+#[cfg(feature = "float_as_double")]
+fn write_float<W: Write>(w: &mut W, f: Float) -> Result<()> {
+    w.write_f64::<LittleEndian>(f)
+}
+
+fn write_floats<W: Write>(w: &mut W, v: &[Float]) -> Result<()> {
+    for f in v {
+        write_float(w, *f)?;
+    }
+    Ok(())
+}
+
+fn write_transform<W: Write>(w: &mut W, tr: &Transform) -> Result<()> {
+    for row in tr.m.m.iter().chain(tr.m_inv.m.iter()) {
+        write_floats(w, row)?;
+    }
+    Ok(())
+}
+
+fn write_items<W: Write, T, F>(w: &mut W, items: &[ParamSetItem<T>], write_value: F) -> Result<()>
+where
+    F: Fn(&mut W, &T) -> Result<()>,
+{
+    w.write_u32::<LittleEndian>(items.len() as u32)?;
+    for item in items {
+        write_string(w, &item.name)?;
+        w.write_u32::<LittleEndian>(item.values.len() as u32)?;
+        for value in &item.values {
+            write_value(w, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Feeds *params* to *state* in the same form they get written to a
+/// scene cache.
+pub fn hash_params<H: Hasher>(params: &ParamSet, state: &mut H) {
+    let mut bytes: Vec<u8> = Vec::new();
+    // writing to memory can't fail
+    write_params(&mut bytes, params).unwrap();
+    state.write(&bytes);
+}
+
+fn write_params<W: Write>(w: &mut W, params: &ParamSet) -> Result<()> {
+    write_string(w, &params.key_word)?;
+    write_string(w, &params.name)?;
+    write_string(w, &params.tex_type)?;
+    write_string(w, &params.tex_name)?;
+    write_items(w, &params.bools, |w, b| w.write_u8(*b as u8))?;
+    write_items(w, &params.ints, |w, i| w.write_i32::<LittleEndian>(*i))?;
+    write_items(w, &params.floats, |w, f| write_float(w, *f))?;
+    write_items(w, &params.point2fs, |w, p| write_floats(w, &[p.x, p.y]))?;
+    write_items(w, &params.vector2fs, |w, v| write_floats(w, &[v.x, v.y]))?;
+    write_items(w, &params.point3fs, |w, p| {
+        write_floats(w, &[p.x, p.y, p.z])
+    })?;
+    write_items(w, &params.vector3fs, |w, v| {
+        write_floats(w, &[v.x, v.y, v.z])
+    })?;
+    write_items(w, &params.normals, |w, n| write_floats(w, &[n.x, n.y, n.z]))?;
+    write_items(w, &params.spectra, |w, s| {
+        for i in 0..Spectrum::N_SAMPLES {
+            write_float(w, s[i])?;
+        }
+        Ok(())
+    })?;
+    write_items(w, &params.strings, |w, s| write_string(w, s))?;
+    write_items(w, &params.textures, |w, s| write_string(w, s))
+}
+
+fn write_call<W: Write>(w: &mut W, call: &ApiCall) -> Result<()> {
+    match call {
+        ApiCall::ActiveTransformAll => w.write_u8(0),
+        ApiCall::ActiveTransformEndTime => w.write_u8(1),
+        ApiCall::ActiveTransformStartTime => w.write_u8(2),
+        ApiCall::AttributeBegin => w.write_u8(3),
+        ApiCall::AttributeEnd => w.write_u8(4),
+        ApiCall::ConcatTransform(tr) => {
+            w.write_u8(5)?;
+            write_transform(w, tr)
+        }
+        ApiCall::LookAt(v) => {
+            w.write_u8(6)?;
+            write_floats(w, v)
+        }
+        ApiCall::MediumInterface(inside, outside) => {
+            w.write_u8(7)?;
+            write_string(w, inside)?;
+            write_string(w, outside)
+        }
+        ApiCall::ObjectEnd => w.write_u8(8),
+        ApiCall::Params(params) => {
+            w.write_u8(9)?;
+            write_params(w, params)
+        }
+        ApiCall::ReverseOrientation => w.write_u8(10),
+        ApiCall::Rotate(v) => {
+            w.write_u8(11)?;
+            write_floats(w, v)
+        }
+        ApiCall::Scale(v) => {
+            w.write_u8(12)?;
+            write_floats(w, v)
+        }
+        ApiCall::SearchDirectory(dir) => {
+            w.write_u8(13)?;
+            write_string(w, dir)
+        }
+        ApiCall::Transform(tr) => {
+            w.write_u8(14)?;
+            write_transform(w, tr)
+        }
+        ApiCall::TransformBegin => w.write_u8(15),
+        ApiCall::TransformEnd => w.write_u8(16),
+        ApiCall::Translate(v) => {
+            w.write_u8(17)?;
+            write_floats(w, v)
+        }
+        ApiCall::WorldBegin => w.write_u8(18),
+        ApiCall::WorldEnd => w.write_u8(19),
+    }
+}
+
+// reading
+
+fn read_string<R: Read>(r: &mut R) -> Result<String> {
+    let len: usize = r.read_u32::<LittleEndian>()? as usize;
+    let mut bytes: Vec<u8> = vec![0_u8; len];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid_data("invalid string"))
+}
+
+#[cfg(not(feature = "float_as_double"))]
+fn read_float<R: Read>(r: &mut R) -> Result<Float> {
+    r.read_f32::<LittleEndian>()
+}
+
+#[cfg(feature = "float_as_double")]
+fn read_float<R: Read>(r: &mut R) -> Result<Float> {
+    r.read_f64::<LittleEndian>()
+}
+
+#[cfg(not(feature = "float_as_double"))]
+fn read_floats<R: Read>(r: &mut R, v: &mut [Float]) -> Result<()> {
+    r.read_f32_into::<LittleEndian>(v)
+}
+
+#[cfg(feature = "float_as_double")]
+fn read_floats<R: Read>(r: &mut R, v: &mut [Float]) -> Result<()> {
+    r.read_f64_into::<LittleEndian>(v)
+}
+
+fn read_transform<R: Read>(r: &mut R) -> Result<Transform> {
+    let mut m: Matrix4x4 = Matrix4x4::default();
+    let mut m_inv: Matrix4x4 = Matrix4x4::default();
+    for row in m.m.iter_mut().chain(m_inv.m.iter_mut()) {
+        read_floats(r, row)?;
+    }
+    Ok(Transform { m, m_inv })
+}
+
+fn read_items<R: Read, T, F>(r: &mut R, read_value: F) -> Result<Vec<ParamSetItem<T>>>
+where
+    F: Fn(&mut R) -> Result<T>,
+{
+    let n_items: u32 = r.read_u32::<LittleEndian>()?;
+    let mut items: Vec<ParamSetItem<T>> = Vec::with_capacity(n_items as usize);
+    for _ in 0..n_items {
+        let name: String = read_string(r)?;
+        let n_values: usize = r.read_u32::<LittleEndian>()? as usize;
+        let mut values: Vec<T> = Vec::with_capacity(n_values);
+        for _ in 0..n_values {
+            values.push(read_value(r)?);
+        }
+        items.push(ParamSetItem {
+            name,
+            values,
+            n_values,
+            looked_up: false,
+        });
+    }
+    Ok(items)
+}
+
+fn read_params<R: Read>(r: &mut R) -> Result<ParamSet> {
+    Ok(ParamSet {
+        key_word: read_string(r)?,
+        name: read_string(r)?,
+        tex_type: read_string(r)?,
+        tex_name: read_string(r)?,
+        bools: read_items(r, |r| Ok(r.read_u8()? != 0_u8))?,
+        ints: read_items(r, |r| r.read_i32::<LittleEndian>())?,
+        floats: read_items(r, |r| read_float(r))?,
+        point2fs: read_items(r, |r| {
+            let mut v: [Float; 2] = [0.0; 2];
+            read_floats(r, &mut v)?;
+            Ok(Point2f { x: v[0], y: v[1] })
+        })?,
+        vector2fs: read_items(r, |r| {
+            let mut v: [Float; 2] = [0.0; 2];
+            read_floats(r, &mut v)?;
+            Ok(Vector2f { x: v[0], y: v[1] })
+        })?,
+        point3fs: read_items(r, |r| {
+            let mut v: [Float; 3] = [0.0; 3];
+            read_floats(r, &mut v)?;
+            Ok(Point3f {
+                x: v[0],
+                y: v[1],
+                z: v[2],
+            })
+        })?,
+        vector3fs: read_items(r, |r| {
+            let mut v: [Float; 3] = [0.0; 3];
+            read_floats(r, &mut v)?;
+            Ok(Vector3f {
+                x: v[0],
+                y: v[1],
+                z: v[2],
+            })
+        })?,
+        normals: read_items(r, |r| {
+            let mut v: [Float; 3] = [0.0; 3];
+            read_floats(r, &mut v)?;
+            Ok(Normal3f {
+                x: v[0],
+                y: v[1],
+                z: v[2],
+            })
+        })?,
+        spectra: read_items(r, |r| {
+            let mut s: Spectrum = Spectrum::default();
+            for i in 0..Spectrum::N_SAMPLES {
+                s[i] = read_float(r)?;
+            }
+            Ok(s)
+        })?,
+        strings: read_items(r, |r| read_string(r))?,
+        textures: read_items(r, |r| read_string(r))?,
+    })
+}
+
+fn read_call<R: Read>(r: &mut R) -> Result<ApiCall> {
+    let call: ApiCall = match r.read_u8()? {
+        0 => ApiCall::ActiveTransformAll,
+        1 => ApiCall::ActiveTransformEndTime,
+        2 => ApiCall::ActiveTransformStartTime,
+        3 => ApiCall::AttributeBegin,
+        4 => ApiCall::AttributeEnd,
+        5 => ApiCall::ConcatTransform(read_transform(r)?),
+        6 => {
+            let mut v: [Float; 9] = [0.0; 9];
+            read_floats(r, &mut v)?;
+            ApiCall::LookAt(v)
+        }
+        7 => {
+            let inside: String = read_string(r)?;
+            let outside: String = read_string(r)?;
+            ApiCall::MediumInterface(inside, outside)
+        }
+        8 => ApiCall::ObjectEnd,
+        9 => ApiCall::Params(Box::new(read_params(r)?)),
+        10 => ApiCall::ReverseOrientation,
+        11 => {
+            let mut v: [Float; 4] = [0.0; 4];
+            read_floats(r, &mut v)?;
+            ApiCall::Rotate(v)
+        }
+        12 => {
+            let mut v: [Float; 3] = [0.0; 3];
+            read_floats(r, &mut v)?;
+            ApiCall::Scale(v)
+        }
+        13 => ApiCall::SearchDirectory(read_string(r)?),
+        14 => ApiCall::Transform(read_transform(r)?),
+        15 => ApiCall::TransformBegin,
+        16 => ApiCall::TransformEnd,
+        17 => {
+            let mut v: [Float; 3] = [0.0; 3];
+            read_floats(r, &mut v)?;
+            ApiCall::Translate(v)
+        }
+        18 => ApiCall::WorldBegin,
+        19 => ApiCall::WorldEnd,
+        _ => return Err(invalid_data("unknown API call")),
+    };
+    Ok(call)
+}
@@ -0,0 +1,275 @@
+//! Statistics about a parsed scene (without rendering it).
+//!
+//! With **pbrt_set_stats_only()** the scene gets parsed, the
+//! acceleration structure gets built, and the lights get
+//! preprocessed as usual, but instead of rendering an image
+//! **WorldEnd** prints a **SceneStats** report: primitive, light, and
+//! texture counts, the memory used by geometry, acceleration
+//! structures, and textures, and a list of potential problems (e.g.
+//! no lights at all, or degenerate triangles). This is useful to
+//! triage scenes which fail (or take forever) on a render farm.
+
+// std
+use std::collections::{BTreeMap, HashSet};
+use std::mem::size_of;
+use std::sync::Arc;
+use std::time::Duration;
+// others
+use log::warn;
+// pbrt
+use crate::accelerators::bvh::{LinearBVHNode, QuantizedBVHNode};
+use crate::accelerators::kdtreeaccel::KdAccelNode;
+use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Vector3f};
+use crate::core::light::Light;
+use crate::core::mipmap::mipmap_memory;
+use crate::core::pbrt::Float;
+use crate::core::primitive::{GeometricPrimitive, MeshPrimitive, Primitive, TransformedPrimitive};
+use crate::core::scene::Scene;
+use crate::core::shape::Shape;
+use crate::shapes::triangle::TriangleMesh;
+
+#[derive(Default)]
+pub struct SceneStats {
+    /// number of (unique) shapes per type
+    pub shapes: BTreeMap<&'static str, usize>,
+    /// number of lights per type
+    pub lights: BTreeMap<&'static str, usize>,
+    pub triangle_meshes: usize,
+    pub mesh_vertices: usize,
+    pub object_instances: usize,
+    /// counted while parsing (see **pbrt_texture()**)
+    pub float_textures: usize,
+    pub spectrum_textures: usize,
+    pub image_textures: usize,
+    /// bytes used by shapes, primitives, and triangle meshes
+    pub geometry_memory: usize,
+    pub accelerator_nodes: usize,
+    /// bytes used by the nodes and primitive lists of BVHs or kd-trees
+    pub accelerator_memory: usize,
+    /// bytes used by the image pyramids of textures and environment maps
+    pub texture_memory: usize,
+    /// MIP map memory before parsing started
+    pub texture_memory_start: usize,
+    pub degenerate_triangles: usize,
+    pub lights_without_power: usize,
+    pub world_bound: Bounds3f,
+    pub build_time: Duration,
+}
+
+impl SceneStats {
+    pub fn new() -> Self {
+        SceneStats {
+            texture_memory_start: mipmap_memory(),
+            ..Default::default()
+        }
+    }
+    /// Walks through the (already built) scene, shared instances and
+    /// meshes are only counted once.
+    pub fn collect(&mut self, scene: &Scene) {
+        let mut visited: HashSet<*const Primitive> = HashSet::new();
+        let mut meshes: HashSet<*const TriangleMesh> = HashSet::new();
+        self.add_primitive(&scene.aggregate, &mut visited, &mut meshes);
+        for light in &scene.lights {
+            let name: &'static str = match **light {
+                Light::DiffuseArea(_) => "diffuse area",
+                Light::Distant(_) => "distant",
+                Light::GonioPhotometric(_) => "goniometric",
+                Light::InfiniteArea(_) => "infinite",
+                Light::Point(_) => "point",
+                Light::Projection(_) => "projection",
+                Light::Spot(_) => "spot",
+            };
+            *self.lights.entry(name).or_insert(0) += 1;
+            if light.power().is_black() {
+                self.lights_without_power += 1;
+            }
+        }
+        self.texture_memory = mipmap_memory().saturating_sub(self.texture_memory_start);
+        self.world_bound = scene.world_bound();
+    }
+    fn add_primitive(
+        &mut self,
+        primitive: &Arc<Primitive>,
+        visited: &mut HashSet<*const Primitive>,
+        meshes: &mut HashSet<*const TriangleMesh>,
+    ) {
+        match &**primitive {
+            Primitive::Geometric(geometric) => {
+                self.geometry_memory += size_of::<GeometricPrimitive>() + size_of::<Shape>();
+                let name: &'static str = match *geometric.shape {
+                    Shape::Crv(_) => "curve",
+                    Shape::Clndr(_) => "cylinder",
+                    Shape::Dsk(_) => "disk",
+                    Shape::Sphr(_) => "sphere",
+                    Shape::Trngl(ref triangle) => {
+                        self.add_mesh(&triangle.get_mesh(), meshes);
+                        let area: Float = triangle.area();
+                        if !area.is_finite() || area <= 0.0 as Float {
+                            self.degenerate_triangles += 1;
+                        }
+                        "triangle"
+                    }
+                };
+                *self.shapes.entry(name).or_insert(0) += 1;
+            }
+            Primitive::Mesh(primitive) => {
+                let mesh: &Arc<TriangleMesh> = &primitive.bvh.mesh;
+                self.geometry_memory += size_of::<MeshPrimitive>();
+                self.add_mesh(mesh, meshes);
+                for id in 0..mesh.n_triangles {
+                    let area: Float = mesh.triangle_area(id);
+                    if !area.is_finite() || area <= 0.0 as Float {
+                        self.degenerate_triangles += 1;
+                    }
+                }
+                *self.shapes.entry("triangle").or_insert(0) += mesh.n_triangles as usize;
+                self.accelerator_nodes += primitive.bvh.bvh.nodes.len();
+                self.accelerator_memory += primitive.bvh.memory();
+            }
+            Primitive::Transformed(transformed) => {
+                self.object_instances += 1;
+                self.geometry_memory += size_of::<TransformedPrimitive>();
+                if visited.insert(Arc::as_ptr(&transformed.primitive)) {
+                    self.add_primitive(&transformed.primitive, visited, meshes);
+                }
+            }
+            Primitive::BVH(bvh) => {
+                let quantized_nodes: usize = bvh.quantized.as_ref().map_or(0, |q| q.nodes.len());
+                self.accelerator_nodes += bvh.nodes.len() + quantized_nodes;
+                self.accelerator_memory += bvh.nodes.len() * size_of::<LinearBVHNode>()
+                    + quantized_nodes * size_of::<QuantizedBVHNode>()
+                    + bvh.primitives.len() * size_of::<Arc<Primitive>>();
+                for p in &bvh.primitives {
+                    self.add_primitive(p, visited, meshes);
+                }
+            }
+            Primitive::KdTree(kdtree) => {
+                self.accelerator_nodes += kdtree.nodes.len();
+                self.accelerator_memory += kdtree.nodes.len() * size_of::<KdAccelNode>()
+                    + kdtree.primitive_indices.len() * size_of::<i32>()
+                    + kdtree.primitives.len() * size_of::<Arc<Primitive>>();
+                for p in &kdtree.primitives {
+                    self.add_primitive(p, visited, meshes);
+                }
+            }
+        }
+    }
+    /// Shared meshes are only counted once.
+    fn add_mesh(&mut self, mesh: &Arc<TriangleMesh>, meshes: &mut HashSet<*const TriangleMesh>) {
+        if meshes.insert(Arc::as_ptr(mesh)) {
+            self.triangle_meshes += 1;
+            self.mesh_vertices += mesh.p.len();
+            self.geometry_memory += mesh.vertex_indices.len() * size_of::<u32>()
+                + mesh.p.len() * size_of::<Point3f>()
+                + mesh.n.len() * size_of::<Normal3f>()
+                + mesh.s.len() * size_of::<Vector3f>()
+                + mesh.uv.len() * size_of::<Point2f>()
+                + mesh.n_half.len() * size_of::<[u16; 3]>()
+                + mesh.uv_half.len() * size_of::<[u16; 2]>();
+        }
+    }
+    /// Things which are most likely mistakes in the scene description.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        if self.shapes.is_empty() {
+            problems.push(String::from("The scene contains no geometry."));
+        }
+        if self.lights.is_empty() {
+            problems.push(String::from(
+                "The scene contains no lights (the image will be black).",
+            ));
+        }
+        if self.lights_without_power > 0 {
+            problems.push(format!(
+                "{} light(s) don't emit any light.",
+                self.lights_without_power
+            ));
+        }
+        if self.degenerate_triangles > 0 {
+            problems.push(format!(
+                "{} triangle(s) have no area (degenerate or invalid vertices).",
+                self.degenerate_triangles
+            ));
+        }
+        let b: &Bounds3f = &self.world_bound;
+        let corners: [Float; 6] = [
+            b.p_min.x, b.p_min.y, b.p_min.z, b.p_max.x, b.p_max.y, b.p_max.z,
+        ];
+        if !self.shapes.is_empty() && corners.iter().any(|c| !c.is_finite()) {
+            problems.push(String::from(
+                "The scene bounds are not finite (invalid transforms or vertices?).",
+            ));
+        }
+        problems
+    }
+    pub fn print(&self) {
+        println!("Scene statistics:");
+        println!("  Shapes:");
+        for (name, count) in &self.shapes {
+            println!("    {:<20} {:>12}", name, count);
+        }
+        println!("    {:<20} {:>12}", "triangle meshes", self.triangle_meshes);
+        println!("    {:<20} {:>12}", "mesh vertices", self.mesh_vertices);
+        println!(
+            "    {:<20} {:>12}",
+            "object instances", self.object_instances
+        );
+        println!("  Lights:");
+        for (name, count) in &self.lights {
+            println!("    {:<20} {:>12}", name, count);
+        }
+        println!("  Textures:");
+        println!("    {:<20} {:>12}", "float", self.float_textures);
+        println!("    {:<20} {:>12}", "spectrum", self.spectrum_textures);
+        println!("    {:<20} {:>12}", "image maps", self.image_textures);
+        println!("  Memory:");
+        println!(
+            "    {:<20} {:>12}",
+            "geometry",
+            format_bytes(self.geometry_memory)
+        );
+        println!(
+            "    {:<20} {:>12} ({} nodes)",
+            "accelerator",
+            format_bytes(self.accelerator_memory),
+            self.accelerator_nodes
+        );
+        println!(
+            "    {:<20} {:>12}",
+            "textures",
+            format_bytes(self.texture_memory)
+        );
+        println!(
+            "  World bounds: [{} {} {}] - [{} {} {}]",
+            self.world_bound.p_min.x,
+            self.world_bound.p_min.y,
+            self.world_bound.p_min.z,
+            self.world_bound.p_max.x,
+            self.world_bound.p_max.y,
+            self.world_bound.p_max.z
+        );
+        println!(
+            "  Scene setup (accelerator, lights): {:.3} s",
+            self.build_time.as_secs_f64()
+        );
+        let problems: Vec<String> = self.problems();
+        if problems.is_empty() {
+            println!("No problems found.");
+        } else {
+            for problem in problems {
+                warn!("{}", problem);
+            }
+        }
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    let mut value: f64 = bytes as f64;
+    for unit in &["B", "KiB", "MiB"] {
+        if value < 1024.0 {
+            return format!("{:.1} {}", value, unit);
+        }
+        value /= 1024.0;
+    }
+    format!("{:.1} GiB", value)
+}
@@ -0,0 +1,120 @@
+//! SIMD versions of geometry math (feature "simd").
+//!
+//! Flat profiles of **rs_pbrt** are dominated by small pieces of
+//! geometry math: ray-box tests while traversing acceleration
+//! structures, transforming points, vectors, and normals (object
+//! instances, cameras), and multiplying 4x4 matrices. The functions of
+//! this module do the same computations with the 4-wide vectors of the
+//! [wide](https://crates.io/crates/wide) crate. Each lane performs the
+//! same operations in the same order as the scalar code, so images
+//! rendered with and without the feature are identical.
+
+// others
+use wide::f64x4;
+#[cfg(not(feature = "float_as_double"))]
+use wide::f32x4 as floatx4;
+#[cfg(feature = "float_as_double")]
+use wide::f64x4 as floatx4;
+// pbrt
+use crate::core::geometry::{Bounds3f, Point3f, Ray, Vector3f};
+use crate::core::pbrt::Float;
+use crate::core::transform::Matrix4x4;
+
+/// Multiplies two matrices, one row of the result at a time.
+#[inline]
+pub fn mtx_mul(m1: &Matrix4x4, m2: &Matrix4x4) -> Matrix4x4 {
+    let rows: [floatx4; 4] = [
+        floatx4::from(m2.m[0]),
+        floatx4::from(m2.m[1]),
+        floatx4::from(m2.m[2]),
+        floatx4::from(m2.m[3]),
+    ];
+    let mut r: Matrix4x4 = Matrix4x4::default();
+    for i in 0..4 {
+        let row: floatx4 = floatx4::splat(m1.m[i][0]) * rows[0]
+            + floatx4::splat(m1.m[i][1]) * rows[1]
+            + floatx4::splat(m1.m[i][2]) * rows[2]
+            + floatx4::splat(m1.m[i][3]) * rows[3];
+        r.m[i] = row.to_array();
+    }
+    r
+}
+
+/// Multiplies the matrix with the point (*x*, *y*, *z*, 1), the
+/// fourth component of the result is the homogeneous weight.
+#[inline]
+pub fn mtx_mul_point(m: &Matrix4x4, x: Float, y: Float, z: Float) -> [Float; 4] {
+    let r: floatx4 = column(m, 0) * floatx4::splat(x)
+        + column(m, 1) * floatx4::splat(y)
+        + column(m, 2) * floatx4::splat(z)
+        + column(m, 3);
+    r.to_array()
+}
+
+/// Multiplies the upper left 3x3 matrix with the vector (*x*, *y*,
+/// *z*), the fourth component of the result is meaningless.
+#[inline]
+pub fn mtx_mul_vector(m: &Matrix4x4, x: Float, y: Float, z: Float) -> [Float; 4] {
+    let r: floatx4 = column(m, 0) * floatx4::splat(x)
+        + column(m, 1) * floatx4::splat(y)
+        + column(m, 2) * floatx4::splat(z);
+    r.to_array()
+}
+
+/// Same as **mtx_mul_vector()**, but with the transposed matrix (used
+/// for normals).
+#[inline]
+pub fn mtx_transposed_mul_vector(m: &Matrix4x4, x: Float, y: Float, z: Float) -> [Float; 4] {
+    let r: floatx4 = floatx4::from(m.m[0]) * floatx4::splat(x)
+        + floatx4::from(m.m[1]) * floatx4::splat(y)
+        + floatx4::from(m.m[2]) * floatx4::splat(z);
+    r.to_array()
+}
+
+#[inline]
+fn column(m: &Matrix4x4, j: usize) -> floatx4 {
+    floatx4::from([m.m[0][j], m.m[1][j], m.m[2][j], m.m[3][j]])
+}
+
+/// The cross product of two vectors (computed in double precision,
+/// like **geometry::vec3_cross_vec3()**).
+#[inline]
+pub fn cross(v1: [Float; 3], v2: [Float; 3]) -> Vector3f {
+    let a: f64x4 = f64x4::from([v1[1] as f64, v1[2] as f64, v1[0] as f64, 0.0]);
+    let b: f64x4 = f64x4::from([v2[2] as f64, v2[0] as f64, v2[1] as f64, 0.0]);
+    let c: f64x4 = f64x4::from([v1[2] as f64, v1[0] as f64, v1[1] as f64, 0.0]);
+    let d: f64x4 = f64x4::from([v2[1] as f64, v2[2] as f64, v2[0] as f64, 0.0]);
+    let r: [f64; 4] = (a * b - c * d).to_array();
+    Vector3f {
+        x: r[0] as Float,
+        y: r[1] as Float,
+        z: r[2] as Float,
+    }
+}
+
+/// The parametric distances of the near and far slabs of a bounding
+/// box along a ray (see **Bounds3::intersect_p()**), for all three
+/// axes at once.
+#[inline]
+pub fn slab_distances(
+    b: &Bounds3f,
+    ray: &Ray,
+    inv_dir: &Vector3f,
+    dir_is_neg: &[u8; 3],
+) -> ([Float; 4], [Float; 4]) {
+    let near: Point3f = Point3f {
+        x: b[dir_is_neg[0]].x,
+        y: b[dir_is_neg[1]].y,
+        z: b[dir_is_neg[2]].z,
+    };
+    let far: Point3f = Point3f {
+        x: b[1_u8 - dir_is_neg[0]].x,
+        y: b[1_u8 - dir_is_neg[1]].y,
+        z: b[1_u8 - dir_is_neg[2]].z,
+    };
+    let o: floatx4 = floatx4::from([ray.o.x, ray.o.y, ray.o.z, 0.0]);
+    let inv: floatx4 = floatx4::from([inv_dir.x, inv_dir.y, inv_dir.z, 0.0]);
+    let t_near: floatx4 = (floatx4::from([near.x, near.y, near.z, 0.0]) - o) * inv;
+    let t_far: floatx4 = (floatx4::from([far.x, far.y, far.z, 0.0]) - o) * inv;
+    (t_near.to_array(), t_far.to_array())
+}
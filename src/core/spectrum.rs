@@ -1,14 +1,68 @@
 //! The **Spectrum** type hides the details of the particular spectral
 //! representation used, so that changing this detail of the system
 //! only requires changing the **Spectrum** implementation.
+//!
+//! # Sampled Spectra
+//!
+//! By default **Spectrum** is an **RGBSpectrum**. Building with the
+//! `sampled_spectrum` feature switches the whole renderer to
+//! **SampledSpectrum**, which stores 60 samples between 400nm and
+//! 700nm. RGB values are converted to (and from) spectra only where
+//! they enter (or leave) the system, e.g. scene parameters, textures,
+//! and the film. The conversion from RGB uses the smooth spectra of
+//! Jakob and Hanika's spectral upsampling, which keeps reflectances
+//! between zero and one. A constant spectrum of one is white, RGB (1,
+//! 1, 1), so greys become constant spectra.
+//!
+//! ```rust
+//! use pbrt::core::pbrt::Float;
+//! use pbrt::core::spectrum::{RGBSpectrum, SampledSpectrum};
+//!
+//! fn main() {
+//!     let rgb: [Float; 3] = [0.8, 0.4, 0.1];
+//!     let s = SampledSpectrum::from_rgb(&rgb);
+//!     let mut back: [Float; 3] = [0.0; 3];
+//!     s.to_rgb(&mut back);
+//!     for c in 0..3 {
+//!         assert!((back[c] - rgb[c]).abs() < 0.01);
+//!     }
+//!     // luminance agrees with the RGB representation
+//!     let y_rgb = RGBSpectrum::from_rgb(&rgb).y();
+//!     assert!((s.y() - y_rgb).abs() < 0.01);
+//! }
+//! ```
+//!
+//! # Color Spaces
+//!
+//! The film converts XYZ values to RGB values of an **RGBColorSpace**
+//! (sRGB by default, ACEScg, or Rec.2020). White stays white, even if
+//! the white point of the color space isn't D65 (like for ACEScg).
+//!
+//! ```rust
+//! use pbrt::core::pbrt::Float;
+//! use pbrt::core::spectrum::{color_matrix_mul, rgb_to_xyz, RGBColorSpace};
+//!
+//! fn main() {
+//!     let mut xyz: [Float; 3] = [0.0; 3];
+//!     rgb_to_xyz(&[1.0, 1.0, 1.0], &mut xyz);
+//!     for color_space in &[RGBColorSpace::ACEScg, RGBColorSpace::Rec2020] {
+//!         let mut rgb: [Float; 3] = [0.0; 3];
+//!         color_matrix_mul(&color_space.xyz_to_rgb_matrix(), &xyz, &mut rgb);
+//!         for c in 0..3 {
+//!             assert!((rgb[c] - 1.0).abs() < 0.001);
+//!         }
+//!     }
+//! }
+//! ```
 
 // std
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 // others
 use num::Zero;
+use rayon::prelude::*;
 // pbrt
-use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, find_interval, lerp};
+use crate::core::pbrt::{Float, Spectrum};
 
 // see spectrum.h
 
@@ -1511,6 +1565,221 @@ pub fn blackbody_normalized(lambda: &[Float], n: usize, t: Float, le: &mut Vec<F
     }
 }
 
+/// Number of samples (300nm to 830nm in 10nm steps) of the CIE
+/// daylight basis functions.
+pub const N_CIE_D_SAMPLES: usize = 54;
+/// CIE daylight basis function S0 (mean).
+pub const CIE_D_S0: [Float; N_CIE_D_SAMPLES] = [
+    0.04, 6.0, 29.6, 55.3, 57.3, 61.8, 61.5, 68.8, 63.4, 65.8, 94.8, 104.8, 105.9, 96.8, 113.9,
+    125.6, 125.5, 121.3, 121.3, 113.5, 113.1, 110.8, 106.5, 108.8, 105.3, 104.4, 100.0, 96.0, 95.1,
+    89.1, 90.5, 90.3, 88.4, 84.0, 85.1, 81.9, 82.6, 84.9, 81.3, 71.9, 74.3, 76.4, 63.3, 71.7, 77.0,
+    65.2, 47.7, 68.6, 65.0, 66.0, 61.0, 53.3, 58.9, 61.9,
+];
+/// CIE daylight basis function S1 (yellow-blue variation).
+pub const CIE_D_S1: [Float; N_CIE_D_SAMPLES] = [
+    0.02, 4.5, 22.4, 42.0, 40.6, 41.6, 38.0, 42.4, 38.5, 35.0, 43.4, 46.3, 43.9, 37.1, 36.7, 35.9,
+    32.6, 27.9, 24.3, 20.1, 16.2, 13.2, 8.6, 6.1, 4.2, 1.9, 0.0, -1.6, -3.5, -3.5, -5.8, -7.2,
+    -8.6, -9.5, -10.9, -10.7, -12.0, -14.0, -13.6, -12.0, -13.3, -12.9, -10.6, -11.6, -12.2, -10.2,
+    -7.8, -11.2, -10.4, -10.6, -9.7, -8.3, -9.3, -9.8,
+];
+/// CIE daylight basis function S2 (pink-green variation).
+pub const CIE_D_S2: [Float; N_CIE_D_SAMPLES] = [
+    0.0, 2.0, 4.0, 8.5, 7.8, 6.7, 5.3, 6.1, 3.0, 1.2, -1.1, -0.5, -0.7, -1.2, -2.6, -2.9, -2.8,
+    -2.6, -2.6, -1.8, -1.5, -1.3, -1.2, -1.0, -0.5, -0.3, 0.0, 0.2, 0.5, 2.1, 3.2, 4.1, 4.7, 5.1,
+    6.7, 7.3, 8.6, 9.8, 10.2, 8.3, 9.6, 8.5, 7.0, 7.6, 8.0, 6.7, 5.2, 7.4, 6.8, 7.0, 6.4, 5.5, 6.1,
+    6.5,
+];
+
+/// Number of samples (380nm to 780nm in 5nm steps) of the CIE
+/// fluorescent illuminants.
+pub const N_CIE_F_SAMPLES: usize = 81;
+/// CIE fluorescent illuminants F1 to F12 (relative spectral power).
+#[allow(clippy::approx_constant)]
+pub const CIE_F: [[Float; N_CIE_F_SAMPLES]; 12] = [
+    // F1 (daylight, 6430K)
+    [
+        1.87, 2.36, 2.94, 3.47, 5.17, 19.49, 6.13, 6.24, 7.01, 7.79, 8.56, 43.67, 16.94, 10.72,
+        11.35, 11.89, 12.37, 12.75, 13.0, 13.15, 13.23, 13.17, 13.13, 12.85, 12.52, 12.2, 11.83,
+        11.5, 11.22, 11.05, 11.03, 11.18, 11.53, 27.74, 17.05, 13.55, 14.33, 15.01, 15.52, 18.29,
+        19.55, 15.48, 14.91, 14.15, 13.22, 12.19, 11.12, 10.03, 8.95, 7.96, 7.02, 6.2, 5.42, 4.73,
+        4.15, 3.64, 3.2, 2.81, 2.47, 2.18, 1.93, 1.72, 1.67, 1.43, 1.29, 1.19, 1.08, 0.96, 0.88,
+        0.81, 0.77, 0.75, 0.73, 0.68, 0.69, 0.64, 0.68, 0.69, 0.61, 0.52, 0.43,
+    ],
+    // F2 (cool white, 4230K)
+    [
+        1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62, 5.06, 34.98, 11.81, 6.27,
+        6.63, 6.93, 7.19, 7.4, 7.54, 7.62, 7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16,
+        7.47, 8.04, 8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47, 22.79, 19.29,
+        18.66, 17.73, 16.54, 15.21, 13.8, 12.36, 10.95, 9.65, 8.4, 7.32, 6.31, 5.43, 4.68, 4.02,
+        3.45, 2.96, 2.55, 2.19, 1.89, 1.64, 1.53, 1.27, 1.1, 0.99, 0.88, 0.76, 0.68, 0.61, 0.56,
+        0.54, 0.51, 0.47, 0.47, 0.43, 0.46, 0.47, 0.4, 0.33, 0.27,
+    ],
+    // F3 (white, 3450K)
+    [
+        0.82, 1.02, 1.26, 1.44, 2.57, 14.36, 2.7, 2.45, 2.73, 3.0, 3.28, 31.85, 9.47, 4.02, 4.25,
+        4.44, 4.59, 4.72, 4.8, 4.86, 4.87, 4.85, 4.88, 4.77, 4.67, 4.62, 4.62, 4.73, 4.99, 5.48,
+        6.25, 7.34, 8.78, 23.82, 16.14, 14.59, 16.63, 18.49, 19.95, 23.11, 24.69, 21.41, 20.85,
+        19.93, 18.67, 17.22, 15.65, 14.04, 12.45, 10.95, 9.51, 8.27, 7.11, 6.09, 5.22, 4.45, 3.8,
+        3.23, 2.75, 2.33, 1.99, 1.7, 1.55, 1.27, 1.09, 0.96, 0.83, 0.71, 0.62, 0.54, 0.49, 0.46,
+        0.43, 0.39, 0.39, 0.35, 0.38, 0.39, 0.33, 0.28, 0.21,
+    ],
+    // F4 (warm white, 2940K)
+    [
+        0.57, 0.7, 0.87, 0.98, 2.01, 13.75, 1.95, 1.59, 1.76, 1.93, 2.1, 30.28, 8.03, 2.55, 2.7,
+        2.82, 2.91, 2.99, 3.04, 3.08, 3.09, 3.09, 3.14, 3.06, 3.0, 2.98, 3.01, 3.14, 3.41, 3.9,
+        4.69, 5.81, 7.32, 22.59, 15.11, 13.88, 16.33, 18.68, 20.64, 24.28, 26.26, 23.28, 22.94,
+        22.14, 20.91, 19.43, 17.74, 16.0, 14.42, 12.56, 10.93, 9.52, 8.18, 7.01, 6.0, 5.11, 4.36,
+        3.69, 3.13, 2.64, 2.24, 1.91, 1.7, 1.39, 1.18, 1.03, 0.88, 0.74, 0.64, 0.54, 0.49, 0.46,
+        0.42, 0.37, 0.37, 0.33, 0.35, 0.36, 0.31, 0.26, 0.19,
+    ],
+    // F5 (daylight, 6350K)
+    [
+        1.87, 2.35, 2.92, 3.45, 5.1, 18.91, 6.0, 6.11, 6.85, 7.58, 8.31, 40.76, 16.06, 10.32,
+        10.91, 11.4, 11.83, 12.17, 12.4, 12.54, 12.58, 12.52, 12.47, 12.2, 11.89, 11.61, 11.33,
+        11.1, 10.96, 10.97, 11.16, 11.54, 12.12, 27.78, 17.73, 14.47, 15.2, 15.77, 16.1, 18.54,
+        19.5, 15.39, 14.64, 13.72, 12.69, 11.57, 10.45, 9.35, 8.29, 7.32, 6.41, 5.63, 4.9, 4.26,
+        3.72, 3.25, 2.83, 2.49, 2.19, 1.93, 1.71, 1.52, 1.48, 1.26, 1.13, 1.05, 0.96, 0.85, 0.78,
+        0.72, 0.68, 0.67, 0.65, 0.61, 0.62, 0.59, 0.62, 0.64, 0.55, 0.47, 0.4,
+    ],
+    // F6 (lite white, 4150K)
+    [
+        1.05, 1.31, 1.63, 1.9, 3.11, 14.8, 3.43, 3.3, 3.68, 4.07, 4.45, 32.61, 10.74, 5.48, 5.78,
+        6.03, 6.25, 6.41, 6.52, 6.58, 6.59, 6.56, 6.56, 6.42, 6.28, 6.2, 6.19, 6.3, 6.6, 7.12,
+        7.94, 9.07, 10.49, 25.22, 17.46, 15.63, 17.22, 18.53, 19.43, 21.97, 23.01, 19.41, 18.56,
+        17.42, 16.09, 14.64, 13.15, 11.68, 10.25, 8.95, 7.74, 6.69, 5.71, 4.87, 4.16, 3.55, 3.02,
+        2.57, 2.2, 1.87, 1.6, 1.37, 1.29, 1.05, 0.91, 0.81, 0.71, 0.61, 0.54, 0.48, 0.44, 0.43,
+        0.4, 0.37, 0.38, 0.35, 0.39, 0.41, 0.33, 0.26, 0.21,
+    ],
+    // F7 (broadband, D65 simulator)
+    [
+        2.56, 3.18, 3.84, 4.53, 6.15, 19.37, 7.37, 7.05, 7.71, 8.41, 9.15, 44.14, 17.52, 11.35,
+        12.0, 12.58, 13.08, 13.45, 13.71, 13.88, 13.95, 13.93, 13.82, 13.64, 13.43, 13.25, 13.08,
+        12.93, 12.78, 12.6, 12.44, 12.33, 12.26, 29.52, 17.05, 12.44, 12.58, 12.72, 12.83, 15.46,
+        16.75, 12.83, 12.67, 12.45, 12.19, 11.89, 11.6, 11.35, 11.12, 10.95, 10.76, 10.42, 10.11,
+        10.04, 10.02, 10.11, 9.87, 8.65, 7.27, 6.44, 5.83, 5.41, 5.04, 4.57, 4.12, 3.77, 3.46,
+        3.08, 2.73, 2.47, 2.25, 2.06, 1.9, 1.75, 1.62, 1.54, 1.45, 1.32, 1.17, 0.99, 0.81,
+    ],
+    // F8 (broadband, D50 simulator)
+    [
+        1.21, 1.5, 1.81, 2.13, 3.17, 13.08, 3.83, 3.45, 3.86, 4.42, 5.09, 34.1, 12.42, 7.68, 8.6,
+        9.46, 10.24, 10.84, 11.33, 11.71, 11.98, 12.17, 12.28, 12.32, 12.35, 12.44, 12.55, 12.68,
+        12.77, 12.72, 12.6, 12.43, 12.22, 28.96, 16.51, 11.79, 11.76, 11.77, 11.84, 14.61, 16.11,
+        12.34, 12.53, 12.72, 12.92, 13.12, 13.34, 13.61, 13.87, 14.07, 14.2, 14.16, 14.13, 14.34,
+        14.5, 14.46, 14.0, 12.58, 10.99, 9.98, 9.22, 8.62, 8.07, 7.39, 6.71, 6.16, 5.63, 5.03,
+        4.46, 4.02, 3.66, 3.36, 3.09, 2.85, 2.65, 2.51, 2.37, 2.15, 1.89, 1.61, 1.32,
+    ],
+    // F9 (broadband, cool white deluxe)
+    [
+        0.9, 1.12, 1.36, 1.6, 2.59, 12.8, 3.05, 2.56, 2.86, 3.3, 3.82, 32.62, 10.77, 5.84, 6.57,
+        7.25, 7.86, 8.35, 8.75, 9.06, 9.31, 9.48, 9.61, 9.68, 9.74, 9.88, 10.04, 10.26, 10.48,
+        10.63, 10.78, 10.96, 11.18, 27.71, 16.29, 12.28, 12.74, 13.21, 13.65, 16.57, 18.14, 14.55,
+        14.65, 14.66, 14.61, 14.5, 14.39, 14.4, 14.47, 14.62, 14.72, 14.55, 14.4, 14.58, 14.88,
+        15.51, 15.47, 13.2, 10.57, 9.18, 8.25, 7.57, 7.03, 6.35, 5.72, 5.25, 4.8, 4.29, 3.8, 3.43,
+        3.12, 2.86, 2.64, 2.43, 2.26, 2.14, 2.02, 1.83, 1.61, 1.38, 1.12,
+    ],
+    // F10 (narrow tri-band, 5000K)
+    [
+        1.11, 0.63, 0.62, 0.57, 1.48, 12.16, 2.12, 2.7, 3.74, 5.14, 6.75, 34.39, 14.86, 10.4,
+        10.76, 10.67, 10.11, 9.27, 8.29, 7.29, 7.91, 16.64, 16.73, 10.44, 5.94, 3.34, 2.35, 1.88,
+        1.59, 1.47, 1.8, 5.71, 40.98, 73.69, 33.61, 8.24, 3.38, 2.47, 2.14, 4.86, 11.45, 14.79,
+        12.16, 8.97, 6.52, 8.31, 44.12, 34.55, 12.09, 12.15, 10.52, 4.43, 1.95, 2.19, 3.19, 2.77,
+        2.29, 2.0, 1.52, 1.35, 1.47, 1.79, 1.74, 1.02, 1.14, 3.32, 4.49, 2.05, 0.49, 0.24, 0.21,
+        0.21, 0.24, 0.24, 0.21, 0.17, 0.21, 0.22, 0.17, 0.12, 0.09,
+    ],
+    // F11 (narrow tri-band, 4000K)
+    [
+        0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33, 4.49, 33.94, 12.13, 6.95,
+        7.19, 7.12, 6.72, 6.13, 5.46, 4.79, 5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.1, 0.89,
+        0.83, 1.18, 4.9, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43, 11.28, 14.76, 12.73,
+        9.74, 7.33, 9.72, 55.27, 42.58, 13.18, 13.16, 12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48,
+        2.14, 1.54, 1.33, 1.46, 1.94, 2.0, 1.2, 1.35, 4.1, 5.58, 2.51, 0.57, 0.27, 0.23, 0.21,
+        0.24, 0.24, 0.2, 0.24, 0.32, 0.26, 0.16, 0.12, 0.09,
+    ],
+    // F12 (narrow tri-band, 3000K)
+    [
+        0.96, 0.64, 0.4, 0.33, 1.19, 12.48, 1.12, 0.94, 1.08, 1.37, 1.78, 29.05, 7.9, 2.65, 2.71,
+        2.65, 2.49, 2.33, 2.1, 1.91, 3.01, 10.83, 11.88, 6.88, 3.43, 1.49, 0.92, 0.71, 0.6, 0.63,
+        1.1, 4.56, 34.4, 65.4, 29.48, 7.16, 3.08, 2.47, 2.27, 5.09, 11.96, 15.32, 14.27, 11.86,
+        9.28, 12.31, 68.53, 53.02, 14.67, 14.38, 14.71, 6.46, 2.57, 2.75, 4.18, 3.44, 2.81, 2.42,
+        1.64, 1.36, 1.49, 2.14, 2.34, 1.42, 1.61, 5.04, 6.98, 3.19, 0.71, 0.3, 0.26, 0.23, 0.28,
+        0.28, 0.21, 0.17, 0.21, 0.19, 0.15, 0.1, 0.05,
+    ],
+];
+
+/// Spectral power distribution of CIE daylight (D-series) illuminants
+/// for a correlated color temperature **t** (between 4000K and
+/// 25000K), normalized to 100 at 560nm.
+pub fn cie_d_illuminant(lambda: &[Float], n: usize, t: Float, le: &mut Vec<Float>) {
+    let t: f64 = clamp_t(t, 4000.0 as Float, 25000.0 as Float) as f64;
+    // chromaticity of the daylight locus
+    let x: f64 = if t <= 7000.0 {
+        -4.6070e9 / (t * t * t) + 2.9678e6 / (t * t) + 0.09911e3 / t + 0.244_063
+    } else {
+        -2.0064e9 / (t * t * t) + 1.9018e6 / (t * t) + 0.24748e3 / t + 0.237_040
+    };
+    let y: f64 = -3.0 * x * x + 2.870 * x - 0.275;
+    let m: f64 = 0.0241 + 0.2562 * x - 0.7341 * y;
+    let m1: Float = ((-1.3515 - 1.7703 * x + 5.9114 * y) / m) as Float;
+    let m2: Float = ((0.0300 - 31.4424 * x + 30.0717 * y) / m) as Float;
+    for item in lambda.iter().take(n) {
+        let pos: Float = (*item - 300.0 as Float) / 10.0 as Float;
+        if pos < 0.0 as Float || pos > (N_CIE_D_SAMPLES - 1) as Float {
+            le.push(0.0 as Float);
+            continue;
+        }
+        let i: usize = (pos as usize).min(N_CIE_D_SAMPLES - 2);
+        let f: Float = pos - i as Float;
+        let s = |table: &[Float; N_CIE_D_SAMPLES]| -> Float { lerp(f, table[i], table[i + 1]) };
+        le.push(s(&CIE_D_S0) + m1 * s(&CIE_D_S1) + m2 * s(&CIE_D_S2));
+    }
+}
+
+/// Blackbody emission for temperature **t** (in Kelvin), normalized
+/// to a maximum value of one and multiplied by **scale**.
+pub fn blackbody_spectrum(t: Float, scale: Float) -> Spectrum {
+    let mut v: Vec<Float> = Vec::with_capacity(N_CIE_SAMPLES as usize);
+    blackbody_normalized(&CIE_LAMBDA, N_CIE_SAMPLES as usize, t, &mut v);
+    Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32) * scale
+}
+
+/// Standard illuminants by name, normalized to a luminance of one:
+/// "stdillum-A" (incandescent), the CIE daylight illuminants
+/// "stdillum-D50", "stdillum-D55", "stdillum-D65", "stdillum-D75"
+/// etc., and the CIE fluorescent illuminants "stdillum-F1" to
+/// "stdillum-F12".
+pub fn std_illuminant(name: &str) -> Option<Spectrum> {
+    let mut v: Vec<Float> = Vec::with_capacity(N_CIE_SAMPLES as usize);
+    let s: Spectrum = if name == "stdillum-A" {
+        blackbody(&CIE_LAMBDA, N_CIE_SAMPLES as usize, 2856.0 as Float, &mut v);
+        Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32)
+    } else if let Some(nominal) = name.strip_prefix("stdillum-D") {
+        let nominal: Float = match nominal.parse::<Float>() {
+            Ok(nominal) => nominal,
+            Err(_) => return None,
+        };
+        // the temperatures were defined before the value of the
+        // second radiation constant (c2) changed
+        let t: Float = nominal * 100.0 as Float * 1.4388 as Float / 1.4380 as Float;
+        if t < 4000.0 as Float || t > 25000.0 as Float {
+            return None;
+        }
+        cie_d_illuminant(&CIE_LAMBDA, N_CIE_SAMPLES as usize, t, &mut v);
+        Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32)
+    } else if let Some(number) = name.strip_prefix("stdillum-F") {
+        let table: &[Float; N_CIE_F_SAMPLES] = match number.parse::<usize>() {
+            Ok(i) if (1..=CIE_F.len()).contains(&i) => &CIE_F[i - 1],
+            _ => return None,
+        };
+        let lambda: Vec<Float> = (0..N_CIE_F_SAMPLES)
+            .map(|i| 380.0 as Float + 5.0 as Float * i as Float)
+            .collect();
+        Spectrum::from_sampled(&lambda, table, N_CIE_F_SAMPLES as i32)
+    } else {
+        return None;
+    };
+    Some(s / s.y())
+}
+
 #[derive(Debug, Clone)]
 pub enum SpectrumType {
     Reflectance,
@@ -1523,6 +1792,12 @@ pub struct RGBSpectrum {
 }
 
 impl RGBSpectrum {
+    pub const N_SAMPLES: usize = 3;
+    /// Representative wavelength (in nm) of the i-th channel, roughly
+    /// the dominant wavelengths of the sRGB primaries.
+    pub fn channel_wavelength(i: usize) -> Float {
+        [611.0 as Float, 549.0 as Float, 464.0 as Float][i]
+    }
     pub fn new(v: Float) -> Self {
         // let n_spectrum_samples = 3; // RGB
         RGBSpectrum { c: [v, v, v] }
@@ -1576,11 +1851,10 @@ impl RGBSpectrum {
     pub fn from_sampled(lambda: &[Float], v: &[Float], n: i32) -> RGBSpectrum {
         // sort samples if unordered, use sorted for returned spectrum
         if !spectrum_samples_sorted(lambda, v, n) {
-            panic!("TODO: if !spectrum_samples_sorted(...)");
-            // std::vector<Float> slambda(&lambda[0], &lambda[n]);
-            // std::vector<Float> sv(&v[0], &v[n]);
-            // SortSpectrumSamples(&slambda[0], &sv[0], n);
-            // return FromSampled(&slambda[0], &sv[0], n);
+            let mut slambda: Vec<Float> = lambda[..n as usize].to_vec();
+            let mut sv: Vec<Float> = v[..n as usize].to_vec();
+            sort_spectrum_samples(&mut slambda, &mut sv);
+            return RGBSpectrum::from_sampled(&slambda, &sv, n);
         }
         let mut xyz: [Float; 3] = [0.0 as Float; 3];
         for i in 0..N_CIE_SAMPLES {
@@ -1612,7 +1886,7 @@ impl RGBSpectrum {
         RGBSpectrum::rgb(self.c[0].exp(), self.c[1].exp(), self.c[2].exp())
     }
     /// Clamp spectrum to lie between the values low and high. Use
-    /// (0.0 as Float, std::f32::INFINITY as Float) if there are no
+    /// (0.0 as Float, Float::INFINITY) if there are no
     /// specific values.
     pub fn clamp(&self, low: Float, high: Float) -> RGBSpectrum {
         let mut ret: RGBSpectrum = RGBSpectrum::default();
@@ -1631,6 +1905,24 @@ impl RGBSpectrum {
         }
         m
     }
+    /// Mean over all samples (used e.g. to turn per-channel PDFs
+    /// into a single one).
+    pub fn average(&self) -> Float {
+        (self.c[0] + self.c[1] + self.c[2]) / 3.0 as Float
+    }
+    /// Index of the only non-zero sample (if there is exactly one).
+    pub fn single_channel(&self) -> Option<usize> {
+        let mut channel: Option<usize> = None;
+        for i in 0..3 {
+            if self.c[i] != 0.0 as Float {
+                if channel.is_some() {
+                    return None;
+                }
+                channel = Some(i);
+            }
+        }
+        channel
+    }
     pub fn has_nans(&self) -> bool {
         for i in 0..3 {
             if self.c[i].is_nan() {
@@ -1639,6 +1931,14 @@ impl RGBSpectrum {
         }
         false
     }
+    pub fn has_infs(&self) -> bool {
+        for i in 0..3 {
+            if self.c[i].is_infinite() {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 impl PartialEq for RGBSpectrum {
@@ -1811,6 +2111,680 @@ impl From<Float> for RGBSpectrum {
     }
 }
 
+// SampledSpectrum
+
+/// First wavelength (in nm) covered by a **SampledSpectrum**.
+pub const SAMPLED_LAMBDA_START: Float = 400.0;
+/// Last wavelength (in nm) covered by a **SampledSpectrum**.
+pub const SAMPLED_LAMBDA_END: Float = 700.0;
+/// Number of (equally sized) wavelength bins of a **SampledSpectrum**.
+pub const N_SPECTRAL_SAMPLES: usize = 60;
+
+/// Precomputed per bin averages of the CIE matching functions,
+/// chromatically adapted from the white of a constant spectrum (close
+/// to the equal energy white) to D65: a constant spectrum of one has
+/// the XYZ values of the (D65) white of the RGB values, so it and RGB
+/// (1, 1, 1) convert to each other.
+struct SampledSpectrumTables {
+    x: [Float; N_SPECTRAL_SAMPLES],
+    y: [Float; N_SPECTRAL_SAMPLES],
+    z: [Float; N_SPECTRAL_SAMPLES],
+}
+
+lazy_static::lazy_static! {
+    static ref SAMPLED_SPECTRUM_TABLES: SampledSpectrumTables = {
+        SampledSpectrumTables::new()
+    };
+    static ref RGB_TO_SPECTRUM_TABLE: RGBToSpectrumTable = {
+        RGBToSpectrumTable::new()
+    };
+}
+
+impl SampledSpectrumTables {
+    fn new() -> Self {
+        let mut x: [Float; N_SPECTRAL_SAMPLES] = [0.0; N_SPECTRAL_SAMPLES];
+        let mut y: [Float; N_SPECTRAL_SAMPLES] = [0.0; N_SPECTRAL_SAMPLES];
+        let mut z: [Float; N_SPECTRAL_SAMPLES] = [0.0; N_SPECTRAL_SAMPLES];
+        let n: i32 = N_CIE_SAMPLES as i32;
+        for i in 0..N_SPECTRAL_SAMPLES {
+            let (wl0, wl1) = SampledSpectrum::bin_range(i);
+            x[i] = average_spectrum_samples(&CIE_LAMBDA, &CIE_X, n, wl0, wl1);
+            y[i] = average_spectrum_samples(&CIE_LAMBDA, &CIE_Y, n, wl0, wl1);
+            z[i] = average_spectrum_samples(&CIE_LAMBDA, &CIE_Z, n, wl0, wl1);
+        }
+        // XYZ values of a constant spectrum of one
+        let scale: f64 = SampledSpectrum::xyz_scale() as f64;
+        let white: [f64; 3] = [
+            x.iter().map(|v| *v as f64).sum::<f64>() * scale,
+            y.iter().map(|v| *v as f64).sum::<f64>() * scale,
+            z.iter().map(|v| *v as f64).sum::<f64>() * scale,
+        ];
+        let sum: f64 = white[0] + white[1] + white[2];
+        let mut adaptation: [[f64; 3]; 3] =
+            bradford_adaptation(&[white[0] / sum, white[1] / sum], &WHITE_D65);
+        for row in adaptation.iter_mut() {
+            for v in row.iter_mut() {
+                *v /= white[1];
+            }
+        }
+        for i in 0..N_SPECTRAL_SAMPLES {
+            let xyz: [f64; 3] = mul_3x3_vec(&adaptation, &[x[i] as f64, y[i] as f64, z[i] as f64]);
+            x[i] = xyz[0] as Float;
+            y[i] = xyz[1] as Float;
+            z[i] = xyz[2] as Float;
+        }
+        SampledSpectrumTables { x, y, z }
+    }
+}
+
+/// Smooth and bounded (between 0 and 1) reflectance spectrum
+/// described by three coefficients of a quadratic polynomial, which
+/// is mapped through a sigmoid (see Jakob and Hanika, "A
+/// Low-Dimensional Function Space for Efficient Spectral
+/// Upsampling"). The polynomial is evaluated for wavelengths
+/// normalized to [0, 1] over the range of **SampledSpectrum**.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RGBSigmoidPolynomial {
+    pub c0: Float,
+    pub c1: Float,
+    pub c2: Float,
+}
+
+impl RGBSigmoidPolynomial {
+    pub fn new(c: [Float; 3]) -> Self {
+        RGBSigmoidPolynomial {
+            c0: c[0],
+            c1: c[1],
+            c2: c[2],
+        }
+    }
+    /// Looks up (and interpolates) the coefficients for an RGB
+    /// reflectance with components between 0 and 1.
+    pub fn from_rgb(rgb: &[Float; 3]) -> Self {
+        RGBSigmoidPolynomial::new(RGB_TO_SPECTRUM_TABLE.lookup(rgb))
+    }
+    pub fn evaluate(&self, lambda: Float) -> Float {
+        let t: Float =
+            (lambda - SAMPLED_LAMBDA_START) / (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START);
+        sigmoid((self.c0 * t + self.c1) * t + self.c2)
+    }
+}
+
+fn sigmoid(x: Float) -> Float {
+    if x.is_infinite() {
+        if x > 0.0 as Float {
+            return 1.0 as Float;
+        }
+        return 0.0 as Float;
+    }
+    0.5 as Float + x / (2.0 as Float * (1.0 as Float + x * x).sqrt())
+}
+
+/// Resolution (per dimension) of the RGB to spectrum coefficient
+/// table.
+const RGB_TO_SPECTRUM_RES: usize = 32;
+/// Maximum number of Gauss-Newton iterations per table entry.
+const RGB_TO_SPECTRUM_ITERATIONS: usize = 64;
+
+/// Sigmoid polynomial coefficients for a grid of RGB values. Like
+/// *rgb2spec* the table is indexed by the largest RGB component (z)
+/// and the other two components divided by it (x and y). It is
+/// filled on first use by fitting each entry so that its spectrum
+/// converts back to the RGB value of the entry. There is one table
+/// only, scene colors are always sRGB. The fit (3 x 32^3 entries)
+/// costs about 0.4 seconds of CPU time in optimized builds, but
+/// several seconds in debug builds, spread over all threads.
+struct RGBToSpectrumTable {
+    z_nodes: Vec<Float>,
+    coeffs: Vec<[Float; 3]>,
+}
+
+impl RGBToSpectrumTable {
+    fn new() -> Self {
+        let res: usize = RGB_TO_SPECTRUM_RES;
+        // more entries near black and near full saturation
+        let smooth_step = |x: f64| -> f64 { x * x * (3.0 - 2.0 * x) };
+        let z_nodes: Vec<f64> = (0..res)
+            .map(|k| smooth_step(smooth_step(k as f64 / (res - 1) as f64)))
+            .collect();
+        let fitter: SigmoidFitter = SigmoidFitter::new();
+        let rows: Vec<(usize, usize)> =
+            (0..3).flat_map(|l| (0..res).map(move |j| (l, j))).collect();
+        // each row is fitted along z starting from a medium
+        // brightness and uses the previous solution as the initial
+        // guess (continuation)
+        let fitted_rows: Vec<Vec<[f64; 3]>> = rows
+            .par_iter()
+            .map(|(l, j)| {
+                let mut row: Vec<[f64; 3]> = vec![[0.0; 3]; res * res];
+                let y: f64 = *j as f64 / (res - 1) as f64;
+                for i in 0..res {
+                    let x: f64 = i as f64 / (res - 1) as f64;
+                    let start: usize = res / 5;
+                    let mut fit = |k: usize, c: [f64; 3]| -> [f64; 3] {
+                        let z: f64 = z_nodes[k];
+                        let mut rgb: [f64; 3] = [0.0; 3];
+                        rgb[*l] = z;
+                        rgb[(*l + 1) % 3] = x * z;
+                        rgb[(*l + 2) % 3] = y * z;
+                        let c: [f64; 3] = fitter.fit(&rgb, c);
+                        row[k * res + i] = c;
+                        c
+                    };
+                    let mut c: [f64; 3] = [0.0; 3];
+                    for k in start..res {
+                        c = fit(k, c);
+                    }
+                    c = [0.0; 3];
+                    for k in (0..start).rev() {
+                        c = fit(k, c);
+                    }
+                }
+                row
+            })
+            .collect();
+        // store as [l][k][j][i]
+        let mut coeffs: Vec<[Float; 3]> = vec![[0.0 as Float; 3]; 3 * res * res * res];
+        for (row, (l, j)) in fitted_rows.iter().zip(rows.iter()) {
+            for k in 0..res {
+                for i in 0..res {
+                    let c: [f64; 3] = row[k * res + i];
+                    coeffs[((l * res + k) * res + j) * res + i] =
+                        [c[0] as Float, c[1] as Float, c[2] as Float];
+                }
+            }
+        }
+        RGBToSpectrumTable {
+            z_nodes: z_nodes.iter().map(|z| *z as Float).collect(),
+            coeffs,
+        }
+    }
+    fn lookup(&self, rgb: &[Float; 3]) -> [Float; 3] {
+        let res: usize = RGB_TO_SPECTRUM_RES;
+        let rgb: [Float; 3] = [
+            clamp_t(rgb[0], 0.0 as Float, 1.0 as Float),
+            clamp_t(rgb[1], 0.0 as Float, 1.0 as Float),
+            clamp_t(rgb[2], 0.0 as Float, 1.0 as Float),
+        ];
+        // find maximum component and compute remapped component values
+        let l: usize = if rgb[0] > rgb[1] {
+            if rgb[0] > rgb[2] {
+                0
+            } else {
+                2
+            }
+        } else if rgb[1] > rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z: Float = rgb[l];
+        if z == 0.0 as Float {
+            // black
+            return [0.0 as Float, 0.0 as Float, -Float::INFINITY];
+        }
+        if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+            // greys are constant spectra (no interpolation error)
+            return [
+                0.0 as Float,
+                0.0 as Float,
+                (z - 0.5 as Float) / (z * (1.0 as Float - z)).sqrt(),
+            ];
+        }
+        let scale: Float = (res - 1) as Float / z;
+        let x: Float = rgb[(l + 1) % 3] * scale;
+        let y: Float = rgb[(l + 2) % 3] * scale;
+        // compute integer indices and offsets for coefficient
+        // interpolation
+        let xi: usize = (x as usize).min(res - 2);
+        let yi: usize = (y as usize).min(res - 2);
+        let zi: usize = find_interval(res as i32, |i| self.z_nodes[i as usize] <= z) as usize;
+        let dx: Float = x - xi as Float;
+        let dy: Float = y - yi as Float;
+        let dz: Float = (z - self.z_nodes[zi]) / (self.z_nodes[zi + 1] - self.z_nodes[zi]);
+        let co = |dx: usize, dy: usize, dz: usize, i: usize| -> Float {
+            self.coeffs[((l * res + zi + dz) * res + yi + dy) * res + xi + dx][i]
+        };
+        let mut c: [Float; 3] = [0.0 as Float; 3];
+        for (i, ci) in c.iter_mut().enumerate() {
+            // trilinearly interpolate sigmoid polynomial coefficient
+            *ci = lerp(
+                dz,
+                lerp(
+                    dy,
+                    lerp(dx, co(0, 0, 0, i), co(1, 0, 0, i)),
+                    lerp(dx, co(0, 1, 0, i), co(1, 1, 0, i)),
+                ),
+                lerp(
+                    dy,
+                    lerp(dx, co(0, 0, 1, i), co(1, 0, 1, i)),
+                    lerp(dx, co(0, 1, 1, i), co(1, 1, 1, i)),
+                ),
+            );
+        }
+        c
+    }
+}
+
+/// Fits sigmoid polynomial coefficients to RGB values (in double
+/// precision).
+struct SigmoidFitter {
+    /// normalized wavelengths of the **SampledSpectrum** bins
+    t: [f64; N_SPECTRAL_SAMPLES],
+    /// linear map from **SampledSpectrum** values to RGB
+    to_rgb: [[f64; N_SPECTRAL_SAMPLES]; 3],
+}
+
+impl SigmoidFitter {
+    fn new() -> Self {
+        let tables: &SampledSpectrumTables = &SAMPLED_SPECTRUM_TABLES;
+        let mut t: [f64; N_SPECTRAL_SAMPLES] = [0.0; N_SPECTRAL_SAMPLES];
+        let mut to_rgb: [[f64; N_SPECTRAL_SAMPLES]; 3] = [[0.0; N_SPECTRAL_SAMPLES]; 3];
+        let scale: f64 = SampledSpectrum::xyz_scale() as f64;
+        for i in 0..N_SPECTRAL_SAMPLES {
+            t[i] = ((SampledSpectrum::bin_center(i) - SAMPLED_LAMBDA_START)
+                / (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START)) as f64;
+            let xyz: [Float; 3] = [tables.x[i], tables.y[i], tables.z[i]];
+            let mut rgb: [Float; 3] = [0.0 as Float; 3];
+            xyz_to_rgb(&xyz, &mut rgb);
+            for k in 0..3 {
+                to_rgb[k][i] = rgb[k] as f64 * scale;
+            }
+        }
+        SigmoidFitter { t, to_rgb }
+    }
+    /// Returns the RGB residual and its Jacobian with respect to the
+    /// coefficients.
+    fn eval(&self, rgb: &[f64; 3], c: &[f64; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+        let mut residual: [f64; 3] = [-rgb[0], -rgb[1], -rgb[2]];
+        let mut jac: [[f64; 3]; 3] = [[0.0; 3]; 3];
+        for i in 0..N_SPECTRAL_SAMPLES {
+            let t: f64 = self.t[i];
+            let x: f64 = (c[0] * t + c[1]) * t + c[2];
+            let y: f64 = 1.0 / (1.0 + x * x).sqrt();
+            let s: f64 = 0.5 * x * y + 0.5;
+            let ds: f64 = 0.5 * y * y * y;
+            let dc: [f64; 3] = [ds * t * t, ds * t, ds];
+            for k in 0..3 {
+                residual[k] += self.to_rgb[k][i] * s;
+                for (j, dcj) in dc.iter().enumerate() {
+                    jac[k][j] += self.to_rgb[k][i] * dcj;
+                }
+            }
+        }
+        (residual, jac)
+    }
+    /// Damped Gauss-Newton iteration starting at **c**.
+    fn fit(&self, rgb: &[f64; 3], c: [f64; 3]) -> [f64; 3] {
+        let norm2 = |r: &[f64; 3]| -> f64 { r[0] * r[0] + r[1] * r[1] + r[2] * r[2] };
+        let mut c: [f64; 3] = c;
+        let (mut residual, mut jac) = self.eval(rgb, &c);
+        let mut err: f64 = norm2(&residual);
+        let mut damping: f64 = 1e-3;
+        for _iteration in 0..RGB_TO_SPECTRUM_ITERATIONS {
+            if err < 1e-12 {
+                break;
+            }
+            // solve (J^T J + damping * diag(J^T J)) delta = -J^T r
+            let mut a: [[f64; 3]; 3] = [[0.0; 3]; 3];
+            let mut g: [f64; 3] = [0.0; 3];
+            for p in 0..3 {
+                for q in 0..3 {
+                    for row in jac.iter() {
+                        a[p][q] += row[p] * row[q];
+                    }
+                }
+                for k in 0..3 {
+                    g[p] -= jac[k][p] * residual[k];
+                }
+            }
+            for (p, row) in a.iter_mut().enumerate() {
+                row[p] *= 1.0 + damping;
+            }
+            let delta: [f64; 3] = match invert_3x3(&a) {
+                Some(a_inv) => mul_3x3_vec(&a_inv, &g),
+                None => break,
+            };
+            let candidate: [f64; 3] = [c[0] + delta[0], c[1] + delta[1], c[2] + delta[2]];
+            let (r, j) = self.eval(rgb, &candidate);
+            let e: f64 = norm2(&r);
+            if e < err {
+                c = candidate;
+                residual = r;
+                jac = j;
+                err = e;
+                damping = (damping * 0.1).max(1e-12);
+            } else {
+                damping *= 10.0;
+                if damping > 1e12 {
+                    break;
+                }
+            }
+        }
+        c
+    }
+}
+
+/// Represents a spectral power distribution (SPD) by point samples
+/// (averages over equally sized wavelength bins) between
+/// SAMPLED_LAMBDA_START and SAMPLED_LAMBDA_END.
+#[derive(Debug, Copy, Clone)]
+pub struct SampledSpectrum {
+    pub c: [Float; N_SPECTRAL_SAMPLES],
+}
+
+impl Default for SampledSpectrum {
+    fn default() -> Self {
+        SampledSpectrum::new(0.0 as Float)
+    }
+}
+
+impl SampledSpectrum {
+    pub const N_SAMPLES: usize = N_SPECTRAL_SAMPLES;
+    /// Representative wavelength (in nm) of the i-th sample.
+    pub fn channel_wavelength(i: usize) -> Float {
+        SampledSpectrum::bin_center(i)
+    }
+    pub fn new(v: Float) -> Self {
+        SampledSpectrum {
+            c: [v; N_SPECTRAL_SAMPLES],
+        }
+    }
+    /// Wavelength range of the i-th sample.
+    pub fn bin_range(i: usize) -> (Float, Float) {
+        let width: Float =
+            (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START) / N_SPECTRAL_SAMPLES as Float;
+        let wl0: Float = SAMPLED_LAMBDA_START + i as Float * width;
+        (wl0, wl0 + width)
+    }
+    /// Central wavelength of the i-th sample.
+    pub fn bin_center(i: usize) -> Float {
+        let (wl0, wl1) = SampledSpectrum::bin_range(i);
+        0.5 as Float * (wl0 + wl1)
+    }
+    fn xyz_scale() -> Float {
+        (SAMPLED_LAMBDA_END - SAMPLED_LAMBDA_START) / (CIE_Y_INTEGRAL * N_SPECTRAL_SAMPLES as Float)
+    }
+    pub fn rgb(r: Float, g: Float, b: Float) -> SampledSpectrum {
+        SampledSpectrum::from_rgb(&[r, g, b])
+    }
+    pub fn from_srgb(rgb: [u8; 3]) -> SampledSpectrum {
+        fn as_float(v: u8) -> Float {
+            v as Float / 255.0
+        }
+        SampledSpectrum::rgb(
+            inverse_gamma_convert_float(as_float(rgb[0])),
+            inverse_gamma_convert_float(as_float(rgb[1])),
+            inverse_gamma_convert_float(as_float(rgb[2])),
+        )
+    }
+    pub fn inverse_gamma_correct(&self) -> SampledSpectrum {
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        self.to_rgb(&mut rgb);
+        SampledSpectrum::rgb(
+            inverse_gamma_convert_float(rgb[0]),
+            inverse_gamma_convert_float(rgb[1]),
+            inverse_gamma_convert_float(rgb[2]),
+        )
+    }
+    /// Converts RGB to a smooth spectrum using a sigmoid polynomial
+    /// (see **RGBSigmoidPolynomial**). Values up to one stay bounded
+    /// by one (reflectances), larger values (e.g. emission or
+    /// scattering coefficients) get upsampled at half their maximum
+    /// and scaled.
+    pub fn from_rgb(rgb: &[Float; 3]) -> SampledSpectrum {
+        let m: Float = rgb[0].max(rgb[1]).max(rgb[2]);
+        if m <= 0.0 as Float {
+            return SampledSpectrum::new(0.0 as Float);
+        }
+        let scale: Float = if m <= 1.0 as Float {
+            1.0 as Float
+        } else {
+            2.0 as Float * m
+        };
+        let rsp: RGBSigmoidPolynomial =
+            RGBSigmoidPolynomial::from_rgb(&[rgb[0] / scale, rgb[1] / scale, rgb[2] / scale]);
+        let mut r: SampledSpectrum = SampledSpectrum::new(0.0 as Float);
+        for i in 0..N_SPECTRAL_SAMPLES {
+            r.c[i] = scale * rsp.evaluate(SampledSpectrum::bin_center(i));
+        }
+        r
+    }
+    pub fn to_rgb(&self, rgb: &mut [Float; 3]) {
+        let mut xyz: [Float; 3] = [0.0 as Float; 3];
+        self.to_xyz(&mut xyz);
+        xyz_to_rgb(&xyz, rgb);
+    }
+    pub fn to_xyz(&self, xyz: &mut [Float; 3]) {
+        let t: &SampledSpectrumTables = &SAMPLED_SPECTRUM_TABLES;
+        xyz[0] = 0.0 as Float;
+        xyz[1] = 0.0 as Float;
+        xyz[2] = 0.0 as Float;
+        for i in 0..N_SPECTRAL_SAMPLES {
+            xyz[0] += t.x[i] * self.c[i];
+            xyz[1] += t.y[i] * self.c[i];
+            xyz[2] += t.z[i] * self.c[i];
+        }
+        let scale: Float = SampledSpectrum::xyz_scale();
+        xyz[0] *= scale;
+        xyz[1] *= scale;
+        xyz[2] *= scale;
+    }
+    pub fn from_xyz(xyz: &[Float; 3], _spectrum_type: SpectrumType) -> SampledSpectrum {
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        xyz_to_rgb(xyz, &mut rgb);
+        SampledSpectrum::from_rgb(&rgb)
+    }
+    pub fn y(&self) -> Float {
+        let t: &SampledSpectrumTables = &SAMPLED_SPECTRUM_TABLES;
+        let mut yy: Float = 0.0;
+        for i in 0..N_SPECTRAL_SAMPLES {
+            yy += t.y[i] * self.c[i];
+        }
+        yy * SampledSpectrum::xyz_scale()
+    }
+    pub fn from_sampled(lambda: &[Float], v: &[Float], n: i32) -> SampledSpectrum {
+        // sort samples if unordered, use sorted for returned spectrum
+        if !spectrum_samples_sorted(lambda, v, n) {
+            let mut slambda: Vec<Float> = lambda[..n as usize].to_vec();
+            let mut sv: Vec<Float> = v[..n as usize].to_vec();
+            sort_spectrum_samples(&mut slambda, &mut sv);
+            return SampledSpectrum::from_sampled(&slambda, &sv, n);
+        }
+        let mut r: SampledSpectrum = SampledSpectrum::new(0.0 as Float);
+        for i in 0..N_SPECTRAL_SAMPLES {
+            // compute average value of given SPD over i-th sample's range
+            let (wl0, wl1) = SampledSpectrum::bin_range(i);
+            r.c[i] = average_spectrum_samples(lambda, v, n, wl0, wl1);
+        }
+        r
+    }
+    // from CoefficientSpectrum
+    pub fn is_black(&self) -> bool {
+        self.c.iter().all(|v| *v == 0.0 as Float)
+    }
+    pub fn sqrt(&self) -> SampledSpectrum {
+        let mut ret: SampledSpectrum = *self;
+        for v in ret.c.iter_mut() {
+            *v = v.sqrt();
+        }
+        ret
+    }
+    pub fn exp(&self) -> SampledSpectrum {
+        let mut ret: SampledSpectrum = *self;
+        for v in ret.c.iter_mut() {
+            *v = v.exp();
+        }
+        ret
+    }
+    /// Clamp spectrum to lie between the values low and high. Use
+    /// (0.0 as Float, Float::INFINITY) if there are no
+    /// specific values.
+    pub fn clamp(&self, low: Float, high: Float) -> SampledSpectrum {
+        let mut ret: SampledSpectrum = *self;
+        for v in ret.c.iter_mut() {
+            *v = clamp_t(*v, low, high);
+        }
+        assert!(!ret.has_nans());
+        ret
+    }
+    pub fn max_component_value(&self) -> Float {
+        self.c.iter().fold(self.c[0], |m, v| m.max(*v))
+    }
+    pub fn average(&self) -> Float {
+        self.c.iter().sum::<Float>() / N_SPECTRAL_SAMPLES as Float
+    }
+    pub fn single_channel(&self) -> Option<usize> {
+        let mut non_zero = self
+            .c
+            .iter()
+            .enumerate()
+            .filter(|(_i, v)| **v != 0.0 as Float);
+        match (non_zero.next(), non_zero.next()) {
+            (Some((i, _v)), None) => Some(i),
+            _ => None,
+        }
+    }
+    pub fn has_nans(&self) -> bool {
+        self.c.iter().any(|v| v.is_nan())
+    }
+    pub fn has_infs(&self) -> bool {
+        self.c.iter().any(|v| v.is_infinite())
+    }
+}
+
+impl PartialEq for SampledSpectrum {
+    fn eq(&self, rhs: &SampledSpectrum) -> bool {
+        self.c[..] == rhs.c[..]
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(mut self, rhs: SampledSpectrum) -> SampledSpectrum {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: SampledSpectrum) {
+        for i in 0..N_SPECTRAL_SAMPLES {
+            self.c[i] += rhs.c[i];
+        }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(mut self, rhs: SampledSpectrum) -> SampledSpectrum {
+        self *= rhs;
+        self
+    }
+}
+
+impl Mul<Float> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(mut self, rhs: Float) -> SampledSpectrum {
+        for v in self.c.iter_mut() {
+            *v *= rhs;
+        }
+        self
+    }
+}
+
+impl Mul<SampledSpectrum> for Float {
+    type Output = SampledSpectrum;
+    fn mul(self, rhs: SampledSpectrum) -> SampledSpectrum {
+        rhs * self
+    }
+}
+
+impl MulAssign for SampledSpectrum {
+    fn mul_assign(&mut self, rhs: SampledSpectrum) {
+        for i in 0..N_SPECTRAL_SAMPLES {
+            self.c[i] *= rhs.c[i];
+        }
+    }
+}
+
+impl Sub for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn sub(mut self, rhs: SampledSpectrum) -> SampledSpectrum {
+        for i in 0..N_SPECTRAL_SAMPLES {
+            self.c[i] -= rhs.c[i];
+        }
+        self
+    }
+}
+
+impl Div for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn div(mut self, rhs: SampledSpectrum) -> SampledSpectrum {
+        for i in 0..N_SPECTRAL_SAMPLES {
+            self.c[i] /= rhs.c[i];
+        }
+        self
+    }
+}
+
+impl Div<Float> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn div(mut self, rhs: Float) -> SampledSpectrum {
+        self /= rhs;
+        self
+    }
+}
+
+impl DivAssign<Float> for SampledSpectrum {
+    fn div_assign(&mut self, rhs: Float) {
+        assert_ne!(rhs, 0.0 as Float);
+        assert!(!rhs.is_nan(), "rhs is NaN");
+        for v in self.c.iter_mut() {
+            *v /= rhs;
+        }
+    }
+}
+
+impl Neg for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn neg(mut self) -> SampledSpectrum {
+        for v in self.c.iter_mut() {
+            *v = -*v;
+        }
+        self
+    }
+}
+
+impl Zero for SampledSpectrum {
+    fn zero() -> SampledSpectrum {
+        SampledSpectrum::new(0.0 as Float)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.is_black()
+    }
+}
+
+impl Index<usize> for SampledSpectrum {
+    type Output = Float;
+    fn index(&self, index: usize) -> &Float {
+        &self.c[index]
+    }
+}
+
+impl IndexMut<usize> for SampledSpectrum {
+    fn index_mut(&mut self, index: usize) -> &mut Float {
+        &mut self.c[index]
+    }
+}
+
+impl From<Float> for SampledSpectrum {
+    fn from(f: Float) -> Self {
+        SampledSpectrum::new(f)
+    }
+}
+
 /// Calculate RGB coefficients from a XYZ representation.
 pub fn xyz_to_rgb(xyz: &[Float; 3], rgb: &mut [Float; 3]) {
     rgb[0] = 3.240_479 * xyz[0] - 1.537_150 * xyz[1] - 0.498_535 * xyz[2];
@@ -1825,6 +2799,201 @@ pub fn rgb_to_xyz(rgb: &[Float; 3], xyz: &mut [Float; 3]) {
     xyz[2] = 0.019_334 * rgb[0] + 0.119_193 * rgb[1] + 0.950_227 * rgb[2];
 }
 
+/// RGB color spaces (primaries and white point) images can be
+/// written in. Scene colors are linear sRGB (with a D65 white), so
+/// for other white points the colors get chromatically adapted.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum RGBColorSpace {
+    #[default]
+    SRGB,
+    ACEScg,
+    Rec2020,
+}
+
+/// CIE xy chromaticity of the D65 white point.
+const WHITE_D65: [f64; 2] = [0.3127, 0.3290];
+
+impl RGBColorSpace {
+    pub fn from_name(name: &str) -> Option<RGBColorSpace> {
+        match name.to_lowercase().as_str() {
+            "srgb" | "rec709" => Some(RGBColorSpace::SRGB),
+            "acescg" => Some(RGBColorSpace::ACEScg),
+            "rec2020" => Some(RGBColorSpace::Rec2020),
+            _ => None,
+        }
+    }
+    /// CIE xy chromaticities of the red, green, and blue primaries.
+    pub fn primaries(&self) -> [[f64; 2]; 3] {
+        match self {
+            RGBColorSpace::SRGB => [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]],
+            RGBColorSpace::ACEScg => [[0.713, 0.293], [0.165, 0.830], [0.128, 0.044]],
+            RGBColorSpace::Rec2020 => [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]],
+        }
+    }
+    /// CIE xy chromaticity of the white point.
+    pub fn white_point(&self) -> [f64; 2] {
+        match self {
+            // ACES white is close to D60
+            RGBColorSpace::ACEScg => [0.32168, 0.33767],
+            _ => WHITE_D65,
+        }
+    }
+    /// Matrix converting (D65 relative) XYZ values to RGB values of
+    /// this color space.
+    pub fn xyz_to_rgb_matrix(&self) -> [[Float; 3]; 3] {
+        if *self == RGBColorSpace::SRGB {
+            // same values as **xyz_to_rgb()**
+            return [
+                [3.240_479, -1.537_15, -0.498_535],
+                [-0.969_256, 1.875_991, 0.041_556],
+                [0.055_648, -0.204_043, 1.057_311],
+            ];
+        }
+        let p: [[f64; 2]; 3] = self.primaries();
+        let w: [f64; 2] = self.white_point();
+        // columns are the XYZ values of the primaries, scaled so that
+        // RGB (1, 1, 1) maps to the white point
+        let mut m: [[f64; 3]; 3] = [[0.0; 3]; 3];
+        for (j, xy) in p.iter().enumerate() {
+            let xyz: [f64; 3] = xy_to_xyz(xy);
+            for i in 0..3 {
+                m[i][j] = xyz[i];
+            }
+        }
+        let scale: [f64; 3] = mul_3x3_vec(&invert_3x3(&m).unwrap(), &xy_to_xyz(&w));
+        for row in m.iter_mut() {
+            for j in 0..3 {
+                row[j] *= scale[j];
+            }
+        }
+        let to_rgb: [[f64; 3]; 3] = mul_3x3(
+            &invert_3x3(&m).unwrap(),
+            &bradford_adaptation(&WHITE_D65, &w),
+        );
+        let mut result: [[Float; 3]; 3] = [[0.0 as Float; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                result[i][j] = to_rgb[i][j] as Float;
+            }
+        }
+        result
+    }
+}
+
+/// Multiplies a color (e.g. XYZ) by a 3x3 matrix.
+pub fn color_matrix_mul(m: &[[Float; 3]; 3], v: &[Float; 3], result: &mut [Float; 3]) {
+    for i in 0..3 {
+        result[i] = m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2];
+    }
+}
+
+/// White balance: chromatically adapts the XYZ values of surfaces lit
+/// by **illuminant** so that a white surface maps to the (D65) white of
+/// the scene, before converting them with **xyz_to_rgb**.
+pub fn white_balance(xyz_to_rgb: &[[Float; 3]; 3], illuminant: &Spectrum) -> [[Float; 3]; 3] {
+    let mut xyz: [Float; 3] = [0.0 as Float; 3];
+    illuminant.to_xyz(&mut xyz);
+    let sum: f64 = (xyz[0] + xyz[1] + xyz[2]) as f64;
+    if sum <= 0.0 {
+        return *xyz_to_rgb;
+    }
+    let src: [f64; 2] = [xyz[0] as f64 / sum, xyz[1] as f64 / sum];
+    let adaptation: [[f64; 3]; 3] = bradford_adaptation(&src, &WHITE_D65);
+    let mut m: [[f64; 3]; 3] = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            m[i][j] = xyz_to_rgb[i][j] as f64;
+        }
+    }
+    let balanced: [[f64; 3]; 3] = mul_3x3(&m, &adaptation);
+    let mut result: [[Float; 3]; 3] = [[0.0 as Float; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            result[i][j] = balanced[i][j] as Float;
+        }
+    }
+    result
+}
+
+/// Spectrum of a light with color temperature **t** (in Kelvin): a
+/// blackbody below 4000K (e.g. tungsten), CIE daylight above.
+pub fn color_temperature_spectrum(t: Float) -> Spectrum {
+    if t < 4000.0 as Float {
+        blackbody_spectrum(t, 1.0 as Float)
+    } else {
+        let mut v: Vec<Float> = Vec::with_capacity(N_CIE_SAMPLES as usize);
+        cie_d_illuminant(&CIE_LAMBDA, N_CIE_SAMPLES as usize, t, &mut v);
+        Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32)
+    }
+}
+
+/// XYZ values (with Y = 1) of a CIE xy chromaticity.
+fn xy_to_xyz(xy: &[f64; 2]) -> [f64; 3] {
+    [xy[0] / xy[1], 1.0, (1.0 - xy[0] - xy[1]) / xy[1]]
+}
+
+/// Bradford chromatic adaptation from the white **src** to **dst**
+/// (as CIE xy chromaticities).
+fn bradford_adaptation(src: &[f64; 2], dst: &[f64; 2]) -> [[f64; 3]; 3] {
+    let bradford: [[f64; 3]; 3] = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let src_lms: [f64; 3] = mul_3x3_vec(&bradford, &xy_to_xyz(src));
+    let dst_lms: [f64; 3] = mul_3x3_vec(&bradford, &xy_to_xyz(dst));
+    let mut scale: [[f64; 3]; 3] = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = dst_lms[i] / src_lms[i];
+    }
+    mul_3x3(&invert_3x3(&bradford).unwrap(), &mul_3x3(&scale, &bradford))
+}
+
+fn invert_3x3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let det: f64 = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let inv_det: f64 = 1.0 / det;
+    Some([
+        [
+            (m[1][1] * m[2][2] - m[1][2] * m[2][1]) * inv_det,
+            (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det,
+            (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det,
+        ],
+        [
+            (m[1][2] * m[2][0] - m[1][0] * m[2][2]) * inv_det,
+            (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det,
+            (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det,
+        ],
+        [
+            (m[1][0] * m[2][1] - m[1][1] * m[2][0]) * inv_det,
+            (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det,
+            (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det,
+        ],
+    ])
+}
+
+fn mul_3x3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut m: [[f64; 3]; 3] = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            m[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j];
+        }
+    }
+    m
+}
+
+fn mul_3x3_vec(a: &[[f64; 3]; 3], v: &[f64; 3]) -> [f64; 3] {
+    [
+        a[0][0] * v[0] + a[0][1] * v[1] + a[0][2] * v[2],
+        a[1][0] * v[0] + a[1][1] * v[1] + a[1][2] * v[2],
+        a[2][0] * v[0] + a[2][1] * v[1] + a[2][2] * v[2],
+    ]
+}
+
 // see spectrum.cpp
 
 /// Are the values sorted by wavelength?
@@ -1837,6 +3006,21 @@ pub fn spectrum_samples_sorted(lambda: &[Float], _vals: &[Float], n: i32) -> boo
     true
 }
 
+/// Sorts the samples by wavelength (keeping the values with their
+/// wavelengths).
+pub fn sort_spectrum_samples(lambda: &mut [Float], vals: &mut [Float]) {
+    let mut sort_vec: Vec<(Float, Float)> = lambda
+        .iter()
+        .zip(vals.iter())
+        .map(|(l, v)| (*l, *v))
+        .collect();
+    sort_vec.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    for (i, (l, v)) in sort_vec.into_iter().enumerate() {
+        lambda[i] = l;
+        vals[i] = v;
+    }
+}
+
 /// Find responsible interval and linearly interpolate between the two
 /// sample values.
 pub fn interpolate_spectrum_samples(lambda: &[Float], vals: &[Float], n: i32, l: Float) -> Float {
@@ -1855,6 +3039,58 @@ pub fn interpolate_spectrum_samples(lambda: &[Float], vals: &[Float], n: i32, l:
     lerp(t, vals[offset], vals[offset + 1])
 }
 
+/// Average of the piecewise linear function given by the samples
+/// over the wavelength range [lambda_start, lambda_end].
+pub fn average_spectrum_samples(
+    lambda: &[Float],
+    vals: &[Float],
+    n: i32,
+    lambda_start: Float,
+    lambda_end: Float,
+) -> Float {
+    let n: usize = n as usize;
+    // handle cases with out-of-bounds range or single sample only
+    if lambda_end <= lambda[0] {
+        return vals[0];
+    }
+    if lambda_start >= lambda[n - 1] {
+        return vals[n - 1];
+    }
+    if n == 1 {
+        return vals[0];
+    }
+    let mut sum: Float = 0.0;
+    // add contributions of constant segments before/after samples
+    if lambda_start < lambda[0] {
+        sum += vals[0] * (lambda[0] - lambda_start);
+    }
+    if lambda_end > lambda[n - 1] {
+        sum += vals[n - 1] * (lambda_end - lambda[n - 1]);
+    }
+    // advance to first relevant wavelength segment
+    let mut i: usize = 0;
+    while lambda_start > lambda[i + 1] {
+        i += 1;
+    }
+    // loop over wavelength sample segments and add contributions
+    let interp = |w: Float, i: usize| -> Float {
+        lerp(
+            (w - lambda[i]) / (lambda[i + 1] - lambda[i]),
+            vals[i],
+            vals[i + 1],
+        )
+    };
+    while i + 1 < n && lambda_end >= lambda[i] {
+        let seg_lambda_start: Float = lambda_start.max(lambda[i]);
+        let seg_lambda_end: Float = lambda_end.min(lambda[i + 1]);
+        sum += 0.5 as Float
+            * (interp(seg_lambda_start, i) + interp(seg_lambda_end, i))
+            * (seg_lambda_end - seg_lambda_start);
+        i += 1;
+    }
+    sum / (lambda_end - lambda_start)
+}
+
 pub fn inverse_gamma_convert_float(v: Float) -> Float {
     if v <= 0.04045 {
         v / 12.92
@@ -1870,3 +3106,118 @@ pub fn gamma_correct(v: Float) -> Float {
         1.055 * Float::powf(v, 1.0 / 2.4) - 0.055
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGB -> sigmoid spectrum -> XYZ -> RGB.
+    fn round_trip(rgb: [Float; 3]) -> [Float; 3] {
+        let s: SampledSpectrum = SampledSpectrum::from_rgb(&rgb);
+        let mut xyz: [Float; 3] = [0.0 as Float; 3];
+        s.to_xyz(&mut xyz);
+        let mut result: [Float; 3] = [0.0 as Float; 3];
+        xyz_to_rgb(&xyz, &mut result);
+        result
+    }
+
+    fn assert_round_trip(rgb: [Float; 3], tolerance: Float) {
+        let result: [Float; 3] = round_trip(rgb);
+        for i in 0..3 {
+            assert!(
+                (result[i] - rgb[i]).abs() <= tolerance,
+                "{:?} came back as {:?}",
+                rgb,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn sigmoid_spectrum_round_trip_greys() {
+        for v in &[0.0, 0.001, 0.01, 0.18, 0.5, 0.8, 0.99, 1.0] {
+            assert_round_trip([*v as Float; 3], 1e-3 as Float);
+        }
+    }
+
+    #[test]
+    fn sigmoid_spectrum_round_trip_gamut_corners() {
+        for r in &[0.0, 1.0] {
+            for g in &[0.0, 1.0] {
+                for b in &[0.0, 1.0] {
+                    assert_round_trip([*r as Float, *g as Float, *b as Float], 1e-3 as Float);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sigmoid_spectrum_round_trip_colors() {
+        for rgb in &[
+            [0.8, 0.2, 0.1],
+            [0.1, 0.5, 0.2],
+            [0.2, 0.3, 0.7],
+            [0.5, 0.5, 0.1],
+            [1.0, 0.5, 0.0],
+        ] {
+            assert_round_trip(
+                [rgb[0] as Float, rgb[1] as Float, rgb[2] as Float],
+                5e-3 as Float,
+            );
+        }
+    }
+
+    #[test]
+    fn fluorescent_illuminants_have_their_chromaticities() {
+        // CIE 1931 chromaticity coordinates of F1 to F12
+        let expected: [[Float; 2]; 12] = [
+            [0.3131, 0.3373],
+            [0.3721, 0.3753],
+            [0.4091, 0.3943],
+            [0.4402, 0.4033],
+            [0.3138, 0.3453],
+            [0.3779, 0.3884],
+            [0.3129, 0.3293],
+            [0.3459, 0.3588],
+            [0.3742, 0.3728],
+            [0.3461, 0.3599],
+            [0.3805, 0.3771],
+            [0.4370, 0.4044],
+        ];
+        for (table, xy) in CIE_F.iter().zip(expected.iter()) {
+            let mut xyz: [Float; 3] = [0.0 as Float; 3];
+            for (i, v) in table.iter().enumerate() {
+                // the CIE tables start at 360nm in 1nm steps
+                let j: usize = 20 + 5 * i;
+                xyz[0] += v * CIE_X[j];
+                xyz[1] += v * CIE_Y[j];
+                xyz[2] += v * CIE_Z[j];
+            }
+            let sum: Float = xyz[0] + xyz[1] + xyz[2];
+            assert!((xyz[0] / sum - xy[0]).abs() < 2e-3 as Float, "{:?}", xy);
+            assert!((xyz[1] / sum - xy[1]).abs() < 2e-3 as Float, "{:?}", xy);
+        }
+    }
+
+    #[test]
+    fn fluorescent_illuminants_by_name() {
+        for i in 1..=12 {
+            let s: Option<Spectrum> = std_illuminant(&format!("stdillum-F{}", i));
+            assert!((s.unwrap().y() - 1.0 as Float).abs() < 1e-3 as Float);
+        }
+        assert!(std_illuminant("stdillum-F0").is_none());
+        assert!(std_illuminant("stdillum-F13").is_none());
+    }
+
+    #[test]
+    fn sampled_spectrum_to_rgb_matches_xyz_path() {
+        // larger values are scaled, not clamped
+        let rgb: [Float; 3] = [4.0, 2.0, 1.0];
+        let mut result: [Float; 3] = [0.0 as Float; 3];
+        SampledSpectrum::from_rgb(&rgb).to_rgb(&mut result);
+        assert_eq!(result, round_trip(rgb));
+        for i in 0..3 {
+            assert!((result[i] - rgb[i]).abs() <= 2e-2 as Float * rgb[i]);
+        }
+    }
+}
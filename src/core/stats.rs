@@ -0,0 +1,396 @@
+//! Statistics about the work done while rendering, like pbrt's
+//! **STAT_COUNTER()** and friends: counters, memory counters,
+//! percentages, ratios, and distributions of integer values.
+//!
+//! A statistic is a *static* with a title like *"Intersections/Regular
+//! ray intersection tests"* (the category before the slash groups
+//! the statistics of the report):
+//!
+//! ```rust
+//! use pbrt::core::stats::{stats_report, StatCounter};
+//!
+//! static N_LOOKUPS: StatCounter = StatCounter::new("Example/Lookups");
+//!
+//! N_LOOKUPS.inc();
+//! assert!(stats_report().contains("Lookups"));
+//! ```
+//!
+//! Each thread counts on its own (without synchronization between
+//! the threads), **stats_report()** adds up the values of all threads.
+
+// std
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// the number of values each thread can count (see **ThreadStats**)
+const MAX_SLOTS: usize = 1024;
+/// the first slot of a statistic which wasn't used yet
+const UNASSIGNED: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatKind {
+    Counter,
+    Memory,
+    Percent,
+    Ratio,
+    Distribution,
+}
+
+impl StatKind {
+    /// The values counted per thread: a counter, numerator and
+    /// denominator, or the sum, count, minimum, and maximum of a
+    /// distribution.
+    fn slots(self) -> usize {
+        match self {
+            StatKind::Counter | StatKind::Memory => 1,
+            StatKind::Percent | StatKind::Ratio => 2,
+            StatKind::Distribution => 4,
+        }
+    }
+}
+
+/// What all kinds of statistics have in common, the place of its
+/// values in the **ThreadStats** gets assigned when it is used for
+/// the first time.
+struct Stat {
+    title: &'static str,
+    kind: StatKind,
+    first_slot: AtomicUsize,
+}
+
+impl Stat {
+    const fn new(title: &'static str, kind: StatKind) -> Self {
+        Stat {
+            title,
+            kind,
+            first_slot: AtomicUsize::new(UNASSIGNED),
+        }
+    }
+    fn first_slot(&'static self) -> usize {
+        let first_slot: usize = self.first_slot.load(Ordering::Acquire);
+        if first_slot != UNASSIGNED {
+            first_slot
+        } else {
+            register(self)
+        }
+    }
+    /// Calls *f* with the values of the calling thread (if it isn't
+    /// ending).
+    fn with_values<F>(&'static self, f: F)
+    where
+        F: FnOnce(&[AtomicU64]),
+    {
+        let first_slot: usize = self.first_slot();
+        let _ = THREAD_STATS
+            .try_with(|stats| f(&stats.0.values[first_slot..first_slot + self.kind.slots()]));
+    }
+    fn slots(&self) -> std::ops::Range<usize> {
+        let first_slot: usize = self.first_slot.load(Ordering::Acquire);
+        first_slot..first_slot + self.kind.slots()
+    }
+}
+
+/// Only the owning thread changes its values, so a load and a store
+/// is enough (no atomic read-modify-write needed).
+fn add(value: &AtomicU64, n: u64) {
+    value.store(value.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+}
+
+/// Counts events, e.g. intersection tests.
+pub struct StatCounter(Stat);
+
+impl StatCounter {
+    pub const fn new(title: &'static str) -> Self {
+        StatCounter(Stat::new(title, StatKind::Counter))
+    }
+    pub fn inc(&'static self) {
+        self.add(1);
+    }
+    pub fn add(&'static self, n: u64) {
+        self.0.with_values(|values| add(&values[0], n));
+    }
+}
+
+/// Counts allocated bytes, e.g. of acceleration structures.
+pub struct StatMemoryCounter(Stat);
+
+impl StatMemoryCounter {
+    pub const fn new(title: &'static str) -> Self {
+        StatMemoryCounter(Stat::new(title, StatKind::Memory))
+    }
+    pub fn add(&'static self, bytes: usize) {
+        self.0.with_values(|values| add(&values[0], bytes as u64));
+    }
+}
+
+/// How often something happened (numerator) in percent of how often
+/// it could have happened (denominator), e.g. hits of intersection
+/// tests.
+pub struct StatPercent(Stat);
+
+impl StatPercent {
+    pub const fn new(title: &'static str) -> Self {
+        StatPercent(Stat::new(title, StatKind::Percent))
+    }
+    pub fn inc_num(&'static self) {
+        self.0.with_values(|values| add(&values[0], 1));
+    }
+    pub fn inc_denom(&'static self) {
+        self.0.with_values(|values| add(&values[1], 1));
+    }
+}
+
+/// The ratio of two counts, e.g. primitives per leaf node.
+pub struct StatRatio(Stat);
+
+impl StatRatio {
+    pub const fn new(title: &'static str) -> Self {
+        StatRatio(Stat::new(title, StatKind::Ratio))
+    }
+    pub fn add_num(&'static self, n: u64) {
+        self.0.with_values(|values| add(&values[0], n));
+    }
+    pub fn add_denom(&'static self, n: u64) {
+        self.0.with_values(|values| add(&values[1], n));
+    }
+}
+
+/// Average, minimum, and maximum of reported values, e.g. path
+/// lengths.
+pub struct StatIntDistribution(Stat);
+
+impl StatIntDistribution {
+    pub const fn new(title: &'static str) -> Self {
+        StatIntDistribution(Stat::new(title, StatKind::Distribution))
+    }
+    pub fn report_value(&'static self, value: u64) {
+        self.0.with_values(|values| {
+            let count: u64 = values[1].load(Ordering::Relaxed);
+            add(&values[0], value);
+            add(&values[1], 1);
+            if count == 0 || value < values[2].load(Ordering::Relaxed) {
+                values[2].store(value, Ordering::Relaxed);
+            }
+            if count == 0 || value > values[3].load(Ordering::Relaxed) {
+                values[3].store(value, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// The values counted by one thread.
+struct ThreadStats {
+    values: Vec<AtomicU64>,
+}
+
+impl ThreadStats {
+    fn new() -> Self {
+        ThreadStats {
+            values: (0..MAX_SLOTS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+/// Registers the values of a thread, and merges them into the ones of
+/// finished threads when the thread ends.
+struct ThreadStatsHandle(Arc<ThreadStats>);
+
+impl ThreadStatsHandle {
+    fn new() -> Self {
+        let stats: Arc<ThreadStats> = Arc::new(ThreadStats::new());
+        REGISTRY.lock().unwrap().threads.push(stats.clone());
+        ThreadStatsHandle(stats)
+    }
+}
+
+impl Drop for ThreadStatsHandle {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = REGISTRY.lock() {
+            let registry: &mut Registry = &mut registry;
+            registry
+                .threads
+                .retain(|stats| !Arc::ptr_eq(stats, &self.0));
+            for stat in &registry.stats {
+                let slots = stat.slots();
+                let values: Vec<u64> = merge(
+                    stat.kind,
+                    &registry.finished[slots.clone()],
+                    load(&self.0.values[slots.clone()]),
+                );
+                registry.finished[slots].copy_from_slice(&values);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_STATS: ThreadStatsHandle = ThreadStatsHandle::new();
+}
+
+/// All statistics used so far, the values of all running threads,
+/// and the merged values of the finished ones.
+struct Registry {
+    stats: Vec<&'static Stat>,
+    next_slot: usize,
+    threads: Vec<Arc<ThreadStats>>,
+    finished: Vec<u64>,
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+        stats: Vec::new(),
+        next_slot: 0,
+        threads: Vec::new(),
+        finished: vec![0; MAX_SLOTS],
+    });
+}
+
+/// Assigns the slots of a statistic used for the first time.
+fn register(stat: &'static Stat) -> usize {
+    let mut registry = REGISTRY.lock().unwrap();
+    // another thread might have been first
+    let first_slot: usize = stat.first_slot.load(Ordering::Acquire);
+    if first_slot != UNASSIGNED {
+        return first_slot;
+    }
+    let first_slot: usize = registry.next_slot;
+    assert!(
+        first_slot + stat.kind.slots() <= MAX_SLOTS,
+        "too many statistics (see stats::MAX_SLOTS)"
+    );
+    registry.next_slot += stat.kind.slots();
+    registry.stats.push(stat);
+    stat.first_slot.store(first_slot, Ordering::Release);
+    first_slot
+}
+
+fn load(values: &[AtomicU64]) -> Vec<u64> {
+    values.iter().map(|v| v.load(Ordering::Relaxed)).collect()
+}
+
+/// Merges the *values* of a thread (or of another statistic with the
+/// same title) into the *merged* ones.
+fn merge(kind: StatKind, merged: &[u64], values: Vec<u64>) -> Vec<u64> {
+    match kind {
+        StatKind::Distribution => {
+            if values[1] == 0 {
+                merged.to_vec()
+            } else if merged[1] == 0 {
+                values
+            } else {
+                vec![
+                    merged[0] + values[0],
+                    merged[1] + values[1],
+                    std::cmp::min(merged[2], values[2]),
+                    std::cmp::max(merged[3], values[3]),
+                ]
+            }
+        }
+        _ => merged.iter().zip(values).map(|(m, v)| m + v).collect(),
+    }
+}
+
+/// A report of all statistics (which were used), grouped by
+/// category, like the one pbrt prints after rendering:
+///
+/// ```text
+/// Statistics:
+///   Intersections
+///     Regular ray intersection tests                                   1234567
+/// ```
+///
+/// Empty if nothing was counted.
+pub fn stats_report() -> String {
+    let registry = REGISTRY.lock().unwrap();
+    // statistics of several modules can have the same title
+    let mut totals: BTreeMap<&str, (StatKind, Vec<u64>)> = BTreeMap::new();
+    for stat in &registry.stats {
+        let slots = stat.slots();
+        let mut values: Vec<u64> = registry.finished[slots.clone()].to_vec();
+        for thread in &registry.threads {
+            values = merge(stat.kind, &values, load(&thread.values[slots.clone()]));
+        }
+        if let Some(total) = totals.get_mut(stat.title) {
+            if total.0 == stat.kind {
+                total.1 = merge(stat.kind, &total.1, values);
+            }
+        } else {
+            totals.insert(stat.title, (stat.kind, values));
+        }
+    }
+    let mut report: String = String::new();
+    let mut last_category: Option<&str> = None;
+    for (title, (kind, values)) in totals {
+        let (category, title) = match title.find('/') {
+            Some(i) => (&title[..i], &title[i + 1..]),
+            None => ("", title),
+        };
+        let line: String = match kind {
+            StatKind::Counter if values[0] > 0 => {
+                format!("{:<42}               {:12}", title, values[0])
+            }
+            StatKind::Memory if values[0] > 0 => {
+                let kib: f64 = values[0] as f64 / 1024.0;
+                if kib < 1024.0 {
+                    format!("{:<42}                  {:9.2} kB", title, kib)
+                } else if kib < 1024.0 * 1024.0 {
+                    format!("{:<42}                  {:9.2} MiB", title, kib / 1024.0)
+                } else {
+                    format!(
+                        "{:<42}                  {:9.2} GiB",
+                        title,
+                        kib / (1024.0 * 1024.0)
+                    )
+                }
+            }
+            StatKind::Percent if values[1] > 0 => format!(
+                "{:<42}{:12} / {:12} ({:.2}%)",
+                title,
+                values[0],
+                values[1],
+                100.0 * values[0] as f64 / values[1] as f64
+            ),
+            StatKind::Ratio if values[1] > 0 => format!(
+                "{:<42}{:12} / {:12} ({:.2}x)",
+                title,
+                values[0],
+                values[1],
+                values[0] as f64 / values[1] as f64
+            ),
+            StatKind::Distribution if values[1] > 0 => format!(
+                "{:<42}                      {:.3} avg [range {} - {}]",
+                title,
+                values[0] as f64 / values[1] as f64,
+                values[2],
+                values[3]
+            ),
+            _ => continue,
+        };
+        // writing into a string can't fail
+        if report.is_empty() {
+            writeln!(report, "Statistics:").unwrap();
+        }
+        if last_category != Some(category) {
+            writeln!(report, "  {}", category).unwrap();
+            last_category = Some(category);
+        }
+        writeln!(report, "    {}", line).unwrap();
+    }
+    report
+}
+
+/// Sets all statistics back to zero, e.g. before rendering the next
+/// frame. Call it only if no other thread is counting.
+pub fn clear_stats() {
+    let mut registry = REGISTRY.lock().unwrap();
+    for value in registry.finished.iter_mut() {
+        *value = 0;
+    }
+    for thread in &registry.threads {
+        for value in &thread.values {
+            value.store(0, Ordering::Relaxed);
+        }
+    }
+}
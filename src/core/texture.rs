@@ -5,15 +5,29 @@
 //! textures.
 
 // std
-use std::f32::consts::PI;
+use std::ops::{Add, AddAssign, Div, Mul};
+// others
+use num;
 // pbrt
 use crate::core::geometry::{spherical_phi, spherical_theta, vec3_dot_vec3};
 use crate::core::geometry::{Point2f, Point3f, Vector2f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
-use crate::core::pbrt::Float;
+use crate::core::mipmap::Clampable;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{clamp_t, lerp, log_2};
+use crate::core::pbrt::{Float, Spectrum};
 use crate::core::pbrt::{INV_2_PI, INV_PI};
 use crate::core::transform::Transform;
+use crate::textures::checkerboard::Checkerboard2DTexture;
+use crate::textures::constant::ConstantTexture;
+use crate::textures::dots::DotsTexture;
+use crate::textures::fbm::FBmTexture;
+use crate::textures::imagemap::ImageTexture;
+use crate::textures::marble::MarbleTexture;
+use crate::textures::mix::MixTexture;
+use crate::textures::scale::ScaleTexture;
+use crate::textures::windy::WindyTexture;
+use crate::textures::wrinkled::WrinkledTexture;
 
 // see texture.h
 
@@ -283,8 +297,77 @@ impl IdentityMapping3D {
     }
 }
 
-pub trait Texture<T> {
-    fn evaluate(&self, si: &SurfaceInteraction) -> T;
+/// The types a **Texture** can be evaluated to, **Float** and
+/// **Spectrum**.
+pub trait TextureValue:
+    Copy
+    + Default
+    + num::Zero
+    + Add<Output = Self>
+    + AddAssign
+    + Clampable
+    + Div<Float, Output = Self>
+    + Mul<Output = Self>
+    + Mul<Float, Output = Self>
+    + From<Float>
+{
+    /// Converts the result of a spectrum-only texture (e.g.
+    /// **MarbleTexture**), **Float** textures use the luminance.
+    fn from_spectrum(s: &Spectrum) -> Self;
+    /// See *convertOut()* in imagemap.h.
+    fn convert_out(&self) -> Self;
+}
+
+impl TextureValue for Float {
+    fn from_spectrum(s: &Spectrum) -> Float {
+        s.y()
+    }
+    fn convert_out(&self) -> Float {
+        *self
+    }
+}
+
+impl TextureValue for Spectrum {
+    fn from_spectrum(s: &Spectrum) -> Spectrum {
+        *s
+    }
+    fn convert_out(&self) -> Spectrum {
+        let mut rgb: [Float; 3] = [0.0 as Float; 3];
+        self.to_rgb(&mut rgb);
+        Spectrum::from_rgb(&rgb)
+    }
+}
+
+/// All built-in textures, dispatched statically (instead of
+/// `Arc<dyn Texture<T>>`) like **Bxdf** in reflection.rs.
+pub enum Texture<T> {
+    Constant(ConstantTexture<T>),
+    Checkerboard(Checkerboard2DTexture<T>),
+    Dots(DotsTexture<T>),
+    FBm(FBmTexture),
+    Image(ImageTexture<T>),
+    Marble(MarbleTexture),
+    Mix(MixTexture<T>),
+    Scale(ScaleTexture<T>),
+    Windy(WindyTexture),
+    Wrinkled(WrinkledTexture),
+}
+
+impl<T: TextureValue> Texture<T> {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        match self {
+            Texture::Constant(texture) => texture.evaluate(si),
+            Texture::Checkerboard(texture) => texture.evaluate(si),
+            Texture::Dots(texture) => texture.evaluate(si),
+            Texture::FBm(texture) => texture.evaluate(si),
+            Texture::Image(texture) => texture.evaluate(si),
+            Texture::Marble(texture) => T::from_spectrum(&texture.evaluate(si)),
+            Texture::Mix(texture) => texture.evaluate(si),
+            Texture::Scale(texture) => texture.evaluate(si),
+            Texture::Windy(texture) => texture.evaluate(si),
+            Texture::Wrinkled(texture) => texture.evaluate(si),
+        }
+    }
 }
 
 pub fn smooth_step(min: Float, max: Float, value: Float) -> Float {
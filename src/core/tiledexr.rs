@@ -0,0 +1,284 @@
+//! Writes tiled (uncompressed, 32-bit float RGB or RGBA) OpenEXR files.
+//!
+//! The tiles can be written in any order (line order "random y"),
+//! each one as soon as it is complete, so the whole image never has
+//! to be kept in memory (see **Film** with *diskbacked*). The offset
+//! table, which tells readers where each tile starts, gets written by
+//! **TiledExrWriter::finish()**.
+
+// std
+use std::fs::File;
+use std::io::{BufWriter, Result, Seek, SeekFrom, Write};
+// others
+use byteorder::{LittleEndian, WriteBytesExt};
+// pbrt
+use crate::core::geometry::{Bounds2i, Point2i};
+use crate::core::pbrt::Float;
+
+const MAGIC: u32 = 20_000_630;
+/// file format version 2, single part, tiled
+const VERSION: u32 = 2 | 0x200;
+/// pixel type FLOAT (see ImfPixelType.h)
+const PIXEL_TYPE_FLOAT: i32 = 2;
+/// line order RANDOM_Y, i.e. tiles are stored in any order
+const LINE_ORDER_RANDOM_Y: u8 = 2;
+
+pub struct TiledExrWriter {
+    file: BufWriter<File>,
+    /// the pixels stored in the file (e.g. the crop window)
+    pub data_window: Bounds2i,
+    pub tile_size: i32,
+    pub tiles_x: i32,
+    pub tiles_y: i32,
+    /// with an alpha channel (RGBA instead of RGB)
+    pub alpha: bool,
+    /// file position of each tile (0 for tiles not written yet)
+    offsets: Vec<u64>,
+    offset_table: u64,
+    position: u64,
+}
+
+impl TiledExrWriter {
+    /// Creates the file and writes the header for an image of
+    /// *resolution* pixels, of which only the *data_window* gets
+    /// stored (in tiles of *tile_size* x *tile_size* pixels), with an
+    /// *alpha* channel or without. The *metadata* becomes string
+    /// attributes of the header.
+    pub fn create(
+        filename: &str,
+        resolution: Point2i,
+        data_window: Bounds2i,
+        tile_size: i32,
+        alpha: bool,
+        metadata: &[(String, String)],
+    ) -> Result<Self> {
+        let (tiles_x, tiles_y) = tile_count(&data_window, tile_size);
+        let mut header: Vec<u8> = Vec::new();
+        header.write_u32::<LittleEndian>(MAGIC)?;
+        header.write_u32::<LittleEndian>(VERSION)?;
+        // channels (sorted by name)
+        let mut channels: Vec<u8> = Vec::new();
+        let names: &[&str] = if alpha {
+            &["A", "B", "G", "R"]
+        } else {
+            &["B", "G", "R"]
+        };
+        for name in names {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.write_i32::<LittleEndian>(PIXEL_TYPE_FLOAT)?;
+            // pLinear and reserved
+            channels.extend_from_slice(&[0_u8; 4]);
+            // x and y sampling
+            channels.write_i32::<LittleEndian>(1)?;
+            channels.write_i32::<LittleEndian>(1)?;
+        }
+        channels.push(0);
+        write_attribute(&mut header, "channels", "chlist", &channels)?;
+        // no compression
+        write_attribute(&mut header, "compression", "compression", &[0_u8])?;
+        let data: Bounds2i = Bounds2i {
+            p_min: data_window.p_min,
+            p_max: Point2i {
+                x: data_window.p_max.x - 1,
+                y: data_window.p_max.y - 1,
+            },
+        };
+        write_attribute(&mut header, "dataWindow", "box2i", &box2i(&data)?)?;
+        let display: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i {
+                x: resolution.x - 1,
+                y: resolution.y - 1,
+            },
+        };
+        write_attribute(&mut header, "displayWindow", "box2i", &box2i(&display)?)?;
+        write_attribute(
+            &mut header,
+            "lineOrder",
+            "lineOrder",
+            &[LINE_ORDER_RANDOM_Y],
+        )?;
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1.0_f32.to_le_bytes(),
+        )?;
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0_u8; 8])?;
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1.0_f32.to_le_bytes(),
+        )?;
+        // tile size and level mode ONE_LEVEL
+        let mut tiles: Vec<u8> = Vec::new();
+        tiles.write_u32::<LittleEndian>(tile_size as u32)?;
+        tiles.write_u32::<LittleEndian>(tile_size as u32)?;
+        tiles.push(0);
+        write_attribute(&mut header, "tiles", "tiledesc", &tiles)?;
+        for (name, value) in metadata {
+            write_attribute(&mut header, name, "string", value.as_bytes())?;
+        }
+        header.push(0);
+        let n_tiles: usize = (tiles_x * tiles_y).max(0) as usize;
+        let offset_table: u64 = header.len() as u64;
+        let mut file: BufWriter<File> = BufWriter::new(File::create(filename)?);
+        file.write_all(&header)?;
+        // placeholder for the offset table
+        file.write_all(&vec![0_u8; 8 * n_tiles])?;
+        Ok(TiledExrWriter {
+            file,
+            data_window,
+            tile_size,
+            tiles_x,
+            tiles_y,
+            alpha,
+            offsets: vec![0_u64; n_tiles],
+            offset_table,
+            position: offset_table + 8 * n_tiles as u64,
+        })
+    }
+    /// See **tile_bounds()**.
+    pub fn tile_bounds(&self, index: usize) -> Bounds2i {
+        tile_bounds(&self.data_window, self.tile_size, index)
+    }
+    /// Writes the (linear) RGB values of tile *index*, one pixel after
+    /// the other, row by row. With an alpha channel each pixel has a
+    /// (premultiplied) RGBA value.
+    pub fn write_tile(&mut self, index: usize, pixels: &[Float]) -> Result<()> {
+        let bounds: Bounds2i = self.tile_bounds(index);
+        let width: usize = (bounds.p_max.x - bounds.p_min.x) as usize;
+        let height: usize = (bounds.p_max.y - bounds.p_min.y) as usize;
+        let n_channels: usize = self.n_channels();
+        // channels sorted by name
+        let order: &[usize] = if self.alpha {
+            &[3, 2, 1, 0]
+        } else {
+            &[2, 1, 0]
+        };
+        let size: usize = 4 * n_channels * width * height;
+        let mut chunk: Vec<u8> = Vec::with_capacity(20 + size);
+        chunk.write_i32::<LittleEndian>(index as i32 % self.tiles_x)?;
+        chunk.write_i32::<LittleEndian>(index as i32 / self.tiles_x)?;
+        // level
+        chunk.write_i32::<LittleEndian>(0)?;
+        chunk.write_i32::<LittleEndian>(0)?;
+        chunk.write_i32::<LittleEndian>(size as i32)?;
+        for y in 0..height {
+            // each scan line stores the channels one after the other
+            for c in order {
+                for x in 0..width {
+                    chunk.write_f32::<LittleEndian>(
+                        pixels[n_channels * (y * width + x) + c] as f32,
+                    )?;
+                }
+            }
+        }
+        self.file.write_all(&chunk)?;
+        self.offsets[index] = self.position;
+        self.position += chunk.len() as u64;
+        Ok(())
+    }
+    /// 4 with an alpha channel, 3 otherwise
+    pub fn n_channels(&self) -> usize {
+        if self.alpha {
+            4
+        } else {
+            3
+        }
+    }
+    pub fn is_written(&self, index: usize) -> bool {
+        self.offsets[index] != 0
+    }
+    /// Writes the tiles which are still missing (black) and the offset
+    /// table.
+    pub fn finish(mut self) -> Result<()> {
+        for index in 0..self.offsets.len() {
+            if !self.is_written(index) {
+                let bounds: Bounds2i = self.tile_bounds(index);
+                let black: Vec<Float> =
+                    vec![0.0 as Float; self.n_channels() * bounds.area() as usize];
+                self.write_tile(index, &black)?;
+            }
+        }
+        self.file.seek(SeekFrom::Start(self.offset_table))?;
+        for offset in &self.offsets {
+            self.file.write_u64::<LittleEndian>(*offset)?;
+        }
+        self.file.flush()
+    }
+}
+
+/// Writes a whole image (e.g. one rendered into memory), *rgb* holds
+/// the (linear) RGB values of the *data_window* pixels row by row.
+pub fn write_exr(
+    filename: &str,
+    resolution: Point2i,
+    data_window: Bounds2i,
+    rgb: &[Float],
+    metadata: &[(String, String)],
+) -> Result<()> {
+    let mut writer: TiledExrWriter =
+        TiledExrWriter::create(filename, resolution, data_window, 64, false, metadata)?;
+    let width: i32 = data_window.p_max.x - data_window.p_min.x;
+    for index in 0..(writer.tiles_x * writer.tiles_y) as usize {
+        let bounds: Bounds2i = writer.tile_bounds(index);
+        let mut tile: Vec<Float> = Vec::with_capacity(3 * bounds.area() as usize);
+        for y in bounds.p_min.y..bounds.p_max.y {
+            let start: usize = 3 * ((y - data_window.p_min.y) * width
+                + (bounds.p_min.x - data_window.p_min.x)) as usize;
+            let end: usize = start + 3 * (bounds.p_max.x - bounds.p_min.x) as usize;
+            tile.extend_from_slice(&rgb[start..end]);
+        }
+        writer.write_tile(index, &tile)?;
+    }
+    writer.finish()
+}
+
+/// The number of tiles in x and y direction.
+pub fn tile_count(data_window: &Bounds2i, tile_size: i32) -> (i32, i32) {
+    let width: i32 = data_window.p_max.x - data_window.p_min.x;
+    let height: i32 = data_window.p_max.y - data_window.p_min.y;
+    (
+        (width + tile_size - 1) / tile_size,
+        (height + tile_size - 1) / tile_size,
+    )
+}
+
+/// The pixels of tile *index* (tiles are numbered row by row), tiles
+/// at the right and bottom border can be smaller.
+pub fn tile_bounds(data_window: &Bounds2i, tile_size: i32, index: usize) -> Bounds2i {
+    let (tiles_x, _tiles_y) = tile_count(data_window, tile_size);
+    let p_min: Point2i = Point2i {
+        x: data_window.p_min.x + (index as i32 % tiles_x) * tile_size,
+        y: data_window.p_min.y + (index as i32 / tiles_x) * tile_size,
+    };
+    Bounds2i {
+        p_min,
+        p_max: Point2i {
+            x: (p_min.x + tile_size).min(data_window.p_max.x),
+            y: (p_min.y + tile_size).min(data_window.p_max.y),
+        },
+    }
+}
+
+pub fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) -> Result<()> {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.write_i32::<LittleEndian>(value.len() as i32)?;
+    header.extend_from_slice(value);
+    Ok(())
+}
+
+pub fn box2i(b: &Bounds2i) -> Result<Vec<u8>> {
+    let mut value: Vec<u8> = Vec::new();
+    value.write_i32::<LittleEndian>(b.p_min.x)?;
+    value.write_i32::<LittleEndian>(b.p_min.y)?;
+    value.write_i32::<LittleEndian>(b.p_max.x)?;
+    value.write_i32::<LittleEndian>(b.p_max.y)?;
+    Ok(value)
+}
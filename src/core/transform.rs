@@ -55,8 +55,9 @@
 // std
 use std;
 use std::cell::Cell;
-use std::f32::consts::PI;
 use std::ops::{Add, Mul};
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{
     bnd3_union_bnd3, bnd3_union_pnt3, nrm_faceforward_nrm, vec3_cross_vec3, vec3_dot_vec3,
@@ -65,10 +66,13 @@ use crate::core::geometry::{
     Bounds3f, Normal3, Point3, Point3f, Ray, RayDifferential, Vector3, Vector3f,
 };
 use crate::core::interaction::SurfaceInteraction;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, gamma, lerp, radians};
 use crate::core::quaternion::Quaternion;
 use crate::core::quaternion::{quat_dot_quat, quat_normalize, quat_slerp};
+#[cfg(feature = "simd")]
+use crate::core::simd;
 
 // see transform.h
 
@@ -152,7 +156,7 @@ impl Matrix4x4 {
                                 icol = k;
                             }
                         } else if *item > 1 {
-                            println!("Singular matrix in MatrixInvert");
+                            warn!("Singular matrix in MatrixInvert");
                         }
                     }
                 }
@@ -170,7 +174,7 @@ impl Matrix4x4 {
             indxr[i] = irow;
             indxc[i] = icol;
             if minv.m[icol][icol] == 0.0 {
-                println!("Singular matrix in MatrixInvert");
+                warn!("Singular matrix in MatrixInvert");
             }
             // set $m[icol][icol]$ to one by scaling row _icol_ appropriately
             let pivinv: Float = 1.0 / minv.m[icol][icol];
@@ -239,6 +243,18 @@ pub fn solve_linear_system_2x2(
 
 /// The product of two matrices.
 pub fn mtx_mul(m1: &Matrix4x4, m2: &Matrix4x4) -> Matrix4x4 {
+    #[cfg(feature = "simd")]
+    {
+        simd::mtx_mul(m1, m2)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        mtx_mul_scalar(m1, m2)
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+fn mtx_mul_scalar(m1: &Matrix4x4, m2: &Matrix4x4) -> Matrix4x4 {
     let mut r: Matrix4x4 = Matrix4x4::default();
     for i in 0..4 {
         for j in 0..4 {
@@ -433,7 +449,7 @@ impl Transform {
         // initialize first three columns of viewing matrix
         let dir: Vector3f = (*look - *pos).normalize();
         if vec3_cross_vec3(&up.normalize(), &dir).length() == 0.0 {
-            println!(
+            warn!(
                 "\"up\" vector ({}, {}, {}) and viewing direction ({}, {}, {}) passed to \
                  LookAt are pointing in the same direction.  Using the identity \
                  transformation.",
@@ -503,14 +519,15 @@ impl Transform {
         let x: Float = p.x;
         let y: Float = p.y;
         let z: Float = p.z;
-        let xp: Float =
-            self.m.m[0][0] * x + self.m.m[0][1] * y + self.m.m[0][2] * z + self.m.m[0][3];
-        let yp: Float =
-            self.m.m[1][0] * x + self.m.m[1][1] * y + self.m.m[1][2] * z + self.m.m[1][3];
-        let zp: Float =
-            self.m.m[2][0] * x + self.m.m[2][1] * y + self.m.m[2][2] * z + self.m.m[2][3];
-        let wp: Float =
-            self.m.m[3][0] * x + self.m.m[3][1] * y + self.m.m[3][2] * z + self.m.m[3][3];
+        #[cfg(feature = "simd")]
+        let [xp, yp, zp, wp]: [Float; 4] = simd::mtx_mul_point(&self.m, x, y, z);
+        #[cfg(not(feature = "simd"))]
+        let [xp, yp, zp, wp]: [Float; 4] = [
+            self.m.m[0][0] * x + self.m.m[0][1] * y + self.m.m[0][2] * z + self.m.m[0][3],
+            self.m.m[1][0] * x + self.m.m[1][1] * y + self.m.m[1][2] * z + self.m.m[1][3],
+            self.m.m[2][0] * x + self.m.m[2][1] * y + self.m.m[2][2] * z + self.m.m[2][3],
+            self.m.m[3][0] * x + self.m.m[3][1] * y + self.m.m[3][2] * z + self.m.m[3][3],
+        ];
         assert!(wp != 0.0, "wp = {:?} != 0.0", wp);
         if wp == 1.0 as Float {
             Point3::<Float> {
@@ -531,6 +548,12 @@ impl Transform {
         let x: Float = v.x;
         let y: Float = v.y;
         let z: Float = v.z;
+        #[cfg(feature = "simd")]
+        {
+            let [x, y, z, _] = simd::mtx_mul_vector(&self.m, x, y, z);
+            Vector3::<Float> { x, y, z }
+        }
+        #[cfg(not(feature = "simd"))]
         Vector3::<Float> {
             x: self.m.m[0][0] * x + self.m.m[0][1] * y + self.m.m[0][2] * z,
             y: self.m.m[1][0] * x + self.m.m[1][1] * y + self.m.m[1][2] * z,
@@ -541,6 +564,12 @@ impl Transform {
         let x: Float = n.x;
         let y: Float = n.y;
         let z: Float = n.z;
+        #[cfg(feature = "simd")]
+        {
+            let [x, y, z, _] = simd::mtx_transposed_mul_vector(&self.m_inv, x, y, z);
+            Normal3::<Float> { x, y, z }
+        }
+        #[cfg(not(feature = "simd"))]
         Normal3::<Float> {
             x: self.m_inv.m[0][0] * x + self.m_inv.m[1][0] * y + self.m_inv.m[2][0] * z,
             y: self.m_inv.m[0][1] * x + self.m_inv.m[1][1] * y + self.m_inv.m[2][1] * z,
@@ -840,8 +869,9 @@ impl Transform {
         ret.n = self.transform_normal(&si.n).normalize();
         ret.wo = self.transform_vector(&si.wo).normalize();
         ret.time = si.time;
+        ret.medium_interface = si.medium_interface.clone();
         ret.uv = si.uv;
-        ret.shape = None; // TODO? si.shape;
+        ret.shape = si.shape;
         ret.dpdu = self.transform_vector(&si.dpdu);
         ret.dpdv = self.transform_vector(&si.dpdv);
         ret.dndu = self.transform_normal(&si.dndu);
@@ -867,7 +897,8 @@ impl Transform {
         //     }
         // }
         // ret.bssrdf = si.bssrdf.clone();
-        ret.primitive = None; // TODO? si.primitive;
+        ret.primitive = si.primitive;
+        ret.eta_outside = si.eta_outside;
         ret.shading.n = nrm_faceforward_nrm(&ret.shading.n, &ret.n);
         // TODO: ret.faceIndex = si.faceIndex;
         *si = ret;
@@ -1,9 +1,8 @@
-// std
-use std::f32::consts::PI;
 // pbrt
 use crate::core::filter::Filter;
 use crate::core::geometry::{Point2f, Vector2f};
 use crate::core::paramset::ParamSet;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 
 #[derive(Debug, Default, Copy, Clone)]
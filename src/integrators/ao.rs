@@ -1,12 +1,26 @@
 // std
 use std::sync::Arc;
 // pbrt
+#[cfg(feature = "gpu")]
+use crate::accelerators::gpu::{GpuHit, GpuIntersector};
 use crate::core::camera::Camera;
+#[cfg(feature = "gpu")]
+use crate::core::camera::CameraSample;
 use crate::core::geometry::{nrm_cross_vec3, nrm_faceforward_vec3, vec3_dot_nrm};
+#[cfg(feature = "gpu")]
+use crate::core::geometry::{pnt2_inside_exclusive, Point2i, Vector2i};
 use crate::core::geometry::{Bounds2i, Normal3f, Point2f, Ray, Vector3f};
+#[cfg(feature = "gpu")]
+use crate::core::integrator::{budget_samples, is_render_cancelled};
+use crate::core::integrator::{first_intersection, FirstHit};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
 use crate::core::material::TransportMode;
+use crate::core::memory::MemoryArena;
 use crate::core::pbrt::{Float, Spectrum};
+#[cfg(feature = "gpu")]
+use crate::core::progress::ProgressReporter;
+#[cfg(feature = "gpu")]
+use crate::core::sampler::pixel_seed;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::{
     cosine_hemisphere_pdf, cosine_sample_hemisphere, uniform_hemisphere_pdf,
@@ -22,15 +36,23 @@ pub struct AOIntegrator {
     pub camera: Arc<Camera>,
     pub sampler: Box<Sampler>,
     pub pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
     // see ao.h
     pub cos_sample: bool,
     pub n_samples: i32,
+    /// occluders further away than this don't count
+    pub max_distance: Float,
+    /// intersect camera and occlusion rays on the GPU (feature "gpu")
+    pub gpu: bool,
 }
 
 impl AOIntegrator {
     pub fn new(
         cos_sample: bool,
         n_samples: i32,
+        max_distance: Float,
+        gpu: bool,
         camera: Arc<Camera>,
         sampler: Box<Sampler>,
         pixel_bounds: Bounds2i,
@@ -39,8 +61,11 @@ impl AOIntegrator {
             camera,
             sampler,
             pixel_bounds,
+            debug_non_finite: false,
             cos_sample,
             n_samples,
+            max_distance,
+            gpu,
         }
     }
     pub fn preprocess(&mut self, _scene: &Scene) {
@@ -51,7 +76,20 @@ impl AOIntegrator {
         r: &mut Ray,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        self.li_first_hit(r, None, scene, sampler, arena, depth)
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        r: &mut Ray,
+        first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        _arena: &MemoryArena,
         _depth: i32,
     ) -> Spectrum {
         // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
@@ -64,8 +102,9 @@ impl AOIntegrator {
             differential: r.differential,
             medium: r.medium.clone(),
         };
-        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
-        if scene.intersect(&mut ray, &mut isect) {
+        let (found, mut isect): (bool, SurfaceInteraction) =
+            first_intersection(scene, &mut ray, first_hit);
+        if found {
             let mode: TransportMode = TransportMode::Radiance;
             isect.compute_scattering_functions(&ray, true, mode);
             // if (!isect.bsdf) {
@@ -81,22 +120,9 @@ impl AOIntegrator {
             let u_opt: Option<&[Point2f]> = sampler.get_2d_array(self.n_samples);
             if let Some(u) = u_opt {
                 for item in u.iter().take(self.n_samples as usize) {
-                    // Vector3f wi;
-                    let mut wi: Vector3f;
-                    let pdf = if self.cos_sample {
-                        wi = cosine_sample_hemisphere(*item);
-                        cosine_hemisphere_pdf(wi.z.abs())
-                    } else {
-                        wi = uniform_sample_hemisphere(*item);
-                        uniform_hemisphere_pdf()
-                    };
-                    // transform wi from local frame to world space.
-                    wi = Vector3f {
-                        x: s.x * wi.x + t.x * wi.y + n.x * wi.z,
-                        y: s.y * wi.x + t.y * wi.y + n.y * wi.z,
-                        z: s.z * wi.x + t.z * wi.y + n.z * wi.z,
-                    };
+                    let (wi, pdf) = self.sample_direction(item, &n, &s, &t);
                     let mut ray: Ray = isect.spawn_ray(&wi);
+                    ray.t_max = self.max_distance;
                     if !scene.intersect_p(&mut ray) {
                         l += Spectrum::new(vec3_dot_nrm(&wi, &n) / (pdf * self.n_samples as Float));
                     }
@@ -105,6 +131,152 @@ impl AOIntegrator {
         }
         l
     }
+    /// Samples a direction in the hemisphere around *n* (with the
+    /// tangents *s* and *t*) and returns it with its PDF.
+    fn sample_direction(
+        &self,
+        u: &Point2f,
+        n: &Normal3f,
+        s: &Vector3f,
+        t: &Vector3f,
+    ) -> (Vector3f, Float) {
+        // Vector3f wi;
+        let wi: Vector3f;
+        let pdf = if self.cos_sample {
+            wi = cosine_sample_hemisphere(*u);
+            cosine_hemisphere_pdf(wi.z.abs())
+        } else {
+            wi = uniform_sample_hemisphere(*u);
+            uniform_hemisphere_pdf()
+        };
+        // transform wi from local frame to world space.
+        (
+            Vector3f {
+                x: s.x * wi.x + t.x * wi.y + n.x * wi.z,
+                y: s.y * wi.x + t.y * wi.y + n.y * wi.z,
+                z: s.z * wi.x + t.z * wi.y + n.z * wi.z,
+            },
+            pdf,
+        )
+    }
+    /// Renders tile by tile like **SamplerIntegrator::render()**, but
+    /// the camera rays of a tile, and then all occlusion rays spawned
+    /// at their hit points, are intersected on the GPU as one batch
+    /// each (a simple wavefront loop). Returns false (without
+    /// rendering anything) if the GPU can't be used for the scene.
+    #[cfg(feature = "gpu")]
+    pub fn render_gpu(&mut self, scene: &Scene) -> bool {
+        let gpu: GpuIntersector = match GpuIntersector::new(scene) {
+            Some(gpu) => gpu,
+            None => return false,
+        };
+        self.preprocess(scene);
+        let film = self.camera.get_film();
+        let sample_bounds: Bounds2i = film.get_sample_bounds();
+        let sample_extent: Vector2i = sample_bounds.diagonal();
+        let tile_size: i32 = 16;
+        let n_tiles: Point2i = Point2i {
+            x: (sample_extent.x + tile_size - 1) / tile_size,
+            y: (sample_extent.y + tile_size - 1) / tile_size,
+        };
+        let mut tile_sampler: Box<Sampler> = self.sampler.clone_with_seed(0_u64);
+        let samples_per_pixel: i64 = budget_samples(tile_sampler.get_samples_per_pixel());
+        let mut progress: ProgressReporter =
+            ProgressReporter::new((n_tiles.x * n_tiles.y) as u64, "Rendering", "tiles");
+        for seed in 0..n_tiles.x * n_tiles.y {
+            if is_render_cancelled() {
+                break;
+            }
+            let tile: Point2i = Point2i {
+                x: seed % n_tiles.x,
+                y: seed / n_tiles.x,
+            };
+            let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
+            let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
+            let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
+            let y1: i32 = std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
+            let tile_bounds: Bounds2i =
+                Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 });
+            let mut film_tile = film.get_film_tile(&tile_bounds);
+            // generate all camera rays of the tile
+            let mut camera_samples: Vec<CameraSample> = Vec::new();
+            let mut ray_weights: Vec<Float> = Vec::new();
+            let mut rays: Vec<Ray> = Vec::new();
+            let mut us: Vec<Vec<Point2f>> = Vec::new();
+            for pixel in &tile_bounds {
+                tile_sampler.reseed(pixel_seed(pixel));
+                tile_sampler.start_pixel(pixel);
+                if !pnt2_inside_exclusive(pixel, &self.pixel_bounds) {
+                    continue;
+                }
+                loop {
+                    let camera_sample: CameraSample = tile_sampler.get_camera_sample(pixel);
+                    let mut ray: Ray = Ray::default();
+                    let ray_weight: Float = self
+                        .camera
+                        .generate_ray_differential(&camera_sample, &mut ray);
+                    ray.scale_differentials(
+                        1.0 as Float / (tile_sampler.get_samples_per_pixel() as Float).sqrt(),
+                    );
+                    us.push(tile_sampler.get_2d_array_vec(self.n_samples));
+                    camera_samples.push(camera_sample);
+                    ray_weights.push(ray_weight);
+                    rays.push(ray);
+                    if !tile_sampler.start_next_sample()
+                        || tile_sampler.get_current_sample_number() >= samples_per_pixel
+                    {
+                        break;
+                    }
+                }
+            }
+            // find the closest hits on the GPU, shade them on the CPU
+            let hits: Vec<Option<GpuHit>> = gpu.intersect(&rays);
+            let mut occlusion_rays: Vec<Ray> = Vec::new();
+            let mut contributions: Vec<(usize, Float)> = Vec::new();
+            for (i, hit) in hits.iter().enumerate() {
+                let hit: &GpuHit = match hit {
+                    Some(hit) if ray_weights[i] > 0.0 as Float => hit,
+                    _ => continue,
+                };
+                let mut ray: Ray = rays[i].clone();
+                let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+                // intersect the hit triangle again for the full interaction
+                if !gpu.primitive(hit.primitive).intersect(&mut ray, &mut isect)
+                    && !scene.intersect(&mut ray, &mut isect)
+                {
+                    continue;
+                }
+                isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+                let n: Normal3f = nrm_faceforward_vec3(&isect.n, &-ray.d);
+                let s: Vector3f = isect.dpdu.normalize();
+                let t: Vector3f = nrm_cross_vec3(&isect.n, &s);
+                for u in &us[i] {
+                    let (wi, pdf) = self.sample_direction(u, &n, &s, &t);
+                    let mut ray: Ray = isect.spawn_ray(&wi);
+                    ray.t_max = self.max_distance;
+                    occlusion_rays.push(ray);
+                    contributions
+                        .push((i, vec3_dot_nrm(&wi, &n) / (pdf * self.n_samples as Float)));
+                }
+            }
+            // trace all occlusion rays of the tile on the GPU
+            let occluded: Vec<bool> = gpu.intersect_p(&occlusion_rays);
+            let mut l: Vec<Spectrum> = vec![Spectrum::default(); rays.len()];
+            for ((i, contribution), occluded) in contributions.iter().zip(occluded.iter()) {
+                if !occluded {
+                    l[*i] += Spectrum::new(*contribution);
+                }
+            }
+            for (i, camera_sample) in camera_samples.iter().enumerate() {
+                film_tile.add_sample(camera_sample.p_film, &mut l[i], ray_weights[i]);
+            }
+            film.merge_film_tile(&film_tile);
+            progress.update(1);
+        }
+        progress.done();
+        film.write_image(1.0 as Float);
+        true
+    }
     pub fn get_camera(&self) -> Arc<Camera> {
         self.camera.clone()
     }
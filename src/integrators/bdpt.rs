@@ -1,12 +1,16 @@
 // std
 use std::cell::Cell;
-use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+// others
+use log::info;
 // pbrt
 use crate::blockqueue::BlockQueue;
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::film::FilmTile;
 use crate::core::geometry::{
-    nrm_abs_dot_vec3, pnt2_inside_exclusive, pnt3_offset_ray_origin, vec3_abs_dot_nrm, vec3_dot_nrm,
+    bnd2_intersect_bnd2, nrm_abs_dot_vec3, pnt2_inside_exclusive, pnt3_offset_ray_origin,
+    vec3_abs_dot_nrm, vec3_dot_nrm,
 };
 use crate::core::geometry::{
     Bounds2i, Bounds3f, Normal3f, Point2f, Point2i, Point3f, Ray, Vector2i, Vector3f,
@@ -14,20 +18,30 @@ use crate::core::geometry::{
 use crate::core::interaction::{
     Interaction, InteractionCommon, MediumInteraction, SurfaceInteraction,
 };
+use crate::core::integrator::{
+    budget_samples, is_render_cancelled, splat_scale, tile_shape, OrderedMerge, TileGrid,
+};
 use crate::core::light::is_delta_light;
 use crate::core::light::{Light, LightFlags, VisibilityTester};
 use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::material::TransportMode;
-use crate::core::medium::{HenyeyGreenstein, Medium, MediumInterface};
+use crate::core::medium::{Medium, MediumInterface, PhaseFunction};
+use crate::core::numa;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
 use crate::core::reflection::Bsdf;
 use crate::core::reflection::BxdfType;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{pixel_seed, Sampler};
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{StatIntDistribution, StatPercent};
 
 // see bdpt.h
 
+static ZERO_RADIANCE_PATHS: StatPercent = StatPercent::new("Integrator/Zero-radiance paths");
+static PATH_LENGTH: StatIntDistribution = StatIntDistribution::new("Integrator/Path length");
+
 #[derive(Default)]
 pub struct EndpointInteraction<'a> {
     // Interaction Public Data
@@ -141,7 +155,7 @@ impl<'a> Interaction for EndpointInteraction<'a> {
         Ray {
             o,
             d: *d,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time: self.time,
             differential: None,
             medium: self.get_medium(d),
@@ -171,7 +185,7 @@ impl<'a> Interaction for EndpointInteraction<'a> {
     fn get_shading_n(&self) -> Option<Normal3f> {
         None
     }
-    fn get_phase(&self) -> Option<Arc<HenyeyGreenstein>> {
+    fn get_phase(&self) -> Option<Arc<PhaseFunction>> {
         None
     }
 }
@@ -292,6 +306,10 @@ impl<'a> Vertex<'a> {
         v.pdf_fwd = pdf;
         v
     }
+    /// The throughput of the subpath up to this vertex.
+    pub fn beta(&self) -> Spectrum {
+        self.beta
+    }
     pub fn p(&self) -> Point3f {
         match self.vertex_type {
             VertexType::Medium => {
@@ -640,7 +658,7 @@ impl<'a> Vertex<'a> {
                             &Ray {
                                 o: self.p(),
                                 d: w,
-                                t_max: std::f32::INFINITY,
+                                t_max: Float::INFINITY,
                                 time: self.time(),
                                 differential: None,
                                 medium: None,
@@ -664,7 +682,7 @@ impl<'a> Vertex<'a> {
                             &Ray {
                                 o: self.p(),
                                 d: w,
-                                t_max: std::f32::INFINITY,
+                                t_max: Float::INFINITY,
                                 time: self.time(),
                                 differential: None,
                                 medium: None,
@@ -724,7 +742,7 @@ impl<'a> Vertex<'a> {
                                     &Ray {
                                         o: self.p(),
                                         d: w,
-                                        t_max: std::f32::INFINITY,
+                                        t_max: Float::INFINITY,
                                         time: self.time(),
                                         differential: None,
                                         medium: None,
@@ -759,7 +777,7 @@ impl<'a> Vertex<'a> {
                                         &Ray {
                                             o: self.p(),
                                             d: w,
-                                            t_max: std::f32::INFINITY,
+                                            t_max: Float::INFINITY,
                                             time: self.time(),
                                             differential: None,
                                             medium: None,
@@ -791,6 +809,9 @@ pub struct BDPTIntegrator {
     // visualize_strategies: bool,
     // visualize_weights: bool,
     pub light_sample_strategy: String, // "power"
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl BDPTIntegrator {
@@ -811,6 +832,7 @@ impl BDPTIntegrator {
             // visualize_strategies,
             // visualize_weights,
             light_sample_strategy,
+            terminator_shadowing: false,
         }
     }
     pub fn get_light_sample_strategy(&self) -> String {
@@ -829,30 +851,28 @@ impl BDPTIntegrator {
         // }
         // partition the image into tiles
         let film = self.get_camera().get_film();
-        let sample_bounds: Bounds2i = film.get_sample_bounds();
-        let sample_extent: Vector2i = sample_bounds.diagonal();
-        let tile_size: i32 = 16;
-        let n_x_tiles: i32 = (sample_extent.x + tile_size - 1) / tile_size;
-        let n_y_tiles: i32 = (sample_extent.y + tile_size - 1) / tile_size;
-        // TODO: ProgressReporter reporter(nXTiles * nYTiles, "Rendering");
+        let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), tile_shape());
+        let tile_size: Vector2i = grid.tile_size;
         // TODO: Allocate buffers for debug visualization
         // ...
         // render and write the output image to disk
         if !scene.lights.is_empty() {
-            let samples_per_pixel: i64 = self.sampler.get_samples_per_pixel();
+            let samples_per_pixel: i64 = budget_samples(self.sampler.get_samples_per_pixel());
+            // the camera samples taken (each with a light subpath)
+            let samples_taken: AtomicU64 = AtomicU64::new(0);
             let num_cores = if num_threads == 0_u8 {
                 num_cpus::get()
             } else {
                 num_threads as usize
             };
-            println!("Rendering with {:?} thread(s) ...", num_cores);
+            info!("Rendering with {:?} thread(s) ...", num_cores);
             {
                 let block_queue = BlockQueue::new(
                     (
-                        (n_x_tiles * tile_size) as u32,
-                        (n_y_tiles * tile_size) as u32,
+                        (grid.n_tiles.x * tile_size.x) as u32,
+                        (grid.n_tiles.y * tile_size.y) as u32,
                     ),
-                    (tile_size as u32, tile_size as u32),
+                    (tile_size.x as u32, tile_size.y as u32),
                     (0, 0),
                 );
                 let bq = &block_queue;
@@ -860,32 +880,34 @@ impl BDPTIntegrator {
                 let sampler = &self.get_sampler();
                 let camera = &self.get_camera();
                 let film = &film;
+                let samples_taken = &samples_taken;
                 // let pixel_bounds = integrator.get_pixel_bounds().clone();
                 crossbeam::scope(|scope| {
                     let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
                     // spawn worker threads
-                    for _ in 0..num_cores {
+                    for thread_index in 0..num_cores {
                         let pixel_tx = pixel_tx.clone();
                         scope.spawn(move |_| {
-                            while let Some((x, y)) = bq.next() {
+                            numa::pin_render_thread(thread_index);
+                            while let Some((position, (x, y))) = bq.next_with_position() {
+                                // stop taking tiles, the rendered ones get merged
+                                if is_render_cancelled() {
+                                    break;
+                                }
                                 let tile: Point2i = Point2i {
                                     x: x as i32,
                                     y: y as i32,
                                 };
-                                let seed: i32 = tile.y * n_x_tiles + tile.x;
-                                let mut tile_sampler: Box<Sampler> =
-                                    sampler.clone_with_seed(seed as u64);
-                                let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
-                                let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
-                                let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
-                                let y1: i32 = std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
-                                let tile_bounds: Bounds2i = Bounds2i::new(
-                                    Point2i { x: x0, y: y0 },
-                                    Point2i { x: x1, y: y1 },
-                                );
+                                let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+                                let tile_bounds: Bounds2i = grid.tile_bounds(tile);
                                 // println!("Starting image tile {:?}", tile_bounds);
                                 let mut film_tile = film.get_film_tile(&tile_bounds);
+                                let mut tile_samples: u64 = 0;
                                 for p_pixel in &tile_bounds {
+                                    if is_render_cancelled() {
+                                        break;
+                                    }
+                                    tile_sampler.reseed(pixel_seed(p_pixel));
                                     tile_sampler.start_pixel(p_pixel);
                                     if !pnt2_inside_exclusive(p_pixel, &integrator.pixel_bounds) {
                                         continue;
@@ -932,6 +954,7 @@ impl BDPTIntegrator {
                                                         integrator.max_depth + 2,
                                                         camera,
                                                         p_film,
+                                                        integrator.terminator_shadowing,
                                                         &mut camera_vertices,
                                                     );
                                                 n_camera = n_camera_new;
@@ -953,6 +976,7 @@ impl BDPTIntegrator {
                                                     time,
                                                     light_distr.clone(),
                                                     // light_to_index,
+                                                    integrator.terminator_shadowing,
                                                     &mut light_vertices,
                                                 );
                                             }
@@ -1005,7 +1029,7 @@ impl BDPTIntegrator {
                                                     if t != 1 {
                                                         l += lpath;
                                                     } else if !lpath.is_black() {
-                                                        film.add_splat(p_film_new, &lpath);
+                                                        film_tile.add_splat(p_film_new, &lpath);
                                                     }
                                                 }
                                             }
@@ -1016,29 +1040,51 @@ impl BDPTIntegrator {
                                             //     l.y()
                                             // );
                                             film_tile.add_sample(p_film, &mut l, 1.0 as Float);
-                                            done = !tile_sampler.start_next_sample();
+                                            tile_samples += 1;
+                                            done = !tile_sampler.start_next_sample()
+                                                || tile_sampler.get_current_sample_number()
+                                                    >= samples_per_pixel;
                                         }
                                     }
                                 }
+                                samples_taken.fetch_add(tile_samples, Ordering::Relaxed);
                                 // send the tile through the channel to main thread
                                 pixel_tx
-                                    .send(film_tile)
+                                    .send((position, film_tile))
                                     .unwrap_or_else(|_| panic!("Failed to send tile"));
                             }
                         });
                     }
+                    // the channel closes when all workers are done
+                    drop(pixel_tx);
                     // spawn thread to collect pixels and render image to file
                     scope.spawn(move |_| {
-                        for _ in pbr::PbIter::new(0..bq.len()) {
-                            let film_tile = pixel_rx.recv().unwrap();
+                        let mut progress: ProgressReporter =
+                            ProgressReporter::new(bq.len() as u64, "Rendering", "tiles");
+                        let mut ordered: OrderedMerge<FilmTile> = OrderedMerge::default();
+                        let mut merge = |film_tile: FilmTile| {
                             // merge image tile into _Film_
                             film.merge_film_tile(&film_tile);
+                            progress.update(1);
+                        };
+                        for (position, film_tile) in pixel_rx.iter() {
+                            ordered.add(position, film_tile, &mut merge);
                         }
+                        ordered.finish(&mut merge);
+                        progress.done();
                     });
                 })
                 .unwrap();
             }
-            film.write_image(1.0 as Float / samples_per_pixel as Float);
+            // a cancelled render took fewer samples
+            let scale: Float = if is_render_cancelled() {
+                let n_pixels: i32 =
+                    bnd2_intersect_bnd2(&film.get_sample_bounds(), &self.pixel_bounds).area();
+                splat_scale(n_pixels as i64, samples_taken.load(Ordering::Relaxed))
+            } else {
+                1.0 as Float / samples_per_pixel as Float
+            };
+            film.write_image(scale);
             // TODO: Write buffers for debug visualization
         }
     }
@@ -1074,12 +1120,16 @@ pub fn correct_shading_normal(
     }
 }
 
+/// Samples a path from the camera through *p_film*, with
+/// *terminator_shadowing* (see **Bsdf::terminator_shadowing()**) for
+/// the BSDFs along it.
 pub fn generate_camera_subpath<'a>(
     scene: &'a Scene,
     sampler: &mut Sampler,
     max_depth: u32,
     camera: &'a Arc<Camera>,
     p_film: Point2f,
+    terminator_shadowing: bool,
     path: &mut Vec<Vertex<'a>>,
 ) -> (usize, Point3f, Float) {
     if max_depth == 0 {
@@ -1112,6 +1162,7 @@ pub fn generate_camera_subpath<'a>(
             pdf_dir,
             max_depth - 1_u32,
             TransportMode::Radiance,
+            terminator_shadowing,
             path,
         ) + 1_usize,
         p,
@@ -1119,6 +1170,8 @@ pub fn generate_camera_subpath<'a>(
     )
 }
 
+/// Samples a path from a light (chosen with *light_distr*), see
+/// **generate_camera_subpath()**.
 pub fn generate_light_subpath<'a>(
     scene: &'a Scene,
     sampler: &mut Sampler,
@@ -1126,6 +1179,7 @@ pub fn generate_light_subpath<'a>(
     time: Float,
     light_distr: Arc<Distribution1D>,
     // TODO: light_to_index
+    terminator_shadowing: bool,
     path: &mut Vec<Vertex<'a>>,
 ) -> usize {
     let mut n_vertices: usize = 0_usize;
@@ -1177,6 +1231,7 @@ pub fn generate_light_subpath<'a>(
             pdf_dir,
             max_depth - 1,
             TransportMode::Importance,
+            terminator_shadowing,
             path,
         );
         // correct subpath sampling densities for infinite area lights
@@ -1203,6 +1258,7 @@ pub fn random_walk<'a>(
     pdf: Float,
     max_depth: u32,
     mode: TransportMode,
+    terminator_shadowing: bool,
     path: &mut Vec<Vertex<'a>>,
 ) -> usize {
     // create a copy of the ray which can be mutated
@@ -1292,6 +1348,7 @@ pub fn random_walk<'a>(
                 // compute scattering functions for _mode_ and skip over medium
                 // boundaries
                 isect.compute_scattering_functions(&ray, true, mode);
+                isect.set_terminator_shadowing(terminator_shadowing);
                 let isect_wo: Vector3f = isect.wo;
                 let isect_shading_n: Normal3f = isect.shading.n;
                 if isect.bsdf.is_none() {
@@ -1574,7 +1631,7 @@ pub fn mis_weight<'a>(
         }
         if let Some(ref lv_mi) = sampled.mi {
             let mut medium_interface: Option<Arc<MediumInterface>> = None;
-            let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+            let mut phase: Option<Arc<PhaseFunction>> = None;
             if let Some(ref medium_interface_arc) = lv_mi.medium_interface {
                 medium_interface = Some(medium_interface_arc.clone());
             }
@@ -1589,6 +1646,7 @@ pub fn mis_weight<'a>(
                 n: lv_mi.n,
                 medium_interface,
                 phase,
+                le: lv_mi.le,
             };
             mi = Some(new_mi);
         }
@@ -1667,7 +1725,7 @@ pub fn mis_weight<'a>(
         }
         if let Some(ref lv_mi) = sampled.mi {
             let mut medium_interface: Option<Arc<MediumInterface>> = None;
-            let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+            let mut phase: Option<Arc<PhaseFunction>> = None;
             if let Some(ref medium_interface_arc) = lv_mi.medium_interface {
                 medium_interface = Some(medium_interface_arc.clone());
             }
@@ -1682,6 +1740,7 @@ pub fn mis_weight<'a>(
                 n: lv_mi.n,
                 medium_interface,
                 phase,
+                le: lv_mi.le,
             };
             mi = Some(new_mi);
         }
@@ -1764,7 +1823,7 @@ pub fn mis_weight<'a>(
         }
         if let Some(ref cv_mi) = camera_vertices[t - 1].mi {
             let mut medium_interface: Option<Arc<MediumInterface>> = None;
-            let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+            let mut phase: Option<Arc<PhaseFunction>> = None;
             if let Some(ref medium_interface_arc) = cv_mi.medium_interface {
                 medium_interface = Some(medium_interface_arc.clone());
             }
@@ -1779,6 +1838,7 @@ pub fn mis_weight<'a>(
                 n: cv_mi.n,
                 medium_interface,
                 phase,
+                le: cv_mi.le,
             };
             mi = Some(new_mi);
         }
@@ -1860,7 +1920,7 @@ pub fn mis_weight<'a>(
         }
         if let Some(ref lv_mi) = light_vertices[s - 1].mi {
             let mut medium_interface: Option<Arc<MediumInterface>> = None;
-            let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+            let mut phase: Option<Arc<PhaseFunction>> = None;
             if let Some(ref medium_interface_arc) = lv_mi.medium_interface {
                 medium_interface = Some(medium_interface_arc.clone());
             }
@@ -1875,6 +1935,7 @@ pub fn mis_weight<'a>(
                 n: lv_mi.n,
                 medium_interface,
                 phase,
+                le: lv_mi.le,
             };
             mi = Some(new_mi);
         }
@@ -1972,7 +2033,7 @@ pub fn mis_weight<'a>(
             }
             if let Some(ref cv_mi) = camera_vertices[t - 2].mi {
                 let mut medium_interface: Option<Arc<MediumInterface>> = None;
-                let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+                let mut phase: Option<Arc<PhaseFunction>> = None;
                 if let Some(ref medium_interface_arc) = cv_mi.medium_interface {
                     medium_interface = Some(medium_interface_arc.clone());
                 }
@@ -1987,6 +2048,7 @@ pub fn mis_weight<'a>(
                     n: cv_mi.n,
                     medium_interface,
                     phase,
+                    le: cv_mi.le,
                 };
                 mi = Some(new_mi);
             }
@@ -2072,7 +2134,7 @@ pub fn mis_weight<'a>(
             }
             if let Some(ref lv_mi) = light_vertices[s - 2].mi {
                 let mut medium_interface: Option<Arc<MediumInterface>> = None;
-                let mut phase: Option<Arc<HenyeyGreenstein>> = None;
+                let mut phase: Option<Arc<PhaseFunction>> = None;
                 if let Some(ref medium_interface_arc) = lv_mi.medium_interface {
                     medium_interface = Some(medium_interface_arc.clone());
                 }
@@ -2087,6 +2149,7 @@ pub fn mis_weight<'a>(
                     n: lv_mi.n,
                     medium_interface,
                     phase,
+                    le: lv_mi.le,
                 };
                 mi = Some(new_mi);
             }
@@ -2210,6 +2273,85 @@ pub fn mis_weight<'a>(
     1.0 as Float / (1.0 as Float + sum_ri)
 }
 
+/// Samples a point on the camera (the $t=1$ strategy) and connects the
+/// light subpath vertex *qs* to it. Returns the (unweighted) path
+/// contribution, with *p_raster* set to where it gets splatted, and
+/// the sampled camera vertex.
+pub fn connect_to_camera<'a>(
+    scene: &'a Scene,
+    qs: &Vertex<'a>,
+    camera: &'a Arc<Camera>,
+    sampler: &mut Sampler,
+    p_raster: &mut Point2f,
+) -> (Spectrum, Option<Vertex<'a>>) {
+    if qs.is_connectible() {
+        let mut iref: InteractionCommon = InteractionCommon::default();
+        // qs.GetInteraction()
+        match qs.vertex_type {
+            VertexType::Medium => {
+                if let Some(ref mi) = qs.mi {
+                    iref.p = mi.p;
+                    iref.time = mi.time;
+                    iref.p_error = mi.p_error;
+                    iref.wo = mi.wo;
+                    iref.n = mi.n;
+                    if let Some(ref medium_interface_arc) = mi.medium_interface {
+                        iref.medium_interface = Some(medium_interface_arc.clone())
+                    }
+                }
+            }
+            VertexType::Surface => {
+                if let Some(ref si) = qs.si {
+                    iref.p = si.p;
+                    iref.time = si.time;
+                    iref.p_error = si.p_error;
+                    iref.wo = si.wo;
+                    iref.n = si.n;
+                }
+            }
+            _ => {
+                if let Some(ref ei) = qs.ei {
+                    iref.p = ei.p;
+                    iref.time = ei.time;
+                    iref.p_error = ei.p_error;
+                    iref.wo = ei.wo;
+                    iref.n = ei.n;
+                }
+            }
+        }
+        let mut wi: Vector3f = Vector3f::default();
+        let mut pdf: Float = 0.0 as Float;
+        let mut vis: VisibilityTester = VisibilityTester::default();
+        let wi_color: Spectrum = camera.sample_wi(
+            &iref,
+            sampler.get_2d(),
+            &mut wi,
+            &mut pdf,
+            p_raster,
+            &mut vis,
+        );
+        if pdf > 0.0 as Float && !wi_color.is_black() {
+            // initialize dynamically sampled vertex and _L_ for $t=1$ case
+            let sampled: Vertex =
+                Vertex::create_camera_from_interaction(camera, &vis.p1, &(wi_color / pdf));
+            let mut l: Spectrum =
+                qs.beta * qs.f(&sampled, TransportMode::Importance) * sampled.beta;
+            // println!("l = {:?}", l);
+            if qs.is_on_surface() {
+                l *= Spectrum::new(vec3_abs_dot_nrm(&wi, &qs.ns()));
+            }
+            assert!(!l.has_nans());
+            // only check visibility after we know that the path
+            // would make a non-zero contribution.
+            if !l.is_black() {
+                l *= vis.tr(scene, sampler);
+            }
+            return (l, Some(sampled));
+        }
+    }
+    (Spectrum::default(), None)
+}
+
 pub fn connect_bdpt<'a>(
     scene: &'a Scene,
     light_vertices: &[Vertex<'a>],
@@ -2254,73 +2396,11 @@ pub fn connect_bdpt<'a>(
             (s - 1),
             light_vertices.len()
         );
-        if light_vertices[s - 1].is_connectible() {
-            let mut iref: InteractionCommon = InteractionCommon::default();
-            // qs.GetInteraction()
-            match light_vertices[s - 1].vertex_type {
-                VertexType::Medium => {
-                    if let Some(ref mi) = light_vertices[s - 1].mi {
-                        iref.p = mi.p;
-                        iref.time = mi.time;
-                        iref.p_error = mi.p_error;
-                        iref.wo = mi.wo;
-                        iref.n = mi.n;
-                        if let Some(ref medium_interface_arc) = mi.medium_interface {
-                            iref.medium_interface = Some(medium_interface_arc.clone())
-                        }
-                    } else {
-                    }
-                }
-                VertexType::Surface => {
-                    if let Some(ref si) = light_vertices[s - 1].si {
-                        iref.p = si.p;
-                        iref.time = si.time;
-                        iref.p_error = si.p_error;
-                        iref.wo = si.wo;
-                        iref.n = si.n;
-                    } else {
-                    }
-                }
-                _ => {
-                    if let Some(ref ei) = light_vertices[s - 1].ei {
-                        iref.p = ei.p;
-                        iref.time = ei.time;
-                        iref.p_error = ei.p_error;
-                        iref.wo = ei.wo;
-                        iref.n = ei.n;
-                    } else {
-                    }
-                }
-            }
-            let mut wi: Vector3f = Vector3f::default();
-            let mut pdf: Float = 0.0 as Float;
-            let mut vis: VisibilityTester = VisibilityTester::default();
-            let wi_color: Spectrum = camera.sample_wi(
-                &iref,
-                sampler.get_2d(),
-                &mut wi,
-                &mut pdf,
-                p_raster,
-                &mut vis,
-            );
-            if pdf > 0.0 as Float && !wi_color.is_black() {
-                // initialize dynamically sampled vertex and _L_ for $t=1$ case
-                sampled =
-                    Vertex::create_camera_from_interaction(camera, &vis.p1, &(wi_color / pdf));
-                l = light_vertices[s - 1].beta
-                    * light_vertices[s - 1].f(&sampled, TransportMode::Importance)
-                    * sampled.beta;
-                // println!("l = {:?}", l);
-                if light_vertices[s - 1].is_on_surface() {
-                    l *= Spectrum::new(vec3_abs_dot_nrm(&wi, &light_vertices[s - 1].ns()));
-                }
-                assert!(!l.has_nans());
-                // only check visibility after we know that the path
-                // would make a non-zero contribution.
-                if !l.is_black() {
-                    l *= vis.tr(scene, sampler);
-                }
-            }
+        let (l_camera, sampled_camera) =
+            connect_to_camera(scene, &light_vertices[s - 1], camera, sampler, p_raster);
+        l = l_camera;
+        if let Some(sampled_camera) = sampled_camera {
+            sampled = sampled_camera;
         }
     } else if s == 1 {
         // sample a point on a light and connect it to the camera subpath
@@ -2445,10 +2525,11 @@ pub fn connect_bdpt<'a>(
             }
         }
     }
-    // TODO:
-    // ++totalPaths;
-    // if (L.IsBlack()) ++zeroRadiancePaths;
-    // ReportValue(pathLength, s + t - 2);
+    ZERO_RADIANCE_PATHS.inc_denom();
+    if l.is_black() {
+        ZERO_RADIANCE_PATHS.inc_num();
+    }
+    PATH_LENGTH.report_value((s + t - 2) as u64);
 
     // compute MIS weight for connection strategy
     let mis_weight_flt = if !l.is_black() {
@@ -5,9 +5,11 @@ use std::sync::Arc;
 use crate::core::camera::Camera;
 use crate::core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use crate::core::geometry::{Bounds2i, Normal3f, Ray, RayDifferential, Vector3f};
+use crate::core::integrator::{first_intersection, FirstHit};
 use crate::core::integrator::{uniform_sample_all_lights, uniform_sample_one_light};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
 use crate::core::material::TransportMode;
+use crate::core::memory::MemoryArena;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
@@ -27,10 +29,15 @@ pub struct DirectLightingIntegrator {
     pub camera: Arc<Camera>,
     pub sampler: Box<Sampler>,
     pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
     // see directlighting.h
     strategy: LightStrategy,
     max_depth: u32,
     n_light_samples: Vec<i32>,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl DirectLightingIntegrator {
@@ -45,9 +52,11 @@ impl DirectLightingIntegrator {
             camera,
             sampler,
             pixel_bounds,
+            debug_non_finite: false,
             strategy,
             max_depth,
             n_light_samples: Vec::new(),
+            terminator_shadowing: false,
         }
     }
     pub fn preprocess(&mut self, scene: &Scene) {
@@ -72,17 +81,32 @@ impl DirectLightingIntegrator {
         ray: &mut Ray,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        self.li_first_hit(ray, None, scene, sampler, arena, depth)
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        ray: &mut Ray,
+        first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::new(0.0 as Float);
         // find closest ray intersection or return background radiance
-        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
-        if scene.intersect(ray, &mut isect) {
+        let (found, mut isect): (bool, SurfaceInteraction) =
+            first_intersection(scene, ray, first_hit);
+        if found {
             // compute scattering functions for surface interaction
             let mode: TransportMode = TransportMode::Radiance;
             isect.compute_scattering_functions(ray, false, mode);
+            isect.set_terminator_shadowing(self.terminator_shadowing);
             // if (!isect.bsdf)
             //     return Li(isect.SpawnRay(ray.d), scene, sampler, arena, depth);
             let wo: Vector3f = isect.wo;
@@ -94,6 +118,7 @@ impl DirectLightingIntegrator {
                         &isect,
                         scene,
                         sampler,
+                        arena,
                         &self.n_light_samples,
                         false,
                     );
@@ -104,14 +129,8 @@ impl DirectLightingIntegrator {
             }
             if ((depth + 1_i32) as u32) < self.max_depth {
                 // trace rays for specular reflection and refraction
-                l += self.specular_reflect(
-                    ray, &isect, scene, sampler, // arena,
-                    depth,
-                );
-                l += self.specular_transmit(
-                    ray, &isect, scene, sampler, // arena,
-                    depth,
-                );
+                l += self.specular_reflect(ray, &isect, scene, sampler, arena, depth);
+                l += self.specular_transmit(ray, &isect, scene, sampler, arena, depth);
             }
         } else {
             for light in &scene.lights {
@@ -135,7 +154,7 @@ impl DirectLightingIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         // compute specular reflection direction _wi_ and BSDF value
@@ -180,7 +199,7 @@ impl DirectLightingIntegrator {
                     };
                     rd.differential = Some(diff);
                 }
-                f * self.li(&mut rd, scene, sampler, depth + 1)
+                f * self.li(&mut rd, scene, sampler, arena, depth + 1)
                     * Spectrum::new(vec3_abs_dot_nrm(&wi, &ns) / pdf)
             } else {
                 Spectrum::new(0.0)
@@ -195,7 +214,7 @@ impl DirectLightingIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         let wo: Vector3f = isect.wo;
@@ -247,7 +266,7 @@ impl DirectLightingIntegrator {
                     };
                     rd.differential = Some(diff);
                 }
-                f * self.li(&mut rd, scene, sampler, depth + 1)
+                f * self.li(&mut rd, scene, sampler, arena, depth + 1)
                     * Spectrum::new(vec3_abs_dot_nrm(&wi, &ns) / pdf)
             } else {
                 Spectrum::new(0.0)
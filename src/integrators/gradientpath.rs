@@ -0,0 +1,299 @@
+// std
+use std::sync::Arc;
+// others
+use log::info;
+// pbrt
+use crate::blockqueue::BlockQueue;
+use crate::core::camera::{Camera, CameraSample};
+use crate::core::film::{Film, FilmAov};
+use crate::core::geometry::pnt2_inside_exclusive;
+use crate::core::geometry::{Bounds2i, Point2f, Point2i, Ray, Vector2i};
+use crate::core::integrator::{budget_samples, is_render_cancelled, tile_shape, TileGrid};
+use crate::core::memory::MemoryArena;
+use crate::core::numa;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::poisson::{solve_screened_poisson, solve_screened_poisson_l1};
+use crate::core::progress::ProgressReporter;
+use crate::core::sampler::{pixel_seed, Sampler};
+use crate::core::scene::Scene;
+use crate::core::stats::StatCounter;
+use crate::integrators::path::PathIntegrator;
+use crate::samplers::random::RandomSampler;
+
+static SHIFTED_PATHS: StatCounter =
+    StatCounter::new("Gradient-Domain Path Tracing/Shifted paths traced");
+
+/// the neighbors a base path gets shifted to
+const SHIFTS: [Vector2i; 4] = [
+    Vector2i { x: 1, y: 0 },
+    Vector2i { x: -1, y: 0 },
+    Vector2i { x: 0, y: 1 },
+    Vector2i { x: 0, y: -1 },
+];
+/// enough for the reconstruction to converge for any *alpha* that
+/// makes sense
+const MAX_SOLVER_ITERATIONS: usize = 1000;
+
+/// How the image gets reconstructed (see **poisson**).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Reconstruction {
+    /// robust against outliers, but slightly biased
+    L1,
+    /// unbiased
+    L2,
+}
+
+/// Gradient-Domain Path Tracing
+pub struct GradientPathIntegrator {
+    /// traces the base and the shifted paths
+    pub path: PathIntegrator,
+    pub reconstruction: Reconstruction,
+    /// weight of the primal image in the reconstruction (see
+    /// **poisson**)
+    pub alpha: Float,
+    /// write the primal image and the gradients as AOVs
+    pub write_gradients: bool,
+}
+
+impl GradientPathIntegrator {
+    pub fn new(
+        path: PathIntegrator,
+        reconstruction: Reconstruction,
+        alpha: Float,
+        write_gradients: bool,
+    ) -> Self {
+        GradientPathIntegrator {
+            path,
+            reconstruction,
+            alpha,
+            write_gradients,
+        }
+    }
+    pub fn render(&mut self, scene: &Scene, num_threads: u8) {
+        self.path.preprocess(scene);
+        let film: Arc<Film> = self.get_camera().get_film();
+        let pixel_bounds: Bounds2i = film.cropped_pixel_bounds;
+        let grid: TileGrid = TileGrid::new(pixel_bounds, tile_shape());
+        let tile_size: Vector2i = grid.tile_size;
+        let num_cores = if num_threads == 0_u8 {
+            num_cpus::get()
+        } else {
+            num_threads as usize
+        };
+        info!("Rendering with {:?} thread(s) ...", num_cores);
+        let primal: FilmAov = film.make_aov("primal");
+        let dx: FilmAov = film.make_aov("dx");
+        let dy: FilmAov = film.make_aov("dy");
+        {
+            let block_queue = BlockQueue::new(
+                (
+                    (grid.n_tiles.x * tile_size.x) as u32,
+                    (grid.n_tiles.y * tile_size.y) as u32,
+                ),
+                (tile_size.x as u32, tile_size.y as u32),
+                (0, 0),
+            );
+            let bq = &block_queue;
+            let integrator = &self;
+            let grid = &grid;
+            let aovs: (&FilmAov, &FilmAov, &FilmAov) = (&primal, &dx, &dy);
+            crossbeam::scope(|scope| {
+                let (tile_tx, tile_rx) = crossbeam_channel::bounded(num_cores);
+                // spawn worker threads
+                for thread_index in 0..num_cores {
+                    let tile_tx = tile_tx.clone();
+                    scope.spawn(move |_| {
+                        numa::pin_render_thread(thread_index);
+                        let mut tile_sampler: Box<Sampler> =
+                            integrator.path.get_sampler().clone_with_seed(0_u64);
+                        let mut replay_sampler: Box<Sampler> = Box::new(Sampler::Random(
+                            RandomSampler::new(tile_sampler.get_samples_per_pixel()),
+                        ));
+                        let arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+                        while let Some((x, y)) = bq.next() {
+                            // stop taking tiles
+                            if is_render_cancelled() {
+                                break;
+                            }
+                            let tile: Point2i = Point2i {
+                                x: x as i32,
+                                y: y as i32,
+                            };
+                            integrator.render_tile(
+                                scene,
+                                grid.tile_bounds(tile),
+                                &mut tile_sampler,
+                                &mut replay_sampler,
+                                &arena,
+                                aovs,
+                            );
+                            tile_tx
+                                .send(())
+                                .unwrap_or_else(|_| panic!("Failed to send tile"));
+                        }
+                    });
+                }
+                // the channel closes when all workers are done
+                drop(tile_tx);
+                // spawn thread to report progress
+                scope.spawn(move |_| {
+                    let mut progress: ProgressReporter =
+                        ProgressReporter::new(bq.len() as u64, "Rendering", "tiles");
+                    for _ in tile_rx.iter() {
+                        progress.update(1);
+                    }
+                    progress.done();
+                });
+            })
+            .unwrap();
+        }
+        // reconstruct the image from the primal image and the gradients
+        let width: usize = (pixel_bounds.p_max.x - pixel_bounds.p_min.x) as usize;
+        let height: usize = (pixel_bounds.p_max.y - pixel_bounds.p_min.y) as usize;
+        info!(
+            "Reconstructing {}x{} image ({:?}, alpha = {}) ...",
+            width, height, self.reconstruction, self.alpha
+        );
+        let solve = match self.reconstruction {
+            Reconstruction::L1 => solve_screened_poisson_l1,
+            Reconstruction::L2 => solve_screened_poisson,
+        };
+        let primal_rgb: Vec<Float> = primal.rgb();
+        let dx_rgb: Vec<Float> = dx.rgb();
+        let dy_rgb: Vec<Float> = dy.rgb();
+        let channel = |rgb: &[Float], c: usize| -> Vec<Float> {
+            rgb.iter().skip(c).step_by(3).copied().collect()
+        };
+        let channels: Vec<Vec<Float>> = (0..3)
+            .map(|c| {
+                solve(
+                    width,
+                    height,
+                    &channel(&primal_rgb, c),
+                    &channel(&dx_rgb, c),
+                    &channel(&dy_rgb, c),
+                    self.alpha,
+                    MAX_SOLVER_ITERATIONS,
+                )
+            })
+            .collect();
+        let image: Vec<Spectrum> = (0..width * height)
+            .map(|i| {
+                // negative values are noise the gradients couldn't remove
+                let rgb: [Float; 3] = [
+                    channels[0][i].max(0.0 as Float),
+                    channels[1][i].max(0.0 as Float),
+                    channels[2][i].max(0.0 as Float),
+                ];
+                Spectrum::from_rgb(&rgb)
+            })
+            .collect();
+        film.set_image(&image);
+        film.write_image(1.0 as Float);
+        if self.write_gradients {
+            for aov in &[primal, dx, dy] {
+                film.write_aov(aov);
+            }
+        }
+    }
+    /// Traces for each sample of each pixel a base path and the same
+    /// path (with the same random numbers) shifted to the four
+    /// neighbors of the pixel. All paths contribute to the primal
+    /// image, and their differences to the gradients.
+    fn render_tile(
+        &self,
+        scene: &Scene,
+        tile_bounds: Bounds2i,
+        tile_sampler: &mut Sampler,
+        replay_sampler: &mut Sampler,
+        arena: &MemoryArena,
+        (primal, dx, dy): (&FilmAov, &FilmAov, &FilmAov),
+    ) {
+        let pixel_bounds: Bounds2i = primal.pixel_bounds;
+        let samples_per_pixel: i64 = budget_samples(tile_sampler.get_samples_per_pixel());
+        for pixel in &tile_bounds {
+            if is_render_cancelled() {
+                break;
+            }
+            tile_sampler.reseed(pixel_seed(pixel));
+            tile_sampler.start_pixel(pixel);
+            let mut done: bool = false;
+            while !done {
+                let camera_sample: CameraSample = tile_sampler.get_camera_sample(pixel);
+                // the random numbers of the paths get replayed for
+                // each shift
+                let seed: u64 = pixel_seed(pixel)
+                    ^ (tile_sampler.get_current_sample_number() as u64)
+                        .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                let base: Spectrum =
+                    self.radiance(scene, &camera_sample, pixel, seed, replay_sampler, arena);
+                primal.add(pixel, &base, 1.0 as Float);
+                for shift in SHIFTS.iter() {
+                    let neighbor: Point2i = pixel + *shift;
+                    if !pnt2_inside_exclusive(neighbor, &pixel_bounds) {
+                        continue;
+                    }
+                    let mut shifted_sample: CameraSample = camera_sample;
+                    shifted_sample.p_film = camera_sample.p_film
+                        + Point2f {
+                            x: shift.x as Float,
+                            y: shift.y as Float,
+                        };
+                    let shifted: Spectrum = self.radiance(
+                        scene,
+                        &shifted_sample,
+                        neighbor,
+                        seed,
+                        replay_sampler,
+                        arena,
+                    );
+                    SHIFTED_PATHS.inc();
+                    primal.add(neighbor, &shifted, 1.0 as Float);
+                    // the gradient from the left (upper) to the right
+                    // (lower) pixel
+                    match (shift.x, shift.y) {
+                        (1, 0) => dx.add(pixel, &(shifted - base), 1.0 as Float),
+                        (-1, 0) => dx.add(neighbor, &(base - shifted), 1.0 as Float),
+                        (0, 1) => dy.add(pixel, &(shifted - base), 1.0 as Float),
+                        _ => dy.add(neighbor, &(base - shifted), 1.0 as Float),
+                    }
+                }
+                done = !tile_sampler.start_next_sample()
+                    || tile_sampler.get_current_sample_number() >= samples_per_pixel;
+            }
+        }
+    }
+    /// The radiance arriving at *camera_sample* (weighted by the
+    /// camera), with the random numbers for the path from *seed*.
+    fn radiance(
+        &self,
+        scene: &Scene,
+        camera_sample: &CameraSample,
+        pixel: Point2i,
+        seed: u64,
+        replay_sampler: &mut Sampler,
+        arena: &MemoryArena,
+    ) -> Spectrum {
+        let camera: Arc<Camera> = self.get_camera();
+        let mut ray: Ray = Ray::default();
+        let ray_weight: Float = camera.generate_ray_differential(camera_sample, &mut ray);
+        if ray_weight <= 0.0 as Float {
+            return Spectrum::default();
+        }
+        ray.scale_differentials(
+            1.0 as Float / (replay_sampler.get_samples_per_pixel() as Float).sqrt(),
+        );
+        replay_sampler.start_pixel(pixel);
+        replay_sampler.reseed(seed);
+        let l: Spectrum = self.path.li(&ray, scene, replay_sampler, arena, 0_i32) * ray_weight;
+        if l.has_nans() || l.has_infs() {
+            // like the other integrators, ignore the sample
+            Spectrum::default()
+        } else {
+            l
+        }
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        self.path.get_camera()
+    }
+}
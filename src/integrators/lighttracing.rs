@@ -0,0 +1,241 @@
+// std
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+// others
+use log::info;
+// pbrt
+use crate::blockqueue::BlockQueue;
+use crate::core::camera::Camera;
+use crate::core::film::{Film, FilmTile};
+use crate::core::geometry::{bnd2_intersect_bnd2, pnt2_inside_exclusive};
+use crate::core::geometry::{Bounds2i, Point2f, Point2i, Vector2i};
+use crate::core::integrator::{
+    budget_samples, compute_light_power_distribution, is_render_cancelled, splat_scale, tile_shape,
+    OrderedMerge, TileGrid,
+};
+use crate::core::numa;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
+use crate::core::sampler::{pixel_seed, Sampler};
+use crate::core::sampling::Distribution1D;
+use crate::core::scene::Scene;
+use crate::core::stats::StatCounter;
+use crate::integrators::bdpt::{
+    connect_to_camera, generate_camera_subpath, generate_light_subpath, Vertex,
+};
+
+static LIGHT_PATHS: StatCounter = StatCounter::new("Light Tracing/Light paths traced");
+static CAMERA_SPLATS: StatCounter = StatCounter::new("Light Tracing/Splats onto the film");
+
+/// Light Tracing (Particle Tracing)
+pub struct LightTracingIntegrator {
+    pub camera: Arc<Camera>,
+    pub sampler: Box<Sampler>,
+    pub pixel_bounds: Bounds2i,
+    pub max_depth: u32,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
+}
+
+impl LightTracingIntegrator {
+    pub fn new(
+        camera: Arc<Camera>,
+        sampler: Box<Sampler>,
+        pixel_bounds: Bounds2i,
+        max_depth: u32,
+    ) -> Self {
+        LightTracingIntegrator {
+            camera,
+            sampler,
+            pixel_bounds,
+            max_depth,
+            terminator_shadowing: false,
+        }
+    }
+    pub fn render(&self, scene: &Scene, num_threads: u8) {
+        let film: Arc<Film> = self.get_camera().get_film();
+        let light_distr: Arc<Distribution1D> = match compute_light_power_distribution(scene) {
+            Some(light_distr) => light_distr,
+            None => {
+                // nothing to trace, write a black image
+                film.write_image(1.0 as Float);
+                return;
+            }
+        };
+        let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), tile_shape());
+        let tile_size: Vector2i = grid.tile_size;
+        let samples_per_pixel: i64 = budget_samples(self.sampler.get_samples_per_pixel());
+        // the light paths traced, one for each camera sample
+        let samples_taken: AtomicU64 = AtomicU64::new(0);
+        let num_cores = if num_threads == 0_u8 {
+            num_cpus::get()
+        } else {
+            num_threads as usize
+        };
+        info!("Rendering with {:?} thread(s) ...", num_cores);
+        {
+            let block_queue = BlockQueue::new(
+                (
+                    (grid.n_tiles.x * tile_size.x) as u32,
+                    (grid.n_tiles.y * tile_size.y) as u32,
+                ),
+                (tile_size.x as u32, tile_size.y as u32),
+                (0, 0),
+            );
+            let bq = &block_queue;
+            let integrator = &self;
+            let grid = &grid;
+            let film = &film;
+            let light_distr = &light_distr;
+            let samples_taken = &samples_taken;
+            crossbeam::scope(|scope| {
+                let (tile_tx, tile_rx) = crossbeam_channel::bounded(num_cores);
+                // spawn worker threads
+                for thread_index in 0..num_cores {
+                    let tile_tx = tile_tx.clone();
+                    scope.spawn(move |_| {
+                        numa::pin_render_thread(thread_index);
+                        let mut tile_sampler: Box<Sampler> =
+                            integrator.sampler.clone_with_seed(0_u64);
+                        while let Some((position, (x, y))) = bq.next_with_position() {
+                            // stop taking tiles, the rendered ones get merged
+                            if is_render_cancelled() {
+                                break;
+                            }
+                            let tile: Point2i = Point2i {
+                                x: x as i32,
+                                y: y as i32,
+                            };
+                            let tile_bounds: Bounds2i = grid.tile_bounds(tile);
+                            let mut film_tile: FilmTile = film.get_film_tile(&tile_bounds);
+                            let samples: u64 = integrator.render_tile(
+                                scene,
+                                tile_bounds,
+                                light_distr,
+                                &mut tile_sampler,
+                                samples_per_pixel,
+                                &mut film_tile,
+                            );
+                            samples_taken.fetch_add(samples, Ordering::Relaxed);
+                            tile_tx
+                                .send((position, film_tile))
+                                .unwrap_or_else(|_| panic!("Failed to send tile"));
+                        }
+                    });
+                }
+                // the channel closes when all workers are done
+                drop(tile_tx);
+                // spawn thread to collect pixels and render image to file
+                scope.spawn(move |_| {
+                    let mut progress: ProgressReporter =
+                        ProgressReporter::new(bq.len() as u64, "Rendering", "tiles");
+                    let mut ordered: OrderedMerge<FilmTile> = OrderedMerge::default();
+                    let mut merge = |film_tile: FilmTile| {
+                        // merge image tile into _Film_
+                        film.merge_film_tile(&film_tile);
+                        progress.update(1);
+                    };
+                    for (position, film_tile) in tile_rx.iter() {
+                        ordered.add(position, film_tile, &mut merge);
+                    }
+                    ordered.finish(&mut merge);
+                    progress.done();
+                });
+            })
+            .unwrap();
+        }
+        // a cancelled render traced fewer light paths
+        let scale: Float = if is_render_cancelled() {
+            let n_pixels: i32 =
+                bnd2_intersect_bnd2(&film.get_sample_bounds(), &self.pixel_bounds).area();
+            splat_scale(n_pixels as i64, samples_taken.load(Ordering::Relaxed))
+        } else {
+            1.0 as Float / samples_per_pixel as Float
+        };
+        film.write_image(scale);
+    }
+    /// Traces for each sample of each pixel a camera ray, which only
+    /// picks up the light of directly visible emitters, and a path
+    /// starting at a light. Each vertex of the light path gets
+    /// connected to a point on the camera and its contribution
+    /// splatted wherever it is seen (kept in *film_tile* until it gets
+    /// merged, see **FilmTile::add_splat()**). Takes at most
+    /// *samples_per_pixel* samples and returns how many it took.
+    fn render_tile(
+        &self,
+        scene: &Scene,
+        tile_bounds: Bounds2i,
+        light_distr: &Arc<Distribution1D>,
+        tile_sampler: &mut Sampler,
+        samples_per_pixel: i64,
+        film_tile: &mut FilmTile,
+    ) -> u64 {
+        let camera: &Arc<Camera> = &self.camera;
+        let mut samples: u64 = 0;
+        for p_pixel in &tile_bounds {
+            if is_render_cancelled() {
+                break;
+            }
+            tile_sampler.reseed(pixel_seed(p_pixel));
+            tile_sampler.start_pixel(p_pixel);
+            if !pnt2_inside_exclusive(p_pixel, &self.pixel_bounds) {
+                continue;
+            }
+            let mut done: bool = false;
+            while !done {
+                let p_film: Point2f = Point2f {
+                    x: p_pixel.x as Float,
+                    y: p_pixel.y as Float,
+                } + tile_sampler.get_2d();
+                // emitters seen by the camera
+                let mut camera_vertices: Vec<Vertex> = Vec::with_capacity(2);
+                let (n_camera, _p, time) = generate_camera_subpath(
+                    scene,
+                    tile_sampler,
+                    2,
+                    camera,
+                    p_film,
+                    self.terminator_shadowing,
+                    &mut camera_vertices,
+                );
+                let mut l: Spectrum = Spectrum::default();
+                if n_camera == 2 && camera_vertices[1].is_light() {
+                    l = camera_vertices[1].le(scene, &camera_vertices[0])
+                        * camera_vertices[1].beta();
+                }
+                film_tile.add_sample(p_film, &mut l, 1.0 as Float);
+                // everything else from the light path
+                let mut light_vertices: Vec<Vertex> =
+                    Vec::with_capacity((self.max_depth + 1) as usize);
+                let n_light: usize = generate_light_subpath(
+                    scene,
+                    tile_sampler,
+                    self.max_depth + 1,
+                    time,
+                    light_distr.clone(),
+                    self.terminator_shadowing,
+                    &mut light_vertices,
+                );
+                LIGHT_PATHS.inc();
+                // the light itself (s = 1) is seen by the camera ray
+                for vertex in light_vertices.iter().take(n_light).skip(1) {
+                    let mut p_raster: Point2f = Point2f::default();
+                    let (l_light, _sampled) =
+                        connect_to_camera(scene, vertex, camera, tile_sampler, &mut p_raster);
+                    if !l_light.is_black() {
+                        film_tile.add_splat(p_raster, &l_light);
+                        CAMERA_SPLATS.inc();
+                    }
+                }
+                samples += 1;
+                done = !tile_sampler.start_next_sample()
+                    || tile_sampler.get_current_sample_number() >= samples_per_pixel;
+            }
+        }
+        samples
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        self.camera.clone()
+    }
+}
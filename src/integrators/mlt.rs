@@ -1,24 +1,33 @@
 // std
 use std::ops::DerefMut;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 // pbrt
 use crate::core::camera::Camera;
 use crate::core::film::Film;
 use crate::core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i};
-use crate::core::integrator::compute_light_power_distribution;
+use crate::core::integrator::{
+    budget_samples, compute_light_power_distribution, is_render_cancelled, splat_scale,
+    OrderedMerge,
+};
 use crate::core::pbrt::erf_inv;
 use crate::core::pbrt::SQRT_2;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
 use crate::core::rng::Rng;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::StatPercent;
 use crate::integrators::bdpt::Vertex;
 use crate::integrators::bdpt::{connect_bdpt, generate_camera_subpath, generate_light_subpath};
 // others
+use log::info;
 use rayon::prelude::*;
 
+static ACCEPTANCE_RATE: StatPercent = StatPercent::new("Integrator/Acceptance rate");
+
 pub const CAMERA_STREAM_INDEX: u8 = 0;
 pub const LIGHT_STREAM_INDEX: u8 = 1;
 pub const CONNECTION_STREAM_INDEX: u8 = 2;
@@ -317,6 +326,9 @@ pub struct MLTIntegrator {
     pub mutations_per_pixel: u32,
     pub sigma: Float,
     pub large_step_probability: Float,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl MLTIntegrator {
@@ -337,6 +349,7 @@ impl MLTIntegrator {
             mutations_per_pixel,
             sigma,
             large_step_probability,
+            terminator_shadowing: false,
         }
     }
     pub fn l(
@@ -388,6 +401,7 @@ impl MLTIntegrator {
                 t,
                 &self.camera,
                 *p_raster,
+                self.terminator_shadowing,
                 &mut camera_vertices,
             );
             n_camera = n_camera_new;
@@ -411,6 +425,7 @@ impl MLTIntegrator {
                 time,
                 light_distr.clone(),
                 // light_to_index,
+                self.terminator_shadowing,
                 &mut light_vertices,
             );
         }
@@ -445,14 +460,13 @@ impl MLTIntegrator {
             num_threads as usize
         };
         if let Some(light_distr) = compute_light_power_distribution(scene) {
-            println!("Generating bootstrap paths ...");
+            info!("Generating bootstrap paths ...");
             // generate bootstrap samples and compute normalization constant $b$
             num_cores = 1; // TMP: disable multi-threading
             let n_bootstrap_samples: u32 = self.n_bootstrap * (self.max_depth + 1);
             let mut bootstrap_weights: Vec<Float> =
                 vec![0.0 as Float; n_bootstrap_samples as usize];
             if !scene.lights.is_empty() {
-                // TODO: ProgressReporter progress(nBootstrap / 256, "Generating bootstrap paths");
                 // let chunk_size: u32 = clamp_t(integrator.n_bootstrap / 128, 1, 8192);
                 let chunk_size: usize = (n_bootstrap_samples / num_cores as u32) as usize;
                 {
@@ -497,9 +511,16 @@ impl MLTIntegrator {
                         }
                         // spawn thread to report progress
                         scope.spawn(move |_| {
-                            for _ in pbr::PbIter::new(0..num_cores) {
+                            let mut progress: ProgressReporter = ProgressReporter::new(
+                                num_cores as u64,
+                                "Generating bootstrap paths",
+                                "bands",
+                            );
+                            for _ in 0..num_cores {
                                 band_rx.recv().unwrap();
+                                progress.update(1);
                             }
+                            progress.done();
                         });
                     })
                     .unwrap();
@@ -510,25 +531,36 @@ impl MLTIntegrator {
             // run _n_chains_ Markov chains in parallel
             num_cores = num_cores_init; // TMP: re-enable multi-threading
             let film: Arc<Film> = self.get_camera().get_film();
+            let mutations_per_pixel: i64 = budget_samples(self.mutations_per_pixel as i64);
             let n_total_mutations: u64 =
-                self.mutations_per_pixel as u64 * film.get_sample_bounds().area() as u64;
+                mutations_per_pixel as u64 * film.get_sample_bounds().area() as u64;
+            // fewer if the render gets cancelled
+            let mutations_done: AtomicU64 = AtomicU64::new(0);
             if !scene.lights.is_empty() {
-                // TODO: let progress_frequency = 32768;
-                // TODO: ProgressReporter progress(nTotalMutations / progressFrequency,
-                //                           "Rendering");
                 // use parallel iterator (par_iter_with) from rayon crate
                 let (sender, receiver) = crossbeam_channel::bounded(num_cores);
                 let n_chains = self.n_chains;
-                // spawn thread to report progress
+                let chain_film: Arc<Film> = film.clone();
+                // spawn thread to add the splats of the chains in chain
+                // order (for any number of threads) and report progress
                 let finish = thread::spawn(move || {
-                    for _ in pbr::PbIter::new(0..n_chains) {
-                        receiver.recv().unwrap();
+                    let mut progress: ProgressReporter =
+                        ProgressReporter::new(n_chains as u64, "Rendering", "chains");
+                    let mut ordered: OrderedMerge<Vec<(Point2f, Spectrum)>> =
+                        OrderedMerge::default();
+                    let mut merge = |splats: Vec<(Point2f, Spectrum)>| {
+                        chain_film.add_splats(&splats);
+                        progress.update(1);
+                    };
+                    for (i, splats) in receiver.iter() {
+                        ordered.add(i as usize, splats, &mut merge);
                     }
+                    ordered.finish(&mut merge);
+                    progress.done();
                 });
                 // for i in 0..n_chains {
                 let ivec: Vec<u32> = (0..n_chains).collect();
                 ivec.par_iter().for_each_with(sender, |s, &i| {
-                    s.send(i).unwrap_or_else(|_| panic!("Failed to send chain"));
                     let n_chain_mutations: u64 = ((i as u64 + 1) * n_total_mutations
                         / n_chains as u64)
                         .min(n_total_mutations)
@@ -556,7 +588,14 @@ impl MLTIntegrator {
                         &mut p_current,
                     );
                     // run the Markov chain for _n_chain_mutations_ steps
+                    let mut mutations: u64 = 0;
+                    let mut splats: Vec<(Point2f, Spectrum)> =
+                        Vec::with_capacity(2 * n_chain_mutations as usize);
                     for _j in 0..n_chain_mutations {
+                        if is_render_cancelled() {
+                            break;
+                        }
+                        mutations += 1;
                         match sampler.deref_mut() {
                             Sampler::MLT(mlt_sampler) => mlt_sampler.start_iteration(),
                             _ => panic!("MLTSampler needed."),
@@ -573,12 +612,12 @@ impl MLTIntegrator {
                         let accept: Float = (1.0 as Float).min(l_proposed.y() / l_current.y());
                         // splat both current and proposed samples to _film_
                         if accept > 0.0 as Float {
-                            film.add_splat(p_proposed, &(l_proposed * accept / l_proposed.y()));
+                            splats.push((p_proposed, l_proposed * accept / l_proposed.y()));
                         }
-                        film.add_splat(
+                        splats.push((
                             p_current,
-                            &(l_current * (1.0 as Float - accept) / l_current.y()),
-                        );
+                            l_current * (1.0 as Float - accept) / l_current.y(),
+                        ));
                         // accept or reject the proposal
                         if rng.uniform_float() < accept {
                             p_current = p_proposed;
@@ -587,24 +626,36 @@ impl MLTIntegrator {
                                 Sampler::MLT(mlt_sampler) => mlt_sampler.accept(),
                                 _ => panic!("MLTSampler needed."),
                             }
-                        // TODO: ++acceptedMutations;
+                            ACCEPTANCE_RATE.inc_num();
                         } else {
                             match sampler.deref_mut() {
                                 Sampler::MLT(mlt_sampler) => mlt_sampler.reject(),
                                 _ => panic!("MLTSampler needed."),
                             }
                         }
-                        // TODO: ++totalMutations;
+                        ACCEPTANCE_RATE.inc_denom();
                         // if (i * n_total_mutations / n_chains + j) % progress_frequency == 0 {
                         //     progress.update();
                         // }
                         // TODO: arena.Reset();
                     }
+                    mutations_done.fetch_add(mutations, Ordering::Relaxed);
+                    s.send((i, splats))
+                        .unwrap_or_else(|_| panic!("Failed to send chain"));
                 });
                 finish.join().unwrap();
             }
             // Store final image computed with MLT
-            film.write_image(b / self.mutations_per_pixel as Float);
+            // a cancelled render made fewer mutations
+            let scale: Float = if is_render_cancelled() {
+                b * splat_scale(
+                    film.get_sample_bounds().area() as i64,
+                    mutations_done.load(Ordering::Relaxed),
+                )
+            } else {
+                b / mutations_per_pixel as Float
+            };
+            film.write_image(scale);
         }
     }
     pub fn get_camera(&self) -> Arc<Camera> {
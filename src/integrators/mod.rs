@@ -4,8 +4,11 @@
 //! - AOIntegrator
 //! - BDPTIntegrator
 //! - DirectLightingIntegrator
+//! - GradientPathIntegrator
+//! - LightTracingIntegrator
 //! - MLTIntegrator
 //! - PathIntegrator
+//! - PhotonMapIntegrator
 //! - SPPMIntegrator
 //! - VolPathIntegrator
 //! - WhittedIntegrator
@@ -19,6 +22,10 @@
 //! points surrounded by a large amount of geometry are rendered dark,
 //! whereas points with little geometry on the visible hemisphere
 //! appear light.
+//! The **nsamples** rays per hit point are distributed uniformly or
+//! (with **cossample**, the default) cosine-weighted over the
+//! hemisphere, and with **maxdistance** only geometry closer than that
+//! occludes.
 //!
 //! ![Ambient Occlusion](/doc/img/cornell_box_pbrt_rust_ao.png)
 //!
@@ -37,8 +44,46 @@
 //! Path tracing incrementally generates paths of scattering events
 //! starting at the camera and ending at light sources in the scene.
 //!
+//! Fireflies, e.g. of light reaching a diffuse surface via glass or a
+//! mirror and being seen via glass again, can be suppressed (at the
+//! price of some bias) by two options of **path** and **volpath**:
+//! **clampindirect** limits each component of the light picked up
+//! after the first bounce, and with **roughening** (e.g. 0.2) each
+//! BSDF after a diffuse or glossy bounce gets at least that fraction
+//! of the roughness of the roughest one before, which turns sharp
+//! glossy and specular reflections into blurry ones where the noise
+//! would be worst.
+//!
+//! Smooth shading normals of low-poly meshes (or strong bump maps)
+//! cause hard shadow terminators. With **terminatorshadowing** (of
+//! any integrator) the BSDFs soften them, see
+//! **Bsdf::terminator_shadowing()**.
+//!
+//! With **irradiancecache** the **path** integrator ends paths at
+//! diffuse surfaces after the first bounce and takes their indirect
+//! light from an **IrradianceCache**, which computes it with
+//! **irradiancecachesamples** rays at a few points and interpolates
+//! it in between (using records up to **irradiancecacheerror** times
+//! their distance to the surrounding geometry away). This makes
+//! scenes lit mostly indirectly, like interiors, much faster to
+//! render with little noise.
+//!
 //! ![Path Tracing](/doc/img/cornell_box_pbrt_rust_path.png)
 //!
+//! ## Gradient-Domain Path Tracing
+//!
+//! The **GradientPathIntegrator** traces, for each sample, a path
+//! like the path tracer and the same path (replaying its random
+//! numbers) through each of the four neighboring pixels. Besides a
+//! (primal) image of all paths, this estimates the differences
+//! between neighboring pixels, which are much less noisy than the
+//! pixels themselves. The final image gets reconstructed from both by
+//! a screened Poisson solver (see **poisson**, **reconstruction** "L1"
+//! by default or "L2", with the weight **reconstructionalpha** of the
+//! primal image), and with **writegradients** the primal image and
+//! the gradients get written as AOVs. The pixel filter of
+//! the film isn't used, each path only contributes to its pixel.
+//!
 //! ## Bidirectional Path Tracing (BDPT)
 //!
 //! Bidirectional path tracing is a generalization of the standard
@@ -50,6 +95,18 @@
 //! ![Bidirectional Path
 //! Tracing](/doc/img/art_gallery_pbrt_rust_bdpt.png)
 //!
+//! ## Light Tracing
+//!
+//! The **LightTracingIntegrator** is the adjoint of the path tracer:
+//! it traces paths starting at the lights (one per camera sample) and
+//! connects each vertex to a point on the camera, adding its
+//! contribution to the pixel it is seen in (see **Film::add_splat()**).
+//! Only the emitters seen directly are found by camera rays. This
+//! renders caustics, which are seen via diffuse surfaces, well, but
+//! not anything seen via glass or mirrors, and it needs a
+//! **perspective** camera. It is the light subpath (with the $t=1$
+//! strategy) of **bdpt** on its own.
+//!
 //! ## Stochastic Progressive Photon Mapping (SPPM)
 //!
 //! A photon mapping integrator that uses particles to estimate
@@ -57,12 +114,43 @@
 //! particles close to but not quite at the point being shaded.
 //!
 //! ![Stochastic Progressive Photon Mapping](/doc/img/caustic_glass_pbrt_rust_sppm.png)
+//!
+//! ## Photon Mapping
+//!
+//! The **PhotonMapIntegrator** is the classic two-pass photon mapping
+//! algorithm: it first traces **photons** paths from the lights and
+//! stores the photons hitting diffuse or glossy surfaces in a global
+//! photon map, and those of **causticphotons** other paths which got
+//! there via specular surfaces only in a caustic photon map (both
+//! kd-trees). Camera rays follow specular surfaces, then direct
+//! lighting gets sampled, caustics come from the **lookupphotons**
+//! nearest photons (within **radius**, by default 2% of the scene
+//! size) of the caustic map, and the indirect illumination from a
+//! final gather (**finalgathersamples** rays looking up the global
+//! map where they hit). Without **finalgather** the global map gets
+//! looked up directly, which is even faster but blotchy. Unlike
+//! **sppm** the result doesn't converge to the correct image with
+//! more samples, but it is smooth after a few, which makes it useful
+//! for previews.
+//!
+//! ## Volumetric Path Tracing
+//!
+//! The **VolPathIntegrator** extends path tracing to participating
+//! media: it samples scattering events inside media attached to
+//! shapes, lights, and the camera (see **MediumInterface**), samples
+//! direct lighting from there, and accounts for the transmittance
+//! along shadow rays. Besides **volpath** only **bdpt**,
+//! **lighttracing**, and **mlt** handle media, the other integrators
+//! ignore them (with a warning).
 
 pub mod ao;
 pub mod bdpt;
 pub mod directlighting;
+pub mod gradientpath;
+pub mod lighttracing;
 pub mod mlt;
 pub mod path;
+pub mod photonmap;
 pub mod sppm;
 pub mod volpath;
 pub mod whitted;
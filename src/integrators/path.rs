@@ -1,35 +1,133 @@
 // std
 use std::borrow::Borrow;
 use std::sync::Arc;
+// others
+use log::debug;
 // pbrt
 // use crate::core::bssrdf::Bssrdf;
 use crate::core::camera::Camera;
-use crate::core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
-use crate::core::geometry::{Bounds2i, Point2f, Ray, Vector3f};
-use crate::core::integrator::uniform_sample_one_light;
+use crate::core::geometry::{nrm_faceforward_vec3, pnt3_distance, vec3_abs_dot_nrm, vec3_dot_nrm};
+use crate::core::geometry::{Bounds2i, Normal3f, Point2f, Ray, Vector3f};
+use crate::core::integrator::{
+    clamp_indirect, record_features, record_light, report_non_finite, sample_one_light,
+};
+use crate::core::integrator::{first_intersection, FirstHit};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
+use crate::core::irradiancecache::IrradianceCache;
 use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::lightdistrib::LightDistribution;
 use crate::core::material::TransportMode;
+use crate::core::medium::{InteriorList, Medium};
+use crate::core::memory::MemoryArena;
+use crate::core::pathdebug::PathEvent;
+use crate::core::pathdebug::{is_recording, lobe_name, nrm, pnt3, record_event, rgb, vec3};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::reflection::BxdfType;
+#[cfg(feature = "polarization")]
+use crate::core::polarization::{specular_mueller, MuellerMatrix, PolarizationState};
+use crate::core::reflection::{BxdfType, ChannelShift};
+use crate::core::rng::Rng;
 use crate::core::sampler::Sampler;
-use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{StatIntDistribution, StatPercent};
+use crate::samplers::random::RandomSampler;
 
 // see path.h
 
+static ZERO_RADIANCE_PATHS: StatPercent = StatPercent::new("Integrator/Zero-radiance paths");
+static PATH_LENGTH: StatIntDistribution = StatIntDistribution::new("Integrator/Path length");
+
+/// The kinds of bounces along a path which have a depth limit of
+/// their own (see **DepthLimits**).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BounceKind {
+    Diffuse,
+    Glossy,
+    Specular,
+    Transmission,
+}
+
+impl BounceKind {
+    /// The kind of a bounce sampled from a BSDF (see **BxdfType**),
+    /// glossy and specular transmission count as *Transmission*,
+    /// diffuse transmission (translucency) as *Diffuse*.
+    pub fn from_sampled_type(sampled_type: u8) -> Self {
+        if (sampled_type & BxdfType::BsdfDiffuse as u8) != 0_u8 {
+            BounceKind::Diffuse
+        } else if (sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8 {
+            BounceKind::Transmission
+        } else if (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8 {
+            BounceKind::Specular
+        } else {
+            BounceKind::Glossy
+        }
+    }
+}
+
+/// How many bounces of each kind (see **BounceKind**) a path can take
+/// (like Arnold's *GI_diffuse_depth* etc.), on top of *max_depth* for
+/// all bounces. Surfaces still get direct light once a limit was
+/// reached, e.g. with *diffuse* 0 diffuse surfaces get no indirect
+/// light, but glass can still be traced through many times.
+#[derive(Debug, Copy, Clone)]
+pub struct DepthLimits {
+    pub diffuse: u32,
+    pub glossy: u32,
+    pub specular: u32,
+    pub transmission: u32,
+}
+
+impl DepthLimits {
+    /// The same limit for all kinds of bounces, with *max_depth* (for
+    /// all bounces) no limit at all.
+    pub fn new(depth: u32) -> Self {
+        DepthLimits {
+            diffuse: depth,
+            glossy: depth,
+            specular: depth,
+            transmission: depth,
+        }
+    }
+    pub fn limit(&self, kind: BounceKind) -> u32 {
+        match kind {
+            BounceKind::Diffuse => self.diffuse,
+            BounceKind::Glossy => self.glossy,
+            BounceKind::Specular => self.specular,
+            BounceKind::Transmission => self.transmission,
+        }
+    }
+}
+
 /// Path Tracing (Global Illumination)
 pub struct PathIntegrator {
     // inherited from SamplerIntegrator (see integrator.h)
     pub camera: Arc<Camera>,
     pub sampler: Box<Sampler>,
     pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
     // see path.h
     max_depth: u32,
     rr_threshold: Float,           // 1.0
     light_sample_strategy: String, // "spatial"
     light_distribution: Option<Arc<LightDistribution>>,
+    /// bounces of each kind a path can take (see **DepthLimits**)
+    pub depth_limits: DepthLimits,
+    /// maximum component of light picked up after the first bounce
+    /// (0 for no clamping, see **clamp_indirect()**)
+    pub clamp_indirect: Float,
+    /// after diffuse or glossy bounces, BSDFs get at least that
+    /// fraction of the roughest one so far (see **Bsdf::roughen()**)
+    pub roughening: Float,
+    /// interpolates the indirect light at diffuse surfaces after the
+    /// first bounce instead of following the path further
+    pub irradiance_cache: Option<IrradianceCache>,
+    /// angle (radians) of a linear polarizer in front of the camera
+    #[cfg(feature = "polarization")]
+    pub polarizer: Option<Float>,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl PathIntegrator {
@@ -45,24 +143,65 @@ impl PathIntegrator {
             camera,
             sampler,
             pixel_bounds,
+            debug_non_finite: false,
             max_depth,
             rr_threshold,
             light_sample_strategy,
             light_distribution: None,
+            depth_limits: DepthLimits::new(max_depth),
+            clamp_indirect: 0.0 as Float,
+            roughening: 0.0 as Float,
+            irradiance_cache: None,
+            #[cfg(feature = "polarization")]
+            polarizer: None,
+            terminator_shadowing: false,
         }
     }
     pub fn preprocess(&mut self, scene: &Scene) {
         self.light_distribution =
             create_light_sample_distribution(self.light_sample_strategy.clone(), scene);
+        if let Some(ref mut irradiance_cache) = self.irradiance_cache {
+            irradiance_cache.reset(&scene.world_bound());
+        }
     }
     pub fn li(
         &self,
         r: &Ray,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        _arena: &MemoryArena,
         _depth: i32,
     ) -> Spectrum {
+        self.li_from(r, None, scene, sampler, 0_u32, true).0
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        r: &Ray,
+        first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        _arena: &MemoryArena,
+        _depth: i32,
+    ) -> Spectrum {
+        self.li_from(r, first_hit, scene, sampler, 0_u32, true).0
+    }
+    /// The radiance along a ray which continues a path after
+    /// *first_bounce* bounces (emitted light at the first surface
+    /// only counts for camera rays), and the distance to the first
+    /// surface hit (infinite if none). With *use_cache* the path ends
+    /// at a diffuse surface after the first bounce, with the indirect
+    /// light from the **IrradianceCache**.
+    fn li_from(
+        &self,
+        r: &Ray,
+        mut first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        first_bounce: u32,
+        use_cache: bool,
+    ) -> (Spectrum, Float) {
         // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut beta: Spectrum = Spectrum::new(1.0 as Float);
@@ -75,7 +214,13 @@ impl PathIntegrator {
             medium: r.medium.clone(),
         };
         let mut specular_bounce: bool = false;
-        let mut bounces: u32 = 0_u32;
+        let mut bounces: u32 = first_bounce;
+        // bounces of each kind (see **BounceKind**)
+        let mut kind_bounces: [u32; 4] = [0_u32; 4];
+        let mut hit_distance: Float = Float::INFINITY;
+        // denoiser features come from the first vertex which isn't
+        // perfectly specular (camera paths only)
+        let mut features_recorded: bool = first_bounce != 0;
         // Added after book publication: etaScale tracks the
         // accumulated effect of radiance scaling due to rays passing
         // through refractive boundaries (see the derivation on p. 527
@@ -85,18 +230,70 @@ impl PathIntegrator {
         // refracted rays that are about to be refracted back out of a
         // medium and thus have their beta value increased.
         let mut eta_scale: Float = 1.0;
+        // roughest BSDF lobe sampled so far (for path roughening)
+        let mut path_roughness: Float = 0.0 as Float;
+        // prioritized primitives the path is inside of
+        let mut interior: InteriorList = InteriorList::default();
+        // set by fluorescent surfaces
+        let mut channel_shift: Option<ChannelShift> = None;
+        #[cfg(feature = "polarization")]
+        let mut polarization: PolarizationState = {
+            let x_axis: Vector3f = self.camera.get_camera_to_world().transform_vector(
+                ray.time,
+                &Vector3f {
+                    x: 1.0 as Float,
+                    y: 0.0 as Float,
+                    z: 0.0 as Float,
+                },
+            );
+            let polarizer: Option<Float> = if first_bounce == 0 {
+                self.polarizer
+            } else {
+                None
+            };
+            let (state, throughput) = PolarizationState::new(&ray.d, &x_axis, polarizer);
+            beta = beta * throughput;
+            state
+        };
         loop {
             // find next path vertex and accumulate contribution
             // println!("Path tracer bounce {:?}, current L = {:?}, beta = {:?}",
             //          bounces, l, beta);
             // intersect _ray_ with scene and store intersection in _isect_
-            let mut isect: SurfaceInteraction = SurfaceInteraction::default();
-            if scene.intersect(&mut ray, &mut isect) {
+            let (found, mut isect): (bool, SurfaceInteraction) =
+                first_intersection(scene, &mut ray, first_hit.take());
+            if found {
+                // skip surfaces inside primitives with a higher priority
+                if interior.is_false_intersection(&isect) {
+                    isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+                    let medium: Option<Arc<Medium>> = ray.medium.clone();
+                    ray = isect.spawn_ray(&ray.d);
+                    interior.cross(&isect, &mut ray, medium);
+                    continue;
+                }
+                isect.eta_outside = interior.eta_outside(&isect);
+                if is_recording() {
+                    record_event(PathEvent::Hit {
+                        depth: bounces,
+                        p: pnt3(&isect.p),
+                        n: nrm(&isect.n),
+                        shading_n: nrm(&isect.shading.n),
+                    });
+                }
                 // possibly add emitted light at intersection
                 if bounces == 0 || specular_bounce {
                     // add emitted light at path vertex
-                    l += beta * isect.le(&-ray.d);
+                    let le: Spectrum =
+                        clamp_indirect(beta * isect.le(&-ray.d), bounces, self.clamp_indirect);
+                    l += le;
+                    record_light(bounces, &le);
                     // println!("Added Le -> L = {:?}", l);
+                    if is_recording() && !le.is_black() {
+                        record_event(PathEvent::Emission {
+                            depth: bounces,
+                            l: rgb(&le),
+                        });
+                    }
                 }
                 // terminate path if _maxDepth_ was reached
                 if bounces >= self.max_depth {
@@ -105,37 +302,108 @@ impl PathIntegrator {
                 // compute scattering functions and skip over medium boundaries
                 let mode: TransportMode = TransportMode::Radiance;
                 isect.compute_scattering_functions(&ray, true, mode);
-                if let Some(ref _bsdf) = isect.bsdf {
-                    // we are fine (for below)
+                if let Some(ref mut bsdf) = isect.bsdf {
+                    // dispersed light keeps its wavelength
+                    bsdf.set_dispersion_channel(&beta);
+                    bsdf.roughen(self.roughening * path_roughness);
+                    bsdf.shadow_terminator = self.terminator_shadowing;
+                    if bsdf.reradiation.is_some() {
+                        // fluorescent surfaces shift light between wavelengths
+                        beta = bsdf.reradiate(&beta, &mut l, &mut channel_shift, sampler.get_1d());
+                    }
                 } else {
                     // TODO: println!("Skipping intersection due to null bsdf");
+                    let medium: Option<Arc<Medium>> = ray.medium.clone();
                     ray = isect.spawn_ray(&ray.d);
+                    interior.cross(&isect, &mut ray, medium);
                     // bounces--;
                     continue;
                 }
+                if bounces == first_bounce && hit_distance.is_infinite() {
+                    hit_distance = pnt3_distance(&r.o, &isect.p);
+                }
                 if let Some(ref light_distribution) = self.light_distribution {
-                    let distrib: Arc<Distribution1D> = light_distribution.lookup(&isect.p);
                     // Sample illumination from lights to find path contribution.
                     // (But skip this for perfectly specular BSDFs.)
                     let bsdf_flags: u8 = BxdfType::BsdfAll as u8 & !(BxdfType::BsdfSpecular as u8);
                     if let Some(ref bsdf) = isect.bsdf {
                         if bsdf.num_components(bsdf_flags) > 0 {
-                            // TODO: ++total_paths;
+                            ZERO_RADIANCE_PATHS.inc_denom();
                             let it: &SurfaceInteraction = isect.borrow();
-                            let ld: Spectrum = beta
-                                * uniform_sample_one_light(
+                            let ld: Spectrum = clamp_indirect(
+                                beta * sample_one_light(
                                     it,
                                     scene,
                                     sampler,
                                     false,
-                                    Some(&distrib),
-                                );
+                                    light_distribution,
+                                ),
+                                bounces,
+                                self.clamp_indirect,
+                            );
                             // TODO: println!("Sampled direct lighting Ld = {:?}", ld);
-                            // TODO: if ld.is_black() {
-                            //     ++zero_radiance_paths;
-                            // }
+                            if ld.is_black() {
+                                ZERO_RADIANCE_PATHS.inc_num();
+                            }
+                            if report_non_finite(&ld, bounces) {
+                                break;
+                            }
                             assert!(ld.y() >= 0.0 as Float, "ld = {:?}", ld);
                             l += ld;
+                            record_light(bounces + 1, &ld);
+                            if is_recording() {
+                                record_event(PathEvent::DirectLight {
+                                    depth: bounces,
+                                    l: rgb(&ld),
+                                });
+                            }
+                        }
+                        if let Some(ref irradiance_cache) = self.irradiance_cache {
+                            let diffuse_flags: u8 =
+                                BxdfType::BsdfDiffuse as u8 | BxdfType::BsdfReflection as u8;
+                            if use_cache
+                                && bounces > 0
+                                && isect.bssrdf.is_none()
+                                && bsdf.num_components(diffuse_flags) > 0
+                                && bsdf.num_components(BxdfType::BsdfAll as u8)
+                                    == bsdf.num_components(diffuse_flags)
+                            {
+                                // the indirect light reflected by a
+                                // diffuse surface comes from the cache
+                                let wo: Vector3f = -ray.d;
+                                let n: Normal3f = nrm_faceforward_vec3(&isect.shading.n, &wo);
+                                let seed: u64 =
+                                    (sampler.get_1d() * 4_294_967_296.0 as Float) as u64;
+                                let mut rng: Rng = Rng::new();
+                                rng.set_sequence(2_u64 * seed);
+                                let mut record_sampler: Sampler =
+                                    Sampler::Random(RandomSampler::new(1_i64));
+                                record_sampler.reseed(2_u64 * seed + 1_u64);
+                                // records stand for the light arriving
+                                // after the first bounce
+                                let e: Spectrum = irradiance_cache.irradiance(
+                                    &isect.p,
+                                    &n,
+                                    &mut rng,
+                                    |wi: &Vector3f| {
+                                        self.li_from(
+                                            &isect.spawn_ray(wi),
+                                            None,
+                                            scene,
+                                            &mut record_sampler,
+                                            2_u32,
+                                            false,
+                                        )
+                                    },
+                                );
+                                let f: Spectrum =
+                                    bsdf.f(&wo, &Vector3f::from(n), BxdfType::BsdfAll as u8);
+                                if !features_recorded {
+                                    record_features(&(beta * f * PI), &isect.shading.n);
+                                }
+                                l += clamp_indirect(beta * f * e, bounces, self.clamp_indirect);
+                                break;
+                            }
                         }
                         // Sample BSDF to get new path direction
                         let wo: Vector3f = -ray.d;
@@ -151,13 +419,50 @@ impl PathIntegrator {
                             bsdf_flags,
                             &mut sampled_type,
                         );
+                        if !features_recorded
+                            && (sampled_type & BxdfType::BsdfSpecular as u8) == 0_u8
+                        {
+                            // one sample estimate of the albedo
+                            let albedo: Spectrum = if pdf > 0.0 as Float {
+                                beta * f * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf
+                            } else {
+                                Spectrum::default()
+                            };
+                            record_features(&albedo, &isect.shading.n);
+                            features_recorded = true;
+                        }
 
                         // println!("Sampled BSDF, f = {:?}, pdf = {:?}", f, pdf);
                         if f.is_black() || pdf == 0.0 as Float {
                             break;
                         }
+                        // terminate path if the limit of this kind of bounce was reached
+                        let kind: BounceKind = BounceKind::from_sampled_type(sampled_type);
+                        kind_bounces[kind as usize] += 1;
+                        if kind_bounces[kind as usize] > self.depth_limits.limit(kind) {
+                            break;
+                        }
                         beta *= (f * vec3_abs_dot_nrm(&wi, &isect.shading.n)) / pdf;
+                        #[cfg(feature = "polarization")]
+                        {
+                            let mueller: Option<MuellerMatrix> =
+                                specular_mueller(bsdf, &wi, sampled_type);
+                            beta = beta * polarization.scatter(&bsdf.ns, &wo, &wi, mueller);
+                        }
+                        if is_recording() {
+                            record_event(PathEvent::BsdfSample {
+                                depth: bounces,
+                                lobe: lobe_name(sampled_type),
+                                wi: vec3(&wi),
+                                f: rgb(&f),
+                                pdf,
+                                beta: rgb(&beta),
+                            });
+                        }
                         // println!("Updated beta = {:?}", beta);
+                        if report_non_finite(&beta, bounces) {
+                            break;
+                        }
                         assert!(beta.y() >= 0.0 as Float);
                         assert!(
                             !(beta.y().is_infinite()),
@@ -170,6 +475,9 @@ impl PathIntegrator {
                             pdf
                         );
                         specular_bounce = (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8;
+                        if !specular_bounce {
+                            path_roughness = path_roughness.max(bsdf.roughness(sampled_type));
+                        }
                         if ((sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8)
                             && ((sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8)
                         {
@@ -184,42 +492,55 @@ impl PathIntegrator {
                                 eta_scale *= 1.0 as Float / (eta * eta);
                             }
                         }
+                        let medium: Option<Arc<Medium>> = ray.medium.clone();
                         ray = isect.spawn_ray(&wi);
+                        if (sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8 {
+                            interior.cross(&isect, &mut ray, medium);
+                        }
 
                         // account for subsurface scattering, if applicable
                         if let Some(ref bssrdf) = isect.bssrdf {
                             if (sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8 {
-                                // importance sample the BSSRDF
-                                let s2: Point2f = sampler.get_2d();
-                                let s1: Float = sampler.get_1d();
-                                let (s, pi_opt) = bssrdf.sample_s(
-                                    // the next three (extra) parameters are used for SeparableBssrdfAdapter
-                                    bssrdf.clone(),
-                                    bssrdf.mode,
-                                    bssrdf.eta,
-                                    // done
-                                    scene,
-                                    s1,
-                                    s2,
-                                    &mut pdf,
-                                );
+                                let (s, pi_opt) = if bssrdf.random_walk {
+                                    // trace the refracted ray through the interior
+                                    pdf = 1.0 as Float;
+                                    bssrdf.sample_random_walk(scene, &ray, sampler)
+                                } else {
+                                    // importance sample the BSSRDF
+                                    let s2: Point2f = sampler.get_2d();
+                                    let s1: Float = sampler.get_1d();
+                                    bssrdf.sample_s(
+                                        // the next three (extra) parameters are used for SeparableBssrdfAdapter
+                                        bssrdf.clone(),
+                                        bssrdf.mode,
+                                        bssrdf.eta,
+                                        // done
+                                        scene,
+                                        s1,
+                                        s2,
+                                        &mut pdf,
+                                    )
+                                };
                                 if s.is_black() || pdf == 0.0 as Float {
                                     break;
                                 }
                                 assert!(!(beta.y().is_infinite()));
                                 beta *= s / pdf;
+                                #[cfg(feature = "polarization")]
+                                polarization.scatter(&isect.shading.n, &wo, &wi, None);
                                 if let Some(pi) = pi_opt {
                                     // account for the direct subsurface scattering component
-                                    let distrib: Arc<Distribution1D> =
-                                        light_distribution.lookup(&pi.p);
-                                    l += beta
-                                        * uniform_sample_one_light(
+                                    l += clamp_indirect(
+                                        beta * sample_one_light(
                                             &pi,
                                             scene,
                                             sampler,
                                             false,
-                                            Some(&distrib),
-                                        );
+                                            light_distribution,
+                                        ),
+                                        bounces + 1,
+                                        self.clamp_indirect,
+                                    );
                                     // account for the indirect subsurface scattering component
                                     let mut wi: Vector3f = Vector3f::default();
                                     let mut pdf: Float = 0.0 as Float;
@@ -253,31 +574,59 @@ impl PathIntegrator {
                         if rr_beta.max_component_value() < self.rr_threshold && bounces > 3 {
                             let q: Float =
                                 (0.05 as Float).max(1.0 as Float - rr_beta.max_component_value());
-                            if sampler.get_1d() < q {
+                            let survived: bool = sampler.get_1d() >= q;
+                            if is_recording() {
+                                record_event(PathEvent::RussianRoulette {
+                                    depth: bounces,
+                                    q,
+                                    survived,
+                                });
+                            }
+                            if !survived {
                                 break;
                             }
                             beta /= 1.0 as Float - q;
                             assert!(!(beta.y().is_infinite()));
                         }
                     } else {
-                        println!("TODO: if let Some(ref bsdf) = isect.bsdf failed");
+                        debug!("TODO: if let Some(ref bsdf) = isect.bsdf failed");
                     }
                 }
             } else {
                 // add emitted light from the environment
+                let l_before: Spectrum = l;
                 if bounces == 0 || specular_bounce {
                     // for (const auto &light : scene.infiniteLights)
                     for light in &scene.infinite_lights {
-                        l += beta * light.le(&mut ray);
+                        l +=
+                            clamp_indirect(beta * light.le(&mut ray), bounces, self.clamp_indirect);
                     }
                     // println!("Added infinite area lights -> L = {:?}", l);
                 }
+                record_light(bounces, &(l - l_before));
+                if is_recording() {
+                    record_event(PathEvent::Escaped {
+                        depth: bounces,
+                        origin: pnt3(&ray.o),
+                        direction: vec3(&ray.d),
+                        l: rgb(&(l - l_before)),
+                    });
+                }
                 // terminate path if ray escaped
                 break;
             }
+            if report_non_finite(&l, bounces) || report_non_finite(&beta, bounces) {
+                break;
+            }
             bounces += 1_u32;
         }
-        l
+        // e.g. emitted light added before the path was terminated
+        report_non_finite(&l, bounces);
+        PATH_LENGTH.report_value(bounces as u64);
+        if let Some(ref shift) = channel_shift {
+            l = shift.apply(&l);
+        }
+        (l, hit_distance)
     }
     pub fn get_camera(&self) -> Arc<Camera> {
         self.camera.clone()
@@ -0,0 +1,632 @@
+// std
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::Arc;
+// others
+use log::info;
+// pbrt
+use crate::core::camera::Camera;
+use crate::core::geometry::{bnd3_union_pnt3, nrm_abs_dot_vec3, pnt3_distance_squared};
+use crate::core::geometry::{vec3_abs_dot_nrm, Bounds2i, Bounds3f, Normal3f, Point2f, Point3f};
+use crate::core::geometry::{Ray, Vector3f};
+use crate::core::integrator::{
+    compute_light_power_distribution, is_render_cancelled, uniform_sample_one_light,
+};
+use crate::core::integrator::{first_intersection, FirstHit};
+use crate::core::interaction::{Interaction, SurfaceInteraction};
+use crate::core::lowdiscrepancy::radical_inverse;
+use crate::core::material::TransportMode;
+use crate::core::memory::MemoryArena;
+use crate::core::pbrt::consts::PI;
+use crate::core::pbrt::lerp;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::reflection::{Bsdf, BxdfType};
+use crate::core::sampler::Sampler;
+use crate::core::sampling::Distribution1D;
+use crate::core::scene::Scene;
+use crate::core::stats::{StatCounter, StatMemoryCounter};
+
+static PHOTON_PATHS: StatCounter = StatCounter::new("Photon Mapping/Photon paths followed");
+static GATHER_RAYS: StatCounter = StatCounter::new("Photon Mapping/Final gather rays");
+static PHOTON_MAP_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Photon maps");
+
+/// the search radius (if not given) relative to the diagonal of the
+/// scene bounds
+const DEFAULT_RADIUS_FRACTION: Float = 0.02;
+
+/// Which light paths a photon stands for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PhotonKind {
+    /// straight from a light source
+    Direct,
+    /// from a light source via specular surfaces only
+    Caustic,
+    /// via at least one diffuse or glossy surface
+    Indirect,
+}
+
+/// A photon stored where it hit a diffuse or glossy surface.
+#[derive(Debug, Copy, Clone)]
+pub struct Photon {
+    pub p: Point3f,
+    /// towards where the photon came from
+    pub wi: Vector3f,
+    pub power: Spectrum,
+    pub kind: PhotonKind,
+}
+
+/// Photons in a kd-tree, each photon splits the photons stored
+/// before and after it along one axis.
+#[derive(Default)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes: Vec<u8> = vec![0_u8; photons.len()];
+        build_kd_tree(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+    /// The (at most) *k* photons closest to *p*, but not further away
+    /// than the square root of *max_distance2*, as squared distances
+    /// and indices, sorted by distance.
+    pub fn nearest(&self, p: &Point3f, k: usize, max_distance2: Float) -> Vec<(Float, usize)> {
+        let mut nearest: Vec<(Float, usize)> = Vec::with_capacity(k + 1);
+        let mut max_distance2: Float = max_distance2;
+        self.search(
+            0,
+            self.photons.len(),
+            p,
+            k,
+            &mut nearest,
+            &mut max_distance2,
+        );
+        nearest
+    }
+    fn search(
+        &self,
+        start: usize,
+        end: usize,
+        p: &Point3f,
+        k: usize,
+        nearest: &mut Vec<(Float, usize)>,
+        max_distance2: &mut Float,
+    ) {
+        if start >= end {
+            return;
+        }
+        // see build_kd_tree()
+        let mid: usize = start + (end - start) / 2;
+        let photon: &Photon = &self.photons[mid];
+        let delta: Float = p[self.axes[mid]] - photon.p[self.axes[mid]];
+        // the side of the splitting plane containing p first
+        let (near, far) = if delta < 0.0 as Float {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.search(near.0, near.1, p, k, nearest, max_distance2);
+        let distance2: Float = pnt3_distance_squared(p, &photon.p);
+        if distance2 < *max_distance2 {
+            let index: usize = nearest
+                .iter()
+                .position(|(d2, _)| *d2 > distance2)
+                .unwrap_or(nearest.len());
+            nearest.insert(index, (distance2, mid));
+            nearest.truncate(k);
+            if nearest.len() == k {
+                *max_distance2 = nearest[k - 1].0;
+            }
+        }
+        if delta * delta < *max_distance2 {
+            self.search(far.0, far.1, p, k, nearest, max_distance2);
+        }
+    }
+}
+
+/// Puts the median photon (along the axis of the largest extent) in
+/// the middle and does the same for the photons before and after it.
+fn build_kd_tree(photons: &mut [Photon], axes: &mut [u8]) {
+    if photons.is_empty() {
+        return;
+    }
+    let mut bounds: Bounds3f = Bounds3f::default();
+    for photon in photons.iter() {
+        bounds = bnd3_union_pnt3(&bounds, &photon.p);
+    }
+    let axis: u8 = bounds.maximum_extent();
+    let mid: usize = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| {
+        a.p[axis].partial_cmp(&b.p[axis]).unwrap_or(Ordering::Equal)
+    });
+    axes[mid] = axis;
+    let (photons_before, photons_after) = photons.split_at_mut(mid);
+    let (axes_before, axes_after) = axes.split_at_mut(mid);
+    build_kd_tree(photons_before, axes_before);
+    build_kd_tree(&mut photons_after[1..], &mut axes_after[1..]);
+}
+
+/// Does the BSDF have diffuse or glossy components?
+fn is_non_specular(bsdf: &Bsdf) -> bool {
+    let both: u8 = BxdfType::BsdfReflection as u8 | BxdfType::BsdfTransmission as u8;
+    bsdf.num_components(BxdfType::BsdfDiffuse as u8 | both) > 0
+        || bsdf.num_components(BxdfType::BsdfGlossy as u8 | both) > 0
+}
+
+/// Photon Mapping (with Final Gathering)
+pub struct PhotonMapIntegrator {
+    // inherited from SamplerIntegrator (see integrator.h)
+    pub camera: Arc<Camera>,
+    pub sampler: Box<Sampler>,
+    pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
+    pub max_depth: u32,
+    /// photon paths traced for the global photon map
+    pub photons: i32,
+    /// photon paths traced for the caustic photon map
+    pub caustic_photons: i32,
+    /// photons used for each radiance estimate
+    pub lookup_photons: i32,
+    /// maximum distance of those photons (0 for a fraction of the
+    /// scene size)
+    pub radius: Float,
+    /// look up the global photon map where the rays of a final gather
+    /// hit (instead of where the camera rays hit)
+    pub final_gather: bool,
+    pub final_gather_samples: i32,
+    global_map: PhotonMap,
+    caustic_map: PhotonMap,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
+}
+
+impl PhotonMapIntegrator {
+    pub fn new(
+        max_depth: u32,
+        camera: Arc<Camera>,
+        sampler: Box<Sampler>,
+        pixel_bounds: Bounds2i,
+    ) -> Self {
+        PhotonMapIntegrator {
+            camera,
+            sampler,
+            pixel_bounds,
+            debug_non_finite: false,
+            max_depth,
+            photons: 200_000,
+            caustic_photons: 200_000,
+            lookup_photons: 50,
+            radius: 0.0 as Float,
+            final_gather: true,
+            final_gather_samples: 16,
+            global_map: PhotonMap::default(),
+            caustic_map: PhotonMap::default(),
+            terminator_shadowing: false,
+        }
+    }
+    /// Traces the photons of both photon maps (first pass).
+    pub fn preprocess(&mut self, scene: &Scene) {
+        if self.final_gather {
+            self.final_gather_samples = self.sampler.round_count(self.final_gather_samples.max(1));
+            self.sampler.request_2d_array(self.final_gather_samples);
+        }
+        if self.radius <= 0.0 as Float {
+            self.radius = DEFAULT_RADIUS_FRACTION * scene.world_bound().diagonal().length();
+        }
+        if let Some(light_distr) = compute_light_power_distribution(scene) {
+            // different Halton sequences for both maps
+            let n_global: u64 = self.photons.max(0) as u64;
+            let n_caustic: u64 = self.caustic_photons.max(0) as u64;
+            let global: Vec<Photon> = self.shoot_photons(scene, &light_distr, 0..n_global, false);
+            let caustic: Vec<Photon> =
+                self.shoot_photons(scene, &light_distr, n_global..n_global + n_caustic, true);
+            info!(
+                "Photon maps: {} global and {} caustic photons (radius {}).",
+                global.len(),
+                caustic.len(),
+                self.radius
+            );
+            PHOTON_MAP_BYTES.add(
+                (global.len() + caustic.len())
+                    * (std::mem::size_of::<Photon>() + std::mem::size_of::<u8>()),
+            );
+            self.global_map = PhotonMap::new(global);
+            self.caustic_map = PhotonMap::new(caustic);
+        }
+    }
+    /// Traces the photon *paths* (Halton sequence indices) on all
+    /// cores.
+    fn shoot_photons(
+        &self,
+        scene: &Scene,
+        light_distr: &Distribution1D,
+        paths: Range<u64>,
+        caustic: bool,
+    ) -> Vec<Photon> {
+        let n_paths: u64 = paths.end - paths.start;
+        if n_paths == 0 {
+            return Vec::new();
+        }
+        // no threads in WebAssembly
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.trace_photons(scene, light_distr, paths, n_paths, caustic)
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let num_cores: u64 = num_cpus::get() as u64;
+            let chunk_size: u64 = n_paths.div_ceil(num_cores);
+            crossbeam::scope(|scope| {
+                let handles: Vec<_> = (0..num_cores)
+                    .map(|c| {
+                        let start: u64 = (paths.start + c * chunk_size).min(paths.end);
+                        let end: u64 = (start + chunk_size).min(paths.end);
+                        scope.spawn(move |_| {
+                            self.trace_photons(scene, light_distr, start..end, n_paths, caustic)
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect()
+            })
+            .unwrap()
+        }
+    }
+    /// Follows photon paths from the lights (like **SPPMIntegrator**)
+    /// and stores photons where they hit diffuse or glossy surfaces.
+    /// For the *caustic* map only photons which got there via
+    /// specular surfaces are kept.
+    fn trace_photons(
+        &self,
+        scene: &Scene,
+        light_distr: &Distribution1D,
+        paths: Range<u64>,
+        n_paths: u64,
+        caustic: bool,
+    ) -> Vec<Photon> {
+        let mut photons: Vec<Photon> = Vec::new();
+        for halton_index in paths {
+            if is_render_cancelled() {
+                break;
+            }
+            // choose light to shoot photon from
+            let mut light_pdf: Float = 0.0 as Float;
+            let light_num: usize =
+                light_distr.sample_discrete(radical_inverse(0, halton_index), Some(&mut light_pdf));
+            if light_pdf == 0.0 as Float {
+                continue;
+            }
+            let light = &scene.lights[light_num];
+            // compute sample values for photon ray leaving light source
+            let u_light_0: Point2f = Point2f {
+                x: radical_inverse(1, halton_index),
+                y: radical_inverse(2, halton_index),
+            };
+            let u_light_1: Point2f = Point2f {
+                x: radical_inverse(3, halton_index),
+                y: radical_inverse(4, halton_index),
+            };
+            let u_light_time: Float = lerp(
+                radical_inverse(5, halton_index),
+                self.camera.get_shutter_open(),
+                self.camera.get_shutter_close(),
+            );
+            let mut halton_dim: u16 = 6;
+            // generate _photon_ray_ from light source and initialize _beta_
+            let mut photon_ray: Ray = Ray::default();
+            let mut n_light: Normal3f = Normal3f::default();
+            let mut pdf_pos: Float = 0.0;
+            let mut pdf_dir: Float = 0.0;
+            let le: Spectrum = light.sample_le(
+                u_light_0,
+                u_light_1,
+                u_light_time,
+                &mut photon_ray,
+                &mut n_light,
+                &mut pdf_pos,
+                &mut pdf_dir,
+            );
+            if pdf_pos == 0.0 as Float || pdf_dir == 0.0 as Float || le.is_black() {
+                continue;
+            }
+            let mut beta: Spectrum =
+                (le * nrm_abs_dot_vec3(&n_light, &photon_ray.d)) / (light_pdf * pdf_pos * pdf_dir);
+            if beta.is_black() {
+                continue;
+            }
+            PHOTON_PATHS.inc();
+            let mut specular_path: bool = true;
+            let mut depth: u32 = 0;
+            while depth < self.max_depth {
+                let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+                if !scene.intersect(&mut photon_ray, &mut isect) {
+                    break;
+                }
+                isect.compute_scattering_functions(&photon_ray, true, TransportMode::Importance);
+                isect.set_terminator_shadowing(self.terminator_shadowing);
+                let bsdf: &Bsdf = if let Some(ref bsdf) = isect.bsdf {
+                    bsdf
+                } else {
+                    photon_ray = isect.spawn_ray(&photon_ray.d);
+                    continue;
+                };
+                let wo: Vector3f = -photon_ray.d;
+                if is_non_specular(bsdf) {
+                    let kind: PhotonKind = if depth == 0 {
+                        PhotonKind::Direct
+                    } else if specular_path {
+                        PhotonKind::Caustic
+                    } else {
+                        PhotonKind::Indirect
+                    };
+                    if !caustic || kind == PhotonKind::Caustic {
+                        photons.push(Photon {
+                            p: isect.p,
+                            wi: wo,
+                            power: beta / n_paths as Float,
+                            kind,
+                        });
+                    }
+                    if caustic {
+                        break;
+                    }
+                }
+                // sample BSDF _fr_ and direction _wi_ for reflected photon
+                let mut wi: Vector3f = Vector3f::default();
+                let mut pdf: Float = 0.0;
+                let mut sampled_type: u8 = u8::MAX;
+                let bsdf_sample: Point2f = Point2f {
+                    x: radical_inverse(halton_dim, halton_index),
+                    y: radical_inverse(halton_dim + 1, halton_index),
+                };
+                let fr: Spectrum = bsdf.sample_f(
+                    &wo,
+                    &mut wi,
+                    bsdf_sample,
+                    &mut pdf,
+                    BxdfType::BsdfAll as u8,
+                    &mut sampled_type,
+                );
+                if fr.is_black() || pdf == 0.0 as Float {
+                    break;
+                }
+                if sampled_type & BxdfType::BsdfSpecular as u8 == 0_u8 {
+                    specular_path = false;
+                }
+                let bnew: Spectrum = beta * fr * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf;
+                // possibly terminate photon path with Russian roulette
+                let q: Float = (0.0 as Float).max(1.0 as Float - bnew.y() / beta.y());
+                if radical_inverse(halton_dim + 2, halton_index) < q {
+                    break;
+                }
+                halton_dim += 3;
+                beta = bnew / (1.0 as Float - q);
+                photon_ray = isect.spawn_ray(&wi);
+                depth += 1;
+            }
+        }
+        photons
+    }
+    /// Follows the camera ray through specular surfaces up to the
+    /// first diffuse or glossy one, where direct lighting is sampled
+    /// and the photon maps are looked up.
+    pub fn li(
+        &self,
+        r: &Ray,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        self.li_first_hit(r, None, scene, sampler, arena, depth)
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        r: &Ray,
+        mut first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        _arena: &MemoryArena,
+        _depth: i32,
+    ) -> Spectrum {
+        let mut l: Spectrum = Spectrum::default();
+        let mut beta: Spectrum = Spectrum::new(1.0 as Float);
+        let mut ray: Ray = Ray {
+            o: r.o,
+            d: r.d,
+            t_max: r.t_max,
+            time: r.time,
+            differential: r.differential,
+            medium: r.medium.clone(),
+        };
+        let mut bounces: u32 = 0_u32;
+        loop {
+            let (found, mut isect): (bool, SurfaceInteraction) =
+                first_intersection(scene, &mut ray, first_hit.take());
+            if !found {
+                for light in &scene.lights {
+                    l += beta * light.le(&mut ray);
+                }
+                break;
+            }
+            isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+            isect.set_terminator_shadowing(self.terminator_shadowing);
+            let bsdf: &Bsdf = if let Some(ref bsdf) = isect.bsdf {
+                bsdf
+            } else {
+                ray = isect.spawn_ray(&ray.d);
+                continue;
+            };
+            let wo: Vector3f = -ray.d;
+            l += beta * isect.le(&wo);
+            if is_non_specular(bsdf) {
+                l += beta * uniform_sample_one_light(&isect, scene, sampler, false, None);
+                l += beta * self.estimate(&self.caustic_map, bsdf, &isect.p, &wo, |_kind| true);
+                if self.final_gather {
+                    l += beta * self.gather(&isect, bsdf, &wo, scene, sampler);
+                } else {
+                    // direct lighting and caustics are already accounted for
+                    l += beta
+                        * self.estimate(&self.global_map, bsdf, &isect.p, &wo, |kind| {
+                            kind == PhotonKind::Indirect
+                        });
+                }
+                break;
+            }
+            bounces += 1;
+            if bounces >= self.max_depth {
+                break;
+            }
+            // follow the specular reflection or transmission
+            let mut wi: Vector3f = Vector3f::default();
+            let mut pdf: Float = 0.0 as Float;
+            let mut sampled_type: u8 = 0_u8;
+            let f: Spectrum = bsdf.sample_f(
+                &wo,
+                &mut wi,
+                sampler.get_2d(),
+                &mut pdf,
+                BxdfType::BsdfAll as u8,
+                &mut sampled_type,
+            );
+            if f.is_black() || pdf == 0.0 as Float {
+                break;
+            }
+            beta *= f * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf;
+            ray = isect.spawn_ray(&wi);
+        }
+        l
+    }
+    /// The indirect illumination at *isect*: rays sampled from the
+    /// diffuse and glossy components of *bsdf* look up the global
+    /// photon map where they hit a diffuse or glossy surface (through
+    /// specular surfaces, but not seeing light sources, which would
+    /// count direct lighting and caustics twice).
+    fn gather(
+        &self,
+        isect: &SurfaceInteraction,
+        bsdf: &Bsdf,
+        wo: &Vector3f,
+        scene: &Scene,
+        sampler: &mut Sampler,
+    ) -> Spectrum {
+        let n: i32 = self.final_gather_samples;
+        let mut u_gather: Vec<Point2f> = sampler.get_2d_array_vec(n);
+        if u_gather.is_empty() {
+            u_gather = (0..n).map(|_| sampler.get_2d()).collect();
+        }
+        let non_specular: u8 = BxdfType::BsdfDiffuse as u8
+            | BxdfType::BsdfGlossy as u8
+            | BxdfType::BsdfReflection as u8
+            | BxdfType::BsdfTransmission as u8;
+        let mut l: Spectrum = Spectrum::default();
+        for u in u_gather {
+            let mut wi: Vector3f = Vector3f::default();
+            let mut pdf: Float = 0.0 as Float;
+            let mut sampled_type: u8 = 0_u8;
+            let f: Spectrum =
+                bsdf.sample_f(wo, &mut wi, u, &mut pdf, non_specular, &mut sampled_type);
+            if f.is_black() || pdf == 0.0 as Float {
+                continue;
+            }
+            GATHER_RAYS.inc();
+            let mut beta: Spectrum = f * vec3_abs_dot_nrm(&wi, &isect.shading.n) / pdf;
+            let mut ray: Ray = isect.spawn_ray(&wi);
+            for _bounce in 0..self.max_depth {
+                let mut hit: SurfaceInteraction = SurfaceInteraction::default();
+                if !scene.intersect(&mut ray, &mut hit) {
+                    break;
+                }
+                hit.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+                hit.set_terminator_shadowing(self.terminator_shadowing);
+                let hit_bsdf: &Bsdf = if let Some(ref hit_bsdf) = hit.bsdf {
+                    hit_bsdf
+                } else {
+                    ray = hit.spawn_ray(&ray.d);
+                    continue;
+                };
+                let wo_hit: Vector3f = -ray.d;
+                if is_non_specular(hit_bsdf) {
+                    l += beta
+                        * self.estimate(&self.global_map, hit_bsdf, &hit.p, &wo_hit, |_kind| true);
+                    break;
+                }
+                let f: Spectrum = hit_bsdf.sample_f(
+                    &wo_hit,
+                    &mut wi,
+                    sampler.get_2d(),
+                    &mut pdf,
+                    BxdfType::BsdfAll as u8,
+                    &mut sampled_type,
+                );
+                if f.is_black() || pdf == 0.0 as Float {
+                    break;
+                }
+                beta *= f * vec3_abs_dot_nrm(&wi, &hit.shading.n) / pdf;
+                ray = hit.spawn_ray(&wi);
+            }
+        }
+        l / n as Float
+    }
+    /// The radiance reflected at *p* towards *wo*, estimated from the
+    /// density of the nearest photons of *map* (those of the kinds
+    /// accepted by *kind_filter*).
+    fn estimate<F>(
+        &self,
+        map: &PhotonMap,
+        bsdf: &Bsdf,
+        p: &Point3f,
+        wo: &Vector3f,
+        kind_filter: F,
+    ) -> Spectrum
+    where
+        F: Fn(PhotonKind) -> bool,
+    {
+        if map.is_empty() {
+            return Spectrum::default();
+        }
+        let k: usize = self.lookup_photons.max(1) as usize;
+        let max_distance2: Float = self.radius * self.radius;
+        let nearest: Vec<(Float, usize)> = map.nearest(p, k, max_distance2);
+        // the disk with all photons found
+        let r2: Float = if nearest.len() == k {
+            nearest[k - 1].0
+        } else {
+            max_distance2
+        };
+        if nearest.is_empty() || r2 == 0.0 as Float {
+            return Spectrum::default();
+        }
+        let mut l: Spectrum = Spectrum::default();
+        for (_distance2, index) in nearest {
+            let photon: &Photon = &map.photons[index];
+            if kind_filter(photon.kind) {
+                l += bsdf.f(wo, &photon.wi, BxdfType::BsdfAll as u8) * photon.power;
+            }
+        }
+        l / (PI * r2)
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        self.camera.clone()
+    }
+    pub fn get_sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+    pub fn get_pixel_bounds(&self) -> Bounds2i {
+        self.pixel_bounds
+    }
+}
@@ -1,10 +1,10 @@
 // std
 use std::borrow::Borrow;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // others
 use atom::*;
 use atomic::Atomic;
+use log::{error, info};
 // pbrt
 use crate::blockqueue::BlockQueue;
 use crate::core::camera::{Camera, CameraSample};
@@ -16,17 +16,31 @@ use crate::core::geometry::{
 use crate::core::geometry::{
     Bounds2i, Bounds3f, Normal3f, Point2f, Point2i, Point3f, Point3i, Ray, Vector2i, Vector3f,
 };
-use crate::core::integrator::{compute_light_power_distribution, uniform_sample_one_light};
+use crate::core::integrator::{
+    budget_samples, compute_light_power_distribution, is_render_cancelled, uniform_sample_one_light,
+};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
 use crate::core::lowdiscrepancy::radical_inverse;
 use crate::core::material::TransportMode;
 use crate::core::parallel::AtomicFloat;
-use crate::core::pbrt::{clamp_t, lerp};
+use crate::core::pbrt::consts::PI;
+use crate::core::pbrt::{clamp_t, gamma_correct, lerp};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::progress::ProgressReporter;
 use crate::core::reflection::{Bsdf, BxdfType};
 use crate::core::scene::Scene;
+use crate::core::stats::{StatCounter, StatIntDistribution, StatMemoryCounter, StatRatio};
 use crate::samplers::halton::HaltonSampler;
 
+static VISIBLE_POINTS_CHECKED: StatRatio = StatRatio::new(
+    "Stochastic Progressive Photon Mapping/Visible points checked per photon intersection",
+);
+static PHOTON_PATHS: StatCounter =
+    StatCounter::new("Stochastic Progressive Photon Mapping/Photon paths followed");
+static GRID_CELLS_PER_VISIBLE_POINT: StatIntDistribution =
+    StatIntDistribution::new("Stochastic Progressive Photon Mapping/Grid cells per visible point");
+static PIXEL_MEMORY_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/SPPM Pixels");
+
 /// Stochastic Progressive Photon Mapping
 pub struct SPPMIntegrator {
     pub camera: Arc<Camera>,
@@ -35,6 +49,9 @@ pub struct SPPMIntegrator {
     pub max_depth: u32,
     pub photons_per_iteration: i32,
     pub write_frequency: i32,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl SPPMIntegrator {
@@ -59,6 +76,7 @@ impl SPPMIntegrator {
             max_depth,
             photons_per_iteration,
             write_frequency,
+            terminator_shadowing: false,
         }
     }
     pub fn render(&self, scene: &Scene, num_threads: u8) {
@@ -67,7 +85,7 @@ impl SPPMIntegrator {
         } else {
             num_threads as usize
         };
-        println!("Rendering with {:?} thread(s) ...", num_cores);
+        info!("Rendering with {:?} thread(s) ...", num_cores);
         // TODO: ProfilePhase p(Prof::IntegratorRender);
 
         // initialize _pixel_bounds_ and _pixels_ array for SPPM
@@ -78,10 +96,17 @@ impl SPPMIntegrator {
         for _i in 0..n_pixels as usize {
             let mut pixel = SPPMPixel::default();
             pixel.radius = self.initial_search_radius;
+            pixel.phi = (0..Spectrum::N_SAMPLES)
+                .map(|_| AtomicFloat::new(0.0 as Float))
+                .collect();
             pixels.push(pixel);
         }
         let inv_sqrt_spp: Float = 1.0 as Float / (self.n_iterations as Float).sqrt();
-        // TODO: let pixel_memory_bytes: usize = n_pixels as usize * std::mem::size_of::<SPPMPixel>();
+        PIXEL_MEMORY_BYTES.add(
+            n_pixels as usize
+                * (std::mem::size_of::<SPPMPixel>()
+                    + Spectrum::N_SAMPLES * std::mem::size_of::<AtomicFloat>()),
+        );
 
         // compute _light_distr_ for sampling lights proportional to power
         if let Some(light_distr) = compute_light_power_distribution(scene) {
@@ -98,8 +123,11 @@ impl SPPMIntegrator {
                 x: (pixel_extent.x + tile_size - 1) / tile_size,
                 y: (pixel_extent.y + tile_size - 1) / tile_size,
             };
-            // TODO: ProgressReporter progress(2 * nIterations, "Rendering");
-            for iteration in pbr::PbIter::new(0..self.n_iterations) {
+            // the first ones of _n_iterations_ (see **budget_samples()**)
+            let n_iterations: i32 = budget_samples(self.n_iterations as i64) as i32;
+            let mut progress: ProgressReporter =
+                ProgressReporter::new(n_iterations as u64, "Rendering", "iterations");
+            for iteration in 0..n_iterations {
                 // generate SPPM visible points
                 {
                     // TODO: ProfilePhase _(Prof::SPPMCameraPass);
@@ -181,7 +209,6 @@ impl SPPMIntegrator {
                                             );
                                             let mut specular_bounce: bool = false;
                                             for depth in 0..integrator.max_depth {
-                                                // TODO: ++totalPhotonSurfaceInteractions;
                                                 let mut isect: SurfaceInteraction =
                                                     SurfaceInteraction::default();
                                                 if scene.intersect(&mut ray, &mut isect) {
@@ -193,6 +220,9 @@ impl SPPMIntegrator {
                                                     isect.compute_scattering_functions(
                                                         &ray, true, mode,
                                                     );
+                                                    isect.set_terminator_shadowing(
+                                                        integrator.terminator_shadowing,
+                                                    );
                                                     if let Some(bsdf) = &isect.bsdf {
                                                         // accumulate direct illumination
                                                         // at SPPM camera ray intersection
@@ -416,9 +446,12 @@ impl SPPMIntegrator {
                                                     }
                                                 }
                                             }
-                                            // ReportValue(grid_cells_per_visible_point,
-                                            //             (1 + pMax.x - pMin.x) * (1 + pMax.y - pMin.y) *
-                                            //                 (1 + pMax.z - pMin.z));
+                                            GRID_CELLS_PER_VISIBLE_POINT.report_value(
+                                                ((1 + p_max.x - p_min.x)
+                                                    * (1 + p_max.y - p_min.y)
+                                                    * (1 + p_max.z - p_min.z))
+                                                    as u64,
+                                            );
                                         }
                                     }
                                 });
@@ -458,11 +491,13 @@ impl SPPMIntegrator {
                         let integrator = &self;
                         let light_distr = &light_distr;
                         crossbeam::scope(|scope| {
-                        let (band_tx, band_rx) = crossbeam_channel::bounded(num_cores);
+                        let mut handles = Vec::with_capacity(bands.len());
                         // spawn worker threads
-                        for (b, band) in bands.into_iter().enumerate() {
-                            let band_tx = band_tx.clone();
-                            scope.spawn(move |_| {
+                        for band in bands.into_iter() {
+                            handles.push(scope.spawn(move |_| {
+                                // the photon contributions of the band, added
+                                // to the visible points in photon order below
+                                let mut contributions: Vec<(&SPPMPixel, Spectrum)> = Vec::new();
                                 for photon_index in band.iter() {
                                     // for photon_index in 0..integrator.photons_per_iteration as usize {
                                     // MemoryArena &arena = photonShootArenas[ThreadIndex];
@@ -529,7 +564,7 @@ impl SPPMIntegrator {
                                             //     light_num, pdf_pos, pdf_dir, le
                                             // );
                                             // C++: return; (from ParallelFor(...{}, photonsPerIteration, 8192);)
-                                            break;
+                                            continue;
                                         }
                                         let mut beta: Spectrum = (le
                                             * nrm_abs_dot_vec3(&n_light, &photon_ray.d))
@@ -537,13 +572,14 @@ impl SPPMIntegrator {
                                         if beta.is_black() {
                                             // println!("light[{}]: beta = {:?}", light_num, beta);
                                             // C++:  return; (from ParallelFor(...{}, photonsPerIteration, 8192);)
-                                            break;
+                                            continue;
                                         }
+                                        PHOTON_PATHS.inc();
                                         // follow photon path through scene and record intersections
                                         for depth in 0..integrator.max_depth {
 					    let mut isect: SurfaceInteraction = SurfaceInteraction::default();
 					    if scene.intersect(&mut photon_ray, &mut isect) {
-                                                // TODO: ++totalPhotonSurfaceInteractions;
+                                                VISIBLE_POINTS_CHECKED.add_denom(1);
                                                 if depth > 0 {
                                                     // add photon contribution to nearby visible points
                                                     let mut photon_grid_index: Point3i =
@@ -570,6 +606,7 @@ impl SPPMIntegrator {
                                                             while let Some(node) = opt {
                                                                 // deal with linked list
                                                                 let pixel = node.pixel;
+                                                                VISIBLE_POINTS_CHECKED.add_num(1);
                                                                 let radius: Float = pixel.radius;
                                                                     if pnt3_distance_squared(
                                                                         &pixel.vp.p,
@@ -601,14 +638,8 @@ impl SPPMIntegrator {
                                                                                     &wi,
                                                                                     bsdf_flags,
                                                                                 );
-                                                                            for i in 0..3 {
-                                                                                pixel.phi[i]
-                                                                                    .add(phi[i]);
-                                                                            }
-                                                                            pixel.m.fetch_add(
-                                                                                1_i32,
-                                                                                atomic::Ordering::Relaxed,
-                                                                            );
+                                                                            contributions
+                                                                                .push((pixel, phi));
                                                                         }
                                                                         // update opt
                                                                         opt = node.next.get();
@@ -622,6 +653,7 @@ impl SPPMIntegrator {
                                                 // compute BSDF at photon intersection point
                                                 let mode: TransportMode = TransportMode::Importance;
 						isect.compute_scattering_functions(&photon_ray, true, mode);
+						isect.set_terminator_shadowing(integrator.terminator_shadowing);
                                                 if let Some(ref photon_bsdf) = isect.bsdf {
                                                     // sample BSDF _fr_ and direction _wi_ for reflected photon
                                                     let mut wi: Vector3f = Vector3f::default();
@@ -681,16 +713,19 @@ impl SPPMIntegrator {
                                         }
                                     }
                                 }
-                            });
-                            // send progress through the channel to main thread
-                            band_tx.send(b).unwrap_or_else(|_| panic!("Failed to send progress"));
+                                contributions
+                            }));
                         }
-                        // spawn thread to report progress
-                        scope.spawn(move |_| {
-                            for _ in 0..num_cores {
-                                band_rx.recv().unwrap();
+                        // the bands in photon order, so the sums are the
+                        // same for any number of threads
+                        for handle in handles {
+                            for (pixel, phi) in handle.join().unwrap() {
+                                for i in 0..Spectrum::N_SAMPLES {
+                                    pixel.phi[i].add(phi[i]);
+                                }
+                                pixel.m.fetch_add(1_i32, atomic::Ordering::Relaxed);
                             }
-                        });
+                        }
                     })
                     .unwrap();
                     }
@@ -718,7 +753,7 @@ impl SPPMIntegrator {
                                             let r_new: Float =
                                                 p.radius * (n_new / (p.n + p_m as Float)).sqrt();
                                             let mut phi: Spectrum = Spectrum::default();
-                                            for j in 0..3 {
+                                            for j in 0..Spectrum::N_SAMPLES {
                                                 phi[j] = Float::from(&p.phi[j]);
                                             }
                                             p.tau = (p.tau + p.vp.beta * phi) * (r_new * r_new)
@@ -726,7 +761,7 @@ impl SPPMIntegrator {
                                             p.n = n_new;
                                             p.radius = r_new;
                                             p.m.store(0, atomic::Ordering::Relaxed);
-                                            for j in 0..3 {
+                                            for j in 0..Spectrum::N_SAMPLES {
                                                 p.phi[j] = AtomicFloat::new(0.0 as Float);
                                             }
                                         }
@@ -751,8 +786,10 @@ impl SPPMIntegrator {
                     }
                 }
                 // periodically store SPPM image in film and write image
-                if iteration + 1 == self.n_iterations
+                let cancelled: bool = is_render_cancelled();
+                if iteration + 1 == n_iterations
                     || ((iteration + 1) % self.write_frequency) == 0
+                    || cancelled
                 {
                     let x0: i32 = pixel_bounds.p_min.x;
                     let x1: i32 = pixel_bounds.p_max.x;
@@ -771,42 +808,17 @@ impl SPPMIntegrator {
                     }
                     film.set_image(&image[..]);
                     film.write_image(1.0 as Float);
-                    // TODO: write SPPM radius image, if requested
-                    // if (getenv("SPPM_RADIUS")) {
-                    //     std::unique_ptr<Float[]> rimg(
-                    //         new Float[3 * pixel_bounds.area()]);
-                    //     Float minrad = 1e30f, maxrad = 0;
-                    //     for (int y = pixel_bounds.p_min.y; y < pixel_bounds.p_max.y; ++y) {
-                    //         for (int x = x0; x < x1; ++x) {
-                    //             const SPPMPixel &p =
-                    //                 pixels[(y - pixel_bounds.p_min.y) * (x1 - x0) +
-                    //                        (x - x0)];
-                    //             minrad = std::min(minrad, p.radius);
-                    //             maxrad = std::max(maxrad, p.radius);
-                    //         }
-                    //     }
-                    //     fprintf(stderr,
-                    //             "iterations: %d (%.2f s) radius range: %f - %f\n",
-                    //             iter + 1, progress.ElapsedMS() / 1000., minrad, maxrad);
-                    //     int offset = 0;
-                    //     for (int y = pixel_bounds.p_min.y; y < pixel_bounds.p_max.y; ++y) {
-                    //         for (int x = x0; x < x1; ++x) {
-                    //             const SPPMPixel &p =
-                    //                 pixels[(y - pixel_bounds.p_min.y) * (x1 - x0) +
-                    //                        (x - x0)];
-                    //             Float v = 1.f - (p.radius - minrad) / (maxrad - minrad);
-                    //             rimg[offset++] = v;
-                    //             rimg[offset++] = v;
-                    //             rimg[offset++] = v;
-                    //         }
-                    //     }
-                    //     Point2i res(pixel_bounds.p_max.x - pixel_bounds.p_min.x,
-                    //                 pixel_bounds.p_max.y - pixel_bounds.p_min.y);
-                    //     WriteImage("sppm_radius.png", rimg.get(), pixel_bounds, res);
-                    // }
+                    // write SPPM radius image, if requested
+                    if std::env::var_os("SPPM_RADIUS").is_some() {
+                        write_radius_image(&pixels, &pixel_bounds, iteration + 1);
+                    }
+                }
+                progress.update(1);
+                if cancelled {
+                    break;
                 }
             }
-            // TODO: progress.Done();
+            progress.done();
         }
     }
     pub fn get_camera(&self) -> Arc<Camera> {
@@ -827,7 +839,7 @@ pub struct SPPMPixel {
     pub radius: Float,
     pub ld: Spectrum,
     pub vp: VisiblePoint,
-    pub phi: [AtomicFloat; 3],
+    pub phi: Vec<AtomicFloat>,
     pub m: Atomic<i32>,
     pub n: Float,
     pub tau: Spectrum,
@@ -847,6 +859,43 @@ impl<'p> SPPMPixelListNode<'p> {
     }
 }
 
+/// Writes the search radius of each pixel to "sppm_radius.png",
+/// white for the smallest and black for the largest radius.
+fn write_radius_image(pixels: &[SPPMPixel], pixel_bounds: &Bounds2i, iterations: i32) {
+    let mut min_radius: Float = Float::INFINITY;
+    let mut max_radius: Float = 0.0 as Float;
+    for p in pixels {
+        min_radius = min_radius.min(p.radius);
+        max_radius = max_radius.max(p.radius);
+    }
+    info!(
+        "iterations: {} radius range: {} - {}",
+        iterations, min_radius, max_radius
+    );
+    let range: Float = (max_radius - min_radius).max(Float::MIN_POSITIVE);
+    let buffer: Vec<u8> = pixels
+        .iter()
+        .map(|p| {
+            let v: Float = 1.0 as Float - (p.radius - min_radius) / range;
+            clamp_t(
+                255.0 as Float * gamma_correct(v) + 0.5,
+                0.0 as Float,
+                255.0 as Float,
+            ) as u8
+        })
+        .collect();
+    let resolution: Vector2i = pixel_bounds.diagonal();
+    if let Err(err) = image::save_buffer(
+        "sppm_radius.png",
+        &buffer,
+        resolution.x as u32,
+        resolution.y as u32,
+        image::ColorType::L8,
+    ) {
+        error!("Can't write \"sppm_radius.png\": {}", err);
+    }
+}
+
 fn to_grid(p: &Point3f, bounds: &Bounds3f, grid_res: &[i32; 3], pi: &mut Point3i) -> bool {
     let mut in_bounds: bool = true;
     let pg: Vector3f = bounds.offset(p);
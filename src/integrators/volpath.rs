@@ -1,24 +1,33 @@
 // std
 use std::borrow::Borrow;
 use std::sync::Arc;
+// others
+use log::debug;
 // pbrt
 // use crate::core::bssrdf::Bssrdf;
 use crate::core::camera::Camera;
 use crate::core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use crate::core::geometry::{Bounds2i, Point2f, Ray, Vector3f};
-use crate::core::integrator::uniform_sample_one_light;
+use crate::core::integrator::{clamp_indirect, record_light, report_non_finite, sample_one_light};
+use crate::core::integrator::{first_intersection, FirstHit};
 use crate::core::interaction::{Interaction, MediumInteraction, SurfaceInteraction};
 use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::lightdistrib::LightDistribution;
 use crate::core::material::TransportMode;
+use crate::core::medium::{InteriorList, Medium};
+use crate::core::memory::MemoryArena;
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::reflection::BxdfType;
+use crate::core::reflection::{BxdfType, ChannelShift};
 use crate::core::sampler::Sampler;
-use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{StatCounter, StatIntDistribution};
 
 // see volpath.h
 
+static VOLUME_INTERACTIONS: StatCounter = StatCounter::new("Integrator/Volume interactions");
+static SURFACE_INTERACTIONS: StatCounter = StatCounter::new("Integrator/Surface interactions");
+static PATH_LENGTH: StatIntDistribution = StatIntDistribution::new("Integrator/Path length");
+
 /// Accounts for scattering and attenuation from participating media
 /// as well as scattering from surfaces
 pub struct VolPathIntegrator {
@@ -26,11 +35,17 @@ pub struct VolPathIntegrator {
     pub camera: Arc<Camera>,
     pub sampler: Box<Sampler>,
     pub pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
     // see volpath.h
     pub max_depth: u32,
     pub rr_threshold: Float,           // 1.0
     pub light_sample_strategy: String, // "spatial"
     pub light_distribution: Option<Arc<LightDistribution>>,
+    /// see **PathIntegrator**
+    pub clamp_indirect: Float,
+    pub roughening: Float,
+    pub terminator_shadowing: bool,
 }
 
 impl VolPathIntegrator {
@@ -46,10 +61,14 @@ impl VolPathIntegrator {
             camera,
             sampler,
             pixel_bounds,
+            debug_non_finite: false,
             max_depth,
             rr_threshold,
             light_sample_strategy,
             light_distribution: None,
+            clamp_indirect: 0.0 as Float,
+            roughening: 0.0 as Float,
+            terminator_shadowing: false,
         }
     }
     pub fn preprocess(&mut self, scene: &Scene) {
@@ -61,7 +80,20 @@ impl VolPathIntegrator {
         r: &mut Ray,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        self.li_first_hit(r, None, scene, sampler, arena, depth)
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        r: &mut Ray,
+        mut first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        _arena: &MemoryArena,
         _depth: i32,
     ) -> Spectrum {
         // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
@@ -86,14 +118,28 @@ impl VolPathIntegrator {
         // refracted rays that are about to be refracted back out of a
         // medium and thus have their beta value increased.
         let mut eta_scale: Float = 1.0;
+        // roughest BSDF lobe (or phase function) so far
+        let mut path_roughness: Float = 0.0 as Float;
+        // prioritized primitives the path is inside of
+        let mut interior: InteriorList = InteriorList::default();
+        // set by fluorescent surfaces
+        let mut channel_shift: Option<ChannelShift> = None;
         loop {
             let mut mi_opt: Option<MediumInteraction> = None;
             // intersect _ray_ with scene and store intersection in _isect_
-            let mut isect: SurfaceInteraction = SurfaceInteraction::default();
-            if scene.intersect(&mut ray, &mut isect) {
+            let (found, mut isect): (bool, SurfaceInteraction) =
+                first_intersection(scene, &mut ray, first_hit.take());
+            if found {
                 // sample the participating medium, if present
                 if let Some(ref medium) = ray.medium {
                     let (spectrum, option) = medium.sample(&ray, sampler);
+                    if let Some(ref mi) = option {
+                        // add light emitted by the medium
+                        let le: Spectrum =
+                            clamp_indirect(beta * mi.le, bounces, self.clamp_indirect);
+                        l += le;
+                        record_light(bounces, &le);
+                    }
                     beta *= spectrum;
                     if let Some(mi) = option {
                         mi_opt = Some(mi);
@@ -108,33 +154,49 @@ impl VolPathIntegrator {
                     if bounces >= self.max_depth {
                         break;
                     }
-                    let mi_p = mi.p;
                     // if mi.is_valid() {...}
                     if let Some(phase) = mi.clone().phase {
-                        // TODO: ++volumeInteractions;
+                        VOLUME_INTERACTIONS.inc();
                         // handle scattering at point in medium for volumetric path tracer
                         if let Some(ref light_distribution) = self.light_distribution {
-                            let distrib: Arc<Distribution1D> = light_distribution.lookup(&mi_p);
-                            l += beta
-                                * uniform_sample_one_light(
+                            let ld: Spectrum = clamp_indirect(
+                                beta * sample_one_light(
                                     &mi as &dyn Interaction,
                                     scene,
                                     sampler,
                                     true,
-                                    Some(&distrib),
-                                );
+                                    light_distribution,
+                                ),
+                                bounces,
+                                self.clamp_indirect,
+                            );
+                            l += ld;
+                            record_light(bounces + 1, &ld);
                             let mut wi: Vector3f = Vector3f::default();
                             phase.sample_p(&(-ray.d), &mut wi, sampler.get_2d());
                             ray = mi.spawn_ray(&wi);
                             specular_bounce = false;
+                            path_roughness = 1.0 as Float;
                         }
                     }
                 } else {
-                    // TODO: ++surfaceInteractions;
+                    SURFACE_INTERACTIONS.inc();
+                    // skip surfaces inside primitives with a higher priority
+                    if interior.is_false_intersection(&isect) {
+                        isect.compute_scattering_functions(&ray, true, TransportMode::Radiance);
+                        let medium: Option<Arc<Medium>> = ray.medium.clone();
+                        ray = isect.spawn_ray(&ray.d);
+                        interior.cross(&isect, &mut ray, medium);
+                        continue;
+                    }
+                    isect.eta_outside = interior.eta_outside(&isect);
                     // possibly add emitted light at intersection
                     if bounces == 0 || specular_bounce {
                         // add emitted light at path vertex
-                        l += beta * isect.le(&-ray.d);
+                        let le: Spectrum =
+                            clamp_indirect(beta * isect.le(&-ray.d), bounces, self.clamp_indirect);
+                        l += le;
+                        record_light(bounces, &le);
                     }
                     // terminate path if _maxDepth_ was reached
                     if bounces >= self.max_depth {
@@ -143,27 +205,34 @@ impl VolPathIntegrator {
                     // compute scattering functions and skip over medium boundaries
                     let mode: TransportMode = TransportMode::Radiance;
                     isect.compute_scattering_functions(&ray, true, mode);
-                    if let Some(ref _bsdf) = isect.bsdf {
-                        // we are fine (for below)
+                    if let Some(ref mut bsdf) = isect.bsdf {
+                        // dispersed light keeps its wavelength
+                        bsdf.set_dispersion_channel(&beta);
+                        bsdf.roughen(self.roughening * path_roughness);
+                        bsdf.shadow_terminator = self.terminator_shadowing;
+                        if bsdf.reradiation.is_some() {
+                            // fluorescent surfaces shift light between wavelengths
+                            beta =
+                                bsdf.reradiate(&beta, &mut l, &mut channel_shift, sampler.get_1d());
+                        }
                     } else {
+                        let medium: Option<Arc<Medium>> = ray.medium.clone();
                         ray = isect.spawn_ray(&ray.d);
+                        interior.cross(&isect, &mut ray, medium);
                         // bounces--;
                         continue;
                     }
                     if let Some(ref light_distribution) = self.light_distribution {
-                        let light_distrib: Arc<Distribution1D> =
-                            light_distribution.lookup(&isect.p);
                         // Sample illumination from lights to find
                         // attenuated path contribution.
                         let it: &SurfaceInteraction = isect.borrow();
-                        l += beta
-                            * uniform_sample_one_light(
-                                it,
-                                scene,
-                                sampler,
-                                true,
-                                Some(&light_distrib),
-                            );
+                        let ld: Spectrum = clamp_indirect(
+                            beta * sample_one_light(it, scene, sampler, true, light_distribution),
+                            bounces,
+                            self.clamp_indirect,
+                        );
+                        l += ld;
+                        record_light(bounces + 1, &ld);
                         if let Some(ref bsdf) = isect.bsdf {
                             // Sample BSDF to get new path direction
                             let wo: Vector3f = -ray.d;
@@ -183,6 +252,9 @@ impl VolPathIntegrator {
                                 break;
                             }
                             beta *= (f * vec3_abs_dot_nrm(&wi, &isect.shading.n)) / pdf;
+                            if report_non_finite(&beta, bounces) {
+                                break;
+                            }
                             assert!(
                                 !(beta.y().is_infinite()),
                                 "[{:#?}, {:?}] = ({:#?} * dot({:#?}, {:#?})) / {:?}",
@@ -194,6 +266,9 @@ impl VolPathIntegrator {
                                 pdf
                             );
                             specular_bounce = (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8;
+                            if !specular_bounce {
+                                path_roughness = path_roughness.max(bsdf.roughness(sampled_type));
+                            }
                             if ((sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8)
                                 && ((sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8)
                             {
@@ -208,24 +283,34 @@ impl VolPathIntegrator {
                                     eta_scale *= 1.0 as Float / (eta * eta);
                                 }
                             }
+                            let medium: Option<Arc<Medium>> = ray.medium.clone();
                             ray = isect.spawn_ray(&wi);
+                            if (sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8 {
+                                interior.cross(&isect, &mut ray, medium);
+                            }
                             // account for attenuated subsurface scattering, if applicable
                             if let Some(ref bssrdf) = isect.bssrdf {
                                 if (sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8 {
-                                    // importance sample the BSSRDF
-                                    let s2: Point2f = sampler.get_2d();
-                                    let s1: Float = sampler.get_1d();
-                                    let (s, pi_opt) = bssrdf.sample_s(
-                                        // the next three (extra) parameters are used for SeparableBssrdfAdapter
-                                        bssrdf.clone(),
-                                        bssrdf.mode,
-                                        bssrdf.eta,
-                                        // done
-                                        scene,
-                                        s1,
-                                        s2,
-                                        &mut pdf,
-                                    );
+                                    let (s, pi_opt) = if bssrdf.random_walk {
+                                        // trace the refracted ray through the interior
+                                        pdf = 1.0 as Float;
+                                        bssrdf.sample_random_walk(scene, &ray, sampler)
+                                    } else {
+                                        // importance sample the BSSRDF
+                                        let s2: Point2f = sampler.get_2d();
+                                        let s1: Float = sampler.get_1d();
+                                        bssrdf.sample_s(
+                                            // the next three (extra) parameters are used for SeparableBssrdfAdapter
+                                            bssrdf.clone(),
+                                            bssrdf.mode,
+                                            bssrdf.eta,
+                                            // done
+                                            scene,
+                                            s1,
+                                            s2,
+                                            &mut pdf,
+                                        )
+                                    };
                                     if s.is_black() || pdf == 0.0 as Float {
                                         break;
                                     }
@@ -233,16 +318,17 @@ impl VolPathIntegrator {
                                     beta *= s / pdf;
                                     if let Some(pi) = pi_opt {
                                         // account for the direct subsurface scattering component
-                                        let distrib: Arc<Distribution1D> =
-                                            light_distribution.lookup(&pi.p);
-                                        l += beta
-                                            * uniform_sample_one_light(
+                                        l += clamp_indirect(
+                                            beta * sample_one_light(
                                                 &pi,
                                                 scene,
                                                 sampler,
                                                 true,
-                                                Some(&distrib),
-                                            );
+                                                light_distribution,
+                                            ),
+                                            bounces + 1,
+                                            self.clamp_indirect,
+                                        );
                                         // account for the indirect subsurface scattering component
                                         let mut wi: Vector3f = Vector3f::default();
                                         let mut pdf: Float = 0.0 as Float;
@@ -275,7 +361,7 @@ impl VolPathIntegrator {
                                 }
                             }
                         } else {
-                            println!("TODO: if let Some(ref bsdf) = isect.bsdf failed");
+                            debug!("TODO: if let Some(ref bsdf) = isect.bsdf failed");
                         }
                     }
                 }
@@ -295,6 +381,13 @@ impl VolPathIntegrator {
                 // sample the participating medium, if present
                 if let Some(ref medium) = ray.medium {
                     let (spectrum, option) = medium.sample(&ray, sampler);
+                    if let Some(ref mi) = option {
+                        // add light emitted by the medium
+                        let le: Spectrum =
+                            clamp_indirect(beta * mi.le, bounces, self.clamp_indirect);
+                        l += le;
+                        record_light(bounces, &le);
+                    }
                     beta *= spectrum;
                     if let Some(mi) = option {
                         mi_opt = Some(mi);
@@ -309,21 +402,24 @@ impl VolPathIntegrator {
                     if bounces >= self.max_depth {
                         break;
                     }
-                    let mi_p = mi.p;
                     // if mi.is_valid() {...}
                     if let Some(phase) = mi.clone().phase {
-                        // TODO: ++volumeInteractions;
+                        VOLUME_INTERACTIONS.inc();
                         // handle scattering at point in medium for volumetric path tracer
                         if let Some(ref light_distribution) = self.light_distribution {
-                            let distrib: Arc<Distribution1D> = light_distribution.lookup(&mi_p);
-                            l += beta
-                                * uniform_sample_one_light(
+                            let ld: Spectrum = clamp_indirect(
+                                beta * sample_one_light(
                                     &mi as &dyn Interaction,
                                     scene,
                                     sampler,
                                     true,
-                                    Some(&distrib),
-                                );
+                                    light_distribution,
+                                ),
+                                bounces,
+                                self.clamp_indirect,
+                            );
+                            l += ld;
+                            record_light(bounces + 1, &ld);
                             let mut wi: Vector3f = Vector3f::default();
                             phase.sample_p(&(-ray.d), &mut wi, sampler.get_2d());
                             ray = mi.spawn_ray(&wi);
@@ -333,15 +429,27 @@ impl VolPathIntegrator {
                 }
                 // add emitted light from the environment
                 if bounces == 0 || specular_bounce {
+                    let l_before: Spectrum = l;
                     for light in &scene.infinite_lights {
-                        l += beta * light.le(&mut ray);
+                        l +=
+                            clamp_indirect(beta * light.le(&mut ray), bounces, self.clamp_indirect);
                     }
+                    record_light(bounces, &(l - l_before));
                 }
                 // terminate path if ray escaped
                 break;
             }
+            if report_non_finite(&l, bounces) || report_non_finite(&beta, bounces) {
+                break;
+            }
             bounces += 1_u32;
         }
+        // e.g. emitted light added before the path was terminated
+        report_non_finite(&l, bounces);
+        PATH_LENGTH.report_value(bounces as u64);
+        if let Some(ref shift) = channel_shift {
+            l = shift.apply(&l);
+        }
         l
     }
     pub fn get_camera(&self) -> Arc<Camera> {
@@ -4,9 +4,11 @@ use std::sync::Arc;
 use crate::core::camera::Camera;
 use crate::core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use crate::core::geometry::{Bounds2i, Normal3f, Ray, RayDifferential, Vector3f};
+use crate::core::integrator::{first_intersection, FirstHit};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::light::VisibilityTester;
 use crate::core::material::TransportMode;
+use crate::core::memory::MemoryArena;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
@@ -20,8 +22,13 @@ pub struct WhittedIntegrator {
     pub camera: Arc<Camera>,
     pub sampler: Box<Sampler>,
     pixel_bounds: Bounds2i,
+    /// see **SamplerIntegrator::set_debug_non_finite()**
+    pub debug_non_finite: bool,
     // see whitted.h
     max_depth: u32,
+    /// soften the shadow terminators of shading normals (see
+    /// **Bsdf::terminator_shadowing()**)
+    pub terminator_shadowing: bool,
 }
 
 impl WhittedIntegrator {
@@ -35,7 +42,9 @@ impl WhittedIntegrator {
             camera,
             sampler,
             pixel_bounds,
+            debug_non_finite: false,
             max_depth,
+            terminator_shadowing: false,
         }
     }
     pub fn preprocess(&mut self, _scene: &Scene) {}
@@ -44,13 +53,27 @@ impl WhittedIntegrator {
         ray: &mut Ray,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
+        depth: i32,
+    ) -> Spectrum {
+        self.li_first_hit(ray, None, scene, sampler, arena, depth)
+    }
+    /// Like **li()**, but starts with the intersection *first_hit* of
+    /// the ray if it was found already.
+    pub fn li_first_hit(
+        &self,
+        ray: &mut Ray,
+        first_hit: FirstHit,
+        scene: &Scene,
+        sampler: &mut Sampler,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         let mut l: Spectrum = Spectrum::default();
         // find closest ray intersection or return background radiance
-        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
-        if scene.intersect(ray, &mut isect) {
+        let (found, mut isect): (bool, SurfaceInteraction) =
+            first_intersection(scene, ray, first_hit);
+        if found {
             // compute emitted and reflected light at ray intersection point
 
             // initialize common variables for Whitted integrator
@@ -60,10 +83,11 @@ impl WhittedIntegrator {
             // compute scattering functions for surface interaction
             let mode: TransportMode = TransportMode::Radiance;
             isect.compute_scattering_functions(ray, false, mode);
+            isect.set_terminator_shadowing(self.terminator_shadowing);
             // if (!isect.bsdf)
             if let Some(ref _bsdf) = isect.bsdf {
             } else {
-                return self.li(&mut isect.spawn_ray(&ray.d), scene, sampler, depth);
+                return self.li(&mut isect.spawn_ray(&ray.d), scene, sampler, arena, depth);
             }
             // compute emitted light if ray hit an area light source
             l += isect.le(&wo);
@@ -103,14 +127,8 @@ impl WhittedIntegrator {
             }
             if depth as u32 + 1 < self.max_depth {
                 // trace rays for specular reflection and refraction
-                l += self.specular_reflect(
-                    ray, &isect, scene, sampler, // arena,
-                    depth,
-                );
-                l += self.specular_transmit(
-                    ray, &isect, scene, sampler, // arena,
-                    depth,
-                );
+                l += self.specular_reflect(ray, &isect, scene, sampler, arena, depth);
+                l += self.specular_transmit(ray, &isect, scene, sampler, arena, depth);
             }
             l
         } else {
@@ -135,7 +153,7 @@ impl WhittedIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         // compute specular reflection direction _wi_ and BSDF value
@@ -180,7 +198,7 @@ impl WhittedIntegrator {
                     };
                     rd.differential = Some(diff);
                 }
-                f * self.li(&mut rd, scene, sampler, depth + 1)
+                f * self.li(&mut rd, scene, sampler, arena, depth + 1)
                     * Spectrum::new(vec3_abs_dot_nrm(&wi, &ns) / pdf)
             } else {
                 Spectrum::new(0.0)
@@ -195,7 +213,7 @@ impl WhittedIntegrator {
         isect: &SurfaceInteraction,
         scene: &Scene,
         sampler: &mut Sampler,
-        // arena: &mut Arena,
+        arena: &MemoryArena,
         depth: i32,
     ) -> Spectrum {
         let wo: Vector3f = isect.wo;
@@ -247,7 +265,7 @@ impl WhittedIntegrator {
                     };
                     rd.differential = Some(diff);
                 }
-                f * self.li(&mut rd, scene, sampler, depth + 1)
+                f * self.li(&mut rd, scene, sampler, arena, depth + 1)
                     * Spectrum::new(vec3_abs_dot_nrm(&wi, &ns) / pdf)
             } else {
                 Spectrum::new(0.0)
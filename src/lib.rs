@@ -13,6 +13,13 @@
 //! 2. [render loop][render_mlt] for Metropolis Light Transport
 //! 2. [render loop][render_sppm] for Stochastic Progressive Photon Mapping
 //!
+//! Warnings, errors, and other messages of the renderer are [log]
+//! events (with the module as target), a program using the crate
+//! decides where they go by installing a logger (e.g.
+//! [env_logger]). Without a logger they are dropped.
+//!
+//! [log]: https://docs.rs/log
+//! [env_logger]: https://docs.rs/env_logger
 //! [rust]: https://www.rust-lang.org
 //! [book]: http://www.pbrt.org
 //! [repo]: https://github.com/wahn/rs_pbrt
@@ -32,3 +39,5 @@ pub mod media;
 pub mod samplers;
 pub mod shapes;
 pub mod textures;
+#[cfg(feature = "wasm")]
+pub mod wasm;
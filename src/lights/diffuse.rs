@@ -1,6 +1,5 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{nrm_abs_dot_vec3, nrm_dot_vec3, vec3_coordinate_system};
@@ -8,6 +7,7 @@ use crate::core::geometry::{Normal3f, Point2f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::{Medium, MediumInterface};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::rng::FLOAT_ONE_MINUS_EPSILON;
 use crate::core::sampling::{cosine_hemisphere_pdf, cosine_sample_hemisphere};
@@ -79,6 +79,13 @@ impl DiffuseAreaLight {
         }
         let new_wi: Vector3f = (p_shape.p - iref.p).normalize();
         *wi = new_wi;
+        // a one-sided light seen from behind contributes nothing, no
+        // need to trace a shadow ray
+        let l: Spectrum = self.l(&p_shape, &-new_wi);
+        if l.is_black() {
+            *pdf = 0.0 as Float;
+            return Spectrum::default();
+        }
         vis.p0 = InteractionCommon {
             p: iref.p,
             time: iref.time,
@@ -95,7 +102,7 @@ impl DiffuseAreaLight {
             n: p_shape.n,
             medium_interface: None,
         };
-        self.l(&p_shape, &-new_wi)
+        l
     }
     pub fn power(&self) -> Spectrum {
         // return (twoSided ? 2 : 1) * Lemit * area * Pi;
@@ -165,7 +172,13 @@ impl DiffuseAreaLight {
         if self.two_sided {
             *pdf_dir = 0.5 as Float * cosine_hemisphere_pdf(nrm_abs_dot_vec3(&n, &ray.d));
         } else {
-            *pdf_dir = cosine_hemisphere_pdf(nrm_dot_vec3(&n, &ray.d));
+            // no emission (and therefore no density) behind a one-sided light
+            let cos_theta: Float = nrm_dot_vec3(n, &ray.d);
+            if cos_theta > 0.0 as Float {
+                *pdf_dir = cosine_hemisphere_pdf(cos_theta);
+            } else {
+                *pdf_dir = 0.0 as Float;
+            }
         }
     }
     pub fn get_flags(&self) -> u8 {
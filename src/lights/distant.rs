@@ -1,6 +1,5 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::RwLock;
 // pbrt
 use crate::core::geometry::vec3_coordinate_system;
@@ -8,6 +7,7 @@ use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f}
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::MediumInterface;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::sampling::concentric_sample_disk;
 use crate::core::scene::Scene;
@@ -129,7 +129,7 @@ impl DistantLight {
         *ray = Ray {
             o: p_disk + self.w_light * world_radius,
             d: -self.w_light,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: None,
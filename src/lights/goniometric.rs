@@ -1,11 +1,11 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::io::BufReader;
 use std::sync::Arc;
 // others
 #[cfg(feature = "openexr")]
 use half::f16;
+use log::warn;
 #[cfg(feature = "openexr")]
 use openexr::{FrameBufferMut, InputFile, PixelType};
 // pbrt
@@ -14,7 +14,9 @@ use crate::core::geometry::{Normal3f, Point2f, Point2i, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::MediumInterface;
+use crate::core::memory::readable_path;
 use crate::core::mipmap::{ImageWrap, MipMap};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::pbrt::{INV_2_PI, INV_PI};
 use crate::core::sampling::{uniform_sample_sphere, uniform_sphere_pdf};
@@ -32,9 +34,9 @@ fn decode_f16(half: u16) -> f32 {
     } else if exp != 31 {
         (mant as f32 + 1024f32) * (2.0f32).powi(exp as i32 - 25)
     } else if mant == 0 {
-        ::std::f32::INFINITY
+        ::Float::INFINITY
     } else {
-        ::std::f32::NAN
+        ::Float::NAN
     };
     if half & 0x8000 != 0 {
         -val
@@ -81,7 +83,7 @@ impl GonioPhotometricLight {
             let mut resolution: Point2i = Point2i::default();
             let mut names_and_fills: Vec<(&str, f64)> = Vec::new();
             // header
-            let file_result = std::fs::File::open(texname.clone());
+            let file_result = readable_path(&texname).and_then(std::fs::File::open);
             if file_result.is_ok() {
                 let mut file = file_result.unwrap();
                 let input_file_result = InputFile::new(&mut file);
@@ -107,7 +109,9 @@ impl GonioPhotometricLight {
                         ];
                     {
                         // read pixels
-                        let mut file = std::fs::File::open(texname.clone()).unwrap();
+                        let mut file = readable_path(&texname)
+                            .and_then(std::fs::File::open)
+                            .unwrap();
                         let mut input_file = InputFile::new(&mut file).unwrap();
                         let mut fb = FrameBufferMut::new(resolution.x as u32, resolution.y as u32);
                         fb.insert_channels(&names_and_fills[..], &mut pixel_data);
@@ -172,7 +176,9 @@ impl GonioPhotometricLight {
         texname: String,
     ) -> Self {
         if texname != "" {
-            let file = std::fs::File::open(texname).unwrap();
+            let file = readable_path(&texname)
+                .and_then(std::fs::File::open)
+                .unwrap();
             let reader = BufReader::new(file);
             let img_result = image::hdr::HdrDecoder::with_strictness(reader, false);
             if img_result.is_ok() {
@@ -187,7 +193,7 @@ impl GonioPhotometricLight {
                     let img_result = hdr.read_image_transform(
                         |p| {
                             let rgb = p.to_hdr();
-                            Spectrum::rgb(rgb[0], rgb[1], rgb[2])
+                            Spectrum::rgb(rgb[0] as Float, rgb[1] as Float, rgb[2] as Float)
                         },
                         &mut texels,
                     );
@@ -217,7 +223,7 @@ impl GonioPhotometricLight {
                     }
                 }
             } else {
-                println!("WARNING: ProjectionLight::new() ... no OpenEXR support !!!");
+                warn!("ProjectionLight::new() ... no OpenEXR support !!!");
             }
         }
         GonioPhotometricLight {
@@ -314,7 +320,7 @@ impl GonioPhotometricLight {
         *ray = Ray {
             o: self.p_light,
             d: uniform_sample_sphere(u1),
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: None,
@@ -1,10 +1,10 @@
 // std
-use std::f32::consts::PI;
 use std::io::BufReader;
 use std::sync::{Arc, RwLock};
 // others
 #[cfg(feature = "openexr")]
 use half::f16;
+use log::warn;
 #[cfg(feature = "openexr")]
 use openexr::{FrameBufferMut, InputFile, PixelType};
 // pbrt
@@ -13,7 +13,9 @@ use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point2i, Point3f, Ray,
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::MediumInterface;
+use crate::core::memory::readable_path;
 use crate::core::mipmap::{ImageWrap, MipMap};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::pbrt::{INV_2_PI, INV_PI};
 use crate::core::sampling::concentric_sample_disk;
@@ -32,9 +34,9 @@ fn decode_f16(half: u16) -> f32 {
     } else if exp != 31 {
         (mant as f32 + 1024f32) * (2.0f32).powi(exp as i32 - 25)
     } else if mant == 0 {
-        ::std::f32::INFINITY
+        ::Float::INFINITY
     } else {
-        ::std::f32::NAN
+        ::Float::NAN
     };
     if half & 0x8000 != 0 {
         -val
@@ -72,7 +74,7 @@ impl InfiniteAreaLight {
             let mut resolution: Point2i = Point2i::default();
             let mut names_and_fills: Vec<(&str, f64)> = Vec::new();
             // header
-            let file_result = std::fs::File::open(texmap.clone());
+            let file_result = readable_path(&texmap).and_then(std::fs::File::open);
             if file_result.is_ok() {
                 let mut file = file_result.unwrap();
                 let input_file_result = InputFile::new(&mut file);
@@ -98,7 +100,9 @@ impl InfiniteAreaLight {
                         ];
                     {
                         // read pixels
-                        let mut file = std::fs::File::open(texmap.clone()).unwrap();
+                        let mut file = readable_path(&texmap)
+                            .and_then(std::fs::File::open)
+                            .unwrap();
                         let mut input_file = InputFile::new(&mut file).unwrap();
                         let mut fb = FrameBufferMut::new(resolution.x as u32, resolution.y as u32);
                         fb.insert_channels(&names_and_fills[..], &mut pixel_data);
@@ -179,7 +183,9 @@ impl InfiniteAreaLight {
     ) -> Self {
         // read texel data from _texmap_ and initialize _Lmap_
         if texmap != "" {
-            let file = std::fs::File::open(texmap).unwrap();
+            let file = readable_path(&texmap)
+                .and_then(std::fs::File::open)
+                .unwrap();
             let reader = BufReader::new(file);
             let img_result = image::hdr::HdrDecoder::with_strictness(reader, false);
             if img_result.is_ok() {
@@ -194,7 +200,7 @@ impl InfiniteAreaLight {
                     let img_result = hdr.read_image_transform(
                         |p| {
                             let rgb = p.to_hdr();
-                            Spectrum::rgb(rgb[0], rgb[1], rgb[2]) * *l
+                            Spectrum::rgb(rgb[0] as Float, rgb[1] as Float, rgb[2] as Float) * *l
                         },
                         &mut texels,
                     );
@@ -245,7 +251,7 @@ impl InfiniteAreaLight {
                     }
                 }
             } else {
-                println!("WARNING: InfiniteAreaLight::new() ... no OpenEXR support !!!");
+                warn!("InfiniteAreaLight::new() ... no OpenEXR support !!!");
             }
         }
         InfiniteAreaLight::default(n_samples, l)
@@ -448,7 +454,7 @@ impl InfiniteAreaLight {
         *ray = Ray {
             o: p_disk + -d * world_radius,
             d,
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: None,
@@ -20,7 +20,9 @@
 //! uniform spatial and directional radiance distribution. The surface
 //! it emits from is defined by a **Shape**. It only emits light on
 //! the side of the surface with outward-facing surface normal; there
-//! is no emission from the other side.
+//! is no emission from the other side, unless the light is created
+//! with `"bool twosided" [ "true" ]`. Two-sided lights are useful for
+//! imported light planes whose orientation is not known.
 //!
 //! ## Distant Lights
 //!
@@ -1,6 +1,5 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::pnt3_distance_squared;
@@ -8,6 +7,7 @@ use crate::core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::{Medium, MediumInterface};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::sampling::{uniform_sample_sphere, uniform_sphere_pdf};
 use crate::core::scene::Scene;
@@ -106,7 +106,7 @@ impl PointLight {
         *ray = Ray {
             o: self.p_light,
             d: uniform_sample_sphere(u1),
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: None,
@@ -1,11 +1,11 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::io::BufReader;
 use std::sync::Arc;
 // others
 #[cfg(feature = "openexr")]
 use half::f16;
+use log::warn;
 #[cfg(feature = "openexr")]
 use openexr::{FrameBufferMut, InputFile, PixelType};
 // pbrt
@@ -14,7 +14,9 @@ use crate::core::geometry::{Bounds2f, Normal3f, Point2f, Point2i, Point3f, Ray,
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::{Medium, MediumInterface};
+use crate::core::memory::readable_path;
 use crate::core::mipmap::{ImageWrap, MipMap};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::cos_theta;
 use crate::core::sampling::{uniform_cone_pdf, uniform_sample_cone};
@@ -32,9 +34,9 @@ fn decode_f16(half: u16) -> f32 {
     } else if exp != 31 {
         (mant as f32 + 1024f32) * (2.0f32).powi(exp as i32 - 25)
     } else if mant == 0 {
-        ::std::f32::INFINITY
+        ::Float::INFINITY
     } else {
-        ::std::f32::NAN
+        ::Float::NAN
     };
     if half & 0x8000 != 0 {
         -val
@@ -87,7 +89,7 @@ impl ProjectionLight {
             let mut resolution: Point2i = Point2i::default();
             let mut names_and_fills: Vec<(&str, f64)> = Vec::new();
             // header
-            let file_result = std::fs::File::open(texname.clone());
+            let file_result = readable_path(&texname).and_then(std::fs::File::open);
             if file_result.is_ok() {
                 let mut file = file_result.unwrap();
                 let input_file_result = InputFile::new(&mut file);
@@ -113,7 +115,9 @@ impl ProjectionLight {
                         ];
                     {
                         // read pixels
-                        let mut file = std::fs::File::open(texname.clone()).unwrap();
+                        let mut file = readable_path(&texname)
+                            .and_then(std::fs::File::open)
+                            .unwrap();
                         let mut input_file = InputFile::new(&mut file).unwrap();
                         let mut fb = FrameBufferMut::new(resolution.x as u32, resolution.y as u32);
                         fb.insert_channels(&names_and_fills[..], &mut pixel_data);
@@ -232,7 +236,9 @@ impl ProjectionLight {
         fov: Float,
     ) -> Self {
         if texname != "" {
-            let file = std::fs::File::open(texname).unwrap();
+            let file = readable_path(&texname)
+                .and_then(std::fs::File::open)
+                .unwrap();
             let reader = BufReader::new(file);
             let img_result = image::hdr::HdrDecoder::with_strictness(reader, false);
             if img_result.is_ok() {
@@ -247,7 +253,7 @@ impl ProjectionLight {
                     let img_result = hdr.read_image_transform(
                         |p| {
                             let rgb = p.to_hdr();
-                            Spectrum::rgb(rgb[0], rgb[1], rgb[2])
+                            Spectrum::rgb(rgb[0] as Float, rgb[1] as Float, rgb[2] as Float)
                         },
                         &mut texels,
                     );
@@ -318,7 +324,7 @@ impl ProjectionLight {
                     }
                 }
             } else {
-                println!("WARNING: ProjectionLight::new() ... no OpenEXR support !!!");
+                warn!("ProjectionLight::new() ... no OpenEXR support !!!");
             }
         }
         ProjectionLight {
@@ -437,7 +443,7 @@ impl ProjectionLight {
         *ray = Ray {
             o: self.p_light,
             d: self.light_to_world.transform_vector(&v),
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: inside,
@@ -1,6 +1,5 @@
 // std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::pnt3_distance_squared;
@@ -8,6 +7,7 @@ use crate::core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon};
 use crate::core::light::{LightFlags, VisibilityTester};
 use crate::core::medium::{Medium, MediumInterface};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::radians;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::cos_theta;
@@ -163,7 +163,7 @@ impl SpotLight {
         *ray = Ray {
             o: self.p_light,
             d: self.light_to_world.transform_vector(&w),
-            t_max: std::f32::INFINITY,
+            t_max: Float::INFINITY,
             time,
             differential: None,
             medium: inside,
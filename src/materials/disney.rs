@@ -1,4 +1,3 @@
-use std::f32;
 use std::sync::Arc;
 
 use num::Zero;
@@ -7,9 +6,11 @@ use crate::core::geometry::{spherical_direction, vec3_abs_dot_vec3, vec3_dot_vec
 use crate::core::geometry::{Point2f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
-use crate::core::pbrt::{clamp_t, lerp};
+use crate::core::pbrt::{clamp_t, consts, lerp};
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::reflect;
 use crate::core::reflection::{abs_cos_theta, fr_schlick, vec3_same_hemisphere_vec3};
@@ -20,23 +21,24 @@ use crate::core::reflection::{
 use crate::core::texture::Texture;
 
 pub struct DisneyMaterial {
-    color: Arc<dyn Texture<Spectrum> + Send + Sync>,
+    color: Arc<Texture<Spectrum>>,
     // base_color: Arc<TextureFloat>,
-    metallic: Arc<dyn Texture<Float> + Send + Sync>,
-    eta: Arc<dyn Texture<Float> + Send + Sync>,
-    roughness: Arc<dyn Texture<Float> + Send + Sync>,
-    specular_tint: Arc<dyn Texture<Float> + Send + Sync>,
-    anisotropic: Arc<dyn Texture<Float> + Send + Sync>,
-    sheen: Arc<dyn Texture<Float> + Send + Sync>,
-    sheen_tint: Arc<dyn Texture<Float> + Send + Sync>,
-    clearcoat: Arc<dyn Texture<Float> + Send + Sync>,
-    clearcoat_gloss: Arc<dyn Texture<Float> + Send + Sync>,
-    spec_trans: Arc<dyn Texture<Float> + Send + Sync>,
-    scatter_distance: Arc<dyn Texture<Spectrum> + Send + Sync>,
-    flatness: Arc<dyn Texture<Float> + Send + Sync>,
-    diff_trans: Arc<dyn Texture<Float> + Send + Sync>,
-    bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+    metallic: Arc<Texture<Float>>,
+    eta: Arc<Texture<Float>>,
+    roughness: Arc<Texture<Float>>,
+    specular_tint: Arc<Texture<Float>>,
+    anisotropic: Arc<Texture<Float>>,
+    sheen: Arc<Texture<Float>>,
+    sheen_tint: Arc<Texture<Float>>,
+    clearcoat: Arc<Texture<Float>>,
+    clearcoat_gloss: Arc<Texture<Float>>,
+    spec_trans: Arc<Texture<Float>>,
+    scatter_distance: Arc<Texture<Spectrum>>,
+    flatness: Arc<Texture<Float>>,
+    diff_trans: Arc<Texture<Float>>,
+    bump_map: Option<Arc<Texture<Float>>>,
     thin: bool,
+    roughness_mapping: RoughnessMapping,
 }
 
 impl DisneyMaterial {
@@ -57,6 +59,7 @@ impl DisneyMaterial {
         let flatness = mp.get_float_texture("flatness", 0.0);
         let diff_trans = mp.get_float_texture("difftrans", 1.0);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let roughness_mapping = find_roughness_mapping(mp, RoughnessMapping::Square);
 
         Arc::new(Material::Disney(Box::new(DisneyMaterial {
             color,
@@ -75,6 +78,7 @@ impl DisneyMaterial {
             diff_trans,
             bump_map,
             thin,
+            roughness_mapping,
         })))
     }
     // Material
@@ -96,7 +100,7 @@ impl DisneyMaterial {
             Material::bump(bump, si);
         }
         // diffuse
-        let c = self.color.evaluate(si).clamp(0.0, f32::INFINITY);
+        let c = self.color.evaluate(si).clamp(0.0, Float::INFINITY);
         let metallic_weight = self.metallic.evaluate(si);
         let e = self.eta.evaluate(si);
         let strans = self.spec_trans.evaluate(si);
@@ -219,8 +223,9 @@ impl DisneyMaterial {
             }
 
             // Create the microfacet distribution for metallic and/or specular transmission.
-            let ax = Float::max(0.001, sqr(rough) / aspect);
-            let ay = Float::max(0.001, sqr(rough) * aspect);
+            let alpha = self.roughness_mapping.alpha(rough);
+            let ax = Float::max(0.001, alpha / aspect);
+            let ay = Float::max(0.001, alpha * aspect);
             let distrib =
                 MicrofacetDistribution::DisneyMicrofacet(DisneyMicrofacetDistribution::new(ax, ay));
 
@@ -261,8 +266,9 @@ impl DisneyMaterial {
                 if self.thin {
                     // Scale roughness based on IOR (Burley 2015, Figure 15).
                     let rscaled = (0.65 * e - 0.35) * rough;
-                    let ax = Float::max(0.001, sqr(rscaled) / aspect);
-                    let ay = Float::max(0.001, sqr(rscaled) * aspect);
+                    let alpha = self.roughness_mapping.alpha(rscaled);
+                    let ax = Float::max(0.001, alpha / aspect);
+                    let ay = Float::max(0.001, alpha * aspect);
                     let scaled_distrib = MicrofacetDistribution::TrowbridgeReitz(
                         TrowbridgeReitzDistribution::new(ax, ay, true),
                     );
@@ -344,9 +350,9 @@ impl DisneyDiffuse {
         // Diffuse fresnel - go from 1 at normal incidence to .5 at grazing.
         // Burley 2015, eq (4).
         if let Some(sc) = self.sc_opt {
-            sc * self.r * f32::consts::FRAC_1_PI * (1.0 - fo / 2.0) * (1.0 - fi / 2.0)
+            sc * self.r * consts::FRAC_1_PI * (1.0 - fo / 2.0) * (1.0 - fi / 2.0)
         } else {
-            self.r * f32::consts::FRAC_1_PI * (1.0 - fo / 2.0) * (1.0 - fi / 2.0)
+            self.r * consts::FRAC_1_PI * (1.0 - fo / 2.0) * (1.0 - fi / 2.0)
         }
     }
     pub fn get_type(&self) -> u8 {
@@ -388,9 +394,9 @@ impl DisneyFakeSS {
         let ss = 1.25 * (fss * (1.0 / (abs_cos_theta(wo) + abs_cos_theta(wi)) - 0.5) + 0.5);
 
         if let Some(sc) = self.sc_opt {
-            sc * self.r * f32::consts::FRAC_1_PI * ss
+            sc * self.r * consts::FRAC_1_PI * ss
         } else {
-            self.r * f32::consts::FRAC_1_PI * ss
+            self.r * consts::FRAC_1_PI * ss
         }
     }
     pub fn get_type(&self) -> u8 {
@@ -428,9 +434,9 @@ impl DisneyRetro {
 
         // Burley 2015, eq (4).
         if let Some(sc) = self.sc_opt {
-            sc * self.r * f32::consts::FRAC_1_PI * rr * (fo + fi + fo * fi * (rr - 1.0))
+            sc * self.r * consts::FRAC_1_PI * rr * (fo + fi + fo * fi * (rr - 1.0))
         } else {
-            self.r * f32::consts::FRAC_1_PI * rr * (fo + fi + fo * fi * (rr - 1.0))
+            self.r * consts::FRAC_1_PI * rr * (fo + fi + fo * fi * (rr - 1.0))
         }
     }
     pub fn get_type(&self) -> u8 {
@@ -525,7 +531,7 @@ impl DisneyClearCoat {
             (1.0 - Float::powf(alpha2, 1.0 - u[0])) / (1.0 - alpha2),
         ));
         let sin_theta = Float::sqrt(Float::max(0.0, 1.0 - cos_theta * cos_theta));
-        let phi = 2.0 * f32::consts::PI * u[1];
+        let phi = 2.0 * consts::PI * u[1];
         let mut wh = spherical_direction(sin_theta, cos_theta, phi);
         if !vec3_same_hemisphere_vec3(wo, &wh) {
             wh = -wh;
@@ -631,7 +637,7 @@ fn gtr1(cos_theta: Float, alpha: Float) -> Float {
     let alpha2 = alpha * alpha;
 
     (alpha2 - 1.0)
-        / (f32::consts::PI * Float::log10(alpha2) * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
+        / (consts::PI * Float::log10(alpha2) * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
 }
 
 #[inline]
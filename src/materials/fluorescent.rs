@@ -0,0 +1,134 @@
+//std
+use std::sync::Arc;
+// others
+use log::warn;
+// pbrt
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::material::{Material, TransportMode};
+use crate::core::paramset::TextureParams;
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::reflection::{Bsdf, Bxdf, LambertianReflection};
+use crate::core::texture::Texture;
+
+/// Describes a diffuse surface which absorbs light at short
+/// wavelengths and re-emits (part of) it at longer ones, like
+/// fluorescent paints or optical brighteners in paper.
+///
+/// The re-radiation matrix has one row per re-emitted and one column
+/// per incident spectral channel (see
+/// **Spectrum::channel_wavelength()**), so it mostly makes sense with
+/// the **sampled_spectrum** feature.  The diagonal holds the ordinary
+/// (non-fluorescent) reflectance.  Only the path and volpath
+/// integrators sample wavelength shifts, the others treat the surface
+/// as a white diffuse reflector.
+pub struct FluorescentMaterial {
+    /// row-major, **Spectrum::N_SAMPLES** squared entries
+    pub reradiation: Arc<Vec<Float>>,
+    pub bump_map: Option<Arc<Texture<Float>>>,
+}
+
+impl FluorescentMaterial {
+    pub fn new(reradiation: Vec<Float>, bump_map: Option<Arc<Texture<Float>>>) -> Self {
+        assert_eq!(reradiation.len(), Spectrum::N_SAMPLES * Spectrum::N_SAMPLES);
+        FluorescentMaterial {
+            reradiation: Arc::new(reradiation),
+            bump_map,
+        }
+    }
+    /// Builds a re-radiation matrix from a Gaussian excitation
+    /// (absorption) band and a Gaussian emission band (both given by
+    /// their peak wavelength and width in nm). A fraction
+    /// *efficiency* of the absorbed light gets re-emitted, but only at
+    /// longer wavelengths (Stokes shift). *kd* is added as ordinary
+    /// diffuse reflectance.
+    pub fn reradiation_from_bands(
+        kd: &Spectrum,
+        excitation: Float,
+        emission: Float,
+        width: Float,
+        efficiency: Float,
+    ) -> Vec<Float> {
+        let n: usize = Spectrum::N_SAMPLES;
+        let gaussian = |lambda: Float, peak: Float| -> Float {
+            let x: Float = (lambda - peak) / width;
+            (-0.5 as Float * x * x).exp()
+        };
+        let mut matrix: Vec<Float> = vec![0.0 as Float; n * n];
+        for j in 0..n {
+            let lambda_j: Float = Spectrum::channel_wavelength(j);
+            let absorbed: Float = gaussian(lambda_j, excitation);
+            // distribute the re-emitted energy over longer wavelengths
+            let mut weights: Vec<Float> = vec![0.0 as Float; n];
+            let mut sum: Float = 0.0 as Float;
+            for (i, weight) in weights.iter_mut().enumerate() {
+                let lambda_i: Float = Spectrum::channel_wavelength(i);
+                if lambda_i > lambda_j {
+                    *weight = gaussian(lambda_i, emission);
+                    sum += *weight;
+                }
+            }
+            // what is absorbed is not reflected any more
+            let reflectance: Float = kd[j] * (1.0 as Float - absorbed);
+            matrix[j * n + j] += reflectance;
+            if sum > 0.0 as Float {
+                for i in 0..n {
+                    matrix[i * n + j] += efficiency * absorbed * weights[i] / sum;
+                }
+            }
+        }
+        matrix
+    }
+    pub fn create(mp: &mut TextureParams) -> Arc<Material> {
+        let n: usize = Spectrum::N_SAMPLES;
+        let kd: Spectrum = mp.find_spectrum("Kd", Spectrum::new(0.5));
+        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let mut matrix: Vec<Float> = mp.material_params.find_float("reradiation");
+        if matrix.is_empty() {
+            matrix = mp.geom_params.find_float("reradiation");
+        }
+        if !matrix.is_empty() && matrix.len() != n * n {
+            warn!(
+                "\"reradiation\" needs {} values ({}x{} spectral channels), got {}.  Ignoring it.",
+                n * n,
+                n,
+                n,
+                matrix.len()
+            );
+            matrix.clear();
+        }
+        if matrix.is_empty() {
+            let excitation: Float = mp.find_float("excitation", 380.0 as Float);
+            let emission: Float = mp.find_float("emission", 440.0 as Float);
+            let width: Float = mp.find_float("bandwidth", 25.0 as Float);
+            let efficiency: Float = mp.find_float("efficiency", 0.8 as Float);
+            matrix = FluorescentMaterial::reradiation_from_bands(
+                &kd, excitation, emission, width, efficiency,
+            );
+        }
+        Arc::new(Material::Fluorescent(Box::new(FluorescentMaterial::new(
+            matrix, bump_map,
+        ))))
+    }
+    // Material
+    pub fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        // arena: &mut Arena,
+        _mode: TransportMode,
+        _allow_multiple_lobes: bool,
+        _material: Option<Arc<Material>>,
+        scale_opt: Option<Spectrum>,
+    ) {
+        if let Some(ref bump) = self.bump_map {
+            Material::bump(bump, si);
+        }
+        let mut bsdf: Bsdf = Bsdf::new(si, 1.0);
+        // the spectral part of the reflectance is in the matrix
+        bsdf.bxdfs[0] = Bxdf::LambertianRefl(LambertianReflection::new(
+            Spectrum::new(1.0 as Float),
+            scale_opt,
+        ));
+        bsdf.reradiation = Some(self.reradiation.clone());
+        si.bsdf = Some(bsdf);
+    }
+}
@@ -1,5 +1,7 @@
 //std
 use std::sync::Arc;
+// others
+use log::debug;
 // pbrt
 use crate::core::api::BsdfState;
 use crate::core::interaction::SurfaceInteraction;
@@ -13,22 +15,18 @@ use crate::core::texture::Texture;
 
 pub struct FourierMaterial {
     pub bsdf_table: Arc<FourierBSDFTable>,
-    pub bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
+    pub bump_map: Option<Arc<Texture<Float>>>,
 }
 
 impl FourierMaterial {
-    pub fn new(
-        bsdf_table: Arc<FourierBSDFTable>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    ) -> Self {
+    pub fn new(bsdf_table: Arc<FourierBSDFTable>, bump_map: Option<Arc<Texture<Float>>>) -> Self {
         FourierMaterial {
             bump_map,
             bsdf_table,
         }
     }
     pub fn create(mp: &mut TextureParams, bsdf_state: &mut BsdfState) -> Arc<Material> {
-        let bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("bumpmap");
+        let bump_map: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("bumpmap");
         let bsdffile: String = mp.find_filename("bsdffile", String::new());
         if let Some(bsdf_table) = bsdf_state.loaded_bsdfs.get(&bsdffile.clone()) {
             // use the BSDF table found
@@ -39,7 +37,7 @@ impl FourierMaterial {
         } else {
             // read BSDF table from file
             let mut bsdf_table: FourierBSDFTable = FourierBSDFTable::default();
-            println!(
+            debug!(
                 "reading {:?} returns {}",
                 bsdffile,
                 bsdf_table.read(&bsdffile)
@@ -4,12 +4,14 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{
-    Bsdf, Bxdf, Fresnel, FresnelDielectric, FresnelSpecular, MicrofacetReflection,
-    MicrofacetTransmission, SpecularReflection, SpecularTransmission,
+    cauchy_b_from_abbe, Bsdf, Bxdf, Fresnel, FresnelDielectric, FresnelSpecular,
+    MicrofacetReflection, MicrofacetTransmission, SpecularReflection, SpecularTransmission,
 };
 use crate::core::texture::Texture;
 
@@ -18,24 +20,27 @@ use crate::core::texture::Texture;
 /// Perfect or glossy specular reflection and transmission, weighted
 /// by Fresnel terms for accurate angular-dependent variation.
 pub struct GlassMaterial {
-    pub kr: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 1.0
-    pub kt: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 1.0
-    pub u_roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.0
-    pub v_roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.0
-    pub index: Arc<dyn Texture<Float> + Sync + Send>,
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub remap_roughness: bool,
+    pub kr: Arc<Texture<Spectrum>>,       // default: 1.0
+    pub kt: Arc<Texture<Spectrum>>,       // default: 1.0
+    pub u_roughness: Arc<Texture<Float>>, // default: 0.0
+    pub v_roughness: Arc<Texture<Float>>, // default: 0.0
+    pub index: Arc<Texture<Float>>,
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping,
+    /// Cauchy coefficient B in square micrometers (default: 0.0, no
+    /// dispersion), only used for smooth glass
+    pub cauchy_b: Float,
 }
 
 impl GlassMaterial {
     pub fn new(
-        kr: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        kt: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        u_roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        v_roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        index: Arc<dyn Texture<Float> + Send + Sync>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        kr: Arc<Texture<Spectrum>>,
+        kt: Arc<Texture<Spectrum>>,
+        u_roughness: Arc<Texture<Float>>,
+        v_roughness: Arc<Texture<Float>>,
+        index: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         GlassMaterial {
             kr,
@@ -44,7 +49,8 @@ impl GlassMaterial {
             v_roughness,
             index,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
+            cauchy_b: 0.0 as Float,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
@@ -53,32 +59,26 @@ impl GlassMaterial {
         let roughu = mp.get_float_texture("uroughness", 0.0 as Float);
         let roughv = mp.get_float_texture("vroughness", 0.0 as Float);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
-        let eta_option: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("eta");
-        if let Some(ref eta) = eta_option {
-            Arc::new(Material::Glass(Box::new(GlassMaterial::new(
-                kr,
-                kt,
-                roughu,
-                roughv,
-                eta.clone(),
-                bump_map,
-                remap_roughness,
-            ))))
-        } else {
-            let eta: Arc<dyn Texture<Float> + Send + Sync> =
-                mp.get_float_texture("index", 1.5 as Float);
-            Arc::new(Material::Glass(Box::new(GlassMaterial::new(
-                kr,
-                kt,
-                roughu,
-                roughv,
-                eta,
-                bump_map,
-                remap_roughness,
-            ))))
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
+        // dispersion, either directly or via the Abbe number
+        let abbe: Float = mp.find_float("abbe", 0.0 as Float);
+        let mut cauchy_b: Float = mp.find_float("cauchyb", 0.0 as Float);
+        if cauchy_b == 0.0 as Float && abbe > 0.0 as Float {
+            let index: Float = mp.find_float("index", 1.5 as Float);
+            let eta_d: Float = mp.find_float("eta", index);
+            cauchy_b = cauchy_b_from_abbe(eta_d, abbe);
         }
+        let eta_option: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("eta");
+        let eta: Arc<Texture<Float>> = if let Some(ref eta) = eta_option {
+            eta.clone()
+        } else {
+            mp.get_float_texture("index", 1.5 as Float)
+        };
+        let mut material: GlassMaterial =
+            GlassMaterial::new(kr, kt, roughu, roughv, eta, bump_map, roughness_mapping);
+        material.cauchy_b = cauchy_b;
+        Arc::new(Material::Glass(Box::new(material)))
     }
     // Material
     pub fn compute_scattering_functions(
@@ -104,43 +104,31 @@ impl GlassMaterial {
         let r: Spectrum = self
             .kr
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let t: Spectrum = self
             .kt
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let is_specular: bool = urough == 0.0 as Float && vrough == 0.0 as Float;
-        let eta: Float = self.index.evaluate(si);
+        // relative to the enclosing dielectric (if any)
+        let eta_outside: Float = si.eta_outside.unwrap_or(1.0 as Float);
+        let eta: Float = self.index.evaluate(si) / eta_outside;
+        let cauchy_b: Float = self.cauchy_b / eta_outside;
         si.bsdf = Some(Bsdf::new(si, eta));
         if let Some(bsdf) = &mut si.bsdf {
             let mut bxdf_idx: usize = 0;
             if is_specular && allow_multiple_lobes {
-                if use_scale {
-                    bsdf.bxdfs[bxdf_idx] = Bxdf::FresnelSpec(FresnelSpecular::new(
-                        r,
-                        t,
-                        1.0 as Float,
-                        eta,
-                        mode,
-                        Some(sc),
-                    ));
-                // bxdf_idx += 1;
+                let mut fresnel_specular: FresnelSpecular = if use_scale {
+                    FresnelSpecular::new(r, t, 1.0 as Float, eta, mode, Some(sc))
                 } else {
-                    bsdf.bxdfs[bxdf_idx] = Bxdf::FresnelSpec(FresnelSpecular::new(
-                        r,
-                        t,
-                        1.0 as Float,
-                        eta,
-                        mode,
-                        None,
-                    ));
-                    // bxdf_idx += 1;
-                }
+                    FresnelSpecular::new(r, t, 1.0 as Float, eta, mode, None)
+                };
+                fresnel_specular.cauchy_b = cauchy_b;
+                bsdf.bxdfs[bxdf_idx] = Bxdf::FresnelSpec(fresnel_specular);
+                // bxdf_idx += 1;
             } else {
-                if self.remap_roughness {
-                    urough = TrowbridgeReitzDistribution::roughness_to_alpha(urough);
-                    vrough = TrowbridgeReitzDistribution::roughness_to_alpha(vrough);
-                }
+                urough = self.roughness_mapping.alpha(urough);
+                vrough = self.roughness_mapping.alpha(vrough);
                 if !r.is_black() {
                     let fresnel = Fresnel::Dielectric(FresnelDielectric {
                         eta_i: 1.0 as Float,
@@ -178,20 +166,14 @@ impl GlassMaterial {
                 }
                 if !t.is_black() {
                     if is_specular {
-                        if use_scale {
-                            bsdf.bxdfs[bxdf_idx] = Bxdf::SpecTrans(SpecularTransmission::new(
-                                t,
-                                1.0,
-                                eta,
-                                mode,
-                                Some(sc),
-                            ));
-                        // bxdf_idx += 1;
+                        let mut specular_transmission: SpecularTransmission = if use_scale {
+                            SpecularTransmission::new(t, 1.0, eta, mode, Some(sc))
                         } else {
-                            bsdf.bxdfs[bxdf_idx] =
-                                Bxdf::SpecTrans(SpecularTransmission::new(t, 1.0, eta, mode, None));
-                            // bxdf_idx += 1;
-                        }
+                            SpecularTransmission::new(t, 1.0, eta, mode, None)
+                        };
+                        specular_transmission.cauchy_b = cauchy_b;
+                        bsdf.bxdfs[bxdf_idx] = Bxdf::SpecTrans(specular_transmission);
+                        // bxdf_idx += 1;
                     } else {
                         let distrib = MicrofacetDistribution::TrowbridgeReitz(
                             TrowbridgeReitzDistribution::new(urough, vrough, true),
@@ -1,12 +1,14 @@
 //std
 use std;
-use std::f32::consts::PI;
 use std::sync::Arc;
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{Point2f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
 use crate::core::paramset::TextureParams;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::{clamp_t, radians};
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{abs_cos_theta, fr_dielectric};
@@ -15,26 +17,26 @@ use crate::core::texture::Texture;
 use crate::textures::constant::ConstantTexture;
 
 pub struct HairMaterial {
-    pub sigma_a: Option<Arc<dyn Texture<Spectrum> + Sync + Send>>,
-    pub color: Option<Arc<dyn Texture<Spectrum> + Sync + Send>>,
-    pub eumelanin: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub pheomelanin: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub eta: Arc<dyn Texture<Float> + Sync + Send>, // default: 1.55
-    pub beta_m: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.3
-    pub beta_n: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.3
-    pub alpha: Arc<dyn Texture<Float> + Sync + Send>, // default: 2.0
+    pub sigma_a: Option<Arc<Texture<Spectrum>>>,
+    pub color: Option<Arc<Texture<Spectrum>>>,
+    pub eumelanin: Option<Arc<Texture<Float>>>,
+    pub pheomelanin: Option<Arc<Texture<Float>>>,
+    pub eta: Arc<Texture<Float>>,    // default: 1.55
+    pub beta_m: Arc<Texture<Float>>, // default: 0.3
+    pub beta_n: Arc<Texture<Float>>, // default: 0.3
+    pub alpha: Arc<Texture<Float>>,  // default: 2.0
 }
 
 impl HairMaterial {
     pub fn new(
-        sigma_a: Option<Arc<dyn Texture<Spectrum> + Send + Sync>>,
-        color: Option<Arc<dyn Texture<Spectrum> + Send + Sync>>,
-        eumelanin: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-        pheomelanin: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-        eta: Arc<dyn Texture<Float> + Send + Sync>,
-        beta_m: Arc<dyn Texture<Float> + Send + Sync>,
-        beta_n: Arc<dyn Texture<Float> + Send + Sync>,
-        alpha: Arc<dyn Texture<Float> + Send + Sync>,
+        sigma_a: Option<Arc<Texture<Spectrum>>>,
+        color: Option<Arc<Texture<Spectrum>>>,
+        eumelanin: Option<Arc<Texture<Float>>>,
+        pheomelanin: Option<Arc<Texture<Float>>>,
+        eta: Arc<Texture<Float>>,
+        beta_m: Arc<Texture<Float>>,
+        beta_n: Arc<Texture<Float>>,
+        alpha: Arc<Texture<Float>>,
     ) -> Self {
         HairMaterial {
             sigma_a,
@@ -48,65 +50,50 @@ impl HairMaterial {
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
-        let mut sigma_a: Option<Arc<dyn Texture<Spectrum> + Send + Sync>> =
+        let mut sigma_a: Option<Arc<Texture<Spectrum>>> =
             mp.get_spectrum_texture_or_null("sigma_a");
-        let color: Option<Arc<dyn Texture<Spectrum> + Send + Sync>> =
-            mp.get_spectrum_texture_or_null("color");
-        let eumelanin: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("eumelanin");
-        let pheomelanin: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("pheomelanin");
+        let color: Option<Arc<Texture<Spectrum>>> = mp.get_spectrum_texture_or_null("color");
+        let eumelanin: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("eumelanin");
+        let pheomelanin: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("pheomelanin");
         if let Some(_sigma_a) = sigma_a.clone() {
             if let Some(_color) = color.clone() {
-                println!("WARNING: Ignoring \"color\" parameter since \"sigma_a\" was provided.");
+                warn!("Ignoring \"color\" parameter since \"sigma_a\" was provided.");
             }
             if let Some(_eumelanin) = eumelanin.clone() {
-                println!(
-                    "WARNING: Ignoring \"eumelanin\" parameter since \"sigma_a\" was provided."
-                );
+                warn!("Ignoring \"eumelanin\" parameter since \"sigma_a\" was provided.");
             }
             if let Some(_pheomelanin) = pheomelanin.clone() {
-                println!(
-                    "WARNING: Ignoring \"pheomelanin\" parameter since \"sigma_a\" was provided."
-                );
+                warn!("Ignoring \"pheomelanin\" parameter since \"sigma_a\" was provided.");
             }
         } else if let Some(_color) = color.clone() {
             if let Some(_sigma_a) = sigma_a.clone() {
-                println!("WARNING: Ignoring \"sigma_a\" parameter since \"color\" was provided.");
+                warn!("Ignoring \"sigma_a\" parameter since \"color\" was provided.");
             }
             if let Some(_eumelanin) = eumelanin.clone() {
-                println!("WARNING: Ignoring \"eumelanin\" parameter since \"color\" was provided.");
+                warn!("Ignoring \"eumelanin\" parameter since \"color\" was provided.");
             }
             if let Some(_pheomelanin) = pheomelanin.clone() {
-                println!(
-                    "WARNING: Ignoring \"pheomelanin\" parameter since \"color\" was provided."
-                );
+                warn!("Ignoring \"pheomelanin\" parameter since \"color\" was provided.");
             }
         } else if let Some(_eumelanin) = eumelanin.clone() {
             if let Some(_sigma_a) = sigma_a.clone() {
-                println!(
-                    "WARNING: Ignoring \"sigma_a\" parameter since \"eumelanin\" was provided."
-                );
+                warn!("Ignoring \"sigma_a\" parameter since \"eumelanin\" was provided.");
             }
             if let Some(_color) = color.clone() {
-                println!("WARNING: Ignoring \"color\" parameter since \"eumelanin\" was provided.");
+                warn!("Ignoring \"color\" parameter since \"eumelanin\" was provided.");
             }
         } else if let Some(_pheomelanin) = pheomelanin.clone() {
             if let Some(_sigma_a) = sigma_a.clone() {
-                println!(
-                    "WARNING: Ignoring \"sigma_a\" parameter since \"pheomelanin\" was provided."
-                );
+                warn!("Ignoring \"sigma_a\" parameter since \"pheomelanin\" was provided.");
             }
             if let Some(_color) = color.clone() {
-                println!(
-                    "WARNING: Ignoring \"color\" parameter since \"pheomelanin\" was provided."
-                );
+                warn!("Ignoring \"color\" parameter since \"pheomelanin\" was provided.");
             }
         } else {
             // default: brown-ish hair.
-            sigma_a = Some(Arc::new(ConstantTexture::new(
+            sigma_a = Some(Arc::new(Texture::Constant(ConstantTexture::new(
                 HairBSDF::sigma_a_from_concentration(1.3 as Float, 0.0 as Float),
-            )));
+            ))));
         }
         let eta = mp.get_float_texture("eta", 1.55);
         let beta_m = mp.get_float_texture("beta_m", 0.3);
@@ -149,7 +136,7 @@ impl HairMaterial {
         } else if let Some(ref color) = self.color {
             let c: Spectrum = color
                 .evaluate(si)
-                .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                .clamp(0.0 as Float, Float::INFINITY);
             sig_a = HairBSDF::sigma_a_from_reflectance(c, bn);
         } else {
             let mut ce: Float = 0.0 as Float;
@@ -309,7 +296,7 @@ impl HairBSDF {
     }
     pub fn sigma_a_from_reflectance(c: Spectrum, beta_n: Float) -> Spectrum {
         let mut sigma_a: Spectrum = Spectrum::default();
-        for i in 0..3 {
+        for i in 0..Spectrum::N_SAMPLES {
             let sqr: Float = beta_n * beta_n;
             let pow3: Float = sqr * beta_n;
             let pow4: Float = pow3 * beta_n;
@@ -14,16 +14,16 @@ use crate::core::texture::Texture;
 
 /// Describes a purely diffuse surface.
 pub struct MatteMaterial {
-    pub kd: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
-    pub sigma: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.0
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+    pub kd: Arc<Texture<Spectrum>>, // default: 0.5
+    pub sigma: Arc<Texture<Float>>, // default: 0.0
+    pub bump_map: Option<Arc<Texture<Float>>>,
 }
 
 impl MatteMaterial {
     pub fn new(
-        kd: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        sigma: Arc<dyn Texture<Float> + Sync + Send>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
+        kd: Arc<Texture<Spectrum>>,
+        sigma: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
     ) -> Self {
         MatteMaterial {
             kd,
@@ -32,9 +32,8 @@ impl MatteMaterial {
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
-        let kd: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kd", Spectrum::new(0.5));
-        let sigma: Arc<dyn Texture<Float> + Sync + Send> = mp.get_float_texture("sigma", 0.0);
+        let kd: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kd", Spectrum::new(0.5));
+        let sigma: Arc<Texture<Float>> = mp.get_float_texture("sigma", 0.0);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
         Arc::new(Material::Matte(Box::new(MatteMaterial::new(
             kd, sigma, bump_map,
@@ -62,7 +61,7 @@ impl MatteMaterial {
         let r: Spectrum = self
             .kd
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let sig: Float = clamp_t(
             self.sigma.evaluate(si) as Float,
             0.0 as Float,
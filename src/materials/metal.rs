@@ -3,7 +3,9 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{Bsdf, Bxdf, Fresnel, FresnelConductor, MicrofacetReflection};
@@ -85,24 +87,24 @@ pub const COPPER_K: [Float; COPPER_SAMPLES as usize] = [
 ];
 
 pub struct MetalMaterial {
-    pub eta: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: copper
-    pub k: Arc<dyn Texture<Spectrum> + Sync + Send>,   // default: copper
-    pub roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.01
-    pub u_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub v_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub remap_roughness: bool,
+    pub eta: Arc<Texture<Spectrum>>,    // default: copper
+    pub k: Arc<Texture<Spectrum>>,      // default: copper
+    pub roughness: Arc<Texture<Float>>, // default: 0.01
+    pub u_roughness: Option<Arc<Texture<Float>>>,
+    pub v_roughness: Option<Arc<Texture<Float>>>,
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping,
 }
 
 impl MetalMaterial {
     pub fn new(
-        eta: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        k: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        u_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        v_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        eta: Arc<Texture<Spectrum>>,
+        k: Arc<Texture<Spectrum>>,
+        roughness: Arc<Texture<Float>>,
+        u_roughness: Option<Arc<Texture<Float>>>,
+        v_roughness: Option<Arc<Texture<Float>>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         MetalMaterial {
             eta,
@@ -111,25 +113,22 @@ impl MetalMaterial {
             u_roughness,
             v_roughness,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
         let copper_n: Spectrum =
             Spectrum::from_sampled(&COPPER_WAVELENGTHS, &COPPER_N, COPPER_SAMPLES as i32);
-        let eta: Arc<dyn Texture<Spectrum> + Send + Sync> =
-            mp.get_spectrum_texture("eta", copper_n);
+        let eta: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("eta", copper_n);
         let copper_k: Spectrum =
             Spectrum::from_sampled(&COPPER_WAVELENGTHS, &COPPER_K, COPPER_SAMPLES as i32);
-        let k: Arc<dyn Texture<Spectrum> + Send + Sync> = mp.get_spectrum_texture("k", copper_k);
-        let roughness: Arc<dyn Texture<Float> + Send + Sync> =
-            mp.get_float_texture("roughness", 0.01 as Float);
-        let u_roughness: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("uroughness");
-        let v_roughness: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("vroughness");
+        let k: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("k", copper_k);
+        let roughness: Arc<Texture<Float>> = mp.get_float_texture("roughness", 0.01 as Float);
+        let u_roughness: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("uroughness");
+        let v_roughness: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("vroughness");
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
         Arc::new(Material::Metal(Box::new(MetalMaterial::new(
             eta,
             k,
@@ -137,7 +136,7 @@ impl MetalMaterial {
             u_roughness,
             v_roughness,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         ))))
     }
     // Material
@@ -171,10 +170,8 @@ impl MetalMaterial {
         } else {
             v_rough = self.roughness.evaluate(si);
         }
-        if self.remap_roughness {
-            u_rough = TrowbridgeReitzDistribution::roughness_to_alpha(u_rough);
-            v_rough = TrowbridgeReitzDistribution::roughness_to_alpha(v_rough);
-        }
+        u_rough = self.roughness_mapping.alpha(u_rough);
+        v_rough = self.roughness_mapping.alpha(v_rough);
         let fr_mf = Fresnel::Conductor(FresnelConductor {
             eta_i: Spectrum::new(1.0 as Float),
             eta_t: self.eta.evaluate(si),
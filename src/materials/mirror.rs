@@ -13,15 +13,12 @@ use crate::core::texture::Texture;
 
 /// A simple mirror, modeled with perfect specular reflection.
 pub struct MirrorMaterial {
-    pub kr: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.9
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+    pub kr: Arc<Texture<Spectrum>>, // default: 0.9
+    pub bump_map: Option<Arc<Texture<Float>>>,
 }
 
 impl MirrorMaterial {
-    pub fn new(
-        kr: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    ) -> Self {
+    pub fn new(kr: Arc<Texture<Spectrum>>, bump_map: Option<Arc<Texture<Float>>>) -> Self {
         MirrorMaterial { kr, bump_map }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
@@ -53,7 +50,7 @@ impl MirrorMaterial {
         let r: Spectrum = self
             .kr
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         si.bsdf = Some(Bsdf::new(si, 1.0));
         if let Some(bsdf) = &mut si.bsdf {
             let bxdf_idx: usize = 0;
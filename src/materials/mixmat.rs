@@ -29,15 +29,11 @@ use crate::materials::hair::HairBSDF;
 pub struct MixMaterial {
     pub m1: Arc<Material>,
     pub m2: Arc<Material>,
-    pub scale: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
+    pub scale: Arc<Texture<Spectrum>>, // default: 0.5
 }
 
 impl MixMaterial {
-    pub fn new(
-        m1: Arc<Material>,
-        m2: Arc<Material>,
-        scale: Arc<dyn Texture<Spectrum> + Send + Sync>,
-    ) -> Self {
+    pub fn new(m1: Arc<Material>, m2: Arc<Material>, scale: Arc<Texture<Spectrum>>) -> Self {
         MixMaterial { m1, m2, scale }
     }
     // Material
@@ -53,9 +49,9 @@ impl MixMaterial {
         let s1: Spectrum = self
             .scale
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let s2: Spectrum =
-            (Spectrum::new(1.0 as Float) - s1).clamp(0.0 as Float, std::f32::INFINITY as Float);
+            (Spectrum::new(1.0 as Float) - s1).clamp(0.0 as Float, Float::INFINITY);
         let mut si2: SurfaceInteraction = SurfaceInteraction::new(
             &si.p,
             &si.p_error,
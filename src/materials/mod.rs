@@ -2,6 +2,7 @@
 //! material implementations must provide.
 //!
 //! - DisneyMaterial
+//! - FluorescentMaterial
 //! - FourierMaterial
 //! - GlassMaterial
 //! - HairMaterial
@@ -25,6 +26,7 @@
 //! ![SubstrateMaterial](/doc/img/ganesha_pbrt_rust.png)
 
 pub mod disney;
+pub mod fluorescent;
 pub mod fourier;
 pub mod glass;
 pub mod hair;
@@ -4,7 +4,9 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{
@@ -17,27 +19,27 @@ use crate::core::texture::Texture;
 /// Plastic can be modeled as a mixture of a diffuse and glossy
 /// scattering function.
 pub struct PlasticMaterial {
-    pub kd: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub ks: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.1
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub remap_roughness: bool,
+    pub kd: Arc<Texture<Spectrum>>,     // default: 0.25
+    pub ks: Arc<Texture<Spectrum>>,     // default: 0.25
+    pub roughness: Arc<Texture<Float>>, // default: 0.1
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping,
 }
 
 impl PlasticMaterial {
     pub fn new(
-        kd: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        ks: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        kd: Arc<Texture<Spectrum>>,
+        ks: Arc<Texture<Spectrum>>,
+        roughness: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         PlasticMaterial {
             kd,
             ks,
             roughness,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
@@ -45,13 +47,14 @@ impl PlasticMaterial {
         let ks = mp.get_spectrum_texture("Ks", Spectrum::new(0.25 as Float));
         let roughness = mp.get_float_texture("roughness", 0.1 as Float);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
         Arc::new(Material::Plastic(Box::new(PlasticMaterial::new(
             kd,
             ks,
             roughness,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         ))))
     }
     // Material
@@ -76,11 +79,11 @@ impl PlasticMaterial {
         let kd: Spectrum = self
             .kd
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let ks: Spectrum = self
             .ks
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let mut rough: Float = self.roughness.evaluate(si);
         si.bsdf = Some(Bsdf::new(si, 1.0));
         if let Some(bsdf) = &mut si.bsdf {
@@ -104,9 +107,7 @@ impl PlasticMaterial {
                     eta_t: 1.0 as Float,
                 });
                 // create microfacet distribution _distrib_ for plastic material
-                if self.remap_roughness {
-                    rough = TrowbridgeReitzDistribution::roughness_to_alpha(rough);
-                }
+                rough = self.roughness_mapping.alpha(rough);
                 let distrib = MicrofacetDistribution::TrowbridgeReitz(
                     TrowbridgeReitzDistribution::new(rough, rough, true),
                 );
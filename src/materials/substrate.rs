@@ -4,7 +4,9 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{Bsdf, Bxdf, FresnelBlend};
@@ -13,22 +15,22 @@ use crate::core::texture::Texture;
 // see substrate.h
 
 pub struct SubstrateMaterial {
-    pub kd: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
-    pub ks: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
-    pub nu: Arc<dyn Texture<Float> + Sync + Send>,    // default: 0.1
-    pub nv: Arc<dyn Texture<Float> + Sync + Send>,    // default: 0.1
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub remap_roughness: bool,
+    pub kd: Arc<Texture<Spectrum>>, // default: 0.5
+    pub ks: Arc<Texture<Spectrum>>, // default: 0.5
+    pub nu: Arc<Texture<Float>>,    // default: 0.1
+    pub nv: Arc<Texture<Float>>,    // default: 0.1
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping,
 }
 
 impl SubstrateMaterial {
     pub fn new(
-        kd: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        ks: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        nu: Arc<dyn Texture<Float> + Sync + Send>,
-        nv: Arc<dyn Texture<Float> + Sync + Send>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        kd: Arc<Texture<Spectrum>>,
+        ks: Arc<Texture<Spectrum>>,
+        nu: Arc<Texture<Float>>,
+        nv: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         SubstrateMaterial {
             kd,
@@ -36,27 +38,24 @@ impl SubstrateMaterial {
             nu,
             nv,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
-        let kd: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kd", Spectrum::new(0.5));
-        let ks: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Ks", Spectrum::new(0.5));
-        let uroughness: Arc<dyn Texture<Float> + Sync + Send> =
-            mp.get_float_texture("uroughness", 0.1);
-        let vroughness: Arc<dyn Texture<Float> + Sync + Send> =
-            mp.get_float_texture("vroughness", 0.1);
+        let kd: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kd", Spectrum::new(0.5));
+        let ks: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Ks", Spectrum::new(0.5));
+        let uroughness: Arc<Texture<Float>> = mp.get_float_texture("uroughness", 0.1);
+        let vroughness: Arc<Texture<Float>> = mp.get_float_texture("vroughness", 0.1);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
         Arc::new(Material::Substrate(Box::new(SubstrateMaterial::new(
             kd,
             ks,
             uroughness,
             vroughness,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         ))))
     }
     // Material
@@ -81,21 +80,19 @@ impl SubstrateMaterial {
         let d: Spectrum = self
             .kd
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let s: Spectrum = self
             .ks
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let mut roughu: Float = self.nu.evaluate(si);
         let mut roughv: Float = self.nv.evaluate(si);
         si.bsdf = Some(Bsdf::new(si, 1.0));
         if let Some(bsdf) = &mut si.bsdf {
             let bxdf_idx: usize = 0;
             if !d.is_black() || !s.is_black() {
-                if self.remap_roughness {
-                    roughu = TrowbridgeReitzDistribution::roughness_to_alpha(roughu);
-                    roughv = TrowbridgeReitzDistribution::roughness_to_alpha(roughv);
-                }
+                roughu = self.roughness_mapping.alpha(roughu);
+                roughv = self.roughness_mapping.alpha(roughv);
                 let distrib: Option<MicrofacetDistribution> =
                     Some(MicrofacetDistribution::TrowbridgeReitz(
                         TrowbridgeReitzDistribution::new(roughu, roughv, true),
@@ -2,6 +2,7 @@
 use std;
 use std::sync::Arc;
 // others
+use log::warn;
 // use time::PreciseTime;
 // pbrt
 use crate::core::bssrdf::compute_beam_diffusion_bssrdf;
@@ -10,7 +11,9 @@ use crate::core::bssrdf::TabulatedBssrdf;
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
 use crate::core::medium::get_medium_scattering_properties;
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{
@@ -22,32 +25,34 @@ use crate::core::texture::Texture;
 // see subsurface.h
 
 pub struct SubsurfaceMaterial {
-    pub scale: Float,                                 // default: 1.0
-    pub kr: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 1.0
-    pub kt: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 1.0
-    pub sigma_a: Arc<dyn Texture<Spectrum> + Sync + Send>,
-    pub sigma_s: Arc<dyn Texture<Spectrum> + Sync + Send>,
-    pub u_roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.0
-    pub v_roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.0
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub eta: Float,            // default: 1.33
-    pub remap_roughness: bool, // default: true
+    pub scale: Float,               // default: 1.0
+    pub kr: Arc<Texture<Spectrum>>, // default: 1.0
+    pub kt: Arc<Texture<Spectrum>>, // default: 1.0
+    pub sigma_a: Arc<Texture<Spectrum>>,
+    pub sigma_s: Arc<Texture<Spectrum>>,
+    pub u_roughness: Arc<Texture<Float>>, // default: 0.0
+    pub v_roughness: Arc<Texture<Float>>, // default: 0.0
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub eta: Float,                          // default: 1.33
+    pub roughness_mapping: RoughnessMapping, // default: Pbrt
     pub table: Arc<BssrdfTable>,
+    pub g: Float,
+    pub random_walk: bool, // default: false
 }
 
 impl SubsurfaceMaterial {
     pub fn new(
         scale: Float,
-        kr: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        kt: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        sigma_a: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        sigma_s: Arc<dyn Texture<Spectrum> + Sync + Send>,
+        kr: Arc<Texture<Spectrum>>,
+        kt: Arc<Texture<Spectrum>>,
+        sigma_a: Arc<Texture<Spectrum>>,
+        sigma_s: Arc<Texture<Spectrum>>,
         g: Float,
         eta: Float,
-        u_roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        v_roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        u_roughness: Arc<Texture<Float>>,
+        v_roughness: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         let mut table: BssrdfTable = BssrdfTable::new(100, 64);
         compute_beam_diffusion_bssrdf(g, eta, &mut table);
@@ -61,8 +66,10 @@ impl SubsurfaceMaterial {
             v_roughness,
             bump_map,
             eta,
-            remap_roughness,
+            roughness_mapping,
             table: Arc::new(table),
+            g,
+            random_walk: false,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
@@ -75,10 +82,7 @@ impl SubsurfaceMaterial {
         let mut g: Float = mp.find_float("g", 0.0 as Float);
         if name != "" {
             if !found {
-                println!(
-                    "WARNING: Named material {:?} not found.  Using defaults.",
-                    name
-                );
+                warn!("Named material {:?} not found.  Using defaults.", name);
             } else {
                 // enforce g=0 (the database specifies reduced
                 // scattering coefficients)
@@ -87,23 +91,30 @@ impl SubsurfaceMaterial {
         }
         let scale: Float = mp.find_float("scale", 1.0 as Float);
         let eta: Float = mp.find_float("eta", 1.33 as Float);
-        let sigma_a: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("sigma_a", sig_a);
-        let sigma_s: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("sigma_s", sig_s);
-        let kr: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kr", Spectrum::new(1.0));
-        let kt: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kr", Spectrum::new(1.0));
-        let roughu: Arc<dyn Texture<Float> + Sync + Send> =
-            mp.get_float_texture("uroughness", 0.0 as Float);
-        let roughv: Arc<dyn Texture<Float> + Sync + Send> =
-            mp.get_float_texture("vroughness", 0.0 as Float);
+        let sigma_a: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("sigma_a", sig_a);
+        let sigma_s: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("sigma_s", sig_s);
+        let kr: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kr", Spectrum::new(1.0));
+        let kt: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kr", Spectrum::new(1.0));
+        let roughu: Arc<Texture<Float>> = mp.get_float_texture("uroughness", 0.0 as Float);
+        let roughv: Arc<Texture<Float>> = mp.get_float_texture("vroughness", 0.0 as Float);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
+        let method: String = mp.find_string("method", String::from("tabulated"));
+        let random_walk: bool = match method.as_str() {
+            "tabulated" => false,
+            "randomwalk" => true,
+            _ => {
+                warn!(
+                    "Subsurface method {:?} unknown. Using \"tabulated\".",
+                    method
+                );
+                false
+            }
+        };
         // let start = PreciseTime::now();
         //let tmp =
-        Arc::new(Material::Subsurface(Box::new(SubsurfaceMaterial::new(
+        let mut material: SubsurfaceMaterial = SubsurfaceMaterial::new(
             scale,
             kr,
             kt,
@@ -114,8 +125,10 @@ impl SubsurfaceMaterial {
             roughu,
             roughv,
             bump_map,
-            remap_roughness,
-        ))))
+            roughness_mapping,
+        );
+        material.random_walk = random_walk;
+        Arc::new(Material::Subsurface(Box::new(material)))
         //;
         // let end = PreciseTime::now();
         // println!(
@@ -148,11 +161,11 @@ impl SubsurfaceMaterial {
         let r: Spectrum = self
             .kr
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let t: Spectrum = self
             .kt
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let mut urough: Float = self.u_roughness.evaluate(si);
         let mut vrough: Float = self.v_roughness.evaluate(si);
         // initialize _bsdf_ for smooth or rough dielectric
@@ -183,10 +196,8 @@ impl SubsurfaceMaterial {
                     ));
                 }
             } else {
-                if self.remap_roughness {
-                    urough = TrowbridgeReitzDistribution::roughness_to_alpha(urough);
-                    vrough = TrowbridgeReitzDistribution::roughness_to_alpha(vrough);
-                }
+                urough = self.roughness_mapping.alpha(urough);
+                vrough = self.roughness_mapping.alpha(vrough);
                 if !r.is_black() {
                     let fresnel = Fresnel::Dielectric(FresnelDielectric {
                         eta_i: 1.0 as Float,
@@ -263,13 +274,13 @@ impl SubsurfaceMaterial {
                 * self
                     .sigma_a
                     .evaluate(si)
-                    .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                    .clamp(0.0 as Float, Float::INFINITY);
             let sig_s: Spectrum = self.scale
                 * self
                     .sigma_s
                     .evaluate(si)
-                    .clamp(0.0 as Float, std::f32::INFINITY as Float);
-            si.bssrdf = Some(TabulatedBssrdf::new(
+                    .clamp(0.0 as Float, Float::INFINITY);
+            let mut bssrdf: TabulatedBssrdf = TabulatedBssrdf::new(
                 si,
                 material,
                 mode,
@@ -277,7 +288,10 @@ impl SubsurfaceMaterial {
                 &sig_a,
                 &sig_s,
                 self.table.clone(),
-            ));
+            );
+            bssrdf.random_walk = self.random_walk;
+            bssrdf.g = self.g;
+            si.bssrdf = Some(bssrdf);
         }
     }
 }
@@ -3,7 +3,9 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{
@@ -13,24 +15,24 @@ use crate::core::reflection::{
 use crate::core::texture::Texture;
 
 pub struct TranslucentMaterial {
-    pub kd: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub ks: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.1
-    pub reflect: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
-    pub transmit: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.5
-    pub bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub remap_roughness: bool, // default: true
+    pub kd: Arc<Texture<Spectrum>>,       // default: 0.25
+    pub ks: Arc<Texture<Spectrum>>,       // default: 0.25
+    pub roughness: Arc<Texture<Float>>,   // default: 0.1
+    pub reflect: Arc<Texture<Spectrum>>,  // default: 0.5
+    pub transmit: Arc<Texture<Spectrum>>, // default: 0.5
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping, // default: Pbrt
 }
 
 impl TranslucentMaterial {
     pub fn new(
-        kd: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        ks: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        reflect: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        transmit: Arc<dyn Texture<Spectrum> + Send + Sync>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        kd: Arc<Texture<Spectrum>>,
+        ks: Arc<Texture<Spectrum>>,
+        roughness: Arc<Texture<Float>>,
+        reflect: Arc<Texture<Spectrum>>,
+        transmit: Arc<Texture<Spectrum>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         TranslucentMaterial {
             kd,
@@ -39,7 +41,7 @@ impl TranslucentMaterial {
             reflect,
             transmit,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
@@ -49,7 +51,8 @@ impl TranslucentMaterial {
         let transmit = mp.get_spectrum_texture("transmit", Spectrum::new(0.5 as Float));
         let roughness = mp.get_float_texture("roughness", 0.1 as Float);
         let bump_map = mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
         Arc::new(Material::Translucent(Box::new(TranslucentMaterial::new(
             kd,
             ks,
@@ -57,7 +60,7 @@ impl TranslucentMaterial {
             reflect,
             transmit,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         ))))
     }
     // Material
@@ -83,11 +86,11 @@ impl TranslucentMaterial {
         let r: Spectrum = self
             .reflect
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let t: Spectrum = self
             .transmit
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         if r.is_black() && t.is_black() {
             si.bsdf = Some(Bsdf::new(si, eta));
             return;
@@ -95,11 +98,11 @@ impl TranslucentMaterial {
         let kd: Spectrum = self
             .kd
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let ks: Spectrum = self
             .ks
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let mut rough: Float = self.roughness.evaluate(si);
         si.bsdf = Some(Bsdf::new(si, eta));
         if let Some(bsdf) = &mut si.bsdf {
@@ -129,9 +132,7 @@ impl TranslucentMaterial {
                 }
             }
             if !ks.is_black() && (!r.is_black() || !t.is_black()) {
-                if self.remap_roughness {
-                    rough = TrowbridgeReitzDistribution::roughness_to_alpha(rough);
-                }
+                rough = self.roughness_mapping.alpha(rough);
                 let distrib = MicrofacetDistribution::TrowbridgeReitz(
                     TrowbridgeReitzDistribution::new(rough, rough, true),
                 );
@@ -4,7 +4,9 @@ use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::material::{Material, TransportMode};
-use crate::core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
+use crate::core::microfacet::{
+    find_roughness_mapping, MicrofacetDistribution, RoughnessMapping, TrowbridgeReitzDistribution,
+};
 use crate::core::paramset::TextureParams;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::reflection::{
@@ -16,32 +18,32 @@ use crate::core::texture::Texture;
 // see uber.h
 
 pub struct UberMaterial {
-    pub kd: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub ks: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.25
-    pub kr: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.0
-    pub kt: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 0.0
-    pub opacity: Arc<dyn Texture<Spectrum> + Sync + Send>, // default: 1.0
-    pub roughness: Arc<dyn Texture<Float> + Sync + Send>, // default: 0.1
-    pub u_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub v_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub eta: Arc<dyn Texture<Float> + Sync + Send>, // default: 1.5
-    pub bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-    pub remap_roughness: bool,
+    pub kd: Arc<Texture<Spectrum>>,      // default: 0.25
+    pub ks: Arc<Texture<Spectrum>>,      // default: 0.25
+    pub kr: Arc<Texture<Spectrum>>,      // default: 0.0
+    pub kt: Arc<Texture<Spectrum>>,      // default: 0.0
+    pub opacity: Arc<Texture<Spectrum>>, // default: 1.0
+    pub roughness: Arc<Texture<Float>>,  // default: 0.1
+    pub u_roughness: Option<Arc<Texture<Float>>>,
+    pub v_roughness: Option<Arc<Texture<Float>>>,
+    pub eta: Arc<Texture<Float>>, // default: 1.5
+    pub bump_map: Option<Arc<Texture<Float>>>,
+    pub roughness_mapping: RoughnessMapping,
 }
 
 impl UberMaterial {
     pub fn new(
-        kd: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        ks: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        kr: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        kt: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        roughness: Arc<dyn Texture<Float> + Sync + Send>,
-        u_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        v_roughness: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        opacity: Arc<dyn Texture<Spectrum> + Sync + Send>,
-        eta: Arc<dyn Texture<Float> + Send + Sync>,
-        bump_map: Option<Arc<dyn Texture<Float> + Sync + Send>>,
-        remap_roughness: bool,
+        kd: Arc<Texture<Spectrum>>,
+        ks: Arc<Texture<Spectrum>>,
+        kr: Arc<Texture<Spectrum>>,
+        kt: Arc<Texture<Spectrum>>,
+        roughness: Arc<Texture<Float>>,
+        u_roughness: Option<Arc<Texture<Float>>>,
+        v_roughness: Option<Arc<Texture<Float>>>,
+        opacity: Arc<Texture<Spectrum>>,
+        eta: Arc<Texture<Float>>,
+        bump_map: Option<Arc<Texture<Float>>>,
+        roughness_mapping: RoughnessMapping,
     ) -> Self {
         UberMaterial {
             kd,
@@ -54,31 +56,23 @@ impl UberMaterial {
             v_roughness,
             eta,
             bump_map,
-            remap_roughness,
+            roughness_mapping,
         }
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material> {
-        let kd: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kd", Spectrum::new(0.25));
-        let ks: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Ks", Spectrum::new(0.25));
-        let kr: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kr", Spectrum::new(0.0));
-        let kt: Arc<dyn Texture<Spectrum> + Sync + Send> =
-            mp.get_spectrum_texture("Kt", Spectrum::new(0.0));
-        let roughness: Arc<dyn Texture<Float> + Send + Sync> =
-            mp.get_float_texture("roughness", 0.1 as Float);
-        let u_roughness: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("uroughness");
-        let v_roughness: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("vroughness");
-        let opacity: Arc<dyn Texture<Spectrum> + Send + Sync> =
+        let kd: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kd", Spectrum::new(0.25));
+        let ks: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Ks", Spectrum::new(0.25));
+        let kr: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kr", Spectrum::new(0.0));
+        let kt: Arc<Texture<Spectrum>> = mp.get_spectrum_texture("Kt", Spectrum::new(0.0));
+        let roughness: Arc<Texture<Float>> = mp.get_float_texture("roughness", 0.1 as Float);
+        let u_roughness: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("uroughness");
+        let v_roughness: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("vroughness");
+        let opacity: Arc<Texture<Spectrum>> =
             mp.get_spectrum_texture("opacity", Spectrum::new(1.0));
-        let bump_map: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("bumpmap");
-        let remap_roughness: bool = mp.find_bool("remaproughness", true);
-        let eta_option: Option<Arc<dyn Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("eta");
+        let bump_map: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("bumpmap");
+        let roughness_mapping: RoughnessMapping =
+            find_roughness_mapping(mp, RoughnessMapping::Pbrt);
+        let eta_option: Option<Arc<Texture<Float>>> = mp.get_float_texture_or_null("eta");
         if let Some(ref eta) = eta_option {
             Arc::new(Material::Uber(Box::new(UberMaterial::new(
                 kd,
@@ -91,11 +85,10 @@ impl UberMaterial {
                 opacity,
                 eta.clone(),
                 bump_map,
-                remap_roughness,
+                roughness_mapping,
             ))))
         } else {
-            let eta: Arc<dyn Texture<Float> + Send + Sync> =
-                mp.get_float_texture("index", 1.5 as Float);
+            let eta: Arc<Texture<Float>> = mp.get_float_texture("index", 1.5 as Float);
             Arc::new(Material::Uber(Box::new(UberMaterial::new(
                 kd,
                 ks,
@@ -107,7 +100,7 @@ impl UberMaterial {
                 opacity,
                 eta,
                 bump_map,
-                remap_roughness,
+                roughness_mapping,
             ))))
         }
     }
@@ -134,19 +127,19 @@ impl UberMaterial {
         let op: Spectrum = self
             .opacity
             .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
+            .clamp(0.0 as Float, Float::INFINITY);
         let t: Spectrum =
-            (Spectrum::new(1.0) - op).clamp(0.0 as Float, std::f32::INFINITY as Float);
+            (Spectrum::new(1.0) - op).clamp(0.0 as Float, Float::INFINITY);
         let kd: Spectrum = op
             * self
                 .kd
                 .evaluate(si)
-                .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                .clamp(0.0 as Float, Float::INFINITY);
         let ks: Spectrum = op
             * self
                 .ks
                 .evaluate(si)
-                .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                .clamp(0.0 as Float, Float::INFINITY);
         let mut u_rough: Float;
         if let Some(ref u_roughness) = self.u_roughness {
             u_rough = u_roughness.evaluate(si);
@@ -163,12 +156,12 @@ impl UberMaterial {
             * self
                 .kr
                 .evaluate(si)
-                .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                .clamp(0.0 as Float, Float::INFINITY);
         let kt: Spectrum = op
             * self
                 .kt
                 .evaluate(si)
-                .clamp(0.0 as Float, std::f32::INFINITY as Float);
+                .clamp(0.0 as Float, Float::INFINITY);
         if !t.is_black() {
             si.bsdf = Some(Bsdf::new(si, 1.0));
         } else {
@@ -203,10 +196,8 @@ impl UberMaterial {
                     eta_i: 1.0,
                     eta_t: e,
                 });
-                if self.remap_roughness {
-                    u_rough = TrowbridgeReitzDistribution::roughness_to_alpha(u_rough);
-                    v_rough = TrowbridgeReitzDistribution::roughness_to_alpha(v_rough);
-                }
+                u_rough = self.roughness_mapping.alpha(u_rough);
+                v_rough = self.roughness_mapping.alpha(v_rough);
                 let distrib = MicrofacetDistribution::TrowbridgeReitz(
                     TrowbridgeReitzDistribution::new(u_rough, v_rough, true),
                 );
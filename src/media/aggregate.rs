@@ -0,0 +1,150 @@
+// std
+use std::sync::Arc;
+// pbrt
+use crate::core::geometry::bnd3_union_bnd3;
+use crate::core::geometry::{Bounds3f, Point3f, Ray};
+use crate::core::interaction::MediumInteraction;
+use crate::core::medium::{Medium, MediumInterface};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::sampler::Sampler;
+
+/// Node of the (flattened) bounding volume hierarchy over the media
+/// of a **VolumeAggregate**.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct VolumeBVHNode {
+    pub bounds: Bounds3f,
+    /// leaf: index of first medium, interior: index of second child
+    pub offset: usize,
+    /// number of media in a leaf, zero for interior nodes
+    pub n_media: usize,
+}
+
+/// Combines many bounded media (e.g. dozens of clouds) into a single
+/// medium. A bounding volume hierarchy finds the media a ray
+/// overlaps, so each ray only has to track through those (using their
+/// own majorants) instead of one giant bounding box.
+#[derive(Clone)]
+pub struct VolumeAggregate {
+    pub media: Arc<Vec<Arc<Medium>>>,
+    pub nodes: Arc<Vec<VolumeBVHNode>>,
+}
+
+impl VolumeAggregate {
+    /// Expects bounded media (e.g. **GridDensityMedium**) and their
+    /// world space bounds.
+    pub fn new(media: Vec<Arc<Medium>>, bounds: Vec<Bounds3f>) -> Self {
+        assert_eq!(media.len(), bounds.len());
+        let mut indices: Vec<usize> = (0..media.len()).collect();
+        let mut nodes: Vec<VolumeBVHNode> = Vec::with_capacity(2 * media.len());
+        if !media.is_empty() {
+            VolumeAggregate::recursive_build(&bounds, &mut indices[..], 0, &mut nodes);
+        }
+        // store media in leaf order
+        let ordered_media: Vec<Arc<Medium>> = indices.iter().map(|i| media[*i].clone()).collect();
+        VolumeAggregate {
+            media: Arc::new(ordered_media),
+            nodes: Arc::new(nodes),
+        }
+    }
+    fn recursive_build(
+        bounds: &[Bounds3f],
+        indices: &mut [usize],
+        start: usize,
+        nodes: &mut Vec<VolumeBVHNode>,
+    ) -> usize {
+        let node_index: usize = nodes.len();
+        let mut node_bounds: Bounds3f = Bounds3f::default();
+        let mut centroid_bounds: Bounds3f = Bounds3f::default();
+        for i in indices.iter() {
+            node_bounds = bnd3_union_bnd3(&node_bounds, &bounds[*i]);
+            let c: Point3f = (bounds[*i].p_min + bounds[*i].p_max) * 0.5 as Float;
+            centroid_bounds = bnd3_union_bnd3(&centroid_bounds, &Bounds3f::new(c, c));
+        }
+        nodes.push(VolumeBVHNode {
+            bounds: node_bounds,
+            offset: start,
+            n_media: indices.len(),
+        });
+        if indices.len() <= 2 {
+            return node_index;
+        }
+        // split at the median centroid along the largest extent
+        let axis: u8 = centroid_bounds.maximum_extent();
+        indices.sort_by(|a, b| {
+            let ca: Float = bounds[*a].p_min[axis] + bounds[*a].p_max[axis];
+            let cb: Float = bounds[*b].p_min[axis] + bounds[*b].p_max[axis];
+            ca.partial_cmp(&cb).unwrap()
+        });
+        let mid: usize = indices.len() / 2;
+        let (left, right) = indices.split_at_mut(mid);
+        VolumeAggregate::recursive_build(bounds, left, start, nodes);
+        let second_child: usize =
+            VolumeAggregate::recursive_build(bounds, right, start + mid, nodes);
+        nodes[node_index].offset = second_child;
+        nodes[node_index].n_media = 0;
+        node_index
+    }
+    /// Indices of all media whose bounds overlap the ray segment.
+    fn overlapping(&self, ray: &Ray) -> Vec<usize> {
+        let mut result: Vec<usize> = Vec::new();
+        if self.nodes.is_empty() {
+            return result;
+        }
+        let mut nodes_to_visit: Vec<usize> = vec![0_usize];
+        while let Some(node_index) = nodes_to_visit.pop() {
+            let node: &VolumeBVHNode = &self.nodes[node_index];
+            let mut t0: Float = 0.0;
+            let mut t1: Float = 0.0;
+            if !node.bounds.intersect_b(ray, &mut t0, &mut t1) {
+                continue;
+            }
+            if node.n_media > 0 {
+                for i in 0..node.n_media {
+                    result.push(node.offset + i);
+                }
+            } else {
+                nodes_to_visit.push(node_index + 1);
+                nodes_to_visit.push(node.offset);
+            }
+        }
+        result
+    }
+    // Medium
+    pub fn tr(&self, r_world: &Ray, sampler: &mut Sampler) -> Spectrum {
+        // extinction adds up, so transmittance multiplies
+        let mut tr: Spectrum = Spectrum::new(1.0 as Float);
+        for i in self.overlapping(r_world) {
+            tr *= self.media[i].tr(r_world, sampler);
+            if tr.is_black() {
+                break;
+            }
+        }
+        tr
+    }
+    pub fn sample(
+        &self,
+        r_world: &Ray,
+        sampler: &mut Sampler,
+    ) -> (Spectrum, Option<MediumInteraction>) {
+        // the first collision of the combined media is the closest
+        // collision sampled in any of them
+        let mut ray: Ray = r_world.clone();
+        let mut beta: Spectrum = Spectrum::new(1.0 as Float);
+        let mut mi_opt: Option<MediumInteraction> = None;
+        for i in self.overlapping(r_world) {
+            let (spectrum, option) = self.media[i].sample(&ray, sampler);
+            if let Some(mi) = option {
+                ray.t_max = (mi.p - ray.o).length() / ray.d.length();
+                beta = spectrum;
+                mi_opt = Some(mi);
+            }
+        }
+        if let Some(ref mut mi) = mi_opt {
+            // scattered rays stay inside the aggregate
+            let aggregate: Option<Arc<Medium>> = Some(Arc::new(Medium::Aggregate(self.clone())));
+            mi.medium_interface =
+                Some(Arc::new(MediumInterface::new(aggregate.clone(), aggregate)));
+        }
+        (beta, mi_opt)
+    }
+}
@@ -4,18 +4,108 @@ use std::sync::Arc;
 use crate::core::geometry::pnt3i_inside_exclusive;
 use crate::core::geometry::{Bounds3f, Bounds3i, Point3f, Point3i, Ray, Vector3f, Vector3i};
 use crate::core::interaction::MediumInteraction;
-use crate::core::medium::{HenyeyGreenstein, Medium};
+use crate::core::medium::{HenyeyGreenstein, Medium, PhaseFunction};
+use crate::core::parallel::AtomicFloat;
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::sampler::Sampler;
+use crate::core::spectrum::blackbody_normalized;
+use crate::core::spectrum::{CIE_LAMBDA, N_CIE_SAMPLES};
+use crate::core::stats::StatRatio;
 use crate::core::transform::Transform;
 
 // see grid.h
 
+static TR_STEPS_PER_CALL: StatRatio = StatRatio::new("Media/Grid steps per Tr() call");
+
+/// Number of entries in the precomputed blackbody ramp.
+const BLACKBODY_RAMP_SIZE: usize = 256;
+/// Maximum resolution (per axis) of the coarse majorant grid.
+const MAJORANT_GRID_RES: i32 = 16;
+
+/// Coarse grid over the medium space unit cube storing the maximum
+/// density found within each cell. Rays step through it with a 3D DDA
+/// and use the local maximum as majorant, which avoids most null
+/// collisions in sparse volumes.
+#[derive(Debug, Default, Clone)]
+pub struct MajorantGrid {
+    pub res: [i32; 3],
+    pub voxels: Vec<Float>,
+}
+
+impl MajorantGrid {
+    pub fn lookup(&self, x: i32, y: i32, z: i32) -> Float {
+        self.voxels[((z * self.res[1] + y) * self.res[0] + x) as usize]
+    }
+    /// Splits the ray segment $[t_min, t_max]$ (medium space, inside
+    /// the unit cube) into pieces with constant maximum density.
+    pub fn segments(&self, ray: &Ray, t_min: Float, t_max: Float) -> Vec<(Float, Float, Float)> {
+        let mut segments: Vec<(Float, Float, Float)> = Vec::new();
+        let p_grid: Point3f = ray.position(t_min);
+        let mut voxel: [i32; 3] = [0; 3];
+        let mut step: [i32; 3] = [0; 3];
+        let mut voxel_limit: [i32; 3] = [0; 3];
+        let mut next_crossing_t: [Float; 3] = [0.0; 3];
+        let mut delta_t: [Float; 3] = [0.0; 3];
+        for axis in 0..3_usize {
+            let res: i32 = self.res[axis];
+            let o: Float = p_grid[axis as u8];
+            let d: Float = ray.d[axis as u8];
+            voxel[axis] = ((o * res as Float) as i32).max(0).min(res - 1);
+            if d == 0.0 as Float {
+                // never crosses a cell boundary along this axis
+                next_crossing_t[axis] = Float::INFINITY;
+                delta_t[axis] = Float::INFINITY;
+                step[axis] = 0;
+                voxel_limit[axis] = res;
+            } else if d > 0.0 as Float {
+                let next_voxel_pos: Float = (voxel[axis] + 1) as Float / res as Float;
+                next_crossing_t[axis] = t_min + (next_voxel_pos - o) / d;
+                delta_t[axis] = 1.0 as Float / (res as Float * d);
+                step[axis] = 1;
+                voxel_limit[axis] = res;
+            } else {
+                let next_voxel_pos: Float = voxel[axis] as Float / res as Float;
+                next_crossing_t[axis] = t_min + (next_voxel_pos - o) / d;
+                delta_t[axis] = -1.0 as Float / (res as Float * d);
+                step[axis] = -1;
+                voxel_limit[axis] = -1;
+            }
+        }
+        let mut t: Float = t_min;
+        loop {
+            // find the axis whose cell boundary is crossed first
+            let mut axis: usize = 0;
+            if next_crossing_t[1] < next_crossing_t[axis] {
+                axis = 1;
+            }
+            if next_crossing_t[2] < next_crossing_t[axis] {
+                axis = 2;
+            }
+            let t_end: Float = next_crossing_t[axis].min(t_max);
+            if t_end > t {
+                segments.push((t, t_end, self.lookup(voxel[0], voxel[1], voxel[2])));
+            }
+            if t_end >= t_max {
+                break;
+            }
+            t = t_end;
+            voxel[axis] += step[axis];
+            if voxel[axis] == voxel_limit[axis] {
+                break;
+            }
+            next_crossing_t[axis] += delta_t[axis];
+        }
+        segments
+    }
+}
+
+#[derive(Clone)]
 pub struct GridDensityMedium {
     pub sigma_a: Spectrum,
     pub sigma_s: Spectrum,
     pub g: Float,
+    pub phase: Arc<PhaseFunction>,
     pub nx: i32,
     pub ny: i32,
     pub nz: i32,
@@ -23,6 +113,19 @@ pub struct GridDensityMedium {
     pub density: Arc<Vec<Float>>,
     pub sigma_t: Float,
     pub inv_max_density: Float,
+    pub majorant_grid: Arc<MajorantGrid>,
+    // emission
+    pub temperature: Option<Arc<Vec<Float>>>,
+    pub temperature_cutoff: Float,
+    pub temperature_scale: Float,
+    pub max_temperature: Float,
+    pub blackbody_ramp: Arc<Vec<Spectrum>>,
+    // motion blur
+    /// velocity grids (x, y, z) in medium space units per time unit
+    pub velocity: Option<[Arc<Vec<Float>>; 3]>,
+    /// time at which the density grid is exact, the middle of the
+    /// camera's shutter interval (see **set_reference_time()**)
+    pub reference_time: AtomicFloat,
 }
 
 impl GridDensityMedium {
@@ -35,15 +138,76 @@ impl GridDensityMedium {
         nz: i32,
         medium_to_world: &Transform,
         d: Arc<Vec<Float>>,
+        temperature: Option<Arc<Vec<Float>>>,
+        le_scale: Float,
+        temperature_cutoff: Float,
+        temperature_scale: Float,
     ) -> Self {
         let mut max_density: Float = 0.0;
         for i in 0..(nx * ny * nz) as usize {
             max_density = max_density.max(d[i]);
         }
+        // coarse grid of local density maxima
+        let res: [i32; 3] = [
+            nx.clamp(1, MAJORANT_GRID_RES),
+            ny.clamp(1, MAJORANT_GRID_RES),
+            nz.clamp(1, MAJORANT_GRID_RES),
+        ];
+        let n: [i32; 3] = [nx, ny, nz];
+        let mut majorants: Vec<Float> = Vec::with_capacity((res[0] * res[1] * res[2]) as usize);
+        for z in 0..res[2] {
+            for y in 0..res[1] {
+                for x in 0..res[0] {
+                    // range of density samples which can influence the
+                    // trilinear interpolation inside this cell
+                    let cell: [i32; 3] = [x, y, z];
+                    let mut lo: [i32; 3] = [0; 3];
+                    let mut hi: [i32; 3] = [0; 3];
+                    for axis in 0..3_usize {
+                        let p0: Float = cell[axis] as Float / res[axis] as Float;
+                        let p1: Float = (cell[axis] + 1) as Float / res[axis] as Float;
+                        lo[axis] = ((p0 * n[axis] as Float - 0.5 as Float).floor() as i32).max(0);
+                        hi[axis] = ((p1 * n[axis] as Float - 0.5 as Float).floor() as i32 + 1)
+                            .min(n[axis] - 1);
+                    }
+                    let mut cell_max: Float = 0.0;
+                    for iz in lo[2]..=hi[2] {
+                        for iy in lo[1]..=hi[1] {
+                            for ix in lo[0]..=hi[0] {
+                                cell_max = cell_max.max(d[((iz * ny + iy) * nx + ix) as usize]);
+                            }
+                        }
+                    }
+                    majorants.push(cell_max);
+                }
+            }
+        }
+        // map the temperature range through a blackbody ramp
+        let mut max_temperature: Float = 0.0;
+        let mut blackbody_ramp: Vec<Spectrum> = Vec::new();
+        if let Some(ref temperature) = temperature {
+            for t in temperature.iter() {
+                max_temperature =
+                    max_temperature.max((*t - temperature_cutoff) * temperature_scale);
+            }
+            if max_temperature > 0.0 as Float {
+                blackbody_ramp.reserve(BLACKBODY_RAMP_SIZE);
+                for i in 0..BLACKBODY_RAMP_SIZE {
+                    let t: Float =
+                        max_temperature * i as Float / (BLACKBODY_RAMP_SIZE - 1) as Float;
+                    let mut v: Vec<Float> = Vec::with_capacity(N_CIE_SAMPLES as usize);
+                    blackbody_normalized(&CIE_LAMBDA, N_CIE_SAMPLES as usize, t, &mut v);
+                    blackbody_ramp.push(
+                        Spectrum::from_sampled(&CIE_LAMBDA, &v, N_CIE_SAMPLES as i32) * le_scale,
+                    );
+                }
+            }
+        }
         GridDensityMedium {
             sigma_a: *sigma_a,
             sigma_s: *sigma_s,
             g,
+            phase: Arc::new(PhaseFunction::HenyeyGreenstein(HenyeyGreenstein { g })),
             nx,
             ny,
             nz,
@@ -51,9 +215,106 @@ impl GridDensityMedium {
             density: d,
             sigma_t: (*sigma_s + *sigma_a)[0],
             inv_max_density: 1.0 as Float / max_density,
+            majorant_grid: Arc::new(MajorantGrid {
+                res,
+                voxels: majorants,
+            }),
+            temperature,
+            temperature_cutoff,
+            temperature_scale,
+            max_temperature,
+            blackbody_ramp: Arc::new(blackbody_ramp),
+            velocity: None,
+            reference_time: AtomicFloat::new(0.0 as Float),
+        }
+    }
+    /// Called when the render starts, once the camera (and its
+    /// shutter interval) is known.
+    pub fn set_reference_time(&self, time: Float) {
+        self.reference_time.store(time);
+    }
+    /// Moves the medium-space point _p_ back along the velocity field
+    /// to where the density at _time_ came from (semi-Lagrangian
+    /// advection).
+    pub fn advect(&self, p: &Point3f, time: Float) -> Point3f {
+        if let Some(ref velocity) = self.velocity {
+            let dt: Float = time - Float::from(&self.reference_time);
+            if dt != 0.0 as Float {
+                let v: Vector3f = Vector3f {
+                    x: self.interpolate(&velocity[0], p),
+                    y: self.interpolate(&velocity[1], p),
+                    z: self.interpolate(&velocity[2], p),
+                };
+                return *p - v * dt;
+            }
+        }
+        *p
+    }
+    /// World space bounds of the density grid.
+    pub fn world_bound(&self) -> Bounds3f {
+        let medium_bounds: Bounds3f = Bounds3f::new(
+            Point3f {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        );
+        Transform::inverse(&self.world_to_medium).transform_bounds(&medium_bounds)
+    }
+    /// Segments of the medium space ray with their maximum density.
+    /// Advected lookups can fetch density from other cells, so moving
+    /// media fall back to the global maximum.
+    fn majorant_segments(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Vec<(Float, Float, Float)> {
+        if self.velocity.is_some() {
+            vec![(t_min, t_max, 1.0 as Float / self.inv_max_density)]
+        } else {
+            self.majorant_grid.segments(ray, t_min, t_max)
         }
     }
     pub fn d(&self, p: &Point3i) -> Float {
+        self.lookup(&self.density, p)
+    }
+    pub fn density(&self, p: &Point3f) -> Float {
+        self.interpolate(&self.density, p)
+    }
+    /// Returns the (scaled) temperature in Kelvin at medium-space
+    /// point _p_, or zero for non-emissive media.
+    pub fn temperature(&self, p: &Point3f) -> Float {
+        if let Some(ref temperature) = self.temperature {
+            let t: Float = self.interpolate(temperature, p);
+            ((t - self.temperature_cutoff) * self.temperature_scale).max(0.0 as Float)
+        } else {
+            0.0 as Float
+        }
+    }
+    /// Emitted radiance at medium-space point _p_, looked up in the
+    /// blackbody ramp.
+    pub fn le(&self, p: &Point3f) -> Spectrum {
+        if self.blackbody_ramp.is_empty() {
+            return Spectrum::default();
+        }
+        let t: Float = self.temperature(p);
+        // very low temperatures don't emit visible light
+        if t <= 100.0 as Float {
+            return Spectrum::default();
+        }
+        let x: Float =
+            (t / self.max_temperature).min(1.0 as Float) * (BLACKBODY_RAMP_SIZE - 1) as Float;
+        let i: usize = (x as usize).min(BLACKBODY_RAMP_SIZE - 2);
+        let dx: Float = x - i as Float;
+        self.blackbody_ramp[i] * (1.0 as Float - dx) + self.blackbody_ramp[i + 1] * dx
+    }
+    fn lookup(&self, grid: &[Float], p: &Point3i) -> Float {
         let sample_bounds: Bounds3i = Bounds3i {
             p_min: Point3i {
                 x: 0_i32,
@@ -69,10 +330,10 @@ impl GridDensityMedium {
         if !pnt3i_inside_exclusive(p, &sample_bounds) {
             0.0 as Float
         } else {
-            self.density[((p.z * self.ny + p.y) * self.nx + p.x) as usize]
+            grid[((p.z * self.ny + p.y) * self.nx + p.x) as usize]
         }
     }
-    pub fn density(&self, p: &Point3f) -> Float {
+    fn interpolate(&self, grid: &[Float], p: &Point3f) -> Float {
         // compute voxel coordinates and offsets for _p_
         let p_samples: Point3f = Point3f {
             x: p.x * self.nx as Float - 0.5 as Float,
@@ -90,61 +351,75 @@ impl GridDensityMedium {
             y: p_samples.y - pi.y as Float,
             z: p_samples.z - pi.z as Float,
         };
-        // trilinearly interpolate density values to compute local density
+        // trilinearly interpolate grid values to compute local value
         let d00: Float = lerp(
             d.x,
-            self.d(&pi),
-            self.d(&(pi
-                + Vector3i {
+            self.lookup(grid, &pi),
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 1_i32,
                     y: 0_i32,
                     z: 0_i32,
-                })),
+                }),
+            ),
         );
         let d10: Float = lerp(
             d.x,
-            self.d(&(pi
-                + Vector3i {
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 0_i32,
                     y: 1_i32,
                     z: 0_i32,
-                })),
-            self.d(&(pi
-                + Vector3i {
+                }),
+            ),
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 1_i32,
                     y: 1_i32,
                     z: 0_i32,
-                })),
+                }),
+            ),
         );
         let d01: Float = lerp(
             d.x,
-            self.d(&(pi
-                + Vector3i {
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 0_i32,
                     y: 0_i32,
                     z: 1_i32,
-                })),
-            self.d(&(pi
-                + Vector3i {
+                }),
+            ),
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 1_i32,
                     y: 0_i32,
                     z: 1_i32,
-                })),
+                }),
+            ),
         );
         let d11: Float = lerp(
             d.x,
-            self.d(&(pi
-                + Vector3i {
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 0_i32,
                     y: 1_i32,
                     z: 1_i32,
-                })),
-            self.d(&(pi
-                + Vector3i {
+                }),
+            ),
+            self.lookup(
+                grid,
+                &(pi + Vector3i {
                     x: 1_i32,
                     y: 1_i32,
                     z: 1_i32,
-                })),
+                }),
+            ),
         );
         let d0: Float = lerp(d.y, d00, d10);
         let d1: Float = lerp(d.y, d01, d11);
@@ -153,7 +428,7 @@ impl GridDensityMedium {
     // Medium
     pub fn tr(&self, r_world: &Ray, sampler: &mut Sampler) -> Spectrum {
         // TODO: ProfilePhase _(Prof::MediumTr);
-        // TODO: ++nTrCalls;
+        TR_STEPS_PER_CALL.add_denom(1);
         let mut in_ray: Ray = Ray::default();
         in_ray.o = r_world.o;
         in_ray.d = r_world.d.normalize();
@@ -180,25 +455,32 @@ impl GridDensityMedium {
         }
         // perform ratio tracking to estimate the transmittance value
         let mut tr: Float = 1.0;
-        let mut t: Float = t_min;
-        loop {
-            // TODO: ++nTrSteps;
-            t -= (1.0 as Float - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
-            if t >= t_max {
-                break;
+        for (t0, t1, max_density) in self.majorant_segments(&ray, t_min, t_max) {
+            if max_density <= 0.0 as Float {
+                // empty space, nothing to track
+                continue;
             }
-            let density: Float = self.density(&ray.position(t));
-            tr *= 1.0 as Float - (0.0 as Float).max(density * self.inv_max_density);
-            // added after book publication: when transmittance gets
-            // low, start applying Russian roulette to terminate
-            // sampling.
-            let rr_threshold: Float = 0.1;
-            if tr < rr_threshold {
-                let q: Float = (0.05 as Float).max(1.0 as Float - tr);
-                if sampler.get_1d() < q {
-                    return Spectrum::default();
+            let inv_max_density: Float = 1.0 as Float / max_density;
+            let mut t: Float = t0;
+            loop {
+                TR_STEPS_PER_CALL.add_num(1);
+                t -= (1.0 as Float - sampler.get_1d()).ln() * inv_max_density / self.sigma_t;
+                if t >= t1 {
+                    break;
+                }
+                let density: Float = self.density(&self.advect(&ray.position(t), ray.time));
+                tr *= 1.0 as Float - (0.0 as Float).max(density * inv_max_density);
+                // added after book publication: when transmittance gets
+                // low, start applying Russian roulette to terminate
+                // sampling.
+                let rr_threshold: Float = 0.1;
+                if tr < rr_threshold {
+                    let q: Float = (0.05 as Float).max(1.0 as Float - tr);
+                    if sampler.get_1d() < q {
+                        return Spectrum::default();
+                    }
+                    tr /= 1.0 as Float - q;
                 }
-                tr /= 1.0 as Float - q;
             }
         }
         Spectrum::new(tr)
@@ -233,35 +515,34 @@ impl GridDensityMedium {
             return (Spectrum::new(1.0 as Float), None);
         }
         // run delta-tracking iterations to sample a medium interaction
-        let mut t: Float = t_min;
-        loop {
-            t -= (1.0 as Float - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
-            if t >= t_max {
-                break;
+        for (t0, t1, max_density) in self.majorant_segments(&ray, t_min, t_max) {
+            if max_density <= 0.0 as Float {
+                continue;
             }
-            if self.density(&ray.position(t)) * self.inv_max_density > sampler.get_1d() {
-                let mi_opt: Option<MediumInteraction>;
-                // populate _mi_ with medium interaction information and return
-                let mi: MediumInteraction = MediumInteraction::new(
-                    &r_world.position(t),
-                    &(-r_world.d),
-                    r_world.time,
-                    Some(Arc::new(Medium::GridDensity(GridDensityMedium {
-                        sigma_a: self.sigma_a,
-                        sigma_s: self.sigma_s,
-                        g: self.g,
-                        nx: self.nx,
-                        ny: self.ny,
-                        nz: self.nz,
-                        world_to_medium: self.world_to_medium,
-                        density: self.density.clone(),
-                        sigma_t: self.sigma_t,
-                        inv_max_density: self.inv_max_density,
-                    }))),
-                    Some(Arc::new(HenyeyGreenstein { g: self.g })),
-                );
-                mi_opt = Some(mi);
-                return (self.sigma_s / self.sigma_t, mi_opt);
+            let inv_max_density: Float = 1.0 as Float / max_density;
+            let mut t: Float = t0;
+            loop {
+                t -= (1.0 as Float - sampler.get_1d()).ln() * inv_max_density / self.sigma_t;
+                if t >= t1 {
+                    break;
+                }
+                let p: Point3f = self.advect(&ray.position(t), ray.time);
+                if self.density(&p) * inv_max_density > sampler.get_1d() {
+                    // populate _mi_ with medium interaction information and return
+                    let mut mi: MediumInteraction = MediumInteraction::new(
+                        &r_world.position(t),
+                        &(-r_world.d),
+                        r_world.time,
+                        Some(Arc::new(Medium::GridDensity(Box::new(self.clone())))),
+                        Some(self.phase.clone()),
+                    );
+                    // emission, weighted by the probability of absorption
+                    let le: Spectrum = self.le(&p);
+                    if !le.is_black() {
+                        mi.le = le * (self.sigma_a / self.sigma_t);
+                    }
+                    return (self.sigma_s / self.sigma_t, Some(mi));
+                }
             }
         }
         (Spectrum::new(1.0 as Float), None)
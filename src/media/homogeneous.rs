@@ -1,10 +1,9 @@
 // std
-use std::f32;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::Ray;
 use crate::core::interaction::MediumInteraction;
-use crate::core::medium::{HenyeyGreenstein, Medium};
+use crate::core::medium::{HenyeyGreenstein, Medium, PhaseFunction};
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::sampler::Sampler;
 
@@ -15,6 +14,7 @@ pub struct HomogeneousMedium {
     pub sigma_s: Spectrum,
     pub sigma_t: Spectrum,
     pub g: Float,
+    pub phase: Arc<PhaseFunction>,
 }
 
 impl HomogeneousMedium {
@@ -24,12 +24,13 @@ impl HomogeneousMedium {
             sigma_s: *sigma_s,
             sigma_t: *sigma_s + *sigma_a,
             g,
+            phase: Arc::new(PhaseFunction::HenyeyGreenstein(HenyeyGreenstein { g })),
         }
     }
     // Medium
     pub fn tr(&self, ray: &Ray, _sampler: &mut Sampler) -> Spectrum {
         // TODO: ProfilePhase _(Prof::MediumTr);
-        (-self.sigma_t * (ray.t_max * ray.d.length()).min(f32::MAX)).exp()
+        (-self.sigma_t * (ray.t_max * ray.d.length()).min(Float::MAX)).exp()
     }
     pub fn sample(
         &self,
@@ -38,7 +39,8 @@ impl HomogeneousMedium {
     ) -> (Spectrum, Option<MediumInteraction>) {
         // TODO: ProfilePhase _(Prof::MediumSample);
         // sample a channel and distance along the ray
-        let channel: usize = ((sampler.get_1d() * 3.0 as Float) as usize).min(2_usize);
+        let channel: usize = ((sampler.get_1d() * Spectrum::N_SAMPLES as Float) as usize)
+            .min(Spectrum::N_SAMPLES - 1);
         let dist: Float = -((1.0 as Float - sampler.get_1d()).ln()) / self.sigma_t[channel];
         let t: Float = (dist / ray.d.length()).min(ray.t_max);
         let sampled_medium: bool = t < ray.t_max;
@@ -47,30 +49,31 @@ impl HomogeneousMedium {
                 &ray.position(t),
                 &(-ray.d),
                 ray.time,
-                Some(Arc::new(Medium::Homogeneous(HomogeneousMedium::new(
-                    &self.sigma_a,
-                    &self.sigma_s,
-                    self.g,
-                )))),
-                Some(Arc::new(HenyeyGreenstein { g: self.g })),
+                Some(Arc::new(Medium::Homogeneous(HomogeneousMedium {
+                    sigma_a: self.sigma_a,
+                    sigma_s: self.sigma_s,
+                    sigma_t: self.sigma_t,
+                    g: self.g,
+                    phase: self.phase.clone(),
+                }))),
+                Some(self.phase.clone()),
             );
             Some(mi)
         } else {
             None
         };
         // compute the transmittance and sampling density
-        let tr: Spectrum = (-self.sigma_t * t.min(f32::MAX) * ray.d.length()).exp();
+        let tr: Spectrum = (-self.sigma_t * t.min(Float::MAX) * ray.d.length()).exp();
         let density = if sampled_medium {
             self.sigma_t * tr
         } else {
             tr
         };
         let mut pdf: Float = 0.0 as Float;
-        for i in 0..3 {
-            // TODO: Spectrum::nSamples
+        for i in 0..Spectrum::N_SAMPLES {
             pdf += density[i];
         }
-        pdf *= 1.0 as Float / 3.0 as Float; // TODO: Spectrum::nSamples
+        pdf *= 1.0 as Float / Spectrum::N_SAMPLES as Float;
         if pdf == 0.0 as Float {
             assert!(tr.is_black());
             pdf = 1.0 as Float;
@@ -4,6 +4,7 @@
 //!
 //! - GridDensityMedium
 //! - HomogeneousMedium
+//! - VolumeAggregate
 //!
 //! ## Grid Density Medium
 //!
@@ -13,5 +14,6 @@
 //!
 //! ![A Volumetric Caustic](/doc/img/volume_caustic_pbrt_rust_mlt.png)
 
+pub mod aggregate;
 pub mod grid;
 pub mod homogeneous;
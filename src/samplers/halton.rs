@@ -1,5 +1,7 @@
 // std
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{Bounds2i, Point2f, Point2i, Vector2i};
 use crate::core::lowdiscrepancy::{
@@ -8,10 +10,10 @@ use crate::core::lowdiscrepancy::{
 };
 use crate::core::lowdiscrepancy::{PRIME_SUMS, PRIME_TABLE_SIZE};
 use crate::core::paramset::ParamSet;
-use crate::core::pbrt::mod_t;
 use crate::core::pbrt::Float;
+use crate::core::pbrt::{mix_bits, mod_t};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{sampler_seed, Sampler};
 
 // Generate random digit permutations for Halton sampler
 lazy_static::lazy_static! {
@@ -57,6 +59,15 @@ pub struct HaltonSampler {
     pub pixel_for_offset: RwLock<Point2i>,
     pub offset_for_current_pixel: RwLock<u64>,
     pub sample_at_pixel_center: bool, // default: false
+    /// scramble all dimensions but the first two (which find the
+    /// samples of a pixel) with random digit permutations, see
+    /// *randomization* (default: true)
+    pub permute_digits: bool,
+    /// selects the digit permutations, see *seed* (default: 0, the
+    /// permutations shared by all Halton samplers)
+    pub seed: u64,
+    /// the digit permutations for a seed other than 0
+    pub permutations: Option<Arc<Vec<u16>>>,
     // inherited from class GlobalSampler (see sampler.h)
     pub dimension: i64,
     pub interval_sample_index: u64,
@@ -110,6 +121,9 @@ impl HaltonSampler {
             pixel_for_offset: RwLock::new(Point2i::default()),
             offset_for_current_pixel: RwLock::new(0_u64),
             sample_at_pixel_center,
+            permute_digits: true,
+            seed: 0_u64,
+            permutations: None,
             dimension: 0_i64,
             interval_sample_index: 0_u64,
             array_start_dim: 5_i64, // static const int arrayStartDim = 5;
@@ -136,6 +150,9 @@ impl HaltonSampler {
             pixel_for_offset: RwLock::new(pixel_for_offset),
             offset_for_current_pixel: RwLock::new(offset_for_current_pixel),
             sample_at_pixel_center: self.sample_at_pixel_center,
+            permute_digits: self.permute_digits,
+            seed: self.seed,
+            permutations: self.permutations.clone(),
             dimension: self.dimension,
             interval_sample_index: self.interval_sample_index,
             array_start_dim: self.array_start_dim,
@@ -156,11 +173,36 @@ impl HaltonSampler {
         let nsamp: i32 = params.find_one_int("pixelsamples", 16);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
         let sample_at_center: bool = params.find_one_bool("samplepixelcenter", false);
-        Box::new(Sampler::Halton(HaltonSampler::new(
-            nsamp as i64,
-            sample_bounds,
-            sample_at_center,
-        )))
+        let randomization: String =
+            params.find_one_string("randomization", String::from("permutedigits"));
+        let permute_digits: bool = match randomization.as_str() {
+            "permutedigits" => true,
+            "none" => false,
+            name => {
+                warn!(
+                    "Halton randomization {:?} unknown (expected permutedigits or none), using permutedigits.",
+                    name
+                );
+                true
+            }
+        };
+        let mut sampler: HaltonSampler =
+            HaltonSampler::new(nsamp as i64, sample_bounds, sample_at_center);
+        sampler.permute_digits = permute_digits;
+        sampler.set_seed(sampler_seed(params));
+        Box::new(Sampler::Halton(sampler))
+    }
+    /// Seed 0 uses the permutations shared by all Halton samplers,
+    /// any other seed its own ones.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.permutations = if seed == 0 {
+            None
+        } else {
+            let mut rng: Rng = Rng::new();
+            rng.set_sequence(mix_bits(seed));
+            Some(Arc::new(compute_radical_inverse_permutations(&mut rng)))
+        };
     }
     pub fn get_index_for_sample(&self, sample_num: u64) -> u64 {
         let pixel_for_offset: Point2i = *self.pixel_for_offset.read().unwrap();
@@ -199,6 +241,8 @@ impl HaltonSampler {
             radical_inverse(dim as u16, index >> self.base_exponents[0] as u64)
         } else if dim == 1 {
             radical_inverse(dim as u16, index / self.base_scales[1] as u64)
+        } else if !self.permute_digits {
+            radical_inverse(dim as u16, index)
         } else {
             scrambled_radical_inverse(dim as u16, index, self.permutation_for_dimension(dim))
         }
@@ -210,7 +254,11 @@ impl HaltonSampler {
                 PRIME_TABLE_SIZE, dim
             );
         }
-        &RADICAL_INVERSE_PERMUTATIONS[PRIME_SUMS[dim as usize] as usize..]
+        let permutations: &[u16] = match self.permutations {
+            Some(ref permutations) => permutations,
+            None => &RADICAL_INVERSE_PERMUTATIONS,
+        };
+        &permutations[PRIME_SUMS[dim as usize] as usize..]
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
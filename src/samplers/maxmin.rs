@@ -1,4 +1,7 @@
+// others
+use log::warn;
 // pbrt
+use crate::core::error::{Error, Result};
 use crate::core::geometry::{Point2f, Point2i};
 use crate::core::lowdiscrepancy::C_MAX_MIN_DIST;
 use crate::core::lowdiscrepancy::{sample_generator_matrix, sobol_2d, van_der_corput};
@@ -6,11 +9,13 @@ use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{is_power_of_2, log_2_int_i64, round_up_pow2_32, round_up_pow2_64};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{sampler_seed, seeded_pixel_seed, Sampler};
 use crate::core::sampling::shuffle;
 
 pub struct MaxMinDistSampler {
     pub samples_per_pixel: i64,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
     pub c_pixel: [u32; 32],
     // inherited from class PixelSampler (see sampler.h)
     pub samples_1d: Vec<Vec<Float>>,
@@ -48,8 +53,8 @@ impl MaxMinDistSampler {
         }
         if !is_power_of_2(samples_per_pixel) {
             samples_per_pixel = round_up_pow2_64(samples_per_pixel);
-            println!(
-                "WARNING: Non power-of-two sample count rounded up to {:?} for MaxMinDistSampler.",
+            warn!(
+                "Non power-of-two sample count rounded up to {:?} for MaxMinDistSampler.",
                 samples_per_pixel
             );
         }
@@ -57,6 +62,7 @@ impl MaxMinDistSampler {
         assert!(c_index >= 0_i32 && c_index < 17);
         let mut mmds: MaxMinDistSampler = MaxMinDistSampler {
             samples_per_pixel,
+            seed: 0_u64,
             c_pixel: C_MAX_MIN_DIST[c_index as usize],
             samples_1d: Vec::new(),
             samples_2d: Vec::new(),
@@ -84,6 +90,7 @@ impl MaxMinDistSampler {
     pub fn clone_with_seed(&self, seed: u64) -> Box<Sampler> {
         let mut mmds = MaxMinDistSampler {
             samples_per_pixel: self.samples_per_pixel,
+            seed: self.seed,
             c_pixel: self.c_pixel,
             samples_1d: self.samples_1d.clone(),
             samples_2d: self.samples_2d.clone(),
@@ -103,14 +110,25 @@ impl MaxMinDistSampler {
         let sampler = Sampler::MaxMinDist(mmds);
         Box::new(sampler)
     }
-    pub fn create(params: &ParamSet) -> Box<Sampler> {
+    pub fn create(params: &ParamSet) -> Result<Box<Sampler>> {
         let nsamp: i32 = params.find_one_int("pixelsamples", 16);
         let sd: i32 = params.find_one_int("dimensions", 4);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-        Box::new(Sampler::MaxMinDist(MaxMinDistSampler::new(
-            nsamp as i64,
-            sd as i64,
-        )))
+        // the count gets rounded up to a power of two (see **new()**)
+        if log_2_int_i64(round_up_pow2_64(nsamp as i64)) as usize >= C_MAX_MIN_DIST.len() {
+            return Err(Error::create(
+                "Sampler",
+                "maxmindist",
+                format!(
+                    "No more than {} samples per pixel are supported (got {}).",
+                    1_i64 << (C_MAX_MIN_DIST.len() - 1),
+                    nsamp
+                ),
+            ));
+        }
+        let mut sampler: MaxMinDistSampler = MaxMinDistSampler::new(nsamp as i64, sd as i64);
+        sampler.seed = sampler_seed(params);
+        Ok(Box::new(Sampler::MaxMinDist(sampler)))
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
@@ -283,7 +301,7 @@ impl MaxMinDistSampler {
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
-        self.rng.set_sequence(seed);
+        self.rng.set_sequence(seeded_pixel_seed(seed, self.seed));
     }
     pub fn get_current_pixel(&self) -> Point2i {
         self.current_pixel
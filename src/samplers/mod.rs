@@ -4,6 +4,7 @@
 //!
 //! - HaltonSampler
 //! - MaxMinDistSampler
+//! - PMJ02BNSampler
 //! - RandomSampler
 //! - SobolSampler
 //! - StratifiedSampler
@@ -14,10 +15,30 @@
 //! The Halton Sampler generates not only points that are guaranteed
 //! to not clump too closely together, but also generates points that
 //! are simultaneously well distributed over all the dimensions of the
-//! sample vector.
+//! sample vector. The digits of all dimensions but the two picking
+//! the positions within a pixel are scrambled by random permutations
+//! (*randomization* "permutedigits"), which breaks up the correlation
+//! between dimensions with large prime bases, "none" keeps the plain
+//! radical inverse. Each pixel starts at its own offset into the
+//! sequence, so neighbouring pixels get different samples. An
+//! alternative to the Sobol Sampler for scenes where the latter shows
+//! structured artefacts.
 //!
 //! ![halton](/doc/img/cornell_box_pbrt_rust_halton.png)
 //!
+//! ## PMJ02BN Sampler
+//!
+//! The PMJ02BN Sampler uses progressive multi-jittered (0,2)
+//! sequences with blue-noise properties: every power of two prefix of
+//! a sequence is stratified in all elementary intervals, and each new
+//! point is placed as far from the earlier ones as the stratification
+//! allows. The sequences (5 of them with 65536 points each) are
+//! generated the first time the sampler is used. The positions within
+//! the pixels come from one sequence spread over a tile of pixels, so
+//! even at low sample counts neighbouring pixels get well separated
+//! samples, which makes it a good choice for progressive previews.
+//! Sample counts are rounded up to a power of four.
+//!
 //! ## Random Sampler
 //!
 //! The Random Sampler is using the random number generetor class
@@ -32,7 +53,14 @@
 //! The Sobol Sampler is very efficient to implement while also being
 //! extremly well distributed over all dimensions of the sample
 //! vector. The weakness of the Sobol' points is that they are prone
-//! to structural grid artefacts before convergence.
+//! to structural grid artefacts before convergence. By default
+//! (*randomization* "none") the plain Sobol' points are used. With
+//! "fastowen" (a hash-based approximation) or the slower "owen" all
+//! dimensions but the two picking the positions within a pixel are
+//! Owen scrambled, seeded by a hash of the pixel, so the error of
+//! neighbouring pixels isn't correlated. The seeds are white noise,
+//! not blue noise, the error doesn't get pushed to high frequencies.
+//! "xor" only flips the same bits of all samples.
 //!
 //! ![sobol](/doc/img/cornell_box_pbrt_rust_sobol.png)
 //!
@@ -48,9 +76,28 @@
 //!
 //! ![lowdiscrepancy](/doc/img/cornell_box_pbrt_rust_lowdiscrepancy.png)
 //!
+//! ## Seeds
+//!
+//! The random numbers of all samplers depend on nothing but the pixel
+//! and the integer parameter *seed* (default: 0), so two renders of a
+//! scene with the same seed give the same image, bit for bit, no
+//! matter how many threads render it. Another seed gives other
+//! samples for every pixel: the Random, Stratified, MaxMinDist,
+//! (0,2)-Sequence, and PMJ02BN samplers get other random numbers (the
+//! latter also shifts its tiles of pixels), the Halton Sampler other
+//! digit permutations, and the Sobol Sampler another scrambling.
+//! Without randomization ("none") the Halton and Sobol samplers
+//! ignore the seed. The seed can be set with *--seed* on the command
+//! line too.
+//!
+//! ```text
+//! Sampler "sobol" "integer pixelsamples" [ 64 ] "integer seed" [ 7 ]
+//! ```
+//!
 
 pub mod halton;
 pub mod maxmin;
+pub mod pmj02bn;
 pub mod random;
 pub mod sobol;
 pub mod stratified;
@@ -0,0 +1,661 @@
+// std
+use std::sync::Arc;
+// others
+use log::warn;
+use rayon::prelude::*;
+// pbrt
+use crate::core::error::{Error, Result};
+use crate::core::geometry::{Point2f, Point2i};
+use crate::core::lowdiscrepancy::{sobol_2d, van_der_corput};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::Float;
+use crate::core::pbrt::{log_2_int_i64, mix_bits, round_up_pow2_32, round_up_pow2_64};
+use crate::core::rng::{Rng, FLOAT_ONE_MINUS_EPSILON};
+use crate::core::sampler::{sampler_seed, seeded_pixel_seed, Sampler};
+
+// see pmj02tables.h (pbrt-v4)
+
+/// The number of independent pmj02bn sequences.
+pub const N_PMJ02BN_SETS: usize = 5;
+/// The number of points in each pmj02bn sequence.
+pub const N_PMJ02BN_SAMPLES: usize = 1 << 16;
+/// Each new point of a sequence is the best of that many candidates
+/// (the one farthest from the points placed before).
+const N_PMJ02BN_CANDIDATES: usize = 10;
+
+// Generate the pmj02bn sequences (the first time they are used)
+lazy_static::lazy_static! {
+    static ref PMJ02BN_SETS: Vec<Vec<Point2f>> = (0..N_PMJ02BN_SETS)
+        .into_par_iter()
+        .map(|set| generate_pmj02bn(N_PMJ02BN_SAMPLES, set as u64))
+        .collect();
+}
+
+/// Returns the points of the pmj02bn sequence *set* (of
+/// **N_PMJ02BN_SETS**).
+pub fn pmj02bn_set(set: usize) -> &'static [Point2f] {
+    &PMJ02BN_SETS[set]
+}
+
+/// Generates *n_samples* (a power of two) points of a progressive
+/// multi-jittered (0,2) sequence with blue-noise properties (see
+/// Christensen, Kensler, and Kilpatrick: Progressive Multi-Jittered
+/// Sample Sequences). Every prefix with a power of two number of
+/// points is stratified in all elementary intervals of that size (a
+/// (0,2)-sequence), and each new point falls into the sub-quadrant of
+/// an earlier point's square stratum left free by the multi-jittered
+/// construction.
+pub fn generate_pmj02bn(n_samples: usize, seed: u64) -> Vec<Point2f> {
+    let mut rng: Rng = Rng::default();
+    rng.set_sequence(seed);
+    let mut generator: Pmj02bnGenerator = Pmj02bnGenerator {
+        points: vec![[rng.uniform_uint32(), rng.uniform_uint32()]],
+        m: 0,
+        occupied: Vec::new(),
+        grid_bits: 0,
+        grid: Vec::new(),
+        rng,
+    };
+    while generator.points.len() < n_samples {
+        generator.extend();
+    }
+    // 24 bits fit into a (single precision) Float without rounding
+    // into another elementary interval
+    let scale: Float = 1.0 as Float / (1_u32 << 24) as Float;
+    generator
+        .points
+        .iter()
+        .map(|p| Point2f {
+            x: (p[0] >> 8) as Float * scale,
+            y: (p[1] >> 8) as Float * scale,
+        })
+        .collect()
+}
+
+/// The state of **generate_pmj02bn()**, points are stored as 32 bit
+/// fixed point numbers.
+struct Pmj02bnGenerator {
+    points: Vec<[u32; 2]>,
+    /// the elementary intervals have an area of 1/2^m
+    m: u32,
+    /// for each shape (2^k by 2^(m-k) intervals) which intervals
+    /// already contain a point
+    occupied: Vec<Vec<bool>>,
+    /// 2^grid_bits by 2^grid_bits cells to find nearby points
+    grid_bits: u32,
+    grid: Vec<Vec<u32>>,
+    rng: Rng,
+}
+
+impl Pmj02bnGenerator {
+    /// Doubles the number of points.
+    fn extend(&mut self) {
+        let n: usize = self.points.len();
+        self.m = (2 * n).trailing_zeros();
+        self.occupied = vec![vec![false; 2 * n]; self.m as usize + 1];
+        self.grid_bits = self.m.saturating_sub(2) / 2;
+        self.grid = vec![Vec::new(); 1 << (2 * self.grid_bits)];
+        for i in 0..n {
+            let p: [u32; 2] = self.points[i];
+            self.mark(p, i);
+        }
+        // the earlier points are stratified in squares of
+        // 1/2^half_bits by 1/2^half_bits, the new ones go into the
+        // sub-quadrants left free in each square
+        let log_n: u32 = n.trailing_zeros();
+        if log_n.is_multiple_of(2) {
+            // one point per square, add one in the diagonally
+            // opposite sub-quadrant
+            let half_bits: u32 = log_n / 2 + 1;
+            for i in 0..n {
+                let [xq, yq] = self.sub_quadrant(i, half_bits);
+                self.add_point(xq ^ 1, yq ^ 1, half_bits);
+            }
+        } else {
+            // two points per square (in diagonally opposite
+            // sub-quadrants), add one in each of the other two
+            let half_bits: u32 = (log_n - 1) / 2 + 1;
+            let flip_x: Vec<bool> = (0..n / 2)
+                .map(|_| self.rng.uniform_uint32() & 1 == 0)
+                .collect();
+            for (i, flip_x) in flip_x.iter().enumerate() {
+                let [xq, yq] = self.sub_quadrant(i, half_bits);
+                if *flip_x {
+                    self.add_point(xq ^ 1, yq, half_bits);
+                } else {
+                    self.add_point(xq, yq ^ 1, half_bits);
+                }
+            }
+            for (i, flip_x) in flip_x.iter().enumerate() {
+                let [xq, yq] = self.sub_quadrant(i, half_bits);
+                if *flip_x {
+                    self.add_point(xq, yq ^ 1, half_bits);
+                } else {
+                    self.add_point(xq ^ 1, yq, half_bits);
+                }
+            }
+        }
+    }
+    /// The leading *bits* bits of both coordinates of point *i*.
+    fn sub_quadrant(&self, i: usize, bits: u32) -> [u32; 2] {
+        let p: [u32; 2] = self.points[i];
+        [p[0] >> (32 - bits), p[1] >> (32 - bits)]
+    }
+    fn interval(&self, xs: usize, ys: usize, k: u32) -> usize {
+        ((ys >> k) << k) | (xs >> (self.m - k))
+    }
+    fn grid_cell(&self, p: [u32; 2]) -> usize {
+        let x: usize = (p[0] as u64 >> (32 - self.grid_bits)) as usize;
+        let y: usize = (p[1] as u64 >> (32 - self.grid_bits)) as usize;
+        (y << self.grid_bits) | x
+    }
+    fn mark(&mut self, p: [u32; 2], i: usize) {
+        let xs: usize = (p[0] >> (32 - self.m)) as usize;
+        let ys: usize = (p[1] >> (32 - self.m)) as usize;
+        for k in 0..=self.m {
+            let interval: usize = self.interval(xs, ys, k);
+            self.occupied[k as usize][interval] = true;
+        }
+        let cell: usize = self.grid_cell(p);
+        self.grid[cell].push(i as u32);
+    }
+    /// Squared (toroidal) distance to the closest point in the
+    /// neighbouring grid cells.
+    fn min_distance(&self, p: [u32; 2]) -> f64 {
+        let res: i64 = 1_i64 << self.grid_bits;
+        let cell: usize = self.grid_cell(p);
+        let cx: i64 = (cell as i64) % res;
+        let cy: i64 = (cell as i64) / res;
+        let mut min_d2: f64 = f64::MAX;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let x: i64 = (cx + dx).rem_euclid(res);
+                let y: i64 = (cy + dy).rem_euclid(res);
+                for i in &self.grid[(y * res + x) as usize] {
+                    let q: [u32; 2] = self.points[*i as usize];
+                    // wrapping differences are toroidal distances
+                    let ddx: f64 = p[0].wrapping_sub(q[0]) as i32 as f64;
+                    let ddy: f64 = p[1].wrapping_sub(q[1]) as i32 as f64;
+                    min_d2 = min_d2.min(ddx * ddx + ddy * ddy);
+                }
+            }
+        }
+        min_d2
+    }
+    /// Finds the x stratum (at resolution 2^m) for y stratum *ys*,
+    /// starting with the leading *bits* bits *xq*, so that the point
+    /// falls into free elementary intervals only.
+    fn find_x(&mut self, ys: usize, k: u32, xp: usize, xq: u32, bits: u32) -> Option<usize> {
+        if self.occupied[k as usize][((ys >> k) << k) | xp] {
+            return None;
+        }
+        if k == self.m {
+            return Some(xp);
+        }
+        if k < bits {
+            let bit: usize = ((xq >> (bits - 1 - k)) & 1) as usize;
+            return self.find_x(ys, k + 1, (xp << 1) | bit, xq, bits);
+        }
+        let first: usize = (self.rng.uniform_uint32() & 1) as usize;
+        for bit in [first, 1 - first] {
+            if let Some(xs) = self.find_x(ys, k + 1, (xp << 1) | bit, xq, bits) {
+                return Some(xs);
+            }
+        }
+        None
+    }
+    /// Adds a point in sub-quadrant (*xq*, *yq*) of the 2^bits by
+    /// 2^bits sub-quadrants, the best of **N_PMJ02BN_CANDIDATES**.
+    fn add_point(&mut self, xq: u32, yq: u32, bits: u32) {
+        let mut best: Option<[u32; 2]> = None;
+        let mut best_d2: f64 = -1.0;
+        // (with the sub-quadrant as a constraint first)
+        for bits in [bits, 0] {
+            let (xq, yq): (u32, u32) = if bits == 0 { (0, 0) } else { (xq, yq) };
+            let per: usize = 1 << (self.m - bits);
+            let y0: usize = yq as usize * per;
+            let mut ys_free: Vec<usize> =
+                (y0..y0 + per).filter(|ys| !self.occupied[0][*ys]).collect();
+            let mut found: usize = 0;
+            while found < N_PMJ02BN_CANDIDATES && !ys_free.is_empty() {
+                let index: usize = self.rng.uniform_uint32_bounded(ys_free.len() as u32) as usize;
+                let ys: usize = ys_free[index];
+                if let Some(xs) = self.find_x(ys, 0, 0, xq, bits) {
+                    found += 1;
+                    // jitter within the elementary interval
+                    let shift: u32 = 32 - self.m;
+                    let mask: u32 = ((1_u64 << shift) - 1) as u32;
+                    let p: [u32; 2] = [
+                        ((xs as u64) << shift) as u32 | (self.rng.uniform_uint32() & mask),
+                        ((ys as u64) << shift) as u32 | (self.rng.uniform_uint32() & mask),
+                    ];
+                    let d2: f64 = self.min_distance(p);
+                    if d2 > best_d2 {
+                        best_d2 = d2;
+                        best = Some(p);
+                    }
+                } else {
+                    ys_free.swap_remove(index);
+                }
+            }
+            if best.is_some() {
+                break;
+            }
+        }
+        let p: [u32; 2] = best.expect("no free elementary interval for pmj02bn point");
+        let i: usize = self.points.len();
+        self.mark(p, i);
+        self.points.push(p);
+    }
+}
+
+/// Rearranges the points of *set* for the (power of two) number of
+/// *samples*, by XORing the indices with a random value (which keeps
+/// each power of two prefix, and each aligned block of them,
+/// stratified) and a random toroidal shift.
+fn scrambled_pmj02bn_2d(set: &[Point2f], samples: &mut [Point2f], rng: &mut Rng) {
+    let index_scramble: usize = rng.uniform_uint32_bounded(samples.len() as u32) as usize;
+    let shift: Point2f = Point2f {
+        x: rng.uniform_float(),
+        y: rng.uniform_float(),
+    };
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let p: Point2f = set[i ^ index_scramble];
+        *sample = Point2f {
+            x: wrap_sample(p.x + shift.x),
+            y: wrap_sample(p.y + shift.y),
+        };
+    }
+}
+
+/// Same as **scrambled_pmj02bn_2d()** for the x coordinates only.
+fn scrambled_pmj02bn_1d(set: &[Point2f], samples: &mut [Float], rng: &mut Rng) {
+    let index_scramble: usize = rng.uniform_uint32_bounded(samples.len() as u32) as usize;
+    let shift: Float = rng.uniform_float();
+    for (i, sample) in samples.iter_mut().enumerate() {
+        *sample = wrap_sample(set[i ^ index_scramble].x + shift);
+    }
+}
+
+fn wrap_sample(v: Float) -> Float {
+    if v >= 1.0 as Float {
+        (v - 1.0 as Float).min(FLOAT_ONE_MINUS_EPSILON)
+    } else {
+        v
+    }
+}
+
+// see pmj02bnsampler.h (pbrt-v4)
+
+pub struct PMJ02BNSampler {
+    pub samples_per_pixel: i64,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
+    pub n_sampled_dimensions: i64,
+    /// the first pmj02bn sequence spread over tiles of
+    /// pixel_tile_size by pixel_tile_size pixels, the samples of each
+    /// pixel (in the order of the sequence) one after another
+    pub pixel_samples: Arc<Vec<Point2f>>,
+    pub pixel_tile_size: i32,
+    // inherited from class PixelSampler (see sampler.h)
+    pub samples_1d: Vec<Vec<Float>>,
+    pub samples_2d: Vec<Vec<Point2f>>,
+    pub current_1d_dimension: i32,
+    pub current_2d_dimension: i32,
+    pub rng: Rng,
+    // inherited from class Sampler (see sampler.h)
+    pub current_pixel: Point2i,
+    pub current_pixel_sample_index: i64,
+    pub samples_1d_array_sizes: Vec<i32>,
+    pub samples_2d_array_sizes: Vec<i32>,
+    pub sample_array_1d: Vec<Vec<Float>>,
+    pub sample_array_2d: Vec<Vec<Point2f>>,
+    pub array_1d_offset: usize,
+    pub array_2d_offset: usize,
+}
+
+impl PMJ02BNSampler {
+    pub fn new(samples_per_pixel: i64, n_sampled_dimensions: i64) -> Self {
+        // the pixels get square strata of the sequences for powers of four only
+        let mut samples_per_pixel: i64 = samples_per_pixel.max(1);
+        let mut spp_pow4: i64 = round_up_pow2_64(samples_per_pixel);
+        if log_2_int_i64(spp_pow4) % 2 == 1 {
+            spp_pow4 *= 2;
+        }
+        if spp_pow4 != samples_per_pixel {
+            samples_per_pixel = spp_pow4;
+            warn!(
+                "Non power-of-four sample count rounded up to {:?} for PMJ02BNSampler.",
+                samples_per_pixel
+            );
+        }
+        let pixel_tile_size: i32 = 1_i32
+            << ((log_2_int_i64(N_PMJ02BN_SAMPLES as i64) - log_2_int_i64(samples_per_pixel)) / 2);
+        // sort the points of the first sequence into the pixels of a tile
+        let n_pixels: usize = (pixel_tile_size * pixel_tile_size) as usize;
+        let n_pixel_samples: usize = n_pixels * samples_per_pixel as usize;
+        let mut pixel_samples: Vec<Point2f> = vec![Point2f::default(); n_pixel_samples];
+        let mut n_stored: Vec<usize> = vec![0; n_pixels];
+        for p in &pmj02bn_set(0)[..n_pixel_samples] {
+            let p: Point2f = *p * pixel_tile_size as Float;
+            let pixel: usize = p.x as usize + p.y as usize * pixel_tile_size as usize;
+            let offset: usize = pixel * samples_per_pixel as usize + n_stored[pixel];
+            pixel_samples[offset] = Point2f {
+                x: p.x - p.x.floor(),
+                y: p.y - p.y.floor(),
+            };
+            n_stored[pixel] += 1;
+        }
+        let mut pmj: PMJ02BNSampler = PMJ02BNSampler {
+            samples_per_pixel,
+            seed: 0_u64,
+            n_sampled_dimensions,
+            pixel_samples: Arc::new(pixel_samples),
+            pixel_tile_size,
+            samples_1d: Vec::new(),
+            samples_2d: Vec::new(),
+            current_1d_dimension: 0_i32,
+            current_2d_dimension: 0_i32,
+            rng: Rng::default(),
+            current_pixel: Point2i::default(),
+            current_pixel_sample_index: 0_i64,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            array_1d_offset: 0_usize,
+            array_2d_offset: 0_usize,
+        };
+        for _i in 0..pmj.n_sampled_dimensions {
+            let additional_1d: Vec<Float> = vec![0.0; pmj.samples_per_pixel as usize];
+            let additional_2d: Vec<Point2f> =
+                vec![Point2f::default(); pmj.samples_per_pixel as usize];
+            pmj.samples_1d.push(additional_1d);
+            pmj.samples_2d.push(additional_2d);
+        }
+        pmj
+    }
+    pub fn clone_with_seed(&self, seed: u64) -> Box<Sampler> {
+        let mut pmj_sampler = PMJ02BNSampler {
+            samples_per_pixel: self.samples_per_pixel,
+            seed: self.seed,
+            n_sampled_dimensions: self.n_sampled_dimensions,
+            pixel_samples: self.pixel_samples.clone(),
+            pixel_tile_size: self.pixel_tile_size,
+            samples_1d: self.samples_1d.clone(),
+            samples_2d: self.samples_2d.clone(),
+            current_1d_dimension: self.current_1d_dimension,
+            current_2d_dimension: self.current_2d_dimension,
+            rng: self.rng,
+            current_pixel: self.current_pixel,
+            current_pixel_sample_index: self.current_pixel_sample_index,
+            samples_1d_array_sizes: self.samples_1d_array_sizes.to_vec(),
+            samples_2d_array_sizes: self.samples_2d_array_sizes.to_vec(),
+            sample_array_1d: self.sample_array_1d.to_vec(),
+            sample_array_2d: self.sample_array_2d.to_vec(),
+            array_1d_offset: self.array_1d_offset,
+            array_2d_offset: self.array_2d_offset,
+        };
+        pmj_sampler.reseed(seed);
+        let sampler = Sampler::PMJ02BN(pmj_sampler);
+        Box::new(sampler)
+    }
+    pub fn create(params: &ParamSet) -> Result<Box<Sampler>> {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        let sd: i32 = params.find_one_int("dimensions", 4);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        // the count gets rounded up to a power of four (see **new()**)
+        if nsamp as i64 > N_PMJ02BN_SAMPLES as i64 {
+            return Err(Error::create(
+                "Sampler",
+                "pmj02bn",
+                format!(
+                    "No more than {} samples per pixel are supported (got {}).",
+                    N_PMJ02BN_SAMPLES, nsamp
+                ),
+            ));
+        }
+        let mut sampler: PMJ02BNSampler = PMJ02BNSampler::new(nsamp as i64, sd as i64);
+        sampler.seed = sampler_seed(params);
+        Ok(Box::new(Sampler::PMJ02BN(sampler)))
+    }
+    // Sampler
+    pub fn start_pixel(&mut self, p: Point2i) {
+        // TODO: ProfilePhase _(Prof::StartPixel);
+        // the pixel's share of the first sequence for the positions
+        // within the pixel, the other sequences (scrambled) for the
+        // other dimensions (another seed shifts the tiles)
+        let spp: usize = self.samples_per_pixel as usize;
+        let shift: u64 = mix_bits(self.seed);
+        let x: i32 = p.x.wrapping_add(shift as i32);
+        let y: i32 = p.y.wrapping_add((shift >> 32) as i32);
+        let pixel: usize = (x.rem_euclid(self.pixel_tile_size)
+            + y.rem_euclid(self.pixel_tile_size) * self.pixel_tile_size)
+            as usize;
+        for (dim, samples) in self.samples_2d.iter_mut().enumerate() {
+            if dim == 0 {
+                samples.copy_from_slice(&self.pixel_samples[pixel * spp..(pixel + 1) * spp]);
+            } else {
+                let set: &[Point2f] = pmj02bn_set(dim % N_PMJ02BN_SETS);
+                scrambled_pmj02bn_2d(set, samples, &mut self.rng);
+            }
+        }
+        for (dim, samples) in self.samples_1d.iter_mut().enumerate() {
+            let set: &[Point2f] = pmj02bn_set(dim % N_PMJ02BN_SETS);
+            scrambled_pmj02bn_1d(set, samples, &mut self.rng);
+        }
+        // generate 1D and 2D array samples, the 2D ones from the
+        // sequences as long as they are long enough
+        for i in 0..self.samples_1d_array_sizes.len() {
+            let samples: &mut [Float] = self.sample_array_1d[i].as_mut_slice();
+            van_der_corput(
+                self.samples_1d_array_sizes[i],
+                self.samples_per_pixel as i32,
+                samples,
+                &mut self.rng,
+            );
+        }
+        for i in 0..self.samples_2d_array_sizes.len() {
+            let samples: &mut [Point2f] = self.sample_array_2d[i].as_mut_slice();
+            if samples.len() <= N_PMJ02BN_SAMPLES {
+                let set: &[Point2f] = pmj02bn_set(i % N_PMJ02BN_SETS);
+                scrambled_pmj02bn_2d(set, samples, &mut self.rng);
+            } else {
+                sobol_2d(
+                    self.samples_2d_array_sizes[i],
+                    self.samples_per_pixel as i32,
+                    samples,
+                    &mut self.rng,
+                );
+            }
+        }
+        // PixelSampler::StartPixel(p);
+        self.current_pixel = p;
+        self.current_pixel_sample_index = 0_i64;
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+    }
+    pub fn get_1d(&mut self) -> Float {
+        // TODO: ProfilePhase _(Prof::GetSample);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "current_pixel_sample_index = {}, samples_per_pixel = {}",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        if self.current_1d_dimension < self.samples_1d.len() as i32 {
+            let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_1d_dimension += 1;
+            sample
+        } else {
+            self.rng.uniform_float()
+        }
+    }
+    pub fn get_2d(&mut self) -> Point2f {
+        // TODO: ProfilePhase _(Prof::GetSample);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "current_pixel_sample_index = {}, samples_per_pixel = {}",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        if self.current_2d_dimension < self.samples_2d.len() as i32 {
+            let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_2d_dimension += 1;
+            sample
+        } else {
+            // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
+            let y = self.rng.uniform_float();
+            let x = self.rng.uniform_float();
+            Point2f { x, y }
+        }
+    }
+    pub fn request_2d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_2d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
+        self.sample_array_2d.push(additional_points);
+    }
+    pub fn round_count(&self, count: i32) -> i32 {
+        round_up_pow2_32(count)
+    }
+    pub fn get_2d_array(&mut self, n: i32) -> Option<&[Point2f]> {
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return None;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        self.array_2d_offset += 1;
+        Some(&self.sample_array_2d[self.array_2d_offset - 1][start..end])
+    }
+    pub fn get_2d_arrays(&mut self, n: i32) -> (Option<&[Point2f]>, Option<&[Point2f]>) {
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return (None, None);
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        self.array_2d_offset += 1;
+        let ret1 = &self.sample_array_2d[self.array_2d_offset - 1][start..end];
+        // repeat code from above
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return (None, None);
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        self.array_2d_offset += 1;
+        let ret2 = &self.sample_array_2d[self.array_2d_offset - 1][start..end];
+        // return tuple
+        (Some(ret1), Some(ret2))
+    }
+    pub fn get_2d_array_vec(&mut self, n: i32) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::new();
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_2d[self.array_2d_offset][start..end].to_vec();
+        self.array_2d_offset += 1;
+        samples
+    }
+    pub fn start_next_sample(&mut self) -> bool {
+        self.current_1d_dimension = 0_i32;
+        self.current_2d_dimension = 0_i32;
+        // Sampler::StartNextSample()
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+        self.current_pixel_sample_index += 1_i64;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng.set_sequence(seeded_pixel_seed(seed, self.seed));
+    }
+    pub fn get_current_pixel(&self) -> Point2i {
+        self.current_pixel
+    }
+    pub fn get_current_sample_number(&self) -> i64 {
+        self.current_pixel_sample_index
+    }
+    pub fn get_samples_per_pixel(&self) -> i64 {
+        self.samples_per_pixel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that each elementary interval of area 1/2^k (of every
+    /// shape) contains exactly one of the first 2^k points.
+    fn assert_stratified(points: &[Point2f], k: u32) {
+        let n: usize = 1 << k;
+        for x_bits in 0..=k {
+            let y_bits: u32 = k - x_bits;
+            let mut occupied: Vec<bool> = vec![false; n];
+            for p in &points[..n] {
+                let x: usize = (p.x * (1_usize << x_bits) as Float) as usize;
+                let y: usize = (p.y * (1_usize << y_bits) as Float) as usize;
+                let interval: usize = (x << y_bits) | y;
+                assert!(
+                    !occupied[interval],
+                    "two of the first {} points in interval ({}, {}) of shape {}x{}",
+                    n,
+                    x,
+                    y,
+                    1_usize << x_bits,
+                    1_usize << y_bits
+                );
+                occupied[interval] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn prefixes_are_02_stratified() {
+        let points: Vec<Point2f> = generate_pmj02bn(1 << 10, 0);
+        assert_eq!(points.len(), 1 << 10);
+        for p in &points {
+            assert!(p.x >= 0.0 as Float && p.x < 1.0 as Float);
+            assert!(p.y >= 0.0 as Float && p.y < 1.0 as Float);
+        }
+        for k in 0..=10 {
+            assert_stratified(&points, k);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_points() {
+        let a: Vec<Point2f> = generate_pmj02bn(256, 3);
+        let b: Vec<Point2f> = generate_pmj02bn(256, 3);
+        assert!(a.iter().zip(&b).all(|(p, q)| p.x == q.x && p.y == q.y));
+        let c: Vec<Point2f> = generate_pmj02bn(256, 4);
+        assert!(a.iter().zip(&c).any(|(p, q)| p.x != q.x || p.y != q.y));
+    }
+}
@@ -3,12 +3,14 @@ use crate::core::geometry::{Point2f, Point2i};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{sampler_seed, seeded_pixel_seed, Sampler};
 
 // see random.h
 
 pub struct RandomSampler {
     pub samples_per_pixel: i64,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
     pub rng: Rng,
     // inherited from class Sampler (see sampler.h)
     pub current_pixel: Point2i,
@@ -25,6 +27,7 @@ impl RandomSampler {
     pub fn new(samples_per_pixel: i64) -> Self {
         RandomSampler {
             samples_per_pixel,
+            seed: 0_u64,
             rng: Rng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
@@ -38,7 +41,8 @@ impl RandomSampler {
     }
     pub fn clone_with_seed(&self, seed: u64) -> Box<Sampler> {
         let mut random_sampler = RandomSampler::new(self.samples_per_pixel);
-        random_sampler.rng.set_sequence(seed);
+        random_sampler.seed = self.seed;
+        random_sampler.reseed(seed);
         // manually copy remaining bits
         random_sampler.current_pixel = self.current_pixel;
         random_sampler.current_pixel_sample_index = self.current_pixel_sample_index;
@@ -54,7 +58,9 @@ impl RandomSampler {
     pub fn create(params: &ParamSet) -> Box<Sampler> {
         let nsamp: i32 = params.find_one_int("pixelsamples", 4);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-        Box::new(Sampler::Random(RandomSampler::new(nsamp as i64)))
+        let mut sampler: RandomSampler = RandomSampler::new(nsamp as i64);
+        sampler.seed = sampler_seed(params);
+        Box::new(Sampler::Random(sampler))
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
@@ -93,7 +99,7 @@ impl RandomSampler {
         Point2f { x, y }
     }
     pub fn reseed(&mut self, seed: u64) {
-        self.rng.set_sequence(seed);
+        self.rng.set_sequence(seeded_pixel_seed(seed, self.seed));
     }
     pub fn request_2d_array(&mut self, n: i32) {
         assert_eq!(self.round_count(n), n);
@@ -1,13 +1,17 @@
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{Bounds2i, Point2f, Point2i, Vector2i};
-use crate::core::lowdiscrepancy::{sobol_interval_to_index, sobol_sample};
+use crate::core::lowdiscrepancy::{
+    sobol_interval_to_index, sobol_sample_randomized, SobolRandomization,
+};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{
-    clamp_t, is_power_of_2, log_2_int_u32, round_up_pow2_32, round_up_pow2_64,
+    clamp_t, is_power_of_2, log_2_int_u32, mix_bits, round_up_pow2_32, round_up_pow2_64,
 };
 use crate::core::rng::FLOAT_ONE_MINUS_EPSILON;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{pixel_seed, sampler_seed, seeded_pixel_seed, Sampler};
 use crate::core::sobolmatrices::NUM_SOBOL_DIMENSIONS;
 
 // see sobol.h
@@ -17,6 +21,15 @@ pub struct SobolSampler {
     pub sample_bounds: Bounds2i,
     pub resolution: i32,
     pub log_2_resolution: i32,
+    /// randomizes all dimensions but the first two (which find the
+    /// samples of a pixel), see *randomization* (default: None, the
+    /// plain Sobol' points of earlier versions)
+    pub randomization: SobolRandomization,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
+    /// the seed of the randomization, a hash of the current pixel (and
+    /// the seed above)
+    pub pixel_seed: u64,
     // inherited from class GlobalSampler (see sampler.h)
     pub dimension: i64,
     pub interval_sample_index: u64,
@@ -34,12 +47,16 @@ pub struct SobolSampler {
 }
 
 impl SobolSampler {
+    /// The pixels get their samples from one Sobol sequence spread
+    /// over *sample_bounds*, which should be the full frame (not a
+    /// crop of it), so the samples of a pixel don't depend on the crop
+    /// window. Pixels outside of it wrap around.
     pub fn new(samples_per_pixel: i64, sample_bounds: &Bounds2i) -> Self {
         let mut samples_per_pixel: i64 = samples_per_pixel;
         if !is_power_of_2(samples_per_pixel) {
             samples_per_pixel = round_up_pow2_64(samples_per_pixel);
-            println!(
-                "WARNING: Non power-of-two sample count rounded up to {:?} for SobolSampler.",
+            warn!(
+                "Non power-of-two sample count rounded up to {:?} for SobolSampler.",
                 samples_per_pixel
             );
         }
@@ -63,6 +80,9 @@ impl SobolSampler {
             },
             resolution,
             log_2_resolution,
+            randomization: SobolRandomization::None,
+            seed: 0_u64,
+            pixel_seed: 0_u64,
             dimension: 0_i64,
             interval_sample_index: 0_u64,
             array_start_dim: 5_i64, // static const int arrayStartDim = 5;
@@ -83,6 +103,9 @@ impl SobolSampler {
             sample_bounds: self.sample_bounds,
             resolution: self.resolution,
             log_2_resolution: self.log_2_resolution,
+            randomization: self.randomization,
+            seed: self.seed,
+            pixel_seed: self.pixel_seed,
             dimension: self.dimension,
             interval_sample_index: self.interval_sample_index,
             array_start_dim: self.array_start_dim,
@@ -102,13 +125,37 @@ impl SobolSampler {
     pub fn create(params: &ParamSet, sample_bounds: &Bounds2i) -> Box<Sampler> {
         let nsamp: i32 = params.find_one_int("pixelsamples", 16);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-        Box::new(Sampler::Sobol(SobolSampler::new(
-            nsamp as i64,
-            sample_bounds,
-        )))
+        let randomization: String = params.find_one_string("randomization", String::from("none"));
+        let randomization: SobolRandomization = match randomization.as_str() {
+            "fastowen" => SobolRandomization::FastOwen,
+            "owen" => SobolRandomization::Owen,
+            "xor" => SobolRandomization::Xor,
+            "none" => SobolRandomization::None,
+            name => {
+                warn!(
+                    "Sobol randomization {:?} unknown (expected none, fastowen, owen, or xor), using none.",
+                    name
+                );
+                SobolRandomization::None
+            }
+        };
+        let mut sampler: SobolSampler = SobolSampler::new(nsamp as i64, sample_bounds);
+        sampler.randomization = randomization;
+        sampler.seed = sampler_seed(params);
+        Box::new(Sampler::Sobol(sampler))
     }
-    pub fn get_index_for_sample(&self, sample_num: u64) -> u64 {
+    /// The current pixel relative to the sample bounds, wrapped around
+    /// the (power of two) resolution.
+    fn pixel_offset(&self) -> Vector2i {
         let v: Vector2i = self.current_pixel - self.sample_bounds.p_min;
+        let resolution: i32 = self.resolution.max(1_i32);
+        Vector2i {
+            x: v.x.rem_euclid(resolution),
+            y: v.y.rem_euclid(resolution),
+        }
+    }
+    pub fn get_index_for_sample(&self, sample_num: u64) -> u64 {
+        let v: Vector2i = self.pixel_offset();
         sobol_interval_to_index(
             self.log_2_resolution as u32,
             sample_num,
@@ -122,17 +169,23 @@ impl SobolSampler {
                 NUM_SOBOL_DIMENSIONS
             );
         }
-        let mut s: Float = sobol_sample(index as i64, dim as i32, 0_u64);
         // remap Sobol$'$ dimensions used for pixel samples
         if dim == 0 || dim == 1 {
-            s = s * self.resolution as Float + self.sample_bounds.p_min[dim as u8] as Float;
+            // (which can't be randomized, they select the pixel)
+            let mut s: Float =
+                sobol_sample_randomized(index as i64, dim as i32, SobolRandomization::None, 0);
+            s *= self.resolution as Float;
             s = clamp_t(
-                s - self.current_pixel[dim as u8] as Float,
+                s - self.pixel_offset()[dim as u8] as Float,
                 0.0 as Float,
                 FLOAT_ONE_MINUS_EPSILON,
             );
+            return s;
         }
-        s
+        // a different (hashed, i.e. white noise) seed for each pixel
+        // and dimension decorrelates the error of neighbouring pixels
+        let seed: u32 = mix_bits(self.pixel_seed ^ dim as u64) as u32;
+        sobol_sample_randomized(index as i64, dim as i32, self.randomization, seed)
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
@@ -140,6 +193,7 @@ impl SobolSampler {
         // Sampler::StartPixel(p);
         self.current_pixel = p;
         self.current_pixel_sample_index = 0_i64;
+        self.pixel_seed = seeded_pixel_seed(pixel_seed(p), self.seed);
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
         // GlobalSampler::StartPixel(p);
@@ -284,7 +338,8 @@ impl SobolSampler {
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, _seed: u64) {
-        // do nothing
+        // do nothing (the randomization only depends on the pixel, so
+        // all passes of a progressive render share it)
     }
     pub fn get_current_pixel(&self) -> Point2i {
         self.current_pixel
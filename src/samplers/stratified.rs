@@ -3,11 +3,13 @@ use crate::core::geometry::{Point2f, Point2i};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{sampler_seed, seeded_pixel_seed, Sampler};
 use crate::core::sampling::{latin_hypercube, shuffle, stratified_sample_1d, stratified_sample_2d};
 
 pub struct StratifiedSampler {
     pub samples_per_pixel: i64,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
     pub x_pixel_samples: i32,
     pub y_pixel_samples: i32,
     pub jitter_samples: bool,
@@ -37,6 +39,7 @@ impl StratifiedSampler {
     ) -> Self {
         let mut ss = StratifiedSampler {
             samples_per_pixel: (x_pixel_samples * y_pixel_samples) as i64,
+            seed: 0_u64,
             x_pixel_samples,
             y_pixel_samples,
             jitter_samples,
@@ -66,6 +69,7 @@ impl StratifiedSampler {
     pub fn clone_with_seed(&self, seed: u64) -> Box<Sampler> {
         let mut ss = StratifiedSampler {
             samples_per_pixel: self.samples_per_pixel,
+            seed: self.seed,
             x_pixel_samples: self.x_pixel_samples,
             y_pixel_samples: self.y_pixel_samples,
             jitter_samples: self.jitter_samples,
@@ -93,9 +97,10 @@ impl StratifiedSampler {
         let ysamp: i32 = params.find_one_int("ysamples", 4);
         let sd: i32 = params.find_one_int("dimensions", 4);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-        Box::new(Sampler::Stratified(StratifiedSampler::new(
-            xsamp, ysamp, jitter, sd as i64,
-        )))
+        let mut sampler: StratifiedSampler =
+            StratifiedSampler::new(xsamp, ysamp, jitter, sd as i64);
+        sampler.seed = sampler_seed(params);
+        Box::new(Sampler::Stratified(sampler))
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
@@ -285,7 +290,7 @@ impl StratifiedSampler {
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
-        self.rng.set_sequence(seed);
+        self.rng.set_sequence(seeded_pixel_seed(seed, self.seed));
     }
     pub fn get_current_pixel(&self) -> Point2i {
         self.current_pixel
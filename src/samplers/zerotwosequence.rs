@@ -1,16 +1,20 @@
+// others
+use log::warn;
 // pbrt
 use crate::core::geometry::{Point2f, Point2i};
 use crate::core::lowdiscrepancy::{sobol_2d, van_der_corput};
 use crate::core::paramset::ParamSet;
-use crate::core::pbrt::round_up_pow2_32;
 use crate::core::pbrt::Float;
+use crate::core::pbrt::{is_power_of_2, round_up_pow2_32, round_up_pow2_64};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{sampler_seed, seeded_pixel_seed, Sampler};
 
 // see zerotwosequence.h
 
 pub struct ZeroTwoSequenceSampler {
     pub samples_per_pixel: i64,
+    /// mixed into the seed of each pixel, see *seed* (default: 0)
+    pub seed: u64,
     pub n_sampled_dimensions: i64,
     // inherited from class PixelSampler (see sampler.h)
     pub samples_1d: Vec<Vec<Float>>,
@@ -33,6 +37,7 @@ impl Default for ZeroTwoSequenceSampler {
     fn default() -> Self {
         let mut lds: ZeroTwoSequenceSampler = ZeroTwoSequenceSampler {
             samples_per_pixel: 1_i64,
+            seed: 0_u64,
             n_sampled_dimensions: 4_i64,
             samples_1d: Vec::new(),
             samples_2d: Vec::new(),
@@ -61,8 +66,18 @@ impl Default for ZeroTwoSequenceSampler {
 
 impl ZeroTwoSequenceSampler {
     pub fn new(samples_per_pixel: i64, n_sampled_dimensions: i64) -> Self {
+        // the $(0,2)$-sequence is only stratified for powers of two
+        let mut samples_per_pixel: i64 = samples_per_pixel;
+        if !is_power_of_2(samples_per_pixel) {
+            samples_per_pixel = round_up_pow2_64(samples_per_pixel);
+            warn!(
+                "Non power-of-two sample count rounded up to {:?} for ZeroTwoSequenceSampler.",
+                samples_per_pixel
+            );
+        }
         let mut lds: ZeroTwoSequenceSampler = ZeroTwoSequenceSampler {
             samples_per_pixel,
+            seed: 0_u64,
             n_sampled_dimensions,
             samples_1d: Vec::new(),
             samples_2d: Vec::new(),
@@ -90,6 +105,7 @@ impl ZeroTwoSequenceSampler {
     pub fn clone_with_seed(&self, seed: u64) -> Box<Sampler> {
         let mut zero_two_sampler = ZeroTwoSequenceSampler {
             samples_per_pixel: self.samples_per_pixel,
+            seed: self.seed,
             n_sampled_dimensions: self.n_sampled_dimensions,
             samples_1d: self.samples_1d.clone(),
             samples_2d: self.samples_2d.clone(),
@@ -113,10 +129,10 @@ impl ZeroTwoSequenceSampler {
         let nsamp: i32 = params.find_one_int("pixelsamples", 16);
         let sd: i32 = params.find_one_int("dimensions", 4);
         // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-        Box::new(Sampler::ZeroTwoSequence(ZeroTwoSequenceSampler::new(
-            nsamp as i64,
-            sd as i64,
-        )))
+        let mut sampler: ZeroTwoSequenceSampler =
+            ZeroTwoSequenceSampler::new(nsamp as i64, sd as i64);
+        sampler.seed = sampler_seed(params);
+        Box::new(Sampler::ZeroTwoSequence(sampler))
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
@@ -279,7 +295,7 @@ impl ZeroTwoSequenceSampler {
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
-        self.rng.set_sequence(seed);
+        self.rng.set_sequence(seeded_pixel_seed(seed, self.seed));
     }
     pub fn get_current_pixel(&self) -> Point2i {
         self.current_pixel
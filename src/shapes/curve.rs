@@ -1,6 +1,10 @@
 // std
+use std::mem;
 use std::sync::Arc;
+// others
+use log::{error, warn};
 // pbrt
+use crate::core::error::{Error, Result};
 use crate::core::geometry::{
     bnd3_expand, bnd3_union_bnd3, nrm_abs_dot_vec3, nrm_cross_vec3, nrm_dot_nrm, pnt3_distance,
     pnt3_distance_squared, pnt3_lerp, vec2_dot, vec3_coordinate_system, vec3_cross_vec3,
@@ -10,12 +14,20 @@ use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteractio
 use crate::core::material::Material;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
-use crate::core::pbrt::{clamp_t, float_to_bits, lerp};
+use crate::core::pbrt::{clamp_t, lerp};
 use crate::core::shape::Shape;
+use crate::core::stats::{StatCounter, StatIntDistribution, StatMemoryCounter, StatPercent};
 use crate::core::transform::Transform;
 
 // see curve.h
 
+static CURVE_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Curves");
+static CURVE_HITS: StatPercent = StatPercent::new("Intersections/Ray-curve intersection tests");
+static REFINEMENT_LEVEL: StatIntDistribution =
+    StatIntDistribution::new("Intersections/Curve refinement level");
+static N_CURVES: StatCounter = StatCounter::new("Scene/Curves");
+static N_SPLIT_CURVES: StatCounter = StatCounter::new("Scene/Split curves");
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CurveType {
     Flat,
@@ -41,6 +53,7 @@ impl CurveCommon {
         curve_type: CurveType,
         norm: Option<[Normal3f; 2]>,
     ) -> Self {
+        N_CURVES.inc();
         if let Some(norm) = norm {
             let n0: Normal3f = norm[0].normalize();
             let n1: Normal3f = norm[1].normalize();
@@ -131,9 +144,9 @@ impl Curve {
                 u_max,
             )));
             segments.push(curve.clone());
-            // TODO: ++nSplitCurves;
+            N_SPLIT_CURVES.inc();
         }
-        // TODO: curveBytes += sizeof(CurveCommon) + n_segments * sizeof(Curve);
+        CURVE_BYTES.add(mem::size_of::<CurveCommon>() + n_segments * mem::size_of::<Curve>());
         segments
     }
     pub fn recursive_intersect(
@@ -320,7 +333,7 @@ impl Curve {
                 }
                 dpdv = ray_to_object.transform_vector(&dpdv_plane);
             }
-            let mut si: SurfaceInteraction = SurfaceInteraction::new(
+            *isect = SurfaceInteraction::new(
                 &ray.position(pc.z),
                 &p_error,
                 Point2f { x: u, y: v },
@@ -332,12 +345,12 @@ impl Curve {
                 ray.time,
                 None,
             );
-            self.object_to_world.transform_surface_interaction(&mut si);
+            self.object_to_world.transform_surface_interaction(isect);
             // if let Some(ref shape) = si.shape {
             //     isect.shape = Some(shape.clone());
             // }
             // }
-            // TODO: ++n_hits;
+            CURVE_HITS.inc_num();
             return true;
         }
     }
@@ -365,7 +378,7 @@ impl Curve {
     }
     pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
         // TODO: ProfilePhase p(isect ? Prof::CurveIntersect : Prof::CurveIntersectP);
-        // TODO: ++nTests;
+        CURVE_HITS.inc_denom();
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -462,7 +475,7 @@ impl Curve {
         let r0: i32 =
             log2(1.414_213_562_37 as Float * 6.0 as Float * l0 / (8.0 as Float * eps)) / 2_i32;
         let max_depth: i32 = clamp_t(r0, 0_i32, 10_i32);
-        // TODO: ReportValue(refinementLevel, maxDepth);
+        REFINEMENT_LEVEL.report_value(max_depth as u64);
         self.recursive_intersect(
             &ray,
             &[cp[0], cp[1], cp[2], cp[3]],
@@ -505,7 +518,7 @@ impl Curve {
         approx_length * avg_width
     }
     pub fn sample(&self, _u: Point2f, _pdf: &mut Float) -> InteractionCommon {
-        println!("FATAL: Curve::sample not implemented.");
+        error!("Curve::sample not implemented.");
         InteractionCommon::default()
     }
     pub fn sample_with_ref_point(
@@ -556,16 +569,20 @@ pub fn create_curve_shape(
     w2o: &Transform,
     reverse_orientation: bool,
     params: &ParamSet,
-) -> Vec<Arc<Shape>> {
+) -> Result<Vec<Arc<Shape>>> {
     let width: Float = params.find_one_float("width", 1.0 as Float);
     let width0: Float = params.find_one_float("width0", width);
     let width1: Float = params.find_one_float("width1", width);
     let cp = params.find_point3f("P");
     if cp.len() != 4_usize {
-        panic!(
-            "Must provide 4 control points for \"curve\" primitive. ((Provided {:?}).",
-            cp.len()
-        );
+        return Err(Error::create(
+            "Shape",
+            "curve",
+            format!(
+                "must provide 4 control points \"P\" (provided {})",
+                cp.len()
+            ),
+        ));
     }
     let curve_type_string: String = params.find_one_string("type", String::from("flat"));
     let mut curve_type: CurveType = CurveType::Flat;
@@ -576,28 +593,36 @@ pub fn create_curve_shape(
     } else if curve_type_string == "cylinder" {
         curve_type = CurveType::Cylinder;
     } else {
-        println!(
-            "ERROR: Unknown curve type \"{:?}\". Using \"flat\".",
+        error!(
+            "Unknown curve type \"{:?}\". Using \"flat\".",
             curve_type_string
         );
     }
     let mut n: Vec<Normal3f> = params.find_normal3f("N");
     if !n.is_empty() {
         if curve_type_string != "ribbon" {
-            println!("WARNING: Curve normals are only used with \"ribbon\" type curves.");
+            warn!("Curve normals are only used with \"ribbon\" type curves.");
             n = Vec::new();
         } else if n.len() != 2_usize {
-            panic!(
-                "Must provide two normals with \"N\" parameter for ribbon curves. (Provided {:?}).",
-                n.len()
-            );
+            return Err(Error::create(
+                "Shape",
+                "curve",
+                format!(
+                    "must provide two normals \"N\" for ribbon curves (provided {})",
+                    n.len()
+                ),
+            ));
         }
     }
     let sd: i32 = params.find_one_int("splitdepth", 3_i32);
     if curve_type == CurveType::Ribbon && n.is_empty() {
-        panic!("Must provide normals \"N\" at curve endpoints with ribbon curves.");
+        return Err(Error::create(
+            "Shape",
+            "curve",
+            String::from("must provide normals \"N\" at curve endpoints with ribbon curves"),
+        ));
     }
-    if n.is_empty() {
+    Ok(if n.is_empty() {
         Curve::create(
             *o2w,
             *w2o,
@@ -621,7 +646,7 @@ pub fn create_curve_shape(
             Some([n[0], n[1]]),
             sd,
         )
-    }
+    })
 }
 
 // Curve Utility Functions
@@ -662,11 +687,14 @@ fn eval_bezier(cp: &[Point3f; 4], u: Float, deriv: Option<&mut Vector3f>) -> Poi
     pnt3_lerp(u, &cp2[0], &cp2[1])
 }
 
+// the cast is needed if Float is f64
+#[allow(clippy::unnecessary_cast)]
 fn log2(v: Float) -> i32 {
     if v < 1.0 as Float {
         return 0_i32;
     }
-    let bits: i32 = float_to_bits(v) as i32;
+    // the exponent of the single precision value is enough
+    let bits: i32 = (v as f32).to_bits() as i32;
 
     // https://graphics.stanford.edu/~seander/bithacks.html#IntegerLog
 
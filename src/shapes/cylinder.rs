@@ -1,5 +1,4 @@
 // std
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::efloat::quadratic_efloat;
@@ -10,6 +9,7 @@ use crate::core::geometry::{
 use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::material::Material;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, gamma, lerp, radians};
 use crate::core::transform::Transform;
@@ -104,16 +104,16 @@ impl Cylinder {
         // compute quadratic cylinder coefficients
 
         // initialize _EFloat_ ray coordinate values
-        let ox = EFloat::new(ray.o.x as f32, o_err.x as f32);
-        let oy = EFloat::new(ray.o.y as f32, o_err.y as f32);
-        // let oz = EFloat::new(ray.o.z as f32, o_err.z as f32);
-        let dx = EFloat::new(ray.d.x as f32, d_err.x as f32);
-        let dy = EFloat::new(ray.d.y as f32, d_err.y as f32);
-        // let dz = EFloat::new(ray.d.z as f32, d_err.z as f32);
+        let ox = EFloat::new(ray.o.x as Float, o_err.x as Float);
+        let oy = EFloat::new(ray.o.y as Float, o_err.y as Float);
+        // let oz = EFloat::new(ray.o.z as Float, o_err.z as Float);
+        let dx = EFloat::new(ray.d.x as Float, d_err.x as Float);
+        let dy = EFloat::new(ray.d.y as Float, d_err.y as Float);
+        // let dz = EFloat::new(ray.d.z as Float, d_err.z as Float);
         let a: EFloat = dx * dx + dy * dy;
-        let b: EFloat = (dx * ox + dy * oy) * 2.0f32;
+        let b: EFloat = (dx * ox + dy * oy) * 2.0 as Float;
         let c: EFloat = ox * ox + oy * oy
-            - EFloat::new(self.radius as f32, 0.0) * EFloat::new(self.radius as f32, 0.0);
+            - EFloat::new(self.radius as Float, 0.0) * EFloat::new(self.radius as Float, 0.0);
 
         // Solve quadratic equation for _t_ values
         let mut t0: EFloat = EFloat::default();
@@ -122,13 +122,13 @@ impl Cylinder {
             return false;
         }
         // check quadric shape _t0_ and _t1_ for nearest intersection
-        if t0.upper_bound() > ray.t_max as f32 || t1.lower_bound() <= 0.0f32 {
+        if t0.upper_bound() > ray.t_max as Float || t1.lower_bound() <= 0.0 as Float {
             return false;
         }
         let mut t_shape_hit: EFloat = t0;
-        if t_shape_hit.lower_bound() <= 0.0f32 {
+        if t_shape_hit.lower_bound() <= 0.0 as Float {
             t_shape_hit = t1;
-            if t_shape_hit.upper_bound() > ray.t_max as f32 {
+            if t_shape_hit.upper_bound() > ray.t_max as Float {
                 return false;
             }
         }
@@ -255,16 +255,16 @@ impl Cylinder {
         // compute quadratic cylinder coefficients
 
         // initialize _EFloat_ ray coordinate values
-        let ox = EFloat::new(ray.o.x as f32, o_err.x as f32);
-        let oy = EFloat::new(ray.o.y as f32, o_err.y as f32);
-        // let oz = EFloat::new(ray.o.z as f32, o_err.z as f32);
-        let dx = EFloat::new(ray.d.x as f32, d_err.x as f32);
-        let dy = EFloat::new(ray.d.y as f32, d_err.y as f32);
-        // let dz = EFloat::new(ray.d.z as f32, d_err.z as f32);
+        let ox = EFloat::new(ray.o.x as Float, o_err.x as Float);
+        let oy = EFloat::new(ray.o.y as Float, o_err.y as Float);
+        // let oz = EFloat::new(ray.o.z as Float, o_err.z as Float);
+        let dx = EFloat::new(ray.d.x as Float, d_err.x as Float);
+        let dy = EFloat::new(ray.d.y as Float, d_err.y as Float);
+        // let dz = EFloat::new(ray.d.z as Float, d_err.z as Float);
         let a: EFloat = dx * dx + dy * dy;
-        let b: EFloat = (dx * ox + dy * oy) * 2.0f32;
+        let b: EFloat = (dx * ox + dy * oy) * 2.0 as Float;
         let c: EFloat = ox * ox + oy * oy
-            - EFloat::new(self.radius as f32, 0.0) * EFloat::new(self.radius as f32, 0.0);
+            - EFloat::new(self.radius as Float, 0.0) * EFloat::new(self.radius as Float, 0.0);
 
         // Solve quadratic equation for _t_ values
         let mut t0: EFloat = EFloat::default();
@@ -273,13 +273,13 @@ impl Cylinder {
             return false;
         }
         // check quadric shape _t0_ and _t1_ for nearest intersection
-        if t0.upper_bound() > ray.t_max as f32 || t1.lower_bound() <= 0.0f32 {
+        if t0.upper_bound() > ray.t_max as Float || t1.lower_bound() <= 0.0 as Float {
             return false;
         }
         let mut t_shape_hit: EFloat = t0;
-        if t_shape_hit.lower_bound() <= 0.0f32 {
+        if t_shape_hit.lower_bound() <= 0.0 as Float {
             t_shape_hit = t1;
-            if t_shape_hit.upper_bound() > ray.t_max as f32 {
+            if t_shape_hit.upper_bound() > ray.t_max as Float {
                 return false;
             }
         }
@@ -1,11 +1,11 @@
 // std
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{nrm_abs_dot_vec3, pnt3_distance_squared};
 use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::material::Material;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, radians};
 use crate::core::sampling::concentric_sample_disk;
@@ -117,7 +117,7 @@ impl Disk {
         // test disk $\phi$ value against $\phimax$
         let mut phi: Float = p_hit.y.atan2(p_hit.x);
         if phi < 0.0 {
-            phi += 2.0_f32 * PI;
+            phi += 2.0 as Float * PI;
         }
         if phi > self.phi_max {
             return false;
@@ -188,7 +188,7 @@ impl Disk {
         // test disk $\phi$ value against $\phimax$
         let mut phi: Float = p_hit.y.atan2(p_hit.x);
         if phi < 0.0 {
-            phi += 2.0_f32 * PI;
+            phi += 2.0 as Float * PI;
         }
         if phi > self.phi_max {
             return false;
@@ -2,7 +2,6 @@
 use std;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::f32::consts::PI;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 // others
@@ -10,6 +9,7 @@ use smallvec::SmallVec;
 // pbrt
 use crate::core::geometry::vec3_cross_vec3;
 use crate::core::geometry::{Normal3f, Point3f, Vector3f};
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::transform::Transform;
 use crate::shapes::triangle::TriangleMesh;
@@ -1,18 +1,19 @@
 // std
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::fs::File;
 use std::hash::BuildHasher;
-use std::io::BufReader;
 use std::path::PathBuf;
 use std::string::String;
 use std::sync::Arc;
 use std::vec::Vec;
 // others
+use log::{debug, warn};
 use ply_rs::parser;
 use ply_rs::ply;
 // pbrt
+use crate::core::error::{Error, Result};
 use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use crate::core::memory::MappedFile;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::shape::Shape;
@@ -26,9 +27,30 @@ pub fn create_ply_mesh<S: BuildHasher>(
     w2o: &Transform,
     reverse_orientation: bool,
     params: &ParamSet,
-    float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>, S>>,
+    float_textures: Arc<HashMap<String, Arc<Texture<Float>>, S>>,
     search_directory: Option<&PathBuf>,
-) -> Vec<Arc<Shape>> {
+) -> Result<Vec<Arc<Shape>>> {
+    let mesh: Arc<TriangleMesh> = read_ply_mesh(
+        o2w,
+        w2o,
+        reverse_orientation,
+        params,
+        float_textures,
+        search_directory,
+    )?;
+    Ok(ply_mesh_shapes(&mesh))
+}
+
+/// Reads the mesh of a **plymesh** shape, without splitting it into
+/// triangles (see **ply_mesh_shapes()**).
+pub fn read_ply_mesh<S: BuildHasher>(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+    float_textures: Arc<HashMap<String, Arc<Texture<Float>>, S>>,
+    search_directory: Option<&PathBuf>,
+) -> Result<Arc<TriangleMesh>> {
     let mut filename: String = params.find_one_string("filename", String::new());
     if let Some(ref search_directory) = search_directory {
         let mut path_buf: PathBuf = PathBuf::from("/");
@@ -36,26 +58,20 @@ pub fn create_ply_mesh<S: BuildHasher>(
         path_buf.push(filename);
         filename = String::from(path_buf.to_str().unwrap());
     }
-    let result = File::open(&filename);
-    if result.is_err() {
-        panic!("Couldn't open PLY file {:?}", filename);
-    }
-    let f = result.unwrap();
-    let mut buf_reader = BufReader::new(f);
+    // parse the mapped file, without copying it through a read buffer
+    let mapped_file: MappedFile =
+        MappedFile::open(&filename).map_err(|err| Error::io(&filename, err))?;
+    let mut buf_reader: &[u8] = &mapped_file;
     let p = parser::Parser::<ply::DefaultElement>::new();
     // header
-    let result = p.read_header(&mut buf_reader);
-    if result.is_err() {
-        panic!("Unable to read the header of PLY file  {:?}", filename);
-    }
-    let header = result.unwrap();
+    let header = p
+        .read_header(&mut buf_reader)
+        .map_err(|err| Error::io(&filename, err))?;
     // println!("header = {:?}", header);
     // payload
-    let result = p.read_payload(&mut buf_reader, &header);
-    if result.is_err() {
-        panic!("Unable to read the payload of PLY file  {:?}", filename);
-    }
-    let payload = result.unwrap();
+    let payload = p
+        .read_payload(&mut buf_reader, &header)
+        .map_err(|err| Error::io(&filename, err))?;
     // println!("payload = {:?}", payload);
     let mut p: Vec<Point3f> = Vec::new();
     let mut n: Vec<Normal3f> = Vec::new();
@@ -74,51 +90,51 @@ pub fn create_ply_mesh<S: BuildHasher>(
                         match name2.as_ref() {
                             "x" => {
                                 if let ply::Property::Float(x) = list2 {
-                                    pnt.x = x;
+                                    pnt.x = x as Float;
                                 }
                             }
                             "y" => {
                                 if let ply::Property::Float(y) = list2 {
-                                    pnt.y = y;
+                                    pnt.y = y as Float;
                                 }
                             }
                             "z" => {
                                 if let ply::Property::Float(z) = list2 {
-                                    pnt.z = z;
+                                    pnt.z = z as Float;
                                 }
                             }
                             "nx" => {
                                 has_normals = true;
                                 if let ply::Property::Float(x) = list2 {
-                                    nrm.x = x;
+                                    nrm.x = x as Float;
                                 }
                             }
                             "ny" => {
                                 has_normals = true;
                                 if let ply::Property::Float(y) = list2 {
-                                    nrm.y = y;
+                                    nrm.y = y as Float;
                                 }
                             }
                             "nz" => {
                                 has_normals = true;
                                 if let ply::Property::Float(z) = list2 {
-                                    nrm.z = z;
+                                    nrm.z = z as Float;
                                 }
                             }
                             "u" | "s" => {
                                 has_uvs = true;
                                 if let ply::Property::Float(x) = list2 {
-                                    pt2.x = x;
+                                    pt2.x = x as Float;
                                 }
                             }
                             "v" | "t" => {
                                 has_uvs = true;
                                 if let ply::Property::Float(y) = list2 {
-                                    pt2.y = y;
+                                    pt2.y = y as Float;
                                 }
                             }
                             _ => {
-                                println!("name2 = {:?}", name2);
+                                debug!("name2 = {:?}", name2);
                                 unreachable!();
                             }
                         }
@@ -154,8 +170,15 @@ pub fn create_ply_mesh<S: BuildHasher>(
                                             vertex_indices.push(v1);
                                             vertex_indices.push(v3);
                                         } else {
-                                            panic!("plymesh: Ignoring face with {} vertices (only triangles and quads are supported!)",
-                                                   vertex_indices.len());
+                                            return Err(Error::create(
+                                                "Shape",
+                                                "plymesh",
+                                                format!(
+                                                    "face with {} vertices in {:?} (only triangles and quads are supported)",
+                                                    vertex_indices.len(),
+                                                    filename
+                                                ),
+                                            ));
                                         }
                                     }
                                     // now we can add the indices to the triangle mesh vertex indices
@@ -178,8 +201,15 @@ pub fn create_ply_mesh<S: BuildHasher>(
                                             vertex_indices.push(v1);
                                             vertex_indices.push(v3);
                                         } else {
-                                            panic!("plymesh: Ignoring face with {} vertices (only triangles and quads are supported!)",
-                                                   vertex_indices.len());
+                                            return Err(Error::create(
+                                                "Shape",
+                                                "plymesh",
+                                                format!(
+                                                    "face with {} vertices in {:?} (only triangles and quads are supported)",
+                                                    vertex_indices.len(),
+                                                    filename
+                                                ),
+                                            ));
                                         }
                                     }
                                     // now we can add the indices to the triangle mesh vertex indices
@@ -191,19 +221,19 @@ pub fn create_ply_mesh<S: BuildHasher>(
                             "nx" => {
                                 has_normals = true;
                                 if let ply::Property::Float(x) = list2 {
-                                    nrm.x = x;
+                                    nrm.x = x as Float;
                                 }
                             }
                             "ny" => {
                                 has_normals = true;
                                 if let ply::Property::Float(y) = list2 {
-                                    nrm.y = y;
+                                    nrm.y = y as Float;
                                 }
                             }
                             "nz" => {
                                 has_normals = true;
                                 if let ply::Property::Float(z) = list2 {
-                                    nrm.z = z;
+                                    nrm.z = z as Float;
                                 }
                             }
                             _ => unreachable!(),
@@ -235,13 +265,13 @@ pub fn create_ply_mesh<S: BuildHasher>(
     }
     let s_ws: Vec<Vector3f> = Vec::new();
     // look up an alpha texture, if applicable
-    let mut alpha_tex: Option<Arc<dyn Texture<Float> + Send + Sync>> = None;
+    let mut alpha_tex: Option<Arc<Texture<Float>>> = None;
     let alpha_tex_name: String = params.find_texture("alpha");
     if alpha_tex_name != "" {
         alpha_tex = match float_textures.get(alpha_tex_name.as_str()) {
             Some(float_texture) => Some(float_texture.clone()),
             None => {
-                println!(
+                warn!(
                     "Couldn't find float texture {:?} for \"alpha\" parameter",
                     alpha_tex_name.as_str()
                 );
@@ -249,15 +279,17 @@ pub fn create_ply_mesh<S: BuildHasher>(
             }
         }
     } else if params.find_one_float("alpha", 1.0 as Float) == 0.0 as Float {
-        alpha_tex = Some(Arc::new(ConstantTexture::new(0.0 as Float)));
+        alpha_tex = Some(Arc::new(Texture::Constant(ConstantTexture::new(
+            0.0 as Float,
+        ))));
     }
-    let mut shadow_alpha_tex: Option<Arc<dyn Texture<Float> + Send + Sync>> = None;
+    let mut shadow_alpha_tex: Option<Arc<Texture<Float>>> = None;
     let shadow_alpha_tex_name: String = params.find_texture("shadowalpha");
     if shadow_alpha_tex_name != "" {
         shadow_alpha_tex = match float_textures.get(shadow_alpha_tex_name.as_str()) {
             Some(float_texture) => Some(float_texture.clone()),
             None => {
-                println!(
+                warn!(
                     "Couldn't find float texture {:?} for \"shadowalpha\" parameter",
                     shadow_alpha_tex_name.as_str()
                 );
@@ -265,7 +297,9 @@ pub fn create_ply_mesh<S: BuildHasher>(
             }
         }
     } else if params.find_one_float("shadowalpha", 1.0 as Float) == 0.0 as Float {
-        shadow_alpha_tex = Some(Arc::new(ConstantTexture::new(0.0 as Float)));
+        shadow_alpha_tex = Some(Arc::new(Texture::Constant(ConstantTexture::new(
+            0.0 as Float,
+        ))));
     }
     let mesh = Arc::new(TriangleMesh::new(
         *o2w,
@@ -281,6 +315,11 @@ pub fn create_ply_mesh<S: BuildHasher>(
         alpha_tex,
         shadow_alpha_tex,
     ));
+    Ok(mesh)
+}
+
+/// One triangle shape per triangle of *mesh*.
+pub fn ply_mesh_shapes(mesh: &Arc<TriangleMesh>) -> Vec<Arc<Shape>> {
     let mut shapes: Vec<Arc<Shape>> = Vec::new();
     for id in 0..mesh.n_triangles {
         let triangle = Arc::new(Shape::Trngl(Triangle::new(
@@ -1,5 +1,4 @@
 // std
-use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use crate::core::efloat::quadratic_efloat;
@@ -11,6 +10,7 @@ use crate::core::geometry::{
 use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::material::Material;
+use crate::core::pbrt::consts::PI;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, gamma, radians};
 use crate::core::sampling::{uniform_cone_pdf, uniform_sample_sphere};
@@ -111,16 +111,16 @@ impl Sphere {
         // compute quadratic sphere coefficients
 
         // initialize _EFloat_ ray coordinate values
-        let ox = EFloat::new(ray.o.x as f32, o_err.x as f32);
-        let oy = EFloat::new(ray.o.y as f32, o_err.y as f32);
-        let oz = EFloat::new(ray.o.z as f32, o_err.z as f32);
-        let dx = EFloat::new(ray.d.x as f32, d_err.x as f32);
-        let dy = EFloat::new(ray.d.y as f32, d_err.y as f32);
-        let dz = EFloat::new(ray.d.z as f32, d_err.z as f32);
+        let ox = EFloat::new(ray.o.x as Float, o_err.x as Float);
+        let oy = EFloat::new(ray.o.y as Float, o_err.y as Float);
+        let oz = EFloat::new(ray.o.z as Float, o_err.z as Float);
+        let dx = EFloat::new(ray.d.x as Float, d_err.x as Float);
+        let dy = EFloat::new(ray.d.y as Float, d_err.y as Float);
+        let dz = EFloat::new(ray.d.z as Float, d_err.z as Float);
         let a: EFloat = dx * dx + dy * dy + dz * dz;
-        let b: EFloat = (dx * ox + dy * oy + dz * oz) * 2.0f32;
+        let b: EFloat = (dx * ox + dy * oy + dz * oz) * 2.0 as Float;
         let c: EFloat = ox * ox + oy * oy + oz * oz
-            - EFloat::new(self.radius as f32, 0.0) * EFloat::new(self.radius as f32, 0.0);
+            - EFloat::new(self.radius as Float, 0.0) * EFloat::new(self.radius as Float, 0.0);
 
         // solve quadratic equation for _t_ values
         let mut t0: EFloat = EFloat::default();
@@ -129,13 +129,13 @@ impl Sphere {
             return false;
         }
         // check quadric shape _t0_ and _t1_ for nearest intersection
-        if t0.upper_bound() > ray.t_max as f32 || t1.lower_bound() <= 0.0f32 {
+        if t0.upper_bound() > ray.t_max as Float || t1.lower_bound() <= 0.0 as Float {
             return false;
         }
         let mut t_shape_hit: EFloat = t0;
-        if t_shape_hit.lower_bound() <= 0.0f32 {
+        if t_shape_hit.lower_bound() <= 0.0 as Float {
             t_shape_hit = t1;
-            if t_shape_hit.upper_bound() > ray.t_max as f32 {
+            if t_shape_hit.upper_bound() > ray.t_max as Float {
                 return false;
             }
         }
@@ -144,11 +144,11 @@ impl Sphere {
         // refine sphere intersection point
         p_hit *= self.radius / pnt3_distance(&p_hit, &Point3f::default());
         if p_hit.x == 0.0 && p_hit.y == 0.0 {
-            p_hit.x = 1e-5_f32 * self.radius;
+            p_hit.x = 1e-5 as Float * self.radius;
         }
         let mut phi: Float = p_hit.y.atan2(p_hit.x);
         if phi < 0.0 {
-            phi += 2.0_f32 * PI;
+            phi += 2.0 as Float * PI;
         }
         // test sphere intersection against clipping parameters
         if (self.z_min > -self.radius && p_hit.z < self.z_min)
@@ -158,7 +158,7 @@ impl Sphere {
             if t_shape_hit == t1 {
                 return false;
             }
-            if t1.upper_bound() > ray.t_max as f32 {
+            if t1.upper_bound() > ray.t_max as Float {
                 return false;
             }
             t_shape_hit = t1;
@@ -168,11 +168,11 @@ impl Sphere {
             // refine sphere intersection point
             p_hit *= self.radius / pnt3_distance(&p_hit, &Point3f::default());
             if p_hit.x == 0.0 && p_hit.y == 0.0 {
-                p_hit.x = 1e-5_f32 * self.radius;
+                p_hit.x = 1e-5 as Float * self.radius;
             }
             phi = p_hit.y.atan2(p_hit.x);
             if phi < 0.0 {
-                phi += 2.0_f32 * PI;
+                phi += 2.0 as Float * PI;
             }
             if (self.z_min > -self.radius && p_hit.z < self.z_min)
                 || (self.z_max < self.radius && p_hit.z > self.z_max)
@@ -277,16 +277,16 @@ impl Sphere {
         // compute quadratic sphere coefficients
 
         // initialize _EFloat_ ray coordinate values
-        let ox = EFloat::new(ray.o.x as f32, o_err.x as f32);
-        let oy = EFloat::new(ray.o.y as f32, o_err.y as f32);
-        let oz = EFloat::new(ray.o.z as f32, o_err.z as f32);
-        let dx = EFloat::new(ray.d.x as f32, d_err.x as f32);
-        let dy = EFloat::new(ray.d.y as f32, d_err.y as f32);
-        let dz = EFloat::new(ray.d.z as f32, d_err.z as f32);
+        let ox = EFloat::new(ray.o.x as Float, o_err.x as Float);
+        let oy = EFloat::new(ray.o.y as Float, o_err.y as Float);
+        let oz = EFloat::new(ray.o.z as Float, o_err.z as Float);
+        let dx = EFloat::new(ray.d.x as Float, d_err.x as Float);
+        let dy = EFloat::new(ray.d.y as Float, d_err.y as Float);
+        let dz = EFloat::new(ray.d.z as Float, d_err.z as Float);
         let a: EFloat = dx * dx + dy * dy + dz * dz;
-        let b: EFloat = (dx * ox + dy * oy + dz * oz) * 2.0f32;
+        let b: EFloat = (dx * ox + dy * oy + dz * oz) * 2.0 as Float;
         let c: EFloat = ox * ox + oy * oy + oz * oz
-            - EFloat::new(self.radius as f32, 0.0) * EFloat::new(self.radius as f32, 0.0);
+            - EFloat::new(self.radius as Float, 0.0) * EFloat::new(self.radius as Float, 0.0);
 
         // solve quadratic equation for _t_ values
         let mut t0: EFloat = EFloat::default();
@@ -295,13 +295,13 @@ impl Sphere {
             return false;
         }
         // check quadric shape _t0_ and _t1_ for nearest intersection
-        if t0.upper_bound() > ray.t_max as f32 || t1.lower_bound() <= 0.0f32 {
+        if t0.upper_bound() > ray.t_max as Float || t1.lower_bound() <= 0.0 as Float {
             return false;
         }
         let mut t_shape_hit: EFloat = t0;
-        if t_shape_hit.lower_bound() <= 0.0f32 {
+        if t_shape_hit.lower_bound() <= 0.0 as Float {
             t_shape_hit = t1;
-            if t_shape_hit.upper_bound() > ray.t_max as f32 {
+            if t_shape_hit.upper_bound() > ray.t_max as Float {
                 return false;
             }
         }
@@ -310,11 +310,11 @@ impl Sphere {
         // refine sphere intersection point
         p_hit *= self.radius / pnt3_distance(&p_hit, &Point3f::default());
         if p_hit.x == 0.0 && p_hit.y == 0.0 {
-            p_hit.x = 1e-5_f32 * self.radius;
+            p_hit.x = 1e-5 as Float * self.radius;
         }
         let mut phi: Float = p_hit.y.atan2(p_hit.x);
         if phi < 0.0 {
-            phi += 2.0_f32 * PI;
+            phi += 2.0 as Float * PI;
         }
         // test sphere intersection against clipping parameters
         if (self.z_min > -self.radius && p_hit.z < self.z_min)
@@ -324,7 +324,7 @@ impl Sphere {
             if t_shape_hit == t1 {
                 return false;
             }
-            if t1.upper_bound() > ray.t_max as f32 {
+            if t1.upper_bound() > ray.t_max as Float {
                 return false;
             }
             t_shape_hit = t1;
@@ -334,11 +334,11 @@ impl Sphere {
             // refine sphere intersection point
             p_hit *= self.radius / pnt3_distance(&p_hit, &Point3f::default());
             if p_hit.x == 0.0 && p_hit.y == 0.0 {
-                p_hit.x = 1e-5_f32 * self.radius;
+                p_hit.x = 1e-5 as Float * self.radius;
             }
             phi = p_hit.y.atan2(p_hit.x);
             if phi < 0.0 {
-                phi += 2.0_f32 * PI;
+                phi += 2.0 as Float * PI;
             }
             if (self.z_min > -self.radius && p_hit.z < self.z_min)
                 || (self.z_max < self.radius && p_hit.z > self.z_max)
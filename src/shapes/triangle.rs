@@ -10,15 +10,26 @@ use crate::core::geometry::{
 };
 use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, Shading, SurfaceInteraction};
+use crate::core::lowmemory::{f16_to_f32, f32_to_f16, low_memory};
 use crate::core::material::Material;
 use crate::core::pbrt::gamma;
 use crate::core::pbrt::Float;
 use crate::core::sampling::uniform_sample_triangle;
+use crate::core::stats::{StatMemoryCounter, StatPercent, StatRatio};
 use crate::core::texture::Texture;
 use crate::core::transform::Transform;
 
 // see triangle.h
 
+static TRI_MESH_BYTES: StatMemoryCounter = StatMemoryCounter::new("Memory/Triangle meshes");
+static TRIS_PER_MESH: StatRatio = StatRatio::new("Scene/Triangles per triangle mesh");
+static TRI_HITS: StatPercent = StatPercent::new("Intersections/Ray-triangle intersection tests");
+
+#[allow(clippy::unnecessary_cast)]
+fn to_f16(value: Float) -> u16 {
+    f32_to_f16(value as f32)
+}
+
 #[derive(Clone)]
 pub struct TriangleMesh {
     /// the total number of triangles in the mesh
@@ -35,8 +46,12 @@ pub struct TriangleMesh {
     pub s: Vec<Vector3f>,
     /// an optional vector of paramtric (u, v) values (texture coordinates)
     pub uv: Vec<Point2f>,
-    pub alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-    pub shadow_alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+    /// *n* as 16-bit floats (in low-memory mode, see **lowmemory**)
+    pub n_half: Vec<[u16; 3]>,
+    /// *uv* as 16-bit floats (in low-memory mode, see **lowmemory**)
+    pub uv_half: Vec<[u16; 2]>,
+    pub alpha_mask: Option<Arc<Texture<Float>>>,
+    pub shadow_alpha_mask: Option<Arc<Texture<Float>>>,
     // inherited from class Shape (see shape.h)
     pub object_to_world: Transform, // TODO: not pub?
     pub world_to_object: Transform, // TODO: not pub?
@@ -56,9 +71,31 @@ impl TriangleMesh {
         s: Vec<Vector3f>,
         n: Vec<Normal3f>,
         uv: Vec<Point2f>,
-        alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
-        shadow_alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+        alpha_mask: Option<Arc<Texture<Float>>>,
+        shadow_alpha_mask: Option<Arc<Texture<Float>>>,
     ) -> Self {
+        TRIS_PER_MESH.add_num(n_triangles as u64);
+        TRIS_PER_MESH.add_denom(1);
+        let (n, uv, n_half, uv_half) = if low_memory().half_attributes {
+            let n_half: Vec<[u16; 3]> = n
+                .iter()
+                .map(|n| [to_f16(n.x), to_f16(n.y), to_f16(n.z)])
+                .collect();
+            let uv_half: Vec<[u16; 2]> = uv.iter().map(|uv| [to_f16(uv.x), to_f16(uv.y)]).collect();
+            (Vec::new(), Vec::new(), n_half, uv_half)
+        } else {
+            (n, uv, Vec::new(), Vec::new())
+        };
+        TRI_MESH_BYTES.add(
+            mem::size_of::<TriangleMesh>()
+                + vertex_indices.len() * mem::size_of::<u32>()
+                + p.len() * mem::size_of::<Point3f>()
+                + n.len() * mem::size_of::<Normal3f>()
+                + s.len() * mem::size_of::<Vector3f>()
+                + uv.len() * mem::size_of::<Point2f>()
+                + n_half.len() * mem::size_of::<[u16; 3]>()
+                + uv_half.len() * mem::size_of::<[u16; 2]>(),
+        );
         TriangleMesh {
             // Shape
             object_to_world,
@@ -73,87 +110,78 @@ impl TriangleMesh {
             n,
             s,
             uv,
+            n_half,
+            uv_half,
             alpha_mask,
             shadow_alpha_mask,
         }
     }
-}
-
-#[derive(Clone)]
-pub struct Triangle {
-    mesh: Arc<TriangleMesh>,
-    pub id: u32,
-    // inherited from class Shape (see shape.h)
-    pub object_to_world: Transform,
-    pub world_to_object: Transform,
-    pub reverse_orientation: bool,
-    pub transform_swaps_handedness: bool,
-    pub material: Option<Arc<Material>>,
-}
-
-impl Triangle {
-    pub fn new(
-        object_to_world: Transform,
-        world_to_object: Transform,
-        reverse_orientation: bool,
-        mesh: Arc<TriangleMesh>,
-        tri_number: u32,
-    ) -> Self {
-        Triangle {
-            mesh,
-            id: tri_number,
-            object_to_world,
-            world_to_object,
-            reverse_orientation,
-            transform_swaps_handedness: false,
-            material: None,
+    pub fn has_normals(&self) -> bool {
+        !self.n.is_empty() || !self.n_half.is_empty()
+    }
+    /// The normal of a vertex (from *n* or *n_half*).
+    pub fn normal(&self, vertex: usize) -> Normal3f {
+        if let Some(n) = self.n_half.get(vertex) {
+            Normal3f {
+                x: f16_to_f32(n[0]) as Float,
+                y: f16_to_f32(n[1]) as Float,
+                z: f16_to_f32(n[2]) as Float,
+            }
+        } else {
+            self.n[vertex]
         }
     }
-    pub fn get_uvs(&self) -> [Point2f; 3] {
-        if self.mesh.uv.is_empty() {
+    /// The (u, v) coordinates of the vertices of a triangle (from
+    /// *uv* or *uv_half*), or the default ones if there are none.
+    pub fn triangle_uvs(&self, id: u32) -> [Point2f; 3] {
+        let vertex = |i: usize| self.vertex_indices[(id * 3) as usize + i] as usize;
+        if !self.uv_half.is_empty() {
+            let uv = |i: usize| Point2f {
+                x: f16_to_f32(self.uv_half[vertex(i)][0]) as Float,
+                y: f16_to_f32(self.uv_half[vertex(i)][1]) as Float,
+            };
+            [uv(0), uv(1), uv(2)]
+        } else if self.uv.is_empty() {
             [
                 Point2f { x: 0.0, y: 0.0 },
                 Point2f { x: 1.0, y: 0.0 },
                 Point2f { x: 1.0, y: 1.0 },
             ]
         } else {
-            [
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize] as usize],
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize],
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize],
-            ]
+            [self.uv[vertex(0)], self.uv[vertex(1)], self.uv[vertex(2)]]
         }
     }
-    // Shape
-    pub fn object_bound(&self) -> Bounds3f {
-        let p0: Point3f = self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-        let p1: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-        let p2: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
-        bnd3_union_pnt3(
-            &Bounds3f::new(
-                self.world_to_object.transform_point(&p0),
-                self.world_to_object.transform_point(&p1),
-            ),
-            &self.world_to_object.transform_point(&p2),
-        )
+    /// The (world space) vertices of a triangle.
+    pub fn triangle_vertices(&self, id: u32) -> [Point3f; 3] {
+        [
+            self.p[self.vertex_indices[(id * 3) as usize] as usize],
+            self.p[self.vertex_indices[(id * 3) as usize + 1] as usize],
+            self.p[self.vertex_indices[(id * 3) as usize + 2] as usize],
+        ]
     }
-    pub fn world_bound(&self) -> Bounds3f {
-        let p0: Point3f = self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-        let p1: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-        let p2: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+    pub fn triangle_bound(&self, id: u32) -> Bounds3f {
+        let [p0, p1, p2] = self.triangle_vertices(id);
         bnd3_union_pnt3(&Bounds3f::new(p0, p1), &p2)
     }
-    pub fn intersect(&self, ray: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+    pub fn triangle_area(&self, id: u32) -> Float {
+        let [p0, p1, p2] = self.triangle_vertices(id);
+        0.5 as Float * vec3_cross_vec3(&(p1 - p0), &(p2 - p0)).length()
+    }
+    /// See **Triangle::intersect()**.
+    pub fn intersect_triangle(
+        &self,
+        id: u32,
+        ray: &Ray,
+        t_hit: &mut Float,
+        isect: &mut SurfaceInteraction,
+    ) -> bool {
+        TRI_HITS.inc_denom();
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let p0: &Point3f = &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
+        let p0: &Point3f = &self.p[self.vertex_indices[(id * 3) as usize] as usize];
         let p1: &Point3f =
-            &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
+            &self.p[self.vertex_indices[(id * 3) as usize + 1] as usize];
         let p2: &Point3f =
-            &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            &self.p[self.vertex_indices[(id * 3) as usize + 2] as usize];
         // translate vertices based on ray origin
         let mut p0t: Point3f = *p0
             - Vector3f {
@@ -288,7 +316,7 @@ impl Triangle {
             return false;
         }
         // compute triangle partial derivatives
-        let uv: [Point2f; 3] = self.get_uvs();
+        let uv: [Point2f; 3] = self.triangle_uvs(id);
         // compute deltas for triangle partial derivatives
         let duv02: Vector2f = uv[0] - uv[2];
         let duv12: Vector2f = uv[1] - uv[2];
@@ -326,7 +354,7 @@ impl Triangle {
         let uv_hit: Point2f = uv[0] * b0 + uv[1] * b1 + uv[2] * b2;
         // test intersection against alpha texture, if present
         // TODO: testAlphaTexture
-        if let Some(alpha_mask) = &self.mesh.alpha_mask {
+        if let Some(alpha_mask) = &self.alpha_mask {
             let wo: Vector3f = -ray.d;
             let isect_local: SurfaceInteraction = SurfaceInteraction::new(
                 &p_hit,
@@ -358,15 +386,15 @@ impl Triangle {
             dndu,
             dndv,
         };
-        if !self.mesh.n.is_empty() || !self.mesh.s.is_empty() {
+        if self.has_normals() || !self.s.is_empty() {
             // initialize _Triangle_ shading geometry
 
             // compute shading normal _ns_ for triangle
             let mut ns: Normal3f;
-            if !self.mesh.n.is_empty() {
-                let n0 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-                let n1 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-                let n2 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            if self.has_normals() {
+                let n0 = self.normal(self.vertex_indices[(id * 3) as usize] as usize);
+                let n1 = self.normal(self.vertex_indices[(id * 3) as usize + 1] as usize);
+                let n2 = self.normal(self.vertex_indices[(id * 3) as usize + 2] as usize);
                 ns = n0 * b0 + n1 * b1 + n2 * b2;
                 if ns.length_squared() > 0.0 {
                     ns = ns.normalize();
@@ -378,10 +406,10 @@ impl Triangle {
             }
             // compute shading tangent _ss_ for triangle
             let mut ss: Vector3f;
-            if !self.mesh.s.is_empty() {
-                let s0 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-                let s1 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-                let s2 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            if !self.s.is_empty() {
+                let s0 = self.s[self.vertex_indices[(id * 3) as usize] as usize];
+                let s1 = self.s[self.vertex_indices[(id * 3) as usize + 1] as usize];
+                let s2 = self.s[self.vertex_indices[(id * 3) as usize + 2] as usize];
                 ss = s0 * b0 + s1 * b1 + s2 * b2;
                 if ss.length_squared() > 0.0 {
                     ss = ss.normalize();
@@ -402,16 +430,14 @@ impl Triangle {
             // compute $\dndu$ and $\dndv$ for triangle shading geometry
             let dndu: Normal3f;
             let dndv: Normal3f;
-            if !self.mesh.n.is_empty() {
+            if self.has_normals() {
                 // compute deltas for triangle partial derivatives of normal
                 let duv02: Vector2f = uv[0] - uv[2];
                 let duv12: Vector2f = uv[1] - uv[2];
-                let dn1: Normal3f = self.mesh.n
-                    [self.mesh.vertex_indices[(self.id * 3) as usize] as usize]
-                    - self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
-                let dn2: Normal3f = self.mesh.n
-                    [self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize]
-                    - self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+                let dn1: Normal3f = self.normal(self.vertex_indices[(id * 3) as usize] as usize)
+                    - self.normal(self.vertex_indices[(id * 3) as usize + 2] as usize);
+                let dn2: Normal3f = self.normal(self.vertex_indices[(id * 3) as usize + 1] as usize)
+                    - self.normal(self.vertex_indices[(id * 3) as usize + 2] as usize);
                 let determinant: Float = duv02.x * duv12.y - duv02.y * duv12.x;
                 let degenerate_uv: bool = determinant.abs() < 1e-8;
                 if degenerate_uv {
@@ -456,17 +482,19 @@ impl Triangle {
         // isect.bssrdf = None;
         isect.shape = None;
         *t_hit = t;
+        TRI_HITS.inc_num();
         true
     }
-    pub fn intersect_p(&self, ray: &Ray) -> bool {
+    /// See **Triangle::intersect_p()**.
+    pub fn intersect_p_triangle(&self, id: u32, ray: &Ray) -> bool {
         // TODO: ProfilePhase p(Prof::TriIntersectP);
-        // TODO: ++nTests;
+        TRI_HITS.inc_denom();
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let p0: &Point3f = &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
+        let p0: &Point3f = &self.p[self.vertex_indices[(id * 3) as usize] as usize];
         let p1: &Point3f =
-            &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
+            &self.p[self.vertex_indices[(id * 3) as usize + 1] as usize];
         let p2: &Point3f =
-            &self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            &self.p[self.vertex_indices[(id * 3) as usize + 2] as usize];
         // translate vertices based on ray origin
         let mut p0t: Point3f = *p0
             - Vector3f {
@@ -601,11 +629,11 @@ impl Triangle {
             return false;
         }
         // TODO: if (testAlphaTexture && (mesh->alphaMask || mesh->shadowAlphaMask)) { ... }
-        if self.mesh.alpha_mask.is_some() || self.mesh.shadow_alpha_mask.is_some() {
+        if self.alpha_mask.is_some() || self.shadow_alpha_mask.is_some() {
             // compute triangle partial derivatives
             let mut dpdu: Vector3f = Vector3f::default();
             let mut dpdv: Vector3f = Vector3f::default();
-            let uv: [Point2f; 3] = self.get_uvs();
+            let uv: [Point2f; 3] = self.triangle_uvs(id);
             // compute deltas for triangle partial derivatives
             let duv02: Vector2f = uv[0] - uv[2];
             let duv12: Vector2f = uv[1] - uv[2];
@@ -648,20 +676,83 @@ impl Triangle {
                 ray.time,
                 None,
             );
-            if let Some(alpha_mask) = &self.mesh.alpha_mask {
+            if let Some(alpha_mask) = &self.alpha_mask {
                 if alpha_mask.evaluate(&isect_local) == 0.0 as Float {
                     return false;
                 }
             }
-            if let Some(shadow_alpha_mask) = &self.mesh.shadow_alpha_mask {
+            if let Some(shadow_alpha_mask) = &self.shadow_alpha_mask {
                 if shadow_alpha_mask.evaluate(&isect_local) == 0.0 as Float {
                     return false;
                 }
             }
         }
-        // TODO: ++nHits;
+        TRI_HITS.inc_num();
         true
     }
+}
+
+#[derive(Clone)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    pub id: u32,
+    // inherited from class Shape (see shape.h)
+    pub object_to_world: Transform,
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+    pub material: Option<Arc<Material>>,
+}
+
+impl Triangle {
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        mesh: Arc<TriangleMesh>,
+        tri_number: u32,
+    ) -> Self {
+        Triangle {
+            mesh,
+            id: tri_number,
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness: false,
+            material: None,
+        }
+    }
+    pub fn get_uvs(&self) -> [Point2f; 3] {
+        self.mesh.triangle_uvs(self.id)
+    }
+    /// The mesh the triangle belongs to.
+    pub fn get_mesh(&self) -> Arc<TriangleMesh> {
+        self.mesh.clone()
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        let p0: Point3f = self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
+        let p1: Point3f =
+            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
+        let p2: Point3f =
+            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+        bnd3_union_pnt3(
+            &Bounds3f::new(
+                self.world_to_object.transform_point(&p0),
+                self.world_to_object.transform_point(&p1),
+            ),
+            &self.world_to_object.transform_point(&p2),
+        )
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        self.mesh.triangle_bound(self.id)
+    }
+    pub fn intersect(&self, ray: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        self.mesh.intersect_triangle(self.id, ray, t_hit, isect)
+    }
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        self.mesh.intersect_p_triangle(self.id, ray)
+    }
     pub fn get_reverse_orientation(&self) -> bool {
         self.reverse_orientation
     }
@@ -672,13 +763,7 @@ impl Triangle {
         self.object_to_world
     }
     pub fn area(&self) -> Float {
-        // get triangle vertices in _p0_, _p1_, and _p2_
-        let p0: Point3f = self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-        let p1: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-        let p2: Point3f =
-            self.mesh.p[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
-        0.5 as Float * vec3_cross_vec3(&(p1 - p0), &(p2 - p0)).length()
+        self.mesh.triangle_area(self.id)
     }
     pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
         let b: Point2f = uniform_sample_triangle(u);
@@ -694,12 +779,18 @@ impl Triangle {
         it.n = Normal3f::from(vec3_cross_vec3(&(p1 - p0), &(p2 - p0))).normalize();
         // ensure correct orientation of the geometric normal; follow
         // the same approach as was used in Triangle::Intersect().
-        if !self.mesh.n.is_empty() {
-            let ns: Normal3f = self.mesh.n
-                [self.mesh.vertex_indices[(self.id * 3) as usize] as usize]
+        if self.mesh.has_normals() {
+            let ns: Normal3f = self
+                .mesh
+                .normal(self.mesh.vertex_indices[(self.id * 3) as usize] as usize)
                 * b[0]
-                + self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize] * b[1]
-                + self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize]
+                + self
+                    .mesh
+                    .normal(self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize)
+                    * b[1]
+                + self
+                    .mesh
+                    .normal(self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize)
                     * (1.0 as Float - b[0] - b[1]);
             it.n = nrm_faceforward_nrm(&it.n, &ns);
         } else if self.reverse_orientation ^ self.transform_swaps_handedness {
@@ -3,22 +3,22 @@ use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{Point2f, Vector2f};
 use crate::core::interaction::SurfaceInteraction;
-use crate::core::texture::{Texture, TextureMapping2D};
+use crate::core::texture::{Texture, TextureMapping2D, TextureValue};
 
 // checkerboard.h
 
 pub struct Checkerboard2DTexture<T> {
-    pub tex1: Arc<dyn Texture<T> + Send + Sync>,
-    pub tex2: Arc<dyn Texture<T> + Send + Sync>,
+    pub tex1: Arc<Texture<T>>,
+    pub tex2: Arc<Texture<T>>,
     pub mapping: Box<TextureMapping2D>,
     // TODO: const AAMethod aaMethod;
 }
 
-impl<T: Copy> Checkerboard2DTexture<T> {
+impl<T: TextureValue> Checkerboard2DTexture<T> {
     pub fn new(
         mapping: Box<TextureMapping2D>,
-        tex1: Arc<dyn Texture<T> + Send + Sync>,
-        tex2: Arc<dyn Texture<T> + Send + Sync>, // , TODO: aaMethod
+        tex1: Arc<Texture<T>>,
+        tex2: Arc<Texture<T>>, // , TODO: aaMethod
     ) -> Self {
         Checkerboard2DTexture {
             tex1,
@@ -26,10 +26,7 @@ impl<T: Copy> Checkerboard2DTexture<T> {
             mapping,
         }
     }
-}
-
-impl<T: Copy> Texture<T> for Checkerboard2DTexture<T> {
-    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> T {
         let mut dstdx: Vector2f = Vector2f::default();
         let mut dstdy: Vector2f = Vector2f::default();
         let st: Point2f = self.mapping.map(si, &mut dstdx, &mut dstdy);
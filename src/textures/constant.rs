@@ -1,6 +1,5 @@
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
-use crate::core::texture::Texture;
 
 // see constant.h
 
@@ -12,10 +11,7 @@ impl<T: Copy> ConstantTexture<T> {
     pub fn new(value: T) -> Self {
         ConstantTexture { value }
     }
-}
-
-impl<T: Copy> Texture<T> for ConstantTexture<T> {
-    fn evaluate(&self, _si: &SurfaceInteraction) -> T {
+    pub fn evaluate(&self, _si: &SurfaceInteraction) -> T {
         self.value
     }
 }
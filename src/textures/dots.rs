@@ -5,21 +5,21 @@ use crate::core::geometry::{Point2f, Vector2f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::pbrt::Float;
 use crate::core::texture::noise_flt;
-use crate::core::texture::{Texture, TextureMapping2D};
+use crate::core::texture::{Texture, TextureMapping2D, TextureValue};
 
 // see dots.h
 
 pub struct DotsTexture<T> {
     pub mapping: Box<TextureMapping2D>,
-    pub outside_dot: Arc<dyn Texture<T> + Send + Sync>,
-    pub inside_dot: Arc<dyn Texture<T> + Send + Sync>,
+    pub outside_dot: Arc<Texture<T>>,
+    pub inside_dot: Arc<Texture<T>>,
 }
 
-impl<T: Copy> DotsTexture<T> {
+impl<T: TextureValue> DotsTexture<T> {
     pub fn new(
         mapping: Box<TextureMapping2D>,
-        outside_dot: Arc<dyn Texture<T> + Send + Sync>,
-        inside_dot: Arc<dyn Texture<T> + Send + Sync>,
+        outside_dot: Arc<Texture<T>>,
+        inside_dot: Arc<Texture<T>>,
     ) -> Self {
         DotsTexture {
             mapping,
@@ -27,10 +27,7 @@ impl<T: Copy> DotsTexture<T> {
             inside_dot,
         }
     }
-}
-
-impl<T: Copy> Texture<T> for DotsTexture<T> {
-    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> T {
         // compute cell indices for dots
         let mut dpdx: Vector2f = Vector2f::default();
         let mut dpdy: Vector2f = Vector2f::default();
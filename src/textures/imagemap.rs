@@ -3,14 +3,15 @@ use std::ops::{Add, AddAssign, Div, Mul};
 use std::path::Path;
 use std::sync::Arc;
 // others
-use image::{DynamicImage, ImageResult};
+use image::{DynamicImage, ImageError, ImageResult};
 use num;
 // pbrt
 use crate::core::geometry::{Point2f, Point2i, Vector2f};
 use crate::core::interaction::SurfaceInteraction;
+use crate::core::memory::readable_path;
 use crate::core::mipmap::{Clampable, ImageWrap, MipMap};
 use crate::core::pbrt::{Float, Spectrum};
-use crate::core::texture::{Texture, TextureMapping2D};
+use crate::core::texture::{TextureMapping2D, TextureValue};
 
 // see imagemap.h
 
@@ -43,12 +44,14 @@ where
         convert: F,
     ) -> ImageTexture<T> {
         let path = Path::new(&filename);
-        let img_result: ImageResult<DynamicImage> = image::open(path);
+        let img_result: ImageResult<DynamicImage> = readable_path(path)
+            .map_err(ImageError::IoError)
+            .and_then(image::open);
         if img_result.is_err() {
             panic!("Error reading \"{}\"", filename);
         }
         let buf = img_result.unwrap();
-        let rgb = buf.to_rgb();
+        let rgb = buf.to_rgb8();
         let res = Point2i {
             x: rgb.width() as i32,
             y: rgb.height() as i32,
@@ -95,44 +98,8 @@ where
     }
 }
 
-pub trait ImageTextureConvert<T> {
-    fn convert_out(from: &T, to: &mut T);
-}
-
-impl ImageTextureConvert<Spectrum> for ImageTexture<Spectrum> {
-    fn convert_out(from: &Spectrum, to: &mut Spectrum) {
-        let mut rgb: [Float; 3] = [0.0 as Float; 3];
-        from.to_rgb(&mut rgb);
-        *to = Spectrum::from_rgb(&rgb);
-    }
-}
-
-impl ImageTextureConvert<Float> for ImageTexture<Float> {
-    fn convert_out(from: &Float, to: &mut Float) {
-        *to = *from;
-    }
-}
-
-impl Texture<Float> for ImageTexture<Float> {
-    fn evaluate(&self, si: &SurfaceInteraction) -> Float {
-        // Vector2f dstdx, dstdy;
-        // Point2f st = mapping->Map(si, &dstdx, &dstdy);
-        // Tmemory mem = mipmap->Lookup(st, dstdx, dstdy);
-        // Treturn ret;
-        // convertOut(mem, &ret);
-        // return ret;
-        let mut dstdx: Vector2f = Vector2f::default();
-        let mut dstdy: Vector2f = Vector2f::default();
-        let st: Point2f = self.mapping.map(si, &mut dstdx, &mut dstdy);
-        let mem: Float = self.mipmap.lookup_pnt_vec_vec(st, &mut dstdx, &mut dstdy);
-        let mut ret: Float = 0.0 as Float;
-        ImageTexture::<Float>::convert_out(&mem, &mut ret);
-        ret
-    }
-}
-
-impl Texture<Spectrum> for ImageTexture<Spectrum> {
-    fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
+impl<T: TextureValue> ImageTexture<T> {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> T {
         // Vector2f dstdx, dstdy;
         // Point2f st = mapping->Map(si, &dstdx, &dstdy);
         // Tmemory mem = mipmap->Lookup(st, dstdx, dstdy);
@@ -142,10 +109,8 @@ impl Texture<Spectrum> for ImageTexture<Spectrum> {
         let mut dstdx: Vector2f = Vector2f::default();
         let mut dstdy: Vector2f = Vector2f::default();
         let st: Point2f = self.mapping.map(si, &mut dstdx, &mut dstdy);
-        let mem: Spectrum = self.mipmap.lookup_pnt_vec_vec(st, &mut dstdx, &mut dstdy);
-        let mut ret: Spectrum = Spectrum::new(0.0);
-        ImageTexture::<Spectrum>::convert_out(&mem, &mut ret);
-        ret
+        let mem: T = self.mipmap.lookup_pnt_vec_vec(st, &mut dstdx, &mut dstdy);
+        mem.convert_out()
     }
 }
 
@@ -3,7 +3,7 @@ use crate::core::geometry::{Point3f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::texture::fbm;
-use crate::core::texture::{Texture, TextureMapping3D};
+use crate::core::texture::TextureMapping3D;
 
 // see marble.h
 
@@ -31,10 +31,7 @@ impl MarbleTexture {
             variation,
         }
     }
-}
-
-impl Texture<Spectrum> for MarbleTexture {
-    fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
         let mut dpdx: Vector3f = Vector3f::default();
         let mut dpdy: Vector3f = Vector3f::default();
         let mut p: Point3f = self.mapping.map(si, &mut dpdx, &mut dpdy);
@@ -1,29 +1,19 @@
 // std
-use std::ops::Mul;
 use std::sync::Arc;
 // pbrt
 use crate::core::interaction::SurfaceInteraction;
-use crate::core::texture::Texture;
+use crate::core::texture::{Texture, TextureValue};
 
 pub struct ScaleTexture<T> {
-    pub tex1: Arc<dyn Texture<T> + Send + Sync>,
-    pub tex2: Arc<dyn Texture<T> + Send + Sync>,
+    pub tex1: Arc<Texture<T>>,
+    pub tex2: Arc<Texture<T>>,
 }
 
-impl<T: Copy> ScaleTexture<T> {
-    pub fn new(
-        tex1: Arc<dyn Texture<T> + Send + Sync>,
-        tex2: Arc<dyn Texture<T> + Send + Sync>,
-    ) -> Self {
+impl<T: TextureValue> ScaleTexture<T> {
+    pub fn new(tex1: Arc<Texture<T>>, tex2: Arc<Texture<T>>) -> Self {
         ScaleTexture { tex1, tex2 }
     }
-}
-
-impl<T: Copy> Texture<T> for ScaleTexture<T>
-where
-    T: Mul<Output = T>,
-{
-    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+    pub fn evaluate(&self, si: &SurfaceInteraction) -> T {
         self.tex1.evaluate(si) * self.tex2.evaluate(si)
     }
 }
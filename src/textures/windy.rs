@@ -3,7 +3,7 @@ use crate::core::geometry::{Point3f, Vector3f};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::pbrt::Float;
 use crate::core::texture::fbm;
-use crate::core::texture::{Texture, TextureMapping3D};
+use crate::core::texture::TextureMapping3D;
 
 // see windy.h
 
@@ -15,13 +15,7 @@ impl WindyTexture {
     pub fn new(mapping: Box<TextureMapping3D>) -> Self {
         WindyTexture { mapping }
     }
-}
-
-impl<T> Texture<T> for WindyTexture
-where
-    T: From<Float>,
-{
-    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+    pub fn evaluate<T: From<Float>>(&self, si: &SurfaceInteraction) -> T {
         let mut dpdx: Vector3f = Vector3f::default();
         let mut dpdy: Vector3f = Vector3f::default();
         let p: Point3f = self.mapping.map(si, &mut dpdx, &mut dpdy);
@@ -0,0 +1,201 @@
+//! JavaScript interface for WebAssembly builds (feature *wasm*). The
+//! library is only built as *rlib*, so ask for a *cdylib* and generate
+//! the JavaScript bindings with
+//! [wasm-bindgen](https://rustwasm.github.io/wasm-bindgen/):
+//!
+//! ```shell
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/pbrt.wasm
+//! ```
+//!
+//! A **WasmRenderer** parses a scene from memory (there is no file
+//! system in the browser, so the scene can't include other files)
+//! and renders it into RGBA pixels for an **ImageData**:
+//!
+//! ```js
+//! const renderer = new WasmRenderer(sceneText);
+//! renderer.render();
+//! const pixels = new Uint8ClampedArray(renderer.image());
+//! context.putImageData(new ImageData(pixels, renderer.width(), renderer.height()), 0, 0);
+//! ```
+//!
+//! There are no threads in WebAssembly, but web workers can render
+//! tiles in parallel. Each worker creates its own renderer from the
+//! same scene and renders some of the tiles with
+//! **render_tiles()**, the main thread merges them with
+//! **merge_tiles()**. The samples of a pixel are seeded by its
+//! coordinates, so the image is the same no matter who rendered which
+//! tile (the tiles are exchanged like the ones of distributed
+//! rendering, see **core::distributed**).
+//!
+//! Only integrators which render tile by tile (**SamplerIntegrator**,
+//! e.g. *path* or *volpath*) are supported.
+
+// std
+use std::sync::Arc;
+// others
+use wasm_bindgen::prelude::*;
+// pbrt
+use crate::core::api::{pbrt_init, pbrt_make_render};
+use crate::core::distributed::{read_frame, read_tile, tile_payload, write_frame};
+use crate::core::film::Film;
+use crate::core::integrator::{tile_shape, Integrator, SamplerIntegrator, TileGrid};
+use crate::core::memory::MemoryArena;
+use crate::core::parseerror::ParseError;
+use crate::core::parser::{parse_json_str, parse_str, RecordMode, Recorder, Variables};
+use crate::core::pbrt::{clamp_t, gamma_correct, Float};
+use crate::core::sampler::Sampler;
+use crate::core::scene::Scene;
+use crate::core::scenecache::ApiCall;
+
+fn to_js_error(errors: &[ParseError]) -> JsValue {
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    JsValue::from_str(&messages.join("\n"))
+}
+
+/// A parsed scene, ready to be rendered (tile by tile).
+#[wasm_bindgen]
+pub struct WasmRenderer {
+    integrator: SamplerIntegrator,
+    scene: Scene,
+    film: Arc<Film>,
+    grid: TileGrid,
+}
+
+#[wasm_bindgen]
+impl WasmRenderer {
+    /// Parses a scene description (.pbrt or .json format) and builds
+    /// the scene. Throws the errors (one per line) if that fails.
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> Result<WasmRenderer, JsValue> {
+        let (mut api_state, mut bsdf_state) = pbrt_init(1_u8);
+        // record the calls, so the scene isn't rendered by WorldEnd
+        let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+        let mut variables: Variables = Variables::default();
+        if text.trim_start().starts_with('{') {
+            parse_json_str(text, &mut api_state, &mut bsdf_state, &mut recorder);
+        } else {
+            parse_str(
+                text,
+                &mut api_state,
+                &mut bsdf_state,
+                &mut recorder,
+                &mut variables,
+                "",
+            );
+        }
+        if !api_state.errors.is_empty() {
+            return Err(to_js_error(&api_state.errors));
+        }
+        let calls: Vec<ApiCall> = recorder.map_or_else(Vec::new, |recording| recording.cache.calls);
+        let (mut api_state, mut bsdf_state) = pbrt_init(1_u8);
+        for call in calls {
+            if let ApiCall::WorldEnd = call {
+                break;
+            }
+            call.execute(&mut api_state, &mut bsdf_state);
+        }
+        let (integrator, scene) = match pbrt_make_render(&mut api_state) {
+            Some(render) => render,
+            None => return Err(to_js_error(&api_state.errors)),
+        };
+        let mut integrator: SamplerIntegrator = match *integrator {
+            Integrator::Sampler(integrator) => integrator,
+            _ => {
+                return Err(JsValue::from_str(
+                    "only integrators which render tile by tile are supported",
+                ))
+            }
+        };
+        integrator.preprocess(&scene);
+        let film: Arc<Film> = integrator.get_camera().get_film();
+        let grid: TileGrid = TileGrid::new(film.get_sample_bounds(), tile_shape());
+        Ok(WasmRenderer {
+            integrator,
+            scene,
+            film,
+            grid,
+        })
+    }
+    /// The width of the (cropped) image in pixels.
+    pub fn width(&self) -> u32 {
+        let bounds = self.film.cropped_pixel_bounds;
+        (bounds.p_max.x - bounds.p_min.x) as u32
+    }
+    /// The height of the (cropped) image in pixels.
+    pub fn height(&self) -> u32 {
+        let bounds = self.film.cropped_pixel_bounds;
+        (bounds.p_max.y - bounds.p_min.y) as u32
+    }
+    /// The number of tiles, see **render_tiles()**.
+    pub fn tile_count(&self) -> u32 {
+        self.grid.len() as u32
+    }
+    /// Renders all tiles into the image.
+    pub fn render(&mut self) {
+        let mut tile_sampler: Box<Sampler> = self.integrator.get_sampler().clone_with_seed(0_u64);
+        let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+        for index in 0..self.grid.len() {
+            let film_tile = self.integrator.render_tile(
+                &self.scene,
+                &self.grid,
+                self.grid.tile(index),
+                &self.film,
+                &mut tile_sampler,
+                &mut arena,
+            );
+            self.film.merge_film_tile(&film_tile);
+        }
+    }
+    /// Renders *count* tiles starting with tile *first* (e.g. in a web
+    /// worker) and returns them for **merge_tiles()**. They are not
+    /// merged into the image of this renderer.
+    pub fn render_tiles(&self, first: u32, count: u32) -> Result<Vec<u8>, JsValue> {
+        let last: usize = std::cmp::min(first as usize + count as usize, self.grid.len());
+        let mut tile_sampler: Box<Sampler> = self.integrator.get_sampler().clone_with_seed(0_u64);
+        let mut arena: MemoryArena = MemoryArena::new(262_144); // 256kB
+        let mut tiles: Vec<u8> = Vec::new();
+        for index in first as usize..last {
+            let film_tile = self.integrator.render_tile(
+                &self.scene,
+                &self.grid,
+                self.grid.tile(index),
+                &self.film,
+                &mut tile_sampler,
+                &mut arena,
+            );
+            tile_payload(index, &film_tile)
+                .and_then(|payload| write_frame(&mut tiles, &payload))
+                .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        }
+        Ok(tiles)
+    }
+    /// Merges tiles from **render_tiles()** (of a renderer of the same
+    /// scene) into the image. Returns the number of merged tiles.
+    pub fn merge_tiles(&mut self, tiles: &[u8]) -> Result<u32, JsValue> {
+        let mut r: &[u8] = tiles;
+        let mut merged: u32 = 0;
+        while !r.is_empty() {
+            let payload: Vec<u8> =
+                read_frame(&mut r).map_err(|err| JsValue::from_str(&err.to_string()))?;
+            let (_index, film_tile) = read_tile(&payload, &self.grid, &self.film)
+                .map_err(|err| JsValue::from_str(&err.to_string()))?;
+            self.film.merge_film_tile(&film_tile);
+            merged += 1;
+        }
+        Ok(merged)
+    }
+    /// The (gamma corrected) RGBA pixels of the image, row by row, as
+    /// expected by **ImageData**.
+    pub fn image(&self) -> Vec<u8> {
+        let rgb: Vec<Float> = self.film.preview_rgb().unwrap_or_default();
+        let mut rgba: Vec<u8> = Vec::with_capacity(rgb.len() / 3 * 4);
+        for pixel in rgb.chunks(3) {
+            for v in pixel {
+                rgba.push(clamp_t(255.0 as Float * gamma_correct(*v) + 0.5, 0.0, 255.0) as u8);
+            }
+            rgba.push(255_u8);
+        }
+        rgba
+    }
+}
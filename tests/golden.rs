@@ -0,0 +1,257 @@
+//! Golden-image regression tests: the small scenes in
+//! *tests/golden/scenes* get rendered (at low sample counts, with one
+//! thread, so the samples are always the same) and compared against
+//! the reference images in *tests/golden/references*. A test fails if
+//! the mean relative squared error (MRSE) exceeds **TOLERANCE**, the
+//! rendered image is then written to the temporary directory of the
+//! tests for inspection. The scenes of the integrators which splat
+//! light paths onto the film also get rendered with several threads,
+//! which has to give the same image bit for bit.
+//!
+//! After an intended change of the output the references get
+//! rendered again with:
+//!
+//! ```shell
+//! > PBRT_UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+//!
+//! The images are stored as PFM files (linear RGB, 32 bit floats).
+
+// std
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Result, Write};
+use std::path::{Path, PathBuf};
+// pbrt
+use pbrt::core::api::{pbrt_init, pbrt_render_to_memory};
+use pbrt::core::parser::{parse_file, RecordMode, Recorder, Variables};
+use pbrt::core::pbrt::Float;
+use pbrt::core::scenecache::ApiCall;
+
+/// maximum mean relative squared error of a rendered image
+const TOLERANCE: f64 = 1e-4;
+/// added to the squared reference values, so dark pixels don't
+/// dominate the error
+const EPSILON: f64 = 1e-2;
+
+/// An image with linear RGB values, row by row (top to bottom).
+struct Image {
+    width: usize,
+    height: usize,
+    rgb: Vec<f32>,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+// the cast is needed if Float is f64
+#[allow(clippy::unnecessary_cast)]
+fn render(scene: &Path, num_threads: u8) -> Image {
+    let (mut api_state, mut bsdf_state) = pbrt_init(num_threads);
+    // record the calls, so the scene gets rendered into memory (not by WorldEnd)
+    let mut recorder: Option<Recorder> = Some(Recorder::new(RecordMode::Record));
+    let mut variables: Variables = Variables::default();
+    parse_file(
+        scene.to_string_lossy().into_owned(),
+        &mut api_state,
+        &mut bsdf_state,
+        &mut recorder,
+        &mut variables,
+        "",
+    );
+    assert!(
+        api_state.errors.is_empty(),
+        "{:?}: {:?}",
+        scene,
+        api_state.errors
+    );
+    let calls: Vec<ApiCall> = recorder.map_or_else(Vec::new, |recording| recording.cache.calls);
+    let (mut api_state, mut bsdf_state) = pbrt_init(num_threads);
+    for call in calls {
+        if let ApiCall::WorldEnd = call {
+            break;
+        }
+        call.execute(&mut api_state, &mut bsdf_state);
+    }
+    let (width, height, rgb) = match pbrt_render_to_memory(&mut api_state) {
+        Some(image) => image,
+        None => panic!("{:?}: {:?}", scene, api_state.errors),
+    };
+    Image {
+        width: width as usize,
+        height: height as usize,
+        rgb: rgb.iter().map(|v: &Float| *v as f32).collect(),
+    }
+}
+
+/// Writes a PFM file (rows bottom to top, little endian).
+fn write_pfm(filename: &Path, image: &Image) -> Result<()> {
+    let mut file: File = File::create(filename)?;
+    write!(file, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+    for row in image.rgb.chunks(image.width * 3).rev() {
+        for v in row {
+            file.write_all(&v.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_pfm(filename: &Path) -> Result<Image> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let mut reader: BufReader<File> = BufReader::new(File::open(filename)?);
+    let mut header: Vec<String> = Vec::new();
+    while header.len() < 3 {
+        let mut line: String = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated header"));
+        }
+        header.push(line.trim().to_string());
+    }
+    if header[0] != "PF" {
+        return Err(invalid("not an RGB PFM file"));
+    }
+    let size: Vec<usize> = header[1]
+        .split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    if size.len() != 2 {
+        return Err(invalid("invalid image size"));
+    }
+    let little_endian: bool = header[2].starts_with('-');
+    let mut data: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() != size[0] * size[1] * 3 * 4 {
+        return Err(invalid("wrong number of pixels"));
+    }
+    let values: Vec<f32> = data
+        .chunks(4)
+        .map(|b| {
+            let bytes: [u8; 4] = [b[0], b[1], b[2], b[3]];
+            if little_endian {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            }
+        })
+        .collect();
+    let rgb: Vec<f32> = values
+        .chunks(size[0] * 3)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    Ok(Image {
+        width: size[0],
+        height: size[1],
+        rgb,
+    })
+}
+
+/// The mean relative squared error of *image* compared to
+/// *reference*.
+fn mrse(image: &Image, reference: &Image) -> f64 {
+    let sum: f64 = image
+        .rgb
+        .iter()
+        .zip(&reference.rgb)
+        .map(|(v, r)| {
+            let (v, r) = (*v as f64, *r as f64);
+            (v - r) * (v - r) / (r * r + EPSILON)
+        })
+        .sum();
+    sum / reference.rgb.len() as f64
+}
+
+/// Renders *tests/golden/scenes/{name}.pbrt* and compares it to
+/// *tests/golden/references/{name}.pfm*.
+fn check(name: &str) {
+    let scene: PathBuf = golden_dir().join("scenes").join(format!("{}.pbrt", name));
+    let reference_file: PathBuf = golden_dir()
+        .join("references")
+        .join(format!("{}.pfm", name));
+    let image: Image = render(&scene, 1);
+    if std::env::var_os("PBRT_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir().join("references")).unwrap();
+        write_pfm(&reference_file, &image).unwrap();
+        return;
+    }
+    let reference: Image = match read_pfm(&reference_file) {
+        Ok(reference) => reference,
+        Err(err) => panic!(
+            "unable to read {:?} ({}), render it with PBRT_UPDATE_GOLDEN=1",
+            reference_file, err
+        ),
+    };
+    assert_eq!(
+        (image.width, image.height),
+        (reference.width, reference.height),
+        "{}: the image size changed",
+        name
+    );
+    let error: f64 = mrse(&image, &reference);
+    if error > TOLERANCE {
+        let rendered: PathBuf =
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.pfm", name));
+        write_pfm(&rendered, &image).unwrap();
+        panic!(
+            "{}: MRSE {:.6} exceeds {} (rendered image: {:?})",
+            name, error, TOLERANCE, rendered
+        );
+    }
+}
+
+/// Renders *tests/golden/scenes/{name}.pbrt* with one and with four
+/// threads and compares the images bit for bit.
+fn check_threads(name: &str) {
+    let scene: PathBuf = golden_dir().join("scenes").join(format!("{}.pbrt", name));
+    let image: Image = render(&scene, 1);
+    let threaded: Image = render(&scene, 4);
+    let differing: usize = image
+        .rgb
+        .iter()
+        .zip(threaded.rgb.iter())
+        .filter(|(a, b)| a.to_bits() != b.to_bits())
+        .count();
+    assert_eq!(
+        differing, 0,
+        "{}: {} values differ with four threads",
+        name, differing
+    );
+}
+
+#[test]
+fn path_materials() {
+    check("path_materials");
+}
+
+#[test]
+fn volpath_medium() {
+    check("volpath_medium");
+}
+
+#[test]
+fn bdpt_glass() {
+    check("bdpt_glass");
+}
+
+#[test]
+fn whitted_mirror() {
+    check("whitted_mirror");
+}
+
+#[test]
+fn sppm_caustic() {
+    check("sppm_caustic");
+}
+
+#[test]
+fn bdpt_glass_threads() {
+    check_threads("bdpt_glass");
+}
+
+#[test]
+fn sppm_caustic_threads() {
+    check_threads("sppm_caustic");
+}